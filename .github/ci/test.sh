@@ -20,6 +20,8 @@ cargo test --manifest-path ./embassy-boot/boot/Cargo.toml
 cargo test --manifest-path ./embassy-boot/boot/Cargo.toml --features nightly
 cargo test --manifest-path ./embassy-boot/boot/Cargo.toml --features nightly,ed25519-dalek
 cargo test --manifest-path ./embassy-boot/boot/Cargo.toml --features nightly,ed25519-salty
+cargo test --manifest-path ./embassy-boot/boot/Cargo.toml --features nightly,ecdsa-p256
+cargo test --manifest-path ./embassy-boot/boot/Cargo.toml --features encryption-chacha20
 
 cargo test --manifest-path ./embassy-nrf/Cargo.toml --no-default-features --features nightly,nrf52840,time-driver-rtc1,gpiote
 
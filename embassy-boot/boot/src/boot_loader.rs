@@ -5,7 +5,14 @@ use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
 use embedded_storage::nor_flash::{NorFlash, NorFlashError, NorFlashErrorKind};
 
-use crate::{State, BOOT_MAGIC, STATE_ERASE_VALUE, SWAP_MAGIC};
+use crate::lz4::{self, Sink, Source};
+use crate::{
+    counter_offset, State, BOOT_MAGIC, COUNTER_LEN, DFU_DETACH_MAGIC, STATE_ERASE_VALUE, SWAP_COMPRESSED_MAGIC,
+    SWAP_MAGIC,
+};
+
+/// Length of the header of a compressed image: the decompressed and compressed lengths.
+const COMPRESSED_HEADER_LEN: u32 = 8;
 
 /// Errors returned by bootloader
 #[derive(PartialEq, Eq, Debug)]
@@ -14,6 +21,10 @@ pub enum BootError {
     Flash(NorFlashErrorKind),
     /// Invalid bootloader magic
     BadMagic,
+    /// Invalid compressed image
+    BadImage,
+    /// Update older than the active firmware
+    Rollback,
 }
 
 #[cfg(feature = "defmt")]
@@ -22,6 +33,8 @@ impl defmt::Format for BootError {
         match self {
             BootError::Flash(_) => defmt::write!(fmt, "BootError::Flash(_)"),
             BootError::BadMagic => defmt::write!(fmt, "BootError::BadMagic"),
+            BootError::BadImage => defmt::write!(fmt, "BootError::BadImage"),
+            BootError::Rollback => defmt::write!(fmt, "BootError::Rollback"),
         }
     }
 }
@@ -35,6 +48,30 @@ where
     }
 }
 
+/// Operation performed by the bootloader before booting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootOperation {
+    /// Swapping the update into the active partition.
+    Swap,
+    /// Reverting a failed update.
+    Revert,
+    /// Decompressing a compressed update into the active partition.
+    Decompress,
+}
+
+/// Progress of the bootloader, reported by [`BootLoader::prepare_boot_with_progress`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootProgress {
+    /// The operation in progress.
+    pub operation: BootOperation,
+    /// Number of pages processed.
+    pub done: u32,
+    /// Total number of pages to process.
+    pub total: u32,
+}
+
 /// Bootloader flash configuration holding the three flashes used by the bootloader
 ///
 /// If only a single flash is actually used, then that flash should be partitioned into three partitions before use.
@@ -59,6 +96,27 @@ impl<'a, FLASH: NorFlash>
     /// Create a bootloader config from the flash and address symbols defined in the linkerfile
     // #[cfg(target_os = "none")]
     pub fn from_linkerfile_blocking(flash: &'a Mutex<NoopRawMutex, RefCell<FLASH>>) -> Self {
+        Self::from_linkerfile_blocking_with_dfu(flash, flash)
+    }
+}
+
+impl<'a, FLASH: NorFlash, DFU: NorFlash>
+    BootLoaderConfig<
+        BlockingPartition<'a, NoopRawMutex, FLASH>,
+        BlockingPartition<'a, NoopRawMutex, DFU>,
+        BlockingPartition<'a, NoopRawMutex, FLASH>,
+    >
+{
+    /// Create a bootloader config with the DFU partition in a separate flash, e.g. an external
+    /// flash, from the address symbols defined in the linkerfile.
+    ///
+    /// The DFU symbols are offsets in `dfu_flash`. Async flash drivers can be used in the
+    /// bootloader with `embassy_embedded_hal::adapter::BlockOnAsync`.
+    // #[cfg(target_os = "none")]
+    pub fn from_linkerfile_blocking_with_dfu(
+        flash: &'a Mutex<NoopRawMutex, RefCell<FLASH>>,
+        dfu_flash: &'a Mutex<NoopRawMutex, RefCell<DFU>>,
+    ) -> Self {
         extern "C" {
             static __bootloader_state_start: u32;
             static __bootloader_state_end: u32;
@@ -80,7 +138,7 @@ impl<'a, FLASH: NorFlash>
             let end = &__bootloader_dfu_end as *const u32 as u32;
             trace!("DFU: 0x{:x} - 0x{:x}", start, end);
 
-            BlockingPartition::new(flash, start, end - start)
+            BlockingPartition::new(dfu_flash, start, end - start)
         };
         let state = unsafe {
             let start = &__bootloader_state_start as *const u32 as u32;
@@ -94,16 +152,62 @@ impl<'a, FLASH: NorFlash>
     }
 }
 
+/// Length of the nonce of encrypted updates.
+#[cfg(feature = "encryption-chacha20")]
+pub const NONCE_LEN: usize = 12;
+
+/// Key and nonce of encrypted updates.
+#[cfg(feature = "encryption-chacha20")]
+struct Cipher {
+    key: [u8; 32],
+    nonce: [u8; NONCE_LEN],
+}
+
+#[cfg(feature = "encryption-chacha20")]
+impl Cipher {
+    /// Encrypt or decrypt a chunk of firmware at the given offset of the active partition.
+    ///
+    /// The previous firmware stored in the DFU partition during a swap is encrypted with its own
+    /// nonce, to never reuse the keystream of the update with another plaintext.
+    fn apply(&self, image: Image, offset: u32, buf: &mut [u8]) {
+        use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+        use chacha20::ChaCha20;
+
+        let mut nonce = self.nonce;
+        if image == Image::Previous {
+            nonce[NONCE_LEN - 1] ^= 0x80;
+        }
+        let mut cipher = ChaCha20::new(&self.key.into(), &nonce.into());
+        cipher.seek(offset);
+        cipher.apply_keystream(buf);
+    }
+}
+
+/// Firmware being copied between partitions.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Image {
+    /// The firmware of the update.
+    Update,
+    /// The firmware being updated.
+    Previous,
+}
+
 /// BootLoader works with any flash implementing embedded_storage.
 pub struct BootLoader<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> {
     active: ACTIVE,
     dfu: DFU,
+    #[cfg(feature = "encryption-chacha20")]
+    cipher: Option<Cipher>,
+    version_offset: Option<u32>,
     /// The state partition has the following format:
     /// All ranges are in multiples of WRITE_SIZE bytes.
     /// | Range    | Description                                                                      |
-    /// | 0..1     | Magic indicating bootloader state. BOOT_MAGIC means boot, SWAP_MAGIC means swap. |
+    /// | 0..1     | Magic indicating bootloader state. BOOT_MAGIC means boot, SWAP_MAGIC means swap, |
+    /// |          | DFU_DETACH_MAGIC means the application requested DFU mode,                       |
+    /// |          | SWAP_COMPRESSED_MAGIC means decompress the DFU partition to active.              |
     /// | 1..2     | Progress validity. ERASE_VALUE means valid, !ERASE_VALUE means invalid.          |
-    /// | 2..2 + N | Progress index used while swapping or reverting      
+    /// | 2..2 + N | Progress index used while swapping or reverting                                  |
+    /// | Last     | Anti-rollback counter, padded to WRITE_SIZE                                      |
     state: STATE,
 }
 
@@ -118,20 +222,74 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
     /// Create a new instance of a bootloader with the flash partitions.
     ///
     /// - All partitions must be aligned with the PAGE_SIZE const generic parameter.
-    /// - The dfu partition must be at least PAGE_SIZE bigger than the active partition.
+    /// - The dfu partition must be at least PAGE_SIZE bigger than the active partition, unless
+    ///   only compressed updates are used.
     pub fn new(config: BootLoaderConfig<ACTIVE, DFU, STATE>) -> Self {
         Self {
             active: config.active,
             dfu: config.dfu,
+            #[cfg(feature = "encryption-chacha20")]
+            cipher: None,
+            version_offset: None,
             state: config.state,
         }
     }
 
+    /// Create a new instance of a bootloader decrypting updates with the given ChaCha20 key.
+    ///
+    /// The key should be read from a protected flash region. Updates are then expected to be
+    /// encrypted with ChaCha20, with the block counter starting at 0 at the beginning of the
+    /// firmware, and a nonce unique to each update. The nonce is written with
+    /// `FirmwareUpdater::write_nonce` to the last erase block of the DFU partition, which must be
+    /// large enough to hold it on top of the swap: at least 1 page and 1 erase block bigger than
+    /// the active partition.
+    ///
+    /// The previous firmware is kept encrypted in the DFU partition until the update is marked
+    /// booted. Compressed updates are not decrypted.
+    #[cfg(feature = "encryption-chacha20")]
+    pub fn new_encrypted(config: BootLoaderConfig<ACTIVE, DFU, STATE>, key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Some(Cipher {
+                key: *key,
+                nonce: [0; NONCE_LEN],
+            }),
+            ..Self::new(config)
+        }
+    }
+
+    /// Enable anti-rollback protection, refusing updates older than the active firmware.
+    ///
+    /// The firmware version is a little endian `u32` at `version_offset` in the firmware, e.g. in
+    /// a section placed after the vector table, and should be covered by the firmware signature.
+    /// Once the application marks a firmware booted, its version is stored as a security counter
+    /// at the end of the state partition, and updates with a lower version are discarded without
+    /// being swapped. The counter only grows, so that reverting a failed update is still possible.
+    ///
+    /// The state partition must be large enough for the counter on top of the swap and revert
+    /// progress.
+    pub fn enable_anti_rollback(&mut self, version_offset: u32) {
+        assert_eq!(0, version_offset % COUNTER_LEN as u32);
+        self.version_offset = Some(version_offset);
+    }
+
     /// Perform necessary boot preparations like swapping images.
     ///
+    /// If [`State::DfuDetach`] is returned, the application has requested an update over USB DFU.
+    /// Partitions are left untouched, and the bootloader should run a DFU class before booting.
+    ///
     /// The DFU partition is assumed to be 1 page bigger than the active partition for the swap
     /// algorithm to work correctly.
     ///
+    /// If the application marked a compressed update, the DFU partition is instead decompressed
+    /// into the active partition, and [`State::Boot`] is returned. As the previous firmware is not
+    /// kept, compressed updates can not be reverted.
+    ///
+    /// A compressed image starts with the length of the decompressed image and the length of the
+    /// compressed data as two little endian `u32`, followed by the image compressed in the LZ4
+    /// block format. As only the compressed image is stored, the DFU partition can be smaller
+    /// than the active partition. Invalid compressed images are discarded, booting the current
+    /// firmware.
+    ///
     /// The provided aligned_buf argument must satisfy any alignment requirements
     /// given by the partition flashes. All flash operations will use this buffer.
     ///
@@ -214,48 +372,315 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
     /// +-----------+--------------+--------+--------+--------+--------+
     ///
     pub fn prepare_boot(&mut self, aligned_buf: &mut [u8]) -> Result<State, BootError> {
-        // Ensure we have enough progress pages to store copy progress
-        assert_eq!(0, Self::PAGE_SIZE % aligned_buf.len() as u32);
-        assert_eq!(0, Self::PAGE_SIZE % ACTIVE::WRITE_SIZE as u32);
-        assert_eq!(0, Self::PAGE_SIZE % ACTIVE::ERASE_SIZE as u32);
-        assert_eq!(0, Self::PAGE_SIZE % DFU::WRITE_SIZE as u32);
-        assert_eq!(0, Self::PAGE_SIZE % DFU::ERASE_SIZE as u32);
-        assert!(aligned_buf.len() >= STATE::WRITE_SIZE);
-        assert_eq!(0, aligned_buf.len() % ACTIVE::WRITE_SIZE);
-        assert_eq!(0, aligned_buf.len() % DFU::WRITE_SIZE);
+        self.prepare_boot_with_progress(aligned_buf, |_| {})
+    }
 
-        assert_partitions(&self.active, &self.dfu, &self.state, Self::PAGE_SIZE);
+    /// Perform necessary boot preparations like [`Self::prepare_boot`], reporting the progress
+    /// of long operations after each page, e.g. to drive a progress bar.
+    pub fn prepare_boot_with_progress(
+        &mut self,
+        aligned_buf: &mut [u8],
+        mut progress: impl FnMut(BootProgress),
+    ) -> Result<State, BootError> {
+        self.assert_config(aligned_buf);
+
+        if self.has_magic(aligned_buf, SWAP_COMPRESSED_MAGIC)? {
+            trace!("Decompressing");
+            match self.decompress(aligned_buf, &mut progress) {
+                Ok(()) => trace!("Decompressing done"),
+                Err(BootError::BadImage) => warn!("Invalid compressed image, discarding update"),
+                Err(BootError::Rollback) => warn!("Update older than the active firmware, discarding update"),
+                Err(e) => return Err(e),
+            }
+            self.set_magic(aligned_buf, BOOT_MAGIC)?;
+            return Ok(State::Boot);
+        }
 
-        // Copy contents from partition N to active
         let state = self.read_state(aligned_buf)?;
+        if state == State::Boot && self.version_offset.is_some() {
+            // The active firmware is marked booted, raise the counter to its version
+            let version = self.active_version(aligned_buf)?;
+            if version > self.read_counter(aligned_buf)? {
+                self.reset_state(aligned_buf, BOOT_MAGIC, version)?;
+            }
+        }
+
+        // Copy contents from partition N to active
         if state == State::Swap {
+            assert!(self.dfu.capacity() >= self.active.capacity() + Self::PAGE_SIZE as usize);
+            #[cfg(feature = "encryption-chacha20")]
+            self.read_nonce(aligned_buf)?;
+
+            // Only check the update before starting to swap, as the active partition is then
+            // partly overwritten.
+            if self.version_offset.is_some() && self.current_progress(aligned_buf)? == 0 {
+                let version = self.update_version(aligned_buf)?;
+                if version < self.min_version(aligned_buf)? {
+                    warn!("Update older than the active firmware, discarding update");
+                    self.set_magic(aligned_buf, BOOT_MAGIC)?;
+                    return Ok(State::Boot);
+                }
+            }
+
             //
             // Check if we already swapped. If we're in the swap state, this means we should revert
             // since the app has failed to mark boot as successful
             //
             if !self.is_swapped(aligned_buf)? {
                 trace!("Swapping");
-                self.swap(aligned_buf)?;
+                self.swap(aligned_buf, &mut progress)?;
                 trace!("Swapping done");
             } else {
                 trace!("Reverting");
-                self.revert(aligned_buf)?;
+                self.revert(aligned_buf, &mut progress)?;
+                self.set_magic(aligned_buf, BOOT_MAGIC)?;
+            }
+        }
+        Ok(state)
+    }
 
-                let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+    /// Check whether the active firmware is booted on trial, after [`Self::prepare_boot`] swapped
+    /// in an update that the application has not marked booted yet.
+    ///
+    /// This is `false` after a revert, or when no update was swapped in, even if the state
+    /// returned by [`Self::prepare_boot`] is [`State::Swap`].
+    pub fn is_trial_boot(&mut self, aligned_buf: &mut [u8]) -> Result<bool, BootError> {
+        Ok(self.read_state(aligned_buf)? == State::Swap && self.is_swapped(aligned_buf)?)
+    }
 
-                // Invalidate progress
-                state_word.fill(!STATE_ERASE_VALUE);
-                self.state.write(STATE::WRITE_SIZE as u32, state_word)?;
+    /// Perform necessary boot preparations like [`Self::prepare_boot`], updating secondary images
+    /// such as co-processor firmware along with this primary image.
+    ///
+    /// The updates of all images are activated, or none of them. The secondary images are only
+    /// swapped along with the primary image, and reverted along with it when the application
+    /// fails to mark the primary image booted. The application marks the secondary images updated
+    /// before the primary image, which commits the update: secondary images marked updated alone
+    /// are discarded. Marking the primary image booted confirms the secondary images too.
+    ///
+    /// Secondary images are not decompressed: compressed updates are only supported for the
+    /// primary image, whose decompression then activates the secondary images without a trial boot.
+    pub fn prepare_boot_multi(
+        &mut self,
+        aligned_buf: &mut [u8],
+        secondaries: &mut [&mut dyn SecondaryImage],
+    ) -> Result<State, BootError> {
+        self.assert_config(aligned_buf);
 
-                // Clear magic and progress
-                self.state.erase(0, self.state.capacity() as u32)?;
+        // The secondary images are done first, so that they are never left behind by a power
+        // failure once the primary image is done.
+        let operation = self.operation(aligned_buf)?;
+        for image in secondaries.iter_mut() {
+            image.follow(operation, aligned_buf)?;
+        }
+        self.prepare_boot(aligned_buf)
+    }
 
-                // Set magic
-                state_word.fill(BOOT_MAGIC);
-                self.state.write(0, state_word)?;
+    fn assert_config(&self, aligned_buf: &[u8]) {
+        // Ensure we have enough progress pages to store copy progress
+        assert_eq!(0, Self::PAGE_SIZE % aligned_buf.len() as u32);
+        assert_eq!(0, Self::PAGE_SIZE % ACTIVE::WRITE_SIZE as u32);
+        assert_eq!(0, Self::PAGE_SIZE % ACTIVE::ERASE_SIZE as u32);
+        assert_eq!(0, Self::PAGE_SIZE % DFU::WRITE_SIZE as u32);
+        assert_eq!(0, Self::PAGE_SIZE % DFU::ERASE_SIZE as u32);
+        assert!(aligned_buf.len() >= STATE::WRITE_SIZE);
+        assert_eq!(0, aligned_buf.len() % ACTIVE::WRITE_SIZE);
+        assert_eq!(0, aligned_buf.len() % DFU::WRITE_SIZE);
+
+        assert_partitions(&self.active, &self.dfu, &self.state, Self::PAGE_SIZE);
+        if let Some(version_offset) = self.version_offset {
+            let page_count = self.active.capacity() / Self::PAGE_SIZE as usize;
+            assert!(2 + 4 * page_count <= counter_offset(self.state.capacity(), STATE::WRITE_SIZE) / STATE::WRITE_SIZE);
+            assert!(version_offset as usize + COUNTER_LEN <= self.active.capacity());
+            assert_eq!(0, aligned_buf.len() % COUNTER_LEN);
+        }
+    }
+
+    /// The operation [`Self::prepare_boot`] is going to perform.
+    fn operation(&mut self, aligned_buf: &mut [u8]) -> Result<Option<BootOperation>, BootError> {
+        if self.has_magic(aligned_buf, SWAP_COMPRESSED_MAGIC)? {
+            return Ok(Some(BootOperation::Decompress));
+        }
+        if self.read_state(aligned_buf)? != State::Swap {
+            return Ok(None);
+        }
+        if self.is_swapped(aligned_buf)? {
+            return Ok(Some(BootOperation::Revert));
+        }
+        if self.version_offset.is_some() && self.current_progress(aligned_buf)? == 0 {
+            #[cfg(feature = "encryption-chacha20")]
+            self.read_nonce(aligned_buf)?;
+            if self.update_version(aligned_buf)? < self.min_version(aligned_buf)? {
+                return Ok(None);
             }
         }
-        Ok(state)
+        Ok(Some(BootOperation::Swap))
+    }
+
+    #[cfg(feature = "encryption-chacha20")]
+    fn read_nonce(&mut self, aligned_buf: &mut [u8]) -> Result<(), BootError> {
+        if let Some(cipher) = &mut self.cipher {
+            let offset = self.dfu.capacity() - DFU::ERASE_SIZE;
+            assert!(offset >= self.active.capacity() + Self::PAGE_SIZE as usize);
+
+            let len = (NONCE_LEN + DFU::READ_SIZE - 1) / DFU::READ_SIZE * DFU::READ_SIZE;
+            self.dfu.read(offset as u32, &mut aligned_buf[..len])?;
+            cipher.nonce.copy_from_slice(&aligned_buf[..NONCE_LEN]);
+        }
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn crypt(&self, image: Image, offset: u32, buf: &mut [u8]) {
+        #[cfg(feature = "encryption-chacha20")]
+        if let Some(cipher) = &self.cipher {
+            cipher.apply(image, offset, buf);
+        }
+    }
+
+    /// Decompress the DFU partition into the active partition.
+    ///
+    /// The compressed data is validated before erasing anything. The decompression is resumed on
+    /// power failure, by decompressing again and skipping the pages already written.
+    fn decompress(&mut self, aligned_buf: &mut [u8], progress: &mut dyn FnMut(BootProgress)) -> Result<(), BootError> {
+        assert_eq!(ACTIVE::READ_SIZE, 1);
+        assert_eq!(0, 32 % DFU::READ_SIZE);
+
+        let mut src = DfuSource::new(&mut self.dfu, 0, COMPRESSED_HEADER_LEN);
+        let mut header = [0; COMPRESSED_HEADER_LEN as usize];
+        for b in header.iter_mut() {
+            *b = src.next()?.ok_or(BootError::BadImage)?;
+        }
+        let image_len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let compressed_len = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if image_len as usize > self.active.capacity()
+            || compressed_len as usize > self.dfu.capacity() - COMPRESSED_HEADER_LEN as usize
+        {
+            return Err(BootError::BadImage);
+        }
+
+        let end = COMPRESSED_HEADER_LEN + compressed_len;
+        let mut validate = lz4::Validate::new(image_len, self.version_offset);
+        lz4::decompress(
+            &mut DfuSource::new(&mut self.dfu, COMPRESSED_HEADER_LEN, end),
+            &mut validate,
+        )?;
+        if !validate.is_complete() {
+            return Err(BootError::BadImage);
+        }
+        if let Some(version) = validate.version() {
+            if version < self.min_version(aligned_buf)? {
+                return Err(BootError::Rollback);
+            }
+        }
+
+        let done = self.current_progress(aligned_buf)? as u32 * Self::PAGE_SIZE;
+        let mut sink = ActiveSink {
+            active: &mut self.active,
+            state: &mut self.state,
+            buf: aligned_buf,
+            page_size: Self::PAGE_SIZE,
+            page_count: (image_len + Self::PAGE_SIZE - 1) / Self::PAGE_SIZE,
+            progress,
+            done,
+            pos: 0,
+            chunk_start: done,
+            filled: 0,
+        };
+        lz4::decompress(
+            &mut DfuSource::new(&mut self.dfu, COMPRESSED_HEADER_LEN, end),
+            &mut sink,
+        )?;
+        sink.finish()
+    }
+
+    /// Reset the state partition, leaving the given magic and the anti-rollback counter.
+    fn set_magic(&mut self, aligned_buf: &mut [u8], magic: u8) -> Result<(), BootError> {
+        let counter = self.read_counter(aligned_buf)?;
+        self.reset_state(aligned_buf, magic, counter)
+    }
+
+    /// Reset the state partition, leaving the given magic and anti-rollback counter.
+    fn reset_state(&mut self, aligned_buf: &mut [u8], magic: u8, counter: u32) -> Result<(), BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+
+        // Invalidate progress
+        state_word.fill(!STATE_ERASE_VALUE);
+        self.state.write(STATE::WRITE_SIZE as u32, state_word)?;
+
+        // Clear magic and progress
+        self.state.erase(0, self.state.capacity() as u32)?;
+
+        // Set counter
+        if counter != 0 {
+            let offset = counter_offset(self.state.capacity(), STATE::WRITE_SIZE);
+            for (i, chunk) in counter.to_le_bytes().chunks(STATE::WRITE_SIZE).enumerate() {
+                state_word.fill(STATE_ERASE_VALUE);
+                state_word[..chunk.len()].copy_from_slice(chunk);
+                self.state.write((offset + i * STATE::WRITE_SIZE) as u32, state_word)?;
+            }
+        }
+
+        // Set magic
+        state_word.fill(magic);
+        self.state.write(0, state_word)?;
+        Ok(())
+    }
+
+    /// Read the anti-rollback counter, 0 if not set.
+    fn read_counter(&mut self, aligned_buf: &mut [u8]) -> Result<u32, BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        let offset = counter_offset(self.state.capacity(), STATE::WRITE_SIZE);
+        let mut counter = [0; COUNTER_LEN];
+        for (i, chunk) in counter.chunks_mut(STATE::WRITE_SIZE).enumerate() {
+            self.state.read((offset + i * STATE::WRITE_SIZE) as u32, state_word)?;
+            chunk.copy_from_slice(&state_word[..chunk.len()]);
+        }
+
+        if counter.iter().all(|&b| b == STATE_ERASE_VALUE) {
+            Ok(0)
+        } else {
+            Ok(u32::from_le_bytes(counter))
+        }
+    }
+
+    /// Lowest version accepted for an update.
+    ///
+    /// The active firmware version is taken into account as the counter is lost on power failure
+    /// while the state partition is reset.
+    fn min_version(&mut self, aligned_buf: &mut [u8]) -> Result<u32, BootError> {
+        let counter = self.read_counter(aligned_buf)?;
+        Ok(counter.max(self.active_version(aligned_buf)?))
+    }
+
+    fn active_version(&mut self, aligned_buf: &mut [u8]) -> Result<u32, BootError> {
+        let (chunk_offset, pos) = self.version_chunk(aligned_buf.len());
+        self.active.read(chunk_offset, aligned_buf)?;
+        Ok(u32::from_le_bytes(
+            aligned_buf[pos..pos + COUNTER_LEN].try_into().unwrap(),
+        ))
+    }
+
+    fn update_version(&mut self, aligned_buf: &mut [u8]) -> Result<u32, BootError> {
+        let (chunk_offset, pos) = self.version_chunk(aligned_buf.len());
+        self.dfu.read(chunk_offset, aligned_buf)?;
+        self.crypt(Image::Update, chunk_offset, aligned_buf);
+        Ok(u32::from_le_bytes(
+            aligned_buf[pos..pos + COUNTER_LEN].try_into().unwrap(),
+        ))
+    }
+
+    /// Offset of the buffer sized chunk of firmware holding the version, and position of the
+    /// version in it.
+    fn version_chunk(&self, buf_len: usize) -> (u32, usize) {
+        let version_offset = unwrap!(self.version_offset);
+        let chunk_offset = version_offset / buf_len as u32 * buf_len as u32;
+        (chunk_offset, (version_offset - chunk_offset) as usize)
+    }
+
+    fn has_magic(&mut self, aligned_buf: &mut [u8], magic: u8) -> Result<bool, BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        self.state.read(0, state_word)?;
+        Ok(!state_word.iter().any(|&b| b != magic))
     }
 
     fn is_swapped(&mut self, aligned_buf: &mut [u8]) -> Result<bool, BootError> {
@@ -296,6 +721,7 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
 
     fn copy_page_once_to_active(
         &mut self,
+        image: Image,
         progress_index: usize,
         from_offset: u32,
         to_offset: u32,
@@ -308,6 +734,7 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
 
             for offset_in_page in (0..page_size).step_by(aligned_buf.len()) {
                 self.dfu.read(from_offset + offset_in_page as u32, aligned_buf)?;
+                self.crypt(image, to_offset + offset_in_page as u32, aligned_buf);
                 self.active.write(to_offset + offset_in_page as u32, aligned_buf)?;
             }
 
@@ -318,6 +745,7 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
 
     fn copy_page_once_to_dfu(
         &mut self,
+        image: Image,
         progress_index: usize,
         from_offset: u32,
         to_offset: u32,
@@ -330,6 +758,7 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
 
             for offset_in_page in (0..page_size).step_by(aligned_buf.len()) {
                 self.active.read(from_offset + offset_in_page as u32, aligned_buf)?;
+                self.crypt(image, from_offset + offset_in_page as u32, aligned_buf);
                 self.dfu.write(to_offset + offset_in_page as u32, aligned_buf)?;
             }
 
@@ -338,7 +767,7 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
         Ok(())
     }
 
-    fn swap(&mut self, aligned_buf: &mut [u8]) -> Result<(), BootError> {
+    fn swap(&mut self, aligned_buf: &mut [u8], progress: &mut dyn FnMut(BootProgress)) -> Result<(), BootError> {
         let page_count = self.active.capacity() as u32 / Self::PAGE_SIZE;
         for page_num in 0..page_count {
             let progress_index = (page_num * 2) as usize;
@@ -347,19 +776,37 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
             let active_from_offset = (page_count - 1 - page_num) * Self::PAGE_SIZE;
             let dfu_to_offset = (page_count - page_num) * Self::PAGE_SIZE;
             //trace!("Copy active {} to dfu {}", active_from_offset, dfu_to_offset);
-            self.copy_page_once_to_dfu(progress_index, active_from_offset, dfu_to_offset, aligned_buf)?;
+            self.copy_page_once_to_dfu(
+                Image::Previous,
+                progress_index,
+                active_from_offset,
+                dfu_to_offset,
+                aligned_buf,
+            )?;
 
             // Copy DFU page to the active page
             let active_to_offset = (page_count - 1 - page_num) * Self::PAGE_SIZE;
             let dfu_from_offset = (page_count - 1 - page_num) * Self::PAGE_SIZE;
             //trace!("Copy dfy {} to active {}", dfu_from_offset, active_to_offset);
-            self.copy_page_once_to_active(progress_index + 1, dfu_from_offset, active_to_offset, aligned_buf)?;
+            self.copy_page_once_to_active(
+                Image::Update,
+                progress_index + 1,
+                dfu_from_offset,
+                active_to_offset,
+                aligned_buf,
+            )?;
+
+            progress(BootProgress {
+                operation: BootOperation::Swap,
+                done: page_num + 1,
+                total: page_count,
+            });
         }
 
         Ok(())
     }
 
-    fn revert(&mut self, aligned_buf: &mut [u8]) -> Result<(), BootError> {
+    fn revert(&mut self, aligned_buf: &mut [u8], progress: &mut dyn FnMut(BootProgress)) -> Result<(), BootError> {
         let page_count = self.active.capacity() as u32 / Self::PAGE_SIZE;
         for page_num in 0..page_count {
             let progress_index = (page_count * 2 + page_num * 2) as usize;
@@ -367,12 +814,30 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
             // Copy the bad active page to the DFU page
             let active_from_offset = page_num * Self::PAGE_SIZE;
             let dfu_to_offset = page_num * Self::PAGE_SIZE;
-            self.copy_page_once_to_dfu(progress_index, active_from_offset, dfu_to_offset, aligned_buf)?;
+            self.copy_page_once_to_dfu(
+                Image::Update,
+                progress_index,
+                active_from_offset,
+                dfu_to_offset,
+                aligned_buf,
+            )?;
 
             // Copy the DFU page back to the active page
             let active_to_offset = page_num * Self::PAGE_SIZE;
             let dfu_from_offset = (page_num + 1) * Self::PAGE_SIZE;
-            self.copy_page_once_to_active(progress_index + 1, dfu_from_offset, active_to_offset, aligned_buf)?;
+            self.copy_page_once_to_active(
+                Image::Previous,
+                progress_index + 1,
+                dfu_from_offset,
+                active_to_offset,
+                aligned_buf,
+            )?;
+
+            progress(BootProgress {
+                operation: BootOperation::Revert,
+                done: page_num + 1,
+                total: page_count,
+            });
         }
 
         Ok(())
@@ -384,12 +849,59 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> BootLoader<ACTIVE, DFU, S
 
         if !state_word.iter().any(|&b| b != SWAP_MAGIC) {
             Ok(State::Swap)
+        } else if !state_word.iter().any(|&b| b != DFU_DETACH_MAGIC) {
+            Ok(State::DfuDetach)
         } else {
             Ok(State::Boot)
         }
     }
 }
 
+/// A secondary image, such as the firmware of a co-processor, updated along with a primary image
+/// by [`BootLoader::prepare_boot_multi`].
+///
+/// Implemented by [`BootLoader`], created with the partitions of the secondary image.
+pub trait SecondaryImage {
+    /// Swap or revert the image along with the primary image, which performs `operation`, or
+    /// nothing when booted.
+    ///
+    /// Returns the state of the image, like [`BootLoader::prepare_boot`].
+    fn follow(&mut self, operation: Option<BootOperation>, aligned_buf: &mut [u8]) -> Result<State, BootError>;
+}
+
+impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash> SecondaryImage for BootLoader<ACTIVE, DFU, STATE> {
+    fn follow(&mut self, operation: Option<BootOperation>, aligned_buf: &mut [u8]) -> Result<State, BootError> {
+        self.assert_config(aligned_buf);
+
+        let state = self.read_state(aligned_buf)?;
+        if state != State::Swap {
+            return Ok(state);
+        }
+        if operation.is_none() {
+            // The update was either not committed, or confirmed with the primary image
+            self.set_magic(aligned_buf, BOOT_MAGIC)?;
+            return Ok(State::Boot);
+        }
+
+        assert!(self.dfu.capacity() >= self.active.capacity() + Self::PAGE_SIZE as usize);
+        #[cfg(feature = "encryption-chacha20")]
+        self.read_nonce(aligned_buf)?;
+
+        let swapped = self.is_swapped(aligned_buf)?;
+        if operation == Some(BootOperation::Revert) {
+            if swapped {
+                trace!("Reverting secondary image");
+                self.revert(aligned_buf, &mut |_| {})?;
+            }
+            self.set_magic(aligned_buf, BOOT_MAGIC)?;
+        } else if !swapped {
+            trace!("Swapping secondary image");
+            self.swap(aligned_buf, &mut |_| {})?;
+        }
+        Ok(state)
+    }
+}
+
 fn assert_partitions<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash>(
     active: &ACTIVE,
     dfu: &DFU,
@@ -398,10 +910,137 @@ fn assert_partitions<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash>(
 ) {
     assert_eq!(active.capacity() as u32 % page_size, 0);
     assert_eq!(dfu.capacity() as u32 % page_size, 0);
-    assert!(dfu.capacity() as u32 - active.capacity() as u32 >= page_size);
     assert!(2 + 2 * (active.capacity() as u32 / page_size) <= state.capacity() as u32 / STATE::WRITE_SIZE as u32);
 }
 
+/// Compressed data read from the DFU partition.
+struct DfuSource<'a, DFU: NorFlash> {
+    dfu: &'a mut DFU,
+    offset: u32,
+    end: u32,
+    buf: [u8; 32],
+    pos: usize,
+    len: usize,
+}
+
+impl<'a, DFU: NorFlash> DfuSource<'a, DFU> {
+    fn new(dfu: &'a mut DFU, offset: u32, end: u32) -> Self {
+        Self {
+            dfu,
+            offset,
+            end,
+            buf: [0; 32],
+            pos: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<'a, DFU: NorFlash> Source for DfuSource<'a, DFU> {
+    fn next(&mut self) -> Result<Option<u8>, BootError> {
+        if self.pos == self.len {
+            if self.offset == self.end {
+                return Ok(None);
+            }
+            // Read whole chunks to respect the read size.
+            let read_len = self.buf.len().min(self.dfu.capacity() - self.offset as usize);
+            self.dfu.read(self.offset, &mut self.buf[..read_len])?;
+            self.len = read_len.min((self.end - self.offset) as usize);
+            self.offset += self.len as u32;
+            self.pos = 0;
+        }
+        let byte = self.buf[self.pos];
+        self.pos += 1;
+        Ok(Some(byte))
+    }
+}
+
+/// Decompressed image written to the active partition, one buffer at a time.
+struct ActiveSink<'a, ACTIVE: NorFlash, STATE: NorFlash> {
+    active: &'a mut ACTIVE,
+    state: &'a mut STATE,
+    buf: &'a mut [u8],
+    page_size: u32,
+    page_count: u32,
+    progress: &'a mut dyn FnMut(BootProgress),
+    /// Length already written before a power failure.
+    done: u32,
+    pos: u32,
+    chunk_start: u32,
+    filled: usize,
+}
+
+impl<'a, ACTIVE: NorFlash, STATE: NorFlash> ActiveSink<'a, ACTIVE, STATE> {
+    fn flush(&mut self) -> Result<(), BootError> {
+        if self.chunk_start % self.page_size == 0 {
+            self.active.erase(self.chunk_start, self.chunk_start + self.page_size)?;
+        }
+        self.buf[self.filled..].fill(STATE_ERASE_VALUE);
+        self.active.write(self.chunk_start, self.buf)?;
+        self.chunk_start += self.buf.len() as u32;
+        self.filled = 0;
+
+        if self.chunk_start % self.page_size == 0 {
+            // Mark the page as written
+            let page = self.chunk_start / self.page_size - 1;
+            let state_word = &mut self.buf[..STATE::WRITE_SIZE];
+            state_word.fill(!STATE_ERASE_VALUE);
+            self.state.write((2 + page) * STATE::WRITE_SIZE as u32, state_word)?;
+
+            (self.progress)(BootProgress {
+                operation: BootOperation::Decompress,
+                done: page + 1,
+                total: self.page_count,
+            });
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), BootError> {
+        if self.filled > 0 {
+            self.flush()?;
+        }
+        if self.chunk_start % self.page_size != 0 {
+            // The last page is not full
+            (self.progress)(BootProgress {
+                operation: BootOperation::Decompress,
+                done: self.page_count,
+                total: self.page_count,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'a, ACTIVE: NorFlash, STATE: NorFlash> Sink for ActiveSink<'a, ACTIVE, STATE> {
+    fn pos(&self) -> u32 {
+        self.pos
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), BootError> {
+        self.pos += 1;
+        if self.pos <= self.done {
+            return Ok(());
+        }
+        self.buf[self.filled] = byte;
+        self.filled += 1;
+        if self.filled == self.buf.len() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, pos: u32) -> Result<u8, BootError> {
+        if pos >= self.chunk_start {
+            Ok(self.buf[(pos - self.chunk_start) as usize])
+        } else {
+            let mut byte = [0];
+            self.active.read(pos, &mut byte)?;
+            Ok(byte[0])
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
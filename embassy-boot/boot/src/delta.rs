@@ -0,0 +1,183 @@
+use crate::{FirmwareUpdaterError, STATE_ERASE_VALUE};
+
+const OP_COPY: u8 = 0;
+const OP_ADD: u8 = 1;
+const OP_INSERT: u8 = 2;
+
+/// Longest operation header: opcode, length and source offset.
+const MAX_HEADER_LEN: usize = 9;
+
+#[derive(Clone, Copy)]
+enum Op {
+    Header,
+    Copy { src: u32, len: u32 },
+    Add { src: u32, len: u32 },
+    Insert { len: u32 },
+}
+
+/// A step of a patch, limited to the space left in the current page.
+pub(crate) enum Action<'d> {
+    /// Copy `len` bytes of the active firmware from offset `src`.
+    Copy { src: u32, len: usize },
+    /// Copy bytes of the active firmware from offset `src`, adding `diff` to them.
+    Add { src: u32, diff: &'d [u8] },
+    /// Insert new bytes.
+    Insert(&'d [u8]),
+}
+
+/// A delta patch being applied to the DFU partition.
+///
+/// A delta patch describes the new firmware relatively to the active firmware, so that only the
+/// differences have to be transferred. It is a sequence of operations, with all integers encoded
+/// in little endian:
+///
+/// - `0`, `len: u32`, `src: u32`: copy `len` bytes of the active firmware at offset `src`.
+/// - `1`, `len: u32`, `src: u32`, followed by `len` bytes: copy `len` bytes of the active firmware
+///   at offset `src`, adding the given bytes to them (wrapping).
+/// - `2`, `len: u32`, followed by `len` bytes: insert the given bytes.
+///
+/// The add operation makes code moved by the update cheap to encode, as the differences are
+/// mostly zeroes and compress well, like in bsdiff.
+///
+/// The patch is fed to [`BlockingFirmwareUpdater::write_firmware_delta`](crate::BlockingFirmwareUpdater::write_firmware_delta)
+/// in chunks of any size as it is received, the new firmware being written to the DFU partition
+/// one page at a time.
+pub struct DeltaPatch<'a> {
+    page: &'a mut [u8],
+    filled: usize,
+    offset: u32,
+    header: [u8; MAX_HEADER_LEN],
+    header_len: usize,
+    op: Op,
+}
+
+impl<'a> DeltaPatch<'a> {
+    /// Start applying a delta patch.
+    ///
+    /// The `page` buffer holds the new firmware until written to the DFU partition. Its length
+    /// must be a multiple of the erase size of the DFU partition.
+    pub fn new(page: &'a mut [u8]) -> Self {
+        Self {
+            page,
+            filled: 0,
+            offset: 0,
+            header: [0; MAX_HEADER_LEN],
+            header_len: 0,
+            op: Op::Header,
+        }
+    }
+
+    /// Length of the new firmware produced so far.
+    pub fn update_len(&self) -> u32 {
+        self.offset + self.filled as u32
+    }
+
+    pub(crate) fn page_len(&self) -> usize {
+        self.page.len()
+    }
+
+    /// Decode the next step of the patch, consuming its bytes from `data`.
+    ///
+    /// Returns `None` once more data is needed.
+    pub(crate) fn next<'d>(&mut self, data: &mut &'d [u8]) -> Result<Option<Action<'d>>, FirmwareUpdaterError> {
+        let space = self.page.len() - self.filled;
+        loop {
+            match self.op {
+                Op::Header => {
+                    if data.is_empty() {
+                        return Ok(None);
+                    }
+                    let opcode = if self.header_len > 0 { self.header[0] } else { data[0] };
+                    let header_len = match opcode {
+                        OP_COPY | OP_ADD => 9,
+                        OP_INSERT => 5,
+                        _ => return Err(FirmwareUpdaterError::BadPatch),
+                    };
+                    let n = (header_len - self.header_len).min(data.len());
+                    self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                    self.header_len += n;
+                    *data = &data[n..];
+                    if self.header_len < header_len {
+                        return Ok(None);
+                    }
+
+                    self.header_len = 0;
+                    let len = u32::from_le_bytes(self.header[1..5].try_into().unwrap());
+                    let src = u32::from_le_bytes(self.header[5..9].try_into().unwrap());
+                    self.op = match opcode {
+                        OP_COPY => Op::Copy { src, len },
+                        OP_ADD => Op::Add { src, len },
+                        _ => Op::Insert { len },
+                    };
+                }
+                Op::Copy { len: 0, .. } | Op::Add { len: 0, .. } | Op::Insert { len: 0 } => self.op = Op::Header,
+                Op::Copy { src, len } => {
+                    let n = space.min(len as usize);
+                    self.op = Op::Copy {
+                        src: src + n as u32,
+                        len: len - n as u32,
+                    };
+                    return Ok(Some(Action::Copy { src, len: n }));
+                }
+                Op::Add { src, len } => {
+                    if data.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = space.min(len as usize).min(data.len());
+                    let (diff, rest) = data.split_at(n);
+                    *data = rest;
+                    self.op = Op::Add {
+                        src: src + n as u32,
+                        len: len - n as u32,
+                    };
+                    return Ok(Some(Action::Add { src, diff }));
+                }
+                Op::Insert { len } => {
+                    if data.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = space.min(len as usize).min(data.len());
+                    let (bytes, rest) = data.split_at(n);
+                    *data = rest;
+                    self.op = Op::Insert { len: len - n as u32 };
+                    return Ok(Some(Action::Insert(bytes)));
+                }
+            }
+        }
+    }
+
+    /// Append `len` bytes to the current page, returning them to be filled in.
+    pub(crate) fn push(&mut self, len: usize) -> &mut [u8] {
+        let start = self.filled;
+        self.filled += len;
+        &mut self.page[start..self.filled]
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.filled == self.page.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// Whether the patch ends on an operation boundary.
+    pub(crate) fn is_complete(&self) -> bool {
+        matches!(self.op, Op::Header) && self.header_len == 0
+    }
+
+    /// Take the current page to write it at the returned offset of the DFU partition, padding it
+    /// with the erase value.
+    pub(crate) fn take_page(&mut self, capacity: usize) -> Result<(u32, &[u8]), FirmwareUpdaterError> {
+        let offset = self.offset;
+        if offset as usize + self.filled > capacity {
+            return Err(FirmwareUpdaterError::BadPatch);
+        }
+        let len = self.page.len().min(capacity - offset as usize);
+        self.page[self.filled..len].fill(STATE_ERASE_VALUE);
+
+        self.offset += len as u32;
+        self.filled = 0;
+        Ok((offset, &self.page[..len]))
+    }
+}
@@ -0,0 +1,174 @@
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::{BootError, State, BOOT_MAGIC, DFU_DETACH_MAGIC, STATE_ERASE_VALUE, SWAP_MAGIC};
+
+/// Index of the state word set when the update is in the swapped bank.
+const TARGET_SWAPPED: usize = 2;
+/// Index of the state word set when the update is in the bank mapped by default.
+const TARGET_RESTORED: usize = 3;
+/// Index of the state word set once the update is booted on trial.
+const TRIAL: usize = 4;
+
+/// The flash banks of a dual-bank chip, such as the STM32H7 or STM32L4, which can be swapped so
+/// that the second bank is mapped, and booted, at the address of the first one.
+pub trait FlashBanks {
+    /// Whether the banks are currently swapped.
+    fn is_swapped(&mut self) -> Result<bool, BootError>;
+
+    /// Swap the banks, or restore their default mapping.
+    ///
+    /// Only returns once the banks are mapped as requested: when the new mapping only applies
+    /// after a reset, e.g. when written to the option bytes, the implementation resets the chip.
+    fn set_swapped(&mut self, swapped: bool) -> Result<(), BootError>;
+}
+
+/// A bootloader for dual-bank chips, activating updates by swapping the flash banks instead of
+/// copying the firmware.
+///
+/// Each bank holds a copy of the bootloader and a firmware. The application writes the update to
+/// the other bank with the [`FirmwareUpdater`](crate::BlockingFirmwareUpdater), using it as DFU
+/// partition, and marks it updated as usual. The bootloader then swaps the banks, which only takes
+/// a few milliseconds and does not wear the flash. As with the swap, the update is booted on trial:
+/// unless the application marks it booted, the banks are swapped back on the next boot.
+///
+/// The state partition must not be remapped when the banks are swapped, e.g. by placing it in an
+/// external flash, and be at least 5 write sizes long.
+pub struct DualBankBootLoader<STATE: NorFlash, BANKS: FlashBanks> {
+    /// The state partition has the following format:
+    /// All ranges are in multiples of WRITE_SIZE bytes.
+    /// | Range | Description                                                                         |
+    /// | 0..1  | Magic indicating bootloader state. BOOT_MAGIC means boot, SWAP_MAGIC means swap,    |
+    /// |       | DFU_DETACH_MAGIC means the application requested DFU mode.                          |
+    /// | 1..2  | Unused, for compatibility with the layout of the swapping bootloader.               |
+    /// | 2..3  | Set when the update is in the swapped bank.                                         |
+    /// | 3..4  | Set when the update is in the bank mapped by default.                               |
+    /// | 4..5  | Set once the update is booted on trial.                                             |
+    state: STATE,
+    banks: BANKS,
+}
+
+impl<STATE: NorFlash, BANKS: FlashBanks> DualBankBootLoader<STATE, BANKS> {
+    /// Create a new instance of a dual-bank bootloader with the state partition and flash banks.
+    pub fn new(state: STATE, banks: BANKS) -> Self {
+        Self { state, banks }
+    }
+
+    /// Perform necessary boot preparations like swapping banks.
+    ///
+    /// Returns [`State::Swap`] when booting an update on trial, or after reverting it because the
+    /// application failed to mark it booted.
+    pub fn prepare_boot(&mut self, aligned_buf: &mut [u8]) -> Result<State, BootError> {
+        assert!(aligned_buf.len() >= STATE::WRITE_SIZE);
+        assert!((TRIAL + 1) * STATE::WRITE_SIZE <= self.state.capacity());
+
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        self.state.read(0, state_word)?;
+        if state_word.iter().any(|&b| b != SWAP_MAGIC) {
+            if !state_word.iter().any(|&b| b != DFU_DETACH_MAGIC) {
+                return Ok(State::DfuDetach);
+            }
+            return Ok(State::Boot);
+        }
+
+        // Record the bank of the update first, as swapping the banks may reset the chip
+        let target = if self.is_set(TARGET_SWAPPED, aligned_buf)? {
+            true
+        } else if self.is_set(TARGET_RESTORED, aligned_buf)? {
+            false
+        } else {
+            let target = !self.banks.is_swapped()?;
+            self.set(if target { TARGET_SWAPPED } else { TARGET_RESTORED }, aligned_buf)?;
+            target
+        };
+
+        if self.is_set(TRIAL, aligned_buf)? {
+            trace!("Reverting");
+            self.banks.set_swapped(!target)?;
+            self.set_magic(aligned_buf, BOOT_MAGIC)?;
+        } else {
+            trace!("Swapping banks");
+            self.banks.set_swapped(target)?;
+            self.set(TRIAL, aligned_buf)?;
+        }
+        Ok(State::Swap)
+    }
+
+    fn is_set(&mut self, index: usize, aligned_buf: &mut [u8]) -> Result<bool, BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        self.state.read((index * STATE::WRITE_SIZE) as u32, state_word)?;
+        Ok(state_word.iter().any(|&b| b != STATE_ERASE_VALUE))
+    }
+
+    fn set(&mut self, index: usize, aligned_buf: &mut [u8]) -> Result<(), BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        state_word.fill(!STATE_ERASE_VALUE);
+        self.state.write((index * STATE::WRITE_SIZE) as u32, state_word)?;
+        Ok(())
+    }
+
+    fn set_magic(&mut self, aligned_buf: &mut [u8], magic: u8) -> Result<(), BootError> {
+        let state_word = &mut aligned_buf[..STATE::WRITE_SIZE];
+        self.state.erase(0, self.state.capacity() as u32)?;
+        state_word.fill(magic);
+        self.state.write(0, state_word)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use embassy_embedded_hal::flash::partition::BlockingPartition;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embassy_sync::blocking_mutex::Mutex;
+
+    use super::*;
+    use crate::mem_flash::MemFlash;
+    use crate::{BlockingFirmwareUpdater, FirmwareUpdaterConfig};
+
+    #[derive(Default)]
+    struct TestBanks {
+        swapped: bool,
+    }
+
+    impl FlashBanks for TestBanks {
+        fn is_swapped(&mut self) -> Result<bool, BootError> {
+            Ok(self.swapped)
+        }
+
+        fn set_swapped(&mut self, swapped: bool) -> Result<(), BootError> {
+            self.swapped = swapped;
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "_verify"))]
+    fn can_swap_banks() {
+        let flash = Mutex::<NoopRawMutex, _>::new(RefCell::new(MemFlash::<69632, 4096, 8>::default()));
+        let mut aligned = [0; 8];
+        let mut updater = BlockingFirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: BlockingPartition::new(&flash, 4096, 65536),
+            state: BlockingPartition::new(&flash, 0, 4096),
+        });
+
+        let mut bootloader = DualBankBootLoader::new(BlockingPartition::new(&flash, 0, 4096), TestBanks::default());
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut aligned).unwrap());
+
+        // The update is booted on trial, and reverted when not marked booted
+        updater.mark_updated(&mut aligned).unwrap();
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut aligned).unwrap());
+        assert!(bootloader.banks.swapped);
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut aligned).unwrap());
+        assert!(!bootloader.banks.swapped);
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut aligned).unwrap());
+
+        // The update is kept once marked booted
+        updater.mark_updated(&mut aligned).unwrap();
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut aligned).unwrap());
+        updater.mark_booted(&mut aligned).unwrap();
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut aligned).unwrap());
+        assert!(bootloader.banks.swapped);
+    }
+}
@@ -3,16 +3,21 @@ use digest::Digest;
 use embassy_embedded_hal::flash::partition::Partition;
 #[cfg(target_os = "none")]
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
-use embedded_storage_async::nor_flash::NorFlash;
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
 
-use super::FirmwareUpdaterConfig;
-use crate::{FirmwareUpdaterError, State, BOOT_MAGIC, STATE_ERASE_VALUE, SWAP_MAGIC};
+use super::{FirmwareUpdaterConfig, UpdateStatus};
+use crate::delta::{Action, DeltaPatch};
+use crate::{
+    counter_offset, FirmwareUpdaterError, State, BOOT_MAGIC, COUNTER_LEN, DFU_DETACH_MAGIC, STATE_ERASE_VALUE,
+    SWAP_COMPRESSED_MAGIC, SWAP_MAGIC,
+};
 
 /// FirmwareUpdater is an application API for interacting with the BootLoader without the ability to
 /// 'mess up' the internal bootloader state
 pub struct FirmwareUpdater<DFU: NorFlash, STATE: NorFlash> {
     dfu: DFU,
     state: STATE,
+    written: u32,
 }
 
 #[cfg(target_os = "none")]
@@ -21,6 +26,22 @@ impl<'a, FLASH: NorFlash>
 {
     /// Create a firmware updater config from the flash and address symbols defined in the linkerfile
     pub fn from_linkerfile(flash: &'a embassy_sync::mutex::Mutex<NoopRawMutex, FLASH>) -> Self {
+        Self::from_linkerfile_with_dfu(flash, flash)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<'a, DFU: NorFlash, STATE: NorFlash>
+    FirmwareUpdaterConfig<Partition<'a, NoopRawMutex, DFU>, Partition<'a, NoopRawMutex, STATE>>
+{
+    /// Create a firmware updater config with the DFU partition in a separate flash, e.g. an
+    /// external flash, from the address symbols defined in the linkerfile.
+    ///
+    /// The DFU symbols are offsets in `dfu_flash`.
+    pub fn from_linkerfile_with_dfu(
+        dfu_flash: &'a embassy_sync::mutex::Mutex<NoopRawMutex, DFU>,
+        state_flash: &'a embassy_sync::mutex::Mutex<NoopRawMutex, STATE>,
+    ) -> Self {
         extern "C" {
             static __bootloader_state_start: u32;
             static __bootloader_state_end: u32;
@@ -33,14 +54,14 @@ impl<'a, FLASH: NorFlash>
             let end = &__bootloader_dfu_end as *const u32 as u32;
             trace!("DFU: 0x{:x} - 0x{:x}", start, end);
 
-            Partition::new(flash, start, end - start)
+            Partition::new(dfu_flash, start, end - start)
         };
         let state = unsafe {
             let start = &__bootloader_state_start as *const u32 as u32;
             let end = &__bootloader_state_end as *const u32 as u32;
             trace!("STATE: 0x{:x} - 0x{:x}", start, end);
 
-            Partition::new(flash, start, end - start)
+            Partition::new(state_flash, start, end - start)
         };
 
         Self { dfu, state }
@@ -53,13 +74,17 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
         Self {
             dfu: config.dfu,
             state: config.state,
+            written: 0,
         }
     }
 
     // Make sure we are running a booted firmware to avoid reverting to a bad state.
+    //
+    // The DFU detach state is also accepted, as that is the state in which a bootloader
+    // running in DFU mode writes the new firmware.
     async fn verify_booted(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
         assert_eq!(aligned.len(), STATE::WRITE_SIZE);
-        if self.get_state(aligned).await? == State::Boot {
+        if matches!(self.get_state(aligned).await?, State::Boot | State::DfuDetach) {
             Ok(())
         } else {
             Err(FirmwareUpdaterError::BadState)
@@ -74,13 +99,26 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
     pub async fn get_state(&mut self, aligned: &mut [u8]) -> Result<State, FirmwareUpdaterError> {
         self.state.read(0, aligned).await?;
 
-        if !aligned.iter().any(|&b| b != SWAP_MAGIC) {
+        if !aligned.iter().any(|&b| b != SWAP_MAGIC) || !aligned.iter().any(|&b| b != SWAP_COMPRESSED_MAGIC) {
             Ok(State::Swap)
+        } else if !aligned.iter().any(|&b| b != DFU_DETACH_MAGIC) {
+            Ok(State::DfuDetach)
         } else {
             Ok(State::Boot)
         }
     }
 
+    /// Obtain the status of the update: the bootloader state, and the number of bytes written
+    /// with [`Self::write_firmware`] or [`Self::write_firmware_delta`].
+    ///
+    /// This is useful to report progress, or the status of an update to a server.
+    pub async fn status(&mut self, aligned: &mut [u8]) -> Result<UpdateStatus, FirmwareUpdaterError> {
+        Ok(UpdateStatus {
+            state: self.get_state(aligned).await?,
+            written: self.written,
+        })
+    }
+
     /// Verify the DFU given a public key. If there is an error then DO NOT
     /// proceed with updating the firmware as it must be signed with a
     /// corresponding private key (otherwise it could be malicious firmware).
@@ -90,6 +128,10 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
     /// If the "ed25519-salty" feature is set (or another similar feature) then the signature is expected to have
     /// been generated from a SHA-512 digest of the firmware bytes.
     ///
+    /// If the "ecdsa-p256" feature is set then the signature is expected to be an ECDSA P-256 signature of a
+    /// SHA-256 digest of the firmware bytes, encoded as the 64 bytes `r || s`. The public key is a SEC1 encoded
+    /// point, compressed or uncompressed.
+    ///
     /// If no signature feature is set then this method will always return a
     /// signature error.
     ///
@@ -155,6 +197,22 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
             );
             r.map_err(into_signature_error)?
         }
+        #[cfg(feature = "ecdsa-p256")]
+        {
+            use p256::ecdsa::signature::hazmat::PrehashVerifier;
+            use p256::ecdsa::{Signature, VerifyingKey};
+            use sha2::Sha256;
+
+            let public_key = VerifyingKey::from_sec1_bytes(_public_key).map_err(FirmwareUpdaterError::Signature)?;
+            let signature = Signature::try_from(_signature).map_err(FirmwareUpdaterError::Signature)?;
+
+            let mut message = [0; 32];
+            self.hash::<Sha256>(_update_len, _aligned, &mut message).await?;
+
+            public_key
+                .verify_prehash(&message, &signature)
+                .map_err(FirmwareUpdaterError::Signature)?
+        }
 
         self.set_magic(_aligned, SWAP_MAGIC).await
     }
@@ -187,6 +245,64 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
         self.set_magic(aligned, SWAP_MAGIC).await
     }
 
+    /// Mark to trigger decompressing the DFU partition on next boot.
+    ///
+    /// The DFU partition must hold a compressed image in the format described by
+    /// [`BootLoader::prepare_boot`](crate::BootLoader::prepare_boot). Unlike swapped updates,
+    /// compressed updates can not be reverted.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    #[cfg(not(feature = "_verify"))]
+    pub async fn mark_updated_compressed(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.set_magic(aligned, SWAP_COMPRESSED_MAGIC).await
+    }
+
+    /// Write the nonce the update was encrypted with, for bootloaders decrypting updates.
+    ///
+    /// The nonce is stored in the last erase block of the DFU partition. It must be written after
+    /// [`Self::prepare_update`], which erases it.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being
+    /// written to. The `buf` buffer must be at least `NONCE_LEN` long, and follow the alignment and size rules of
+    /// the DFU flash.
+    #[cfg(feature = "encryption-chacha20")]
+    pub async fn write_nonce(
+        &mut self,
+        aligned: &mut [u8],
+        buf: &mut [u8],
+        nonce: &[u8; crate::NONCE_LEN],
+    ) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.verify_booted(aligned).await?;
+
+        let offset = (self.dfu.capacity() - DFU::ERASE_SIZE) as u32;
+        self.dfu.erase(offset, offset + DFU::ERASE_SIZE as u32).await?;
+
+        buf.fill(STATE_ERASE_VALUE);
+        buf[..nonce.len()].copy_from_slice(nonce);
+        self.dfu.write(offset, buf).await?;
+        Ok(())
+    }
+
+    /// Mark to trigger USB DFU mode on next boot.
+    ///
+    /// The bootloader will report [`State::DfuDetach`] from `prepare_boot`, and is expected to run
+    /// a DFU class to receive the new firmware instead of booting the application.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    pub async fn mark_dfu(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.verify_booted(aligned).await?;
+        self.set_magic(aligned, DFU_DETACH_MAGIC).await
+    }
+
     /// Mark firmware boot successful and stop rollback on reset.
     ///
     /// # Safety
@@ -212,9 +328,29 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
                 self.state.write(STATE::WRITE_SIZE as u32, aligned).await?;
             }
 
+            // Keep the anti-rollback counter of the bootloader
+            let offset = counter_offset(self.state.capacity(), STATE::WRITE_SIZE);
+            let mut counter = [0; COUNTER_LEN];
+            for (i, chunk) in counter.chunks_mut(STATE::WRITE_SIZE).enumerate() {
+                self.state
+                    .read((offset + i * STATE::WRITE_SIZE) as u32, aligned)
+                    .await?;
+                chunk.copy_from_slice(&aligned[..chunk.len()]);
+            }
+
             // Clear magic and progress
             self.state.erase(0, self.state.capacity() as u32).await?;
 
+            if counter.iter().any(|&b| b != STATE_ERASE_VALUE) {
+                for (i, chunk) in counter.chunks(STATE::WRITE_SIZE).enumerate() {
+                    aligned.fill(STATE_ERASE_VALUE);
+                    aligned[..chunk.len()].copy_from_slice(chunk);
+                    self.state
+                        .write((offset + i * STATE::WRITE_SIZE) as u32, aligned)
+                        .await?;
+                }
+            }
+
             // Set magic
             aligned.fill(magic);
             self.state.write(0, aligned).await?;
@@ -243,16 +379,91 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
         self.dfu.erase(offset as u32, (offset + data.len()) as u32).await?;
 
         self.dfu.write(offset as u32, data).await?;
+        self.written = self.written.max((offset + data.len()) as u32);
 
         Ok(())
     }
 
+    /// Apply a chunk of a delta patch, writing the new firmware to the DFU partition.
+    ///
+    /// The patch is applied against the `active` partition, which must be readable without alignment
+    /// requirements. Chunks can be of any size, and must be passed in order. Once the whole patch has been
+    /// applied, call [`Self::finish_firmware_delta`].
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    pub async fn write_firmware_delta<ACTIVE: ReadNorFlash>(
+        &mut self,
+        aligned: &mut [u8],
+        patch: &mut DeltaPatch<'_>,
+        active: &mut ACTIVE,
+        mut data: &[u8],
+    ) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        assert_eq!(ACTIVE::READ_SIZE, 1);
+        assert_eq!(patch.page_len() % DFU::ERASE_SIZE, 0);
+
+        self.verify_booted(aligned).await?;
+
+        while let Some(action) = patch.next(&mut data)? {
+            match action {
+                Action::Copy { src, len } => {
+                    if src as usize + len > active.capacity() {
+                        return Err(FirmwareUpdaterError::BadPatch);
+                    }
+                    active.read(src, patch.push(len)).await?;
+                }
+                Action::Add { src, diff } => {
+                    if src as usize + diff.len() > active.capacity() {
+                        return Err(FirmwareUpdaterError::BadPatch);
+                    }
+                    let buf = patch.push(diff.len());
+                    active.read(src, buf).await?;
+                    for (b, d) in buf.iter_mut().zip(diff) {
+                        *b = b.wrapping_add(*d);
+                    }
+                }
+                Action::Insert(bytes) => patch.push(bytes.len()).copy_from_slice(bytes),
+            }
+            if patch.is_full() {
+                self.write_delta_page(patch).await?;
+            }
+        }
+        self.written = patch.update_len();
+        Ok(())
+    }
+
+    /// Finish applying a delta patch, returning the length of the new firmware.
+    ///
+    /// The new firmware can then be verified and marked as updated like a firmware written with
+    /// [`Self::write_firmware`].
+    pub async fn finish_firmware_delta(&mut self, patch: &mut DeltaPatch<'_>) -> Result<u32, FirmwareUpdaterError> {
+        if !patch.is_complete() {
+            return Err(FirmwareUpdaterError::BadPatch);
+        }
+        let update_len = patch.update_len();
+        if !patch.is_empty() {
+            self.write_delta_page(patch).await?;
+        }
+        Ok(update_len)
+    }
+
+    async fn write_delta_page(&mut self, patch: &mut DeltaPatch<'_>) -> Result<(), FirmwareUpdaterError> {
+        let (offset, page) = patch.take_page(self.dfu.capacity())?;
+        self.dfu.erase(offset, offset + page.len() as u32).await?;
+        self.dfu.write(offset, page).await?;
+        Ok(())
+    }
+
     /// Prepare for an incoming DFU update by erasing the entire DFU area and
     /// returning its `Partition`.
     ///
     /// Using this instead of `write_firmware` allows for an optimized API in
     /// exchange for added complexity.
     ///
+    /// Data written directly to the returned partition is not counted by [`Self::status`].
+    ///
     /// # Safety
     ///
     /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
@@ -261,6 +472,7 @@ impl<DFU: NorFlash, STATE: NorFlash> FirmwareUpdater<DFU, STATE> {
         self.verify_booted(aligned).await?;
 
         self.dfu.erase(0, self.dfu.capacity() as u32).await?;
+        self.written = 0;
 
         Ok(&mut self.dfu)
     }
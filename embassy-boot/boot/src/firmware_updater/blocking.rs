@@ -3,16 +3,21 @@ use digest::Digest;
 use embassy_embedded_hal::flash::partition::BlockingPartition;
 #[cfg(target_os = "none")]
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
-use embedded_storage::nor_flash::NorFlash;
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
 
-use super::FirmwareUpdaterConfig;
-use crate::{FirmwareUpdaterError, State, BOOT_MAGIC, STATE_ERASE_VALUE, SWAP_MAGIC};
+use super::{FirmwareUpdaterConfig, UpdateStatus};
+use crate::delta::{Action, DeltaPatch};
+use crate::{
+    counter_offset, FirmwareUpdaterError, State, BOOT_MAGIC, COUNTER_LEN, DFU_DETACH_MAGIC, STATE_ERASE_VALUE,
+    SWAP_COMPRESSED_MAGIC, SWAP_MAGIC,
+};
 
 /// Blocking FirmwareUpdater is an application API for interacting with the BootLoader without the ability to
 /// 'mess up' the internal bootloader state
 pub struct BlockingFirmwareUpdater<DFU: NorFlash, STATE: NorFlash> {
     dfu: DFU,
     state: STATE,
+    written: u32,
 }
 
 #[cfg(target_os = "none")]
@@ -22,6 +27,22 @@ impl<'a, FLASH: NorFlash>
     /// Create a firmware updater config from the flash and address symbols defined in the linkerfile
     pub fn from_linkerfile_blocking(
         flash: &'a embassy_sync::blocking_mutex::Mutex<NoopRawMutex, core::cell::RefCell<FLASH>>,
+    ) -> Self {
+        Self::from_linkerfile_blocking_with_dfu(flash, flash)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<'a, DFU: NorFlash, STATE: NorFlash>
+    FirmwareUpdaterConfig<BlockingPartition<'a, NoopRawMutex, DFU>, BlockingPartition<'a, NoopRawMutex, STATE>>
+{
+    /// Create a firmware updater config with the DFU partition in a separate flash, e.g. an
+    /// external flash, from the address symbols defined in the linkerfile.
+    ///
+    /// The DFU symbols are offsets in `dfu_flash`.
+    pub fn from_linkerfile_blocking_with_dfu(
+        dfu_flash: &'a embassy_sync::blocking_mutex::Mutex<NoopRawMutex, core::cell::RefCell<DFU>>,
+        state_flash: &'a embassy_sync::blocking_mutex::Mutex<NoopRawMutex, core::cell::RefCell<STATE>>,
     ) -> Self {
         extern "C" {
             static __bootloader_state_start: u32;
@@ -35,14 +56,14 @@ impl<'a, FLASH: NorFlash>
             let end = &__bootloader_dfu_end as *const u32 as u32;
             trace!("DFU: 0x{:x} - 0x{:x}", start, end);
 
-            BlockingPartition::new(flash, start, end - start)
+            BlockingPartition::new(dfu_flash, start, end - start)
         };
         let state = unsafe {
             let start = &__bootloader_state_start as *const u32 as u32;
             let end = &__bootloader_state_end as *const u32 as u32;
             trace!("STATE: 0x{:x} - 0x{:x}", start, end);
 
-            BlockingPartition::new(flash, start, end - start)
+            BlockingPartition::new(state_flash, start, end - start)
         };
 
         Self { dfu, state }
@@ -55,13 +76,17 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
         Self {
             dfu: config.dfu,
             state: config.state,
+            written: 0,
         }
     }
 
     // Make sure we are running a booted firmware to avoid reverting to a bad state.
+    //
+    // The DFU detach state is also accepted, as that is the state in which a bootloader
+    // running in DFU mode writes the new firmware.
     fn verify_booted(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
         assert_eq!(aligned.len(), STATE::WRITE_SIZE);
-        if self.get_state(aligned)? == State::Boot {
+        if matches!(self.get_state(aligned)?, State::Boot | State::DfuDetach) {
             Ok(())
         } else {
             Err(FirmwareUpdaterError::BadState)
@@ -76,13 +101,26 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
     pub fn get_state(&mut self, aligned: &mut [u8]) -> Result<State, FirmwareUpdaterError> {
         self.state.read(0, aligned)?;
 
-        if !aligned.iter().any(|&b| b != SWAP_MAGIC) {
+        if !aligned.iter().any(|&b| b != SWAP_MAGIC) || !aligned.iter().any(|&b| b != SWAP_COMPRESSED_MAGIC) {
             Ok(State::Swap)
+        } else if !aligned.iter().any(|&b| b != DFU_DETACH_MAGIC) {
+            Ok(State::DfuDetach)
         } else {
             Ok(State::Boot)
         }
     }
 
+    /// Obtain the status of the update: the bootloader state, and the number of bytes written
+    /// with [`Self::write_firmware`] or [`Self::write_firmware_delta`].
+    ///
+    /// This is useful to report progress, or the status of an update to a server.
+    pub fn status(&mut self, aligned: &mut [u8]) -> Result<UpdateStatus, FirmwareUpdaterError> {
+        Ok(UpdateStatus {
+            state: self.get_state(aligned)?,
+            written: self.written,
+        })
+    }
+
     /// Verify the DFU given a public key. If there is an error then DO NOT
     /// proceed with updating the firmware as it must be signed with a
     /// corresponding private key (otherwise it could be malicious firmware).
@@ -92,6 +130,10 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
     /// If the "ed25519-salty" feature is set (or another similar feature) then the signature is expected to have
     /// been generated from a SHA-512 digest of the firmware bytes.
     ///
+    /// If the "ecdsa-p256" feature is set then the signature is expected to be an ECDSA P-256 signature of a
+    /// SHA-256 digest of the firmware bytes, encoded as the 64 bytes `r || s`. The public key is a SEC1 encoded
+    /// point, compressed or uncompressed.
+    ///
     /// If no signature feature is set then this method will always return a
     /// signature error.
     ///
@@ -157,6 +199,22 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
             );
             r.map_err(into_signature_error)?
         }
+        #[cfg(feature = "ecdsa-p256")]
+        {
+            use p256::ecdsa::signature::hazmat::PrehashVerifier;
+            use p256::ecdsa::{Signature, VerifyingKey};
+            use sha2::Sha256;
+
+            let public_key = VerifyingKey::from_sec1_bytes(_public_key).map_err(FirmwareUpdaterError::Signature)?;
+            let signature = Signature::try_from(_signature).map_err(FirmwareUpdaterError::Signature)?;
+
+            let mut message = [0; 32];
+            self.hash::<Sha256>(_update_len, _aligned, &mut message)?;
+
+            public_key
+                .verify_prehash(&message, &signature)
+                .map_err(FirmwareUpdaterError::Signature)?
+        }
 
         self.set_magic(_aligned, SWAP_MAGIC)
     }
@@ -189,6 +247,64 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
         self.set_magic(aligned, SWAP_MAGIC)
     }
 
+    /// Mark to trigger decompressing the DFU partition on next boot.
+    ///
+    /// The DFU partition must hold a compressed image in the format described by
+    /// [`BootLoader::prepare_boot`](crate::BootLoader::prepare_boot). Unlike swapped updates,
+    /// compressed updates can not be reverted.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    #[cfg(not(feature = "_verify"))]
+    pub fn mark_updated_compressed(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.set_magic(aligned, SWAP_COMPRESSED_MAGIC)
+    }
+
+    /// Write the nonce the update was encrypted with, for bootloaders decrypting updates.
+    ///
+    /// The nonce is stored in the last erase block of the DFU partition. It must be written after
+    /// [`Self::prepare_update`], which erases it.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being
+    /// written to. The `buf` buffer must be at least `NONCE_LEN` long, and follow the alignment and size rules of
+    /// the DFU flash.
+    #[cfg(feature = "encryption-chacha20")]
+    pub fn write_nonce(
+        &mut self,
+        aligned: &mut [u8],
+        buf: &mut [u8],
+        nonce: &[u8; crate::NONCE_LEN],
+    ) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.verify_booted(aligned)?;
+
+        let offset = (self.dfu.capacity() - DFU::ERASE_SIZE) as u32;
+        self.dfu.erase(offset, offset + DFU::ERASE_SIZE as u32)?;
+
+        buf.fill(STATE_ERASE_VALUE);
+        buf[..nonce.len()].copy_from_slice(nonce);
+        self.dfu.write(offset, buf)?;
+        Ok(())
+    }
+
+    /// Mark to trigger USB DFU mode on next boot.
+    ///
+    /// The bootloader will report [`State::DfuDetach`] from `prepare_boot`, and is expected to run
+    /// a DFU class to receive the new firmware instead of booting the application.
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    pub fn mark_dfu(&mut self, aligned: &mut [u8]) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        self.verify_booted(aligned)?;
+        self.set_magic(aligned, DFU_DETACH_MAGIC)
+    }
+
     /// Mark firmware boot successful and stop rollback on reset.
     ///
     /// # Safety
@@ -214,9 +330,25 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
                 self.state.write(STATE::WRITE_SIZE as u32, aligned)?;
             }
 
+            // Keep the anti-rollback counter of the bootloader
+            let offset = counter_offset(self.state.capacity(), STATE::WRITE_SIZE);
+            let mut counter = [0; COUNTER_LEN];
+            for (i, chunk) in counter.chunks_mut(STATE::WRITE_SIZE).enumerate() {
+                self.state.read((offset + i * STATE::WRITE_SIZE) as u32, aligned)?;
+                chunk.copy_from_slice(&aligned[..chunk.len()]);
+            }
+
             // Clear magic and progress
             self.state.erase(0, self.state.capacity() as u32)?;
 
+            if counter.iter().any(|&b| b != STATE_ERASE_VALUE) {
+                for (i, chunk) in counter.chunks(STATE::WRITE_SIZE).enumerate() {
+                    aligned.fill(STATE_ERASE_VALUE);
+                    aligned[..chunk.len()].copy_from_slice(chunk);
+                    self.state.write((offset + i * STATE::WRITE_SIZE) as u32, aligned)?;
+                }
+            }
+
             // Set magic
             aligned.fill(magic);
             self.state.write(0, aligned)?;
@@ -244,16 +376,91 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
         self.dfu.erase(offset as u32, (offset + data.len()) as u32)?;
 
         self.dfu.write(offset as u32, data)?;
+        self.written = self.written.max((offset + data.len()) as u32);
 
         Ok(())
     }
 
+    /// Apply a chunk of a delta patch, writing the new firmware to the DFU partition.
+    ///
+    /// The patch is applied against the `active` partition, which must be readable without alignment
+    /// requirements. Chunks can be of any size, and must be passed in order. Once the whole patch has been
+    /// applied, call [`Self::finish_firmware_delta`].
+    ///
+    /// # Safety
+    ///
+    /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
+    pub fn write_firmware_delta<ACTIVE: ReadNorFlash>(
+        &mut self,
+        aligned: &mut [u8],
+        patch: &mut DeltaPatch<'_>,
+        active: &mut ACTIVE,
+        mut data: &[u8],
+    ) -> Result<(), FirmwareUpdaterError> {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        assert_eq!(ACTIVE::READ_SIZE, 1);
+        assert_eq!(patch.page_len() % DFU::ERASE_SIZE, 0);
+
+        self.verify_booted(aligned)?;
+
+        while let Some(action) = patch.next(&mut data)? {
+            match action {
+                Action::Copy { src, len } => {
+                    if src as usize + len > active.capacity() {
+                        return Err(FirmwareUpdaterError::BadPatch);
+                    }
+                    active.read(src, patch.push(len))?;
+                }
+                Action::Add { src, diff } => {
+                    if src as usize + diff.len() > active.capacity() {
+                        return Err(FirmwareUpdaterError::BadPatch);
+                    }
+                    let buf = patch.push(diff.len());
+                    active.read(src, buf)?;
+                    for (b, d) in buf.iter_mut().zip(diff) {
+                        *b = b.wrapping_add(*d);
+                    }
+                }
+                Action::Insert(bytes) => patch.push(bytes.len()).copy_from_slice(bytes),
+            }
+            if patch.is_full() {
+                self.write_delta_page(patch)?;
+            }
+        }
+        self.written = patch.update_len();
+        Ok(())
+    }
+
+    /// Finish applying a delta patch, returning the length of the new firmware.
+    ///
+    /// The new firmware can then be verified and marked as updated like a firmware written with
+    /// [`Self::write_firmware`].
+    pub fn finish_firmware_delta(&mut self, patch: &mut DeltaPatch<'_>) -> Result<u32, FirmwareUpdaterError> {
+        if !patch.is_complete() {
+            return Err(FirmwareUpdaterError::BadPatch);
+        }
+        let update_len = patch.update_len();
+        if !patch.is_empty() {
+            self.write_delta_page(patch)?;
+        }
+        Ok(update_len)
+    }
+
+    fn write_delta_page(&mut self, patch: &mut DeltaPatch<'_>) -> Result<(), FirmwareUpdaterError> {
+        let (offset, page) = patch.take_page(self.dfu.capacity())?;
+        self.dfu.erase(offset, offset + page.len() as u32)?;
+        self.dfu.write(offset, page)?;
+        Ok(())
+    }
+
     /// Prepare for an incoming DFU update by erasing the entire DFU area and
     /// returning its `Partition`.
     ///
     /// Using this instead of `write_firmware` allows for an optimized API in
     /// exchange for added complexity.
     ///
+    /// Data written directly to the returned partition is not counted by [`Self::status`].
+    ///
     /// # Safety
     ///
     /// The `aligned` buffer must have a size of STATE::WRITE_SIZE, and follow the alignment rules for the flash being written to.
@@ -261,6 +468,7 @@ impl<DFU: NorFlash, STATE: NorFlash> BlockingFirmwareUpdater<DFU, STATE> {
         assert_eq!(aligned.len(), STATE::WRITE_SIZE);
         self.verify_booted(aligned)?;
         self.dfu.erase(0, self.dfu.capacity() as u32)?;
+        self.written = 0;
 
         Ok(&mut self.dfu)
     }
@@ -299,4 +507,59 @@ mod tests {
 
         assert_eq!(Sha1::digest(update).as_slice(), hash);
     }
+
+    #[test]
+    fn can_apply_delta() {
+        let flash = Mutex::<NoopRawMutex, _>::new(RefCell::new(MemFlash::<131072, 4096, 8>::default()));
+        let state = BlockingPartition::new(&flash, 0, 4096);
+        let dfu = BlockingPartition::new(&flash, 65536, 65536);
+
+        let mut active = MemFlash::<8192, 4096, 8>::default();
+        for (i, b) in active.mem.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let mut patch_data = [0; 64];
+        let mut len = 0;
+        // Copy the second page of the active firmware.
+        for b in [0u8].iter().chain(&4096u32.to_le_bytes()).chain(&4096u32.to_le_bytes()) {
+            patch_data[len] = *b;
+            len += 1;
+        }
+        // Add 1 to the 3 first bytes.
+        for b in [1u8]
+            .iter()
+            .chain(&3u32.to_le_bytes())
+            .chain(&0u32.to_le_bytes())
+            .chain(&[1, 1, 1])
+        {
+            patch_data[len] = *b;
+            len += 1;
+        }
+        // Insert 2 bytes.
+        for b in [2u8].iter().chain(&2u32.to_le_bytes()).chain(&[0xAA, 0xBB]) {
+            patch_data[len] = *b;
+            len += 1;
+        }
+
+        let mut updater = BlockingFirmwareUpdater::new(FirmwareUpdaterConfig { dfu, state });
+        let mut aligned = [0; 8];
+        let mut page = [0; 4096];
+        let mut patch = DeltaPatch::new(&mut page);
+        for chunk in patch_data[..len].chunks(3) {
+            updater
+                .write_firmware_delta(&mut aligned, &mut patch, &mut active, chunk)
+                .unwrap();
+        }
+        assert_eq!(4101, updater.finish_firmware_delta(&mut patch).unwrap());
+
+        let mut expected = [0; 4101];
+        expected[..4096].copy_from_slice(&active.mem[4096..]);
+        expected[4096..4099].copy_from_slice(&[1, 2, 3]);
+        expected[4099..].copy_from_slice(&[0xAA, 0xBB]);
+
+        let mut written = [0; 4101];
+        flash.lock(|f| written.copy_from_slice(&f.borrow().mem[65536..65536 + 4101]));
+        assert_eq!(expected, written);
+    }
 }
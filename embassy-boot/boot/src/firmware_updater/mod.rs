@@ -7,6 +7,8 @@ pub use asynch::FirmwareUpdater;
 pub use blocking::BlockingFirmwareUpdater;
 use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind};
 
+use crate::State;
+
 /// Firmware updater flash configuration holding the two flashes used by the updater
 ///
 /// If only a single flash is actually used, then that flash should be partitioned into two partitions before use.
@@ -19,6 +21,16 @@ pub struct FirmwareUpdaterConfig<DFU, STATE> {
     pub state: STATE,
 }
 
+/// Status of a firmware update, as seen by the application.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UpdateStatus {
+    /// The bootloader state.
+    pub state: State,
+    /// Number of bytes of the update written to the DFU partition by this updater.
+    pub written: u32,
+}
+
 /// Errors returned by FirmwareUpdater
 #[derive(Debug)]
 pub enum FirmwareUpdaterError {
@@ -28,6 +40,8 @@ pub enum FirmwareUpdaterError {
     Signature(signature::Error),
     /// Bad state.
     BadState,
+    /// Malformed delta patch, or patch not matching the active firmware.
+    BadPatch,
 }
 
 #[cfg(feature = "defmt")]
@@ -37,6 +51,7 @@ impl defmt::Format for FirmwareUpdaterError {
             FirmwareUpdaterError::Flash(_) => defmt::write!(fmt, "FirmwareUpdaterError::Flash(_)"),
             FirmwareUpdaterError::Signature(_) => defmt::write!(fmt, "FirmwareUpdaterError::Signature(_)"),
             FirmwareUpdaterError::BadState => defmt::write!(fmt, "FirmwareUpdaterError::BadState"),
+            FirmwareUpdaterError::BadPatch => defmt::write!(fmt, "FirmwareUpdaterError::BadPatch"),
         }
     }
 }
@@ -5,8 +5,11 @@
 mod fmt;
 
 mod boot_loader;
+mod delta;
 mod digest_adapters;
+mod dual_bank;
 mod firmware_updater;
+mod lz4;
 #[cfg(test)]
 mod mem_flash;
 #[cfg(test)]
@@ -15,22 +18,39 @@ mod test_flash;
 // The expected value of the flash after an erase
 // TODO: Use the value provided by NorFlash when available
 pub(crate) const STATE_ERASE_VALUE: u8 = 0xFF;
-pub use boot_loader::{BootError, BootLoader, BootLoaderConfig};
+#[cfg(feature = "encryption-chacha20")]
+pub use boot_loader::NONCE_LEN;
+pub use boot_loader::{BootError, BootLoader, BootLoaderConfig, BootOperation, BootProgress, SecondaryImage};
+pub use delta::DeltaPatch;
+pub use dual_bank::{DualBankBootLoader, FlashBanks};
 #[cfg(feature = "nightly")]
 pub use firmware_updater::FirmwareUpdater;
-pub use firmware_updater::{BlockingFirmwareUpdater, FirmwareUpdaterConfig, FirmwareUpdaterError};
+pub use firmware_updater::{BlockingFirmwareUpdater, FirmwareUpdaterConfig, FirmwareUpdaterError, UpdateStatus};
 
 pub(crate) const BOOT_MAGIC: u8 = 0xD0;
 pub(crate) const SWAP_MAGIC: u8 = 0xF0;
+pub(crate) const DFU_DETACH_MAGIC: u8 = 0xE0;
+pub(crate) const SWAP_COMPRESSED_MAGIC: u8 = 0xC0;
+
+/// Length of the anti-rollback counter, a little endian `u32` stored at the end of the state
+/// partition.
+pub(crate) const COUNTER_LEN: usize = 4;
+
+/// Offset of the anti-rollback counter in the state partition, padded to the write size.
+pub(crate) const fn counter_offset(capacity: usize, write_size: usize) -> usize {
+    capacity - (COUNTER_LEN + write_size - 1) / write_size * write_size
+}
 
 /// The state of the bootloader after running prepare.
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum State {
     /// Bootloader is ready to boot the active partition.
     Boot,
     /// Bootloader has swapped the active partition with the dfu partition and will attempt boot.
     Swap,
+    /// Application has received a request to reboot into DFU mode to apply an update.
+    DfuDetach,
 }
 
 /// Buffer aligned to 32 byte boundary, largest known alignment requirement for embassy-boot.
@@ -101,6 +121,37 @@ mod tests {
         assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
     }
 
+    #[test]
+    fn test_dfu_detach_state() {
+        let flash = BlockingTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<57344, 4096, 4>::default(),
+            dfu: MemFlash::<61440, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        flash.state().write(0, &[BOOT_MAGIC; 4]).unwrap();
+
+        let mut aligned = [0; 4];
+        let mut updater = BlockingFirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        updater.mark_dfu(&mut aligned).unwrap();
+        assert_eq!(State::DfuDetach, updater.get_state(&mut aligned).unwrap());
+
+        // Writing firmware is allowed while in DFU mode.
+        updater.write_firmware(&mut aligned, 0, &[0xAA; 4096]).unwrap();
+
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+
+        let mut page = [0; 4096];
+        assert_eq!(State::DfuDetach, bootloader.prepare_boot(&mut page).unwrap());
+    }
+
     #[test]
     #[cfg(all(feature = "nightly", not(feature = "_verify")))]
     fn test_swap_state() {
@@ -173,6 +224,297 @@ mod tests {
         assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
     }
 
+    #[test]
+    #[cfg(all(feature = "nightly", not(feature = "_verify")))]
+    fn test_swap_progress() {
+        const FIRMWARE_SIZE: usize = 57344;
+        let flash = AsyncTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<61440, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        const UPDATE: [u8; FIRMWARE_SIZE] = [0xAA; FIRMWARE_SIZE];
+        let mut aligned = [0; 4];
+
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        block_on(updater.write_firmware(&mut aligned, 0, &UPDATE[..4096])).unwrap();
+        block_on(updater.write_firmware(&mut aligned, 4096, &UPDATE[4096..])).unwrap();
+        assert_eq!(
+            UpdateStatus {
+                state: State::Boot,
+                written: FIRMWARE_SIZE as u32
+            },
+            block_on(updater.status(&mut aligned)).unwrap()
+        );
+        block_on(updater.mark_updated(&mut aligned)).unwrap();
+        assert_eq!(State::Swap, block_on(updater.status(&mut aligned)).unwrap().state);
+
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+
+        let mut page = [0; 1024];
+        let mut reports = 0;
+        let state = bootloader
+            .prepare_boot_with_progress(&mut page, |p| {
+                reports += 1;
+                assert_eq!(BootOperation::Swap, p.operation);
+                assert_eq!(reports, p.done);
+                assert_eq!(14, p.total);
+            })
+            .unwrap();
+        assert_eq!(State::Swap, state);
+        assert_eq!(14, reports);
+    }
+
+    #[test]
+    #[cfg(all(feature = "nightly", not(feature = "_verify")))]
+    fn test_trial_boot() {
+        const FIRMWARE_SIZE: usize = 8192;
+        let flash = AsyncTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<12288, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        const UPDATE: [u8; FIRMWARE_SIZE] = [0xAA; FIRMWARE_SIZE];
+        let mut aligned = [0; 4];
+
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        block_on(updater.write_firmware(&mut aligned, 0, &UPDATE)).unwrap();
+        block_on(updater.mark_updated(&mut aligned)).unwrap();
+
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+
+        let mut page = [0; 1024];
+        assert!(!bootloader.is_trial_boot(&mut page).unwrap());
+
+        // The update is swapped in, and booted on trial
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut page).unwrap());
+        assert!(bootloader.is_trial_boot(&mut page).unwrap());
+
+        // It was not marked booted: the previous firmware is reverted to, and booted for good
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut page).unwrap());
+        assert!(!bootloader.is_trial_boot(&mut page).unwrap());
+
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
+        assert!(!bootloader.is_trial_boot(&mut page).unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "nightly", not(feature = "_verify")))]
+    fn test_multi_image() {
+        const FIRMWARE_SIZE: usize = 8192;
+        let primary = AsyncTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<12288, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+        let secondary = AsyncTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<12288, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        const ORIGINAL: [u8; FIRMWARE_SIZE] = [0x55; FIRMWARE_SIZE];
+        const UPDATE: [u8; FIRMWARE_SIZE] = [0xAA; FIRMWARE_SIZE];
+        let mut aligned = [0; 4];
+        let mut page = [0; 1024];
+        let mut read_buf = [0; FIRMWARE_SIZE];
+
+        for flash in [&primary, &secondary] {
+            block_on(flash.active().erase(0, FIRMWARE_SIZE as u32)).unwrap();
+            block_on(flash.active().write(0, &ORIGINAL)).unwrap();
+        }
+
+        // The secondary image is discarded when the update is not committed by the primary image
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: secondary.dfu(),
+            state: secondary.state(),
+        });
+        block_on(updater.write_firmware(&mut aligned, 0, &UPDATE)).unwrap();
+        block_on(updater.mark_updated(&mut aligned)).unwrap();
+
+        let (primary, secondary) = (primary.into_blocking(), secondary.into_blocking());
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: primary.active(),
+            dfu: primary.dfu(),
+            state: primary.state(),
+        });
+        let mut secondary_bootloader = BootLoader::new(BootLoaderConfig {
+            active: secondary.active(),
+            dfu: secondary.dfu(),
+            state: secondary.state(),
+        });
+        assert_eq!(
+            State::Boot,
+            bootloader
+                .prepare_boot_multi(&mut page, &mut [&mut secondary_bootloader])
+                .unwrap()
+        );
+        secondary.active().read(0, &mut read_buf).unwrap();
+        assert_eq!(ORIGINAL, read_buf);
+
+        // Both images are swapped once committed
+        let (primary, secondary) = (primary.into_async(), secondary.into_async());
+        for flash in [&secondary, &primary] {
+            let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+                dfu: flash.dfu(),
+                state: flash.state(),
+            });
+            block_on(updater.write_firmware(&mut aligned, 0, &UPDATE)).unwrap();
+            block_on(updater.mark_updated(&mut aligned)).unwrap();
+        }
+
+        let (primary, secondary) = (primary.into_blocking(), secondary.into_blocking());
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: primary.active(),
+            dfu: primary.dfu(),
+            state: primary.state(),
+        });
+        let mut secondary_bootloader = BootLoader::new(BootLoaderConfig {
+            active: secondary.active(),
+            dfu: secondary.dfu(),
+            state: secondary.state(),
+        });
+        assert_eq!(
+            State::Swap,
+            bootloader
+                .prepare_boot_multi(&mut page, &mut [&mut secondary_bootloader])
+                .unwrap()
+        );
+        for flash in [&primary, &secondary] {
+            flash.active().read(0, &mut read_buf).unwrap();
+            assert_eq!(UPDATE, read_buf);
+        }
+
+        // Both images are reverted when the primary image is not marked booted
+        assert_eq!(
+            State::Swap,
+            bootloader
+                .prepare_boot_multi(&mut page, &mut [&mut secondary_bootloader])
+                .unwrap()
+        );
+        for flash in [&primary, &secondary] {
+            flash.active().read(0, &mut read_buf).unwrap();
+            assert_eq!(ORIGINAL, read_buf);
+        }
+        assert_eq!(
+            State::Boot,
+            bootloader
+                .prepare_boot_multi(&mut page, &mut [&mut secondary_bootloader])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "nightly", not(feature = "_verify")))]
+    fn test_anti_rollback() {
+        const FIRMWARE_SIZE: usize = 57344;
+        const VERSION_OFFSET: usize = 0x100;
+        let flash = AsyncTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<61440, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        let firmware = |version: u32| {
+            let mut firmware = [version as u8; FIRMWARE_SIZE];
+            firmware[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&version.to_le_bytes());
+            firmware
+        };
+        let mut aligned = [0; 4];
+        let mut page = [0; 1024];
+        let mut read_buf = [0; FIRMWARE_SIZE];
+
+        block_on(flash.active().erase(0, FIRMWARE_SIZE as u32)).unwrap();
+        block_on(flash.active().write(0, &firmware(2))).unwrap();
+
+        // Boot the active firmware, storing its version
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        bootloader.enable_anti_rollback(VERSION_OFFSET as u32);
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
+
+        // An older update is discarded
+        let flash = flash.into_async();
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        block_on(updater.write_firmware(&mut aligned, 0, &firmware(1))).unwrap();
+        block_on(updater.mark_updated(&mut aligned)).unwrap();
+
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        bootloader.enable_anti_rollback(VERSION_OFFSET as u32);
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
+        flash.active().read(0, &mut read_buf).unwrap();
+        assert_eq!(firmware(2), read_buf);
+
+        // A newer update is swapped, and becomes the minimum version once booted
+        let flash = flash.into_async();
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        block_on(updater.write_firmware(&mut aligned, 0, &firmware(3))).unwrap();
+        block_on(updater.mark_updated(&mut aligned)).unwrap();
+
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        bootloader.enable_anti_rollback(VERSION_OFFSET as u32);
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut page).unwrap());
+        flash.active().read(0, &mut read_buf).unwrap();
+        assert_eq!(firmware(3), read_buf);
+
+        let flash = flash.into_async();
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        block_on(updater.mark_booted(&mut aligned)).unwrap();
+
+        let flash = flash.into_blocking();
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        bootloader.enable_anti_rollback(VERSION_OFFSET as u32);
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
+        let mut counter = [0; 4];
+        flash.state().read(4096 - 4, &mut counter).unwrap();
+        assert_eq!(3, u32::from_le_bytes(counter));
+    }
+
     #[test]
     #[cfg(all(feature = "nightly", not(feature = "_verify")))]
     fn test_swap_state_active_page_biggest() {
@@ -257,7 +599,111 @@ mod tests {
     }
 
     #[test]
-    #[cfg(all(feature = "nightly", feature = "_verify"))]
+    #[cfg(not(feature = "_verify"))]
+    fn test_compressed_update() {
+        // The DFU partition is smaller than the image.
+        let flash = BlockingTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<8192, 4096, 4>::default(),
+            dfu: MemFlash::<4096, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        // "abc" repeated, encoded as 3 literals followed by a match of 8189 bytes.
+        let mut update = [0; 4096];
+        update[..4].copy_from_slice(&8192u32.to_le_bytes());
+        update[4..8].copy_from_slice(&39u32.to_le_bytes());
+        update[8..14].copy_from_slice(&[0x3F, b'a', b'b', b'c', 0x03, 0x00]);
+        update[14..46].fill(0xFF);
+        update[46] = 10;
+
+        let mut aligned = [0; 4];
+        let mut updater = BlockingFirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        updater.write_firmware(&mut aligned, 0, &update).unwrap();
+        updater.mark_updated_compressed(&mut aligned).unwrap();
+
+        let mut bootloader = BootLoader::new(BootLoaderConfig {
+            active: flash.active(),
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        let mut page = [0; 4096];
+        assert_eq!(State::Boot, bootloader.prepare_boot(&mut page).unwrap());
+
+        let mut read_buf = [0; 8192];
+        flash.active().read(0, &mut read_buf).unwrap();
+        for (i, b) in read_buf.iter().enumerate() {
+            assert_eq!(b"abc"[i % 3], *b);
+        }
+        assert_eq!(State::Boot, updater.get_state(&mut aligned).unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "encryption-chacha20", not(feature = "_verify")))]
+    fn test_encrypted_swap() {
+        use chacha20::cipher::{KeyIvInit, StreamCipher};
+        use chacha20::ChaCha20;
+
+        const FIRMWARE_SIZE: usize = 8192;
+        // The last DFU page holds the nonce.
+        let flash = BlockingTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<FIRMWARE_SIZE, 4096, 4>::default(),
+            dfu: MemFlash::<16384, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        const ORIGINAL: [u8; FIRMWARE_SIZE] = [0x55; FIRMWARE_SIZE];
+        const UPDATE: [u8; FIRMWARE_SIZE] = [0xAA; FIRMWARE_SIZE];
+        let key = [0x42; 32];
+        let nonce = [0x01; NONCE_LEN];
+
+        let mut encrypted = UPDATE;
+        ChaCha20::new(&key.into(), &nonce.into()).apply_keystream(&mut encrypted);
+
+        flash.active().erase(0, ORIGINAL.len() as u32).unwrap();
+        flash.active().write(0, &ORIGINAL).unwrap();
+
+        let mut aligned = [0; 4];
+        let mut updater = BlockingFirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+        updater.write_firmware(&mut aligned, 0, &encrypted).unwrap();
+        updater.write_nonce(&mut aligned, &mut [0; 16], &nonce).unwrap();
+        updater.mark_updated(&mut aligned).unwrap();
+
+        let mut bootloader = BootLoader::new_encrypted(
+            BootLoaderConfig {
+                active: flash.active(),
+                dfu: flash.dfu(),
+                state: flash.state(),
+            },
+            &key,
+        );
+
+        let mut page = [0; 1024];
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut page).unwrap());
+
+        let mut read_buf = [0; FIRMWARE_SIZE];
+        flash.active().read(0, &mut read_buf).unwrap();
+        assert_eq!(UPDATE, read_buf);
+        // The previous firmware is not stored in plaintext
+        flash.dfu().read(4096, &mut read_buf).unwrap();
+        assert_ne!(ORIGINAL, read_buf);
+
+        // Running again should cause a revert
+        assert_eq!(State::Swap, bootloader.prepare_boot(&mut page).unwrap());
+
+        flash.active().read(0, &mut read_buf).unwrap();
+        assert_eq!(ORIGINAL, read_buf);
+        flash.dfu().read(0, &mut read_buf).unwrap();
+        assert_eq!(encrypted, read_buf);
+    }
+
+    #[test]
+    #[cfg(all(feature = "nightly", any(feature = "ed25519-dalek", feature = "ed25519-salty")))]
     fn test_verify() {
         // The following key setup is based on:
         // https://docs.rs/ed25519-dalek/latest/ed25519_dalek/#example
@@ -308,4 +754,49 @@ mod tests {
         ))
         .is_ok());
     }
+
+    #[test]
+    #[cfg(all(feature = "nightly", feature = "ecdsa-p256"))]
+    fn test_verify_p256() {
+        use p256::ecdsa::signature::hazmat::PrehashSigner;
+        use p256::ecdsa::{Signature, SigningKey};
+        use sha2::{Digest, Sha256};
+
+        let signing_key = SigningKey::from_bytes(&[0x42; 32]).unwrap();
+
+        let firmware: &[u8] = b"This are bytes that would otherwise be firmware bytes for DFU.";
+        let message = Sha256::digest(firmware);
+        let signature: Signature = signing_key.sign_prehash(&message).unwrap();
+        let public_key = signing_key.verifying_key().to_encoded_point(false);
+
+        // Setup flash
+        let flash = BlockingTestFlash::new(BootLoaderConfig {
+            active: MemFlash::<0, 0, 0>::default(),
+            dfu: MemFlash::<4096, 4096, 4>::default(),
+            state: MemFlash::<4096, 4096, 4>::default(),
+        });
+
+        let firmware_len = firmware.len();
+
+        let mut write_buf = [0; 4096];
+        write_buf[0..firmware_len].copy_from_slice(firmware);
+        flash.dfu().write(0, &write_buf).unwrap();
+
+        // On with the test
+        let flash = flash.into_async();
+        let mut updater = FirmwareUpdater::new(FirmwareUpdaterConfig {
+            dfu: flash.dfu(),
+            state: flash.state(),
+        });
+
+        let mut aligned = [0; 4];
+
+        assert!(block_on(updater.verify_and_mark_updated(
+            public_key.as_bytes(),
+            signature.as_ref(),
+            firmware_len as u32,
+            &mut aligned,
+        ))
+        .is_ok());
+    }
 }
@@ -0,0 +1,123 @@
+//! Decoder for the LZ4 block format, used by compressed updates.
+//!
+//! Matches are copied from the already decompressed output, so the decoder itself only needs a
+//! few bytes of RAM: the output is read back from flash when needed.
+
+use crate::BootError;
+
+/// Compressed input.
+pub(crate) trait Source {
+    /// Read the next byte, or `None` at the end of the input.
+    fn next(&mut self) -> Result<Option<u8>, BootError>;
+}
+
+/// Decompressed output.
+pub(crate) trait Sink {
+    /// Number of bytes pushed so far.
+    fn pos(&self) -> u32;
+    /// Append a byte to the output.
+    fn push(&mut self, byte: u8) -> Result<(), BootError>;
+    /// Read back a byte already pushed.
+    fn get(&mut self, pos: u32) -> Result<u8, BootError>;
+}
+
+/// Decompress a LZ4 block from `src` into `sink`.
+pub(crate) fn decompress(src: &mut impl Source, sink: &mut impl Sink) -> Result<(), BootError> {
+    loop {
+        let token = match src.next()? {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+
+        let literals = read_len(src, token >> 4)?;
+        for _ in 0..literals {
+            let byte = src.next()?.ok_or(BootError::BadImage)?;
+            sink.push(byte)?;
+        }
+
+        // The last sequence only has literals.
+        let lo = match src.next()? {
+            Some(lo) => lo,
+            None => return Ok(()),
+        };
+        let hi = src.next()?.ok_or(BootError::BadImage)?;
+        let offset = u16::from_le_bytes([lo, hi]) as u32;
+        if offset == 0 || offset > sink.pos() {
+            return Err(BootError::BadImage);
+        }
+
+        let len = read_len(src, token & 0xF)? + 4;
+        for _ in 0..len {
+            let byte = sink.get(sink.pos() - offset)?;
+            sink.push(byte)?;
+        }
+    }
+}
+
+fn read_len(src: &mut impl Source, nibble: u8) -> Result<u32, BootError> {
+    let mut len = nibble as u32;
+    if nibble == 0xF {
+        loop {
+            let byte = src.next()?.ok_or(BootError::BadImage)?;
+            len = len.saturating_add(byte as u32);
+            if byte != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// A sink only checking the output length, to validate an image before overwriting anything.
+///
+/// The firmware version is also extracted when at a known offset.
+pub(crate) struct Validate {
+    pos: u32,
+    len: u32,
+    version_offset: Option<u32>,
+    version: [u8; 4],
+}
+
+impl Validate {
+    pub(crate) fn new(len: u32, version_offset: Option<u32>) -> Self {
+        Self {
+            pos: 0,
+            len,
+            version_offset,
+            version: [0; 4],
+        }
+    }
+
+    /// Whether the output has the expected length.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.pos == self.len
+    }
+
+    /// The little endian version at the version offset, if any.
+    pub(crate) fn version(&self) -> Option<u32> {
+        self.version_offset.map(|_| u32::from_le_bytes(self.version))
+    }
+}
+
+impl Sink for Validate {
+    fn pos(&self) -> u32 {
+        self.pos
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), BootError> {
+        if self.pos == self.len {
+            return Err(BootError::BadImage);
+        }
+        if let Some(offset) = self.version_offset {
+            if (offset..offset + 4).contains(&self.pos) {
+                self.version[(self.pos - offset) as usize] = byte;
+            }
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn get(&mut self, _pos: u32) -> Result<u8, BootError> {
+        Ok(0)
+    }
+}
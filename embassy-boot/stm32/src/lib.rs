@@ -5,7 +5,12 @@ mod fmt;
 
 #[cfg(feature = "nightly")]
 pub use embassy_boot::FirmwareUpdater;
-pub use embassy_boot::{AlignedBuffer, BlockingFirmwareUpdater, BootLoaderConfig, FirmwareUpdaterConfig, State};
+pub use embassy_boot::{
+    AlignedBuffer, BlockingFirmwareUpdater, BootLoaderConfig, BootOperation, BootProgress, DeltaPatch,
+    DualBankBootLoader, FirmwareUpdaterConfig, FlashBanks, SecondaryImage, State, UpdateStatus,
+};
+use embassy_stm32::wdg::{self, IndependentWatchdog};
+use embassy_stm32::Peripheral;
 use embedded_storage::nor_flash::NorFlash;
 
 /// A bootloader for STM32 devices.
@@ -25,12 +30,80 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash, const BUFFER_SIZE: usize>
         }
     }
 
+    /// Create a new bootloader instance decrypting updates with the given key.
+    ///
+    /// See [`embassy_boot::BootLoader::new_encrypted`].
+    #[cfg(feature = "encryption-chacha20")]
+    pub fn new_encrypted(config: BootLoaderConfig<ACTIVE, DFU, STATE>, key: &[u8; 32]) -> Self {
+        Self {
+            boot: embassy_boot::BootLoader::new_encrypted(config, key),
+            aligned_buf: AlignedBuffer([0; BUFFER_SIZE]),
+        }
+    }
+
+    /// Enable anti-rollback protection, refusing updates older than the active firmware.
+    ///
+    /// The firmware version is a little endian `u32` at `version_offset` in the firmware.
+    pub fn enable_anti_rollback(&mut self, version_offset: u32) {
+        self.boot.enable_anti_rollback(version_offset);
+    }
+
     /// Inspect the bootloader state and perform actions required before booting, such as swapping
     /// firmware.
-    pub fn prepare(&mut self) {
+    ///
+    /// Returns the state the bootloader was in. If it is [`State::DfuDetach`], the application
+    /// requested to be updated over USB DFU before being booted again.
+    pub fn prepare(&mut self) -> State {
         self.boot
             .prepare_boot(self.aligned_buf.as_mut())
+            .expect("Boot prepare error")
+    }
+
+    /// Inspect the bootloader state like [`Self::prepare`], updating secondary images such as
+    /// co-processor firmware along with the application: all updates are activated, or none.
+    ///
+    /// See [`embassy_boot::BootLoader::prepare_boot_multi`].
+    pub fn prepare_multi(&mut self, secondaries: &mut [&mut dyn SecondaryImage]) -> State {
+        self.boot
+            .prepare_boot_multi(self.aligned_buf.as_mut(), secondaries)
+            .expect("Boot prepare error")
+    }
+
+    /// Inspect the bootloader state like [`Self::prepare`], calling `progress` as pages are swapped,
+    /// e.g. to drive a LED or a display during a long update.
+    pub fn prepare_with_progress(&mut self, progress: impl FnMut(BootProgress)) -> State {
+        self.boot
+            .prepare_boot_with_progress(self.aligned_buf.as_mut(), progress)
+            .expect("Boot prepare error")
+    }
+
+    /// Inspect the bootloader state like [`Self::prepare`], and start the independent watchdog if
+    /// the application is booted on trial after an update.
+    ///
+    /// The application must call `mark_booted` before the watchdog times out. If it fails to,
+    /// e.g. because the new firmware hangs, the watchdog resets the device and the bootloader
+    /// reverts to the previous firmware.
+    ///
+    /// The application does not need to pet the watchdog. However, the watchdog can not be
+    /// stopped once started, so it still resets the device once after `mark_booted`. The
+    /// confirmed firmware is then booted without a watchdog.
+    pub fn prepare_with_trial_watchdog<T: wdg::Instance>(
+        &mut self,
+        iwdg: impl Peripheral<P = T>,
+        timeout_us: u32,
+    ) -> State {
+        let state = self.prepare();
+        // A revert also starts from the swap state, but boots the confirmed firmware.
+        let trial = self
+            .boot
+            .is_trial_boot(self.aligned_buf.as_mut())
             .expect("Boot prepare error");
+        if trial {
+            trace!("Trial boot, starting watchdog");
+            let mut wdt = IndependentWatchdog::new(iwdg, timeout_us);
+            wdt.unleash();
+        }
+        state
     }
 
     /// Boots the application.
@@ -51,3 +124,16 @@ impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash, const BUFFER_SIZE: usize>
         cortex_m::asm::bootload(start as *const u32)
     }
 }
+
+// The secondary image uses its own buffer, sized for its flash.
+impl<ACTIVE: NorFlash, DFU: NorFlash, STATE: NorFlash, const BUFFER_SIZE: usize> SecondaryImage
+    for BootLoader<ACTIVE, DFU, STATE, BUFFER_SIZE>
+{
+    fn follow(
+        &mut self,
+        operation: Option<BootOperation>,
+        _aligned_buf: &mut [u8],
+    ) -> Result<State, embassy_boot::BootError> {
+        self.boot.follow(operation, self.aligned_buf.as_mut())
+    }
+}
@@ -0,0 +1,472 @@
+#![no_std]
+#![feature(async_fn_in_trait)]
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+// must be first
+mod fmt;
+
+pub mod mcp2515;
+pub mod mcp2518fd;
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_can::{BusState, ErrorKind, Filter, Frame, Id, TooManyFilters};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::{Channel, DynamicReceiver, DynamicSender};
+use embassy_sync::waitqueue::WakerRegistration;
+use heapless::Vec;
+
+/// Maximum number of filters, the number of filters of the MCP2518FD.
+pub const MAX_FILTERS: usize = 32;
+
+/// Operating mode, selected when creating the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Normal operation on the bus.
+    Normal,
+    /// Transmitted frames are received back, and nothing is sent on the bus.
+    Loopback,
+    /// Frames are received from the bus, but nothing is sent, not even acknowledges.
+    ListenOnly,
+}
+
+/// Error.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI error.
+    Spi(E),
+    /// The controller didn't switch to the requested operating mode: it's missing, or its
+    /// oscillator isn't running.
+    ModeTimeout,
+    /// The bitrate can't be reached from the frequency of the oscillator.
+    Bitrate,
+}
+
+/// Frames and status shared by the driver handle and its runner.
+struct Shared {
+    fd: bool,
+    bus_state: BusState,
+    reported_bus_state: BusState,
+    overrun: bool,
+    rx_waker: WakerRegistration,
+    /// Frames given to the runner and not sent yet.
+    tx_pending: usize,
+    tx_waker: WakerRegistration,
+    /// Filters of the received frames, `None` to receive all of them.
+    filters: Option<Vec<Filter, MAX_FILTERS>>,
+    filters_changed: bool,
+    runner_waker: WakerRegistration,
+}
+
+impl Shared {
+    const fn new() -> Self {
+        Self {
+            fd: false,
+            bus_state: BusState::ErrorActive,
+            reported_bus_state: BusState::ErrorActive,
+            overrun: false,
+            rx_waker: WakerRegistration::new(),
+            tx_pending: 0,
+            tx_waker: WakerRegistration::new(),
+            filters: None,
+            filters_changed: false,
+            runner_waker: WakerRegistration::new(),
+        }
+    }
+}
+
+/// Internal state of the driver, with room for `N_RX` received frames.
+pub struct State<const N_RX: usize> {
+    rx: Channel<NoopRawMutex, Frame, N_RX>,
+    // A single frame waits for a transmit buffer of the controller, which sends them by priority.
+    tx: Channel<NoopRawMutex, Frame, 1>,
+    shared: Mutex<NoopRawMutex, RefCell<Shared>>,
+}
+
+impl<const N_RX: usize> State<N_RX> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            rx: Channel::new(),
+            tx: Channel::new(),
+            shared: Mutex::new(RefCell::new(Shared::new())),
+        }
+    }
+}
+
+fn new<'d, const N_RX: usize>(state: &'d mut State<N_RX>, fd: bool) -> (Can<'d>, Channels<'d>) {
+    state.shared.lock(|s| s.borrow_mut().fd = fd);
+    let shared = &state.shared;
+    let can = Can {
+        tx: CanTx {
+            tx: state.tx.sender().into(),
+            shared,
+        },
+        rx: CanRx {
+            rx: state.rx.receiver().into(),
+            shared,
+        },
+    };
+    let ch = Channels {
+        rx: state.rx.sender().into(),
+        tx: state.tx.receiver().into(),
+        shared,
+    };
+    (can, ch)
+}
+
+/// Driver handle, implementing the `embassy-can` traits.
+///
+/// The frames are exchanged with the controller by the runner, which must be running.
+pub struct Can<'d> {
+    tx: CanTx<'d>,
+    rx: CanRx<'d>,
+}
+
+impl<'d> Can<'d> {
+    /// Split the handle into its transmit and receive halves, to use them from different tasks.
+    pub fn split(self) -> (CanTx<'d>, CanRx<'d>) {
+        (self.tx, self.rx)
+    }
+}
+
+/// Transmit half of [`Can`].
+pub struct CanTx<'d> {
+    tx: DynamicSender<'d, Frame>,
+    shared: &'d Mutex<NoopRawMutex, RefCell<Shared>>,
+}
+
+/// Receive half of [`Can`].
+pub struct CanRx<'d> {
+    rx: DynamicReceiver<'d, Frame>,
+    shared: &'d Mutex<NoopRawMutex, RefCell<Shared>>,
+}
+
+fn set_filters(shared: &Mutex<NoopRawMutex, RefCell<Shared>>, filters: &[Filter]) -> Result<(), TooManyFilters> {
+    let filters = Vec::from_slice(filters).map_err(|_| TooManyFilters)?;
+    shared.lock(|s| {
+        let s = &mut *s.borrow_mut();
+        s.filters = Some(filters);
+        s.filters_changed = true;
+        s.runner_waker.wake();
+    });
+    Ok(())
+}
+
+fn bus_state(shared: &Mutex<NoopRawMutex, RefCell<Shared>>) -> BusState {
+    shared.lock(|s| s.borrow().bus_state)
+}
+
+impl<'d> embassy_can::ErrorType for Can<'d> {
+    type Error = ErrorKind;
+}
+
+impl<'d> embassy_can::Transmit for Can<'d> {
+    async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+        self.tx.transmit(frame).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush().await
+    }
+}
+
+impl<'d> embassy_can::Receive for Can<'d> {
+    async fn receive(&mut self) -> Result<Frame, Self::Error> {
+        self.rx.receive().await
+    }
+}
+
+impl<'d> embassy_can::Filters for Can<'d> {
+    /// Set the filters, applied by the runner.
+    ///
+    /// The frames matching no filter are dropped by the runner, when the controller can't
+    /// express the filters in hardware. The MCP2515 can't receive frames while its filters are
+    /// changed.
+    fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters> {
+        set_filters(self.rx.shared, filters)
+    }
+}
+
+impl<'d> embassy_can::Status for Can<'d> {
+    fn bus_state(&self) -> BusState {
+        bus_state(self.rx.shared)
+    }
+}
+
+impl<'d> embassy_can::ErrorType for CanTx<'d> {
+    type Error = ErrorKind;
+}
+
+impl<'d> embassy_can::Transmit for CanTx<'d> {
+    async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+        poll_fn(|cx| {
+            self.shared.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                assert!(s.fd || !frame.is_fd(), "the MCP2515 doesn't support CAN FD frames");
+
+                // Count the frame along with queueing it, so that `flush` can't miss it.
+                match self.tx.try_send(*frame) {
+                    Ok(()) => {
+                        s.tx_pending += 1;
+                        s.runner_waker.wake();
+                        Poll::Ready(Ok(()))
+                    }
+                    Err(_) => {
+                        s.tx_waker.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        poll_fn(|cx| {
+            self.shared.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                s.tx_waker.register(cx.waker());
+                match s.tx_pending {
+                    0 => Poll::Ready(Ok(())),
+                    _ => Poll::Pending,
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl<'d> embassy_can::Status for CanTx<'d> {
+    fn bus_state(&self) -> BusState {
+        bus_state(self.shared)
+    }
+}
+
+impl<'d> embassy_can::ErrorType for CanRx<'d> {
+    type Error = ErrorKind;
+}
+
+impl<'d> embassy_can::Receive for CanRx<'d> {
+    async fn receive(&mut self) -> Result<Frame, Self::Error> {
+        poll_fn(|cx| {
+            self.shared.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                s.rx_waker.register(cx.waker());
+                if s.overrun {
+                    s.overrun = false;
+                    return Poll::Ready(Err(ErrorKind::Overrun));
+                } else if let Ok(frame) = self.rx.try_recv() {
+                    return Poll::Ready(Ok(frame));
+                }
+
+                // Report the bus state when it gets worse than the last one reported.
+                let reported = s.reported_bus_state;
+                s.reported_bus_state = s.bus_state;
+                if s.bus_state > reported {
+                    return Poll::Ready(Err(match s.bus_state {
+                        BusState::BusOff => ErrorKind::BusOff,
+                        BusState::ErrorPassive => ErrorKind::ErrorPassive,
+                        _ => ErrorKind::ErrorWarning,
+                    }));
+                }
+
+                Poll::Pending
+            })
+        })
+        .await
+    }
+}
+
+impl<'d> embassy_can::Filters for CanRx<'d> {
+    fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters> {
+        set_filters(self.shared, filters)
+    }
+}
+
+impl<'d> embassy_can::Status for CanRx<'d> {
+    fn bus_state(&self) -> BusState {
+        bus_state(self.shared)
+    }
+}
+
+/// The runner side of the shared state.
+struct Channels<'d> {
+    rx: DynamicSender<'d, Frame>,
+    tx: DynamicReceiver<'d, Frame>,
+    shared: &'d Mutex<NoopRawMutex, RefCell<Shared>>,
+}
+
+impl<'d> Channels<'d> {
+    /// Pass a received frame to the handle, if it matches the filters.
+    fn received(&self, frame: Frame) {
+        let accepted = self.shared.lock(|s| match &s.borrow().filters {
+            Some(filters) => filters.iter().any(|f| f.matches(frame.id())),
+            None => true,
+        });
+        if !accepted {
+            return;
+        }
+        match self.rx.try_send(frame) {
+            Ok(()) => self.shared.lock(|s| s.borrow_mut().rx_waker.wake()),
+            Err(_) => self.overrun(),
+        }
+    }
+
+    /// Received frames were lost.
+    fn overrun(&self) {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            s.overrun = true;
+            s.rx_waker.wake();
+        });
+    }
+
+    fn set_bus_state(&self, state: BusState) {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            if s.bus_state != state {
+                s.bus_state = state;
+                s.rx_waker.wake();
+            }
+        });
+    }
+
+    /// `n` frames were sent.
+    fn sent(&self, n: usize) {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            s.tx_pending = s.tx_pending.saturating_sub(n);
+            if s.tx_pending == 0 {
+                s.tx_waker.wake();
+            }
+        });
+    }
+
+    /// The next frame to send, if any.
+    fn try_tx(&self) -> Option<Frame> {
+        let frame = self.tx.try_recv().ok()?;
+        // Room for the next one.
+        self.shared.lock(|s| s.borrow_mut().tx_waker.wake());
+        Some(frame)
+    }
+
+    /// Wait for a frame to send.
+    async fn tx(&self) -> Frame {
+        poll_fn(|cx| {
+            self.shared.lock(|s| s.borrow_mut().runner_waker.register(cx.waker()));
+            match self.try_tx() {
+                Some(frame) => Poll::Ready(frame),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// The new filters, if they changed.
+    fn take_filters(&self) -> Option<Option<Vec<Filter, MAX_FILTERS>>> {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            if !s.filters_changed {
+                return None;
+            }
+            s.filters_changed = false;
+            Some(s.filters.clone())
+        })
+    }
+
+    async fn filters_changed(&self) {
+        poll_fn(|cx| {
+            self.shared.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                s.runner_waker.register(cx.waker());
+                match s.filters_changed {
+                    true => Poll::Ready(()),
+                    false => Poll::Pending,
+                }
+            })
+        })
+        .await
+    }
+}
+
+/// DLC of a frame of `len` bytes.
+fn len_to_dlc(len: usize) -> u8 {
+    match len {
+        0..=8 => len as u8,
+        12 => 9,
+        16 => 10,
+        20 => 11,
+        24 => 12,
+        32 => 13,
+        48 => 14,
+        _ => 15,
+    }
+}
+
+/// Length of the data of a CAN FD frame with `dlc`.
+fn dlc_to_len(dlc: u8) -> usize {
+    match dlc & 0x0F {
+        dlc @ 0..=8 => dlc as usize,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
+
+/// Key of the arbitration on the bus: the lowest one wins.
+fn arbitration_key(id: Id) -> u32 {
+    match id {
+        Id::Standard(id) => (id.as_raw() as u32) << 19,
+        // The base identifier, then the IDE bit, recessive for extended identifiers.
+        Id::Extended(id) => (id.as_raw() >> 18) << 19 | 1 << 18 | (id.as_raw() & 0x3FFFF),
+    }
+}
+
+struct Timings {
+    prescaler: u32,
+    seg1: u32,
+    seg2: u32,
+}
+
+/// Finds the timings with the most time quanta per bit, and the sample point closest to 87.5%.
+///
+/// `seg1` is the time segment before the sample point, without the synchronization segment.
+fn calc_timings(clock: u32, bitrate: u32, max_prescaler: u32, max_seg1: u32, max_seg2: u32) -> Option<Timings> {
+    if bitrate == 0 {
+        return None;
+    }
+
+    for prescaler in 1..=max_prescaler {
+        let prescaler_bitrate = bitrate.checked_mul(prescaler)?;
+        if clock % prescaler_bitrate != 0 {
+            continue;
+        }
+        let quanta = clock / prescaler_bitrate;
+        if quanta < 8 {
+            // Fewer quanta with higher prescalers.
+            return None;
+        }
+        if quanta > 1 + max_seg1 + max_seg2 {
+            continue;
+        }
+
+        // The sample point is at the end of seg1.
+        let seg1 = (quanta * 7 + 4) / 8 - 1;
+        let seg2 = quanta - 1 - seg1;
+        if seg1 <= max_seg1 && seg2 <= max_seg2 {
+            return Some(Timings { prescaler, seg1, seg2 });
+        }
+    }
+    None
+}
@@ -0,0 +1,471 @@
+//! Driver for the MCP2515, a classic CAN controller with three transmit buffers and two receive
+//! buffers.
+
+use embassy_can::{BusState, ExtendedId, Filter, Frame, Id, StandardId};
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+use heapless::Vec;
+
+use crate::{arbitration_key, calc_timings, Can, Channels, Error, Mode, State, MAX_FILTERS};
+
+const INSTR_RESET: u8 = 0xC0;
+const INSTR_READ: u8 = 0x03;
+const INSTR_WRITE: u8 = 0x02;
+const INSTR_BIT_MODIFY: u8 = 0x05;
+const fn instr_load_tx(n: usize) -> u8 {
+    0x40 | (n as u8) << 1
+}
+const fn instr_rts(n: usize) -> u8 {
+    0x80 | 1 << n
+}
+const fn instr_read_rx(n: usize) -> u8 {
+    0x90 | (n as u8) << 2
+}
+
+const REG_CANSTAT: u8 = 0x0E;
+const REG_CANCTRL: u8 = 0x0F;
+const REG_RXF: [u8; 6] = [0x00, 0x04, 0x08, 0x10, 0x14, 0x18];
+const REG_RXM: [u8; 2] = [0x20, 0x24];
+const REG_CNF3: u8 = 0x28;
+const REG_CANINTE: u8 = 0x2B;
+const REG_CANINTF: u8 = 0x2C;
+const REG_EFLG: u8 = 0x2D;
+const REG_TXBCTRL: [u8; 3] = [0x30, 0x40, 0x50];
+const REG_RXBCTRL: [u8; 2] = [0x60, 0x70];
+
+const CANCTRL_REQOP_MASK: u8 = 0xE0;
+const TXBCTRL_TXP_MASK: u8 = 0x03;
+const REQOP_NORMAL: u8 = 0x00;
+const REQOP_LOOPBACK: u8 = 0x40;
+const REQOP_LISTEN_ONLY: u8 = 0x60;
+const REQOP_CONFIG: u8 = 0x80;
+
+const INT_RX0: u8 = 0x01;
+const INT_RX1: u8 = 0x02;
+const INT_TX: [u8; 3] = [0x04, 0x08, 0x10];
+const INT_ERR: u8 = 0x20;
+
+const EFLG_EWARN: u8 = 0x01;
+const EFLG_RXEP: u8 = 0x08;
+const EFLG_TXEP: u8 = 0x10;
+const EFLG_TXBO: u8 = 0x20;
+const EFLG_RX0OVR: u8 = 0x40;
+const EFLG_RX1OVR: u8 = 0x80;
+
+/// Receive any frame, without the filters.
+const RXBCTRL_RXM_ANY: u8 = 0x60;
+/// Roll over to receive buffer 1 when receive buffer 0 is full.
+const RXB0CTRL_BUKT: u8 = 0x04;
+
+const SIDL_IDE: u8 = 0x08;
+const SIDL_SRR: u8 = 0x10;
+const DLC_RTR: u8 = 0x40;
+
+/// Number of filters of each receive buffer: 2 for buffer 0, 4 for buffer 1.
+const RXB_FILTERS: [usize; 2] = [2, 4];
+
+/// Time for the controller to switch operating modes.
+const MODE_TIMEOUT: Duration = Duration::from_millis(10);
+/// Interval between updates of the bus state, which only interrupts when it gets worse.
+const BUS_STATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Configuration of the MCP2515.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Frequency of the oscillator, in Hz. Common modules have an 8 MHz crystal.
+    pub oscillator: u32,
+    /// Bitrate, in bit/s.
+    pub bitrate: u32,
+    /// Operating mode.
+    pub mode: Mode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            oscillator: 8_000_000,
+            bitrate: 500_000,
+            mode: Mode::Normal,
+        }
+    }
+}
+
+struct Mcp2515<SPI> {
+    spi: SPI,
+    mode: Mode,
+}
+
+impl<SPI: SpiDevice> Mcp2515<SPI> {
+    async fn read(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[INSTR_READ, addr]), Operation::Read(buf)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[INSTR_WRITE, addr]), Operation::Write(data)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn bit_modify(&mut self, addr: u8, mask: u8, value: u8) -> Result<(), Error<SPI::Error>> {
+        self.instruction(&[INSTR_BIT_MODIFY, addr, mask, value]).await
+    }
+
+    async fn instruction(&mut self, instr: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(instr)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn set_reqop(&mut self, reqop: u8) -> Result<(), Error<SPI::Error>> {
+        self.bit_modify(REG_CANCTRL, CANCTRL_REQOP_MASK, reqop).await?;
+        // The controller switches once the frame on the bus, if any, is over.
+        for _ in 0..MODE_TIMEOUT.as_millis() {
+            let mut canstat = [0];
+            self.read(REG_CANSTAT, &mut canstat).await?;
+            if canstat[0] & CANCTRL_REQOP_MASK == reqop {
+                return Ok(());
+            }
+            Timer::after(Duration::from_millis(1)).await;
+        }
+        Err(Error::ModeTimeout)
+    }
+
+    async fn set_operating_mode(&mut self) -> Result<(), Error<SPI::Error>> {
+        let reqop = match self.mode {
+            Mode::Normal => REQOP_NORMAL,
+            Mode::Loopback => REQOP_LOOPBACK,
+            Mode::ListenOnly => REQOP_LISTEN_ONLY,
+        };
+        self.set_reqop(reqop).await
+    }
+
+    async fn init(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
+        self.instruction(&[INSTR_RESET]).await?;
+        // The oscillator starts within 128 cycles, in configuration mode.
+        Timer::after(Duration::from_millis(1)).await;
+        self.set_reqop(REQOP_CONFIG).await?;
+
+        // A time quantum is 2 oscillator cycles, or more with the prescaler. The sample point
+        // needs a phase segment 2 of at least 2 quanta, and a phase segment 1 at least as long.
+        let mut timings = calc_timings(config.oscillator / 2, config.bitrate, 64, 16, 8).ok_or(Error::Bitrate)?;
+        if timings.seg2 < 2 {
+            timings.seg1 -= 1;
+            timings.seg2 = 2;
+        }
+        let phseg1 = timings.seg2.max(timings.seg1.saturating_sub(8));
+        let prseg = timings.seg1 - phseg1;
+        if prseg == 0 || phseg1 > 8 {
+            return Err(Error::Bitrate);
+        }
+        let cnf1 = (timings.prescaler - 1) as u8;
+        // BTLMODE: phase segment 2 set by CNF3.
+        let cnf2 = 0x80 | ((phseg1 - 1) as u8) << 3 | (prseg - 1) as u8;
+        let cnf3 = (timings.seg2 - 1) as u8;
+        self.write(REG_CNF3, &[cnf3, cnf2, cnf1]).await?;
+
+        self.write_filters(None).await?;
+        self.write(
+            REG_CANINTE,
+            &[INT_RX0 | INT_RX1 | INT_TX[0] | INT_TX[1] | INT_TX[2] | INT_ERR],
+        )
+        .await?;
+
+        self.set_operating_mode().await
+    }
+
+    /// Write the filters, in configuration mode.
+    ///
+    /// Receive buffer 0 has 2 filters, buffer 1 has 4, and each buffer has a single mask: the
+    /// filters fit if they have at most two different masks. Otherwise all the frames are
+    /// received, and filtered by the runner.
+    async fn write_filters(&mut self, filters: Option<&[Filter]>) -> Result<(), Error<SPI::Error>> {
+        let groups = filters.and_then(group_filters);
+        let groups = match groups {
+            Some(groups) => groups,
+            None => {
+                self.write(REG_RXBCTRL[0], &[RXBCTRL_RXM_ANY | RXB0CTRL_BUKT]).await?;
+                return self.write(REG_RXBCTRL[1], &[RXBCTRL_RXM_ANY]).await;
+            }
+        };
+
+        let mut rxf = 0;
+        for (n, group) in groups.iter().enumerate() {
+            self.write(REG_RXM[n], &encode_id(group[0].mask(), group[0].is_extended(), false))
+                .await?;
+            // Pad with copies of the first filter.
+            for i in 0..RXB_FILTERS[n] {
+                let filter = group.get(i).unwrap_or(&group[0]);
+                self.write(
+                    REG_RXF[rxf],
+                    &encode_id(filter.id(), filter.is_extended(), filter.is_extended()),
+                )
+                .await?;
+                rxf += 1;
+            }
+        }
+        self.write(REG_RXBCTRL[0], &[RXB0CTRL_BUKT]).await?;
+        self.write(REG_RXBCTRL[1], &[0]).await
+    }
+
+    /// Change the filters, stopping the reception meanwhile.
+    async fn set_filters(&mut self, filters: Option<&[Filter]>) -> Result<(), Error<SPI::Error>> {
+        self.set_reqop(REQOP_CONFIG).await?;
+        self.write_filters(filters).await?;
+        self.set_operating_mode().await
+    }
+
+    async fn read_rx(&mut self, n: usize) -> Result<Option<Frame>, Error<SPI::Error>> {
+        // Reading the buffer with this instruction clears its interrupt flag.
+        let mut buf = [0; 13];
+        self.spi
+            .transaction(&mut [Operation::Write(&[instr_read_rx(n)]), Operation::Read(&mut buf)])
+            .await
+            .map_err(Error::Spi)?;
+
+        let sid = (buf[0] as u16) << 3 | (buf[1] >> 5) as u16;
+        let dlc = (buf[4] & 0x0F).min(8);
+        let (id, remote) = if buf[1] & SIDL_IDE != 0 {
+            let eid = (sid as u32) << 18 | ((buf[1] & 0x03) as u32) << 16 | (buf[2] as u32) << 8 | buf[3] as u32;
+            (Id::from(ExtendedId::new(eid).unwrap()), buf[4] & DLC_RTR != 0)
+        } else {
+            (Id::from(StandardId::new(sid).unwrap()), buf[1] & SIDL_SRR != 0)
+        };
+
+        Ok(match remote {
+            true => Frame::new_remote(id, dlc),
+            false => Frame::new_data(id, &buf[5..][..dlc as usize]),
+        })
+    }
+
+    async fn load_tx(&mut self, n: usize, frame: &Frame) -> Result<(), Error<SPI::Error>> {
+        let (raw, extended) = match frame.id() {
+            Id::Standard(id) => (id.as_raw() as u32, false),
+            Id::Extended(id) => (id.as_raw(), true),
+        };
+        let mut buf = [0; 14];
+        buf[0] = instr_load_tx(n);
+        buf[1..5].copy_from_slice(&encode_id(raw, extended, extended));
+        buf[5] = frame.len() as u8;
+        if frame.is_remote_frame() {
+            buf[5] |= DLC_RTR;
+        }
+        let data = frame.data();
+        buf[6..][..data.len()].copy_from_slice(data);
+        self.instruction(&buf[..6 + data.len()]).await
+    }
+}
+
+/// Split the filters into the groups of the two receive buffers, each group sharing its mask.
+fn group_filters(filters: &[Filter]) -> Option<[Vec<Filter, 4>; 2]> {
+    if filters.is_empty() {
+        return None;
+    }
+
+    let same_mask = |a: &Filter, b: &Filter| a.mask() == b.mask() && a.is_extended() == b.is_extended();
+    let (first, others): (Vec<Filter, MAX_FILTERS>, Vec<Filter, MAX_FILTERS>) =
+        filters.iter().partition(|f| same_mask(f, &filters[0]));
+
+    let mut groups = [Vec::new(), Vec::new()];
+    if others.is_empty() {
+        // A single mask, for both buffers.
+        if first.len() > RXB_FILTERS[0] + RXB_FILTERS[1] {
+            return None;
+        }
+        let (rxb0, rxb1) = first.split_at(first.len().saturating_sub(RXB_FILTERS[1]));
+        groups[0] = Vec::from_slice(rxb0).ok()?;
+        groups[1] = Vec::from_slice(rxb1).ok()?;
+        if groups[0].is_empty() {
+            groups[0] = groups[1].clone();
+        }
+    } else {
+        if !others.iter().all(|f| same_mask(f, &others[0])) {
+            return None;
+        }
+        let (small, large) = match first.len() <= others.len() {
+            true => (first, others),
+            false => (others, first),
+        };
+        if small.len() > RXB_FILTERS[0] {
+            return None;
+        }
+        groups[0] = Vec::from_slice(&small).ok()?;
+        groups[1] = Vec::from_slice(&large).ok()?;
+    }
+    Some(groups)
+}
+
+/// Encode an identifier, filter or mask to the layout of the SIDH, SIDL, EID8 and EID0 registers.
+///
+/// A standard mask leaves the extended bits clear: they would match the first data bytes.
+fn encode_id(raw: u32, extended: bool, ide: bool) -> [u8; 4] {
+    let sid = if extended { raw >> 18 } else { raw };
+    let mut regs = [(sid >> 3) as u8, (sid << 5) as u8, 0, 0];
+    if extended {
+        regs[1] |= (raw >> 16) as u8 & 0x03;
+        regs[2] = (raw >> 8) as u8;
+        regs[3] = raw as u8;
+    }
+    if ide {
+        regs[1] |= SIDL_IDE;
+    }
+    regs
+}
+
+/// Background runner for the MCP2515.
+///
+/// You must call `.run()` in a background task for the controller to operate.
+pub struct Runner<'d, SPI: SpiDevice, INT: Wait> {
+    mcp: Mcp2515<SPI>,
+    ch: Channels<'d>,
+    int: INT,
+    /// Arbitration keys of the frames in the transmit buffers.
+    tx_buffers: [Option<u32>; 3],
+}
+
+impl<'d, SPI: SpiDevice, INT: Wait> Runner<'d, SPI, INT> {
+    pub async fn run(mut self) -> ! {
+        loop {
+            if let Some(filters) = self.ch.take_filters() {
+                if self.mcp.set_filters(filters.as_deref()).await.is_err() {
+                    warn!("filters update failed");
+                }
+            }
+
+            if self.poll().await.is_err() {
+                warn!("SPI error");
+            }
+
+            while let Some(n) = self.free_tx_buffer() {
+                let Some(frame) = self.ch.try_tx() else { break };
+                self.transmit(n, &frame).await;
+            }
+
+            let tx_free = self.free_tx_buffer();
+            let ch = &self.ch;
+            let tx = async {
+                match tx_free {
+                    Some(_) => ch.tx().await,
+                    None => core::future::pending().await,
+                }
+            };
+            let event = select4(
+                self.int.wait_for_low(),
+                tx,
+                ch.filters_changed(),
+                Timer::after(BUS_STATE_POLL_INTERVAL),
+            )
+            .await;
+            if let Either4::Second(frame) = event {
+                self.transmit(unwrap!(tx_free), &frame).await;
+            }
+        }
+    }
+
+    fn free_tx_buffer(&self) -> Option<usize> {
+        self.tx_buffers.iter().position(|b| b.is_none())
+    }
+
+    /// Handle the interrupts, and update the bus state.
+    async fn poll(&mut self) -> Result<(), Error<SPI::Error>> {
+        // CANINTF, then EFLG.
+        let mut regs = [0; 2];
+        self.mcp.read(REG_CANINTF, &mut regs).await?;
+        let [intf, eflg] = regs;
+
+        for (n, flag) in [INT_RX0, INT_RX1].into_iter().enumerate() {
+            if intf & flag != 0 {
+                if let Some(frame) = self.mcp.read_rx(n).await? {
+                    self.ch.received(frame);
+                }
+            }
+        }
+
+        let mut clear = intf & INT_ERR;
+        for (n, flag) in INT_TX.into_iter().enumerate() {
+            if intf & flag != 0 {
+                self.tx_buffers[n] = None;
+                self.ch.sent(1);
+                clear |= flag;
+            }
+        }
+        if clear != 0 {
+            self.mcp.bit_modify(REG_CANINTF, clear, 0).await?;
+        }
+
+        if eflg & (EFLG_RX0OVR | EFLG_RX1OVR) != 0 {
+            self.ch.overrun();
+            self.mcp.bit_modify(REG_EFLG, EFLG_RX0OVR | EFLG_RX1OVR, 0).await?;
+        }
+
+        self.ch.set_bus_state(if eflg & EFLG_TXBO != 0 {
+            BusState::BusOff
+        } else if eflg & (EFLG_TXEP | EFLG_RXEP) != 0 {
+            BusState::ErrorPassive
+        } else if eflg & EFLG_EWARN != 0 {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        });
+        Ok(())
+    }
+
+    /// Send `frame` from the transmit buffer `n`.
+    async fn transmit(&mut self, n: usize, frame: &Frame) {
+        self.tx_buffers[n] = Some(arbitration_key(frame.id()));
+        if self.load_tx(n, frame).await.is_err() {
+            warn!("transmit failed");
+            self.tx_buffers[n] = None;
+            self.ch.sent(1);
+        }
+    }
+
+    /// The controller sends the buffer with the highest priority first: the pending frames are
+    /// given priorities by identifier, as the arbitration on the bus does.
+    async fn load_tx(&mut self, n: usize, frame: &Frame) -> Result<(), Error<SPI::Error>> {
+        self.mcp.load_tx(n, frame).await?;
+
+        let buffers = self.tx_buffers;
+        for (i, key) in buffers.iter().enumerate() {
+            let Some(key) = *key else { continue };
+            // Ties are broken by buffer, to keep each priority distinct.
+            let rank = buffers
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| matches!(*other, Some(other) if (other, j) < (key, i)))
+                .count();
+            self.mcp
+                .bit_modify(REG_TXBCTRL[i], TXBCTRL_TXP_MASK, 3 - rank as u8)
+                .await?;
+        }
+
+        self.mcp.instruction(&[instr_rts(n)]).await
+    }
+}
+
+/// Obtain a driver for the MCP2515 on `spi`, with its interrupt pin `int`.
+pub async fn new<'d, const N_RX: usize, SPI: SpiDevice, INT: Wait>(
+    config: Config,
+    state: &'d mut State<N_RX>,
+    spi: SPI,
+    int: INT,
+) -> Result<(Can<'d>, Runner<'d, SPI, INT>), Error<SPI::Error>> {
+    let (can, ch) = crate::new(state, false);
+    let mut mcp = Mcp2515 { spi, mode: config.mode };
+    mcp.init(&config).await?;
+    let runner = Runner {
+        mcp,
+        ch,
+        int,
+        tx_buffers: [None; 3],
+    };
+    Ok((can, runner))
+}
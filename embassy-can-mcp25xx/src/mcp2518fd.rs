@@ -0,0 +1,479 @@
+//! Driver for the MCP2518FD, and the MCP2517FD, CAN FD controllers.
+//!
+//! Frames are sent from the transmit queue, which sends them by priority, and received in FIFO 1.
+
+use embassy_can::{BusState, ExtendedId, Filter, Frame, Id, StandardId, MAX_DATA_LEN};
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::{calc_timings, dlc_to_len, len_to_dlc, Can, Channels, Error, Mode, State, MAX_FILTERS};
+
+const INSTR_RESET: u16 = 0x0;
+const INSTR_WRITE: u16 = 0x2;
+const INSTR_READ: u16 = 0x3;
+
+const REG_C1CON: u16 = 0x000;
+const REG_C1NBTCFG: u16 = 0x004;
+const REG_C1DBTCFG: u16 = 0x008;
+const REG_C1TDC: u16 = 0x00C;
+const REG_C1INT: u16 = 0x01C;
+const REG_C1TREC: u16 = 0x034;
+const REG_C1TXQCON: u16 = 0x050;
+const REG_C1TXQSTA: u16 = 0x054;
+const REG_C1TXQUA: u16 = 0x058;
+const REG_C1FIFOCON1: u16 = 0x05C;
+const REG_C1FIFOSTA1: u16 = 0x060;
+const REG_C1FIFOUA1: u16 = 0x064;
+const REG_C1FLTCON: u16 = 0x1D0;
+const REG_C1FLTOBJ: u16 = 0x1F0;
+const REG_OSC: u16 = 0xE00;
+const RAM_START: u16 = 0x400;
+
+/// Reset value, without the transmit event FIFO.
+const C1CON_INIT: u32 = 0x0490_0760;
+const C1CON_OPMOD_SHIFT: u32 = 21;
+const OPMOD_NORMAL_FD: u8 = 0b000;
+const OPMOD_LOOPBACK: u8 = 0b010;
+const OPMOD_LISTEN_ONLY: u8 = 0b011;
+const OPMOD_CONFIG: u8 = 0b100;
+
+const C1TDC_TDCMOD_AUTO: u32 = 0b10 << 16;
+
+const C1INT_TXIE: u32 = 1 << 16;
+const C1INT_RXIE: u32 = 1 << 17;
+const C1INT_RXOVIE: u32 = 1 << 27;
+const C1INT_CERRIE: u32 = 1 << 29;
+/// CERRIF, in the second byte of C1INT.
+const C1INT1_CERRIF: u8 = 1 << 5;
+
+/// TXQ and FIFO control, first byte.
+const FIFOCON_TFNRFNIE: u8 = 1 << 0;
+const FIFOCON_TFERFFIE: u8 = 1 << 2;
+const FIFOCON_RXOVIE: u8 = 1 << 3;
+/// TXQ and FIFO control, second byte.
+const FIFOCON1_UINC: u8 = 1 << 0;
+const FIFOCON1_TXREQ: u8 = 1 << 1;
+/// Unlimited retransmissions.
+const FIFOCON_TXAT_UNLIMITED: u32 = 0b11 << 21;
+const FIFOCON_PLSIZE_64: u32 = 0b111 << 29;
+const FIFOCON_FSIZE_SHIFT: u32 = 24;
+
+const FIFOSTA_TFNRFNIF: u8 = 1 << 0;
+const FIFOSTA_TFERFFIF: u8 = 1 << 2;
+const FIFOSTA_RXOVIF: u8 = 1 << 3;
+
+/// C1TREC, third byte.
+const TREC2_EWARN: u8 = 1 << 0;
+const TREC2_RXBP: u8 = 1 << 3;
+const TREC2_TXBP: u8 = 1 << 4;
+const TREC2_TXBO: u8 = 1 << 5;
+
+const OSC_OSCRDY: u32 = 1 << 10;
+
+const FLTCON_FLTEN: u8 = 0x80;
+/// Filters send their frames to FIFO 1.
+const FLTCON_FIFO: u8 = 1;
+const FLTOBJ_EXIDE: u32 = 1 << 30;
+const MASK_MIDE: u32 = 1 << 30;
+
+const OBJ_IDE: u32 = 1 << 4;
+const OBJ_RTR: u32 = 1 << 5;
+const OBJ_BRS: u32 = 1 << 6;
+const OBJ_FDF: u32 = 1 << 7;
+
+/// Size of the transmit queue. All the 31 FIFOs take room in the 2 KiB of RAM, 16 bytes each
+/// when unused: what's left is shared by the transmit queue and the receive FIFO.
+const TXQ_SIZE: u32 = 8;
+/// Size of the receive FIFO.
+const RX_FIFO_SIZE: u32 = 12;
+/// Size of a message object header.
+const OBJ_HEADER_LEN: usize = 8;
+
+/// Time for the oscillator to start, and for the controller to switch operating modes.
+const MODE_TIMEOUT: Duration = Duration::from_millis(10);
+/// Interval between updates of the bus state, which only interrupts on errors.
+const BUS_STATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Configuration of the MCP2518FD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Frequency of the oscillator, in Hz: 40 MHz, or 20 MHz. The PLL isn't used.
+    pub oscillator: u32,
+    /// Nominal bitrate, in bit/s.
+    pub bitrate: u32,
+    /// Data bitrate of the CAN FD frames with bit rate switching, in bit/s. `None` for the
+    /// nominal bitrate.
+    pub data_bitrate: Option<u32>,
+    /// Operating mode.
+    pub mode: Mode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            oscillator: 40_000_000,
+            bitrate: 500_000,
+            data_bitrate: Some(2_000_000),
+            mode: Mode::Normal,
+        }
+    }
+}
+
+struct Mcp2518fd<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> Mcp2518fd<SPI> {
+    async fn read(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        let header = (INSTR_READ << 12 | addr).to_be_bytes();
+        self.spi
+            .transaction(&mut [Operation::Write(&header), Operation::Read(buf)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn write(&mut self, addr: u16, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let header = (INSTR_WRITE << 12 | addr).to_be_bytes();
+        self.spi
+            .transaction(&mut [Operation::Write(&header), Operation::Write(data)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn read_u32(&mut self, addr: u16) -> Result<u32, Error<SPI::Error>> {
+        let mut buf = [0; 4];
+        self.read(addr, &mut buf).await?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    async fn write_u32(&mut self, addr: u16, value: u32) -> Result<(), Error<SPI::Error>> {
+        self.write(addr, &value.to_le_bytes()).await
+    }
+
+    async fn set_opmod(&mut self, opmod: u8) -> Result<(), Error<SPI::Error>> {
+        // REQOP is in the fourth byte of C1CON, along with ABAT and TXBWS, left at 0.
+        self.write(REG_C1CON + 3, &[opmod]).await?;
+        // The controller switches once the frame on the bus, if any, is over.
+        for _ in 0..MODE_TIMEOUT.as_millis() {
+            let con = self.read_u32(REG_C1CON).await?;
+            if (con >> C1CON_OPMOD_SHIFT) as u8 & 0x07 == opmod {
+                return Ok(());
+            }
+            Timer::after(Duration::from_millis(1)).await;
+        }
+        Err(Error::ModeTimeout)
+    }
+
+    async fn init(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&(INSTR_RESET << 12).to_be_bytes())])
+            .await
+            .map_err(Error::Spi)?;
+
+        // The controller comes out of reset in configuration mode, once its oscillator is ready.
+        let mut ready = false;
+        for _ in 0..MODE_TIMEOUT.as_millis() {
+            Timer::after(Duration::from_millis(1)).await;
+            if self.read_u32(REG_OSC).await? & OSC_OSCRDY != 0 {
+                ready = true;
+                break;
+            }
+        }
+        let con = self.read_u32(REG_C1CON).await?;
+        if !ready || (con >> C1CON_OPMOD_SHIFT) as u8 & 0x07 != OPMOD_CONFIG {
+            return Err(Error::ModeTimeout);
+        }
+
+        let nominal = calc_timings(config.oscillator, config.bitrate, 256, 256, 128).ok_or(Error::Bitrate)?;
+        let data_bitrate = config.data_bitrate.unwrap_or(config.bitrate);
+        let data = calc_timings(config.oscillator, data_bitrate, 256, 32, 16).ok_or(Error::Bitrate)?;
+        let btcfg =
+            |t: &crate::Timings| (t.prescaler - 1) << 24 | (t.seg1 - 1) << 16 | (t.seg2 - 1) << 8 | (t.seg2 - 1);
+        self.write_u32(REG_C1NBTCFG, btcfg(&nominal)).await?;
+        self.write_u32(REG_C1DBTCFG, btcfg(&data)).await?;
+        // The transmitter delay compensation is needed at high data bitrates, with a fast clock.
+        let tdc = match data.prescaler {
+            1 | 2 => C1TDC_TDCMOD_AUTO | (data.seg1 * data.prescaler).min(63) << 8,
+            _ => 0,
+        };
+        self.write_u32(REG_C1TDC, tdc).await?;
+
+        self.write_u32(REG_C1CON, C1CON_INIT).await?;
+        self.write_u32(
+            REG_C1TXQCON,
+            FIFOCON_PLSIZE_64 | (TXQ_SIZE - 1) << FIFOCON_FSIZE_SHIFT | FIFOCON_TXAT_UNLIMITED,
+        )
+        .await?;
+        self.write_u32(
+            REG_C1FIFOCON1,
+            FIFOCON_PLSIZE_64 | (RX_FIFO_SIZE - 1) << FIFOCON_FSIZE_SHIFT | (FIFOCON_RXOVIE | FIFOCON_TFNRFNIE) as u32,
+        )
+        .await?;
+        self.write_filters(None).await?;
+        self.write_u32(REG_C1INT, C1INT_TXIE | C1INT_RXIE | C1INT_RXOVIE | C1INT_CERRIE)
+            .await?;
+
+        self.set_opmod(match config.mode {
+            Mode::Normal => OPMOD_NORMAL_FD,
+            Mode::Loopback => OPMOD_LOOPBACK,
+            Mode::ListenOnly => OPMOD_LISTEN_ONLY,
+        })
+        .await
+    }
+
+    /// Write the filters, each with its own mask. `None` to receive all the frames.
+    ///
+    /// The filters are disabled while they're written: the frames received meanwhile are lost.
+    async fn write_filters(&mut self, filters: Option<&[Filter]>) -> Result<(), Error<SPI::Error>> {
+        self.write(REG_C1FLTCON, &[0; MAX_FILTERS]).await?;
+
+        let filters = match filters {
+            Some(filters) => filters,
+            None => {
+                // A single filter, with MIDE clear to match both standard and extended identifiers.
+                self.write(REG_C1FLTOBJ, &[0; 8]).await?;
+                return self.write(REG_C1FLTCON, &[FLTCON_FLTEN | FLTCON_FIFO]).await;
+            }
+        };
+
+        let mut fltcon = [0; MAX_FILTERS];
+        for (m, filter) in filters.iter().enumerate() {
+            let mut obj = encode_id(filter.id(), filter.is_extended());
+            if filter.is_extended() {
+                obj |= FLTOBJ_EXIDE;
+            }
+            let mask = encode_id(filter.mask(), filter.is_extended()) | MASK_MIDE;
+
+            let mut regs = [0; 8];
+            regs[..4].copy_from_slice(&obj.to_le_bytes());
+            regs[4..].copy_from_slice(&mask.to_le_bytes());
+            self.write(REG_C1FLTOBJ + 8 * m as u16, &regs).await?;
+            fltcon[m] = FLTCON_FLTEN | FLTCON_FIFO;
+        }
+        self.write(REG_C1FLTCON, &fltcon).await
+    }
+
+    /// Read the next frame of the receive FIFO, if any.
+    async fn read_rx(&mut self) -> Result<Option<Frame>, Error<SPI::Error>> {
+        let ua = self.read_u32(REG_C1FIFOUA1).await? as u16;
+        let mut header = [0; OBJ_HEADER_LEN];
+        self.read(RAM_START + ua, &mut header).await?;
+        let [r0 @ .., _, _, _, _] = header;
+        let [_, _, _, _, r1 @ ..] = header;
+        let (r0, r1) = (u32::from_le_bytes(r0), u32::from_le_bytes(r1));
+
+        let sid = r0 & 0x7FF;
+        let id = if r1 & OBJ_IDE != 0 {
+            Id::Extended(unwrap!(ExtendedId::new(sid << 18 | (r0 >> 11) & 0x3FFFF)))
+        } else {
+            Id::Standard(unwrap!(StandardId::new(sid as u16)))
+        };
+        let dlc = (r1 & 0x0F) as u8;
+        let fd = r1 & OBJ_FDF != 0;
+        let len = if fd { dlc_to_len(dlc) } else { dlc.min(8) as usize };
+
+        // The RAM is read by words.
+        let mut data = [0; MAX_DATA_LEN];
+        if r1 & OBJ_RTR == 0 && len > 0 {
+            self.read(RAM_START + ua + OBJ_HEADER_LEN as u16, &mut data[..(len + 3) & !3])
+                .await?;
+        }
+        self.write(REG_C1FIFOCON1 + 1, &[FIFOCON1_UINC]).await?;
+
+        Ok(if fd {
+            Frame::new_fd(id, &data[..len], r1 & OBJ_BRS != 0)
+        } else if r1 & OBJ_RTR != 0 {
+            Frame::new_remote(id, dlc.min(8))
+        } else {
+            Frame::new_data(id, &data[..len])
+        })
+    }
+
+    /// Queue `frame` in the transmit queue, which must not be full.
+    async fn load_tx(&mut self, frame: &Frame) -> Result<(), Error<SPI::Error>> {
+        let ua = self.read_u32(REG_C1TXQUA).await? as u16;
+
+        let (raw, extended) = match frame.id() {
+            Id::Standard(id) => (id.as_raw() as u32, false),
+            Id::Extended(id) => (id.as_raw(), true),
+        };
+        let mut t1 = len_to_dlc(frame.len()) as u32;
+        if extended {
+            t1 |= OBJ_IDE;
+        }
+        if frame.is_remote_frame() {
+            t1 |= OBJ_RTR;
+        }
+        if frame.is_fd() {
+            t1 |= OBJ_FDF;
+        }
+        if frame.bit_rate_switching() {
+            t1 |= OBJ_BRS;
+        }
+
+        // The RAM is written by words.
+        let data = frame.data();
+        let mut obj = [0; OBJ_HEADER_LEN + MAX_DATA_LEN];
+        obj[..4].copy_from_slice(&encode_id(raw, extended).to_le_bytes());
+        obj[4..8].copy_from_slice(&t1.to_le_bytes());
+        obj[8..][..data.len()].copy_from_slice(data);
+        let len = OBJ_HEADER_LEN + ((data.len() + 3) & !3);
+        self.write(RAM_START + ua, &obj[..len]).await?;
+
+        self.write(REG_C1TXQCON + 1, &[FIFOCON1_UINC | FIFOCON1_TXREQ]).await
+    }
+}
+
+/// Encode an identifier, filter or mask to the layout of the message objects, the filter objects
+/// and the masks: the standard identifier, or the base identifier, then the extension.
+fn encode_id(raw: u32, extended: bool) -> u32 {
+    match extended {
+        true => raw >> 18 | (raw & 0x3FFFF) << 11,
+        false => raw,
+    }
+}
+
+/// Background runner for the MCP2518FD.
+///
+/// You must call `.run()` in a background task for the controller to operate.
+pub struct Runner<'d, SPI: SpiDevice, INT: Wait> {
+    mcp: Mcp2518fd<SPI>,
+    ch: Channels<'d>,
+    int: INT,
+    /// Frames in the transmit queue.
+    tx_queued: usize,
+    tx_full: bool,
+}
+
+impl<'d, SPI: SpiDevice, INT: Wait> Runner<'d, SPI, INT> {
+    pub async fn run(mut self) -> ! {
+        loop {
+            if let Some(filters) = self.ch.take_filters() {
+                if self.mcp.write_filters(filters.as_deref()).await.is_err() {
+                    warn!("filters update failed");
+                }
+            }
+
+            if self.poll().await.is_err() {
+                warn!("SPI error");
+            }
+
+            let tx_full = self.tx_full;
+            let ch = &self.ch;
+            let tx = async {
+                match tx_full {
+                    false => ch.tx().await,
+                    true => core::future::pending().await,
+                }
+            };
+            let event = select4(
+                self.int.wait_for_low(),
+                tx,
+                ch.filters_changed(),
+                Timer::after(BUS_STATE_POLL_INTERVAL),
+            )
+            .await;
+            if let Either4::Second(frame) = event {
+                self.transmit(&frame).await;
+            }
+        }
+    }
+
+    /// Handle the interrupts, queue the frames to send, and update the bus state.
+    async fn poll(&mut self) -> Result<(), Error<SPI::Error>> {
+        let mut sta = [0];
+        self.mcp.read(REG_C1FIFOSTA1, &mut sta).await?;
+        if sta[0] & FIFOSTA_RXOVIF != 0 {
+            self.ch.overrun();
+            self.mcp.write(REG_C1FIFOSTA1, &[!FIFOSTA_RXOVIF]).await?;
+        }
+        // Read the frames received so far. The next ones interrupt again.
+        while sta[0] & FIFOSTA_TFNRFNIF != 0 {
+            if let Some(frame) = self.mcp.read_rx().await? {
+                self.ch.received(frame);
+            }
+            self.mcp.read(REG_C1FIFOSTA1, &mut sta).await?;
+        }
+
+        // Clear the error interrupt: the bus state is read below.
+        self.mcp.write(REG_C1INT + 1, &[!C1INT1_CERRIF]).await?;
+
+        self.update_tx().await?;
+        while !self.tx_full {
+            let Some(frame) = self.ch.try_tx() else { break };
+            self.transmit(&frame).await;
+        }
+
+        let mut trec = [0; 4];
+        self.mcp.read(REG_C1TREC, &mut trec).await?;
+        self.ch.set_bus_state(if trec[2] & TREC2_TXBO != 0 {
+            BusState::BusOff
+        } else if trec[2] & (TREC2_TXBP | TREC2_RXBP) != 0 {
+            BusState::ErrorPassive
+        } else if trec[2] & TREC2_EWARN != 0 {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        });
+        Ok(())
+    }
+
+    /// Update the state of the transmit queue, and its interrupts.
+    ///
+    /// The queue interrupts when it gets empty, once all the frames are sent, and when it gets
+    /// room for another frame while full.
+    async fn update_tx(&mut self) -> Result<(), Error<SPI::Error>> {
+        let mut sta = [0];
+        self.mcp.read(REG_C1TXQSTA, &mut sta).await?;
+        if sta[0] & FIFOSTA_TFERFFIF != 0 && self.tx_queued > 0 {
+            self.ch.sent(self.tx_queued);
+            self.tx_queued = 0;
+        }
+        self.tx_full = sta[0] & FIFOSTA_TFNRFNIF == 0;
+
+        let mut ie = 0;
+        if self.tx_queued > 0 {
+            ie |= FIFOCON_TFERFFIE;
+        }
+        if self.tx_full {
+            ie |= FIFOCON_TFNRFNIE;
+        }
+        self.mcp.write(REG_C1TXQCON, &[ie]).await
+    }
+
+    async fn transmit(&mut self, frame: &Frame) {
+        self.tx_queued += 1;
+        if self.mcp.load_tx(frame).await.is_err() {
+            warn!("transmit failed");
+            self.tx_queued -= 1;
+            self.ch.sent(1);
+        }
+        if self.update_tx().await.is_err() {
+            warn!("SPI error");
+        }
+    }
+}
+
+/// Obtain a driver for the MCP2518FD on `spi`, with its interrupt pin `int`.
+pub async fn new<'d, const N_RX: usize, SPI: SpiDevice, INT: Wait>(
+    config: Config,
+    state: &'d mut State<N_RX>,
+    spi: SPI,
+    int: INT,
+) -> Result<(Can<'d>, Runner<'d, SPI, INT>), Error<SPI::Error>> {
+    let (can, ch) = crate::new(state, true);
+    let mut mcp = Mcp2518fd { spi };
+    mcp.init(&config).await?;
+    let runner = Runner {
+        mcp,
+        ch,
+        int,
+        tx_queued: 0,
+        tx_full: false,
+    };
+    Ok((can, runner))
+}
@@ -0,0 +1,356 @@
+#![no_std]
+#![feature(async_fn_in_trait)]
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+mod router;
+
+pub use embedded_can::{ExtendedId, Id, StandardId};
+pub use router::{Route, Router};
+
+/// Maximum length of the data of a frame, for CAN FD.
+pub const MAX_DATA_LEN: usize = 64;
+
+/// A classic CAN or CAN FD frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    fd: bool,
+    brs: bool,
+    len: u8,
+    data: [u8; MAX_DATA_LEN],
+}
+
+impl Frame {
+    /// Create a classic CAN data frame.
+    ///
+    /// Returns `None` if `data` is longer than 8 bytes.
+    pub fn new_data(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+        Some(Self::new(id.into(), false, false, data))
+    }
+
+    /// Create a CAN FD data frame.
+    ///
+    /// When `brs` is set, the data is sent at the data bitrate of the controller. Returns `None`
+    /// if the length of `data` isn't a valid CAN FD length: 0 to 8, 12, 16, 20, 24, 32, 48 or 64
+    /// bytes.
+    pub fn new_fd(id: impl Into<Id>, data: &[u8], brs: bool) -> Option<Self> {
+        if !matches!(data.len(), 0..=8 | 12 | 16 | 20 | 24 | 32 | 48 | 64) {
+            return None;
+        }
+        Some(Self::new(id.into(), true, brs, data))
+    }
+
+    /// Create a classic CAN remote frame, requesting `dlc` bytes.
+    ///
+    /// Returns `None` if `dlc` is greater than 8.
+    pub fn new_remote(id: impl Into<Id>, dlc: u8) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+        let mut frame = Self::new(id.into(), false, false, &[]);
+        frame.remote = true;
+        frame.len = dlc;
+        Some(frame)
+    }
+
+    fn new(id: Id, fd: bool, brs: bool, data: &[u8]) -> Self {
+        let mut frame = Self {
+            id,
+            remote: false,
+            fd,
+            brs,
+            len: data.len() as u8,
+            data: [0; MAX_DATA_LEN],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        frame
+    }
+
+    /// Identifier of the frame.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Whether this is a remote frame.
+    pub fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    /// Whether this is a CAN FD frame.
+    pub fn is_fd(&self) -> bool {
+        self.fd
+    }
+
+    /// Whether the data of this CAN FD frame is sent at the data bitrate.
+    pub fn bit_rate_switching(&self) -> bool {
+        self.brs
+    }
+
+    /// Length of the data, or the requested length for a remote frame.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the length is 0.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Data of the frame. Empty for a remote frame.
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.len as usize]
+        }
+    }
+}
+
+impl embedded_can::Frame for Frame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        Self::new_data(id, data)
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        Self::new_remote(id, dlc.try_into().ok()?)
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        self.len as usize
+    }
+
+    fn data(&self) -> &[u8] {
+        Frame::data(self)
+    }
+}
+
+/// Acceptance filter: matches the frames whose identifier is equal to the one of the filter, on
+/// the bits set in the mask.
+///
+/// A filter matches either standard or extended identifiers, never both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    id: u32,
+    mask: u32,
+    extended: bool,
+}
+
+impl Filter {
+    /// Match the standard identifiers equal to `id` on the bits set in `mask`.
+    pub fn standard(id: StandardId, mask: u16) -> Self {
+        Self {
+            id: id.as_raw() as u32,
+            mask: (mask & StandardId::MAX.as_raw()) as u32,
+            extended: false,
+        }
+    }
+
+    /// Match the extended identifiers equal to `id` on the bits set in `mask`.
+    pub fn extended(id: ExtendedId, mask: u32) -> Self {
+        Self {
+            id: id.as_raw(),
+            mask: mask & ExtendedId::MAX.as_raw(),
+            extended: true,
+        }
+    }
+
+    /// Match exactly `id`.
+    pub fn exact(id: impl Into<Id>) -> Self {
+        match id.into() {
+            Id::Standard(id) => Self::standard(id, u16::MAX),
+            Id::Extended(id) => Self::extended(id, u32::MAX),
+        }
+    }
+
+    /// Match all the standard identifiers.
+    pub fn all_standard() -> Self {
+        Self::standard(StandardId::ZERO, 0)
+    }
+
+    /// Match all the extended identifiers.
+    pub fn all_extended() -> Self {
+        Self::extended(ExtendedId::ZERO, 0)
+    }
+
+    /// Whether this filter matches extended identifiers.
+    pub fn is_extended(&self) -> bool {
+        self.extended
+    }
+
+    /// Identifier of the filter: 11 bits for a standard one, 29 bits for an extended one.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Mask of the filter: 11 bits for a standard one, 29 bits for an extended one.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Whether `id` matches the filter.
+    pub fn matches(&self, id: Id) -> bool {
+        let (raw, extended) = match id {
+            Id::Standard(id) => (id.as_raw() as u32, false),
+            Id::Extended(id) => (id.as_raw(), true),
+        };
+        extended == self.extended && (raw ^ self.id) & self.mask == 0
+    }
+}
+
+/// More filters than the controller has were given to [`Filters::set_filters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TooManyFilters;
+
+/// Error state of the controller on the bus, from its error counters.
+///
+/// States are ordered from the best to the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusState {
+    /// Normal operation.
+    ErrorActive,
+    /// One of the error counters reached the warning limit of 96, with the controller still error
+    /// active.
+    ErrorWarning,
+    /// One of the error counters reached 128: the controller only signals errors passively.
+    ErrorPassive,
+    /// The transmit error counter went over 255: the controller doesn't take part in bus
+    /// communication anymore, until it recovers.
+    BusOff,
+}
+
+/// Kind of an error, or event, reported by a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Bit stuffing error.
+    Stuff,
+    /// Form error: a fixed-form field had an illegal value.
+    Form,
+    /// A transmitted frame was not acknowledged.
+    Acknowledge,
+    /// A bit was sent recessive, but read back dominant.
+    BitRecessive,
+    /// A bit was sent dominant, but read back recessive.
+    BitDominant,
+    /// CRC error.
+    Crc,
+    /// The controller became error warning.
+    ErrorWarning,
+    /// The controller became error passive.
+    ErrorPassive,
+    /// The controller went bus-off.
+    BusOff,
+    /// Received frames were lost, because the receive buffers were full.
+    Overrun,
+    /// Another error, specific to the controller.
+    Other,
+}
+
+/// Error of a controller.
+pub trait Error: core::fmt::Debug {
+    /// Kind of the error.
+    fn kind(&self) -> ErrorKind;
+}
+
+impl Error for ErrorKind {
+    fn kind(&self) -> ErrorKind {
+        *self
+    }
+}
+
+/// Error type of a controller.
+pub trait ErrorType {
+    /// Error type.
+    type Error: Error;
+}
+
+impl<T: ErrorType + ?Sized> ErrorType for &mut T {
+    type Error = T::Error;
+}
+
+/// Transmit side of a controller.
+pub trait Transmit: ErrorType {
+    /// Queue `frame` to be sent, waiting for room in the transmit queue.
+    ///
+    /// Queued frames are sent by priority, the lowest identifier first, like the arbitration on
+    /// the bus does: a frame may be sent before frames queued earlier with a higher identifier.
+    ///
+    /// Controllers without CAN FD support panic when given a CAN FD frame.
+    async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error>;
+
+    /// Wait until all the queued frames have been sent.
+    async fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T: Transmit + ?Sized> Transmit for &mut T {
+    async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+        T::transmit(self, frame).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        T::flush(self).await
+    }
+}
+
+/// Receive side of a controller.
+pub trait Receive: ErrorType {
+    /// Receive the next frame accepted by the filters.
+    ///
+    /// Errors are events, after which frames can still be received: lost frames are reported
+    /// once as [`ErrorKind::Overrun`], and changes of the bus state to a worse one as
+    /// [`ErrorKind::ErrorWarning`], [`ErrorKind::ErrorPassive`] or [`ErrorKind::BusOff`].
+    async fn receive(&mut self) -> Result<Frame, Self::Error>;
+}
+
+impl<T: Receive + ?Sized> Receive for &mut T {
+    async fn receive(&mut self) -> Result<Frame, Self::Error> {
+        T::receive(self).await
+    }
+}
+
+/// Acceptance filters of a controller.
+pub trait Filters {
+    /// Receive only the frames matching one of `filters`.
+    ///
+    /// No frames are received with an empty list: use [`Filter::all_standard`] and
+    /// [`Filter::all_extended`] to receive all of them.
+    fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters>;
+}
+
+impl<T: Filters + ?Sized> Filters for &mut T {
+    fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters> {
+        T::set_filters(self, filters)
+    }
+}
+
+/// Status of a controller.
+pub trait Status {
+    /// Current error state of the controller on the bus.
+    fn bus_state(&self) -> BusState;
+}
+
+impl<T: Status + ?Sized> Status for &mut T {
+    fn bus_state(&self) -> BusState {
+        T::bus_state(self)
+    }
+}
@@ -0,0 +1,64 @@
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::Channel;
+
+use crate::{Error, ErrorKind, Filter, Frame, Receive};
+
+/// A stream of received frames: the frames matching a filter, sent to a channel.
+pub struct Route<'a, M: RawMutex, const N: usize> {
+    filter: Filter,
+    channel: &'a Channel<M, Frame, N>,
+}
+
+impl<'a, M: RawMutex, const N: usize> Route<'a, M, N> {
+    /// Send the frames matching `filter` to `channel`.
+    pub const fn new(filter: Filter, channel: &'a Channel<M, Frame, N>) -> Self {
+        Self { filter, channel }
+    }
+
+    /// Filter of the route.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+}
+
+/// Dispatches the received frames to several streams, by identifier.
+///
+/// Each frame is sent to the first route matching it, and dropped if there's none. The frames
+/// for a route whose channel is full are dropped too, like frames are by controllers whose
+/// receive buffers are full.
+///
+/// The controller only needs to receive the frames of the routes: set its filters with
+/// [`Router::filters`].
+pub struct Router<'a, M: RawMutex, const N: usize> {
+    routes: &'a [Route<'a, M, N>],
+}
+
+impl<'a, M: RawMutex, const N: usize> Router<'a, M, N> {
+    /// Create a router dispatching frames to `routes`.
+    pub const fn new(routes: &'a [Route<'a, M, N>]) -> Self {
+        Self { routes }
+    }
+
+    /// Filters of the routes, in order.
+    pub fn filters(&self) -> impl Iterator<Item = Filter> + 'a {
+        self.routes.iter().map(|r| r.filter)
+    }
+
+    /// Dispatch the frames received from `rx`.
+    ///
+    /// Returns the first error other than an overrun, for the caller to handle, e.g. waiting for
+    /// the controller to recover from bus-off. Call `run` again to resume the dispatching.
+    pub async fn run<R: Receive>(&self, rx: &mut R) -> R::Error {
+        loop {
+            match rx.receive().await {
+                Ok(frame) => {
+                    if let Some(route) = self.routes.iter().find(|r| r.filter.matches(frame.id())) {
+                        let _ = route.channel.try_send(frame);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Overrun => {}
+                Err(e) => return e,
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use embassy_futures::block_on;
+
+/// Wrapper that implements blocking traits by blocking on async implementations.
+///
+/// This allows using async drivers where a blocking implementation is required, e.g. an
+/// external flash with an async driver in a bootloader. The operations busy-wait until the
+/// async operation completes, so the driver must not depend on the executor making progress.
+pub struct BlockOnAsync<T> {
+    wrapped: T,
+}
+
+impl<T> BlockOnAsync<T> {
+    /// Create a new instance of a wrapper for a given peripheral.
+    pub fn new(wrapped: T) -> Self {
+        Self { wrapped }
+    }
+}
+
+//
+// NOR flash implementations
+//
+use embedded_storage::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+use embedded_storage_async::nor_flash::{NorFlash as AsyncNorFlash, ReadNorFlash as AsyncReadNorFlash};
+
+impl<T> ErrorType for BlockOnAsync<T>
+where
+    T: ErrorType,
+{
+    type Error = T::Error;
+}
+
+impl<T> NorFlash for BlockOnAsync<T>
+where
+    T: AsyncNorFlash,
+{
+    const WRITE_SIZE: usize = <T as AsyncNorFlash>::WRITE_SIZE;
+    const ERASE_SIZE: usize = <T as AsyncNorFlash>::ERASE_SIZE;
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+        block_on(self.wrapped.write(offset, data))
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        block_on(self.wrapped.erase(from, to))
+    }
+}
+
+impl<T> ReadNorFlash for BlockOnAsync<T>
+where
+    T: AsyncReadNorFlash,
+{
+    const READ_SIZE: usize = <T as AsyncReadNorFlash>::READ_SIZE;
+
+    fn read(&mut self, address: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+        block_on(self.wrapped.read(address, data))
+    }
+
+    fn capacity(&self) -> usize {
+        self.wrapped.capacity()
+    }
+}
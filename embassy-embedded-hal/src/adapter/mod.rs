@@ -1,7 +1,9 @@
 //! Adapters between embedded-hal traits.
 
+mod block_on_async;
 mod blocking_async;
 mod yielding_async;
 
+pub use block_on_async::BlockOnAsync;
 pub use blocking_async::BlockingAsync;
 pub use yielding_async::YieldingAsync;
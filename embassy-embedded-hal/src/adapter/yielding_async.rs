@@ -4,14 +4,36 @@ use embassy_futures::yield_now;
 ///
 /// This can be used in combination with BlockingAsync<T> to enforce yields
 /// between long running blocking operations.
+///
+/// With a chunk size, the SPI reads and writes, and the NOR flash reads and writes, are also
+/// split in chunks of at most this many words or bytes, yielding after each, so that a long
+/// operation of a blocking driver doesn't starve the other tasks.
 pub struct YieldingAsync<T> {
     wrapped: T,
+    chunk_size: usize,
 }
 
 impl<T> YieldingAsync<T> {
     /// Create a new instance of a wrapper that yields after each operation.
     pub fn new(wrapped: T) -> Self {
-        Self { wrapped }
+        Self {
+            wrapped,
+            chunk_size: usize::MAX,
+        }
+    }
+
+    /// Create a new instance of a wrapper that yields after each chunk of `chunk_size` words or
+    /// bytes of an operation.
+    ///
+    /// The flash operations round the chunks down to a multiple of their read or write size.
+    pub fn with_chunk_size(wrapped: T, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0);
+        Self { wrapped, chunk_size }
+    }
+
+    /// Chunk size of an operation in units of `size` bytes.
+    fn chunk_size_in(&self, size: usize) -> usize {
+        core::cmp::max(self.chunk_size / size, 1) * size
     }
 }
 
@@ -80,14 +102,18 @@ where
     }
 
     async fn write(&mut self, data: &[Word]) -> Result<(), Self::Error> {
-        self.wrapped.write(data).await?;
-        yield_now().await;
+        for chunk in data.chunks(self.chunk_size) {
+            self.wrapped.write(chunk).await?;
+            yield_now().await;
+        }
         Ok(())
     }
 
     async fn read(&mut self, data: &mut [Word]) -> Result<(), Self::Error> {
-        self.wrapped.read(data).await?;
-        yield_now().await;
+        for chunk in data.chunks_mut(self.chunk_size) {
+            self.wrapped.read(chunk).await?;
+            yield_now().await;
+        }
         Ok(())
     }
 
@@ -98,8 +124,10 @@ where
     }
 
     async fn transfer_in_place(&mut self, words: &mut [Word]) -> Result<(), Self::Error> {
-        self.wrapped.transfer_in_place(words).await?;
-        yield_now().await;
+        for chunk in words.chunks_mut(self.chunk_size) {
+            self.wrapped.transfer_in_place(chunk).await?;
+            yield_now().await;
+        }
         Ok(())
     }
 }
@@ -117,7 +145,12 @@ impl<T: embedded_storage_async::nor_flash::ReadNorFlash> embedded_storage_async:
     const READ_SIZE: usize = T::READ_SIZE;
 
     async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        self.wrapped.read(offset, bytes).await?;
+        // Yield between each chunk
+        let chunk_size = self.chunk_size_in(T::READ_SIZE);
+        for (n, chunk) in bytes.chunks_mut(chunk_size).enumerate() {
+            self.wrapped.read(offset + (n * chunk_size) as u32, chunk).await?;
+            yield_now().await;
+        }
         Ok(())
     }
 
@@ -131,8 +164,12 @@ impl<T: embedded_storage_async::nor_flash::NorFlash> embedded_storage_async::nor
     const ERASE_SIZE: usize = T::ERASE_SIZE;
 
     async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.wrapped.write(offset, bytes).await?;
-        yield_now().await;
+        // Yield between each chunk
+        let chunk_size = self.chunk_size_in(T::WRITE_SIZE);
+        for (n, chunk) in bytes.chunks(chunk_size).enumerate() {
+            self.wrapped.write(offset + (n * chunk_size) as u32, chunk).await?;
+            yield_now().await;
+        }
         Ok(())
     }
 
@@ -166,4 +203,19 @@ mod tests {
         assert_eq!((0, 128), flash.erases[0]);
         assert_eq!((128, 256), flash.erases[1]);
     }
+
+    #[futures_test::test]
+    async fn can_write_in_chunks() {
+        let flash = MemFlash::<1024, 128, 4>::new(0x00);
+        let mut yielding = YieldingAsync::with_chunk_size(flash, 30);
+
+        yielding.write(64, &[0xaa; 64]).await.unwrap();
+
+        let flash = yielding.wrapped;
+        assert_eq!(3, flash.writes.len());
+        assert_eq!((64, 28), flash.writes[0]);
+        assert_eq!((92, 28), flash.writes[1]);
+        assert_eq!((120, 8), flash.writes[2]);
+        assert!(flash.mem[64..128].iter().all(|&b| b == 0xaa));
+    }
 }
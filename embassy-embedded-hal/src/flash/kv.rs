@@ -0,0 +1,435 @@
+//! Wear-leveled key-value store on a flash.
+//!
+//! The flash, usually a [`Partition`](super::partition::Partition), is split in two banks of
+//! whole erase sectors. Values are appended to the active bank as records, each holding a key, a
+//! length and a CRC: writing a key again appends a new record, and deleting it appends an empty
+//! one, so the flash is only erased when the bank is full. The latest value of each key is then
+//! copied to the other bank, which becomes the active one once complete.
+//!
+//! A record interrupted by a reset fails its CRC check and is ignored, the previous value of the
+//! key being kept, and the active bank stays valid until the compaction completes.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+/// Identifies a valid bank.
+const BANK_MAGIC: u32 = 0x4b56_5331;
+/// Length of the header of the records: key, length and CRC.
+const RECORD_HEADER_LEN: usize = 8;
+/// Length marking a deleted key.
+const DELETED: u16 = 0xfffe;
+/// Length of an erased record header.
+const ERASED: u16 = 0xffff;
+
+/// Key-value store error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The latest values don't leave room for this one.
+    Full,
+    /// The value doesn't fit in the record buffer of the store.
+    ValueTooLarge,
+    /// The value doesn't fit in the buffer it's read into.
+    BufferTooSmall,
+    /// Underlying flash error.
+    Flash(E),
+}
+
+/// Record read from the flash.
+#[derive(Clone, Copy)]
+struct Record {
+    key: u16,
+    /// Length of the value, `None` if the key is deleted.
+    len: Option<usize>,
+    /// Length of the record in the flash.
+    size: u32,
+    /// The CRC matches: the record is complete.
+    valid: bool,
+}
+
+enum Entry {
+    Record(Record),
+    /// The rest of the bank is erased.
+    End,
+    /// The rest of the bank can't be parsed, after an interrupted write.
+    Garbage,
+}
+
+/// Key-value store, on a flash split in two banks, with records of up to `B` bytes: an 8-byte
+/// header, and the value, rounded up to the write size of the flash.
+pub struct KvStore<F: NorFlash, const B: usize> {
+    flash: F,
+    /// Size of a bank.
+    bank_size: u32,
+    /// Active bank.
+    bank: u32,
+    /// Sequence number of the active bank, incremented on each compaction.
+    seq: u32,
+    /// Offset of the free space, after the records of the active bank.
+    free: u32,
+    buf: [u8; B],
+}
+
+impl<F: NorFlash, const B: usize> KvStore<F, B> {
+    /// Mount the store on `flash`, formatting it if it doesn't hold a valid bank.
+    pub async fn new(flash: F) -> Result<Self, Error<F::Error>> {
+        let bank_size = (flash.capacity() / 2) as u32;
+        assert!(bank_size > 0 && bank_size % F::ERASE_SIZE as u32 == 0);
+        assert!(F::WRITE_SIZE % F::READ_SIZE == 0);
+        assert!(B >= align(RECORD_HEADER_LEN, F::WRITE_SIZE));
+
+        let mut this = Self {
+            flash,
+            bank_size,
+            bank: 0,
+            seq: 0,
+            free: 0,
+            buf: [0; B],
+        };
+
+        let banks = [this.bank_seq(0).await?, this.bank_seq(1).await?];
+        match banks {
+            [Some(seq0), Some(seq1)] => {
+                let bank = if (seq1.wrapping_sub(seq0) as i32) > 0 { 1 } else { 0 };
+                this.bank = bank;
+                this.seq = banks[bank as usize].unwrap();
+            }
+            [Some(seq), None] => this.seq = seq,
+            [None, Some(seq)] => {
+                this.bank = 1;
+                this.seq = seq;
+            }
+            [None, None] => {
+                this.format().await?;
+                return Ok(this);
+            }
+        }
+
+        this.free = Self::header_len();
+        loop {
+            match this.entry_at(this.bank, this.free).await? {
+                Entry::Record(record) => this.free += record.size,
+                Entry::End => break,
+                // Nothing can be appended after garbage: compact on the next write.
+                Entry::Garbage => {
+                    this.free = bank_size;
+                    break;
+                }
+            }
+        }
+        Ok(this)
+    }
+
+    /// Erase all the values.
+    pub async fn format(&mut self) -> Result<(), Error<F::Error>> {
+        self.flash.erase(0, 2 * self.bank_size).await.map_err(Error::Flash)?;
+        self.bank = 0;
+        self.seq = 0;
+        self.write_bank_header(0, 0).await?;
+        self.free = Self::header_len();
+        Ok(())
+    }
+
+    /// Read the value of `key` into `buf`, returning its length, or `None` if it isn't set.
+    pub async fn read(&mut self, key: u16, buf: &mut [u8]) -> Result<Option<usize>, Error<F::Error>> {
+        let offset = match self.latest(self.bank, key).await? {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let record = match self.entry_at(self.bank, offset).await? {
+            Entry::Record(record) => record,
+            _ => unreachable!(),
+        };
+        let len = match record.len {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        if len > buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        buf[..len].copy_from_slice(&self.buf[RECORD_HEADER_LEN..][..len]);
+        Ok(Some(len))
+    }
+
+    /// Set the value of `key`.
+    pub async fn write(&mut self, key: u16, value: &[u8]) -> Result<(), Error<F::Error>> {
+        if value.len() >= DELETED as usize || align(RECORD_HEADER_LEN + value.len(), F::WRITE_SIZE) > B {
+            return Err(Error::ValueTooLarge);
+        }
+        self.append(key, Some(value)).await
+    }
+
+    /// Delete the value of `key`.
+    pub async fn delete(&mut self, key: u16) -> Result<(), Error<F::Error>> {
+        if self.latest(self.bank, key).await?.is_none() {
+            return Ok(());
+        }
+        self.append(key, None).await
+    }
+
+    /// Release the flash.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    const fn header_len() -> u32 {
+        align(RECORD_HEADER_LEN, F::WRITE_SIZE) as u32
+    }
+
+    async fn append(&mut self, key: u16, value: Option<&[u8]>) -> Result<(), Error<F::Error>> {
+        let len = value.map_or(0, |v| v.len());
+        let size = align(RECORD_HEADER_LEN + len, F::WRITE_SIZE) as u32;
+        if self.free + size > self.bank_size {
+            self.compact().await?;
+            if self.free + size > self.bank_size {
+                return Err(Error::Full);
+            }
+        }
+
+        let len_field = match value {
+            Some(value) => {
+                self.buf[RECORD_HEADER_LEN..][..value.len()].copy_from_slice(value);
+                value.len() as u16
+            }
+            None => DELETED,
+        };
+        self.buf[RECORD_HEADER_LEN + len..size as usize].fill(0xff);
+        self.buf[..2].copy_from_slice(&key.to_le_bytes());
+        self.buf[2..4].copy_from_slice(&len_field.to_le_bytes());
+        let crc = crc32(&self.buf[..4], &self.buf[RECORD_HEADER_LEN..][..len]);
+        self.buf[4..8].copy_from_slice(&crc.to_le_bytes());
+
+        let offset = self.bank * self.bank_size + self.free;
+        self.flash
+            .write(offset, &self.buf[..size as usize])
+            .await
+            .map_err(Error::Flash)?;
+        self.free += size;
+        Ok(())
+    }
+
+    /// Copy the latest values to the other bank, and make it the active one.
+    async fn compact(&mut self) -> Result<(), Error<F::Error>> {
+        let from = self.bank;
+        let to = 1 - from;
+        self.flash
+            .erase(to * self.bank_size, (to + 1) * self.bank_size)
+            .await
+            .map_err(Error::Flash)?;
+
+        let mut offset = Self::header_len();
+        let mut free = Self::header_len();
+        while let Entry::Record(record) = self.entry_at(from, offset).await? {
+            if record.valid && record.len.is_some() && self.latest(from, record.key).await? == Some(offset) {
+                // Read the record again, as looking for the latest one overwrote it.
+                self.entry_at(from, offset).await?;
+                self.flash
+                    .write(to * self.bank_size + free, &self.buf[..record.size as usize])
+                    .await
+                    .map_err(Error::Flash)?;
+                free += record.size;
+            }
+            offset += record.size;
+        }
+
+        // The bank becomes valid once complete.
+        let seq = self.seq.wrapping_add(1);
+        self.write_bank_header(to, seq).await?;
+        self.bank = to;
+        self.seq = seq;
+        self.free = free;
+        Ok(())
+    }
+
+    async fn write_bank_header(&mut self, bank: u32, seq: u32) -> Result<(), Error<F::Error>> {
+        let len = Self::header_len() as usize;
+        self.buf[..len].fill(0xff);
+        self.buf[..4].copy_from_slice(&BANK_MAGIC.to_le_bytes());
+        self.buf[4..8].copy_from_slice(&seq.to_le_bytes());
+        self.flash
+            .write(bank * self.bank_size, &self.buf[..len])
+            .await
+            .map_err(Error::Flash)?;
+        Ok(())
+    }
+
+    /// Sequence number of `bank`, or `None` if it isn't valid.
+    async fn bank_seq(&mut self, bank: u32) -> Result<Option<u32>, Error<F::Error>> {
+        let len = align(8, F::READ_SIZE);
+        self.flash
+            .read(bank * self.bank_size, &mut self.buf[..len])
+            .await
+            .map_err(Error::Flash)?;
+        if self.buf[..4] != BANK_MAGIC.to_le_bytes() {
+            return Ok(None);
+        }
+        Ok(Some(u32::from_le_bytes(self.buf[4..8].try_into().unwrap())))
+    }
+
+    /// Offset of the latest valid record of `key` in `bank`.
+    async fn latest(&mut self, bank: u32, key: u16) -> Result<Option<u32>, Error<F::Error>> {
+        let mut latest = None;
+        let mut offset = Self::header_len();
+        while let Entry::Record(record) = self.entry_at(bank, offset).await? {
+            if record.valid && record.key == key {
+                latest = Some(offset);
+            }
+            offset += record.size;
+        }
+        Ok(latest)
+    }
+
+    /// Read the record at `offset` of `bank` into the record buffer.
+    async fn entry_at(&mut self, bank: u32, offset: u32) -> Result<Entry, Error<F::Error>> {
+        let base = bank * self.bank_size;
+        let header_len = align(RECORD_HEADER_LEN, F::READ_SIZE);
+        if offset + header_len as u32 > self.bank_size {
+            return Ok(Entry::End);
+        }
+        self.flash
+            .read(base + offset, &mut self.buf[..header_len])
+            .await
+            .map_err(Error::Flash)?;
+
+        let key = u16::from_le_bytes([self.buf[0], self.buf[1]]);
+        let len_field = u16::from_le_bytes([self.buf[2], self.buf[3]]);
+        if key == 0xffff && len_field == ERASED {
+            return Ok(Entry::End);
+        }
+        let len = match len_field {
+            DELETED => None,
+            ERASED => return Ok(Entry::Garbage),
+            len => Some(len as usize),
+        };
+        let size = align(RECORD_HEADER_LEN + len.unwrap_or(0), F::WRITE_SIZE);
+        if size > B || offset + size as u32 > self.bank_size {
+            return Ok(Entry::Garbage);
+        }
+
+        self.flash
+            .read(base + offset, &mut self.buf[..size])
+            .await
+            .map_err(Error::Flash)?;
+        let crc = u32::from_le_bytes(self.buf[4..8].try_into().unwrap());
+        let valid = crc == crc32(&self.buf[..4], &self.buf[RECORD_HEADER_LEN..][..len.unwrap_or(0)]);
+        Ok(Entry::Record(Record {
+            key,
+            len,
+            size: size as u32,
+            valid,
+        }))
+    }
+}
+
+const fn align(len: usize, size: usize) -> usize {
+    (len + size - 1) / size * size
+}
+
+/// CRC-32 (IEEE) of the record header and value.
+fn crc32(header: &[u8], value: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in header.iter().chain(value) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::mem_flash::MemFlash;
+
+    type Flash = MemFlash<1024, 256, 4>;
+
+    #[futures_test::test]
+    async fn can_write_and_read() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        let mut buf = [0; 16];
+
+        assert_eq!(None, store.read(1, &mut buf).await.unwrap());
+        store.write(1, b"hello").await.unwrap();
+        store.write(2, b"world!").await.unwrap();
+        store.write(1, b"again").await.unwrap();
+
+        assert_eq!(Some(5), store.read(1, &mut buf).await.unwrap());
+        assert_eq!(b"again", &buf[..5]);
+        assert_eq!(Some(6), store.read(2, &mut buf).await.unwrap());
+        assert_eq!(b"world!", &buf[..6]);
+        assert_eq!(Err(Error::BufferTooSmall), store.read(2, &mut buf[..4]).await);
+    }
+
+    #[futures_test::test]
+    async fn can_delete() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        let mut buf = [0; 16];
+
+        store.write(1, b"hello").await.unwrap();
+        store.delete(1).await.unwrap();
+
+        assert_eq!(None, store.read(1, &mut buf).await.unwrap());
+    }
+
+    #[futures_test::test]
+    async fn keeps_values_when_remounted() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        store.write(1, b"hello").await.unwrap();
+        store.write(2, b"world").await.unwrap();
+
+        let mut store = KvStore::<_, 64>::new(store.into_inner()).await.unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(Some(5), store.read(1, &mut buf).await.unwrap());
+        assert_eq!(b"hello", &buf[..5]);
+
+        store.write(3, b"!").await.unwrap();
+        assert_eq!(Some(1), store.read(3, &mut buf).await.unwrap());
+    }
+
+    #[futures_test::test]
+    async fn compacts_when_full() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        let mut buf = [0; 16];
+
+        for n in 0..100u32 {
+            store.write(1, &n.to_le_bytes()).await.unwrap();
+            store.write(2, b"constant").await.unwrap();
+        }
+
+        let flash = store.into_inner();
+        assert!(flash.erases.len() > 2);
+
+        let mut store = KvStore::<_, 64>::new(flash).await.unwrap();
+        assert_eq!(Some(4), store.read(1, &mut buf).await.unwrap());
+        assert_eq!(99u32.to_le_bytes(), buf[..4]);
+        assert_eq!(Some(8), store.read(2, &mut buf).await.unwrap());
+        assert_eq!(b"constant", &buf[..8]);
+    }
+
+    #[futures_test::test]
+    async fn ignores_interrupted_writes() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        store.write(1, b"hello").await.unwrap();
+        store.write(1, b"world").await.unwrap();
+
+        // Corrupt the last record, as if its write was interrupted.
+        let mut flash = store.into_inner();
+        flash.mem[8 + 16 + 12] = 0xff;
+
+        let mut store = KvStore::<_, 64>::new(flash).await.unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(Some(5), store.read(1, &mut buf).await.unwrap());
+        assert_eq!(b"hello", &buf[..5]);
+    }
+
+    #[futures_test::test]
+    async fn rejects_too_large_values() {
+        let mut store = KvStore::<_, 64>::new(Flash::default()).await.unwrap();
+        assert_eq!(Err(Error::ValueTooLarge), store.write(1, &[0; 57]).await);
+        store.write(1, &[0; 56]).await.unwrap();
+    }
+}
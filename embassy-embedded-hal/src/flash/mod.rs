@@ -1,6 +1,8 @@
 //! Utilities related to flash.
 
 mod concat_flash;
+#[cfg(feature = "nightly")]
+pub mod kv;
 #[cfg(test)]
 pub(crate) mod mem_flash;
 pub mod partition;
@@ -40,6 +40,13 @@ impl<'a, M: RawMutex, T: NorFlash> Partition<'a, M, T> {
     pub const fn size(&self) -> u32 {
         self.size
     }
+
+    fn contains(&self, offset: u32, len: usize) -> bool {
+        u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .map_or(false, |end| end <= self.size)
+    }
 }
 
 impl<M: RawMutex, T: NorFlash> ErrorType for Partition<'_, M, T> {
@@ -50,7 +57,7 @@ impl<M: RawMutex, T: NorFlash> ReadNorFlash for Partition<'_, M, T> {
     const READ_SIZE: usize = T::READ_SIZE;
 
     async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        if offset + bytes.len() as u32 > self.size {
+        if !self.contains(offset, bytes.len()) {
             return Err(Error::OutOfBounds);
         }
 
@@ -68,7 +75,7 @@ impl<M: RawMutex, T: NorFlash> NorFlash for Partition<'_, M, T> {
     const ERASE_SIZE: usize = T::ERASE_SIZE;
 
     async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        if offset + bytes.len() as u32 > self.size {
+        if !self.contains(offset, bytes.len()) {
             return Err(Error::OutOfBounds);
         }
 
@@ -77,7 +84,7 @@ impl<M: RawMutex, T: NorFlash> NorFlash for Partition<'_, M, T> {
     }
 
     async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-        if to > self.size {
+        if from > to || to > self.size {
             return Err(Error::OutOfBounds);
         }
 
@@ -136,4 +143,32 @@ mod tests {
         let flash = flash.try_lock().unwrap();
         assert!(flash.mem[128..256].iter().position(|&x| x != 0xFF).is_none());
     }
+
+    #[futures_test::test]
+    async fn rejects_out_of_bounds() {
+        let flash = MemFlash::<1024, 128, 4>::default();
+
+        let flash = Mutex::<NoopRawMutex, _>::new(flash);
+        let mut partition = Partition::new(&flash, 128, 256);
+
+        let mut read_buf = [0; 8];
+        assert!(matches!(
+            partition.read(252, &mut read_buf).await,
+            Err(Error::OutOfBounds)
+        ));
+        assert!(matches!(
+            partition.read(u32::MAX - 3, &mut read_buf).await,
+            Err(Error::OutOfBounds)
+        ));
+        assert!(matches!(
+            partition.write(256, &[0xAA; 4]).await,
+            Err(Error::OutOfBounds)
+        ));
+        assert!(matches!(partition.erase(128, 0).await, Err(Error::OutOfBounds)));
+        assert!(matches!(partition.erase(0, 384).await, Err(Error::OutOfBounds)));
+
+        let flash = flash.try_lock().unwrap();
+        assert!(flash.writes.is_empty());
+        assert!(flash.erases.is_empty());
+    }
 }
@@ -41,6 +41,13 @@ impl<'a, M: RawMutex, T: NorFlash> BlockingPartition<'a, M, T> {
     pub const fn size(&self) -> u32 {
         self.size
     }
+
+    fn contains(&self, offset: u32, len: usize) -> bool {
+        u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .map_or(false, |end| end <= self.size)
+    }
 }
 
 impl<M: RawMutex, T: NorFlash> ErrorType for BlockingPartition<'_, M, T> {
@@ -51,7 +58,7 @@ impl<M: RawMutex, T: NorFlash> ReadNorFlash for BlockingPartition<'_, M, T> {
     const READ_SIZE: usize = T::READ_SIZE;
 
     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        if offset + bytes.len() as u32 > self.size {
+        if !self.contains(offset, bytes.len()) {
             return Err(Error::OutOfBounds);
         }
 
@@ -73,7 +80,7 @@ impl<M: RawMutex, T: NorFlash> NorFlash for BlockingPartition<'_, M, T> {
     const ERASE_SIZE: usize = T::ERASE_SIZE;
 
     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        if offset + bytes.len() as u32 > self.size {
+        if !self.contains(offset, bytes.len()) {
             return Err(Error::OutOfBounds);
         }
 
@@ -86,7 +93,7 @@ impl<M: RawMutex, T: NorFlash> NorFlash for BlockingPartition<'_, M, T> {
     }
 
     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-        if to > self.size {
+        if from > to || to > self.size {
             return Err(Error::OutOfBounds);
         }
 
@@ -146,4 +153,26 @@ mod tests {
         let flash = flash.into_inner().take();
         assert!(flash.mem[128..256].iter().position(|&x| x != 0xFF).is_none());
     }
+
+    #[test]
+    fn rejects_out_of_bounds() {
+        let flash = MemFlash::<1024, 128, 4>::default();
+
+        let flash = Mutex::<NoopRawMutex, _>::new(RefCell::new(flash));
+        let mut partition = BlockingPartition::new(&flash, 128, 256);
+
+        let mut read_buf = [0; 8];
+        assert!(matches!(partition.read(252, &mut read_buf), Err(Error::OutOfBounds)));
+        assert!(matches!(
+            partition.read(u32::MAX - 3, &mut read_buf),
+            Err(Error::OutOfBounds)
+        ));
+        assert!(matches!(partition.write(256, &[0xAA; 4]), Err(Error::OutOfBounds)));
+        assert!(matches!(partition.erase(128, 0), Err(Error::OutOfBounds)));
+        assert!(matches!(partition.erase(0, 384), Err(Error::OutOfBounds)));
+
+        let flash = flash.into_inner().take();
+        assert!(flash.writes.is_empty());
+        assert!(flash.erases.is_empty());
+    }
 }
@@ -26,6 +26,11 @@ pub trait SetConfig {
     /// The configuration type used by this driver.
     type Config;
 
+    /// The error returned when the configuration isn't supported.
+    type ConfigError;
+
     /// Set the configuration of the driver.
-    fn set_config(&mut self, config: &Self::Config);
+    ///
+    /// The driver is left unchanged if the configuration isn't supported.
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), Self::ConfigError>;
 }
@@ -25,6 +25,8 @@
 
 use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::mutex::Mutex;
+#[cfg(feature = "time")]
+use embassy_time::{with_timeout, Duration, TimeoutError};
 use embedded_hal_async::i2c;
 
 use crate::shared_bus::I2cDeviceError;
@@ -125,14 +127,14 @@ where
 {
     async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
         let mut bus = self.bus.lock().await;
-        bus.set_config(&self.config);
+        bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
         bus.read(address, buffer).await.map_err(I2cDeviceError::I2c)?;
         Ok(())
     }
 
     async fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
         let mut bus = self.bus.lock().await;
-        bus.set_config(&self.config);
+        bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
         bus.write(address, bytes).await.map_err(I2cDeviceError::I2c)?;
         Ok(())
     }
@@ -144,7 +146,7 @@ where
         rd_buffer: &mut [u8],
     ) -> Result<(), I2cDeviceError<BUS::Error>> {
         let mut bus = self.bus.lock().await;
-        bus.set_config(&self.config);
+        bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
         bus.write_read(address, wr_buffer, rd_buffer)
             .await
             .map_err(I2cDeviceError::I2c)?;
@@ -153,10 +155,218 @@ where
 
     async fn transaction(&mut self, address: u8, operations: &mut [i2c::Operation<'_>]) -> Result<(), Self::Error> {
         let mut bus = self.bus.lock().await;
-        bus.set_config(&self.config);
+        bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
         bus.transaction(address, operations)
             .await
             .map_err(I2cDeviceError::I2c)?;
         Ok(())
     }
 }
+
+/// I2C device behind a channel of a TCA9548-style multiplexer, on a shared bus.
+///
+/// The channel is selected, by writing its bit to the control register of the multiplexer,
+/// before each operation, in the same lock of the bus: devices of the same address behind
+/// different channels, or different multiplexers, can then be used like any other device.
+pub struct I2cDeviceOnMux<'a, M: RawMutex, BUS> {
+    bus: &'a Mutex<M, BUS>,
+    mux_address: u8,
+    channel: u8,
+}
+
+impl<'a, M: RawMutex, BUS> I2cDeviceOnMux<'a, M, BUS> {
+    /// Create a new `I2cDeviceOnMux`, behind `channel`, from 0 to 7, of the multiplexer at
+    /// `mux_address`.
+    pub fn new(bus: &'a Mutex<M, BUS>, mux_address: u8, channel: u8) -> Self {
+        assert!(channel < 8);
+        Self {
+            bus,
+            mux_address,
+            channel,
+        }
+    }
+}
+
+impl<'a, M: RawMutex, BUS> i2c::ErrorType for I2cDeviceOnMux<'a, M, BUS>
+where
+    BUS: i2c::ErrorType,
+{
+    type Error = I2cDeviceError<BUS::Error>;
+}
+
+impl<M, BUS> i2c::I2c for I2cDeviceOnMux<'_, M, BUS>
+where
+    M: RawMutex + 'static,
+    BUS: i2c::I2c + 'static,
+{
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        bus.write(self.mux_address, &[1 << self.channel])
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        bus.read(address, read).await.map_err(I2cDeviceError::I2c)?;
+        Ok(())
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        bus.write(self.mux_address, &[1 << self.channel])
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        bus.write(address, write).await.map_err(I2cDeviceError::I2c)?;
+        Ok(())
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        bus.write(self.mux_address, &[1 << self.channel])
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        bus.write_read(address, write, read)
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        Ok(())
+    }
+
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        bus.write(self.mux_address, &[1 << self.channel])
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        bus.transaction(address, operations)
+            .await
+            .map_err(I2cDeviceError::I2c)?;
+        Ok(())
+    }
+}
+
+/// Recovery of a stuck I2C bus, such as clocking SCL until the devices release SDA, or resetting
+/// the peripheral.
+#[cfg(feature = "time")]
+pub trait I2cBusRecovery<BUS> {
+    /// Recover `bus`.
+    async fn recover(&mut self, bus: &mut BUS);
+}
+
+#[cfg(feature = "time")]
+impl<BUS, F: FnMut(&mut BUS)> I2cBusRecovery<BUS> for F {
+    async fn recover(&mut self, bus: &mut BUS) {
+        self(bus)
+    }
+}
+
+/// I2C device on a shared bus, with a timeout on its operations and a recovery of the bus.
+///
+/// This is like [`I2cDevice`], but an operation on the bus taking longer than the timeout is
+/// cancelled, and fails with [`I2cDeviceError::Timeout`]. The bus is then recovered, as it is
+/// after `max_errors` operations in a row failing with a bus error, a loss of arbitration or a
+/// missing acknowledge, so that a stuck device doesn't block the other devices of the bus.
+#[cfg(feature = "time")]
+pub struct I2cDeviceWithTimeout<'a, M: RawMutex, BUS, R> {
+    bus: &'a Mutex<M, BUS>,
+    timeout: Duration,
+    max_errors: u8,
+    errors: u8,
+    recovery: R,
+}
+
+#[cfg(feature = "time")]
+impl<'a, M: RawMutex, BUS, R: I2cBusRecovery<BUS>> I2cDeviceWithTimeout<'a, M, BUS, R> {
+    /// Create a new `I2cDeviceWithTimeout`.
+    pub fn new(bus: &'a Mutex<M, BUS>, timeout: Duration, max_errors: u8, recovery: R) -> Self {
+        Self {
+            bus,
+            timeout,
+            max_errors,
+            errors: 0,
+            recovery,
+        }
+    }
+
+    /// Count the errors of an operation, and recover the bus if needed.
+    async fn check<E: i2c::Error>(
+        &mut self,
+        bus: &mut BUS,
+        result: Result<Result<(), E>, TimeoutError>,
+    ) -> Result<(), I2cDeviceError<E>> {
+        let error = match result {
+            Ok(Ok(())) => {
+                self.errors = 0;
+                return Ok(());
+            }
+            Ok(Err(e)) => match e.kind() {
+                i2c::ErrorKind::Bus | i2c::ErrorKind::ArbitrationLoss | i2c::ErrorKind::NoAcknowledge(_) => {
+                    self.errors = self.errors.saturating_add(1);
+                    if self.errors < self.max_errors {
+                        return Err(I2cDeviceError::I2c(e));
+                    }
+                    I2cDeviceError::I2c(e)
+                }
+                _ => return Err(I2cDeviceError::I2c(e)),
+            },
+            Err(TimeoutError) => I2cDeviceError::Timeout,
+        };
+
+        self.errors = 0;
+        self.recovery.recover(bus).await;
+        Err(error)
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'a, M: RawMutex, BUS, R> i2c::ErrorType for I2cDeviceWithTimeout<'a, M, BUS, R>
+where
+    BUS: i2c::ErrorType,
+{
+    type Error = I2cDeviceError<BUS::Error>;
+}
+
+#[cfg(feature = "time")]
+impl<M, BUS, R> i2c::I2c for I2cDeviceWithTimeout<'_, M, BUS, R>
+where
+    M: RawMutex + 'static,
+    BUS: i2c::I2c + 'static,
+    R: I2cBusRecovery<BUS>,
+{
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        let result = with_timeout(self.timeout, bus.read(address, read)).await;
+        self.check(&mut bus, result).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        let result = with_timeout(self.timeout, bus.write(address, write)).await;
+        self.check(&mut bus, result).await
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        let result = with_timeout(self.timeout, bus.write_read(address, write, read)).await;
+        self.check(&mut bus, result).await
+    }
+
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), I2cDeviceError<BUS::Error>> {
+        let mut bus = self.bus.lock().await;
+        let result = with_timeout(self.timeout, bus.transaction(address, operations)).await;
+        self.check(&mut bus, result).await
+    }
+}
@@ -115,6 +115,80 @@ where
     }
 }
 
+/// I2C device behind a channel of a TCA9548-style multiplexer, on a shared bus.
+///
+/// The channel is selected, by writing its bit to the control register of the multiplexer,
+/// before each operation, in the same lock of the bus: devices of the same address behind
+/// different channels, or different multiplexers, can then be used like any other device.
+pub struct I2cDeviceOnMux<'a, M: RawMutex, BUS> {
+    bus: &'a Mutex<M, RefCell<BUS>>,
+    mux_address: u8,
+    channel: u8,
+}
+
+impl<'a, M: RawMutex, BUS> I2cDeviceOnMux<'a, M, BUS> {
+    /// Create a new `I2cDeviceOnMux`, behind `channel`, from 0 to 7, of the multiplexer at
+    /// `mux_address`.
+    pub fn new(bus: &'a Mutex<M, RefCell<BUS>>, mux_address: u8, channel: u8) -> Self {
+        assert!(channel < 8);
+        Self {
+            bus,
+            mux_address,
+            channel,
+        }
+    }
+}
+
+impl<'a, M: RawMutex, BUS> ErrorType for I2cDeviceOnMux<'a, M, BUS>
+where
+    BUS: ErrorType,
+{
+    type Error = I2cDeviceError<BUS::Error>;
+}
+
+impl<M, BUS> I2c for I2cDeviceOnMux<'_, M, BUS>
+where
+    M: RawMutex,
+    BUS: I2c,
+{
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.lock(|bus| {
+            let mut bus = bus.borrow_mut();
+            bus.write(self.mux_address, &[1 << self.channel])
+                .map_err(I2cDeviceError::I2c)?;
+            bus.read(address, buffer).map_err(I2cDeviceError::I2c)
+        })
+    }
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus.lock(|bus| {
+            let mut bus = bus.borrow_mut();
+            bus.write(self.mux_address, &[1 << self.channel])
+                .map_err(I2cDeviceError::I2c)?;
+            bus.write(address, bytes).map_err(I2cDeviceError::I2c)
+        })
+    }
+
+    fn write_read(&mut self, address: u8, wr_buffer: &[u8], rd_buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.lock(|bus| {
+            let mut bus = bus.borrow_mut();
+            bus.write(self.mux_address, &[1 << self.channel])
+                .map_err(I2cDeviceError::I2c)?;
+            bus.write_read(address, wr_buffer, rd_buffer)
+                .map_err(I2cDeviceError::I2c)
+        })
+    }
+
+    fn transaction<'a>(&mut self, address: u8, operations: &mut [Operation<'a>]) -> Result<(), Self::Error> {
+        self.bus.lock(|bus| {
+            let mut bus = bus.borrow_mut();
+            bus.write(self.mux_address, &[1 << self.channel])
+                .map_err(I2cDeviceError::I2c)?;
+            bus.transaction(address, operations).map_err(I2cDeviceError::I2c)
+        })
+    }
+}
+
 /// I2C device on a shared bus, with its own configuration.
 ///
 /// This is like [`I2cDevice`], with an additional bus configuration that's applied
@@ -148,7 +222,7 @@ where
     fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
         self.bus.lock(|bus| {
             let mut bus = bus.borrow_mut();
-            bus.set_config(&self.config);
+            bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
             bus.read(address, buffer).map_err(I2cDeviceError::I2c)
         })
     }
@@ -156,7 +230,7 @@ where
     fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
         self.bus.lock(|bus| {
             let mut bus = bus.borrow_mut();
-            bus.set_config(&self.config);
+            bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
             bus.write(address, bytes).map_err(I2cDeviceError::I2c)
         })
     }
@@ -164,7 +238,7 @@ where
     fn write_read(&mut self, address: u8, wr_buffer: &[u8], rd_buffer: &mut [u8]) -> Result<(), Self::Error> {
         self.bus.lock(|bus| {
             let mut bus = bus.borrow_mut();
-            bus.set_config(&self.config);
+            bus.set_config(&self.config).map_err(|_| I2cDeviceError::Config)?;
             bus.write_read(address, wr_buffer, rd_buffer)
                 .map_err(I2cDeviceError::I2c)
         })
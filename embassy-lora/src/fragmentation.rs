@@ -0,0 +1,369 @@
+//! LoRaWAN Fragmented Data Block Transport (TS004 v1.0.0), the transport of firmware updates
+//! over the air.
+//!
+//! The fragments of a data block are received as downlinks on [`FPORT`], and written to a
+//! [`FragmentSink`], such as the DFU partition of an embassy-boot `FirmwareUpdater`. The answers
+//! to the requests of the server are sent back as uplinks on the same port.
+//!
+//! Only the uncoded fragments are written: the redundancy fragments are ignored, so the server
+//! must send the fragments reported missing again.
+//!
+//! The multicast sessions that usually carry the fragments, and their keys, are handled by the
+//! LoRaWAN device, which doesn't support them yet: the fragments can also be sent to each device
+//! on its unicast session.
+
+/// Port of the fragmentation messages.
+pub const FPORT: u8 = 201;
+
+const PACKAGE_IDENTIFIER: u8 = 3;
+const PACKAGE_VERSION: u8 = 1;
+
+const PACKAGE_VERSION_REQ: u8 = 0x00;
+const FRAG_SESSION_STATUS_REQ: u8 = 0x01;
+const FRAG_SESSION_SETUP_REQ: u8 = 0x02;
+const FRAG_SESSION_DELETE_REQ: u8 = 0x03;
+const DATA_FRAGMENT: u8 = 0x08;
+
+/// Destination of the fragments of a data block.
+pub trait FragmentSink {
+    /// Error of the writes.
+    type Error;
+
+    /// Write the fragment `data` at `offset` in the data block.
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Fragmentation error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// A message was too short for its command.
+    Malformed,
+    /// The sink failed to write a fragment.
+    Sink(E),
+}
+
+/// Outcome of a downlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Outcome {
+    /// Bytes of the answers at the start of the response buffer, to send as an uplink on
+    /// [`FPORT`] if not 0.
+    pub response_len: usize,
+    /// Set when the data block is complete: its size, and the descriptor set by the server.
+    pub complete: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    nb_frag: u16,
+    frag_size: u8,
+    /// Size of the data block, without the padding of the last fragment.
+    size: u32,
+    descriptor: u32,
+    received: u16,
+    complete: bool,
+}
+
+/// Fragmentation session, receiving data blocks of up to `8 * M` fragments.
+///
+/// Only the session of index 0 is supported.
+pub struct Fragmentation<S: FragmentSink, const M: usize> {
+    sink: S,
+    session: Option<Session>,
+    /// Bitmap of the received uncoded fragments.
+    fragments: [u8; M],
+}
+
+impl<S: FragmentSink, const M: usize> Fragmentation<S, M> {
+    /// Create a fragmentation session writing the fragments to `sink`, without a data block
+    /// until the server sets one up.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            session: None,
+            fragments: [0; M],
+        }
+    }
+
+    /// The sink, to use the data block once complete.
+    pub fn sink(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Handle a downlink received on [`FPORT`], writing the answers to `response`, which should
+    /// hold at least the 5 bytes of an answer: the answers that don't fit are dropped.
+    pub async fn handle(&mut self, payload: &[u8], response: &mut [u8]) -> Result<Outcome, Error<S::Error>> {
+        let mut outcome = Outcome::default();
+        let mut rest = payload;
+        while let Some((&cid, params)) = rest.split_first() {
+            let mut answer = [0u8; 5];
+            let (len, used) = match cid {
+                PACKAGE_VERSION_REQ => {
+                    answer[..3].copy_from_slice(&[cid, PACKAGE_IDENTIFIER, PACKAGE_VERSION]);
+                    (3, 0)
+                }
+                FRAG_SESSION_STATUS_REQ => {
+                    let param = *params.first().ok_or(Error::Malformed)?;
+                    (self.status(param, &mut answer), 1)
+                }
+                FRAG_SESSION_SETUP_REQ => {
+                    let params = params.get(..10).ok_or(Error::Malformed)?;
+                    (self.setup(params, &mut answer), 10)
+                }
+                FRAG_SESSION_DELETE_REQ => {
+                    let param = *params.first().ok_or(Error::Malformed)?;
+                    (self.delete(param, &mut answer), 1)
+                }
+                DATA_FRAGMENT => {
+                    // The fragment takes the rest of the message.
+                    let header = params.get(..2).ok_or(Error::Malformed)?;
+                    let index_and_n = u16::from_le_bytes([header[0], header[1]]);
+                    outcome.complete = self.fragment(index_and_n, &params[2..]).await?;
+                    (0, params.len())
+                }
+                // Unknown command: the rest of the message can't be parsed.
+                _ => break,
+            };
+
+            if outcome.response_len + len <= response.len() {
+                response[outcome.response_len..][..len].copy_from_slice(&answer[..len]);
+                outcome.response_len += len;
+            }
+            rest = &params[used..];
+        }
+        Ok(outcome)
+    }
+
+    fn status(&self, param: u8, answer: &mut [u8; 5]) -> usize {
+        let participants = param & 0x01 != 0;
+        let index = (param >> 1) & 0x03;
+        let session = match self.session {
+            Some(session) if index == 0 => session,
+            _ => return 0,
+        };
+        let missing = session.nb_frag - session.received;
+        // Only the devices missing fragments answer, unless all are asked to.
+        if !participants && missing == 0 {
+            return 0;
+        }
+        let received_and_index = session.received & 0x3fff | (index as u16) << 14;
+        answer[0] = FRAG_SESSION_STATUS_REQ;
+        answer[1..3].copy_from_slice(&received_and_index.to_le_bytes());
+        answer[3] = missing.min(255) as u8;
+        // Status: the matrix memory is never short, as redundancy isn't decoded.
+        answer[4] = 0;
+        5
+    }
+
+    fn setup(&mut self, params: &[u8], answer: &mut [u8; 5]) -> usize {
+        let index = (params[0] >> 4) & 0x03;
+        let nb_frag = u16::from_le_bytes([params[1], params[2]]);
+        let frag_size = params[3];
+        let padding = params[5] as u32;
+        let size = (nb_frag as u32 * frag_size as u32).checked_sub(padding);
+        let descriptor = u32::from_le_bytes([params[6], params[7], params[8], params[9]]);
+
+        let mut status = 0;
+        if (params[4] >> 3) & 0x07 != 0 || size.is_none() {
+            // Encoding unsupported, or more padding than data.
+            status |= 1 << 0;
+        }
+        if (nb_frag as usize) > 8 * M {
+            // Not enough memory.
+            status |= 1 << 1;
+        }
+        if index != 0 {
+            // FragSession index not supported.
+            status |= 1 << 2;
+        }
+        if let (0, Some(size)) = (status, size) {
+            self.session = Some(Session {
+                nb_frag,
+                frag_size,
+                size,
+                descriptor,
+                received: 0,
+                complete: false,
+            });
+            self.fragments = [0; M];
+        }
+
+        answer[0] = FRAG_SESSION_SETUP_REQ;
+        answer[1] = status | index << 6;
+        2
+    }
+
+    fn delete(&mut self, param: u8, answer: &mut [u8; 5]) -> usize {
+        let index = param & 0x03;
+        let mut status = index;
+        if index == 0 && self.session.is_some() {
+            self.session = None;
+        } else {
+            // Session does not exist.
+            status |= 1 << 2;
+        }
+        answer[0] = FRAG_SESSION_DELETE_REQ;
+        answer[1] = status;
+        2
+    }
+
+    async fn fragment(&mut self, index_and_n: u16, data: &[u8]) -> Result<Option<(u32, u32)>, Error<S::Error>> {
+        let n = index_and_n & 0x3fff;
+        let index = index_and_n >> 14;
+        let session = match &mut self.session {
+            Some(session) if index == 0 && !session.complete => session,
+            _ => return Ok(None),
+        };
+        if n == 0 || data.len() != session.frag_size as usize {
+            return Err(Error::Malformed);
+        }
+
+        if n > session.nb_frag {
+            // Redundancy fragment.
+            return Ok(None);
+        }
+
+        let bit = (n - 1) as usize;
+        if self.fragments[bit / 8] & (1 << (bit % 8)) != 0 {
+            return Ok(None);
+        }
+
+        let offset = bit as u32 * session.frag_size as u32;
+        self.sink.write(offset, data).await.map_err(Error::Sink)?;
+        self.fragments[bit / 8] |= 1 << (bit % 8);
+        session.received += 1;
+
+        if session.received < session.nb_frag {
+            return Ok(None);
+        }
+        session.complete = true;
+        Ok(Some((session.size, session.descriptor)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    const FRAG_SIZE: usize = 4;
+    const DESCRIPTOR: u32 = 0x1234_5678;
+
+    struct MemSink {
+        data: [u8; 64],
+    }
+
+    impl FragmentSink for MemSink {
+        type Error = Infallible;
+
+        async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+            self.data[offset as usize..][..data.len()].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    fn handle<const M: usize>(frag: &mut Fragmentation<MemSink, M>, payload: &[u8], response: &mut [u8]) -> Outcome {
+        block_on(frag.handle(payload, response)).unwrap()
+    }
+
+    /// Set up a session of `nb_frag` fragments, returning the setup answer.
+    fn setup<const M: usize>(frag: &mut Fragmentation<MemSink, M>, nb_frag: u16, padding: u8) -> [u8; 2] {
+        let mut req = [0; 11];
+        req[0] = FRAG_SESSION_SETUP_REQ;
+        req[2..4].copy_from_slice(&nb_frag.to_le_bytes());
+        req[4] = FRAG_SIZE as u8;
+        req[6] = padding;
+        req[7..].copy_from_slice(&DESCRIPTOR.to_le_bytes());
+        let mut response = [0; 8];
+        let outcome = handle(frag, &req, &mut response);
+        assert_eq!(2, outcome.response_len);
+        [response[0], response[1]]
+    }
+
+    /// Send fragment `n`, filled with `n`.
+    fn fragment<const M: usize>(frag: &mut Fragmentation<MemSink, M>, n: u16) -> Option<(u32, u32)> {
+        let [n0, n1] = n.to_le_bytes();
+        let b = n as u8;
+        let outcome = handle(frag, &[DATA_FRAGMENT, n0, n1, b, b, b, b], &mut []);
+        assert_eq!(0, outcome.response_len);
+        outcome.complete
+    }
+
+    /// Received and missing fragments, as answered to a status request to all participants.
+    fn status<const M: usize>(frag: &mut Fragmentation<MemSink, M>) -> (u16, u8) {
+        let mut response = [0; 8];
+        let outcome = handle(frag, &[FRAG_SESSION_STATUS_REQ, 0x01], &mut response);
+        assert_eq!(5, outcome.response_len);
+        assert_eq!(FRAG_SESSION_STATUS_REQ, response[0]);
+        (u16::from_le_bytes([response[1], response[2]]), response[3])
+    }
+
+    fn new() -> Fragmentation<MemSink, 1> {
+        Fragmentation::new(MemSink { data: [0; 64] })
+    }
+
+    #[test]
+    fn test_in_order() {
+        let mut frag = new();
+        assert_eq!([FRAG_SESSION_SETUP_REQ, 0], setup(&mut frag, 4, 2));
+
+        for n in 1..4 {
+            assert_eq!(None, fragment(&mut frag, n));
+        }
+        assert_eq!(Some((4 * FRAG_SIZE as u32 - 2, DESCRIPTOR)), fragment(&mut frag, 4));
+        assert_eq!((4, 0), status(&mut frag));
+
+        let data = &frag.sink().data;
+        for n in 1..=4 {
+            assert_eq!(&[n as u8; FRAG_SIZE], &data[(n - 1) * FRAG_SIZE..][..FRAG_SIZE]);
+        }
+    }
+
+    #[test]
+    fn test_lost_fragment_recovered() {
+        let mut frag = new();
+        setup(&mut frag, 4, 0);
+
+        assert_eq!(None, fragment(&mut frag, 1));
+        assert_eq!(None, fragment(&mut frag, 2));
+        assert_eq!(None, fragment(&mut frag, 4));
+        // Duplicates are ignored.
+        assert_eq!(None, fragment(&mut frag, 2));
+        assert_eq!((3, 1), status(&mut frag));
+
+        // The server sends the missing fragment again.
+        assert_eq!(Some((4 * FRAG_SIZE as u32, DESCRIPTOR)), fragment(&mut frag, 3));
+        assert_eq!(&[3; FRAG_SIZE], &frag.sink().data[2 * FRAG_SIZE..][..FRAG_SIZE]);
+    }
+
+    #[test]
+    fn test_too_many_lost() {
+        let mut frag = new();
+        setup(&mut frag, 4, 0);
+
+        assert_eq!(None, fragment(&mut frag, 1));
+        // Redundancy fragments are not decoded, so they don't make up for the lost ones.
+        for n in 5..8 {
+            assert_eq!(None, fragment(&mut frag, n));
+        }
+        assert_eq!((1, 3), status(&mut frag));
+    }
+
+    #[test]
+    fn test_setup_rejected() {
+        let mut frag = new();
+        // More fragments than the bitmap holds.
+        assert_eq!([FRAG_SESSION_SETUP_REQ, 1 << 1], setup(&mut frag, 9, 0));
+        // More padding than data.
+        let answer = setup(&mut frag, 1, FRAG_SIZE as u8 + 1);
+        assert_eq!([FRAG_SESSION_SETUP_REQ, 1 << 0], answer);
+
+        let mut response = [0; 8];
+        let outcome = handle(&mut frag, &[FRAG_SESSION_STATUS_REQ, 0x01], &mut response);
+        assert_eq!(0, outcome.response_len);
+    }
+}
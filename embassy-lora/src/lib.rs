@@ -4,6 +4,8 @@
 
 pub(crate) mod fmt;
 
+/// LoRaWAN fragmented data block transport, for firmware updates over the air
+pub mod fragmentation;
 /// interface variants required by the external lora physical layer crate (lora-phy)
 pub mod iv;
 
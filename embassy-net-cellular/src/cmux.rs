@@ -0,0 +1,274 @@
+//! 3GPP TS 27.010 multiplexer, in basic mode.
+
+use embedded_io::asynch::Write;
+
+const FLAG: u8 = 0xF9;
+const EA: u8 = 0x01;
+const CR: u8 = 0x02;
+/// Poll/final bit of the control field.
+const PF: u8 = 0x10;
+
+pub const SABM: u8 = 0x2F;
+pub const UA: u8 = 0x63;
+pub const DM: u8 = 0x0F;
+pub const UIH: u8 = 0xEF;
+
+/// Maximum length of the information field: the default of `AT+CMUX=0`.
+pub const N1: usize = 31;
+
+/// Longest information field received, larger frames are dropped.
+const MAX_INFO: usize = 128;
+
+/// Control channel.
+pub const DLCI_CONTROL: u8 = 0;
+pub const DLCI_AT: u8 = 1;
+pub const DLCI_DATA: u8 = 2;
+
+/// Close down command of the control channel, with its length.
+pub const CLD: [u8; 2] = [0xC3, 0x01];
+/// Modem status command type of the control channel.
+const MSC: u8 = 0xE3;
+/// V.24 signals of the modem status command: DV, RTR and RTC set.
+const MSC_SIGNALS: u8 = 0x8D;
+
+/// Frame check sequence over the address, control and length fields.
+fn fcs(fields: &[u8]) -> u8 {
+    let mut crc = 0xFF;
+    for &b in fields {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xE0 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// Send a frame on `dlci`, with `info` of at most [`N1`] bytes.
+pub async fn send<W: Write>(tx: &mut W, dlci: u8, control: u8, info: &[u8]) -> Result<(), W::Error> {
+    assert!(info.len() <= N1);
+
+    // The frames of the station which started the multiplexer are commands.
+    let address = dlci << 2 | CR | EA;
+    let control = if control == UIH { control } else { control | PF };
+    let header = [address, control, (info.len() as u8) << 1 | EA];
+
+    let mut frame = [0; N1 + 6];
+    frame[0] = FLAG;
+    frame[1..4].copy_from_slice(&header);
+    frame[4..][..info.len()].copy_from_slice(info);
+    frame[4 + info.len()] = 0xFF - fcs(&header);
+    frame[5 + info.len()] = FLAG;
+    tx.write_all(&frame[..6 + info.len()]).await
+}
+
+/// Send the modem status command of `dlci` on the control channel, ready to exchange data.
+pub async fn send_msc<W: Write>(tx: &mut W, dlci: u8) -> Result<(), W::Error> {
+    let msc = [MSC, 2 << 1 | EA, dlci << 2 | CR | EA, MSC_SIGNALS];
+    send(tx, DLCI_CONTROL, UIH, &msc).await
+}
+
+/// Received frame.
+pub struct Frame<'a> {
+    pub dlci: u8,
+    /// Control field, without the poll/final bit.
+    pub control: u8,
+    pub info: &'a [u8],
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Flag,
+    Address,
+    Control,
+    Length,
+    Length2,
+    Info,
+    Fcs,
+}
+
+/// Decoder of the received frames.
+///
+/// In basic mode, the information field isn't escaped: the frames are delimited by their length.
+pub struct Decoder {
+    state: State,
+    header: [u8; 4],
+    header_len: usize,
+    len: usize,
+    pos: usize,
+    info: [u8; MAX_INFO],
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Flag,
+            header: [0; 4],
+            header_len: 0,
+            len: 0,
+            pos: 0,
+            info: [0; MAX_INFO],
+        }
+    }
+
+    fn push_header(&mut self, b: u8) {
+        self.header[self.header_len] = b;
+        self.header_len += 1;
+    }
+
+    fn start_info(&mut self) -> State {
+        self.pos = 0;
+        if self.len == 0 {
+            State::Fcs
+        } else {
+            State::Info
+        }
+    }
+
+    /// Decode `b`, returning the frame it completes if it's valid.
+    pub fn push(&mut self, b: u8) -> Option<Frame<'_>> {
+        match self.state {
+            State::Flag => {
+                if b == FLAG {
+                    self.state = State::Address;
+                }
+            }
+            State::Address => {
+                // Repeated flags, or the opening flag following a closing one.
+                if b != FLAG {
+                    self.header_len = 0;
+                    self.push_header(b);
+                    self.state = State::Control;
+                }
+            }
+            State::Control => {
+                self.push_header(b);
+                self.state = State::Length;
+            }
+            State::Length => {
+                self.push_header(b);
+                self.len = (b >> 1) as usize;
+                self.state = if b & EA == 0 { State::Length2 } else { self.start_info() };
+            }
+            State::Length2 => {
+                self.push_header(b);
+                self.len |= (b as usize) << 7;
+                self.state = self.start_info();
+            }
+            State::Info => {
+                if self.pos < MAX_INFO {
+                    self.info[self.pos] = b;
+                }
+                self.pos += 1;
+                if self.pos == self.len {
+                    self.state = State::Fcs;
+                }
+            }
+            State::Fcs => {
+                self.state = State::Flag;
+                let mut fields = [0; 5];
+                fields[..self.header_len].copy_from_slice(&self.header[..self.header_len]);
+                fields[self.header_len] = b;
+                if fcs(&fields[..self.header_len + 1]) != 0xCF || self.len > MAX_INFO {
+                    warn!("cmux: dropping invalid frame");
+                    return None;
+                }
+                return Some(Frame {
+                    dlci: self.header[0] >> 2,
+                    control: self.header[1] & !PF,
+                    info: &self.info[..self.len],
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Answer a command received on the control channel, with the command itself as a response.
+pub async fn respond<W: Write>(tx: &mut W, info: &[u8]) -> Result<(), W::Error> {
+    match info.first() {
+        Some(&t) if t & CR != 0 && info.len() <= N1 => {
+            let mut response = [0; N1];
+            response[..info.len()].copy_from_slice(info);
+            response[0] = t & !CR;
+            send(tx, DLCI_CONTROL, UIH, &response[..info.len()]).await
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::mem_io::MemWriter;
+
+    #[test]
+    fn test_fcs() {
+        // Check value of the CRC, over "123456789".
+        assert_eq!(0xD0, fcs(b"123456789"));
+        // The FCS of the fields followed by their transmitted FCS.
+        assert_eq!(0xCF, fcs(&[0x03, 0x3F, 0x01, 0x1C]));
+    }
+
+    #[test]
+    fn test_send_sabm() {
+        let mut tx = MemWriter::<64>::new();
+        block_on(send(&mut tx, DLCI_CONTROL, SABM, &[])).unwrap();
+        assert_eq!(&[0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9], tx.written());
+    }
+
+    #[test]
+    fn test_decode_ua() {
+        let mut decoder = Decoder::new();
+        for b in [0xF9, 0x03, 0x73, 0x01] {
+            assert!(decoder.push(b).is_none());
+        }
+        // The frame is complete with its FCS, the closing flag is not needed.
+        let frame = decoder.push(0xD7).unwrap();
+        assert_eq!(DLCI_CONTROL, frame.dlci);
+        assert_eq!(UA, frame.control);
+        assert!(frame.info.is_empty());
+        assert!(decoder.push(0xF9).is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut tx = MemWriter::<128>::new();
+        block_on(send(&mut tx, DLCI_DATA, UIH, b"hello")).unwrap();
+        block_on(send(&mut tx, DLCI_AT, UIH, &[FLAG, EA])).unwrap();
+
+        let mut decoder = Decoder::new();
+        let mut frames = 0;
+        for &b in tx.written() {
+            if let Some(frame) = decoder.push(b) {
+                match frames {
+                    0 => {
+                        assert_eq!(DLCI_DATA, frame.dlci);
+                        assert_eq!(b"hello", frame.info);
+                    }
+                    _ => {
+                        assert_eq!(DLCI_AT, frame.dlci);
+                        assert_eq!(&[FLAG, EA], frame.info);
+                    }
+                }
+                assert_eq!(UIH, frame.control);
+                frames += 1;
+            }
+        }
+        assert_eq!(2, frames);
+    }
+
+    #[test]
+    fn test_bad_fcs() {
+        let mut tx = MemWriter::<64>::new();
+        block_on(send(&mut tx, DLCI_DATA, UIH, b"hello")).unwrap();
+        let len = tx.len;
+        tx.buf[len - 2] ^= 0x01;
+
+        let mut decoder = Decoder::new();
+        for &b in tx.written() {
+            assert!(decoder.push(b).is_none());
+        }
+    }
+}
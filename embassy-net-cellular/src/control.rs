@@ -0,0 +1,93 @@
+use embassy_time::{with_timeout, Duration};
+
+use crate::{Error, IpConfig, Shared};
+
+/// Time for the modem to answer an AT command.
+const AT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Control of the modem, through its AT command channel.
+pub struct Control<'a> {
+    shared: &'a Shared,
+}
+
+impl<'a> Control<'a> {
+    pub(crate) fn new(shared: &'a Shared) -> Self {
+        Self { shared }
+    }
+
+    /// Send the AT `command` made of `parts`, and wait for `OK`.
+    ///
+    /// The lines of the answer before `OK` are written to `response`, each ending with `\n`,
+    /// and truncated to the room left.
+    async fn command(&mut self, parts: &[&[u8]], response: &mut [u8]) -> Result<usize, Error> {
+        // Drop the unsolicited results received until now.
+        let mut stale = [0; 32];
+        while self.shared.at_rx.try_read(&mut stale).is_ok() {}
+
+        for part in parts {
+            self.shared.at_tx.write_all(part).await;
+        }
+        self.shared.at_tx.write_all(b"\r").await;
+
+        let read_response = async {
+            let mut len = 0;
+            let mut line = [0; 128];
+            let mut line_len = 0;
+            loop {
+                let mut buf = [0; 32];
+                let n = self.shared.at_rx.read(&mut buf).await;
+                for &b in &buf[..n] {
+                    if b != b'\r' && b != b'\n' {
+                        if line_len < line.len() {
+                            line[line_len] = b;
+                            line_len += 1;
+                        }
+                        continue;
+                    }
+
+                    let l = &line[..line_len];
+                    line_len = 0;
+                    if l == b"OK" {
+                        return Ok(len);
+                    }
+                    if l == b"ERROR" || l.starts_with(b"+CME ERROR") || l.starts_with(b"+CMS ERROR") {
+                        return Err(Error::Command);
+                    }
+                    if !l.is_empty() {
+                        for &b in l.iter().chain(b"\n") {
+                            if len < response.len() {
+                                response[len] = b;
+                                len += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        with_timeout(AT_TIMEOUT, read_response)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    /// Send the AT `command`, without the trailing `\r`, and wait for `OK`.
+    ///
+    /// The lines of the answer before `OK` are written to `response`, each ending with `\n`,
+    /// and truncated to the room left. Returns the length of the answer.
+    pub async fn at(&mut self, command: &str, response: &mut [u8]) -> Result<usize, Error> {
+        self.command(&[command.as_bytes()], response).await
+    }
+
+    /// Set the APN of the PDP context 1, dial the data channel, and negotiate the PPP session.
+    ///
+    /// Returns the IPv4 configuration of the session, for the `embassy-net` stack: the link is up
+    /// from then on. If the network requires credentials, set them with [`at`](Self::at) first, using
+    /// the command of the modem.
+    pub async fn connect(&mut self, apn: &str) -> Result<IpConfig, Error> {
+        self.command(&[&b"AT+CGDCONT=1,\"IP\",\""[..], apn.as_bytes(), b"\""], &mut [])
+            .await?;
+
+        self.shared.connected.reset();
+        self.shared.dial.signal(());
+        self.shared.connected.wait().await
+    }
+}
@@ -0,0 +1,449 @@
+#![cfg_attr(test, feature(async_fn_in_trait))]
+#![no_std]
+#![doc = include_str!("../README.md")]
+
+// must go first!
+mod fmt;
+
+mod cmux;
+mod control;
+#[cfg(test)]
+mod mem_io;
+mod ppp;
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+pub use control::Control;
+use embassy_futures::select::{select, select4, Either, Either4};
+use embassy_net_driver_channel as ch;
+use embassy_net_driver_channel::driver::LinkState;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::pipe::Pipe;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant, Ticker, Timer};
+use embedded_io::asynch::{Read, Write};
+
+use crate::ppp::{Phase, Ppp};
+
+const MTU: usize = 1500;
+
+/// Capacity of the pipes of the AT command channel.
+const AT_BUF: usize = 256;
+
+/// Time to dial the data channel, until `CONNECT`.
+const DIAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Type alias for the embassy-net driver.
+pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
+
+/// Error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The modem didn't answer in time.
+    Timeout,
+    /// The modem answered a command with an error.
+    Command,
+    /// The modem didn't connect the data channel.
+    NoCarrier,
+    /// The PPP session couldn't be negotiated, or was terminated during the negotiation.
+    Negotiation,
+    /// The UART failed.
+    Io,
+}
+
+/// IPv4 configuration of the PPP session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IpConfig {
+    /// Address of the modem end of the link.
+    pub address: [u8; 4],
+    /// Address of the network end of the link, if given.
+    pub peer_address: Option<[u8; 4]>,
+    /// DNS servers given by the network.
+    pub dns_servers: [Option<[u8; 4]>; 2],
+}
+
+pub(crate) struct Shared {
+    at_rx: Pipe<NoopRawMutex, AT_BUF>,
+    at_tx: Pipe<NoopRawMutex, AT_BUF>,
+    dial: Signal<NoopRawMutex, ()>,
+    connected: Signal<NoopRawMutex, Result<IpConfig, Error>>,
+}
+
+/// Internal state for the embassy-net integration.
+pub struct State<const N_RX: usize, const N_TX: usize> {
+    ch_state: ch::State<MTU, N_RX, N_TX>,
+    shared: Shared,
+}
+
+impl<const N_RX: usize, const N_TX: usize> State<N_RX, N_TX> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            ch_state: ch::State::new(),
+            shared: Shared {
+                at_rx: Pipe::new(),
+                at_tx: Pipe::new(),
+                dial: Signal::new(),
+                connected: Signal::new(),
+            },
+        }
+    }
+}
+
+/// State of the data channel.
+struct Session {
+    ppp: Ppp,
+    /// Phase of the PPP link when last checked.
+    phase: Phase,
+    /// Deadline of the dialing in progress.
+    dialing: Option<Instant>,
+    /// Line of the data channel in command mode.
+    line: [u8; 32],
+    line_len: usize,
+}
+
+impl Session {
+    /// Update the link state on a change of the PPP phase, and tell the control of the outcome
+    /// of the negotiation.
+    fn update(&mut self, shared: &Shared, state: ch::StateRunner) {
+        let phase = self.ppp.phase();
+        match (self.phase, phase) {
+            (Phase::Open, Phase::Open) => {}
+            (_, Phase::Open) => {
+                state.set_link_state(LinkState::Up);
+                shared.connected.signal(Ok(self.ppp.ip_config()));
+            }
+            (Phase::Open, _) => {
+                warn!("ppp: link down");
+                state.set_link_state(LinkState::Down);
+            }
+            (Phase::Dead, _) | (_, Phase::Establish | Phase::Authenticate | Phase::Network) => {}
+            (_, Phase::Dead) => shared.connected.signal(Err(Error::Negotiation)),
+        }
+        self.phase = phase;
+    }
+
+    /// Handle a line of the data channel while dialing.
+    fn dial_line(&mut self, shared: &Shared) {
+        let line = &self.line[..self.line_len];
+        if line.starts_with(b"CONNECT") {
+            debug!("data channel connected");
+            self.dialing = None;
+            self.ppp.start();
+        } else if [&b"NO CARRIER"[..], b"BUSY", b"NO DIALTONE", b"NO ANSWER", b"ERROR"]
+            .iter()
+            .any(|r| line.starts_with(r))
+        {
+            self.dialing = None;
+            shared.connected.signal(Err(Error::NoCarrier));
+        }
+    }
+}
+
+/// Background runner for the modem.
+///
+/// You must call `.run()` in a background task for the modem to operate.
+pub struct Runner<'d, R: Read, W: Write> {
+    ch: ch::Runner<'d, MTU>,
+    shared: &'d Shared,
+    rx: R,
+    tx: Mutex<NoopRawMutex, W>,
+}
+
+impl<'d, R: Read, W: Write> Runner<'d, R, W> {
+    /// Run the modem, starting the multiplexer again if the UART fails.
+    pub async fn run(self) -> ! {
+        let Self {
+            ch,
+            shared,
+            mut rx,
+            mut tx,
+        } = self;
+        let (state, mut rx_chan, mut tx_chan) = ch.split();
+        loop {
+            match start_mux(&mut rx, tx.get_mut()).await {
+                Ok(()) => {
+                    let e = run_mux(shared, state, &mut rx_chan, &mut tx_chan, &mut rx, &tx).await;
+                    warn!("multiplexer stopped: {:?}", e);
+                }
+                Err(e) => warn!("failed to start the multiplexer: {:?}", e),
+            }
+            state.set_link_state(LinkState::Down);
+            Timer::after(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Write `command` in plain AT mode, and wait for `OK`.
+async fn plain_command<R: Read, W: Write>(rx: &mut R, tx: &mut W, command: &[u8]) -> Result<(), Error> {
+    tx.write_all(command).await.map_err(|_| Error::Io)?;
+    tx.write_all(b"\r").await.map_err(|_| Error::Io)?;
+
+    let wait_ok = async {
+        let mut line = [0; 16];
+        let mut len = 0;
+        loop {
+            let mut b = [0];
+            rx.read(&mut b).await.map_err(|_| Error::Io)?;
+            match b[0] {
+                b'\r' | b'\n' => {
+                    match &line[..len] {
+                        b"OK" => return Ok(()),
+                        b"ERROR" => return Err(Error::Command),
+                        _ => {}
+                    }
+                    len = 0;
+                }
+                b if len < line.len() => {
+                    line[len] = b;
+                    len += 1;
+                }
+                _ => {}
+            }
+        }
+    };
+    with_timeout(Duration::from_secs(1), wait_ok)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
+
+/// Switch the modem to multiplexer mode, and open the channels.
+async fn start_mux<R: Read, W: Write>(rx: &mut R, tx: &mut W) -> Result<(), Error> {
+    // The modem may still multiplex from a previous run.
+    cmux::send(tx, cmux::DLCI_CONTROL, cmux::UIH, &cmux::CLD)
+        .await
+        .map_err(|_| Error::Io)?;
+    Timer::after(Duration::from_millis(100)).await;
+
+    let mut ready = Err(Error::Timeout);
+    for _ in 0..10 {
+        ready = plain_command(rx, tx, b"AT").await;
+        if ready.is_ok() {
+            break;
+        }
+    }
+    ready?;
+    plain_command(rx, tx, b"ATE0").await?;
+    plain_command(rx, tx, b"AT+CMUX=0").await?;
+
+    for dlci in [cmux::DLCI_CONTROL, cmux::DLCI_AT, cmux::DLCI_DATA] {
+        cmux::send(tx, dlci, cmux::SABM, &[]).await.map_err(|_| Error::Io)?;
+
+        let wait_ua = async {
+            let mut decoder = cmux::Decoder::new();
+            loop {
+                let mut b = [0];
+                rx.read(&mut b).await.map_err(|_| Error::Io)?;
+                match decoder.push(b[0]) {
+                    Some(frame) if frame.dlci == dlci && frame.control == cmux::UA => return Ok(()),
+                    Some(frame) if frame.dlci == dlci && frame.control == cmux::DM => return Err(Error::Command),
+                    _ => {}
+                }
+            }
+        };
+        with_timeout(Duration::from_secs(1), wait_ua)
+            .await
+            .map_err(|_| Error::Timeout)??;
+
+        if dlci != cmux::DLCI_CONTROL {
+            cmux::send_msc(tx, dlci).await.map_err(|_| Error::Io)?;
+        }
+    }
+    debug!("multiplexer started");
+    Ok(())
+}
+
+/// Run the multiplexer until the UART fails.
+async fn run_mux<R: Read, W: Write>(
+    shared: &Shared,
+    state: ch::StateRunner<'_>,
+    rx_chan: &mut ch::RxRunner<'_, MTU>,
+    tx_chan: &mut ch::TxRunner<'_, MTU>,
+    rx: &mut R,
+    tx: &Mutex<NoopRawMutex, W>,
+) -> Error {
+    let session = RefCell::new(Session {
+        ppp: Ppp::new(),
+        phase: Phase::Dead,
+        dialing: None,
+        line: [0; 32],
+        line_len: 0,
+    });
+    let session = &session;
+
+    let receive = async {
+        let mut buf = [0; 64];
+        let mut cmux = cmux::Decoder::new();
+        let mut hdlc = ppp::Decoder::new();
+        loop {
+            let n = rx.read(&mut buf).await.map_err(|_| Error::Io)?;
+            for &b in &buf[..n] {
+                let Some(frame) = cmux.push(b) else { continue };
+                match (frame.dlci, frame.control) {
+                    (cmux::DLCI_CONTROL, cmux::UIH) => {
+                        cmux::respond(&mut *tx.lock().await, frame.info)
+                            .await
+                            .map_err(|_| Error::Io)?;
+                    }
+                    (cmux::DLCI_AT, cmux::UIH) => {
+                        if shared.at_rx.try_write(frame.info).is_err() {
+                            warn!("AT response dropped");
+                        }
+                    }
+                    (cmux::DLCI_DATA, cmux::UIH) => {
+                        for &b in frame.info {
+                            let mut s = session.borrow_mut();
+                            if s.dialing.is_some() {
+                                match b {
+                                    b'\r' | b'\n' => {
+                                        s.dial_line(shared);
+                                        s.line_len = 0;
+                                    }
+                                    b if s.line_len < s.line.len() => {
+                                        let len = s.line_len;
+                                        s.line[len] = b;
+                                        s.line_len += 1;
+                                    }
+                                    _ => {}
+                                }
+                                if s.dialing.is_some() {
+                                    continue;
+                                }
+                            }
+                            drop(s);
+
+                            let answer = match hdlc.push(b) {
+                                Some((ppp::PROTO_IPV4, packet)) if session.borrow().ppp.phase() == Phase::Open => {
+                                    match rx_chan.try_rx_buf() {
+                                        Some(buf) if packet.len() <= buf.len() => {
+                                            buf[..packet.len()].copy_from_slice(packet);
+                                            rx_chan.rx_done(packet.len());
+                                        }
+                                        _ => warn!("IP packet dropped"),
+                                    }
+                                    None
+                                }
+                                Some((protocol, packet)) => session.borrow_mut().ppp.handle(protocol, packet),
+                                None => None,
+                            };
+
+                            // Also the first request, once connected.
+                            let request = {
+                                let mut s = session.borrow_mut();
+                                s.update(shared, state);
+                                s.ppp.poll_request()
+                            };
+                            if answer.is_none() && request.is_none() {
+                                continue;
+                            }
+                            let mut tx = tx.lock().await;
+                            for packet in answer.iter().chain(request.iter()) {
+                                ppp::send(&mut *tx, packet.protocol, packet.data())
+                                    .await
+                                    .map_err(|_| Error::Io)?;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    let transmit = async {
+        let mut ticker = Ticker::every(Duration::from_secs(1));
+        let mut buf = [0; cmux::N1];
+        loop {
+            match select4(
+                shared.at_tx.read(&mut buf),
+                tx_chan.tx_buf(),
+                ticker.next(),
+                shared.dial.wait(),
+            )
+            .await
+            {
+                Either4::First(n) => {
+                    cmux::send(&mut *tx.lock().await, cmux::DLCI_AT, cmux::UIH, &buf[..n])
+                        .await
+                        .map_err(|_| Error::Io)?;
+                }
+                Either4::Second(p) => {
+                    // Dropped if the link isn't open, as an unplugged cable would.
+                    if session.borrow().ppp.phase() == Phase::Open {
+                        ppp::send(&mut *tx.lock().await, ppp::PROTO_IPV4, p)
+                            .await
+                            .map_err(|_| Error::Io)?;
+                    }
+                    tx_chan.tx_done();
+                }
+                Either4::Third(()) => {
+                    let request = {
+                        let mut s = session.borrow_mut();
+                        if s.dialing.map_or(false, |deadline| Instant::now() > deadline) {
+                            s.dialing = None;
+                            shared.connected.signal(Err(Error::Timeout));
+                        }
+                        s.ppp.on_timer();
+                        s.update(shared, state);
+                        s.ppp.poll_request()
+                    };
+                    if let Some(packet) = request {
+                        ppp::send(&mut *tx.lock().await, packet.protocol, packet.data())
+                            .await
+                            .map_err(|_| Error::Io)?;
+                    }
+                }
+                Either4::Fourth(()) => {
+                    {
+                        let mut s = session.borrow_mut();
+                        if s.ppp.phase() == Phase::Open {
+                            shared.connected.signal(Ok(s.ppp.ip_config()));
+                            continue;
+                        }
+                        s.dialing = Some(Instant::now() + DIAL_TIMEOUT);
+                        s.line_len = 0;
+                    }
+                    cmux::send(&mut *tx.lock().await, cmux::DLCI_DATA, cmux::UIH, b"ATD*99#\r")
+                        .await
+                        .map_err(|_| Error::Io)?;
+                }
+            }
+        }
+    };
+
+    let r: Result<Infallible, Error> = match select(receive, transmit).await {
+        Either::First(r) | Either::Second(r) => r,
+    };
+    match r {
+        Ok(never) => match never {},
+        Err(e) => e,
+    }
+}
+
+/// Obtain a driver for using the modem with [`embassy-net`](https://crates.io/crates/embassy-net).
+///
+/// `rx` and `tx` are the two halves of the UART to the modem, which must run in plain AT mode at
+/// the start.
+pub fn new<'a, const N_RX: usize, const N_TX: usize, R: Read, W: Write>(
+    state: &'a mut State<N_RX, N_TX>,
+    rx: R,
+    tx: W,
+) -> (Device<'a>, Control<'a>, Runner<'a, R, W>) {
+    let (ch_runner, device) = ch::new_ip(&mut state.ch_state);
+    let shared = &state.shared;
+
+    (
+        device,
+        Control::new(shared),
+        Runner {
+            ch: ch_runner,
+            shared,
+            rx,
+            tx: Mutex::new(tx),
+        },
+    )
+}
@@ -0,0 +1,36 @@
+use core::convert::Infallible;
+
+use embedded_io::asynch::Write;
+use embedded_io::Io;
+
+/// Writer keeping the written bytes in memory.
+pub struct MemWriter<const SIZE: usize> {
+    pub buf: [u8; SIZE],
+    pub len: usize,
+}
+
+impl<const SIZE: usize> MemWriter<SIZE> {
+    pub const fn new() -> Self {
+        Self { buf: [0; SIZE], len: 0 }
+    }
+
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl<const SIZE: usize> Io for MemWriter<SIZE> {
+    type Error = Infallible;
+}
+
+impl<const SIZE: usize> Write for MemWriter<SIZE> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.buf[self.len..][..buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
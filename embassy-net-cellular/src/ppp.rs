@@ -0,0 +1,662 @@
+//! PPP client (RFC 1661) with the HDLC-like framing of RFC 1662.
+//!
+//! The link is negotiated with LCP, authenticated with PAP if the modem asks for it, then the IPv4
+//! address and DNS servers are obtained with IPCP (RFC 1332, RFC 1877). No option is requested
+//! from the modem beyond those: the protocol and address fields are never compressed, and all the
+//! control characters are escaped.
+
+use embedded_io::asynch::Write;
+
+use crate::{cmux, IpConfig, MTU};
+
+pub const PROTO_IPV4: u16 = 0x0021;
+const PROTO_LCP: u16 = 0xC021;
+const PROTO_PAP: u16 = 0xC023;
+const PROTO_IPCP: u16 = 0x8021;
+
+const FLAG: u8 = 0x7E;
+const ESCAPE: u8 = 0x7D;
+const ADDRESS_CONTROL: [u8; 2] = [0xFF, 0x03];
+
+const FCS_INIT: u16 = 0xFFFF;
+/// FCS of a frame, including its own FCS, when it's valid.
+const FCS_GOOD: u16 = 0xF0B8;
+
+const CONFIGURE_REQ: u8 = 1;
+const CONFIGURE_ACK: u8 = 2;
+const CONFIGURE_NAK: u8 = 3;
+const CONFIGURE_REJ: u8 = 4;
+const TERMINATE_REQ: u8 = 5;
+const TERMINATE_ACK: u8 = 6;
+const CODE_REJ: u8 = 7;
+const PROTOCOL_REJ: u8 = 8;
+const ECHO_REQ: u8 = 9;
+const ECHO_REPLY: u8 = 10;
+const DISCARD_REQ: u8 = 11;
+
+const PAP_REQ: u8 = 1;
+const PAP_ACK: u8 = 2;
+const PAP_NAK: u8 = 3;
+
+const LCP_MRU: u8 = 1;
+const LCP_ACCM: u8 = 2;
+const LCP_AUTH: u8 = 3;
+const LCP_MAGIC: u8 = 5;
+const LCP_PFC: u8 = 7;
+const LCP_ACFC: u8 = 8;
+
+const IPCP_ADDRESS: u8 = 3;
+const IPCP_DNS1: u8 = 129;
+const IPCP_DNS2: u8 = 131;
+
+/// Requests sent in a phase before giving up.
+const MAX_REQUESTS: u8 = 10;
+
+/// Longest control packet sent: longer rejected packets and echo data are truncated.
+const CONTROL_MTU: usize = 128;
+
+fn fcs16(mut fcs: u16, data: &[u8]) -> u16 {
+    for &b in data {
+        fcs ^= b as u16;
+        for _ in 0..8 {
+            fcs = if fcs & 1 != 0 { (fcs >> 1) ^ 0x8408 } else { fcs >> 1 };
+        }
+    }
+    fcs
+}
+
+/// Data of the multiplexer frames, sent once full.
+struct Chunk {
+    buf: [u8; cmux::N1],
+    len: usize,
+}
+
+impl Chunk {
+    async fn push<W: Write>(&mut self, tx: &mut W, b: u8) -> Result<(), W::Error> {
+        if self.len == self.buf.len() {
+            self.flush(tx).await?;
+        }
+        self.buf[self.len] = b;
+        self.len += 1;
+        Ok(())
+    }
+
+    async fn flush<W: Write>(&mut self, tx: &mut W) -> Result<(), W::Error> {
+        if self.len > 0 {
+            cmux::send(tx, cmux::DLCI_DATA, cmux::UIH, &self.buf[..self.len]).await?;
+            self.len = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Send a frame of `protocol` on the data channel of the multiplexer.
+pub async fn send<W: Write>(tx: &mut W, protocol: u16, data: &[u8]) -> Result<(), W::Error> {
+    let header = [
+        ADDRESS_CONTROL[0],
+        ADDRESS_CONTROL[1],
+        (protocol >> 8) as u8,
+        protocol as u8,
+    ];
+    let fcs = (!fcs16(fcs16(FCS_INIT, &header), data)).to_le_bytes();
+
+    let mut chunk = Chunk {
+        buf: [0; cmux::N1],
+        len: 0,
+    };
+    chunk.push(tx, FLAG).await?;
+    for &b in header.iter().chain(data).chain(&fcs) {
+        if b < 0x20 || b == FLAG || b == ESCAPE {
+            chunk.push(tx, ESCAPE).await?;
+            chunk.push(tx, b ^ 0x20).await?;
+        } else {
+            chunk.push(tx, b).await?;
+        }
+    }
+    chunk.push(tx, FLAG).await?;
+    chunk.flush(tx).await
+}
+
+/// Decoder of the received frames.
+pub struct Decoder {
+    /// Address, control and protocol fields, information and FCS.
+    buf: [u8; MTU + 6],
+    len: usize,
+    escape: bool,
+    overflow: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MTU + 6],
+            len: 0,
+            escape: false,
+            overflow: false,
+        }
+    }
+
+    /// Decode `b`, returning the protocol and information of the frame it completes if it's
+    /// valid.
+    pub fn push(&mut self, b: u8) -> Option<(u16, &[u8])> {
+        match b {
+            FLAG => {
+                let len = self.len;
+                let overflow = self.overflow;
+                self.len = 0;
+                self.escape = false;
+                self.overflow = false;
+
+                // Also the flags between frames.
+                if len < 4 || overflow {
+                    return None;
+                }
+                if fcs16(FCS_INIT, &self.buf[..len]) != FCS_GOOD {
+                    warn!("ppp: dropping frame with bad FCS");
+                    return None;
+                }
+
+                let mut frame = &self.buf[..len - 2];
+                if frame.starts_with(&ADDRESS_CONTROL) {
+                    frame = &frame[2..];
+                }
+                // A compressed protocol field is a single odd byte.
+                match *frame.first()? {
+                    p if p & 1 != 0 => Some((p as u16, &frame[1..])),
+                    p => Some((u16::from_be_bytes([p, *frame.get(1)?]), &frame[2..])),
+                }
+            }
+            ESCAPE => {
+                self.escape = true;
+                None
+            }
+            _ => {
+                let b = if self.escape { b ^ 0x20 } else { b };
+                self.escape = false;
+                if self.len < self.buf.len() {
+                    self.buf[self.len] = b;
+                    self.len += 1;
+                } else {
+                    self.overflow = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Control packet to send.
+pub struct Packet {
+    pub protocol: u16,
+    buf: [u8; CONTROL_MTU],
+    len: usize,
+}
+
+impl Packet {
+    fn new(protocol: u16, code: u8, id: u8) -> Self {
+        let mut buf = [0; CONTROL_MTU];
+        buf[0] = code;
+        buf[1] = id;
+        buf[3] = 4;
+        Self { protocol, buf, len: 4 }
+    }
+
+    /// Append `data`, truncated to the room left.
+    fn push(&mut self, data: &[u8]) {
+        let n = data.len().min(CONTROL_MTU - self.len);
+        self.buf[self.len..][..n].copy_from_slice(&data[..n]);
+        self.len += n;
+        self.buf[2..4].copy_from_slice(&(self.len as u16).to_be_bytes());
+    }
+
+    fn push_option(&mut self, option: u8, value: &[u8]) {
+        self.push(&[option, 2 + value.len() as u8]);
+        self.push(value);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 4
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Code, identifier and data of a control packet.
+fn parse(packet: &[u8]) -> Option<(u8, u8, &[u8])> {
+    let len = u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]) as usize;
+    let data = packet.get(4..len)?;
+    Some((packet[0], packet[1], data))
+}
+
+/// Configuration options of a control packet, with their type and length.
+fn options(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    core::iter::from_fn(move || {
+        let len = *data.get(1)? as usize;
+        if len < 2 || len > data.len() {
+            return None;
+        }
+        let (option, rest) = data.split_at(len);
+        data = rest;
+        Some(option)
+    })
+}
+
+/// Phase of the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Phase {
+    Dead,
+    Establish,
+    Authenticate,
+    Network,
+    Open,
+}
+
+/// Configure-Requests acknowledged, in each direction.
+#[derive(Clone, Copy, Default)]
+struct Negotiation {
+    ours: bool,
+    theirs: bool,
+}
+
+/// State of the PPP link, producing the control packets to send.
+pub struct Ppp {
+    phase: Phase,
+    /// Identifier of our last request.
+    id: u8,
+    /// Requests sent in the current phase.
+    requests: u8,
+    /// Whether the request of the current phase should be sent.
+    pending: bool,
+    lcp: Negotiation,
+    ipcp: Negotiation,
+    pap: bool,
+    address: [u8; 4],
+    /// DNS servers requested, `None` when the modem rejected the option.
+    dns: [Option<[u8; 4]>; 2],
+    peer_address: Option<[u8; 4]>,
+}
+
+impl Ppp {
+    pub const fn new() -> Self {
+        Self {
+            phase: Phase::Dead,
+            id: 0,
+            requests: 0,
+            pending: false,
+            lcp: Negotiation {
+                ours: false,
+                theirs: false,
+            },
+            ipcp: Negotiation {
+                ours: false,
+                theirs: false,
+            },
+            pap: false,
+            address: [0; 4],
+            dns: [Some([0; 4]); 2],
+            peer_address: None,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Start negotiating the link, once the data channel is connected.
+    pub fn start(&mut self) {
+        *self = Self {
+            id: self.id,
+            ..Self::new()
+        };
+        self.enter(Phase::Establish);
+    }
+
+    pub fn ip_config(&self) -> IpConfig {
+        IpConfig {
+            address: self.address,
+            peer_address: self.peer_address,
+            dns_servers: self.dns.map(|dns| dns.filter(|dns| *dns != [0; 4])),
+        }
+    }
+
+    fn enter(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.requests = 0;
+        self.pending = !matches!(phase, Phase::Dead | Phase::Open);
+    }
+
+    fn progress(&mut self) {
+        match self.phase {
+            Phase::Establish if self.lcp.ours && self.lcp.theirs => {
+                self.enter(if self.pap { Phase::Authenticate } else { Phase::Network })
+            }
+            Phase::Network if self.ipcp.ours && self.ipcp.theirs => self.enter(Phase::Open),
+            _ => {}
+        }
+    }
+
+    fn next_id(&mut self) -> u8 {
+        self.id = self.id.wrapping_add(1);
+        self.id
+    }
+
+    /// Request of the current phase, if it should be sent.
+    pub fn poll_request(&mut self) -> Option<Packet> {
+        if !self.pending {
+            return None;
+        }
+        self.pending = false;
+
+        let id = self.next_id();
+        match self.phase {
+            Phase::Establish => Some(Packet::new(PROTO_LCP, CONFIGURE_REQ, id)),
+            Phase::Authenticate => {
+                // Empty peer ID and password: the modem authenticates with the network itself.
+                let mut packet = Packet::new(PROTO_PAP, PAP_REQ, id);
+                packet.push(&[0, 0]);
+                Some(packet)
+            }
+            Phase::Network => {
+                let mut packet = Packet::new(PROTO_IPCP, CONFIGURE_REQ, id);
+                packet.push_option(IPCP_ADDRESS, &self.address);
+                for (option, dns) in [IPCP_DNS1, IPCP_DNS2].into_iter().zip(self.dns) {
+                    if let Some(dns) = dns {
+                        packet.push_option(option, &dns);
+                    }
+                }
+                Some(packet)
+            }
+            Phase::Dead | Phase::Open => None,
+        }
+    }
+
+    /// Handle the restart timer, expiring every second. Returns `true` when giving up the
+    /// negotiation.
+    pub fn on_timer(&mut self) -> bool {
+        let acked = match self.phase {
+            Phase::Establish => self.lcp.ours,
+            Phase::Network => self.ipcp.ours,
+            Phase::Authenticate => false,
+            Phase::Dead | Phase::Open => return false,
+        };
+        self.requests += 1;
+        if self.requests > MAX_REQUESTS {
+            self.enter(Phase::Dead);
+            return true;
+        }
+        self.pending = !acked;
+        false
+    }
+
+    /// Handle a control packet of `protocol`, returning the answer to send.
+    pub fn handle(&mut self, protocol: u16, packet: &[u8]) -> Option<Packet> {
+        match (protocol, self.phase) {
+            (_, Phase::Dead) => None,
+            (PROTO_LCP, _) => self.handle_lcp(packet),
+            (PROTO_PAP, Phase::Authenticate) => self.handle_pap(packet),
+            (PROTO_IPCP, Phase::Network | Phase::Open) => self.handle_ipcp(packet),
+            // Silently discarded before their phase.
+            (PROTO_PAP | PROTO_IPCP | PROTO_IPV4, _) | (_, Phase::Establish) => None,
+            _ => {
+                let mut reject = Packet::new(PROTO_LCP, PROTOCOL_REJ, self.next_id());
+                reject.push(&protocol.to_be_bytes());
+                reject.push(packet);
+                Some(reject)
+            }
+        }
+    }
+
+    fn handle_lcp(&mut self, packet: &[u8]) -> Option<Packet> {
+        let (code, id, data) = parse(packet)?;
+        match code {
+            CONFIGURE_REQ => {
+                // Renegotiation of an established link.
+                if self.phase != Phase::Establish {
+                    self.start();
+                }
+
+                let mut reject = Packet::new(PROTO_LCP, CONFIGURE_REJ, id);
+                let mut nak = Packet::new(PROTO_LCP, CONFIGURE_NAK, id);
+                let mut pap = false;
+                for option in options(data) {
+                    match option[0] {
+                        LCP_MRU | LCP_ACCM | LCP_MAGIC | LCP_PFC | LCP_ACFC => {}
+                        LCP_AUTH if option[2..] == PROTO_PAP.to_be_bytes() => pap = true,
+                        // Only PAP is supported.
+                        LCP_AUTH => nak.push_option(LCP_AUTH, &PROTO_PAP.to_be_bytes()),
+                        _ => reject.push(option),
+                    }
+                }
+                if !reject.is_empty() {
+                    return Some(reject);
+                }
+                if !nak.is_empty() {
+                    return Some(nak);
+                }
+
+                self.pap = pap;
+                self.lcp.theirs = true;
+                self.progress();
+                let mut ack = Packet::new(PROTO_LCP, CONFIGURE_ACK, id);
+                ack.push(data);
+                Some(ack)
+            }
+            CONFIGURE_ACK if id == self.id && self.phase == Phase::Establish => {
+                self.lcp.ours = true;
+                self.progress();
+                None
+            }
+            // Nothing is requested: the request is sent again on the timer.
+            CONFIGURE_ACK | CONFIGURE_NAK | CONFIGURE_REJ => None,
+            TERMINATE_REQ => {
+                self.enter(Phase::Dead);
+                Some(Packet::new(PROTO_LCP, TERMINATE_ACK, id))
+            }
+            TERMINATE_ACK => {
+                self.enter(Phase::Dead);
+                None
+            }
+            ECHO_REQ if self.phase != Phase::Establish => {
+                // Our magic number is 0, as it's not negotiated.
+                let mut reply = Packet::new(PROTO_LCP, ECHO_REPLY, id);
+                reply.push(&[0; 4]);
+                reply.push(data.get(4..).unwrap_or(&[]));
+                Some(reply)
+            }
+            ECHO_REQ | ECHO_REPLY | DISCARD_REQ | CODE_REJ | PROTOCOL_REJ => None,
+            _ => {
+                let mut reject = Packet::new(PROTO_LCP, CODE_REJ, self.next_id());
+                reject.push(packet);
+                Some(reject)
+            }
+        }
+    }
+
+    fn handle_pap(&mut self, packet: &[u8]) -> Option<Packet> {
+        let (code, id, _) = parse(packet)?;
+        match code {
+            PAP_ACK if id == self.id => self.enter(Phase::Network),
+            PAP_NAK if id == self.id => {
+                warn!("ppp: authentication failed");
+                self.enter(Phase::Dead)
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_ipcp(&mut self, packet: &[u8]) -> Option<Packet> {
+        let (code, id, data) = parse(packet)?;
+        match code {
+            CONFIGURE_REQ => {
+                // Renegotiation of an opened network layer.
+                if self.phase == Phase::Open {
+                    self.ipcp = Negotiation::default();
+                    self.enter(Phase::Network);
+                }
+
+                let mut reject = Packet::new(PROTO_IPCP, CONFIGURE_REJ, id);
+                let mut peer_address = None;
+                for option in options(data) {
+                    match (option[0], <[u8; 4]>::try_from(&option[2..])) {
+                        (IPCP_ADDRESS, Ok(address)) => peer_address = Some(address),
+                        _ => reject.push(option),
+                    }
+                }
+                if !reject.is_empty() {
+                    return Some(reject);
+                }
+
+                self.peer_address = peer_address;
+                self.ipcp.theirs = true;
+                self.progress();
+                let mut ack = Packet::new(PROTO_IPCP, CONFIGURE_ACK, id);
+                ack.push(data);
+                Some(ack)
+            }
+            CONFIGURE_ACK if id == self.id => {
+                self.ipcp.ours = true;
+                self.progress();
+                None
+            }
+            CONFIGURE_NAK if id == self.id => {
+                // The values to request instead.
+                for option in options(data) {
+                    if let Ok(value) = <[u8; 4]>::try_from(&option[2..]) {
+                        match option[0] {
+                            IPCP_ADDRESS => self.address = value,
+                            IPCP_DNS1 => self.dns[0] = Some(value),
+                            IPCP_DNS2 => self.dns[1] = Some(value),
+                            _ => {}
+                        }
+                    }
+                }
+                self.pending = true;
+                None
+            }
+            CONFIGURE_REJ if id == self.id => {
+                for option in options(data) {
+                    match option[0] {
+                        IPCP_DNS1 => self.dns[0] = None,
+                        IPCP_DNS2 => self.dns[1] = None,
+                        _ => {}
+                    }
+                }
+                self.pending = true;
+                None
+            }
+            CONFIGURE_ACK | CONFIGURE_NAK | CONFIGURE_REJ | TERMINATE_ACK | CODE_REJ => None,
+            TERMINATE_REQ => {
+                self.enter(Phase::Dead);
+                Some(Packet::new(PROTO_IPCP, TERMINATE_ACK, id))
+            }
+            _ => {
+                let mut reject = Packet::new(PROTO_IPCP, CODE_REJ, self.next_id());
+                reject.push(packet);
+                Some(reject)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::mem_io::MemWriter;
+
+    /// Frame of `protocol` with `data`, followed by its FCS, without the flags and unescaped.
+    fn frame(protocol: u16, data: &[u8]) -> MemWriter<64> {
+        let mut frame = MemWriter::new();
+        let [p0, p1] = protocol.to_be_bytes();
+        block_on(frame.write_all(&[ADDRESS_CONTROL[0], ADDRESS_CONTROL[1], p0, p1])).unwrap();
+        block_on(frame.write_all(data)).unwrap();
+        let fcs = !fcs16(FCS_INIT, frame.written());
+        block_on(frame.write_all(&fcs.to_le_bytes())).unwrap();
+        frame
+    }
+
+    /// Delimit `frame` with flags, escaping all its bytes.
+    fn escape_all(frame: &[u8]) -> MemWriter<128> {
+        let mut stream = MemWriter::new();
+        block_on(stream.write_all(&[FLAG])).unwrap();
+        for &b in frame {
+            block_on(stream.write_all(&[ESCAPE, b ^ 0x20])).unwrap();
+        }
+        block_on(stream.write_all(&[FLAG])).unwrap();
+        stream
+    }
+
+    /// Decode `stream`, returning the protocol and information of the single valid frame in it.
+    fn decode(stream: &[u8]) -> Option<(u16, MemWriter<64>)> {
+        let mut decoder = Decoder::new();
+        let mut decoded = None;
+        for &b in stream {
+            if let Some((protocol, data)) = decoder.push(b) {
+                assert!(decoded.is_none());
+                let mut info = MemWriter::new();
+                block_on(info.write_all(data)).unwrap();
+                decoded = Some((protocol, info));
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn test_fcs16() {
+        // Check value of the CRC, over "123456789".
+        assert_eq!(0x906E, !fcs16(FCS_INIT, b"123456789"));
+        // The FCS of a frame followed by its transmitted FCS.
+        let frame = frame(PROTO_LCP, &[CONFIGURE_REQ, 1, 0, 4]);
+        assert_eq!(FCS_GOOD, fcs16(FCS_INIT, frame.written()));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [0x45, FLAG, ESCAPE, 0x00, 0x1F, 0x20, 0xFF];
+        let mut tx = MemWriter::<128>::new();
+        block_on(send(&mut tx, PROTO_IPV4, &data)).unwrap();
+
+        // The frame is sent in the information fields of the data channel.
+        let mut stream = MemWriter::<128>::new();
+        let mut decoder = cmux::Decoder::new();
+        for &b in tx.written() {
+            if let Some(frame) = decoder.push(b) {
+                assert_eq!(cmux::DLCI_DATA, frame.dlci);
+                block_on(stream.write_all(frame.info)).unwrap();
+            }
+        }
+
+        // The flags only delimit the frame, and the control characters are escaped.
+        let stream = stream.written();
+        assert_eq!(FLAG, stream[0]);
+        assert_eq!(FLAG, stream[stream.len() - 1]);
+        assert!(stream[1..stream.len() - 1].iter().all(|&b| b != FLAG && b >= 0x20));
+
+        let (protocol, info) = decode(stream).unwrap();
+        assert_eq!(PROTO_IPV4, protocol);
+        assert_eq!(&data, info.written());
+    }
+
+    #[test]
+    fn test_escaped_bytes() {
+        let data = [FLAG, ESCAPE, 0x11, 0x13, 0x41];
+        let frame = frame(PROTO_IPV4, &data);
+        let (protocol, info) = decode(escape_all(frame.written()).written()).unwrap();
+        assert_eq!(PROTO_IPV4, protocol);
+        assert_eq!(&data, info.written());
+    }
+
+    #[test]
+    fn test_bad_fcs() {
+        let mut frame = frame(PROTO_IPV4, &[0x45, 0x00, 0x00, 0x14]);
+        assert!(decode(escape_all(frame.written()).written()).is_some());
+
+        frame.buf[5] ^= 0x01;
+        assert!(decode(escape_all(frame.written()).written()).is_none());
+    }
+}
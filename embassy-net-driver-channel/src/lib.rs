@@ -43,6 +43,7 @@ struct Shared {
     link_state: LinkState,
     waker: WakerRegistration,
     ethernet_address: [u8; 6],
+    ieee802154_address: [u8; 8],
 }
 
 pub struct Runner<'d, const MTU: usize> {
@@ -207,10 +208,34 @@ impl<'d, const MTU: usize> TxRunner<'d, MTU> {
 pub fn new<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
     state: &'d mut State<MTU, N_RX, N_TX>,
     ethernet_address: [u8; 6],
+) -> (Runner<'d, MTU>, Device<'d, MTU>) {
+    new_with_medium(state, Medium::Ethernet, ethernet_address, [0; 8])
+}
+
+/// Create a driver for a device carrying IP packets, without an Ethernet header.
+pub fn new_ip<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
+    state: &'d mut State<MTU, N_RX, N_TX>,
+) -> (Runner<'d, MTU>, Device<'d, MTU>) {
+    new_with_medium(state, Medium::Ip, [0; 6], [0; 8])
+}
+
+/// Create a driver for an IEEE 802.15.4 device, with the extended address `ieee802154_address`.
+pub fn new_ieee802154<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
+    state: &'d mut State<MTU, N_RX, N_TX>,
+    ieee802154_address: [u8; 8],
+) -> (Runner<'d, MTU>, Device<'d, MTU>) {
+    new_with_medium(state, Medium::Ieee802154, [0; 6], ieee802154_address)
+}
+
+fn new_with_medium<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
+    state: &'d mut State<MTU, N_RX, N_TX>,
+    medium: Medium,
+    ethernet_address: [u8; 6],
+    ieee802154_address: [u8; 8],
 ) -> (Runner<'d, MTU>, Device<'d, MTU>) {
     let mut caps = Capabilities::default();
     caps.max_transmission_unit = MTU;
-    caps.medium = Medium::Ethernet;
+    caps.medium = medium;
 
     // safety: this is a self-referential struct, however:
     // - it can't move while the `'d` borrow is active.
@@ -223,6 +248,7 @@ pub fn new<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
         shared: Mutex::new(RefCell::new(Shared {
             link_state: LinkState::Down,
             ethernet_address,
+            ieee802154_address,
             waker: WakerRegistration::new(),
         })),
     });
@@ -293,6 +319,10 @@ impl<'d, const MTU: usize> embassy_net_driver::Driver for Device<'d, MTU> {
         self.shared.lock(|s| s.borrow().ethernet_address)
     }
 
+    fn ieee802154_address(&self) -> [u8; 8] {
+        self.shared.lock(|s| s.borrow().ieee802154_address)
+    }
+
     fn link_state(&mut self, cx: &mut Context) -> LinkState {
         self.shared.lock(|s| {
             let s = &mut *s.borrow_mut();
@@ -53,6 +53,13 @@ pub trait Driver {
 
     /// Get the device's Ethernet address.
     fn ethernet_address(&self) -> [u8; 6];
+
+    /// Get the device's IEEE 802.15.4 extended address, for the [`Medium::Ieee802154`] medium.
+    ///
+    /// Devices of other mediums don't need to implement this.
+    fn ieee802154_address(&self) -> [u8; 8] {
+        [0; 8]
+    }
 }
 
 impl<T: ?Sized + Driver> Driver for &mut T {
@@ -78,6 +85,9 @@ impl<T: ?Sized + Driver> Driver for &mut T {
     fn ethernet_address(&self) -> [u8; 6] {
         T::ethernet_address(self)
     }
+    fn ieee802154_address(&self) -> [u8; 8] {
+        T::ieee802154_address(self)
+    }
 }
 
 /// A token to receive a single network packet.
@@ -164,6 +174,12 @@ pub enum Medium {
     ///
     /// Examples of devices of this type are the Linux `tun`, PPP interfaces, VPNs in tun (layer 3) mode.
     Ip,
+
+    /// IEEE 802.15.4 medium. Devices of this type send and receive IEEE 802.15.4 MAC frames, without
+    /// the FCS, carrying IPv6 compressed by 6LoWPAN. They are addressed by their extended address.
+    ///
+    /// Examples of devices of this type are 2.4 GHz 802.15.4 radios, such as the one of the nRF52840.
+    Ieee802154,
 }
 
 impl Default for Medium {
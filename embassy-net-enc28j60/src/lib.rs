@@ -0,0 +1,357 @@
+//! [`embassy-net`](https://crates.io/crates/embassy-net) driver for the Microchip ENC28J60 ethernet chip.
+//!
+//! The received frames and the link changes are signaled on the interrupt pin. The link runs in
+//! half duplex, the ENC28J60 not negotiating it.
+#![no_std]
+
+mod regs;
+
+use embassy_futures::select::{select, Either};
+use embassy_net_driver_channel as ch;
+use embassy_net_driver_channel::driver::LinkState;
+use embassy_time::{Duration, Timer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::regs::*;
+
+const MTU: usize = 1514;
+
+/// Maximum frame length, with the CRC.
+const MAX_FRAME_LEN: u16 = 1518;
+
+// Errata: the receive buffer may be corrupted unless it starts at address 0.
+const RXST: u16 = 0x0000;
+const RXND: u16 = 0x19FF;
+// Room for a frame, its control byte and its transmit status vector.
+const TXST: u16 = 0x1A00;
+
+/// Bit of the receive status vector set when the frame was received without error.
+const RSV_RECEIVED_OK: u16 = 1 << 7;
+
+/// Interval between checks for the end of a transmission.
+const TX_POLL: Duration = Duration::from_micros(100);
+
+/// Type alias for the embassy-net driver
+pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
+
+/// ENC28J60 error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI error.
+    Spi(E),
+    /// The chip didn't answer with a valid revision ID.
+    NoChip,
+}
+
+/// Internal state for the embassy-net integration.
+pub struct State<const N_RX: usize, const N_TX: usize> {
+    ch_state: ch::State<MTU, N_RX, N_TX>,
+}
+
+impl<const N_RX: usize, const N_TX: usize> State<N_RX, N_TX> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            ch_state: ch::State::new(),
+        }
+    }
+}
+
+/// The chip registers and memory, behind the SPI device.
+struct Chip<S> {
+    spi: S,
+    bank: u8,
+    next_packet: u16,
+}
+
+impl<S: SpiDevice> Chip<S> {
+    async fn select_bank(&mut self, reg: Register) -> Result<(), S::Error> {
+        if reg.is_common() || reg.bank == self.bank {
+            return Ok(());
+        }
+        self.bit_field_clear(ECON1, ECON1_BSEL).await?;
+        self.bit_field_set(ECON1, reg.bank).await?;
+        self.bank = reg.bank;
+        Ok(())
+    }
+
+    async fn read(&mut self, reg: Register) -> Result<u8, S::Error> {
+        self.select_bank(reg).await?;
+        let mut buf = [OP_RCR | reg.addr, 0, 0];
+        let len = if reg.mac_mii { 3 } else { 2 };
+        self.spi.transfer_in_place(&mut buf[..len]).await?;
+        Ok(buf[len - 1])
+    }
+
+    async fn write(&mut self, reg: Register, value: u8) -> Result<(), S::Error> {
+        self.select_bank(reg).await?;
+        self.spi.write(&[OP_WCR | reg.addr, value]).await
+    }
+
+    /// Write a 16-bit register pair, `low` being the address of its low byte.
+    async fn write_u16(&mut self, low: Register, value: u16) -> Result<(), S::Error> {
+        let [lo, hi] = value.to_le_bytes();
+        self.write(low, lo).await?;
+        self.write(
+            Register {
+                addr: low.addr + 1,
+                ..low
+            },
+            hi,
+        )
+        .await
+    }
+
+    async fn bit_field_set(&mut self, reg: Register, mask: u8) -> Result<(), S::Error> {
+        self.select_bank(reg).await?;
+        self.spi.write(&[OP_BFS | reg.addr, mask]).await
+    }
+
+    async fn bit_field_clear(&mut self, reg: Register, mask: u8) -> Result<(), S::Error> {
+        self.select_bank(reg).await?;
+        self.spi.write(&[OP_BFC | reg.addr, mask]).await
+    }
+
+    async fn read_buffer(&mut self, buf: &mut [u8]) -> Result<(), S::Error> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[OP_RBM]), Operation::Read(buf)])
+            .await
+    }
+
+    async fn write_buffer(&mut self, buf: &[u8]) -> Result<(), S::Error> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[OP_WBM]), Operation::Write(buf)])
+            .await
+    }
+
+    async fn wait_mii(&mut self) -> Result<(), S::Error> {
+        while self.read(MISTAT).await? & MISTAT_BUSY != 0 {}
+        Ok(())
+    }
+
+    async fn read_phy(&mut self, addr: u8) -> Result<u16, S::Error> {
+        self.write(MIREGADR, addr).await?;
+        self.write(MICMD, MICMD_MIIRD).await?;
+        self.wait_mii().await?;
+        self.write(MICMD, 0).await?;
+        let lo = self.read(MIRDL).await?;
+        let hi = self.read(MIRDH).await?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    async fn write_phy(&mut self, addr: u8, value: u16) -> Result<(), S::Error> {
+        let [lo, hi] = value.to_le_bytes();
+        self.write(MIREGADR, addr).await?;
+        self.write(MIWRL, lo).await?;
+        // Writing the high byte starts the write.
+        self.write(MIWRH, hi).await?;
+        self.wait_mii().await
+    }
+
+    async fn init(&mut self, mac_addr: [u8; 6]) -> Result<(), Error<S::Error>> {
+        self.spi.write(&[OP_SRC]).await.map_err(Error::Spi)?;
+        // Errata: the soft reset doesn't clear CLKRDY, so wait for the oscillator to start before
+        // polling it.
+        Timer::after(Duration::from_millis(1)).await;
+        self.bank = 0;
+        while self.read(ESTAT).await.map_err(Error::Spi)? & ESTAT_CLKRDY == 0 {}
+
+        let revision = self.read(EREVID).await.map_err(Error::Spi)?;
+        if revision == 0x00 || revision == 0xFF {
+            return Err(Error::NoChip);
+        }
+
+        self.init_buffers(mac_addr).await.map_err(Error::Spi)
+    }
+
+    async fn init_buffers(&mut self, mac_addr: [u8; 6]) -> Result<(), S::Error> {
+        self.write_u16(ERXSTL, RXST).await?;
+        self.write_u16(ERXNDL, RXND).await?;
+        // Errata: the receive buffer may be corrupted if ERXRDPT is even.
+        self.write_u16(ERXRDPTL, RXND).await?;
+        self.next_packet = RXST;
+        self.write_u16(ETXSTL, TXST).await?;
+        self.bit_field_set(ECON2, ECON2_AUTOINC).await?;
+
+        self.write(ERXFCON, ERXFCON_UCEN | ERXFCON_CRCEN | ERXFCON_BCEN).await?;
+
+        // MAC in half duplex.
+        self.write(MACON1, MACON1_MARXEN).await?;
+        self.write(MACON3, MACON3_PADCFG_60 | MACON3_TXCRCEN | MACON3_FRMLNEN)
+            .await?;
+        self.write(MACON4, MACON4_DEFER).await?;
+        self.write_u16(MAMXFLL, MAX_FRAME_LEN).await?;
+        self.write(MABBIPG, 0x12).await?;
+        self.write(MAIPGL, 0x12).await?;
+        self.write(MAIPGH, 0x0C).await?;
+
+        self.write(MAADR1, mac_addr[0]).await?;
+        self.write(MAADR2, mac_addr[1]).await?;
+        self.write(MAADR3, mac_addr[2]).await?;
+        self.write(MAADR4, mac_addr[3]).await?;
+        self.write(MAADR5, mac_addr[4]).await?;
+        self.write(MAADR6, mac_addr[5]).await?;
+
+        // PHY in half duplex, without looping the transmitted frames back.
+        self.write_phy(PHCON1, 0).await?;
+        self.write_phy(PHCON2, PHCON2_HDLDIS).await?;
+
+        // Interrupt on the received frames and the link changes.
+        self.write_phy(PHIE, PHIE_PGEIE | PHIE_PLNKIE).await?;
+        self.write(EIE, EIE_INTIE | EIE_PKTIE | EIE_LINKIE).await?;
+
+        self.bit_field_set(ECON1, ECON1_RXEN).await
+    }
+
+    async fn is_link_up(&mut self) -> Result<bool, S::Error> {
+        Ok(self.read_phy(PHSTAT2).await? & PHSTAT2_LSTAT != 0)
+    }
+
+    /// Clear the link change interrupt, and return whether the link changed.
+    async fn link_changed(&mut self) -> Result<bool, S::Error> {
+        if self.read(EIR).await? & EIR_LINKIF == 0 {
+            return Ok(false);
+        }
+        // Reading PHIR clears LINKIF.
+        self.read_phy(PHIR).await?;
+        Ok(true)
+    }
+
+    async fn has_frame(&mut self) -> Result<bool, S::Error> {
+        // Errata: the packet pending flag is unreliable, use the packet counter.
+        Ok(self.read(EPKTCNT).await? != 0)
+    }
+
+    /// Read the next received frame to `buf`, dropping the frames received with errors or too
+    /// long for `buf`. Returns the length of the frame, or `None` when no frame is left.
+    async fn receive(&mut self, buf: &mut [u8]) -> Result<Option<usize>, S::Error> {
+        loop {
+            if !self.has_frame().await? {
+                return Ok(None);
+            }
+
+            self.write_u16(ERDPTL, self.next_packet).await?;
+            let mut header = [0; 6];
+            self.read_buffer(&mut header).await?;
+            let next_packet = u16::from_le_bytes([header[0], header[1]]);
+            let byte_count = u16::from_le_bytes([header[2], header[3]]) as usize;
+            let status = u16::from_le_bytes([header[4], header[5]]);
+
+            // Without the CRC.
+            let len = byte_count.saturating_sub(4);
+            let ok = status & RSV_RECEIVED_OK != 0 && len <= buf.len();
+            if ok {
+                self.read_buffer(&mut buf[..len]).await?;
+            }
+
+            // Free the frame. Errata: the receive buffer may be corrupted if ERXRDPT is even, so
+            // free up to the byte before the next frame.
+            self.next_packet = next_packet;
+            let read_ptr = if next_packet == RXST { RXND } else { next_packet - 1 };
+            self.write_u16(ERXRDPTL, read_ptr).await?;
+            self.bit_field_set(ECON2, ECON2_PKTDEC).await?;
+
+            if ok {
+                return Ok(Some(len));
+            }
+        }
+    }
+
+    async fn transmit(&mut self, frame: &[u8]) -> Result<(), S::Error> {
+        // A frame takes at most a few milliseconds to send.
+        while self.read(ECON1).await? & ECON1_TXRTS != 0 {
+            Timer::after(TX_POLL).await;
+        }
+
+        // Errata: the transmit logic may stall after an error in half duplex, reset it.
+        self.bit_field_set(ECON1, ECON1_TXRST).await?;
+        self.bit_field_clear(ECON1, ECON1_TXRST).await?;
+        self.bit_field_clear(EIR, EIR_TXERIF).await?;
+
+        self.write_u16(EWRPTL, TXST).await?;
+        // Control byte: send with the settings of MACON3.
+        self.write_buffer(&[0]).await?;
+        self.write_buffer(frame).await?;
+        self.write_u16(ETXNDL, TXST + frame.len() as u16).await?;
+        self.bit_field_set(ECON1, ECON1_TXRTS).await
+    }
+}
+
+/// Background runner for the ENC28J60.
+///
+/// You must call `.run()` in a background task for the chip to operate.
+pub struct Runner<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> {
+    chip: Chip<SPI>,
+    ch: ch::Runner<'d, MTU>,
+    int: INT,
+    _reset: RST,
+}
+
+impl<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'d, SPI, INT, RST> {
+    pub async fn run(mut self) -> ! {
+        let (state_chan, mut rx_chan, mut tx_chan) = self.ch.split();
+
+        let set_link_state = |up| state_chan.set_link_state(if up { LinkState::Up } else { LinkState::Down });
+        set_link_state(matches!(self.chip.is_link_up().await, Ok(true)));
+
+        loop {
+            match select(self.int.wait_for_low(), tx_chan.tx_buf()).await {
+                Either::First(_) => {
+                    if let Ok(true) = self.chip.link_changed().await {
+                        set_link_state(matches!(self.chip.is_link_up().await, Ok(true)));
+                    }
+
+                    // The interrupt stays asserted until all the received frames are read.
+                    while let Ok(true) = self.chip.has_frame().await {
+                        let buf = rx_chan.rx_buf().await;
+                        match self.chip.receive(buf).await {
+                            Ok(Some(n)) => rx_chan.rx_done(n),
+                            _ => break,
+                        }
+                    }
+                }
+                Either::Second(frame) => {
+                    self.chip.transmit(frame).await.ok();
+                    tx_chan.tx_done();
+                }
+            }
+        }
+    }
+}
+
+/// Obtain a driver for using the ENC28J60 with [`embassy-net`](https://crates.io/crates/embassy-net).
+pub async fn new<'a, const N_RX: usize, const N_TX: usize, SPI: SpiDevice, INT: Wait, RST: OutputPin>(
+    mac_addr: [u8; 6],
+    state: &'a mut State<N_RX, N_TX>,
+    spi_dev: SPI,
+    int: INT,
+    mut reset: RST,
+) -> Result<(Device<'a>, Runner<'a, SPI, INT, RST>), Error<SPI::Error>> {
+    // Reset the chip.
+    reset.set_low().ok();
+    // Ensure the reset is registered.
+    Timer::after(Duration::from_millis(1)).await;
+    reset.set_high().ok();
+    Timer::after(Duration::from_millis(1)).await;
+
+    let mut chip = Chip {
+        spi: spi_dev,
+        bank: 0,
+        next_packet: RXST,
+    };
+    chip.init(mac_addr).await?;
+
+    let (ch, device) = ch::new(&mut state.ch_state, mac_addr);
+    Ok((
+        device,
+        Runner {
+            chip,
+            ch,
+            int,
+            _reset: reset,
+        },
+    ))
+}
@@ -0,0 +1,133 @@
+//! Registers of the ENC28J60.
+
+/// A control register, in one of the four banks unless it's common to all of them.
+#[derive(Debug, Clone, Copy)]
+pub struct Register {
+    pub bank: u8,
+    pub addr: u8,
+    /// MAC and MII registers send a dummy byte before their value when read.
+    pub mac_mii: bool,
+}
+
+impl Register {
+    /// Whether the register is mapped in all the banks.
+    pub const fn is_common(&self) -> bool {
+        self.addr >= 0x1B
+    }
+}
+
+const fn eth(bank: u8, addr: u8) -> Register {
+    Register {
+        bank,
+        addr,
+        mac_mii: false,
+    }
+}
+
+const fn mac(bank: u8, addr: u8) -> Register {
+    Register {
+        bank,
+        addr,
+        mac_mii: true,
+    }
+}
+
+// Common
+pub const EIE: Register = eth(0, 0x1B);
+pub const EIR: Register = eth(0, 0x1C);
+pub const ESTAT: Register = eth(0, 0x1D);
+pub const ECON2: Register = eth(0, 0x1E);
+pub const ECON1: Register = eth(0, 0x1F);
+
+// Bank 0
+pub const ERDPTL: Register = eth(0, 0x00);
+pub const EWRPTL: Register = eth(0, 0x02);
+pub const ETXSTL: Register = eth(0, 0x04);
+pub const ETXNDL: Register = eth(0, 0x06);
+pub const ERXSTL: Register = eth(0, 0x08);
+pub const ERXNDL: Register = eth(0, 0x0A);
+pub const ERXRDPTL: Register = eth(0, 0x0C);
+
+// Bank 1
+pub const ERXFCON: Register = eth(1, 0x18);
+pub const EPKTCNT: Register = eth(1, 0x19);
+
+// Bank 2
+pub const MACON1: Register = mac(2, 0x00);
+pub const MACON3: Register = mac(2, 0x02);
+pub const MACON4: Register = mac(2, 0x03);
+pub const MABBIPG: Register = mac(2, 0x04);
+pub const MAIPGL: Register = mac(2, 0x06);
+pub const MAIPGH: Register = mac(2, 0x07);
+pub const MAMXFLL: Register = mac(2, 0x0A);
+pub const MICMD: Register = mac(2, 0x12);
+pub const MIREGADR: Register = mac(2, 0x14);
+pub const MIWRL: Register = mac(2, 0x16);
+pub const MIWRH: Register = mac(2, 0x17);
+pub const MIRDL: Register = mac(2, 0x18);
+pub const MIRDH: Register = mac(2, 0x19);
+
+// Bank 3
+pub const MAADR5: Register = mac(3, 0x00);
+pub const MAADR6: Register = mac(3, 0x01);
+pub const MAADR3: Register = mac(3, 0x02);
+pub const MAADR4: Register = mac(3, 0x03);
+pub const MAADR1: Register = mac(3, 0x04);
+pub const MAADR2: Register = mac(3, 0x05);
+pub const MISTAT: Register = mac(3, 0x0A);
+pub const EREVID: Register = eth(3, 0x12);
+
+pub const EIE_LINKIE: u8 = 1 << 4;
+pub const EIE_PKTIE: u8 = 1 << 6;
+pub const EIE_INTIE: u8 = 1 << 7;
+
+pub const EIR_TXERIF: u8 = 1 << 1;
+pub const EIR_LINKIF: u8 = 1 << 4;
+
+pub const ESTAT_CLKRDY: u8 = 1 << 0;
+
+pub const ECON2_PKTDEC: u8 = 1 << 6;
+pub const ECON2_AUTOINC: u8 = 1 << 7;
+
+pub const ECON1_BSEL: u8 = 0b11;
+pub const ECON1_RXEN: u8 = 1 << 2;
+pub const ECON1_TXRTS: u8 = 1 << 3;
+pub const ECON1_TXRST: u8 = 1 << 7;
+
+pub const ERXFCON_BCEN: u8 = 1 << 0;
+pub const ERXFCON_CRCEN: u8 = 1 << 5;
+pub const ERXFCON_UCEN: u8 = 1 << 7;
+
+pub const MACON1_MARXEN: u8 = 1 << 0;
+
+pub const MACON3_FRMLNEN: u8 = 1 << 1;
+pub const MACON3_TXCRCEN: u8 = 1 << 4;
+/// Pad the short frames to 60 bytes, then append the CRC.
+pub const MACON3_PADCFG_60: u8 = 0b001 << 5;
+
+pub const MACON4_DEFER: u8 = 1 << 6;
+
+pub const MICMD_MIIRD: u8 = 1 << 0;
+
+pub const MISTAT_BUSY: u8 = 1 << 0;
+
+// PHY registers, accessed through the MII registers.
+pub const PHCON1: u8 = 0x00;
+pub const PHCON2: u8 = 0x10;
+pub const PHSTAT2: u8 = 0x11;
+pub const PHIE: u8 = 0x12;
+pub const PHIR: u8 = 0x13;
+
+pub const PHCON2_HDLDIS: u16 = 1 << 8;
+pub const PHSTAT2_LSTAT: u16 = 1 << 10;
+pub const PHIE_PGEIE: u16 = 1 << 1;
+pub const PHIE_PLNKIE: u16 = 1 << 4;
+
+// SPI instructions.
+pub const OP_RCR: u8 = 0b000 << 5;
+pub const OP_RBM: u8 = 0x3A;
+pub const OP_WCR: u8 = 0b010 << 5;
+pub const OP_WBM: u8 = 0x7A;
+pub const OP_BFS: u8 = 0b100 << 5;
+pub const OP_BFC: u8 = 0b101 << 5;
+pub const OP_SRC: u8 = 0xFF;
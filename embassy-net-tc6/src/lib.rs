@@ -0,0 +1,428 @@
+//! [`embassy-net`](https://crates.io/crates/embassy-net) driver for the 10BASE-T1S/T1L MAC-PHYs
+//! using the OPEN Alliance 10BASE-T1x MAC-PHY serial interface (TC6), such as the Microchip
+//! LAN8650/LAN8651 and the Analog Devices ADIN1110.
+#![no_std]
+
+// must be first
+mod fmt;
+
+pub mod regs;
+mod tc6;
+
+use embassy_futures::select::select3;
+use embassy_net_driver_channel as ch;
+use embassy_net_driver_channel::driver::LinkState;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::regs::*;
+use crate::tc6::{Footer, Tc6, CHUNK_PAYLOAD};
+
+const MTU: usize = 1514;
+/// Received frames end with their FCS.
+const FCS_LEN: usize = 4;
+
+/// Time for the MAC-PHY to come out of reset.
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+/// Time for an MDIO transaction.
+const MDIO_TIMEOUT: Duration = Duration::from_millis(10);
+/// Interval between checks of the link.
+const LINK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Type alias for the embassy-net driver
+pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
+
+/// The MAC-PHY, for its vendor specific configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Chip {
+    /// Microchip LAN8650/LAN8651, 10BASE-T1S.
+    Lan865x,
+    /// Analog Devices ADIN1110, 10BASE-T1L, strapped for the OPEN Alliance SPI protocol.
+    Adin1110,
+}
+
+/// Error.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI error.
+    Spi(E),
+    /// The MAC-PHY rejected the header of a transaction.
+    HeaderBad,
+    /// Bad parity of a received footer.
+    Parity,
+    /// The MAC-PHY didn't come out of reset.
+    ResetTimeout,
+    /// The PHY didn't answer on MDIO.
+    MdioTimeout,
+}
+
+/// Internal state for the embassy-net integration.
+pub struct State<const N_RX: usize, const N_TX: usize> {
+    ch_state: ch::State<MTU, N_RX, N_TX>,
+}
+
+impl<const N_RX: usize, const N_TX: usize> State<N_RX, N_TX> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            ch_state: ch::State::new(),
+        }
+    }
+}
+
+/// A received frame, put together from the chunks.
+struct RxFrame {
+    buf: [u8; MTU + FCS_LEN],
+    len: usize,
+    active: bool,
+}
+
+impl RxFrame {
+    const fn new() -> Self {
+        Self {
+            buf: [0; MTU + FCS_LEN],
+            len: 0,
+            active: false,
+        }
+    }
+
+    fn start(&mut self) {
+        self.active = true;
+        self.len = 0;
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        if !self.active {
+            return;
+        }
+        if self.len + data.len() > self.buf.len() {
+            warn!("received frame too long, dropping");
+            self.active = false;
+            return;
+        }
+        self.buf[self.len..][..data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+
+    fn end(&mut self, drop: bool, rx_chan: &mut ch::RxRunner<'_, MTU>) {
+        if self.active && !drop && self.len > FCS_LEN {
+            let len = self.len - FCS_LEN;
+            match rx_chan.try_rx_buf() {
+                Some(buf) => {
+                    buf[..len].copy_from_slice(&self.buf[..len]);
+                    rx_chan.rx_done(len);
+                }
+                None => warn!("rx buffer full, dropping frame"),
+            }
+        }
+        self.active = false;
+    }
+
+    /// Take the data of a received chunk.
+    fn push(&mut self, footer: Footer, payload: &[u8; CHUNK_PAYLOAD], rx_chan: &mut ch::RxRunner<'_, MTU>) {
+        let start = footer.start().then(|| footer.start_offset());
+        let end = footer.end().then(|| footer.end_offset());
+        match (start, end) {
+            // A whole frame.
+            (Some(start), Some(end)) if start <= end => {
+                self.start();
+                self.append(&payload[start..=end]);
+                self.end(footer.frame_drop(), rx_chan);
+            }
+            // The end of a frame, then maybe the start of the next one.
+            (start, Some(end)) => {
+                self.append(&payload[..=end]);
+                self.end(footer.frame_drop(), rx_chan);
+                if let Some(start) = start {
+                    self.start();
+                    self.append(&payload[start..]);
+                }
+            }
+            (Some(start), None) => {
+                self.start();
+                self.append(&payload[start..]);
+            }
+            (None, None) => self.append(payload),
+        }
+    }
+}
+
+/// The MAC-PHY and its configuration.
+struct Mac<SPI, RST> {
+    tc6: Tc6<SPI>,
+    chip: Chip,
+    mac_addr: [u8; 6],
+    reset: RST,
+}
+
+impl<SPI: SpiDevice, RST: OutputPin> Mac<SPI, RST> {
+    /// Reset and configure the MAC-PHY.
+    async fn init(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.reset.set_low().ok();
+        // Ensure the reset is registered.
+        Timer::after(Duration::from_millis(1)).await;
+        self.reset.set_high().ok();
+
+        let deadline = Instant::now() + RESET_TIMEOUT;
+        loop {
+            Timer::after(Duration::from_millis(1)).await;
+            // Transactions can fail until the MAC-PHY is out of reset.
+            if let Ok(status) = self.tc6.read_register(STATUS0).await {
+                if status & STATUS0_RESETC != 0 {
+                    break;
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(Error::ResetTimeout);
+            }
+        }
+        self.tc6.write_register(STATUS0, STATUS0_RESETC).await?;
+
+        match self.chip {
+            Chip::Lan865x => self.init_lan865x().await?,
+            Chip::Adin1110 => self.init_adin1110().await?,
+        }
+
+        // Store and forward, with 64 byte chunks. Setting SYNC ends the configuration.
+        self.tc6
+            .modify_register(CONFIG0, |v| {
+                v & !(CONFIG0_TXCTE | CONFIG0_RXCTE | CONFIG0_CPS_MASK) | CONFIG0_CPS_64 | CONFIG0_SYNC
+            })
+            .await
+    }
+
+    async fn init_lan865x(&mut self) -> Result<(), Error<SPI::Error>> {
+        let mac = self.mac_addr;
+        self.tc6
+            .write_register(LAN865X_MAC_SAB1, u32::from_le_bytes([mac[0], mac[1], mac[2], mac[3]]))
+            .await?;
+        self.tc6
+            .write_register(LAN865X_MAC_SAT1, u32::from_le_bytes([mac[4], mac[5], 0, 0]))
+            .await?;
+
+        // Accept all the multicast frames, e.g. for IPv6 neighbor discovery.
+        self.tc6.write_register(LAN865X_MAC_HRB, 0xFFFF_FFFF).await?;
+        self.tc6.write_register(LAN865X_MAC_HRT, 0xFFFF_FFFF).await?;
+        self.tc6
+            .modify_register(LAN865X_MAC_NCFGR, |v| v | LAN865X_MAC_NCFGR_MTIHEN)
+            .await?;
+
+        self.tc6
+            .modify_register(LAN865X_MAC_NCR, |v| v | LAN865X_MAC_NCR_TXEN | LAN865X_MAC_NCR_RXEN)
+            .await
+    }
+
+    async fn init_adin1110(&mut self) -> Result<(), Error<SPI::Error>> {
+        // Forward to the host: our address, broadcast, and multicast.
+        let filters = [
+            (self.mac_addr, [0xFF; 6]),
+            ([0xFF; 6], [0xFF; 6]),
+            ([0x01, 0, 0, 0, 0, 0], [0x01, 0, 0, 0, 0, 0]),
+        ];
+        for (i, (addr, mask)) in filters.into_iter().enumerate() {
+            let i = i as u16;
+            let upper = |a: [u8; 6]| u16::from_be_bytes([a[0], a[1]]) as u32;
+            let lower = |a: [u8; 6]| u32::from_be_bytes([a[2], a[3], a[4], a[5]]);
+            self.tc6.write_register(adin1110_addr_msk_upr(i), upper(mask)).await?;
+            self.tc6.write_register(adin1110_addr_msk_lwr(i), lower(mask)).await?;
+            self.tc6
+                .write_register(
+                    adin1110_addr_filt_upr(i),
+                    ADIN1110_ADDR_FILT_APPLY2PORT1 | ADIN1110_ADDR_FILT_TO_HOST | upper(addr),
+                )
+                .await?;
+            self.tc6.write_register(adin1110_addr_filt_lwr(i), lower(addr)).await?;
+        }
+
+        // The PHY comes out of reset in software power-down.
+        self.adin1110_mdio_write(ADIN1110_MMD_VS1, ADIN1110_CRSM_SFT_PD_CNTRL, 0)
+            .await
+    }
+
+    /// Write a clause 45 register of the PHY of the ADIN1110.
+    async fn adin1110_mdio_write(&mut self, mmd: u32, reg: u32, value: u32) -> Result<(), Error<SPI::Error>> {
+        let base = ADIN1110_MDIO_PRTAD << 21 | mmd << 16;
+        for op in [ADIN1110_MDIOACC_OP_ADDR | reg, ADIN1110_MDIOACC_OP_WRITE | value] {
+            self.tc6.write_register(ADIN1110_MDIOACC, base | op).await?;
+            let deadline = Instant::now() + MDIO_TIMEOUT;
+            while self.tc6.read_register(ADIN1110_MDIOACC).await? & ADIN1110_MDIOACC_TRDONE == 0 {
+                if Instant::now() > deadline {
+                    return Err(Error::MdioTimeout);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reset and configure the MAC-PHY, until it works.
+    async fn init_retry(&mut self) {
+        while let Err(e) = self.init().await {
+            match e {
+                Error::ResetTimeout => warn!("MAC-PHY reset timed out"),
+                _ => warn!("MAC-PHY configuration failed"),
+            }
+            Timer::after(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Background runner for the MAC-PHY.
+///
+/// You must call `.run()` in a background task for the chip to operate.
+pub struct Runner<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> {
+    mac: Mac<SPI, RST>,
+    ch: ch::Runner<'d, MTU>,
+    int: INT,
+}
+
+impl<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'d, SPI, INT, RST> {
+    /// Read a register of the MAC-PHY.
+    pub async fn read_register(&mut self, reg: Register) -> Result<u32, Error<SPI::Error>> {
+        self.mac.tc6.read_register(reg).await
+    }
+
+    /// Write a register of the MAC-PHY, e.g. for vendor specific settings before calling `run()`.
+    ///
+    /// Such settings are lost if the MAC-PHY resets by itself: the runner then applies its own
+    /// configuration only.
+    pub async fn write_register(&mut self, reg: Register, value: u32) -> Result<(), Error<SPI::Error>> {
+        self.mac.tc6.write_register(reg, value).await
+    }
+
+    pub async fn run(mut self) -> ! {
+        let (state_chan, mut rx_chan, mut tx_chan) = self.ch.split();
+        let mut rx_frame = RxFrame::new();
+        // Offset of the next chunk of the frame being sent.
+        let mut tx_offset = 0;
+        let mut tx_credits = 0;
+        let mut rx_chunks = 0;
+        // Exchange a chunk even if there's nothing to do, for the footer.
+        let mut poll = true;
+        let mut next_link_poll = Instant::now();
+
+        loop {
+            if Instant::now() >= next_link_poll {
+                match self.mac.tc6.read_register(PHY_BASIC_STATUS).await {
+                    Ok(status) if status & PHY_BASIC_STATUS_LINK != 0 => state_chan.set_link_state(LinkState::Up),
+                    Ok(_) => state_chan.set_link_state(LinkState::Down),
+                    Err(_) => warn!("link status read failed"),
+                }
+                next_link_poll = Instant::now() + LINK_POLL_INTERVAL;
+            }
+
+            let tx_ready = tx_credits > 0 && tx_chan.try_tx_buf().is_some();
+            if !poll && !tx_ready && rx_chunks == 0 {
+                // Nothing to exchange: wait for the MAC-PHY, a frame to send, or the next link check.
+                let has_credits = tx_credits > 0;
+                select3(
+                    self.int.wait_for_low(),
+                    async {
+                        if has_credits {
+                            tx_chan.tx_buf().await;
+                        } else {
+                            // The MAC-PHY interrupts when credits come back.
+                            core::future::pending::<()>().await;
+                        }
+                    },
+                    Timer::at(next_link_poll),
+                )
+                .await;
+                poll = true;
+                continue;
+            }
+            poll = false;
+
+            let (tx, start, end) = match tx_chan.try_tx_buf() {
+                Some(buf) if tx_credits > 0 => {
+                    let tx = &buf[tx_offset..][..(buf.len() - tx_offset).min(CHUNK_PAYLOAD)];
+                    let start = tx_offset == 0;
+                    let end = tx_offset + tx.len() == buf.len();
+                    (tx, start, end)
+                }
+                _ => (&[][..], false, false),
+            };
+            let tx_len = tx.len();
+
+            let mut payload = [0; CHUNK_PAYLOAD];
+            let footer = match self.mac.tc6.transfer_chunk(tx, start, end, &mut payload).await {
+                Ok(footer) => footer,
+                Err(e) => {
+                    warn!("data transaction failed");
+                    // The received chunk is lost, and so is the frame it belongs to.
+                    rx_frame.active = false;
+                    // A rejected header means the chunk was ignored, so send it again. Otherwise it's
+                    // unknown whether it got through, so drop the frame rather than corrupt it.
+                    if tx_len > 0 && !matches!(e, Error::HeaderBad) {
+                        tx_chan.tx_done();
+                        tx_offset = 0;
+                    }
+                    poll = true;
+                    continue;
+                }
+            };
+
+            if !footer.sync() {
+                warn!("MAC-PHY reset, configuring again");
+                state_chan.set_link_state(LinkState::Down);
+                self.mac.init_retry().await;
+                rx_frame.active = false;
+                // Frames are sent whole: start the current one again.
+                tx_offset = 0;
+                tx_credits = 0;
+                rx_chunks = 0;
+                poll = true;
+                next_link_poll = Instant::now();
+                continue;
+            }
+
+            if tx_len > 0 {
+                tx_offset += tx_len;
+                if end {
+                    tx_chan.tx_done();
+                    tx_offset = 0;
+                }
+            }
+            tx_credits = footer.tx_credits();
+            rx_chunks = footer.rx_chunks();
+
+            if footer.extended_status() {
+                // Clear the events, which are only reported.
+                match self.mac.tc6.read_register(STATUS0).await {
+                    Ok(status) => {
+                        debug!("STATUS0: {:x}", status);
+                        self.mac.tc6.write_register(STATUS0, status).await.ok();
+                    }
+                    Err(_) => warn!("STATUS0 read failed"),
+                }
+            }
+
+            if footer.data_valid() {
+                rx_frame.push(footer, &payload, &mut rx_chan);
+            }
+        }
+    }
+}
+
+/// Obtain a driver for using the MAC-PHY `chip` with [`embassy-net`](https://crates.io/crates/embassy-net).
+pub async fn new<'a, const N_RX: usize, const N_TX: usize, SPI: SpiDevice, INT: Wait, RST: OutputPin>(
+    chip: Chip,
+    mac_addr: [u8; 6],
+    state: &'a mut State<N_RX, N_TX>,
+    spi_dev: SPI,
+    int: INT,
+    reset: RST,
+) -> Result<(Device<'a>, Runner<'a, SPI, INT, RST>), Error<SPI::Error>> {
+    let (ch, device) = ch::new(&mut state.ch_state, mac_addr);
+    let mut mac = Mac {
+        tc6: Tc6::new(spi_dev),
+        chip,
+        mac_addr,
+        reset,
+    };
+    mac.init().await?;
+    Ok((device, Runner { mac, ch, int }))
+}
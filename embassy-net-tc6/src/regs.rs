@@ -0,0 +1,80 @@
+//! Registers of the OPEN Alliance MAC-PHYs, and the vendor ones used by the driver.
+
+/// A register, in one of the memory map selectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Register {
+    /// Memory map selector.
+    pub mms: u8,
+    pub addr: u16,
+}
+
+impl Register {
+    pub const fn new(mms: u8, addr: u16) -> Self {
+        Self { mms, addr }
+    }
+}
+
+const fn reg(mms: u8, addr: u16) -> Register {
+    Register::new(mms, addr)
+}
+
+// Standard control and status, MMS 0.
+pub const CONFIG0: Register = reg(0, 0x04);
+pub const STATUS0: Register = reg(0, 0x08);
+
+// Clause 22 PHY registers, mapped in MMS 0.
+pub const PHY_BASIC_STATUS: Register = reg(0, 0xFF01);
+
+pub const CONFIG0_SYNC: u32 = 1 << 15;
+pub const CONFIG0_TXCTE: u32 = 1 << 9;
+pub const CONFIG0_RXCTE: u32 = 1 << 8;
+pub const CONFIG0_CPS_MASK: u32 = 0b111;
+/// 64 byte chunks.
+pub const CONFIG0_CPS_64: u32 = 6;
+
+pub const STATUS0_RESETC: u32 = 1 << 6;
+
+pub const PHY_BASIC_STATUS_LINK: u32 = 1 << 2;
+
+// Microchip LAN865x MAC, MMS 1.
+pub const LAN865X_MAC_NCR: Register = reg(1, 0x00);
+pub const LAN865X_MAC_NCFGR: Register = reg(1, 0x01);
+pub const LAN865X_MAC_HRB: Register = reg(1, 0x20);
+pub const LAN865X_MAC_HRT: Register = reg(1, 0x21);
+pub const LAN865X_MAC_SAB1: Register = reg(1, 0x22);
+pub const LAN865X_MAC_SAT1: Register = reg(1, 0x23);
+
+pub const LAN865X_MAC_NCR_TXEN: u32 = 1 << 3;
+pub const LAN865X_MAC_NCR_RXEN: u32 = 1 << 2;
+pub const LAN865X_MAC_NCFGR_MTIHEN: u32 = 1 << 6;
+
+// Analog Devices ADIN1110, MMS 0.
+pub const ADIN1110_MDIOACC: Register = reg(0, 0x20);
+
+/// Address filter `i`: upper and lower parts of the address, then of the mask.
+pub const fn adin1110_addr_filt_upr(i: u16) -> Register {
+    reg(0, 0x50 + 2 * i)
+}
+pub const fn adin1110_addr_filt_lwr(i: u16) -> Register {
+    reg(0, 0x51 + 2 * i)
+}
+pub const fn adin1110_addr_msk_upr(i: u16) -> Register {
+    reg(0, 0x70 + 2 * i)
+}
+pub const fn adin1110_addr_msk_lwr(i: u16) -> Register {
+    reg(0, 0x71 + 2 * i)
+}
+
+pub const ADIN1110_ADDR_FILT_APPLY2PORT1: u32 = 1 << 30;
+pub const ADIN1110_ADDR_FILT_TO_HOST: u32 = 1 << 16;
+
+pub const ADIN1110_MDIOACC_TRDONE: u32 = 1 << 31;
+pub const ADIN1110_MDIOACC_OP_ADDR: u32 = 0 << 26;
+pub const ADIN1110_MDIOACC_OP_WRITE: u32 = 1 << 26;
+/// The internal PHY.
+pub const ADIN1110_MDIO_PRTAD: u32 = 1;
+
+/// Software power-down of the PHY, in the vendor specific MMD 0x1E.
+pub const ADIN1110_MMD_VS1: u32 = 0x1E;
+pub const ADIN1110_CRSM_SFT_PD_CNTRL: u32 = 0x8812;
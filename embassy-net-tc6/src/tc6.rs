@@ -0,0 +1,202 @@
+//! The OPEN Alliance 10BASE-T1x MAC-PHY serial interface: control and data transactions.
+
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::regs::Register;
+use crate::Error;
+
+/// Size of the payload of a data chunk.
+pub const CHUNK_PAYLOAD: usize = 64;
+/// Size of a data chunk on the bus: the payload and the header, or the footer.
+pub const CHUNK_SIZE: usize = CHUNK_PAYLOAD + 4;
+
+const DNC: u32 = 1 << 31;
+
+// Control header.
+const CTRL_HDRB: u32 = 1 << 30;
+const CTRL_WNR: u32 = 1 << 29;
+
+// Data header.
+const DATA_DV: u32 = 1 << 21;
+const DATA_SV: u32 = 1 << 20;
+const DATA_EV: u32 = 1 << 14;
+
+// Data footer.
+const FOOTER_EXST: u32 = 1 << 31;
+const FOOTER_HDRB: u32 = 1 << 30;
+const FOOTER_SYNC: u32 = 1 << 29;
+const FOOTER_DV: u32 = 1 << 21;
+const FOOTER_SV: u32 = 1 << 20;
+const FOOTER_FD: u32 = 1 << 15;
+const FOOTER_EV: u32 = 1 << 14;
+
+/// Set the parity bit of `word`, for odd parity.
+fn with_parity(word: u32) -> u32 {
+    let word = word & !1;
+    word | (word.count_ones() & 1 == 0) as u32
+}
+
+fn parity_ok(word: u32) -> bool {
+    word.count_ones() & 1 == 1
+}
+
+/// The footer of a received data chunk.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Footer(u32);
+
+impl Footer {
+    /// The MAC-PHY has events pending in STATUS0.
+    pub fn extended_status(&self) -> bool {
+        self.0 & FOOTER_EXST != 0
+    }
+
+    /// The MAC-PHY is configured: cleared when it resets.
+    pub fn sync(&self) -> bool {
+        self.0 & FOOTER_SYNC != 0
+    }
+
+    /// Receive chunks available.
+    pub fn rx_chunks(&self) -> u8 {
+        (self.0 >> 24) as u8 & 0x1F
+    }
+
+    /// Transmit credits, in chunks.
+    pub fn tx_credits(&self) -> u8 {
+        (self.0 >> 1) as u8 & 0x1F
+    }
+
+    /// The payload carries received data.
+    pub fn data_valid(&self) -> bool {
+        self.0 & FOOTER_DV != 0
+    }
+
+    /// A frame starts in the payload, at [`start_offset`](Self::start_offset).
+    pub fn start(&self) -> bool {
+        self.0 & FOOTER_SV != 0
+    }
+
+    /// Offset of the start of the frame, in bytes.
+    pub fn start_offset(&self) -> usize {
+        ((self.0 >> 16) & 0xF) as usize * 4
+    }
+
+    /// A frame ends in the payload, at [`end_offset`](Self::end_offset).
+    pub fn end(&self) -> bool {
+        self.0 & FOOTER_EV != 0
+    }
+
+    /// Offset of the last byte of the frame.
+    pub fn end_offset(&self) -> usize {
+        ((self.0 >> 8) & 0x3F) as usize
+    }
+
+    /// The frame ending in the payload must be dropped.
+    pub fn frame_drop(&self) -> bool {
+        self.0 & FOOTER_FD != 0
+    }
+}
+
+/// The SPI interface of the MAC-PHY.
+pub struct Tc6<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> Tc6<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+
+    fn control_header(reg: Register, write: bool) -> u32 {
+        let wnr = if write { CTRL_WNR } else { 0 };
+        // LEN is the number of registers minus one: always a single one.
+        with_parity(wnr | (reg.mms as u32 & 0xF) << 24 | (reg.addr as u32) << 8)
+    }
+
+    /// Check the echoed header of a control transaction.
+    fn check_echo(sent: u32, echoed: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let echoed = u32::from_be_bytes(echoed.try_into().unwrap());
+        if echoed & CTRL_HDRB != 0 || echoed != sent {
+            return Err(Error::HeaderBad);
+        }
+        Ok(())
+    }
+
+    pub async fn read_register(&mut self, reg: Register) -> Result<u32, Error<SPI::Error>> {
+        let header = Self::control_header(reg, false);
+        // The header, then room for the echo of the header and the value.
+        let mut buf = [0; 12];
+        buf[..4].copy_from_slice(&header.to_be_bytes());
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut buf)])
+            .await
+            .map_err(Error::Spi)?;
+        Self::check_echo(header, &buf[4..8])?;
+        Ok(u32::from_be_bytes(buf[8..12].try_into().unwrap()))
+    }
+
+    pub async fn write_register(&mut self, reg: Register, value: u32) -> Result<(), Error<SPI::Error>> {
+        let header = Self::control_header(reg, true);
+        // The header and the value, then room for the end of their echo.
+        let mut buf = [0; 12];
+        buf[..4].copy_from_slice(&header.to_be_bytes());
+        buf[4..8].copy_from_slice(&value.to_be_bytes());
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut buf)])
+            .await
+            .map_err(Error::Spi)?;
+        Self::check_echo(header, &buf[4..8])
+    }
+
+    pub async fn modify_register(
+        &mut self,
+        reg: Register,
+        f: impl FnOnce(u32) -> u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let value = self.read_register(reg).await?;
+        self.write_register(reg, f(value)).await
+    }
+
+    /// Exchange a data chunk: send `tx`, which `start`s and/or `end`s a frame, and receive a
+    /// payload in `rx`, whose footer is returned.
+    ///
+    /// The chunk carries no data if `tx` is empty.
+    pub async fn transfer_chunk(
+        &mut self,
+        tx: &[u8],
+        start: bool,
+        end: bool,
+        rx: &mut [u8; CHUNK_PAYLOAD],
+    ) -> Result<Footer, Error<SPI::Error>> {
+        let mut header = DNC;
+        if !tx.is_empty() {
+            header |= DATA_DV;
+            if start {
+                // Frames always start at the beginning of the payload.
+                header |= DATA_SV;
+            }
+            if end {
+                header |= DATA_EV | ((tx.len() - 1) as u32) << 8;
+            }
+        }
+        let header = with_parity(header);
+
+        let mut chunk = [0; CHUNK_SIZE];
+        chunk[..4].copy_from_slice(&header.to_be_bytes());
+        chunk[4..4 + tx.len()].copy_from_slice(tx);
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut chunk)])
+            .await
+            .map_err(Error::Spi)?;
+
+        let footer = u32::from_be_bytes(chunk[CHUNK_PAYLOAD..].try_into().unwrap());
+        if !parity_ok(footer) {
+            return Err(Error::Parity);
+        }
+        if footer & FOOTER_HDRB != 0 {
+            return Err(Error::HeaderBad);
+        }
+        rx.copy_from_slice(&chunk[..CHUNK_PAYLOAD]);
+        Ok(Footer(footer))
+    }
+}
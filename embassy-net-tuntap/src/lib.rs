@@ -1,22 +1,26 @@
+#![doc = include_str!("../README.md")]
 use std::io;
 use std::io::{Read, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::task::Context;
 
 use async_io::Async;
-use embassy_net_driver::{self, Capabilities, Driver, LinkState};
+use embassy_net_driver::{self, Capabilities, Driver, LinkState, Medium};
 use log::*;
 
 pub const SIOCGIFMTU: libc::c_ulong = 0x8921;
 pub const _SIOCGIFINDEX: libc::c_ulong = 0x8933;
 pub const _ETH_P_ALL: libc::c_short = 0x0003;
 pub const TUNSETIFF: libc::c_ulong = 0x400454CA;
-pub const _IFF_TUN: libc::c_int = 0x0001;
+pub const IFF_TUN: libc::c_int = 0x0001;
 pub const IFF_TAP: libc::c_int = 0x0002;
 pub const IFF_NO_PI: libc::c_int = 0x1000;
 
 const ETHERNET_HEADER_LEN: usize = 14;
 
+/// Default Ethernet address of TAP devices.
+const DEFAULT_ETHERNET_ADDRESS: [u8; 6] = [0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+
 #[repr(C)]
 #[derive(Debug)]
 struct ifreq {
@@ -46,9 +50,11 @@ fn ifreq_ioctl(lower: libc::c_int, ifreq: &mut ifreq, cmd: libc::c_ulong) -> io:
     Ok(ifreq.ifr_data)
 }
 
+/// A TUN or TAP interface, opened in non-blocking mode.
 #[derive(Debug)]
 pub struct TunTap {
     fd: libc::c_int,
+    medium: Medium,
     mtu: usize,
 }
 
@@ -59,7 +65,14 @@ impl AsRawFd for TunTap {
 }
 
 impl TunTap {
-    pub fn new(name: &str) -> io::Result<TunTap> {
+    /// Open the interface `name`: a TAP interface for [`Medium::Ethernet`], a TUN one for [`Medium::Ip`].
+    ///
+    /// The interface must exist, and be owned by the user, see `ip tuntap add`.
+    pub fn new(name: &str, medium: Medium) -> io::Result<TunTap> {
+        if name.len() >= libc::IF_NAMESIZE || !matches!(medium, Medium::Ethernet | Medium::Ip) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
         unsafe {
             let fd = libc::open(
                 "/dev/net/tun\0".as_ptr() as *const libc::c_char,
@@ -70,23 +83,39 @@ impl TunTap {
             }
 
             let mut ifreq = ifreq_for(name);
-            ifreq.ifr_data = IFF_TAP | IFF_NO_PI;
-            ifreq_ioctl(fd, &mut ifreq, TUNSETIFF)?;
+            ifreq.ifr_data = match medium {
+                Medium::Ethernet => IFF_TAP,
+                _ => IFF_TUN,
+            } | IFF_NO_PI;
+            if let Err(e) = ifreq_ioctl(fd, &mut ifreq, TUNSETIFF) {
+                libc::close(fd);
+                return Err(e);
+            }
 
             let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, libc::IPPROTO_IP);
             if socket == -1 {
-                return Err(io::Error::last_os_error());
+                let e = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(e);
             }
-
             let ip_mtu = ifreq_ioctl(socket, &mut ifreq, SIOCGIFMTU);
             libc::close(socket);
-            let ip_mtu = ip_mtu? as usize;
+            let ip_mtu = match ip_mtu {
+                Ok(ip_mtu) => ip_mtu as usize,
+                Err(e) => {
+                    libc::close(fd);
+                    return Err(e);
+                }
+            };
 
             // SIOCGIFMTU returns the IP MTU (typically 1500 bytes.)
             // smoltcp counts the entire Ethernet packet in the MTU, so add the Ethernet header size to it.
-            let mtu = ip_mtu + ETHERNET_HEADER_LEN;
+            let mtu = match medium {
+                Medium::Ethernet => ip_mtu + ETHERNET_HEADER_LEN,
+                _ => ip_mtu,
+            };
 
-            Ok(TunTap { fd, mtu })
+            Ok(TunTap { fd, medium, mtu })
         }
     }
 }
@@ -125,16 +154,37 @@ impl io::Write for TunTap {
     }
 }
 
+/// `embassy-net` driver for a TUN or TAP interface.
 pub struct TunTapDevice {
     device: Async<TunTap>,
+    ethernet_address: [u8; 6],
 }
 
 impl TunTapDevice {
+    /// Open the TAP interface `name`, for a stack with the `medium-ethernet` feature.
     pub fn new(name: &str) -> io::Result<TunTapDevice> {
+        Self::new_with_medium(name, Medium::Ethernet)
+    }
+
+    /// Open the TUN interface `name`, for a stack with the `medium-ip` feature.
+    pub fn new_tun(name: &str) -> io::Result<TunTapDevice> {
+        Self::new_with_medium(name, Medium::Ip)
+    }
+
+    fn new_with_medium(name: &str, medium: Medium) -> io::Result<TunTapDevice> {
         Ok(Self {
-            device: Async::new(TunTap::new(name)?)?,
+            device: Async::new(TunTap::new(name, medium)?)?,
+            ethernet_address: DEFAULT_ETHERNET_ADDRESS,
         })
     }
+
+    /// Set the Ethernet address of the device, instead of the default `02:03:04:05:06:07`.
+    ///
+    /// Give each device on the same network its own address, e.g. when several
+    /// instances share a bridge.
+    pub fn set_ethernet_address(&mut self, address: [u8; 6]) {
+        self.ethernet_address = address;
+    }
 }
 
 impl Driver for TunTapDevice {
@@ -164,7 +214,11 @@ impl Driver for TunTapDevice {
         }
     }
 
-    fn transmit(&mut self, _cx: &mut Context) -> Option<Self::TxToken<'_>> {
+    fn transmit(&mut self, cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        // The queue of the interface is full: wait for room, instead of dropping the packet.
+        if !self.device.poll_writable(cx).is_ready() {
+            return None;
+        }
         Some(TxToken {
             device: &mut self.device,
         })
@@ -173,6 +227,7 @@ impl Driver for TunTapDevice {
     fn capabilities(&self) -> Capabilities {
         let mut caps = Capabilities::default();
         caps.max_transmission_unit = self.device.get_ref().mtu;
+        caps.medium = self.device.get_ref().medium;
         caps
     }
 
@@ -181,7 +236,7 @@ impl Driver for TunTapDevice {
     }
 
     fn ethernet_address(&self) -> [u8; 6] {
-        [0x02, 0x03, 0x04, 0x05, 0x06, 0x07]
+        self.ethernet_address
     }
 }
 
@@ -212,7 +267,7 @@ impl<'a> embassy_net_driver::TxToken for TxToken<'a> {
         let mut buffer = vec![0; len];
         let result = f(&mut buffer);
 
-        // todo handle WouldBlock with async
+        // A full queue is only possible if it filled up since `transmit` was polled.
         match self.device.get_mut().write(&buffer) {
             Ok(_) => {}
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => info!("transmit WouldBlock"),
@@ -0,0 +1,47 @@
+//! Supported WIZnet chips.
+//!
+//! The chips share the socket registers, at different addresses, and their SPI frames differ.
+mod w5100s;
+mod w5500;
+
+pub use w5100s::W5100S;
+pub use w5500::W5500;
+
+pub(crate) mod sealed {
+    pub trait Chip {
+        type Address: Copy;
+
+        const COMMON_MODE: Self::Address;
+        const COMMON_MAC: Self::Address;
+        const COMMON_SOCKET_INTR: Self::Address;
+        const COMMON_PHY_CFG: Self::Address;
+
+        const SOCKET_MODE: Self::Address;
+        const SOCKET_COMMAND: Self::Address;
+        const SOCKET_RXBUF_SIZE: Self::Address;
+        const SOCKET_TXBUF_SIZE: Self::Address;
+        const SOCKET_TX_FREE_SIZE: Self::Address;
+        const SOCKET_TX_DATA_WRITE_PTR: Self::Address;
+        const SOCKET_RECVD_SIZE: Self::Address;
+        const SOCKET_RX_DATA_READ_PTR: Self::Address;
+        const SOCKET_INTR_MASK: Self::Address;
+        const SOCKET_INTR: Self::Address;
+
+        /// Value of the socket mode register: MACRAW mode with MAC filtering.
+        const SOCKET_MODE_VALUE: u8;
+
+        /// Size of the RX and TX buffers of the raw socket, in bytes.
+        const BUF_SIZE: u16;
+
+        /// Address of the RX buffer byte at `ptr`, as read from the RX read pointer.
+        fn rx_addr(ptr: u16) -> Self::Address;
+        /// Address of the TX buffer byte at `ptr`, as read from the TX write pointer.
+        fn tx_addr(ptr: u16) -> Self::Address;
+
+        /// Header of the SPI frame accessing `address`, sent before the data.
+        fn header(address: Self::Address, write: bool) -> [u8; 3];
+    }
+}
+
+/// A WIZnet ethernet chip.
+pub trait Chip: sealed::Chip {}
@@ -0,0 +1,50 @@
+use super::sealed;
+
+const SOCKET_BASE: u16 = 0x400;
+const TX_BASE: u16 = 0x4000;
+const RX_BASE: u16 = 0x6000;
+
+const OP_READ: u8 = 0x0F;
+const OP_WRITE: u8 = 0xF0;
+
+/// WIZnet W5100S.
+pub enum W5100S {}
+
+impl super::Chip for W5100S {}
+impl sealed::Chip for W5100S {
+    type Address = u16;
+
+    const COMMON_MODE: Self::Address = 0x00;
+    const COMMON_MAC: Self::Address = 0x09;
+    const COMMON_SOCKET_INTR: Self::Address = 0x16;
+    const COMMON_PHY_CFG: Self::Address = 0x3C;
+
+    const SOCKET_MODE: Self::Address = SOCKET_BASE + 0x00;
+    const SOCKET_COMMAND: Self::Address = SOCKET_BASE + 0x01;
+    const SOCKET_RXBUF_SIZE: Self::Address = SOCKET_BASE + 0x1E;
+    const SOCKET_TXBUF_SIZE: Self::Address = SOCKET_BASE + 0x1F;
+    const SOCKET_TX_FREE_SIZE: Self::Address = SOCKET_BASE + 0x20;
+    const SOCKET_TX_DATA_WRITE_PTR: Self::Address = SOCKET_BASE + 0x24;
+    const SOCKET_RECVD_SIZE: Self::Address = SOCKET_BASE + 0x26;
+    const SOCKET_RX_DATA_READ_PTR: Self::Address = SOCKET_BASE + 0x28;
+    const SOCKET_INTR_MASK: Self::Address = SOCKET_BASE + 0x2C;
+    const SOCKET_INTR: Self::Address = SOCKET_BASE + 0x02;
+
+    const SOCKET_MODE_VALUE: u8 = (1 << 2) | (1 << 6);
+
+    const BUF_SIZE: u16 = 0x2000;
+
+    // The buffers are mapped in the address space, and don't wrap by themselves.
+    fn rx_addr(ptr: u16) -> Self::Address {
+        RX_BASE + (ptr & (Self::BUF_SIZE - 1))
+    }
+
+    fn tx_addr(ptr: u16) -> Self::Address {
+        TX_BASE + (ptr & (Self::BUF_SIZE - 1))
+    }
+
+    fn header(address: Self::Address, write: bool) -> [u8; 3] {
+        let [hi, lo] = address.to_be_bytes();
+        [if write { OP_WRITE } else { OP_READ }, hi, lo]
+    }
+}
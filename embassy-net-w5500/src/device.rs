@@ -1,62 +1,70 @@
+use core::marker::PhantomData;
+
 use embedded_hal_async::spi::SpiDevice;
 
+use crate::chip::Chip;
 use crate::socket;
 use crate::spi::SpiInterface;
 
-pub const MODE: u16 = 0x00;
-pub const MAC: u16 = 0x09;
-pub const SOCKET_INTR: u16 = 0x18;
-pub const PHY_CFG: u16 = 0x2E;
-
-#[repr(u8)]
-pub enum RegisterBlock {
-    Common = 0x00,
-    Socket0 = 0x01,
-    TxBuf = 0x02,
-    RxBuf = 0x03,
-}
-
-/// W5500 in MACRAW mode
-#[derive(Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct W5500<SPI> {
-    bus: SpiInterface<SPI>,
+/// WIZnet chip in MACRAW mode
+pub struct WiznetDevice<C: Chip, SPI> {
+    bus: SpiInterface<SPI, C>,
 }
 
-impl<SPI: SpiDevice> W5500<SPI> {
-    /// Create and initialize the W5500 driver
-    pub async fn new(spi: SPI, mac_addr: [u8; 6]) -> Result<W5500<SPI>, SPI::Error> {
-        let mut bus = SpiInterface(spi);
+impl<C: Chip, SPI: SpiDevice> WiznetDevice<C, SPI> {
+    /// Create and initialize the driver
+    pub async fn new(spi: SPI, mac_addr: [u8; 6]) -> Result<Self, SPI::Error> {
+        let mut bus = SpiInterface(spi, PhantomData);
         // Reset device
-        bus.write_frame(RegisterBlock::Common, MODE, &[0x80]).await?;
+        bus.write_frame(C::COMMON_MODE, &[0x80]).await?;
 
         // Enable interrupt pin
-        bus.write_frame(RegisterBlock::Common, SOCKET_INTR, &[0x01]).await?;
+        bus.write_frame(C::COMMON_SOCKET_INTR, &[0x01]).await?;
         // Enable receive interrupt
-        bus.write_frame(
-            RegisterBlock::Socket0,
-            socket::SOCKET_INTR_MASK,
-            &[socket::Interrupt::Receive as u8],
-        )
-        .await?;
+        bus.write_frame(C::SOCKET_INTR_MASK, &[socket::Interrupt::Receive as u8])
+            .await?;
 
         // Set MAC address
-        bus.write_frame(RegisterBlock::Common, MAC, &mac_addr).await?;
+        bus.write_frame(C::COMMON_MAC, &mac_addr).await?;
 
-        // Set the raw socket RX/TX buffer sizes to  16KB
-        bus.write_frame(RegisterBlock::Socket0, socket::TXBUF_SIZE, &[16])
-            .await?;
-        bus.write_frame(RegisterBlock::Socket0, socket::RXBUF_SIZE, &[16])
-            .await?;
+        // Give all the buffer memory to the raw socket, in KB
+        let buf_size = (C::BUF_SIZE / 1024) as u8;
+        bus.write_frame(C::SOCKET_TXBUF_SIZE, &[buf_size]).await?;
+        bus.write_frame(C::SOCKET_RXBUF_SIZE, &[buf_size]).await?;
 
         // MACRAW mode with MAC filtering.
-        let mode: u8 = (1 << 2) | (1 << 7);
-        bus.write_frame(RegisterBlock::Socket0, socket::MODE, &[mode]).await?;
+        bus.write_frame(C::SOCKET_MODE, &[C::SOCKET_MODE_VALUE]).await?;
         socket::command(&mut bus, socket::Command::Open).await?;
 
         Ok(Self { bus })
     }
 
+    /// Read `buffer` from the RX buffer at `ptr`, wrapping around its end.
+    async fn read_buf(&mut self, ptr: u16, buffer: &mut [u8]) -> Result<(), SPI::Error> {
+        let to_end = (C::BUF_SIZE - ptr % C::BUF_SIZE) as usize;
+        let (first, second) = buffer.split_at_mut(to_end.min(buffer.len()));
+        self.bus.read_frame(C::rx_addr(ptr), first).await?;
+        if !second.is_empty() {
+            self.bus
+                .read_frame(C::rx_addr(ptr.wrapping_add(first.len() as u16)), second)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Write `buffer` to the TX buffer at `ptr`, wrapping around its end.
+    async fn write_buf(&mut self, ptr: u16, buffer: &[u8]) -> Result<(), SPI::Error> {
+        let to_end = (C::BUF_SIZE - ptr % C::BUF_SIZE) as usize;
+        let (first, second) = buffer.split_at(to_end.min(buffer.len()));
+        self.bus.write_frame(C::tx_addr(ptr), first).await?;
+        if !second.is_empty() {
+            self.bus
+                .write_frame(C::tx_addr(ptr.wrapping_add(first.len() as u16)), second)
+                .await?;
+        }
+        Ok(())
+    }
+
     /// Read bytes from the RX buffer. Returns the number of bytes read.
     async fn read_bytes(&mut self, buffer: &mut [u8], offset: u16) -> Result<usize, SPI::Error> {
         let rx_size = socket::get_rx_size(&mut self.bus).await? as usize;
@@ -68,7 +76,7 @@ impl<SPI: SpiDevice> W5500<SPI> {
         };
 
         let read_ptr = socket::get_rx_read_ptr(&mut self.bus).await?.wrapping_add(offset);
-        self.bus.read_frame(RegisterBlock::RxBuf, read_ptr, read_buffer).await?;
+        self.read_buf(read_ptr, read_buffer).await?;
         socket::set_rx_read_ptr(&mut self.bus, read_ptr.wrapping_add(read_buffer.len() as u16)).await?;
 
         Ok(read_buffer.len())
@@ -114,7 +122,7 @@ impl<SPI: SpiDevice> W5500<SPI> {
     pub async fn write_frame(&mut self, frame: &[u8]) -> Result<usize, SPI::Error> {
         while socket::get_tx_free_size(&mut self.bus).await? < frame.len() as u16 {}
         let write_ptr = socket::get_tx_write_ptr(&mut self.bus).await?;
-        self.bus.write_frame(RegisterBlock::TxBuf, write_ptr, frame).await?;
+        self.write_buf(write_ptr, frame).await?;
         socket::set_tx_write_ptr(&mut self.bus, write_ptr.wrapping_add(frame.len() as u16)).await?;
         socket::command(&mut self.bus, socket::Command::Send).await?;
         Ok(frame.len())
@@ -122,10 +130,7 @@ impl<SPI: SpiDevice> W5500<SPI> {
 
     pub async fn is_link_up(&mut self) -> bool {
         let mut link = [0];
-        self.bus
-            .read_frame(RegisterBlock::Common, PHY_CFG, &mut link)
-            .await
-            .ok();
+        self.bus.read_frame(C::COMMON_PHY_CFG, &mut link).await.ok();
         link[0] & 1 == 1
     }
 }
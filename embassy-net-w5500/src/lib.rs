@@ -1,6 +1,7 @@
-//! [`embassy-net`](https://crates.io/crates/embassy-net) driver for the WIZnet W5500 ethernet chip.
+//! [`embassy-net`](https://crates.io/crates/embassy-net) driver for the WIZnet W5500 and W5100S ethernet chips.
 #![no_std]
 
+pub mod chip;
 mod device;
 mod socket;
 mod spi;
@@ -13,10 +14,11 @@ use embedded_hal::digital::OutputPin;
 use embedded_hal_async::digital::Wait;
 use embedded_hal_async::spi::SpiDevice;
 
-use crate::device::W5500;
+pub use crate::chip::{Chip, W5100S, W5500};
+use crate::device::WiznetDevice;
 const MTU: usize = 1514;
 
-/// Type alias for the embassy-net driver for W5500
+/// Type alias for the embassy-net driver
 pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
 
 /// Internal state for the embassy-net integration.
@@ -33,18 +35,18 @@ impl<const N_RX: usize, const N_TX: usize> State<N_RX, N_TX> {
     }
 }
 
-/// Background runner for the W5500.
+/// Background runner for the WIZnet chip.
 ///
-/// You must call `.run()` in a background task for the W5500 to operate.
-pub struct Runner<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> {
-    mac: W5500<SPI>,
+/// You must call `.run()` in a background task for the chip to operate.
+pub struct Runner<'d, C: Chip, SPI: SpiDevice, INT: Wait, RST: OutputPin> {
+    mac: WiznetDevice<C, SPI>,
     ch: ch::Runner<'d, MTU>,
     int: INT,
     _reset: RST,
 }
 
-/// You must call this in a background task for the W5500 to operate.
-impl<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'d, SPI, INT, RST> {
+/// You must call this in a background task for the chip to operate.
+impl<'d, C: Chip, SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'d, C, SPI, INT, RST> {
     pub async fn run(mut self) -> ! {
         let (state_chan, mut rx_chan, mut tx_chan) = self.ch.split();
         loop {
@@ -78,23 +80,23 @@ impl<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'d, SPI, INT, RST> {
     }
 }
 
-/// Obtain a driver for using the W5500 with [`embassy-net`](https://crates.io/crates/embassy-net).
-pub async fn new<'a, const N_RX: usize, const N_TX: usize, SPI: SpiDevice, INT: Wait, RST: OutputPin>(
+/// Obtain a driver for using the WIZnet chip `C` with [`embassy-net`](https://crates.io/crates/embassy-net).
+pub async fn new<'a, const N_RX: usize, const N_TX: usize, C: Chip, SPI: SpiDevice, INT: Wait, RST: OutputPin>(
     mac_addr: [u8; 6],
     state: &'a mut State<N_RX, N_TX>,
     spi_dev: SPI,
     int: INT,
     mut reset: RST,
-) -> (Device<'a>, Runner<'a, SPI, INT, RST>) {
-    // Reset the W5500.
+) -> (Device<'a>, Runner<'a, C, SPI, INT, RST>) {
+    // Reset the chip.
     reset.set_low().ok();
     // Ensure the reset is registered.
     Timer::after(Duration::from_millis(1)).await;
     reset.set_high().ok();
-    // Wait for the W5500 to achieve PLL lock.
+    // Wait for the chip to achieve PLL lock.
     Timer::after(Duration::from_millis(2)).await;
 
-    let mac = W5500::new(spi_dev, mac_addr).await.unwrap();
+    let mac = WiznetDevice::new(spi_dev, mac_addr).await.unwrap();
 
     let (runner, device) = ch::new(&mut state.ch_state, mac_addr);
     (
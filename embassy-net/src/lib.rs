@@ -30,10 +30,14 @@ pub use smoltcp::iface::MulticastError;
 use smoltcp::iface::{Interface, SocketHandle, SocketSet, SocketStorage};
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4::{self, RetryConfig};
+#[cfg(feature = "medium-ethernet")]
+pub use smoltcp::wire::EthernetAddress;
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip", feature = "medium-ieee802154"))]
+pub use smoltcp::wire::HardwareAddress;
 #[cfg(feature = "udp")]
 pub use smoltcp::wire::IpListenEndpoint;
-#[cfg(feature = "medium-ethernet")]
-pub use smoltcp::wire::{EthernetAddress, HardwareAddress};
+#[cfg(feature = "medium-ieee802154")]
+pub use smoltcp::wire::{Ieee802154Address, Ieee802154Pan};
 pub use smoltcp::wire::{IpAddress, IpCidr};
 #[cfg(feature = "proto-ipv4")]
 pub use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
@@ -134,6 +138,11 @@ pub struct Config {
     /// IPv6 configuration
     #[cfg(feature = "proto-ipv6")]
     pub ipv6: ConfigV6,
+    /// PAN identifier of the IEEE 802.15.4 network, the one the radio is configured with.
+    ///
+    /// Used as the destination PAN of the sent frames, and to filter the received ones.
+    #[cfg(feature = "medium-ieee802154")]
+    pub ieee802154_pan_id: Option<u16>,
 }
 
 impl Config {
@@ -144,6 +153,8 @@ impl Config {
             ipv4: ConfigV4::Static(config),
             #[cfg(feature = "proto-ipv6")]
             ipv6: ConfigV6::None,
+            #[cfg(feature = "medium-ieee802154")]
+            ieee802154_pan_id: None,
         }
     }
 
@@ -154,6 +165,8 @@ impl Config {
             #[cfg(feature = "proto-ipv4")]
             ipv4: ConfigV4::None,
             ipv6: ConfigV6::Static(config),
+            #[cfg(feature = "medium-ieee802154")]
+            ieee802154_pan_id: None,
         }
     }
 
@@ -169,6 +182,8 @@ impl Config {
             ipv4: ConfigV4::Dhcp(config),
             #[cfg(feature = "proto-ipv6")]
             ipv6: ConfigV6::None,
+            #[cfg(feature = "medium-ieee802154")]
+            ieee802154_pan_id: None,
         }
     }
 }
@@ -232,7 +247,6 @@ impl<D: Driver + 'static> Stack<D> {
         resources: &'static mut StackResources<SOCK>,
         random_seed: u64,
     ) -> Self {
-        #[cfg(feature = "medium-ethernet")]
         let medium = device.capabilities().medium;
 
         let hardware_addr = match medium {
@@ -240,6 +254,8 @@ impl<D: Driver + 'static> Stack<D> {
             Medium::Ethernet => HardwareAddress::Ethernet(EthernetAddress(device.ethernet_address())),
             #[cfg(feature = "medium-ip")]
             Medium::Ip => HardwareAddress::Ip,
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => HardwareAddress::Ieee802154(Ieee802154Address::Extended(device.ieee802154_address())),
             #[allow(unreachable_patterns)]
             _ => panic!(
                 "Unsupported medium {:?}. Make sure to enable it in embassy-net's Cargo features.",
@@ -248,6 +264,10 @@ impl<D: Driver + 'static> Stack<D> {
         };
         let mut iface_cfg = smoltcp::iface::Config::new(hardware_addr);
         iface_cfg.random_seed = random_seed;
+        #[cfg(feature = "medium-ieee802154")]
+        {
+            iface_cfg.pan_id = config.ieee802154_pan_id.map(Ieee802154Pan);
+        }
 
         let iface = Interface::new(
             iface_cfg,
@@ -563,7 +583,7 @@ impl<D: Driver + 'static> Inner<D> {
     /// Replaces the current IPv6 static configuration with a newly supplied config.
     #[cfg(feature = "proto-ipv6")]
     fn apply_config_v6(&mut self, s: &mut SocketStack, config: StaticConfigV6) {
-        #[cfg(feature = "medium-ethernet")]
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
         let medium = self.device.capabilities().medium;
 
         debug!("Acquired IPv6 configuration:");
@@ -577,8 +597,9 @@ impl<D: Driver + 'static> Inner<D> {
             }
         });
 
-        #[cfg(feature = "medium-ethernet")]
-        if Medium::Ethernet == medium {
+        // IEEE 802.15.4 devices resolve neighbors too, with NDISC.
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if matches!(medium, Medium::Ethernet | Medium::Ieee802154) {
             if let Some(gateway) = config.gateway {
                 debug!("   Default gateway: {}", gateway);
                 s.iface.routes_mut().add_default_ipv6_route(gateway).unwrap();
@@ -0,0 +1,84 @@
+//! Access Control Lists (ACL) driver, to protect regions of the internal flash.
+//!
+//! Each of the 8 ACL regions covers a page-aligned range of flash, and restricts the CPU and
+//! debugger accesses to it. A region can be configured only once: the configuration is locked
+//! until the next reset. This is typically done by a bootloader, to write-protect itself and
+//! read-protect key storage before starting the application.
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::nvmc::{Error, FLASH_SIZE, PAGE_SIZE};
+use crate::peripherals::ACL;
+use crate::{pac, Peripheral};
+
+/// Number of ACL regions.
+pub const REGION_COUNT: usize = 8;
+
+/// Accesses blocked in a region.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protection {
+    /// Writes and erases are blocked.
+    Write,
+    /// Reads, writes and erases are blocked.
+    ReadWrite,
+}
+
+/// ACL driver.
+pub struct Acl<'d> {
+    _p: PeripheralRef<'d, ACL>,
+}
+
+impl<'d> Acl<'d> {
+    /// Create a new ACL driver.
+    pub fn new(acl: impl Peripheral<P = ACL> + 'd) -> Self {
+        into_ref!(acl);
+        Self { _p: acl }
+    }
+
+    /// Protect the flash range `from..to` with the ACL region `region`.
+    ///
+    /// `from` and `to` must be page-aligned. The protection is locked until the next reset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `region` >= [`REGION_COUNT`].
+    pub fn protect(&mut self, region: usize, from: u32, to: u32, protection: Protection) -> Result<(), Error> {
+        assert!(region < REGION_COUNT);
+
+        if from > to || to as usize > FLASH_SIZE {
+            return Err(Error::OutOfBounds);
+        }
+        if from as usize % PAGE_SIZE != 0 || to as usize % PAGE_SIZE != 0 {
+            return Err(Error::Unaligned);
+        }
+
+        // PERM: bit 1 disables writes, bit 2 disables reads.
+        let perm = match protection {
+            Protection::Write => 1 << 1,
+            Protection::ReadWrite => (1 << 1) | (1 << 2),
+        };
+
+        let r = &regs().acl[region];
+        r.addr.write(|w| unsafe { w.bits(from) });
+        r.perm.write(|w| unsafe { w.bits(perm) });
+        // Writing SIZE enables the region, so it goes last.
+        r.size.write(|w| unsafe { w.bits(to - from) });
+
+        Ok(())
+    }
+
+    /// Returns whether the ACL region `region` is configured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `region` >= [`REGION_COUNT`].
+    pub fn is_configured(&self, region: usize) -> bool {
+        assert!(region < REGION_COUNT);
+        regs().acl[region].size.read().bits() != 0
+    }
+}
+
+fn regs() -> &'static pac::acl::RegisterBlock {
+    unsafe { &*pac::ACL::ptr() }
+}
@@ -0,0 +1,80 @@
+//! Block Protection (BPROT) driver, to write-protect pages of the internal flash.
+//!
+//! Each 4 KB page of flash has a protection bit. Once set, writes and erases to the page are
+//! blocked until the next reset. This is typically done by a bootloader, to write-protect itself
+//! before starting the application.
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::nvmc::{Error, FLASH_SIZE, PAGE_SIZE};
+use crate::peripherals::BPROT;
+use crate::{pac, Peripheral};
+
+/// BPROT driver.
+pub struct Bprot<'d> {
+    _p: PeripheralRef<'d, BPROT>,
+}
+
+impl<'d> Bprot<'d> {
+    /// Create a new BPROT driver.
+    pub fn new(bprot: impl Peripheral<P = BPROT> + 'd) -> Self {
+        into_ref!(bprot);
+        Self { _p: bprot }
+    }
+
+    /// Write-protect the flash range `from..to`.
+    ///
+    /// `from` and `to` must be page-aligned. The protection is locked until the next reset.
+    pub fn protect(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        if from > to || to as usize > FLASH_SIZE {
+            return Err(Error::OutOfBounds);
+        }
+        if from as usize % PAGE_SIZE != 0 || to as usize % PAGE_SIZE != 0 {
+            return Err(Error::Unaligned);
+        }
+
+        for page in from as usize / PAGE_SIZE..to as usize / PAGE_SIZE {
+            // Writing 0 bits has no effect, so only the page's bit needs to be written.
+            let bit = 1 << (page % 32);
+            let r = regs();
+            match page / 32 {
+                0 => r.config0.write(|w| unsafe { w.bits(bit) }),
+                1 => r.config1.write(|w| unsafe { w.bits(bit) }),
+                #[cfg(feature = "nrf52832")]
+                2 => r.config2.write(|w| unsafe { w.bits(bit) }),
+                #[cfg(feature = "nrf52832")]
+                3 => r.config3.write(|w| unsafe { w.bits(bit) }),
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the page containing `address` is write-protected.
+    pub fn is_protected(&self, address: u32) -> bool {
+        let page = address as usize / PAGE_SIZE;
+        let r = regs();
+        let bits = match page / 32 {
+            0 => r.config0.read().bits(),
+            1 => r.config1.read().bits(),
+            #[cfg(feature = "nrf52832")]
+            2 => r.config2.read().bits(),
+            #[cfg(feature = "nrf52832")]
+            3 => r.config3.read().bits(),
+            _ => return false,
+        };
+        bits & (1 << (page % 32)) != 0
+    }
+
+    /// Set whether the protection is disabled while a debugger is attached.
+    ///
+    /// It's enabled by default, which makes it possible to reflash a protected bootloader.
+    pub fn set_disable_in_debug(&mut self, disable: bool) {
+        regs().disableindebug.write(|w| unsafe { w.bits(disable as u32) });
+    }
+}
+
+fn regs() -> &'static pac::bprot::RegisterBlock {
+    unsafe { &*pac::BPROT::ptr() }
+}
@@ -19,6 +19,9 @@ embassy_hal_common::peripherals! {
     // NVMC
     NVMC,
 
+    // ACL
+    ACL,
+
     // RNG
     RNG,
 
@@ -37,6 +40,10 @@ embassy_hal_common::peripherals! {
     TIMER1,
     TIMER2,
 
+    // EGU
+    EGU0,
+    EGU1,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -126,6 +133,9 @@ embassy_hal_common::peripherals! {
     // TEMP
     TEMP,
 
+    // RADIO
+    RADIO,
+
     // QDEC
     QDEC,
 }
@@ -148,6 +158,13 @@ impl_timer!(TIMER0, TIMER0, TIMER0);
 impl_timer!(TIMER1, TIMER1, TIMER1);
 impl_timer!(TIMER2, TIMER2, TIMER2);
 
+impl_egu!(EGU0, EGU0, SWI0_EGU0);
+impl_egu!(EGU1, EGU1, SWI1_EGU1);
+
+impl_rtc!(RTC0, RTC0, RTC0, 3);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+
 impl_pin!(P0_00, 0, 0);
 impl_pin!(P0_01, 0, 1);
 impl_pin!(P0_02, 0, 2);
@@ -22,6 +22,9 @@ embassy_hal_common::peripherals! {
     // NVMC
     NVMC,
 
+    // ACL
+    ACL,
+
     // RNG
     RNG,
 
@@ -38,6 +41,14 @@ embassy_hal_common::peripherals! {
     TIMER2,
     TIMER3,
 
+    // EGU
+    EGU0,
+    EGU1,
+    EGU2,
+    EGU3,
+    EGU4,
+    EGU5,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -127,6 +138,9 @@ embassy_hal_common::peripherals! {
     // TEMP
     TEMP,
 
+    // RADIO
+    RADIO,
+
     // QDEC
     QDEC,
 }
@@ -153,6 +167,17 @@ impl_timer!(TIMER1, TIMER1, TIMER1);
 impl_timer!(TIMER2, TIMER2, TIMER2);
 impl_timer!(TIMER3, TIMER3, TIMER3, extended);
 
+impl_egu!(EGU0, EGU0, SWI0_EGU0);
+impl_egu!(EGU1, EGU1, SWI1_EGU1);
+impl_egu!(EGU2, EGU2, SWI2_EGU2);
+impl_egu!(EGU3, EGU3, SWI3_EGU3);
+impl_egu!(EGU4, EGU4, SWI4_EGU4);
+impl_egu!(EGU5, EGU5, SWI5_EGU5);
+
+impl_rtc!(RTC0, RTC0, RTC0, 3);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+
 impl_qdec!(QDEC, QDEC, QDEC);
 
 impl_rng!(RNG, RNG, RNG);
@@ -24,6 +24,9 @@ embassy_hal_common::peripherals! {
     // NVMC
     NVMC,
 
+    // BPROT
+    BPROT,
+
     // RNG
     RNG,
 
@@ -38,6 +41,9 @@ embassy_hal_common::peripherals! {
     // SAADC
     SAADC,
 
+    // COMP and LPCOMP share their registers, only one can be used at a time
+    COMP_LPCOMP,
+
     // PWM
     PWM0,
     PWM1,
@@ -50,6 +56,14 @@ embassy_hal_common::peripherals! {
     TIMER3,
     TIMER4,
 
+    // EGU
+    EGU0,
+    EGU1,
+    EGU2,
+    EGU3,
+    EGU4,
+    EGU5,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -141,6 +155,9 @@ embassy_hal_common::peripherals! {
     // TEMP
     TEMP,
 
+    // RADIO
+    RADIO,
+
     // QDEC
     QDEC,
 
@@ -149,6 +166,10 @@ embassy_hal_common::peripherals! {
 
     // PDM
     PDM,
+
+    // NFCT
+    #[cfg(not(feature = "nfc-pins-as-gpio"))]
+    NFCT,
 }
 
 impl_uarte!(UARTE0, UARTE0, UARTE0_UART0);
@@ -175,6 +196,9 @@ impl_pdm!(PDM, PDM, PDM);
 
 impl_qdec!(QDEC, QDEC, QDEC);
 
+impl_comp!(COMP_LPCOMP, COMP, COMP_LPCOMP);
+impl_lpcomp!(COMP_LPCOMP, LPCOMP, COMP_LPCOMP);
+
 impl_rng!(RNG, RNG, RNG);
 
 impl_timer!(TIMER0, TIMER0, TIMER0);
@@ -183,6 +207,18 @@ impl_timer!(TIMER2, TIMER2, TIMER2);
 impl_timer!(TIMER3, TIMER3, TIMER3, extended);
 impl_timer!(TIMER4, TIMER4, TIMER4, extended);
 
+impl_egu!(EGU0, EGU0, SWI0_EGU0);
+impl_egu!(EGU1, EGU1, SWI1_EGU1);
+impl_egu!(EGU2, EGU2, SWI2_EGU2);
+impl_egu!(EGU3, EGU3, SWI3_EGU3);
+impl_egu!(EGU4, EGU4, SWI4_EGU4);
+impl_egu!(EGU5, EGU5, SWI5_EGU5);
+
+impl_rtc!(RTC0, RTC0, RTC0, 3);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+impl_rtc!(RTC2, RTC2, RTC2, 4);
+
 impl_pin!(P0_00, 0, 0);
 impl_pin!(P0_01, 0, 1);
 impl_pin!(P0_02, 0, 2);
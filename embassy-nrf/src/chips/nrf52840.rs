@@ -23,6 +23,9 @@ embassy_hal_common::peripherals! {
     // NVMC
     NVMC,
 
+    // ACL
+    ACL,
+
     // RNG
     RNG,
 
@@ -45,6 +48,9 @@ embassy_hal_common::peripherals! {
     // SAADC
     SAADC,
 
+    // COMP and LPCOMP share their registers, only one can be used at a time
+    COMP_LPCOMP,
+
     // PWM
     PWM0,
     PWM1,
@@ -58,6 +64,14 @@ embassy_hal_common::peripherals! {
     TIMER3,
     TIMER4,
 
+    // EGU
+    EGU0,
+    EGU1,
+    EGU2,
+    EGU3,
+    EGU4,
+    EGU5,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -167,11 +181,18 @@ embassy_hal_common::peripherals! {
     // TEMP
     TEMP,
 
+    // RADIO
+    RADIO,
+
     // PDM
     PDM,
 
     // I2S
     I2S,
+
+    // NFCT
+    #[cfg(not(feature = "nfc-pins-as-gpio"))]
+    NFCT,
 }
 
 #[cfg(feature = "nightly")]
@@ -206,12 +227,27 @@ impl_timer!(TIMER2, TIMER2, TIMER2);
 impl_timer!(TIMER3, TIMER3, TIMER3, extended);
 impl_timer!(TIMER4, TIMER4, TIMER4, extended);
 
+impl_egu!(EGU0, EGU0, SWI0_EGU0);
+impl_egu!(EGU1, EGU1, SWI1_EGU1);
+impl_egu!(EGU2, EGU2, SWI2_EGU2);
+impl_egu!(EGU3, EGU3, SWI3_EGU3);
+impl_egu!(EGU4, EGU4, SWI4_EGU4);
+impl_egu!(EGU5, EGU5, SWI5_EGU5);
+
+impl_rtc!(RTC0, RTC0, RTC0, 3);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+impl_rtc!(RTC2, RTC2, RTC2, 4);
+
 impl_qspi!(QSPI, QSPI, QSPI);
 
 impl_pdm!(PDM, PDM, PDM);
 
 impl_qdec!(QDEC, QDEC, QDEC);
 
+impl_comp!(COMP_LPCOMP, COMP, COMP_LPCOMP);
+impl_lpcomp!(COMP_LPCOMP, LPCOMP, COMP_LPCOMP);
+
 impl_rng!(RNG, RNG, RNG);
 
 impl_pin!(P0_00, 0, 0);
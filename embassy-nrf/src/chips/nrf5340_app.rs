@@ -238,9 +238,15 @@ embassy_hal_common::peripherals! {
     SERIAL2,
     SERIAL3,
 
+    // High-speed SPI
+    SPIM4,
+
     // SAADC
     SAADC,
 
+    // COMP and LPCOMP share their registers, only one can be used at a time
+    COMP_LPCOMP,
+
     // PWM
     PWM0,
     PWM1,
@@ -262,6 +268,17 @@ embassy_hal_common::peripherals! {
     QDEC0,
     QDEC1,
 
+    // IPC
+    IPC,
+
+    // EGU
+    EGU0,
+    EGU1,
+    EGU2,
+    EGU3,
+    EGU4,
+    EGU5,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -380,6 +397,7 @@ impl_spim!(SERIAL0, SPIM0, SERIAL0);
 impl_spim!(SERIAL1, SPIM1, SERIAL1);
 impl_spim!(SERIAL2, SPIM2, SERIAL2);
 impl_spim!(SERIAL3, SPIM3, SERIAL3);
+impl_spim!(SPIM4, SPIM4, SPIM4);
 
 impl_spis!(SERIAL0, SPIS0, SERIAL0);
 impl_spis!(SERIAL1, SPIS1, SERIAL1);
@@ -405,6 +423,17 @@ impl_timer!(TIMER0, TIMER0, TIMER0);
 impl_timer!(TIMER1, TIMER1, TIMER1);
 impl_timer!(TIMER2, TIMER2, TIMER2);
 
+impl_egu!(EGU0, EGU0, EGU0);
+impl_egu!(EGU1, EGU1, EGU1);
+impl_egu!(EGU2, EGU2, EGU2);
+impl_egu!(EGU3, EGU3, EGU3);
+impl_egu!(EGU4, EGU4, EGU4);
+impl_egu!(EGU5, EGU5, EGU5);
+
+impl_rtc!(RTC0, RTC0, RTC0, 4);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+
 impl_qspi!(QSPI, QSPI, QSPI);
 
 impl_pdm!(PDM0, PDM0, PDM0);
@@ -412,6 +441,9 @@ impl_pdm!(PDM0, PDM0, PDM0);
 impl_qdec!(QDEC0, QDEC0, QDEC0);
 impl_qdec!(QDEC1, QDEC1, QDEC1);
 
+impl_comp!(COMP_LPCOMP, COMP, COMP_LPCOMP);
+impl_lpcomp!(COMP_LPCOMP, LPCOMP, COMP_LPCOMP);
+
 impl_pin!(P0_00, 0, 0);
 impl_pin!(P0_01, 0, 1);
 #[cfg(feature = "nfc-pins-as-gpio")]
@@ -143,6 +143,12 @@ embassy_hal_common::peripherals! {
     TIMER1,
     TIMER2,
 
+    // IPC
+    IPC,
+
+    // EGU
+    EGU0,
+
     // GPIOTE
     GPIOTE_CH0,
     GPIOTE_CH1,
@@ -257,6 +263,12 @@ impl_timer!(TIMER0, TIMER0, TIMER0);
 impl_timer!(TIMER1, TIMER1, TIMER1);
 impl_timer!(TIMER2, TIMER2, TIMER2);
 
+impl_egu!(EGU0, EGU0, EGU0);
+
+impl_rtc!(RTC0, RTC0, RTC0, 4);
+#[cfg(not(feature = "time-driver-rtc1"))]
+impl_rtc!(RTC1, RTC1, RTC1, 4);
+
 impl_rng!(RNG, RNG, RNG);
 
 impl_pin!(P0_00, 0, 0);
@@ -0,0 +1,219 @@
+//! Clock control.
+//!
+//! The clocks are first configured by [`embassy_nrf::init`](crate::init), according to
+//! [`Config`](crate::config::Config). [`Clocks`] then allows changing them at runtime:
+//!
+//! - The high frequency crystal oscillator (HFXO) is needed by the radio, and for accurate
+//!   timings, but draws much more power than the internal oscillator. It's started by the first
+//!   [`Clocks::request_hfxo`] and stopped when the last [`Hfxo`] it returned is dropped, so that
+//!   each user can request it only when needed.
+//! - The source of the low frequency clock (LFCLK) can be changed with
+//!   [`Clocks::set_lfclk_source`].
+//! - The internal RC oscillator of the LFCLK can be calibrated with [`Clocks::calibrate`]. It
+//!   should be calibrated every few seconds, or when the temperature changes by 0.5 °C, to keep
+//!   its accuracy of 500 ppm.
+
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Poll;
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::config::LfclkSource;
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, pac};
+
+#[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
+type ClockIrq = interrupt::typelevel::POWER_CLOCK;
+#[cfg(any(feature = "_nrf5340", feature = "_nrf9160"))]
+type ClockIrq = interrupt::typelevel::CLOCK_POWER;
+
+// HFCLKSTAT and LFCLKSTAT bits
+const STAT_SRC_XTAL: u32 = 1 << 0;
+const STAT_STATE_RUNNING: u32 = 1 << 16;
+
+/// Number of users of the HFXO, including `init` when configured with it.
+pub(crate) static HFXO_USERS: AtomicUsize = AtomicUsize::new(0);
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<ClockIrq> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+
+        if r.events_hfclkstarted.read().bits() != 0 {
+            r.intenclr.write(|w| w.hfclkstarted().clear());
+            WAKER.wake();
+        }
+        if r.events_lfclkstarted.read().bits() != 0 {
+            r.intenclr.write(|w| w.lfclkstarted().clear());
+            WAKER.wake();
+        }
+        #[cfg(not(feature = "_nrf9160"))]
+        if r.events_done.read().bits() != 0 {
+            r.intenclr.write(|w| w.done().clear());
+            WAKER.wake();
+        }
+    }
+}
+
+/// Clock control driver.
+///
+/// It can be copied freely, to be shared with the tasks using the clocks.
+#[derive(Clone, Copy)]
+pub struct Clocks {
+    _private: (),
+}
+
+impl Clocks {
+    /// Create a new clock control driver.
+    pub fn new(_irq: impl interrupt::typelevel::Binding<ClockIrq, InterruptHandler> + 'static) -> Self {
+        ClockIrq::unpend();
+        unsafe { ClockIrq::enable() };
+
+        Self { _private: () }
+    }
+
+    /// Request the HFXO, starting it if needed, and wait for it to run.
+    ///
+    /// The HFXO is kept running until the returned [`Hfxo`], and those returned to other users,
+    /// are dropped.
+    pub async fn request_hfxo(&self) -> Hfxo {
+        let r = regs();
+
+        critical_section::with(|_| {
+            if HFXO_USERS.fetch_add(1, Ordering::Relaxed) == 0 {
+                r.events_hfclkstarted.reset();
+                r.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+            }
+        });
+        // Release the HFXO if the future is dropped.
+        let hfxo = Hfxo { _private: () };
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.hfclkstat.read().bits() == STAT_STATE_RUNNING | STAT_SRC_XTAL {
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| w.hfclkstarted().set());
+            Poll::Pending
+        })
+        .await;
+
+        hfxo
+    }
+
+    /// Whether the HFXO is running.
+    pub fn is_hfxo_running(&self) -> bool {
+        regs().hfclkstat.read().bits() == STAT_STATE_RUNNING | STAT_SRC_XTAL
+    }
+
+    /// Change the source of the LFCLK, and wait for it to run from the new source.
+    ///
+    /// The LFCLK is stopped while switching, which also stops the RTCs. The time driver, when
+    /// enabled, doesn't count time during the switch.
+    pub async fn set_lfclk_source(&self, source: LfclkSource) {
+        let r = regs();
+
+        r.tasks_lfclkstop.write(|w| unsafe { w.bits(1) });
+        while r.lfclkstat.read().bits() & STAT_STATE_RUNNING != 0 {}
+
+        write_lfclksrc(&source);
+
+        r.events_lfclkstarted.reset();
+        r.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_lfclkstarted.read().bits() != 0 {
+                r.events_lfclkstarted.reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| w.lfclkstarted().set());
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Calibrate the internal RC oscillator of the LFCLK.
+    ///
+    /// The HFXO is requested during the calibration, which takes about 17 ms.
+    #[cfg(not(feature = "_nrf9160"))]
+    pub async fn calibrate(&self) {
+        let r = regs();
+        let _hfxo = self.request_hfxo().await;
+
+        r.events_done.reset();
+        r.tasks_cal.write(|w| unsafe { w.bits(1) });
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_done.read().bits() != 0 {
+                r.events_done.reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| w.done().set());
+            Poll::Pending
+        })
+        .await;
+    }
+}
+
+/// Request of the HFXO, returned by [`Clocks::request_hfxo`].
+///
+/// The HFXO is stopped when the last request is dropped.
+pub struct Hfxo {
+    _private: (),
+}
+
+impl Drop for Hfxo {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            if HFXO_USERS.fetch_sub(1, Ordering::Relaxed) == 1 {
+                regs().tasks_hfclkstop.write(|w| unsafe { w.bits(1) });
+            }
+        });
+    }
+}
+
+/// Select the source of the LFCLK, which must be stopped.
+pub(crate) fn write_lfclksrc(source: &LfclkSource) {
+    let r = regs();
+
+    #[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
+    match source {
+        LfclkSource::InternalRC => r.lfclksrc.write(|w| w.src().rc()),
+        LfclkSource::Synthesized => r.lfclksrc.write(|w| w.src().synth()),
+
+        LfclkSource::ExternalXtal => r.lfclksrc.write(|w| w.src().xtal()),
+
+        LfclkSource::ExternalLowSwing => r.lfclksrc.write(|w| {
+            w.src().xtal();
+            w.external().enabled();
+            w.bypass().disabled();
+            w
+        }),
+        LfclkSource::ExternalFullSwing => r.lfclksrc.write(|w| {
+            w.src().xtal();
+            w.external().enabled();
+            w.bypass().enabled();
+            w
+        }),
+    }
+    #[cfg(feature = "_nrf9160")]
+    match source {
+        LfclkSource::InternalRC => r.lfclksrc.write(|w| w.src().lfrc()),
+        LfclkSource::ExternalXtal => r.lfclksrc.write(|w| w.src().lfxo()),
+    }
+    #[cfg(feature = "_nrf5340")]
+    let _ = (r, source);
+}
+
+fn regs() -> &'static pac::clock::RegisterBlock {
+    unsafe { &*pac::CLOCK::ptr() }
+}
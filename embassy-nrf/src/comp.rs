@@ -0,0 +1,270 @@
+//! Comparator (COMP) driver.
+//!
+//! The comparator compares an analog input against a threshold derived from a reference voltage,
+//! without running the SAADC. The thresholds have a hysteresis: the output goes up when the
+//! input rises above the up threshold, and down when it falls below the down threshold.
+
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::saadc::sealed::Input as _;
+use crate::saadc::{AnyInput, Input};
+use crate::{interrupt, pac, Peripheral};
+
+/// Reference voltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reference {
+    /// Internal 1.2 V reference.
+    Int1v2 = 0,
+    /// Internal 1.8 V reference, requires VDD above 2.7 V.
+    Int1v8 = 1,
+    /// Internal 2.4 V reference, requires VDD above 3.3 V.
+    Int2v4 = 2,
+    /// VDD.
+    Vdd = 4,
+}
+
+/// Speed and power mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Speed {
+    /// Low power, slow response.
+    Low = 0,
+    /// Normal power and response time.
+    Normal = 1,
+    /// High power, fast response.
+    High = 2,
+}
+
+/// COMP config.
+#[non_exhaustive]
+pub struct Config {
+    /// Reference voltage the thresholds are relative to.
+    pub reference: Reference,
+    /// Up threshold, in 64ths of the reference voltage minus one (0..=63): the output goes up
+    /// when the input rises above `(threshold_up + 1) / 64 * reference`.
+    pub threshold_up: u8,
+    /// Down threshold, in 64ths of the reference voltage minus one (0..=63): the output goes
+    /// down when the input falls below `(threshold_down + 1) / 64 * reference`.
+    pub threshold_down: u8,
+    /// Speed and power mode.
+    pub speed: Speed,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            reference: Reference::Int1v2,
+            threshold_up: 32,
+            threshold_down: 30,
+            speed: Speed::Low,
+        }
+    }
+}
+
+const INT_READY: u32 = 1 << 0;
+const INT_DOWN: u32 = 1 << 1;
+const INT_UP: u32 = 1 << 2;
+const INT_CROSS: u32 = 1 << 3;
+
+#[derive(Clone, Copy)]
+enum Event {
+    Down,
+    Up,
+    Cross,
+}
+
+impl Event {
+    fn mask(self) -> u32 {
+        match self {
+            Event::Down => INT_DOWN,
+            Event::Up => INT_UP,
+            Event::Cross => INT_CROSS,
+        }
+    }
+
+    fn is_triggered(self, r: &pac::comp::RegisterBlock) -> bool {
+        match self {
+            Event::Down => r.events_down.read().bits() != 0,
+            Event::Up => r.events_up.read().bits() != 0,
+            Event::Cross => r.events_cross.read().bits() != 0,
+        }
+    }
+
+    fn clear(self, r: &pac::comp::RegisterBlock) {
+        match self {
+            Event::Down => r.events_down.reset(),
+            Event::Up => r.events_up.reset(),
+            Event::Cross => r.events_cross.reset(),
+        }
+    }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        if r.events_down.read().bits() != 0 || r.events_up.read().bits() != 0 || r.events_cross.read().bits() != 0 {
+            r.intenclr.write(|w| unsafe { w.bits(INT_DOWN | INT_UP | INT_CROSS) });
+            T::state().waker.wake();
+        }
+    }
+}
+
+/// Comparator driver.
+pub struct Comp<'d, T: Instance> {
+    _p: PeripheralRef<'d, T>,
+    _input: PeripheralRef<'d, AnyInput>,
+}
+
+impl<'d, T: Instance> Comp<'d, T> {
+    /// Create a new comparator, comparing an analog input pin against the configured thresholds.
+    ///
+    /// The comparator runs until dropped.
+    pub fn new(
+        comp: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        input: impl Peripheral<P = impl Input> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(comp, input);
+        let r = T::regs();
+
+        let channel: u8 = input.channel().into();
+        assert!(
+            (1..=8).contains(&channel),
+            "only analog input pins can be connected to the comparator"
+        );
+        assert!(config.threshold_up <= 63 && config.threshold_down <= 63);
+
+        r.psel.write(|w| unsafe { w.bits(channel as u32 - 1) });
+        r.refsel.write(|w| unsafe { w.bits(config.reference as u32) });
+        r.th.write(|w| unsafe { w.bits((config.threshold_up as u32) << 8 | config.threshold_down as u32) });
+        // Single-ended mode
+        r.mode.write(|w| unsafe { w.bits(config.speed as u32) });
+        r.enable.write(|w| w.enable().enabled());
+
+        r.events_ready.reset();
+        r.tasks_start.write(|w| unsafe { w.bits(1) });
+        while r.events_ready.read().bits() == 0 {}
+        r.events_ready.reset();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _p: comp,
+            _input: input.map_into(),
+        }
+    }
+
+    /// Whether the input is currently above the threshold.
+    pub fn sample(&mut self) -> bool {
+        let r = T::regs();
+        r.tasks_sample.write(|w| unsafe { w.bits(1) });
+        r.result.read().bits() != 0
+    }
+
+    /// Wait for the input to rise above the up threshold.
+    pub async fn wait_for_cross_up(&mut self) {
+        self.wait(Event::Up).await
+    }
+
+    /// Wait for the input to fall below the down threshold.
+    pub async fn wait_for_cross_down(&mut self) {
+        self.wait(Event::Down).await
+    }
+
+    /// Wait for the input to cross either threshold, returning whether it is now above it.
+    pub async fn wait_for_cross(&mut self) -> bool {
+        self.wait(Event::Cross).await;
+        self.sample()
+    }
+
+    async fn wait(&mut self, event: Event) {
+        let r = T::regs();
+        event.clear(r);
+        r.intenset.write(|w| unsafe { w.bits(event.mask()) });
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            if event.is_triggered(r) {
+                event.clear(r);
+                return Poll::Ready(());
+            }
+            // The interrupt is disabled when any event triggers
+            r.intenset.write(|w| unsafe { w.bits(event.mask()) });
+            Poll::Pending
+        })
+        .await;
+
+        r.intenclr.write(|w| unsafe { w.bits(event.mask()) });
+    }
+}
+
+impl<'d, T: Instance> Drop for Comp<'d, T> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.intenclr
+            .write(|w| unsafe { w.bits(INT_READY | INT_DOWN | INT_UP | INT_CROSS) });
+        r.tasks_stop.write(|w| unsafe { w.bits(1) });
+        r.enable.write(|w| w.enable().disabled());
+    }
+}
+
+pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    use super::*;
+
+    pub struct State {
+        pub waker: AtomicWaker,
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            Self {
+                waker: AtomicWaker::new(),
+            }
+        }
+    }
+
+    pub trait Instance {
+        fn regs() -> &'static pac::comp::RegisterBlock;
+        fn state() -> &'static State;
+    }
+}
+
+/// COMP peripheral instance.
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + 'static {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_comp {
+    ($type:ident, $pac_type:ident, $irq:ident) => {
+        impl crate::comp::sealed::Instance for peripherals::$type {
+            fn regs() -> &'static pac::comp::RegisterBlock {
+                unsafe { &*pac::$pac_type::ptr() }
+            }
+            fn state() -> &'static crate::comp::sealed::State {
+                static STATE: crate::comp::sealed::State = crate::comp::sealed::State::new();
+                &STATE
+            }
+        }
+        impl crate::comp::Instance for peripherals::$type {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
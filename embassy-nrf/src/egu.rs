@@ -0,0 +1,154 @@
+//! Event Generator Unit (EGU) driver.
+//!
+//! The EGU has 16 channels, each with a TRIGGER task and a TRIGGERED event. Triggering a task,
+//! from software or through (D)PPI, generates the corresponding event. This makes it possible to
+//! start (D)PPI chains from software, and to be notified when a chain reaches a given point.
+//!
+//! Each EGU shares its interrupt with a software interrupt (SWI): an EGU can't be used while its
+//! interrupt drives an `InterruptExecutor`.
+
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::ppi::{Event, Task};
+use crate::{interrupt, pac, Peripheral};
+
+/// Number of channels of an EGU.
+pub const CHANNEL_COUNT: usize = 16;
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        for (n, waker) in s.wakers.iter().enumerate() {
+            if r.events_triggered[n].read().bits() != 0 {
+                r.intenclr.write(|w| unsafe { w.bits(1 << n) });
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// EGU driver.
+pub struct Egu<'d, T: Instance> {
+    _p: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Egu<'d, T> {
+    /// Create a new EGU driver.
+    pub fn new(
+        egu: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(egu);
+
+        let r = T::regs();
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        for n in 0..CHANNEL_COUNT {
+            r.events_triggered[n].reset();
+        }
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _p: egu }
+    }
+
+    /// Trigger the task of channel `n`, generating its event.
+    pub fn trigger(&mut self, n: usize) {
+        T::regs().tasks_trigger[n].write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Wait for the event of channel `n` to be generated.
+    ///
+    /// Returns immediately if it was generated since the last wait.
+    pub async fn wait(&mut self, n: usize) {
+        let r = T::regs();
+        let s = T::state();
+        poll_fn(|cx| {
+            s.wakers[n].register(cx.waker());
+            if r.events_triggered[n].read().bits() != 0 {
+                r.events_triggered[n].reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| unsafe { w.bits(1 << n) });
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Returns the TRIGGER task of channel `n`, for use with PPI.
+    pub fn task_trigger(&self, n: usize) -> Task {
+        Task::from_reg(&T::regs().tasks_trigger[n])
+    }
+
+    /// Returns the TRIGGERED event of channel `n`, for use with PPI.
+    pub fn event_triggered(&self, n: usize) -> Event {
+        Event::from_reg(&T::regs().events_triggered[n])
+    }
+}
+
+impl<'d, T: Instance> Drop for Egu<'d, T> {
+    fn drop(&mut self) {
+        T::regs().intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    }
+}
+
+pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    use super::*;
+
+    pub struct State {
+        pub wakers: [AtomicWaker; CHANNEL_COUNT],
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            const NEW_AW: AtomicWaker = AtomicWaker::new();
+            Self {
+                wakers: [NEW_AW; CHANNEL_COUNT],
+            }
+        }
+    }
+
+    pub trait Instance {
+        fn regs() -> &'static pac::egu0::RegisterBlock;
+        fn state() -> &'static State;
+    }
+}
+
+/// EGU peripheral instance.
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + 'static + Send {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_egu {
+    ($type:ident, $pac_type:ident, $irq:ident) => {
+        impl crate::egu::sealed::Instance for peripherals::$type {
+            fn regs() -> &'static pac::egu0::RegisterBlock {
+                unsafe { &*(pac::$pac_type::ptr() as *const pac::egu0::RegisterBlock) }
+            }
+            fn state() -> &'static crate::egu::sealed::State {
+                static STATE: crate::egu::sealed::State = crate::egu::sealed::State::new();
+                &STATE
+            }
+        }
+        impl crate::egu::Instance for peripherals::$type {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
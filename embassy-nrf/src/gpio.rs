@@ -446,6 +446,24 @@ impl sealed::Pin for AnyPin {
     }
 }
 
+/// Hand a pin over to the network core of the nRF5340, for use as GPIO or by its peripherals.
+///
+/// The pin can no longer be used by the application core. In secure mode, it is also made
+/// non-secure, as the network core can only access non-secure pins.
+#[cfg(feature = "_nrf5340-app")]
+pub fn assign_to_network_core(pin: impl Pin) {
+    #[cfg(feature = "nrf5340-app-s")]
+    {
+        let spu = unsafe { &*pac::SPU::ptr() };
+        spu.gpioport[pin.pin_port() as usize / 32]
+            .perm
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << pin.pin())) });
+    }
+
+    // MCUSEL: NetworkMCU
+    pin.conf().write(|w| unsafe { w.bits(1 << 28) });
+}
+
 // ====================
 
 pub(crate) trait PselBits {
@@ -0,0 +1,133 @@
+//! Interprocessor communication (IPC) driver, to signal events between the application and the
+//! network cores of the nRF5340.
+//!
+//! The IPC peripheral of each core has 16 send tasks and 16 receive events, connected through 16
+//! IPC channels shared by both cores: a send task signals the channels it's configured for, and
+//! a receive event is triggered by the channels it's configured for. The cores exchange data
+//! through shared RAM, or through the two general purpose registers of the IPC.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::InterruptExt;
+use crate::peripherals::IPC;
+use crate::ppi::{Event, Task};
+use crate::{interrupt, pac, Peripheral};
+
+/// Number of send tasks, receive events and channels.
+pub const EVENT_COUNT: usize = 16;
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::IPC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+        for (n, waker) in WAKERS.iter().enumerate() {
+            if r.events_receive[n].read().bits() != 0 {
+                r.intenclr.write(|w| unsafe { w.bits(1 << n) });
+                waker.wake();
+            }
+        }
+    }
+}
+
+const NEW_AW: AtomicWaker = AtomicWaker::new();
+static WAKERS: [AtomicWaker; EVENT_COUNT] = [NEW_AW; EVENT_COUNT];
+
+/// IPC driver.
+pub struct Ipc<'d> {
+    _p: PeripheralRef<'d, IPC>,
+}
+
+impl<'d> Ipc<'d> {
+    /// Create a new IPC driver.
+    ///
+    /// No task or event is connected to a channel until configured.
+    pub fn new(
+        ipc: impl Peripheral<P = IPC> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::IPC, InterruptHandler> + 'd,
+    ) -> Self {
+        into_ref!(ipc);
+
+        let r = regs();
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        for n in 0..EVENT_COUNT {
+            r.send_cnf[n].write(|w| unsafe { w.bits(0) });
+            r.receive_cnf[n].write(|w| unsafe { w.bits(0) });
+            r.events_receive[n].reset();
+        }
+
+        interrupt::IPC.unpend();
+        unsafe { interrupt::IPC.enable() };
+
+        Self { _p: ipc }
+    }
+
+    /// Configure the send task `n` to signal the channels of the `channels` bitmask.
+    pub fn configure_send(&mut self, n: usize, channels: u16) {
+        regs().send_cnf[n].write(|w| unsafe { w.bits(channels as u32) });
+    }
+
+    /// Configure the receive event `n` to be triggered by the channels of the `channels` bitmask.
+    pub fn configure_receive(&mut self, n: usize, channels: u16) {
+        regs().receive_cnf[n].write(|w| unsafe { w.bits(channels as u32) });
+    }
+
+    /// Trigger the send task `n`, signaling the other core.
+    pub fn send(&mut self, n: usize) {
+        regs().tasks_send[n].write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Wait for the receive event `n` to be triggered by the other core.
+    ///
+    /// Returns immediately if it was triggered since the last wait.
+    pub async fn wait(&mut self, n: usize) {
+        let r = regs();
+        poll_fn(|cx| {
+            WAKERS[n].register(cx.waker());
+            if r.events_receive[n].read().bits() != 0 {
+                r.events_receive[n].reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| unsafe { w.bits(1 << n) });
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Read the general purpose register `n` (0 or 1), shared by both cores.
+    pub fn read_gpmem(&self, n: usize) -> u32 {
+        regs().gpmem[n].read().bits()
+    }
+
+    /// Write the general purpose register `n` (0 or 1), shared by both cores.
+    pub fn write_gpmem(&mut self, n: usize, value: u32) {
+        regs().gpmem[n].write(|w| unsafe { w.bits(value) });
+    }
+
+    /// Returns the send task `n`, for use with DPPI.
+    pub fn task_send(&self, n: usize) -> Task {
+        Task::from_reg(&regs().tasks_send[n])
+    }
+
+    /// Returns the receive event `n`, for use with DPPI.
+    pub fn event_receive(&self, n: usize) -> Event {
+        Event::from_reg(&regs().events_receive[n])
+    }
+}
+
+impl<'d> Drop for Ipc<'d> {
+    fn drop(&mut self) {
+        regs().intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    }
+}
+
+fn regs() -> &'static pac::ipc::RegisterBlock {
+    unsafe { &*pac::IPC::ptr() }
+}
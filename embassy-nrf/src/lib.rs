@@ -33,12 +33,47 @@ pub(crate) mod util;
 #[cfg(feature = "_time-driver")]
 mod time_driver;
 
+#[cfg(any(
+    feature = "nrf52805",
+    feature = "nrf52811",
+    feature = "nrf52820",
+    feature = "nrf52833",
+    feature = "nrf52840"
+))]
+pub mod acl;
+#[cfg(any(feature = "nrf52810", feature = "nrf52832"))]
+pub mod bprot;
 pub mod buffered_uarte;
+pub mod clock;
+#[cfg(any(
+    feature = "nrf52810",
+    feature = "nrf52811",
+    feature = "nrf52832",
+    feature = "nrf52833",
+    feature = "nrf52840",
+    feature = "_nrf5340-app"
+))]
+pub mod comp;
+pub mod egu;
 pub mod gpio;
 #[cfg(feature = "gpiote")]
 pub mod gpiote;
 #[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
 pub mod i2s;
+#[cfg(feature = "_nrf5340")]
+pub mod ipc;
+#[cfg(any(
+    feature = "nrf52832",
+    feature = "nrf52833",
+    feature = "nrf52840",
+    feature = "_nrf5340-app"
+))]
+pub mod lpcomp;
+#[cfg(all(
+    any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"),
+    not(feature = "nfc-pins-as-gpio")
+))]
+pub mod nfct;
 pub mod nvmc;
 #[cfg(any(
     feature = "nrf52810",
@@ -50,6 +85,7 @@ pub mod nvmc;
     feature = "_nrf9160"
 ))]
 pub mod pdm;
+pub mod power;
 pub mod ppi;
 #[cfg(not(any(feature = "nrf52805", feature = "nrf52820", feature = "_nrf5340-net")))]
 pub mod pwm;
@@ -57,8 +93,11 @@ pub mod pwm;
 pub mod qdec;
 #[cfg(any(feature = "nrf52840", feature = "_nrf5340-app"))]
 pub mod qspi;
+#[cfg(feature = "_nrf52")]
+pub mod radio;
 #[cfg(not(any(feature = "_nrf5340-app", feature = "_nrf9160")))]
 pub mod rng;
+pub mod rtc;
 #[cfg(not(any(feature = "nrf52820", feature = "_nrf5340-net")))]
 pub mod saadc;
 pub mod spim;
@@ -451,35 +490,12 @@ pub fn init(config: config::Config) -> Peripherals {
             r.events_hfclkstarted.write(|w| unsafe { w.bits(0) });
             r.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
             while r.events_hfclkstarted.read().bits() == 0 {}
+            clock::HFXO_USERS.store(1, core::sync::atomic::Ordering::Relaxed);
         }
     }
 
     // Configure LFCLK.
-    #[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
-    match config.lfclk_source {
-        config::LfclkSource::InternalRC => r.lfclksrc.write(|w| w.src().rc()),
-        config::LfclkSource::Synthesized => r.lfclksrc.write(|w| w.src().synth()),
-
-        config::LfclkSource::ExternalXtal => r.lfclksrc.write(|w| w.src().xtal()),
-
-        config::LfclkSource::ExternalLowSwing => r.lfclksrc.write(|w| {
-            w.src().xtal();
-            w.external().enabled();
-            w.bypass().disabled();
-            w
-        }),
-        config::LfclkSource::ExternalFullSwing => r.lfclksrc.write(|w| {
-            w.src().xtal();
-            w.external().enabled();
-            w.bypass().enabled();
-            w
-        }),
-    }
-    #[cfg(feature = "_nrf9160")]
-    match config.lfclk_source {
-        config::LfclkSource::InternalRC => r.lfclksrc.write(|w| w.src().lfrc()),
-        config::LfclkSource::ExternalXtal => r.lfclksrc.write(|w| w.src().lfxo()),
-    }
+    clock::write_lfclksrc(&config.lfclk_source);
 
     // Start LFCLK.
     // Datasheet says this could take 100us from synth source
@@ -0,0 +1,275 @@
+//! Low-power comparator (LPCOMP) driver.
+//!
+//! The low-power comparator compares an analog input against a fraction of VDD, drawing much less
+//! current than the SAADC. Unlike other peripherals, it can wake the system from System OFF,
+//! which makes it a good fit to monitor a battery threshold.
+
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::saadc::sealed::Input as _;
+use crate::saadc::{AnyInput, Input};
+use crate::{interrupt, pac, Peripheral};
+
+/// Reference voltage, as a fraction of VDD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum Reference {
+    Vdd1_8 = 0,
+    Vdd2_8 = 1,
+    Vdd3_8 = 2,
+    Vdd4_8 = 3,
+    Vdd5_8 = 4,
+    Vdd6_8 = 5,
+    Vdd7_8 = 6,
+    Vdd1_16 = 8,
+    Vdd3_16 = 9,
+    Vdd5_16 = 10,
+    Vdd7_16 = 11,
+    Vdd9_16 = 12,
+    Vdd11_16 = 13,
+    Vdd13_16 = 14,
+    Vdd15_16 = 15,
+}
+
+/// Crossing of the reference voltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Detect {
+    /// The input crosses the reference either way.
+    Cross = 0,
+    /// The input rises above the reference.
+    Up = 1,
+    /// The input falls below the reference.
+    Down = 2,
+}
+
+/// LPCOMP config.
+#[non_exhaustive]
+pub struct Config {
+    /// Reference voltage.
+    pub reference: Reference,
+    /// Enable the 50 mV hysteresis.
+    pub hysteresis: bool,
+    /// Crossing waking the system from System OFF, see [`Lpcomp::into_wakeup`].
+    pub wakeup: Detect,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            reference: Reference::Vdd4_8,
+            hysteresis: true,
+            wakeup: Detect::Cross,
+        }
+    }
+}
+
+const INT_READY: u32 = 1 << 0;
+const INT_DOWN: u32 = 1 << 1;
+const INT_UP: u32 = 1 << 2;
+const INT_CROSS: u32 = 1 << 3;
+
+impl Detect {
+    fn mask(self) -> u32 {
+        match self {
+            Detect::Cross => INT_CROSS,
+            Detect::Up => INT_UP,
+            Detect::Down => INT_DOWN,
+        }
+    }
+
+    fn is_triggered(self, r: &pac::lpcomp::RegisterBlock) -> bool {
+        match self {
+            Detect::Cross => r.events_cross.read().bits() != 0,
+            Detect::Up => r.events_up.read().bits() != 0,
+            Detect::Down => r.events_down.read().bits() != 0,
+        }
+    }
+
+    fn clear(self, r: &pac::lpcomp::RegisterBlock) {
+        match self {
+            Detect::Cross => r.events_cross.reset(),
+            Detect::Up => r.events_up.reset(),
+            Detect::Down => r.events_down.reset(),
+        }
+    }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        if r.events_down.read().bits() != 0 || r.events_up.read().bits() != 0 || r.events_cross.read().bits() != 0 {
+            r.intenclr.write(|w| unsafe { w.bits(INT_DOWN | INT_UP | INT_CROSS) });
+            T::state().waker.wake();
+        }
+    }
+}
+
+/// Low-power comparator driver.
+pub struct Lpcomp<'d, T: Instance> {
+    _p: PeripheralRef<'d, T>,
+    _input: PeripheralRef<'d, AnyInput>,
+}
+
+impl<'d, T: Instance> Lpcomp<'d, T> {
+    /// Create a new low-power comparator, comparing an analog input pin against the reference.
+    ///
+    /// The comparator runs until dropped.
+    pub fn new(
+        lpcomp: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        input: impl Peripheral<P = impl Input> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(lpcomp, input);
+        let r = T::regs();
+
+        let channel: u8 = input.channel().into();
+        assert!(
+            (1..=8).contains(&channel),
+            "only analog input pins can be connected to the comparator"
+        );
+
+        r.psel.write(|w| unsafe { w.bits(channel as u32 - 1) });
+        r.refsel.write(|w| unsafe { w.bits(config.reference as u32) });
+        r.hyst.write(|w| unsafe { w.bits(config.hysteresis as u32) });
+        r.anadetect.write(|w| unsafe { w.bits(config.wakeup as u32) });
+        r.enable.write(|w| w.enable().enabled());
+
+        r.events_ready.reset();
+        r.tasks_start.write(|w| unsafe { w.bits(1) });
+        while r.events_ready.read().bits() == 0 {}
+        r.events_ready.reset();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _p: lpcomp,
+            _input: input.map_into(),
+        }
+    }
+
+    /// Whether the input is currently above the reference.
+    pub fn sample(&mut self) -> bool {
+        let r = T::regs();
+        r.tasks_sample.write(|w| unsafe { w.bits(1) });
+        r.result.read().bits() != 0
+    }
+
+    /// Wait for the input to rise above the reference.
+    pub async fn wait_for_cross_up(&mut self) {
+        self.wait(Detect::Up).await
+    }
+
+    /// Wait for the input to fall below the reference.
+    pub async fn wait_for_cross_down(&mut self) {
+        self.wait(Detect::Down).await
+    }
+
+    /// Wait for the input to cross the reference, returning whether it is now above it.
+    pub async fn wait_for_cross(&mut self) -> bool {
+        self.wait(Detect::Cross).await;
+        self.sample()
+    }
+
+    /// Keep the comparator running once the driver is released, so that the configured
+    /// [`wakeup`](Config::wakeup) crossing wakes the system from System OFF.
+    ///
+    /// Call this right before entering System OFF.
+    pub fn into_wakeup(self) {
+        T::regs()
+            .intenclr
+            .write(|w| unsafe { w.bits(INT_READY | INT_DOWN | INT_UP | INT_CROSS) });
+        core::mem::forget(self);
+    }
+
+    async fn wait(&mut self, detect: Detect) {
+        let r = T::regs();
+        detect.clear(r);
+        r.intenset.write(|w| unsafe { w.bits(detect.mask()) });
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            if detect.is_triggered(r) {
+                detect.clear(r);
+                return Poll::Ready(());
+            }
+            // The interrupt is disabled when any event triggers
+            r.intenset.write(|w| unsafe { w.bits(detect.mask()) });
+            Poll::Pending
+        })
+        .await;
+
+        r.intenclr.write(|w| unsafe { w.bits(detect.mask()) });
+    }
+}
+
+impl<'d, T: Instance> Drop for Lpcomp<'d, T> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.intenclr
+            .write(|w| unsafe { w.bits(INT_READY | INT_DOWN | INT_UP | INT_CROSS) });
+        r.tasks_stop.write(|w| unsafe { w.bits(1) });
+        r.enable.write(|w| w.enable().disabled());
+    }
+}
+
+pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    use super::*;
+
+    pub struct State {
+        pub waker: AtomicWaker,
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            Self {
+                waker: AtomicWaker::new(),
+            }
+        }
+    }
+
+    pub trait Instance {
+        fn regs() -> &'static pac::lpcomp::RegisterBlock;
+        fn state() -> &'static State;
+    }
+}
+
+/// LPCOMP peripheral instance.
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + 'static {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_lpcomp {
+    ($type:ident, $pac_type:ident, $irq:ident) => {
+        impl crate::lpcomp::sealed::Instance for peripherals::$type {
+            fn regs() -> &'static pac::lpcomp::RegisterBlock {
+                unsafe { &*pac::$pac_type::ptr() }
+            }
+            fn state() -> &'static crate::lpcomp::sealed::State {
+                static STATE: crate::lpcomp::sealed::State = crate::lpcomp::sealed::State::new();
+                &STATE
+            }
+        }
+        impl crate::lpcomp::Instance for peripherals::$type {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
@@ -0,0 +1,349 @@
+//! NFC-A tag (NFCT) driver.
+//!
+//! The NFCT peripheral emulates a NFC-A tag: it detects the field of a reader, and performs the
+//! anti-collision and selection on its own. Once selected, the frames of the higher level
+//! protocol, such as the Type 2 or Type 4 (ISO-DEP) tag protocols, are received and transmitted
+//! with [`NfcT::receive`] and [`NfcT::transmit`].
+//!
+//! The NFC antenna pins can not be used by this driver when the `nfc-pins-as-gpio` feature is
+//! enabled.
+
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::InterruptExt;
+use crate::peripherals::NFCT;
+use crate::util::slice_in_ram_or;
+use crate::{interrupt, pac, Peripheral};
+
+/// Maximum length of a frame, in bytes.
+pub const MAX_FRAME_LEN: usize = 257;
+
+/// Length of the CRC appended to received frames.
+const CRC_LEN: usize = 2;
+
+// FRAMESTATUS.RX bits
+const FRAMESTATUS_CRCERROR: u32 = 1 << 0;
+const FRAMESTATUS_PARITYSTATUS: u32 = 1 << 2;
+const FRAMESTATUS_OVERRUN: u32 = 1 << 3;
+
+// ERRORSTATUS bits
+const ERRORSTATUS_FRAMEDELAYTIMEOUT: u32 = 1 << 0;
+
+/// NFCT error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The field of the reader was lost.
+    FieldLost,
+    /// The received frame has an invalid CRC.
+    Crc,
+    /// The received frame has an invalid parity.
+    Parity,
+    /// The received frame did not fit in the buffer.
+    Overrun,
+    /// The response was not transmitted in time.
+    FrameDelayTimeout,
+    /// The buffer is longer than [`MAX_FRAME_LEN`].
+    BufferTooLong,
+    /// The buffer is not in data RAM. It's most likely in flash, and nRF's DMA cannot access flash.
+    BufferNotInRAM,
+}
+
+/// NFCID1 of the tag, used during anti-collision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NfcId {
+    /// 4 bytes NFCID1.
+    SingleSize([u8; 4]),
+    /// 7 bytes NFCID1.
+    DoubleSize([u8; 7]),
+    /// 10 bytes NFCID1.
+    TripleSize([u8; 10]),
+}
+
+/// Protocol advertised in the SEL_RES response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// Type 2 tag.
+    Type2,
+    /// Type 4 tag, using ISO-DEP.
+    Type4,
+}
+
+/// NFCT configuration.
+#[non_exhaustive]
+pub struct Config {
+    /// NFCID1 of the tag.
+    pub nfcid1: NfcId,
+    /// Protocol advertised in SEL_RES.
+    pub protocol: Protocol,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            nfcid1: NfcId::DoubleSize([0x5F, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]),
+            protocol: Protocol::Type2,
+        }
+    }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::NFCT> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        WAKER.wake();
+    }
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// NFC-A tag driver.
+pub struct NfcT<'d> {
+    _p: PeripheralRef<'d, NFCT>,
+}
+
+impl<'d> NfcT<'d> {
+    /// Create a new NFCT driver, and start sensing the field of a reader.
+    pub fn new(
+        nfct: impl Peripheral<P = NFCT> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::NFCT, InterruptHandler> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(nfct);
+
+        let r = regs();
+
+        // NFCID1, in the registers used for its size, most significant bytes first.
+        let (size, id): (u32, &[u8]) = match &config.nfcid1 {
+            NfcId::SingleSize(id) => (0, id),
+            NfcId::DoubleSize(id) => (1, id),
+            NfcId::TripleSize(id) => (2, id),
+        };
+        let be = |bytes: &[u8]| bytes.iter().fold(0, |acc, &b| acc << 8 | b as u32);
+        match id.len() {
+            4 => r.nfcid1_last.write(|w| unsafe { w.bits(be(id)) }),
+            7 => {
+                r.nfcid1_2nd_last.write(|w| unsafe { w.bits(be(&id[..3])) });
+                r.nfcid1_last.write(|w| unsafe { w.bits(be(&id[3..])) });
+            }
+            _ => {
+                r.nfcid1_3rd_last.write(|w| unsafe { w.bits(be(&id[..3])) });
+                r.nfcid1_2nd_last.write(|w| unsafe { w.bits(be(&id[3..6])) });
+                r.nfcid1_last.write(|w| unsafe { w.bits(be(&id[6..])) });
+            }
+        }
+
+        // SENS_RES: NFCID1 size, and bit frame SDD 00100 as used by Type 2 and Type 4 tags.
+        r.sensres.write(|w| unsafe { w.bits(size << 6 | 0b00100) });
+        // SEL_RES: the cascade bit is handled by the hardware.
+        let protocol = match config.protocol {
+            Protocol::Type2 => 0b00,
+            Protocol::Type4 => 0b01,
+        };
+        r.selres.write(|w| unsafe { w.bits(protocol << 5) });
+
+        // Leave as much time as possible to answer.
+        r.framedelaymax.write(|w| unsafe { w.bits(0xFFFF) });
+
+        // Activate when the field is detected, and go back to sensing when it is lost.
+        r.shorts.write(|w| {
+            w.fielddetected_activate().enabled();
+            w.fieldlost_sense().enabled()
+        });
+
+        interrupt::NFCT.unpend();
+        unsafe { interrupt::NFCT.enable() };
+
+        r.tasks_sense.write(|w| unsafe { w.bits(1) });
+
+        Self { _p: nfct }
+    }
+
+    /// Whether the field of a reader is present.
+    pub fn is_field_present(&self) -> bool {
+        regs().fieldpresent.read().bits() & 1 != 0
+    }
+
+    /// Wait for the field of a reader to be detected.
+    pub async fn wait_for_field(&mut self) {
+        let r = regs();
+
+        r.events_fielddetected.reset();
+        if self.is_field_present() {
+            return;
+        }
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_fielddetected.read().bits() != 0 {
+                r.events_fielddetected.reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| w.fielddetected().set());
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Wait for the tag to be selected by a reader, after the anti-collision.
+    ///
+    /// The first frame of the higher level protocol can then be received.
+    pub async fn wait_for_selected(&mut self) {
+        let r = regs();
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_selected.read().bits() != 0 {
+                r.events_selected.reset();
+                return Poll::Ready(());
+            }
+            r.intenset.write(|w| w.selected().set());
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Receive a frame from the reader, returning its length without the CRC.
+    pub async fn receive(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        slice_in_ram_or(buf, Error::BufferNotInRAM)?;
+        let r = regs();
+
+        // In case the future is dropped, stop waiting for a frame.
+        let on_drop = OnDrop::new(|| {
+            regs().tasks_goidle.write(|w| unsafe { w.bits(1) });
+        });
+
+        r.packetptr.write(|w| unsafe { w.bits(buf.as_mut_ptr() as u32) });
+        r.maxlen
+            .write(|w| unsafe { w.bits(buf.len().min(MAX_FRAME_LEN) as u32) });
+        r.framestatus.rx.write(|w| unsafe { w.bits(0xF) });
+        r.events_rxframeend.reset();
+        r.events_fieldlost.reset();
+
+        compiler_fence(Ordering::SeqCst);
+        r.tasks_enablerxdata.write(|w| unsafe { w.bits(1) });
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_rxframeend.read().bits() != 0 {
+                r.events_rxframeend.reset();
+                return Poll::Ready(Ok(()));
+            }
+            if r.events_fieldlost.read().bits() != 0 {
+                r.events_fieldlost.reset();
+                return Poll::Ready(Err(Error::FieldLost));
+            }
+            r.intenset.write(|w| {
+                w.rxframeend().set();
+                w.fieldlost().set()
+            });
+            Poll::Pending
+        })
+        .await?;
+
+        compiler_fence(Ordering::SeqCst);
+        on_drop.defuse();
+
+        let status = r.framestatus.rx.read().bits();
+        r.framestatus.rx.write(|w| unsafe { w.bits(status) });
+        if status & FRAMESTATUS_OVERRUN != 0 {
+            return Err(Error::Overrun);
+        }
+        if status & FRAMESTATUS_PARITYSTATUS != 0 {
+            return Err(Error::Parity);
+        }
+        if status & FRAMESTATUS_CRCERROR != 0 {
+            return Err(Error::Crc);
+        }
+
+        let len = (r.rxd.amount.read().bits() >> 3 & 0x1FF) as usize;
+        Ok(len.saturating_sub(CRC_LEN))
+    }
+
+    /// Transmit a frame to the reader, in response to the last frame received.
+    ///
+    /// The CRC is computed and appended by the hardware.
+    pub async fn transmit(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() > MAX_FRAME_LEN {
+            return Err(Error::BufferTooLong);
+        }
+        slice_in_ram_or(data, Error::BufferNotInRAM)?;
+        let r = regs();
+
+        r.packetptr.write(|w| unsafe { w.bits(data.as_ptr() as u32) });
+        r.txd.amount.write(|w| unsafe { w.bits((data.len() as u32) << 3) });
+        r.errorstatus
+            .write(|w| unsafe { w.bits(ERRORSTATUS_FRAMEDELAYTIMEOUT) });
+        r.events_txframeend.reset();
+        r.events_error.reset();
+        r.events_fieldlost.reset();
+
+        compiler_fence(Ordering::SeqCst);
+        r.tasks_starttx.write(|w| unsafe { w.bits(1) });
+
+        let res = poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_txframeend.read().bits() != 0 {
+                r.events_txframeend.reset();
+                return Poll::Ready(Ok(()));
+            }
+            if r.events_error.read().bits() != 0 {
+                r.events_error.reset();
+                let status = r.errorstatus.read().bits();
+                r.errorstatus.write(|w| unsafe { w.bits(status) });
+                if status & ERRORSTATUS_FRAMEDELAYTIMEOUT != 0 {
+                    return Poll::Ready(Err(Error::FrameDelayTimeout));
+                }
+            }
+            if r.events_fieldlost.read().bits() != 0 {
+                r.events_fieldlost.reset();
+                return Poll::Ready(Err(Error::FieldLost));
+            }
+            r.intenset.write(|w| {
+                w.txframeend().set();
+                w.error().set();
+                w.fieldlost().set()
+            });
+            Poll::Pending
+        })
+        .await;
+
+        compiler_fence(Ordering::SeqCst);
+        res
+    }
+
+    /// Put the tag to sleep, e.g. after receiving a HLTA command.
+    ///
+    /// The tag then only answers a WUPA command from the reader, performing the anti-collision
+    /// again.
+    pub fn sleep(&mut self) {
+        regs().tasks_gosleep.write(|w| unsafe { w.bits(1) });
+    }
+}
+
+impl<'d> Drop for NfcT<'d> {
+    fn drop(&mut self) {
+        let r = regs();
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        r.shorts.reset();
+        r.tasks_disable.write(|w| unsafe { w.bits(1) });
+    }
+}
+
+fn regs() -> &'static pac::nfct::RegisterBlock {
+    unsafe { &*pac::NFCT::ptr() }
+}
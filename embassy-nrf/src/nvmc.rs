@@ -84,6 +84,41 @@ impl<'d> Nvmc<'d> {
         }
     }
 
+    /// Write `bytes` at `offset`, which doesn't need to be word-aligned.
+    ///
+    /// The bytes of the partial words at the start and the end of the range are written as
+    /// 0xFF, which leaves them unchanged since writes can only clear bits. Note that a word can
+    /// only be written a limited number of times between erases (nWRITE in the datasheet).
+    pub fn blocking_write_unaligned(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        if offset as usize + bytes.len() > FLASH_SIZE {
+            return Err(Error::OutOfBounds);
+        }
+
+        self.enable_write();
+        self.wait_ready();
+
+        let mut offset = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let word_addr = offset & !3;
+            let start = (offset - word_addr) as usize;
+            let len = core::cmp::min(4 - start, bytes.len());
+
+            let mut word = [0xFF; 4];
+            word[start..start + len].copy_from_slice(&bytes[..len]);
+            unsafe { ptr::write_volatile(word_addr as *mut u32, u32::from_le_bytes(word)) };
+            self.wait_ready_write();
+
+            offset += len as u32;
+            bytes = &bytes[len..];
+        }
+
+        self.enable_read();
+        self.wait_ready();
+
+        Ok(())
+    }
+
     fn enable_erase(&self) {
         #[cfg(not(feature = "_ns"))]
         Self::regs().config.write(|w| w.wen().een());
@@ -160,7 +195,7 @@ impl<'d> NorFlash for Nvmc<'d> {
         if offset as usize + bytes.len() > FLASH_SIZE {
             return Err(Error::OutOfBounds);
         }
-        if offset as usize % 4 != 0 || bytes.len() as usize % 4 != 0 {
+        if offset as usize % 4 != 0 || bytes.len() % 4 != 0 {
             return Err(Error::Unaligned);
         }
 
@@ -184,3 +219,69 @@ impl<'d> NorFlash for Nvmc<'d> {
         Ok(())
     }
 }
+
+#[cfg(feature = "nightly")]
+mod _eh1 {
+    use embassy_futures::yield_now;
+    use embedded_storage_async::nor_flash::{NorFlash as AsyncNorFlash, ReadNorFlash as AsyncReadNorFlash};
+
+    use super::*;
+
+    impl<'d> AsyncReadNorFlash for Nvmc<'d> {
+        const READ_SIZE: usize = <Self as ReadNorFlash>::READ_SIZE;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            ReadNorFlash::read(self, offset, bytes)
+        }
+
+        fn capacity(&self) -> usize {
+            FLASH_SIZE
+        }
+    }
+
+    /// The NVMC stalls the CPU while it's busy, so these erase and write a page at a time,
+    /// yielding in between to let other tasks run.
+    impl<'d> AsyncNorFlash for Nvmc<'d> {
+        const WRITE_SIZE: usize = <Self as NorFlash>::WRITE_SIZE;
+        const ERASE_SIZE: usize = <Self as NorFlash>::ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            if to < from || to as usize > FLASH_SIZE {
+                return Err(Error::OutOfBounds);
+            }
+            if from as usize % PAGE_SIZE != 0 || to as usize % PAGE_SIZE != 0 {
+                return Err(Error::Unaligned);
+            }
+
+            for page_addr in (from..to).step_by(PAGE_SIZE) {
+                NorFlash::erase(self, page_addr, page_addr + PAGE_SIZE as u32)?;
+                yield_now().await;
+            }
+
+            Ok(())
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            if offset as usize + bytes.len() > FLASH_SIZE {
+                return Err(Error::OutOfBounds);
+            }
+            if offset as usize % 4 != 0 || bytes.len() % 4 != 0 {
+                return Err(Error::Unaligned);
+            }
+
+            let mut offset = offset;
+            let mut bytes = bytes;
+            while !bytes.is_empty() {
+                // Write up to the end of the current page.
+                let len = core::cmp::min(PAGE_SIZE - offset as usize % PAGE_SIZE, bytes.len());
+                NorFlash::write(self, offset, &bytes[..len])?;
+                yield_now().await;
+
+                offset += len as u32;
+                bytes = &bytes[len..];
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -23,7 +23,10 @@ pub struct InterruptHandler<T: Instance> {
 
 impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
     unsafe fn on_interrupt() {
-        T::regs().intenclr.write(|w| w.end().clear());
+        T::regs().intenclr.write(|w| {
+            w.end().clear();
+            w.started().clear()
+        });
         T::state().waker.wake();
     }
 }
@@ -179,6 +182,65 @@ impl<'d, T: Instance> Pdm<'d, T> {
         Ok(())
     }
 
+    /// Start sampling continuously, alternately into each of the two buffers.
+    ///
+    /// Filled buffers are then obtained with [`ContinuousSampler::read`]. The peripheral keeps
+    /// sampling into the other buffer meanwhile, and overwrites the one returned once it is
+    /// filled too: the time spent processing a buffer should not exceed the time taken to fill
+    /// it, or samples get corrupted. Sampling is stopped when the sampler is dropped.
+    ///
+    /// The microphone needs some time to settle after starting, so the first buffers should
+    /// usually be discarded.
+    pub fn continuous<'s, const N: usize>(
+        &'s mut self,
+        bufs: &'s mut [[i16; N]; 2],
+    ) -> Result<ContinuousSampler<'s, T, N>, Error> {
+        if N == 0 {
+            return Err(Error::BufferZeroLength);
+        }
+        if N > EASY_DMA_SIZE {
+            return Err(Error::BufferTooLong);
+        }
+
+        let r = T::regs();
+
+        if r.events_started.read().bits() != 0 {
+            Self::stop_immediately();
+        }
+
+        r.sample
+            .ptr
+            .write(|w| unsafe { w.sampleptr().bits(bufs[0].as_ptr() as u32) });
+        r.sample.maxcnt.write(|w| unsafe { w.buffsize().bits(N as _) });
+
+        r.events_end.reset();
+        r.events_started.reset();
+
+        compiler_fence(Ordering::SeqCst);
+
+        r.tasks_start.write(|w| unsafe { w.bits(1) });
+
+        Ok(ContinuousSampler {
+            bufs,
+            current: 0,
+            _phantom: PhantomData,
+        })
+    }
+
+    // Stop sampling and wait for it to stop in a blocking fashion
+    fn stop_immediately() {
+        let r = T::regs();
+
+        compiler_fence(Ordering::SeqCst);
+
+        r.events_stopped.reset();
+        r.tasks_stop.write(|w| unsafe { w.bits(1) });
+
+        while r.events_stopped.read().bits() == 0 {}
+        r.events_stopped.reset();
+        r.events_started.reset();
+    }
+
     async fn wait_for_sample() {
         let r = T::regs();
 
@@ -200,6 +262,66 @@ impl<'d, T: Instance> Pdm<'d, T> {
     }
 }
 
+/// Continuous sampling into double buffers, created with [`Pdm::continuous`].
+pub struct ContinuousSampler<'s, T: Instance, const N: usize> {
+    bufs: &'s mut [[i16; N]; 2],
+    current: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<'s, T: Instance, const N: usize> ContinuousSampler<'s, T, N> {
+    /// Wait for the next buffer to be filled, and return it.
+    pub async fn read(&mut self) -> &[i16; N] {
+        let r = T::regs();
+
+        let ready = poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            let mut ready = None;
+            if r.events_end.read().bits() != 0 {
+                compiler_fence(Ordering::SeqCst);
+                r.events_end.reset();
+
+                ready = Some(self.current);
+                self.current = 1 - self.current;
+            }
+
+            // The pointer is double buffered: once a buffer is started, set the following one.
+            if r.events_started.read().bits() != 0 {
+                r.events_started.reset();
+                let next = &self.bufs[1 - self.current];
+                r.sample
+                    .ptr
+                    .write(|w| unsafe { w.sampleptr().bits(next.as_ptr() as u32) });
+            }
+
+            r.intenset.write(|w| {
+                w.end().set();
+                w.started().set()
+            });
+
+            match ready {
+                Some(ready) => Poll::Ready(ready),
+                None => Poll::Pending,
+            }
+        })
+        .await;
+
+        &self.bufs[ready]
+    }
+}
+
+impl<'s, T: Instance, const N: usize> Drop for ContinuousSampler<'s, T, N> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.intenclr.write(|w| {
+            w.end().clear();
+            w.started().clear()
+        });
+        Pdm::<T>::stop_immediately();
+    }
+}
+
 /// PDM microphone driver Config
 pub struct Config {
     /// Use stero or mono operation
@@ -0,0 +1,101 @@
+//! System OFF and RAM retention.
+//!
+//! In System OFF, the chip draws the least power possible: all clocks and peripherals are
+//! stopped, and only the configured wakeup sources are kept alive. Waking up resets the chip,
+//! so the program starts again from `main`. The contents of RAM are lost, except for the
+//! sections whose retention was enabled with [`set_ram_retention`].
+//!
+//! The chip wakes up from System OFF on:
+//! - the DETECT signal of the GPIO port, on pins configured with [`enable_wakeup_pin`],
+//! - an LPCOMP crossing, see [`Lpcomp::into_wakeup`](crate::lpcomp::Lpcomp::into_wakeup),
+//! - an NFC field, see [`enable_wakeup_nfc`],
+//! - a reset.
+
+use embassy_hal_common::into_ref;
+
+use crate::gpio::sealed::Pin as _;
+use crate::gpio::{Level, Pin as GpioPin, Pull};
+use crate::{pac, Peripheral};
+
+/// Number of RAM blocks whose retention can be configured.
+#[cfg(feature = "nrf52840")]
+pub const RAM_BLOCK_COUNT: usize = 9;
+/// Number of RAM blocks whose retention can be configured.
+#[cfg(not(feature = "nrf52840"))]
+pub const RAM_BLOCK_COUNT: usize = 8;
+
+/// Configure `pin` as an input whose level wakes the chip from System OFF.
+///
+/// The chip wakes up when the pin level is `level`. The pin configuration is kept when the
+/// pin is dropped, so that it stays active in System OFF.
+pub fn enable_wakeup_pin(pin: impl Peripheral<P = impl GpioPin>, pull: Pull, level: Level) {
+    into_ref!(pin);
+
+    pin.conf().write(|w| {
+        w.dir().input();
+        w.input().connect();
+        match pull {
+            Pull::None => w.pull().disabled(),
+            Pull::Up => w.pull().pullup(),
+            Pull::Down => w.pull().pulldown(),
+        };
+        match level {
+            Level::Low => w.sense().low(),
+            Level::High => w.sense().high(),
+        };
+        w
+    });
+}
+
+/// Keep the NFCT peripheral sensing for a field, which wakes the chip from System OFF.
+///
+/// The NFC pins must not be configured as GPIOs.
+#[cfg(all(
+    any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"),
+    not(feature = "nfc-pins-as-gpio")
+))]
+pub fn enable_wakeup_nfc(_nfct: impl Peripheral<P = crate::peripherals::NFCT>) {
+    let r = unsafe { &*pac::NFCT::ptr() };
+    r.tasks_sense.write(|w| unsafe { w.bits(1) });
+}
+
+/// Set which sections of RAM block `block` retain their contents in System OFF.
+///
+/// Bit `n` of `sections` enables the retention of section `n`. Retention costs power, so only
+/// the sections holding data to be kept across System OFF should be retained.
+///
+/// # Panics
+///
+/// Panics if `block` >= [`RAM_BLOCK_COUNT`].
+pub fn set_ram_retention(block: usize, sections: u16) {
+    assert!(block < RAM_BLOCK_COUNT);
+
+    #[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
+    let ram = unsafe { &(*pac::POWER::ptr()).ram[block] };
+    #[cfg(any(feature = "_nrf5340", feature = "_nrf9160"))]
+    let ram = unsafe { &(*pac::VMC::ptr()).ram[block] };
+
+    let mask = (sections as u32) << 16;
+    ram.powerclr.write(|w| unsafe { w.bits(!mask & 0xFFFF_0000) });
+    ram.powerset.write(|w| unsafe { w.bits(mask) });
+}
+
+/// Enter System OFF.
+///
+/// This doesn't return: the chip resets when woken up. In debug interface mode, System OFF is
+/// emulated and the CPU keeps running, so this then waits forever instead.
+#[cfg(not(feature = "_nrf5340-net"))]
+pub fn system_off() -> ! {
+    #[cfg(not(any(feature = "_nrf5340", feature = "_nrf9160")))]
+    unsafe { &*pac::POWER::ptr() }
+        .systemoff
+        .write(|w| w.systemoff().enter());
+    #[cfg(any(feature = "_nrf5340", feature = "_nrf9160"))]
+    unsafe { &*pac::REGULATORS::ptr() }
+        .systemoff
+        .write(|w| w.systemoff().enter());
+
+    loop {
+        cortex_m::asm::wfe();
+    }
+}
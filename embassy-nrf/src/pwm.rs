@@ -2,16 +2,38 @@
 
 #![macro_use]
 
+use core::future::poll_fn;
+use core::marker::PhantomData;
 use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
 
+use embassy_hal_common::drop::OnDrop;
 use embassy_hal_common::{into_ref, PeripheralRef};
 
 use crate::gpio::sealed::Pin as _;
 use crate::gpio::{AnyPin, Pin as GpioPin, PselBits};
+use crate::interrupt::typelevel::Interrupt;
 use crate::ppi::{Event, Task};
 use crate::util::slice_in_ram_or;
 use crate::{interrupt, pac, Peripheral};
 
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        if r.events_loopsdone.read().bits() != 0 {
+            s.waker.wake();
+            r.intenclr.write(|w| w.loopsdone().clear());
+        }
+    }
+}
+
 /// SimplePwm is the traditional pwm interface you're probably used to, allowing
 /// to simply set a duty cycle across up to four channels.
 pub struct SimplePwm<'d, T: Instance> {
@@ -53,6 +75,7 @@ impl<'d, T: Instance> SequencePwm<'d, T> {
     #[allow(unused_unsafe)]
     pub fn new_1ch(
         pwm: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         ch0: impl Peripheral<P = impl GpioPin> + 'd,
         config: Config,
     ) -> Result<Self, Error> {
@@ -64,6 +87,7 @@ impl<'d, T: Instance> SequencePwm<'d, T> {
     #[allow(unused_unsafe)]
     pub fn new_2ch(
         pwm: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         ch0: impl Peripheral<P = impl GpioPin> + 'd,
         ch1: impl Peripheral<P = impl GpioPin> + 'd,
         config: Config,
@@ -76,6 +100,7 @@ impl<'d, T: Instance> SequencePwm<'d, T> {
     #[allow(unused_unsafe)]
     pub fn new_3ch(
         pwm: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         ch0: impl Peripheral<P = impl GpioPin> + 'd,
         ch1: impl Peripheral<P = impl GpioPin> + 'd,
         ch2: impl Peripheral<P = impl GpioPin> + 'd,
@@ -96,6 +121,7 @@ impl<'d, T: Instance> SequencePwm<'d, T> {
     #[allow(unused_unsafe)]
     pub fn new_4ch(
         pwm: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
         ch0: impl Peripheral<P = impl GpioPin> + 'd,
         ch1: impl Peripheral<P = impl GpioPin> + 'd,
         ch2: impl Peripheral<P = impl GpioPin> + 'd,
@@ -170,6 +196,9 @@ impl<'d, T: Instance> SequencePwm<'d, T> {
         r.prescaler.write(|w| w.prescaler().bits(config.prescaler as u8));
         r.countertop.write(|w| unsafe { w.countertop().bits(config.max_duty) });
 
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
         Ok(Self {
             _peri: _pwm,
             ch0,
@@ -393,6 +422,19 @@ impl<'d, 's, T: Instance> SingleSequencer<'d, 's, T> {
         self.sequencer.start(start_seq, times)
     }
 
+    /// Play the sequence `times` times, and wait for the playback to complete.
+    ///
+    /// The peripheral is stopped when this returns. Cancellation also stops the playback.
+    pub async fn play(&self, times: u16) -> Result<(), Error> {
+        let (start_seq, loops) = match times {
+            0 => return Err(Error::SequenceTimesAtLeastOne),
+            1 => (StartSequence::One, 1),
+            n if n & 1 == 1 => (StartSequence::One, (n / 2) + 1),
+            n => (StartSequence::Zero, n / 2),
+        };
+        self.sequencer.play(start_seq, loops).await
+    }
+
     /// Stop playback. Disables the peripheral. Does NOT clear the last duty
     /// cycle from the pin. Returns any sequences previously provided to
     /// `start` so that they may be further mutated.
@@ -485,6 +527,41 @@ impl<'d, 's, T: Instance> Sequencer<'d, 's, T> {
         Ok(())
     }
 
+    /// Play both sequences combined as one `loops` times, starting with `start_seq`, and wait
+    /// for the playback to complete.
+    ///
+    /// The peripheral is stopped when this returns. Cancellation also stops the playback.
+    pub async fn play(&self, start_seq: StartSequence, loops: u16) -> Result<(), Error> {
+        let r = T::regs();
+        let s = T::state();
+
+        r.events_loopsdone.reset();
+        r.intenset.write(|w| w.loopsdone().set());
+
+        let on_drop = OnDrop::new(|| {
+            r.intenclr.write(|w| w.loopsdone().clear());
+            self.stop();
+        });
+
+        self.start(start_seq, SequenceMode::Loop(loops))?;
+
+        poll_fn(|cx| {
+            s.waker.register(cx.waker());
+
+            if r.events_loopsdone.read().bits() != 0 {
+                r.events_loopsdone.reset();
+                return Poll::Ready(());
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        drop(on_drop);
+
+        Ok(())
+    }
+
     /// Stop playback. Disables the peripheral. Does NOT clear the last duty
     /// cycle from the pin. Returns any sequences previously provided to
     /// `start` so that they may be further mutated.
@@ -832,10 +909,25 @@ impl<'a, T: Instance> Drop for SimplePwm<'a, T> {
 }
 
 pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
     use super::*;
 
+    pub struct State {
+        pub waker: AtomicWaker,
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            Self {
+                waker: AtomicWaker::new(),
+            }
+        }
+    }
+
     pub trait Instance {
         fn regs() -> &'static pac::pwm0::RegisterBlock;
+        fn state() -> &'static State;
     }
 }
 
@@ -851,6 +943,10 @@ macro_rules! impl_pwm {
             fn regs() -> &'static pac::pwm0::RegisterBlock {
                 unsafe { &*pac::$pac_type::ptr() }
             }
+            fn state() -> &'static crate::pwm::sealed::State {
+                static STATE: crate::pwm::sealed::State = crate::pwm::sealed::State::new();
+                &STATE
+            }
         }
         impl crate::pwm::Instance for peripherals::$type {
             type Interrupt = crate::interrupt::typelevel::$irq;
@@ -21,7 +21,7 @@ pub struct Qdec<'d, T: Instance> {
 /// QDEC config
 #[non_exhaustive]
 pub struct Config {
-    /// Number of samples
+    /// Number of samples per report
     pub num_samples: NumSamples,
     /// Sample period
     pub period: SamplePeriod,
@@ -118,6 +118,19 @@ impl<'d, T: Instance> Qdec<'d, T> {
         r.ledpre
             .write(|w| unsafe { w.ledpre().bits(config.led_pre_usecs.min(511)) });
 
+        // Set the number of samples per report
+        r.reportper.write(|w| match config.num_samples {
+            NumSamples::_10smpl => w.reportper()._10smpl(),
+            NumSamples::_40smpl => w.reportper()._40smpl(),
+            NumSamples::_80smpl => w.reportper()._80smpl(),
+            NumSamples::_120smpl => w.reportper()._120smpl(),
+            NumSamples::_160smpl => w.reportper()._160smpl(),
+            NumSamples::_200smpl => w.reportper()._200smpl(),
+            NumSamples::_240smpl => w.reportper()._240smpl(),
+            NumSamples::_280smpl => w.reportper()._280smpl(),
+            NumSamples::_1smpl => w.reportper()._1smpl(),
+        });
+
         // Set sample period
         r.sampleper.write(|w| match config.period {
             SamplePeriod::_128us => w.sampleper()._128us(),
@@ -185,6 +198,38 @@ impl<'d, T: Instance> Qdec<'d, T> {
         .await;
         value
     }
+
+    /// Wait until the decoder accumulated at least `steps` steps in either direction, and return
+    /// the number of steps.
+    ///
+    /// The decoder reports the accumulated steps once every [`Config::num_samples`] samples, so
+    /// that the CPU is only woken up once per report. Up to a report worth of additional steps
+    /// can be returned. Steps in opposite directions cancel out.
+    pub async fn wait_for_steps(&mut self, steps: u16) -> i32 {
+        let mut acc = 0i32;
+        while acc.unsigned_abs() < steps as u32 {
+            acc += self.wait_report().await as i32;
+        }
+        acc
+    }
+
+    /// Wait for a non-null report, and read and clear the accumulator.
+    async fn wait_report(&mut self) -> i16 {
+        let t = T::regs();
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            if t.events_reportrdy.read().bits() == 0 {
+                t.intenset.write(|w| w.reportrdy().set());
+                Poll::Pending
+            } else {
+                t.events_reportrdy.reset();
+                unsafe { t.tasks_readclracc.write(|w| w.bits(1)) };
+                Poll::Ready(t.accread.read().bits() as i16)
+            }
+        })
+        .await
+    }
 }
 
 /// Sample period
@@ -19,6 +19,13 @@ pub use crate::pac::qspi::ifconfig0::{
 pub use crate::pac::qspi::ifconfig1::SPIMODE_A as SpiMode;
 use crate::{interrupt, Peripheral};
 
+/// Start address of the XIP region, where the flash memory is mapped for execution in place.
+#[cfg(feature = "nrf52840")]
+pub const XIP_START: u32 = 0x1200_0000;
+/// Start address of the XIP region, where the flash memory is mapped for execution in place.
+#[cfg(feature = "_nrf5340-app")]
+pub const XIP_START: u32 = 0x1000_0000;
+
 /// Deep power-down config.
 pub struct DeepPowerDownConfig {
     /// Time required for entering DPM, in units of 16us
@@ -257,27 +264,41 @@ impl<'d, T: Instance> Qspi<'d, T> {
         Ok(())
     }
 
-    fn custom_instruction_start(&mut self, opcode: u8, req: &[u8], len: u8) -> Result<(), Error> {
-        assert!(req.len() <= 8);
+    /// Do a custom QSPI instruction in long frame mode, for instructions transferring more than 8
+    /// bytes of data, such as SFDP reads.
+    ///
+    /// The opcode is sent first, then the data is transferred in chunks of up to 8 bytes while
+    /// CSN is kept asserted. `req` is sent (padded with zeros) while `resp` is received.
+    pub async fn custom_instruction_long(&mut self, opcode: u8, req: &[u8], resp: &mut [u8]) -> Result<(), Error> {
+        let ondrop = OnDrop::new(Self::blocking_wait_ready);
 
-        let mut dat0: u32 = 0;
-        let mut dat1: u32 = 0;
+        let len = core::cmp::max(req.len(), resp.len());
 
-        for i in 0..4 {
-            if i < req.len() {
-                dat0 |= (req[i] as u32) << (i * 8);
-            }
-        }
-        for i in 0..4 {
-            if i + 4 < req.len() {
-                dat1 |= (req[i + 4] as u32) << (i * 8);
-            }
+        self.custom_instruction_long_start(opcode, &[], 0, len == 0);
+        self.wait_ready().await;
+
+        for start in (0..len).step_by(8) {
+            let end = core::cmp::min(start + 8, len);
+            let req = &req[core::cmp::min(start, req.len())..core::cmp::min(end, req.len())];
+            self.custom_instruction_long_start(opcode, req, (end - start) as u8, end == len);
+            self.wait_ready().await;
+
+            let resp_len = resp.len();
+            let resp = &mut resp[core::cmp::min(start, resp_len)..core::cmp::min(end, resp_len)];
+            self.custom_instruction_finish(resp)?;
         }
 
-        let r = T::regs();
-        r.cinstrdat0.write(|w| unsafe { w.bits(dat0) });
-        r.cinstrdat1.write(|w| unsafe { w.bits(dat1) });
+        ondrop.defuse();
+
+        Ok(())
+    }
+
+    fn custom_instruction_start(&mut self, opcode: u8, req: &[u8], len: u8) -> Result<(), Error> {
+        assert!(req.len() <= 8);
+
+        Self::write_instruction_data(req);
 
+        let r = T::regs();
         r.events_ready.reset();
         r.intenset.write(|w| w.ready().set());
 
@@ -295,6 +316,46 @@ impl<'d, T: Instance> Qspi<'d, T> {
         Ok(())
     }
 
+    fn custom_instruction_long_start(&mut self, opcode: u8, req: &[u8], len: u8, stop: bool) {
+        Self::write_instruction_data(req);
+
+        let r = T::regs();
+        r.events_ready.reset();
+        r.intenset.write(|w| w.ready().set());
+
+        r.cinstrconf.write(|w| {
+            let w = unsafe { w.opcode().bits(opcode) };
+            let w = unsafe { w.length().bits(len + 1) };
+            let w = w.lio2().bit(true);
+            let w = w.lio3().bit(true);
+            let w = w.wipwait().bit(false);
+            let w = w.wren().bit(false);
+            let w = w.lfen().bit(true);
+            let w = w.lfstop().bit(stop);
+            w
+        });
+    }
+
+    fn write_instruction_data(req: &[u8]) {
+        let mut dat0: u32 = 0;
+        let mut dat1: u32 = 0;
+
+        for i in 0..4 {
+            if i < req.len() {
+                dat0 |= (req[i] as u32) << (i * 8);
+            }
+        }
+        for i in 0..4 {
+            if i + 4 < req.len() {
+                dat1 |= (req[i + 4] as u32) << (i * 8);
+            }
+        }
+
+        let r = T::regs();
+        r.cinstrdat0.write(|w| unsafe { w.bits(dat0) });
+        r.cinstrdat1.write(|w| unsafe { w.bits(dat1) });
+    }
+
     fn custom_instruction_finish(&mut self, resp: &mut [u8]) -> Result<(), Error> {
         let r = T::regs();
 
@@ -307,7 +368,7 @@ impl<'d, T: Instance> Qspi<'d, T> {
         }
         for i in 0..4 {
             if i + 4 < resp.len() {
-                resp[i] = (dat1 >> (i * 8)) as u8;
+                resp[i + 4] = (dat1 >> (i * 8)) as u8;
             }
         }
         Ok(())
@@ -492,6 +553,32 @@ impl<'d, T: Instance> Qspi<'d, T> {
         Ok(())
     }
 
+    /// Set the XIP offset.
+    ///
+    /// Reads from the XIP region, starting at [`XIP_START`], are mapped to the flash memory
+    /// starting at this offset. Cached data from the previous mapping is not invalidated.
+    pub fn set_xip_offset(&mut self, offset: u32) {
+        T::regs().xipoffset.write(|w| unsafe { w.xipoffset().bits(offset) });
+    }
+
+    /// Get the XIP offset.
+    pub fn xip_offset(&self) -> u32 {
+        T::regs().xipoffset.read().xipoffset().bits()
+    }
+
+    /// Get the flash memory contents through the XIP region.
+    ///
+    /// The slice covers the flash capacity, starting at the XIP offset.
+    ///
+    /// # Safety
+    ///
+    /// The flash memory must not be written or erased, and the XIP offset must not change, while
+    /// the slice is in use.
+    pub unsafe fn xip_slice(&self) -> &[u8] {
+        let len = self.capacity.saturating_sub(self.xip_offset());
+        core::slice::from_raw_parts(XIP_START as *const u8, len as usize)
+    }
+
     fn bounds_check(&self, address: u32, len: usize) -> Result<(), Error> {
         let len_u32: u32 = len.try_into().map_err(|_| Error::OutOfBounds)?;
         let end_address = address.checked_add(len_u32).ok_or(Error::OutOfBounds)?;
@@ -0,0 +1,458 @@
+//! Bluetooth Low Energy radio with precise timing.
+//!
+//! [`BleRadio`] sends and receives link layer packets on the LE 1M PHY at precise times, as a
+//! building block for BLE stacks. It combines the RADIO with a TIMER counting microseconds and
+//! PPI channels, so that:
+//!
+//! - the radio is enabled by a timer compare, at the exact anchor point of a connection event,
+//! - the access address of received packets is timestamped by the timer, and a receive window
+//!   closes by itself when nothing is received,
+//! - a response follows a packet T_IFS (150 µs) later, the turnaround being done by the radio.
+//!
+//! Times are in microseconds of [`BleRadio::now`], and wrap around after about 71 minutes. The
+//! radio starts sending or receiving the preamble [`RAMP_UP_US`] after it is enabled.
+//!
+//! Packets are link layer PDUs: a header byte, a length byte and the payload. The whitening,
+//! the access address, the preamble and the CRC are handled by the radio.
+
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU32, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::InterruptExt;
+use crate::peripherals::RADIO;
+use crate::ppi::{AnyConfigurableChannel, ConfigurableChannel, Event, Ppi, Task};
+use crate::timer::{self, Timer};
+use crate::util::slice_in_ram_or;
+use crate::{interrupt, pac, Peripheral};
+
+/// Time between enabling the radio and the start of the preamble.
+pub const RAMP_UP_US: u32 = 140;
+
+/// Inter frame space, between the end of a packet and the start of the next one.
+pub const T_IFS_US: u32 = 150;
+
+/// Length of the PDU header.
+const HEADER_LEN: usize = 2;
+
+// SHORTS bits
+const SHORT_READY_START: u32 = 1 << 0;
+const SHORT_END_DISABLE: u32 = 1 << 1;
+const SHORT_DISABLED_TXEN: u32 = 1 << 2;
+const SHORT_DISABLED_RXEN: u32 = 1 << 3;
+const SHORT_ADDRESS_RSSISTART: u32 = 1 << 4;
+const SHORT_DISABLED_RSSISTOP: u32 = 1 << 8;
+
+// INTEN bits
+const INT_DISABLED: u32 = 1 << 4;
+
+/// BLE radio error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The requested time has already passed.
+    TooLate,
+    /// No packet was received in the receive window.
+    Timeout,
+    /// The received packet has an invalid CRC.
+    Crc,
+    /// The buffer can not hold a PDU header, or its length byte exceeds its size.
+    BufferTooShort,
+    /// The buffer is not in data RAM. It's most likely in flash, and nRF's DMA cannot access flash.
+    BufferNotInRAM,
+}
+
+/// Information about a received packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxInfo {
+    /// Time at which the end of the access address was received.
+    pub timestamp: u32,
+    /// Received signal strength, in dBm.
+    pub rssi: i8,
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RADIO> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+        if r.events_disabled.read().bits() == 0 {
+            return;
+        }
+
+        // The radio is ramping up for the second packet of a turnaround.
+        let ptr = TURNAROUND_PTR.swap(0, Ordering::Relaxed);
+        if ptr != 0 {
+            r.events_disabled.reset();
+            if TURNAROUND_NEEDS_ADDRESS.load(Ordering::Relaxed) && r.events_address.read().bits() == 0 {
+                // Nothing to answer to
+                r.shorts.reset();
+                r.tasks_disable.write(|w| unsafe { w.bits(1) });
+            } else {
+                r.packetptr.write(|w| unsafe { w.bits(ptr) });
+                r.shorts
+                    .write(|w| unsafe { w.bits(TURNAROUND_SHORTS.load(Ordering::Relaxed)) });
+                let ppi = crate::ppi::regs();
+                ppi.chenclr
+                    .write(|w| unsafe { w.bits(TURNAROUND_CHENCLR.load(Ordering::Relaxed)) });
+                ppi.chenset
+                    .write(|w| unsafe { w.bits(TURNAROUND_CHENSET.load(Ordering::Relaxed)) });
+            }
+            return;
+        }
+
+        r.intenclr.write(|w| unsafe { w.bits(INT_DISABLED) });
+        WAKER.wake();
+    }
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+// Packet pointer, shorts and PPI channels to apply when the radio is disabled after the first
+// packet of a turnaround, or 0 when there's no turnaround.
+static TURNAROUND_PTR: AtomicU32 = AtomicU32::new(0);
+static TURNAROUND_SHORTS: AtomicU32 = AtomicU32::new(0);
+static TURNAROUND_CHENSET: AtomicU32 = AtomicU32::new(0);
+static TURNAROUND_CHENCLR: AtomicU32 = AtomicU32::new(0);
+// Whether the second packet of the turnaround is only sent when the first one was received.
+static TURNAROUND_NEEDS_ADDRESS: AtomicBool = AtomicBool::new(false);
+
+/// The first packet of an operation.
+enum First<'a> {
+    Tx(&'a [u8]),
+    Rx(&'a mut [u8]),
+}
+
+/// The packet following the first one after T_IFS, if any.
+enum Then<'a> {
+    None,
+    Tx(&'a [u8]),
+    Rx(&'a mut [u8]),
+}
+
+/// BLE radio driver with precise timing.
+pub struct BleRadio<'d, T: timer::Instance> {
+    _p: PeripheralRef<'d, RADIO>,
+    timer: Timer<'d, T>,
+    ppi_txen: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    ppi_rxen: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    ppi_timeout: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    ppi_address: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    address_chen: u32,
+    chen: u32,
+}
+
+impl<'d, T: timer::Instance> BleRadio<'d, T> {
+    /// Create a new BLE radio driver.
+    ///
+    /// The timer is used as time base, and the PPI channels to control the radio with it.
+    pub fn new(
+        radio: impl Peripheral<P = RADIO> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RADIO, InterruptHandler> + 'd,
+        timer: impl Peripheral<P = T> + 'd,
+        ppi_ch1: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+        ppi_ch2: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+        ppi_ch3: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+        ppi_ch4: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+    ) -> Self {
+        into_ref!(radio, ppi_ch1, ppi_ch2, ppi_ch3, ppi_ch4);
+        let address_chen = 1 << ppi_ch4.number();
+        let chen = 1 << ppi_ch1.number() | 1 << ppi_ch2.number() | 1 << ppi_ch3.number() | address_chen;
+
+        let r = regs();
+        r.power.write(|w| unsafe { w.bits(1) });
+
+        // Ble_1Mbit mode
+        r.mode.write(|w| unsafe { w.bits(3) });
+        // 1 byte S0 for the header, 8 bits length field.
+        r.pcnf0.write(|w| unsafe { w.bits(1 << 8 | 8) });
+        // 3 bytes base address, little endian, whitening enabled.
+        r.pcnf1.write(|w| unsafe { w.bits(1 << 25 | 3 << 16 | 255) });
+        // 3 bytes CRC, not including the access address.
+        r.crccnf.write(|w| unsafe { w.bits(1 << 8 | 3) });
+        r.crcpoly.write(|w| unsafe { w.bits(0x00065B) });
+        r.txaddress.write(|w| unsafe { w.bits(0) });
+        r.rxaddresses.write(|w| unsafe { w.bits(1) });
+        r.tifs.write(|w| unsafe { w.bits(T_IFS_US) });
+
+        let timer = Timer::new(timer);
+        let ppi_txen = Ppi::new_one_to_one(
+            ppi_ch1.map_into(),
+            timer.cc(0).event_compare(),
+            Task::from_reg(&r.tasks_txen),
+        );
+        let ppi_rxen = Ppi::new_one_to_one(
+            ppi_ch2.map_into(),
+            timer.cc(0).event_compare(),
+            Task::from_reg(&r.tasks_rxen),
+        );
+        let ppi_timeout = Ppi::new_one_to_one(
+            ppi_ch3.map_into(),
+            timer.cc(1).event_compare(),
+            Task::from_reg(&r.tasks_disable),
+        );
+        // Capturing the time of the access address also cancels the timeout, as the timer will
+        // not reach it again before wrapping around.
+        let ppi_address = Ppi::new_one_to_one(
+            ppi_ch4.map_into(),
+            Event::from_reg(&r.events_address),
+            timer.cc(1).task_capture(),
+        );
+        timer.start();
+
+        interrupt::RADIO.unpend();
+        unsafe { interrupt::RADIO.enable() };
+
+        let mut this = Self {
+            _p: radio,
+            timer,
+            ppi_txen,
+            ppi_rxen,
+            ppi_timeout,
+            ppi_address,
+            address_chen,
+            chen,
+        };
+        this.set_channel(37);
+        this.set_access_address(0x8E89BED6);
+        this.set_crc_init(0x555555);
+        this
+    }
+
+    /// Current time, in microseconds.
+    pub fn now(&self) -> u32 {
+        self.timer.cc(2).capture()
+    }
+
+    /// Set the channel, from 0 to 39, also used to initialize the whitening.
+    pub fn set_channel(&mut self, channel: u8) {
+        let frequency = match channel {
+            0..=10 => 4 + 2 * channel as u32,
+            11..=36 => 28 + 2 * (channel as u32 - 11),
+            37 => 2,
+            38 => 26,
+            39 => 80,
+            _ => panic!("invalid BLE channel {}", channel),
+        };
+        let r = regs();
+        r.frequency.write(|w| unsafe { w.bits(frequency) });
+        r.datawhiteiv.write(|w| unsafe { w.bits(0x40 | channel as u32) });
+    }
+
+    /// Set the access address of sent and received packets.
+    pub fn set_access_address(&mut self, access_address: u32) {
+        let r = regs();
+        r.base0.write(|w| unsafe { w.bits(access_address << 8) });
+        r.prefix0.write(|w| unsafe { w.bits(access_address >> 24) });
+    }
+
+    /// Set the initial value of the CRC, with the 24 least significant bits.
+    pub fn set_crc_init(&mut self, crc_init: u32) {
+        regs().crcinit.write(|w| unsafe { w.bits(crc_init & 0xFF_FFFF) });
+    }
+
+    /// Set the transmit power, in dBm. Must be one of the values supported by the chip.
+    pub fn set_tx_power(&mut self, power: i8) {
+        regs().txpower.write(|w| unsafe { w.bits(power as u8 as u32) });
+    }
+
+    /// Send a packet, enabling the radio at `at`.
+    pub async fn transmit_at(&mut self, at: u32, pdu: &[u8]) -> Result<(), Error> {
+        self.run(at, First::Tx(pdu), Then::None, 0).await.map(|_| ())
+    }
+
+    /// Receive a packet, enabling the radio at `at` and giving up when the access address was not
+    /// received `window_us` later.
+    pub async fn receive_at(&mut self, at: u32, window_us: u32, buf: &mut [u8]) -> Result<RxInfo, Error> {
+        let timeout = at.wrapping_add(RAMP_UP_US + window_us);
+        self.run(at, First::Rx(buf), Then::None, timeout).await
+    }
+
+    /// Send a packet, enabling the radio at `at`, and receive the response T_IFS after it.
+    ///
+    /// The response must start within `window_us` after T_IFS.
+    pub async fn transmit_then_receive(
+        &mut self,
+        at: u32,
+        pdu: &[u8],
+        window_us: u32,
+        buf: &mut [u8],
+    ) -> Result<RxInfo, Error> {
+        let len = check_pdu(pdu)?;
+        let timeout = at.wrapping_add(RAMP_UP_US + air_time_us(len) + T_IFS_US + window_us);
+        self.run(at, First::Tx(pdu), Then::Rx(buf), timeout).await
+    }
+
+    /// Receive a packet, enabling the radio at `at` and giving up when the access address was not
+    /// received `window_us` later, and send the response T_IFS after it.
+    ///
+    /// The response is sent even if the received packet has an invalid CRC, as required by the
+    /// link layer within a connection event. It is not sent when nothing was received.
+    pub async fn receive_then_transmit(
+        &mut self,
+        at: u32,
+        window_us: u32,
+        buf: &mut [u8],
+        pdu: &[u8],
+    ) -> Result<RxInfo, Error> {
+        let timeout = at.wrapping_add(RAMP_UP_US + window_us);
+        self.run(at, First::Rx(buf), Then::Tx(pdu), timeout).await
+    }
+
+    async fn run(&mut self, at: u32, first: First<'_>, then: Then<'_>, timeout: u32) -> Result<RxInfo, Error> {
+        let r = regs();
+
+        // Validate the buffers, and retrieve their DMA pointers and receive lengths.
+        let (first_ptr, first_rx_len) = match first {
+            First::Tx(pdu) => (tx_ptr(pdu)?, None),
+            First::Rx(buf) => (rx_ptr(buf)?, Some(buf.len())),
+        };
+        let (then_ptr, then_rx_len) = match then {
+            Then::None => (0, None),
+            Then::Tx(pdu) => (tx_ptr(pdu)?, None),
+            Then::Rx(buf) => (rx_ptr(buf)?, Some(buf.len())),
+        };
+        if let Some(len) = first_rx_len.into_iter().chain(then_rx_len).min() {
+            let max_len = (len - HEADER_LEN).min(255) as u32;
+            r.pcnf1.modify(|r, w| unsafe { w.bits(r.bits() & !0xFF | max_len) });
+        }
+
+        // The radio and the PPI channels must be disabled whatever happens.
+        let chen = self.chen;
+        let on_drop = OnDrop::new(|| {
+            TURNAROUND_PTR.store(0, Ordering::Relaxed);
+            crate::ppi::regs().chenclr.write(|w| unsafe { w.bits(chen) });
+            r.intenclr.write(|w| unsafe { w.bits(INT_DISABLED) });
+            r.shorts.reset();
+            r.tasks_disable.write(|w| unsafe { w.bits(1) });
+            while r.state.read().bits() != 0 {}
+            r.events_disabled.reset();
+        });
+
+        let rx_shorts = SHORT_READY_START | SHORT_END_DISABLE | SHORT_ADDRESS_RSSISTART | SHORT_DISABLED_RSSISTOP;
+        let tx_shorts = SHORT_READY_START | SHORT_END_DISABLE;
+        let mut shorts = if first_rx_len.is_some() { rx_shorts } else { tx_shorts };
+        if then_ptr != 0 {
+            if then_rx_len.is_some() {
+                shorts |= SHORT_DISABLED_RXEN;
+                TURNAROUND_SHORTS.store(rx_shorts, Ordering::Relaxed);
+                // The access address of the sent packet must not cancel the timeout of the response,
+                // so it is only captured once receiving.
+                TURNAROUND_CHENSET.store(self.address_chen, Ordering::Relaxed);
+                TURNAROUND_CHENCLR.store(0, Ordering::Relaxed);
+            } else {
+                shorts |= SHORT_DISABLED_TXEN;
+                TURNAROUND_SHORTS.store(tx_shorts, Ordering::Relaxed);
+                // Keep the time of the received access address.
+                TURNAROUND_CHENSET.store(0, Ordering::Relaxed);
+                TURNAROUND_CHENCLR.store(self.address_chen, Ordering::Relaxed);
+            }
+            TURNAROUND_NEEDS_ADDRESS.store(first_rx_len.is_some(), Ordering::Relaxed);
+        }
+        TURNAROUND_PTR.store(then_ptr, Ordering::Relaxed);
+
+        compiler_fence(Ordering::SeqCst);
+        r.packetptr.write(|w| unsafe { w.bits(first_ptr) });
+        r.events_disabled.reset();
+        r.events_address.reset();
+        r.events_crcok.reset();
+        r.events_crcerror.reset();
+        r.shorts.write(|w| unsafe { w.bits(shorts) });
+        r.intenset.write(|w| unsafe { w.bits(INT_DISABLED) });
+
+        self.timer.cc(0).write(at);
+        let receiving = first_rx_len.is_some() || then_rx_len.is_some();
+        if receiving {
+            self.timer.cc(1).write(timeout);
+            self.ppi_timeout.enable();
+        }
+        if first_rx_len.is_some() {
+            self.ppi_address.enable();
+            self.ppi_rxen.enable();
+        } else {
+            self.ppi_txen.enable();
+        }
+
+        // The radio would never be enabled if the compare was set too late.
+        let now = self.now();
+        if at.wrapping_sub(now) > i32::MAX as u32 && r.state.read().bits() == 0 {
+            return Err(Error::TooLate);
+        }
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if TURNAROUND_PTR.load(Ordering::Relaxed) == 0 && r.events_disabled.read().bits() != 0 {
+                r.events_disabled.reset();
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        })
+        .await;
+
+        compiler_fence(Ordering::SeqCst);
+        crate::ppi::regs().chenclr.write(|w| unsafe { w.bits(chen) });
+        r.shorts.reset();
+        on_drop.defuse();
+
+        if !receiving {
+            return Ok(RxInfo { timestamp: 0, rssi: 0 });
+        }
+        let timestamp = self.timer.cc(1).read();
+        if r.events_crcok.read().bits() == 0 && r.events_crcerror.read().bits() == 0 {
+            return Err(Error::Timeout);
+        }
+        if r.events_crcok.read().bits() == 0 {
+            return Err(Error::Crc);
+        }
+
+        Ok(RxInfo {
+            timestamp,
+            rssi: -(r.rssisample.read().bits() as i8),
+        })
+    }
+}
+
+impl<'d, T: timer::Instance> Drop for BleRadio<'d, T> {
+    fn drop(&mut self) {
+        self.timer.stop();
+        regs().power.write(|w| unsafe { w.bits(0) });
+    }
+}
+
+/// Length of a PDU, checking its length byte.
+fn check_pdu(pdu: &[u8]) -> Result<usize, Error> {
+    if pdu.len() < HEADER_LEN || pdu.len() < HEADER_LEN + pdu[1] as usize {
+        return Err(Error::BufferTooShort);
+    }
+    Ok(HEADER_LEN + pdu[1] as usize)
+}
+
+fn tx_ptr(pdu: &[u8]) -> Result<u32, Error> {
+    check_pdu(pdu)?;
+    slice_in_ram_or(pdu, Error::BufferNotInRAM)?;
+    Ok(pdu.as_ptr() as u32)
+}
+
+fn rx_ptr(buf: &mut [u8]) -> Result<u32, Error> {
+    if buf.len() < HEADER_LEN {
+        return Err(Error::BufferTooShort);
+    }
+    Ok(buf.as_mut_ptr() as u32)
+}
+
+/// Time to send a PDU of `len` bytes with its preamble, access address and CRC.
+fn air_time_us(len: usize) -> u32 {
+    (1 + 4 + len as u32 + 3) * 8
+}
+
+fn regs() -> &'static pac::radio::RegisterBlock {
+    unsafe { &*pac::RADIO::ptr() }
+}
@@ -0,0 +1,571 @@
+//! Enhanced ShockBurst (ESB) radio driver.
+//!
+//! ESB is a Nordic proprietary protocol, compatible with the nRF24L01+ when its dynamic payload
+//! length and acknowledgement payloads are enabled. The driver can act as:
+//!
+//! - primary transmitter (PTX), with [`Esb::send`]: it sends a packet to a pipe and, unless the
+//!   packet doesn't request it, waits for the acknowledgement, retransmitting the packet when
+//!   none is received,
+//! - primary receiver (PRX), with [`Esb::receive`]: it receives packets on the enabled pipes,
+//!   and answers those requesting it with an acknowledgement carrying a payload.
+//!
+//! The address of pipe `n` is the base address followed by prefix `n`, pipe 0 using base address
+//! 0 and pipes 1 to 7 sharing base address 1. On the nRF24L01+, the prefix is the least
+//! significant byte of the address of the pipe.
+
+use core::cmp::max;
+use core::future::poll_fn;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{compiler_fence, AtomicU32, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::InterruptExt;
+use crate::peripherals::RADIO;
+use crate::ppi::{AnyConfigurableChannel, ConfigurableChannel, Ppi, Task};
+use crate::timer::{self, Timer};
+use crate::{interrupt, pac, Peripheral};
+
+/// Maximum length of a payload.
+pub const MAX_PAYLOAD_LEN: usize = 32;
+
+/// Time between enabling the radio and the start of the preamble.
+const RAMP_UP_US: u32 = 140;
+
+/// Time to wait for an acknowledgement after its expected end, and for the radio to be disabled.
+const MARGIN_US: u32 = 50;
+
+/// Length of the length and S1 bytes preceding the payload in RAM.
+const HEADER_LEN: usize = 2;
+
+// S1 bits, following the 6 bits length in the packet control field.
+const S1_ACK: u8 = 1 << 0;
+const S1_PID_SHIFT: u8 = 1;
+
+// SHORTS bits
+const SHORT_READY_START: u32 = 1 << 0;
+const SHORT_END_DISABLE: u32 = 1 << 1;
+const SHORT_DISABLED_TXEN: u32 = 1 << 2;
+const SHORT_DISABLED_RXEN: u32 = 1 << 3;
+const SHORT_ADDRESS_RSSISTART: u32 = 1 << 4;
+const SHORT_DISABLED_RSSISTOP: u32 = 1 << 8;
+
+const TX_SHORTS: u32 = SHORT_READY_START | SHORT_END_DISABLE;
+const RX_SHORTS: u32 = SHORT_READY_START | SHORT_END_DISABLE | SHORT_ADDRESS_RSSISTART | SHORT_DISABLED_RSSISTOP;
+
+// INTEN bits
+const INT_DISABLED: u32 = 1 << 4;
+
+/// ESB error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The packet was not acknowledged, even after retransmitting it.
+    NoAck,
+    /// The received packet has an invalid CRC.
+    Crc,
+}
+
+/// On-air bitrate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bitrate {
+    /// 1 Mbps.
+    Mbps1,
+    /// 2 Mbps.
+    Mbps2,
+}
+
+/// ESB configuration.
+#[non_exhaustive]
+pub struct Config {
+    /// Bitrate.
+    pub bitrate: Bitrate,
+    /// Channel, from 0 to 100, the frequency being 2400 MHz + `channel`.
+    pub channel: u8,
+    /// Transmit power, in dBm. Must be one of the values supported by the chip.
+    pub tx_power: i8,
+    /// Length of the addresses, prefix included, from 3 to 5 bytes.
+    pub address_len: u8,
+    /// Base address of pipe 0.
+    pub base_address_0: [u8; 4],
+    /// Base address of pipes 1 to 7.
+    pub base_address_1: [u8; 4],
+    /// Address prefixes of pipes 0 to 7.
+    pub prefixes: [u8; 8],
+    /// Pipes on which packets are received, bit `n` enabling pipe `n`.
+    pub rx_pipes: u8,
+    /// Number of times a packet is retransmitted when it is not acknowledged.
+    pub retransmit_count: u8,
+    /// Time between the starts of two transmissions of a packet, in microseconds.
+    ///
+    /// It's extended when too short to receive an acknowledgement with the maximum payload length.
+    pub retransmit_delay_us: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // The defaults of the nRF24L01+.
+        Self {
+            bitrate: Bitrate::Mbps2,
+            channel: 2,
+            tx_power: 0,
+            address_len: 5,
+            base_address_0: [0xE7; 4],
+            base_address_1: [0xC2; 4],
+            prefixes: [0xE7, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, 0xC7, 0xC8],
+            rx_pipes: 0b11,
+            retransmit_count: 3,
+            retransmit_delay_us: 250,
+        }
+    }
+}
+
+/// An ESB packet, sent or received by the radio.
+pub struct Packet {
+    // Length and S1 bytes, followed by the payload.
+    buffer: [u8; HEADER_LEN + MAX_PAYLOAD_LEN],
+    pipe: u8,
+    no_ack: bool,
+    rssi: i8,
+}
+
+impl Packet {
+    /// Create an empty packet, for pipe 0 and requesting an acknowledgement.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; HEADER_LEN + MAX_PAYLOAD_LEN],
+            pipe: 0,
+            no_ack: false,
+            rssi: 0,
+        }
+    }
+
+    /// Set the payload.
+    ///
+    /// Panics if longer than `MAX_PAYLOAD_LEN` bytes.
+    pub fn copy_from_slice(&mut self, payload: &[u8]) {
+        assert!(payload.len() <= MAX_PAYLOAD_LEN);
+        self.buffer[HEADER_LEN..][..payload.len()].copy_from_slice(payload);
+        self.buffer[0] = payload.len() as u8;
+    }
+
+    /// Length of the payload.
+    pub fn len(&self) -> usize {
+        (self.buffer[0] as usize).min(MAX_PAYLOAD_LEN)
+    }
+
+    /// Whether the payload is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pipe the packet is sent to, or was received on.
+    pub fn pipe(&self) -> u8 {
+        self.pipe
+    }
+
+    /// Set the pipe the packet is sent to, from 0 to 7.
+    pub fn set_pipe(&mut self, pipe: u8) {
+        assert!(pipe < 8);
+        self.pipe = pipe;
+    }
+
+    /// Whether the packet doesn't request an acknowledgement.
+    pub fn no_ack(&self) -> bool {
+        self.no_ack
+    }
+
+    /// Set whether the packet doesn't request an acknowledgement.
+    pub fn set_no_ack(&mut self, no_ack: bool) {
+        self.no_ack = no_ack;
+    }
+
+    /// Received signal strength of a received packet, in dBm.
+    pub fn rssi(&self) -> i8 {
+        self.rssi
+    }
+}
+
+impl Default for Packet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Packet {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[HEADER_LEN..][..self.len()]
+    }
+}
+
+impl DerefMut for Packet {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len();
+        &mut self.buffer[HEADER_LEN..][..len]
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RadioTask {
+    Txen,
+    Rxen,
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RADIO> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+        if r.events_disabled.read().bits() == 0 {
+            return;
+        }
+
+        // The radio is ramping up for the acknowledgement of the packet.
+        let ptr = TURNAROUND_PTR.swap(0, Ordering::Relaxed);
+        if ptr != 0 {
+            r.events_disabled.reset();
+
+            // When receiving, only packets received intact and requesting it are acknowledged,
+            // with their PID.
+            let rx_ptr = TURNAROUND_RX_PTR.load(Ordering::Relaxed) as *const u8;
+            let answer = if rx_ptr.is_null() {
+                true
+            } else {
+                let s1 = *rx_ptr.add(1);
+                let answer = r.events_crcok.read().bits() != 0 && s1 & S1_ACK != 0;
+                if answer {
+                    *(ptr as *mut u8).add(1) = s1 & !S1_ACK;
+                }
+                answer
+            };
+
+            if answer {
+                r.packetptr.write(|w| unsafe { w.bits(ptr) });
+                r.shorts
+                    .write(|w| unsafe { w.bits(TURNAROUND_SHORTS.load(Ordering::Relaxed)) });
+            } else {
+                r.shorts.reset();
+                r.tasks_disable.write(|w| unsafe { w.bits(1) });
+            }
+            return;
+        }
+
+        r.intenclr.write(|w| unsafe { w.bits(INT_DISABLED) });
+        WAKER.wake();
+    }
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+// Packet pointer and shorts of the acknowledgement, applied when the radio is disabled after the
+// first packet of an operation, or 0 when there's no acknowledgement.
+static TURNAROUND_PTR: AtomicU32 = AtomicU32::new(0);
+static TURNAROUND_SHORTS: AtomicU32 = AtomicU32::new(0);
+// Packet pointer of the received packet, deciding whether it's acknowledged, or 0 when sending.
+static TURNAROUND_RX_PTR: AtomicU32 = AtomicU32::new(0);
+
+/// ESB radio driver.
+pub struct Esb<'d, T: timer::Instance> {
+    _p: PeripheralRef<'d, RADIO>,
+    timer: Timer<'d, T>,
+    ppi_txen: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    ppi_timeout: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    chen: u32,
+    config: Config,
+    // Length and S1 bytes, followed by the payload, of the sent packet or acknowledgement.
+    tx: [u8; HEADER_LEN + MAX_PAYLOAD_LEN],
+    // PID of the last sent packet.
+    pid: u8,
+    // PID and CRC of the last packet received on each pipe.
+    last_rx: [Option<(u8, u32)>; 8],
+}
+
+impl<'d, T: timer::Instance> Esb<'d, T> {
+    /// Create a new ESB radio driver.
+    ///
+    /// The timer and the PPI channels are used to time retransmits and acknowledgement timeouts.
+    pub fn new(
+        radio: impl Peripheral<P = RADIO> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RADIO, InterruptHandler> + 'd,
+        timer: impl Peripheral<P = T> + 'd,
+        ppi_ch1: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+        ppi_ch2: impl Peripheral<P = impl ConfigurableChannel> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(radio, ppi_ch1, ppi_ch2);
+        let chen = 1 << ppi_ch1.number() | 1 << ppi_ch2.number();
+
+        let r = regs();
+        r.power.write(|w| unsafe { w.bits(1) });
+
+        // 6 bits length field and 3 bits S1 for the PID and the no acknowledgement flag.
+        r.pcnf0.write(|w| unsafe { w.bits(6 | 3 << 16) });
+        // 16 bits CRC, including the address.
+        r.crccnf.write(|w| unsafe { w.bits(2) });
+        r.crcinit.write(|w| unsafe { w.bits(0xFFFF) });
+        r.crcpoly.write(|w| unsafe { w.bits(0x11021) });
+
+        let timer = Timer::new(timer);
+        let ppi_txen = Ppi::new_one_to_one(
+            ppi_ch1.map_into(),
+            timer.cc(0).event_compare(),
+            Task::from_reg(&r.tasks_txen),
+        );
+        let ppi_timeout = Ppi::new_one_to_one(
+            ppi_ch2.map_into(),
+            timer.cc(1).event_compare(),
+            Task::from_reg(&r.tasks_disable),
+        );
+        timer.start();
+
+        interrupt::RADIO.unpend();
+        unsafe { interrupt::RADIO.enable() };
+
+        let mut this = Self {
+            _p: radio,
+            timer,
+            ppi_txen,
+            ppi_timeout,
+            chen,
+            config,
+            tx: [0; HEADER_LEN + MAX_PAYLOAD_LEN],
+            pid: 0,
+            last_rx: [None; 8],
+        };
+        this.apply_config();
+        this
+    }
+
+    /// Change the configuration.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.apply_config();
+    }
+
+    fn apply_config(&mut self) {
+        let r = regs();
+
+        assert!(self.config.channel <= 100);
+        assert!((3..=5).contains(&self.config.address_len));
+
+        let mode = match self.config.bitrate {
+            Bitrate::Mbps1 => 0,
+            Bitrate::Mbps2 => 1,
+        };
+        r.mode.write(|w| unsafe { w.bits(mode) });
+        r.frequency.write(|w| unsafe { w.bits(self.config.channel as u32) });
+        r.txpower
+            .write(|w| unsafe { w.bits(self.config.tx_power as u8 as u32) });
+
+        // Big endian, as the nRF24L01+ sends the most significant bit first.
+        let balen = self.config.address_len as u32 - 1;
+        r.pcnf1
+            .write(|w| unsafe { w.bits(MAX_PAYLOAD_LEN as u32 | balen << 16 | 1 << 24) });
+
+        // The radio sends the least significant bit of each address byte first.
+        let base = |b: [u8; 4]| u32::from_be_bytes(b.map(u8::reverse_bits));
+        let prefix = |p: &[u8]| u32::from_le_bytes([p[0], p[1], p[2], p[3]].map(u8::reverse_bits));
+        r.base0.write(|w| unsafe { w.bits(base(self.config.base_address_0)) });
+        r.base1.write(|w| unsafe { w.bits(base(self.config.base_address_1)) });
+        r.prefix0
+            .write(|w| unsafe { w.bits(prefix(&self.config.prefixes[..4])) });
+        r.prefix1
+            .write(|w| unsafe { w.bits(prefix(&self.config.prefixes[4..])) });
+        r.rxaddresses.write(|w| unsafe { w.bits(self.config.rx_pipes as u32) });
+    }
+
+    /// Send a packet to its pipe, as primary transmitter.
+    ///
+    /// Unless the packet doesn't request an acknowledgement, this waits for it, retransmitting
+    /// the packet up to [`Config::retransmit_count`] times, and copies its payload to `ack`.
+    pub async fn send(&mut self, packet: &Packet, ack: &mut Packet) -> Result<(), Error> {
+        let r = regs();
+
+        let len = packet.len();
+        self.tx[..HEADER_LEN + len].copy_from_slice(&packet.buffer[..HEADER_LEN + len]);
+        self.pid = (self.pid + 1) % 4;
+        self.tx[1] = self.pid << S1_PID_SHIFT | if packet.no_ack { 0 } else { S1_ACK };
+        r.packetptr.write(|w| unsafe { w.bits(self.tx.as_ptr() as u32) });
+        r.txaddress.write(|w| unsafe { w.bits(packet.pipe as u32) });
+
+        if packet.no_ack {
+            TURNAROUND_PTR.store(0, Ordering::Relaxed);
+            self.run(RadioTask::Txen, None, TX_SHORTS, None).await;
+            return Ok(());
+        }
+
+        // Time from enabling the radio to the end of the longest acknowledgement.
+        let duration = RAMP_UP_US + self.air_time_us(len) + RAMP_UP_US + self.air_time_us(MAX_PAYLOAD_LEN) + MARGIN_US;
+        let mut at = None;
+        for _ in 0..=self.config.retransmit_count {
+            TURNAROUND_PTR.store(ack.buffer.as_mut_ptr() as u32, Ordering::Relaxed);
+            TURNAROUND_SHORTS.store(RX_SHORTS, Ordering::Relaxed);
+            TURNAROUND_RX_PTR.store(0, Ordering::Relaxed);
+            r.packetptr.write(|w| unsafe { w.bits(self.tx.as_ptr() as u32) });
+
+            let start = self
+                .run(RadioTask::Txen, at, TX_SHORTS | SHORT_DISABLED_RXEN, Some(duration))
+                .await;
+
+            if r.events_crcok.read().bits() != 0 {
+                ack.pipe = packet.pipe;
+                ack.no_ack = true;
+                ack.rssi = -(r.rssisample.read().bits() as i8);
+                return Ok(());
+            }
+
+            let delay = max(self.config.retransmit_delay_us, duration + MARGIN_US);
+            at = Some(start.wrapping_add(delay));
+        }
+
+        Err(Error::NoAck)
+    }
+
+    /// Receive a packet on one of the enabled pipes, as primary receiver.
+    ///
+    /// If the packet requests it, it's acknowledged with `ack` as payload, whatever the pipe it
+    /// was received on. Retransmits of a packet are acknowledged again, but only returned once.
+    /// The acknowledgement is sent as soon as the packet is received, so the task of the
+    /// receiver should not be delayed by other tasks.
+    pub async fn receive(&mut self, packet: &mut Packet, ack: &Packet) -> Result<(), Error> {
+        let r = regs();
+
+        let len = ack.len();
+        self.tx[0] = len as u8;
+        self.tx[HEADER_LEN..][..len].copy_from_slice(ack);
+
+        loop {
+            TURNAROUND_PTR.store(self.tx.as_mut_ptr() as u32, Ordering::Relaxed);
+            TURNAROUND_SHORTS.store(TX_SHORTS, Ordering::Relaxed);
+            TURNAROUND_RX_PTR.store(packet.buffer.as_mut_ptr() as u32, Ordering::Relaxed);
+            r.packetptr
+                .write(|w| unsafe { w.bits(packet.buffer.as_mut_ptr() as u32) });
+
+            self.run(RadioTask::Rxen, None, RX_SHORTS | SHORT_DISABLED_TXEN, None)
+                .await;
+
+            if r.events_crcok.read().bits() == 0 {
+                return Err(Error::Crc);
+            }
+
+            let pipe = r.rxmatch.read().bits() as u8;
+            let pid = packet.buffer[1] >> S1_PID_SHIFT;
+            let crc = r.rxcrc.read().bits();
+            if self.last_rx[pipe as usize] == Some((pid, crc)) {
+                continue;
+            }
+            self.last_rx[pipe as usize] = Some((pid, crc));
+
+            packet.pipe = pipe;
+            packet.no_ack = packet.buffer[1] & S1_ACK == 0;
+            packet.rssi = -(r.rssisample.read().bits() as i8);
+            return Ok(());
+        }
+    }
+
+    /// Enable the radio, at `at` or right away, with the shorts performing the first packet of
+    /// an operation, and wait for it to be disabled once the operation completes. When
+    /// `timeout_us` is set, the radio is disabled that long after being enabled.
+    ///
+    /// Returns the time at which the radio was enabled.
+    async fn run(&mut self, task: RadioTask, at: Option<u32>, shorts: u32, timeout_us: Option<u32>) -> u32 {
+        let r = regs();
+
+        // The radio and the PPI channels must be disabled whatever happens.
+        let chen = self.chen;
+        let on_drop = OnDrop::new(|| {
+            TURNAROUND_PTR.store(0, Ordering::Relaxed);
+            crate::ppi::regs().chenclr.write(|w| unsafe { w.bits(chen) });
+            r.intenclr.write(|w| unsafe { w.bits(INT_DISABLED) });
+            r.shorts.reset();
+            r.tasks_disable.write(|w| unsafe { w.bits(1) });
+            while r.state.read().bits() != 0 {}
+            r.events_disabled.reset();
+        });
+
+        compiler_fence(Ordering::SeqCst);
+        r.events_disabled.reset();
+        r.events_crcok.reset();
+        r.shorts.write(|w| unsafe { w.bits(shorts) });
+        r.intenset.write(|w| unsafe { w.bits(INT_DISABLED) });
+
+        let start = match at {
+            Some(at) => {
+                self.timer.cc(0).write(at);
+                self.ppi_txen.enable();
+                // The radio would never be enabled if the compare was set too late.
+                let now = self.now();
+                if at.wrapping_sub(now) > i32::MAX as u32 && r.state.read().bits() == 0 {
+                    self.ppi_txen.disable();
+                    r.tasks_txen.write(|w| unsafe { w.bits(1) });
+                    now
+                } else {
+                    at
+                }
+            }
+            None => {
+                match task {
+                    RadioTask::Txen => r.tasks_txen.write(|w| unsafe { w.bits(1) }),
+                    RadioTask::Rxen => r.tasks_rxen.write(|w| unsafe { w.bits(1) }),
+                }
+                self.now()
+            }
+        };
+        if let Some(timeout_us) = timeout_us {
+            self.timer.cc(1).write(start.wrapping_add(timeout_us));
+            self.ppi_timeout.enable();
+        }
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if TURNAROUND_PTR.load(Ordering::Relaxed) == 0 && r.events_disabled.read().bits() != 0 {
+                r.events_disabled.reset();
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        })
+        .await;
+
+        compiler_fence(Ordering::SeqCst);
+        crate::ppi::regs().chenclr.write(|w| unsafe { w.bits(chen) });
+        r.shorts.reset();
+        on_drop.defuse();
+
+        start
+    }
+
+    /// Current time of the timer, in microseconds.
+    fn now(&self) -> u32 {
+        self.timer.cc(2).capture()
+    }
+
+    /// Time to send a packet with a payload of `len` bytes.
+    fn air_time_us(&self, len: usize) -> u32 {
+        // Preamble, address, packet control field rounded up to 2 bytes, payload and CRC.
+        let bits = (1 + self.config.address_len as u32 + 2 + len as u32 + 2) * 8;
+        match self.config.bitrate {
+            Bitrate::Mbps1 => bits,
+            Bitrate::Mbps2 => bits / 2,
+        }
+    }
+}
+
+impl<'d, T: timer::Instance> Drop for Esb<'d, T> {
+    fn drop(&mut self) {
+        self.timer.stop();
+        regs().power.write(|w| unsafe { w.bits(0) });
+    }
+}
+
+fn regs() -> &'static pac::radio::RegisterBlock {
+    unsafe { &*pac::RADIO::ptr() }
+}
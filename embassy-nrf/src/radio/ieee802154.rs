@@ -0,0 +1,431 @@
+//! IEEE 802.15.4 radio driver.
+//!
+//! The driver sends and receives MAC frames on the 2.4 GHz O-QPSK PHY, the radio appending and
+//! checking the frame check sequence (FCS). It leaves addressing, security and the upper layers,
+//! such as 6LoWPAN or Thread, to the user, with two exceptions:
+//!
+//! - Frames requesting an acknowledgement and addressed to this device are acknowledged by
+//!   [`Radio::receive`] when [`Config::auto_ack`] is enabled. Acknowledgements of sent frames are
+//!   received as any other frame.
+//! - [`Radio::send`] can perform a clear channel assessment (CCA) first.
+
+use core::future::poll_fn;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+#[cfg(feature = "time")]
+use embassy_time::Instant;
+
+use crate::interrupt::InterruptExt;
+use crate::peripherals::RADIO;
+use crate::{interrupt, pac, Peripheral};
+
+/// Maximum length of a PSDU, including the 2 bytes FCS.
+pub const MAX_PACKET_SIZE: usize = 127;
+
+/// Length of the FCS.
+const FCS_LEN: usize = 2;
+
+// MAC frame control bits
+const FRAME_TYPE_ACK: u16 = 0b010;
+const FRAME_TYPE_MASK: u16 = 0b111;
+const ACK_REQUEST: u16 = 1 << 5;
+const DST_ADDR_MODE_SHIFT: u16 = 10;
+const ADDR_MODE_SHORT: u16 = 0b10;
+const ADDR_MODE_EXTENDED: u16 = 0b11;
+const FRAME_VERSION_SHIFT: u16 = 12;
+
+// SHORTS bits
+const SHORT_RXREADY_CCASTART: u32 = 1 << 11;
+const SHORT_CCAIDLE_TXEN: u32 = 1 << 12;
+const SHORT_CCABUSY_DISABLE: u32 = 1 << 13;
+const SHORT_TXREADY_START: u32 = 1 << 18;
+const SHORT_RXREADY_START: u32 = 1 << 19;
+const SHORT_PHYEND_DISABLE: u32 = 1 << 20;
+
+// INTEN bits
+const INT_DISABLED: u32 = 1 << 4;
+#[cfg(feature = "time")]
+const INT_FRAMESTART: u32 = 1 << 14;
+
+/// Radio error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The clear channel assessment found the channel busy, the frame was not sent.
+    ChannelBusy,
+    /// The received frame has an invalid FCS.
+    Crc,
+}
+
+/// Clear channel assessment mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Cca {
+    /// Send without assessing the channel.
+    Disabled,
+    /// The channel is busy when the energy on it is above the threshold, as a value of the
+    /// energy detection level.
+    EnergyDetection {
+        /// Energy detection threshold.
+        ed_threshold: u8,
+    },
+    /// The channel is busy when an 802.15.4 signal is detected.
+    CarrierSense,
+}
+
+/// Radio configuration.
+#[non_exhaustive]
+pub struct Config {
+    /// Channel, from 11 to 26.
+    pub channel: u8,
+    /// Transmit power, in dBm. Must be one of +8 to +2, 0, -4, -8, -12, -16, -20 or -40 dBm, the
+    /// values above +4 dBm being only supported by the nRF52840.
+    pub tx_power: i8,
+    /// Clear channel assessment performed before sending.
+    pub cca: Cca,
+    /// PAN identifier of this device.
+    pub pan_id: u16,
+    /// Short address of this device.
+    pub short_address: u16,
+    /// Extended address of this device.
+    pub extended_address: u64,
+    /// Acknowledge received frames requesting it and addressed to this device.
+    pub auto_ack: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            channel: 11,
+            tx_power: 0,
+            cca: Cca::CarrierSense,
+            pan_id: 0xFFFF,
+            short_address: 0xFFFF,
+            extended_address: 0,
+            auto_ack: true,
+        }
+    }
+}
+
+/// A MAC frame, sent or received by the radio.
+pub struct Packet {
+    // PHR, containing the PSDU length, followed by the PSDU.
+    buffer: [u8; MAX_PACKET_SIZE + 1],
+    lqi: u8,
+    #[cfg(feature = "time")]
+    timestamp: Instant,
+}
+
+impl Packet {
+    /// Create an empty packet.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; MAX_PACKET_SIZE + 1],
+            lqi: 0,
+            #[cfg(feature = "time")]
+            timestamp: Instant::from_ticks(0),
+        }
+    }
+
+    /// Set the MAC frame to send, without the FCS which is appended by the radio.
+    ///
+    /// Panics if longer than `MAX_PACKET_SIZE - 2` bytes.
+    pub fn copy_from_slice(&mut self, frame: &[u8]) {
+        assert!(frame.len() <= MAX_PACKET_SIZE - FCS_LEN);
+        self.buffer[1..][..frame.len()].copy_from_slice(frame);
+        self.buffer[0] = (frame.len() + FCS_LEN) as u8;
+    }
+
+    /// Length of the MAC frame, without the FCS.
+    pub fn len(&self) -> usize {
+        (self.buffer[0] as usize).saturating_sub(FCS_LEN)
+    }
+
+    /// Whether the MAC frame is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Link quality indicator of a received frame.
+    pub fn lqi(&self) -> u8 {
+        self.lqi
+    }
+
+    /// Time at which the start of a received frame, right after its SFD, was detected.
+    #[cfg(feature = "time")]
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    fn frame_control(&self) -> Option<u16> {
+        (self.len() >= 3).then(|| u16::from_le_bytes([self.buffer[1], self.buffer[2]]))
+    }
+
+    /// Whether the frame requests an acknowledgement from this device.
+    fn needs_ack(&self, config: &Config) -> bool {
+        let fc = match self.frame_control() {
+            Some(fc) => fc,
+            None => return false,
+        };
+        // Frames of the 2015 version are acknowledged by enhanced acknowledgements, left to the user.
+        if fc & FRAME_TYPE_MASK == FRAME_TYPE_ACK || fc & ACK_REQUEST == 0 || (fc >> FRAME_VERSION_SHIFT) & 0b11 > 1 {
+            return false;
+        }
+
+        let frame = &self[..];
+        let pan_id = match frame.get(3..5) {
+            Some(pan_id) => u16::from_le_bytes([pan_id[0], pan_id[1]]),
+            None => return false,
+        };
+        if pan_id != config.pan_id && pan_id != 0xFFFF {
+            return false;
+        }
+        match (fc >> DST_ADDR_MODE_SHIFT) & 0b11 {
+            ADDR_MODE_SHORT => frame.get(5..7) == Some(&config.short_address.to_le_bytes()[..]),
+            ADDR_MODE_EXTENDED => frame.get(5..13) == Some(&config.extended_address.to_le_bytes()[..]),
+            _ => false,
+        }
+    }
+}
+
+impl Default for Packet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Packet {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[1..][..self.len()]
+    }
+}
+
+impl DerefMut for Packet {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len();
+        &mut self.buffer[1..][..len]
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Task {
+    Txen,
+    Rxen,
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RADIO> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = regs();
+        #[cfg(feature = "time")]
+        if r.events_framestart.read().bits() != 0 {
+            r.events_framestart.reset();
+            let now = Instant::now();
+            critical_section::with(|cs| TIMESTAMP.borrow(cs).set(now));
+        }
+        if r.events_disabled.read().bits() != 0 {
+            r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+            WAKER.wake();
+        }
+    }
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+#[cfg(feature = "time")]
+static TIMESTAMP: critical_section::Mutex<core::cell::Cell<Instant>> =
+    critical_section::Mutex::new(core::cell::Cell::new(Instant::from_ticks(0)));
+
+/// IEEE 802.15.4 radio driver.
+pub struct Radio<'d> {
+    _p: PeripheralRef<'d, RADIO>,
+    config: Config,
+    // PHR and PSDU of the acknowledgements.
+    ack: [u8; 6],
+}
+
+impl<'d> Radio<'d> {
+    /// Create a new IEEE 802.15.4 radio driver.
+    pub fn new(
+        radio: impl Peripheral<P = RADIO> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RADIO, InterruptHandler> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(radio);
+
+        let r = regs();
+        r.power.write(|w| unsafe { w.bits(1) });
+
+        // Ieee802154_250Kbit mode, with fast ramp-up.
+        r.mode.write(|w| unsafe { w.bits(15) });
+        r.modecnf0.modify(|r, w| unsafe { w.bits(r.bits() | 1) });
+        // 8 bits PHR including the FCS, and 32 bits zero preamble.
+        r.pcnf0.write(|w| unsafe { w.bits(8 | 2 << 24 | 1 << 26) });
+        r.pcnf1.write(|w| unsafe { w.bits(MAX_PACKET_SIZE as u32) });
+        // 16 bits CRC-CCITT, not skipping any byte of the PSDU.
+        r.crccnf.write(|w| unsafe { w.bits(2 | 2 << 8) });
+        r.crcpoly.write(|w| unsafe { w.bits(0x11021) });
+        r.crcinit.write(|w| unsafe { w.bits(0) });
+
+        interrupt::RADIO.unpend();
+        unsafe { interrupt::RADIO.enable() };
+
+        let mut this = Self {
+            _p: radio,
+            config,
+            ack: [(3 + FCS_LEN) as u8, FRAME_TYPE_ACK as u8, 0, 0, 0, 0],
+        };
+        this.apply_config();
+        this
+    }
+
+    /// Change the configuration.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.apply_config();
+    }
+
+    fn apply_config(&mut self) {
+        let r = regs();
+
+        assert!((11..=26).contains(&self.config.channel));
+        // Channels are 5 MHz apart, from 2405 MHz.
+        r.frequency
+            .write(|w| unsafe { w.bits(5 + 5 * (self.config.channel as u32 - 11)) });
+        r.txpower
+            .write(|w| unsafe { w.bits(self.config.tx_power as u8 as u32) });
+
+        let (mode, ed_threshold) = match self.config.cca {
+            Cca::Disabled | Cca::CarrierSense => (1, 0),
+            Cca::EnergyDetection { ed_threshold } => (0, ed_threshold),
+        };
+        r.ccactrl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !0xFF07 | mode | (ed_threshold as u32) << 8) });
+    }
+
+    /// Send a frame, after a clear channel assessment when configured.
+    pub async fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        let r = regs();
+
+        r.packetptr.write(|w| unsafe { w.bits(packet.buffer.as_ptr() as u32) });
+        r.events_ccabusy.reset();
+        match self.config.cca {
+            Cca::Disabled => {
+                self.run(Task::Txen, SHORT_TXREADY_START | SHORT_PHYEND_DISABLE, 0)
+                    .await
+            }
+            _ => {
+                let shorts = SHORT_RXREADY_CCASTART
+                    | SHORT_CCAIDLE_TXEN
+                    | SHORT_CCABUSY_DISABLE
+                    | SHORT_TXREADY_START
+                    | SHORT_PHYEND_DISABLE;
+                self.run(Task::Rxen, shorts, 0).await
+            }
+        }
+
+        if r.events_ccabusy.read().bits() != 0 {
+            r.events_ccabusy.reset();
+            return Err(Error::ChannelBusy);
+        }
+        Ok(())
+    }
+
+    /// Receive a frame, acknowledging it when configured.
+    ///
+    /// Frames are not filtered by address. The acknowledgement is sent as soon as the frame is
+    /// received, so the task of the receiver should not be delayed by other tasks.
+    pub async fn receive(&mut self, packet: &mut Packet) -> Result<(), Error> {
+        let r = regs();
+
+        r.packetptr
+            .write(|w| unsafe { w.bits(packet.buffer.as_mut_ptr() as u32) });
+        r.events_crcok.reset();
+        #[cfg(feature = "time")]
+        let interrupts = INT_FRAMESTART;
+        #[cfg(not(feature = "time"))]
+        let interrupts = 0;
+        self.run(Task::Rxen, SHORT_RXREADY_START | SHORT_PHYEND_DISABLE, interrupts)
+            .await;
+
+        if r.events_crcok.read().bits() == 0 {
+            return Err(Error::Crc);
+        }
+        r.events_crcok.reset();
+
+        // The last byte of the FCS is replaced by the LQI in RAM.
+        packet.lqi = packet.buffer[packet.buffer[0] as usize];
+        #[cfg(feature = "time")]
+        {
+            packet.timestamp = critical_section::with(|cs| TIMESTAMP.borrow(cs).get());
+        }
+
+        if self.config.auto_ack && packet.needs_ack(&self.config) {
+            self.ack[3] = packet.buffer[3];
+            r.packetptr.write(|w| unsafe { w.bits(self.ack.as_ptr() as u32) });
+            self.run(Task::Txen, SHORT_TXREADY_START | SHORT_PHYEND_DISABLE, 0)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Enable the radio with the shorts performing an operation, and wait for them to disable it
+    /// once the operation completes.
+    async fn run(&mut self, task: Task, shorts: u32, interrupts: u32) {
+        let r = regs();
+
+        // Disable the radio if the future is dropped.
+        let on_drop = OnDrop::new(|| {
+            r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+            r.shorts.reset();
+            r.tasks_disable.write(|w| unsafe { w.bits(1) });
+            while r.state.read().bits() != 0 {}
+            r.events_disabled.reset();
+        });
+
+        compiler_fence(Ordering::SeqCst);
+        r.events_disabled.reset();
+        r.shorts.write(|w| unsafe { w.bits(shorts) });
+        r.intenset.write(|w| unsafe { w.bits(INT_DISABLED | interrupts) });
+        match task {
+            Task::Txen => r.tasks_txen.write(|w| unsafe { w.bits(1) }),
+            Task::Rxen => r.tasks_rxen.write(|w| unsafe { w.bits(1) }),
+        }
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.events_disabled.read().bits() != 0 {
+                r.events_disabled.reset();
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        })
+        .await;
+
+        compiler_fence(Ordering::SeqCst);
+        r.shorts.reset();
+        on_drop.defuse();
+    }
+}
+
+impl<'d> Drop for Radio<'d> {
+    fn drop(&mut self) {
+        regs().power.write(|w| unsafe { w.bits(0) });
+    }
+}
+
+fn regs() -> &'static pac::radio::RegisterBlock {
+    unsafe { &*pac::RADIO::ptr() }
+}
@@ -0,0 +1,9 @@
+//! Radio drivers.
+//!
+//! The RADIO peripheral requires the high frequency crystal oscillator: use
+//! [`HfclkSource::ExternalXtal`](crate::config::HfclkSource::ExternalXtal).
+
+pub mod ble;
+pub mod esb;
+#[cfg(any(feature = "nrf52833", feature = "nrf52840"))]
+pub mod ieee802154;
@@ -0,0 +1,252 @@
+//! Real Time Counter (RTC) driver.
+//!
+//! The RTC is a 24-bit counter clocked by the 32.768 kHz low frequency clock, running in all
+//! power modes but System OFF. Its compare channels can wake the CPU, or trigger tasks through
+//! PPI, at precise times and with very low power consumption.
+//!
+//! For "sleep for X seconds" or timeouts, you should use
+//! [`embassy-time`](https://crates.io/crates/embassy-time) instead. With the `time-driver-rtc1`
+//! feature, RTC1 is used by the time driver and isn't available here.
+
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::ppi::{Event, Task};
+use crate::{interrupt, pac, Peripheral};
+
+/// Maximum value of the 24-bit counter.
+pub const COUNTER_MAX: u32 = (1 << 24) - 1;
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        for n in 0..T::CCS {
+            if r.events_compare[n].read().bits() != 0 {
+                r.intenclr.write(|w| unsafe { w.bits(1 << (16 + n)) });
+                s.wakers[n].wake();
+            }
+        }
+    }
+}
+
+/// RTC driver.
+pub struct Rtc<'d, T: Instance> {
+    _p: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Rtc<'d, T> {
+    /// Create a new RTC driver.
+    ///
+    /// The counter ticks at 32768 / (`prescaler` + 1) Hz. It's stopped and cleared, and must be
+    /// started with [`start`](Self::start). The low frequency clock must be running, which
+    /// [`embassy_nrf::init`](crate::init) ensures.
+    pub fn new(
+        rtc: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        prescaler: u16,
+    ) -> Self {
+        into_ref!(rtc);
+
+        let r = T::regs();
+        r.tasks_stop.write(|w| unsafe { w.bits(1) });
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        r.evtenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        r.prescaler.write(|w| unsafe { w.bits(prescaler as u32) });
+        r.tasks_clear.write(|w| unsafe { w.bits(1) });
+        for n in 0..T::CCS {
+            r.cc[n].write(|w| unsafe { w.bits(0) });
+            r.events_compare[n].reset();
+        }
+        r.events_ovrflw.reset();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _p: rtc }
+    }
+
+    /// Starts the counter.
+    pub fn start(&mut self) {
+        T::regs().tasks_start.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Stops the counter.
+    pub fn stop(&mut self) {
+        T::regs().tasks_stop.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Resets the counter to 0.
+    pub fn clear(&mut self) {
+        T::regs().tasks_clear.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Returns the current value of the counter.
+    pub fn counter(&self) -> u32 {
+        T::regs().counter.read().bits()
+    }
+
+    /// Set the compare value of channel `n`.
+    ///
+    /// The COMPARE event of the channel fires when the counter reaches `value`. The value is
+    /// truncated to 24 bits.
+    ///
+    /// # Panics
+    /// Panics if `n` >= the number of compare channels of this RTC.
+    pub fn set_compare(&mut self, n: usize, value: u32) {
+        assert!(n < T::CCS);
+        T::regs().cc[n].write(|w| unsafe { w.bits(value & COUNTER_MAX) });
+    }
+
+    /// Wait for the COMPARE event of channel `n`.
+    ///
+    /// Returns immediately if the event fired since it was last cleared. The event is cleared
+    /// when this returns.
+    ///
+    /// # Panics
+    /// Panics if `n` >= the number of compare channels of this RTC.
+    pub async fn wait_compare(&mut self, n: usize) {
+        assert!(n < T::CCS);
+
+        let r = T::regs();
+        let s = T::state();
+
+        r.intenset.write(|w| unsafe { w.bits(1 << (16 + n)) });
+
+        // In case the future is dropped, disable the interrupt.
+        let on_drop = OnDrop::new(|| {
+            r.intenclr.write(|w| unsafe { w.bits(1 << (16 + n)) });
+        });
+
+        poll_fn(|cx| {
+            s.wakers[n].register(cx.waker());
+
+            if r.events_compare[n].read().bits() != 0 {
+                r.events_compare[n].reset();
+                return Poll::Ready(());
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        drop(on_drop);
+    }
+
+    /// Returns the START task, for use with PPI.
+    pub fn task_start(&self) -> Task {
+        Task::from_reg(&T::regs().tasks_start)
+    }
+
+    /// Returns the STOP task, for use with PPI.
+    pub fn task_stop(&self) -> Task {
+        Task::from_reg(&T::regs().tasks_stop)
+    }
+
+    /// Returns the CLEAR task, for use with PPI.
+    pub fn task_clear(&self) -> Task {
+        Task::from_reg(&T::regs().tasks_clear)
+    }
+
+    /// Returns the COMPARE event of channel `n`, for use with PPI.
+    ///
+    /// This enables the routing of the event to PPI.
+    ///
+    /// # Panics
+    /// Panics if `n` >= the number of compare channels of this RTC.
+    pub fn event_compare(&mut self, n: usize) -> Event {
+        assert!(n < T::CCS);
+        let r = T::regs();
+        r.evtenset.write(|w| unsafe { w.bits(1 << (16 + n)) });
+        Event::from_reg(&r.events_compare[n])
+    }
+
+    /// Returns the TICK event, for use with PPI.
+    ///
+    /// This enables the routing of the event to PPI. The TICK event fires on every increment of
+    /// the counter.
+    pub fn event_tick(&mut self) -> Event {
+        let r = T::regs();
+        r.evtenset.write(|w| w.tick().set());
+        Event::from_reg(&r.events_tick)
+    }
+
+    /// Returns the OVRFLW event, for use with PPI.
+    ///
+    /// This enables the routing of the event to PPI.
+    pub fn event_overflow(&mut self) -> Event {
+        let r = T::regs();
+        r.evtenset.write(|w| w.ovrflw().set());
+        Event::from_reg(&r.events_ovrflw)
+    }
+}
+
+impl<'d, T: Instance> Drop for Rtc<'d, T> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.tasks_stop.write(|w| unsafe { w.bits(1) });
+        r.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        r.evtenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    }
+}
+
+pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    use super::*;
+
+    pub struct State {
+        pub wakers: [AtomicWaker; 4],
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            const NEW_AW: AtomicWaker = AtomicWaker::new();
+            Self { wakers: [NEW_AW; 4] }
+        }
+    }
+
+    pub trait Instance {
+        /// The number of compare channels this instance has.
+        const CCS: usize;
+        fn regs() -> &'static pac::rtc0::RegisterBlock;
+        fn state() -> &'static State;
+    }
+}
+
+/// RTC peripheral instance.
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + 'static + Send {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_rtc {
+    ($type:ident, $pac_type:ident, $irq:ident, $ccs:literal) => {
+        impl crate::rtc::sealed::Instance for peripherals::$type {
+            const CCS: usize = $ccs;
+            fn regs() -> &'static pac::rtc0::RegisterBlock {
+                unsafe { &*(pac::$pac_type::ptr() as *const pac::rtc0::RegisterBlock) }
+            }
+            fn state() -> &'static crate::rtc::sealed::State {
+                static STATE: crate::rtc::sealed::State = crate::rtc::sealed::State::new();
+                &STATE
+            }
+        }
+        impl crate::rtc::Instance for peripherals::$type {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
@@ -6,19 +6,38 @@
 
 #![macro_use]
 
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
 use embassy_hal_common::{into_ref, PeripheralRef};
 
+use crate::interrupt::typelevel::Interrupt;
 use crate::ppi::{Event, Task};
-use crate::{pac, Peripheral};
+use crate::{interrupt, pac, Peripheral};
 
 pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
 
     use super::*;
 
+    pub struct State {
+        pub wakers: [AtomicWaker; 6],
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            const NEW_AW: AtomicWaker = AtomicWaker::new();
+            Self { wakers: [NEW_AW; 6] }
+        }
+    }
+
     pub trait Instance {
         /// The number of CC registers this instance has.
         const CCS: usize;
         fn regs() -> &'static pac::timer0::RegisterBlock;
+        fn state() -> &'static State;
     }
     pub trait ExtendedInstance {}
 
@@ -41,6 +60,10 @@ macro_rules! impl_timer {
             fn regs() -> &'static pac::timer0::RegisterBlock {
                 unsafe { &*(pac::$pac_type::ptr() as *const pac::timer0::RegisterBlock) }
             }
+            fn state() -> &'static crate::timer::sealed::State {
+                static STATE: crate::timer::sealed::State = crate::timer::sealed::State::new();
+                &STATE
+            }
         }
         impl crate::timer::Instance for peripherals::$type {
             type Interrupt = crate::interrupt::typelevel::$irq;
@@ -56,6 +79,39 @@ macro_rules! impl_timer {
     };
 }
 
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        for n in 0..T::CCS {
+            if r.events_compare[n].read().bits() != 0 {
+                r.intenclr.write(|w| unsafe { w.bits(1 << (16 + n)) });
+                s.wakers[n].wake();
+            }
+        }
+    }
+}
+
+/// Timer type, selecting whether compare events can be awaited.
+pub trait TimerType: sealed::TimerType {}
+
+/// Marker type for a timer whose compare events can be awaited.
+pub enum Awaitable {}
+
+/// Marker type for a timer whose compare events can't be awaited.
+pub enum NotAwaitable {}
+
+impl sealed::TimerType for Awaitable {}
+impl sealed::TimerType for NotAwaitable {}
+impl TimerType for Awaitable {}
+impl TimerType for NotAwaitable {}
+
 /// Timer frequency
 #[repr(u8)]
 pub enum Frequency {
@@ -90,8 +146,9 @@ pub enum Frequency {
 /// or trigger an event when the counter reaches a certain value.
 
 /// Timer driver.
-pub struct Timer<'d, T: Instance> {
+pub struct Timer<'d, T: Instance, I: TimerType = NotAwaitable> {
     _p: PeripheralRef<'d, T>,
+    _i: PhantomData<I>,
 }
 
 impl<'d, T: Instance> Timer<'d, T> {
@@ -110,13 +167,35 @@ impl<'d, T: Instance> Timer<'d, T> {
     pub fn new_counter(timer: impl Peripheral<P = T> + 'd) -> Self {
         Self::new_inner(timer, true)
     }
+}
+
+impl<'d, T: Instance> Timer<'d, T, Awaitable> {
+    /// Create a new `Timer` driver whose compare events can be awaited with [`Cc::wait`].
+    pub fn new_awaitable(
+        timer: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        let this = Self::new_inner(timer, false);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this
+    }
+}
 
+impl<'d, T: Instance, I: TimerType> Timer<'d, T, I> {
     fn new_inner(timer: impl Peripheral<P = T> + 'd, is_counter: bool) -> Self {
         into_ref!(timer);
 
         let regs = T::regs();
 
-        let this = Self { _p: timer };
+        let this = Self {
+            _p: timer,
+            _i: PhantomData,
+        };
+
+        regs.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
 
         // Stop the timer before doing anything else,
         // since changing BITMODE while running can cause 'unpredictable behaviour' according to the specification.
@@ -145,6 +224,7 @@ impl<'d, T: Instance> Timer<'d, T> {
             cc.unshort_compare_stop();
             // Initialize the CC registers as 0.
             cc.write(0);
+            cc.clear_event();
         }
 
         this
@@ -212,13 +292,14 @@ impl<'d, T: Instance> Timer<'d, T> {
     ///
     /// # Panics
     /// Panics if `n` >= the number of CC registers this timer has (4 for a normal timer, 6 for an extended timer).
-    pub fn cc(&self, n: usize) -> Cc<'d, T> {
+    pub fn cc(&self, n: usize) -> Cc<'d, T, I> {
         if n >= T::CCS {
             panic!("Cannot get CC register {} of timer with {} CC registers.", n, T::CCS);
         }
         Cc {
             n,
             _p: unsafe { self._p.clone_unchecked() },
+            _i: PhantomData,
         }
     }
 }
@@ -230,12 +311,13 @@ impl<'d, T: Instance> Timer<'d, T> {
 ///
 /// The timer will fire the register's COMPARE event when its counter reaches the value stored in the register.
 /// When the register's CAPTURE task is triggered, the timer will store the current value of its counter in the register
-pub struct Cc<'d, T: Instance> {
+pub struct Cc<'d, T: Instance, I: TimerType = NotAwaitable> {
     n: usize,
     _p: PeripheralRef<'d, T>,
+    _i: PhantomData<I>,
 }
 
-impl<'d, T: Instance> Cc<'d, T> {
+impl<'d, T: Instance, I: TimerType> Cc<'d, T, I> {
     /// Get the current value stored in the register.
     pub fn read(&self) -> u32 {
         T::regs().cc[self.n].read().cc().bits()
@@ -259,7 +341,12 @@ impl<'d, T: Instance> Cc<'d, T> {
     ///
     /// When triggered, this task will capture the current value of the timer's counter in this register.
     pub fn task_capture(&self) -> Task {
-        Task::from_reg(&T::regs().tasks_capture)
+        Task::from_reg(&T::regs().tasks_capture[self.n])
+    }
+
+    /// Clear this CC register's COMPARE event.
+    pub fn clear_event(&self) {
+        T::regs().events_compare[self.n].reset();
     }
 
     /// Returns this CC register's COMPARE event, for use with PPI.
@@ -305,3 +392,36 @@ impl<'d, T: Instance> Cc<'d, T> {
             .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << (8 + self.n))) })
     }
 }
+
+impl<'d, T: Instance> Cc<'d, T, Awaitable> {
+    /// Wait until the timer's counter reaches the value stored in this register.
+    ///
+    /// Returns immediately if the COMPARE event was fired since it was last cleared. The event
+    /// is cleared when this returns.
+    pub async fn wait(&self) {
+        let regs = T::regs();
+        let s = T::state();
+        let n = self.n;
+
+        regs.intenset.write(|w| unsafe { w.bits(1 << (16 + n)) });
+
+        // In case the future is dropped, disable the interrupt.
+        let on_drop = OnDrop::new(|| {
+            regs.intenclr.write(|w| unsafe { w.bits(1 << (16 + n)) });
+        });
+
+        poll_fn(|cx| {
+            s.wakers[n].register(cx.waker());
+
+            if regs.events_compare[n].read().bits() != 0 {
+                regs.events_compare[n].reset();
+                return Poll::Ready(());
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        drop(on_drop);
+    }
+}
@@ -15,7 +15,8 @@ use embassy_sync::waitqueue::AtomicWaker;
 use embassy_time::{Duration, Instant};
 
 use crate::chip::{EASY_DMA_SIZE, FORCE_COPY_BUFFER_SIZE};
-use crate::gpio::Pin as GpioPin;
+use crate::gpio::sealed::Pin as _;
+use crate::gpio::{AnyPin, Pin as GpioPin};
 use crate::interrupt::typelevel::Interrupt;
 use crate::util::{slice_in_ram, slice_in_ram_or};
 use crate::{gpio, interrupt, pac, Peripheral};
@@ -90,6 +91,9 @@ pub enum Error {
     /// Overrun error.
     Overrun,
     /// Timeout error.
+    ///
+    /// The transfer didn't complete in time, most likely because a device is holding SCL or SDA
+    /// low. The bus has been recovered with [`Twim::recover`].
     Timeout,
 }
 
@@ -316,6 +320,7 @@ impl<'d, T: Instance> Twim<'d, T> {
             }
             if Instant::now() > deadline {
                 r.tasks_stop.write(|w| unsafe { w.bits(1) });
+                self.recover();
                 return Err(Error::Timeout);
             }
         }
@@ -346,6 +351,68 @@ impl<'d, T: Instance> Twim<'d, T> {
         })
     }
 
+    /// Wait for stop or error, recovering the bus on timeout.
+    #[cfg(feature = "time")]
+    async fn async_wait_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        if embassy_time::with_timeout(timeout, self.async_wait()).await.is_err() {
+            T::regs().tasks_stop.write(|w| unsafe { w.bits(1) });
+            self.recover();
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Recover the bus from a device holding SDA low, which can happen when the device was in the
+    /// middle of sending a byte when the transfer was aborted or the chip reset.
+    ///
+    /// SCL is toggled until the device releases SDA, at most 9 times, then a STOP condition is
+    /// generated. This is done automatically when a transfer with a timeout times out.
+    pub fn recover(&mut self) {
+        let r = T::regs();
+
+        // The pins are driven as GPIOs while the TWIM is disabled, in open drain as set up by `new`.
+        r.enable.write(|w| w.enable().disabled());
+        let sda = unsafe { AnyPin::steal(r.psel.sda.read().bits() as u8) };
+        let scl = unsafe { AnyPin::steal(r.psel.scl.read().bits() as u8) };
+        let sda_is_high = || sda.block().in_.read().bits() & (1 << sda._pin()) != 0;
+        // Half a period at less than 100 kHz, whatever the clock of the CPU.
+        let half_period = || cortex_m::asm::delay(1_000);
+
+        sda.set_high();
+        scl.set_high();
+        sda.conf().modify(|_, w| w.dir().output());
+        scl.conf().modify(|_, w| w.dir().output());
+        half_period();
+
+        for _ in 0..9 {
+            if sda_is_high() {
+                break;
+            }
+            scl.set_low();
+            half_period();
+            scl.set_high();
+            half_period();
+        }
+
+        // STOP condition: SDA rising while SCL is high.
+        scl.set_low();
+        half_period();
+        sda.set_low();
+        half_period();
+        scl.set_high();
+        half_period();
+        sda.set_high();
+        half_period();
+
+        sda.conf().modify(|_, w| w.dir().input());
+        scl.conf().modify(|_, w| w.dir().input());
+
+        r.events_stopped.reset();
+        r.events_error.reset();
+        self.clear_errorsrc();
+        r.enable.write(|w| w.enable().enabled());
+    }
+
     fn setup_write_from_ram(&mut self, address: u8, buffer: &[u8], inten: bool) -> Result<(), Error> {
         let r = T::regs();
 
@@ -707,6 +774,55 @@ impl<'d, T: Instance> Twim<'d, T> {
         self.check_rx(rd_buffer.len())?;
         Ok(())
     }
+
+    // ===========================================
+
+    /// Read from an I2C slave with timeout.
+    ///
+    /// See [`read`](Twim::read).
+    #[cfg(feature = "time")]
+    pub async fn read_timeout(&mut self, address: u8, buffer: &mut [u8], timeout: Duration) -> Result<(), Error> {
+        self.setup_read(address, buffer, true)?;
+        self.async_wait_timeout(timeout).await?;
+        compiler_fence(SeqCst);
+        self.check_errorsrc()?;
+        self.check_rx(buffer.len())?;
+        Ok(())
+    }
+
+    /// Write to an I2C slave with timeout.
+    ///
+    /// See [`write`](Twim::write).
+    #[cfg(feature = "time")]
+    pub async fn write_timeout(&mut self, address: u8, buffer: &[u8], timeout: Duration) -> Result<(), Error> {
+        self.setup_write(address, buffer, true)?;
+        self.async_wait_timeout(timeout).await?;
+        compiler_fence(SeqCst);
+        self.check_errorsrc()?;
+        self.check_tx(buffer.len())?;
+        Ok(())
+    }
+
+    /// Write data to an I2C slave, then read data from the slave without
+    /// triggering a stop condition between the two, with timeout.
+    ///
+    /// See [`write_read`](Twim::write_read).
+    #[cfg(feature = "time")]
+    pub async fn write_read_timeout(
+        &mut self,
+        address: u8,
+        wr_buffer: &[u8],
+        rd_buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        self.setup_write_read(address, wr_buffer, rd_buffer, true)?;
+        self.async_wait_timeout(timeout).await?;
+        compiler_fence(SeqCst);
+        self.check_errorsrc()?;
+        self.check_tx(wr_buffer.len())?;
+        self.check_rx(rd_buffer.len())?;
+        Ok(())
+    }
 }
 
 impl<'a, T: Instance> Drop for Twim<'a, T> {
@@ -889,9 +1005,12 @@ mod eha {
 
 impl<'d, T: Instance> SetConfig for Twim<'d, T> {
     type Config = Config;
-    fn set_config(&mut self, config: &Self::Config) {
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), Self::ConfigError> {
         let r = T::regs();
         r.frequency
             .write(|w| unsafe { w.frequency().bits(config.frequency as u32) });
+
+        Ok(())
     }
 }
@@ -18,6 +18,7 @@ use core::marker::PhantomData;
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::task::Poll;
 
+use embassy_embedded_hal::SetConfig;
 use embassy_hal_common::drop::OnDrop;
 use embassy_hal_common::{into_ref, PeripheralRef};
 use pac::uarte0::RegisterBlock;
@@ -245,6 +246,18 @@ impl<'d, T: Instance> Uarte<'d, T> {
     }
 }
 
+impl<'d, T: Instance> SetConfig for Uarte<'d, T> {
+    type Config = Config;
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), Self::ConfigError> {
+        let r = T::regs();
+        r.config.modify(|_, w| w.parity().variant(config.parity));
+        r.baudrate.write(|w| w.baudrate().variant(config.baudrate));
+
+        Ok(())
+    }
+}
+
 fn configure(r: &RegisterBlock, config: Config, hardware_flow_control: bool) {
     r.config.write(|w| {
         w.hwfc().bit(hardware_flow_control);
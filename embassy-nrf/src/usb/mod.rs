@@ -29,6 +29,16 @@ static EP_IN_WAKERS: [AtomicWaker; 8] = [NEW_AW; 8];
 static EP_OUT_WAKERS: [AtomicWaker; 8] = [NEW_AW; 8];
 static READY_ENDPOINTS: AtomicU32 = AtomicU32::new(0);
 
+/// Index of the isochronous endpoint, which is fixed in hardware.
+const ISO_INDEX: usize = 8;
+
+/// Largest isochronous packet.
+const ISO_MAX_PACKET_SIZE: usize = 1023;
+
+/// RAM copy of isochronous IN data in flash, too big for the stack. There's a single
+/// isochronous IN endpoint, writing one packet at a time.
+static mut ISO_IN_RAM_BUF: [u8; ISO_MAX_PACKET_SIZE] = [0; ISO_MAX_PACKET_SIZE];
+
 /// Interrupt handler.
 pub struct InterruptHandler<T: Instance> {
     _phantom: PhantomData<T>,
@@ -83,6 +93,15 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
                 }
             }
         }
+
+        // The isochronous endpoint has no EPDATA handshake. A received packet can be read
+        // and a new packet can be written once per frame, after SOF.
+        if regs.events_sof.read().bits() != 0 {
+            regs.events_sof.reset();
+            READY_ENDPOINTS.fetch_or(In::mask(ISO_INDEX) | Out::mask(ISO_INDEX), Ordering::AcqRel);
+            In::waker(ISO_INDEX).wake();
+            Out::waker(ISO_INDEX).wake();
+        }
     }
 }
 
@@ -154,10 +173,12 @@ impl<'d, T: Instance, V: VbusDetect + 'd> driver::Driver<'d> for Driver<'d, T, V
     }
 
     fn start(self, control_max_packet_size: u16) -> (Self::Bus, Self::ControlPipe) {
+        let iso_split = self.alloc_in.is_used(ISO_INDEX) && self.alloc_out.is_used(ISO_INDEX);
         (
             Bus {
                 _p: unsafe { self._p.clone_unchecked() },
                 power_available: false,
+                iso_split,
                 vbus_detect: self.vbus_detect,
             },
             ControlPipe {
@@ -172,6 +193,7 @@ impl<'d, T: Instance, V: VbusDetect + 'd> driver::Driver<'d> for Driver<'d, T, V
 pub struct Bus<'d, T: Instance, V: VbusDetect> {
     _p: PeripheralRef<'d, T>,
     power_available: bool,
+    iso_split: bool,
     vbus_detect: V,
 }
 
@@ -198,6 +220,15 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
 
         errata::post_enable();
 
+        // The isochronous buffer is shared between IN and OUT. Split it in half if both are used.
+        if self.iso_split {
+            regs.isosplit.write(|w| w.split().half_in());
+        } else {
+            regs.isosplit.write(|w| w.split().one_dir());
+        }
+        // Send a zero-length packet instead of no response when no IN data has been written.
+        regs.isoinconfig.write(|w| w.response().zero_data());
+
         unsafe { NVIC::unmask(pac::Interrupt::USBD) };
 
         regs.intenset.write(|w| {
@@ -233,8 +264,9 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
                 // Disable all endpoints except EP0
                 regs.epinen.write(|w| unsafe { w.bits(0x01) });
                 regs.epouten.write(|w| unsafe { w.bits(0x01) });
+                regs.intenclr.write(|w| w.sof().clear());
                 READY_ENDPOINTS.store(In::mask(0), Ordering::Release);
-                for i in 1..=7 {
+                for i in 1..=ISO_INDEX {
                     In::waker(i).wake();
                     Out::waker(i).wake();
                 }
@@ -283,27 +315,11 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
     }
 
     fn endpoint_set_stalled(&mut self, ep_addr: EndpointAddress, stalled: bool) {
-        let regs = T::regs();
-        unsafe {
-            if ep_addr.index() == 0 {
-                regs.tasks_ep0stall.write(|w| w.tasks_ep0stall().bit(stalled));
-            } else {
-                regs.epstall.write(|w| {
-                    w.ep().bits(ep_addr.index() as u8 & 0b111);
-                    w.io().bit(ep_addr.is_in());
-                    w.stall().bit(stalled)
-                });
-            }
-        }
+        endpoint_set_stalled(T::regs(), ep_addr, stalled)
     }
 
     fn endpoint_is_stalled(&mut self, ep_addr: EndpointAddress) -> bool {
-        let regs = T::regs();
-        let i = ep_addr.index();
-        match ep_addr.direction() {
-            Direction::Out => regs.halted.epout[i].read().getstatus().is_halted(),
-            Direction::In => regs.halted.epin[i].read().getstatus().is_halted(),
-        }
+        endpoint_is_stalled(T::regs(), ep_addr)
     }
 
     fn endpoint_set_enabled(&mut self, ep_addr: EndpointAddress, enabled: bool) {
@@ -337,6 +353,10 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
                     READY_ENDPOINTS.fetch_and(!ready_mask, Ordering::AcqRel);
                 }
 
+                if i == ISO_INDEX {
+                    update_sof_interrupt(regs);
+                }
+
                 In::waker(i).wake();
             }
             Direction::Out => {
@@ -351,7 +371,12 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
                 });
 
                 let ready_mask = Out::mask(i);
-                if enabled {
+                if i == ISO_INDEX {
+                    if !enabled {
+                        READY_ENDPOINTS.fetch_and(!ready_mask, Ordering::AcqRel);
+                    }
+                    update_sof_interrupt(regs);
+                } else if enabled {
                     // when first enabled, bulk/interrupt OUT endpoints will *not* receive data (the
                     // peripheral will NAK all incoming packets) until we write a zero to the SIZE
                     // register (see figure 203 of the 52840 manual). To avoid that we write a 0 to the
@@ -404,6 +429,44 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
     }
 }
 
+fn endpoint_set_stalled(regs: &RegisterBlock, ep_addr: EndpointAddress, stalled: bool) {
+    unsafe {
+        if ep_addr.index() == ISO_INDEX {
+            // Isochronous endpoints can't be stalled.
+        } else if ep_addr.index() == 0 {
+            regs.tasks_ep0stall.write(|w| w.tasks_ep0stall().bit(stalled));
+        } else {
+            regs.epstall.write(|w| {
+                w.ep().bits(ep_addr.index() as u8 & 0b111);
+                w.io().bit(ep_addr.is_in());
+                w.stall().bit(stalled)
+            });
+        }
+    }
+}
+
+fn endpoint_is_stalled(regs: &RegisterBlock, ep_addr: EndpointAddress) -> bool {
+    let i = ep_addr.index();
+    if i == ISO_INDEX {
+        return false;
+    }
+    match ep_addr.direction() {
+        Direction::Out => regs.halted.epout[i].read().getstatus().is_halted(),
+        Direction::In => regs.halted.epin[i].read().getstatus().is_halted(),
+    }
+}
+
+/// Enable the SOF interrupt only while the isochronous endpoint is in use, as it fires every frame.
+fn update_sof_interrupt(regs: &RegisterBlock) {
+    let iso_mask = 1 << ISO_INDEX;
+    if (regs.epinen.read().bits() | regs.epouten.read().bits()) & iso_mask != 0 {
+        regs.events_sof.reset();
+        regs.intenset.write(|w| w.sof().set());
+    } else {
+        regs.intenclr.write(|w| w.sof().clear());
+    }
+}
+
 /// Type-level marker for OUT endpoints.
 pub enum Out {}
 
@@ -484,6 +547,14 @@ impl<'d, T: Instance, Dir: EndpointDir> driver::Endpoint for Endpoint<'d, T, Dir
         })
         .await
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        endpoint_set_stalled(T::regs(), self.info.addr, stalled)
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled(T::regs(), self.info.addr)
+    }
 }
 
 impl<'d, T: Instance, Dir> Endpoint<'d, T, Dir> {
@@ -586,6 +657,56 @@ unsafe fn write_dma<T: Instance>(i: usize, buf: &[u8]) {
     dma_end();
 }
 
+unsafe fn read_iso_dma<T: Instance>(buf: &mut [u8]) -> Result<usize, EndpointError> {
+    let regs = T::regs();
+
+    // SIZE.ISOOUT holds the size of the packet received in the previous frame.
+    let r = regs.size.isoout.read();
+    let size = if r.zero().is_zero_data() {
+        0
+    } else {
+        r.size().bits() as usize
+    };
+    if size > buf.len() {
+        return Err(EndpointError::BufferOverflow);
+    }
+
+    regs.isoout.ptr.write(|w| w.bits(buf.as_ptr() as u32));
+    regs.isoout.maxcnt.write(|w| w.bits(size as u32));
+
+    dma_start();
+    regs.events_endisoout.reset();
+    regs.tasks_startisoout.write(|w| w.tasks_startisoout().set_bit());
+    while regs.events_endisoout.read().events_endisoout().bit_is_clear() {}
+    regs.events_endisoout.reset();
+    dma_end();
+
+    Ok(size)
+}
+
+unsafe fn write_iso_dma<T: Instance>(buf: &[u8]) {
+    let regs = T::regs();
+
+    let ptr = if !slice_in_ram(buf) {
+        // EasyDMA can't read FLASH, so we copy through RAM
+        let ram_buf = &mut ISO_IN_RAM_BUF[..buf.len()];
+        ram_buf.copy_from_slice(buf);
+        ram_buf.as_ptr()
+    } else {
+        buf.as_ptr()
+    };
+
+    regs.isoin.ptr.write(|w| w.bits(ptr as u32));
+    regs.isoin.maxcnt.write(|w| w.bits(buf.len() as u32));
+
+    regs.events_endisoin.reset();
+
+    dma_start();
+    regs.tasks_startisoin.write(|w| w.bits(1));
+    while regs.events_endisoin.read().bits() == 0 {}
+    dma_end();
+}
+
 impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
         let i = self.info.addr.index();
@@ -593,7 +714,11 @@ impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
 
         self.wait_data_ready().await.map_err(|_| EndpointError::Disabled)?;
 
-        unsafe { read_dma::<T>(i, buf) }
+        if i == ISO_INDEX {
+            unsafe { read_iso_dma::<T>(buf) }
+        } else {
+            unsafe { read_dma::<T>(i, buf) }
+        }
     }
 }
 
@@ -602,9 +727,17 @@ impl<'d, T: Instance> driver::EndpointIn for Endpoint<'d, T, In> {
         let i = self.info.addr.index();
         assert!(i != 0);
 
+        if buf.len() > self.info.max_packet_size as usize {
+            return Err(EndpointError::BufferOverflow);
+        }
+
         self.wait_data_ready().await.map_err(|_| EndpointError::Disabled)?;
 
-        unsafe { write_dma::<T>(i, buf) }
+        if i == ISO_INDEX {
+            unsafe { write_iso_dma::<T>(buf) }
+        } else {
+            unsafe { write_dma::<T>(i, buf) }
+        }
 
         Ok(())
     }
@@ -791,6 +924,10 @@ impl Allocator {
 
         Ok(alloc_index)
     }
+
+    fn is_used(&self, index: usize) -> bool {
+        self.used & (1 << index) != 0
+    }
 }
 
 pub(crate) mod sealed {
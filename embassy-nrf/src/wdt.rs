@@ -3,8 +3,12 @@
 //! This HAL implements a basic watchdog timer with 1..=8 handles.
 //! Once the watchdog has been started, it cannot be stopped.
 
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{mem, ptr};
+
+use crate::interrupt::InterruptExt;
 use crate::pac::WDT;
-use crate::peripherals;
+use crate::{interrupt, peripherals};
 
 const MIN_TICKS: u32 = 15;
 
@@ -58,6 +62,38 @@ impl Default for Config {
     }
 }
 
+/// Interrupt handler.
+///
+/// On timeout, calls the callback set with [`Watchdog::set_timeout_callback`].
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::WDT> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = unsafe { &*WDT::ptr() };
+
+        if r.events_timeout.read().bits() != 0 {
+            r.events_timeout.reset();
+
+            let callback = TIMEOUT_CALLBACK.load(Ordering::Relaxed);
+            if !callback.is_null() {
+                let callback: fn(u8) = unsafe { mem::transmute(callback) };
+                callback(unfed_handles());
+            }
+        }
+    }
+}
+
+static TIMEOUT_CALLBACK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+fn unfed_handles() -> u8 {
+    let r = unsafe { &*WDT::ptr() };
+    let enabled = r.rren.read().bits();
+    let status = r.reqstatus.read().bits();
+    (status & enabled) as u8
+}
+
 /// Watchdog driver.
 pub struct Watchdog {
     _private: (),
@@ -143,6 +179,31 @@ impl Watchdog {
         r.intenclr.write(|w| w.timeout().set_bit());
     }
 
+    /// Call `callback` when the watchdog times out, right before the reset.
+    ///
+    /// The callback runs in the WDT interrupt and receives a bitmask of the handles that weren't
+    /// pet in the last period, which identifies the wedged tasks. It only has two LFCLK ticks
+    /// (61 microseconds) before the reset occurs, so it should do little more than store the
+    /// bitmask to memory retained across resets, or log it.
+    pub fn set_timeout_callback(
+        &mut self,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::WDT, InterruptHandler>,
+        callback: fn(u8),
+    ) {
+        TIMEOUT_CALLBACK.store(callback as *mut (), Ordering::Relaxed);
+
+        interrupt::WDT.unpend();
+        unsafe { interrupt::WDT.enable() };
+
+        self.enable_interrupt();
+    }
+
+    /// Bitmask of the handles that haven't been pet within the current window.
+    #[inline(always)]
+    pub fn unfed_handles(&self) -> u8 {
+        unfed_handles()
+    }
+
     /// Is the watchdog still awaiting pets from any handle?
     ///
     /// This reports whether sufficient pets have been received from all
@@ -174,6 +235,11 @@ impl WatchdogHandle {
         r.rr[self.index as usize].write(|w| w.rr().reload());
     }
 
+    /// Index of this handle, as used in the [`Watchdog::unfed_handles`] bitmask.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
     /// Has this handle been pet within the current window?
     pub fn is_pet(&self) -> bool {
         let r = unsafe { &*WDT::ptr() };
@@ -1,23 +1,38 @@
 use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::ptr;
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::task::Poll;
 
+use embassy_hal_common::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
+use embassy_time::{Duration, Timer};
 use embedded_hal_02::adc::{Channel, OneShot};
 
 use crate::gpio::Pin;
 use crate::interrupt::typelevel::Binding;
 use crate::interrupt::InterruptExt;
+use crate::pac::dma::vals::{DataSize, TreqSel};
 use crate::peripherals::ADC;
-use crate::{interrupt, pac, peripherals, Peripheral};
+use crate::{dma, interrupt, pac, peripherals, Peripheral};
+
+/// ADC input of the on-die temperature sensor.
+const TEMPERATURE_CHANNEL: u8 = 4;
+
+/// DMA request signal of the ADC FIFO.
+const DREQ_ADC: u8 = 36;
+
+/// ADC clock cycles taken by a conversion.
+const CYCLES_PER_SAMPLE: u32 = 96;
+
 static WAKER: AtomicWaker = AtomicWaker::new();
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Error {
-    // No errors for now
+    /// Samples were overwritten in the ring buffer before being read.
+    Overrun,
 }
 
 #[non_exhaustive]
@@ -28,6 +43,33 @@ impl Default for Config {
         Self {}
     }
 }
+/// Inputs sampled in turn by [`Adc::start_free_running`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Channels(u8);
+
+impl Channels {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// Sample `pin`, disabling its pull resistors.
+    pub fn pin<PIN: Channel<Adc<'static>, ID = u8> + Pin>(self, pin: &mut PIN) -> Self {
+        configure_pin(pin);
+        Self(self.0 | 1 << PIN::channel())
+    }
+
+    /// Sample the temperature sensor.
+    pub fn temperature(self) -> Self {
+        Self(self.0 | 1 << TEMPERATURE_CHANNEL)
+    }
+
+    /// Number of inputs sampled.
+    pub fn count(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+}
+
 pub struct Adc<'d> {
     phantom: PhantomData<&'d ADC>,
 }
@@ -82,14 +124,7 @@ impl<'d> Adc<'d> {
 
     pub async fn read<PIN: Channel<Adc<'d>, ID = u8> + Pin>(&mut self, pin: &mut PIN) -> u16 {
         let r = Self::regs();
-        // disable pull-down and pull-up resistors
-        // pull-down resistors are enabled by default
-        pin.pad_ctrl().modify(|w| {
-            w.set_ie(true);
-            let (pu, pd) = (false, false);
-            w.set_pue(pu);
-            w.set_pde(pd);
-        });
+        configure_pin(pin);
         r.cs().modify(|w| {
             w.set_ainsel(PIN::channel());
             w.set_start_once(true)
@@ -105,7 +140,7 @@ impl<'d> Adc<'d> {
             Self::wait_for_ready().await;
         }
         r.cs().modify(|w| {
-            w.set_ainsel(4);
+            w.set_ainsel(TEMPERATURE_CHANNEL);
             w.set_start_once(true)
         });
         Self::wait_for_ready().await;
@@ -114,12 +149,7 @@ impl<'d> Adc<'d> {
 
     pub fn blocking_read<PIN: Channel<Adc<'d>, ID = u8> + Pin>(&mut self, pin: &mut PIN) -> u16 {
         let r = Self::regs();
-        pin.pad_ctrl().modify(|w| {
-            w.set_ie(true);
-            let (pu, pd) = (false, false);
-            w.set_pue(pu);
-            w.set_pde(pd);
-        });
+        configure_pin(pin);
         r.cs().modify(|w| {
             w.set_ainsel(PIN::channel());
             w.set_start_once(true)
@@ -133,12 +163,204 @@ impl<'d> Adc<'d> {
         r.cs().modify(|w| w.set_ts_en(true));
         while !r.cs().read().ready() {}
         r.cs().modify(|w| {
-            w.set_ainsel(4);
+            w.set_ainsel(TEMPERATURE_CHANNEL);
             w.set_start_once(true)
         });
         while !r.cs().read().ready() {}
         r.result().read().result().into()
     }
+
+    /// Start sampling `channels` in turn, at `sample_rate` samples per second in total, the DMA
+    /// channel `dma` streaming the samples into the ring buffer `buffer`.
+    ///
+    /// The samples of the channels are interleaved, in the order of their inputs, the temperature
+    /// sensor last. The length of `buffer` must be a power of two, of at most 16384 samples, and
+    /// the buffer must be aligned to its size in bytes, as the DMA wraps around it by address.
+    pub fn start_free_running<'a, C: dma::Channel>(
+        &'a mut self,
+        channels: Channels,
+        sample_rate: u32,
+        dma: impl Peripheral<P = C> + 'a,
+        buffer: &'a mut [u16],
+    ) -> FreeRunning<'a, C> {
+        into_ref!(dma);
+
+        assert!(channels.count() > 0);
+        let bytes = core::mem::size_of_val(buffer);
+        assert!(bytes >= 2 && bytes <= 1 << 15 && bytes.is_power_of_two());
+        assert_eq!(buffer.as_ptr() as usize % bytes, 0);
+
+        // The ADC takes at least 96 cycles per conversion, at a rate of 500 kS/s.
+        let clk = crate::clocks::clk_adc_freq();
+        assert!(sample_rate > 0 && sample_rate <= clk / CYCLES_PER_SAMPLE);
+        // Conversions start every 1 + INT + FRAC / 256 cycles.
+        let div = ((clk as u64) << 8) / sample_rate as u64 - (1 << 8);
+
+        let r = Self::regs();
+        r.div().write(|w| {
+            w.set_int((div >> 8) as u16);
+            w.set_frac(div as u8);
+        });
+
+        // Drop the samples of previous reads.
+        r.fcs().write(|w| w.set_en(false));
+        while !r.fcs().read().empty() {
+            r.fifo().read();
+        }
+        r.fcs().write(|w| {
+            w.set_en(true);
+            w.set_dreq_en(true);
+            w.set_thresh(1);
+        });
+
+        let p = dma.regs();
+        p.read_addr().write_value(r.fifo().as_ptr() as u32);
+        p.write_addr().write_value(buffer.as_mut_ptr() as u32);
+        p.trans_count().write_value(u32::MAX);
+        compiler_fence(Ordering::SeqCst);
+        p.ctrl_trig().write(|w| {
+            w.set_treq_sel(TreqSel(DREQ_ADC));
+            w.set_data_size(DataSize::SIZE_HALFWORD);
+            w.set_incr_read(false);
+            w.set_incr_write(true);
+            // Wrap the write address around the buffer.
+            w.set_ring_sel(true);
+            w.set_ring_size(bytes.trailing_zeros() as u8);
+            w.set_chain_to(dma.number());
+            w.set_en(true);
+        });
+        compiler_fence(Ordering::SeqCst);
+
+        // Round-robin sampling starts from the first channel.
+        r.cs().modify(|w| {
+            w.set_ts_en(channels.0 & 1 << TEMPERATURE_CHANNEL != 0);
+            w.set_ainsel(channels.0.trailing_zeros() as u8);
+            w.set_rrobin(channels.0);
+            w.set_start_many(true);
+        });
+
+        FreeRunning {
+            dma,
+            buffer,
+            period: Duration::from_hz(sample_rate as u64),
+            transfers: 0,
+            read: 0,
+        }
+    }
+}
+
+/// Free-running sampling into a ring buffer, started by [`Adc::start_free_running`].
+///
+/// Sampling stops when this is dropped.
+pub struct FreeRunning<'a, C: dma::Channel> {
+    dma: PeripheralRef<'a, C>,
+    buffer: &'a mut [u16],
+    period: Duration,
+    /// Samples written by the previous runs of the DMA channel, each of `u32::MAX` transfers.
+    transfers: u64,
+    /// Samples read so far.
+    read: u64,
+}
+
+impl<'a, C: dma::Channel> FreeRunning<'a, C> {
+    /// Samples written to the ring buffer since sampling started.
+    fn written(&mut self) -> u64 {
+        let p = self.dma.regs();
+        let remaining = p.trans_count().read();
+        if remaining == 0 && !p.ctrl_trig().read().busy() {
+            // Restart the DMA channel, which continues from its current write address.
+            self.transfers += u32::MAX as u64;
+            p.ctrl_trig().modify(|w| w.set_en(true));
+            return self.transfers;
+        }
+        self.transfers + (u32::MAX - remaining) as u64
+    }
+
+    /// Read samples into `buf`, waiting until at least `buf.len()` of them, or half the ring
+    /// buffer, are available, and returning the number of samples read.
+    ///
+    /// Returns [`Error::Overrun`] if samples were overwritten before being read, and continues
+    /// from the oldest sample still in the buffer on the next read.
+    pub async fn read(&mut self, buf: &mut [u16]) -> Result<usize, Error> {
+        let len = self.buffer.len();
+        let wanted = buf.len().min(len / 2).max(1) as u64;
+
+        let mut written = self.written();
+        while written - self.read < wanted {
+            let missing = (wanted - (written - self.read)) as u32;
+            Timer::after(self.period * missing).await;
+            written = self.written();
+        }
+
+        if written - self.read > len as u64 {
+            self.read = written - len as u64;
+            return Err(Error::Overrun);
+        }
+
+        compiler_fence(Ordering::SeqCst);
+        let n = buf.len().min((written - self.read) as usize);
+        for sample in &mut buf[..n] {
+            let i = self.read as usize & (len - 1);
+            // The DMA channel writes the buffer concurrently.
+            *sample = unsafe { ptr::read_volatile(self.buffer.as_ptr().add(i)) };
+            self.read += 1;
+        }
+
+        let written = self.written();
+        if written - (self.read - n as u64) > len as u64 {
+            // The DMA channel overwrote samples while they were copied.
+            self.read = written - len as u64;
+            return Err(Error::Overrun);
+        }
+        Ok(n)
+    }
+}
+
+impl<'a, C: dma::Channel> Drop for FreeRunning<'a, C> {
+    fn drop(&mut self) {
+        let r = Adc::regs();
+        r.cs().modify(|w| {
+            w.set_start_many(false);
+            w.set_rrobin(0);
+        });
+        while !r.cs().read().ready() {}
+
+        pac::DMA
+            .chan_abort()
+            .modify(|m| m.set_chan_abort(1 << self.dma.number()));
+        while self.dma.regs().ctrl_trig().read().busy() {}
+
+        r.fcs().write(|w| w.set_en(false));
+        while !r.fcs().read().empty() {
+            r.fifo().read();
+        }
+    }
+}
+
+/// Convert a reading of the temperature sensor to degrees Celsius.
+///
+/// Uses the typical characteristics of the sensor from the RP2040 datasheet: 0.706 V at 27 °C,
+/// falling by 1.721 mV per °C, with the ADC referenced to 3.3 V. The sensor varies between chips,
+/// and the result strongly depends on the reference voltage: measure it more precisely with
+/// [`convert_to_celsius_with_vref`].
+pub fn convert_to_celsius(raw: u16) -> f32 {
+    convert_to_celsius_with_vref(raw, 3.3)
+}
+
+/// Convert a reading of the temperature sensor to degrees Celsius, with the ADC referenced to
+/// `vref` volts.
+pub fn convert_to_celsius_with_vref(raw: u16, vref: f32) -> f32 {
+    let volts = raw as f32 * vref / 4096.0;
+    27.0 - (volts - 0.706) / 0.001721
+}
+
+/// Disable the pull resistors of `pin`, the pull-down being enabled on reset.
+fn configure_pin(pin: &mut impl Pin) {
+    pin.pad_ctrl().modify(|w| {
+        w.set_ie(true);
+        w.set_pue(false);
+        w.set_pde(false);
+    });
 }
 
 macro_rules! impl_pin {
@@ -65,6 +65,8 @@ pub struct ClockConfig {
     pub usb_clk: Option<UsbClkConfig>,
     pub adc_clk: Option<AdcClkConfig>,
     pub rtc_clk: Option<RtcClkConfig>,
+    /// Voltage of the core, raised before the clocks are configured.
+    pub core_voltage: CoreVoltage,
     // gpin0: Option<(u32, Gpin<'static, AnyPin>)>,
     // gpin1: Option<(u32, Gpin<'static, AnyPin>)>,
 }
@@ -122,11 +124,36 @@ impl ClockConfig {
                 div_frac: 0,
                 phase: 0,
             }),
+            core_voltage: CoreVoltage::V1_10,
             // gpin0: None,
             // gpin1: None,
         }
     }
 
+    /// Clock the system at `sys_hz` from the crystal, overclocking it above the nominal 125 MHz
+    /// or running it slower, the other clocks being configured as by [`ClockConfig::crystal`].
+    ///
+    /// The core voltage is raised to 1.15 V above 133 MHz, the maximum of the datasheet, and to
+    /// 1.20 V above 200 MHz: overclocked chips aren't guaranteed to work, and the flash may need
+    /// a slower boot2.
+    ///
+    /// Panics if the system PLL can't produce `sys_hz` exactly.
+    pub fn crystal_freq(crystal_hz: u32, sys_hz: u32) -> Self {
+        let mut config = Self::crystal(crystal_hz);
+        let Some(pll) = find_pll_config(crystal_hz, sys_hz) else {
+            panic!("No PLL configuration for the requested system frequency");
+        };
+        if let Some(xosc) = config.xosc.as_mut() {
+            xosc.sys_pll = Some(pll);
+        }
+        config.core_voltage = match sys_hz {
+            0..=133_000_000 => CoreVoltage::V1_10,
+            133_000_001..=200_000_000 => CoreVoltage::V1_15,
+            _ => CoreVoltage::V1_20,
+        };
+        config
+    }
+
     pub fn rosc() -> Self {
         Self {
             rosc: Some(RoscConfig {
@@ -160,6 +187,7 @@ impl ClockConfig {
                 div_frac: 171,
                 phase: 0,
             }),
+            core_voltage: CoreVoltage::V1_10,
             // gpin0: None,
             // gpin1: None,
         }
@@ -210,6 +238,60 @@ pub struct PllConfig {
     pub post_div2: u8,
 }
 
+/// Find a PLL configuration producing `output_hz` from `input_hz`, favoring a high VCO frequency
+/// for a lower jitter, like the `vcocalc.py` script of the pico-sdk.
+pub fn find_pll_config(input_hz: u32, output_hz: u32) -> Option<PllConfig> {
+    let input = input_hz as u64;
+    let output = output_hz as u64;
+    for fbdiv in (16..=320u16).rev() {
+        let vco = input * fbdiv as u64;
+        if !(750_000_000..=1_600_000_000).contains(&vco) {
+            continue;
+        }
+        for post_div1 in 1..=7u8 {
+            for post_div2 in 1..=post_div1 {
+                if vco == output * (post_div1 * post_div2) as u64 {
+                    return Some(PllConfig {
+                        refdiv: 1,
+                        fbdiv,
+                        post_div1,
+                        post_div2,
+                    });
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Output voltage of the core regulator.
+#[repr(u8)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoreVoltage {
+    V0_85 = 0b0110,
+    V0_90 = 0b0111,
+    V0_95 = 0b1000,
+    V1_00 = 0b1001,
+    V1_05 = 0b1010,
+    /// Reset value.
+    V1_10 = 0b1011,
+    V1_15 = 0b1100,
+    V1_20 = 0b1101,
+    V1_25 = 0b1110,
+    V1_30 = 0b1111,
+}
+
+fn set_core_voltage(voltage: CoreVoltage) {
+    let vreg = pac::VREG_AND_CHIP_RESET.vreg();
+    if vreg.read().vsel() == voltage as u8 {
+        return;
+    }
+    vreg.modify(|w| w.set_vsel(voltage as u8));
+    // Wait for the output to be in regulation at the new voltage.
+    while !vreg.read().rok() {}
+}
+
 pub struct RefClkConfig {
     pub src: RefClkSrc,
     pub div: u8,
@@ -330,6 +412,9 @@ pub(crate) unsafe fn init(config: ClockConfig) {
     c.clk_ref_ctrl().modify(|w| w.set_src(ClkRefCtrlSrc::ROSC_CLKSRC_PH));
     while c.clk_ref_selected().read() != 1 {}
 
+    // Raise the core voltage before the system clock.
+    set_core_voltage(config.core_voltage);
+
     // Reset the PLLs
     let mut peris = reset::Peripherals(0);
     peris.set_pll_sys(true);
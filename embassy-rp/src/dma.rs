@@ -96,6 +96,7 @@ pub unsafe fn write_repeated<'a, C: Channel, W: Word>(
     )
 }
 
+/// Copy `from` to `to`, which must have the same length, as fast as the bus allows.
 pub unsafe fn copy<'a, C: Channel, W: Word>(
     ch: impl Peripheral<P = C> + 'a,
     from: &[W],
@@ -190,9 +191,262 @@ impl<'a, C: Channel> Future for Transfer<'a, C> {
     }
 }
 
+/// Bit of CTRL_TRIG feeding the data of the channel to the sniffer.
+const CTRL_SNIFF_EN: u32 = 1 << 23;
+
+/// A transfer of a scatter-gather list, loaded in the registers of the data channel by the
+/// control channel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlBlock {
+    read_addr: u32,
+    write_addr: u32,
+    trans_count: u32,
+    ctrl: u32,
+}
+
+impl ControlBlock {
+    /// End of a scatter-gather list.
+    pub const END: Self = Self {
+        read_addr: 0,
+        write_addr: 0,
+        trans_count: 0,
+        ctrl: 0,
+    };
+
+    /// Transfer `len` words from `from` to `to`, paced by `dreq`, incrementing the addresses as
+    /// requested.
+    pub unsafe fn new<W: Word>(
+        from: *const W,
+        incr_read: bool,
+        to: *mut W,
+        incr_write: bool,
+        len: usize,
+        dreq: u8,
+    ) -> Self {
+        let mut w = pac::dma::regs::CtrlTrig(0);
+        // TODO: Add all DREQ options to pac vals::TreqSel, and use
+        // `set_treq:sel`
+        w.0 = ((dreq as u32) & 0x3f) << 15usize;
+        w.set_data_size(W::size());
+        w.set_incr_read(incr_read);
+        w.set_incr_write(incr_write);
+        w.set_en(true);
+        Self {
+            read_addr: from as u32,
+            write_addr: to as u32,
+            trans_count: len as u32,
+            ctrl: w.0,
+        }
+    }
+
+    /// Copy `from` to `to`, which must have the same length.
+    pub unsafe fn copy<W: Word>(from: &[W], to: &mut [W]) -> Self {
+        assert_eq!(from.len(), to.len());
+        Self::new(
+            from.as_ptr(),
+            true,
+            to.as_mut_ptr(),
+            true,
+            from.len(),
+            vals::TreqSel::PERMANENT.0,
+        )
+    }
+}
+
+/// Run the transfers of `blocks`, up to the first [`ControlBlock::END`], one after the other on
+/// the `data` channel, the `ctrl` channel loading each of them in its registers.
+pub unsafe fn scatter_gather<'a, D: Channel, C: Channel>(
+    data: impl Peripheral<P = D> + 'a,
+    ctrl: impl Peripheral<P = C> + 'a,
+    blocks: &'a mut [ControlBlock],
+) -> ScatterGather<'a, D, C> {
+    into_ref!(data, ctrl);
+
+    assert_eq!(blocks.last(), Some(&ControlBlock::END));
+    // Each transfer triggers the control channel to load the next one.
+    for block in blocks.iter_mut().filter(|b| **b != ControlBlock::END) {
+        let mut w = pac::dma::regs::CtrlTrig(block.ctrl);
+        w.set_chain_to(ctrl.number());
+        block.ctrl = w.0;
+    }
+
+    let p = ctrl.regs();
+    p.read_addr().write_value(blocks.as_ptr() as u32);
+    p.write_addr().write_value(data.regs().read_addr().as_ptr() as u32);
+    p.trans_count().write_value(4);
+
+    compiler_fence(Ordering::SeqCst);
+
+    p.ctrl_trig().write(|w| {
+        w.set_treq_sel(vals::TreqSel::PERMANENT);
+        w.set_data_size(DataSize::SIZE_WORD);
+        w.set_incr_read(true);
+        w.set_incr_write(true);
+        // Wrap around the 4 registers of the data channel, ending with CTRL_TRIG.
+        w.set_ring_sel(true);
+        w.set_ring_size(4);
+        w.set_chain_to(ctrl.number());
+        w.set_en(true);
+    });
+
+    compiler_fence(Ordering::SeqCst);
+
+    ScatterGather {
+        data,
+        ctrl,
+        end: blocks.as_ptr_range().end as u32,
+    }
+}
+
+/// Transfer of a scatter-gather list, started by [`scatter_gather`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ScatterGather<'a, D: Channel, C: Channel> {
+    data: PeripheralRef<'a, D>,
+    ctrl: PeripheralRef<'a, C>,
+    end: u32,
+}
+
+impl<'a, D: Channel, C: Channel> Drop for ScatterGather<'a, D, C> {
+    fn drop(&mut self) {
+        pac::DMA
+            .chan_abort()
+            .modify(|m| m.set_chan_abort(1 << self.ctrl.number() | 1 << self.data.number()));
+        while self.ctrl.regs().ctrl_trig().read().busy() || self.data.regs().ctrl_trig().read().busy() {}
+    }
+}
+
+impl<'a, D: Channel, C: Channel> Unpin for ScatterGather<'a, D, C> {}
+impl<'a, D: Channel, C: Channel> Future for ScatterGather<'a, D, C> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The control channel completes after loading each block, the last time after loading
+        // the end of the list.
+        CHANNEL_WAKERS[self.ctrl.number() as usize].register(cx.waker());
+
+        let ctrl = self.ctrl.regs();
+        if ctrl.read_addr().read() == self.end
+            && !ctrl.ctrl_trig().read().busy()
+            && !self.data.regs().ctrl_trig().read().busy()
+        {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Calculation of the sniffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SniffCalc {
+    /// CRC-32, IEEE 802.3 polynomial.
+    Crc32,
+    /// CRC-32, IEEE 802.3 polynomial, with bit-reversed data.
+    Crc32Reversed,
+    /// CRC-16-CCITT.
+    Crc16Ccitt,
+    /// CRC-16-CCITT, with bit-reversed data.
+    Crc16CcittReversed,
+    /// XOR reduction over all the data.
+    Xor,
+    /// Sum of all the data, modulo 2^32.
+    Sum,
+}
+
+impl SniffCalc {
+    fn bits(self) -> u32 {
+        match self {
+            Self::Crc32 => 0x0,
+            Self::Crc32Reversed => 0x1,
+            Self::Crc16Ccitt => 0x2,
+            Self::Crc16CcittReversed => 0x3,
+            Self::Xor => 0xe,
+            Self::Sum => 0xf,
+        }
+    }
+}
+
+/// Configuration of the sniffer.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SniffConfig {
+    pub calc: SniffCalc,
+    /// Initial value of the checksum.
+    pub seed: u32,
+    /// Swap the bytes of the words before the calculation.
+    pub byte_swap: bool,
+    /// Bit-reverse the result when it's read.
+    pub out_reverse: bool,
+    /// Invert the result when it's read.
+    pub out_invert: bool,
+}
+
+impl SniffConfig {
+    /// The CRC-32 of zlib, Ethernet and PNG.
+    pub const fn crc32() -> Self {
+        Self {
+            calc: SniffCalc::Crc32Reversed,
+            seed: 0xffff_ffff,
+            byte_swap: false,
+            out_reverse: true,
+            out_invert: true,
+        }
+    }
+}
+
+impl Default for SniffConfig {
+    fn default() -> Self {
+        Self::crc32()
+    }
+}
+
+/// Calculate the checksum of `data`, configured by `config`, reading it with the DMA channel `ch`
+/// while the sniffer watches it.
+pub async fn checksum<C: Channel, W: Word>(ch: impl Peripheral<P = C>, data: &[W], config: &SniffConfig) -> u32 {
+    into_ref!(ch);
+
+    let mut sink = 0u32;
+
+    pac::DMA.sniff_data().write_value(config.seed);
+    pac::DMA.sniff_ctrl().write_value(pac::dma::regs::SniffCtrl(
+        // EN, DMACH, CALC, BSWAP, OUT_REV, OUT_INV
+        1 | (ch.number() as u32) << 1
+            | config.calc.bits() << 5
+            | (config.byte_swap as u32) << 9
+            | (config.out_reverse as u32) << 10
+            | (config.out_invert as u32) << 11,
+    ));
+
+    let (from_ptr, len) = crate::dma::slice_ptr_parts(data);
+    let p = ch.regs();
+    p.read_addr().write_value(from_ptr as u32);
+    p.write_addr().write_value(&mut sink as *mut u32 as u32);
+    p.trans_count().write_value(len as u32);
+
+    compiler_fence(Ordering::SeqCst);
+
+    p.ctrl_trig().write(|w| {
+        w.0 = ((vals::TreqSel::PERMANENT.0 as u32) << 15) | CTRL_SNIFF_EN;
+        w.set_data_size(W::size());
+        w.set_incr_read(true);
+        w.set_incr_write(false);
+        w.set_chain_to(ch.number());
+        w.set_en(true);
+    });
+
+    compiler_fence(Ordering::SeqCst);
+    Transfer::new(ch.reborrow()).await;
+
+    let result = pac::DMA.sniff_data().read();
+    pac::DMA.sniff_ctrl().write_value(pac::dma::regs::SniffCtrl(0));
+    result
+}
+
 pub(crate) const CHANNEL_COUNT: usize = 12;
 const NEW_AW: AtomicWaker = AtomicWaker::new();
-static CHANNEL_WAKERS: [AtomicWaker; CHANNEL_COUNT] = [NEW_AW; CHANNEL_COUNT];
+pub(crate) static CHANNEL_WAKERS: [AtomicWaker; CHANNEL_COUNT] = [NEW_AW; CHANNEL_COUNT];
 
 mod sealed {
     pub trait Channel {}
@@ -1,3 +1,8 @@
+//! Flash driver, for the QSPI flash the program runs from.
+//!
+//! Erasing and writing the flash stops execute-in-place, so the driver runs these operations
+//! from RAM, with interrupts disabled and CORE1 paused: it must be used from CORE0.
+
 use core::marker::PhantomData;
 
 use embassy_hal_common::Peripheral;
@@ -242,6 +247,59 @@ impl<'d, T: Instance, const FLASH_SIZE: usize> NorFlash for Flash<'d, T, FLASH_S
     }
 }
 
+#[cfg(feature = "nightly")]
+mod asynch {
+    use embassy_futures::yield_now;
+    use embedded_storage_async::nor_flash::{NorFlash as AsyncNorFlash, ReadNorFlash as AsyncReadNorFlash};
+
+    use super::*;
+
+    impl<'d, T: Instance, const FLASH_SIZE: usize> AsyncReadNorFlash for Flash<'d, T, FLASH_SIZE> {
+        const READ_SIZE: usize = READ_SIZE;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            Self::read(self, offset, bytes)
+        }
+
+        fn capacity(&self) -> usize {
+            Self::capacity(self)
+        }
+    }
+
+    /// Interrupts are disabled, and CORE1 paused, while the flash is erased or written, so these
+    /// erase a sector and write a page at a time, yielding in between to let other tasks run.
+    impl<'d, T: Instance, const FLASH_SIZE: usize> AsyncNorFlash for Flash<'d, T, FLASH_SIZE> {
+        const WRITE_SIZE: usize = WRITE_SIZE;
+        const ERASE_SIZE: usize = ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            check_erase(self, from, to)?;
+
+            for sector in (from..to).step_by(ERASE_SIZE) {
+                Self::erase(self, sector, sector + ERASE_SIZE as u32)?;
+                yield_now().await;
+            }
+            Ok(())
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            check_write(self, offset, bytes.len())?;
+
+            // Split the bytes at page boundaries.
+            let mut offset = offset;
+            let mut bytes = bytes;
+            while !bytes.is_empty() {
+                let len = (PAGE_SIZE - offset as usize % PAGE_SIZE).min(bytes.len());
+                Self::write(self, offset, &bytes[..len])?;
+                offset += len as u32;
+                bytes = &bytes[len..];
+                yield_now().await;
+            }
+            Ok(())
+        }
+    }
+}
+
 #[allow(dead_code)]
 mod ram_helpers {
     use core::marker::PhantomData;
@@ -2,6 +2,7 @@ use core::future;
 use core::marker::PhantomData;
 use core::task::Poll;
 
+use embassy_embedded_hal::SetConfig;
 use embassy_hal_common::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 use pac::i2c;
@@ -40,6 +41,18 @@ pub enum Error {
     AddressReserved(u16),
 }
 
+/// I2C configuration error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The frequency is above the 1 MHz of fast mode plus.
+    FrequencyTooHigh,
+    /// The frequency is too low for the divisors at the current peripheral clock.
+    FrequencyTooLow,
+    /// The peripheral clock is too slow for the frequency.
+    ClockTooSlow,
+}
+
 #[non_exhaustive]
 #[derive(Copy, Clone)]
 pub struct Config {
@@ -52,7 +65,7 @@ impl Default for Config {
     }
 }
 
-const FIFO_SIZE: u8 = 16;
+pub(crate) const FIFO_SIZE: u8 = 16;
 
 pub struct I2c<'d, T: Instance, M: Mode> {
     phantom: PhantomData<(&'d mut T, M)>,
@@ -325,9 +338,6 @@ impl<'d, T: Instance + 'd, M: Mode> I2c<'d, T, M> {
     ) -> Self {
         into_ref!(_peri);
 
-        assert!(config.frequency <= 1_000_000);
-        assert!(config.frequency > 0);
-
         let p = T::regs();
 
         let reset = T::reset();
@@ -371,11 +381,29 @@ impl<'d, T: Instance + 'd, M: Mode> I2c<'d, T, M> {
             w.set_pde(false);
         });
 
+        unwrap!(Self::set_config_inner(&config));
+
+        // Enable I2C block
+        p.ic_enable().write(|w| w.set_enable(true));
+
+        Self { phantom: PhantomData }
+    }
+
+    /// Set the bus frequency, with the I2C block disabled.
+    fn set_config_inner(config: &Config) -> Result<(), ConfigError> {
+        if config.frequency > 1_000_000 {
+            return Err(ConfigError::FrequencyTooHigh);
+        }
+        if config.frequency == 0 {
+            return Err(ConfigError::FrequencyTooLow);
+        }
+
         // Configure baudrate
 
         // There are some subtleties to I2C timing which we are completely
         // ignoring here See:
         // https://github.com/raspberrypi/pico-sdk/blob/bfcbefafc5d2a210551a4d9d80b4303d4ae0adf7/src/rp2_common/hardware_i2c/i2c.c#L69
+        let p = T::regs();
         let clk_base = crate::clocks::clk_peri_freq();
 
         let period = (clk_base + config.frequency / 2) / config.frequency;
@@ -383,10 +411,12 @@ impl<'d, T: Instance + 'd, M: Mode> I2c<'d, T, M> {
         let hcnt = period - lcnt; // and 2/5 (40%) of the period high
 
         // Check for out-of-range divisors:
-        assert!(hcnt <= 0xffff);
-        assert!(lcnt <= 0xffff);
-        assert!(hcnt >= 8);
-        assert!(lcnt >= 8);
+        if hcnt > 0xffff || lcnt > 0xffff {
+            return Err(ConfigError::FrequencyTooLow);
+        }
+        if hcnt < 8 || lcnt < 8 {
+            return Err(ConfigError::ClockTooSlow);
+        }
 
         // Per I2C-bus specification a device in standard or fast mode must
         // internally provide a hold time of at least 300ns for the SDA
@@ -399,14 +429,18 @@ impl<'d, T: Instance + 'd, M: Mode> I2c<'d, T, M> {
             ((clk_base * 3) / 10_000_000) + 1
         } else {
             // fast mode plus requires a clk_base > 32MHz
-            assert!(clk_base >= 32_000_000);
+            if clk_base < 32_000_000 {
+                return Err(ConfigError::ClockTooSlow);
+            }
 
             // sda_tx_hold_count = clk_base [cycles/s] * 120ns * (1s /
             // 1e9ns) Reduce 120/1e9 to 3/25e6 to avoid numbers that don't
             // fit in uint. Add 1 to avoid division truncation.
             ((clk_base * 3) / 25_000_000) + 1
         };
-        assert!(sda_tx_hold_count <= lcnt - 2);
+        if sda_tx_hold_count > lcnt - 2 {
+            return Err(ConfigError::ClockTooSlow);
+        }
 
         p.ic_fs_scl_hcnt().write(|w| w.set_ic_fs_scl_hcnt(hcnt as u16));
         p.ic_fs_scl_lcnt().write(|w| w.set_ic_fs_scl_lcnt(lcnt as u16));
@@ -415,10 +449,7 @@ impl<'d, T: Instance + 'd, M: Mode> I2c<'d, T, M> {
         p.ic_sda_hold()
             .modify(|w| w.set_ic_sda_tx_hold(sda_tx_hold_count as u16));
 
-        // Enable I2C block
-        p.ic_enable().write(|w| w.set_enable(true));
-
-        Self { phantom: PhantomData }
+        Ok(())
     }
 
     fn setup(addr: u16) -> Result<(), Error> {
@@ -737,10 +768,22 @@ mod nightly {
     }
 }
 
-fn i2c_reserved_addr(addr: u16) -> bool {
+pub(crate) fn i2c_reserved_addr(addr: u16) -> bool {
     (addr & 0x78) == 0 || (addr & 0x78) == 0x78
 }
 
+impl<'d, T: Instance, M: Mode> SetConfig for I2c<'d, T, M> {
+    type Config = Config;
+    type ConfigError = ConfigError;
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ConfigError> {
+        let p = T::regs();
+        p.ic_enable().write(|w| w.set_enable(false));
+        let res = Self::set_config_inner(config);
+        p.ic_enable().write(|w| w.set_enable(true));
+        res
+    }
+}
+
 mod sealed {
     use embassy_sync::waitqueue::AtomicWaker;
 
@@ -0,0 +1,340 @@
+//! I2C slave driver, for a device controlled by another I2C master.
+
+use core::future;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::into_ref;
+use pac::i2c;
+
+use crate::gpio::sealed::Pin;
+use crate::i2c::{i2c_reserved_addr, AbortReason, Instance, InterruptHandler, SclPin, SdaPin, FIFO_SIZE};
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::{pac, Peripheral};
+
+/// Bit of IC_TX_ABRT_SOURCE set when the TX FIFO is flushed at the end of a read.
+const ABRT_SLVFLUSH_TXFIFO: u32 = 1 << 13;
+
+/// I2C slave error
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// I2C abort with error
+    Abort(AbortReason),
+    /// User passed in a response buffer that was 0 length
+    InvalidResponseBufferLength,
+    /// The master wrote more bytes than fit in the buffer, only that many were kept
+    PartialWrite(usize),
+    /// The general call wrote more bytes than fit in the buffer, only that many were kept
+    PartialGeneralCall(usize),
+}
+
+/// Transaction started by the master.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// The master wrote this many bytes to the general call address.
+    GeneralCall(usize),
+    /// The master reads: respond with [`I2cSlave::respond_to_read`].
+    Read,
+    /// The master wrote this many bytes.
+    Write(usize),
+    /// The master wrote this many bytes, then reads after a repeated start: respond with
+    /// [`I2cSlave::respond_to_read`].
+    WriteRead(usize),
+}
+
+/// Outcome of a response to a read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadStatus {
+    /// The master read all the bytes and ended the transaction.
+    Done,
+    /// The master read all the bytes, and wants more.
+    NeedMoreBytes,
+    /// The master ended the transaction before reading this many bytes.
+    LeftoverBytes(u16),
+}
+
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// 7-bit address of the slave.
+    pub addr: u16,
+    /// Also accept writes to the general call address, 0.
+    pub general_call: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: 0x55,
+            general_call: true,
+        }
+    }
+}
+
+pub struct I2cSlave<'d, T: Instance> {
+    phantom: PhantomData<&'d mut T>,
+}
+
+impl<'d, T: Instance> I2cSlave<'d, T> {
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>>,
+        config: Config,
+    ) -> Self {
+        into_ref!(_peri, scl, sda);
+
+        assert!(config.addr < 0x80);
+        assert!(!i2c_reserved_addr(config.addr));
+
+        let p = T::regs();
+
+        let reset = T::reset();
+        crate::reset::reset(reset);
+        crate::reset::unreset_wait(reset);
+
+        p.ic_enable().write(|w| w.set_enable(false));
+
+        p.ic_sar().write(|w| w.set_ic_sar(config.addr));
+        p.ic_con().modify(|w| {
+            w.set_master_mode(false);
+            w.set_ic_slave_disable(false);
+            w.set_tx_empty_ctrl(true);
+        });
+        p.ic_ack_general_call()
+            .write(|w| w.set_ack_gen_call(config.general_call));
+
+        // Set FIFO watermarks to 1 to make things simpler. This is encoded
+        // by a register value of 0.
+        p.ic_tx_tl().write(|w| w.set_tx_tl(0));
+        p.ic_rx_tl().write(|w| w.set_rx_tl(0));
+
+        // Configure SCL & SDA pins
+        scl.io().ctrl().write(|w| w.set_funcsel(3));
+        sda.io().ctrl().write(|w| w.set_funcsel(3));
+
+        scl.pad_ctrl().write(|w| {
+            w.set_schmitt(true);
+            w.set_ie(true);
+            w.set_od(false);
+            w.set_pue(true);
+            w.set_pde(false);
+        });
+        sda.pad_ctrl().write(|w| {
+            w.set_schmitt(true);
+            w.set_ie(true);
+            w.set_od(false);
+            w.set_pue(true);
+            w.set_pde(false);
+        });
+
+        // Clear interrupts
+        p.ic_clr_intr().read();
+
+        // Enable I2C block
+        p.ic_enable().write(|w| w.set_enable(true));
+
+        // mask everything initially
+        p.ic_intr_mask().write_value(i2c::regs::IcIntrMask(0));
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { phantom: PhantomData }
+    }
+
+    /// Calls `f` to check if we are ready or not.
+    /// If not, `g` is called once the waker is set (to eg enable the required interrupts).
+    async fn wait_on<F, U, G>(&mut self, mut f: F, mut g: G) -> U
+    where
+        F: FnMut(&mut Self) -> Poll<U>,
+        G: FnMut(&mut Self),
+    {
+        future::poll_fn(|cx| {
+            let r = f(self);
+
+            if r.is_pending() {
+                T::waker().register(cx.waker());
+                g(self);
+            }
+            r
+        })
+        .await
+    }
+
+    /// Move the bytes of the rx fifo to `buffer` from `offset`, dropping those that don't fit,
+    /// and return the offset past the last byte, counting the dropped ones.
+    fn drain_fifo(&mut self, buffer: &mut [u8], offset: usize) -> usize {
+        let p = T::regs();
+        let len = p.ic_rxflr().read().rxflr() as usize;
+        for i in offset..offset + len {
+            let byte = p.ic_data_cmd().read().dat();
+            if let Some(b) = buffer.get_mut(i) {
+                *b = byte;
+            }
+        }
+        offset + len
+    }
+
+    /// Wait for the master to address the slave, receiving the bytes it writes in `buffer`.
+    pub async fn listen(&mut self, buffer: &mut [u8]) -> Result<Command, Error> {
+        let p = T::regs();
+
+        p.ic_clr_intr().read();
+        // Wake on the first received byte.
+        p.ic_rx_tl().write(|w| w.set_rx_tl(0));
+
+        let mut len = 0;
+        let cmd = self
+            .wait_on(
+                |me| {
+                    let stat = p.ic_raw_intr_stat().read();
+                    if p.ic_rxflr().read().rxflr() > 0 {
+                        len = me.drain_fifo(buffer, len);
+                        // Receiving data: wake less often, before the fifo fills up.
+                        p.ic_rx_tl().write(|w| w.set_rx_tl(FIFO_SIZE - 4));
+                    }
+
+                    if stat.restart_det() && stat.rd_req() {
+                        Poll::Ready(Command::WriteRead(len))
+                    } else if stat.gen_call() && stat.stop_det() && len > 0 {
+                        Poll::Ready(Command::GeneralCall(len))
+                    } else if stat.stop_det() {
+                        Poll::Ready(Command::Write(len))
+                    } else if stat.rd_req() {
+                        Poll::Ready(Command::Read)
+                    } else {
+                        Poll::Pending
+                    }
+                },
+                |_me| {
+                    p.ic_intr_mask().modify(|w| {
+                        w.set_m_stop_det(true);
+                        w.set_m_restart_det(true);
+                        w.set_m_gen_call(true);
+                        w.set_m_rd_req(true);
+                        w.set_m_rx_full(true);
+                    });
+                },
+            )
+            .await;
+
+        // Keep a pending read request for `respond_to_read`.
+        p.ic_clr_stop_det().read();
+        p.ic_clr_restart_det().read();
+        p.ic_clr_gen_call().read();
+
+        match cmd {
+            Command::Write(len) if len > buffer.len() => Err(Error::PartialWrite(buffer.len())),
+            Command::WriteRead(len) if len > buffer.len() => Err(Error::PartialWrite(buffer.len())),
+            Command::GeneralCall(len) if len > buffer.len() => Err(Error::PartialGeneralCall(buffer.len())),
+            cmd => Ok(cmd),
+        }
+    }
+
+    /// Respond to a read of the master with the bytes of `buffer`.
+    pub async fn respond_to_read(&mut self, buffer: &[u8]) -> Result<ReadStatus, Error> {
+        if buffer.is_empty() {
+            return Err(Error::InvalidResponseBufferLength);
+        }
+
+        let p = T::regs();
+
+        let mut sent = 0;
+        let ret = self
+            .wait_on(
+                |me| {
+                    if let Some(ret) = me.read_and_clear_abort_reason() {
+                        return Poll::Ready(ret);
+                    }
+
+                    if sent < buffer.len() {
+                        let capacity = FIFO_SIZE - p.ic_txflr().read().txflr();
+                        let end = buffer.len().min(sent + capacity as usize);
+                        p.ic_clr_rd_req().read();
+                        for &byte in &buffer[sent..end] {
+                            p.ic_data_cmd().write(|w| w.set_dat(byte));
+                        }
+                        sent = end;
+                        return Poll::Pending;
+                    }
+
+                    let stat = p.ic_raw_intr_stat().read();
+                    if stat.rx_done() && stat.stop_det() {
+                        Poll::Ready(Ok(ReadStatus::Done))
+                    } else if stat.rd_req() && stat.tx_empty() {
+                        Poll::Ready(Ok(ReadStatus::NeedMoreBytes))
+                    } else {
+                        Poll::Pending
+                    }
+                },
+                |_me| {
+                    p.ic_intr_mask().modify(|w| {
+                        w.set_m_stop_det(true);
+                        w.set_m_rx_done(true);
+                        w.set_m_tx_empty(true);
+                        w.set_m_tx_abrt(true);
+                    })
+                },
+            )
+            .await;
+
+        p.ic_clr_rx_done().read();
+        p.ic_clr_stop_det().read();
+
+        ret
+    }
+
+    /// Respond to a read of the master with `fill` until it ends the transaction.
+    pub async fn respond_till_stop(&mut self, fill: u8) -> Result<(), Error> {
+        loop {
+            match self.respond_to_read(&[fill]).await? {
+                ReadStatus::NeedMoreBytes => (),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Respond to a read of the master with the bytes of `buffer`, then with `fill` until it ends
+    /// the transaction.
+    pub async fn respond_and_fill(&mut self, buffer: &[u8], fill: u8) -> Result<ReadStatus, Error> {
+        let status = self.respond_to_read(buffer).await?;
+        if status == ReadStatus::NeedMoreBytes {
+            self.respond_till_stop(fill).await?;
+            Ok(ReadStatus::Done)
+        } else {
+            Ok(status)
+        }
+    }
+
+    /// Read and clear the abort reason, the end of a read with bytes left in the tx fifo not
+    /// being an error.
+    fn read_and_clear_abort_reason(&mut self) -> Option<Result<ReadStatus, Error>> {
+        let p = T::regs();
+        let abort_reason = p.ic_tx_abrt_source().read();
+        if abort_reason.0 != 0 {
+            // Note clearing the abort flag also clears the reason, and this
+            // instance of flag is clear-on-read! Note also the
+            // IC_CLR_TX_ABRT register always reads as 0.
+            p.ic_clr_tx_abrt().read();
+
+            if abort_reason.0 & ABRT_SLVFLUSH_TXFIFO != 0 {
+                // The master ended the read with bytes left in the tx fifo, which are flushed.
+                let flushed = (abort_reason.0 >> 23) as u16;
+                return Some(Ok(ReadStatus::LeftoverBytes(flushed)));
+            }
+
+            let reason = if abort_reason.arb_lost() {
+                AbortReason::ArbitrationLoss
+            } else {
+                AbortReason::Other(abort_reason.0)
+            };
+            Some(Err(Error::Abort(reason)))
+        } else {
+            None
+        }
+    }
+}
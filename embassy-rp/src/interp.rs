@@ -0,0 +1,208 @@
+//! SIO interpolators
+//!
+//! Each core has its own two interpolators, at the same addresses: an [`Interp`] drives the
+//! interpolator of the core it is used from, so it must stay on one core.
+//!
+//! Each lane of an interpolator shifts, masks and sign-extends its accumulator, adds the result
+//! to its base, and the pop registers add these results back to the accumulators, in a single
+//! cycle. Interpolator 0 can also blend between its two bases, and interpolator 1 clamp its lane
+//! 0 result between them.
+
+use core::marker::PhantomData;
+
+use crate::pac::sio::regs::InterpCtrlLane;
+use crate::{pac, peripherals, Peripheral};
+
+/// Lane of an interpolator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lane {
+    Lane0 = 0,
+    Lane1 = 1,
+}
+
+/// Configuration of a lane.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LaneConfig {
+    /// Logical right shift of the accumulator, from 0 to 31.
+    pub shift: u8,
+    /// Least significant bit kept after the shift, from 0 to 31.
+    pub mask_lsb: u8,
+    /// Most significant bit kept after the shift, from `mask_lsb` to 31.
+    pub mask_msb: u8,
+    /// Sign-extend the masked value from `mask_msb`.
+    pub signed: bool,
+    /// Take the accumulator of the other lane as input.
+    pub cross_input: bool,
+    /// Feed the result of the other lane back to the accumulator when popping.
+    pub cross_result: bool,
+    /// Add the raw input to the base, instead of the shifted and masked one, for the lane
+    /// result: the full result still uses the masked value.
+    pub add_raw: bool,
+    /// ORed into bits 29:28 of the lane result, to make it an address in a given memory.
+    pub force_msb: u8,
+}
+
+impl Default for LaneConfig {
+    fn default() -> Self {
+        Self {
+            shift: 0,
+            mask_lsb: 0,
+            mask_msb: 31,
+            signed: false,
+            cross_input: false,
+            cross_result: false,
+            add_raw: false,
+            force_msb: 0,
+        }
+    }
+}
+
+impl LaneConfig {
+    fn write(&self, w: &mut InterpCtrlLane) {
+        assert!(self.shift < 32);
+        assert!(self.mask_lsb <= self.mask_msb && self.mask_msb < 32);
+        assert!(self.force_msb < 4);
+
+        w.set_shift(self.shift);
+        w.set_mask_lsb(self.mask_lsb);
+        w.set_mask_msb(self.mask_msb);
+        w.set_signed(self.signed);
+        w.set_cross_input(self.cross_input);
+        w.set_cross_result(self.cross_result);
+        w.set_add_raw(self.add_raw);
+        w.set_force_msb(self.force_msb);
+    }
+}
+
+/// Interpolator configuration.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub lane0: LaneConfig,
+    pub lane1: LaneConfig,
+    /// Blend mode, on interpolator 0 only: lane 1 result is a linear interpolation between
+    /// base 0 and base 1, by the fraction in the low 8 bits of its shifted and masked value.
+    /// Lane 0 result doesn't add base 0, and the full result only adds base 2 to lane 0
+    /// shifted and masked value.
+    pub blend: bool,
+    /// Clamp mode, on interpolator 1 only: lane 0 result is its shifted and masked value,
+    /// clamped between base 0 and base 1.
+    pub clamp: bool,
+}
+
+/// Interpolator driver.
+pub struct Interp<'d, T: Instance> {
+    phantom: PhantomData<&'d mut T>,
+}
+
+impl<'d, T: Instance> Interp<'d, T> {
+    pub fn new(_inner: impl Peripheral<P = T> + 'd, config: Config) -> Self {
+        let mut this = Self { phantom: PhantomData };
+        this.set_config(&config);
+        this
+    }
+
+    pub fn set_config(&mut self, config: &Config) {
+        assert!(!config.blend || T::NUMBER == 0, "only interpolator 0 blends");
+        assert!(!config.clamp || T::NUMBER == 1, "only interpolator 1 clamps");
+
+        let interp = Self::regs();
+        interp.ctrl_lane(0).write(|w| {
+            config.lane0.write(w);
+            w.set_blend(config.blend);
+            w.set_clamp(config.clamp);
+        });
+        interp.ctrl_lane(1).write(|w| config.lane1.write(w));
+    }
+
+    #[inline]
+    fn regs() -> pac::sio::Interp {
+        pac::SIO.interp(T::NUMBER)
+    }
+
+    #[inline]
+    pub fn accum(&self, lane: Lane) -> u32 {
+        Self::regs().accum(lane as usize).read()
+    }
+
+    #[inline]
+    pub fn set_accum(&mut self, lane: Lane, value: u32) {
+        Self::regs().accum(lane as usize).write_value(value)
+    }
+
+    /// Add `value` to the accumulator of `lane`, atomically.
+    #[inline]
+    pub fn add_accum(&mut self, lane: Lane, value: u32) {
+        Self::regs().accum_add(lane as usize).write_value(value)
+    }
+
+    /// Read base `n`, from 0 to 2.
+    #[inline]
+    pub fn base(&self, n: usize) -> u32 {
+        assert!(n < 3);
+        Self::regs().base(n).read()
+    }
+
+    /// Write base `n`, from 0 to 2.
+    #[inline]
+    pub fn set_base(&mut self, n: usize, value: u32) {
+        assert!(n < 3);
+        Self::regs().base(n).write_value(value)
+    }
+
+    /// Write the low 16 bits of `value` to base 0 and its high 16 bits to base 1, at once,
+    /// sign-extending them if the lanes are signed.
+    #[inline]
+    pub fn set_base_1and0(&mut self, value: u32) {
+        Self::regs().base_1and0().write_value(value)
+    }
+
+    /// Result of `lane`, without updating the accumulators.
+    #[inline]
+    pub fn peek(&self, lane: Lane) -> u32 {
+        Self::regs().peek(lane as usize).read()
+    }
+
+    /// Result of `lane`, updating the accumulators.
+    #[inline]
+    pub fn pop(&mut self, lane: Lane) -> u32 {
+        Self::regs().pop(lane as usize).read()
+    }
+
+    /// Full result, the sum of base 2 and of both lane values, without updating the
+    /// accumulators.
+    #[inline]
+    pub fn peek_full(&self) -> u32 {
+        Self::regs().peek_full().read()
+    }
+
+    /// Full result, the sum of base 2 and of both lane values, updating the accumulators.
+    #[inline]
+    pub fn pop_full(&mut self) -> u32 {
+        Self::regs().pop_full().read()
+    }
+}
+
+mod sealed {
+    pub trait Instance {
+        const NUMBER: usize;
+    }
+}
+
+pub trait Instance: sealed::Instance {}
+
+macro_rules! impl_instance {
+    ($type:ident, $number:expr) => {
+        impl sealed::Instance for peripherals::$type {
+            const NUMBER: usize = $number;
+        }
+        impl Instance for peripherals::$type {}
+    };
+}
+
+impl_instance!(INTERP0, 0);
+impl_instance!(INTERP1, 1);
@@ -419,7 +419,7 @@ division_function! {
     }
 }
 
-fn divider_unsigned(n: u32, d: u32) -> DivResult<u32> {
+pub(crate) fn divider_unsigned(n: u32, d: u32) -> DivResult<u32> {
     let packed = unsafe { unsigned_divmod(n, d) };
     DivResult {
         quotient: packed as u32,
@@ -427,7 +427,7 @@ fn divider_unsigned(n: u32, d: u32) -> DivResult<u32> {
     }
 }
 
-fn divider_signed(n: i32, d: i32) -> DivResult<i32> {
+pub(crate) fn divider_signed(n: i32, d: i32) -> DivResult<i32> {
     let packed = unsafe { signed_divmod(n, d) };
     // Double casts to avoid sign extension
     DivResult {
@@ -437,7 +437,9 @@ fn divider_signed(n: i32, d: i32) -> DivResult<i32> {
 }
 
 /// Result of divide/modulo operation
-struct DivResult<T> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DivResult<T> {
     /// The quotient of divide/modulo operation
     pub quotient: T,
     /// The remainder of divide/modulo operation
@@ -16,12 +16,16 @@ pub mod flash;
 mod float;
 pub mod gpio;
 pub mod i2c;
+pub mod i2c_slave;
+pub mod interp;
 pub mod multicore;
+pub mod power;
 pub mod pwm;
 mod reset;
 pub mod rom_data;
 pub mod rtc;
 pub mod spi;
+pub mod spi_slave;
 #[cfg(feature = "time-driver")]
 pub mod timer;
 pub mod uart;
@@ -33,6 +37,10 @@ pub mod watchdog;
 // TODO: move `pio_instr_util` and `relocate` to inside `pio`
 pub mod pio;
 pub mod pio_instr_util;
+pub mod pio_quadrature;
+pub mod pio_uart;
+pub mod pio_vga;
+pub mod pio_ws2812;
 pub mod relocate;
 
 // Reexports
@@ -190,6 +198,11 @@ embassy_hal_common::peripherals! {
     PIO1,
 
     WATCHDOG,
+
+    INTERP0,
+    INTERP1,
+
+    TIMER_ALARM3,
 }
 
 macro_rules! select_bootloader {
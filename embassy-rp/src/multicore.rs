@@ -5,6 +5,10 @@
 //!
 //! The entrypoint for core1 can be any function that never returns, including closures.
 //!
+//! The cores can exchange `u32` values through the SIO FIFO with [`fifo_send()`] and
+//! [`fifo_receive()`]. The FIFO is also used to pause core1 while core0 writes to the flash, which
+//! is handled transparently.
+//!
 //! Enable the `critical-section-impl` feature in embassy-rp when sharing data across cores using
 //! the `embassy-sync` primitives and `CriticalSectionRawMutex`.
 //!
@@ -50,14 +54,28 @@
 use core::mem::ManuallyDrop;
 use core::sync::atomic::{compiler_fence, AtomicBool, Ordering};
 
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
 use crate::interrupt::InterruptExt;
 use crate::peripherals::CORE1;
 use crate::{gpio, interrupt, pac};
 
 const PAUSE_TOKEN: u32 = 0xDEADBEEF;
 const RESUME_TOKEN: u32 = !0xDEADBEEF;
+/// Sent before values equal to a token, so they're not taken for one.
+const ESCAPE_TOKEN: u32 = 0xDEADBEEE;
 static IS_CORE1_INIT: AtomicBool = AtomicBool::new(false);
 
+/// Number of values each core buffers until they're received.
+const FIFO_RX_SIZE: usize = 16;
+
+const NEW_RX: Channel<CriticalSectionRawMutex, u32, FIFO_RX_SIZE> = Channel::new();
+/// Values received by each core.
+static FIFO_RX: [Channel<CriticalSectionRawMutex, u32, FIFO_RX_SIZE>; 2] = [NEW_RX; 2];
+/// Whether the next value read by each core follows an `ESCAPE_TOKEN`.
+static FIFO_ESCAPED: [AtomicBool; 2] = [AtomicBool::new(false), AtomicBool::new(false)];
+
 #[inline(always)]
 fn install_stack_guard(stack_bottom: *mut usize) {
     let core = unsafe { cortex_m::Peripherals::steal() };
@@ -106,6 +124,18 @@ impl<const SIZE: usize> Stack<SIZE> {
     }
 }
 
+#[cfg(feature = "rt")]
+#[interrupt]
+unsafe fn SIO_IRQ_PROC0() {
+    let sio = pac::SIO;
+    // Clear IRQ
+    sio.fifo().st().write(|w| w.set_wof(false));
+
+    while sio.fifo().st().read().vld() {
+        fifo_dispatch(0, fifo_read());
+    }
+}
+
 #[cfg(feature = "rt")]
 #[interrupt]
 #[link_section = ".data.ram_func"]
@@ -116,7 +146,7 @@ unsafe fn SIO_IRQ_PROC1() {
 
     while sio.fifo().st().read().vld() {
         // Pause CORE1 execution and disable interrupts
-        if fifo_read_wfe() == PAUSE_TOKEN {
+        if fifo_dispatch(1, fifo_read_wfe()) == Some(PAUSE_TOKEN) {
             cortex_m::interrupt::disable();
             // Signal to CORE0 that execution is paused
             fifo_write(PAUSE_TOKEN);
@@ -250,18 +280,84 @@ where
 /// Pause execution on CORE1.
 pub fn pause_core1() {
     if IS_CORE1_INIT.load(Ordering::Acquire) {
-        fifo_write(PAUSE_TOKEN);
-        // Wait for CORE1 to signal it has paused execution.
-        while fifo_read() != PAUSE_TOKEN {}
+        send_token_core0(PAUSE_TOKEN);
     }
 }
 
 /// Resume CORE1 execution.
 pub fn resume_core1() {
     if IS_CORE1_INIT.load(Ordering::Acquire) {
-        fifo_write(RESUME_TOKEN);
-        // Wait for CORE1 to signal it has resumed execution.
-        while fifo_read() != RESUME_TOKEN {}
+        send_token_core0(RESUME_TOKEN);
+    }
+}
+
+/// Send a token to CORE1, and wait for it to be sent back.
+fn send_token_core0(token: u32) {
+    // Keep the FIFO interrupt handler from taking the reply.
+    let irq_enabled = interrupt::SIO_IRQ_PROC0.is_enabled();
+    interrupt::SIO_IRQ_PROC0.disable();
+
+    fifo_write(token);
+    // Values sent by CORE1 before the reply are kept for `fifo_receive`.
+    while fifo_dispatch(0, fifo_read()) != Some(token) {}
+
+    if irq_enabled {
+        unsafe { interrupt::SIO_IRQ_PROC0.enable() };
+    }
+}
+
+/// Send `value` to the other core, which receives it with [`fifo_receive()`].
+///
+/// This blocks while the FIFO is full, until the FIFO interrupt of the other core drains it. The
+/// other core buffers up to 16 values, and drops the following ones until they're received.
+pub fn fifo_send(value: u32) {
+    cortex_m::interrupt::free(|_| {
+        if matches!(value, PAUSE_TOKEN | RESUME_TOKEN | ESCAPE_TOKEN) {
+            fifo_write(ESCAPE_TOKEN);
+        }
+        fifo_write(value);
+    });
+}
+
+/// Receive a value sent by the other core with [`fifo_send()`].
+///
+/// On core0, the values are only read from the FIFO once this is called for the first time, so
+/// core1 blocks in [`fifo_send()`] until then.
+pub async fn fifo_receive() -> u32 {
+    let core = pac::SIO.cpuid().read() as usize;
+    if core == 0 && !interrupt::SIO_IRQ_PROC0.is_enabled() {
+        unsafe { interrupt::SIO_IRQ_PROC0.enable() };
+    }
+    FIFO_RX[core].recv().await
+}
+
+/// Handle a value read from the FIFO by `core`: values sent with `fifo_send` are buffered, and
+/// tokens are returned.
+#[inline(always)]
+fn fifo_dispatch(core: usize, value: u32) -> Option<u32> {
+    // Only `core` accesses its flag, with its FIFO interrupt disabled outside of the handler.
+    if FIFO_ESCAPED[core].load(Ordering::Relaxed) {
+        FIFO_ESCAPED[core].store(false, Ordering::Relaxed);
+        fifo_buffer(core, value);
+        return None;
+    }
+
+    match value {
+        PAUSE_TOKEN | RESUME_TOKEN => Some(value),
+        ESCAPE_TOKEN => {
+            FIFO_ESCAPED[core].store(true, Ordering::Relaxed);
+            None
+        }
+        _ => {
+            fifo_buffer(core, value);
+            None
+        }
+    }
+}
+
+fn fifo_buffer(core: usize, value: u32) {
+    if FIFO_RX[core].try_send(value).is_err() {
+        warn!("core{} FIFO buffer full, value dropped", core);
     }
 }
 
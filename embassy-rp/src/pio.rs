@@ -9,7 +9,7 @@ use embassy_hal_common::{into_ref, Peripheral, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 use fixed::types::extra::U8;
 use fixed::FixedU32;
-use pac::io::vals::Gpio0ctrlFuncsel;
+use pac::io::vals::{Gpio0ctrlFuncsel, Inover, Outover};
 use pac::pio::vals::SmExecctrlStatusSel;
 use pio::{SideSet, Wrap};
 
@@ -277,6 +277,22 @@ impl<'l, PIO: Instance> Pin<'l, PIO> {
         });
     }
 
+    /// Invert the pin's output, as driven by the state machines.
+    #[inline]
+    pub fn set_output_inversion(&mut self, invert: bool) {
+        self.pin.io().ctrl().modify(|w| {
+            w.set_outover(if invert { Outover::INVERT } else { Outover::NORMAL });
+        });
+    }
+
+    /// Invert the pin's input, as read by the state machines.
+    #[inline]
+    pub fn set_input_inversion(&mut self, invert: bool) {
+        self.pin.io().ctrl().modify(|w| {
+            w.set_inover(if invert { Inover::INVERT } else { Inover::NORMAL });
+        });
+    }
+
     pub fn set_input_sync_bypass<'a>(&mut self, bypass: bool) {
         let mask = 1 << self.pin();
         if bypass {
@@ -416,6 +432,16 @@ impl<'d, PIO: Instance, const SM: usize> StateMachineTx<'d, PIO, SM> {
         FifoOutFuture::new(self, value)
     }
 
+    /// Address of the TX FIFO, for DMA transfers set up by other drivers.
+    pub(crate) fn fifo_addr(&self) -> *mut u32 {
+        PIO::PIO.txf(SM).as_ptr() as *mut u32
+    }
+
+    /// DMA request signal of the TX FIFO.
+    pub(crate) fn dreq(&self) -> u8 {
+        PIO::PIO_NO * 8 + SM as u8
+    }
+
     pub fn dma_push<'a, C: Channel, W: Word>(&'a mut self, ch: PeripheralRef<'a, C>, data: &'a [W]) -> Transfer<'a, C> {
         let pio_no = PIO::PIO_NO;
         let p = ch.regs();
@@ -449,6 +475,15 @@ impl<'d, PIO: Instance, const SM: usize> Drop for StateMachine<'d, PIO, SM> {
     }
 }
 
+/// The clock divider for `freq` instructions per second, from the current system clock.
+fn clock_divider(freq: u32) -> FixedU32<U8> {
+    assert!(freq > 0, "frequency must be > 0");
+    let bits = ((crate::clocks::clk_sys_freq() as u64) << 8) / freq as u64;
+    assert!(bits >= 1 << 8, "frequency must be <= the system clock");
+    assert!(bits <= 65536 << 8, "frequency too low, clkdiv must be <= 65536");
+    FixedU32::from_bits(bits as u32)
+}
+
 fn assert_consecutive<'d, PIO: Instance>(pins: &[&Pin<'d, PIO>]) {
     for (p1, p2) in pins.iter().zip(pins.iter().skip(1)) {
         // purposely does not allow wrap-around because we can't claim pins 30 and 31.
@@ -531,6 +566,12 @@ impl<'d, PIO: Instance> Default for Config<'d, PIO> {
 }
 
 impl<'d, PIO: Instance> Config<'d, PIO> {
+    /// Sets the clock divider so that the state machine runs `freq` instructions per second,
+    /// from the current system clock.
+    pub fn set_frequency(&mut self, freq: u32) {
+        self.clock_divider = clock_divider(freq);
+    }
+
     pub fn get_exec(&self) -> ExecConfig {
         self.exec
     }
@@ -674,6 +715,24 @@ impl<'d, PIO: Instance + 'd, const SM: usize> StateMachine<'d, PIO, SM> {
         PIO::PIO.ctrl().write_set(|w| w.set_clkdiv_restart(mask));
     }
 
+    /// Changes the clock divider of the state machine, which can be running.
+    pub fn set_clock_divider(&mut self, clock_divider: FixedU32<U8>) {
+        assert!(clock_divider <= 65536, "clkdiv must be <= 65536");
+        assert!(clock_divider >= 1, "clkdiv must be >= 1");
+        Self::this_sm().clkdiv().write(|w| w.0 = clock_divider.to_bits() << 8);
+    }
+
+    /// Changes the clock divider of the state machine, which can be running, to run `freq`
+    /// instructions per second.
+    pub fn set_frequency(&mut self, freq: u32) {
+        self.set_clock_divider(clock_divider(freq));
+    }
+
+    /// Returns the address of the instruction being executed.
+    pub fn get_addr(&self) -> u8 {
+        Self::this_sm().addr().read().addr()
+    }
+
     fn with_paused(&mut self, f: impl FnOnce(&mut Self)) {
         let enabled = self.is_enabled();
         self.set_enable(false);
@@ -0,0 +1,123 @@
+//! Quadrature encoder driver, on a PIO state machine.
+//!
+//! The state machine follows the A and B phases of the encoder and counts the steps itself, at up
+//! to a step every 14 system clock cycles, so the CPU only reads the position when it needs it.
+//!
+//! The program uses a computed jump on the pin states, so it must be at the start of the
+//! instruction memory, and takes 29 of its 32 instructions.
+
+use embassy_time::Instant;
+
+use crate::gpio::Pull;
+use crate::pio::{Common, Config, Instance, PioPin, ShiftDirection, StateMachine};
+use crate::relocate::RelocatedProgram;
+use crate::{pio_instr_util, Peripheral};
+
+/// Quadrature encoder driver.
+pub struct QuadratureEncoder<'d, P: Instance, const S: usize> {
+    sm: StateMachine<'d, P, S>,
+    last: Option<(i32, Instant)>,
+}
+
+impl<'d, P: Instance, const S: usize> QuadratureEncoder<'d, P, S> {
+    /// Load the program at the start of the instruction memory of `pio`, and start `sm` counting
+    /// the steps of the encoder on `pin_a` and `pin_b`, which must be consecutive pins.
+    ///
+    /// The pins are pulled up, for encoders with open-collector outputs or mechanical contacts.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        mut sm: StateMachine<'d, P, S>,
+        pin_a: impl Peripheral<P = impl PioPin + 'd> + 'd,
+        pin_b: impl Peripheral<P = impl PioPin + 'd> + 'd,
+    ) -> Self {
+        let prg = pio_proc::pio_asm!(
+            ".origin 0",
+            // Jump table, on the previous and current states of the pins: 00 state
+            "jmp update",    // read 00
+            "jmp decrement", // read 01
+            "jmp increment", // read 10
+            "jmp update",    // read 11
+            // 01 state
+            "jmp increment", // read 00
+            "jmp update",    // read 01
+            "jmp update",    // read 10
+            "jmp decrement", // read 11
+            // 10 state
+            "jmp decrement", // read 00
+            "jmp update",    // read 01
+            "jmp update",    // read 10
+            "jmp increment", // read 11
+            // 11 state, its last entries being the actions
+            "jmp update",    // read 00
+            "jmp increment", // read 01
+            "decrement:",
+            // Decrement Y, jumping to the next instruction anyway
+            "jmp y--, update", // read 10
+            ".wrap_target",
+            "update:",
+            // Push the count, dropped if the RX FIFO is full
+            "mov isr, y", // read 11
+            "push noblock",
+            // Shift the previous state of the pins, kept in the OSR, and their new state into
+            // the ISR, then jump to the table entry.
+            "out isr, 2",
+            "in pins, 2",
+            "mov osr, isr",
+            "mov pc, isr",
+            // Increment Y, by negating, decrementing and negating it
+            "increment:",
+            "mov y, ~y",
+            "jmp y--, increment_cont",
+            "increment_cont:",
+            "mov y, ~y",
+            ".wrap",
+        );
+
+        let mut pin_a = pio.make_pio_pin(pin_a);
+        let mut pin_b = pio.make_pio_pin(pin_b);
+        pin_a.set_pull(Pull::Up);
+        pin_b.set_pull(Pull::Up);
+
+        let mut cfg = Config::default();
+        cfg.set_in_pins(&[&pin_a, &pin_b]);
+
+        let relocated = RelocatedProgram::new(&prg.program);
+        cfg.use_program(&pio.load_program(&relocated), &[]);
+
+        cfg.shift_in.direction = ShiftDirection::Left;
+        cfg.shift_out.direction = ShiftDirection::Right;
+
+        sm.set_config(&cfg);
+        // Start counting from 0.
+        unsafe { pio_instr_util::set_y(&mut sm, 0) };
+        sm.set_enable(true);
+
+        Self { sm, last: None }
+    }
+
+    /// Read the position, in steps, four per cycle of the phases.
+    pub async fn read(&mut self) -> i32 {
+        // The state machine keeps the RX FIFO full of counts, drop them for a new one.
+        let rx = self.sm.rx();
+        for _ in 0..rx.level() {
+            rx.pull();
+        }
+        rx.wait_pull().await as i32
+    }
+
+    /// Read the velocity since the previous call, in steps per second, or `None` on the first
+    /// call.
+    pub async fn read_velocity(&mut self) -> Option<f32> {
+        let position = self.read().await;
+        let now = Instant::now();
+
+        let velocity = self.last.map(|(last_position, last_time)| {
+            let steps = position.wrapping_sub(last_position);
+            let elapsed = (now - last_time).as_micros();
+            steps as f32 * 1_000_000.0 / elapsed.max(1) as f32
+        });
+
+        self.last = Some((position, now));
+        velocity
+    }
+}
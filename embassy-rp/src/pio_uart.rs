@@ -0,0 +1,322 @@
+//! UART on PIO state machines, for when the two hardware UARTs aren't enough.
+//!
+//! A state machine transmits and another one receives, 8 data bits, no parity and 1 stop bit, at
+//! any baud rate the PIO clock divider can produce with 8 cycles per bit. Both take the transmitted
+//! or received bytes through their FIFO: bytes received while the RX FIFO is full are lost.
+
+use embassy_time::{Duration, Timer};
+
+use crate::gpio::{Level, Pull};
+use crate::pio::{Common, Config as PioConfig, Direction, FifoJoin, Instance, PioPin, ShiftDirection, StateMachine};
+use crate::relocate::RelocatedProgram;
+use crate::Peripheral;
+
+/// State machine cycles per bit.
+const CYCLES_PER_BIT: u32 = 8;
+
+/// PIO UART configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Config {
+    pub baudrate: u32,
+    /// Invert the tx pin output
+    pub invert_tx: bool,
+    /// Invert the rx pin input
+    pub invert_rx: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            baudrate: 115200,
+            invert_tx: false,
+            invert_rx: false,
+        }
+    }
+}
+
+/// PIO UART error.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Triggered when a break is received
+    Break,
+    /// Triggered when the received character didn't have a valid stop bit.
+    Framing,
+}
+
+/// PIO UART, transmitting with the `TX` state machine and receiving with the `RX` one.
+pub struct PioUart<'d, P: Instance, const TX: usize, const RX: usize> {
+    tx: PioUartTx<'d, P, TX>,
+    rx: PioUartRx<'d, P, RX>,
+}
+
+impl<'d, P: Instance, const TX: usize, const RX: usize> PioUart<'d, P, TX, RX> {
+    /// Load the programs in the instruction memory of `pio`, and start `tx_sm` transmitting on
+    /// `tx_pin` and `rx_sm` receiving on `rx_pin`.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        tx_sm: StateMachine<'d, P, TX>,
+        rx_sm: StateMachine<'d, P, RX>,
+        tx_pin: impl Peripheral<P = impl PioPin + 'd> + 'd,
+        rx_pin: impl Peripheral<P = impl PioPin + 'd> + 'd,
+        config: Config,
+    ) -> Self {
+        Self {
+            tx: PioUartTx::new(pio, tx_sm, tx_pin, config),
+            rx: PioUartRx::new(pio, rx_sm, rx_pin, config),
+        }
+    }
+
+    /// Transmit the bytes of `buffer`, returning once they are all in the TX FIFO.
+    pub async fn write(&mut self, buffer: &[u8]) {
+        self.tx.write(buffer).await
+    }
+
+    /// Wait for the bytes in the TX FIFO to be transmitted.
+    pub async fn flush(&mut self) {
+        self.tx.flush().await
+    }
+
+    /// Read at least one byte, and at most `buffer.len()`, returning the number of bytes read.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read(buffer).await
+    }
+
+    /// Split the UART in a transmitter and a receiver, to use them from different tasks.
+    pub fn split(self) -> (PioUartTx<'d, P, TX>, PioUartRx<'d, P, RX>) {
+        (self.tx, self.rx)
+    }
+}
+
+/// Transmitter of a [`PioUart`].
+pub struct PioUartTx<'d, P: Instance, const S: usize> {
+    sm: StateMachine<'d, P, S>,
+    bit_time: Duration,
+}
+
+impl<'d, P: Instance, const S: usize> PioUartTx<'d, P, S> {
+    /// Load the program in the instruction memory of `pio`, and start `sm` transmitting on `pin`.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        mut sm: StateMachine<'d, P, S>,
+        pin: impl Peripheral<P = impl PioPin + 'd> + 'd,
+        config: Config,
+    ) -> Self {
+        let prg = pio_proc::pio_asm!(
+            ".side_set 1 opt",
+            // Assert the stop bit, or stall with the line idle
+            "pull side 1 [7]",
+            // Preload the bit counter, and assert the start bit for 8 cycles
+            "set x, 7 side 0 [7]",
+            "bitloop:",
+            // Shift the bits out, from the least significant one, 8 cycles each
+            "out pins, 1",
+            "jmp x-- bitloop [6]",
+        );
+
+        let mut pin = pio.make_pio_pin(pin);
+        pin.set_output_inversion(config.invert_tx);
+
+        let mut cfg = PioConfig::default();
+        cfg.set_out_pins(&[&pin]);
+
+        let relocated = RelocatedProgram::new(&prg.program);
+        cfg.use_program(&pio.load_program(&relocated), &[&pin]);
+
+        cfg.set_frequency(config.baudrate * CYCLES_PER_BIT);
+        cfg.fifo_join = FifoJoin::TxOnly;
+        cfg.shift_out.direction = ShiftDirection::Right;
+
+        sm.set_config(&cfg);
+        sm.set_pins(Level::High, &[&pin]);
+        sm.set_pin_dirs(Direction::Out, &[&pin]);
+        sm.set_enable(true);
+
+        Self {
+            sm,
+            bit_time: Duration::from_hz(config.baudrate as u64),
+        }
+    }
+
+    /// Transmit the bytes of `buffer`, returning once they are all in the TX FIFO.
+    pub async fn write(&mut self, buffer: &[u8]) {
+        for &byte in buffer {
+            self.sm.tx().wait_push(byte as u32).await;
+        }
+    }
+
+    /// Wait for the bytes in the TX FIFO to be transmitted.
+    pub async fn flush(&mut self) {
+        while !self.sm.tx().empty() {
+            Timer::after(self.bit_time).await;
+        }
+
+        // The last byte is transmitted once the state machine stalls on the empty FIFO again,
+        // which sets the stall flag as long as it waits.
+        self.sm.tx().stalled();
+        while !self.sm.tx().stalled() {
+            Timer::after(self.bit_time).await;
+        }
+    }
+}
+
+/// Receiver of a [`PioUart`].
+pub struct PioUartRx<'d, P: Instance, const S: usize> {
+    sm: StateMachine<'d, P, S>,
+    error: Option<Error>,
+}
+
+impl<'d, P: Instance, const S: usize> PioUartRx<'d, P, S> {
+    /// Load the program in the instruction memory of `pio`, and start `sm` receiving on `pin`.
+    ///
+    /// The pin is pulled up, to keep the line idle while nothing drives it.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        mut sm: StateMachine<'d, P, S>,
+        pin: impl Peripheral<P = impl PioPin + 'd> + 'd,
+        config: Config,
+    ) -> Self {
+        let prg = pio_proc::pio_asm!(
+            "start:",
+            // Wait for the start bit
+            "wait 0 pin 0",
+            // Preload the bit counter, and wait for the middle of the first data bit
+            "set x, 7 [10]",
+            "bitloop:",
+            // Sample the data bits, 8 cycles apart, then the stop bit
+            "in pins, 1",
+            "jmp x-- bitloop [6]",
+            "in pins, 1",
+            // Push the character, dropped if the RX FIFO is full
+            "push noblock",
+            // On a framing error or a break, wait for the line to be idle again
+            "jmp pin start",
+            "wait 1 pin 0",
+        );
+
+        let mut pin = pio.make_pio_pin(pin);
+        pin.set_pull(Pull::Up);
+        pin.set_input_inversion(config.invert_rx);
+
+        let mut cfg = PioConfig::default();
+        cfg.set_in_pins(&[&pin]);
+        cfg.set_jmp_pin(&pin);
+
+        let relocated = RelocatedProgram::new(&prg.program);
+        cfg.use_program(&pio.load_program(&relocated), &[]);
+
+        cfg.set_frequency(config.baudrate * CYCLES_PER_BIT);
+        cfg.fifo_join = FifoJoin::RxOnly;
+        cfg.shift_in.direction = ShiftDirection::Right;
+
+        sm.set_config(&cfg);
+        sm.set_pin_dirs(Direction::In, &[&pin]);
+        sm.set_enable(true);
+
+        Self { sm, error: None }
+    }
+
+    /// Read at least one byte, and at most `buffer.len()`, returning the number of bytes read.
+    ///
+    /// An error is returned once the bytes received before it are read.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let word = self.sm.rx().wait_pull().await;
+        buffer[0] = decode(word)?;
+
+        let mut n = 1;
+        while n < buffer.len() {
+            let Some(word) = self.sm.rx().try_pull() else {
+                break;
+            };
+            match decode(word) {
+                Ok(byte) => buffer[n] = byte,
+                Err(err) => {
+                    self.error = Some(err);
+                    break;
+                }
+            }
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Decode a received character, its 8 data bits and stop bit being shifted in from the top.
+fn decode(word: u32) -> Result<u8, Error> {
+    let byte = (word >> 23) as u8;
+    if word & (1 << 31) != 0 {
+        Ok(byte)
+    } else if byte == 0 {
+        Err(Error::Break)
+    } else {
+        Err(Error::Framing)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod eio {
+    use super::*;
+
+    impl embedded_io::Error for Error {
+        fn kind(&self) -> embedded_io::ErrorKind {
+            embedded_io::ErrorKind::Other
+        }
+    }
+
+    impl<'d, P: Instance, const TX: usize, const RX: usize> embedded_io::Io for PioUart<'d, P, TX, RX> {
+        type Error = Error;
+    }
+
+    impl<'d, P: Instance, const S: usize> embedded_io::Io for PioUartTx<'d, P, S> {
+        type Error = Error;
+    }
+
+    impl<'d, P: Instance, const S: usize> embedded_io::Io for PioUartRx<'d, P, S> {
+        type Error = Error;
+    }
+
+    impl<'d, P: Instance, const TX: usize, const RX: usize> embedded_io::asynch::Read for PioUart<'d, P, TX, RX> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.rx.read(buf).await
+        }
+    }
+
+    impl<'d, P: Instance, const S: usize> embedded_io::asynch::Read for PioUartRx<'d, P, S> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Self::read(self, buf).await
+        }
+    }
+
+    impl<'d, P: Instance, const TX: usize, const RX: usize> embedded_io::asynch::Write for PioUart<'d, P, TX, RX> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.write(buf).await;
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.tx.flush().await;
+            Ok(())
+        }
+    }
+
+    impl<'d, P: Instance, const S: usize> embedded_io::asynch::Write for PioUartTx<'d, P, S> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Self::write(self, buf).await;
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Self::flush(self).await;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! VGA video output, on a PIO state machine.
+//!
+//! The state machine shifts out the pixels of each line, along with the sync signals, at the
+//! pixel clock. Two DMA channels chained to each other send two line buffers in turn, so the
+//! output never stops, and the task draws the next line in one buffer while the other is being
+//! sent: the task only has to keep up with the lines, and runs with the executor like any other.
+
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, Peripheral, PeripheralRef};
+
+use crate::dma::{AnyChannel, Channel, CHANNEL_WAKERS};
+use crate::pac::dma::vals::DataSize;
+use crate::pio::{Common, Config, Direction, FifoJoin, Instance, Pin, ShiftConfig, ShiftDirection, StateMachine};
+use crate::relocate::RelocatedProgram;
+
+/// Video timing, in pixels and lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timing {
+    pub pixel_freq: u32,
+    pub h_active: u16,
+    pub h_front_porch: u16,
+    pub h_sync: u16,
+    pub h_back_porch: u16,
+    /// Sync pulse high, rather than low.
+    pub h_sync_positive: bool,
+    pub v_active: u16,
+    pub v_front_porch: u16,
+    pub v_sync: u16,
+    pub v_back_porch: u16,
+    /// Sync pulse high, rather than low.
+    pub v_sync_positive: bool,
+}
+
+impl Timing {
+    /// 640x480 at 60 Hz, from a 25.2 MHz pixel clock rather than 25.175 MHz, which monitors
+    /// accept.
+    pub const VGA_640X480_60: Self = Self {
+        pixel_freq: 25_200_000,
+        h_active: 640,
+        h_front_porch: 16,
+        h_sync: 96,
+        h_back_porch: 48,
+        h_sync_positive: false,
+        v_active: 480,
+        v_front_porch: 10,
+        v_sync: 2,
+        v_back_porch: 33,
+        v_sync_positive: false,
+    };
+
+    /// Pixels of a line, including the blanking.
+    pub const fn h_total(&self) -> usize {
+        (self.h_active + self.h_front_porch + self.h_sync + self.h_back_porch) as usize
+    }
+
+    /// Lines of a frame, including the blanking.
+    pub const fn v_total(&self) -> usize {
+        (self.v_active + self.v_front_porch + self.v_sync + self.v_back_porch) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// A line wasn't ready in time: the output restarted from the top of a frame.
+    Underrun,
+}
+
+/// VGA output, with lines of `W` pixels including the blanking.
+///
+/// Each pixel is a `u16` holding its color in the low bits, one per color pin, from the least
+/// significant one: the bits above are the sync signals, and must be 0.
+pub struct Vga<'d, P: Instance, const S: usize, const W: usize> {
+    sm: StateMachine<'d, P, S>,
+    dma: [PeripheralRef<'d, AnyChannel>; 2],
+    buffers: &'d mut [[u16; W]; 2],
+    timing: Timing,
+    /// Position of the HSYNC bit in the pixels, VSYNC being the next one.
+    sync_shift: usize,
+    /// Buffer to draw the next line in.
+    next: usize,
+    /// Number of the next line to draw.
+    line: usize,
+}
+
+impl<'d, P: Instance, const S: usize, const W: usize> Vga<'d, P, S, W> {
+    /// Load the program in the instruction memory of `pio`, and start `sm` driving `pins`: the
+    /// color pins from the least significant bit, then HSYNC, then VSYNC, which must be
+    /// consecutive.
+    ///
+    /// `W` must be the [`h_total`](Timing::h_total) of `timing`.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        mut sm: StateMachine<'d, P, S>,
+        dma0: impl Peripheral<P = impl Channel> + 'd,
+        dma1: impl Peripheral<P = impl Channel> + 'd,
+        pins: &mut [Pin<'d, P>],
+        buffers: &'d mut [[u16; W]; 2],
+        timing: Timing,
+    ) -> Self {
+        into_ref!(dma0, dma1);
+
+        assert_eq!(W, timing.h_total());
+        assert!(W % 2 == 0, "lines must have an even number of pixels");
+        assert!(pins.len() >= 3 && pins.len() <= 16);
+        for (p1, p2) in pins.iter().zip(pins.iter().skip(1)) {
+            assert!(p1.pin() + 1 == p2.pin(), "pins must be consecutive");
+        }
+
+        // The pixels hold 1 while the sync pulses last: invert the pins of negative pulses.
+        let sync_shift = pins.len() - 2;
+        pins[sync_shift].set_output_inversion(!timing.h_sync_positive);
+        pins[sync_shift + 1].set_output_inversion(!timing.v_sync_positive);
+
+        let mut a: pio::Assembler<32> = pio::Assembler::new();
+        let mut wrap_target = a.label();
+        let mut wrap_source = a.label();
+        a.bind(&mut wrap_target);
+        a.out(pio::OutDestination::PINS, 16);
+        a.bind(&mut wrap_source);
+
+        let prg = a.assemble_with_wrap(wrap_source, wrap_target);
+        let mut cfg = Config::default();
+
+        cfg.set_out_pins(&[&pins[0]]);
+        let mut pin_cfg = cfg.get_pins();
+        pin_cfg.out_count = pins.len() as u8;
+        unsafe { cfg.set_pins(pin_cfg) };
+        for pin in pins.iter() {
+            sm.set_pin_dirs(Direction::Out, &[pin]);
+        }
+
+        let relocated = RelocatedProgram::new(&prg);
+        cfg.use_program(&pio.load_program(&relocated), &[]);
+
+        cfg.set_frequency(timing.pixel_freq);
+
+        // Two pixels per word, the first one in the low half.
+        cfg.fifo_join = FifoJoin::TxOnly;
+        cfg.shift_out = ShiftConfig {
+            auto_fill: true,
+            threshold: 32,
+            direction: ShiftDirection::Right,
+        };
+
+        sm.set_config(&cfg);
+
+        let mut this = Self {
+            sm,
+            dma: [dma0.map_into(), dma1.map_into()],
+            buffers,
+            timing,
+            sync_shift,
+            next: 0,
+            line: 0,
+        };
+        this.start();
+        this
+    }
+
+    /// Start the output from the top of a frame, the first two lines black.
+    fn start(&mut self) {
+        self.sm.set_enable(false);
+        self.sm.restart();
+        self.sm.clear_fifos();
+
+        for (n, buffer) in self.buffers.iter_mut().enumerate() {
+            draw_blanking(buffer, &self.timing, self.sync_shift, n);
+            buffer[..self.timing.h_active as usize].fill(0);
+        }
+
+        let fifo = self.sm.tx().fifo_addr();
+        let dreq = self.sm.tx().dreq();
+        // Configure the second channel first, without triggering it.
+        for n in [1, 0] {
+            let p = self.dma[n].regs();
+            p.read_addr().write_value(self.buffers[n].as_ptr() as u32);
+            p.write_addr().write_value(fifo as u32);
+            p.trans_count().write_value(W as u32 / 2);
+
+            let mut w = crate::pac::dma::regs::CtrlTrig(0);
+            // TODO: Add all DREQ options to pac vals::TreqSel, and use
+            // `set_treq:sel`
+            w.0 = ((dreq as u32) & 0x3f) << 15usize;
+            w.set_data_size(DataSize::SIZE_WORD);
+            w.set_incr_read(true);
+            w.set_incr_write(false);
+            // Each channel triggers the other when it completes.
+            w.set_chain_to(self.dma[1 - n].number());
+            w.set_en(true);
+
+            compiler_fence(Ordering::SeqCst);
+
+            if n == 1 {
+                p.al1_ctrl().write_value(w.0);
+            } else {
+                p.ctrl_trig().write_value(w);
+            }
+        }
+
+        compiler_fence(Ordering::SeqCst);
+
+        self.next = 0;
+        self.line = 2;
+        self.sm.set_enable(true);
+    }
+
+    /// Wait for the buffer of the next line to be free, draw its blanking, and return its number.
+    async fn advance(&mut self) -> Result<usize, Error> {
+        let n = self.next;
+        let ch = self.dma[n].regs();
+        let number = self.dma[n].number() as usize;
+        poll_fn(|cx| {
+            CHANNEL_WAKERS[number].register(cx.waker());
+            if ch.ctrl_trig().read().busy() {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await;
+
+        // Both channels idle: the output stopped.
+        if !self.dma[1 - n].regs().ctrl_trig().read().busy() {
+            self.abort();
+            self.start();
+            return Err(Error::Underrun);
+        }
+
+        let line = self.line;
+        self.line = (line + 1) % self.timing.v_total();
+        self.next = 1 - n;
+
+        let buffer = &mut self.buffers[n];
+        draw_blanking(buffer, &self.timing, self.sync_shift, line);
+        // The channel sends the buffer again once the other one completes.
+        ch.read_addr().write_value(buffer.as_ptr() as u32);
+
+        Ok(line)
+    }
+
+    /// Wait for a line buffer to be free, and return the number of the next visible line, with
+    /// its pixels to draw.
+    ///
+    /// The line must be drawn before the previous one is sent, within the time of a line:
+    /// otherwise the next call returns [`Error::Underrun`], after restarting the output.
+    pub async fn next_line(&mut self) -> Result<(usize, &mut [u16]), Error> {
+        loop {
+            let line = self.advance().await?;
+            if line < self.timing.v_active as usize {
+                let n = 1 - self.next;
+                return Ok((line, &mut self.buffers[n][..self.timing.h_active as usize]));
+            }
+        }
+    }
+
+    /// Wait for the start of the vertical sync, sending black lines until then.
+    pub async fn wait_for_vsync(&mut self) -> Result<(), Error> {
+        let v_sync_start = (self.timing.v_active + self.timing.v_front_porch) as usize;
+        loop {
+            let line = self.advance().await?;
+            if line < self.timing.v_active as usize {
+                let n = 1 - self.next;
+                self.buffers[n][..self.timing.h_active as usize].fill(0);
+            } else if line == v_sync_start {
+                return Ok(());
+            }
+        }
+    }
+
+    fn abort(&mut self) {
+        let mask = 1 << self.dma[0].number() | 1 << self.dma[1].number();
+        crate::pac::DMA.chan_abort().modify(|m| m.set_chan_abort(mask));
+        while self.dma.iter().any(|ch| ch.regs().ctrl_trig().read().busy()) {}
+    }
+}
+
+impl<'d, P: Instance, const S: usize, const W: usize> Drop for Vga<'d, P, S, W> {
+    fn drop(&mut self) {
+        self.sm.set_enable(false);
+        self.abort();
+    }
+}
+
+/// Draw the sync signals of `line` in `buffer`, and the blanking: the whole line if it isn't
+/// visible, or the pixels after the visible ones otherwise.
+fn draw_blanking(buffer: &mut [u16], timing: &Timing, sync_shift: usize, line: usize) {
+    let v_sync_start = (timing.v_active + timing.v_front_porch) as usize;
+    let v_sync = (v_sync_start..v_sync_start + timing.v_sync as usize).contains(&line);
+    let blank = (v_sync as u16) << (sync_shift + 1);
+    let h_sync = blank | 1 << sync_shift;
+
+    let h_sync_start = (timing.h_active + timing.h_front_porch) as usize;
+    let h_sync_end = h_sync_start + timing.h_sync as usize;
+    if line >= timing.v_active as usize {
+        buffer[..h_sync_start].fill(blank);
+    } else {
+        buffer[timing.h_active as usize..h_sync_start].fill(blank);
+    }
+    buffer[h_sync_start..h_sync_end].fill(h_sync);
+    buffer[h_sync_end..].fill(blank);
+}
@@ -0,0 +1,119 @@
+//! WS2812 (NeoPixel) addressable LED driver, on a PIO state machine.
+//!
+//! The colors are sent by DMA, so that the timing of the bits doesn't depend on the executor.
+
+use embassy_hal_common::{into_ref, Peripheral, PeripheralRef};
+use embassy_time::{Duration, Instant, Timer};
+pub use smart_leds::RGB8;
+
+use crate::dma::{AnyChannel, Channel};
+use crate::pio::{Common, Config, FifoJoin, Instance, PioPin, ShiftConfig, ShiftDirection, StateMachine};
+use crate::relocate::RelocatedProgram;
+
+/// Bit rate of the LEDs.
+const BIT_FREQ: u32 = 800_000;
+
+// Cycles of each part of a bit: high for T1 cycles, then high or low for T2 cycles depending on
+// the bit, then low for T3 cycles.
+const T1: u8 = 2;
+const T2: u8 = 5;
+const T3: u8 = 3;
+const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+/// Time to send the words still in the joined TX FIFO once the DMA transfer completes, one more
+/// in the output shift register, plus the low time latching the colors, of up to 280 us on recent
+/// LEDs, in microseconds.
+const LATCH_US: u64 = 9 * 30 + 300;
+
+/// WS2812 driver, for a string of up to `N` LEDs.
+pub struct Ws2812<'d, P: Instance, const S: usize, const N: usize> {
+    dma: PeripheralRef<'d, AnyChannel>,
+    sm: StateMachine<'d, P, S>,
+    words: [u32; N],
+    latched_at: Instant,
+}
+
+impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
+    /// Load the program in the instruction memory of `pio`, and start `sm` driving the data
+    /// input of the string on `pin`.
+    pub fn new(
+        pio: &mut Common<'d, P>,
+        mut sm: StateMachine<'d, P, S>,
+        dma: impl Peripheral<P = impl Channel> + 'd,
+        pin: impl Peripheral<P = impl PioPin + 'd> + 'd,
+    ) -> Self {
+        into_ref!(dma);
+
+        let side_set = pio::SideSet::new(false, 1, false);
+        let mut a: pio::Assembler<32> = pio::Assembler::new_with_side_set(side_set);
+
+        let mut wrap_target = a.label();
+        let mut wrap_source = a.label();
+        let mut do_zero = a.label();
+        a.set_with_side_set(pio::SetDestination::PINDIRS, 1, 0);
+        a.bind(&mut wrap_target);
+        // Do stop bit
+        a.out_with_delay_and_side_set(pio::OutDestination::X, 1, T3 - 1, 0);
+        // Do start bit
+        a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, T1 - 1, 1);
+        // Do data bit = 1
+        a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut wrap_target, T2 - 1, 1);
+        a.bind(&mut do_zero);
+        // Do data bit = 0
+        a.nop_with_delay_and_side_set(T2 - 1, 0);
+        a.bind(&mut wrap_source);
+
+        let prg = a.assemble_with_wrap(wrap_source, wrap_target);
+        let mut cfg = Config::default();
+
+        let out_pin = pio.make_pio_pin(pin);
+        cfg.set_out_pins(&[&out_pin]);
+        cfg.set_set_pins(&[&out_pin]);
+
+        let relocated = RelocatedProgram::new(&prg);
+        cfg.use_program(&pio.load_program(&relocated), &[&out_pin]);
+
+        cfg.set_frequency(BIT_FREQ * CYCLES_PER_BIT);
+
+        // The 24 bits of a color are sent from the most significant bit of the words.
+        cfg.fifo_join = FifoJoin::TxOnly;
+        cfg.shift_out = ShiftConfig {
+            auto_fill: true,
+            threshold: 24,
+            direction: ShiftDirection::Left,
+        };
+
+        sm.set_config(&cfg);
+        sm.set_enable(true);
+
+        Self {
+            dma: dma.map_into(),
+            sm,
+            words: [0; N],
+            latched_at: Instant::now(),
+        }
+    }
+
+    /// Set the colors of the first `colors.len()` LEDs of the string, at most `N`.
+    ///
+    /// Returns once the colors are sent, the LEDs taking them a few hundred microseconds later:
+    /// the next write waits for that.
+    pub async fn write(&mut self, colors: &[RGB8]) {
+        assert!(colors.len() <= N);
+
+        // Wait for the LEDs to latch the previous colors.
+        Timer::at(self.latched_at).await;
+
+        // The LEDs take the green, then the red, then the blue byte.
+        for (word, color) in self.words.iter_mut().zip(colors) {
+            *word = (u32::from(color.g) << 24) | (u32::from(color.r) << 16) | (u32::from(color.b) << 8);
+        }
+
+        self.sm
+            .tx()
+            .dma_push(self.dma.reborrow(), &self.words[..colors.len()])
+            .await;
+
+        self.latched_at = Instant::now() + Duration::from_micros(LATCH_US);
+    }
+}
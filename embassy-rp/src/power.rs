@@ -0,0 +1,143 @@
+//! Low-power modes
+//!
+//! [`sleep`] runs the system clock from the reference clock, with the system PLL powered down
+//! unless another clock runs from it, until an interrupt: the timer, the RTC, USB and the peripherals clocked from the USB PLL keep
+//! running, so the time driver keeps the time and its alarms wake the core. The peripherals
+//! clocked from the system clock, by default including the UARTs and SPIs, run slower meanwhile.
+//!
+//! [`dormant_until`] stops all the oscillators until a GPIO wakes the chip, the lowest power
+//! mode: the timer stops too, so the time driver doesn't count the time spent dormant, and its
+//! alarms are delayed by it.
+
+use embassy_hal_common::into_ref;
+use pac::clocks::vals::{
+    ClkAdcCtrlAuxsrc, ClkPeriCtrlAuxsrc, ClkRefCtrlSrc, ClkRtcCtrlAuxsrc, ClkSysCtrlSrc, ClkUsbCtrlAuxsrc,
+};
+
+use crate::gpio::sealed::Pin as _;
+use crate::gpio::{Bank, Input, InterruptTrigger, Pin, Pull};
+use crate::{pac, Peripheral};
+
+/// Run the system clock from the reference clock, with the system PLL powered down, until an
+/// interrupt is pending.
+///
+/// This blocks: call it when all the tasks wait for an interrupt, the interrupt handlers
+/// running once the system clock is restored.
+pub fn sleep() {
+    critical_section::with(|_| {
+        let c = pac::CLOCKS;
+        let sys_ctrl = c.clk_sys_ctrl().read();
+        c.clk_sys_ctrl().modify(|w| w.set_src(ClkSysCtrlSrc::CLK_REF));
+        while c.clk_sys_selected().read() != 1 {}
+
+        let pll = pac::PLL_SYS;
+        let pwr = pll.pwr().read();
+        // Keep the system PLL running if other clocks run from it.
+        let pll_in_use = {
+            let peri = c.clk_peri_ctrl().read();
+            let usb = c.clk_usb_ctrl().read();
+            let adc = c.clk_adc_ctrl().read();
+            let rtc = c.clk_rtc_ctrl().read();
+            (peri.enable() && peri.auxsrc() == ClkPeriCtrlAuxsrc::CLKSRC_PLL_SYS)
+                || (usb.enable() && usb.auxsrc() == ClkUsbCtrlAuxsrc::CLKSRC_PLL_SYS)
+                || (adc.enable() && adc.auxsrc() == ClkAdcCtrlAuxsrc::CLKSRC_PLL_SYS)
+                || (rtc.enable() && rtc.auxsrc() == ClkRtcCtrlAuxsrc::CLKSRC_PLL_SYS)
+        };
+        if !pll_in_use {
+            pll.pwr().write(|w| {
+                w.set_pd(true);
+                w.set_dsmpd(true);
+                w.set_postdivpd(true);
+                w.set_vcopd(true);
+            });
+        }
+
+        // Interrupts are masked, but still wake the core: their handlers run once the critical
+        // section ends.
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+
+        if !pll_in_use {
+            pll.pwr().write_value(pwr);
+            if !pwr.pd() {
+                while !pll.cs().read().lock() {}
+            }
+        }
+
+        c.clk_sys_ctrl().write_value(sys_ctrl);
+        while c.clk_sys_selected().read() != 1 << sys_ctrl.src() as u32 {}
+    });
+}
+
+/// Stop all the oscillators until `pin` triggers a wake-up, as configured by `trigger`.
+///
+/// The pin is an input with `pull` meanwhile. The reference clock must run from the XOSC or the
+/// ROSC, as configured by [`ClockConfig::crystal`](crate::clocks::ClockConfig::crystal) and
+/// [`ClockConfig::rosc`](crate::clocks::ClockConfig::rosc): the PLLs running from it lock again,
+/// and the clocks are restored, before this returns.
+pub fn dormant_until<T: Pin>(pin: impl Peripheral<P = T>, pull: Pull, trigger: InterruptTrigger) {
+    into_ref!(pin);
+    assert!(pin._bank() == Bank::Bank0, "only the pins of bank 0 wake the chip");
+
+    let number = pin.pin() as usize;
+    let group = number / 8;
+    let pin_group = number % 8;
+
+    let _input = Input::new(pin, pull);
+
+    critical_section::with(|_| {
+        let c = pac::CLOCKS;
+        let ref_src = c.clk_ref_ctrl().read().src();
+        let xosc = match ref_src {
+            ClkRefCtrlSrc::XOSC_CLKSRC => true,
+            ClkRefCtrlSrc::ROSC_CLKSRC_PH => false,
+            _ => panic!("The reference clock must run from an oscillator to go dormant"),
+        };
+
+        // Run the system clock from the oscillator, the PLLs losing their lock while it's stopped.
+        let sys_ctrl = c.clk_sys_ctrl().read();
+        c.clk_sys_ctrl().modify(|w| w.set_src(ClkSysCtrlSrc::CLK_REF));
+        while c.clk_sys_selected().read() != 1 {}
+
+        // Clear the stale edges, and enable the wake-up.
+        let io = pac::IO_BANK0;
+        let clear_edges = || {
+            io.intr(group).write(|w| {
+                w.set_edge_high(pin_group, true);
+                w.set_edge_low(pin_group, true);
+            })
+        };
+        clear_edges();
+        io.int_dormant_wake().inte(group).write(|w| match trigger {
+            InterruptTrigger::LevelLow => w.set_level_low(pin_group, true),
+            InterruptTrigger::LevelHigh => w.set_level_high(pin_group, true),
+            InterruptTrigger::EdgeLow => w.set_edge_low(pin_group, true),
+            InterruptTrigger::EdgeHigh => w.set_edge_high(pin_group, true),
+            InterruptTrigger::AnyEdge => {
+                w.set_edge_low(pin_group, true);
+                w.set_edge_high(pin_group, true);
+            }
+        });
+
+        // Execution stops on this write, and resumes on the wake-up.
+        if xosc {
+            pac::XOSC.dormant().write_value(pac::xosc::vals::Dormant::DORMANT);
+            while !pac::XOSC.status().read().stable() {}
+        } else {
+            pac::ROSC.dormant().write_value(pac::rosc::vals::Dormant::DORMANT);
+            while !pac::ROSC.status().read().stable() {}
+        }
+
+        io.int_dormant_wake().inte(group).write_value(pac::io::regs::Int(0));
+        clear_edges();
+
+        for pll in [pac::PLL_SYS, pac::PLL_USB] {
+            if !pll.pwr().read().pd() {
+                while !pll.cs().read().lock() {}
+            }
+        }
+
+        c.clk_sys_ctrl().write_value(sys_ctrl);
+        while c.clk_sys_selected().read() != 1 << sys_ctrl.src() as u32 {}
+    });
+}
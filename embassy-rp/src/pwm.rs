@@ -1,6 +1,7 @@
 //! Pulse Width Modulation (PWM)
 
 use embassy_hal_common::{into_ref, Peripheral, PeripheralRef};
+use embassy_time::{Duration, Instant, Timer};
 use fixed::traits::ToFixed;
 use fixed::FixedU16;
 use pac::pwm::regs::{ChDiv, Intr};
@@ -213,6 +214,58 @@ impl<'d, T: Channel> Pwm<'d, T> {
         pac::PWM.intr().write_value(Intr(self.bit() as _));
     }
 
+    /// Count the input during `gate`: its edges in the edge-sensitive modes, or the cycles it is
+    /// high in the level-sensitive mode, both divided by the divider. Returns the count and the
+    /// time it took, or `None` if the counter wrapped at `top`.
+    pub async fn count_during(&mut self, gate: Duration) -> Option<(u16, Duration)> {
+        self.set_counter(0);
+        self.clear_wrapped();
+        let start = Instant::now();
+        Timer::after(gate).await;
+        let count = self.counter();
+        let elapsed = start.elapsed();
+        if self.wrapped() {
+            return None;
+        }
+        Some((count, elapsed))
+    }
+
+    /// Measure the frequency of the input, in Hz, counting its edges during `gate`.
+    ///
+    /// The PWM must be created with [`InputMode::RisingEdge`] or [`InputMode::FallingEdge`]. The
+    /// counter must not wrap during `gate`: for high frequencies, shorten it or raise the divider.
+    /// Returns `None` if it wrapped.
+    pub async fn measure_frequency(&mut self, gate: Duration) -> Option<f32> {
+        let divmode = self.inner.regs().csr().read().divmode();
+        assert!(divmode == Divmode::RISE || divmode == Divmode::FALL);
+
+        let (count, elapsed) = self.count_during(gate).await?;
+        let edges = count as f32 * self.divider();
+        Some(edges * 1_000_000.0 / elapsed.as_micros().max(1) as f32)
+    }
+
+    /// Measure the duty cycle of the input, between 0 and 1, counting the system clock cycles it
+    /// is high during `gate`.
+    ///
+    /// The PWM must be created with [`InputMode::Level`]. The counter must not wrap during
+    /// `gate`: at the full system clock, it does after about half a millisecond, so raise the
+    /// divider for longer gates. Returns `None` if it wrapped.
+    pub async fn measure_duty_cycle(&mut self, gate: Duration) -> Option<f32> {
+        assert!(self.inner.regs().csr().read().divmode() == Divmode::LEVEL);
+
+        let (count, elapsed) = self.count_during(gate).await?;
+        let high = count as f32 * self.divider();
+        let total = crate::clocks::clk_sys_freq() as f32 * elapsed.as_micros() as f32 / 1_000_000.0;
+        Some((high / total).min(1.0))
+    }
+
+    /// The divider, read back from the hardware, an integer part of 0 dividing by 256.
+    fn divider(&self) -> f32 {
+        let div = self.inner.regs().div().read().0 & 0xfff;
+        let div = if div < 0x10 { div + 0x1000 } else { div };
+        div as f32 / 16.0
+    }
+
     #[inline]
     fn bit(&self) -> u32 {
         1 << self.inner.number() as usize
@@ -270,6 +270,25 @@ rom_functions! {
     b"WV" unsafe fn wait_for_vector() -> !;
 }
 
+pub use crate::intrinsics::DivResult;
+
+/// Divide `n` by `d` with the hardware divider of the SIO, returning both the quotient and the
+/// remainder in 8 cycles. The state of a division interrupted by this one is saved and restored.
+///
+/// Dividing by 0 returns a quotient of `u32::MAX` and a remainder of `n`.
+pub fn divide_unsigned(n: u32, d: u32) -> DivResult<u32> {
+    crate::intrinsics::divider_unsigned(n, d)
+}
+
+/// Divide `n` by `d` with the hardware divider of the SIO, returning both the quotient and the
+/// remainder in 8 cycles. The state of a division interrupted by this one is saved and restored.
+///
+/// Dividing by 0 returns a quotient of -1 if `n` is positive or 0, and 1 otherwise, and a remainder of
+/// `n`.
+pub fn divide_signed(n: i32, d: i32) -> DivResult<i32> {
+    crate::intrinsics::divider_signed(n, d)
+}
+
 // Various C intrinsics in the ROM
 intrinsics! {
     #[alias = __popcountdi2]
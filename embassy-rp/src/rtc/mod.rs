@@ -1,6 +1,11 @@
 mod filter;
 
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
 use embassy_hal_common::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
 
 pub use self::filter::DateTimeFilter;
 
@@ -10,6 +15,11 @@ mod datetime;
 
 pub use self::datetime::{DateTime, DayOfWeek, Error as DateTimeError};
 use crate::clocks::clk_rtc_freq;
+use crate::interrupt::typelevel::Binding;
+use crate::interrupt::InterruptExt;
+use crate::{interrupt, pac};
+
+static WAKER: AtomicWaker = AtomicWaker::new();
 
 /// A reference to the real time clock of the system
 pub struct RealTimeClock<'d, T: Instance> {
@@ -22,12 +32,19 @@ impl<'d, T: Instance> RealTimeClock<'d, T> {
     /// # Errors
     ///
     /// Will return `RtcError::InvalidDateTime` if the datetime is not a valid range.
-    pub fn new(inner: impl Peripheral<P = T> + 'd, initial_date: DateTime) -> Result<Self, RtcError> {
+    pub fn new(
+        inner: impl Peripheral<P = T> + 'd,
+        _irq: impl Binding<interrupt::typelevel::RTC_IRQ, InterruptHandler>,
+        initial_date: DateTime,
+    ) -> Result<Self, RtcError> {
         into_ref!(inner);
 
         // Set the RTC divider
         inner.regs().clkdiv_m1().write(|w| w.set_clkdiv_m1(clk_rtc_freq() - 1));
 
+        interrupt::RTC_IRQ.unpend();
+        unsafe { interrupt::RTC_IRQ.enable() };
+
         let mut result = Self { inner };
         result.set_leap_year_check(true); // should be on by default, make sure this is the case.
         result.set_datetime(initial_date)?;
@@ -141,13 +158,59 @@ impl<'d, T: Instance> RealTimeClock<'d, T> {
         }
     }
 
-    /// Clear the interrupt. This should be called every time the `RTC_IRQ` interrupt is triggered,
-    /// or the next [`schedule_alarm`] will never fire.
+    /// Clear the interrupt. This should be called every time the alarm fires, or the next
+    /// [`schedule_alarm`] will never fire.
     ///
     /// [`schedule_alarm`]: #method.schedule_alarm
     pub fn clear_interrupt(&mut self) {
         self.disable_alarm();
     }
+
+    /// Check if the alarm scheduled with [`schedule_alarm`] fired, and hasn't been cleared.
+    ///
+    /// [`schedule_alarm`]: #method.schedule_alarm
+    pub fn alarm_fired(&self) -> bool {
+        self.inner.regs().intr().read().rtc()
+    }
+
+    /// Schedule an alarm with `filter`, and wait for it to fire.
+    ///
+    /// The alarm fires when the fields set in the filter match the current datetime, and keeps
+    /// matching until one of them changes: for example, a filter on the minute only matches during
+    /// that whole minute. The alarm is disabled once it fired, so waiting again with the same filter
+    /// while it still matches returns right away.
+    pub async fn wait_for_alarm(&mut self, filter: DateTimeFilter) {
+        self.schedule_alarm(filter);
+
+        let regs = self.inner.regs();
+        let on_drop = OnDrop::new(|| regs.irq_setup_0().modify(|w| w.set_match_ena(false)));
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if regs.intr().read().rtc() {
+                return Poll::Ready(());
+            }
+            regs.inte().modify(|w| w.set_rtc(true));
+            Poll::Pending
+        })
+        .await;
+
+        on_drop.defuse();
+        self.disable_alarm();
+    }
+}
+
+/// RTC interrupt handler.
+pub struct InterruptHandler {
+    _empty: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RTC_IRQ> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        // The interrupt stays pending while the alarm matches, it's cleared by disabling the alarm.
+        pac::RTC.inte().modify(|w| w.set_rtc(false));
+        WAKER.wake();
+    }
 }
 
 /// Errors that can occur on methods on [RealTimeClock]
@@ -48,6 +48,16 @@ fn div_roundup(a: u32, b: u32) -> u32 {
 }
 
 fn calc_prescs(freq: u32) -> (u8, u8) {
+    match try_calc_prescs(freq) {
+        Some(prescs) => prescs,
+        None => panic!("Requested too low SPI frequency"),
+    }
+}
+
+fn try_calc_prescs(freq: u32) -> Option<(u8, u8)> {
+    if freq == 0 {
+        return None;
+    }
     let clk_peri = crate::clocks::clk_peri_freq();
 
     // final SPI frequency: spi_freq = clk_peri / presc / postdiv
@@ -57,13 +67,13 @@ fn calc_prescs(freq: u32) -> (u8, u8) {
     // divide extra by 2, so we get rid of the "presc must be even" requirement
     let ratio = div_roundup(clk_peri, freq * 2);
     if ratio > 127 * 256 {
-        panic!("Requested too low SPI frequency");
+        return None;
     }
 
     let presc = div_roundup(ratio, 256);
     let postdiv = if presc == 1 { ratio } else { div_roundup(ratio, presc) };
 
-    ((presc * 2) as u8, (postdiv - 1) as u8)
+    Some(((presc * 2) as u8, (postdiv - 1) as u8))
 }
 
 impl<'d, T: Instance, M: Mode> Spi<'d, T, M> {
@@ -431,6 +441,7 @@ mod sealed {
         const RX_DREQ: u8;
 
         fn regs(&self) -> pac::spi::Spi;
+        fn reset() -> pac::resets::regs::Peripherals;
     }
 }
 
@@ -438,7 +449,7 @@ pub trait Mode: sealed::Mode {}
 pub trait Instance: sealed::Instance {}
 
 macro_rules! impl_instance {
-    ($type:ident, $irq:ident, $tx_dreq:expr, $rx_dreq:expr) => {
+    ($type:ident, $irq:ident, $reset:ident, $tx_dreq:expr, $rx_dreq:expr) => {
         impl sealed::Instance for peripherals::$type {
             const TX_DREQ: u8 = $tx_dreq;
             const RX_DREQ: u8 = $rx_dreq;
@@ -446,13 +457,19 @@ macro_rules! impl_instance {
             fn regs(&self) -> pac::spi::Spi {
                 pac::$type
             }
+
+            fn reset() -> pac::resets::regs::Peripherals {
+                let mut ret = pac::resets::regs::Peripherals::default();
+                ret.$reset(true);
+                ret
+            }
         }
         impl Instance for peripherals::$type {}
     };
 }
 
-impl_instance!(SPI0, Spi0, 16, 17);
-impl_instance!(SPI1, Spi1, 18, 19);
+impl_instance!(SPI0, Spi0, set_spi0, 16, 17);
+impl_instance!(SPI1, Spi1, set_spi1, 18, 19);
 
 pub trait ClkPin<T: Instance>: GpioPin {}
 pub trait CsPin<T: Instance>: GpioPin {}
@@ -597,9 +614,15 @@ mod eha {
 
 impl<'d, T: Instance, M: Mode> SetConfig for Spi<'d, T, M> {
     type Config = Config;
-    fn set_config(&mut self, config: &Self::Config) {
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
         let p = self.inner.regs();
-        let (presc, postdiv) = calc_prescs(config.frequency);
+        // The frequency is too low for the dividers.
+        let (presc, postdiv) = try_calc_prescs(config.frequency).ok_or(())?;
+
+        // The format only changes while the SSP is disabled, which shared bus devices rely on
+        // when switching between their configurations.
+        p.cr1().modify(|w| w.set_sse(false));
         p.cpsr().write(|w| w.set_cpsdvsr(presc));
         p.cr0().write(|w| {
             w.set_dss(0b0111); // 8bit
@@ -607,5 +630,7 @@ impl<'d, T: Instance, M: Mode> SetConfig for Spi<'d, T, M> {
             w.set_sph(config.phase == Phase::CaptureOnSecondTransition);
             w.set_scr(postdiv);
         });
+        p.cr1().modify(|w| w.set_sse(true));
+        Ok(())
     }
 }
@@ -0,0 +1,188 @@
+//! Serial Peripheral Interface, slave mode
+//!
+//! A transfer is prepared before the master selects the slave: the DMA channels stream the
+//! received bytes to the read buffer, and the bytes of the write buffer to the SPI. It completes
+//! when the master deselects the slave.
+//!
+//! In the motorola format with [`Phase::CaptureOnFirstTransition`], the SSP requires the chip
+//! select to be deasserted between bytes, so the default configuration captures on the second
+//! transition, letting the master keep it asserted for the whole transfer.
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+pub use embedded_hal_02::spi::{Phase, Polarity};
+
+use crate::dma::{AnyChannel, Channel};
+use crate::gpio::sealed::Pin as _;
+use crate::gpio::{AnyPin, InputFuture, InterruptTrigger};
+use crate::spi::{ClkPin, CsPin, Instance, MisoPin, MosiPin};
+use crate::Peripheral;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The master transferred more bytes than fit in the read buffer.
+    Overrun,
+}
+
+#[non_exhaustive]
+#[derive(Clone)]
+pub struct Config {
+    pub phase: Phase,
+    pub polarity: Polarity,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            phase: Phase::CaptureOnSecondTransition,
+            polarity: Polarity::IdleHigh,
+        }
+    }
+}
+
+pub struct SpiSlave<'d, T: Instance> {
+    inner: PeripheralRef<'d, T>,
+    cs: PeripheralRef<'d, AnyPin>,
+    tx_dma: PeripheralRef<'d, AnyChannel>,
+    rx_dma: PeripheralRef<'d, AnyChannel>,
+    config: Config,
+}
+
+impl<'d, T: Instance> SpiSlave<'d, T> {
+    pub fn new(
+        inner: impl Peripheral<P = T> + 'd,
+        clk: impl Peripheral<P = impl ClkPin<T> + 'd> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T> + 'd> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T> + 'd> + 'd,
+        cs: impl Peripheral<P = impl CsPin<T> + 'd> + 'd,
+        tx_dma: impl Peripheral<P = impl Channel> + 'd,
+        rx_dma: impl Peripheral<P = impl Channel> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(inner, clk, mosi, miso, cs, tx_dma, rx_dma);
+
+        clk.io().ctrl().write(|w| w.set_funcsel(1));
+        mosi.io().ctrl().write(|w| w.set_funcsel(1));
+        miso.io().ctrl().write(|w| w.set_funcsel(1));
+        cs.io().ctrl().write(|w| w.set_funcsel(1));
+
+        let mut this = Self {
+            inner,
+            cs: cs.map_into(),
+            tx_dma: tx_dma.map_into(),
+            rx_dma: rx_dma.map_into(),
+            config,
+        };
+        this.configure();
+        this
+    }
+
+    /// Reset the SSP, dropping the contents of its FIFOs, and configure it as a slave.
+    fn configure(&mut self) {
+        let reset = T::reset();
+        crate::reset::reset(reset);
+        crate::reset::unreset_wait(reset);
+
+        let p = self.inner.regs();
+        p.cr0().write(|w| {
+            w.set_dss(0b0111); // 8bit
+            w.set_spo(self.config.polarity == Polarity::IdleHigh);
+            w.set_sph(self.config.phase == Phase::CaptureOnSecondTransition);
+        });
+
+        // Always enable DREQ signals -- harmless if DMA is not listening
+        p.dmacr().write(|reg| {
+            reg.set_rxdmae(true);
+            reg.set_txdmae(true);
+        });
+
+        // finally, enable as a slave.
+        p.cr1().write(|w| {
+            w.set_ms(true);
+            w.set_sse(true);
+        });
+    }
+
+    pub fn set_config(&mut self, config: &Config) {
+        self.config = config.clone();
+        self.configure();
+    }
+
+    /// Prepare a transfer, then wait for the master to select and deselect the slave.
+    ///
+    /// The bytes of `write` are sent, those sent after them if the master transfers more are
+    /// undefined. The received bytes are stored in `read`, and their number is returned, or
+    /// [`Error::Overrun`] if the master transferred more than `read.len()` bytes.
+    pub async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<usize, Error> {
+        // Drop the bytes left over from the previous transfer.
+        self.configure();
+
+        let p = self.inner.regs();
+        let deselected = InputFuture::new(self.cs.reborrow(), InterruptTrigger::EdgeHigh);
+
+        // Start RX first, so that no byte is lost.
+        let rx_transfer = unsafe {
+            crate::dma::read(
+                self.rx_dma.reborrow(),
+                p.dr().as_ptr() as *const u8,
+                &mut *read as *mut [u8],
+                T::RX_DREQ,
+            )
+        };
+        let tx_transfer = unsafe {
+            crate::dma::write(
+                self.tx_dma.reborrow(),
+                write as *const [u8],
+                p.dr().as_ptr() as *mut u8,
+                T::TX_DREQ,
+            )
+        };
+
+        deselected.await;
+
+        drop(tx_transfer);
+        drop(rx_transfer);
+
+        let mut received = read.len() - self.rx_dma.regs().trans_count().read() as usize;
+
+        // Collect the bytes the DMA channel didn't move yet.
+        let mut overrun = p.ris().read().rorris();
+        while p.sr().read().rne() {
+            let byte = p.dr().read().data() as u8;
+            match read.get_mut(received) {
+                Some(b) => {
+                    *b = byte;
+                    received += 1;
+                }
+                None => overrun = true,
+            }
+        }
+
+        if overrun {
+            return Err(Error::Overrun);
+        }
+        Ok(received)
+    }
+
+    /// Prepare a transfer receiving bytes in `read`, then wait for the master to select and
+    /// deselect the slave.
+    ///
+    /// Returns the number of bytes received, or [`Error::Overrun`] if the master transferred
+    /// more than `read.len()` bytes.
+    pub async fn read(&mut self, read: &mut [u8]) -> Result<usize, Error> {
+        self.transfer(read, &[]).await
+    }
+
+    /// Prepare a transfer sending the bytes of `write`, then wait for the master to select and
+    /// deselect the slave, ignoring the received bytes.
+    pub async fn write(&mut self, write: &[u8]) {
+        let _ = self.transfer(&mut [], write).await;
+    }
+}
+
+impl<'d, T: Instance> Drop for SpiSlave<'d, T> {
+    fn drop(&mut self) {
+        self.inner.regs().cr1().write(|w| w.set_sse(false));
+    }
+}
@@ -1,13 +1,23 @@
+//! Time driver, and hardware alarm
+//!
+//! The time driver uses alarms 0 to 2 of the TIMER, and leaves alarm 3 to [`Alarm`].
+
 use core::cell::Cell;
+use core::future::poll_fn;
+use core::task::Poll;
 
 use atomic_polyfill::{AtomicU8, Ordering};
 use critical_section::CriticalSection;
+use embassy_hal_common::{into_ref, PeripheralRef};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
 use embassy_time::driver::{AlarmHandle, Driver};
+use embassy_time::Instant;
 
+use crate::interrupt::typelevel::Binding;
 use crate::interrupt::InterruptExt;
-use crate::{interrupt, pac};
+use crate::{interrupt, pac, peripherals, Peripheral};
 
 struct AlarmState {
     timestamp: Cell<u64>,
@@ -15,7 +25,7 @@ struct AlarmState {
 }
 unsafe impl Send for AlarmState {}
 
-const ALARM_COUNT: usize = 4;
+const ALARM_COUNT: usize = 3;
 const DUMMY_ALARM: AlarmState = AlarmState {
     timestamp: Cell::new(0),
     callback: Cell::new(None),
@@ -136,17 +146,15 @@ pub unsafe fn init() {
         }
     });
 
-    // enable all irqs
+    // enable the irqs of the driver alarms
     pac::TIMER.inte().write(|w| {
         w.set_alarm(0, true);
         w.set_alarm(1, true);
         w.set_alarm(2, true);
-        w.set_alarm(3, true);
     });
     interrupt::TIMER_IRQ_0.enable();
     interrupt::TIMER_IRQ_1.enable();
     interrupt::TIMER_IRQ_2.enable();
-    interrupt::TIMER_IRQ_3.enable();
 }
 
 #[cfg(feature = "rt")]
@@ -167,8 +175,109 @@ fn TIMER_IRQ_2() {
     DRIVER.check_alarm(2)
 }
 
-#[cfg(feature = "rt")]
-#[interrupt]
-fn TIMER_IRQ_3() {
-    DRIVER.check_alarm(3)
+/// Alarm of the TIMER left to [`Alarm`].
+const USER_ALARM: usize = 3;
+
+static ALARM_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Hardware alarm, firing its interrupt at an exact time, independently of the time driver and
+/// of its queue.
+///
+/// Handlers bound to `TIMER_IRQ_3` after [`InterruptHandler`] run as soon as the alarm fires.
+pub struct Alarm<'d> {
+    _alarm: PeripheralRef<'d, peripherals::TIMER_ALARM3>,
+}
+
+impl<'d> Alarm<'d> {
+    pub fn new(
+        alarm: impl Peripheral<P = peripherals::TIMER_ALARM3> + 'd,
+        _irq: impl Binding<interrupt::typelevel::TIMER_IRQ_3, InterruptHandler>,
+    ) -> Self {
+        into_ref!(alarm);
+
+        pac::TIMER.armed().write(|w| w.set_armed(1 << USER_ALARM));
+        pac::TIMER.intr().write(|w| w.set_alarm(USER_ALARM, true));
+        critical_section::with(|_| {
+            pac::TIMER.inte().modify(|w| w.set_alarm(USER_ALARM, true));
+        });
+        interrupt::TIMER_IRQ_3.unpend();
+        unsafe { interrupt::TIMER_IRQ_3.enable() };
+
+        Self { _alarm: alarm }
+    }
+
+    /// Fire the alarm at `at`, which must be less than 2^32 us (about 71 minutes) away, replacing
+    /// the previous schedule. The alarm fires immediately if `at` has passed.
+    pub fn schedule_at(&mut self, at: Instant) {
+        let timestamp = at.as_ticks();
+        let now = DRIVER.now();
+        assert!(timestamp < now + (1 << 32), "alarm too far in the future");
+
+        // The alarm compares the low 32 bits of the time only.
+        pac::TIMER.alarm(USER_ALARM).write_value(timestamp as u32);
+
+        if timestamp <= DRIVER.now() {
+            // The time may have gone past the alarm before it was armed: fire it by hand.
+            pac::TIMER.armed().write(|w| w.set_armed(1 << USER_ALARM));
+            critical_section::with(|_| {
+                pac::TIMER.intf().modify(|w| w.set_alarm(USER_ALARM, true));
+            });
+        }
+    }
+
+    /// Disarm the alarm, if it hasn't fired yet.
+    pub fn cancel(&mut self) {
+        pac::TIMER.armed().write(|w| w.set_armed(1 << USER_ALARM));
+        critical_section::with(|_| {
+            pac::TIMER.intf().modify(|w| w.set_alarm(USER_ALARM, false));
+        });
+        pac::TIMER.intr().write(|w| w.set_alarm(USER_ALARM, true));
+    }
+
+    /// Whether the alarm is scheduled and didn't fire yet.
+    pub fn is_armed(&self) -> bool {
+        pac::TIMER.armed().read().armed() & (1 << USER_ALARM) != 0 || pac::TIMER.intf().read().alarm(USER_ALARM)
+    }
+
+    /// Wait for the alarm to fire, returning immediately if it isn't armed.
+    pub async fn wait(&mut self) {
+        poll_fn(|cx| {
+            ALARM_WAKER.register(cx.waker());
+            if self.is_armed() {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await
+    }
+
+    /// Fire the alarm at `at`, as [`schedule_at`](Self::schedule_at), and wait for it.
+    pub async fn wait_until(&mut self, at: Instant) {
+        self.schedule_at(at);
+        self.wait().await
+    }
+}
+
+impl<'d> Drop for Alarm<'d> {
+    fn drop(&mut self) {
+        self.cancel();
+        critical_section::with(|_| {
+            pac::TIMER.inte().modify(|w| w.set_alarm(USER_ALARM, false));
+        });
+        interrupt::TIMER_IRQ_3.disable();
+    }
+}
+
+/// Interrupt handler of [`Alarm`].
+pub struct InterruptHandler {
+    _empty: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::TIMER_IRQ_3> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        pac::TIMER.intf().modify(|w| w.set_alarm(USER_ALARM, false));
+        pac::TIMER.intr().write(|w| w.set_alarm(USER_ALARM, true));
+        ALARM_WAKER.wake();
+    }
 }
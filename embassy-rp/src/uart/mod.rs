@@ -3,6 +3,7 @@ use core::marker::PhantomData;
 use core::task::Poll;
 
 use atomic_polyfill::{AtomicU16, Ordering};
+use embassy_embedded_hal::SetConfig;
 use embassy_futures::select::{select, Either};
 use embassy_hal_common::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
@@ -609,6 +610,26 @@ impl<'d, T: Instance + 'd, M: Mode> Uart<'d, T, M> {
             pin.pad_ctrl().write(|w| w.set_ie(true));
         }
 
+        Self::set_format_inner(&config);
+
+        r.uartifls().write(|w| {
+            w.set_rxiflsel(0b000);
+            w.set_txiflsel(0b000);
+        });
+
+        r.uartcr().write(|w| {
+            w.set_uarten(true);
+            w.set_rxe(true);
+            w.set_txe(true);
+            w.set_ctsen(cts.is_some());
+            w.set_rtsen(rts.is_some());
+        });
+    }
+
+    /// Set the baudrate and the frame format, the UART being disabled.
+    fn set_format_inner(config: &Config) {
+        let r = T::regs();
+
         Self::set_baudrate_inner(config.baudrate);
 
         let (pen, eps) = match config.parity {
@@ -624,19 +645,6 @@ impl<'d, T: Instance + 'd, M: Mode> Uart<'d, T, M> {
             w.set_eps(eps);
             w.set_fen(true);
         });
-
-        r.uartifls().write(|w| {
-            w.set_rxiflsel(0b000);
-            w.set_txiflsel(0b000);
-        });
-
-        r.uartcr().write(|w| {
-            w.set_uarten(true);
-            w.set_rxe(true);
-            w.set_txe(true);
-            w.set_ctsen(cts.is_some());
-            w.set_rtsen(rts.is_some());
-        });
     }
 
     /// sets baudrate on runtime    
@@ -709,6 +717,25 @@ impl<'d, T: Instance> Uart<'d, T, Async> {
     }
 }
 
+/// Changes the baudrate and the frame format of the UART, after the pending transmission: the
+/// pin inversions stay as configured when created.
+impl<'d, T: Instance, M: Mode> SetConfig for Uart<'d, T, M> {
+    type Config = Config;
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        if config.baudrate == 0 {
+            return Err(());
+        }
+
+        let r = T::regs();
+        while r.uartfr().read().busy() {}
+        r.uartcr().modify(|w| w.set_uarten(false));
+        Self::set_format_inner(config);
+        r.uartcr().modify(|w| w.set_uarten(true));
+        Ok(())
+    }
+}
+
 mod eh02 {
     use super::*;
 
@@ -13,6 +13,8 @@ use embassy_usb_driver::{
 use crate::interrupt::typelevel::{Binding, Interrupt};
 use crate::{interrupt, pac, peripherals, Peripheral, RegExt};
 
+pub mod host;
+
 pub(crate) mod sealed {
     pub trait Instance {
         fn regs() -> crate::pac::usb::Usb;
@@ -405,12 +407,12 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
         .await
     }
 
-    fn endpoint_set_stalled(&mut self, _ep_addr: EndpointAddress, _stalled: bool) {
-        todo!();
+    fn endpoint_set_stalled(&mut self, ep_addr: EndpointAddress, stalled: bool) {
+        endpoint_set_stalled::<T>(ep_addr, stalled)
     }
 
-    fn endpoint_is_stalled(&mut self, _ep_addr: EndpointAddress) -> bool {
-        todo!();
+    fn endpoint_is_stalled(&mut self, ep_addr: EndpointAddress) -> bool {
+        endpoint_is_stalled::<T>(ep_addr)
     }
 
     fn endpoint_set_enabled(&mut self, ep_addr: EndpointAddress, enabled: bool) {
@@ -451,7 +453,39 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     async fn disable(&mut self) {}
 
     async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+        // The SIE drives resume signaling for the required time, and clears the bit when done.
+        T::regs().sie_ctrl().modify(|w| w.set_resume(true));
+        Ok(())
+    }
+}
+
+fn endpoint_set_stalled<T: Instance>(ep_addr: EndpointAddress, stalled: bool) {
+    trace!("set_stalled {:?} {}", ep_addr, stalled);
+    let n = ep_addr.index();
+    match ep_addr.direction() {
+        Direction::In => {
+            // The stall of EP0 must also be armed, it is cleared by each SETUP packet.
+            if n == 0 {
+                T::regs().ep_stall_arm().modify(|w| w.set_ep0_in(stalled));
+            }
+            T::dpram().ep_in_buffer_control(n).modify(|w| w.set_stall(stalled));
+            EP_IN_WAKERS[n].wake();
+        }
+        Direction::Out => {
+            if n == 0 {
+                T::regs().ep_stall_arm().modify(|w| w.set_ep0_out(stalled));
+            }
+            T::dpram().ep_out_buffer_control(n).modify(|w| w.set_stall(stalled));
+            EP_OUT_WAKERS[n].wake();
+        }
+    }
+}
+
+fn endpoint_is_stalled<T: Instance>(ep_addr: EndpointAddress) -> bool {
+    let n = ep_addr.index();
+    match ep_addr.direction() {
+        Direction::In => T::dpram().ep_in_buffer_control(n).read().stall(),
+        Direction::Out => T::dpram().ep_out_buffer_control(n).read().stall(),
     }
 }
 
@@ -510,6 +544,14 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, In> {
         .await;
         trace!("wait_enabled IN OK");
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        endpoint_set_stalled::<T>(self.info.addr, stalled)
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, Out> {
@@ -532,6 +574,14 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, Out> {
         .await;
         trace!("wait_enabled OUT OK");
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        endpoint_set_stalled::<T>(self.info.addr, stalled)
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
@@ -557,7 +607,8 @@ impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
 
         trace!("READ OK, rx_len = {}", rx_len);
 
-        let pid = !val.pid(0);
+        // Full speed isochronous transfers always use DATA0.
+        let pid = self.info.ep_type != EndpointType::Isochronous && !val.pid(0);
         T::dpram().ep_out_buffer_control(index).write(|w| {
             w.set_pid(0, pid);
             w.set_length(0, self.info.max_packet_size);
@@ -595,7 +646,8 @@ impl<'d, T: Instance> driver::EndpointIn for Endpoint<'d, T, In> {
 
         self.buf.write(buf);
 
-        let pid = !val.pid(0);
+        // Full speed isochronous transfers always use DATA0.
+        let pid = self.info.ep_type != EndpointType::Isochronous && !val.pid(0);
         T::dpram().ep_in_buffer_control(index).write(|w| {
             w.set_pid(0, pid);
             w.set_length(0, buf.len() as _);
@@ -0,0 +1,756 @@
+//! USB host mode.
+//!
+//! Control and bulk transfers run one transaction at a time on EPX, the single endpoint buffer the
+//! controller drives from software, shared by the pipes. Interrupt pipes each get one of the 15
+//! interrupt endpoints the controller polls by itself. Only full-speed and low-speed devices on
+//! the root port are supported, without hubs.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::slice;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use atomic_polyfill::{AtomicU16, AtomicU8};
+use embassy_hal_common::drop::OnDrop;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+use embassy_time::{Duration, Timer};
+use embassy_usb_driver::host::{self, DeviceEvent, PipeAllocError, PipeError, Speed};
+use embassy_usb_driver::{Direction, EndpointAddress, EndpointInfo, EndpointType};
+
+use super::Instance;
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::pac::common::{Reg, RW};
+use crate::pac::usb::regs::SieCtrl;
+use crate::pac::usb_dpram::regs::{EpBufferControl, EpControl, SetupPacketHigh, SetupPacketLow};
+use crate::pac::usb_dpram::vals::EpControlEndpointType;
+use crate::{interrupt, Peripheral};
+
+/// Host mode EPX control register, in the DPRAM. Device mode has no endpoint control there.
+const EPX_CONTROL: usize = 0x100;
+/// Data buffer of EPX, in the DPRAM.
+const EPX_DATA: usize = 0x180;
+/// Data buffers of the interrupt endpoint slots, after the one of EPX.
+const INT_DATA: usize = 0x1c0;
+
+const fn int_ep_data(slot: usize) -> usize {
+    INT_DATA + (slot - 1) * MAX_PACKET_SIZE
+}
+
+/// Largest packet of full-speed control, bulk and interrupt endpoints.
+const MAX_PACKET_SIZE: usize = 64;
+
+/// Number of interrupt endpoint slots, numbered from 1.
+const INT_SLOT_COUNT: usize = 15;
+
+// Port events, set by the interrupt handler
+const PORT_CONNECTED: u8 = 1 << 0;
+const PORT_DISCONNECTED: u8 = 1 << 1;
+
+// EPX transaction results
+const RESULT_BUSY: u8 = 0;
+const RESULT_DONE: u8 = 1;
+const RESULT_STALL: u8 = 2;
+const RESULT_ERROR: u8 = 3;
+const RESULT_OVERFLOW: u8 = 4;
+const RESULT_DISCONNECTED: u8 = 5;
+
+/// Transactions failing with a CRC, timeout or toggle error are retried this many times.
+const MAX_RETRIES: usize = 3;
+
+/// USB 2.0 spec, 7.1.7.5: the root port is reset for at least 50 ms.
+const RESET_DURATION_MS: u64 = 50;
+/// USB 2.0 spec, 7.1.7.3: the device gets 10 ms of recovery after reset.
+const RESET_RECOVERY_MS: u64 = 10;
+
+const NEW_AW: AtomicWaker = AtomicWaker::new();
+static PORT_WAKER: AtomicWaker = NEW_AW;
+/// `PORT_*` events not handled yet by [`Driver::wait_for_device_event()`](host::Driver::wait_for_device_event).
+static PORT_EVENTS: AtomicU8 = AtomicU8::new(0);
+static EPX_WAKER: AtomicWaker = NEW_AW;
+/// `RESULT_*` of the current EPX transaction.
+static EPX_RESULT: AtomicU8 = AtomicU8::new(RESULT_BUSY);
+/// Held by the pipe running a transaction on EPX.
+static EPX_LOCK: Mutex<CriticalSectionRawMutex, ()> = Mutex::new(());
+static INT_WAKERS: [AtomicWaker; INT_SLOT_COUNT] = [NEW_AW; INT_SLOT_COUNT];
+/// Mask of the interrupt endpoint slots owned by pipes.
+static INT_ALLOCATED: AtomicU16 = AtomicU16::new(0);
+
+fn epx_control<T: Instance>() -> Reg<EpControl, RW> {
+    unsafe { Reg::from_ptr((T::dpram().as_ptr() as *mut u8).add(EPX_CONTROL) as _) }
+}
+
+/// Buffer control of EPX, in place of the one of endpoint 0 IN in device mode.
+fn epx_buffer_control<T: Instance>() -> Reg<EpBufferControl, RW> {
+    T::dpram().ep_in_buffer_control(0)
+}
+
+/// Endpoint control of an interrupt endpoint slot, in place of the one of endpoint `slot` IN.
+fn int_ep_control<T: Instance>(slot: usize) -> Reg<EpControl, RW> {
+    T::dpram().ep_in_control(slot - 1)
+}
+
+/// Buffer control of an interrupt endpoint slot, in place of the one of endpoint `slot` IN.
+fn int_ep_buffer_control<T: Instance>(slot: usize) -> Reg<EpBufferControl, RW> {
+    T::dpram().ep_in_buffer_control(slot)
+}
+
+fn dpram_copy_from<T: Instance>(offset: usize, buf: &mut [u8]) {
+    compiler_fence(Ordering::SeqCst);
+    let mem = unsafe { slice::from_raw_parts((T::dpram().as_ptr() as *const u8).add(offset), buf.len()) };
+    buf.copy_from_slice(mem);
+    compiler_fence(Ordering::SeqCst);
+}
+
+fn dpram_copy_to<T: Instance>(offset: usize, data: &[u8]) {
+    compiler_fence(Ordering::SeqCst);
+    let mem = unsafe { slice::from_raw_parts_mut((T::dpram().as_ptr() as *mut u8).add(offset), data.len()) };
+    mem.copy_from_slice(data);
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Writes a buffer control register, setting AVAILABLE last as the datasheet requires.
+fn arm_buffer(reg: Reg<EpBufferControl, RW>, mut value: EpBufferControl) {
+    reg.write_value(value);
+    cortex_m::asm::delay(12);
+    value.set_available(0, true);
+    reg.write_value(value);
+}
+
+/// SIE_CTRL bits kept set: frames are started, and the data lines pulled down to detect devices.
+fn sie_ctrl_base() -> SieCtrl {
+    let mut w = SieCtrl(0);
+    w.set_sof_en(true);
+    w.set_keep_alive_en(true);
+    w.set_pulldown_en(true);
+    w.set_ep0_int_1buf(true);
+    w
+}
+
+fn stop_epx<T: Instance>() {
+    let mut sie_ctrl = sie_ctrl_base();
+    sie_ctrl.set_stop_trans(true);
+    T::regs().sie_ctrl().write_value(sie_ctrl);
+}
+
+fn is_connected<T: Instance>() -> bool {
+    T::regs().sie_status().read().speed() != 0
+}
+
+/// Records the result of the EPX transaction, keeping the first one.
+fn finish_epx(result: u8) {
+    if EPX_RESULT
+        .compare_exchange(RESULT_BUSY, result, Ordering::AcqRel, Ordering::Relaxed)
+        .is_ok()
+    {
+        EPX_WAKER.wake();
+    }
+}
+
+fn to_error(result: u8) -> PipeError {
+    match result {
+        RESULT_STALL => PipeError::Stall,
+        RESULT_OVERFLOW => PipeError::BufferOverflow,
+        RESULT_DISCONNECTED => PipeError::Disconnected,
+        _ => PipeError::Transaction,
+    }
+}
+
+/// Host mode interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = T::regs();
+        let ints = regs.ints().read();
+
+        if ints.host_conn_dis() {
+            let connected = is_connected::<T>();
+            // Writing the speed clears the interrupt.
+            regs.sie_status().write(|w| w.set_speed(0b11));
+
+            if connected {
+                PORT_EVENTS.fetch_or(PORT_CONNECTED, Ordering::Release);
+            } else {
+                PORT_EVENTS.fetch_or(PORT_DISCONNECTED, Ordering::Release);
+                finish_epx(RESULT_DISCONNECTED);
+                for waker in &INT_WAKERS {
+                    waker.wake();
+                }
+            }
+            PORT_WAKER.wake();
+        }
+
+        if ints.stall() {
+            regs.sie_status().write(|w| w.set_stall_rec(true));
+            finish_epx(RESULT_STALL);
+        }
+
+        if ints.error_data_seq()
+            || ints.error_rx_timeout()
+            || ints.error_rx_overflow()
+            || ints.error_bit_stuff()
+            || ints.error_crc()
+        {
+            let status = regs.sie_status().read();
+            regs.sie_status().write(|w| {
+                w.set_crc_error(status.crc_error());
+                w.set_bit_stuff_error(status.bit_stuff_error());
+                w.set_rx_overflow(status.rx_overflow());
+                w.set_rx_timeout(status.rx_timeout());
+                w.set_data_seq_error(status.data_seq_error());
+            });
+            finish_epx(RESULT_ERROR);
+        }
+
+        if ints.trans_complete() {
+            regs.sie_status().write(|w| w.set_trans_complete(true));
+            finish_epx(RESULT_DONE);
+        }
+
+        if ints.buff_status() {
+            let status = regs.buff_status().read();
+            regs.buff_status().write_value(status);
+
+            // Each slot has the two bits of the endpoint with its number, EPX has the first ones.
+            for slot in 1..=INT_SLOT_COUNT {
+                if status.ep_in(slot) || status.ep_out(slot) {
+                    INT_WAKERS[slot - 1].wake();
+                }
+            }
+        }
+    }
+}
+
+/// USB host driver.
+///
+/// Any number of control and bulk pipes can be allocated, their transfers take turns on EPX. At
+/// most 15 interrupt pipes can be allocated at the same time.
+pub struct Driver<'d, T: Instance> {
+    phantom: PhantomData<&'d mut T>,
+    connected: bool,
+}
+
+impl<'d, T: Instance> Driver<'d, T> {
+    /// Initializes the USB controller in host mode.
+    ///
+    /// The VBUS supply of the port is not controlled by the controller: on most boards, it's
+    /// switched on with a GPIO or always on.
+    pub fn new(_usb: impl Peripheral<P = T> + 'd, _irq: impl Binding<T::Interrupt, InterruptHandler<T>>) -> Self {
+        unsafe {
+            // zero fill regs
+            let p = T::regs().as_ptr() as *mut u32;
+            for i in 0..0x9c / 4 {
+                p.add(i).write_volatile(0)
+            }
+
+            // zero fill epmem
+            let p = T::dpram().as_ptr() as *mut u32;
+            for i in 0..0x180 / 4 {
+                p.add(i).write_volatile(0)
+            }
+        }
+
+        PORT_EVENTS.store(0, Ordering::Relaxed);
+        INT_ALLOCATED.store(0, Ordering::Relaxed);
+
+        let regs = T::regs();
+        regs.usb_muxing().write(|w| {
+            w.set_to_phy(true);
+            w.set_softcon(true);
+        });
+        regs.usb_pwr().write(|w| {
+            w.set_vbus_detect(true);
+            w.set_vbus_detect_override_en(true);
+        });
+        regs.main_ctrl().write(|w| {
+            w.set_controller_en(true);
+            w.set_host_ndevice(true);
+        });
+        regs.sie_ctrl().write_value(sie_ctrl_base());
+        regs.inte().write(|w| {
+            w.set_host_conn_dis(true);
+            w.set_trans_complete(true);
+            w.set_buff_status(true);
+            w.set_stall(true);
+            w.set_error_data_seq(true);
+            w.set_error_rx_timeout(true);
+            w.set_error_rx_overflow(true);
+            w.set_error_bit_stuff(true);
+            w.set_error_crc(true);
+        });
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            phantom: PhantomData,
+            connected: false,
+        }
+    }
+
+    fn alloc_pipe(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Pipe<'d, T>, PipeAllocError> {
+        if speed == Speed::High || endpoint.max_packet_size as usize > MAX_PACKET_SIZE {
+            return Err(PipeAllocError);
+        }
+
+        let slot = match endpoint.ep_type {
+            EndpointType::Isochronous => return Err(PipeAllocError),
+            EndpointType::Interrupt => Some(alloc_int_slot::<T>(device_address, endpoint)?),
+            _ => None,
+        };
+
+        Ok(Pipe {
+            _phantom: PhantomData,
+            slot,
+            device_address,
+            endpoint: endpoint.addr.index() as u8,
+            ep_type: endpoint.ep_type,
+            max_packet_size: endpoint.max_packet_size,
+            data1: false,
+        })
+    }
+}
+
+/// Allocates an interrupt endpoint slot, and starts polling the endpoint.
+fn alloc_int_slot<T: Instance>(device_address: u8, endpoint: &EndpointInfo) -> Result<usize, PipeAllocError> {
+    let slot = critical_section::with(|_| {
+        let allocated = INT_ALLOCATED.load(Ordering::Relaxed);
+        let slot = (1..=INT_SLOT_COUNT).find(|slot| allocated & (1 << slot) == 0)?;
+        INT_ALLOCATED.store(allocated | (1 << slot), Ordering::Relaxed);
+        Some(slot)
+    })
+    .ok_or(PipeAllocError)?;
+
+    // ADDR_ENDP1 to ADDR_ENDP15 go with the slots.
+    T::regs().addr_endpx(slot - 1).write(|w| {
+        w.set_address(device_address);
+        w.set_endpoint(endpoint.addr.index() as u8);
+        w.set_intep_dir(endpoint.addr.is_out());
+    });
+
+    int_ep_buffer_control::<T>(slot).write_value(EpBufferControl(0));
+    int_ep_control::<T>(slot).write(|w| {
+        w.set_enable(true);
+        w.set_interrupt_per_buff(true);
+        w.set_endpoint_type(EpControlEndpointType::INTERRUPT);
+        w.set_host_poll_interval(endpoint.interval_ms.max(1) as u16 - 1);
+        w.set_buffer_address(int_ep_data(slot) as u16);
+    });
+
+    // The field starts with the bit of slot 1.
+    critical_section::with(|_| {
+        T::regs()
+            .int_ep_ctrl()
+            .modify(|w| w.set_int_ep_active(w.int_ep_active() | 1 << (slot - 1)))
+    });
+
+    trace!("allocated interrupt slot {} for ep {:02x}", slot, endpoint.addr.index());
+    Ok(slot)
+}
+
+impl<'d, T: Instance> host::Driver<'d> for Driver<'d, T> {
+    type ControlPipe = ControlPipe<'d, T>;
+    type PipeIn = PipeIn<'d, T>;
+    type PipeOut = PipeOut<'d, T>;
+
+    async fn wait_for_device_event(&mut self) -> DeviceEvent {
+        poll_fn(|cx| {
+            PORT_WAKER.register(cx.waker());
+
+            let events = PORT_EVENTS.swap(0, Ordering::AcqRel);
+            if events & PORT_DISCONNECTED != 0 && self.connected {
+                self.connected = false;
+                // A new connection is reported on the next call.
+                PORT_EVENTS.fetch_or(events & PORT_CONNECTED, Ordering::AcqRel);
+                return Poll::Ready(DeviceEvent::Disconnected);
+            }
+
+            if events & PORT_CONNECTED != 0 && is_connected::<T>() && !self.connected {
+                self.connected = true;
+                let speed = match T::regs().sie_status().read().speed() {
+                    1 => Speed::Low,
+                    _ => Speed::Full,
+                };
+                return Poll::Ready(DeviceEvent::Connected(speed));
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn bus_reset(&mut self) {
+        // The controller drives the reset and clears the bit by itself.
+        let mut sie_ctrl = sie_ctrl_base();
+        sie_ctrl.set_reset_bus(true);
+        T::regs().sie_ctrl().write_value(sie_ctrl);
+        Timer::after(Duration::from_millis(RESET_DURATION_MS)).await;
+        Timer::after(Duration::from_millis(RESET_RECOVERY_MS)).await;
+    }
+
+    fn alloc_control_pipe(
+        &mut self,
+        device_address: u8,
+        max_packet_size: u16,
+        speed: Speed,
+    ) -> Result<Self::ControlPipe, PipeAllocError> {
+        let endpoint = EndpointInfo {
+            addr: EndpointAddress::from_parts(0, Direction::Out),
+            ep_type: EndpointType::Control,
+            max_packet_size,
+            interval_ms: 0,
+        };
+        let pipe = self.alloc_pipe(device_address, &endpoint, speed)?;
+        Ok(ControlPipe { pipe })
+    }
+
+    fn alloc_pipe_in(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeIn, PipeAllocError> {
+        let pipe = self.alloc_pipe(device_address, endpoint, speed)?;
+        Ok(PipeIn { pipe, info: *endpoint })
+    }
+
+    fn alloc_pipe_out(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeOut, PipeAllocError> {
+        let pipe = self.alloc_pipe(device_address, endpoint, speed)?;
+        Ok(PipeOut { pipe, info: *endpoint })
+    }
+}
+
+impl<'d, T: Instance> Drop for Driver<'d, T> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        T::regs().main_ctrl().write(|w| {
+            w.set_controller_en(false);
+            w.set_host_ndevice(false);
+        });
+    }
+}
+
+/// Packet of a transaction.
+enum Packet<'b> {
+    Setup(&'b [u8; 8]),
+    In(&'b mut [u8]),
+    Out(&'b [u8]),
+}
+
+/// A pipe to an endpoint, using EPX or an interrupt endpoint slot.
+struct Pipe<'d, T: Instance> {
+    _phantom: PhantomData<&'d mut T>,
+    /// Interrupt endpoint slot polled by the controller, or `None` for transactions on EPX.
+    slot: Option<usize>,
+    device_address: u8,
+    endpoint: u8,
+    ep_type: EndpointType,
+    max_packet_size: u16,
+    /// Data PID of the next packet.
+    data1: bool,
+}
+
+impl<'d, T: Instance> Pipe<'d, T> {
+    /// Buffer control of the next packet, with `len` bytes in it or to receive.
+    fn buffer_control(&self, len: usize) -> EpBufferControl {
+        let mut w = EpBufferControl(0);
+        w.set_length(0, len as u16);
+        w.set_pid(0, self.data1);
+        w.set_last(0, true);
+        w
+    }
+
+    /// Runs a transaction, retrying it on errors, and returns the number of bytes received or sent.
+    async fn transaction(&mut self, packet: &mut Packet<'_>) -> Result<usize, PipeError> {
+        let mut retries = 0;
+        loop {
+            let result = match self.slot {
+                None => self.epx_transaction(packet).await,
+                Some(slot) => self.int_transaction(slot, packet).await,
+            };
+            match result {
+                Ok(n) => {
+                    if !matches!(packet, Packet::Setup(_)) {
+                        self.data1 = !self.data1;
+                    }
+                    return Ok(n);
+                }
+                Err(RESULT_ERROR) if retries < MAX_RETRIES => retries += 1,
+                Err(result) => return Err(to_error(result)),
+            }
+        }
+    }
+
+    async fn epx_transaction(&mut self, packet: &mut Packet<'_>) -> Result<usize, u8> {
+        let _lock = EPX_LOCK.lock().await;
+
+        if !is_connected::<T>() {
+            return Err(RESULT_DISCONNECTED);
+        }
+
+        let ep_type = match self.ep_type {
+            EndpointType::Bulk => EpControlEndpointType::BULK,
+            _ => EpControlEndpointType::CONTROL,
+        };
+        T::regs().addr_endp().write(|w| {
+            w.set_address(self.device_address);
+            w.set_endpoint(self.endpoint);
+        });
+        epx_control::<T>().write(|w| {
+            w.set_enable(true);
+            w.set_interrupt_per_buff(true);
+            w.set_endpoint_type(ep_type);
+            w.set_buffer_address(EPX_DATA as u16);
+        });
+
+        let mut sie_ctrl = sie_ctrl_base();
+        match packet {
+            Packet::Setup(setup) => {
+                let low = u32::from_le_bytes([setup[0], setup[1], setup[2], setup[3]]);
+                let high = u32::from_le_bytes([setup[4], setup[5], setup[6], setup[7]]);
+                T::dpram().setup_packet_low().write_value(SetupPacketLow(low));
+                T::dpram().setup_packet_high().write_value(SetupPacketHigh(high));
+                sie_ctrl.set_send_setup(true);
+            }
+            Packet::In(_) => {
+                let buf_ctrl = self.buffer_control(self.max_packet_size as usize);
+                arm_buffer(epx_buffer_control::<T>(), buf_ctrl);
+                sie_ctrl.set_receive_data(true);
+            }
+            Packet::Out(data) => {
+                dpram_copy_to::<T>(EPX_DATA, data);
+                let mut buf_ctrl = self.buffer_control(data.len());
+                buf_ctrl.set_full(0, true);
+                arm_buffer(epx_buffer_control::<T>(), buf_ctrl);
+                sie_ctrl.set_send_data(true);
+            }
+        }
+
+        EPX_RESULT.store(RESULT_BUSY, Ordering::Release);
+
+        let stop_on_drop = OnDrop::new(|| stop_epx::<T>());
+
+        // The controller retries NAKed transactions by itself. As for AVAILABLE, START_TRANS is set
+        // once the other bits are settled.
+        T::regs().sie_ctrl().write_value(sie_ctrl);
+        cortex_m::asm::delay(12);
+        sie_ctrl.set_start_trans(true);
+        T::regs().sie_ctrl().write_value(sie_ctrl);
+
+        let result = poll_fn(|cx| {
+            EPX_WAKER.register(cx.waker());
+            match EPX_RESULT.load(Ordering::Acquire) {
+                RESULT_BUSY => Poll::Pending,
+                result => Poll::Ready(result),
+            }
+        })
+        .await;
+
+        stop_on_drop.defuse();
+
+        if result != RESULT_DONE {
+            stop_epx::<T>();
+            return Err(result);
+        }
+
+        match packet {
+            Packet::Setup(setup) => Ok(setup.len()),
+            Packet::In(buf) => {
+                let n = epx_buffer_control::<T>().read().length(0) as usize;
+                if n > buf.len() {
+                    return Err(RESULT_OVERFLOW);
+                }
+                dpram_copy_from::<T>(EPX_DATA, &mut buf[..n]);
+                Ok(n)
+            }
+            Packet::Out(data) => Ok(data.len()),
+        }
+    }
+
+    async fn int_transaction(&mut self, slot: usize, packet: &mut Packet<'_>) -> Result<usize, u8> {
+        let buf_ctrl = match packet {
+            Packet::Setup(_) => unreachable!(),
+            Packet::In(_) => self.buffer_control(self.max_packet_size as usize),
+            Packet::Out(data) => {
+                dpram_copy_to::<T>(int_ep_data(slot), data);
+                let mut buf_ctrl = self.buffer_control(data.len());
+                buf_ctrl.set_full(0, true);
+                buf_ctrl
+            }
+        };
+
+        let cancel_on_drop = OnDrop::new(|| int_ep_buffer_control::<T>(slot).write_value(EpBufferControl(0)));
+
+        // The controller polls the endpoint at its interval while the buffer is available.
+        arm_buffer(int_ep_buffer_control::<T>(slot), buf_ctrl);
+
+        let result = poll_fn(|cx| {
+            INT_WAKERS[slot - 1].register(cx.waker());
+            if !is_connected::<T>() {
+                return Poll::Ready(Err(RESULT_DISCONNECTED));
+            }
+            match int_ep_buffer_control::<T>(slot).read() {
+                val if val.available(0) => Poll::Pending,
+                val => Poll::Ready(Ok(val)),
+            }
+        })
+        .await;
+
+        cancel_on_drop.defuse();
+        let val = result.map_err(|e| {
+            int_ep_buffer_control::<T>(slot).write_value(EpBufferControl(0));
+            e
+        })?;
+
+        match packet {
+            Packet::Setup(_) => unreachable!(),
+            Packet::In(buf) => {
+                let n = val.length(0) as usize;
+                if n > buf.len() {
+                    return Err(RESULT_OVERFLOW);
+                }
+                dpram_copy_from::<T>(int_ep_data(slot), &mut buf[..n]);
+                Ok(n)
+            }
+            Packet::Out(data) => Ok(data.len()),
+        }
+    }
+
+    /// Reads packets into `buf` until a short packet is received, or `buf` is full.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        let mps = self.max_packet_size as usize;
+        let mut total = 0;
+        loop {
+            let n = self.transaction(&mut Packet::In(&mut buf[total..])).await?;
+            total += n;
+            if n < mps || total == buf.len() {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Writes `data` in packets, with a zero-length packet if `data` is empty.
+    async fn write(&mut self, data: &[u8]) -> Result<(), PipeError> {
+        if data.is_empty() {
+            return self.transaction(&mut Packet::Out(data)).await.map(drop);
+        }
+
+        for packet in data.chunks(self.max_packet_size as usize) {
+            self.transaction(&mut Packet::Out(packet)).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance> Drop for Pipe<'d, T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            critical_section::with(|_| {
+                T::regs()
+                    .int_ep_ctrl()
+                    .modify(|w| w.set_int_ep_active(w.int_ep_active() & !(1 << (slot - 1))));
+                INT_ALLOCATED.fetch_and(!(1 << slot), Ordering::Relaxed);
+            });
+            int_ep_control::<T>(slot).write_value(EpControl(0));
+            int_ep_buffer_control::<T>(slot).write_value(EpBufferControl(0));
+        }
+    }
+}
+
+/// Control pipe to endpoint 0 of a device.
+pub struct ControlPipe<'d, T: Instance> {
+    pipe: Pipe<'d, T>,
+}
+
+impl<'d, T: Instance> ControlPipe<'d, T> {
+    async fn setup(&mut self, setup: &[u8; 8]) -> Result<(), PipeError> {
+        self.pipe.transaction(&mut Packet::Setup(setup)).await?;
+        // Data and status stages start with DATA1.
+        self.pipe.data1 = true;
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance> host::ControlPipe for ControlPipe<'d, T> {
+    fn set_device_address(&mut self, device_address: u8) {
+        self.pipe.device_address = device_address;
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: u16) {
+        self.pipe.max_packet_size = max_packet_size;
+    }
+
+    async fn control_in(&mut self, setup: &[u8; 8], buf: &mut [u8]) -> Result<usize, PipeError> {
+        self.setup(setup).await?;
+
+        let n = match buf.is_empty() {
+            true => 0,
+            false => self.pipe.read(buf).await?,
+        };
+
+        // Status stage
+        self.pipe.data1 = true;
+        self.pipe.write(&[]).await?;
+        Ok(n)
+    }
+
+    async fn control_out(&mut self, setup: &[u8; 8], data: &[u8]) -> Result<(), PipeError> {
+        self.setup(setup).await?;
+
+        if !data.is_empty() {
+            self.pipe.write(data).await?;
+        }
+
+        // Status stage
+        self.pipe.data1 = true;
+        self.pipe.read(&mut []).await?;
+        Ok(())
+    }
+}
+
+/// Pipe to an IN endpoint.
+pub struct PipeIn<'d, T: Instance> {
+    pipe: Pipe<'d, T>,
+    info: EndpointInfo,
+}
+
+impl<'d, T: Instance> host::PipeIn for PipeIn<'d, T> {
+    fn info(&self) -> &EndpointInfo {
+        &self.info
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        self.pipe.read(buf).await
+    }
+}
+
+/// Pipe to an OUT endpoint.
+pub struct PipeOut<'d, T: Instance> {
+    pipe: Pipe<'d, T>,
+    info: EndpointInfo,
+}
+
+impl<'d, T: Instance> host::PipeOut for PipeOut<'d, T> {
+    fn info(&self) -> &EndpointInfo {
+        &self.info
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> Result<(), PipeError> {
+        self.pipe.write(buf).await
+    }
+}
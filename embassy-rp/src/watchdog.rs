@@ -4,14 +4,28 @@
 //! processor if software gets stuck in an infinite loop. The programmer must periodically write a value to the watchdog to
 //! stop it from reaching zero.
 //!
+//! The scratch registers of the watchdog keep their value across the resets it triggers, so they
+//! can tell the restarted firmware why it was restarted. Registers 4 to 7 are used by the bootrom,
+//! for example by [`Watchdog::reset_to_usb_boot()`].
+//!
 //! Credit: based on `rp-hal` implementation (also licensed Apache+MIT)
 
 use core::marker::PhantomData;
 
 use embassy_time::Duration;
 
-use crate::pac;
 use crate::peripherals::WATCHDOG;
+use crate::{pac, rom_data};
+
+/// The reason for a system reset from the watchdog.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetReason {
+    /// The reset was forced, with [`Watchdog::trigger_reset()`] or by the bootrom.
+    Forced,
+    /// The watchdog timer wasn't fed in time.
+    TimedOut,
+}
 
 /// Watchdog peripheral
 pub struct Watchdog {
@@ -97,6 +111,11 @@ impl Watchdog {
         self.enable(true);
     }
 
+    /// Stop the watchdog timer
+    pub fn stop(&mut self) {
+        self.enable(false);
+    }
+
     /// Trigger a system reset
     pub fn trigger_reset(&mut self) {
         self.configure_wdog_reset_triggers();
@@ -107,4 +126,63 @@ impl Watchdog {
             w.set_trigger(true);
         })
     }
+
+    /// Reset the chip into the USB bootloader.
+    ///
+    /// The bootrom resets the chip with the watchdog, this stops the watchdog timer first so that
+    /// it doesn't interfere. `gpio_activity_pin_mask` and `disable_interface_mask` are passed to
+    /// [`rom_data::reset_to_usb_boot()`].
+    pub fn reset_to_usb_boot(&mut self, gpio_activity_pin_mask: u32, disable_interface_mask: u32) -> ! {
+        self.stop();
+        rom_data::reset_to_usb_boot(gpio_activity_pin_mask, disable_interface_mask);
+        // The bootrom doesn't return.
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+
+    /// Store data in a scratch register, from 0 to 7
+    pub fn set_scratch(&mut self, index: usize, value: u32) {
+        let watchdog = pac::WATCHDOG;
+        match index {
+            0 => watchdog.scratch0().write(|w| *w = value),
+            1 => watchdog.scratch1().write(|w| *w = value),
+            2 => watchdog.scratch2().write(|w| *w = value),
+            3 => watchdog.scratch3().write(|w| *w = value),
+            4 => watchdog.scratch4().write(|w| *w = value),
+            5 => watchdog.scratch5().write(|w| *w = value),
+            6 => watchdog.scratch6().write(|w| *w = value),
+            7 => watchdog.scratch7().write(|w| *w = value),
+            _ => panic!("Invalid watchdog scratch index"),
+        }
+    }
+
+    /// Read data from a scratch register, from 0 to 7
+    pub fn get_scratch(&mut self, index: usize) -> u32 {
+        let watchdog = pac::WATCHDOG;
+        match index {
+            0 => watchdog.scratch0().read(),
+            1 => watchdog.scratch1().read(),
+            2 => watchdog.scratch2().read(),
+            3 => watchdog.scratch3().read(),
+            4 => watchdog.scratch4().read(),
+            5 => watchdog.scratch5().read(),
+            6 => watchdog.scratch6().read(),
+            7 => watchdog.scratch7().read(),
+            _ => panic!("Invalid watchdog scratch index"),
+        }
+    }
+
+    /// Get the reason for the last system reset, if it was caused by the watchdog.
+    pub fn reset_reason(&self) -> Option<ResetReason> {
+        let watchdog = pac::WATCHDOG;
+        let reason = watchdog.reason().read();
+        if reason.force() {
+            Some(ResetReason::Forced)
+        } else if reason.timer() {
+            Some(ResetReason::TimedOut)
+        } else {
+            None
+        }
+    }
 }
@@ -1,5 +1,6 @@
 #![no_std]
 #![cfg_attr(feature = "ble", feature(async_fn_in_trait))]
+#![doc = include_str!("../README.md")]
 
 // This must go FIRST so that all the other modules see its macros.
 pub mod fmt;
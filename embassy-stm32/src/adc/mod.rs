@@ -9,12 +9,16 @@ mod _version;
 
 #[cfg(not(adc_f1))]
 mod resolution;
+#[cfg(all(any(adc_v2, adc_v3), not(gpdma)))]
+mod ringbuffered;
 mod sample_time;
 
 #[allow(unused)]
 pub use _version::*;
 #[cfg(not(adc_f1))]
 pub use resolution::Resolution;
+#[cfg(all(any(adc_v2, adc_v3), not(gpdma)))]
+pub use ringbuffered::{Error, RingBufferedAdc, Trigger, TriggerEdge, MAX_SEQUENCE_LEN};
 pub use sample_time::SampleTime;
 
 use crate::peripherals;
@@ -34,6 +38,9 @@ pub(crate) mod sealed {
 
     pub trait AdcPin<T: Instance> {
         fn channel(&self) -> u8;
+
+        /// Configure the pin as an analog input.
+        fn setup(&mut self) {}
     }
 
     pub trait InternalChannel<T> {
@@ -49,6 +56,8 @@ pub trait Instance: sealed::Instance + crate::Peripheral<P = Self> + crate::rcc:
 pub trait AdcPin<T: Instance>: sealed::AdcPin<T> {}
 pub trait InternalChannel<T>: sealed::InternalChannel<T> {}
 
+dma_trait!(RxDma, Instance);
+
 #[cfg(not(stm32h7))]
 foreach_peripheral!(
     (adc, $inst:ident) => {
@@ -116,6 +125,10 @@ macro_rules! impl_adc_pin {
             fn channel(&self) -> u8 {
                 $ch
             }
+
+            fn setup(&mut self) {
+                <Self as crate::gpio::sealed::Pin>::set_as_analog(self);
+            }
         }
     };
 }
@@ -0,0 +1,326 @@
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::into_ref;
+
+use super::sealed::AdcPin as _;
+use crate::adc::{Adc, AdcPin, Instance, RxDma, SampleTime};
+use crate::dma::RingBuffer;
+use crate::Peripheral;
+
+// CR1 bits
+#[cfg(adc_v2)]
+const CR1_SCAN: u32 = 1 << 8;
+
+// CR2 bits
+#[cfg(adc_v2)]
+const CR2_CONT: u32 = 1 << 1;
+#[cfg(adc_v2)]
+const CR2_DMA: u32 = 1 << 8;
+#[cfg(adc_v2)]
+const CR2_DDS: u32 = 1 << 9;
+#[cfg(adc_v2)]
+const CR2_EXTSEL_POS: u32 = 24;
+#[cfg(adc_v2)]
+const CR2_SWSTART: u32 = 1 << 30;
+#[cfg(adc_v2)]
+const CR2_TRIGGER: u32 = 0x3F << CR2_EXTSEL_POS;
+
+// SR bits
+#[cfg(adc_v2)]
+const SR_OVR: u32 = 1 << 5;
+
+// CR bits
+#[cfg(adc_v3)]
+const CR_ADSTART: u32 = 1 << 2;
+#[cfg(adc_v3)]
+const CR_ADSTP: u32 = 1 << 4;
+
+// CFGR bits
+#[cfg(adc_v3)]
+const CFGR_DMAEN: u32 = 1 << 0;
+#[cfg(adc_v3)]
+const CFGR_DMACFG: u32 = 1 << 1;
+#[cfg(adc_v3)]
+const CFGR_EXTSEL_POS: u32 = 6;
+#[cfg(adc_v3)]
+const CFGR_CONT: u32 = 1 << 13;
+#[cfg(adc_v3)]
+const CFGR_TRIGGER: u32 = 0x3F << CFGR_EXTSEL_POS;
+
+// ISR bits
+#[cfg(adc_v3)]
+const ISR_OVR: u32 = 1 << 4;
+
+/// Ring-buffered ADC errors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Samples were lost because they weren't read from the ring buffer, or from the ADC, in
+    /// time.
+    Overrun,
+}
+
+/// Maximum number of channels in a conversion sequence.
+pub const MAX_SEQUENCE_LEN: usize = 16;
+
+/// Active edge of the external trigger.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerEdge {
+    Rising = 1,
+    Falling = 2,
+    Both = 3,
+}
+
+/// External trigger of a conversion sequence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Trigger {
+    /// The EXTSEL value of the trigger source, usually a timer TRGO or capture/compare event.
+    ///
+    /// The sources are listed in the "External triggers for regular channels" table of the
+    /// reference manual.
+    pub source: u8,
+    pub edge: TriggerEdge,
+}
+
+/// Continuous conversion of a channel sequence into a circular DMA buffer.
+///
+/// Each conversion of the sequence writes one sample per channel, in the order of the sequence.
+pub struct RingBufferedAdc<'d, T: Instance, RxDma: super::RxDma<T>> {
+    _adc: Adc<'d, T>,
+    ring_buf: RingBuffer<'d, RxDma, u16>,
+    trigger: Option<Trigger>,
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Turn the `Adc` into a ring-buffered ADC, which converts `sequence` in the background
+    /// and writes the samples to `dma_buf`.
+    ///
+    /// Without a `trigger`, the sequence is converted again as soon as it completes. With a
+    /// `trigger`, each trigger event converts the sequence once, which samples at the rate of a
+    /// timer.
+    ///
+    /// `dma_buf` must be large enough to hold the samples produced between two calls to
+    /// [`RingBufferedAdc::read()`], and its length should be a multiple of the length of
+    /// `sequence`, so that samples stay aligned with their channel.
+    pub fn into_ring_buffered<D: RxDma<T>>(
+        self,
+        dma: impl Peripheral<P = D> + 'd,
+        dma_buf: &'d mut [u16],
+        sequence: &mut [(&mut dyn AdcPin<T>, SampleTime)],
+        trigger: Option<Trigger>,
+    ) -> RingBufferedAdc<'d, T, D> {
+        into_ref!(dma);
+        assert!(dma_buf.len() > 0 && dma_buf.len() <= 0xFFFF);
+        assert!(sequence.len() > 0 && sequence.len() <= MAX_SEQUENCE_LEN);
+
+        for (i, (pin, sample_time)) in sequence.iter_mut().enumerate() {
+            pin.setup();
+            Self::set_channel_sample_time(pin.channel(), *sample_time);
+            set_sequence_channel::<T>(i, pin.channel());
+        }
+
+        let r = T::regs();
+        let len = sequence.len() as u32 - 1;
+
+        #[cfg(adc_v2)]
+        {
+            r.sqr1().modify(|w| w.0 = (w.0 & !(0xF << 20)) | (len << 20));
+            r.cr1().modify(|w| w.0 |= CR1_SCAN);
+        }
+
+        #[cfg(adc_v3)]
+        {
+            r.sqr1().modify(|w| w.0 = (w.0 & !0xF) | len);
+        }
+
+        let request = dma.request();
+        let opts = Default::default();
+        let ring_buf = unsafe { RingBuffer::new_read(dma, request, r.dr().as_ptr() as *mut u16, dma_buf, opts) };
+
+        RingBufferedAdc {
+            _adc: self,
+            ring_buf,
+            trigger,
+        }
+    }
+}
+
+impl<'d, T: Instance, RxDma: super::RxDma<T>> RingBufferedAdc<'d, T, RxDma> {
+    /// Start converting in the background.
+    pub fn start(&mut self) {
+        // Clear the ring buffer so that it is ready to receive samples
+        self.ring_buf.clear();
+
+        // fence before starting DMA.
+        compiler_fence(Ordering::SeqCst);
+
+        self.ring_buf.start();
+
+        let r = T::regs();
+        let trigger = match self.trigger {
+            Some(t) => (t.source as u32) | ((t.edge as u32) << 4),
+            None => 0,
+        };
+
+        #[cfg(adc_v2)]
+        {
+            r.sr().modify(|w| w.0 &= !SR_OVR);
+            r.cr2().modify(|w| {
+                w.0 &= !(CR2_TRIGGER | CR2_CONT);
+                w.0 |= CR2_DMA | CR2_DDS | (trigger << CR2_EXTSEL_POS);
+                if self.trigger.is_none() {
+                    w.0 |= CR2_CONT;
+                }
+            });
+            if self.trigger.is_none() {
+                r.cr2().modify(|w| w.0 |= CR2_SWSTART);
+            }
+        }
+
+        #[cfg(adc_v3)]
+        {
+            enable_adc::<T>();
+            r.isr().write(|w| w.0 = ISR_OVR);
+            r.cfgr().modify(|w| {
+                w.0 &= !(CFGR_TRIGGER | CFGR_CONT);
+                w.0 |= CFGR_DMAEN | CFGR_DMACFG | (trigger << CFGR_EXTSEL_POS);
+                if self.trigger.is_none() {
+                    w.0 |= CFGR_CONT;
+                }
+            });
+            r.cr().modify(|w| w.0 |= CR_ADSTART);
+        }
+    }
+
+    /// Stop converting in the background.
+    pub fn stop(&mut self) {
+        let r = T::regs();
+
+        #[cfg(adc_v2)]
+        r.cr2().modify(|w| w.0 &= !(CR2_TRIGGER | CR2_CONT | CR2_DMA | CR2_DDS));
+
+        #[cfg(adc_v3)]
+        {
+            if r.cr().read().0 & CR_ADSTART != 0 {
+                r.cr().modify(|w| w.0 |= CR_ADSTP);
+                while r.cr().read().0 & CR_ADSTART != 0 {}
+            }
+            r.cfgr().modify(|w| w.0 &= !(CFGR_DMAEN | CFGR_DMACFG | CFGR_CONT));
+        }
+
+        self.ring_buf.request_stop();
+        while self.ring_buf.is_running() {}
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Read the samples that are available in the ring buffer.
+    ///
+    /// If no samples are available, this waits until the DMA has filled half of the ring buffer,
+    /// or all of it, and returns at least one sample and at most half the buffer size.
+    ///
+    /// Background conversion is started if `start()` has not been called. It's stopped if an
+    /// error is returned, after which `start()` or `read()` start it again.
+    pub async fn read(&mut self, buf: &mut [u16]) -> Result<usize, Error> {
+        if !self.ring_buf.is_running() {
+            self.start();
+        }
+
+        loop {
+            if has_overrun::<T>() {
+                self.stop();
+                return Err(Error::Overrun);
+            }
+
+            match self.ring_buf.read(buf) {
+                Ok((0, _)) => {}
+                Ok((len, _)) => return Ok(len),
+                Err(_) => {
+                    self.stop();
+                    return Err(Error::Overrun);
+                }
+            }
+
+            self.wait_for_samples().await;
+        }
+    }
+
+    /// Wait for the DMA half-transfer or transfer-complete event.
+    async fn wait_for_samples(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+
+        let mut registered = false;
+        poll_fn(|cx| {
+            self.ring_buf.set_waker(cx.waker());
+
+            if registered {
+                Poll::Ready(())
+            } else {
+                registered = true;
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<'d, T: Instance, RxDma: super::RxDma<T>> Drop for RingBufferedAdc<'d, T, RxDma> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The ADC stops requesting DMA transfers after an overrun, which happens when the DMA doesn't
+/// read a sample before the next one is converted.
+fn has_overrun<T: Instance>() -> bool {
+    #[cfg(adc_v2)]
+    let ovr = T::regs().sr().read().0 & SR_OVR != 0;
+    #[cfg(adc_v3)]
+    let ovr = T::regs().isr().read().0 & ISR_OVR != 0;
+    ovr
+}
+
+#[cfg(adc_v2)]
+fn set_sequence_channel<T: Instance>(index: usize, channel: u8) {
+    let r = T::regs();
+    match index {
+        0..=5 => r.sqr3().modify(|w| w.set_sq(index, channel)),
+        6..=11 => r.sqr2().modify(|w| w.set_sq(index - 6, channel)),
+        _ => r.sqr1().modify(|w| w.set_sq(index - 12, channel)),
+    }
+}
+
+#[cfg(adc_v3)]
+fn set_sequence_channel<T: Instance>(index: usize, channel: u8) {
+    let r = T::regs();
+    match index {
+        0..=3 => r.sqr1().modify(|w| w.set_sq(index, channel)),
+        4..=8 => r.sqr2().modify(|w| w.set_sq(index - 4, channel)),
+        9..=13 => r.sqr3().modify(|w| w.set_sq(index - 9, channel)),
+        _ => r.sqr4().modify(|w| w.set_sq(index - 14, channel)),
+    }
+}
+
+#[cfg(adc_v3)]
+fn enable_adc<T: Instance>() {
+    let r = T::regs();
+    if r.cr().read().aden() {
+        return;
+    }
+
+    // Make sure bits are off
+    while r.cr().read().addis() {
+        // spin
+    }
+
+    r.isr().modify(|reg| reg.set_adrdy(true));
+    r.cr().modify(|reg| reg.set_aden(true));
+
+    while !r.isr().read().adrdy() {
+        // spin
+    }
+}
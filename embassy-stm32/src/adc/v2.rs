@@ -204,7 +204,7 @@ where
         val
     }
 
-    fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
+    pub(super) fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
         let sample_time = sample_time.into();
         if ch <= 9 {
             T::regs().smpr2().modify(|reg| reg.set_smp(ch as _, sample_time));
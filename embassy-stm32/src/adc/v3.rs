@@ -204,12 +204,12 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 
     #[cfg(stm32g0)]
-    fn set_channel_sample_time(_ch: u8, sample_time: SampleTime) {
+    pub(super) fn set_channel_sample_time(_ch: u8, sample_time: SampleTime) {
         T::regs().smpr().modify(|reg| reg.set_smp1(sample_time.into()));
     }
 
     #[cfg(not(stm32g0))]
-    fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
+    pub(super) fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
         let sample_time = sample_time.into();
         T::regs()
             .smpr(ch as usize / 10)
@@ -0,0 +1,538 @@
+#![macro_use]
+
+//! AES hardware accelerator.
+//!
+//! The AES peripheral encrypts and decrypts 16-byte blocks with a 128-bit or, on some devices,
+//! a 256-bit key, in the ECB, CBC, CTR and GCM chaining modes. The async methods move the data
+//! with DMA, the CPU being free while a buffer is processed.
+//!
+//! All the methods process their buffer in place, and take the key and IV on each call, so that
+//! one peripheral can serve several sessions.
+//!
+//! With the `rustcrypto` feature, [`AesGcm`], [`AesCtr`] and [`AesCbcEncryptor`] /
+//! [`AesCbcDecryptor`] implement the `aead` and `cipher` traits of RustCrypto.
+
+use embassy_futures::join::join;
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::dma::Transfer;
+use crate::pac::aes::regs;
+use crate::rcc::RccPeripheral;
+use crate::{peripherals, Peripheral};
+
+#[cfg(feature = "rustcrypto")]
+mod rustcrypto;
+#[cfg(feature = "rustcrypto")]
+pub use rustcrypto::*;
+
+// CR DATATYPE value of byte-swapped data
+const DATATYPE_BYTE: u8 = 0b10;
+
+// CR MODE values
+const MODE_ENCRYPT: u8 = 0b00;
+const MODE_KEY_DERIVATION: u8 = 0b01;
+const MODE_DECRYPT: u8 = 0b10;
+
+// CR CHMOD values
+const CHMOD_ECB: u8 = 0b00;
+const CHMOD_CBC: u8 = 0b01;
+const CHMOD_CTR: u8 = 0b10;
+const CHMOD_GCM: u8 = 0b11;
+
+// CR GCMPH values
+const GCMPH_INIT: u8 = 0b00;
+const GCMPH_HEADER: u8 = 0b01;
+const GCMPH_PAYLOAD: u8 = 0b10;
+const GCMPH_FINAL: u8 = 0b11;
+
+/// Size of an AES block, in bytes.
+pub const BLOCK_SIZE: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The key isn't 16 or 32 bytes long.
+    KeySize,
+    /// The buffer isn't a multiple of the block size, in a mode that requires it.
+    Length,
+    /// The buffer of a DMA transfer isn't aligned to 4 bytes.
+    Alignment,
+    /// The peripheral detected a read or write error.
+    Peripheral,
+    /// The authentication tag doesn't match the data.
+    Authentication,
+}
+
+/// Chaining mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode<'a> {
+    /// Electronic codebook, the buffer must be a multiple of the block size.
+    Ecb,
+    /// Cipher block chaining, the buffer must be a multiple of the block size.
+    Cbc { iv: &'a [u8; BLOCK_SIZE] },
+    /// Counter mode, incrementing the last 32 bits of the initial counter block.
+    Ctr { iv: &'a [u8; BLOCK_SIZE] },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// AES driver.
+pub struct Aes<'d, T: Instance, DmaIn = crate::dma::NoDma, DmaOut = crate::dma::NoDma> {
+    _peri: PeripheralRef<'d, T>,
+    indma: PeripheralRef<'d, DmaIn>,
+    outdma: PeripheralRef<'d, DmaOut>,
+}
+
+impl<'d, T: Instance, DmaIn, DmaOut> Aes<'d, T, DmaIn, DmaOut> {
+    /// Create an AES driver, the DMA channels can be `NoDma` for the blocking methods.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        indma: impl Peripheral<P = DmaIn> + 'd,
+        outdma: impl Peripheral<P = DmaOut> + 'd,
+    ) -> Self {
+        into_ref!(peri, indma, outdma);
+
+        T::enable();
+        T::reset();
+
+        Self {
+            _peri: peri,
+            indma,
+            outdma,
+        }
+    }
+
+    /// Encrypt or decrypt `buffer` in place.
+    pub fn blocking_process(
+        &mut self,
+        key: &[u8],
+        mode: Mode,
+        direction: Direction,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        start::<T>(key, mode, direction, buffer.len())?;
+        let result = process_blocks::<T>(buffer);
+        stop::<T>();
+        result
+    }
+
+    /// Encrypt or decrypt `buffer` in place, moving the data with DMA.
+    ///
+    /// `buffer` must be aligned to 4 bytes.
+    pub async fn process(
+        &mut self,
+        key: &[u8],
+        mode: Mode<'_>,
+        direction: Direction,
+        buffer: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        DmaIn: crate::aes::DmaIn<T>,
+        DmaOut: crate::aes::DmaOut<T>,
+    {
+        start::<T>(key, mode, direction, buffer.len())?;
+        let result = self.process_blocks_dma(buffer).await;
+        stop::<T>();
+        result
+    }
+
+    /// Encrypt `buffer` in place with AES-GCM, authenticating `aad` too, and return the
+    /// authentication tag.
+    ///
+    /// On the devices whose AES peripheral lacks the NPBLB field, for example the STM32L47x,
+    /// the tag is only right if `buffer` is a multiple of the block size.
+    pub fn blocking_gcm_encrypt(
+        &mut self,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; BLOCK_SIZE], Error> {
+        gcm_start::<T>(key, nonce, aad, Direction::Encrypt)?;
+        let result = process_blocks::<T>(buffer).and_then(|_| gcm_finish::<T>(aad.len(), buffer.len()));
+        stop::<T>();
+        result
+    }
+
+    /// Decrypt `buffer` in place with AES-GCM, and check the authentication `tag` of `aad` and
+    /// `buffer`.
+    ///
+    /// On an authentication error, `buffer` holds unauthenticated plaintext that must be
+    /// discarded.
+    pub fn blocking_gcm_decrypt(
+        &mut self,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error> {
+        gcm_start::<T>(key, nonce, aad, Direction::Decrypt)?;
+        let result = process_blocks::<T>(buffer).and_then(|_| gcm_finish::<T>(aad.len(), buffer.len()));
+        stop::<T>();
+        check_tag(&result?, tag)
+    }
+
+    /// Encrypt `buffer` in place with AES-GCM, moving the data with DMA, and return the
+    /// authentication tag.
+    ///
+    /// `buffer` must be aligned to 4 bytes. See [`blocking_gcm_encrypt()`](Aes::blocking_gcm_encrypt).
+    pub async fn gcm_encrypt(
+        &mut self,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; BLOCK_SIZE], Error>
+    where
+        DmaIn: crate::aes::DmaIn<T>,
+        DmaOut: crate::aes::DmaOut<T>,
+    {
+        gcm_start::<T>(key, nonce, aad, Direction::Encrypt)?;
+        let result = match self.process_blocks_dma(buffer).await {
+            Ok(()) => gcm_finish::<T>(aad.len(), buffer.len()),
+            Err(e) => Err(e),
+        };
+        stop::<T>();
+        result
+    }
+
+    /// Decrypt `buffer` in place with AES-GCM, moving the data with DMA, and check the
+    /// authentication `tag`.
+    ///
+    /// `buffer` must be aligned to 4 bytes. See [`blocking_gcm_decrypt()`](Aes::blocking_gcm_decrypt).
+    pub async fn gcm_decrypt(
+        &mut self,
+        key: &[u8],
+        nonce: &[u8; 12],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error>
+    where
+        DmaIn: crate::aes::DmaIn<T>,
+        DmaOut: crate::aes::DmaOut<T>,
+    {
+        gcm_start::<T>(key, nonce, aad, Direction::Decrypt)?;
+        let result = match self.process_blocks_dma(buffer).await {
+            Ok(()) => gcm_finish::<T>(aad.len(), buffer.len()),
+            Err(e) => Err(e),
+        };
+        stop::<T>();
+        check_tag(&result?, tag)
+    }
+
+    /// Process the full blocks of `buffer` with DMA, and the last partial block with the CPU.
+    async fn process_blocks_dma(&mut self, buffer: &mut [u8]) -> Result<(), Error>
+    where
+        DmaIn: crate::aes::DmaIn<T>,
+        DmaOut: crate::aes::DmaOut<T>,
+    {
+        let full = buffer.len() / BLOCK_SIZE * BLOCK_SIZE;
+        let (blocks, tail) = buffer.split_at_mut(full);
+
+        if !blocks.is_empty() {
+            if blocks.as_ptr() as usize % 4 != 0 {
+                return Err(Error::Alignment);
+            }
+
+            // The peripheral swaps the bytes of the words, so that they're in memory order.
+            let words = core::ptr::slice_from_raw_parts_mut(blocks.as_mut_ptr() as *mut u32, full / 4);
+
+            let r = T::regs();
+            r.cr().modify(|w| {
+                w.set_dmainen(true);
+                w.set_dmaouten(true);
+            });
+
+            let in_request = self.indma.request();
+            let out_request = self.outdma.request();
+            let in_transfer = unsafe {
+                Transfer::new_write_raw(
+                    &mut self.indma,
+                    in_request,
+                    words,
+                    r.dinr().as_ptr() as *mut u32,
+                    Default::default(),
+                )
+            };
+            let out_transfer = unsafe {
+                Transfer::new_read_raw(
+                    &mut self.outdma,
+                    out_request,
+                    r.doutr().as_ptr() as *mut u32,
+                    words,
+                    Default::default(),
+                )
+            };
+            join(in_transfer, out_transfer).await;
+
+            r.cr().modify(|w| {
+                w.set_dmainen(false);
+                w.set_dmaouten(false);
+                w.set_ccfc(true);
+            });
+            check_errors::<T>()?;
+        }
+
+        process_blocks::<T>(tail)
+    }
+}
+
+impl<'d, T: Instance, DmaIn, DmaOut> Drop for Aes<'d, T, DmaIn, DmaOut> {
+    fn drop(&mut self) {
+        stop::<T>();
+        T::disable();
+    }
+}
+
+fn wait_for_ccf<T: Instance>() -> Result<(), Error> {
+    loop {
+        check_errors::<T>()?;
+        if T::regs().sr().read().ccf() {
+            return Ok(());
+        }
+    }
+}
+
+fn check_errors<T: Instance>() -> Result<(), Error> {
+    let sr = T::regs().sr().read();
+    if sr.rderr() || sr.wrerr() {
+        T::regs().cr().modify(|w| w.set_errc(true));
+        return Err(Error::Peripheral);
+    }
+    Ok(())
+}
+
+fn clear_ccf<T: Instance>() {
+    T::regs().cr().modify(|w| w.set_ccfc(true));
+}
+
+/// Write `key` to the key registers, the first word of the key in the highest register.
+///
+/// Returns whether the key is 256 bits long.
+fn write_key<T: Instance>(key: &[u8]) -> Result<bool, Error> {
+    let keysize = match key.len() {
+        16 => false,
+        32 => true,
+        _ => return Err(Error::KeySize),
+    };
+
+    let r = T::regs();
+    let words = key.len() / 4;
+    for (i, chunk) in key.chunks_exact(4).enumerate() {
+        let n = words - 1 - i;
+        let value = u32::from_be_bytes(chunk.try_into().unwrap());
+        match n {
+            0..=3 => r.keyr(n).write_value(value),
+            _ => r.keyr4(n - 4).write_value(value),
+        }
+    }
+
+    Ok(keysize)
+}
+
+/// Write `iv` to the IV registers, the first word of the IV in the highest register.
+fn write_iv<T: Instance>(iv: &[u8; BLOCK_SIZE]) {
+    for (i, chunk) in iv.chunks_exact(4).enumerate() {
+        T::regs()
+            .ivr(3 - i)
+            .write_value(u32::from_be_bytes(chunk.try_into().unwrap()));
+    }
+}
+
+/// Configure the peripheral for a mode and a direction, and enable it.
+fn start<T: Instance>(key: &[u8], mode: Mode, direction: Direction, len: usize) -> Result<(), Error> {
+    let (chmod, iv) = match mode {
+        Mode::Ecb => (CHMOD_ECB, None),
+        Mode::Cbc { iv } => (CHMOD_CBC, Some(iv)),
+        Mode::Ctr { iv } => (CHMOD_CTR, Some(iv)),
+    };
+    if chmod != CHMOD_CTR && len % BLOCK_SIZE != 0 {
+        return Err(Error::Length);
+    }
+
+    let r = T::regs();
+    r.cr().write(|_| {});
+    let keysize = write_key::<T>(key)?;
+    let mut cr = regs::Cr(0);
+    cr.set_datatype(DATATYPE_BYTE);
+    cr.set_chmod(chmod);
+    cr.set_keysize(keysize);
+
+    // The ECB and CBC decryptions use the key schedule, derived from the key first.
+    let mode = match direction {
+        Direction::Encrypt => MODE_ENCRYPT,
+        Direction::Decrypt if chmod == CHMOD_CTR => MODE_ENCRYPT,
+        Direction::Decrypt => {
+            r.cr().write_value(cr);
+            r.cr().modify(|w| w.set_mode(MODE_KEY_DERIVATION));
+            r.cr().modify(|w| w.set_en(true));
+            wait_for_ccf::<T>()?;
+            clear_ccf::<T>();
+            MODE_DECRYPT
+        }
+    };
+
+    if let Some(iv) = iv {
+        write_iv::<T>(iv);
+    }
+    cr.set_mode(mode);
+    r.cr().write_value(cr);
+    r.cr().modify(|w| w.set_en(true));
+    Ok(())
+}
+
+fn stop<T: Instance>() {
+    T::regs().cr().modify(|w| {
+        w.set_en(false);
+        w.set_dmainen(false);
+        w.set_dmaouten(false);
+    });
+}
+
+/// Process a block, padding it with zeros if it's shorter than the block size.
+fn process_block<T: Instance>(block: &mut [u8]) -> Result<(), Error> {
+    let mut padded = [0; BLOCK_SIZE];
+    padded[..block.len()].copy_from_slice(block);
+
+    write_block::<T>(&padded);
+    wait_for_ccf::<T>()?;
+    read_block::<T>(&mut padded);
+    clear_ccf::<T>();
+
+    block.copy_from_slice(&padded[..block.len()]);
+    Ok(())
+}
+
+fn process_blocks<T: Instance>(buffer: &mut [u8]) -> Result<(), Error> {
+    for block in buffer.chunks_mut(BLOCK_SIZE) {
+        if block.len() < BLOCK_SIZE {
+            // Exclude the padding from the GCM tag.
+            let npblb = (BLOCK_SIZE - block.len()) as u8;
+            T::regs().cr().modify(|w| w.set_npblb(npblb));
+        }
+        process_block::<T>(block)?;
+    }
+    T::regs().cr().modify(|w| w.set_npblb(0));
+    Ok(())
+}
+
+fn write_block<T: Instance>(block: &[u8; BLOCK_SIZE]) {
+    for chunk in block.chunks_exact(4) {
+        T::regs()
+            .dinr()
+            .write_value(u32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+}
+
+fn read_block<T: Instance>(block: &mut [u8; BLOCK_SIZE]) {
+    for chunk in block.chunks_exact_mut(4) {
+        chunk.copy_from_slice(&T::regs().doutr().read().to_le_bytes());
+    }
+}
+
+fn set_gcm_phase<T: Instance>(phase: u8) {
+    T::regs().cr().modify(|w| {
+        w.set_gcmph(phase);
+        w.set_en(true);
+    });
+}
+
+/// Configure the peripheral for GCM, compute the hash key, and authenticate `aad`. The
+/// peripheral is then ready for the payload.
+fn gcm_start<T: Instance>(key: &[u8], nonce: &[u8; 12], aad: &[u8], direction: Direction) -> Result<(), Error> {
+    let r = T::regs();
+    r.cr().write(|_| {});
+    let keysize = write_key::<T>(key)?;
+    let mode = match direction {
+        Direction::Encrypt => MODE_ENCRYPT,
+        Direction::Decrypt => MODE_DECRYPT,
+    };
+    r.cr().write(|w| {
+        w.set_datatype(DATATYPE_BYTE);
+        w.set_mode(mode);
+        w.set_chmod(CHMOD_GCM);
+        w.set_keysize(keysize);
+    });
+
+    // The payload starts at counter 2, counter 1 encrypts the tag.
+    let mut iv = [0; BLOCK_SIZE];
+    iv[..12].copy_from_slice(nonce);
+    iv[15] = 2;
+    write_iv::<T>(&iv);
+
+    // The peripheral disables itself once the hash key is computed.
+    set_gcm_phase::<T>(GCMPH_INIT);
+    wait_for_ccf::<T>()?;
+    clear_ccf::<T>();
+
+    if !aad.is_empty() {
+        set_gcm_phase::<T>(GCMPH_HEADER);
+        for block in aad.chunks(BLOCK_SIZE) {
+            let mut padded = [0; BLOCK_SIZE];
+            padded[..block.len()].copy_from_slice(block);
+            write_block::<T>(&padded);
+            wait_for_ccf::<T>()?;
+            clear_ccf::<T>();
+        }
+    }
+
+    set_gcm_phase::<T>(GCMPH_PAYLOAD);
+    Ok(())
+}
+
+/// Authenticate the lengths of the data, and return the tag.
+fn gcm_finish<T: Instance>(aad_len: usize, payload_len: usize) -> Result<[u8; BLOCK_SIZE], Error> {
+    set_gcm_phase::<T>(GCMPH_FINAL);
+
+    let mut block = [0; BLOCK_SIZE];
+    block[..8].copy_from_slice(&(aad_len as u64 * 8).to_be_bytes());
+    block[8..].copy_from_slice(&(payload_len as u64 * 8).to_be_bytes());
+    write_block::<T>(&block);
+    wait_for_ccf::<T>()?;
+
+    let mut tag = [0; BLOCK_SIZE];
+    read_block::<T>(&mut tag);
+    clear_ccf::<T>();
+    Ok(tag)
+}
+
+/// Compare the tags in constant time.
+fn check_tag(computed: &[u8; BLOCK_SIZE], tag: &[u8; BLOCK_SIZE]) -> Result<(), Error> {
+    let diff = computed.iter().zip(tag).fold(0, |diff, (a, b)| diff | (a ^ b));
+    if diff == 0 {
+        Ok(())
+    } else {
+        Err(Error::Authentication)
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::aes::Aes;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {}
+
+dma_trait!(DmaIn, Instance);
+dma_trait!(DmaOut, Instance);
+
+foreach_peripheral!(
+    (aes, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::aes::Aes {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+);
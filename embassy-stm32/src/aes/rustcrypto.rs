@@ -0,0 +1,260 @@
+//! RustCrypto trait implementations, with blocking processing.
+
+use core::cell::RefCell;
+
+use aead::consts::{U0, U1, U12, U16};
+use aead::{AeadCore, AeadInPlace, Nonce, Tag};
+use cipher::inout::{InOut, InOutBuf};
+use cipher::{
+    Block, BlockBackend, BlockClosure, BlockDecryptMut, BlockEncryptMut, BlockSizeUser, ParBlocksSizeUser,
+    StreamCipher, StreamCipherError,
+};
+
+use super::{Aes, Direction, Error, Instance, Mode, BLOCK_SIZE};
+
+/// A key of 16 or 32 bytes.
+#[derive(Clone)]
+struct Key {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Key {
+    fn new(key: &[u8]) -> Result<Self, Error> {
+        if key.len() != 16 && key.len() != 32 {
+            return Err(Error::KeySize);
+        }
+        let mut bytes = [0; 32];
+        bytes[..key.len()].copy_from_slice(key);
+        Ok(Self { bytes, len: key.len() })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// AES-GCM with a 96-bit nonce, implementing [`AeadInPlace`].
+pub struct AesGcm<'a, 'd, T: Instance, DmaIn, DmaOut> {
+    aes: RefCell<&'a mut Aes<'d, T, DmaIn, DmaOut>>,
+    key: Key,
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesGcm<'a, 'd, T, DmaIn, DmaOut> {
+    pub fn new(aes: &'a mut Aes<'d, T, DmaIn, DmaOut>, key: &[u8]) -> Result<Self, Error> {
+        Ok(Self {
+            aes: RefCell::new(aes),
+            key: Key::new(key)?,
+        })
+    }
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AeadCore for AesGcm<'a, 'd, T, DmaIn, DmaOut> {
+    type NonceSize = U12;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AeadInPlace for AesGcm<'a, 'd, T, DmaIn, DmaOut> {
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        let nonce = nonce.as_slice().try_into().unwrap();
+        let tag = self
+            .aes
+            .borrow_mut()
+            .blocking_gcm_encrypt(self.key.as_slice(), nonce, associated_data, buffer)
+            .map_err(|_| aead::Error)?;
+        Ok(Tag::<Self>::clone_from_slice(&tag))
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        let nonce = nonce.as_slice().try_into().unwrap();
+        let tag = tag.as_slice().try_into().unwrap();
+        self.aes
+            .borrow_mut()
+            .blocking_gcm_decrypt(self.key.as_slice(), nonce, associated_data, buffer, tag)
+            .map_err(|_| aead::Error)
+    }
+}
+
+/// AES-CTR with a 32-bit big-endian counter, like `ctr::Ctr32BE`, implementing
+/// [`StreamCipher`].
+pub struct AesCtr<'a, 'd, T: Instance, DmaIn, DmaOut> {
+    aes: &'a mut Aes<'d, T, DmaIn, DmaOut>,
+    key: Key,
+    counter: [u8; BLOCK_SIZE],
+    keystream: [u8; BLOCK_SIZE],
+    /// Number of bytes of `keystream` already used.
+    pos: usize,
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesCtr<'a, 'd, T, DmaIn, DmaOut> {
+    pub fn new(aes: &'a mut Aes<'d, T, DmaIn, DmaOut>, key: &[u8], iv: &[u8; BLOCK_SIZE]) -> Result<Self, Error> {
+        Ok(Self {
+            aes,
+            key: Key::new(key)?,
+            counter: *iv,
+            keystream: [0; BLOCK_SIZE],
+            pos: BLOCK_SIZE,
+        })
+    }
+
+    fn add_to_counter(&mut self, blocks: usize) {
+        let n = u32::from_be_bytes(self.counter[12..].try_into().unwrap());
+        self.counter[12..].copy_from_slice(&n.wrapping_add(blocks as u32).to_be_bytes());
+    }
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> StreamCipher for AesCtr<'a, 'd, T, DmaIn, DmaOut> {
+    fn try_apply_keystream_inout(&mut self, buf: InOutBuf<'_, '_, u8>) -> Result<(), StreamCipherError> {
+        let buffer = buf.into_out_with_copied_in();
+
+        // The rest of the keystream of the previous call.
+        let n = (BLOCK_SIZE - self.pos).min(buffer.len());
+        for (b, k) in buffer[..n].iter_mut().zip(&self.keystream[self.pos..]) {
+            *b ^= k;
+        }
+        self.pos += n;
+        let buffer = &mut buffer[n..];
+
+        let full = buffer.len() / BLOCK_SIZE * BLOCK_SIZE;
+        let (blocks, tail) = buffer.split_at_mut(full);
+        let iv = self.counter;
+        self.aes
+            .blocking_process(self.key.as_slice(), Mode::Ctr { iv: &iv }, Direction::Encrypt, blocks)
+            .map_err(|_| StreamCipherError)?;
+        self.add_to_counter(full / BLOCK_SIZE);
+
+        if !tail.is_empty() {
+            // Keep the keystream of the last block for the next call.
+            let iv = self.counter;
+            self.keystream = [0; BLOCK_SIZE];
+            self.aes
+                .blocking_process(
+                    self.key.as_slice(),
+                    Mode::Ctr { iv: &iv },
+                    Direction::Encrypt,
+                    &mut self.keystream,
+                )
+                .map_err(|_| StreamCipherError)?;
+            self.add_to_counter(1);
+
+            for (b, k) in tail.iter_mut().zip(&self.keystream) {
+                *b ^= k;
+            }
+            self.pos = tail.len();
+        }
+
+        Ok(())
+    }
+}
+
+/// AES-CBC encryption, implementing [`BlockEncryptMut`].
+pub struct AesCbcEncryptor<'a, 'd, T: Instance, DmaIn, DmaOut> {
+    aes: &'a mut Aes<'d, T, DmaIn, DmaOut>,
+    key: Key,
+    iv: [u8; BLOCK_SIZE],
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesCbcEncryptor<'a, 'd, T, DmaIn, DmaOut> {
+    pub fn new(aes: &'a mut Aes<'d, T, DmaIn, DmaOut>, key: &[u8], iv: &[u8; BLOCK_SIZE]) -> Result<Self, Error> {
+        Ok(Self {
+            aes,
+            key: Key::new(key)?,
+            iv: *iv,
+        })
+    }
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockSizeUser for AesCbcEncryptor<'a, 'd, T, DmaIn, DmaOut> {
+    type BlockSize = U16;
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockEncryptMut for AesCbcEncryptor<'a, 'd, T, DmaIn, DmaOut> {
+    fn encrypt_with_backend_mut(&mut self, f: impl BlockClosure<BlockSize = Self::BlockSize>) {
+        f.call(&mut CbcBackend {
+            aes: &mut *self.aes,
+            key: &self.key,
+            iv: &mut self.iv,
+            direction: Direction::Encrypt,
+        })
+    }
+}
+
+/// AES-CBC decryption, implementing [`BlockDecryptMut`].
+pub struct AesCbcDecryptor<'a, 'd, T: Instance, DmaIn, DmaOut> {
+    aes: &'a mut Aes<'d, T, DmaIn, DmaOut>,
+    key: Key,
+    iv: [u8; BLOCK_SIZE],
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesCbcDecryptor<'a, 'd, T, DmaIn, DmaOut> {
+    pub fn new(aes: &'a mut Aes<'d, T, DmaIn, DmaOut>, key: &[u8], iv: &[u8; BLOCK_SIZE]) -> Result<Self, Error> {
+        Ok(Self {
+            aes,
+            key: Key::new(key)?,
+            iv: *iv,
+        })
+    }
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockSizeUser for AesCbcDecryptor<'a, 'd, T, DmaIn, DmaOut> {
+    type BlockSize = U16;
+}
+
+impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockDecryptMut for AesCbcDecryptor<'a, 'd, T, DmaIn, DmaOut> {
+    fn decrypt_with_backend_mut(&mut self, f: impl BlockClosure<BlockSize = Self::BlockSize>) {
+        f.call(&mut CbcBackend {
+            aes: &mut *self.aes,
+            key: &self.key,
+            iv: &mut self.iv,
+            direction: Direction::Decrypt,
+        })
+    }
+}
+
+/// Processes the blocks one by one, chaining them through `iv`.
+struct CbcBackend<'b, 'd, T: Instance, DmaIn, DmaOut> {
+    aes: &'b mut Aes<'d, T, DmaIn, DmaOut>,
+    key: &'b Key,
+    iv: &'b mut [u8; BLOCK_SIZE],
+    direction: Direction,
+}
+
+impl<'b, 'd, T: Instance, DmaIn, DmaOut> BlockSizeUser for CbcBackend<'b, 'd, T, DmaIn, DmaOut> {
+    type BlockSize = U16;
+}
+
+impl<'b, 'd, T: Instance, DmaIn, DmaOut> ParBlocksSizeUser for CbcBackend<'b, 'd, T, DmaIn, DmaOut> {
+    type ParBlocksSize = U1;
+}
+
+impl<'b, 'd, T: Instance, DmaIn, DmaOut> BlockBackend for CbcBackend<'b, 'd, T, DmaIn, DmaOut> {
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut data = [0; BLOCK_SIZE];
+        data.copy_from_slice(block.get_in());
+        let input = data;
+
+        let iv = *self.iv;
+        // The key and the block size are checked on construction.
+        unwrap!(self
+            .aes
+            .blocking_process(self.key.as_slice(), Mode::Cbc { iv: &iv }, self.direction, &mut data));
+
+        *self.iv = match self.direction {
+            Direction::Encrypt => data,
+            Direction::Decrypt => input,
+        };
+        block.get_out().copy_from_slice(&data);
+    }
+}
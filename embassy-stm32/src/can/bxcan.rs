@@ -3,6 +3,7 @@ use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
 use core::task::Poll;
 
+use atomic_polyfill::Ordering;
 pub use bxcan;
 use bxcan::{Data, ExtendedId, Frame, Id, StandardId};
 use embassy_hal_common::{into_ref, PeripheralRef};
@@ -87,6 +88,10 @@ pub enum BusError {
     BusOff,
     BusPassive,
     BusWarning,
+    /// Received frames were lost, because the RX FIFOs or the RX buffer were full.
+    Overrun,
+    /// The frame is a CAN FD frame, which bxCAN can't send.
+    FdFrame,
 }
 
 impl<'d, T: Instance> Can<'d, T> {
@@ -120,6 +125,9 @@ impl<'d, T: Instance> Can<'d, T> {
                 w.set_fmpie(0, Fmpie::from_bits(1));
                 w.set_fmpie(1, Fmpie::from_bits(1));
                 w.set_tmeie(Tmeie::from_bits(1));
+                w.set_ewgie(true);
+                w.set_epvie(true);
+                w.set_bofie(true);
             });
 
             T::regs().mcr().write(|w| {
@@ -181,10 +189,18 @@ impl<'d, T: Instance> Can<'d, T> {
     }
 
     /// Returns a tuple of the time the message was received and the message frame
+    ///
+    /// Frames are received by the interrupt handlers, and buffered until read. Frames received
+    /// while the buffer is full are dropped, which is reported once as [`BusError::Overrun`].
+    ///
+    /// Which frames are received, and in which FIFO, is set by the filter banks, configured with
+    /// [`modify_filters`](bxcan::Can::modify_filters).
     pub async fn read(&mut self) -> Result<(u16, bxcan::Frame), BusError> {
         poll_fn(|cx| {
             T::state().err_waker.register(cx.waker());
-            if let Poll::Ready((time, frame)) = T::state().rx_queue.recv().poll_unpin(cx) {
+            if T::state().rx_overrun.swap(false, Ordering::Relaxed) {
+                return Poll::Ready(Err(BusError::Overrun));
+            } else if let Poll::Ready((time, frame)) = T::state().rx_queue.recv().poll_unpin(cx) {
                 return Poll::Ready(Ok((time, frame)));
             } else if let Some(err) = self.curr_error() {
                 return Poll::Ready(Err(err));
@@ -195,6 +211,14 @@ impl<'d, T: Instance> Can<'d, T> {
         .await
     }
 
+    /// Returns a buffered frame, if any, without waiting.
+    pub fn try_read(&mut self) -> Result<Option<(u16, bxcan::Frame)>, BusError> {
+        if T::state().rx_overrun.swap(false, Ordering::Relaxed) {
+            return Err(BusError::Overrun);
+        }
+        Ok(T::state().rx_queue.try_recv().ok())
+    }
+
     fn curr_error(&self) -> Option<BusError> {
         let err = { T::regs().esr().read() };
         if err.boff() {
@@ -220,8 +244,15 @@ impl<'d, T: Instance> Can<'d, T> {
         let fifo = regs.rx(fifo_idx);
 
         loop {
+            let rfr_val = rfr.read();
+            if rfr_val.fovr() {
+                rfr.modify(|v| v.set_fovr(true));
+                state.rx_overrun.store(true, Ordering::Relaxed);
+                state.err_waker.wake();
+            }
+
             // If there are no pending messages, there is nothing to do
-            if rfr.read().fmp() == 0 {
+            if rfr_val.fmp() == 0 {
                 return;
             }
 
@@ -247,7 +278,10 @@ impl<'d, T: Instance> Can<'d, T> {
             /*
                 NOTE: consensus was reached that if rx_queue is full, packets should be dropped
             */
-            let _ = state.rx_queue.try_send((time, frame));
+            if state.rx_queue.try_send((time, frame)).is_err() {
+                state.rx_overrun.store(true, Ordering::Relaxed);
+                state.err_waker.wake();
+            }
         }
     }
 
@@ -372,6 +406,9 @@ impl<'d, T: Instance> DerefMut for Can<'d, T> {
 }
 
 pub(crate) mod sealed {
+    use atomic_polyfill::AtomicBool;
+    #[cfg(feature = "nightly")]
+    use atomic_polyfill::AtomicU8;
     use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
     use embassy_sync::channel::Channel;
     use embassy_sync::waitqueue::AtomicWaker;
@@ -380,6 +417,10 @@ pub(crate) mod sealed {
         pub tx_waker: AtomicWaker,
         pub err_waker: AtomicWaker,
         pub rx_queue: Channel<CriticalSectionRawMutex, (u16, bxcan::Frame), 32>,
+        pub rx_overrun: AtomicBool,
+        /// Last bus state reported by `embassy_can::Receive::receive`.
+        #[cfg(feature = "nightly")]
+        pub reported_bus_state: AtomicU8,
     }
 
     impl State {
@@ -388,6 +429,9 @@ pub(crate) mod sealed {
                 tx_waker: AtomicWaker::new(),
                 err_waker: AtomicWaker::new(),
                 rx_queue: Channel::new(),
+                rx_overrun: AtomicBool::new(false),
+                #[cfg(feature = "nightly")]
+                reported_bus_state: AtomicU8::new(0),
             }
         }
     }
@@ -539,3 +583,212 @@ impl IntoBusError for Lec {
         }
     }
 }
+
+#[cfg(feature = "nightly")]
+pub use can_traits::{CanRx, CanTx};
+
+#[cfg(feature = "nightly")]
+mod can_traits {
+    use bxcan::filter::Mask32;
+    use embassy_can::{BusState, ErrorKind, Filter, Frame, TooManyFilters};
+
+    use super::super::id;
+    use super::*;
+
+    impl embassy_can::Error for BusError {
+        fn kind(&self) -> ErrorKind {
+            match self {
+                BusError::Stuff => ErrorKind::Stuff,
+                BusError::Form => ErrorKind::Form,
+                BusError::Acknowledge => ErrorKind::Acknowledge,
+                BusError::BitRecessive => ErrorKind::BitRecessive,
+                BusError::BitDominant => ErrorKind::BitDominant,
+                BusError::Crc => ErrorKind::Crc,
+                BusError::Software => ErrorKind::Other,
+                BusError::BusOff => ErrorKind::BusOff,
+                BusError::BusPassive => ErrorKind::ErrorPassive,
+                BusError::BusWarning => ErrorKind::ErrorWarning,
+                BusError::Overrun => ErrorKind::Overrun,
+                BusError::FdFrame => ErrorKind::Other,
+            }
+        }
+    }
+
+    fn to_bxcan(frame: &Frame) -> Result<bxcan::Frame, BusError> {
+        if frame.is_fd() {
+            return Err(BusError::FdFrame);
+        }
+        let id = id::to_bxcan(frame.id());
+        if frame.is_remote_frame() {
+            Ok(bxcan::Frame::new_remote(id, frame.len() as u8))
+        } else {
+            Ok(bxcan::Frame::new_data(id, unwrap!(Data::new(frame.data()))))
+        }
+    }
+
+    fn from_bxcan(frame: &bxcan::Frame) -> Frame {
+        let id = id::from_bxcan(frame.id());
+        match frame.data() {
+            Some(data) => unwrap!(Frame::new_data(id, data)),
+            None => unwrap!(Frame::new_remote(id, frame.dlc())),
+        }
+    }
+
+    fn bus_state<T: Instance>() -> BusState {
+        let esr = T::regs().esr().read();
+        if esr.boff() {
+            BusState::BusOff
+        } else if esr.epvf() {
+            BusState::ErrorPassive
+        } else if esr.ewgf() {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::ErrorType for Can<'d, T> {
+        type Error = BusError;
+    }
+
+    impl<'d, T: Instance> embassy_can::Transmit for Can<'d, T> {
+        async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+            let mut frame = to_bxcan(frame)?;
+            loop {
+                // The mailboxes are sent by identifier priority. When they are full, a frame with a
+                // higher priority than a pending one takes its mailbox: queue that one again.
+                match self.write(&frame).await.dequeued_frame() {
+                    Some(dequeued) => frame = dequeued.clone(),
+                    None => return Ok(()),
+                }
+            }
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            poll_fn(|cx| {
+                T::state().tx_waker.register(cx.waker());
+                let tsr = T::regs().tsr().read();
+                if (0..3).all(|mb| tsr.tme(mb)) {
+                    return Poll::Ready(Ok(()));
+                }
+
+                Poll::Pending
+            })
+            .await
+        }
+    }
+
+    async fn receive<T: Instance>() -> Result<Frame, BusError> {
+        poll_fn(|cx| {
+            let state = T::state();
+            state.err_waker.register(cx.waker());
+            if state.rx_overrun.swap(false, Ordering::Relaxed) {
+                return Poll::Ready(Err(BusError::Overrun));
+            } else if let Poll::Ready((_, frame)) = state.rx_queue.recv().poll_unpin(cx) {
+                return Poll::Ready(Ok(from_bxcan(&frame)));
+            }
+
+            // Report the bus state when it gets worse than the last one reported.
+            let bus_state = bus_state::<T>();
+            let reported = state.reported_bus_state.swap(bus_state as u8, Ordering::Relaxed);
+            if bus_state as u8 > reported {
+                return Poll::Ready(Err(match bus_state {
+                    BusState::BusOff => BusError::BusOff,
+                    BusState::ErrorPassive => BusError::BusPassive,
+                    _ => BusError::BusWarning,
+                }));
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    impl<'d, T: Instance> embassy_can::Receive for Can<'d, T> {
+        async fn receive(&mut self) -> Result<Frame, Self::Error> {
+            receive::<T>().await
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::Filters for Can<'d, T>
+    where
+        BxcanInstance<'d, T>: bxcan::FilterOwner,
+    {
+        /// Configures a filter bank for each filter, the frames they match going to FIFO 0.
+        ///
+        /// Up to 14 filters are supported: on dual CAN devices, CAN1 has half of the banks with the
+        /// default split.
+        fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters> {
+            let num_banks = <BxcanInstance<'d, T> as bxcan::FilterOwner>::NUM_FILTER_BANKS.min(14);
+            if filters.len() > num_banks as usize {
+                return Err(TooManyFilters);
+            }
+
+            let mut banks = self.can.modify_filters();
+            banks.clear();
+            for (i, filter) in filters.iter().enumerate() {
+                let mask = if filter.is_extended() {
+                    Mask32::frames_with_ext_id(
+                        unwrap!(ExtendedId::new(filter.id())),
+                        unwrap!(ExtendedId::new(filter.mask())),
+                    )
+                } else {
+                    Mask32::frames_with_std_id(
+                        unwrap!(StandardId::new(filter.id() as u16)),
+                        unwrap!(StandardId::new(filter.mask() as u16)),
+                    )
+                };
+                banks.enable_bank(i as u8, bxcan::Fifo::Fifo0, mask);
+            }
+            Ok(())
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::Status for Can<'d, T> {
+        fn bus_state(&self) -> BusState {
+            bus_state::<T>()
+        }
+    }
+
+    /// Transmit half of [`Can`], from [`Can::split`].
+    pub struct CanTx<'c, 'd, T: Instance> {
+        can: &'c mut Can<'d, T>,
+    }
+
+    /// Receive half of [`Can`], from [`Can::split`].
+    pub struct CanRx<'c, 'd, T: Instance> {
+        _can: PhantomData<&'c Can<'d, T>>,
+    }
+
+    impl<'d, T: Instance> Can<'d, T> {
+        /// Split the driver into its transmit and receive halves, to use them from different tasks
+        /// with the `embassy-can` traits.
+        pub fn split<'c>(&'c mut self) -> (CanTx<'c, 'd, T>, CanRx<'c, 'd, T>) {
+            (CanTx { can: self }, CanRx { _can: PhantomData })
+        }
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::ErrorType for CanTx<'c, 'd, T> {
+        type Error = BusError;
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::Transmit for CanTx<'c, 'd, T> {
+        async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+            embassy_can::Transmit::transmit(&mut *self.can, frame).await
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            embassy_can::Transmit::flush(&mut *self.can).await
+        }
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::ErrorType for CanRx<'c, 'd, T> {
+        type Error = BusError;
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::Receive for CanRx<'c, 'd, T> {
+        async fn receive(&mut self) -> Result<Frame, Self::Error> {
+            receive::<T>().await
+        }
+    }
+}
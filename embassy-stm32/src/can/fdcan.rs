@@ -0,0 +1,900 @@
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ptr;
+use core::task::Poll;
+
+pub use bxcan::{ExtendedId, Id, StandardId};
+use embassy_hal_common::{into_ref, PeripheralRef};
+use futures::FutureExt;
+
+use crate::gpio::sealed::AFType;
+use crate::interrupt::typelevel::Interrupt;
+use crate::rcc::RccPeripheral;
+use crate::time::Hertz;
+use crate::{interrupt, peripherals, Peripheral};
+
+/// Maximum length of the data of a frame.
+pub const MAX_DATA_LEN: usize = 64;
+
+// Message RAM layout of each instance, in 32-bit words. It's fixed on G0/G4/L5/U5, and
+// programmed the same way on H7, where the message RAM is shared by all instances.
+const STD_FILTERS: usize = 0;
+const EXT_FILTERS: usize = STD_FILTERS + STD_FILTERS_LEN;
+const RX_FIFO: [usize; 2] = [
+    EXT_FILTERS + EXT_FILTERS_LEN * 2,
+    EXT_FILTERS + EXT_FILTERS_LEN * 2 + 3 * ELEMENT_WORDS,
+];
+const TX_EVENT_FIFO: usize = RX_FIFO[1] + 3 * ELEMENT_WORDS;
+const TX_BUFFERS: usize = TX_EVENT_FIFO + 3 * 2;
+const INSTANCE_WORDS: usize = TX_BUFFERS + 3 * ELEMENT_WORDS;
+
+/// Size of the RX and TX elements: 2 header words and 64 data bytes.
+const ELEMENT_WORDS: usize = 2 + MAX_DATA_LEN / 4;
+const STD_FILTERS_LEN: usize = 28;
+const EXT_FILTERS_LEN: usize = 8;
+const RX_FIFO_LEN: u8 = 3;
+const TX_BUFFERS_LEN: u8 = 3;
+
+#[cfg(stm32g0)]
+const MSG_RAM_BASE: usize = 0x4000_b400;
+#[cfg(stm32g4)]
+const MSG_RAM_BASE: usize = 0x4000_a400;
+#[cfg(any(stm32h7, stm32l5, stm32u5))]
+const MSG_RAM_BASE: usize = 0x4000_ac00;
+
+/// Interrupt handler.
+pub struct IT0InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::IT0Interrupt> for IT0InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = T::regs();
+        let ir = regs.ir().read();
+
+        if ir.rfn(0) || ir.rfn(1) {
+            regs.ir().write(|w| {
+                w.set_rfn(0, true);
+                w.set_rfn(1, true);
+            });
+            Fdcan::<T>::receive_fifo(0);
+            Fdcan::<T>::receive_fifo(1);
+        }
+
+        if ir.tc() {
+            regs.ir().write(|w| w.set_tc(true));
+            T::state().tx_waker.wake();
+        }
+
+        if ir.bo() || ir.ep() || ir.ew() {
+            regs.ir().write(|w| {
+                w.set_bo(true);
+                w.set_ep(true);
+                w.set_ew(true);
+            });
+            T::state().err_waker.wake();
+        }
+    }
+}
+
+/// A classic CAN or CAN FD frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    fd: bool,
+    brs: bool,
+    len: u8,
+    data: [u8; MAX_DATA_LEN],
+}
+
+impl Frame {
+    /// Create a classic CAN data frame.
+    ///
+    /// Returns `None` if `data` is longer than 8 bytes.
+    pub fn new_data(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+        Some(Self::new(id.into(), false, false, data))
+    }
+
+    /// Create a CAN FD data frame.
+    ///
+    /// When `brs` is set, the data is sent at the data bitrate set with
+    /// [`Fdcan::set_fd_data_bitrate`]. Returns `None` if the length of `data` isn't a valid CAN FD
+    /// length: 0 to 8, 12, 16, 20, 24, 32, 48 or 64 bytes.
+    pub fn new_fd(id: impl Into<Id>, data: &[u8], brs: bool) -> Option<Self> {
+        len_to_dlc(data.len())?;
+        Some(Self::new(id.into(), true, brs, data))
+    }
+
+    /// Create a classic CAN remote frame, requesting `dlc` bytes.
+    ///
+    /// Returns `None` if `dlc` is greater than 8.
+    pub fn new_remote(id: impl Into<Id>, dlc: u8) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+        let mut frame = Self::new(id.into(), false, false, &[]);
+        frame.remote = true;
+        frame.len = dlc;
+        Some(frame)
+    }
+
+    fn new(id: Id, fd: bool, brs: bool, data: &[u8]) -> Self {
+        let mut frame = Self {
+            id,
+            remote: false,
+            fd,
+            brs,
+            len: data.len() as u8,
+            data: [0; MAX_DATA_LEN],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        frame
+    }
+
+    /// Identifier of the frame.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Whether this is a remote frame.
+    pub fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    /// Whether this is a CAN FD frame.
+    pub fn is_fd(&self) -> bool {
+        self.fd
+    }
+
+    /// Whether the data of this CAN FD frame is sent at the data bitrate.
+    pub fn bit_rate_switching(&self) -> bool {
+        self.brs
+    }
+
+    /// Length of the data, or the requested length for a remote frame.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the length is 0.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Data of the frame. Empty for a remote frame.
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.len as usize]
+        }
+    }
+}
+
+/// Operating mode, selected when enabling the peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OperatingMode {
+    /// Normal operation on the bus.
+    Normal,
+    /// Transmitted frames are received back, and nothing is sent on the bus.
+    InternalLoopback,
+    /// Transmitted frames are received back, and also sent on the bus.
+    ExternalLoopback,
+    /// Frames are received from the bus, but nothing is sent, not even acknowledges.
+    BusMonitoring,
+}
+
+/// Bus error.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusError {
+    Stuff,
+    Form,
+    Acknowledge,
+    BitRecessive,
+    BitDominant,
+    Crc,
+    BusOff,
+    BusPassive,
+    BusWarning,
+}
+
+/// FDCAN driver.
+pub struct Fdcan<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Fdcan<'d, T> {
+    /// Creates a new FDCAN driver, keeping the peripheral in initialization mode.
+    ///
+    /// The bitrates must be set before calling [`Fdcan::enable`]. Frames with any identifier are
+    /// received.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::IT0Interrupt, IT0InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(peri, rx, tx);
+
+        rx.set_as_af(rx.af_num(), AFType::Input);
+        tx.set_as_af(tx.af_num(), AFType::OutputPushPull);
+
+        T::enable();
+        T::reset();
+
+        let regs = T::regs();
+        regs.cccr().modify(|w| w.set_init(true));
+        while !regs.cccr().read().init() {}
+        regs.cccr().modify(|w| w.set_cce(true));
+
+        #[cfg(can_fdcan_h7)]
+        {
+            let base = (T::MSG_RAM_INDEX * INSTANCE_WORDS) as u16;
+            regs.sidfc().write(|w| {
+                w.set_flssa(base + STD_FILTERS as u16);
+                w.set_lss(0);
+            });
+            regs.xidfc().write(|w| {
+                w.set_flesa(base + EXT_FILTERS as u16);
+                w.set_lse(0);
+            });
+            for (n, &start) in RX_FIFO.iter().enumerate() {
+                regs.rxfc(n).write(|w| {
+                    w.set_fsa(base + start as u16);
+                    w.set_fs(RX_FIFO_LEN);
+                });
+            }
+            regs.txefc().write(|w| {
+                w.set_efsa(base + TX_EVENT_FIFO as u16);
+                w.set_efs(0);
+            });
+            regs.txbc().write(|w| {
+                w.set_tbsa(base + TX_BUFFERS as u16);
+                w.set_tfqs(TX_BUFFERS_LEN);
+            });
+            // 64 bytes of data in all the RX and TX elements.
+            regs.rxesc().write(|w| w.0 = 0x777);
+            regs.txesc().write(|w| w.0 = 0x7);
+        }
+
+        // Send the queued frames by identifier priority (TFQM), like the arbitration on the bus.
+        regs.txbc().modify(|w| w.0 |= 1 << 24);
+
+        // Timestamps from the internal counter, in bit times.
+        regs.tscc().write(|w| w.0 = 0x1);
+
+        regs.ie().write(|w| {
+            w.set_rfne(0, true);
+            w.set_rfne(1, true);
+            w.set_tce(true);
+            w.set_boe(true);
+            w.set_epe(true);
+            w.set_ewe(true);
+        });
+        regs.ile().write(|w| w.set_eint0(true));
+        // Raise TC for all the TX buffers.
+        regs.txbtie().write(|w| w.0 = (1 << TX_BUFFERS_LEN) - 1);
+
+        T::IT0Interrupt::unpend();
+        unsafe { T::IT0Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Set the nominal bitrate, used for the arbitration phase and for classic CAN frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bitrate can't be reached from the peripheral clock.
+    pub fn set_bitrate(&mut self, bitrate: u32) {
+        let t = unwrap!(calc_timings(T::frequency(), bitrate, 512, 256, 128));
+        T::regs().nbtp().write(|w| {
+            w.set_nbrp(t.prescaler - 1);
+            w.set_ntseg1((t.seg1 - 1) as u8);
+            w.set_ntseg2((t.seg2 - 1) as u8);
+            w.set_nsjw((t.seg2 - 1) as u8);
+        });
+    }
+
+    /// Set the data bitrate, used for the data phase of CAN FD frames with bit rate switching,
+    /// and enable CAN FD operation.
+    ///
+    /// Transmitter delay compensation is enabled for the highest data bitrates, where the delay
+    /// of the transceiver is more than a time quantum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bitrate can't be reached from the peripheral clock.
+    pub fn set_fd_data_bitrate(&mut self, bitrate: u32) {
+        let t = unwrap!(calc_timings(T::frequency(), bitrate, 32, 32, 16));
+        let tdc = t.prescaler <= 2;
+
+        let regs = T::regs();
+        regs.dbtp().write(|w| {
+            w.set_dbrp((t.prescaler - 1) as u8);
+            w.set_dtseg1((t.seg1 - 1) as u8);
+            w.set_dtseg2((t.seg2 - 1) as u8);
+            w.set_dsjw((t.seg2 - 1) as u8);
+            w.set_tdc(tdc);
+        });
+        if tdc {
+            // Secondary sample point at the sample point, in peripheral clock cycles.
+            regs.tdcr().write(|w| w.set_tdco((t.prescaler * (1 + t.seg1)) as u8));
+        }
+        regs.cccr().modify(|w| {
+            w.set_fdoe(true);
+            w.set_brse(true);
+        });
+    }
+
+    /// Leave initialization mode, and start taking part in bus communication.
+    pub fn enable(&mut self, mode: OperatingMode) {
+        let regs = T::regs();
+
+        let test = matches!(mode, OperatingMode::InternalLoopback | OperatingMode::ExternalLoopback);
+        let mon = matches!(mode, OperatingMode::InternalLoopback | OperatingMode::BusMonitoring);
+        regs.cccr().modify(|w| {
+            w.set_test(test);
+            w.set_mon(mon);
+        });
+        regs.test().write(|w| w.set_lbck(test));
+
+        regs.cccr().modify(|w| w.set_init(false));
+        while regs.cccr().read().init() {}
+    }
+
+    /// Queues the frame to be sent, waiting for a free TX buffer.
+    pub async fn write(&mut self, frame: &Frame) {
+        poll_fn(|cx| {
+            T::state().tx_waker.register(cx.waker());
+
+            let regs = T::regs();
+            let txfqs = regs.txfqs().read();
+            if txfqs.tfqf() {
+                return Poll::Pending;
+            }
+
+            let index = txfqs.tfqpi() as usize;
+            unsafe { write_tx_element(T::msg_ram(TX_BUFFERS + index * ELEMENT_WORDS), frame) };
+            regs.txbar().write(|w| w.0 = 1 << index);
+
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    /// Waits until all the queued frames have been sent.
+    pub async fn flush(&self) {
+        poll_fn(|cx| {
+            T::state().tx_waker.register(cx.waker());
+            if T::regs().txbrp().read().0 == 0 {
+                return Poll::Ready(());
+            }
+
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Returns a tuple of the time the frame was received and the frame.
+    pub async fn read(&mut self) -> Result<(u16, Frame), BusError> {
+        poll_fn(|cx| {
+            T::state().err_waker.register(cx.waker());
+            if let Poll::Ready((time, frame)) = T::state().rx_queue.recv().poll_unpin(cx) {
+                return Poll::Ready(Ok((time, frame)));
+            } else if let Some(err) = self.curr_error() {
+                return Poll::Ready(Err(err));
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn curr_error(&self) -> Option<BusError> {
+        let psr = T::regs().psr().read();
+        if psr.bo() {
+            return Some(BusError::BusOff);
+        } else if psr.ep() {
+            return Some(BusError::BusPassive);
+        } else if psr.ew() {
+            return Some(BusError::BusWarning);
+        }
+        match psr.0 & 0x7 {
+            1 => Some(BusError::Stuff),
+            2 => Some(BusError::Form),
+            3 => Some(BusError::Acknowledge),
+            4 => Some(BusError::BitRecessive),
+            5 => Some(BusError::BitDominant),
+            6 => Some(BusError::Crc),
+            _ => None,
+        }
+    }
+
+    unsafe fn receive_fifo(fifo: usize) {
+        let state = T::state();
+        let regs = T::regs();
+
+        loop {
+            let rxfs = regs.rxfs(fifo).read();
+            // If there are no pending frames, there is nothing to do
+            if rxfs.ffl() == 0 {
+                return;
+            }
+
+            let index = rxfs.fgi() as usize;
+            let (time, frame) = read_rx_element(T::msg_ram(RX_FIFO[fifo] + index * ELEMENT_WORDS));
+            regs.rxfa(fifo).write(|w| w.set_fai(index as u8));
+
+            /*
+                NOTE: consensus was reached that if rx_queue is full, packets should be dropped
+            */
+            let _ = state.rx_queue.try_send((time, frame));
+        }
+    }
+}
+
+impl<'d, T: Instance> Drop for Fdcan<'d, T> {
+    fn drop(&mut self) {
+        T::regs().cccr().modify(|w| w.set_init(true));
+        T::disable();
+    }
+}
+
+struct Timings {
+    prescaler: u16,
+    seg1: u16,
+    seg2: u16,
+}
+
+/// Finds the timings with the most time quanta per bit, and the sample point closest to 87.5%.
+fn calc_timings(
+    periph_clock: Hertz,
+    bitrate: u32,
+    max_prescaler: u16,
+    max_seg1: u16,
+    max_seg2: u16,
+) -> Option<Timings> {
+    let periph_clock = periph_clock.0;
+    if bitrate == 0 {
+        return None;
+    }
+
+    for prescaler in 1..=max_prescaler {
+        let prescaler_bitrate = bitrate.checked_mul(prescaler as u32)?;
+        if periph_clock % prescaler_bitrate != 0 {
+            continue;
+        }
+        let quanta = periph_clock / prescaler_bitrate;
+        if quanta < 8 {
+            // Fewer quanta with higher prescalers.
+            return None;
+        }
+        if quanta > (1 + max_seg1 + max_seg2) as u32 {
+            continue;
+        }
+
+        // The sample point is at the end of seg1.
+        let seg1 = ((quanta * 7 + 4) / 8 - 1) as u16;
+        let seg2 = quanta as u16 - 1 - seg1;
+        if seg1 <= max_seg1 && seg2 <= max_seg2 {
+            return Some(Timings { prescaler, seg1, seg2 });
+        }
+    }
+    None
+}
+
+fn len_to_dlc(len: usize) -> Option<u8> {
+    match len {
+        0..=8 => Some(len as u8),
+        12 => Some(9),
+        16 => Some(10),
+        20 => Some(11),
+        24 => Some(12),
+        32 => Some(13),
+        48 => Some(14),
+        64 => Some(15),
+        _ => None,
+    }
+}
+
+fn dlc_to_len(dlc: u8, fd: bool) -> usize {
+    match dlc {
+        0..=8 => dlc as usize,
+        _ if !fd => 8,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
+
+// Element header bits, the same for RX and TX elements.
+const XTD: u32 = 1 << 30;
+const RTR: u32 = 1 << 29;
+const FDF: u32 = 1 << 21;
+const BRS: u32 = 1 << 20;
+
+unsafe fn write_tx_element(element: *mut u32, frame: &Frame) {
+    let mut t0 = match frame.id {
+        Id::Standard(id) => (id.as_raw() as u32) << 18,
+        Id::Extended(id) => id.as_raw() | XTD,
+    };
+    if frame.remote {
+        t0 |= RTR;
+    }
+
+    let dlc = if frame.remote {
+        frame.len
+    } else {
+        unwrap!(len_to_dlc(frame.len as usize))
+    };
+    let mut t1 = (dlc as u32) << 16;
+    if frame.fd {
+        t1 |= FDF;
+    }
+    if frame.brs {
+        t1 |= BRS;
+    }
+
+    ptr::write_volatile(element, t0);
+    ptr::write_volatile(element.add(1), t1);
+    for (i, chunk) in frame.data[..frame.len as usize].chunks(4).enumerate() {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        ptr::write_volatile(element.add(2 + i), u32::from_le_bytes(word));
+    }
+}
+
+unsafe fn read_rx_element(element: *const u32) -> (u16, Frame) {
+    let r0 = ptr::read_volatile(element);
+    let r1 = ptr::read_volatile(element.add(1));
+
+    let id = if r0 & XTD != 0 {
+        Id::from(ExtendedId::new_unchecked(r0 & 0x1FFF_FFFF))
+    } else {
+        Id::from(StandardId::new_unchecked(((r0 >> 18) & 0x7FF) as u16))
+    };
+    let fd = r1 & FDF != 0;
+    let dlc = ((r1 >> 16) & 0xF) as u8;
+
+    let mut frame = Frame::new(id, fd, r1 & BRS != 0, &[]);
+    frame.remote = r0 & RTR != 0;
+    if frame.remote {
+        frame.len = dlc;
+    } else {
+        let len = dlc_to_len(dlc, fd);
+        for i in 0..(len + 3) / 4 {
+            let word = ptr::read_volatile(element.add(2 + i)).to_le_bytes();
+            frame.data[i * 4..i * 4 + 4].copy_from_slice(&word);
+        }
+        frame.len = len as u8;
+    }
+
+    ((r1 & 0xFFFF) as u16, frame)
+}
+
+pub(crate) mod sealed {
+    #[cfg(feature = "nightly")]
+    use atomic_polyfill::AtomicU8;
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+    use embassy_sync::channel::Channel;
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    pub struct State {
+        pub tx_waker: AtomicWaker,
+        pub err_waker: AtomicWaker,
+        pub rx_queue: Channel<CriticalSectionRawMutex, (u16, super::Frame), 32>,
+        /// Last bus state reported by `embassy_can::Receive::receive`.
+        #[cfg(feature = "nightly")]
+        pub reported_bus_state: AtomicU8,
+    }
+
+    impl State {
+        pub const fn new() -> Self {
+            Self {
+                tx_waker: AtomicWaker::new(),
+                err_waker: AtomicWaker::new(),
+                rx_queue: Channel::new(),
+                #[cfg(feature = "nightly")]
+                reported_bus_state: AtomicU8::new(0),
+            }
+        }
+    }
+
+    pub trait Instance {
+        /// Index of this instance, which sets where its elements are in the message RAM.
+        const MSG_RAM_INDEX: usize;
+
+        fn regs() -> &'static crate::pac::can::Fdcan;
+        fn state() -> &'static State;
+
+        /// Pointer to the word at `offset` in the message RAM of this instance.
+        fn msg_ram(offset: usize) -> *mut u32 {
+            (super::MSG_RAM_BASE as *mut u32).wrapping_add(Self::MSG_RAM_INDEX * super::INSTANCE_WORDS + offset)
+        }
+    }
+}
+
+pub trait IT0Instance {
+    type IT0Interrupt: crate::interrupt::typelevel::Interrupt;
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + IT0Instance + 'static {}
+
+macro_rules! msg_ram_index {
+    (FDCAN1) => {
+        0
+    };
+    (FDCAN2) => {
+        1
+    };
+    (FDCAN3) => {
+        2
+    };
+}
+
+foreach_peripheral!(
+    (can, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            const MSG_RAM_INDEX: usize = msg_ram_index!($inst);
+
+            fn regs() -> &'static crate::pac::can::Fdcan {
+                &crate::pac::$inst
+            }
+
+            fn state() -> &'static sealed::State {
+                static STATE: sealed::State = sealed::State::new();
+                &STATE
+            }
+        }
+
+        impl Instance for peripherals::$inst {}
+
+        foreach_interrupt!(
+            ($inst,can,FDCAN,IT0,$irq:ident) => {
+                impl IT0Instance for peripherals::$inst {
+                    type IT0Interrupt = crate::interrupt::typelevel::$irq;
+                }
+            };
+        );
+    };
+);
+
+pin_trait!(RxPin, Instance);
+pin_trait!(TxPin, Instance);
+
+#[cfg(feature = "nightly")]
+pub use can_traits::{FdcanRx, FdcanTx};
+
+#[cfg(feature = "nightly")]
+mod can_traits {
+    use atomic_polyfill::Ordering;
+    use embassy_can::{BusState, ErrorKind, Filter, Frame, TooManyFilters};
+
+    use super::super::id;
+    use super::*;
+
+    /// Classic filter: the first identifier is matched on the bits set in the second one.
+    const FT_CLASSIC: u32 = 0b10 << 30;
+    /// Store the matching frames in RX FIFO 0, in a standard filter.
+    const SFEC_FIFO0: u32 = 0b001 << 27;
+    /// Store the matching frames in RX FIFO 0, in an extended filter.
+    const EFEC_FIFO0: u32 = 0b001 << 29;
+
+    impl embassy_can::Error for BusError {
+        fn kind(&self) -> ErrorKind {
+            match self {
+                BusError::Stuff => ErrorKind::Stuff,
+                BusError::Form => ErrorKind::Form,
+                BusError::Acknowledge => ErrorKind::Acknowledge,
+                BusError::BitRecessive => ErrorKind::BitRecessive,
+                BusError::BitDominant => ErrorKind::BitDominant,
+                BusError::Crc => ErrorKind::Crc,
+                BusError::BusOff => ErrorKind::BusOff,
+                BusError::BusPassive => ErrorKind::ErrorPassive,
+                BusError::BusWarning => ErrorKind::ErrorWarning,
+            }
+        }
+    }
+
+    fn to_fdcan(frame: &Frame) -> super::Frame {
+        let id = id::to_bxcan(frame.id());
+        let frame = if frame.is_remote_frame() {
+            super::Frame::new_remote(id, frame.len() as u8)
+        } else if frame.is_fd() {
+            super::Frame::new_fd(id, frame.data(), frame.bit_rate_switching())
+        } else {
+            super::Frame::new_data(id, frame.data())
+        };
+        unwrap!(frame)
+    }
+
+    fn from_fdcan(frame: &super::Frame) -> Frame {
+        let id = id::from_bxcan(frame.id());
+        let frame = if frame.is_remote_frame() {
+            Frame::new_remote(id, frame.len() as u8)
+        } else if frame.is_fd() {
+            Frame::new_fd(id, frame.data(), frame.bit_rate_switching())
+        } else {
+            Frame::new_data(id, frame.data())
+        };
+        unwrap!(frame)
+    }
+
+    fn bus_state<T: Instance>() -> BusState {
+        let psr = T::regs().psr().read();
+        if psr.bo() {
+            BusState::BusOff
+        } else if psr.ep() {
+            BusState::ErrorPassive
+        } else if psr.ew() {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::ErrorType for Fdcan<'d, T> {
+        type Error = BusError;
+    }
+
+    impl<'d, T: Instance> embassy_can::Transmit for Fdcan<'d, T> {
+        async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+            self.write(&to_fdcan(frame)).await;
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Fdcan::flush(self).await;
+            Ok(())
+        }
+    }
+
+    async fn receive<T: Instance>() -> Result<Frame, BusError> {
+        poll_fn(|cx| {
+            let state = T::state();
+            state.err_waker.register(cx.waker());
+            if let Poll::Ready((_, frame)) = state.rx_queue.recv().poll_unpin(cx) {
+                return Poll::Ready(Ok(from_fdcan(&frame)));
+            }
+
+            // Report the bus state when it gets worse than the last one reported.
+            let bus_state = bus_state::<T>();
+            let reported = state.reported_bus_state.swap(bus_state as u8, Ordering::Relaxed);
+            if bus_state as u8 > reported {
+                return Poll::Ready(Err(match bus_state {
+                    BusState::BusOff => BusError::BusOff,
+                    BusState::ErrorPassive => BusError::BusPassive,
+                    _ => BusError::BusWarning,
+                }));
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    impl<'d, T: Instance> embassy_can::Receive for Fdcan<'d, T> {
+        async fn receive(&mut self) -> Result<Frame, Self::Error> {
+            receive::<T>().await
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::Filters for Fdcan<'d, T> {
+        /// Configures up to 28 standard and 8 extended filters, the frames they match going to
+        /// FIFO 0. The frames matching none of them are rejected.
+        ///
+        /// The peripheral briefly goes back to initialization mode if it was enabled.
+        fn set_filters(&mut self, filters: &[Filter]) -> Result<(), TooManyFilters> {
+            let std_len = filters.iter().filter(|f| !f.is_extended()).count();
+            let ext_len = filters.len() - std_len;
+            if std_len > STD_FILTERS_LEN || ext_len > EXT_FILTERS_LEN {
+                return Err(TooManyFilters);
+            }
+
+            let regs = T::regs();
+            let enabled = !regs.cccr().read().init();
+            if enabled {
+                regs.cccr().modify(|w| w.set_init(true));
+                while !regs.cccr().read().init() {}
+                regs.cccr().modify(|w| w.set_cce(true));
+            }
+
+            let (mut std_index, mut ext_index) = (0, 0);
+            for filter in filters {
+                unsafe {
+                    if filter.is_extended() {
+                        let element = T::msg_ram(EXT_FILTERS + ext_index * 2);
+                        ptr::write_volatile(element, EFEC_FIFO0 | filter.id());
+                        ptr::write_volatile(element.add(1), FT_CLASSIC | filter.mask());
+                        ext_index += 1;
+                    } else {
+                        let element = T::msg_ram(STD_FILTERS + std_index);
+                        ptr::write_volatile(element, FT_CLASSIC | SFEC_FIFO0 | filter.id() << 16 | filter.mask());
+                        std_index += 1;
+                    }
+                }
+            }
+
+            // Number of filters (LSS and LSE), and reject the non-matching frames (ANFS and ANFE).
+            #[cfg(can_fdcan_h7)]
+            {
+                regs.sidfc().modify(|w| w.set_lss(std_len as _));
+                regs.xidfc().modify(|w| w.set_lse(ext_len as _));
+                regs.gfc().modify(|w| w.0 = w.0 & !0x3C | 0b10 << 4 | 0b10 << 2);
+            }
+            #[cfg(not(can_fdcan_h7))]
+            regs.rxgfc().modify(|w| {
+                w.0 = w.0 & !(0xF << 24 | 0x1F << 16 | 0x3C)
+                    | (ext_len as u32) << 24
+                    | (std_len as u32) << 16
+                    | 0b10 << 4
+                    | 0b10 << 2
+            });
+
+            if enabled {
+                regs.cccr().modify(|w| w.set_init(false));
+                while regs.cccr().read().init() {}
+            }
+            Ok(())
+        }
+    }
+
+    impl<'d, T: Instance> embassy_can::Status for Fdcan<'d, T> {
+        fn bus_state(&self) -> BusState {
+            bus_state::<T>()
+        }
+    }
+
+    /// Transmit half of [`Fdcan`], from [`Fdcan::split`].
+    pub struct FdcanTx<'c, 'd, T: Instance> {
+        can: &'c mut Fdcan<'d, T>,
+    }
+
+    /// Receive half of [`Fdcan`], from [`Fdcan::split`].
+    pub struct FdcanRx<'c, 'd, T: Instance> {
+        _can: PhantomData<&'c Fdcan<'d, T>>,
+    }
+
+    impl<'d, T: Instance> Fdcan<'d, T> {
+        /// Split the driver into its transmit and receive halves, to use them from different tasks
+        /// with the `embassy-can` traits.
+        pub fn split<'c>(&'c mut self) -> (FdcanTx<'c, 'd, T>, FdcanRx<'c, 'd, T>) {
+            (FdcanTx { can: self }, FdcanRx { _can: PhantomData })
+        }
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::ErrorType for FdcanTx<'c, 'd, T> {
+        type Error = BusError;
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::Transmit for FdcanTx<'c, 'd, T> {
+        async fn transmit(&mut self, frame: &Frame) -> Result<(), Self::Error> {
+            embassy_can::Transmit::transmit(&mut *self.can, frame).await
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            embassy_can::Transmit::flush(&mut *self.can).await
+        }
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::ErrorType for FdcanRx<'c, 'd, T> {
+        type Error = BusError;
+    }
+
+    impl<'c, 'd, T: Instance> embassy_can::Receive for FdcanRx<'c, 'd, T> {
+        async fn receive(&mut self) -> Result<Frame, Self::Error> {
+            receive::<T>().await
+        }
+    }
+}
@@ -1,5 +1,24 @@
 #![macro_use]
 
 #[cfg_attr(can_bxcan, path = "bxcan.rs")]
+#[cfg_attr(any(can_fdcan_v1, can_fdcan_h7), path = "fdcan.rs")]
 mod _version;
 pub use _version::*;
+
+/// Conversions between the identifiers of `bxcan` and of `embassy-can`.
+#[cfg(feature = "nightly")]
+mod id {
+    pub fn to_bxcan(id: embassy_can::Id) -> bxcan::Id {
+        match id {
+            embassy_can::Id::Standard(id) => bxcan::Id::Standard(unwrap!(bxcan::StandardId::new(id.as_raw()))),
+            embassy_can::Id::Extended(id) => bxcan::Id::Extended(unwrap!(bxcan::ExtendedId::new(id.as_raw()))),
+        }
+    }
+
+    pub fn from_bxcan(id: bxcan::Id) -> embassy_can::Id {
+        match id {
+            bxcan::Id::Standard(id) => embassy_can::Id::Standard(unwrap!(embassy_can::StandardId::new(id.as_raw()))),
+            bxcan::Id::Extended(id) => embassy_can::Id::Extended(unwrap!(embassy_can::ExtendedId::new(id.as_raw()))),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+#![macro_use]
+
+//! Comparators (COMP)
+//!
+//! A comparator compares the voltage of its non-inverting input, a pin, with its inverting
+//! input, usually a fraction of the internal voltage reference. It keeps running without the
+//! CPU: its output changes wake the tasks waiting for them through the EXTI, including from Stop
+//! mode.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::comp::regs::Csr;
+use crate::pac::EXTI;
+use crate::{interrupt, Peripheral};
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The interrupt is shared with other comparators, only handle this one's line.
+        let (bank, line) = exti_line::<T>();
+        if EXTI.pr(bank).read().line(line) {
+            EXTI.imr(bank).modify(|w| w.set_line(line, false));
+            EXTI.pr(bank).write(|w| w.set_line(line, true));
+            T::state().waker.wake();
+        }
+    }
+}
+
+/// Inverting input of a comparator.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InvertingInput {
+    /// 1/4 of the internal voltage reference.
+    QuarterVrefint,
+    /// 1/2 of the internal voltage reference.
+    HalfVrefint,
+    /// 3/4 of the internal voltage reference.
+    ThreeQuarterVrefint,
+    /// The internal voltage reference.
+    Vrefint,
+    /// Another INMSEL value, for example a DAC channel or a pin, as listed in the reference
+    /// manual.
+    Other(u8),
+}
+
+impl InvertingInput {
+    fn inmsel(&self) -> u8 {
+        match self {
+            InvertingInput::QuarterVrefint => 0,
+            InvertingInput::HalfVrefint => 1,
+            InvertingInput::ThreeQuarterVrefint => 2,
+            InvertingInput::Vrefint => 3,
+            InvertingInput::Other(inmsel) => *inmsel,
+        }
+    }
+}
+
+/// Hysteresis of a comparator, which keeps noise from toggling its output.
+#[cfg(stm32g4)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hysteresis {
+    None = 0,
+    Mv10 = 1,
+    Mv20 = 2,
+    Mv30 = 3,
+    Mv40 = 4,
+    Mv50 = 5,
+    Mv60 = 6,
+    Mv70 = 7,
+}
+
+/// Hysteresis of a comparator, which keeps noise from toggling its output.
+#[cfg(stm32l4)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hysteresis {
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+}
+
+/// Power mode of a comparator, a trade-off between speed and consumption.
+#[cfg(stm32l4)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerMode {
+    HighSpeed = 0,
+    MediumSpeed = 1,
+    UltraLowPower = 3,
+}
+
+/// Comparator configuration
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub inverting_input: InvertingInput,
+    pub hysteresis: Hysteresis,
+    /// Invert the output, which is then high when the non-inverting input is below the
+    /// inverting input.
+    pub invert_output: bool,
+    /// Timer output that blanks the comparator output, to ignore the current spikes at the
+    /// start of a PWM cycle. The BLANKSEL values are listed in the reference manual, 0 disables
+    /// the blanking.
+    pub blanking: u8,
+    #[cfg(stm32l4)]
+    pub power_mode: PowerMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            inverting_input: InvertingInput::HalfVrefint,
+            hysteresis: Hysteresis::None,
+            invert_output: false,
+            blanking: 0,
+            #[cfg(stm32l4)]
+            power_mode: PowerMode::HighSpeed,
+        }
+    }
+}
+
+/// Comparator driver.
+pub struct Comp<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Comp<'d, T> {
+    /// Create and enable a comparator, comparing the voltage on `inp` with the inverting input
+    /// selected in `config`.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        inp: impl Peripheral<P = impl InpPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri, inp);
+        inp.set_as_analog();
+
+        enable();
+
+        let mut csr = Csr(0);
+        csr.set_inmsel(config.inverting_input.inmsel());
+        csr.set_inpsel(inp.inpsel());
+        csr.set_hyst(config.hysteresis as u8);
+        csr.set_blanksel(config.blanking);
+        csr.set_pol(config.invert_output);
+        #[cfg(stm32l4)]
+        csr.set_pwrmode(config.power_mode as u8);
+
+        // The internal reference goes through a scaler, and its fractions through a divider.
+        match config.inverting_input {
+            InvertingInput::Vrefint => csr.set_scalen(true),
+            InvertingInput::Other(_) => {}
+            _ => {
+                csr.set_scalen(true);
+                csr.set_brgen(true);
+            }
+        }
+
+        T::regs().csr().write_value(csr);
+        csr.set_en(true);
+        T::regs().csr().write_value(csr);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Whether the output of the comparator is high.
+    pub fn output_level(&self) -> bool {
+        T::regs().csr().read().value()
+    }
+
+    /// Wait for the output to be high.
+    pub async fn wait_for_output_high(&mut self) {
+        self.wait_for_output(true).await
+    }
+
+    /// Wait for the output to be low.
+    pub async fn wait_for_output_low(&mut self) {
+        self.wait_for_output(false).await
+    }
+
+    async fn wait_for_output(&mut self, level: bool) {
+        let (bank, line) = exti_line::<T>();
+
+        // Arm the edge that sets the output to `level` before checking it, so that it can't be
+        // missed.
+        critical_section::with(|_| {
+            EXTI.rtsr(bank).modify(|w| w.set_line(line, level));
+            EXTI.ftsr(bank).modify(|w| w.set_line(line, !level));
+            EXTI.pr(bank).write(|w| w.set_line(line, true));
+            EXTI.imr(bank).modify(|w| w.set_line(line, true));
+        });
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if self.output_level() == level || !EXTI.imr(bank).read().line(line) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        critical_section::with(|_| EXTI.imr(bank).modify(|w| w.set_line(line, false)));
+    }
+}
+
+impl<'d, T: Instance> Drop for Comp<'d, T> {
+    fn drop(&mut self) {
+        let (bank, line) = exti_line::<T>();
+        critical_section::with(|_| EXTI.imr(bank).modify(|w| w.set_line(line, false)));
+        T::regs().csr().write_value(Csr(0));
+    }
+}
+
+/// EXTI bank and line in the bank of the comparator output.
+fn exti_line<T: Instance>() -> (usize, usize) {
+    (T::EXTI_LINE / 32, T::EXTI_LINE % 32)
+}
+
+/// The comparators are clocked with SYSCFG.
+fn enable() {
+    critical_section::with(|_| {
+        crate::pac::RCC.apb2enr().modify(|w| w.set_syscfgen(true));
+    });
+}
+
+pub(crate) struct State {
+    waker: AtomicWaker,
+}
+
+impl State {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        const EXTI_LINE: usize;
+
+        fn regs() -> crate::pac::comp::Comp;
+        fn state() -> &'static super::State;
+    }
+
+    pub trait InpPin<T: Instance> {
+        fn inpsel(&self) -> u8;
+    }
+}
+
+pub trait Instance: sealed::Instance + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+/// Non-inverting input pin of a comparator.
+pub trait InpPin<T: Instance>: sealed::InpPin<T> + crate::gpio::Pin {}
+
+macro_rules! impl_comp {
+    ($inst:ident, $exti_line:expr, $irq:ident) => {
+        foreach_peripheral!(
+            (comp, $inst) => {
+                impl sealed::Instance for crate::peripherals::$inst {
+                    const EXTI_LINE: usize = $exti_line;
+
+                    fn regs() -> crate::pac::comp::Comp {
+                        crate::pac::$inst
+                    }
+
+                    fn state() -> &'static State {
+                        static STATE: State = State::new();
+                        &STATE
+                    }
+                }
+
+                impl Instance for crate::peripherals::$inst {
+                    type Interrupt = crate::interrupt::typelevel::$irq;
+                }
+            };
+        );
+    };
+}
+
+#[cfg(stm32g4)]
+impl_comp!(COMP1, 21, COMP1_2_3);
+#[cfg(stm32g4)]
+impl_comp!(COMP2, 22, COMP1_2_3);
+#[cfg(stm32g4)]
+impl_comp!(COMP3, 29, COMP1_2_3);
+#[cfg(stm32g4)]
+impl_comp!(COMP4, 30, COMP4_5_6);
+#[cfg(stm32g4)]
+impl_comp!(COMP5, 31, COMP4_5_6);
+#[cfg(stm32g4)]
+impl_comp!(COMP6, 32, COMP4_5_6);
+#[cfg(stm32g4)]
+impl_comp!(COMP7, 33, COMP7);
+
+#[cfg(stm32l4)]
+impl_comp!(COMP1, 21, COMP);
+#[cfg(stm32l4)]
+impl_comp!(COMP2, 22, COMP);
+
+macro_rules! impl_comp_inp_pin {
+    ($inst:ident, $pin:ident, $inpsel:expr) => {
+        impl crate::comp::InpPin<peripherals::$inst> for crate::peripherals::$pin {}
+        impl crate::comp::sealed::InpPin<peripherals::$inst> for crate::peripherals::$pin {
+            fn inpsel(&self) -> u8 {
+                $inpsel
+            }
+        }
+    };
+}
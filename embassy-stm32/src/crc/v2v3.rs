@@ -11,10 +11,13 @@ pub struct Crc<'d> {
     _config: Config,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ConfigError {
     InvalidPolynomial,
 }
 
+#[derive(Copy, Clone)]
 pub struct Config {
     reverse_in: InputReverseConfig,
     reverse_out: bool,
@@ -25,6 +28,8 @@ pub struct Config {
     crc_poly: u32,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum InputReverseConfig {
     None,
     Byte,
@@ -55,9 +60,38 @@ impl Config {
             crc_poly,
         })
     }
+
+    /// CRC-32 of Ethernet, zlib and PNG.
+    ///
+    /// The checksum must be inverted with `!crc`, the peripheral doesn't apply a final XOR.
+    pub fn crc32() -> Self {
+        Config {
+            reverse_in: InputReverseConfig::Byte,
+            reverse_out: true,
+            #[cfg(crc_v3)]
+            poly_size: PolySize::Width32,
+            crc_init_value: 0xFFFF_FFFF,
+            #[cfg(crc_v3)]
+            crc_poly: 0x04C1_1DB7,
+        }
+    }
+
+    /// CRC-16 of Modbus RTU frames, in the 16 low bits of the checksum.
+    #[cfg(crc_v3)]
+    pub fn crc16_modbus() -> Self {
+        Config {
+            reverse_in: InputReverseConfig::Byte,
+            reverse_out: true,
+            poly_size: PolySize::Width16,
+            crc_init_value: 0xFFFF,
+            crc_poly: 0x8005,
+        }
+    }
 }
 
 #[cfg(crc_v3)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum PolySize {
     Width7,
     Width8,
@@ -84,10 +118,17 @@ impl<'d> Crc<'d> {
         instance
     }
 
+    /// Resets the CRC unit to the initial value of the configuration.
     pub fn reset(&mut self) {
         PAC_CRC.cr().modify(|w| w.set_reset(true));
     }
 
+    /// Reconfigures the CRC peripheral, and resets it.
+    pub fn set_config(&mut self, config: Config) {
+        self._config = config;
+        self.reconfigure();
+    }
+
     /// Reconfigures the CRC peripheral. Doesn't reset.
     fn reconfigure(&mut self) {
         // Init CRC value
@@ -160,4 +201,9 @@ impl<'d> Crc<'d> {
         }
         PAC_CRC.dr().read()
     }
+
+    /// Returns the computed checksum.
+    pub fn read(&self) -> u32 {
+        PAC_CRC.dr().read()
+    }
 }
@@ -194,7 +194,7 @@ pub struct Dac<'d, T: Instance, TxCh1, TxCh2> {
 pub struct DacCh1<'d, T: Instance, Tx> {
     /// To consume T
     _peri: PeripheralRef<'d, T>,
-    #[allow(unused)] // For chips whose DMA is not (yet) supported
+    #[allow(unused)] // For chips with GPDMA, which is not supported yet
     dma: PeripheralRef<'d, Tx>,
 }
 
@@ -204,7 +204,7 @@ pub struct DacCh1<'d, T: Instance, Tx> {
 pub struct DacCh2<'d, T: Instance, Tx> {
     /// Instead of PeripheralRef to consume T
     phantom: PhantomData<&'d mut T>,
-    #[allow(unused)] // For chips whose DMA is not (yet) supported
+    #[allow(unused)] // For chips with GPDMA, which is not supported yet
     dma: PeripheralRef<'d, Tx>,
 }
 
@@ -249,7 +249,7 @@ impl<'d, T: Instance, Tx> DacCh1<'d, T, Tx> {
     /// Note that for performance reasons in circular mode the transfer complete interrupt is disabled.
     ///
     /// **Important:** Channel 1 has to be configured for the DAC instance!
-    #[cfg(all(bdma, not(dma)))] // It currently only works with BDMA-only chips (DMA should theoretically work though)
+    #[cfg(not(gpdma))]
     pub async fn write(&mut self, data: ValueArray<'_>, circular: bool) -> Result<(), Error>
     where
         Tx: DmaCh1<T>,
@@ -361,7 +361,7 @@ impl<'d, T: Instance, Tx> DacCh2<'d, T, Tx> {
     /// Note that for performance reasons in circular mode the transfer complete interrupt is disabled.
     ///
     /// **Important:** Channel 2 has to be configured for the DAC instance!
-    #[cfg(all(bdma, not(dma)))] // It currently only works with BDMA-only chips (DMA should theoretically work though)
+    #[cfg(not(gpdma))]
     pub async fn write(&mut self, data: ValueArray<'_>, circular: bool) -> Result<(), Error>
     where
         Tx: DmaCh2<T>,
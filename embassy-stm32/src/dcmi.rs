@@ -76,11 +76,31 @@ pub enum Error {
     PeripheralError,
 }
 
+/// A window of the frame to capture, instead of the whole frame.
+///
+/// The horizontal values are counted in pixel clocks, not in pixels: with an 8-bit interface,
+/// a RGB565 pixel takes 2 pixel clocks.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Crop {
+    /// Number of pixel clocks to skip at the start of each line.
+    pub x: u16,
+    /// Number of lines to skip at the start of the frame.
+    pub y: u16,
+    /// Number of pixel clocks to capture in each line.
+    pub width: u16,
+    /// Number of lines to capture.
+    pub height: u16,
+}
+
 #[non_exhaustive]
 pub struct Config {
     pub vsync_level: VSyncDataInvalidLevel,
     pub hsync_level: HSyncDataInvalidLevel,
     pub pixclk_polarity: PixelClockPolarity,
+    /// Captures only a window of the frame.
+    pub crop: Option<Crop>,
+    /// JPEG mode, for compressed data of unknown length. Use [`Dcmi::capture_jpeg`] to capture.
+    pub jpeg: bool,
 }
 
 impl Default for Config {
@@ -89,6 +109,8 @@ impl Default for Config {
             vsync_level: VSyncDataInvalidLevel::High,
             hsync_level: HSyncDataInvalidLevel::Low,
             pixclk_polarity: PixelClockPolarity::RisingEdge,
+            crop: None,
+            jpeg: false,
         }
     }
 }
@@ -341,8 +363,21 @@ where
             r.set_hspol(config.hsync_level == HSyncDataInvalidLevel::High);
             r.set_fcrc(0x00); // capture every frame
             r.set_edm(edm); // extended data mode
+            r.set_jpeg(config.jpeg);
+            r.set_crop(config.crop.is_some());
         });
 
+        if let Some(crop) = config.crop {
+            peri.regs().cwstrtr().write(|w| {
+                w.set_vst(crop.y);
+                w.set_hoffcnt(crop.x);
+            });
+            peri.regs().cwsizer().write(|w| {
+                w.set_vline(crop.height - 1);
+                w.set_capcnt(crop.width - 1);
+            });
+        }
+
         T::Interrupt::unpend();
         unsafe { T::Interrupt::enable() };
 
@@ -386,6 +421,59 @@ where
         }
     }
 
+    /// Captures a frame in JPEG mode, and returns its length in bytes.
+    ///
+    /// The length of a JPEG frame isn't known in advance, so the capture finishes at the end of
+    /// the frame, and `buffer` only needs to be large enough for it. If the buffer gets full, the
+    /// end of the frame is lost, and the length of the buffer is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the driver isn't configured in JPEG mode, or if `buffer.len() > 0xffff`.
+    pub async fn capture_jpeg(&mut self, buffer: &mut [u32]) -> Result<usize, Error> {
+        let r = self.inner.regs();
+        assert!(r.cr().read().jpeg(), "DCMI not configured in JPEG mode");
+        assert!(buffer.len() <= 0xffff);
+
+        let len = buffer.len();
+        let src = r.dr().as_ptr() as *mut u32;
+        let request = self.dma.request();
+        let mut dma_read = unsafe { Transfer::new_read(&mut self.dma, request, src, buffer, Default::default()) };
+
+        Self::clear_interrupt_flags();
+        Self::enable_irqs();
+
+        Self::toggle(true);
+
+        let result = poll_fn(|cx| {
+            STATE.waker.register(cx.waker());
+
+            let ris = crate::pac::DCMI.ris().read();
+            if ris.err_ris() {
+                crate::pac::DCMI.icr().write(|r| r.set_err_isc(true));
+                Poll::Ready(Err(Error::PeripheralError))
+            } else if ris.ovr_ris() {
+                crate::pac::DCMI.icr().write(|r| r.set_ovr_isc(true));
+                Poll::Ready(Err(Error::Overrun))
+            } else if ris.frame_ris() {
+                crate::pac::DCMI.icr().write(|r| r.set_frame_isc(true));
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        Self::toggle(false);
+
+        // The DMA transfer doesn't complete when the frame is shorter than the buffer.
+        let captured = len - dma_read.get_remaining_transfers() as usize;
+        dma_read.request_stop();
+        drop(dma_read);
+
+        result.map(|_| captured * 4)
+    }
+
     async fn capture_small(&mut self, buffer: &mut [u32]) -> Result<(), Error> {
         let r = self.inner.regs();
         let src = r.dr().as_ptr() as *mut u32;
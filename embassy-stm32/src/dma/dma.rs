@@ -1,6 +1,7 @@
-use core::future::Future;
+use core::future::{poll_fn, Future};
 use core::marker::PhantomData;
 use core::pin::Pin;
+use core::slice;
 use core::sync::atomic::{fence, Ordering};
 use core::task::{Context, Poll, Waker};
 
@@ -10,7 +11,7 @@ use embassy_sync::waitqueue::AtomicWaker;
 
 use super::ringbuffer::{DmaCtrl, DmaRingBuffer, OverrunError};
 use super::word::{Word, WordSize};
-use super::Dir;
+use super::{Dir, Half};
 use crate::_generated::DMA_CHANNEL_COUNT;
 use crate::interrupt::typelevel::Interrupt;
 use crate::interrupt::Priority;
@@ -29,6 +30,12 @@ pub struct TransferOptions {
     pub flow_ctrl: FlowControl,
     /// FIFO threshold for DMA FIFO mode. If none, direct mode is used.
     pub fifo_threshold: Option<FifoThreshold>,
+    /// Enable circular DMA
+    pub circular: bool,
+    /// Enable half transfer interrupt
+    pub half_transfer_ir: bool,
+    /// Enable transfer complete interrupt
+    pub complete_transfer_ir: bool,
 }
 
 impl Default for TransferOptions {
@@ -38,6 +45,9 @@ impl Default for TransferOptions {
             mburst: Burst::Single,
             flow_ctrl: FlowControl::Dma,
             fifo_threshold: None,
+            circular: false,
+            half_transfer_ir: false,
+            complete_transfer_ir: true,
         }
     }
 }
@@ -57,6 +67,7 @@ impl From<Dir> for vals::Dir {
         match raw {
             Dir::MemoryToPeripheral => Self::MEMORYTOPERIPHERAL,
             Dir::PeripheralToMemory => Self::PERIPHERALTOMEMORY,
+            Dir::MemoryToMemory => Self::MEMORYTOMEMORY,
         }
     }
 }
@@ -130,6 +141,7 @@ impl From<FifoThreshold> for vals::Fth {
 struct State {
     ch_wakers: [AtomicWaker; DMA_CHANNEL_COUNT],
     complete_count: [AtomicUsize; DMA_CHANNEL_COUNT],
+    half_count: [AtomicUsize; DMA_CHANNEL_COUNT],
 }
 
 impl State {
@@ -139,6 +151,7 @@ impl State {
         Self {
             ch_wakers: [AW; DMA_CHANNEL_COUNT],
             complete_count: [ZERO; DMA_CHANNEL_COUNT],
+            half_count: [ZERO; DMA_CHANNEL_COUNT],
         }
     }
 }
@@ -189,6 +202,7 @@ pub(crate) unsafe fn on_irq_inner(dma: pac::dma::Dma, channel_num: usize, index:
     if isr.htif(channel_num % 4) && cr.read().htie() {
         // Acknowledge half transfer complete interrupt
         dma.ifcr(channel_num / 4).write(|w| w.set_htif(channel_num % 4, true));
+        STATE.half_count[index].fetch_add(1, Ordering::Release);
     } else if isr.tcif(channel_num % 4) && cr.read().tcie() {
         // Acknowledge  transfer complete interrupt
         dma.ifcr(channel_num / 4).write(|w| w.set_tcif(channel_num % 4, true));
@@ -320,6 +334,38 @@ impl<'a, C: Channel> Transfer<'a, C> {
         )
     }
 
+    /// Copy `src` to `dst`, which must have the same length, memory to memory.
+    ///
+    /// On the STM32F2, F4 and F7, only the channels of DMA2 can do memory to memory transfers, and
+    /// neither DMA can access the DTCM or CCM RAM. The transfer goes through the FIFO, with
+    /// `options.fifo_threshold` or half-full by default.
+    pub unsafe fn new_copy<W: Word>(
+        channel: impl Peripheral<P = C> + 'a,
+        src: &'a [W],
+        dst: &'a mut [W],
+        mut options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        assert_eq!(src.len(), dst.len());
+        assert!(dst.len() > 0 && dst.len() <= 0xFFFF);
+
+        options.fifo_threshold = Some(options.fifo_threshold.unwrap_or(FifoThreshold::Half));
+        options.circular = false;
+
+        Self::new_inner(
+            channel,
+            Request::default(),
+            Dir::MemoryToMemory,
+            src.as_ptr() as *const u32,
+            dst.as_mut_ptr() as *mut u32,
+            dst.len(),
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, C>,
         _request: Request,
@@ -364,9 +410,17 @@ impl<'a, C: Channel> Transfer<'a, C> {
                 true => vals::Inc::INCREMENTED,
                 false => vals::Inc::FIXED,
             });
-            w.set_pinc(vals::Inc::FIXED);
+            // In memory to memory mode, the peripheral port reads the source.
+            w.set_pinc(match dir {
+                Dir::MemoryToMemory => vals::Inc::INCREMENTED,
+                _ => vals::Inc::FIXED,
+            });
             w.set_teie(true);
-            w.set_tcie(true);
+            w.set_tcie(options.complete_transfer_ir);
+            w.set_htie(options.half_transfer_ir);
+            if options.circular {
+                w.set_circ(vals::Circ::ENABLED);
+            }
             #[cfg(dma_v1)]
             w.set_trbuff(true);
 
@@ -388,6 +442,7 @@ impl<'a, C: Channel> Transfer<'a, C> {
         let isrbit = self.channel.num() % 4;
 
         self.channel.regs().ifcr(isrn).write(|w| {
+            w.set_htif(isrbit, true);
             w.set_tcif(isrbit, true);
             w.set_teif(isrbit, true);
         });
@@ -451,6 +506,115 @@ impl<'a, C: Channel> Future for Transfer<'a, C> {
 
 // ==================================
 
+/// A circular peripheral to memory transfer, notifying each time a half of the buffer is filled.
+///
+/// While the DMA controller fills one half of the buffer, the other half, returned by
+/// [`CircularTransfer::wait_half()`], can be processed with [`CircularTransfer::half()`].
+pub struct CircularTransfer<'a, C: Channel, W: Word> {
+    transfer: Transfer<'a, C>,
+    buf: *mut W,
+    len: usize,
+    events: usize,
+}
+
+impl<'a, C: Channel, W: Word> CircularTransfer<'a, C, W> {
+    /// Start filling `buf`, whose length must be even, from the peripheral in circular mode.
+    ///
+    /// The interrupt and circular settings of `options` are ignored.
+    pub unsafe fn new_read(
+        channel: impl Peripheral<P = C> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buf: &'a mut [W],
+        mut options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        let len = buf.len();
+        assert!(len > 0 && len <= 0xFFFF && len % 2 == 0);
+
+        options.circular = true;
+        options.half_transfer_ir = true;
+        options.complete_transfer_ir = true;
+
+        STATE.complete_count[channel.index()].store(0, Ordering::Release);
+        STATE.half_count[channel.index()].store(0, Ordering::Release);
+
+        let transfer = Transfer::new_inner(
+            channel,
+            request,
+            Dir::PeripheralToMemory,
+            peri_addr as *const u32,
+            buf.as_mut_ptr() as *mut u32,
+            len,
+            true,
+            W::size(),
+            options,
+        );
+
+        Self {
+            transfer,
+            buf: buf.as_mut_ptr(),
+            len,
+            events: 0,
+        }
+    }
+
+    /// Wait for the DMA controller to fill the next half of the buffer, and return it.
+    ///
+    /// Returns `OverrunError` if more than one half was filled since the last call: the half
+    /// expected was overwritten. The next call then waits for the next half filled.
+    pub async fn wait_half(&mut self) -> Result<Half, OverrunError> {
+        let index = self.transfer.channel.index();
+
+        poll_fn(|cx| {
+            STATE.ch_wakers[index].register(cx.waker());
+
+            let events =
+                STATE.half_count[index].load(Ordering::Acquire) + STATE.complete_count[index].load(Ordering::Acquire);
+            if events == self.events {
+                return Poll::Pending;
+            }
+
+            let overrun = events > self.events + 1;
+            self.events = events;
+            if overrun {
+                return Poll::Ready(Err(OverrunError));
+            }
+
+            // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+            fence(Ordering::SeqCst);
+
+            // The half transfer event comes first, then the transfer complete event.
+            match events % 2 {
+                1 => Poll::Ready(Ok(Half::First)),
+                _ => Poll::Ready(Ok(Half::Second)),
+            }
+        })
+        .await
+    }
+
+    /// A half of the buffer, which must be processed before the DMA controller fills it again.
+    pub fn half(&mut self, half: Half) -> &[W] {
+        let len = self.len / 2;
+        let offset = match half {
+            Half::First => 0,
+            Half::Second => len,
+        };
+        unsafe { slice::from_raw_parts(self.buf.add(offset), len) }
+    }
+
+    pub fn request_stop(&mut self) {
+        self.transfer.request_stop()
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        self.transfer.is_running()
+    }
+}
+
+// ==================================
+
 pub struct DoubleBuffered<'a, C: Channel, W: Word> {
     channel: PeripheralRef<'a, C>,
     _phantom: PhantomData<W>,
@@ -619,8 +783,33 @@ pub struct RingBuffer<'a, C: Channel, W: Word> {
 
 impl<'a, C: Channel, W: Word> RingBuffer<'a, C, W> {
     pub unsafe fn new_read(
+        channel: impl Peripheral<P = C> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buffer: &'a mut [W],
+        options: TransferOptions,
+    ) -> Self {
+        Self::new_inner(channel, request, Dir::PeripheralToMemory, peri_addr, buffer, options)
+    }
+
+    /// Create a ring buffer that the DMA controller reads from, to write to the peripheral.
+    ///
+    /// The DMA controller starts with the contents of `buffer`, then loops over it, reading what
+    /// was written with [`RingBuffer::write`].
+    pub unsafe fn new_write(
+        channel: impl Peripheral<P = C> + 'a,
+        request: Request,
+        buffer: &'a mut [W],
+        peri_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        Self::new_inner(channel, request, Dir::MemoryToPeripheral, peri_addr, buffer, options)
+    }
+
+    unsafe fn new_inner(
         channel: impl Peripheral<P = C> + 'a,
         _request: Request,
+        dir: Dir,
         peri_addr: *mut W,
         buffer: &'a mut [W],
         options: TransferOptions,
@@ -630,7 +819,6 @@ impl<'a, C: Channel, W: Word> RingBuffer<'a, C, W> {
         let len = buffer.len();
         assert!(len > 0 && len <= 0xFFFF);
 
-        let dir = Dir::PeripheralToMemory;
         let data_size = W::size();
 
         let channel_number = channel.num();
@@ -706,6 +894,13 @@ impl<'a, C: Channel, W: Word> RingBuffer<'a, C, W> {
         self.ringbuf.read(DmaCtrlImpl(self.channel.reborrow()), buf)
     }
 
+    /// Write bytes to the ring buffer, for a ring buffer created with [`RingBuffer::new_write`]
+    /// Return a tuple of the length written and the space remaining in the buffer
+    /// OverrunError is returned if the DMA controller read the portion to be written before it was written.
+    pub fn write(&mut self, buf: &[W]) -> Result<(usize, usize), OverrunError> {
+        self.ringbuf.write(DmaCtrlImpl(self.channel.reborrow()), buf)
+    }
+
     // The capacity of the ringbuffer
     pub fn cap(&self) -> usize {
         self.ringbuf.cap()
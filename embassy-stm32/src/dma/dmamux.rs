@@ -26,6 +26,80 @@ pub struct DMAMUX1;
 #[cfg(stm32h7)]
 pub struct DMAMUX2;
 
+/// Edge of the trigger signal generating the DMA requests.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GeneratorPolarity {
+    Rising,
+    Falling,
+    Both,
+}
+
+macro_rules! impl_request_generator {
+    ($mux:ident) => {
+        impl $mux {
+            /// Generate `requests` DMA requests (1 to 32) on each edge of the trigger signal
+            /// `signal_id`, from a table of the reference manual, with request generator
+            /// `generator`.
+            ///
+            /// Returns the request to configure the DMA transfer with. The DMA channel then copies
+            /// on a timer or EXTI event, for example, rather than on a peripheral request.
+            pub fn enable_request_generator(
+                generator: usize,
+                signal_id: u8,
+                polarity: GeneratorPolarity,
+                requests: u8,
+            ) -> u8 {
+                assert!(generator < 8);
+                assert!(signal_id < 32);
+                assert!(requests > 0 && requests <= 32);
+
+                let gpol = match polarity {
+                    GeneratorPolarity::Rising => pac::dmamux::vals::Pol::RISINGEDGE,
+                    GeneratorPolarity::Falling => pac::dmamux::vals::Pol::FALLINGEDGE,
+                    GeneratorPolarity::Both => pac::dmamux::vals::Pol::BOTHEDGES,
+                };
+
+                let r = pac::$mux;
+                // The settings can only be changed with the generator disabled.
+                r.rgcr(generator).write(|_| {});
+                r.rgcfr().write(|w| w.set_cof(generator, true));
+                r.rgcr(generator).write(|w| {
+                    w.set_sig_id(signal_id);
+                    w.set_gpol(gpol);
+                    w.set_gnbreq(requests - 1);
+                });
+                r.rgcr(generator).modify(|w| w.set_ge(true));
+
+                // The request generator outputs are the requests following "memory to memory".
+                generator as u8 + 1
+            }
+
+            /// Stop request generator `generator`.
+            pub fn disable_request_generator(generator: usize) {
+                assert!(generator < 8);
+                pac::$mux.rgcr(generator).write(|_| {});
+            }
+
+            /// Check and clear the overrun flag of request generator `generator`, set when a
+            /// trigger edge came before the requests of the previous one were served.
+            pub fn request_generator_overrun(generator: usize) -> bool {
+                assert!(generator < 8);
+                let r = pac::$mux;
+                let overrun = r.rgsr().read().of(generator);
+                if overrun {
+                    r.rgcfr().write(|w| w.set_cof(generator, true));
+                }
+                overrun
+            }
+        }
+    };
+}
+
+impl_request_generator!(DMAMUX1);
+#[cfg(stm32h7)]
+impl_request_generator!(DMAMUX2);
+
 pub trait MuxChannel: dmamux_sealed::MuxChannel {
     type Mux;
 }
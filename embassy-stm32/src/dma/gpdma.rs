@@ -230,6 +230,31 @@ impl<'a, C: Channel> Transfer<'a, C> {
         )
     }
 
+    /// Copy `src` to `dst`, which must have the same length, memory to memory.
+    pub unsafe fn new_copy<W: Word>(
+        channel: impl Peripheral<P = C> + 'a,
+        src: &'a [W],
+        dst: &'a mut [W],
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        assert_eq!(src.len(), dst.len());
+        assert!(dst.len() > 0 && dst.len() * W::size().bytes() <= 0xFFFF);
+
+        Self::new_inner(
+            channel,
+            0,
+            Dir::MemoryToMemory,
+            src.as_ptr() as *const u32,
+            dst.as_mut_ptr() as *mut u32,
+            dst.len(),
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, C>,
         request: Request,
@@ -257,14 +282,16 @@ impl<'a, C: Channel> Transfer<'a, C> {
         ch.tr1().write(|w| {
             w.set_sdw(data_size.into());
             w.set_ddw(data_size.into());
-            w.set_sinc(dir == Dir::MemoryToPeripheral && incr_mem);
-            w.set_dinc(dir == Dir::PeripheralToMemory && incr_mem);
+            w.set_sinc(dir != Dir::PeripheralToMemory && incr_mem);
+            w.set_dinc(dir != Dir::MemoryToPeripheral && incr_mem);
         });
         ch.tr2().write(|w| {
-            w.set_dreq(match dir {
-                Dir::MemoryToPeripheral => vals::ChTr2Dreq::DESTINATIONPERIPHERAL,
-                Dir::PeripheralToMemory => vals::ChTr2Dreq::SOURCEPERIPHERAL,
-            });
+            match dir {
+                Dir::MemoryToPeripheral => w.set_dreq(vals::ChTr2Dreq::DESTINATIONPERIPHERAL),
+                Dir::PeripheralToMemory => w.set_dreq(vals::ChTr2Dreq::SOURCEPERIPHERAL),
+                // SWREQ: the transfer is requested by software, as soon as the channel is enabled.
+                Dir::MemoryToMemory => w.0 |= 1 << 9,
+            }
             w.set_reqsel(request);
         });
         ch.br1().write(|w| {
@@ -277,7 +304,7 @@ impl<'a, C: Channel> Transfer<'a, C> {
                 ch.sar().write_value(mem_addr as _);
                 ch.dar().write_value(peri_addr as _);
             }
-            Dir::PeripheralToMemory => {
+            Dir::PeripheralToMemory | Dir::MemoryToMemory => {
                 ch.sar().write_value(peri_addr as _);
                 ch.dar().write_value(mem_addr as _);
             }
@@ -0,0 +1,206 @@
+//! MDMA, the master DMA controller of the STM32H7.
+//!
+//! The MDMA has 16 channels on the 64-bit AXI bus, with access to the TCM of the core, which the
+//! DMA and BDMA can't reach. [`Mdma::split()`] gives the channels, which copy blocks of memory
+//! asynchronously with [`Channel::copy()`].
+//!
+//! The data cache isn't maintained: buffers in cacheable memory must be cleaned and invalidated
+//! around the copies.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::{fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::word::{Word, WordSize};
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::mdma::vals;
+use crate::peripherals::MDMA;
+use crate::rcc::RccPeripheral;
+use crate::{interrupt, pac, Peripheral};
+
+/// Number of MDMA channels.
+pub const CHANNEL_COUNT: usize = 16;
+
+/// Longest block, in bytes.
+const MAX_BLOCK_SIZE: usize = 0x1_0000;
+
+/// Bytes moved per request, the most the channel FIFO allows.
+const BUFFER_TRANSFER_LENGTH: u8 = 128;
+
+static WAKERS: [AtomicWaker; CHANNEL_COUNT] = {
+    const AW: AtomicWaker = AtomicWaker::new();
+    [AW; CHANNEL_COUNT]
+};
+
+/// MDMA error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Bus error on the source or destination, or invalid configuration.
+    Transfer,
+}
+
+/// MDMA interrupt handler.
+pub struct InterruptHandler {}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::MDMA> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let pending = pac::MDMA.gisr0().read();
+        for ch in 0..CHANNEL_COUNT {
+            if pending.gif(ch) {
+                // Mask the interrupts of the channel, the future checks the flags.
+                pac::MDMA.ch(ch).cr().modify(|w| {
+                    w.set_teie(false);
+                    w.set_ctcie(false);
+                });
+                WAKERS[ch].wake();
+            }
+        }
+    }
+}
+
+/// MDMA driver.
+pub struct Mdma<'d> {
+    _peri: PeripheralRef<'d, MDMA>,
+}
+
+impl<'d> Mdma<'d> {
+    pub fn new(
+        peri: impl Peripheral<P = MDMA> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::MDMA, InterruptHandler> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+
+        MDMA::enable();
+        MDMA::reset();
+
+        interrupt::typelevel::MDMA::unpend();
+        unsafe { interrupt::typelevel::MDMA::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Split the MDMA into its channels.
+    pub fn split(self) -> [Channel<'d>; CHANNEL_COUNT] {
+        core::array::from_fn(|num| Channel {
+            num,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// MDMA channel.
+pub struct Channel<'d> {
+    num: usize,
+    _phantom: PhantomData<&'d mut MDMA>,
+}
+
+impl<'d> Channel<'d> {
+    /// Copy `src` to `dst`, which must have the same length, of at most 64 KiB.
+    pub async fn copy<W: Word>(&mut self, src: &[W], dst: &mut [W]) -> Result<(), Error> {
+        assert_eq!(src.len(), dst.len());
+
+        let bytes = dst.len() * W::size().bytes();
+        assert!(bytes > 0 && bytes <= MAX_BLOCK_SIZE);
+
+        let size = match W::size() {
+            WordSize::OneByte => vals::Wordsize::BYTE,
+            WordSize::TwoBytes => vals::Wordsize::HALFWORD,
+            WordSize::FourBytes => vals::Wordsize::WORD,
+        };
+
+        let ch = pac::MDMA.ch(self.num);
+
+        // "Preceding reads and writes cannot be moved past subsequent writes."
+        fence(Ordering::SeqCst);
+
+        ch.cr().write(|_| {});
+        clear_flags(ch);
+
+        // Increment both addresses by one word, in one block moved in buffers of 128 bytes,
+        // requested by software.
+        ch.tcr().write(|w| {
+            w.set_sinc(vals::Inc::INCREMENTED);
+            w.set_dinc(vals::Inc::INCREMENTED);
+            w.set_ssize(size);
+            w.set_dsize(size);
+            w.set_sincos(size);
+            w.set_dincos(size);
+            w.set_tlen(BUFFER_TRANSFER_LENGTH - 1);
+            w.set_trgm(vals::Trgm::BLOCK);
+            w.set_swrm(true);
+        });
+        ch.bndtr().write(|w| w.set_bndt(bytes as u32));
+        ch.sar().write_value(src.as_ptr() as u32);
+        ch.dar().write_value(dst.as_mut_ptr() as u32);
+        ch.brur().write(|_| {});
+        ch.lar().write_value(0);
+
+        // The TCMs are reached through the AHB bus.
+        ch.tbr().write(|w| {
+            w.set_sbus(is_tcm(src.as_ptr() as u32));
+            w.set_dbus(is_tcm(dst.as_ptr() as u32));
+        });
+
+        let on_drop = OnDrop::new(|| {
+            ch.cr().write(|_| {});
+            while ch.cr().read().en() {}
+        });
+
+        ch.cr().write(|w| {
+            w.set_pl(vals::Pl::VERYHIGH);
+            w.set_teie(true);
+            w.set_ctcie(true);
+            w.set_en(true);
+        });
+        ch.cr().modify(|w| w.set_swrq(true));
+
+        let res = poll_fn(|cx| {
+            WAKERS[self.num].register(cx.waker());
+
+            let isr = ch.isr().read();
+            if isr.teif() {
+                return Poll::Ready(Err(Error::Transfer));
+            }
+            if isr.ctcif() {
+                return Poll::Ready(Ok(()));
+            }
+
+            ch.cr().modify(|w| {
+                w.set_teie(true);
+                w.set_ctcie(true);
+            });
+            Poll::Pending
+        })
+        .await;
+
+        on_drop.defuse();
+        ch.cr().write(|_| {});
+        clear_flags(ch);
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+
+        res
+    }
+}
+
+/// Whether `addr` is in the ITCM or DTCM.
+fn is_tcm(addr: u32) -> bool {
+    addr < 0x0001_0000 || (0x2000_0000..0x2002_0000).contains(&addr)
+}
+
+fn clear_flags(ch: pac::mdma::MdmaCh) {
+    ch.ifcr().write(|w| {
+        w.set_cteif(true);
+        w.set_cctcif(true);
+        w.set_cbrtif(true);
+        w.set_cbtif(true);
+        w.set_cltcif(true);
+    });
+}
@@ -21,6 +21,9 @@ pub use gpdma::*;
 #[cfg(dmamux)]
 mod dmamux;
 
+#[cfg(mdma)]
+pub mod mdma;
+
 pub(crate) mod ringbuffer;
 pub mod word;
 
@@ -37,6 +40,15 @@ use crate::interrupt::Priority;
 enum Dir {
     MemoryToPeripheral,
     PeripheralToMemory,
+    MemoryToMemory,
+}
+
+/// Half of the buffer of a circular transfer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Half {
+    First,
+    Second,
 }
 
 pub struct NoDma;
@@ -77,7 +77,41 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
     /// If not all of the bytes were read, then there will be some bytes in the buffer remaining
     /// The length remaining is the capacity, ring_buf.len(), less the bytes remaining after the read
     /// OverrunError is returned if the portion to be read was overwritten by the DMA controller.
-    pub fn read(&mut self, mut dma: impl DmaCtrl, buf: &mut [W]) -> Result<(usize, usize), OverrunError> {
+    pub fn read(&mut self, dma: impl DmaCtrl, buf: &mut [W]) -> Result<(usize, usize), OverrunError> {
+        self.transfer(dma, buf.len(), |dma_buf, dma_pos, buf_pos, len| {
+            // We need to do it like this instead of a simple copy_from_slice() because
+            // reading from a part of memory that may be simultaneously written to is unsafe
+            for i in 0..len {
+                buf[buf_pos + i] = unsafe { core::ptr::read_volatile(dma_buf.add(dma_pos + i)) };
+            }
+        })
+    }
+
+    /// Write bytes to the ring buffer, for a DMA transfer that reads from it.
+    ///
+    /// The bytes are written to the part of the buffer that the DMA controller has already read.
+    /// Return a tuple of the length written and the space remaining in the buffer.
+    /// OverrunError is returned if the DMA controller read the portion to be written before it
+    /// was written, i.e. if the writes were too late to keep up with the DMA.
+    pub fn write(&mut self, dma: impl DmaCtrl, buf: &[W]) -> Result<(usize, usize), OverrunError> {
+        self.transfer(dma, buf.len(), |dma_buf, dma_pos, buf_pos, len| {
+            for i in 0..len {
+                unsafe { core::ptr::write_volatile(dma_buf.add(dma_pos + i), buf[buf_pos + i]) };
+            }
+        })
+    }
+
+    /// Copy between the ring buffer and a buffer of `len` bytes, using `copy(dma_buf, dma_pos, buf_pos, len)`.
+    ///
+    /// When reading, the available portion of the ring buffer is the one written by the DMA
+    /// controller since the last read. When writing, it's the one read by the DMA controller
+    /// since the last write. The DMA position is tracked the same way in both cases.
+    fn transfer(
+        &mut self,
+        mut dma: impl DmaCtrl,
+        len: usize,
+        mut copy: impl FnMut(*mut W, usize, usize, usize),
+    ) -> Result<(usize, usize), OverrunError> {
         /*
             This algorithm is optimistic: we assume we haven't overrun more than a full buffer and then check
             after we've done our work to see we have. This is because on stm32, an interrupt is not guaranteed
@@ -100,7 +134,7 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
         } else if self.start < end {
             // The available, unread portion in the ring buffer DOES NOT wrap
             // Copy out the bytes from the dma buffer
-            let len = self.copy_to(buf, self.start..end);
+            let len = self.copy_to(&mut copy, len, 0, self.start..end);
 
             compiler_fence(Ordering::SeqCst);
 
@@ -123,7 +157,7 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
 
                 Ok((len, self.cap() - self.start))
             }
-        } else if self.start + buf.len() < self.cap() {
+        } else if self.start + len < self.cap() {
             // The available, unread portion in the ring buffer DOES wrap
             // The DMA writer has wrapped since we last read and is currently
             // writing (or the next byte added will be) in the beginning of the ring buffer.
@@ -131,7 +165,7 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
             // The provided read buffer is not large enough to include all bytes from the tail of the dma buffer.
 
             // Copy out from the dma buffer
-            let len = self.copy_to(buf, self.start..self.cap());
+            let len = self.copy_to(&mut copy, len, 0, self.start..self.cap());
 
             compiler_fence(Ordering::SeqCst);
 
@@ -158,8 +192,8 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
             // so the next read will not have any unread tail bytes in the ring buffer.
 
             // Copy out from the dma buffer
-            let tail = self.copy_to(buf, self.start..self.cap());
-            let head = self.copy_to(&mut buf[tail..], 0..end);
+            let tail = self.copy_to(&mut copy, len, 0, self.start..self.cap());
+            let head = self.copy_to(&mut copy, len, tail, 0..end);
 
             compiler_fence(Ordering::SeqCst);
 
@@ -178,21 +212,18 @@ impl<'a, W: Word> DmaRingBuffer<'a, W> {
             }
         }
     }
-    /// Copy from the dma buffer at `data_range` into `buf`
-    fn copy_to(&mut self, buf: &mut [W], data_range: Range<usize>) -> usize {
+    /// Copy between the dma buffer at `data_range` and the buffer of `len` bytes from `buf_pos`
+    fn copy_to(
+        &mut self,
+        copy: &mut impl FnMut(*mut W, usize, usize, usize),
+        len: usize,
+        buf_pos: usize,
+        data_range: Range<usize>,
+    ) -> usize {
         // Limit the number of bytes that can be copied
-        let length = usize::min(data_range.len(), buf.len());
+        let length = usize::min(data_range.len(), len - buf_pos);
 
-        // Copy from dma buffer into read buffer
-        // We need to do it like this instead of a simple copy_from_slice() because
-        // reading from a part of memory that may be simultaneously written to is unsafe
-        unsafe {
-            let dma_buf = self.dma_buf.as_ptr();
-
-            for i in 0..length {
-                buf[i] = core::ptr::read_volatile(dma_buf.offset((data_range.start + i) as isize));
-            }
-        }
+        copy(self.dma_buf.as_mut_ptr(), data_range.start, buf_pos, length);
 
         length
     }
@@ -482,4 +513,86 @@ mod tests {
         let mut buf = [0; 6];
         assert_eq!(OverrunError, ringbuf.read(&mut dma, &mut buf).unwrap_err());
     }
+
+    #[test]
+    fn can_write() {
+        let mut dma = TestCircularTransfer::new(16);
+
+        let mut dma_buf = [0u8; 16];
+        let mut ringbuf = DmaRingBuffer::new(&mut dma_buf);
+
+        /*
+            Nothing can be written until the DMA reader has read some bytes
+        */
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(0),
+            TestCircularTransferRequest::GetCompleteCount(0),
+        ]);
+        assert_eq!(0, ringbuf.write(&mut dma, &[1, 2]).unwrap().0);
+        assert_eq!(0, ringbuf.start);
+
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(4),
+            TestCircularTransferRequest::PositionRequest(5),
+            TestCircularTransferRequest::GetCompleteCount(0),
+        ]);
+        assert_eq!(4, ringbuf.write(&mut dma, &[1, 2, 3, 4, 5, 6]).unwrap().0);
+        assert_eq!(4, ringbuf.start);
+        assert_eq!([1, 2, 3, 4, 0], ringbuf.dma_buf[..5]);
+    }
+
+    #[test]
+    fn can_write_with_wrap() {
+        let mut dma = TestCircularTransfer::new(16);
+
+        let mut dma_buf = [0u8; 16];
+        let mut ringbuf = DmaRingBuffer::new(&mut dma_buf);
+
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(14),
+            TestCircularTransferRequest::PositionRequest(16),
+            TestCircularTransferRequest::GetCompleteCount(0),
+        ]);
+        assert_eq!(14, ringbuf.write(&mut dma, &[1; 14]).unwrap().0);
+        assert_eq!(14, ringbuf.start);
+
+        /*
+            The DMA reader has wrapped, write the tail and the head of the buffer
+        */
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(2),
+            TestCircularTransferRequest::PositionRequest(3),
+            TestCircularTransferRequest::ResetCompleteCount(1),
+        ]);
+        assert_eq!(4, ringbuf.write(&mut dma, &[2, 3, 4, 5, 6, 7]).unwrap().0);
+        assert_eq!(2, ringbuf.start);
+        assert_eq!([4, 5], ringbuf.dma_buf[..2]);
+        assert_eq!([2, 3], ringbuf.dma_buf[14..]);
+    }
+
+    #[test]
+    fn cannot_write_when_dma_reader_overtakes() {
+        let mut dma = TestCircularTransfer::new(16);
+
+        let mut dma_buf = [0u8; 16];
+        let mut ringbuf = DmaRingBuffer::new(&mut dma_buf);
+
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(2),
+            TestCircularTransferRequest::PositionRequest(2),
+            TestCircularTransferRequest::GetCompleteCount(0),
+        ]);
+        assert_eq!(2, ringbuf.write(&mut dma, &[1; 6]).unwrap().0);
+        assert_eq!(2, ringbuf.start);
+
+        /*
+            The DMA reader has read a whole lap, including bytes that weren't written
+        */
+        dma.setup(vec![
+            TestCircularTransferRequest::PositionRequest(4),
+            TestCircularTransferRequest::PositionRequest(6),
+            TestCircularTransferRequest::GetCompleteCount(1),
+        ]);
+        assert_eq!(OverrunError, ringbuf.write(&mut dma, &[1; 6]).unwrap_err());
+    }
 }
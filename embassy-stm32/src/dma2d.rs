@@ -0,0 +1,365 @@
+//! Chrom-ART Accelerator (DMA2D)
+//!
+//! The DMA2D fills and copies rectangles of pixels, converting between pixel formats and
+//! blending a foreground over a background on the way, without using the CPU. It's used to
+//! compose the framebuffers shown by the [`ltdc`](crate::ltdc).
+//!
+//! The DMA2D doesn't go through the data cache: on F7/H7, clean the cache for the input buffers
+//! before a transfer, and invalidate it for the output buffer after.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, Peripheral};
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        T::regs().cr().modify(|w| w.0 &= !CR_IE);
+        STATE.waker.wake();
+    }
+}
+
+struct State {
+    waker: AtomicWaker,
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+static STATE: State = State::new();
+
+// CR bits
+const CR_START: u32 = 1 << 0;
+const CR_ABORT: u32 = 1 << 2;
+const CR_TEIE: u32 = 1 << 8;
+const CR_TCIE: u32 = 1 << 9;
+const CR_CEIE: u32 = 1 << 13;
+const CR_IE: u32 = CR_TEIE | CR_TCIE | CR_CEIE;
+
+// ISR and IFCR bits
+const IT_TE: u32 = 1 << 0;
+const IT_TC: u32 = 1 << 1;
+const IT_CE: u32 = 1 << 5;
+const IT_ALL: u32 = 0x3F;
+
+/// Transfer modes
+const MODE_M2M_PFC: u32 = 0b01;
+const MODE_M2M_BLEND: u32 = 0b10;
+const MODE_R2M: u32 = 0b11;
+
+/// Errors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// A buffer is too small for the rectangle.
+    BufferTooSmall,
+    /// The stride of a buffer is smaller than the width of the rectangle.
+    InvalidStride,
+    /// A bus error happened while accessing a buffer.
+    Transfer,
+    /// The DMA2D rejected its configuration, for example a misaligned buffer.
+    Configuration,
+}
+
+/// Pixel format of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputFormat {
+    Argb8888 = 0,
+    Rgb888 = 1,
+    Rgb565 = 2,
+    Argb1555 = 3,
+    Argb4444 = 4,
+    L8 = 5,
+    Al44 = 6,
+    Al88 = 7,
+    L4 = 8,
+    A8 = 9,
+    A4 = 10,
+}
+
+impl InputFormat {
+    fn bits_per_pixel(&self) -> usize {
+        match self {
+            InputFormat::Argb8888 => 32,
+            InputFormat::Rgb888 => 24,
+            InputFormat::Rgb565 | InputFormat::Argb1555 | InputFormat::Argb4444 | InputFormat::Al88 => 16,
+            InputFormat::L8 | InputFormat::Al44 | InputFormat::A8 => 8,
+            InputFormat::L4 | InputFormat::A4 => 4,
+        }
+    }
+}
+
+/// Pixel format of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutputFormat {
+    Argb8888 = 0,
+    Rgb888 = 1,
+    Rgb565 = 2,
+    Argb1555 = 3,
+    Argb4444 = 4,
+}
+
+impl OutputFormat {
+    fn bits_per_pixel(&self) -> usize {
+        match self {
+            OutputFormat::Argb8888 => 32,
+            OutputFormat::Rgb888 => 24,
+            OutputFormat::Rgb565 | OutputFormat::Argb1555 | OutputFormat::Argb4444 => 16,
+        }
+    }
+}
+
+/// How the alpha of an input is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alpha {
+    /// The alpha of the pixels, or opaque for formats without alpha.
+    Pixel,
+    /// The given alpha, for all the pixels.
+    Replace(u8),
+    /// The alpha of the pixels, multiplied by the given alpha.
+    Multiply(u8),
+}
+
+/// An input rectangle of pixels.
+#[derive(Clone, Copy)]
+pub struct Input<'a> {
+    /// The pixels, starting with the top left pixel of the rectangle.
+    pub data: &'a [u8],
+    /// Number of pixels between the starts of two lines.
+    pub stride: u16,
+    pub format: InputFormat,
+    pub alpha: Alpha,
+}
+
+impl<'a> Input<'a> {
+    fn check(&self, width: u16, height: u16) -> Result<(), Error> {
+        check_len(
+            self.data.len(),
+            self.stride,
+            width,
+            height,
+            self.format.bits_per_pixel(),
+        )
+    }
+}
+
+/// The output rectangle of pixels.
+pub struct Output<'a> {
+    /// The pixels, starting with the top left pixel of the rectangle.
+    pub data: &'a mut [u8],
+    /// Number of pixels between the starts of two lines.
+    pub stride: u16,
+    pub format: OutputFormat,
+}
+
+impl<'a> Output<'a> {
+    fn check(&self, width: u16, height: u16) -> Result<(), Error> {
+        check_len(
+            self.data.len(),
+            self.stride,
+            width,
+            height,
+            self.format.bits_per_pixel(),
+        )
+    }
+}
+
+/// DMA2D driver.
+pub struct Dma2d<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Dma2d<'d, T> {
+    /// Create a new DMA2D driver.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+
+        T::enable();
+        T::reset();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Fill a rectangle of `width` x `height` pixels of `output` with `color`.
+    ///
+    /// `color` is in the output format, for example 0xAARRGGBB for ARGB8888, or a RGB565 value.
+    pub async fn fill(&mut self, output: Output<'_>, width: u16, height: u16, color: u32) -> Result<(), Error> {
+        output.check(width, height)?;
+
+        let regs = T::regs();
+        regs.ocolr().write(|w| w.0 = color);
+        self.set_output(&output, width, height);
+
+        self.run(MODE_R2M).await
+    }
+
+    /// Copy a rectangle of `width` x `height` pixels from `input` to `output`, converting their
+    /// format.
+    ///
+    /// The alpha of `input` only matters when both formats have an alpha channel.
+    pub async fn copy(&mut self, input: Input<'_>, output: Output<'_>, width: u16, height: u16) -> Result<(), Error> {
+        input.check(width, height)?;
+        output.check(width, height)?;
+
+        let regs = T::regs();
+        regs.fgmar().write(|w| w.0 = input.data.as_ptr() as u32);
+        regs.fgor().write(|w| w.0 = (input.stride - width) as u32);
+        regs.fgpfccr().write(|w| w.0 = pfccr(input.format, input.alpha));
+        self.set_output(&output, width, height);
+
+        self.run(MODE_M2M_PFC).await
+    }
+
+    /// Blend a rectangle of `width` x `height` pixels from `foreground` over `background`, and
+    /// write the result to `output`.
+    pub async fn blend(
+        &mut self,
+        foreground: Input<'_>,
+        background: Input<'_>,
+        output: Output<'_>,
+        width: u16,
+        height: u16,
+    ) -> Result<(), Error> {
+        for input in [&foreground, &background] {
+            input.check(width, height)?;
+        }
+        output.check(width, height)?;
+
+        let regs = T::regs();
+        regs.fgmar().write(|w| w.0 = foreground.data.as_ptr() as u32);
+        regs.fgor().write(|w| w.0 = (foreground.stride - width) as u32);
+        regs.fgpfccr()
+            .write(|w| w.0 = pfccr(foreground.format, foreground.alpha));
+        regs.bgmar().write(|w| w.0 = background.data.as_ptr() as u32);
+        regs.bgor().write(|w| w.0 = (background.stride - width) as u32);
+        regs.bgpfccr()
+            .write(|w| w.0 = pfccr(background.format, background.alpha));
+        self.set_output(&output, width, height);
+
+        self.run(MODE_M2M_BLEND).await
+    }
+
+    fn set_output(&mut self, output: &Output<'_>, width: u16, height: u16) {
+        let regs = T::regs();
+        regs.omar().write(|w| w.0 = output.data.as_ptr() as u32);
+        regs.oor().write(|w| w.0 = (output.stride - width) as u32);
+        regs.opfccr().write(|w| w.0 = output.format as u32);
+        regs.nlr().write(|w| w.0 = ((width as u32) << 16) | height as u32);
+    }
+
+    async fn run(&mut self, mode: u32) -> Result<(), Error> {
+        let regs = T::regs();
+
+        regs.ifcr().write(|w| w.0 = IT_ALL);
+        regs.cr().write(|w| w.0 = (mode << 16) | CR_IE | CR_START);
+
+        // Abort the transfer if the future is dropped.
+        let on_drop = OnDrop::new(|| {
+            regs.cr().modify(|w| w.0 |= CR_ABORT);
+            while regs.cr().read().0 & CR_START != 0 {}
+        });
+
+        let res = poll_fn(|cx| {
+            STATE.waker.register(cx.waker());
+
+            let isr = regs.isr().read().0;
+            if isr & IT_TE != 0 {
+                return Poll::Ready(Err(Error::Transfer));
+            } else if isr & IT_CE != 0 {
+                return Poll::Ready(Err(Error::Configuration));
+            } else if isr & IT_TC != 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            regs.cr().modify(|w| w.0 |= CR_IE);
+            Poll::Pending
+        })
+        .await;
+
+        on_drop.defuse();
+        regs.ifcr().write(|w| w.0 = IT_ALL);
+
+        res
+    }
+}
+
+impl<'d, T: Instance> Drop for Dma2d<'d, T> {
+    fn drop(&mut self) {
+        T::disable();
+    }
+}
+
+/// FGPFCCR/BGPFCCR value
+fn pfccr(format: InputFormat, alpha: Alpha) -> u32 {
+    let (am, alpha) = match alpha {
+        Alpha::Pixel => (0, 0xFF),
+        Alpha::Replace(alpha) => (1, alpha),
+        Alpha::Multiply(alpha) => (2, alpha),
+    };
+    ((alpha as u32) << 24) | (am << 16) | format as u32
+}
+
+/// Check that a buffer holds the rectangle.
+fn check_len(len: usize, stride: u16, width: u16, height: u16, bits_per_pixel: usize) -> Result<(), Error> {
+    if stride < width {
+        return Err(Error::InvalidStride);
+    }
+    if height == 0 {
+        return Ok(());
+    }
+    let pixels = (height as usize - 1) * stride as usize + width as usize;
+    if len < (pixels * bits_per_pixel + 7) / 8 {
+        return Err(Error::BufferTooSmall);
+    }
+    Ok(())
+}
+
+pub(crate) mod sealed {
+    pub trait Instance: crate::rcc::RccPeripheral {
+        fn regs() -> crate::pac::dma2d::Dma2d;
+    }
+}
+
+pub trait Instance: sealed::Instance + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, dma2d, DMA2D, GLOBAL, $irq:ident) => {
+        impl sealed::Instance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::dma2d::Dma2d {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
@@ -8,10 +8,13 @@ pub mod generic_smi;
 use core::mem::MaybeUninit;
 use core::task::Context;
 
+use embassy_hal_common::PeripheralRef;
 use embassy_net_driver::{Capabilities, LinkState};
 use embassy_sync::waitqueue::AtomicWaker;
 
 pub use self::_version::{InterruptHandler, *};
+use crate::gpio::sealed::Pin as _;
+use crate::gpio::AnyPin;
 
 #[allow(unused)]
 const MTU: usize = 1514;
@@ -51,6 +54,26 @@ impl<const TX: usize, const RX: usize> PacketQueue<TX, RX> {
 
 static WAKER: AtomicWaker = AtomicWaker::new();
 
+/// Pins of the interface between the MAC and the PHY.
+pub(crate) enum Pins<'d> {
+    /// Reduced Media Independent Interface.
+    Rmii([PeripheralRef<'d, AnyPin>; 9]),
+    /// Media Independent Interface.
+    Mii([PeripheralRef<'d, AnyPin>; 14]),
+}
+
+impl<'d> Pins<'d> {
+    fn set_as_disconnected(&mut self) {
+        let pins = match self {
+            Pins::Rmii(pins) => &mut pins[..],
+            Pins::Mii(pins) => &mut pins[..],
+        };
+        for pin in pins {
+            pin.set_as_disconnected();
+        }
+    }
+}
+
 impl<'d, T: Instance, P: PHY> embassy_net_driver::Driver for Ethernet<'d, T, P> {
     type RxToken<'a> = RxToken<'a, 'd> where Self: 'a;
     type TxToken<'a> = TxToken<'a, 'd> where Self: 'a;
@@ -179,3 +202,10 @@ pin_trait!(RXD1Pin, Instance);
 pin_trait!(TXD0Pin, Instance);
 pin_trait!(TXD1Pin, Instance);
 pin_trait!(TXEnPin, Instance);
+pin_trait!(RXClkPin, Instance);
+pin_trait!(TXClkPin, Instance);
+pin_trait!(RXDVPin, Instance);
+pin_trait!(RXD2Pin, Instance);
+pin_trait!(RXD3Pin, Instance);
+pin_trait!(TXD2Pin, Instance);
+pin_trait!(TXD3Pin, Instance);
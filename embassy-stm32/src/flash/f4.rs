@@ -4,10 +4,10 @@ use core::sync::atomic::{fence, Ordering};
 
 use atomic_polyfill::AtomicBool;
 use embassy_sync::waitqueue::AtomicWaker;
-use pac::flash::regs::Sr;
+use pac::flash::regs::{Optcr, Optkeyr, Sr};
 use pac::FLASH_SIZE;
 
-use super::{FlashBank, FlashRegion, FlashSector, FLASH_REGIONS, WRITE_SIZE};
+use super::{Flash, FlashBank, FlashRegion, FlashSector, FLASH_REGIONS, WRITE_SIZE};
 use crate::flash::Error;
 use crate::pac;
 
@@ -214,6 +214,33 @@ pub const fn get_flash_regions() -> &'static [&'static FlashRegion] {
     &FLASH_REGIONS
 }
 
+impl<'d, MODE> Flash<'d, MODE> {
+    /// The option bytes in use, the OPTCR register.
+    pub fn option_bytes(&self) -> u32 {
+        pac::FLASH.optcr().read().0
+    }
+
+    /// Program the option bytes of the OPTCR register, apart from its lock and start bits.
+    ///
+    /// Beware of the read protection level: setting level 2 is irreversible.
+    pub fn blocking_write_option_bytes(&mut self, value: u32) -> Result<(), Error> {
+        unsafe {
+            pac::FLASH.optkeyr().write_value(Optkeyr(0x0819_2A3B));
+            pac::FLASH.optkeyr().write_value(Optkeyr(0x4C5D_6E7F));
+
+            let mut optcr = Optcr(value);
+            optcr.set_optlock(false);
+            optcr.set_optstrt(false);
+            pac::FLASH.optcr().write_value(optcr);
+            pac::FLASH.optcr().modify(|w| w.set_optstrt(true));
+
+            let res = blocking_wait_ready();
+            pac::FLASH.optcr().modify(|w| w.set_optlock(true));
+            res
+        }
+    }
+}
+
 pub(crate) unsafe fn on_interrupt() {
     // Clear IRQ flags
     pac::FLASH.sr().write(|w| {
@@ -1,12 +1,64 @@
 use core::convert::TryInto;
+use core::future::poll_fn;
 use core::ptr::write_volatile;
+use core::task::Poll;
 
 use atomic_polyfill::{fence, Ordering};
+use embassy_sync::waitqueue::AtomicWaker;
+use pac::flash::regs::{Optkeyr, Sr};
 
-use super::{FlashRegion, FlashSector, BANK1_REGION, FLASH_REGIONS, WRITE_SIZE};
+use super::{Flash, FlashBank, FlashRegion, FlashSector, BANK1_REGION, FLASH_REGIONS, WRITE_SIZE};
 use crate::flash::Error;
 use crate::pac;
 
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+impl<'d, MODE> Flash<'d, MODE> {
+    /// Whether the banks are swapped, bank 2 being mapped at the start of the flash and bank 1
+    /// after it. The offsets given to the driver are always in the address space.
+    pub fn is_bank_swapped(&self) -> bool {
+        is_bank_swapped()
+    }
+
+    /// Swap the banks, or not, from the next reset, by programming the SWAP_BANK option bit.
+    ///
+    /// A bootloader can for example write the new firmware to the bank it doesn't run from, then
+    /// swap the banks and reset.
+    pub fn blocking_set_bank_swap(&mut self, swapped: bool) -> Result<(), Error> {
+        let mut optsr = pac::FLASH.optsr_prg().read();
+        optsr.set_swap_bank_opt(swapped);
+        self.blocking_write_option_bytes(optsr.0)
+    }
+
+    /// The option bytes in use, the OPTSR_CUR register.
+    pub fn option_bytes(&self) -> u32 {
+        pac::FLASH.optsr_cur().read().0
+    }
+
+    /// Program the option bytes of the OPTSR_PRG register, which apply immediately, apart from the
+    /// bank swap.
+    ///
+    /// Beware of the read protection level: setting level 2 is irreversible.
+    pub fn blocking_write_option_bytes(&mut self, value: u32) -> Result<(), Error> {
+        pac::FLASH.optkeyr().write_value(Optkeyr(0x0819_2A3B));
+        pac::FLASH.optkeyr().write_value(Optkeyr(0x4C5D_6E7F));
+
+        pac::FLASH.optsr_prg().modify(|w| w.0 = value);
+        pac::FLASH.optcr().modify(|w| w.set_optstart(true));
+        while pac::FLASH.optsr_cur().read().opt_busy() {}
+
+        let res = if pac::FLASH.optsr_cur().read().optchangeerr() {
+            pac::FLASH.optccr().write(|w| w.set_clr_optchangeerr(true));
+            Err(Error::Prog)
+        } else {
+            Ok(())
+        };
+
+        pac::FLASH.optcr().modify(|w| w.set_optlock(true));
+        res
+    }
+}
+
 pub const fn is_default_layout() -> bool {
     true
 }
@@ -19,6 +71,50 @@ pub fn get_flash_regions() -> &'static [&'static FlashRegion] {
     &FLASH_REGIONS
 }
 
+fn is_bank_swapped() -> bool {
+    is_dual_bank() && pac::FLASH.optsr_cur().read().swap_bank_opt()
+}
+
+/// The registers of the bank mapped at `bank` in the address space, which are those of the other
+/// bank when the banks are swapped.
+fn bank_regs(bank: FlashBank) -> pac::flash::Bank {
+    let index = match bank {
+        FlashBank::Bank2 => 1,
+        _ => 0,
+    };
+    pac::FLASH.bank(index ^ is_bank_swapped() as usize)
+}
+
+fn address_bank_regs(address: u32) -> pac::flash::Bank {
+    if address < BANK1_REGION.end() {
+        bank_regs(FlashBank::Bank1)
+    } else {
+        bank_regs(FlashBank::Bank2)
+    }
+}
+
+pub(crate) unsafe fn on_interrupt() {
+    let banks = if is_dual_bank() { 2 } else { 1 };
+    for index in 0..banks {
+        let bank = pac::FLASH.bank(index);
+        // Mask the interrupts, the future checks the flags.
+        bank.cr().modify(|w| {
+            w.set_eopie(false);
+            w.set_wrperrie(false);
+            w.set_pgserrie(false);
+            w.set_incerrie(false);
+            w.set_operrie(false);
+        });
+        bank.sr().modify(|w| {
+            if w.eop() {
+                w.set_eop(true);
+            }
+        });
+    }
+
+    WAKER.wake();
+}
+
 pub(crate) unsafe fn lock() {
     pac::FLASH.bank(0).cr().modify(|w| w.set_lock(true));
     if is_dual_bank() {
@@ -41,13 +137,46 @@ pub(crate) unsafe fn enable_blocking_write() {
 
 pub(crate) unsafe fn disable_blocking_write() {}
 
+pub(crate) unsafe fn enable_write() {
+    assert_eq!(0, WRITE_SIZE % 4);
+}
+
+pub(crate) unsafe fn disable_write() {}
+
+pub(crate) async unsafe fn write(start_address: u32, buf: &[u8; WRITE_SIZE]) -> Result<(), Error> {
+    // The flash word is programmed once fully written, the bank not stalling reads meanwhile.
+    let bank = address_bank_regs(start_address);
+    bank.cr().write(|w| {
+        w.set_pg(true);
+        w.set_psize(2); // 32 bits at once
+    });
+    cortex_m::asm::isb();
+    cortex_m::asm::dsb();
+    fence(Ordering::SeqCst);
+
+    let mut address = start_address;
+    for val in buf.chunks(4) {
+        write_volatile(address as *mut u32, u32::from_le_bytes(val.try_into().unwrap()));
+        address += val.len() as u32;
+
+        // prevents parallelism errors
+        fence(Ordering::SeqCst);
+    }
+
+    let res = wait_ready(bank).await;
+
+    bank.cr().write(|w| w.set_pg(false));
+
+    cortex_m::asm::isb();
+    cortex_m::asm::dsb();
+    fence(Ordering::SeqCst);
+
+    res
+}
+
 pub(crate) unsafe fn blocking_write(start_address: u32, buf: &[u8; WRITE_SIZE]) -> Result<(), Error> {
     // We cannot have the write setup sequence in begin_write as it depends on the address
-    let bank = if start_address < BANK1_REGION.end() {
-        pac::FLASH.bank(0)
-    } else {
-        pac::FLASH.bank(1)
-    };
+    let bank = address_bank_regs(start_address);
     bank.cr().write(|w| {
         w.set_pg(true);
         w.set_psize(2); // 32 bits at once
@@ -82,8 +211,26 @@ pub(crate) unsafe fn blocking_write(start_address: u32, buf: &[u8; WRITE_SIZE])
     res.unwrap()
 }
 
+pub(crate) async unsafe fn erase_sector(sector: &FlashSector) -> Result<(), Error> {
+    // Only the bank erased is stalled, the other one can still be read and run from.
+    let bank = bank_regs(sector.bank);
+    bank.cr().modify(|w| {
+        w.set_ser(true);
+        w.set_snb(sector.index_in_bank)
+    });
+
+    bank.cr().modify(|w| {
+        w.set_start(true);
+    });
+
+    let ret: Result<(), Error> = wait_ready(bank).await;
+    bank.cr().modify(|w| w.set_ser(false));
+    bank_clear_all_err(bank);
+    ret
+}
+
 pub(crate) unsafe fn blocking_erase_sector(sector: &FlashSector) -> Result<(), Error> {
-    let bank = pac::FLASH.bank(sector.bank as usize);
+    let bank = bank_regs(sector.bank);
     bank.cr().modify(|w| {
         w.set_ser(true);
         w.set_snb(sector.index_in_bank)
@@ -140,42 +287,67 @@ unsafe fn bank_clear_all_err(bank: pac::flash::Bank) {
     });
 }
 
+async fn wait_ready(bank: pac::flash::Bank) -> Result<(), Error> {
+    poll_fn(|cx| {
+        WAKER.register(cx.waker());
+
+        let sr = bank.sr().read();
+        if !sr.bsy() && !sr.qw() {
+            Poll::Ready(get_result(sr))
+        } else {
+            bank.cr().modify(|w| {
+                w.set_eopie(true);
+                w.set_wrperrie(true);
+                w.set_pgserrie(true);
+                w.set_incerrie(true);
+                w.set_operrie(true);
+            });
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 unsafe fn blocking_wait_ready(bank: pac::flash::Bank) -> Result<(), Error> {
     loop {
         let sr = bank.sr().read();
 
         if !sr.bsy() && !sr.qw() {
-            if sr.wrperr() {
-                return Err(Error::Protected);
-            }
-            if sr.pgserr() {
-                error!("pgserr");
-                return Err(Error::Seq);
-            }
-            if sr.incerr() {
-                // writing to a different address when programming 256 bit word was not finished
-                error!("incerr");
-                return Err(Error::Seq);
-            }
-            if sr.operr() {
-                return Err(Error::Prog);
-            }
-            if sr.sneccerr1() {
-                // single ECC error
-                return Err(Error::Prog);
-            }
-            if sr.dbeccerr() {
-                // double ECC error
-                return Err(Error::Prog);
-            }
-            if sr.rdperr() {
-                return Err(Error::Protected);
-            }
-            if sr.rdserr() {
-                return Err(Error::Protected);
-            }
-
-            return Ok(());
+            return get_result(sr);
         }
     }
 }
+
+fn get_result(sr: Sr) -> Result<(), Error> {
+    if sr.wrperr() {
+        return Err(Error::Protected);
+    }
+    if sr.pgserr() {
+        error!("pgserr");
+        return Err(Error::Seq);
+    }
+    if sr.incerr() {
+        // writing to a different address when programming 256 bit word was not finished
+        error!("incerr");
+        return Err(Error::Seq);
+    }
+    if sr.operr() {
+        return Err(Error::Prog);
+    }
+    if sr.sneccerr1() {
+        // single ECC error
+        return Err(Error::Prog);
+    }
+    if sr.dbeccerr() {
+        // double ECC error
+        return Err(Error::Prog);
+    }
+    if sr.rdperr() {
+        return Err(Error::Protected);
+    }
+    if sr.rdserr() {
+        return Err(Error::Protected);
+    }
+
+    Ok(())
+}
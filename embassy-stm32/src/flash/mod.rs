@@ -1,11 +1,11 @@
 use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind};
 
-#[cfg(flash_f4)]
+#[cfg(any(flash_f4, flash_h7))]
 mod asynch;
 #[cfg(flash)]
 mod common;
 
-#[cfg(flash_f4)]
+#[cfg(any(flash_f4, flash_h7))]
 pub use asynch::InterruptHandler;
 #[cfg(flash)]
 pub use common::*;
@@ -4,6 +4,8 @@ use embassy_hal_common::into_ref;
 
 use crate::gpio::sealed::AFType;
 use crate::gpio::{Pull, Speed};
+use crate::pac::fmc::regs::{Btr, Bwtr};
+use crate::pac::fmc::vals::{Accmod, Mtyp, Mwid};
 use crate::Peripheral;
 
 pub struct Fmc<'d, T: Instance> {
@@ -24,11 +26,7 @@ where
     }
 
     fn memory_controller_enable(&mut self) {
-        // fmc v1 and v2 does not have the fmcen bit
-        // fsmc v1, v2 and v3 does not have the fmcen bit
-        // This is a "not" because it is expected that all future versions have this bit
-        #[cfg(not(any(fmc_v1x3, fmc_v2x1, fsmc_v1x0, fsmc_v1x3, fsmc_v2x3, fsmc_v3x1)))]
-        T::REGS.bcr1().modify(|r| r.set_fmcen(true));
+        memory_controller_enable::<T>();
     }
 
     fn source_clock_hz(&self) -> u32 {
@@ -36,6 +34,202 @@ where
     }
 }
 
+fn memory_controller_enable<T: Instance>() {
+    // fmc v1 and v2 does not have the fmcen bit
+    // fsmc v1, v2 and v3 does not have the fmcen bit
+    // This is a "not" because it is expected that all future versions have this bit
+    #[cfg(not(any(fmc_v1x3, fmc_v2x1, fsmc_v1x0, fsmc_v1x3, fsmc_v2x3, fsmc_v3x1)))]
+    T::REGS.bcr1().modify(|r| r.set_fmcen(true));
+}
+
+/// Sub-bank of the NOR/PSRAM/SRAM bank, selected by the NEx pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NorSramBank {
+    Bank1,
+    Bank2,
+    Bank3,
+    Bank4,
+}
+
+impl NorSramBank {
+    fn index(&self) -> usize {
+        match self {
+            NorSramBank::Bank1 => 0,
+            NorSramBank::Bank2 => 1,
+            NorSramBank::Bank3 => 2,
+            NorSramBank::Bank4 => 3,
+        }
+    }
+}
+
+/// Type of an asynchronous memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NorSramType {
+    Sram = 0b00,
+    Psram = 0b01,
+    Nor = 0b10,
+}
+
+/// Width of the data bus of an asynchronous memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NorSramWidth {
+    Bits8 = 0b00,
+    Bits16 = 0b01,
+    Bits32 = 0b10,
+}
+
+/// Access mode of an asynchronous memory, which sets the shape of the NOE and NWE signals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccessMode {
+    A = 0b00,
+    B = 0b01,
+    C = 0b10,
+    D = 0b11,
+}
+
+/// Timings of an asynchronous memory, in FMC kernel clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NorSramTiming {
+    /// Address setup phase duration (ADDSET), 0 to 15
+    pub address_setup: u8,
+    /// Address hold phase duration in access mode D (ADDHLD), 1 to 15
+    pub address_hold: u8,
+    /// Data phase duration (DATAST), 1 to 255
+    pub data_setup: u8,
+    /// Bus turnaround phase duration (BUSTURN), 0 to 15
+    pub bus_turnaround: u8,
+    pub access_mode: AccessMode,
+}
+
+impl NorSramTiming {
+    fn check(&self) {
+        assert!(self.address_setup <= 15);
+        assert!((1..=15).contains(&self.address_hold));
+        assert!(self.data_setup >= 1);
+        assert!(self.bus_turnaround <= 15);
+    }
+
+    fn btr(&self) -> Btr {
+        self.check();
+        let mut w = Btr(0);
+        w.set_addset(self.address_setup);
+        w.set_addhld(self.address_hold);
+        w.set_datast(self.data_setup);
+        w.set_busturn(self.bus_turnaround);
+        w.set_accmod(Accmod::from_bits(self.access_mode as u8));
+        w
+    }
+
+    fn bwtr(&self) -> Bwtr {
+        self.check();
+        let mut w = Bwtr(0);
+        w.set_addset(self.address_setup);
+        w.set_addhld(self.address_hold);
+        w.set_datast(self.data_setup);
+        w.set_busturn(self.bus_turnaround);
+        w.set_accmod(Accmod::from_bits(self.access_mode as u8));
+        w
+    }
+}
+
+impl Default for NorSramTiming {
+    fn default() -> Self {
+        Self {
+            address_setup: 15,
+            address_hold: 15,
+            data_setup: 255,
+            bus_turnaround: 15,
+            access_mode: AccessMode::A,
+        }
+    }
+}
+
+/// Configuration of an asynchronous memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NorSramConfig {
+    pub memory_type: NorSramType,
+    pub data_width: NorSramWidth,
+    /// Timings of the reads, and of the writes when `write_timing` is `None`.
+    pub timing: NorSramTiming,
+    /// Timings of the writes, when they differ from those of the reads (extended mode).
+    pub write_timing: Option<NorSramTiming>,
+    /// Allow writes to the memory (WREN)
+    pub write_enable: bool,
+}
+
+impl Default for NorSramConfig {
+    fn default() -> Self {
+        Self {
+            memory_type: NorSramType::Sram,
+            data_width: NorSramWidth::Bits16,
+            timing: Default::default(),
+            write_timing: None,
+            write_enable: true,
+        }
+    }
+}
+
+/// An asynchronous memory (SRAM, PSRAM or NOR flash), mapped in the address space.
+pub struct NorSram<'d, T: Instance> {
+    bank: NorSramBank,
+    _peri: PhantomData<&'d mut T>,
+}
+
+impl<'d, T: Instance> NorSram<'d, T> {
+    fn new(bank: NorSramBank, config: NorSramConfig) -> Self {
+        // The FMC isn't reset, to keep the configuration of the other banks.
+        <T as crate::rcc::sealed::RccPeripheral>::enable();
+
+        let regs = T::REGS;
+        let n = bank.index();
+
+        // BCR1 has its own type, with the bits that apply to the whole FMC.
+        macro_rules! modify_bcr {
+            ($f:expr) => {
+                match n {
+                    0 => regs.bcr1().modify($f),
+                    n => regs.bcr(n - 1).modify($f),
+                }
+            };
+        }
+
+        // Disable the bank while configuring it.
+        modify_bcr!(|w| w.set_mbken(false));
+
+        regs.btr(n).write_value(config.timing.btr());
+        if let Some(write_timing) = config.write_timing {
+            regs.bwtr(n).write_value(write_timing.bwtr());
+        }
+
+        modify_bcr!(|w| {
+            w.set_mtyp(Mtyp::from_bits(config.memory_type as u8));
+            w.set_mwid(Mwid::from_bits(config.data_width as u8));
+            w.set_faccen(config.memory_type == NorSramType::Nor);
+            w.set_wren(config.write_enable);
+            w.set_extmod(config.write_timing.is_some());
+            w.set_mbken(true);
+        });
+
+        memory_controller_enable::<T>();
+
+        Self {
+            bank,
+            _peri: PhantomData,
+        }
+    }
+
+    /// Address where the memory is mapped.
+    pub fn ptr(&self) -> *mut u8 {
+        (0x6000_0000 + 0x0400_0000 * self.bank.index()) as *mut u8
+    }
+}
+
 macro_rules! config_pins {
     ($($pin:ident),*) => {
         into_ref!($($pin),*);
@@ -85,6 +279,37 @@ macro_rules! fmc_sdram_constructor {
     };
 }
 
+macro_rules! fmc_norsram_constructor {
+    ($name:ident: (
+        bank: $bank:expr,
+        addr: [$(($addr_pin_name:ident: $addr_signal:ident)),*],
+        d: [$(($d_pin_name:ident: $d_signal:ident)),*],
+        nbl: [$(($nbl_pin_name:ident: $nbl_signal:ident)),*],
+        ctrl: [$(($ctrl_pin_name:ident: $ctrl_signal:ident)),*]
+    )) => {
+        pub fn $name(
+            _instance: impl Peripheral<P = T> + 'd,
+            $($addr_pin_name: impl Peripheral<P = impl $addr_signal<T>> + 'd),*,
+            $($d_pin_name: impl Peripheral<P = impl $d_signal<T>> + 'd),*,
+            $($nbl_pin_name: impl Peripheral<P = impl $nbl_signal<T>> + 'd),*,
+            $($ctrl_pin_name: impl Peripheral<P = impl $ctrl_signal<T>> + 'd),*,
+            config: NorSramConfig
+        ) -> NorSram<'d, T> {
+
+        critical_section::with(|_| {
+            config_pins!(
+                $($addr_pin_name),*,
+                $($d_pin_name),*,
+                $($nbl_pin_name),*,
+                $($ctrl_pin_name),*
+            );
+        });
+
+            NorSram::new($bank, config)
+        }
+    };
+}
+
 impl<'d, T: Instance> Fmc<'d, T> {
     fmc_sdram_constructor!(sdram_a12bits_d16bits_4banks_bank1: (
         bank: stm32_fmc::SdramTargetBank::Bank1,
@@ -161,6 +386,230 @@ impl<'d, T: Instance> Fmc<'d, T> {
             (sdcke: SDCKE1Pin), (sdclk: SDCLKPin), (sdncas: SDNCASPin), (sdne: SDNE1Pin), (sdnras: SDNRASPin), (sdnwe: SDNWEPin)
         ]
     ));
+
+    fmc_sdram_constructor!(sdram_a13bits_d16bits_4banks_bank1: (
+        bank: stm32_fmc::SdramTargetBank::Bank1,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin)
+        ],
+        ba: [(ba0: BA0Pin), (ba1: BA1Pin)],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (sdcke: SDCKE0Pin), (sdclk: SDCLKPin), (sdncas: SDNCASPin), (sdne: SDNE0Pin), (sdnras: SDNRASPin), (sdnwe: SDNWEPin)
+        ]
+    ));
+
+    fmc_sdram_constructor!(sdram_a13bits_d32bits_4banks_bank1: (
+        bank: stm32_fmc::SdramTargetBank::Bank1,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin)
+        ],
+        ba: [(ba0: BA0Pin), (ba1: BA1Pin)],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin),
+            (d16: D16Pin), (d17: D17Pin), (d18: D18Pin), (d19: D19Pin), (d20: D20Pin), (d21: D21Pin), (d22: D22Pin), (d23: D23Pin),
+            (d24: D24Pin), (d25: D25Pin), (d26: D26Pin), (d27: D27Pin), (d28: D28Pin), (d29: D29Pin), (d30: D30Pin), (d31: D31Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin), (nbl2: NBL2Pin), (nbl3: NBL3Pin)
+        ],
+        ctrl: [
+            (sdcke: SDCKE0Pin), (sdclk: SDCLKPin), (sdncas: SDNCASPin), (sdne: SDNE0Pin), (sdnras: SDNRASPin), (sdnwe: SDNWEPin)
+        ]
+    ));
+
+    fmc_sdram_constructor!(sdram_a13bits_d16bits_4banks_bank2: (
+        bank: stm32_fmc::SdramTargetBank::Bank2,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin)
+        ],
+        ba: [(ba0: BA0Pin), (ba1: BA1Pin)],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (sdcke: SDCKE1Pin), (sdclk: SDCLKPin), (sdncas: SDNCASPin), (sdne: SDNE1Pin), (sdnras: SDNRASPin), (sdnwe: SDNWEPin)
+        ]
+    ));
+
+    fmc_sdram_constructor!(sdram_a13bits_d32bits_4banks_bank2: (
+        bank: stm32_fmc::SdramTargetBank::Bank2,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin)
+        ],
+        ba: [(ba0: BA0Pin), (ba1: BA1Pin)],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin),
+            (d16: D16Pin), (d17: D17Pin), (d18: D18Pin), (d19: D19Pin), (d20: D20Pin), (d21: D21Pin), (d22: D22Pin), (d23: D23Pin),
+            (d24: D24Pin), (d25: D25Pin), (d26: D26Pin), (d27: D27Pin), (d28: D28Pin), (d29: D29Pin), (d30: D30Pin), (d31: D31Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin), (nbl2: NBL2Pin), (nbl3: NBL3Pin)
+        ],
+        ctrl: [
+            (sdcke: SDCKE1Pin), (sdclk: SDCLKPin), (sdncas: SDNCASPin), (sdne: SDNE1Pin), (sdnras: SDNRASPin), (sdnwe: SDNWEPin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a19bits_d16bits_bank1: (
+        bank: NorSramBank::Bank1,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE1Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a19bits_d16bits_bank2: (
+        bank: NorSramBank::Bank2,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE2Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a19bits_d16bits_bank3: (
+        bank: NorSramBank::Bank3,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE3Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a19bits_d16bits_bank4: (
+        bank: NorSramBank::Bank4,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE4Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a23bits_d16bits_bank1: (
+        bank: NorSramBank::Bank1,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin), (a19: A19Pin), (a20: A20Pin), (a21: A21Pin), (a22: A22Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE1Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a23bits_d16bits_bank2: (
+        bank: NorSramBank::Bank2,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin), (a19: A19Pin), (a20: A20Pin), (a21: A21Pin), (a22: A22Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE2Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a23bits_d16bits_bank3: (
+        bank: NorSramBank::Bank3,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin), (a19: A19Pin), (a20: A20Pin), (a21: A21Pin), (a22: A22Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE3Pin)
+        ]
+    ));
+
+    fmc_norsram_constructor!(norsram_a23bits_d16bits_bank4: (
+        bank: NorSramBank::Bank4,
+        addr: [
+            (a0: A0Pin), (a1: A1Pin), (a2: A2Pin), (a3: A3Pin), (a4: A4Pin), (a5: A5Pin), (a6: A6Pin), (a7: A7Pin), (a8: A8Pin), (a9: A9Pin), (a10: A10Pin), (a11: A11Pin),
+            (a12: A12Pin), (a13: A13Pin), (a14: A14Pin), (a15: A15Pin), (a16: A16Pin), (a17: A17Pin), (a18: A18Pin), (a19: A19Pin), (a20: A20Pin), (a21: A21Pin), (a22: A22Pin)
+        ],
+        d: [
+            (d0: D0Pin), (d1: D1Pin), (d2: D2Pin), (d3: D3Pin), (d4: D4Pin), (d5: D5Pin), (d6: D6Pin), (d7: D7Pin),
+            (d8: D8Pin), (d9: D9Pin), (d10: D10Pin), (d11: D11Pin), (d12: D12Pin), (d13: D13Pin), (d14: D14Pin), (d15: D15Pin)
+        ],
+        nbl: [
+            (nbl0: NBL0Pin), (nbl1: NBL1Pin)
+        ],
+        ctrl: [
+            (noe: NOEPin), (nwe: NWEPin), (ne: NE4Pin)
+        ]
+    ));
 }
 
 pub(crate) mod sealed {
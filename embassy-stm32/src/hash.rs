@@ -0,0 +1,304 @@
+#![macro_use]
+
+//! HASH hardware accelerator.
+//!
+//! Computes SHA-1, SHA-224, SHA-256 and MD5 digests, and their HMAC, of messages fed in any
+//! number of updates. The state of a computation is kept in a [`Context`], saved and restored
+//! around each update, so that several digests can be computed at the same time, for example
+//! the transcript hashes of a TLS handshake.
+//!
+//! The async [`update()`](Hash::update) feeds the data with DMA, which needs the MDMAT bit of
+//! the peripheral: it isn't available on the STM32F41x.
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::dma::Transfer;
+use crate::pac::hash::regs::{Cr, Imr, Str};
+use crate::rcc::RccPeripheral;
+use crate::{peripherals, Peripheral};
+
+/// Number of context swap registers.
+const NUM_CONTEXT_REGS: usize = 54;
+
+/// Size of the blocks processed by the peripheral, in bytes.
+const BLOCK_SIZE: usize = 64;
+
+/// The first block is only processed once the first word of the next one is written.
+const FIRST_BLOCK_SIZE: usize = BLOCK_SIZE + 4;
+
+/// Key length above which the HMAC key is hashed first.
+const HMAC_LONG_KEY: usize = 64;
+
+/// Longest digest, of SHA-256.
+pub const MAX_DIGEST_SIZE: usize = 32;
+
+/// Hash algorithm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Algorithm {
+    Sha1,
+    Md5,
+    Sha224,
+    Sha256,
+}
+
+impl Algorithm {
+    fn set_algo(self, w: &mut Cr) {
+        let (algo1, algo0) = match self {
+            Algorithm::Sha1 => (false, false),
+            Algorithm::Md5 => (false, true),
+            Algorithm::Sha224 => (true, false),
+            Algorithm::Sha256 => (true, true),
+        };
+        w.set_algo1(algo1);
+        w.set_algo0(algo0);
+    }
+
+    /// Size of the digest, in bytes.
+    pub fn digest_size(self) -> usize {
+        match self {
+            Algorithm::Sha1 => 20,
+            Algorithm::Md5 => 16,
+            Algorithm::Sha224 => 28,
+            Algorithm::Sha256 => 32,
+        }
+    }
+}
+
+/// State of a digest computation.
+pub struct Context<'k> {
+    algorithm: Algorithm,
+    key: Option<&'k [u8]>,
+    /// Data not fed to the peripheral yet, because it doesn't fill a block.
+    buffer: [u8; FIRST_BLOCK_SIZE],
+    buflen: usize,
+    first_block: bool,
+    cr: u32,
+    imr: u32,
+    str: u32,
+    csr: [u32; NUM_CONTEXT_REGS],
+}
+
+impl<'k> Context<'k> {
+    fn block_size(&self) -> usize {
+        if self.first_block {
+            FIRST_BLOCK_SIZE
+        } else {
+            BLOCK_SIZE
+        }
+    }
+}
+
+/// HASH driver.
+pub struct Hash<'d, T: Instance, D = crate::dma::NoDma> {
+    _peri: PeripheralRef<'d, T>,
+    dma: PeripheralRef<'d, D>,
+}
+
+impl<'d, T: Instance, D> Hash<'d, T, D> {
+    /// Create a HASH driver, the DMA channel can be `NoDma` for the blocking methods.
+    pub fn new(peri: impl Peripheral<P = T> + 'd, dma: impl Peripheral<P = D> + 'd) -> Self {
+        into_ref!(peri, dma);
+
+        T::enable();
+        T::reset();
+
+        Self { _peri: peri, dma }
+    }
+
+    /// Start a digest computation, of the HMAC with `key` if it's set.
+    pub fn start<'k>(&mut self, algorithm: Algorithm, key: Option<&'k [u8]>) -> Context<'k> {
+        let mut cr = Cr(0);
+        // Bytes, swapped in the words by the peripheral.
+        cr.set_datatype(0b10);
+        algorithm.set_algo(&mut cr);
+        if let Some(key) = key {
+            cr.set_mode(true);
+            cr.set_lkey(key.len() > HMAC_LONG_KEY);
+        }
+        T::regs().cr().write_value(cr);
+        cr.set_init(true);
+        T::regs().cr().write_value(cr);
+
+        // The HMAC starts with the hash of the key.
+        if let Some(key) = key {
+            write_key::<T>(key);
+            while T::regs().sr().read().busy() {}
+        }
+
+        let mut ctx = Context {
+            algorithm,
+            key,
+            buffer: [0; FIRST_BLOCK_SIZE],
+            buflen: 0,
+            first_block: true,
+            cr: 0,
+            imr: 0,
+            str: 0,
+            csr: [0; NUM_CONTEXT_REGS],
+        };
+        store_context::<T>(&mut ctx);
+        ctx
+    }
+
+    /// Feed `input` to the digest computation of `ctx`.
+    pub fn blocking_update(&mut self, ctx: &mut Context, input: &[u8]) {
+        load_context::<T>(ctx);
+
+        let mut input = input;
+        while !input.is_empty() {
+            let n = (ctx.block_size() - ctx.buflen).min(input.len());
+            ctx.buffer[ctx.buflen..ctx.buflen + n].copy_from_slice(&input[..n]);
+            ctx.buflen += n;
+            input = &input[n..];
+
+            if ctx.buflen == ctx.block_size() {
+                while T::regs().sr().read().busy() {}
+                write_words::<T>(&ctx.buffer[..ctx.buflen]);
+                ctx.buflen = 0;
+                ctx.first_block = false;
+            }
+        }
+
+        store_context::<T>(ctx);
+    }
+
+    /// Feed `input` to the digest computation of `ctx`, moving the full blocks with DMA when
+    /// they're aligned to 4 bytes.
+    pub async fn update(&mut self, ctx: &mut Context<'_>, input: &[u8])
+    where
+        D: crate::hash::DmaIn<T>,
+    {
+        // Fill the pending block, so that the rest of the data starts a block.
+        let n = if ctx.buflen > 0 || ctx.first_block {
+            (ctx.block_size() - ctx.buflen).min(input.len())
+        } else {
+            0
+        };
+        self.blocking_update(ctx, &input[..n]);
+        let input = &input[n..];
+
+        let full = input.len() / BLOCK_SIZE * BLOCK_SIZE;
+        let (blocks, rest) = input.split_at(full);
+
+        if !blocks.is_empty() && blocks.as_ptr() as usize % 4 == 0 && ctx.buflen == 0 {
+            load_context::<T>(ctx);
+
+            // MDMAT keeps the end of the transfer from starting the digest calculation.
+            T::regs().cr().modify(|w| {
+                w.set_dmae(true);
+                w.set_mdmat(true);
+            });
+            let words = core::ptr::slice_from_raw_parts(blocks.as_ptr() as *const u32, full / 4);
+            let request = self.dma.request();
+            let dst = T::regs().din().as_ptr() as *mut u32;
+            let transfer = unsafe { Transfer::new_write_raw(&mut self.dma, request, words, dst, Default::default()) };
+            transfer.await;
+            while T::regs().sr().read().busy() {}
+            T::regs().cr().modify(|w| w.set_dmae(false));
+
+            store_context::<T>(ctx);
+            self.blocking_update(ctx, rest);
+        } else {
+            self.blocking_update(ctx, input);
+        }
+    }
+
+    /// Finish the digest computation of `ctx`, write the digest to the start of `digest`, and
+    /// return its size.
+    pub fn blocking_finish(&mut self, mut ctx: Context, digest: &mut [u8]) -> usize {
+        load_context::<T>(&ctx);
+
+        // Feed the rest of the data, the last word being partial.
+        T::regs().str().modify(|w| w.set_nblw(((ctx.buflen % 4) * 8) as u8));
+        write_words::<T>(&ctx.buffer[..ctx.buflen]);
+        ctx.buflen = 0;
+        T::regs().str().modify(|w| w.set_dcal(true));
+
+        // The HMAC ends with the hash of the key, and the inner hash.
+        if let Some(key) = ctx.key {
+            while !T::regs().sr().read().dinis() {}
+            write_key::<T>(key);
+        }
+        while !T::regs().sr().read().dcis() {}
+
+        let size = ctx.algorithm.digest_size();
+        for (i, chunk) in digest[..size].chunks_mut(4).enumerate() {
+            chunk.copy_from_slice(&T::regs().hr(i).read().to_be_bytes()[..chunk.len()]);
+        }
+        size
+    }
+}
+
+impl<'d, T: Instance, D> Drop for Hash<'d, T, D> {
+    fn drop(&mut self) {
+        T::disable();
+    }
+}
+
+/// Write bytes to the data input, the peripheral swapping the bytes of the words so that they're
+/// in memory order.
+fn write_words<T: Instance>(data: &[u8]) {
+    for chunk in data.chunks(4) {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        T::regs().din().write_value(u32::from_le_bytes(word));
+    }
+}
+
+/// Feed the HMAC key, and start its hash.
+fn write_key<T: Instance>(key: &[u8]) {
+    T::regs().str().modify(|w| w.set_nblw(((key.len() % 4) * 8) as u8));
+    write_words::<T>(key);
+    T::regs().str().modify(|w| w.set_dcal(true));
+}
+
+fn load_context<T: Instance>(ctx: &Context) {
+    let regs = T::regs();
+    regs.imr().write_value(Imr(ctx.imr));
+    regs.str().write_value(Str(ctx.str));
+    let mut cr = Cr(ctx.cr);
+    regs.cr().write_value(cr);
+    cr.set_init(true);
+    regs.cr().write_value(cr);
+    for (i, csr) in ctx.csr.iter().enumerate() {
+        regs.csr(i).write_value(*csr);
+    }
+}
+
+fn store_context<T: Instance>(ctx: &mut Context) {
+    // The context can only be saved between blocks.
+    let regs = T::regs();
+    while regs.sr().read().busy() {}
+
+    ctx.imr = regs.imr().read().0;
+    ctx.str = regs.str().read().0;
+    let mut cr = regs.cr().read();
+    cr.set_init(false);
+    ctx.cr = cr.0;
+    for (i, csr) in ctx.csr.iter_mut().enumerate() {
+        *csr = regs.csr(i).read();
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::hash::Hash;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {}
+
+dma_trait!(DmaIn, Instance);
+
+foreach_peripheral!(
+    (hash, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::hash::Hash {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+);
@@ -0,0 +1,550 @@
+#![macro_use]
+
+//! HRTIM, high-resolution timer.
+//!
+//! The HRTIM has a master timer and 5 timing units, A to E (and F on the STM32G4), each one
+//! with two outputs and a 16-bit counter clocked at up to 32 times the HRTIM clock: on a
+//! 144 MHz HRTIM clock, the resolution of the PWM is 217 ps.
+//!
+//! [`Hrtim`] runs the timing units as PWM generators: output 1 is set at the start of the
+//! period and reset on the duty cycle, output 2 is its complement, with an optional dead time.
+//! The fault inputs set the outputs of the timing units protected by them to a safe state, in
+//! hardware, and the burst mode idles the outputs during a part of the burst period, to reduce
+//! the switching losses of a converter at light load.
+
+use core::marker::PhantomData;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::AnyPin;
+use crate::pac::hrtim::regs;
+use crate::rcc::RccPeripheral;
+use crate::time::Hertz;
+use crate::{peripherals, Peripheral};
+
+// ISR and ICR fault flags
+#[cfg(not(stm32g4))]
+const ISR_FLT_MASK: u32 = 0b1_1111;
+#[cfg(stm32g4)]
+const ISR_FLT_MASK: u32 = 0b101_1111;
+
+/// BMCR.BMCLK value of the prescaled HRTIM clock.
+const BMCLK_PRESCALED: u8 = 0b1010;
+
+/// Highest period and compare value.
+const MAX_PERIOD: u16 = 0xffdf;
+/// Highest dead time, in dead time generator ticks.
+const MAX_DEAD_TIME: u16 = 0x1ff;
+
+#[cfg(not(stm32g4))]
+const TIMERS: usize = 5;
+#[cfg(stm32g4)]
+const TIMERS: usize = 6;
+
+/// Timing unit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Timer {
+    A,
+    B,
+    C,
+    D,
+    E,
+    #[cfg(stm32g4)]
+    F,
+}
+
+impl Timer {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Fault input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Fault {
+    Fault1,
+    Fault2,
+    Fault3,
+    Fault4,
+    Fault5,
+    #[cfg(stm32g4)]
+    Fault6,
+}
+
+impl Fault {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Flag of the fault in ISR and ICR: FLT6 comes after SYSFLT.
+    fn flag(self) -> u32 {
+        match self.index() {
+            5 => 1 << 6,
+            i => 1 << i,
+        }
+    }
+}
+
+/// State of the outputs of a timing unit on a fault.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultState {
+    Active,
+    Inactive,
+    HighZ,
+}
+
+/// Fault input polarity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultPolarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// Fault input configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultConfig {
+    pub polarity: FaultPolarity,
+    /// Digital filter, from 0 (no filter) to 15, see the reference manual for the sampling
+    /// frequency and count of each value.
+    pub filter: u8,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            polarity: FaultPolarity::ActiveLow,
+            filter: 0,
+        }
+    }
+}
+
+/// PWM configuration of a timing unit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmConfig {
+    pub frequency: Hertz,
+    /// Dead time between the edges of output 1 and output 2, in ns. With 0, output 2 is the
+    /// exact complement of output 1.
+    pub dead_time_ns: u32,
+}
+
+/// Burst mode configuration.
+///
+/// The burst clock is the HRTIM clock divided by 2^`prescaler_power`. At the start of each
+/// burst period, the outputs of the timing units in burst mode are idle for `idle` burst clock
+/// ticks, the timing units keep on counting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BurstConfig {
+    /// From 0 to 15.
+    pub prescaler_power: u8,
+    /// Burst period, in burst clock ticks.
+    pub period: u16,
+    /// Idle duration, in burst clock ticks, shorter than the period.
+    pub idle: u16,
+    /// Repeat the burst periods until [`Hrtim::stop_burst()`], or run a single one.
+    pub continuous: bool,
+}
+
+/// An output pin of a timing unit.
+pub struct PwmPin<'d, T: Instance> {
+    pin: PeripheralRef<'d, AnyPin>,
+    af: u8,
+    timer: Timer,
+    output: usize,
+    _phantom: PhantomData<T>,
+}
+
+/// A fault input pin.
+pub struct FaultPin<'d, T: Instance> {
+    pin: PeripheralRef<'d, AnyPin>,
+    af: u8,
+    fault: Fault,
+    _phantom: PhantomData<T>,
+}
+
+macro_rules! output_pins {
+    ($($trait:ident, $fn:ident, $timer:ident, $output:literal;)*) => {
+        $(pin_trait!($trait, Instance);)*
+
+        impl<'d, T: Instance> PwmPin<'d, T> {
+            $(
+                pub fn $fn(pin: impl Peripheral<P = impl $trait<T>> + 'd) -> Self {
+                    into_ref!(pin);
+                    Self {
+                        af: pin.af_num(),
+                        pin: pin.map_into(),
+                        timer: Timer::$timer,
+                        output: $output,
+                        _phantom: PhantomData,
+                    }
+                }
+            )*
+        }
+    };
+}
+
+output_pins!(
+    ChA1Pin, cha1, A, 0;
+    ChA2Pin, cha2, A, 1;
+    ChB1Pin, chb1, B, 0;
+    ChB2Pin, chb2, B, 1;
+    ChC1Pin, chc1, C, 0;
+    ChC2Pin, chc2, C, 1;
+    ChD1Pin, chd1, D, 0;
+    ChD2Pin, chd2, D, 1;
+    ChE1Pin, che1, E, 0;
+    ChE2Pin, che2, E, 1;
+);
+
+#[cfg(stm32g4)]
+output_pins!(
+    ChF1Pin, chf1, F, 0;
+    ChF2Pin, chf2, F, 1;
+);
+
+macro_rules! fault_pins {
+    ($($trait:ident, $fn:ident, $fault:ident;)*) => {
+        $(pin_trait!($trait, Instance);)*
+
+        impl<'d, T: Instance> FaultPin<'d, T> {
+            $(
+                pub fn $fn(pin: impl Peripheral<P = impl $trait<T>> + 'd) -> Self {
+                    into_ref!(pin);
+                    Self {
+                        af: pin.af_num(),
+                        pin: pin.map_into(),
+                        fault: Fault::$fault,
+                        _phantom: PhantomData,
+                    }
+                }
+            )*
+        }
+    };
+}
+
+fault_pins!(
+    Flt1Pin, flt1, Fault1;
+    Flt2Pin, flt2, Fault2;
+    Flt3Pin, flt3, Fault3;
+    Flt4Pin, flt4, Fault4;
+    Flt5Pin, flt5, Fault5;
+);
+
+#[cfg(stm32g4)]
+fault_pins!(
+    Flt6Pin, flt6, Fault6;
+);
+
+/// HRTIM driver.
+pub struct Hrtim<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    /// Connected outputs 1 and 2 of each timing unit.
+    outputs: [[bool; 2]; TIMERS],
+}
+
+impl<'d, T: Instance> Hrtim<'d, T> {
+    /// Create an HRTIM driver, with the output pins of the timing units to use.
+    ///
+    /// The outputs stay disabled until [`Self::enable_outputs()`].
+    pub fn new(peri: impl Peripheral<P = T> + 'd, pins: impl IntoIterator<Item = PwmPin<'d, T>>) -> Self {
+        into_ref!(peri);
+
+        T::enable();
+        T::reset();
+
+        let mut outputs = [[false; 2]; TIMERS];
+        for pin in pins {
+            critical_section::with(|_| {
+                pin.pin.set_low();
+                pin.pin.set_as_af(pin.af, AFType::OutputPushPull);
+                pin.pin.set_speed(crate::gpio::Speed::VeryHigh);
+            });
+            outputs[pin.timer.index()][pin.output] = true;
+        }
+
+        // Calibrate the delay-locked loop of the high-resolution clock once, then periodically.
+        let r = T::regs();
+        r.dllcr().write(|w| w.set_cal(true));
+        while !r.isr().read().dllrdy() {}
+        r.dllcr().write(|w| w.set_calen(true));
+
+        Self { _peri: peri, outputs }
+    }
+
+    /// Configure a timing unit as a PWM generator, stopped, with the shortest duty cycle.
+    ///
+    /// The dead time can't be changed while the outputs of the timing unit are enabled.
+    pub fn configure_pwm(&mut self, timer: Timer, config: &PwmConfig) {
+        self.stop(timer);
+
+        let psc = self.set_frequency(timer, config.frequency);
+
+        let r = T::regs();
+        let tim = r.tim(timer.index());
+
+        // Output 1 is set on the period, and reset on the duty cycle.
+        tim.setr(0).write(|w| w.set_per(true));
+        tim.rstr(0).write(|w| w.set_cmp(0, true));
+
+        if config.dead_time_ns == 0 {
+            tim.setr(1).write(|w| w.set_cmp(0, true));
+            tim.rstr(1).write(|w| w.set_per(true));
+        } else {
+            // The dead time generator makes output 2 the complement of output 1, and ignores
+            // its set and reset events.
+            let fhrtim = clock_hz::<T>() as u64;
+            let ticks = |dtprsc: u8| config.dead_time_ns as u64 * fhrtim * 8 / (1_000_000_000 << dtprsc);
+            let dtprsc = unwrap!((0..=7).find(|dtprsc| ticks(*dtprsc) <= MAX_DEAD_TIME as u64));
+            let dt = ticks(dtprsc).max(1) as u16;
+            tim.dt().write(|w| {
+                w.set_dtprsc(dtprsc);
+                w.set_dtr(dt);
+                w.set_dtf(dt);
+            });
+        }
+        tim.outr().modify(|w| w.set_dten(config.dead_time_ns != 0));
+
+        tim.cmp(0).write(|w| w.set_cmp(min_ticks(psc)));
+        // Load the preloaded registers now, the next ones are loaded at the end of the period.
+        r.cr2().write(|w| w.set_swu(timer.index(), true));
+    }
+
+    /// Set the PWM frequency of a timing unit, and return the prescaler.
+    ///
+    /// Changing the prescaler of a running timing unit glitches its outputs, a small change of
+    /// frequency keeps the prescaler.
+    fn set_frequency(&mut self, timer: Timer, frequency: Hertz) -> u8 {
+        let fhrtim = clock_hz::<T>() as u64;
+        let period = |psc: u8| fhrtim * 32 / (frequency.0 as u64) >> psc;
+        let psc = unwrap!((0..=7).find(|psc| period(*psc) <= MAX_PERIOD as u64));
+        let period = period(psc) as u16;
+        assert!(period >= min_ticks(psc), "frequency too high");
+
+        trace!("HRTIM timer {} CKPSC={}, period={}", timer, psc, period);
+
+        let tim = T::regs().tim(timer.index());
+        tim.cr().modify(|w| {
+            w.set_ckpsc(psc);
+            w.set_cont(true);
+            w.set_preen(true);
+            w.set_trepu(true);
+        });
+        tim.per().write(|w| w.set_per(period));
+        psc
+    }
+
+    /// Change the PWM frequency of a timing unit configured with [`Self::configure_pwm()`].
+    ///
+    /// The duty cycle isn't scaled, it must be set again.
+    pub fn set_pwm_frequency(&mut self, timer: Timer, frequency: Hertz) {
+        self.set_frequency(timer, frequency);
+    }
+
+    /// The duty cycle of a timing unit for a 100% duty cycle, its period.
+    pub fn get_max_duty(&self, timer: Timer) -> u16 {
+        T::regs().tim(timer.index()).per().read().per()
+    }
+
+    /// Set the duty cycle of a timing unit, applied at the end of the current period.
+    ///
+    /// The duty cycle is clamped to the shortest pulse, a few ns: disable the outputs to keep
+    /// them inactive.
+    pub fn set_duty(&mut self, timer: Timer, duty: u16) {
+        assert!(duty <= self.get_max_duty(timer));
+        let tim = T::regs().tim(timer.index());
+        let psc = tim.cr().read().ckpsc();
+        tim.cmp(0).write(|w| w.set_cmp(duty.max(min_ticks(psc))));
+    }
+
+    /// Start the counter of a timing unit.
+    pub fn start(&mut self, timer: Timer) {
+        T::regs().mcr().modify(|w| w.set_tcen(timer.index(), true));
+    }
+
+    /// Stop the counter of a timing unit, keeping its outputs in their current state.
+    pub fn stop(&mut self, timer: Timer) {
+        T::regs().mcr().modify(|w| w.set_tcen(timer.index(), false));
+    }
+
+    /// Enable the connected outputs of a timing unit, also after a fault disabled them.
+    pub fn enable_outputs(&mut self, timer: Timer) {
+        let [out1, out2] = self.outputs[timer.index()];
+        T::regs().oenr().write(|w| {
+            w.set_t1oen(timer.index(), out1);
+            w.set_t2oen(timer.index(), out2);
+        });
+    }
+
+    /// Disable the outputs of a timing unit, they're set to their inactive state.
+    pub fn disable_outputs(&mut self, timer: Timer) {
+        T::regs().odisr().write(|w| {
+            w.set_t1odis(timer.index(), true);
+            w.set_t2odis(timer.index(), true);
+        });
+    }
+
+    /// Configure a fault input, on a pin.
+    pub fn configure_fault(&mut self, pin: FaultPin<'d, T>, config: FaultConfig) {
+        assert!(config.filter <= 15);
+        critical_section::with(|_| pin.pin.set_as_af(pin.af, AFType::Input));
+
+        let r = T::regs();
+        let polarity = config.polarity == FaultPolarity::ActiveHigh;
+        // The fault must be configured while it's disabled.
+        match pin.fault.index() {
+            n @ 0..=3 => {
+                r.fltinr1().modify(|w| {
+                    w.set_flte(n, false);
+                    w.set_fltp(n, polarity);
+                    w.set_fltf(n, config.filter);
+                });
+                r.fltinr1().modify(|w| w.set_flte(n, true));
+            }
+            n => {
+                let n = n - 4;
+                r.fltinr2().modify(|w| {
+                    w.set_flte(n, false);
+                    w.set_fltp(n, polarity);
+                    w.set_fltf(n, config.filter);
+                });
+                r.fltinr2().modify(|w| w.set_flte(n, true));
+            }
+        }
+    }
+
+    /// Protect a timing unit with fault inputs: when one of them is active, its outputs are
+    /// set to `state` and disabled.
+    pub fn set_fault_protection(&mut self, timer: Timer, faults: &[Fault], state: FaultState) {
+        let tim = T::regs().tim(timer.index());
+        tim.fltr().write(|w| {
+            for fault in faults {
+                w.set_flten(fault.index(), true);
+            }
+        });
+
+        let state = match state {
+            FaultState::Active => 0b01,
+            FaultState::Inactive => 0b10,
+            FaultState::HighZ => 0b11,
+        };
+        tim.outr().modify(|w| {
+            w.set_fault(0, state);
+            w.set_fault(1, state);
+        });
+    }
+
+    /// Whether a fault occurred since the last [`Self::clear_fault()`].
+    pub fn is_faulted(&self, fault: Fault) -> bool {
+        T::regs().isr().read().0 & ISR_FLT_MASK & fault.flag() != 0
+    }
+
+    /// Clear the flag of a fault. The outputs it disabled must be enabled again.
+    pub fn clear_fault(&mut self, fault: Fault) {
+        T::regs().icr().write_value(regs::Icr(fault.flag()));
+    }
+
+    /// Configure the burst mode, for the outputs of `timers`, and start it.
+    ///
+    /// The outputs are idle in their inactive state. A single burst starts now, continuous
+    /// bursts run until [`Self::stop_burst()`].
+    pub fn start_burst(&mut self, timers: &[Timer], config: &BurstConfig) {
+        assert!(config.prescaler_power <= 15);
+        assert!(0 < config.idle && config.idle < config.period);
+
+        self.stop_burst();
+
+        let r = T::regs();
+        let mut bmcr = regs::Bmcr(0);
+        bmcr.set_bmclk(BMCLK_PRESCALED);
+        bmcr.set_bmprsc(config.prescaler_power);
+        bmcr.set_bmpren(true);
+        bmcr.set_bmom(config.continuous);
+        for timer in timers {
+            bmcr.set_tbm(timer.index(), true);
+            r.tim(timer.index()).outr().modify(|w| {
+                w.set_idlem(0, true);
+                w.set_idlem(1, true);
+            });
+        }
+
+        r.bmper().write(|w| w.set_bmper(config.period - 1));
+        r.bmcmpr().write(|w| w.set_bmcmp(config.idle - 1));
+        r.bmcr().write_value(bmcr);
+        bmcr.set_bme(true);
+        r.bmcr().write_value(bmcr);
+        r.bmtrgr().write(|w| w.set_sw(true));
+    }
+
+    /// Stop the burst mode at once, the outputs run again.
+    pub fn stop_burst(&mut self) {
+        let r = T::regs();
+        r.bmcr().modify(|w| w.set_bme(false));
+        for index in 0..TIMERS {
+            r.tim(index).outr().modify(|w| {
+                w.set_idlem(0, false);
+                w.set_idlem(1, false);
+            });
+        }
+    }
+
+    /// Whether the burst mode is in its idle phase.
+    pub fn is_burst_idle(&self) -> bool {
+        T::regs().bmcr().read().bmstat()
+    }
+}
+
+impl<'d, T: Instance> Drop for Hrtim<'d, T> {
+    fn drop(&mut self) {
+        T::regs().odisr().write(|w| {
+            for (index, [out1, out2]) in self.outputs.iter().enumerate() {
+                w.set_t1odis(index, *out1);
+                w.set_t2odis(index, *out2);
+            }
+        });
+        T::disable();
+    }
+}
+
+/// Frequency of the HRTIM clock.
+fn clock_hz<T: Instance>() -> u32 {
+    #[cfg(stm32f3)]
+    if let Some(hrtim) = unsafe { crate::rcc::get_freqs() }.hrtim {
+        return hrtim.0;
+    }
+    T::frequency().0
+}
+
+/// The shortest period and compare value, in ticks, for the prescaler `psc`.
+fn min_ticks(psc: u8) -> u16 {
+    match psc {
+        0..=5 => 0x60 >> psc,
+        _ => 3,
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::hrtim::Hrtim;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {}
+
+foreach_peripheral!(
+    (hrtim, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::hrtim::Hrtim {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+);
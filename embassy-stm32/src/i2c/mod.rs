@@ -7,6 +7,11 @@ use crate::interrupt;
 mod _version;
 pub use _version::*;
 
+#[cfg(i2c_v2)]
+mod slave;
+#[cfg(i2c_v2)]
+pub use slave::*;
+
 #[cfg(feature = "time")]
 mod timeout;
 #[cfg(feature = "time")]
@@ -0,0 +1,349 @@
+//! I2C slave mode
+//!
+//! The peripheral answers to its own address, so that an STM32 can expose registers to a host
+//! on the bus. A transaction starts with [`I2cSlave::listen()`], which returns what the master
+//! asked for, and is answered with [`I2cSlave::respond_to_write()`] or
+//! [`I2cSlave::respond_to_read()`].
+//!
+//! With clock stretching, which is enabled by default, the peripheral holds SCL low until the
+//! task answers, so the master waits as long as needed.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use super::_version::Timings;
+use crate::gpio::sealed::AFType;
+use crate::gpio::Pull;
+use crate::i2c::{Error, Instance, InterruptHandler, SclPin, SdaPin};
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::{interrupt, Peripheral};
+
+// CR1 bits
+const CR1_PE: u32 = 1 << 0;
+const CR1_TXIE: u32 = 1 << 1;
+const CR1_RXIE: u32 = 1 << 2;
+const CR1_ADDRIE: u32 = 1 << 3;
+const CR1_NACKIE: u32 = 1 << 4;
+const CR1_STOPIE: u32 = 1 << 5;
+const CR1_NOSTRETCH: u32 = 1 << 17;
+const CR1_GCEN: u32 = 1 << 19;
+const CR1_SLAVE_IE: u32 = CR1_TXIE | CR1_RXIE | CR1_ADDRIE | CR1_NACKIE | CR1_STOPIE;
+
+// CR2 bits
+const CR2_NACK: u32 = 1 << 15;
+
+// OAR1 and OAR2 bits
+const OAR1_OA1MODE: u32 = 1 << 10;
+const OAR_EN: u32 = 1 << 15;
+
+// ISR and ICR bits
+const ISR_TXE: u32 = 1 << 0;
+const ISR_TXIS: u32 = 1 << 1;
+const ISR_RXNE: u32 = 1 << 2;
+const ISR_ADDR: u32 = 1 << 3;
+const ISR_NACKF: u32 = 1 << 4;
+const ISR_STOPF: u32 = 1 << 5;
+const ISR_BERR: u32 = 1 << 8;
+const ISR_ARLO: u32 = 1 << 9;
+const ISR_OVR: u32 = 1 << 10;
+const ISR_DIR: u32 = 1 << 16;
+const ISR_ADDCODE_POS: u32 = 17;
+
+/// Called by the [`InterruptHandler`]: the slave events are handled by the task, so mask them
+/// until it has.
+pub(super) fn on_interrupt<T: Instance>() {
+    let regs = T::regs();
+    if regs.cr1().read().0 & CR1_SLAVE_IE != 0 {
+        critical_section::with(|_| regs.cr1().modify(|w| w.0 &= !CR1_SLAVE_IE));
+        T::state().waker.wake();
+    }
+}
+
+/// Own address of the slave.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+/// I2C slave configuration
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct SlaveConfig {
+    pub address: Address,
+    /// Second 7-bit address, which the slave also answers to.
+    pub address2: Option<u8>,
+    /// Answer to the general call address, 0.
+    pub general_call: bool,
+    /// Hold SCL low until the task handles the events. Without it, the task must keep up with
+    /// the bus, or the transfers see overruns and underruns.
+    pub clock_stretching: bool,
+    pub sda_pullup: bool,
+    pub scl_pullup: bool,
+}
+
+impl Default for SlaveConfig {
+    fn default() -> Self {
+        Self {
+            address: Address::SevenBit(0x42),
+            address2: None,
+            general_call: false,
+            clock_stretching: true,
+            sda_pullup: false,
+            scl_pullup: false,
+        }
+    }
+}
+
+/// Direction of a transaction, seen from the master.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveCommandKind {
+    /// The master writes, answer with [`I2cSlave::respond_to_write()`].
+    Write,
+    /// The master reads, answer with [`I2cSlave::respond_to_read()`].
+    Read,
+}
+
+/// Transaction started by the master.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlaveCommand {
+    pub kind: SlaveCommandKind,
+    /// The 7-bit address that matched, or the upper bits of a 10-bit address.
+    pub address: u8,
+}
+
+/// I2C slave driver.
+pub struct I2cSlave<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> I2cSlave<'d, T> {
+    /// Create a new I2C slave.
+    ///
+    /// `freq` is the highest frequency of the bus, which sets the data setup and hold times.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+        config: SlaveConfig,
+    ) -> Self {
+        into_ref!(peri, scl, sda);
+
+        T::enable();
+        T::reset();
+
+        scl.set_as_af_pull(
+            scl.af_num(),
+            AFType::OutputOpenDrain,
+            match config.scl_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+        );
+        sda.set_as_af_pull(
+            sda.af_num(),
+            AFType::OutputOpenDrain,
+            match config.sda_pullup {
+                true => Pull::Up,
+                false => Pull::None,
+            },
+        );
+
+        let regs = T::regs();
+        regs.cr1().modify(|w| w.0 &= !CR1_PE);
+
+        let timings = Timings::new(T::frequency(), freq);
+        regs.timingr().write(|reg| {
+            reg.set_presc(timings.prescale);
+            reg.set_scll(timings.scll);
+            reg.set_sclh(timings.sclh);
+            reg.set_sdadel(timings.sdadel);
+            reg.set_scldel(timings.scldel);
+        });
+
+        // The address can only be changed while it's disabled.
+        regs.oar1().write(|w| w.0 = 0);
+        regs.oar1().write(|w| {
+            w.0 = match config.address {
+                Address::SevenBit(addr) => (addr as u32) << 1,
+                Address::TenBit(addr) => (addr as u32 & 0x3FF) | OAR1_OA1MODE,
+            } | OAR_EN
+        });
+        regs.oar2().write(|w| w.0 = 0);
+        if let Some(addr) = config.address2 {
+            regs.oar2().write(|w| w.0 = ((addr as u32) << 1) | OAR_EN);
+        }
+
+        regs.cr1().modify(|w| {
+            w.0 &= !(CR1_NOSTRETCH | CR1_GCEN);
+            if !config.clock_stretching {
+                w.0 |= CR1_NOSTRETCH;
+            }
+            if config.general_call {
+                w.0 |= CR1_GCEN;
+            }
+            w.0 |= CR1_PE;
+        });
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Wait for the master to address the slave, and return what it asked for.
+    ///
+    /// The transaction must then be answered with [`respond_to_write()`](Self::respond_to_write)
+    /// or [`respond_to_read()`](Self::respond_to_read), depending on its kind.
+    pub async fn listen(&mut self) -> Result<SlaveCommand, Error> {
+        self.wait(CR1_ADDRIE, |isr| {
+            if isr & ISR_ADDR != 0 {
+                let kind = match isr & ISR_DIR != 0 {
+                    true => SlaveCommandKind::Read,
+                    false => SlaveCommandKind::Write,
+                };
+                let address = ((isr >> ISR_ADDCODE_POS) & 0x7F) as u8;
+                Poll::Ready(Ok(SlaveCommand { kind, address }))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Receive the bytes written by the master into `buffer`, and return their number.
+    ///
+    /// This returns at the end of the write, when the master stops the transaction or restarts
+    /// it, for example to read a register after writing its address. The bytes that don't fit in
+    /// `buffer` are not acknowledged.
+    pub async fn respond_to_write(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let regs = T::regs();
+        let mut len = 0;
+
+        let _on_drop = OnDrop::new(|| {
+            regs.cr1().modify(|w| w.0 &= !CR1_SLAVE_IE);
+        });
+
+        regs.cr2().modify(|w| w.0 &= !CR2_NACK);
+        if buffer.is_empty() {
+            regs.cr2().modify(|w| w.0 |= CR2_NACK);
+        }
+        regs.icr().write(|w| w.0 = ISR_ADDR);
+
+        self.wait(CR1_RXIE | CR1_STOPIE | CR1_ADDRIE, |isr| {
+            if isr & ISR_RXNE != 0 {
+                let byte = regs.rxdr().read().0 as u8;
+                if len < buffer.len() {
+                    buffer[len] = byte;
+                    len += 1;
+                }
+                if len == buffer.len() {
+                    regs.cr2().modify(|w| w.0 |= CR2_NACK);
+                }
+                Poll::Pending
+            } else if isr & ISR_STOPF != 0 {
+                regs.icr().write(|w| w.0 = ISR_STOPF);
+                Poll::Ready(Ok(len))
+            } else if isr & ISR_ADDR != 0 {
+                // Repeated start, handled by the next `listen()`.
+                Poll::Ready(Ok(len))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Send the bytes of `buffer` to the master, and return the number of bytes it read.
+    ///
+    /// If the master reads more bytes than `buffer` holds, it gets `0xFF`.
+    pub async fn respond_to_read(&mut self, buffer: &[u8]) -> Result<usize, Error> {
+        let regs = T::regs();
+        let mut len = 0;
+
+        let _on_drop = OnDrop::new(|| {
+            regs.cr1().modify(|w| w.0 &= !CR1_SLAVE_IE);
+        });
+
+        // Flush the byte left over from the previous transaction.
+        regs.isr().write(|w| w.0 = ISR_TXE);
+        regs.icr().write(|w| w.0 = ISR_ADDR);
+
+        self.wait(CR1_TXIE | CR1_NACKIE | CR1_STOPIE | CR1_ADDRIE, |isr| {
+            if isr & ISR_TXIS != 0 {
+                let byte = buffer.get(len).copied().unwrap_or(0xFF);
+                regs.txdr().write(|w| w.0 = byte as u32);
+                len += 1;
+                Poll::Pending
+            } else if isr & ISR_NACKF != 0 {
+                // The master doesn't want more bytes, the last one wasn't sent.
+                regs.icr().write(|w| w.0 = ISR_NACKF);
+                len = len.saturating_sub(1);
+                Poll::Pending
+            } else if isr & ISR_STOPF != 0 {
+                regs.icr().write(|w| w.0 = ISR_STOPF);
+                regs.isr().write(|w| w.0 = ISR_TXE);
+                Poll::Ready(Ok(len.min(buffer.len())))
+            } else if isr & ISR_ADDR != 0 {
+                Poll::Ready(Ok(len.min(buffer.len())))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Wait until `f` is ready, calling it with the ISR after the events of `ie`.
+    async fn wait<R>(&mut self, ie: u32, mut f: impl FnMut(u32) -> Poll<Result<R, Error>>) -> Result<R, Error> {
+        let regs = T::regs();
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            let isr = regs.isr().read().0;
+            if let Err(e) = check_errors::<T>(isr) {
+                return Poll::Ready(Err(e));
+            }
+
+            let res = f(isr);
+            if res.is_pending() {
+                critical_section::with(|_| regs.cr1().modify(|w| w.0 |= ie));
+            }
+            res
+        })
+        .await
+    }
+}
+
+impl<'d, T: Instance> Drop for I2cSlave<'d, T> {
+    fn drop(&mut self) {
+        T::regs().cr1().modify(|w| w.0 &= !(CR1_PE | CR1_SLAVE_IE));
+        T::disable();
+    }
+}
+
+/// Return and clear the bus errors.
+fn check_errors<T: Instance>(isr: u32) -> Result<(), Error> {
+    let regs = T::regs();
+    if isr & ISR_BERR != 0 {
+        regs.icr().write(|w| w.0 = ISR_BERR);
+        Err(Error::Bus)
+    } else if isr & ISR_ARLO != 0 {
+        regs.icr().write(|w| w.0 = ISR_ARLO);
+        Err(Error::Arbitration)
+    } else if isr & ISR_OVR != 0 {
+        regs.icr().write(|w| w.0 = ISR_OVR);
+        Err(Error::Overrun)
+    } else {
+        Ok(())
+    }
+}
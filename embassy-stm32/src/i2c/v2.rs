@@ -35,6 +35,8 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
         critical_section::with(|_| {
             regs.cr1().modify(|w| w.set_tcie(false));
         });
+
+        super::slave::on_interrupt::<T>();
     }
 }
 
@@ -815,16 +817,16 @@ impl Stop {
     }
 }
 
-struct Timings {
-    prescale: u8,
-    scll: u8,
-    sclh: u8,
-    sdadel: u8,
-    scldel: u8,
+pub(super) struct Timings {
+    pub(super) prescale: u8,
+    pub(super) scll: u8,
+    pub(super) sclh: u8,
+    pub(super) sdadel: u8,
+    pub(super) scldel: u8,
 }
 
 impl Timings {
-    fn new(i2cclk: Hertz, freq: Hertz) -> Self {
+    pub(super) fn new(i2cclk: Hertz, freq: Hertz) -> Self {
         let i2cclk = i2cclk.0;
         let freq = freq.0;
         // Refer to RM0433 Rev 7 Figure 539 for setup and hold timing:
@@ -997,8 +999,18 @@ mod eha {
 
 impl<'d, T: Instance> SetConfig for I2c<'d, T> {
     type Config = Hertz;
-    fn set_config(&mut self, config: &Self::Config) {
-        let timings = Timings::new(T::frequency(), *config);
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        // The timings need a clock at least 4 times the bus frequency, and of 17 MHz for fast
+        // mode plus.
+        let i2cclk = T::frequency();
+        if config.0 == 0 || i2cclk.0 / config.0 < 4 || (config.0 > 400_000 && i2cclk.0 < 17_000_000) {
+            return Err(());
+        }
+
+        let timings = Timings::new(i2cclk, *config);
+        // The timings only change while the peripheral is disabled.
+        T::regs().cr1().modify(|reg| reg.set_pe(false));
         T::regs().timingr().write(|reg| {
             reg.set_presc(timings.prescale);
             reg.set_scll(timings.scll);
@@ -1006,5 +1018,8 @@ impl<'d, T: Instance> SetConfig for I2c<'d, T> {
             reg.set_sdadel(timings.sdadel);
             reg.set_scldel(timings.scldel);
         });
+        T::regs().cr1().modify(|reg| reg.set_pe(true));
+
+        Ok(())
     }
 }
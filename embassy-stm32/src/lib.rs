@@ -19,20 +19,30 @@ pub mod timer;
 
 // Sometimes-present hardware
 
+#[cfg(aes)]
+pub mod aes;
 #[cfg(adc)]
 pub mod adc;
 #[cfg(can)]
 pub mod can;
+#[cfg(all(comp, any(stm32g4, stm32l4)))]
+pub mod comp;
 #[cfg(dac)]
 pub mod dac;
 #[cfg(dcmi)]
 pub mod dcmi;
+#[cfg(dma2d)]
+pub mod dma2d;
 #[cfg(eth)]
 pub mod eth;
 #[cfg(feature = "exti")]
 pub mod exti;
 #[cfg(fmc)]
 pub mod fmc;
+#[cfg(hash)]
+pub mod hash;
+#[cfg(all(hrtim, any(stm32f3, stm32g4)))]
+pub mod hrtim;
 #[cfg(i2c)]
 pub mod i2c;
 
@@ -43,6 +53,16 @@ pub mod flash;
 pub mod i2s;
 #[cfg(stm32wb)]
 pub mod ipcc;
+#[cfg(all(feature = "low-power", stm32l4))]
+pub mod low_power;
+#[cfg(ltdc)]
+pub mod ltdc;
+#[cfg(opamp)]
+pub mod opamp;
+#[cfg(octospi)]
+pub mod ospi;
+#[cfg(all(pka, any(stm32wb, stm32wl, stm32l5)))]
+pub mod pka;
 pub mod pwm;
 #[cfg(quadspi)]
 pub mod qspi;
@@ -50,17 +70,25 @@ pub mod qspi;
 pub mod rng;
 #[cfg(all(rtc, not(rtc_v1)))]
 pub mod rtc;
+#[cfg(all(sai, not(gpdma)))]
+pub mod sai;
 #[cfg(sdmmc)]
 pub mod sdmmc;
 #[cfg(spi)]
 pub mod spi;
+#[cfg(stm32wl)]
+pub mod subghz;
+#[cfg(tsc)]
+pub mod tsc;
+#[cfg(ucpd)]
+pub mod ucpd;
 #[cfg(usart)]
 pub mod usart;
 #[cfg(usb)]
 pub mod usb;
 #[cfg(otg)]
 pub mod usb_otg;
-#[cfg(iwdg)]
+#[cfg(any(iwdg, wwdg))]
 pub mod wdg;
 
 // This must go last, so that it sees all the impl_foo! macros defined earlier.
@@ -0,0 +1,253 @@
+//! Low-power executor and Standby mode.
+//!
+//! The timer of the time driver isn't clocked in Stop mode, so the [`Executor`] of this module
+//! keeps time with the RTC: when all the tasks are idle, it programs the RTC wakeup timer for
+//! the next timer of the time driver, enters Stop mode, and moves the time driver forward by the
+//! time spent in Stop mode, measured by the RTC calendar, when it wakes up.
+//!
+//! In Stop mode, the high-speed clocks are off, so only the peripherals clocked by LSE, LSI or
+//! HSI16 in Stop mode, and the EXTI lines, can wake the MCU up. The drivers that need the
+//! high-speed clocks, for example during a DMA transfer, must hold a [`StopBlocker`] so that
+//! the executor only sleeps. The state of peripherals that isn't retained in Stop 2 mode can be
+//! saved and restored by the hooks set with [`Executor::set_stop_hooks()`].
+//!
+//! [`enter_standby()`] enters Standby mode, where the RAM is lost and the MCU wakes up with a
+//! reset. The RTC and its backup registers keep running, for the applications that spend most
+//! of their time in Standby mode and only run for a short measurement.
+
+use core::marker::PhantomData;
+
+use atomic_polyfill::{AtomicBool, AtomicU32, Ordering};
+use embassy_executor::raw::{self, Pender};
+use embassy_executor::Spawner;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::pwr::vals::Lpms;
+use crate::pac::{PWR, RCC};
+use crate::peripherals::RTC;
+use crate::rtc::{Rtc, WakeupInterruptHandler};
+use crate::{interrupt, time_driver};
+
+/// Shortest time for which Stop mode is entered, in ticks of the time driver. Shorter idle
+/// periods only sleep, the wakeup from Stop mode and the restart of the clocks taking time too.
+const MIN_STOP_TICKS: u64 = embassy_time::TICK_HZ / 500;
+
+static STOP_BLOCKERS: AtomicU32 = AtomicU32::new(0);
+
+/// Set by the pender, when a task is woken up after the executor polled.
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Stop mode entered by the executor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StopMode {
+    /// Stop 1 mode, with the main regulator in low-power mode and all the peripherals retained.
+    Stop1,
+    /// Stop 2 mode, the lowest-power mode retaining the RAM, where some peripherals lose their
+    /// state, as listed in the reference manual.
+    Stop2,
+}
+
+impl StopMode {
+    fn lpms(self) -> Lpms {
+        match self {
+            StopMode::Stop1 => Lpms::STOP1,
+            StopMode::Stop2 => Lpms::STOP2,
+        }
+    }
+}
+
+/// Prevents the executor from entering Stop mode while it's alive.
+pub struct StopBlocker {
+    _private: (),
+}
+
+impl Drop for StopBlocker {
+    fn drop(&mut self) {
+        STOP_BLOCKERS.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Prevent the executor from entering Stop mode until the returned [`StopBlocker`] is dropped.
+pub fn block_stop() -> StopBlocker {
+    STOP_BLOCKERS.fetch_add(1, Ordering::Acquire);
+    StopBlocker { _private: () }
+}
+
+/// Thread-mode executor entering Stop mode when idle.
+///
+/// Without an RTC, set with [`set_rtc()`](Executor::set_rtc), the executor only sleeps like the
+/// thread-mode executor of `embassy-executor`.
+pub struct Executor {
+    inner: raw::Executor,
+    rtc: Option<Rtc<'static, RTC>>,
+    stop_mode: StopMode,
+    before_stop: Option<fn()>,
+    after_stop: Option<fn()>,
+    not_send: PhantomData<*mut ()>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            inner: raw::Executor::new(Pender::new_from_callback(pend, core::ptr::null_mut())),
+            rtc: None,
+            stop_mode: StopMode::Stop2,
+            before_stop: None,
+            after_stop: None,
+            not_send: PhantomData,
+        }
+    }
+
+    /// Keep time with `rtc` in Stop mode, and wake the MCU up with its wakeup timer.
+    ///
+    /// The RTC must be clocked by LSE or LSI, and its wakeup timer is used by the executor.
+    pub fn set_rtc(
+        &mut self,
+        rtc: Rtc<'static, RTC>,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RTC_WKUP, WakeupInterruptHandler>,
+    ) {
+        interrupt::typelevel::RTC_WKUP::unpend();
+        unsafe { interrupt::typelevel::RTC_WKUP::enable() };
+
+        self.rtc = Some(rtc);
+    }
+
+    /// Set the Stop mode entered when idle, Stop 2 by default.
+    pub fn set_stop_mode(&mut self, stop_mode: StopMode) {
+        self.stop_mode = stop_mode;
+    }
+
+    /// Set functions called with interrupts disabled before entering Stop mode, and after
+    /// leaving it, to save and restore the state of the peripherals that isn't retained.
+    pub fn set_stop_hooks(&mut self, before_stop: fn(), after_stop: fn()) {
+        self.before_stop = Some(before_stop);
+        self.after_stop = Some(after_stop);
+    }
+
+    /// Run the executor.
+    ///
+    /// The `init` closure is called with a [`Spawner`] that spawns tasks on this executor. Use it
+    /// to spawn the initial task(s). After `init` returns, the executor starts running the tasks.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        init(self.inner.spawner());
+
+        loop {
+            PENDING.store(false, Ordering::Relaxed);
+            unsafe { self.inner.poll() };
+            self.idle();
+        }
+    }
+
+    fn idle(&mut self) {
+        let ticks = time_driver::time_until_next_alarm().unwrap_or(u64::MAX);
+
+        let rtc = match &mut self.rtc {
+            Some(rtc) if ticks >= MIN_STOP_TICKS && STOP_BLOCKERS.load(Ordering::Acquire) == 0 => rtc,
+            _ => {
+                cortex_m::asm::wfe();
+                return;
+            }
+        };
+
+        // The interrupts that wake the MCU up are handled once the time and the clocks are
+        // restored.
+        critical_section::with(|_| {
+            // A task woken up since the poll must run first.
+            if PENDING.load(Ordering::Relaxed) {
+                return;
+            }
+
+            if let Some(before_stop) = self.before_stop {
+                before_stop();
+            }
+
+            time_driver::pause_time();
+            let start = rtc.time_of_day_ticks();
+            rtc.start_wakeup_alarm(ticks);
+
+            enter_stop(self.stop_mode);
+
+            rtc.stop_wakeup_alarm();
+            let end = rtc.time_of_day_ticks();
+            const DAY_TICKS: u64 = 24 * 60 * 60 * embassy_time::TICK_HZ;
+            time_driver::resume_time((end + DAY_TICKS - start) % DAY_TICKS);
+
+            if let Some(after_stop) = self.after_stop {
+                after_stop();
+            }
+        });
+    }
+}
+
+fn pend(_context: *mut ()) {
+    PENDING.store(true, Ordering::Relaxed);
+    cortex_m::asm::sev();
+}
+
+/// Enter Stop mode until an interrupt is pending, and restart the clocks.
+fn enter_stop(stop_mode: StopMode) {
+    // The MCU wakes up clocked by MSI or HSI16, and the PLL is stopped.
+    let cr = RCC.cr().read();
+    let sw = RCC.cfgr().read().sw();
+
+    PWR.cr1().modify(|w| w.set_lpms(stop_mode.lpms()));
+
+    let mut scb = unsafe { cortex_m::Peripherals::steal() }.SCB;
+    scb.set_sleepdeep();
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+    scb.clear_sleepdeep();
+
+    if cr.hseon() {
+        RCC.cr().modify(|w| w.set_hseon(true));
+        while !RCC.cr().read().hserdy() {}
+    }
+    if cr.hsion() {
+        RCC.cr().modify(|w| w.set_hsion(true));
+        while !RCC.cr().read().hsirdy() {}
+    }
+    if cr.pllon() {
+        RCC.cr().modify(|w| w.set_pllon(true));
+        while !RCC.cr().read().pllrdy() {}
+    }
+
+    RCC.cfgr().modify(|w| w.set_sw(sw));
+    while RCC.cfgr().read().sws().to_bits() != sw.to_bits() {}
+}
+
+/// Enter Standby mode, and wake up with a reset after `wakeup_secs` seconds, between 1 and
+/// 65536, or on a wakeup pin if `None`.
+///
+/// The RAM and the state of the peripherals are lost, except for the RTC and its backup
+/// registers, which can hold the state of the application.
+pub fn enter_standby(rtc: &mut Rtc<'_, RTC>, wakeup_secs: Option<u32>) -> ! {
+    match wakeup_secs {
+        Some(secs) => rtc.start_wakeup_alarm_secs(secs),
+        None => rtc.stop_wakeup_alarm(),
+    }
+
+    // A pending wakeup flag would wake the MCU up immediately.
+    PWR.scr().write(|w| {
+        w.set_csbf(true);
+        for n in 0..5 {
+            w.set_cwuf(n, true);
+        }
+    });
+    PWR.cr1().modify(|w| w.set_lpms(Lpms::STANDBY));
+
+    let mut scb = unsafe { cortex_m::Peripherals::steal() }.SCB;
+    scb.set_sleepdeep();
+
+    loop {
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Check whether the MCU was reset by a wakeup from Standby mode, and clear the flag.
+pub fn woke_from_standby() -> bool {
+    let standby = PWR.sr1().read().sbf();
+    PWR.scr().write(|w| w.set_csbf(true));
+    standby
+}
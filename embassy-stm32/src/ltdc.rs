@@ -0,0 +1,571 @@
+//! LCD-TFT Display Controller (LTDC)
+//!
+//! The LTDC drives RGB panels, reading the pixels of up to 2 layers from framebuffers in memory,
+//! usually in external SDRAM set up with [`fmc`](crate::fmc).
+//!
+//! The pixel clock isn't configured by this driver: on H7 it's `pll3_r_ck`, set with
+//! [`rcc::Config::pll3`](crate::rcc::Config).
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::gpio::sealed::AFType;
+use crate::gpio::Speed;
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, Peripheral};
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = T::regs();
+        let isr = regs.isr().read();
+
+        if isr.lif() {
+            regs.ier().modify(|w| w.set_lie(false));
+        }
+        if isr.rrif() {
+            regs.ier().modify(|w| w.set_rrie(false));
+        }
+        STATE.waker.wake();
+    }
+}
+
+struct State {
+    waker: AtomicWaker,
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+static STATE: State = State::new();
+
+/// Polarity of a synchronization signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Polarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// Edge of the pixel clock on which the panel samples the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PixelClockPolarity {
+    RisingEdge,
+    FallingEdge,
+}
+
+/// Panel timings and signal polarities.
+///
+/// Horizontal values are in pixel clocks, vertical values in lines. The defaults are for the
+/// common 480x272 panels.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub active_width: u16,
+    pub active_height: u16,
+    pub h_sync: u16,
+    pub h_back_porch: u16,
+    pub h_front_porch: u16,
+    pub v_sync: u16,
+    pub v_back_porch: u16,
+    pub v_front_porch: u16,
+    pub h_sync_polarity: Polarity,
+    pub v_sync_polarity: Polarity,
+    pub data_enable_polarity: Polarity,
+    pub pixel_clock_polarity: PixelClockPolarity,
+    /// Color shown where no layer is, as 0xRRGGBB.
+    pub background_color: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            active_width: 480,
+            active_height: 272,
+            h_sync: 41,
+            h_back_porch: 13,
+            h_front_porch: 32,
+            v_sync: 10,
+            v_back_porch: 2,
+            v_front_porch: 2,
+            h_sync_polarity: Polarity::ActiveLow,
+            v_sync_polarity: Polarity::ActiveLow,
+            data_enable_polarity: Polarity::ActiveLow,
+            pixel_clock_polarity: PixelClockPolarity::RisingEdge,
+            background_color: 0x000000,
+        }
+    }
+}
+
+/// Layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Layer {
+    /// Bottom layer
+    Layer1,
+    /// Top layer, blended over layer 1
+    Layer2,
+}
+
+impl Layer {
+    fn index(&self) -> usize {
+        match self {
+            Layer::Layer1 => 0,
+            Layer::Layer2 => 1,
+        }
+    }
+}
+
+/// Pixel format of a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PixelFormat {
+    Argb8888 = 0,
+    Rgb888 = 1,
+    Rgb565 = 2,
+    Argb1555 = 3,
+    Argb4444 = 4,
+    L8 = 5,
+    Al44 = 6,
+    Al88 = 7,
+}
+
+impl PixelFormat {
+    /// Size of a pixel in bytes.
+    pub fn bytes_per_pixel(&self) -> u16 {
+        match self {
+            PixelFormat::Argb8888 => 4,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgb565 | PixelFormat::Argb1555 | PixelFormat::Argb4444 | PixelFormat::Al88 => 2,
+            PixelFormat::L8 | PixelFormat::Al44 => 1,
+        }
+    }
+
+    fn has_alpha(&self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Argb8888
+                | PixelFormat::Argb1555
+                | PixelFormat::Argb4444
+                | PixelFormat::Al44
+                | PixelFormat::Al88
+        )
+    }
+}
+
+/// Layer configuration
+///
+/// The window is the area of the panel covered by the layer, in pixels. The framebuffer has
+/// the same size as the window.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LayerConfig {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub pixel_format: PixelFormat,
+    /// Constant alpha, multiplied with the alpha of the pixels when the format has one.
+    pub alpha: u8,
+}
+
+macro_rules! config_pins {
+    ($($pin:ident),*) => {
+        into_ref!($($pin),*);
+        critical_section::with(|_| {
+            $(
+                $pin.set_as_af($pin.af_num(), AFType::OutputPushPull);
+                $pin.set_speed(Speed::VeryHigh);
+            )*
+        })
+    };
+}
+
+/// LTDC driver.
+pub struct Ltdc<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    config: Config,
+}
+
+impl<'d, T: Instance> Ltdc<'d, T> {
+    /// Create a new LTDC driver for a panel with a 16-bit interface, connected to the 5, 6 and
+    /// 5 most significant bits of red, green and blue.
+    pub fn new_rgb565(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        clk: impl Peripheral<P = impl ClkPin<T>> + 'd,
+        hsync: impl Peripheral<P = impl HsyncPin<T>> + 'd,
+        vsync: impl Peripheral<P = impl VsyncPin<T>> + 'd,
+        de: impl Peripheral<P = impl DePin<T>> + 'd,
+        r3: impl Peripheral<P = impl R3Pin<T>> + 'd,
+        r4: impl Peripheral<P = impl R4Pin<T>> + 'd,
+        r5: impl Peripheral<P = impl R5Pin<T>> + 'd,
+        r6: impl Peripheral<P = impl R6Pin<T>> + 'd,
+        r7: impl Peripheral<P = impl R7Pin<T>> + 'd,
+        g2: impl Peripheral<P = impl G2Pin<T>> + 'd,
+        g3: impl Peripheral<P = impl G3Pin<T>> + 'd,
+        g4: impl Peripheral<P = impl G4Pin<T>> + 'd,
+        g5: impl Peripheral<P = impl G5Pin<T>> + 'd,
+        g6: impl Peripheral<P = impl G6Pin<T>> + 'd,
+        g7: impl Peripheral<P = impl G7Pin<T>> + 'd,
+        b3: impl Peripheral<P = impl B3Pin<T>> + 'd,
+        b4: impl Peripheral<P = impl B4Pin<T>> + 'd,
+        b5: impl Peripheral<P = impl B5Pin<T>> + 'd,
+        b6: impl Peripheral<P = impl B6Pin<T>> + 'd,
+        b7: impl Peripheral<P = impl B7Pin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri);
+        config_pins!(clk, hsync, vsync, de);
+        config_pins!(r3, r4, r5, r6, r7, g2, g3, g4, g5, g6, g7, b3, b4, b5, b6, b7);
+
+        Self::new_inner(peri, config)
+    }
+
+    /// Create a new LTDC driver for a panel with a 24-bit interface.
+    pub fn new_rgb888(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        clk: impl Peripheral<P = impl ClkPin<T>> + 'd,
+        hsync: impl Peripheral<P = impl HsyncPin<T>> + 'd,
+        vsync: impl Peripheral<P = impl VsyncPin<T>> + 'd,
+        de: impl Peripheral<P = impl DePin<T>> + 'd,
+        r0: impl Peripheral<P = impl R0Pin<T>> + 'd,
+        r1: impl Peripheral<P = impl R1Pin<T>> + 'd,
+        r2: impl Peripheral<P = impl R2Pin<T>> + 'd,
+        r3: impl Peripheral<P = impl R3Pin<T>> + 'd,
+        r4: impl Peripheral<P = impl R4Pin<T>> + 'd,
+        r5: impl Peripheral<P = impl R5Pin<T>> + 'd,
+        r6: impl Peripheral<P = impl R6Pin<T>> + 'd,
+        r7: impl Peripheral<P = impl R7Pin<T>> + 'd,
+        g0: impl Peripheral<P = impl G0Pin<T>> + 'd,
+        g1: impl Peripheral<P = impl G1Pin<T>> + 'd,
+        g2: impl Peripheral<P = impl G2Pin<T>> + 'd,
+        g3: impl Peripheral<P = impl G3Pin<T>> + 'd,
+        g4: impl Peripheral<P = impl G4Pin<T>> + 'd,
+        g5: impl Peripheral<P = impl G5Pin<T>> + 'd,
+        g6: impl Peripheral<P = impl G6Pin<T>> + 'd,
+        g7: impl Peripheral<P = impl G7Pin<T>> + 'd,
+        b0: impl Peripheral<P = impl B0Pin<T>> + 'd,
+        b1: impl Peripheral<P = impl B1Pin<T>> + 'd,
+        b2: impl Peripheral<P = impl B2Pin<T>> + 'd,
+        b3: impl Peripheral<P = impl B3Pin<T>> + 'd,
+        b4: impl Peripheral<P = impl B4Pin<T>> + 'd,
+        b5: impl Peripheral<P = impl B5Pin<T>> + 'd,
+        b6: impl Peripheral<P = impl B6Pin<T>> + 'd,
+        b7: impl Peripheral<P = impl B7Pin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri);
+        config_pins!(clk, hsync, vsync, de);
+        config_pins!(r0, r1, r2, r3, r4, r5, r6, r7);
+        config_pins!(g0, g1, g2, g3, g4, g5, g6, g7);
+        config_pins!(b0, b1, b2, b3, b4, b5, b6, b7);
+
+        Self::new_inner(peri, config)
+    }
+
+    fn new_inner(peri: PeripheralRef<'d, T>, config: Config) -> Self {
+        T::enable();
+        T::reset();
+
+        let regs = T::regs();
+        let c = &config;
+
+        // The registers hold the accumulated values, minus one.
+        let ahbp = c.h_sync + c.h_back_porch - 1;
+        let avbp = c.v_sync + c.v_back_porch - 1;
+        let aaw = ahbp + c.active_width;
+        let aah = avbp + c.active_height;
+
+        regs.sscr().write(|w| {
+            w.set_hsw(c.h_sync - 1);
+            w.set_vsh(c.v_sync - 1);
+        });
+        regs.bpcr().write(|w| {
+            w.set_ahbp(ahbp);
+            w.set_avbp(avbp);
+        });
+        regs.awcr().write(|w| {
+            w.set_aaw(aaw);
+            w.set_aah(aah);
+        });
+        regs.twcr().write(|w| {
+            w.set_totalw(aaw + c.h_front_porch);
+            w.set_totalh(aah + c.v_front_porch);
+        });
+        regs.bccr().write(|w| w.0 = c.background_color & 0xFF_FFFF);
+
+        let mut gcr = 0;
+        if c.h_sync_polarity == Polarity::ActiveHigh {
+            gcr |= 1 << 31;
+        }
+        if c.v_sync_polarity == Polarity::ActiveHigh {
+            gcr |= 1 << 30;
+        }
+        if c.data_enable_polarity == Polarity::ActiveHigh {
+            gcr |= 1 << 29;
+        }
+        if c.pixel_clock_polarity == PixelClockPolarity::FallingEdge {
+            gcr |= 1 << 28;
+        }
+        regs.gcr().write(|w| w.0 = gcr);
+        regs.gcr().modify(|w| w.set_ltdcen(true));
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri, config }
+    }
+
+    /// Enable a layer, and show `framebuffer` in it.
+    ///
+    /// # Safety
+    ///
+    /// `framebuffer` must point to `config.width * config.height` pixels in `config.pixel_format`,
+    /// which must stay valid until another framebuffer is set, the layer is disabled, or the
+    /// driver is dropped.
+    pub unsafe fn enable_layer(&mut self, layer: Layer, config: &LayerConfig, framebuffer: *const u8) {
+        let regs = T::regs();
+        let l = regs.layer(layer.index());
+
+        let ahbp = self.config.h_sync + self.config.h_back_porch - 1;
+        let avbp = self.config.v_sync + self.config.v_back_porch - 1;
+        l.whpcr().write(|w| {
+            w.set_whstpos(ahbp + config.x + 1);
+            w.set_whsppos(ahbp + config.x + config.width);
+        });
+        l.wvpcr().write(|w| {
+            w.set_wvstpos(avbp + config.y + 1);
+            w.set_wvsppos(avbp + config.y + config.height);
+        });
+
+        l.pfcr().write(|w| w.0 = config.pixel_format as u32);
+        l.cacr().write(|w| w.set_consta(config.alpha));
+        // Pixel alpha x constant alpha, or constant alpha only.
+        let (bf1, bf2) = if config.pixel_format.has_alpha() {
+            (6, 7)
+        } else {
+            (4, 5)
+        };
+        l.bfcr().write(|w| w.0 = (bf1 << 8) | bf2);
+
+        let pitch = config.width * config.pixel_format.bytes_per_pixel();
+        l.cfbar().write(|w| w.set_cfbadd(framebuffer as u32));
+        l.cfblr().write(|w| {
+            w.set_cfbp(pitch);
+            w.set_cfbll(pitch + 3);
+        });
+        l.cfblnr().write(|w| w.set_cfblnbr(config.height));
+
+        l.cr().modify(|w| w.set_len(true));
+
+        // Apply immediately
+        regs.srcr().write(|w| w.0 = SRCR_IMR);
+    }
+
+    /// Disable a layer, from the next frame.
+    pub fn disable_layer(&mut self, layer: Layer) {
+        let regs = T::regs();
+        regs.layer(layer.index()).cr().modify(|w| w.set_len(false));
+        regs.srcr().write(|w| w.0 = SRCR_VBR);
+    }
+
+    /// Show `framebuffer` in an enabled layer, from the next frame.
+    ///
+    /// The previous framebuffer is read until [`wait_reload`](Self::wait_reload) returns: to avoid
+    /// tearing with double buffering, wait for the reload before drawing in it again.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`enable_layer`](Self::enable_layer), with the configuration of the layer.
+    pub unsafe fn set_framebuffer(&mut self, layer: Layer, framebuffer: *const u8) {
+        let regs = T::regs();
+        regs.layer(layer.index())
+            .cfbar()
+            .write(|w| w.set_cfbadd(framebuffer as u32));
+        regs.srcr().write(|w| w.0 = SRCR_VBR);
+    }
+
+    /// Wait for the changes requested since the last frame to be applied, at the start of the
+    /// vertical blanking.
+    pub async fn wait_reload(&mut self) {
+        let regs = T::regs();
+
+        poll_fn(|cx| {
+            STATE.waker.register(cx.waker());
+
+            if regs.srcr().read().0 & SRCR_VBR == 0 {
+                return Poll::Ready(());
+            }
+            regs.ier().modify(|w| w.set_rrie(true));
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Wait for the start of the next vertical blanking, when the last line of the frame has
+    /// been sent to the panel.
+    pub async fn wait_vsync(&mut self) {
+        let regs = T::regs();
+
+        let aah = self.config.v_sync + self.config.v_back_porch + self.config.active_height - 1;
+        regs.lipcr().write(|w| w.set_lipos(aah + 1));
+        regs.icr().write(|w| w.set_clif(true));
+
+        poll_fn(|cx| {
+            STATE.waker.register(cx.waker());
+
+            if regs.isr().read().lif() {
+                regs.icr().write(|w| w.set_clif(true));
+                return Poll::Ready(());
+            }
+            regs.ier().modify(|w| w.set_lie(true));
+            Poll::Pending
+        })
+        .await;
+    }
+}
+
+impl<'d, T: Instance> Drop for Ltdc<'d, T> {
+    fn drop(&mut self) {
+        T::regs().gcr().modify(|w| w.set_ltdcen(false));
+        T::disable();
+    }
+}
+
+/// SRCR: immediate reload
+const SRCR_IMR: u32 = 1 << 0;
+/// SRCR: reload at the start of the vertical blanking
+const SRCR_VBR: u32 = 1 << 1;
+
+#[cfg(feature = "embedded-graphics")]
+mod eg {
+    use embedded_graphics_core::draw_target::DrawTarget;
+    use embedded_graphics_core::geometry::{OriginDimensions, Size};
+    use embedded_graphics_core::pixelcolor::raw::RawU16;
+    use embedded_graphics_core::pixelcolor::Rgb565;
+    use embedded_graphics_core::Pixel;
+
+    /// A RGB565 framebuffer, to draw in with `embedded-graphics`.
+    pub struct Framebuffer<'a> {
+        buffer: &'a mut [u16],
+        width: u16,
+        height: u16,
+    }
+
+    impl<'a> Framebuffer<'a> {
+        /// # Panics
+        ///
+        /// Panics if `buffer` is smaller than `width * height` pixels.
+        pub fn new(buffer: &'a mut [u16], width: u16, height: u16) -> Self {
+            assert!(buffer.len() >= width as usize * height as usize);
+            Self { buffer, width, height }
+        }
+
+        /// Pointer to the pixels, for [`Ltdc::set_framebuffer`](super::Ltdc::set_framebuffer).
+        pub fn as_ptr(&self) -> *const u8 {
+            self.buffer.as_ptr() as *const u8
+        }
+    }
+
+    impl<'a> OriginDimensions for Framebuffer<'a> {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.height as u32)
+        }
+    }
+
+    impl<'a> DrawTarget for Framebuffer<'a> {
+        type Color = Rgb565;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if point.x >= 0 && point.y >= 0 && point.x < self.width as i32 && point.y < self.height as i32 {
+                    let index = point.y as usize * self.width as usize + point.x as usize;
+                    self.buffer[index] = RawU16::from(color).into_inner();
+                }
+            }
+            Ok(())
+        }
+
+        fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+            self.buffer.fill(RawU16::from(color).into_inner());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+pub use eg::Framebuffer;
+
+pub(crate) mod sealed {
+    pub trait Instance: crate::rcc::RccPeripheral {
+        fn regs() -> crate::pac::ltdc::Ltdc;
+    }
+}
+
+pub trait Instance: sealed::Instance + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pin_trait!(ClkPin, Instance);
+pin_trait!(HsyncPin, Instance);
+pin_trait!(VsyncPin, Instance);
+pin_trait!(DePin, Instance);
+pin_trait!(R0Pin, Instance);
+pin_trait!(R1Pin, Instance);
+pin_trait!(R2Pin, Instance);
+pin_trait!(R3Pin, Instance);
+pin_trait!(R4Pin, Instance);
+pin_trait!(R5Pin, Instance);
+pin_trait!(R6Pin, Instance);
+pin_trait!(R7Pin, Instance);
+pin_trait!(G0Pin, Instance);
+pin_trait!(G1Pin, Instance);
+pin_trait!(G2Pin, Instance);
+pin_trait!(G3Pin, Instance);
+pin_trait!(G4Pin, Instance);
+pin_trait!(G5Pin, Instance);
+pin_trait!(G6Pin, Instance);
+pin_trait!(G7Pin, Instance);
+pin_trait!(B0Pin, Instance);
+pin_trait!(B1Pin, Instance);
+pin_trait!(B2Pin, Instance);
+pin_trait!(B3Pin, Instance);
+pin_trait!(B4Pin, Instance);
+pin_trait!(B5Pin, Instance);
+pin_trait!(B6Pin, Instance);
+pin_trait!(B7Pin, Instance);
+
+foreach_interrupt!(
+    ($inst:ident, ltdc, LTDC, GLOBAL, $irq:ident) => {
+        impl sealed::Instance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::ltdc::Ltdc {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
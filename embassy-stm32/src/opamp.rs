@@ -0,0 +1,404 @@
+#![macro_use]
+
+//! Operational amplifiers (OPAMP)
+//!
+//! Each op-amp buffers, or amplifies with a programmable gain, the voltage of its non-inverting
+//! input. Its output goes to a pin, and on G4 it can also be routed internally to an ADC channel.
+//!
+//! The offsets of the op-amps are trimmed in the factory, and [`OpAmp::calibrate()`] trims them
+//! again for the current supply voltage and temperature.
+
+use core::marker::PhantomData;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embedded_hal_02::blocking::delay::DelayUs;
+
+#[cfg(not(opamp_l4))]
+use crate::pac::opamp::vals::{Calsel, PgaGain, VmSel, VpSel};
+#[cfg(opamp_g4)]
+use crate::pac::opamp::vals::{Opahsm, Opaintoen};
+use crate::Peripheral;
+
+// OPAMODE values, L4
+#[cfg(opamp_l4)]
+const OPAMODE_PGA: u8 = 0b10;
+#[cfg(opamp_l4)]
+const OPAMODE_FOLLOWER: u8 = 0b11;
+
+/// Time for the calibration output to settle after a change of the trimming value.
+const TRIMMING_DELAY_US: u32 = 2_000;
+
+/// Gain of the op-amp.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OpAmpGain {
+    /// Follower mode: the output follows the input.
+    Mul1,
+    /// The internal programmable gain amplifier (PGA) multiplies the input by 2.
+    Mul2,
+    Mul4,
+    Mul8,
+    Mul16,
+}
+
+impl OpAmpGain {
+    /// PGA_GAIN value, for the non-inverting PGA mode.
+    fn pga_gain(&self) -> Option<u8> {
+        match self {
+            OpAmpGain::Mul1 => None,
+            OpAmpGain::Mul2 => Some(0),
+            OpAmpGain::Mul4 => Some(1),
+            OpAmpGain::Mul8 => Some(2),
+            OpAmpGain::Mul16 => Some(3),
+        }
+    }
+}
+
+/// Differential pair of the input stage, trimmed separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum DiffPair {
+    /// Trimmed with the calibration input at 90% of VDDA.
+    N,
+    /// Trimmed with the calibration input at 10% of VDDA.
+    P,
+}
+
+/// Speed of the op-amp.
+#[cfg(opamp_g4)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OpAmpSpeed {
+    Normal,
+    HighSpeed,
+}
+
+/// Op-amp driver.
+pub struct OpAmp<'d, T: Instance> {
+    _inner: PeripheralRef<'d, T>,
+}
+
+/// Output of an op-amp on its VOUT pin.
+///
+/// The op-amp is disabled when this is dropped.
+pub struct OpAmpOutput<'a, T: Instance> {
+    _phantom: PhantomData<&'a mut T>,
+}
+
+/// Output of an op-amp, routed internally to an ADC channel.
+///
+/// Read it with the ADC like a pin. The op-amp is disabled when this is dropped.
+#[cfg(opamp_g4)]
+pub struct OpAmpInternalOutput<'a, T: Instance> {
+    _phantom: PhantomData<&'a mut T>,
+}
+
+impl<'d, T: Instance> OpAmp<'d, T> {
+    /// Create a new op-amp driver. The op-amp stays disabled until one of its outputs is used.
+    pub fn new(opamp: impl Peripheral<P = T> + 'd) -> Self {
+        into_ref!(opamp);
+
+        enable();
+
+        #[cfg(opamp_l4)]
+        set_high_range();
+
+        Self { _inner: opamp }
+    }
+
+    /// Set the speed of the op-amp. The high-speed mode has a higher slew rate and gain
+    /// bandwidth, and consumes more.
+    #[cfg(opamp_g4)]
+    pub fn set_speed(&mut self, speed: OpAmpSpeed) {
+        T::regs().csr().modify(|w| {
+            w.set_opahsm(match speed {
+                OpAmpSpeed::Normal => Opahsm::NORMAL,
+                OpAmpSpeed::HighSpeed => Opahsm::HIGHSPEED,
+            })
+        });
+    }
+
+    /// Calibrate the offset of the op-amp, for the current supply voltage and temperature.
+    ///
+    /// The op-amp must not be in use. This takes about 25 ms, and the trimming values are kept
+    /// until the op-amp is calibrated again.
+    pub fn calibrate(&mut self, delay: &mut impl DelayUs<u32>) {
+        T::regs().csr().modify(|w| {
+            set_enabled(w, true);
+            w.set_usertrim(true);
+            w.set_calon(true);
+        });
+
+        let trim_n = trim::<T>(DiffPair::N, delay);
+        let trim_p = trim::<T>(DiffPair::P, delay);
+
+        T::regs().csr().modify(|w| {
+            set_enabled(w, false);
+            w.set_calon(false);
+        });
+        trace!("OPAMP calibrated, trimming n={} p={}", trim_n, trim_p);
+    }
+
+    /// Buffer, or amplify with `gain`, the voltage on `in_pin` to `out_pin`.
+    ///
+    /// The op-amp is enabled until the returned [`OpAmpOutput`] is dropped.
+    pub fn buffer_ext(
+        &mut self,
+        in_pin: &mut impl NonInvertingPin<T>,
+        out_pin: &mut impl OutputPin<T>,
+        gain: OpAmpGain,
+    ) -> OpAmpOutput<'_, T> {
+        in_pin.set_as_analog();
+        out_pin.set_as_analog();
+
+        configure::<T>(in_pin.channel(), gain);
+        T::regs().csr().modify(|w| set_enabled(w, true));
+
+        OpAmpOutput { _phantom: PhantomData }
+    }
+
+    /// Buffer, or amplify with `gain`, the voltage on `in_pin` to an internal ADC channel.
+    ///
+    /// The op-amp is enabled until the returned [`OpAmpInternalOutput`] is dropped.
+    #[cfg(opamp_g4)]
+    pub fn buffer_int(&mut self, in_pin: &mut impl NonInvertingPin<T>, gain: OpAmpGain) -> OpAmpInternalOutput<'_, T> {
+        in_pin.set_as_analog();
+
+        configure::<T>(in_pin.channel(), gain);
+        T::regs().csr().modify(|w| {
+            w.set_opaintoen(Opaintoen::ADCCHANNEL);
+            w.set_opampen(true);
+        });
+
+        OpAmpInternalOutput { _phantom: PhantomData }
+    }
+}
+
+impl<'a, T: Instance> Drop for OpAmpOutput<'a, T> {
+    fn drop(&mut self) {
+        T::regs().csr().modify(|w| set_enabled(w, false));
+    }
+}
+
+#[cfg(opamp_g4)]
+impl<'a, T: Instance> Drop for OpAmpInternalOutput<'a, T> {
+    fn drop(&mut self) {
+        T::regs().csr().modify(|w| {
+            w.set_opampen(false);
+            w.set_opaintoen(Opaintoen::OUTPUTPIN);
+        });
+    }
+}
+
+/// Select the input and the mode of the op-amp.
+#[cfg(not(opamp_l4))]
+fn configure<T: Instance>(channel: u8, gain: OpAmpGain) {
+    T::regs().csr().modify(|w| {
+        w.set_vp_sel(VpSel::from_bits(channel));
+        match gain.pga_gain() {
+            Some(pga_gain) => {
+                w.set_vm_sel(VmSel::PGA);
+                w.set_pga_gain(PgaGain::from_bits(pga_gain));
+            }
+            None => w.set_vm_sel(VmSel::OUTPUT),
+        }
+    });
+}
+
+/// Select the input and the mode of the op-amp.
+#[cfg(opamp_l4)]
+fn configure<T: Instance>(_channel: u8, gain: OpAmpGain) {
+    T::regs().csr().modify(|w| {
+        // Only the GPIO input can be selected, VP_SEL=1 connects the DAC.
+        w.set_vp_sel(false);
+        match gain.pga_gain() {
+            Some(pga_gain) => {
+                w.set_opamode(OPAMODE_PGA);
+                w.set_pga_gain(pga_gain);
+            }
+            None => w.set_opamode(OPAMODE_FOLLOWER),
+        }
+    });
+}
+
+#[cfg(not(opamp_l4))]
+fn set_enabled(w: &mut crate::pac::opamp::regs::Csr, enabled: bool) {
+    w.set_opampen(enabled);
+}
+
+#[cfg(opamp_l4)]
+fn set_enabled(w: &mut crate::pac::opamp::regs::Csr, enabled: bool) {
+    w.set_opaen(enabled);
+}
+
+/// Find the trimming value of a differential pair by dichotomy, and return it.
+fn trim<T: Instance>(pair: DiffPair, delay: &mut impl DelayUs<u32>) -> u8 {
+    #[cfg(not(opamp_l4))]
+    let calsel = match pair {
+        DiffPair::N => Calsel::PERCENT90,
+        DiffPair::P => Calsel::PERCENT10,
+    };
+    // CALSEL selects 10% of VDDA when set on L4.
+    #[cfg(opamp_l4)]
+    let calsel = pair == DiffPair::P;
+    T::regs().csr().modify(|w| w.set_calsel(calsel));
+
+    let mut value = 16;
+    let mut delta = 8;
+    while delta != 0 {
+        set_trim::<T>(pair, value);
+        delay.delay_us(TRIMMING_DELAY_US);
+
+        if calout_too_low::<T>() {
+            value += delta;
+        } else {
+            value -= delta;
+        }
+        delta >>= 1;
+    }
+
+    // The right value is either the current one, or the one above.
+    set_trim::<T>(pair, value);
+    delay.delay_us(TRIMMING_DELAY_US);
+    if calout_too_low::<T>() && value < 31 {
+        value += 1;
+        set_trim::<T>(pair, value);
+    }
+
+    value
+}
+
+/// Whether the calibration output says that the trimming value must be increased.
+fn calout_too_low<T: Instance>() -> bool {
+    let calout = T::regs().csr().read().calout();
+    // The polarity of CALOUT is reversed on L4.
+    #[cfg(opamp_l4)]
+    let calout = !calout;
+    calout
+}
+
+#[cfg(not(opamp_l4))]
+fn set_trim<T: Instance>(pair: DiffPair, value: u8) {
+    T::regs().csr().modify(|w| match pair {
+        DiffPair::N => w.set_trimoffsetn(value),
+        DiffPair::P => w.set_trimoffsetp(value),
+    });
+}
+
+/// The trimming values are in the OTR register on L4.
+#[cfg(opamp_l4)]
+fn set_trim<T: Instance>(pair: DiffPair, value: u8) {
+    T::regs().otr().modify(|w| match pair {
+        DiffPair::N => w.set_trimoffsetn(value),
+        DiffPair::P => w.set_trimoffsetp(value),
+    });
+}
+
+/// The op-amps are clocked with SYSCFG on F3 and G4, and have their own clock on L4.
+fn enable() {
+    critical_section::with(|_| {
+        #[cfg(not(opamp_l4))]
+        crate::pac::RCC.apb2enr().modify(|w| w.set_syscfgen(true));
+        #[cfg(opamp_l4)]
+        crate::pac::RCC.apb1enr1().modify(|w| w.set_opampen(true));
+    });
+}
+
+/// Select the high voltage range of both L4 op-amps, for VDDA above 2.4 V.
+#[cfg(opamp_l4)]
+fn set_high_range() {
+    // OPA_RANGE is only in OPAMP1_CSR, for both op-amps.
+    crate::pac::OPAMP1.csr().modify(|w| w.set_opa_range(true));
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::opamp::Opamp;
+    }
+
+    pub trait NonInvertingPin<T: Instance> {
+        fn channel(&self) -> u8;
+    }
+
+    pub trait OutputPin<T: Instance> {}
+}
+
+pub trait Instance: sealed::Instance + 'static {}
+/// Non-inverting input pin of an op-amp.
+pub trait NonInvertingPin<T: Instance>: sealed::NonInvertingPin<T> + crate::gpio::Pin {}
+/// Output pin of an op-amp.
+pub trait OutputPin<T: Instance>: sealed::OutputPin<T> + crate::gpio::Pin {}
+
+foreach_peripheral!(
+    (opamp, $inst:ident) => {
+        impl sealed::Instance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::opamp::Opamp {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {}
+    };
+);
+
+macro_rules! impl_opamp_vp_pin {
+    ($inst:ident, $pin:ident, $ch:expr) => {
+        impl crate::opamp::NonInvertingPin<peripherals::$inst> for crate::peripherals::$pin {}
+        impl crate::opamp::sealed::NonInvertingPin<peripherals::$inst> for crate::peripherals::$pin {
+            fn channel(&self) -> u8 {
+                $ch
+            }
+        }
+    };
+}
+
+macro_rules! impl_opamp_vout_pin {
+    ($inst:ident, $pin:ident) => {
+        impl crate::opamp::OutputPin<peripherals::$inst> for crate::peripherals::$pin {}
+        impl crate::opamp::sealed::OutputPin<peripherals::$inst> for crate::peripherals::$pin {}
+    };
+}
+
+#[cfg(all(opamp_g4, adc))]
+macro_rules! impl_opamp_internal_output {
+    ($inst:ident, $adc:ident, $ch:expr) => {
+        foreach_peripheral!(
+            (adc, $adc) => {
+                impl<'a> crate::adc::sealed::AdcPin<crate::peripherals::$adc>
+                    for OpAmpInternalOutput<'a, crate::peripherals::$inst>
+                {
+                    fn channel(&self) -> u8 {
+                        $ch
+                    }
+                }
+
+                impl<'a> crate::adc::AdcPin<crate::peripherals::$adc>
+                    for OpAmpInternalOutput<'a, crate::peripherals::$inst>
+                {
+                }
+            };
+        );
+    };
+}
+
+#[cfg(all(opamp_g4, adc))]
+foreach_peripheral!(
+    (opamp, OPAMP1) => {
+        impl_opamp_internal_output!(OPAMP1, ADC1, 13);
+    };
+    (opamp, OPAMP2) => {
+        impl_opamp_internal_output!(OPAMP2, ADC2, 16);
+    };
+    (opamp, OPAMP3) => {
+        impl_opamp_internal_output!(OPAMP3, ADC2, 18);
+        impl_opamp_internal_output!(OPAMP3, ADC3, 13);
+    };
+    (opamp, OPAMP4) => {
+        impl_opamp_internal_output!(OPAMP4, ADC5, 5);
+    };
+    (opamp, OPAMP5) => {
+        impl_opamp_internal_output!(OPAMP5, ADC5, 3);
+    };
+    (opamp, OPAMP6) => {
+        impl_opamp_internal_output!(OPAMP6, ADC4, 17);
+    };
+);
@@ -0,0 +1,794 @@
+//! Octo-SPI interface (OCTOSPI)
+//!
+//! The OCTOSPI drives external single, dual, quad or octal SPI memories, such as octal flashes
+//! and PSRAMs, in SDR or DTR mode. Commands can be sent in indirect mode with
+//! [`Ospi::blocking_read`], [`Ospi::blocking_write`] and their DMA variants, or the memory can be
+//! mapped in the address space with [`Ospi::enable_memory_mapped_mode`], for execute in place
+//! (XIP) or to use a PSRAM as regular memory.
+//!
+//! On chips with an OCTOSPI I/O manager (OCTOSPIM), such as the H7A3/B0 and U5, the manager must
+//! route the pins of the port to the OCTOSPI before using this driver: it isn't configured here.
+
+#![macro_use]
+
+pub mod enums;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use enums::*;
+
+use crate::dma::Transfer;
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Speed};
+use crate::pac::octospi::Octospi as Regs;
+use crate::rcc::RccPeripheral;
+use crate::{peripherals, Peripheral};
+
+// CR bits
+const CR_EN: u32 = 1 << 0;
+const CR_ABORT: u32 = 1 << 1;
+const CR_DMAEN: u32 = 1 << 2;
+const CR_FTHRES_POS: u32 = 8;
+const CR_APMS: u32 = 1 << 22;
+const CR_FMODE_POS: u32 = 28;
+const CR_FMODE_MASK: u32 = 0b11 << CR_FMODE_POS;
+
+// DCR1 bits
+const DCR1_CKMODE: u32 = 1 << 0;
+const DCR1_FRCK: u32 = 1 << 1;
+const DCR1_DLYBYP: u32 = 1 << 3;
+
+// SR and FCR bits
+const SR_TEF: u32 = 1 << 0;
+const SR_TCF: u32 = 1 << 1;
+const SR_FTF: u32 = 1 << 2;
+const SR_SMF: u32 = 1 << 3;
+const SR_BUSY: u32 = 1 << 5;
+const FCR_ALL: u32 = 0b11011;
+
+// CCR bits
+const CCR_DQSE: u32 = 1 << 29;
+
+// TCR bits
+const TCR_DHQC: u32 = 1 << 28;
+const TCR_SSHIFT: u32 = 1 << 30;
+
+/// Size of the address range where the memory is mapped.
+const MEMORY_WINDOW_SIZE: usize = 0x1000_0000;
+
+/// OCTOSPI error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The operation isn't possible in the current state, for example an indirect transfer while
+    /// the memory is mapped, or an empty buffer.
+    InvalidConfiguration,
+    /// The address of the transfer is out of the range of the memory.
+    TransferError,
+}
+
+/// Configuration of a transaction.
+///
+/// Each phase (instruction, address, alternate bytes, data) is skipped when its width is
+/// [`OspiWidth::NONE`].
+#[derive(Clone, Copy)]
+pub struct TransferConfig {
+    /// Instruction width (IMODE)
+    pub iwidth: OspiWidth,
+    /// Instruction size (ISIZE)
+    pub isize: AddressSize,
+    /// Instruction double transfer rate (IDTR)
+    pub idtr: bool,
+
+    /// Address width (ADMODE)
+    pub adwidth: OspiWidth,
+    /// Address size (ADSIZE)
+    pub adsize: AddressSize,
+    /// Address double transfer rate (ADDTR)
+    pub addtr: bool,
+
+    /// Alternate bytes width (ABMODE)
+    pub abwidth: OspiWidth,
+    /// Alternate bytes size (ABSIZE)
+    pub absize: AddressSize,
+    /// Alternate bytes double transfer rate (ABDTR)
+    pub abdtr: bool,
+
+    /// Data width (DMODE)
+    pub dwidth: OspiWidth,
+    /// Data double transfer rate (DDTR)
+    pub ddtr: bool,
+    /// Sample the data with the DQS signal (DQSE), needed by PSRAMs and octal DTR flashes.
+    pub dqse: bool,
+
+    /// Instruction
+    pub instruction: Option<u32>,
+    /// Memory address, ignored in memory-mapped mode
+    pub address: Option<u32>,
+    /// Alternate bytes
+    pub alternate_bytes: Option<u32>,
+    /// Number of dummy cycles (DCYC)
+    pub dummy: DummyCycles,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        Self {
+            iwidth: OspiWidth::NONE,
+            isize: AddressSize::_8Bit,
+            idtr: false,
+
+            adwidth: OspiWidth::NONE,
+            adsize: AddressSize::_8Bit,
+            addtr: false,
+
+            abwidth: OspiWidth::NONE,
+            absize: AddressSize::_8Bit,
+            abdtr: false,
+
+            dwidth: OspiWidth::NONE,
+            ddtr: false,
+            dqse: false,
+
+            instruction: None,
+            address: None,
+            alternate_bytes: None,
+            dummy: DummyCycles::_0,
+        }
+    }
+}
+
+/// OCTOSPI configuration.
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Type of the memory (MTYP)
+    pub memory_type: MemoryType,
+    /// Memory size represented as 2^[0-32], as reasonable minimum 1KiB(9) was chosen.
+    /// If you need other value the whose predefined use `Other` variant.
+    pub device_size: MemorySize,
+    /// Minimum number of cycles that chip select must be high between issued commands
+    pub chip_select_high_time: ChipSelectHighTime,
+    /// Keep the clock running even when no transaction is in progress (FRCK)
+    pub free_running_clock: bool,
+    /// Keep CLK high while chip select is released, mode 3, instead of low, mode 0 (CKMODE)
+    pub clock_mode: bool,
+    /// Scalar factor for generating CLK [0-255], CLK = kernel clock / (prescaler + 1)
+    pub clock_prescaler: u8,
+    /// Sample the data half a clock cycle later (SSHIFT), to account for external delays
+    pub sample_shifting: bool,
+    /// Delay the data output by a quarter of a clock cycle (DHQC), for DTR mode
+    pub delay_hold_quarter_cycle: bool,
+    /// Split transfers crossing a 2^[0-31] bytes boundary (CSBOUND), 0 to never split
+    pub chip_select_boundary: u8,
+    /// Bypass the delay block used to sample data with DQS (DLYBYP)
+    pub delay_block_bypass: bool,
+    /// Maximum number of clock cycles of a transfer, when two OCTOSPIs share a port (MAXTRAN)
+    pub max_transfer: u8,
+    /// Number of clock cycles after which chip select is released, for PSRAM refresh (REFRESH)
+    pub refresh: u32,
+    /// Number of bytes to trigger FIFO threshold flag.
+    pub fifo_threshold: FIFOThresholdLevel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            memory_type: MemoryType::Micron,
+            device_size: MemorySize::Other(0),
+            chip_select_high_time: ChipSelectHighTime::_5Cycle,
+            free_running_clock: false,
+            clock_mode: false,
+            clock_prescaler: 0,
+            sample_shifting: false,
+            delay_hold_quarter_cycle: false,
+            chip_select_boundary: 0,
+            delay_block_bypass: true,
+            max_transfer: 0,
+            refresh: 0,
+            fifo_threshold: FIFOThresholdLevel::_16Bytes,
+        }
+    }
+}
+
+macro_rules! config_pins {
+    ($($pin:ident),*) => {
+        into_ref!($($pin),*);
+        $(
+            $pin.set_as_af($pin.af_num(), AFType::OutputPushPull);
+            $pin.set_speed(Speed::VeryHigh);
+        )*
+    };
+}
+
+/// OCTOSPI driver.
+#[allow(dead_code)]
+pub struct Ospi<'d, T: Instance, Dma> {
+    _peri: PeripheralRef<'d, T>,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    d0: Option<PeripheralRef<'d, AnyPin>>,
+    d1: Option<PeripheralRef<'d, AnyPin>>,
+    d2: Option<PeripheralRef<'d, AnyPin>>,
+    d3: Option<PeripheralRef<'d, AnyPin>>,
+    d4: Option<PeripheralRef<'d, AnyPin>>,
+    d5: Option<PeripheralRef<'d, AnyPin>>,
+    d6: Option<PeripheralRef<'d, AnyPin>>,
+    d7: Option<PeripheralRef<'d, AnyPin>>,
+    nss: Option<PeripheralRef<'d, AnyPin>>,
+    dqs: Option<PeripheralRef<'d, AnyPin>>,
+    dma: PeripheralRef<'d, Dma>,
+    config: Config,
+    memory_mapped: bool,
+}
+
+impl<'d, T: Instance, Dma> Ospi<'d, T, Dma> {
+    /// Create a new OCTOSPI driver for a quad SPI memory.
+    pub fn new_quadspi(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        d0: impl Peripheral<P = impl D0Pin<T>> + 'd,
+        d1: impl Peripheral<P = impl D1Pin<T>> + 'd,
+        d2: impl Peripheral<P = impl D2Pin<T>> + 'd,
+        d3: impl Peripheral<P = impl D3Pin<T>> + 'd,
+        nss: impl Peripheral<P = impl NSSPin<T>> + 'd,
+        dma: impl Peripheral<P = Dma> + 'd,
+        config: Config,
+    ) -> Self {
+        config_pins!(sck, d0, d1, d2, d3, nss);
+
+        Self::new_inner(
+            peri,
+            [
+                Some(sck.map_into()),
+                Some(d0.map_into()),
+                Some(d1.map_into()),
+                Some(d2.map_into()),
+                Some(d3.map_into()),
+                None,
+                None,
+                None,
+                None,
+                Some(nss.map_into()),
+                None,
+            ],
+            dma,
+            config,
+        )
+    }
+
+    /// Create a new OCTOSPI driver for an octal SPI memory.
+    pub fn new_octospi(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        d0: impl Peripheral<P = impl D0Pin<T>> + 'd,
+        d1: impl Peripheral<P = impl D1Pin<T>> + 'd,
+        d2: impl Peripheral<P = impl D2Pin<T>> + 'd,
+        d3: impl Peripheral<P = impl D3Pin<T>> + 'd,
+        d4: impl Peripheral<P = impl D4Pin<T>> + 'd,
+        d5: impl Peripheral<P = impl D5Pin<T>> + 'd,
+        d6: impl Peripheral<P = impl D6Pin<T>> + 'd,
+        d7: impl Peripheral<P = impl D7Pin<T>> + 'd,
+        nss: impl Peripheral<P = impl NSSPin<T>> + 'd,
+        dqs: impl Peripheral<P = impl DQSPin<T>> + 'd,
+        dma: impl Peripheral<P = Dma> + 'd,
+        config: Config,
+    ) -> Self {
+        config_pins!(sck, d0, d1, d2, d3, d4, d5, d6, d7, nss, dqs);
+
+        Self::new_inner(
+            peri,
+            [
+                Some(sck.map_into()),
+                Some(d0.map_into()),
+                Some(d1.map_into()),
+                Some(d2.map_into()),
+                Some(d3.map_into()),
+                Some(d4.map_into()),
+                Some(d5.map_into()),
+                Some(d6.map_into()),
+                Some(d7.map_into()),
+                Some(nss.map_into()),
+                Some(dqs.map_into()),
+            ],
+            dma,
+            config,
+        )
+    }
+
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        pins: [Option<PeripheralRef<'d, AnyPin>>; 11],
+        dma: impl Peripheral<P = Dma> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri, dma);
+
+        T::enable();
+        T::reset();
+
+        let fthres: u8 = config.fifo_threshold.into();
+        T::REGS.cr().write(|w| w.0 = (fthres as u32) << CR_FTHRES_POS);
+
+        while T::REGS.sr().read().0 & SR_BUSY != 0 {}
+
+        let mtyp: u8 = config.memory_type.into();
+        let devsize: u8 = config.device_size.into();
+        let csht: u8 = config.chip_select_high_time.into();
+        T::REGS.dcr1().write(|w| {
+            w.0 = (mtyp as u32) << 24 | (devsize as u32) << 16 | (csht as u32) << 8;
+            if config.clock_mode {
+                w.0 |= DCR1_CKMODE;
+            }
+            if config.free_running_clock {
+                w.0 |= DCR1_FRCK;
+            }
+            if config.delay_block_bypass {
+                w.0 |= DCR1_DLYBYP;
+            }
+        });
+        T::REGS.dcr2().write(|w| w.0 = config.clock_prescaler as u32);
+        T::REGS
+            .dcr3()
+            .write(|w| w.0 = (config.chip_select_boundary as u32) << 16 | config.max_transfer as u32);
+        T::REGS.dcr4().write(|w| w.0 = config.refresh);
+
+        T::REGS.cr().modify(|w| w.0 |= CR_EN);
+
+        let [sck, d0, d1, d2, d3, d4, d5, d6, d7, nss, dqs] = pins;
+        Self {
+            _peri: peri,
+            sck,
+            d0,
+            d1,
+            d2,
+            d3,
+            d4,
+            d5,
+            d6,
+            d7,
+            nss,
+            dqs,
+            dma,
+            config,
+            memory_mapped: false,
+        }
+    }
+
+    /// Send a command without data.
+    pub fn command(&mut self, transaction: TransferConfig) -> Result<(), Error> {
+        self.setup_transaction(OspiMode::IndirectWrite, &transaction, None)?;
+        self.wait_complete()
+    }
+
+    /// Read data from the memory.
+    pub fn blocking_read(&mut self, buf: &mut [u8], transaction: TransferConfig) -> Result<(), Error> {
+        self.setup_transaction(OspiMode::IndirectRead, &transaction, Some(buf.len()))?;
+
+        for b in buf.iter_mut() {
+            loop {
+                let sr = T::REGS.sr().read().0;
+                if sr & SR_TEF != 0 {
+                    return Err(self.transfer_error());
+                }
+                if sr & (SR_FTF | SR_TCF) != 0 {
+                    break;
+                }
+            }
+            *b = unsafe { (T::REGS.dr().as_ptr() as *mut u8).read_volatile() };
+        }
+
+        self.wait_complete()
+    }
+
+    /// Write data to the memory.
+    pub fn blocking_write(&mut self, buf: &[u8], transaction: TransferConfig) -> Result<(), Error> {
+        self.setup_transaction(OspiMode::IndirectWrite, &transaction, Some(buf.len()))?;
+
+        for b in buf {
+            loop {
+                let sr = T::REGS.sr().read().0;
+                if sr & SR_TEF != 0 {
+                    return Err(self.transfer_error());
+                }
+                if sr & SR_FTF != 0 {
+                    break;
+                }
+            }
+            unsafe { (T::REGS.dr().as_ptr() as *mut u8).write_volatile(*b) };
+        }
+
+        self.wait_complete()
+    }
+
+    /// Read data from the memory, using DMA.
+    pub fn blocking_read_dma(&mut self, buf: &mut [u8], transaction: TransferConfig) -> Result<(), Error>
+    where
+        Dma: OctoDma<T>,
+    {
+        self.setup_transaction(OspiMode::IndirectRead, &transaction, Some(buf.len()))?;
+
+        let request = self.dma.request();
+        let transfer = unsafe {
+            Transfer::new_read(
+                &mut self.dma,
+                request,
+                T::REGS.dr().as_ptr() as *mut u8,
+                buf,
+                Default::default(),
+            )
+        };
+
+        T::REGS.cr().modify(|w| w.0 |= CR_DMAEN);
+        transfer.blocking_wait();
+
+        self.wait_complete()
+    }
+
+    /// Write data to the memory, using DMA.
+    pub fn blocking_write_dma(&mut self, buf: &[u8], transaction: TransferConfig) -> Result<(), Error>
+    where
+        Dma: OctoDma<T>,
+    {
+        self.setup_transaction(OspiMode::IndirectWrite, &transaction, Some(buf.len()))?;
+
+        let request = self.dma.request();
+        let transfer = unsafe {
+            Transfer::new_write(
+                &mut self.dma,
+                request,
+                buf,
+                T::REGS.dr().as_ptr() as *mut u8,
+                Default::default(),
+            )
+        };
+
+        T::REGS.cr().modify(|w| w.0 |= CR_DMAEN);
+        transfer.blocking_wait();
+
+        self.wait_complete()
+    }
+
+    /// Read data from the memory, using DMA.
+    pub async fn read(&mut self, buf: &mut [u8], transaction: TransferConfig) -> Result<(), Error>
+    where
+        Dma: OctoDma<T>,
+    {
+        self.setup_transaction(OspiMode::IndirectRead, &transaction, Some(buf.len()))?;
+
+        // Abort the transaction if the future is dropped.
+        let on_drop = OnDrop::new(|| Self::abort());
+
+        let request = self.dma.request();
+        let transfer = unsafe {
+            Transfer::new_read(
+                &mut self.dma,
+                request,
+                T::REGS.dr().as_ptr() as *mut u8,
+                buf,
+                Default::default(),
+            )
+        };
+
+        T::REGS.cr().modify(|w| w.0 |= CR_DMAEN);
+        transfer.await;
+
+        on_drop.defuse();
+        self.wait_complete()
+    }
+
+    /// Write data to the memory, using DMA.
+    pub async fn write(&mut self, buf: &[u8], transaction: TransferConfig) -> Result<(), Error>
+    where
+        Dma: OctoDma<T>,
+    {
+        self.setup_transaction(OspiMode::IndirectWrite, &transaction, Some(buf.len()))?;
+
+        // Abort the transaction if the future is dropped.
+        let on_drop = OnDrop::new(|| Self::abort());
+
+        let request = self.dma.request();
+        let transfer = unsafe {
+            Transfer::new_write(
+                &mut self.dma,
+                request,
+                buf,
+                T::REGS.dr().as_ptr() as *mut u8,
+                Default::default(),
+            )
+        };
+
+        T::REGS.cr().modify(|w| w.0 |= CR_DMAEN);
+        transfer.await;
+
+        on_drop.defuse();
+        self.wait_complete()
+    }
+
+    /// Read a register of the memory every `interval` clock cycles, until the bits in `mask`
+    /// match `value`.
+    ///
+    /// `len` is the size of the register, 1 to 4 bytes. This is typically used to wait for the
+    /// end of an erase or a program, by polling the status register of a flash.
+    pub fn blocking_auto_poll(
+        &mut self,
+        transaction: TransferConfig,
+        len: usize,
+        mask: u32,
+        value: u32,
+        interval: u16,
+    ) -> Result<(), Error> {
+        if !(1..=4).contains(&len) {
+            return Err(Error::InvalidConfiguration);
+        }
+
+        self.wait_idle()?;
+        T::REGS.psmkr().write(|w| w.0 = mask);
+        T::REGS.psmar().write(|w| w.0 = value);
+        T::REGS.pir().write(|w| w.0 = interval as u32);
+        T::REGS.cr().modify(|w| w.0 |= CR_APMS);
+
+        self.setup_transaction(OspiMode::AutoPolling, &transaction, Some(len))?;
+
+        loop {
+            let sr = T::REGS.sr().read().0;
+            if sr & SR_TEF != 0 {
+                return Err(self.transfer_error());
+            }
+            if sr & SR_SMF != 0 {
+                break;
+            }
+        }
+
+        T::REGS.fcr().write(|w| w.0 = FCR_ALL);
+        T::REGS.cr().modify(|w| w.0 &= !CR_APMS);
+        Ok(())
+    }
+
+    /// Map the memory in the address space, at [`Ospi::memory_mapped_base`].
+    ///
+    /// Reads use `read`, and writes use `write`, which must have a data phase. The address is
+    /// ignored: it's the offset of the access from the base. Indirect transfers fail until
+    /// [`Ospi::disable_memory_mapped_mode`] is called.
+    pub fn enable_memory_mapped_mode(&mut self, read: TransferConfig, write: TransferConfig) -> Result<(), Error> {
+        self.wait_idle()?;
+
+        T::REGS.tcr().write(|w| w.0 = self.tcr(&read));
+        T::REGS.ccr().write(|w| w.0 = ccr(&read));
+        if let Some(ab) = read.alternate_bytes {
+            T::REGS.abr().write(|w| w.0 = ab);
+        }
+        T::REGS.ir().write(|w| w.0 = read.instruction.unwrap_or(0));
+
+        T::REGS.wtcr().write(|w| w.0 = self.tcr(&write));
+        T::REGS.wccr().write(|w| w.0 = ccr(&write));
+        if let Some(ab) = write.alternate_bytes {
+            T::REGS.wabr().write(|w| w.0 = ab);
+        }
+        T::REGS.wir().write(|w| w.0 = write.instruction.unwrap_or(0));
+
+        let fmode: u8 = OspiMode::MemoryMapped.into();
+        T::REGS
+            .cr()
+            .modify(|w| w.0 = (w.0 & !(CR_FMODE_MASK | CR_DMAEN)) | (fmode as u32) << CR_FMODE_POS);
+
+        self.memory_mapped = true;
+        Ok(())
+    }
+
+    /// Stop mapping the memory in the address space, to use indirect transfers again.
+    pub fn disable_memory_mapped_mode(&mut self) {
+        if !self.memory_mapped {
+            return;
+        }
+
+        Self::abort();
+        T::REGS.cr().modify(|w| w.0 &= !CR_FMODE_MASK);
+        self.memory_mapped = false;
+    }
+
+    /// Whether the memory is mapped in the address space.
+    pub fn is_memory_mapped(&self) -> bool {
+        self.memory_mapped
+    }
+
+    /// Address where the memory is mapped.
+    pub fn memory_mapped_base(&self) -> *mut u8 {
+        T::MEMORY_BASE as *mut u8
+    }
+
+    /// The memory, when it's mapped in the address space.
+    ///
+    /// The slice covers the device size given in the [`Config`], up to the 256 MiB mapped by the
+    /// OCTOSPI.
+    pub fn memory_mapped_slice(&self) -> Option<&[u8]> {
+        if !self.memory_mapped {
+            return None;
+        }
+        let devsize: u8 = self.config.device_size.into();
+        let len = 1usize
+            .checked_shl(devsize as u32 + 1)
+            .map_or(MEMORY_WINDOW_SIZE, |len| len.min(MEMORY_WINDOW_SIZE));
+        Some(unsafe { core::slice::from_raw_parts(self.memory_mapped_base(), len) })
+    }
+
+    fn setup_transaction(
+        &mut self,
+        fmode: OspiMode,
+        transaction: &TransferConfig,
+        len: Option<usize>,
+    ) -> Result<(), Error> {
+        if len == Some(0) {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.wait_idle()?;
+
+        let fmode: u8 = fmode.into();
+        T::REGS
+            .cr()
+            .modify(|w| w.0 = (w.0 & !(CR_FMODE_MASK | CR_DMAEN)) | (fmode as u32) << CR_FMODE_POS);
+
+        if let Some(len) = len {
+            T::REGS.dlr().write(|w| w.0 = len as u32 - 1);
+        }
+
+        T::REGS.tcr().write(|w| w.0 = self.tcr(transaction));
+        T::REGS.ccr().write(|w| w.0 = ccr(transaction));
+
+        // The transaction starts when the last of the instruction and the address is written,
+        // or on the first data written to DR for writes.
+        if let Some(ab) = transaction.alternate_bytes {
+            T::REGS.abr().write(|w| w.0 = ab);
+        }
+        if let Some(instruction) = transaction.instruction {
+            T::REGS.ir().write(|w| w.0 = instruction);
+        }
+        if let Some(address) = transaction.address {
+            T::REGS.ar().write(|w| w.0 = address);
+        }
+
+        Ok(())
+    }
+
+    fn tcr(&self, transaction: &TransferConfig) -> u32 {
+        let dcyc: u8 = transaction.dummy.into();
+        let mut tcr = dcyc as u32;
+        if self.config.delay_hold_quarter_cycle {
+            tcr |= TCR_DHQC;
+        }
+        if self.config.sample_shifting {
+            tcr |= TCR_SSHIFT;
+        }
+        tcr
+    }
+
+    fn wait_idle(&mut self) -> Result<(), Error> {
+        if self.memory_mapped {
+            return Err(Error::InvalidConfiguration);
+        }
+
+        while T::REGS.sr().read().0 & SR_BUSY != 0 {}
+        T::REGS.fcr().write(|w| w.0 = FCR_ALL);
+        Ok(())
+    }
+
+    fn wait_complete(&mut self) -> Result<(), Error> {
+        loop {
+            let sr = T::REGS.sr().read().0;
+            if sr & SR_TEF != 0 {
+                return Err(self.transfer_error());
+            }
+            if sr & SR_TCF != 0 {
+                break;
+            }
+        }
+
+        T::REGS.fcr().write(|w| w.0 = FCR_ALL);
+        T::REGS.cr().modify(|w| w.0 &= !CR_DMAEN);
+        Ok(())
+    }
+
+    fn transfer_error(&mut self) -> Error {
+        Self::abort();
+        Error::TransferError
+    }
+
+    fn abort() {
+        T::REGS.cr().modify(|w| w.0 |= CR_ABORT);
+        while T::REGS.cr().read().0 & CR_ABORT != 0 {}
+        while T::REGS.sr().read().0 & SR_BUSY != 0 {}
+        T::REGS.fcr().write(|w| w.0 = FCR_ALL);
+        T::REGS.cr().modify(|w| w.0 &= !CR_DMAEN);
+    }
+}
+
+impl<'d, T: Instance, Dma> Drop for Ospi<'d, T, Dma> {
+    fn drop(&mut self) {
+        Self::abort();
+        T::REGS.cr().modify(|w| w.0 &= !CR_EN);
+
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.d0.as_ref().map(|x| x.set_as_disconnected());
+        self.d1.as_ref().map(|x| x.set_as_disconnected());
+        self.d2.as_ref().map(|x| x.set_as_disconnected());
+        self.d3.as_ref().map(|x| x.set_as_disconnected());
+        self.d4.as_ref().map(|x| x.set_as_disconnected());
+        self.d5.as_ref().map(|x| x.set_as_disconnected());
+        self.d6.as_ref().map(|x| x.set_as_disconnected());
+        self.d7.as_ref().map(|x| x.set_as_disconnected());
+        self.nss.as_ref().map(|x| x.set_as_disconnected());
+        self.dqs.as_ref().map(|x| x.set_as_disconnected());
+
+        T::disable();
+    }
+}
+
+/// CCR/WCCR value
+fn ccr(transaction: &TransferConfig) -> u32 {
+    let imode: u8 = transaction.iwidth.into();
+    let isize: u8 = transaction.isize.into();
+    let admode: u8 = transaction.adwidth.into();
+    let adsize: u8 = transaction.adsize.into();
+    let abmode: u8 = transaction.abwidth.into();
+    let absize: u8 = transaction.absize.into();
+    let dmode: u8 = transaction.dwidth.into();
+
+    let mut ccr = (imode as u32)
+        | (transaction.idtr as u32) << 3
+        | (isize as u32) << 4
+        | (admode as u32) << 8
+        | (transaction.addtr as u32) << 11
+        | (adsize as u32) << 12
+        | (abmode as u32) << 16
+        | (transaction.abdtr as u32) << 19
+        | (absize as u32) << 20
+        | (dmode as u32) << 24
+        | (transaction.ddtr as u32) << 27;
+    if transaction.dqse {
+        ccr |= CCR_DQSE;
+    }
+    ccr
+}
+
+pub(crate) mod sealed {
+    use super::*;
+
+    pub trait Instance {
+        const REGS: Regs;
+        const MEMORY_BASE: usize;
+    }
+}
+
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + RccPeripheral {}
+
+pin_trait!(SckPin, Instance);
+pin_trait!(D0Pin, Instance);
+pin_trait!(D1Pin, Instance);
+pin_trait!(D2Pin, Instance);
+pin_trait!(D3Pin, Instance);
+pin_trait!(D4Pin, Instance);
+pin_trait!(D5Pin, Instance);
+pin_trait!(D6Pin, Instance);
+pin_trait!(D7Pin, Instance);
+pin_trait!(NSSPin, Instance);
+pin_trait!(DQSPin, Instance);
+
+dma_trait!(OctoDma, Instance);
+
+macro_rules! memory_base {
+    (OCTOSPI1) => {
+        0x9000_0000
+    };
+    (OCTOSPI2) => {
+        0x7000_0000
+    };
+}
+
+foreach_peripheral!(
+    (octospi, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            const REGS: Regs = crate::pac::$inst;
+            const MEMORY_BASE: usize = memory_base!($inst);
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+);
@@ -0,0 +1,417 @@
+//! PKA, public key accelerator.
+//!
+//! Verifies ECDSA signatures and computes ECDH shared secrets on elliptic curves over prime
+//! fields, such as [`P256`]. The operations run in the background, [`Pka`] waiting for their
+//! completion interrupt.
+//!
+//! The operands are big-endian byte strings, like in SEC 1. The PKA RAM layout of the STM32WB,
+//! STM32WL and STM32L5 is supported; the STM32U5 lays the operands out differently, and isn't
+//! supported yet.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::rcc::RccPeripheral;
+use crate::{interrupt, peripherals, Peripheral};
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// Operating modes
+const MODE_ECC_MUL: u8 = 0x20;
+const MODE_ECDSA_VERIFY: u8 = 0x26;
+
+/// Offset of the PKA RAM, in bytes.
+const RAM_OFFSET: usize = 0x400;
+
+// ECC scalar multiplication operands, byte offsets in the peripheral
+const ECC_MUL_IN_EXP_NB_BITS: usize = 0x400;
+const ECC_MUL_IN_OP_NB_BITS: usize = 0x404;
+const ECC_MUL_IN_A_COEFF_SIGN: usize = 0x408;
+const ECC_MUL_IN_A_COEFF: usize = 0x40c;
+const ECC_MUL_IN_MOD_GF: usize = 0x460;
+const ECC_MUL_IN_K: usize = 0xe94;
+const ECC_MUL_IN_POINT_X: usize = 0x55c;
+const ECC_MUL_IN_POINT_Y: usize = 0x5b0;
+const ECC_MUL_OUT_X: usize = 0x55c;
+const ECC_MUL_OUT_Y: usize = 0x5b0;
+
+// ECDSA verification operands, byte offsets in the peripheral
+const ECDSA_IN_ORDER_NB_BITS: usize = 0x404;
+const ECDSA_IN_MOD_NB_BITS: usize = 0x4b4;
+const ECDSA_IN_A_COEFF_SIGN: usize = 0x45c;
+const ECDSA_IN_A_COEFF: usize = 0x460;
+const ECDSA_IN_MOD_GF: usize = 0x4b8;
+const ECDSA_IN_GENERATOR_X: usize = 0x5e8;
+const ECDSA_IN_GENERATOR_Y: usize = 0x63c;
+const ECDSA_IN_PUBLIC_KEY_X: usize = 0xf40;
+const ECDSA_IN_PUBLIC_KEY_Y: usize = 0xf94;
+const ECDSA_IN_SIGNATURE_R: usize = 0x1098;
+const ECDSA_IN_SIGNATURE_S: usize = 0xa44;
+const ECDSA_IN_HASH_E: usize = 0xfe8;
+const ECDSA_IN_ORDER_N: usize = 0xd5c;
+const ECDSA_OUT_RESULT: usize = 0x5b0;
+
+/// Largest operand, of 521-bit curves, in bytes.
+const MAX_OPERAND_SIZE: usize = 66;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// An operand is larger than the curve parameters, or than the PKA supports.
+    OperandSize,
+    /// The signature doesn't match the hash and the public key.
+    InvalidSignature,
+    /// The PKA RAM was accessed during an operation.
+    Ram,
+    /// The PKA accessed an address outside of its RAM.
+    Address,
+}
+
+/// Parameters of an elliptic curve `y² = x³ + ax + b` over a prime field.
+#[derive(Debug, Copy, Clone)]
+pub struct Curve {
+    /// Prime `p` of the field.
+    pub modulus: &'static [u8],
+    /// Absolute value of the `a` coefficient.
+    pub a: &'static [u8],
+    /// Whether the `a` coefficient is negative.
+    pub a_negative: bool,
+    /// Order `n` of the generator.
+    pub order: &'static [u8],
+    /// Coordinates of the generator `G`.
+    pub generator_x: &'static [u8],
+    pub generator_y: &'static [u8],
+}
+
+/// NIST P-256, also known as secp256r1.
+pub const P256: Curve = Curve {
+    modulus: &[
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    ],
+    a: &[0x03],
+    a_negative: true,
+    order: &[
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xbc, 0xe6,
+        0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+    ],
+    generator_x: &[
+        0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40, 0xf2, 0x77, 0x03,
+        0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98, 0xc2, 0x96,
+    ],
+    generator_y: &[
+        0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c, 0x0f, 0x9e, 0x16, 0x2b, 0xce,
+        0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6, 0x40, 0x68, 0x37, 0xbf, 0x51, 0xf5,
+    ],
+};
+
+impl Curve {
+    /// Size of the field elements, in bytes.
+    pub fn size(&self) -> usize {
+        self.modulus.len()
+    }
+
+    fn modulus_bits(&self) -> u32 {
+        bit_length(self.modulus)
+    }
+
+    fn order_bits(&self) -> u32 {
+        bit_length(self.order)
+    }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        set_interrupts::<T>(false);
+        WAKER.wake();
+    }
+}
+
+/// PKA driver.
+pub struct Pka<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Pka<'d, T> {
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+
+        T::enable();
+        T::reset();
+
+        T::regs().cr().write(|w| w.set_en(true));
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Verify the ECDSA `signature`, the `r` and `s` integers, of `hash` with the public key
+    /// `(public_x, public_y)`.
+    ///
+    /// The hash is truncated to the size of the curve order, as specified by ECDSA.
+    pub async fn ecdsa_verify(
+        &mut self,
+        curve: &Curve,
+        public_x: &[u8],
+        public_y: &[u8],
+        hash: &[u8],
+        signature_r: &[u8],
+        signature_s: &[u8],
+    ) -> Result<(), Error> {
+        let size = curve.size();
+        let order_size = curve.order.len();
+        if size > MAX_OPERAND_SIZE
+            || public_x.len() > size
+            || public_y.len() > size
+            || signature_r.len() > order_size
+            || signature_s.len() > order_size
+        {
+            return Err(Error::OperandSize);
+        }
+        let hash = &hash[..hash.len().min(order_size)];
+
+        wait_idle::<T>();
+
+        let words = operand_words(curve.modulus_bits());
+        let order_words = operand_words(curve.order_bits());
+        write_word::<T>(ECDSA_IN_ORDER_NB_BITS, curve.order_bits());
+        write_word::<T>(ECDSA_IN_MOD_NB_BITS, curve.modulus_bits());
+        write_word::<T>(ECDSA_IN_A_COEFF_SIGN, curve.a_negative as u32);
+        write_operand::<T>(ECDSA_IN_A_COEFF, curve.a, words);
+        write_operand::<T>(ECDSA_IN_MOD_GF, curve.modulus, words);
+        write_operand::<T>(ECDSA_IN_GENERATOR_X, curve.generator_x, words);
+        write_operand::<T>(ECDSA_IN_GENERATOR_Y, curve.generator_y, words);
+        write_operand::<T>(ECDSA_IN_PUBLIC_KEY_X, public_x, words);
+        write_operand::<T>(ECDSA_IN_PUBLIC_KEY_Y, public_y, words);
+        write_operand::<T>(ECDSA_IN_SIGNATURE_R, signature_r, order_words);
+        write_operand::<T>(ECDSA_IN_SIGNATURE_S, signature_s, order_words);
+        write_operand::<T>(ECDSA_IN_HASH_E, hash, order_words);
+        write_operand::<T>(ECDSA_IN_ORDER_N, curve.order, order_words);
+
+        self.run(MODE_ECDSA_VERIFY).await?;
+
+        match read_word::<T>(ECDSA_OUT_RESULT) {
+            0 => Ok(()),
+            _ => Err(Error::InvalidSignature),
+        }
+    }
+
+    /// Multiply the point `(x, y)` of `curve` by the scalar `k`, and write the coordinates of the
+    /// result to `out_x` and `out_y`, of the size of the curve.
+    pub async fn ecc_mul(
+        &mut self,
+        curve: &Curve,
+        k: &[u8],
+        x: &[u8],
+        y: &[u8],
+        out_x: &mut [u8],
+        out_y: &mut [u8],
+    ) -> Result<(), Error> {
+        let size = curve.size();
+        if size > MAX_OPERAND_SIZE
+            || k.len() > curve.order.len()
+            || x.len() > size
+            || y.len() > size
+            || out_x.len() != size
+            || out_y.len() != size
+        {
+            return Err(Error::OperandSize);
+        }
+
+        wait_idle::<T>();
+
+        let words = operand_words(curve.modulus_bits());
+        write_word::<T>(ECC_MUL_IN_EXP_NB_BITS, curve.order_bits());
+        write_word::<T>(ECC_MUL_IN_OP_NB_BITS, curve.modulus_bits());
+        write_word::<T>(ECC_MUL_IN_A_COEFF_SIGN, curve.a_negative as u32);
+        write_operand::<T>(ECC_MUL_IN_A_COEFF, curve.a, words);
+        write_operand::<T>(ECC_MUL_IN_MOD_GF, curve.modulus, words);
+        write_operand::<T>(ECC_MUL_IN_K, k, operand_words(curve.order_bits()));
+        write_operand::<T>(ECC_MUL_IN_POINT_X, x, words);
+        write_operand::<T>(ECC_MUL_IN_POINT_Y, y, words);
+
+        self.run(MODE_ECC_MUL).await?;
+
+        read_operand::<T>(ECC_MUL_OUT_X, out_x);
+        read_operand::<T>(ECC_MUL_OUT_Y, out_y);
+        Ok(())
+    }
+
+    /// Compute the public key of `private_key`, the scalar multiplication of the generator.
+    pub async fn ecc_public_key(
+        &mut self,
+        curve: &Curve,
+        private_key: &[u8],
+        public_x: &mut [u8],
+        public_y: &mut [u8],
+    ) -> Result<(), Error> {
+        self.ecc_mul(
+            curve,
+            private_key,
+            curve.generator_x,
+            curve.generator_y,
+            public_x,
+            public_y,
+        )
+        .await
+    }
+
+    /// Compute the ECDH shared secret of `private_key` and the peer's public key
+    /// `(public_x, public_y)`: the x coordinate of their product, written to `shared_secret`, of
+    /// the size of the curve.
+    ///
+    /// The peer's public key must have been validated, the PKA doesn't check that it's on the
+    /// curve.
+    pub async fn ecdh(
+        &mut self,
+        curve: &Curve,
+        private_key: &[u8],
+        public_x: &[u8],
+        public_y: &[u8],
+        shared_secret: &mut [u8],
+    ) -> Result<(), Error> {
+        let mut y = [0; MAX_OPERAND_SIZE];
+        let y = &mut y[..curve.size().min(MAX_OPERAND_SIZE)];
+        self.ecc_mul(curve, private_key, public_x, public_y, shared_secret, y)
+            .await
+    }
+
+    /// Start the operation of `mode` with the operands in the PKA RAM, and wait for its end.
+    async fn run(&mut self, mode: u8) -> Result<(), Error> {
+        let r = T::regs();
+        clear_flags::<T>();
+        r.cr().modify(|w| w.set_mode(mode));
+        r.cr().modify(|w| w.set_start(true));
+
+        let sr = poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            set_interrupts::<T>(true);
+
+            let sr = r.sr().read();
+            if sr.procendf() || sr.ramerrf() || sr.addrerrf() {
+                Poll::Ready(sr)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        set_interrupts::<T>(false);
+        clear_flags::<T>();
+
+        if sr.ramerrf() {
+            Err(Error::Ram)
+        } else if sr.addrerrf() {
+            Err(Error::Address)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'d, T: Instance> Drop for Pka<'d, T> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        T::regs().cr().write(|_| {});
+        T::disable();
+    }
+}
+
+fn bit_length(n: &[u8]) -> u32 {
+    match n.iter().position(|&b| b != 0) {
+        Some(i) => (n.len() - i) as u32 * 8 - n[i].leading_zeros(),
+        None => 0,
+    }
+}
+
+fn operand_words(bits: u32) -> usize {
+    (bits as usize + 31) / 32
+}
+
+fn set_interrupts<T: Instance>(enabled: bool) {
+    T::regs().cr().modify(|w| {
+        w.set_procendie(enabled);
+        w.set_ramerrie(enabled);
+        w.set_addrerrie(enabled);
+    });
+}
+
+fn clear_flags<T: Instance>() {
+    T::regs().clrfr().write(|w| {
+        w.set_procendfc(true);
+        w.set_ramerrfc(true);
+        w.set_addrerrfc(true);
+    });
+}
+
+/// An operation that was cancelled keeps running, and must end before the RAM is written.
+fn wait_idle<T: Instance>() {
+    while T::regs().sr().read().busy() {}
+}
+
+fn read_word<T: Instance>(address: usize) -> u32 {
+    T::regs().ram((address - RAM_OFFSET) / 4).read()
+}
+
+fn write_word<T: Instance>(address: usize, value: u32) {
+    T::regs().ram((address - RAM_OFFSET) / 4).write_value(value)
+}
+
+/// Write the big-endian `value` to `words` words of the PKA RAM, least significant word first,
+/// followed by the zero word ending the operands.
+fn write_operand<T: Instance>(address: usize, value: &[u8], words: usize) {
+    let byte = |i: usize| if i < value.len() { value[value.len() - 1 - i] } else { 0 };
+    for i in 0..words {
+        let word = u32::from_le_bytes([byte(i * 4), byte(i * 4 + 1), byte(i * 4 + 2), byte(i * 4 + 3)]);
+        write_word::<T>(address + i * 4, word);
+    }
+    write_word::<T>(address + words * 4, 0);
+}
+
+/// Read an operand of the PKA RAM to the big-endian `value`.
+fn read_operand<T: Instance>(address: usize, value: &mut [u8]) {
+    let len = value.len();
+    for (n, byte) in value.iter_mut().enumerate() {
+        let i = len - 1 - n;
+        *byte = (read_word::<T>(address + i / 4 * 4) >> (i % 4 * 8)) as u8;
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::pka::Pka;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_peripheral!(
+    (pka, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::pka::Pka {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$inst;
+        }
+    };
+);
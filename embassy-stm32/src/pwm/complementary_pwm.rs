@@ -7,7 +7,7 @@ use super::simple_pwm::*;
 use super::*;
 #[allow(unused_imports)]
 use crate::gpio::sealed::{AFType, Pin};
-use crate::gpio::AnyPin;
+use crate::gpio::{AnyPin, Pull};
 use crate::time::Hertz;
 use crate::Peripheral;
 
@@ -41,8 +41,27 @@ complementary_channel_impl!(new_ch2, Ch2, Channel2Pin, Channel2ComplementaryPin)
 complementary_channel_impl!(new_ch3, Ch3, Channel3Pin, Channel3ComplementaryPin);
 complementary_channel_impl!(new_ch4, Ch4, Channel4Pin, Channel4ComplementaryPin);
 
+// BDTR bits
+const BDTR_OSSI: u32 = 1 << 10;
+const BDTR_OSSR: u32 = 1 << 11;
+const BDTR_BKE: u32 = 1 << 12;
+const BDTR_BKP: u32 = 1 << 13;
+
+// SR bits
+const SR_BIF: u32 = 1 << 7;
+
+/// Active level of the break input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakPolarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
 pub struct ComplementaryPwm<'d, T> {
     inner: PeripheralRef<'d, T>,
+    freq: Hertz,
+    counting_mode: CountingMode,
 }
 
 impl<'d, T: ComplementaryCaptureCompare16bitInstance> ComplementaryPwm<'d, T> {
@@ -67,7 +86,11 @@ impl<'d, T: ComplementaryCaptureCompare16bitInstance> ComplementaryPwm<'d, T> {
         T::enable();
         <T as crate::rcc::sealed::RccPeripheral>::reset();
 
-        let mut this = Self { inner: tim };
+        let mut this = Self {
+            inner: tim,
+            freq,
+            counting_mode: CountingMode::EdgeAlignedUp,
+        };
 
         this.inner.set_frequency(freq);
         this.inner.start();
@@ -96,7 +119,15 @@ impl<'d, T: ComplementaryCaptureCompare16bitInstance> ComplementaryPwm<'d, T> {
     }
 
     pub fn set_freq(&mut self, freq: Hertz) {
-        self.inner.set_frequency(freq);
+        set_frequency(&mut *self.inner, self.counting_mode, freq);
+        self.freq = freq;
+    }
+
+    /// Set the counting mode, which aligns the PWM pulses on the edges or the center of the
+    /// period, keeping the frequency of the period.
+    pub fn set_counting_mode(&mut self, mode: CountingMode) {
+        set_counting_mode(&mut *self.inner, mode, self.freq);
+        self.counting_mode = mode;
     }
 
     pub fn get_max_duty(&self) -> u16 {
@@ -108,6 +139,51 @@ impl<'d, T: ComplementaryCaptureCompare16bitInstance> ComplementaryPwm<'d, T> {
         self.inner.set_compare_value(channel, duty)
     }
 
+    /// Enable the break input on `pin`.
+    ///
+    /// When the break input is active, the hardware disables the outputs without CPU
+    /// intervention, and drives them to their idle level, low by default. They stay disabled
+    /// until [`resume_after_break()`](Self::resume_after_break) is called, which is the fail-safe
+    /// behavior needed by motor drives on overcurrent.
+    pub fn enable_break_input(&mut self, pin: impl Peripheral<P = impl BreakInputPin<T>> + 'd, polarity: BreakPolarity)
+    where
+        T: CaptureCompare16bitInstance,
+    {
+        into_ref!(pin);
+
+        // Keep the input inactive when nothing drives it.
+        let pull = match polarity {
+            BreakPolarity::ActiveLow => Pull::Up,
+            BreakPolarity::ActiveHigh => Pull::Down,
+        };
+        pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+
+        let r = T::regs_advanced();
+        r.bdtr().modify(|w| {
+            w.0 |= BDTR_OSSI | BDTR_OSSR | BDTR_BKE;
+            match polarity {
+                BreakPolarity::ActiveLow => w.0 &= !BDTR_BKP,
+                BreakPolarity::ActiveHigh => w.0 |= BDTR_BKP,
+            }
+        });
+
+        // The break flag may have been set while configuring the input.
+        r.sr().write(|w| w.0 = !SR_BIF);
+    }
+
+    /// Whether a break disabled the outputs.
+    pub fn is_break_active(&self) -> bool {
+        T::regs_advanced().sr().read().0 & SR_BIF != 0
+    }
+
+    /// Enable the outputs again after a break.
+    ///
+    /// This has no effect while the break input is still active.
+    pub fn resume_after_break(&mut self) {
+        T::regs_advanced().sr().write(|w| w.0 = !SR_BIF);
+        self.inner.enable_outputs(true);
+    }
+
     /// Set the dead time as a proportion of max_duty
     pub fn set_dead_time(&mut self, value: u16) {
         let (ckd, value) = compute_dead_time_value(value);
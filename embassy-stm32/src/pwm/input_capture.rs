@@ -0,0 +1,322 @@
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::simple_pwm::{Ch1, Ch2, Ch3, Ch4};
+use super::*;
+#[allow(unused_imports)]
+use crate::gpio::sealed::{AFType, Pin};
+use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::{interrupt, Peripheral};
+
+// CCMR input bits, per channel of the register
+const CCMR_CCS_MASK: u32 = 0b11;
+const CCMR_ICF_POS: u32 = 4;
+const CCMR_ICF_MASK: u32 = 0b1111 << CCMR_ICF_POS;
+
+// CCER bits, per channel
+const CCER_CCP: u32 = 1 << 1;
+const CCER_CCNP: u32 = 1 << 3;
+
+/// Capture/compare interrupt handler.
+pub struct InterruptHandler<T: InputCaptureInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: InputCaptureInstance> interrupt::typelevel::Handler<T::CaptureCompareInterrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs_gp16();
+        let sr = r.sr().read();
+        let dier = r.dier().read();
+
+        // Mask the channels that captured, their futures read the captured value.
+        for n in 0..4 {
+            if sr.ccif(n + 1) && dier.ccie(n + 1) {
+                r.dier().modify(|w| w.set_ccie(n + 1, false));
+                T::state().wakers[n].wake();
+            }
+        }
+    }
+}
+
+/// Edges captured by an input capture channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputCaptureMode {
+    Rising,
+    Falling,
+    BothEdges,
+}
+
+/// Input of a capture channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputTISelection {
+    /// The pin of the channel.
+    Normal = 1,
+    /// The pin of the other channel of the pair, channel 2 for channel 1 and the reverse.
+    Alternate = 2,
+    /// The trigger input of the slave mode controller.
+    Trc = 3,
+}
+
+pub struct CapturePin<'d, Perip, Channel> {
+    _pin: PeripheralRef<'d, AnyPin>,
+    phantom: PhantomData<(Perip, Channel)>,
+}
+
+macro_rules! channel_impl {
+    ($new_chx:ident, $channel:ident, $pin_trait:ident) => {
+        impl<'d, Perip: CaptureCompare16bitInstance> CapturePin<'d, Perip, $channel> {
+            pub fn $new_chx(pin: impl Peripheral<P = impl $pin_trait<Perip>> + 'd, pull: Pull) -> Self {
+                into_ref!(pin);
+                critical_section::with(|_| {
+                    pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+                    #[cfg(gpio_v2)]
+                    pin.set_speed(crate::gpio::Speed::VeryHigh);
+                });
+                CapturePin {
+                    _pin: pin.map_into(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+channel_impl!(new_ch1, Ch1, Channel1Pin);
+channel_impl!(new_ch2, Ch2, Channel2Pin);
+channel_impl!(new_ch3, Ch3, Channel3Pin);
+channel_impl!(new_ch4, Ch4, Channel4Pin);
+
+/// Input capture driver.
+///
+/// Each channel latches the counter of the timer when an edge is detected on its input, which
+/// timestamps the edges without CPU latency. The counter wraps around, so the time between two
+/// captures is their wrapping difference, as long as it's shorter than the counter period.
+pub struct InputCapture<'d, T> {
+    inner: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: InputCaptureInstance> InputCapture<'d, T> {
+    /// Create an input capture driver whose counter ticks at `freq`.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: Option<CapturePin<'d, T, Ch1>>,
+        _ch2: Option<CapturePin<'d, T, Ch2>>,
+        _ch3: Option<CapturePin<'d, T, Ch3>>,
+        _ch4: Option<CapturePin<'d, T, Ch4>>,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable();
+        <T as crate::rcc::sealed::RccPeripheral>::reset();
+
+        set_tick_frequency::<T>(freq);
+        T::regs_gp16().cr1().modify(|w| w.set_cen(true));
+
+        for channel in [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4] {
+            set_input_ti_selection::<T>(channel, InputTISelection::Normal);
+        }
+
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        Self { inner: tim }
+    }
+
+    /// Enable capturing on `channel`.
+    pub fn enable(&mut self, channel: Channel) {
+        self.inner.enable_channel(channel, true);
+    }
+
+    /// Disable capturing on `channel`.
+    pub fn disable(&mut self, channel: Channel) {
+        self.inner.enable_channel(channel, false);
+    }
+
+    /// Set the edges captured by `channel`.
+    pub fn set_input_capture_mode(&mut self, channel: Channel, mode: InputCaptureMode) {
+        set_input_capture_mode::<T>(channel, mode);
+    }
+
+    /// Set the input of `channel`.
+    pub fn set_input_ti_selection(&mut self, channel: Channel, tisel: InputTISelection) {
+        set_input_ti_selection::<T>(channel, tisel);
+    }
+
+    /// Set the digital filter of `channel`, the ICxF value listed in the reference manual.
+    ///
+    /// The filter ignores pulses shorter than a number of samples, which debounces noisy inputs.
+    pub fn set_input_capture_filter(&mut self, channel: Channel, filter: u8) {
+        set_input_capture_filter::<T>(channel, filter);
+    }
+
+    /// Frequency of the counter.
+    pub fn get_tick_frequency(&self) -> Hertz {
+        let psc = T::regs_gp16().psc().read().psc() as u32;
+        Hertz(T::frequency().0 / (psc + 1))
+    }
+
+    /// Current value of the counter.
+    pub fn get_counter(&self) -> u16 {
+        T::regs_gp16().cnt().read().cnt()
+    }
+
+    /// Last value captured by `channel`.
+    pub fn get_capture_value(&self, channel: Channel) -> u16 {
+        T::regs_gp16().ccr(channel.raw()).read().ccr()
+    }
+
+    /// Wait for a rising edge on `channel`, and return the captured counter value.
+    pub async fn wait_for_rising_edge(&mut self, channel: Channel) -> u16 {
+        self.wait_for_edge(channel, InputCaptureMode::Rising).await
+    }
+
+    /// Wait for a falling edge on `channel`, and return the captured counter value.
+    pub async fn wait_for_falling_edge(&mut self, channel: Channel) -> u16 {
+        self.wait_for_edge(channel, InputCaptureMode::Falling).await
+    }
+
+    /// Wait for an edge on `channel`, and return the captured counter value.
+    pub async fn wait_for_any_edge(&mut self, channel: Channel) -> u16 {
+        self.wait_for_edge(channel, InputCaptureMode::BothEdges).await
+    }
+
+    async fn wait_for_edge(&mut self, channel: Channel, mode: InputCaptureMode) -> u16 {
+        set_input_capture_mode::<T>(channel, mode);
+
+        // Discard the edges captured before the wait.
+        let _ = self.get_capture_value(channel);
+        self.enable(channel);
+
+        wait_for_capture::<T>(channel).await
+    }
+}
+
+impl<'d, T: InputCaptureInstance> Drop for InputCapture<'d, T> {
+    fn drop(&mut self) {
+        T::regs_gp16().dier().modify(|w| w.0 = 0);
+        T::regs_gp16().cr1().modify(|w| w.set_cen(false));
+    }
+}
+
+/// Set the prescaler so that the counter ticks at `freq`, and let it count up to its maximum.
+pub(crate) fn set_tick_frequency<T: InputCaptureInstance>(freq: Hertz) {
+    let psc = T::frequency().0 / freq.0;
+    assert!(psc > 0 && psc <= 0x1_0000);
+
+    let r = T::regs_gp16();
+    r.psc().write(|w| w.set_psc((psc - 1) as u16));
+    r.egr().write(|w| w.set_ug(true));
+}
+
+pub(crate) fn set_input_capture_mode<T: InputCaptureInstance>(channel: Channel, mode: InputCaptureMode) {
+    let bits = match mode {
+        InputCaptureMode::Rising => 0,
+        InputCaptureMode::Falling => CCER_CCP,
+        InputCaptureMode::BothEdges => CCER_CCP | CCER_CCNP,
+    };
+    let pos = channel.raw() * 4;
+    T::regs_gp16()
+        .ccer()
+        .modify(|w| w.0 = (w.0 & !((CCER_CCP | CCER_CCNP) << pos)) | (bits << pos));
+}
+
+pub(crate) fn set_input_capture_filter<T: InputCaptureInstance>(channel: Channel, filter: u8) {
+    assert!(filter < 16);
+    let raw = channel.raw();
+    let pos = (raw % 2) * 8;
+    T::regs_gp16()
+        .ccmr_input(raw / 2)
+        .modify(|w| w.0 = (w.0 & !(CCMR_ICF_MASK << pos)) | ((filter as u32) << (CCMR_ICF_POS + pos)));
+}
+
+/// The capture selection is only writable while the channel is disabled.
+pub(crate) fn set_input_ti_selection<T: InputCaptureInstance>(channel: Channel, tisel: InputTISelection) {
+    let raw = channel.raw();
+    let pos = (raw % 2) * 8;
+    T::regs_gp16()
+        .ccmr_input(raw / 2)
+        .modify(|w| w.0 = (w.0 & !(CCMR_CCS_MASK << pos)) | ((tisel as u32) << pos));
+}
+
+/// Wait for `channel` to capture, and return the captured value.
+pub(crate) async fn wait_for_capture<T: InputCaptureInstance>(channel: Channel) -> u16 {
+    let n = channel.raw();
+    let r = T::regs_gp16();
+
+    let _on_drop = OnDrop::new(|| {
+        critical_section::with(|_| r.dier().modify(|w| w.set_ccie(n + 1, false)));
+    });
+
+    poll_fn(|cx| {
+        T::state().wakers[n].register(cx.waker());
+
+        // Reading the captured value clears the flag.
+        if r.sr().read().ccif(n + 1) {
+            return Poll::Ready(r.ccr(n).read().ccr());
+        }
+
+        critical_section::with(|_| r.dier().modify(|w| w.set_ccie(n + 1, true)));
+        Poll::Pending
+    })
+    .await
+}
+
+pub struct State {
+    pub(crate) wakers: [AtomicWaker; 4],
+}
+
+impl State {
+    pub(crate) const fn new() -> Self {
+        const NEW_AW: AtomicWaker = AtomicWaker::new();
+        Self { wakers: [NEW_AW; 4] }
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait InputCaptureInstance {
+        fn state() -> &'static super::State;
+    }
+}
+
+/// Timer whose channels can capture their inputs.
+pub trait InputCaptureInstance: sealed::InputCaptureInstance + CaptureCompare16bitInstance {
+    type CaptureCompareInterrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_input_capture {
+    ($inst:ident, $irq:ident) => {
+        impl sealed::InputCaptureInstance for crate::peripherals::$inst {
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
+        }
+
+        impl InputCaptureInstance for crate::peripherals::$inst {
+            type CaptureCompareInterrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
+
+foreach_interrupt! {
+    ($inst:ident, timer, TIM_GP16, CC, $irq:ident) => {
+        impl_input_capture!($inst, $irq);
+    };
+    ($inst:ident, timer, TIM_GP32, CC, $irq:ident) => {
+        impl_input_capture!($inst, $irq);
+    };
+    ($inst:ident, timer, TIM_ADV, CC, $irq:ident) => {
+        impl_input_capture!($inst, $irq);
+    };
+}
@@ -1,4 +1,7 @@
 pub mod complementary_pwm;
+pub mod input_capture;
+pub mod pwm_input;
+pub mod qei;
 pub mod simple_pwm;
 
 use stm32_metapac::timer::vals::Ckd;
@@ -54,6 +57,64 @@ impl From<OutputCompareMode> for stm32_metapac::timer::vals::Ocm {
     }
 }
 
+/// Counting mode of the timer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CountingMode {
+    /// The counter counts up, and the PWM pulses start at the beginning of the period.
+    EdgeAlignedUp,
+    /// The counter counts down, and the PWM pulses end at the end of the period.
+    EdgeAlignedDown,
+    /// The counter counts up then down, and the PWM pulses are centered in the period. The
+    /// compare interrupts are raised while counting down.
+    CenterAlignedDownInterrupts,
+    /// The counter counts up then down, and the PWM pulses are centered in the period. The
+    /// compare interrupts are raised while counting up.
+    CenterAlignedUpInterrupts,
+    /// The counter counts up then down, and the PWM pulses are centered in the period. The
+    /// compare interrupts are raised while counting up and down.
+    CenterAlignedBothInterrupts,
+}
+
+impl CountingMode {
+    pub fn is_center_aligned(&self) -> bool {
+        !matches!(self, CountingMode::EdgeAlignedUp | CountingMode::EdgeAlignedDown)
+    }
+
+    /// DIR and CMS bits of CR1.
+    fn cr1_bits(&self) -> u32 {
+        match self {
+            CountingMode::EdgeAlignedUp => 0,
+            CountingMode::EdgeAlignedDown => 1 << 4,
+            CountingMode::CenterAlignedDownInterrupts => 0b01 << 5,
+            CountingMode::CenterAlignedUpInterrupts => 0b10 << 5,
+            CountingMode::CenterAlignedBothInterrupts => 0b11 << 5,
+        }
+    }
+}
+
+/// Set the counting mode, and the frequency of the PWM period.
+///
+/// The counting mode can only be changed while the counter is stopped. A center-aligned period
+/// lasts two counter periods.
+fn set_counting_mode<T: CaptureCompare16bitInstance>(tim: &mut T, mode: CountingMode, freq: crate::time::Hertz) {
+    let r = T::regs_gp16();
+    tim.stop();
+    r.cr1().modify(|w| w.0 = (w.0 & !(0b111 << 4)) | mode.cr1_bits());
+    set_frequency(tim, mode, freq);
+    tim.start();
+}
+
+fn set_frequency<T: CaptureCompare16bitInstance>(tim: &mut T, mode: CountingMode, freq: crate::time::Hertz) {
+    use crate::timer::sealed::Basic16bitInstance;
+
+    let freq = match mode.is_center_aligned() {
+        true => crate::time::Hertz(freq.0 * 2),
+        false => freq,
+    };
+    Basic16bitInstance::set_frequency(tim, freq);
+}
+
 pub(crate) mod sealed {
     use super::*;
 
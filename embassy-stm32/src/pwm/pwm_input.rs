@@ -0,0 +1,153 @@
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use super::input_capture::*;
+use super::*;
+#[allow(unused_imports)]
+use crate::gpio::sealed::{AFType, Pin};
+use crate::gpio::Pull;
+use crate::interrupt::typelevel::Interrupt;
+use crate::time::Hertz;
+use crate::{interrupt, Peripheral};
+
+// SMCR bits
+const SMCR_SMS_RESET: u32 = 0b100;
+const SMCR_TS_POS: u32 = 4;
+const SMCR_TS_TI1FP1: u32 = 0b101;
+const SMCR_TS_TI2FP2: u32 = 0b110;
+
+/// PWM input driver.
+///
+/// Measures the period and the pulse width of a PWM signal on the pin of channel 1 or channel 2.
+/// The rising edges of the signal reset the counter, so that one channel captures the period
+/// and the other one the width of the high pulse, without any CPU intervention.
+pub struct PwmInput<'d, T> {
+    period_channel: Channel,
+    width_channel: Channel,
+    inner: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: InputCaptureInstance> PwmInput<'d, T> {
+    /// Create a PWM input driver measuring the signal on the pin of channel 1, with a counter
+    /// ticking at `freq`.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+            #[cfg(gpio_v2)]
+            pin.set_speed(crate::gpio::Speed::VeryHigh);
+        });
+
+        Self::new_inner(tim, Channel::Ch1, Channel::Ch2, SMCR_TS_TI1FP1, freq)
+    }
+
+    /// Create a PWM input driver measuring the signal on the pin of channel 2, with a counter
+    /// ticking at `freq`.
+    pub fn new_alt(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl Channel2Pin<T>> + 'd,
+        pull: Pull,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+            #[cfg(gpio_v2)]
+            pin.set_speed(crate::gpio::Speed::VeryHigh);
+        });
+
+        Self::new_inner(tim, Channel::Ch2, Channel::Ch1, SMCR_TS_TI2FP2, freq)
+    }
+
+    fn new_inner(
+        tim: impl Peripheral<P = T> + 'd,
+        period_channel: Channel,
+        width_channel: Channel,
+        ts: u32,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable();
+        <T as crate::rcc::sealed::RccPeripheral>::reset();
+
+        set_tick_frequency::<T>(freq);
+
+        // Both channels capture the input of the period channel, on opposite edges.
+        set_input_ti_selection::<T>(period_channel, InputTISelection::Normal);
+        set_input_capture_mode::<T>(period_channel, InputCaptureMode::Rising);
+        set_input_ti_selection::<T>(width_channel, InputTISelection::Alternate);
+        set_input_capture_mode::<T>(width_channel, InputCaptureMode::Falling);
+
+        // Reset the counter on the rising edges.
+        T::regs_gp16()
+            .smcr()
+            .modify(|w| w.0 = (w.0 & !0x0001_0077) | (ts << SMCR_TS_POS) | SMCR_SMS_RESET);
+
+        T::regs_gp16().cr1().modify(|w| w.set_cen(true));
+
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        Self {
+            period_channel,
+            width_channel,
+            inner: tim,
+        }
+    }
+
+    /// Start measuring.
+    pub fn enable(&mut self) {
+        self.inner.enable_channel(self.period_channel, true);
+        self.inner.enable_channel(self.width_channel, true);
+    }
+
+    /// Stop measuring.
+    pub fn disable(&mut self) {
+        self.inner.enable_channel(self.period_channel, false);
+        self.inner.enable_channel(self.width_channel, false);
+    }
+
+    /// Period of the last measured PWM cycle, in counter ticks.
+    ///
+    /// The measurements are updated at each rising edge, and are stale when the signal stops.
+    pub fn get_period_ticks(&self) -> u16 {
+        T::regs_gp16().ccr(self.period_channel.raw()).read().ccr()
+    }
+
+    /// Width of the high pulse of the last measured PWM cycle, in counter ticks.
+    pub fn get_width_ticks(&self) -> u16 {
+        T::regs_gp16().ccr(self.width_channel.raw()).read().ccr()
+    }
+
+    /// Duty cycle of the last measured PWM cycle, between 0 and 1.
+    pub fn get_duty_cycle(&self) -> f32 {
+        let period = self.get_period_ticks();
+        if period == 0 {
+            return 0.;
+        }
+        self.get_width_ticks() as f32 / period as f32
+    }
+
+    /// Wait for the end of a PWM cycle, and return its period and pulse width in counter ticks.
+    pub async fn wait_for_cycle(&mut self) -> (u16, u16) {
+        // Discard the cycles measured before the wait.
+        let _ = self.get_period_ticks();
+
+        let period = wait_for_capture::<T>(self.period_channel).await;
+        (period, self.get_width_ticks())
+    }
+}
+
+impl<'d, T: InputCaptureInstance> Drop for PwmInput<'d, T> {
+    fn drop(&mut self) {
+        T::regs_gp16().dier().modify(|w| w.0 = 0);
+        T::regs_gp16().cr1().modify(|w| w.set_cen(false));
+    }
+}
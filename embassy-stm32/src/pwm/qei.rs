@@ -0,0 +1,206 @@
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use super::input_capture::*;
+use super::simple_pwm::{Ch1, Ch2};
+use super::*;
+#[allow(unused_imports)]
+use crate::gpio::sealed::{AFType, Pin};
+use crate::gpio::{AnyPin, Pull};
+use crate::interrupt::typelevel::Interrupt;
+use crate::{interrupt, Peripheral};
+
+// CR1 bits
+const CR1_DIR: u32 = 1 << 4;
+
+// SMCR bits
+const SMCR_SMS_MASK: u32 = 0x0001_0007;
+const SMCR_SMS_ENCODER_MODE_3: u32 = 0b011;
+
+/// Counting direction of the encoder.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    Upcounting,
+    Downcounting,
+}
+
+pub struct QeiPin<'d, Perip, Channel> {
+    _pin: PeripheralRef<'d, AnyPin>,
+    phantom: PhantomData<(Perip, Channel)>,
+}
+
+macro_rules! channel_impl {
+    ($new_chx:ident, $channel:ident, $pin_trait:ident) => {
+        impl<'d, Perip: CaptureCompare16bitInstance> QeiPin<'d, Perip, $channel> {
+            pub fn $new_chx(pin: impl Peripheral<P = impl $pin_trait<Perip>> + 'd, pull: Pull) -> Self {
+                into_ref!(pin);
+                critical_section::with(|_| {
+                    pin.set_as_af_pull(pin.af_num(), AFType::Input, pull);
+                    #[cfg(gpio_v2)]
+                    pin.set_speed(crate::gpio::Speed::VeryHigh);
+                });
+                QeiPin {
+                    _pin: pin.map_into(),
+                    phantom: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+channel_impl!(new_ch1, Ch1, Channel1Pin);
+channel_impl!(new_ch2, Ch2, Channel2Pin);
+
+/// Quadrature encoder interface driver.
+///
+/// The timer counts the edges of the A and B signals of the encoder, on the pins of channel 1
+/// and channel 2, up or down depending on their phase. The hardware counter is 16 bits wide, and
+/// [`position()`](Qei::position) extends it to 64 bits, by accumulating its variations since the
+/// previous call. It must be called at least once every 32768 counts so that no wrap around is
+/// missed, which [`wait_for_delta()`](Qei::wait_for_delta) does.
+pub struct Qei<'d, T> {
+    _inner: PeripheralRef<'d, T>,
+    last_count: u16,
+    position: i64,
+}
+
+impl<'d, T: InputCaptureInstance> Qei<'d, T> {
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: QeiPin<'d, T, Ch1>,
+        _ch2: QeiPin<'d, T, Ch2>,
+        _irq: impl interrupt::typelevel::Binding<T::CaptureCompareInterrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(tim);
+
+        T::enable();
+        <T as crate::rcc::sealed::RccPeripheral>::reset();
+
+        let r = T::regs_gp16();
+
+        // Count the edges of both inputs, unfiltered and not inverted.
+        set_input_ti_selection::<T>(Channel::Ch1, InputTISelection::Normal);
+        set_input_ti_selection::<T>(Channel::Ch2, InputTISelection::Normal);
+        set_input_capture_mode::<T>(Channel::Ch1, InputCaptureMode::Rising);
+        set_input_capture_mode::<T>(Channel::Ch2, InputCaptureMode::Rising);
+        r.smcr()
+            .modify(|w| w.0 = (w.0 & !SMCR_SMS_MASK) | SMCR_SMS_ENCODER_MODE_3);
+
+        r.arr().write(|w| w.set_arr(u16::MAX));
+        r.cnt().write(|w| w.set_cnt(0));
+        r.cr1().modify(|w| w.set_cen(true));
+
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        Self {
+            _inner: tim,
+            last_count: 0,
+            position: 0,
+        }
+    }
+
+    /// Set the digital filter of both inputs, the ICxF value listed in the reference manual.
+    ///
+    /// The filter ignores pulses shorter than a number of samples, which rejects the bounces of
+    /// mechanical encoders.
+    pub fn set_input_filter(&mut self, filter: u8) {
+        set_input_capture_filter::<T>(Channel::Ch1, filter);
+        set_input_capture_filter::<T>(Channel::Ch2, filter);
+    }
+
+    /// Value of the hardware counter.
+    pub fn count(&self) -> u16 {
+        T::regs_gp16().cnt().read().cnt()
+    }
+
+    /// Direction of the last count.
+    pub fn direction(&self) -> Direction {
+        if T::regs_gp16().cr1().read().0 & CR1_DIR != 0 {
+            Direction::Downcounting
+        } else {
+            Direction::Upcounting
+        }
+    }
+
+    /// Position of the encoder, extended to 64 bits.
+    pub fn position(&mut self) -> i64 {
+        let count = self.count();
+        self.position += count.wrapping_sub(self.last_count) as i16 as i64;
+        self.last_count = count;
+        self.position
+    }
+
+    /// Set the position of the encoder.
+    pub fn set_position(&mut self, position: i64) {
+        self.last_count = self.count();
+        self.position = position;
+    }
+
+    /// Wait for the encoder to move by at least `delta` counts from its current position, in
+    /// either direction, and return the new position.
+    ///
+    /// `delta` must be between 1 and 32767.
+    pub async fn wait_for_delta(&mut self, delta: u16) -> i64 {
+        assert!(delta > 0 && delta <= i16::MAX as u16);
+
+        let r = T::regs_gp16();
+        self.position();
+
+        // Channels 3 and 4 compare the counter to the positions that end the wait, in both
+        // directions.
+        r.ccr(2).write(|w| w.set_ccr(self.last_count.wrapping_add(delta)));
+        r.ccr(3).write(|w| w.set_ccr(self.last_count.wrapping_sub(delta)));
+        r.sr().write(|w| w.0 = !((1 << 3) | (1 << 4)));
+
+        let _on_drop = OnDrop::new(|| {
+            critical_section::with(|_| {
+                r.dier().modify(|w| {
+                    w.set_ccie(3, false);
+                    w.set_ccie(4, false);
+                })
+            });
+        });
+
+        poll_fn(|cx| {
+            T::state().wakers[2].register(cx.waker());
+            T::state().wakers[3].register(cx.waker());
+
+            let sr = r.sr().read();
+            if sr.ccif(3) || sr.ccif(4) {
+                return Poll::Ready(());
+            }
+
+            // The counter may have gone past a compare value before it was set.
+            let count = r.cnt().read().cnt();
+            if count.wrapping_sub(self.last_count) as i16 as i32 >= delta as i32
+                || self.last_count.wrapping_sub(count) as i16 as i32 >= delta as i32
+            {
+                return Poll::Ready(());
+            }
+
+            critical_section::with(|_| {
+                r.dier().modify(|w| {
+                    w.set_ccie(3, true);
+                    w.set_ccie(4, true);
+                })
+            });
+            Poll::Pending
+        })
+        .await;
+
+        self.position()
+    }
+}
+
+impl<'d, T: InputCaptureInstance> Drop for Qei<'d, T> {
+    fn drop(&mut self) {
+        T::regs_gp16().dier().modify(|w| w.0 = 0);
+        T::regs_gp16().cr1().modify(|w| w.set_cen(false));
+    }
+}
@@ -46,6 +46,8 @@ channel_impl!(new_ch4, Ch4, Channel4Pin);
 
 pub struct SimplePwm<'d, T> {
     inner: PeripheralRef<'d, T>,
+    freq: Hertz,
+    counting_mode: CountingMode,
 }
 
 impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
@@ -66,7 +68,11 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
         T::enable();
         <T as crate::rcc::sealed::RccPeripheral>::reset();
 
-        let mut this = Self { inner: tim };
+        let mut this = Self {
+            inner: tim,
+            freq,
+            counting_mode: CountingMode::EdgeAlignedUp,
+        };
 
         this.inner.set_frequency(freq);
         this.inner.start();
@@ -93,7 +99,15 @@ impl<'d, T: CaptureCompare16bitInstance> SimplePwm<'d, T> {
     }
 
     pub fn set_freq(&mut self, freq: Hertz) {
-        self.inner.set_frequency(freq);
+        set_frequency(&mut *self.inner, self.counting_mode, freq);
+        self.freq = freq;
+    }
+
+    /// Set the counting mode, which aligns the PWM pulses on the edges or the center of the
+    /// period, keeping the frequency of the period.
+    pub fn set_counting_mode(&mut self, mode: CountingMode) {
+        set_counting_mode(&mut *self.inner, mode, self.freq);
+        self.counting_mode = mode;
     }
 
     pub fn get_max_duty(&self) -> u16 {
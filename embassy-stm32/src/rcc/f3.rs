@@ -10,6 +10,17 @@ pub const HSI_FREQ: Hertz = Hertz(8_000_000);
 /// LSI speed
 pub const LSI_FREQ: Hertz = Hertz(40_000);
 
+/// HRTIM clock source
+#[cfg(hrtim)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum HrtimClockSrc {
+    /// APB2 clock
+    #[default]
+    BusClk,
+    /// Twice the PLL output, 144 MHz at most, for the highest resolution
+    PllClk,
+}
+
 /// Clocks configutation
 #[non_exhaustive]
 #[derive(Default)]
@@ -36,6 +47,10 @@ pub struct Config {
     /// - The System clock frequency is either 48MHz or 72MHz
     /// - APB1 clock has a minimum frequency of 10MHz
     pub pll48: bool,
+    /// HRTIM clock source. The PLL must be the system clock, with an AHB prescaler of 1, to
+    /// clock the HRTIM.
+    #[cfg(hrtim)]
+    pub hrtim: HrtimClockSrc,
 }
 
 // Information required to setup the PLL clock
@@ -148,6 +163,17 @@ pub(crate) unsafe fn init(config: Config) {
         });
     }
 
+    // CFGR3 HRTIM1SW
+    #[cfg(hrtim)]
+    let hrtim = match config.hrtim {
+        HrtimClockSrc::BusClk => None,
+        HrtimClockSrc::PllClk => {
+            assert!(pll_config.is_some() && hpre_div == 1);
+            RCC.cfgr3().modify(|w| w.0 |= 1 << 12);
+            Some(Hertz(sysclk * 2))
+        }
+    };
+
     // Set prescalers
     // CFGR has been written before (PLL, PLL48) don't overwrite these settings
     RCC.cfgr().modify(|w| {
@@ -177,6 +203,8 @@ pub(crate) unsafe fn init(config: Config) {
         apb1_tim: Hertz(pclk1 * timer_mul1),
         apb2_tim: Hertz(pclk2 * timer_mul2),
         ahb1: Hertz(hclk),
+        #[cfg(hrtim)]
+        hrtim,
     });
 }
 
@@ -69,6 +69,9 @@ pub struct Clocks {
     #[cfg(stm32f1)]
     pub adc: Hertz,
 
+    #[cfg(all(rcc_f3, hrtim))]
+    pub hrtim: Option<Hertz>,
+
     #[cfg(any(rcc_h5, rcc_h50, rcc_h7, rcc_h7ab))]
     pub adc: Option<Hertz>,
 }
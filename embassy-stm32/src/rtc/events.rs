@@ -0,0 +1,514 @@
+//! RTC alarms, periodic wakeup timer and tamper inputs.
+//!
+//! The RTC events reach the NVIC through EXTI lines, so the tasks waiting for them are woken up
+//! from Stop mode too.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_common::drop::OnDrop;
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::{byte_to_bcd2, Instance, Rtc};
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::rtc::regs::{Alrmr, Isr};
+use crate::pac::rtc::vals::{AlrmrMsk, Wucksel};
+use crate::pac::EXTI;
+use crate::{interrupt, pac};
+
+// EXTI lines of the RTC events
+#[cfg(any(rtc_v2f2, rtc_v2f4, rtc_v2f7))]
+const EXTI_ALARM: usize = 17;
+#[cfg(any(rtc_v2f2, rtc_v2f4, rtc_v2f7))]
+const EXTI_TAMPER: usize = 21;
+#[cfg(any(rtc_v2f2, rtc_v2f4, rtc_v2f7))]
+const EXTI_WAKEUP: usize = 22;
+#[cfg(rtc_v2l4)]
+const EXTI_ALARM: usize = 18;
+#[cfg(rtc_v2l4)]
+const EXTI_TAMPER: usize = 19;
+#[cfg(rtc_v2l4)]
+const EXTI_WAKEUP: usize = 20;
+
+static ALARM_WAKER: AtomicWaker = AtomicWaker::new();
+static WAKEUP_WAKER: AtomicWaker = AtomicWaker::new();
+static TAMPER_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Mask the EXTI line that triggered the interrupt, the futures check the RTC flags.
+unsafe fn on_exti_interrupt(line: usize, waker: &AtomicWaker) {
+    EXTI.imr(0).modify(|w| w.set_line(line, false));
+    EXTI.pr(0).write(|w| w.set_line(line, true));
+    waker.wake();
+}
+
+/// Trigger the EXTI line of an RTC event on its rising edge.
+fn enable_exti_line(line: usize) {
+    critical_section::with(|_| {
+        EXTI.rtsr(0).modify(|w| w.set_line(line, true));
+        EXTI.pr(0).write(|w| w.set_line(line, true));
+    });
+}
+
+fn unmask_exti_line(line: usize) {
+    critical_section::with(|_| EXTI.imr(0).modify(|w| w.set_line(line, true)));
+}
+
+fn mask_exti_line(line: usize) {
+    critical_section::with(|_| EXTI.imr(0).modify(|w| w.set_line(line, false)));
+}
+
+/// Clear the ISR `flags`, which are cleared by writing 0, without leaving the initialization
+/// mode.
+fn clear_isr_flags(r: &pac::rtc::Rtc, flags: Isr) {
+    let init = r.isr().read().init();
+    let mut w = Isr(!flags.0);
+    w.set_init(init);
+    r.isr().write_value(w);
+}
+
+/// TAFCR, named TAMPCR on L4.
+#[cfg(rtc_v2l4)]
+fn tafcr(r: &pac::rtc::Rtc) -> pac::common::Reg<pac::rtc::regs::Tampcr, pac::common::RW> {
+    r.tampcr()
+}
+
+#[cfg(not(rtc_v2l4))]
+fn tafcr(r: &pac::rtc::Rtc) -> pac::common::Reg<pac::rtc::regs::Tafcr, pac::common::RW> {
+    r.tafcr()
+}
+
+/// RTC alarm interrupt handler.
+pub struct AlarmInterruptHandler {}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RTC_ALARM> for AlarmInterruptHandler {
+    unsafe fn on_interrupt() {
+        on_exti_interrupt(EXTI_ALARM, &ALARM_WAKER);
+    }
+}
+
+/// RTC wakeup timer interrupt handler.
+pub struct WakeupInterruptHandler {}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RTC_WKUP> for WakeupInterruptHandler {
+    unsafe fn on_interrupt() {
+        on_exti_interrupt(EXTI_WAKEUP, &WAKEUP_WAKER);
+    }
+}
+
+/// RTC tamper interrupt handler.
+pub struct TamperInterruptHandler {}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::TAMP_STAMP> for TamperInterruptHandler {
+    unsafe fn on_interrupt() {
+        on_exti_interrupt(EXTI_TAMPER, &TAMPER_WAKER);
+    }
+}
+
+/// RTC alarm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    A,
+    B,
+}
+
+impl Alarm {
+    fn index(self) -> usize {
+        match self {
+            Alarm::A => 0,
+            Alarm::B => 1,
+        }
+    }
+}
+
+/// Time matched by an alarm.
+///
+/// The fields set to `None` are not compared, so that an alarm with only `second` set fires
+/// every minute, and an alarm with no field set fires every second.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlarmTime {
+    /// Day of the month, 1 to 31.
+    pub day: Option<u8>,
+    /// Hour, 0 to 23.
+    pub hour: Option<u8>,
+    /// Minute, 0 to 59.
+    pub minute: Option<u8>,
+    /// Second, 0 to 59.
+    pub second: Option<u8>,
+}
+
+impl AlarmTime {
+    fn to_alrmr(&self) -> Alrmr {
+        /// The mask of the field, and its tens and units digits.
+        fn field(value: Option<u8>, max: u8) -> (AlrmrMsk, u8, u8) {
+            match value {
+                Some(value) => {
+                    assert!(value <= max);
+                    let (tens, bcd) = byte_to_bcd2(value);
+                    (AlrmrMsk::NOTMASK, tens, bcd & 0xf)
+                }
+                None => (AlrmrMsk::MASK, 0, 0),
+            }
+        }
+
+        if let Some(day) = self.day {
+            assert!(day >= 1);
+        }
+
+        let mut w = Alrmr(0);
+        let (msk, tens, units) = field(self.second, 59);
+        w.set_msk1(msk);
+        w.set_st(tens);
+        w.set_su(units);
+        let (msk, tens, units) = field(self.minute, 59);
+        w.set_msk2(msk);
+        w.set_mnt(tens);
+        w.set_mnu(units);
+        let (msk, tens, units) = field(self.hour, 23);
+        w.set_msk3(msk);
+        w.set_ht(tens);
+        w.set_hu(units);
+        let (msk, tens, units) = field(self.day, 31);
+        w.set_msk4(msk);
+        w.set_dt(tens);
+        w.set_du(units);
+        w
+    }
+}
+
+/// Tamper input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TamperInput {
+    /// RTC_TAMP1 input.
+    Tamper1,
+    /// RTC_TAMP2 input.
+    Tamper2,
+    /// RTC_TAMP3 input.
+    #[cfg(rtc_v2l4)]
+    Tamper3,
+}
+
+impl TamperInput {
+    fn index(self) -> usize {
+        match self {
+            TamperInput::Tamper1 => 0,
+            TamperInput::Tamper2 => 1,
+            #[cfg(rtc_v2l4)]
+            TamperInput::Tamper3 => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => TamperInput::Tamper1,
+            1 => TamperInput::Tamper2,
+            #[cfg(rtc_v2l4)]
+            2 => TamperInput::Tamper3,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Level of a tamper input that detects a tampering.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TamperLevel {
+    /// A rising edge detects a tampering.
+    High,
+    /// A falling edge detects a tampering.
+    Low,
+}
+
+impl<'d, T: Instance> Rtc<'d, T> {
+    /// Set `alarm` to fire when the calendar matches `time`, and enable it.
+    pub fn set_alarm(
+        &mut self,
+        alarm: Alarm,
+        time: AlarmTime,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RTC_ALARM, AlarmInterruptHandler>,
+    ) {
+        let n = alarm.index();
+        let alrmr = time.to_alrmr();
+
+        enable_exti_line(EXTI_ALARM);
+
+        self.write(false, |r| {
+            // The alarm register is only writable while the alarm is disabled.
+            r.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+            while !r.isr().read().alrwf(n) {}
+
+            r.alrmr(n).write_value(alrmr);
+
+            clear_isr_flags(r, alarm_flag(n));
+            r.cr().modify(|w| {
+                w.set_alre(n, true);
+                w.set_alrie(n, true);
+            });
+        });
+
+        interrupt::typelevel::RTC_ALARM::unpend();
+        unsafe { interrupt::typelevel::RTC_ALARM::enable() };
+    }
+
+    /// Disable `alarm`.
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        let n = alarm.index();
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+            clear_isr_flags(r, alarm_flag(n));
+        });
+    }
+
+    /// Wait for `alarm` to fire.
+    ///
+    /// The alarm must have been set with [`set_alarm()`](Rtc::set_alarm), and stays enabled: it
+    /// fires again at the next match of its time.
+    pub async fn wait_for_alarm(&mut self, alarm: Alarm) {
+        wait_for_flag::<T>(EXTI_ALARM, &ALARM_WAKER, alarm_flag(alarm.index())).await;
+    }
+
+    /// Start the periodic wakeup timer, which fires every `period_secs` seconds, between 1 and
+    /// 65536.
+    pub fn start_wakeup_timer(
+        &mut self,
+        period_secs: u32,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RTC_WKUP, WakeupInterruptHandler>,
+    ) {
+        assert!(period_secs >= 1 && period_secs <= 0x1_0000);
+
+        // The timer counts the 1 Hz ck_spre clock.
+        self.set_wakeup_timer(Wucksel::CLOCKSPARE, (period_secs - 1) as u16);
+
+        interrupt::typelevel::RTC_WKUP::unpend();
+        unsafe { interrupt::typelevel::RTC_WKUP::enable() };
+    }
+
+    fn set_wakeup_timer(&mut self, wucksel: Wucksel, wut: u16) {
+        enable_exti_line(EXTI_WAKEUP);
+
+        self.write(false, |r| {
+            // The reload value is only writable while the timer is disabled.
+            r.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+            while !r.isr().read().wutwf() {}
+
+            r.wutr().write(|w| w.set_wut(wut));
+            r.cr().modify(|w| w.set_wucksel(wucksel));
+
+            clear_isr_flags(r, wakeup_flag());
+            r.cr().modify(|w| {
+                w.set_wute(true);
+                w.set_wutie(true);
+            });
+        });
+    }
+
+    /// Stop the periodic wakeup timer.
+    pub fn stop_wakeup_timer(&mut self) {
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+            clear_isr_flags(r, wakeup_flag());
+        });
+    }
+
+    /// Wait for the next period of the wakeup timer started with
+    /// [`start_wakeup_timer()`](Rtc::start_wakeup_timer).
+    pub async fn wait_for_wakeup(&mut self) {
+        wait_for_flag::<T>(EXTI_WAKEUP, &WAKEUP_WAKER, wakeup_flag()).await;
+    }
+
+    /// Enable the detection of tamperings on `input`, for example a switch opened with the
+    /// enclosure of the device.
+    ///
+    /// The tamper input is an additional function of its pin, which doesn't need to be
+    /// configured. A tampering erases the backup registers.
+    pub fn enable_tamper(
+        &mut self,
+        input: TamperInput,
+        level: TamperLevel,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::TAMP_STAMP, TamperInterruptHandler>,
+    ) {
+        enable_exti_line(EXTI_TAMPER);
+
+        let r = T::regs();
+        let trg = level == TamperLevel::Low;
+
+        // TAFCR isn't write protected, but the trigger is only writable while the input is
+        // disabled.
+        tafcr(&r).modify(|w| match input {
+            TamperInput::Tamper1 => {
+                w.set_tamp1e(false);
+                w.set_tamp1trg(trg);
+            }
+            TamperInput::Tamper2 => {
+                w.set_tamp2e(false);
+                w.set_tamp2trg(trg);
+            }
+            #[cfg(rtc_v2l4)]
+            TamperInput::Tamper3 => {
+                w.set_tamp3e(false);
+                w.set_tamp3trg(trg);
+            }
+        });
+        clear_isr_flags(&r, tamper_flags([input.index()]));
+        tafcr(&r).modify(|w| {
+            set_tamper_enabled(w, input, true);
+            w.set_tampie(true);
+        });
+
+        interrupt::typelevel::TAMP_STAMP::unpend();
+        unsafe { interrupt::typelevel::TAMP_STAMP::enable() };
+    }
+
+    /// Disable the detection of tamperings on `input`.
+    pub fn disable_tamper(&mut self, input: TamperInput) {
+        let r = T::regs();
+        tafcr(&r).modify(|w| set_tamper_enabled(w, input, false));
+        clear_isr_flags(&r, tamper_flags([input.index()]));
+    }
+
+    /// Wait for a tampering on one of the inputs enabled with
+    /// [`enable_tamper()`](Rtc::enable_tamper), and return that input.
+    pub async fn wait_for_tamper(&mut self) -> TamperInput {
+        #[cfg(rtc_v2l4)]
+        const INPUTS: usize = 3;
+        #[cfg(not(rtc_v2l4))]
+        const INPUTS: usize = 2;
+        let flags = tamper_flags(0..INPUTS);
+
+        let isr = wait_for_flag::<T>(EXTI_TAMPER, &TAMPER_WAKER, flags).await;
+        let n = unwrap!((0..INPUTS).find(|n| isr.tampf(*n)));
+        TamperInput::from_index(n)
+    }
+}
+
+fn alarm_flag(n: usize) -> Isr {
+    let mut flags = Isr(0);
+    flags.set_alrf(n, true);
+    flags
+}
+
+fn wakeup_flag() -> Isr {
+    let mut flags = Isr(0);
+    flags.set_wutf(true);
+    flags
+}
+
+fn tamper_flags(inputs: impl IntoIterator<Item = usize>) -> Isr {
+    let mut flags = Isr(0);
+    for n in inputs {
+        flags.set_tampf(n, true);
+    }
+    flags
+}
+
+#[cfg(rtc_v2l4)]
+fn set_tamper_enabled(w: &mut pac::rtc::regs::Tampcr, input: TamperInput, enabled: bool) {
+    match input {
+        TamperInput::Tamper1 => w.set_tamp1e(enabled),
+        TamperInput::Tamper2 => w.set_tamp2e(enabled),
+        TamperInput::Tamper3 => w.set_tamp3e(enabled),
+    }
+}
+
+#[cfg(not(rtc_v2l4))]
+fn set_tamper_enabled(w: &mut pac::rtc::regs::Tafcr, input: TamperInput, enabled: bool) {
+    match input {
+        TamperInput::Tamper1 => w.set_tamp1e(enabled),
+        TamperInput::Tamper2 => w.set_tamp2e(enabled),
+    }
+}
+
+/// Wait for one of the ISR `flags`, clear it, and return the ISR value.
+async fn wait_for_flag<T: Instance>(line: usize, waker: &AtomicWaker, flags: Isr) -> Isr {
+    let r = T::regs();
+
+    let _on_drop = OnDrop::new(|| mask_exti_line(line));
+
+    poll_fn(|cx| {
+        waker.register(cx.waker());
+
+        // Unmask before checking the flags, so that a flag set in between triggers the interrupt.
+        unmask_exti_line(line);
+
+        let isr = r.isr().read();
+        let pending = isr.0 & flags.0;
+        if pending != 0 {
+            // Only clear the flag that is returned, the others are returned by the next wait.
+            clear_isr_flags(&r, Isr(1 << pending.trailing_zeros()));
+            return Poll::Ready(isr);
+        }
+
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(feature = "low-power")]
+impl<'d, T: Instance> Rtc<'d, T> {
+    /// Frequency of RTCCLK, assuming that the prescalers divide it down to 1 Hz.
+    fn rtcclk_hz(&self) -> u64 {
+        (self.rtc_config.async_prescaler as u64 + 1) * (self.rtc_config.sync_prescaler as u64 + 1)
+    }
+
+    /// Start the wakeup timer to wake the MCU up from Stop mode after `ticks` of the time
+    /// driver, or after the longest period of the timer, about 32 seconds.
+    pub(crate) fn start_wakeup_alarm(&mut self, ticks: u64) {
+        // The timer counts RTCCLK/16, for a resolution of about 0.5 ms.
+        let wut = ticks * self.rtcclk_hz() / 16 / embassy_time::TICK_HZ;
+        self.set_wakeup_timer(Wucksel::DIV16, wut.clamp(1, 0x1_0000) as u16 - 1);
+
+        // The interrupt handler masks the line.
+        unmask_exti_line(EXTI_WAKEUP);
+    }
+
+    /// Start the wakeup timer to wake the MCU up from Standby mode after `secs` seconds.
+    pub(crate) fn start_wakeup_alarm_secs(&mut self, secs: u32) {
+        assert!(secs >= 1 && secs <= 0x1_0000);
+        self.set_wakeup_timer(Wucksel::CLOCKSPARE, (secs - 1) as u16);
+    }
+
+    pub(crate) fn stop_wakeup_alarm(&mut self) {
+        mask_exti_line(EXTI_WAKEUP);
+        self.stop_wakeup_timer();
+    }
+
+    /// Time of the day, in ticks of the time driver.
+    pub(crate) fn time_of_day_ticks(&self) -> u64 {
+        let r = T::regs();
+
+        // The calendar shadow registers are resynchronized after a wakeup from Stop mode.
+        let mut rsf = Isr(0);
+        rsf.set_rsf(true);
+        clear_isr_flags(&r, rsf);
+        while !r.isr().read().rsf() {}
+
+        // Reading SSR locks TR until DR is read.
+        let ss = r.ssr().read().ss() as u64;
+        let tr = r.tr().read();
+        let _ = r.dr().read();
+
+        let second = super::bcd2_to_byte((tr.st(), tr.su())) as u64;
+        let minute = super::bcd2_to_byte((tr.mnt(), tr.mnu())) as u64;
+        let hour = super::bcd2_to_byte((tr.ht(), tr.hu())) as u64;
+
+        // The sub-second counter counts down from the synchronous prescaler value.
+        let prediv_s = self.rtc_config.sync_prescaler as u64;
+        let subsecond = prediv_s.saturating_sub(ss) * embassy_time::TICK_HZ / (prediv_s + 1);
+
+        ((hour * 60 + minute) * 60 + second) * embassy_time::TICK_HZ + subsecond
+    }
+}
@@ -1,8 +1,12 @@
 //! RTC peripheral abstraction
 use core::marker::PhantomData;
 mod datetime;
+#[cfg(any(rtc_v2f2, rtc_v2f4, rtc_v2f7, rtc_v2l4))]
+mod events;
 
 pub use self::datetime::{DateTime, DayOfWeek, Error as DateTimeError};
+#[cfg(any(rtc_v2f2, rtc_v2f4, rtc_v2f7, rtc_v2l4))]
+pub use self::events::*;
 
 /// refer to AN4759 to compare features of RTC2 and RTC3
 #[cfg_attr(any(rtc_v1), path = "v1.rs")]
@@ -0,0 +1,612 @@
+//! Serial Audio Interface (SAI)
+//!
+//! Each SAI has two sub-blocks, A and B, which are independent audio interfaces: each one can
+//! be a master or a slave, transmit or receive, with the I2S or TDM protocols. A sub-block can
+//! also be synchronous with the other one, sharing its SCK and FS signals, for example to
+//! transmit and receive the same stream of frames with an audio codec.
+//!
+//! The audio samples go through a circular DMA buffer: [`Sai::write`] and [`Sai::read`] copy
+//! samples to and from it while the DMA keeps the sub-block fed, so the buffer must be large
+//! enough to cover the time between two calls.
+
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+
+use crate::dma::ringbuffer::OverrunError;
+use crate::dma::word::Word;
+use crate::dma::{Channel, RingBuffer};
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::{AnyPin, Speed};
+use crate::rcc::RccPeripheral;
+use crate::{peripherals, Peripheral};
+
+// CR1 bits
+const CR1_MODE_POS: u32 = 0;
+const CR1_DS_POS: u32 = 5;
+const CR1_CKSTR: u32 = 1 << 9;
+const CR1_SYNCEN_POS: u32 = 10;
+const CR1_MONO: u32 = 1 << 12;
+const CR1_SAIEN: u32 = 1 << 16;
+const CR1_DMAEN: u32 = 1 << 17;
+const CR1_MCKDIV_POS: u32 = 20;
+#[cfg(not(any(sai_v1, sai_v2)))]
+const CR1_MCKEN: u32 = 1 << 27;
+
+// CR2 bits
+const CR2_FFLUSH: u32 = 1 << 3;
+
+// FRCR bits
+const FRCR_FSALL_POS: u32 = 8;
+const FRCR_FSDEF: u32 = 1 << 16;
+const FRCR_FSPOL: u32 = 1 << 17;
+const FRCR_FSOFF: u32 = 1 << 18;
+
+// SLOTR bits
+const SLOTR_SLOTSZ_POS: u32 = 6;
+const SLOTR_NBSLOT_POS: u32 = 8;
+const SLOTR_SLOTEN_POS: u32 = 16;
+
+// SR and CLRFR bits
+const SR_OVRUDR: u32 = 1 << 0;
+const CLRFR_ALL: u32 = 0x77;
+
+/// SAI error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// [`Sai::write`] was called on a receiver.
+    NotATransmitter,
+    /// [`Sai::read`] was called on a transmitter.
+    NotAReceiver,
+    /// The samples weren't read or written in time, and some were lost.
+    Overrun,
+}
+
+impl From<OverrunError> for Error {
+    fn from(_: OverrunError) -> Self {
+        Self::Overrun
+    }
+}
+
+/// Whether the sub-block generates the SCK and FS signals.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    Master,
+    Slave,
+}
+
+/// Direction of the samples.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxRx {
+    Transmitter,
+    Receiver,
+}
+
+/// Audio protocol
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// I2S (Philips) standard: two slots, left and right, with FS low during the left one.
+    I2s,
+    /// TDM (DSP mode A): 1 to 16 slots, with a one bit FS pulse before the first one.
+    Tdm { slots: u8 },
+}
+
+/// Number of bits of each sample.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataSize {
+    Data8,
+    Data10,
+    Data16,
+    Data20,
+    Data24,
+    Data32,
+}
+
+impl DataSize {
+    fn ds(&self) -> u32 {
+        match self {
+            DataSize::Data8 => 0b010,
+            DataSize::Data10 => 0b011,
+            DataSize::Data16 => 0b100,
+            DataSize::Data20 => 0b101,
+            DataSize::Data24 => 0b110,
+            DataSize::Data32 => 0b111,
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        match self {
+            DataSize::Data8 => 8,
+            DataSize::Data10 => 10,
+            DataSize::Data16 => 16,
+            DataSize::Data20 => 20,
+            DataSize::Data24 => 24,
+            DataSize::Data32 => 32,
+        }
+    }
+}
+
+/// Number of bits of each slot, at least the data size.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlotSize {
+    /// The data size
+    DataSize,
+    Channel16,
+    Channel32,
+}
+
+/// SAI configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    pub mode: Mode,
+    pub tx_rx: TxRx,
+    pub protocol: Protocol,
+    pub data_size: DataSize,
+    pub slot_size: SlotSize,
+    /// Transmit the same sample in both slots, for the I2S protocol.
+    pub mono: bool,
+    /// Master clock divider (MCKDIV).
+    ///
+    /// The master clock (MCLK) is 256 times the frame rate. It's divided from the kernel clock
+    /// of the SAI by `2 * master_clock_divider` on F4/F7/L4, and by `master_clock_divider` on
+    /// the other families, 0 meaning no division.
+    pub master_clock_divider: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Master,
+            tx_rx: TxRx::Transmitter,
+            protocol: Protocol::I2s,
+            data_size: DataSize::Data16,
+            slot_size: SlotSize::DataSize,
+            mono: false,
+            master_clock_divider: 0,
+        }
+    }
+}
+
+/// Sub-block A of a SAI, returned by [`split_subblocks`].
+pub struct SubBlockA<'d, T: Instance> {
+    _peri: PhantomData<&'d mut T>,
+}
+
+/// Sub-block B of a SAI, returned by [`split_subblocks`].
+pub struct SubBlockB<'d, T: Instance> {
+    _peri: PhantomData<&'d mut T>,
+}
+
+/// Split a SAI into its two sub-blocks, to create a driver for each one.
+pub fn split_subblocks<'d, T: Instance>(_peri: impl Peripheral<P = T> + 'd) -> (SubBlockA<'d, T>, SubBlockB<'d, T>) {
+    T::enable();
+
+    (SubBlockA { _peri: PhantomData }, SubBlockB { _peri: PhantomData })
+}
+
+macro_rules! config_pins {
+    ($($pin:ident),*) => {
+        into_ref!($($pin),*);
+        critical_section::with(|_| {
+            $(
+                $pin.set_as_af($pin.af_num(), AFType::OutputPushPull);
+                $pin.set_speed(Speed::VeryHigh);
+            )*
+        })
+    };
+}
+
+/// SAI sub-block driver.
+pub struct Sai<'d, T: Instance, C: Channel, W: Word> {
+    _peri: PhantomData<&'d mut T>,
+    index: usize,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    sd: Option<PeripheralRef<'d, AnyPin>>,
+    fs: Option<PeripheralRef<'d, AnyPin>>,
+    mclk: Option<PeripheralRef<'d, AnyPin>>,
+    ring_buffer: RingBuffer<'d, C, W>,
+    tx_rx: TxRx,
+}
+
+impl<'d, T: Instance, C: Channel, W: Word> Sai<'d, T, C, W> {
+    /// Create a driver for sub-block A, with its own SCK and FS signals.
+    pub fn new_asynchronous_a(
+        _sub_block: SubBlockA<'d, T>,
+        sck: impl Peripheral<P = impl SckAPin<T>> + 'd,
+        sd: impl Peripheral<P = impl SdAPin<T>> + 'd,
+        fs: impl Peripheral<P = impl FsAPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaA<T>,
+    {
+        config_pins!(sck, sd, fs);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            0,
+            Some(sck.map_into()),
+            Some(sd.map_into()),
+            Some(fs.map_into()),
+            None,
+            false,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    /// Create a driver for sub-block A, with its own SCK and FS signals, and outputting the
+    /// master clock on MCLK.
+    pub fn new_asynchronous_a_with_mclk(
+        _sub_block: SubBlockA<'d, T>,
+        sck: impl Peripheral<P = impl SckAPin<T>> + 'd,
+        sd: impl Peripheral<P = impl SdAPin<T>> + 'd,
+        fs: impl Peripheral<P = impl FsAPin<T>> + 'd,
+        mclk: impl Peripheral<P = impl MclkAPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaA<T>,
+    {
+        config_pins!(sck, sd, fs, mclk);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            0,
+            Some(sck.map_into()),
+            Some(sd.map_into()),
+            Some(fs.map_into()),
+            Some(mclk.map_into()),
+            false,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    /// Create a driver for sub-block A, using the SCK and FS signals of sub-block B.
+    ///
+    /// It must be started before sub-block B.
+    pub fn new_synchronous_a(
+        _sub_block: SubBlockA<'d, T>,
+        sd: impl Peripheral<P = impl SdAPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaA<T>,
+    {
+        config_pins!(sd);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            0,
+            None,
+            Some(sd.map_into()),
+            None,
+            None,
+            true,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    /// Create a driver for sub-block B, with its own SCK and FS signals.
+    pub fn new_asynchronous_b(
+        _sub_block: SubBlockB<'d, T>,
+        sck: impl Peripheral<P = impl SckBPin<T>> + 'd,
+        sd: impl Peripheral<P = impl SdBPin<T>> + 'd,
+        fs: impl Peripheral<P = impl FsBPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaB<T>,
+    {
+        config_pins!(sck, sd, fs);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            1,
+            Some(sck.map_into()),
+            Some(sd.map_into()),
+            Some(fs.map_into()),
+            None,
+            false,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    /// Create a driver for sub-block B, with its own SCK and FS signals, and outputting the
+    /// master clock on MCLK.
+    pub fn new_asynchronous_b_with_mclk(
+        _sub_block: SubBlockB<'d, T>,
+        sck: impl Peripheral<P = impl SckBPin<T>> + 'd,
+        sd: impl Peripheral<P = impl SdBPin<T>> + 'd,
+        fs: impl Peripheral<P = impl FsBPin<T>> + 'd,
+        mclk: impl Peripheral<P = impl MclkBPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaB<T>,
+    {
+        config_pins!(sck, sd, fs, mclk);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            1,
+            Some(sck.map_into()),
+            Some(sd.map_into()),
+            Some(fs.map_into()),
+            Some(mclk.map_into()),
+            false,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    /// Create a driver for sub-block B, using the SCK and FS signals of sub-block A.
+    ///
+    /// It must be started before sub-block A.
+    pub fn new_synchronous_b(
+        _sub_block: SubBlockB<'d, T>,
+        sd: impl Peripheral<P = impl SdBPin<T>> + 'd,
+        dma: impl Peripheral<P = C> + 'd,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self
+    where
+        C: DmaB<T>,
+    {
+        config_pins!(sd);
+        into_ref!(dma);
+        let request = dma.request();
+
+        Self::new_inner(
+            1,
+            None,
+            Some(sd.map_into()),
+            None,
+            None,
+            true,
+            dma,
+            request,
+            dma_buf,
+            config,
+        )
+    }
+
+    fn new_inner(
+        index: usize,
+        sck: Option<PeripheralRef<'d, AnyPin>>,
+        sd: Option<PeripheralRef<'d, AnyPin>>,
+        fs: Option<PeripheralRef<'d, AnyPin>>,
+        mclk: Option<PeripheralRef<'d, AnyPin>>,
+        synchronous: bool,
+        dma: impl Peripheral<P = C> + 'd,
+        request: crate::dma::Request,
+        dma_buf: &'d mut [W],
+        config: Config,
+    ) -> Self {
+        let ch = T::REGS.ch(index);
+        ch.cr1().modify(|w| w.0 &= !CR1_SAIEN);
+        while ch.cr1().read().0 & CR1_SAIEN != 0 {}
+
+        let slot_bits = match config.slot_size {
+            SlotSize::DataSize => config.data_size.bits(),
+            SlotSize::Channel16 => 16,
+            SlotSize::Channel32 => 32,
+        };
+        assert!(slot_bits >= config.data_size.bits());
+
+        let (slots, frcr) = match config.protocol {
+            Protocol::I2s => (2, (slot_bits - 1) << FRCR_FSALL_POS | FRCR_FSDEF | FRCR_FSOFF),
+            Protocol::Tdm { slots } => {
+                assert!((1..=16).contains(&slots));
+                (slots as u32, FRCR_FSPOL | FRCR_FSOFF)
+            }
+        };
+        let frame_bits = slots * slot_bits;
+        assert!(frame_bits <= 256);
+
+        let slotsz = match config.slot_size {
+            SlotSize::DataSize => 0b00,
+            SlotSize::Channel16 => 0b01,
+            SlotSize::Channel32 => 0b10,
+        };
+
+        let mode = match (config.mode, config.tx_rx) {
+            (Mode::Master, TxRx::Transmitter) => 0b00,
+            (Mode::Master, TxRx::Receiver) => 0b01,
+            (Mode::Slave, TxRx::Transmitter) => 0b10,
+            (Mode::Slave, TxRx::Receiver) => 0b11,
+        };
+
+        let mut cr1 = mode << CR1_MODE_POS
+            | config.data_size.ds() << CR1_DS_POS
+            | (synchronous as u32) << CR1_SYNCEN_POS
+            | ((config.master_clock_divider & 0x3F) as u32) << CR1_MCKDIV_POS;
+        // Transmitters drive the data on the falling edge of SCK, receivers sample it on the
+        // rising edge.
+        if config.tx_rx == TxRx::Receiver {
+            cr1 |= CR1_CKSTR;
+        }
+        if config.mono && config.protocol == Protocol::I2s {
+            cr1 |= CR1_MONO;
+        }
+        #[cfg(not(any(sai_v1, sai_v2)))]
+        if mclk.is_some() {
+            cr1 |= CR1_MCKEN;
+        }
+
+        ch.cr1().write(|w| w.0 = cr1);
+        ch.cr2().write(|w| w.0 = CR2_FFLUSH);
+        ch.frcr().write(|w| w.0 = frcr | (frame_bits - 1));
+        ch.slotr().write(|w| {
+            w.0 = ((1 << slots) - 1) << SLOTR_SLOTEN_POS | (slots - 1) << SLOTR_NBSLOT_POS | slotsz << SLOTR_SLOTSZ_POS
+        });
+        ch.clrfr().write(|w| w.0 = CLRFR_ALL);
+
+        let opts = Default::default();
+        let dr = ch.dr().as_ptr() as *mut W;
+        let ring_buffer = match config.tx_rx {
+            TxRx::Transmitter => unsafe { RingBuffer::new_write(dma, request, dma_buf, dr, opts) },
+            TxRx::Receiver => unsafe { RingBuffer::new_read(dma, request, dr, dma_buf, opts) },
+        };
+
+        Self {
+            _peri: PhantomData,
+            index,
+            sck,
+            sd,
+            fs,
+            mclk,
+            ring_buffer,
+            tx_rx: config.tx_rx,
+        }
+    }
+
+    /// Start the transfers.
+    ///
+    /// A transmitter starts with the samples that were in the DMA buffer when it was created.
+    pub fn start(&mut self) {
+        let ch = T::REGS.ch(self.index);
+
+        self.ring_buffer.clear();
+        self.ring_buffer.start();
+
+        ch.clrfr().write(|w| w.0 = CLRFR_ALL);
+        ch.cr1().modify(|w| w.0 |= CR1_DMAEN | CR1_SAIEN);
+    }
+
+    /// Write samples, waiting for room in the DMA buffer.
+    pub async fn write(&mut self, data: &[W]) -> Result<(), Error> {
+        if self.tx_rx != TxRx::Transmitter {
+            return Err(Error::NotATransmitter);
+        }
+
+        let mut written = 0;
+        poll_fn(|cx| {
+            self.ring_buffer.set_waker(cx.waker());
+            self.check_overrun()?;
+
+            while written < data.len() {
+                match self.ring_buffer.write(&data[written..])? {
+                    (0, _) => return Poll::Pending,
+                    (len, _) => written += len,
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+
+    /// Read samples, waiting for them to be received.
+    pub async fn read(&mut self, data: &mut [W]) -> Result<(), Error> {
+        if self.tx_rx != TxRx::Receiver {
+            return Err(Error::NotAReceiver);
+        }
+
+        let mut read = 0;
+        poll_fn(|cx| {
+            self.ring_buffer.set_waker(cx.waker());
+            self.check_overrun()?;
+
+            while read < data.len() {
+                match self.ring_buffer.read(&mut data[read..])? {
+                    (0, _) => return Poll::Pending,
+                    (len, _) => read += len,
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+
+    /// Whether the FIFO of the sub-block was overrun, or underrun, since the last call.
+    fn check_overrun(&mut self) -> Result<(), Error> {
+        let ch = T::REGS.ch(self.index);
+        if ch.sr().read().0 & SR_OVRUDR != 0 {
+            ch.clrfr().write(|w| w.0 = SR_OVRUDR);
+            return Err(Error::Overrun);
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, C: Channel, W: Word> Drop for Sai<'d, T, C, W> {
+    fn drop(&mut self) {
+        let ch = T::REGS.ch(self.index);
+        ch.cr1().modify(|w| w.0 &= !(CR1_SAIEN | CR1_DMAEN));
+        self.ring_buffer.request_stop();
+
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.sd.as_ref().map(|x| x.set_as_disconnected());
+        self.fs.as_ref().map(|x| x.set_as_disconnected());
+        self.mclk.as_ref().map(|x| x.set_as_disconnected());
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        const REGS: crate::pac::sai::Sai;
+    }
+}
+
+pub trait Instance: Peripheral<P = Self> + sealed::Instance + RccPeripheral {}
+
+pin_trait!(SckAPin, Instance);
+pin_trait!(SckBPin, Instance);
+pin_trait!(FsAPin, Instance);
+pin_trait!(FsBPin, Instance);
+pin_trait!(SdAPin, Instance);
+pin_trait!(SdBPin, Instance);
+pin_trait!(MclkAPin, Instance);
+pin_trait!(MclkBPin, Instance);
+
+dma_trait!(DmaA, Instance);
+dma_trait!(DmaB, Instance);
+
+foreach_peripheral!(
+    (sai, $inst:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            const REGS: crate::pac::sai::Sai = crate::pac::$inst;
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+);
@@ -49,6 +49,14 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
 /// Frequency used for SD Card initialization. Must be no higher than 400 kHz.
 const SD_INIT_FREQ: Hertz = Hertz(400_000);
 
+/// Maximum frequency of eMMC devices with the default timing.
+const EMMC_DEFAULT_FREQ: u32 = 26_000_000;
+/// Maximum frequency of eMMC devices with the high speed timing.
+const EMMC_HS_FREQ: u32 = 52_000_000;
+
+/// Relative Card Address assigned to eMMC devices.
+const EMMC_RCA: u32 = 1;
+
 /// The signalling scheme used on the SDMMC bus
 #[non_exhaustive]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -104,6 +112,44 @@ pub enum Error {
     PeripheralBusy,
 }
 
+/// Kind of card
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CardKind {
+    /// SD card, initialized with [`Sdmmc::init_card`]
+    #[default]
+    Sd,
+    /// eMMC device, initialized with [`Sdmmc::init_emmc`]
+    Emmc,
+}
+
+/// Fields of the eMMC Extended CSD register
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtCsd {
+    /// Number of 512 byte sectors, for devices larger than 2 GB
+    pub sec_count: u32,
+    /// Supported high speed timings
+    pub device_type: u8,
+    /// Revision of the Extended CSD structure
+    pub ext_csd_rev: u8,
+}
+
+impl ExtCsd {
+    fn parse(block: &DataBlock) -> Self {
+        Self {
+            sec_count: u32::from_le_bytes([block[212], block[213], block[214], block[215]]),
+            device_type: block[196],
+            ext_csd_rev: block[192],
+        }
+    }
+
+    /// Whether the device supports the 52 MHz high speed timing
+    pub fn high_speed_52mhz(&self) -> bool {
+        self.device_type & 0x02 != 0
+    }
+}
+
 /// A SD command
 struct Cmd {
     cmd: u8,
@@ -128,13 +174,21 @@ pub struct Card {
     pub scr: SCR,
     /// SD Status
     pub status: SDStatus,
+    /// Kind of card
+    pub card_kind: CardKind,
+    /// eMMC Extended CSD
+    pub ext_csd: ExtCsd,
 }
 
 impl Card {
     /// Size in bytes
     pub fn size(&self) -> u64 {
-        // SDHC / SDXC / SDUC
-        u64::from(self.csd.block_count()) * 512
+        match self.card_kind {
+            // The CSD can only describe eMMC devices up to 2 GB
+            CardKind::Emmc if self.ext_csd.sec_count != 0 => u64::from(self.ext_csd.sec_count) * 512,
+            // SDHC / SDXC / SDUC
+            _ => u64::from(self.csd.block_count()) * 512,
+        }
     }
 }
 
@@ -153,6 +207,8 @@ enum CmdAppOper {
     SDMMC_STD_CAPACITY = 0x0000_0000,
     SDMMC_CHECK_PATTERN = 0x0000_01AA,
     SD_SWITCH_1_8V_CAPACITY = 0x0100_0000,
+    /// eMMC: sector addressing, 2.7 - 3.6 V
+    EMMC_SECTOR_MODE_HV = 0x40FF_8000,
 }
 
 #[derive(Eq, PartialEq, Copy, Clone)]
@@ -225,6 +281,9 @@ const DMA_TRANSFER_OPTIONS: crate::dma::TransferOptions = crate::dma::TransferOp
     mburst: crate::dma::Burst::Incr4,
     flow_ctrl: crate::dma::FlowControl::Peripheral,
     fifo_threshold: Some(crate::dma::FifoThreshold::Full),
+    circular: false,
+    half_transfer_ir: false,
+    complete_transfer_ir: true,
 };
 #[cfg(all(sdmmc_v1, not(dma)))]
 const DMA_TRANSFER_OPTIONS: crate::dma::TransferOptions = crate::dma::TransferOptions {
@@ -263,6 +322,10 @@ pub struct Sdmmc<'d, T: Instance, Dma: SdmmcDma<T> = NoDma> {
     d1: Option<PeripheralRef<'d, AnyPin>>,
     d2: Option<PeripheralRef<'d, AnyPin>>,
     d3: Option<PeripheralRef<'d, AnyPin>>,
+    d4: Option<PeripheralRef<'d, AnyPin>>,
+    d5: Option<PeripheralRef<'d, AnyPin>>,
+    d6: Option<PeripheralRef<'d, AnyPin>>,
+    d7: Option<PeripheralRef<'d, AnyPin>>,
 
     config: Config,
     /// Current clock to card
@@ -305,6 +368,10 @@ impl<'d, T: Instance, Dma: SdmmcDma<T>> Sdmmc<'d, T, Dma> {
             None,
             None,
             None,
+            None,
+            None,
+            None,
+            None,
             config,
         )
     }
@@ -348,6 +415,69 @@ impl<'d, T: Instance, Dma: SdmmcDma<T>> Sdmmc<'d, T, Dma> {
             Some(d1.map_into()),
             Some(d2.map_into()),
             Some(d3.map_into()),
+            None,
+            None,
+            None,
+            None,
+            config,
+        )
+    }
+
+    pub fn new_8bit(
+        sdmmc: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        dma: impl Peripheral<P = Dma> + 'd,
+        clk: impl Peripheral<P = impl CkPin<T>> + 'd,
+        cmd: impl Peripheral<P = impl CmdPin<T>> + 'd,
+        d0: impl Peripheral<P = impl D0Pin<T>> + 'd,
+        d1: impl Peripheral<P = impl D1Pin<T>> + 'd,
+        d2: impl Peripheral<P = impl D2Pin<T>> + 'd,
+        d3: impl Peripheral<P = impl D3Pin<T>> + 'd,
+        d4: impl Peripheral<P = impl D4Pin<T>> + 'd,
+        d5: impl Peripheral<P = impl D5Pin<T>> + 'd,
+        d6: impl Peripheral<P = impl D6Pin<T>> + 'd,
+        d7: impl Peripheral<P = impl D7Pin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(clk, cmd, d0, d1, d2, d3, d4, d5, d6, d7);
+
+        critical_section::with(|_| {
+            clk.set_as_af_pull(clk.af_num(), AFType::OutputPushPull, Pull::None);
+            cmd.set_as_af_pull(cmd.af_num(), AFType::OutputPushPull, Pull::Up);
+            d0.set_as_af_pull(d0.af_num(), AFType::OutputPushPull, Pull::Up);
+            d1.set_as_af_pull(d1.af_num(), AFType::OutputPushPull, Pull::Up);
+            d2.set_as_af_pull(d2.af_num(), AFType::OutputPushPull, Pull::Up);
+            d3.set_as_af_pull(d3.af_num(), AFType::OutputPushPull, Pull::Up);
+            d4.set_as_af_pull(d4.af_num(), AFType::OutputPushPull, Pull::Up);
+            d5.set_as_af_pull(d5.af_num(), AFType::OutputPushPull, Pull::Up);
+            d6.set_as_af_pull(d6.af_num(), AFType::OutputPushPull, Pull::Up);
+            d7.set_as_af_pull(d7.af_num(), AFType::OutputPushPull, Pull::Up);
+
+            clk.set_speed(Speed::VeryHigh);
+            cmd.set_speed(Speed::VeryHigh);
+            d0.set_speed(Speed::VeryHigh);
+            d1.set_speed(Speed::VeryHigh);
+            d2.set_speed(Speed::VeryHigh);
+            d3.set_speed(Speed::VeryHigh);
+            d4.set_speed(Speed::VeryHigh);
+            d5.set_speed(Speed::VeryHigh);
+            d6.set_speed(Speed::VeryHigh);
+            d7.set_speed(Speed::VeryHigh);
+        });
+
+        Self::new_inner(
+            sdmmc,
+            dma,
+            clk.map_into(),
+            cmd.map_into(),
+            d0.map_into(),
+            Some(d1.map_into()),
+            Some(d2.map_into()),
+            Some(d3.map_into()),
+            Some(d4.map_into()),
+            Some(d5.map_into()),
+            Some(d6.map_into()),
+            Some(d7.map_into()),
             config,
         )
     }
@@ -384,6 +514,10 @@ impl<'d, T: Instance> Sdmmc<'d, T, NoDma> {
             None,
             None,
             None,
+            None,
+            None,
+            None,
+            None,
             config,
         )
     }
@@ -426,6 +560,68 @@ impl<'d, T: Instance> Sdmmc<'d, T, NoDma> {
             Some(d1.map_into()),
             Some(d2.map_into()),
             Some(d3.map_into()),
+            None,
+            None,
+            None,
+            None,
+            config,
+        )
+    }
+
+    pub fn new_8bit(
+        sdmmc: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        clk: impl Peripheral<P = impl CkPin<T>> + 'd,
+        cmd: impl Peripheral<P = impl CmdPin<T>> + 'd,
+        d0: impl Peripheral<P = impl D0Pin<T>> + 'd,
+        d1: impl Peripheral<P = impl D1Pin<T>> + 'd,
+        d2: impl Peripheral<P = impl D2Pin<T>> + 'd,
+        d3: impl Peripheral<P = impl D3Pin<T>> + 'd,
+        d4: impl Peripheral<P = impl D4Pin<T>> + 'd,
+        d5: impl Peripheral<P = impl D5Pin<T>> + 'd,
+        d6: impl Peripheral<P = impl D6Pin<T>> + 'd,
+        d7: impl Peripheral<P = impl D7Pin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(clk, cmd, d0, d1, d2, d3, d4, d5, d6, d7);
+
+        critical_section::with(|_| {
+            clk.set_as_af_pull(clk.af_num(), AFType::OutputPushPull, Pull::None);
+            cmd.set_as_af_pull(cmd.af_num(), AFType::OutputPushPull, Pull::Up);
+            d0.set_as_af_pull(d0.af_num(), AFType::OutputPushPull, Pull::Up);
+            d1.set_as_af_pull(d1.af_num(), AFType::OutputPushPull, Pull::Up);
+            d2.set_as_af_pull(d2.af_num(), AFType::OutputPushPull, Pull::Up);
+            d3.set_as_af_pull(d3.af_num(), AFType::OutputPushPull, Pull::Up);
+            d4.set_as_af_pull(d4.af_num(), AFType::OutputPushPull, Pull::Up);
+            d5.set_as_af_pull(d5.af_num(), AFType::OutputPushPull, Pull::Up);
+            d6.set_as_af_pull(d6.af_num(), AFType::OutputPushPull, Pull::Up);
+            d7.set_as_af_pull(d7.af_num(), AFType::OutputPushPull, Pull::Up);
+
+            clk.set_speed(Speed::VeryHigh);
+            cmd.set_speed(Speed::VeryHigh);
+            d0.set_speed(Speed::VeryHigh);
+            d1.set_speed(Speed::VeryHigh);
+            d2.set_speed(Speed::VeryHigh);
+            d3.set_speed(Speed::VeryHigh);
+            d4.set_speed(Speed::VeryHigh);
+            d5.set_speed(Speed::VeryHigh);
+            d6.set_speed(Speed::VeryHigh);
+            d7.set_speed(Speed::VeryHigh);
+        });
+
+        Self::new_inner(
+            sdmmc,
+            NoDma.into_ref(),
+            clk.map_into(),
+            cmd.map_into(),
+            d0.map_into(),
+            Some(d1.map_into()),
+            Some(d2.map_into()),
+            Some(d3.map_into()),
+            Some(d4.map_into()),
+            Some(d5.map_into()),
+            Some(d6.map_into()),
+            Some(d7.map_into()),
             config,
         )
     }
@@ -441,6 +637,10 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         d1: Option<PeripheralRef<'d, AnyPin>>,
         d2: Option<PeripheralRef<'d, AnyPin>>,
         d3: Option<PeripheralRef<'d, AnyPin>>,
+        d4: Option<PeripheralRef<'d, AnyPin>>,
+        d5: Option<PeripheralRef<'d, AnyPin>>,
+        d6: Option<PeripheralRef<'d, AnyPin>>,
+        d7: Option<PeripheralRef<'d, AnyPin>>,
         config: Config,
     ) -> Self {
         into_ref!(sdmmc, dma);
@@ -481,6 +681,10 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             d1,
             d2,
             d3,
+            d4,
+            d5,
+            d6,
+            d7,
 
             config,
             clock: SD_INIT_FREQ,
@@ -984,6 +1188,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         let regs = T::regs();
         let ker_ck = T::kernel_clk();
 
+        // SD cards are limited to 4 bit
         let bus_width = match self.d3.is_some() {
             true => BusWidth::Four,
             false => BusWidth::One,
@@ -1073,7 +1278,6 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
 
         // Set bus width
         let (width, acmd_arg) = match bus_width {
-            BusWidth::Eight => unimplemented!(),
             BusWidth::Four if card.scr.bus_width_four() => (BusWidth::Four, 2),
             _ => (BusWidth::One, 0),
         };
@@ -1223,6 +1427,12 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
                 // TODO: Make this configurable
                 let mut timeout: u32 = 0x00FF_FFFF;
 
+                let card = *self.card()?;
+                if card.card_kind == CardKind::Emmc {
+                    // Wait for the end of programming (CMD13)
+                    return Self::wait_transfer_state(&card);
+                }
+
                 // Try to read card status (ACMD13)
                 while timeout > 0 {
                     match self.read_sd_status().await {
@@ -1238,6 +1448,194 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         }
     }
 
+    /// Initializes an eMMC device and sets the bus at the
+    /// specified frequency.
+    ///
+    /// The widest bus allowed by the pins is used. Frequencies above
+    /// 26 MHz switch the device to the high speed timing, up to 52 MHz.
+    pub async fn init_emmc(&mut self, freq: Hertz) -> Result<(), Error> {
+        let regs = T::regs();
+        let ker_ck = T::kernel_clk();
+
+        let (bus_width, widbus, width_arg) = if self.d7.is_some() {
+            (BusWidth::Eight, 2, 2)
+        } else if self.d3.is_some() {
+            (BusWidth::Four, 1, 1)
+        } else {
+            (BusWidth::One, 0, 0)
+        };
+
+        // While the SD/SDIO card or eMMC is in identification mode,
+        // the SDMMC_CK frequency must be no more than 400 kHz.
+        let (_bypass, clkdiv, init_clock) = unwrap!(clk_div(ker_ck, SD_INIT_FREQ.0));
+        self.clock = init_clock;
+
+        // CPSMACT and DPSMACT must be 0 to set WIDBUS
+        Self::wait_idle();
+
+        regs.clkcr().modify(|w| {
+            w.set_widbus(0);
+            w.set_clkdiv(clkdiv);
+            #[cfg(sdmmc_v1)]
+            w.set_bypass(_bypass);
+        });
+
+        regs.power().modify(|w| w.set_pwrctrl(PowerCtrl::On as u8));
+        Self::cmd(Cmd::idle(), false)?;
+
+        let mut card = Card {
+            card_kind: CardKind::Emmc,
+            ..Default::default()
+        };
+
+        let ocr = loop {
+            // The R3 response has no CRC
+            match Self::cmd(Cmd::send_op_cond(CmdAppOper::EMMC_SECTOR_MODE_HV as u32), false) {
+                // CMD1
+                Ok(_) => (),
+                Err(Error::Crc) => (),
+                Err(err) => return Err(err),
+            }
+            let ocr: OCR = regs.respr(0).read().cardstatus().into();
+            if !ocr.is_busy() {
+                // Power up done
+                break ocr;
+            }
+        };
+
+        // Sector addressing is reported like SDHC cards
+        if ocr.high_capacity() {
+            card.card_type = CardCapacity::SDHC;
+        } else {
+            card.card_type = CardCapacity::SDSC;
+        }
+        card.ocr = ocr;
+
+        Self::cmd(Cmd::all_send_cid(), false)?; // CMD2
+        let cid0 = regs.respr(0).read().cardstatus() as u128;
+        let cid1 = regs.respr(1).read().cardstatus() as u128;
+        let cid2 = regs.respr(2).read().cardstatus() as u128;
+        let cid3 = regs.respr(3).read().cardstatus() as u128;
+        let cid = (cid0 << 96) | (cid1 << 64) | (cid2 << 32) | (cid3);
+        card.cid = cid.into();
+
+        // The host assigns the address of eMMC devices
+        card.rca = EMMC_RCA;
+        Self::cmd(Cmd::set_rel_addr(card.rca << 16), false)?; // CMD3
+
+        Self::cmd(Cmd::send_csd(card.rca << 16), false)?;
+        let csd0 = regs.respr(0).read().cardstatus() as u128;
+        let csd1 = regs.respr(1).read().cardstatus() as u128;
+        let csd2 = regs.respr(2).read().cardstatus() as u128;
+        let csd3 = regs.respr(3).read().cardstatus() as u128;
+        let csd = (csd0 << 96) | (csd1 << 64) | (csd2 << 32) | (csd3);
+        card.csd = csd.into();
+
+        self.select_card(Some(&card))?;
+
+        let mut ext_csd = DataBlock([0; 512]);
+        self.read_ext_csd(&mut ext_csd).await?;
+        card.ext_csd = ExtCsd::parse(&ext_csd);
+
+        // Set bus width (EXT_CSD BUS_WIDTH)
+        Self::switch_ext_csd(&card, 183, width_arg)?;
+
+        // CPSMACT and DPSMACT must be 0 to set WIDBUS
+        Self::wait_idle();
+
+        regs.clkcr().modify(|w| w.set_widbus(widbus));
+
+        // Set Clock
+        if freq.0 > EMMC_DEFAULT_FREQ && card.ext_csd.high_speed_52mhz() {
+            // Switch to high speed timing (EXT_CSD HS_TIMING)
+            Self::switch_ext_csd(&card, 185, 1)?;
+            self.clkcr_set_clkdiv(freq.0.min(EMMC_HS_FREQ), bus_width)?;
+        } else {
+            self.clkcr_set_clkdiv(freq.0.min(EMMC_DEFAULT_FREQ), bus_width)?;
+        }
+
+        if self.read_status(&card)?.state() != CurrentState::Transfer {
+            return Err(Error::SignalingSwitchFailed);
+        }
+
+        self.card = Some(card);
+
+        Ok(())
+    }
+
+    /// Reads the Extended CSD register of an eMMC device (CMD8)
+    ///
+    /// The device must be selected, which it is after [`init_emmc`](Self::init_emmc).
+    pub async fn read_ext_csd(&mut self, buffer: &mut DataBlock) -> Result<(), Error> {
+        // NOTE(unsafe) DataBlock uses align 4
+        let buffer = unsafe { &mut *((&mut buffer.0) as *mut [u8; 512] as *mut [u32; 128]) };
+
+        Self::cmd(Cmd::set_block_length(512), false)?; // CMD16
+
+        let regs = T::regs();
+        let on_drop = OnDrop::new(|| Self::on_drop());
+
+        let transfer = self.prepare_datapath_read(buffer, 512, 9);
+        InterruptHandler::<T>::data_interrupts(true);
+        Self::cmd(Cmd::hs_send_ext_csd(0), true)?;
+
+        let res = poll_fn(|cx| {
+            T::state().register(cx.waker());
+            let status = regs.star().read();
+
+            if status.dcrcfail() {
+                return Poll::Ready(Err(Error::Crc));
+            } else if status.dtimeout() {
+                return Poll::Ready(Err(Error::Timeout));
+            } else if status.dataend() {
+                return Poll::Ready(Ok(()));
+            }
+            Poll::Pending
+        })
+        .await;
+        Self::clear_interrupt_flags();
+
+        if res.is_ok() {
+            on_drop.defuse();
+            Self::stop_datapath();
+            drop(transfer);
+        }
+        res
+    }
+
+    /// Writes a byte of the Extended CSD register of an eMMC device (CMD6),
+    /// and waits for the device to apply it.
+    fn switch_ext_csd(card: &Card, index: u8, value: u8) -> Result<(), Error> {
+        // Access: write byte
+        let arg = (0b11 << 24) | ((index as u32) << 16) | ((value as u32) << 8);
+        Self::cmd(Cmd::cmd6(arg), false)?;
+
+        Self::wait_transfer_state(card)
+    }
+
+    /// Polls the card status until the card is back in the _Transfer State_
+    fn wait_transfer_state(card: &Card) -> Result<(), Error> {
+        let regs = T::regs();
+
+        // TODO: Make this configurable
+        let mut timeout: u32 = 0x00FF_FFFF;
+
+        while timeout > 0 {
+            match Self::cmd(Cmd::card_status(card.rca << 16), false) {
+                Ok(_) => {
+                    let status: CardStatus = regs.respr(0).read().cardstatus().into();
+                    if status.state() == CurrentState::Transfer {
+                        return Ok(());
+                    }
+                }
+                Err(Error::Timeout) => (), // Try again
+                Err(e) => return Err(e),
+            }
+            timeout -= 1;
+        }
+        Err(Error::SoftwareTimeout)
+    }
+
     /// Get a reference to the initialized card
     ///
     /// # Errors
@@ -1273,6 +1671,18 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Drop for Sdmmc<'d, T, Dma> {
             if let Some(x) = &mut self.d3 {
                 x.set_as_disconnected();
             }
+            if let Some(x) = &mut self.d4 {
+                x.set_as_disconnected();
+            }
+            if let Some(x) = &mut self.d5 {
+                x.set_as_disconnected();
+            }
+            if let Some(x) = &mut self.d6 {
+                x.set_as_disconnected();
+            }
+            if let Some(x) = &mut self.d7 {
+                x.set_as_disconnected();
+            }
         });
     }
 }
@@ -1288,6 +1698,11 @@ impl Cmd {
         Cmd::new(0, 0, Response::None)
     }
 
+    /// CMD1: eMMC Send Operating Conditions
+    const fn send_op_cond(arg: u32) -> Cmd {
+        Cmd::new(1, arg, Response::Short)
+    }
+
     /// CMD2: Send CID
     const fn all_send_cid() -> Cmd {
         Cmd::new(2, 0, Response::Long)
@@ -1298,6 +1713,11 @@ impl Cmd {
         Cmd::new(3, 0, Response::Short)
     }
 
+    /// CMD3: eMMC Set Relative Address
+    const fn set_rel_addr(rca: u32) -> Cmd {
+        Cmd::new(3, rca, Response::Short)
+    }
+
     /// CMD6: Switch Function Command
     /// ACMD6: Bus Width
     const fn cmd6(arg: u32) -> Cmd {
@@ -1309,7 +1729,7 @@ impl Cmd {
         Cmd::new(7, rca, Response::Short)
     }
 
-    /// CMD8:
+    /// CMD8: Send Interface Condition (SD), Send Extended CSD (eMMC)
     const fn hs_send_ext_csd(arg: u32) -> Cmd {
         Cmd::new(8, arg, Response::Short)
     }
@@ -1523,7 +1943,7 @@ mod sdmmc_rs {
 
         fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
             let card = self.card()?;
-            let count = card.csd.block_count();
+            let count = (card.size() / 512) as u32;
             Ok(BlockCount(count))
         }
     }
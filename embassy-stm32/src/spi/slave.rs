@@ -0,0 +1,319 @@
+use core::marker::PhantomData;
+
+use super::*;
+
+/// SPI driver in slave mode, for designs where the STM32 is a co-processor on a bus driven by
+/// another master.
+///
+/// The clock is generated by the master, so a transfer must be prepared with
+/// [`prepare()`](SpiSlave::prepare) before the master starts clocking: this loads the first words
+/// to send and arms the DMA channels, which then exchange words as the master clocks them.
+pub struct SpiSlave<'d, T: Instance, Tx, Rx> {
+    _peri: PeripheralRef<'d, T>,
+    sck: PeripheralRef<'d, AnyPin>,
+    mosi: PeripheralRef<'d, AnyPin>,
+    miso: PeripheralRef<'d, AnyPin>,
+    nss: Option<PeripheralRef<'d, AnyPin>>,
+    txdma: PeripheralRef<'d, Tx>,
+    rxdma: PeripheralRef<'d, Rx>,
+}
+
+impl<'d, T: Instance, Tx, Rx> SpiSlave<'d, T, Tx, Rx> {
+    /// Create an SPI slave selected by the master through the hardware NSS pin.
+    ///
+    /// MISO is driven only while NSS is low, so that several slaves can share the bus.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        nss: impl Peripheral<P = impl CsPin<T>> + 'd,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(sck, mosi, miso, nss);
+
+        sck.set_as_af(sck.af_num(), AFType::Input);
+        sck.set_speed(crate::gpio::Speed::VeryHigh);
+        mosi.set_as_af(mosi.af_num(), AFType::Input);
+        mosi.set_speed(crate::gpio::Speed::VeryHigh);
+        miso.set_as_af(miso.af_num(), AFType::OutputPushPull);
+        miso.set_speed(crate::gpio::Speed::VeryHigh);
+        nss.set_as_af_pull(nss.af_num(), AFType::Input, Pull::Up);
+
+        Self::new_inner(
+            peri,
+            sck.map_into(),
+            mosi.map_into(),
+            miso.map_into(),
+            Some(nss.map_into()),
+            txdma,
+            rxdma,
+            config,
+        )
+    }
+
+    /// Create an SPI slave that is always selected, without an NSS pin.
+    ///
+    /// The slave must be alone on the bus, and the master must not send clock edges outside of
+    /// the transfers, or the slave loses its word alignment.
+    pub fn new_without_nss(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(sck, mosi, miso);
+
+        sck.set_as_af(sck.af_num(), AFType::Input);
+        sck.set_speed(crate::gpio::Speed::VeryHigh);
+        mosi.set_as_af(mosi.af_num(), AFType::Input);
+        mosi.set_speed(crate::gpio::Speed::VeryHigh);
+        miso.set_as_af(miso.af_num(), AFType::OutputPushPull);
+        miso.set_speed(crate::gpio::Speed::VeryHigh);
+
+        Self::new_inner(
+            peri,
+            sck.map_into(),
+            mosi.map_into(),
+            miso.map_into(),
+            None,
+            txdma,
+            rxdma,
+            config,
+        )
+    }
+
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: PeripheralRef<'d, AnyPin>,
+        mosi: PeripheralRef<'d, AnyPin>,
+        miso: PeripheralRef<'d, AnyPin>,
+        nss: Option<PeripheralRef<'d, AnyPin>>,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri, txdma, rxdma);
+
+        let cpha = config.raw_phase();
+        let cpol = config.raw_polarity();
+        let lsbfirst = config.raw_byte_order();
+        // Without an NSS pin, the slave is selected by software.
+        let ssm = nss.is_none();
+
+        T::enable();
+        T::reset();
+
+        #[cfg(any(spi_v1, spi_f1))]
+        {
+            T::REGS.cr2().modify(|w| {
+                w.set_ssoe(false);
+            });
+            T::REGS.cr1().modify(|w| {
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+
+                w.set_mstr(vals::Mstr::SLAVE);
+                w.set_lsbfirst(lsbfirst);
+                // With software management, SSI is the level of the internal NSS.
+                w.set_ssi(false);
+                w.set_ssm(ssm);
+                w.set_crcen(false);
+                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+                w.set_dff(<u8 as sealed::Word>::CONFIG)
+            });
+        }
+        #[cfg(spi_v2)]
+        {
+            T::REGS.cr2().modify(|w| {
+                let (ds, frxth) = <u8 as sealed::Word>::CONFIG;
+                w.set_frxth(frxth);
+                w.set_ds(ds);
+                w.set_ssoe(false);
+            });
+            T::REGS.cr1().modify(|w| {
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+
+                w.set_mstr(vals::Mstr::SLAVE);
+                w.set_lsbfirst(lsbfirst);
+                // With software management, SSI is the level of the internal NSS.
+                w.set_ssi(false);
+                w.set_ssm(ssm);
+                w.set_crcen(false);
+                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            });
+        }
+        #[cfg(any(spi_v3, spi_v4, spi_v5))]
+        {
+            T::REGS.ifcr().write(|w| w.0 = 0xffff_ffff);
+            T::REGS.cfg2().modify(|w| {
+                w.set_ssoe(false);
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+                w.set_lsbfirst(lsbfirst);
+                w.set_ssm(ssm);
+                w.set_master(vals::Master::SLAVE);
+                w.set_comm(vals::Comm::FULLDUPLEX);
+                w.set_afcntr(vals::Afcntr::CONTROLLED);
+                w.set_ssiop(vals::Ssiop::ACTIVELOW);
+            });
+            T::REGS.cfg1().modify(|w| {
+                w.set_crcen(false);
+                w.set_dsize(<u8 as sealed::Word>::CONFIG);
+                w.set_fthlv(vals::Fthlv::ONEFRAME);
+            });
+            T::REGS.cr2().modify(|w| {
+                w.set_tsize(0);
+            });
+            T::REGS.cr1().modify(|w| {
+                // With software management, SSI is the level of the internal NSS.
+                w.set_ssi(false);
+            });
+        }
+
+        Self {
+            _peri: peri,
+            sck,
+            mosi,
+            miso,
+            nss,
+            txdma,
+            rxdma,
+        }
+    }
+
+    /// Prepare a full-duplex transfer of `read.len()` words, sending the words of `write`.
+    ///
+    /// The words are exchanged as the master clocks them, and the returned [`SlaveTransfer`]
+    /// completes once `read.len()` words have been received. Prepare the transfer before the
+    /// master starts clocking, for example before signalling it with a "data ready" pin, or the
+    /// first words are lost.
+    ///
+    /// Dropping the `SlaveTransfer` aborts it.
+    pub fn prepare<'a, W: Word>(&'a mut self, read: &'a mut [W], write: &'a [W]) -> SlaveTransfer<'a, T, Tx, Rx>
+    where
+        Tx: TxDma<T>,
+        Rx: RxDma<T>,
+    {
+        assert_eq!(read.len(), write.len());
+        assert!(read.len() > 0);
+
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        set_slave_word_size::<T>(W::CONFIG);
+
+        // SPIv3 clears rxfifo on SPE=0
+        #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
+        flush_rx_fifo(T::REGS);
+        #[cfg(any(spi_v3, spi_v4, spi_v5))]
+        T::REGS.ifcr().write(|w| w.0 = 0xffff_ffff);
+
+        set_rxdmaen(T::REGS, true);
+
+        let rx_request = self.rxdma.request();
+        let rx_src = T::REGS.rx_ptr();
+        let rx = unsafe { Transfer::new_read(&mut self.rxdma, rx_request, rx_src, read, Default::default()) };
+
+        let tx_request = self.txdma.request();
+        let tx_dst = T::REGS.tx_ptr();
+        let tx = unsafe { Transfer::new_write(&mut self.txdma, tx_request, write, tx_dst, Default::default()) };
+
+        // The DMA fills the transmit buffer as soon as it's enabled, so the first word is ready
+        // for the first clock edge.
+        set_txdmaen(T::REGS, true);
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+
+        SlaveTransfer {
+            tx,
+            rx,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Prepare a full-duplex transfer, and wait for the master to complete it.
+    ///
+    /// See [`prepare()`](SpiSlave::prepare).
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error>
+    where
+        Tx: TxDma<T>,
+        Rx: RxDma<T>,
+    {
+        self.prepare(read, write).wait().await
+    }
+}
+
+impl<'d, T: Instance, Tx, Rx> Drop for SpiSlave<'d, T, Tx, Rx> {
+    fn drop(&mut self) {
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+
+        self.sck.set_as_disconnected();
+        self.mosi.set_as_disconnected();
+        self.miso.set_as_disconnected();
+        self.nss.as_ref().map(|x| x.set_as_disconnected());
+    }
+}
+
+/// A prepared SPI slave transfer, see [`SpiSlave::prepare()`].
+pub struct SlaveTransfer<'a, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>> {
+    tx: Transfer<'a, Tx>,
+    rx: Transfer<'a, Rx>,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>> SlaveTransfer<'a, T, Tx, Rx> {
+    /// Wait for the master to clock all the words of the transfer.
+    pub async fn wait(mut self) -> Result<(), Error> {
+        join(&mut self.tx, &mut self.rx).await;
+
+        check_error_flags(T::REGS.sr().read())
+    }
+
+    /// Number of words that are still to be received.
+    pub fn remaining(&self) -> usize {
+        self.rx.get_remaining_transfers() as usize
+    }
+}
+
+impl<'a, T: Instance, Tx: TxDma<T>, Rx: RxDma<T>> Drop for SlaveTransfer<'a, T, Tx, Rx> {
+    fn drop(&mut self) {
+        // Stop shifting words before the DMA channels are stopped by the transfers.
+        T::REGS.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+
+        #[cfg(not(any(spi_v3, spi_v4, spi_v5)))]
+        T::REGS.cr2().modify(|reg| {
+            reg.set_txdmaen(false);
+            reg.set_rxdmaen(false);
+        });
+        #[cfg(any(spi_v3, spi_v4, spi_v5))]
+        T::REGS.cfg1().modify(|reg| {
+            reg.set_txdmaen(false);
+            reg.set_rxdmaen(false);
+        });
+    }
+}
+
+/// Set the word size, with the peripheral disabled.
+fn set_slave_word_size<T: Instance>(word_size: word_impl::Config) {
+    #[cfg(any(spi_v1, spi_f1))]
+    T::REGS.cr1().modify(|w| w.set_dff(word_size));
+    #[cfg(spi_v2)]
+    T::REGS.cr2().modify(|w| {
+        w.set_frxth(word_size.1);
+        w.set_ds(word_size.0);
+    });
+    #[cfg(any(spi_v3, spi_v4, spi_v5))]
+    T::REGS.cfg1().modify(|w| w.set_dsize(word_size));
+}
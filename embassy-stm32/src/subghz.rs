@@ -0,0 +1,328 @@
+//! SUBGHZ, the sub-GHz radio of the STM32WL.
+//!
+//! The radio is a Semtech SX126x, controlled by commands on the SUBGHZSPI. Unlike an external
+//! SX126x, its NSS, BUSY and IRQ lines are internal: the chip select is driven through the PWR,
+//! the busy state is read from the PWR, and the radio interrupts are the `SUBGHZ_RADIO`
+//! interrupt, so no GPIO is needed.
+//!
+//! [`SubGhz`] gives raw access to the commands, registers and data buffer of the radio, for
+//! custom modulations, and runs the operations waiting for a radio interrupt asynchronously:
+//! channel activity detection with [`SubGhz::cad()`], and duty-cycled reception with
+//! [`SubGhz::rx_duty_cycle()`]. The LoRa and LoRaWAN stacks are provided by `embassy-lora`.
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::pwr::vals::{Nss, Rfbusys};
+use crate::pac::{PWR, RCC};
+use crate::peripherals::SUBGHZSPI;
+use crate::spi::{self, RxDma, Spi, TxDma};
+use crate::{interrupt, Peripheral};
+
+static IRQ_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Command opcodes.
+pub mod opcode {
+    pub const CLR_IRQ_STATUS: u8 = 0x02;
+    pub const SET_DIO_IRQ_PARAMS: u8 = 0x08;
+    pub const WRITE_REGISTER: u8 = 0x0d;
+    pub const WRITE_BUFFER: u8 = 0x0e;
+    pub const GET_IRQ_STATUS: u8 = 0x12;
+    pub const GET_RX_BUFFER_STATUS: u8 = 0x13;
+    pub const GET_PACKET_STATUS: u8 = 0x14;
+    pub const READ_REGISTER: u8 = 0x1d;
+    pub const READ_BUFFER: u8 = 0x1e;
+    pub const SET_STANDBY: u8 = 0x80;
+    pub const SET_RX: u8 = 0x82;
+    pub const SET_TX: u8 = 0x83;
+    pub const SET_SLEEP: u8 = 0x84;
+    pub const SET_RF_FREQUENCY: u8 = 0x86;
+    pub const SET_CAD_PARAMS: u8 = 0x88;
+    pub const CALIBRATE: u8 = 0x89;
+    pub const SET_PACKET_TYPE: u8 = 0x8a;
+    pub const SET_MODULATION_PARAMS: u8 = 0x8b;
+    pub const SET_PACKET_PARAMS: u8 = 0x8c;
+    pub const SET_TX_PARAMS: u8 = 0x8e;
+    pub const SET_BUFFER_BASE_ADDRESS: u8 = 0x8f;
+    pub const SET_RX_DUTY_CYCLE: u8 = 0x94;
+    pub const SET_PA_CONFIG: u8 = 0x95;
+    pub const SET_TCXO_MODE: u8 = 0x97;
+    pub const GET_STATUS: u8 = 0xc0;
+    pub const SET_FS: u8 = 0xc1;
+    pub const SET_CAD: u8 = 0xc5;
+}
+
+/// Radio interrupt flags, in the IRQ status and masks.
+pub mod irq {
+    pub const TX_DONE: u16 = 1 << 0;
+    pub const RX_DONE: u16 = 1 << 1;
+    pub const PREAMBLE_DETECTED: u16 = 1 << 2;
+    pub const SYNC_WORD_VALID: u16 = 1 << 3;
+    pub const HEADER_VALID: u16 = 1 << 4;
+    pub const HEADER_ERROR: u16 = 1 << 5;
+    pub const CRC_ERROR: u16 = 1 << 6;
+    pub const CAD_DONE: u16 = 1 << 7;
+    pub const CAD_DETECTED: u16 = 1 << 8;
+    pub const TIMEOUT: u16 = 1 << 9;
+    pub const ALL: u16 = 0x03ff;
+}
+
+/// Error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    Spi(spi::Error),
+    /// The radio timed out, without receiving a packet.
+    Timeout,
+    /// A packet was received with a header or CRC error.
+    Crc,
+}
+
+impl From<spi::Error> for Error {
+    fn from(e: spi::Error) -> Self {
+        Self::Spi(e)
+    }
+}
+
+/// Count of LoRa symbols for channel activity detection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CadSymbols {
+    _1 = 0x00,
+    _2 = 0x01,
+    _4 = 0x02,
+    _8 = 0x03,
+    _16 = 0x04,
+}
+
+/// Channel activity detection parameters.
+///
+/// The detection thresholds depend on the spreading factor and the bandwidth, see the
+/// application note AN1200.48 of Semtech for their values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CadConfig {
+    pub symbols: CadSymbols,
+    pub detection_peak: u8,
+    pub detection_min: u8,
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler {}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::SUBGHZ_RADIO> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        // The radio interrupt is a level, masked until the IRQ status is cleared.
+        interrupt::typelevel::SUBGHZ_RADIO::disable();
+        IRQ_SIGNAL.signal(());
+    }
+}
+
+/// Sub-GHz radio driver.
+pub struct SubGhz<'d, Tx, Rx> {
+    spi: Spi<'d, SUBGHZSPI, Tx, Rx>,
+}
+
+impl<'d, Tx: TxDma<SUBGHZSPI>, Rx: RxDma<SUBGHZSPI>> SubGhz<'d, Tx, Rx> {
+    /// Create a radio driver, resetting the radio.
+    pub fn new(
+        peri: impl Peripheral<P = SUBGHZSPI> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::SUBGHZ_RADIO, InterruptHandler> + 'd,
+        txdma: impl Peripheral<P = Tx> + 'd,
+        rxdma: impl Peripheral<P = Rx> + 'd,
+    ) -> Self {
+        let spi = Spi::new_subghz(peri, txdma, rxdma);
+        interrupt::typelevel::SUBGHZ_RADIO::disable();
+
+        let mut this = Self { spi };
+        this.reset();
+        this
+    }
+
+    /// Reset the radio. It starts in the standby mode, with the RC oscillator.
+    pub fn reset(&mut self) {
+        RCC.csr().modify(|w| w.set_rfrst(true));
+        RCC.csr().modify(|w| w.set_rfrst(false));
+        PWR.subghzspicr().modify(|w| w.set_nss(Nss::HIGH));
+    }
+
+    /// Whether the radio is busy, processing a command, or asleep.
+    pub fn is_busy(&self) -> bool {
+        PWR.sr2().read().rfbusys() == Rfbusys::BUSY
+    }
+
+    /// Wait until the radio accepts a new command.
+    pub async fn wait_for_busy(&mut self) {
+        // Most commands take a few us, the wakeup from sleep ~300 us.
+        while self.is_busy() {
+            embassy_futures::yield_now().await;
+        }
+    }
+
+    /// Send a command, with its parameters.
+    pub async fn command(&mut self, opcode: u8, params: &[u8]) -> Result<(), Error> {
+        self.transaction(&[opcode], params, &mut []).await
+    }
+
+    /// Send a command reading data, and return the status of the radio, sent before the data.
+    pub async fn read_command(&mut self, opcode: u8, data: &mut [u8]) -> Result<u8, Error> {
+        let mut status = [0];
+        self.nss_low().await;
+        let result = async {
+            self.spi.write(&[opcode]).await?;
+            self.spi.read(&mut status).await?;
+            self.spi.read(data).await
+        }
+        .await;
+        self.nss_high();
+        result.map_err(Error::Spi)?;
+        Ok(status[0])
+    }
+
+    /// Write registers of the radio, from `addr`.
+    pub async fn write_registers(&mut self, addr: u16, data: &[u8]) -> Result<(), Error> {
+        let [high, low] = addr.to_be_bytes();
+        self.transaction(&[opcode::WRITE_REGISTER, high, low], data, &mut [])
+            .await
+    }
+
+    /// Read registers of the radio, from `addr`.
+    pub async fn read_registers(&mut self, addr: u16, data: &mut [u8]) -> Result<(), Error> {
+        let [high, low] = addr.to_be_bytes();
+        // The status follows the address.
+        self.transaction(&[opcode::READ_REGISTER, high, low, 0], &[], data)
+            .await
+    }
+
+    /// Write the data buffer of the radio, from `offset`.
+    pub async fn write_buffer(&mut self, offset: u8, data: &[u8]) -> Result<(), Error> {
+        self.transaction(&[opcode::WRITE_BUFFER, offset], data, &mut []).await
+    }
+
+    /// Read the data buffer of the radio, from `offset`.
+    pub async fn read_buffer(&mut self, offset: u8, data: &mut [u8]) -> Result<(), Error> {
+        self.transaction(&[opcode::READ_BUFFER, offset, 0], &[], data).await
+    }
+
+    /// The length and the buffer offset of the last received packet.
+    pub async fn rx_buffer_status(&mut self) -> Result<(u8, u8), Error> {
+        let mut status = [0; 2];
+        self.transaction(&[opcode::GET_RX_BUFFER_STATUS, 0], &[], &mut status)
+            .await?;
+        Ok((status[0], status[1]))
+    }
+
+    /// The pending radio interrupt flags, see [`irq`].
+    pub async fn irq_status(&mut self) -> Result<u16, Error> {
+        let mut status = [0; 2];
+        self.transaction(&[opcode::GET_IRQ_STATUS, 0], &[], &mut status).await?;
+        Ok(u16::from_be_bytes(status))
+    }
+
+    /// Clear radio interrupt flags.
+    pub async fn clear_irq(&mut self, flags: u16) -> Result<(), Error> {
+        self.command(opcode::CLR_IRQ_STATUS, &flags.to_be_bytes()).await
+    }
+
+    /// Wait for one of the radio interrupts of `flags`, and return the pending flags, cleared.
+    ///
+    /// The interrupts must be routed with [`Self::set_irq_mask()`] before starting the radio.
+    pub async fn wait_for_irq(&mut self, flags: u16) -> Result<u16, Error> {
+        loop {
+            let status = self.irq_status().await?;
+            if status & flags != 0 {
+                self.clear_irq(status).await?;
+                return Ok(status);
+            }
+
+            IRQ_SIGNAL.reset();
+            interrupt::typelevel::SUBGHZ_RADIO::unpend();
+            unsafe { interrupt::typelevel::SUBGHZ_RADIO::enable() };
+            IRQ_SIGNAL.wait().await;
+        }
+    }
+
+    /// Route radio interrupts to the `SUBGHZ_RADIO` interrupt.
+    pub async fn set_irq_mask(&mut self, flags: u16) -> Result<(), Error> {
+        let [high, low] = flags.to_be_bytes();
+        // The IRQ mask, and the masks of the 3 internal IRQ lines, all OR-ed to the interrupt.
+        let params = [high, low, high, low, 0, 0, 0, 0];
+        self.command(opcode::SET_DIO_IRQ_PARAMS, &params).await
+    }
+
+    /// Run a channel activity detection, with the LoRa modulation parameters set up, and return
+    /// whether a LoRa preamble was detected.
+    pub async fn cad(&mut self, config: &CadConfig) -> Result<bool, Error> {
+        // Exit to standby after the detection, with no timeout.
+        let params = [
+            config.symbols as u8,
+            config.detection_peak,
+            config.detection_min,
+            0x00,
+            0,
+            0,
+            0,
+        ];
+        self.command(opcode::SET_CAD_PARAMS, &params).await?;
+        self.prepare_irq(irq::CAD_DONE | irq::CAD_DETECTED).await?;
+        self.command(opcode::SET_CAD, &[]).await?;
+
+        let status = self.wait_for_irq(irq::CAD_DONE).await?;
+        Ok(status & irq::CAD_DETECTED != 0)
+    }
+
+    /// Receive with duty cycling, with the packet parameters set up: the radio listens for
+    /// `rx_period_us`, then sleeps for `sleep_period_us`, until it detects a preamble, and
+    /// receives the packet.
+    ///
+    /// Return the length and the buffer offset of the received packet, to read with
+    /// [`Self::read_buffer()`]. The periods are rounded to 15.625 us steps, up to 262 s.
+    pub async fn rx_duty_cycle(&mut self, rx_period_us: u32, sleep_period_us: u32) -> Result<(u8, u8), Error> {
+        let steps = |us: u32| ((us as u64 * 64 / 1000).clamp(1, 0xff_ffff) as u32).to_be_bytes();
+        let rx = steps(rx_period_us);
+        let sleep = steps(sleep_period_us);
+        let params = [rx[1], rx[2], rx[3], sleep[1], sleep[2], sleep[3]];
+
+        let flags = irq::RX_DONE | irq::HEADER_ERROR | irq::CRC_ERROR | irq::TIMEOUT;
+        self.prepare_irq(flags).await?;
+        self.command(opcode::SET_RX_DUTY_CYCLE, &params).await?;
+
+        let status = self.wait_for_irq(flags).await?;
+        if status & (irq::HEADER_ERROR | irq::CRC_ERROR) != 0 {
+            Err(Error::Crc)
+        } else if status & irq::TIMEOUT != 0 {
+            Err(Error::Timeout)
+        } else {
+            self.rx_buffer_status().await
+        }
+    }
+
+    async fn prepare_irq(&mut self, flags: u16) -> Result<(), Error> {
+        self.clear_irq(irq::ALL).await?;
+        self.set_irq_mask(flags).await
+    }
+
+    /// Write `header` and `write`, then read `read`, in a single NSS assertion.
+    async fn transaction(&mut self, header: &[u8], write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.nss_low().await;
+        let result = async {
+            self.spi.write(header).await?;
+            self.spi.write(write).await?;
+            self.spi.read(read).await
+        }
+        .await;
+        self.nss_high();
+        result.map_err(Error::Spi)
+    }
+
+    async fn nss_low(&mut self) {
+        // Asserting NSS wakes the radio up from sleep, it's busy until it's ready.
+        PWR.subghzspicr().modify(|w| w.set_nss(Nss::LOW));
+        self.wait_for_busy().await;
+    }
+
+    fn nss_high(&mut self) {
+        PWR.subghzspicr().modify(|w| w.set_nss(Nss::HIGH));
+    }
+}
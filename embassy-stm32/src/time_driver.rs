@@ -241,6 +241,59 @@ impl RtcDriver {
         })
     }
 
+    /// Number of ticks until the next alarm, `None` if no alarm is set.
+    #[cfg(feature = "low-power")]
+    fn time_until_next_alarm(&self) -> Option<u64> {
+        critical_section::with(|cs| {
+            let now = self.now();
+            self.alarms
+                .borrow(cs)
+                .iter()
+                .map(|alarm| alarm.timestamp.get())
+                .min()
+                .filter(|at| *at != u64::MAX)
+                .map(|at| at.saturating_sub(now))
+        })
+    }
+
+    /// Stop the counter, before entering Stop mode where the timer isn't clocked.
+    #[cfg(feature = "low-power")]
+    fn pause_time(&self) {
+        T::regs_gp16().cr1().modify(|w| w.set_cen(false));
+    }
+
+    /// Move the time forward by the `elapsed` ticks spent in Stop mode, and restart the counter.
+    #[cfg(feature = "low-power")]
+    fn resume_time(&self, elapsed: u64) {
+        let r = T::regs_gp16();
+
+        critical_section::with(|cs| {
+            let now = self.now() + elapsed;
+
+            // Inverse of `calc_now()`: the counter is in the upper half during odd periods.
+            let period = (now >> 15) as u32;
+            let counter = (now & 0x7fff) as u16 | (((period & 1) as u16) << 15);
+
+            self.period.store(period, Ordering::Relaxed);
+            r.cnt().write(|w| w.set_cnt(counter));
+            r.sr().write_value(regs::SrGp(0));
+
+            let t = (period as u64) << 15;
+            for n in 0..ALARM_COUNT {
+                let at = self.alarms.borrow(cs)[n].timestamp.get();
+                if at <= now {
+                    // The alarm expired during Stop mode.
+                    r.dier().modify(|w| w.set_ccie(n + 1, false));
+                    self.trigger_alarm(n, cs);
+                } else if at < t + 0xc000 {
+                    r.dier().modify(|w| w.set_ccie(n + 1, true));
+                }
+            }
+
+            r.cr1().modify(|w| w.set_cen(true));
+        })
+    }
+
     fn get_alarm<'a>(&'a self, cs: CriticalSection<'a>, alarm: AlarmHandle) -> &'a AlarmState {
         // safety: we're allowed to assume the AlarmState is created by us, and
         // we never create one that's out of bounds.
@@ -332,3 +385,19 @@ impl Driver for RtcDriver {
 pub(crate) fn init() {
     DRIVER.init()
 }
+
+/// Number of ticks until the next alarm of the time driver, `None` if no alarm is set.
+#[cfg(feature = "low-power")]
+pub(crate) fn time_until_next_alarm() -> Option<u64> {
+    DRIVER.time_until_next_alarm()
+}
+
+#[cfg(feature = "low-power")]
+pub(crate) fn pause_time() {
+    DRIVER.pause_time()
+}
+
+#[cfg(feature = "low-power")]
+pub(crate) fn resume_time(elapsed: u64) {
+    DRIVER.resume_time(elapsed)
+}
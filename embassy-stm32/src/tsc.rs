@@ -0,0 +1,567 @@
+#![macro_use]
+
+//! TSC, touch sensing controller.
+//!
+//! The TSC measures the capacitance of electrodes by transferring their charge to a sampling
+//! capacitor, in up to 8 groups of 4 IOs. In each group, one IO is connected to the sampling
+//! capacitor, and the others to electrodes, the channels. An acquisition measures one channel
+//! per group, all the groups in parallel: the count of charge transfers, read with
+//! [`Tsc::count()`], decreases when a finger gets close to the electrode.
+//!
+//! [`TouchKey`] turns the counts of a channel into debounced pressed and released events, and
+//! [`Tsc::wait_for_event()`] acquires the channels of several keys until one of them changes.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::gpio::sealed::{AFType, Pin as _};
+use crate::gpio::AnyPin;
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::tsc::regs;
+use crate::rcc::RccPeripheral;
+use crate::{interrupt, peripherals, Peripheral};
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Number of IO groups.
+pub const GROUPS: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The count of a group reached the max count value before the sampling capacitor was
+    /// charged, the electrode is likely shorted or disconnected.
+    MaxCount,
+    /// The channels of an acquisition must be in different groups, with a sampling capacitor.
+    InvalidChannels,
+}
+
+/// Division of the AHB clock for the charge transfer pulses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PulsePrescaler {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+}
+
+/// Count value ending an acquisition with [`Error::MaxCount`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MaxCount {
+    Count255,
+    Count511,
+    Count1023,
+    Count2047,
+    Count4095,
+    Count8191,
+    Count16383,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Duration of the high state of the charge transfer pulses, from 1 to 16 pulse periods.
+    pub charge_transfer_high: u8,
+    /// Duration of the low state of the charge transfer pulses, from 1 to 16 pulse periods.
+    pub charge_transfer_low: u8,
+    pub pulse_prescaler: PulsePrescaler,
+    pub max_count: MaxCount,
+    /// Spread spectrum deviation, from 1 to 128 periods of the AHB clock, or of half of it if
+    /// `spread_spectrum_div2` is set, reducing the emissions of the pulses.
+    pub spread_spectrum: Option<u8>,
+    pub spread_spectrum_div2: bool,
+    /// Leave the IOs floating between acquisitions, instead of driving them low.
+    pub floating_idle: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            charge_transfer_high: 2,
+            charge_transfer_low: 2,
+            pulse_prescaler: PulsePrescaler::Div4,
+            max_count: MaxCount::Count8191,
+            spread_spectrum: None,
+            spread_spectrum_div2: false,
+            floating_idle: false,
+        }
+    }
+}
+
+/// A channel, identified by its group and IO.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Channel {
+    /// Group, from 0 to 7.
+    pub group: u8,
+    /// IO in the group, from 0 to 3.
+    pub io: u8,
+}
+
+impl Channel {
+    fn bit(self) -> u32 {
+        1 << (self.group * 4 + self.io)
+    }
+}
+
+/// Role of an IO.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IoRole {
+    SamplingCapacitor,
+    Channel,
+}
+
+/// An IO pin of the TSC, with its role.
+pub struct IoPin<'d, T: Instance> {
+    pin: PeripheralRef<'d, AnyPin>,
+    af: u8,
+    channel: Channel,
+    role: IoRole,
+    _phantom: PhantomData<T>,
+}
+
+impl<'d, T: Instance> IoPin<'d, T> {
+    /// The channel of the IO.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+macro_rules! io_pins {
+    ($($trait:ident, $fn:ident, $group:literal, $io:literal;)*) => {
+        $(pin_trait!($trait, Instance);)*
+
+        impl<'d, T: Instance> IoPin<'d, T> {
+            $(
+                pub fn $fn(pin: impl Peripheral<P = impl $trait<T>> + 'd, role: IoRole) -> Self {
+                    into_ref!(pin);
+                    Self {
+                        af: pin.af_num(),
+                        pin: pin.map_into(),
+                        channel: Channel { group: $group, io: $io },
+                        role,
+                        _phantom: PhantomData,
+                    }
+                }
+            )*
+        }
+    };
+}
+
+io_pins!(
+    G1IO1Pin, g1_io1, 0, 0;
+    G1IO2Pin, g1_io2, 0, 1;
+    G1IO3Pin, g1_io3, 0, 2;
+    G1IO4Pin, g1_io4, 0, 3;
+    G2IO1Pin, g2_io1, 1, 0;
+    G2IO2Pin, g2_io2, 1, 1;
+    G2IO3Pin, g2_io3, 1, 2;
+    G2IO4Pin, g2_io4, 1, 3;
+    G3IO1Pin, g3_io1, 2, 0;
+    G3IO2Pin, g3_io2, 2, 1;
+    G3IO3Pin, g3_io3, 2, 2;
+    G3IO4Pin, g3_io4, 2, 3;
+    G4IO1Pin, g4_io1, 3, 0;
+    G4IO2Pin, g4_io2, 3, 1;
+    G4IO3Pin, g4_io3, 3, 2;
+    G4IO4Pin, g4_io4, 3, 3;
+    G5IO1Pin, g5_io1, 4, 0;
+    G5IO2Pin, g5_io2, 4, 1;
+    G5IO3Pin, g5_io3, 4, 2;
+    G5IO4Pin, g5_io4, 4, 3;
+    G6IO1Pin, g6_io1, 5, 0;
+    G6IO2Pin, g6_io2, 5, 1;
+    G6IO3Pin, g6_io3, 5, 2;
+    G6IO4Pin, g6_io4, 5, 3;
+    G7IO1Pin, g7_io1, 6, 0;
+    G7IO2Pin, g7_io2, 6, 1;
+    G7IO3Pin, g7_io3, 6, 2;
+    G7IO4Pin, g7_io4, 6, 3;
+    G8IO1Pin, g8_io1, 7, 0;
+    G8IO2Pin, g8_io2, 7, 1;
+    G8IO3Pin, g8_io3, 7, 2;
+    G8IO4Pin, g8_io4, 7, 3;
+);
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        T::regs().ier().write(|_| {});
+        WAKER.wake();
+    }
+}
+
+/// TSC driver.
+pub struct Tsc<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    /// Mask of the sampling capacitor IOs.
+    sampling: u32,
+    /// Mask of the channel IOs.
+    channels: u32,
+}
+
+impl<'d, T: Instance> Tsc<'d, T> {
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pins: impl IntoIterator<Item = IoPin<'d, T>>,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri);
+
+        T::enable();
+        T::reset();
+
+        let mut sampling = 0;
+        let mut channels = 0;
+        for io in pins {
+            match io.role {
+                IoRole::SamplingCapacitor => {
+                    io.pin.set_as_af(io.af, AFType::OutputOpenDrain);
+                    sampling |= io.channel.bit();
+                }
+                IoRole::Channel => {
+                    io.pin.set_as_af(io.af, AFType::OutputPushPull);
+                    channels |= io.channel.bit();
+                }
+            }
+        }
+
+        let r = T::regs();
+        r.cr().write(|w| {
+            w.set_tsce(true);
+            w.set_ctph(config.charge_transfer_high.clamp(1, 16) - 1);
+            w.set_ctpl(config.charge_transfer_low.clamp(1, 16) - 1);
+            w.set_pgpsc(config.pulse_prescaler as u8);
+            w.set_mcv(config.max_count as u8);
+            if let Some(deviation) = config.spread_spectrum {
+                w.set_sse(true);
+                w.set_ssd(deviation.clamp(1, 128) - 1);
+                w.set_sspsc(config.spread_spectrum_div2);
+            }
+            w.set_iodef(config.floating_idle);
+        });
+
+        // The Schmitt triggers of the IOs disturb the measurement.
+        r.iohcr().modify(|w| w.0 &= !(sampling | channels));
+        r.ioscr().write_value(regs::Ioscr(sampling));
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _peri: peri,
+            sampling,
+            channels,
+        }
+    }
+
+    /// Select the channels measured by the next acquisitions, at most one per group.
+    pub fn set_channels(&mut self, channels: &[Channel]) -> Result<(), Error> {
+        let mut ios = 0;
+        let mut groups = 0;
+        for channel in channels {
+            let group = 1 << channel.group;
+            if groups & group != 0 || self.channels & channel.bit() == 0 {
+                return Err(Error::InvalidChannels);
+            }
+            if self.sampling & (0b1111 << (channel.group * 4)) == 0 {
+                return Err(Error::InvalidChannels);
+            }
+            ios |= channel.bit();
+            groups |= group;
+        }
+
+        T::regs().ioccr().write_value(regs::Ioccr(ios));
+        T::regs().iogcsr().write_value(regs::Iogcsr(groups));
+        Ok(())
+    }
+
+    /// Acquire the selected channels, and wait for the end of the acquisition.
+    pub async fn acquire(&mut self) -> Result<(), Error> {
+        self.start();
+
+        let isr = poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            T::regs().ier().write(|w| {
+                w.set_eoaie(true);
+                w.set_mceie(true);
+            });
+
+            let isr = T::regs().isr().read();
+            if isr.eoaf() || isr.mcef() {
+                Poll::Ready(isr)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        self.finish(isr)
+    }
+
+    /// Acquire the selected channels, and busy-wait for the end of the acquisition.
+    pub fn blocking_acquire(&mut self) -> Result<(), Error> {
+        self.start();
+
+        let isr = loop {
+            let isr = T::regs().isr().read();
+            if isr.eoaf() || isr.mcef() {
+                break isr;
+            }
+        };
+
+        self.finish(isr)
+    }
+
+    fn start(&mut self) {
+        clear_flags::<T>();
+        T::regs().cr().modify(|w| w.set_start(true));
+    }
+
+    fn finish(&mut self, isr: regs::Isr) -> Result<(), Error> {
+        T::regs().ier().write(|_| {});
+        clear_flags::<T>();
+
+        if isr.mcef() {
+            Err(Error::MaxCount)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Count of charge transfers of the group of `channel` in the last acquisition.
+    pub fn count(&self, channel: Channel) -> u16 {
+        T::regs().iogcr(channel.group as usize).read().cnt()
+    }
+
+    /// Acquire the channels of `keys` until one of them is pressed or released, and return its
+    /// index and event.
+    ///
+    /// The channels of the keys in different groups are acquired together, and up to 32 keys
+    /// are handled. A key in error, for example with a disconnected electrode, stays released.
+    pub async fn wait_for_event(&mut self, keys: &mut [TouchKey]) -> Result<(usize, KeyEvent), Error> {
+        if keys.is_empty() {
+            return Err(Error::InvalidChannels);
+        }
+
+        loop {
+            // The keys are measured in rounds of one key per group.
+            let mut measured = [false; 32];
+            let keys_count = keys.len().min(measured.len());
+            loop {
+                let mut round = [None; GROUPS];
+                for (i, key) in keys[..keys_count].iter().enumerate() {
+                    let group = key.channel.group as usize;
+                    if !measured[i] && round[group].is_none() {
+                        round[group] = Some(i);
+                    }
+                }
+                if round.iter().all(|k| k.is_none()) {
+                    break;
+                }
+
+                let mut channels = [Channel { group: 0, io: 0 }; GROUPS];
+                let mut n = 0;
+                for &i in round.iter().flatten() {
+                    channels[n] = keys[i].channel;
+                    n += 1;
+                    measured[i] = true;
+                }
+                self.set_channels(&channels[..n])?;
+
+                let result = self.acquire().await;
+                let mut event = None;
+                for &i in round.iter().flatten() {
+                    let count = match result {
+                        Ok(()) => Some(self.count(keys[i].channel)),
+                        Err(_) => None,
+                    };
+                    if let Some(e) = keys[i].update(count) {
+                        event.get_or_insert((i, e));
+                    }
+                }
+                if let Some(event) = event {
+                    return Ok(event);
+                }
+            }
+        }
+    }
+}
+
+impl<'d, T: Instance> Drop for Tsc<'d, T> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        T::regs().cr().write(|_| {});
+        T::disable();
+    }
+}
+
+/// Event of a [`TouchKey`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyEvent {
+    Pressed,
+    Released,
+}
+
+/// Debounced touch detection on the counts of a channel.
+///
+/// The key calibrates its baseline, the count when untouched, on its first samples, and tracks
+/// its slow drift while released. The key is pressed when the count drops below the baseline
+/// by more than the threshold, and released when it gets back above the baseline minus the
+/// hysteresis, both for `debounce` consecutive samples.
+pub struct TouchKey {
+    channel: Channel,
+    threshold: u16,
+    hysteresis: u16,
+    debounce: u8,
+    /// Sum of the calibration samples, while calibrating.
+    calibration: u32,
+    samples: u8,
+    baseline: u16,
+    pressed: bool,
+    /// Consecutive samples contradicting the state.
+    changes: u8,
+}
+
+/// Number of samples averaged into the baseline on calibration.
+const CALIBRATION_SAMPLES: u8 = 8;
+
+impl TouchKey {
+    /// Create a key on `channel`, pressed when its count drops by more than `threshold`, and
+    /// released when the drop gets below `hysteresis`, for `debounce` samples.
+    pub fn new(channel: Channel, threshold: u16, hysteresis: u16, debounce: u8) -> Self {
+        Self {
+            channel,
+            threshold,
+            hysteresis: hysteresis.min(threshold),
+            debounce: debounce.max(1),
+            calibration: 0,
+            samples: 0,
+            baseline: 0,
+            pressed: false,
+            changes: 0,
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Count of the channel when untouched, 0 while calibrating.
+    pub fn baseline(&self) -> u16 {
+        self.baseline
+    }
+
+    /// Restart the calibration of the baseline, for example after a change of configuration.
+    pub fn recalibrate(&mut self) {
+        self.calibration = 0;
+        self.samples = 0;
+        self.baseline = 0;
+        self.pressed = false;
+        self.changes = 0;
+    }
+
+    /// Update the key with the count of a new acquisition, `None` if it failed, and return the
+    /// event it caused.
+    pub fn update(&mut self, count: Option<u16>) -> Option<KeyEvent> {
+        let count = match count {
+            Some(count) => count,
+            None => {
+                self.changes = 0;
+                return match core::mem::replace(&mut self.pressed, false) {
+                    true => Some(KeyEvent::Released),
+                    false => None,
+                };
+            }
+        };
+
+        if self.samples < CALIBRATION_SAMPLES {
+            self.calibration += count as u32;
+            self.samples += 1;
+            if self.samples == CALIBRATION_SAMPLES {
+                self.baseline = (self.calibration / CALIBRATION_SAMPLES as u32) as u16;
+            }
+            return None;
+        }
+
+        let delta = self.baseline.saturating_sub(count);
+        let change = match self.pressed {
+            false => delta > self.threshold,
+            true => delta < self.hysteresis,
+        };
+
+        if !change {
+            self.changes = 0;
+            if !self.pressed {
+                // Follow the slow drift of the baseline, with the temperature and humidity.
+                let baseline = self.baseline as i32;
+                self.baseline = (baseline + (count as i32 - baseline) / 16) as u16;
+            }
+            return None;
+        }
+
+        self.changes += 1;
+        if self.changes < self.debounce {
+            return None;
+        }
+
+        self.changes = 0;
+        self.pressed = !self.pressed;
+        match self.pressed {
+            true => Some(KeyEvent::Pressed),
+            false => Some(KeyEvent::Released),
+        }
+    }
+}
+
+fn clear_flags<T: Instance>() {
+    T::regs().icr().write(|w| {
+        w.set_eoaic(true);
+        w.set_mceic(true);
+    });
+}
+
+pub(crate) mod sealed {
+    pub trait Instance {
+        fn regs() -> crate::pac::tsc::Tsc;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, tsc, $block:ident, GLOBAL, $irq:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::tsc::Tsc {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
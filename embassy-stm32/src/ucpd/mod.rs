@@ -0,0 +1,441 @@
+#![macro_use]
+
+//! USB Type-C / Power Delivery interface (UCPD).
+//!
+//! [`Ucpd`] is a Type-C sink: it presents the Rd pull-downs on both CC lines, and reports the
+//! voltage on them, which tells whether a source is attached and the current it offers. Once
+//! attached, the BMC PHY is enabled on the CC line of the cable, to transmit and receive Power
+//! Delivery messages with DMA. GoodCRC, message IDs and retries are left to the protocol layer,
+//! like the minimal one of the [`sink`] module.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::dma::Transfer;
+use crate::gpio::sealed::Pin as _;
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::ucpd::{regs, vals};
+use crate::rcc::RccPeripheral;
+use crate::{interrupt, peripherals, Peripheral};
+
+#[cfg(feature = "time")]
+pub mod sink;
+
+/// CFGR1.RXORDSETEN bits of SOP and hard reset.
+const RXORDSETEN_SOP_HARD_RESET: u16 = 1 << 0 | 1 << 3;
+
+/// K-codes of the SOP ordered set.
+const ORDSET_SOP: u32 = 0x18 | 0x18 << 5 | 0x18 << 10 | 0x11 << 15;
+
+/// Nominal half-bit clock, for the 300 kbit/s BMC bit rate.
+const HBIT_CLK_HZ: u32 = 600_000;
+/// Nominal inter-frame gap timer clock.
+const IFRGAP_CLK_HZ: u32 = 625_000;
+/// Transition window of 10 half-bits, within the 12-20 us of the specification.
+const TRANSWIN: u8 = 9;
+/// Highest clock of the PHY.
+const MAX_UCPD_CLK_HZ: u32 = 18_000_000;
+
+/// Voltage state of a CC line, seen from the sink.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CcVState {
+    /// No source on this line, or the VCONN line of the cable.
+    Open,
+    /// The source offers the default USB current, 500 mA or 900 mA.
+    UsbDefault,
+    /// The source offers 1.5 A at 5 V.
+    Current1A5,
+    /// The source offers 3 A at 5 V.
+    Current3A0,
+}
+
+impl CcVState {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => CcVState::Open,
+            1 => CcVState::UsbDefault,
+            2 => CcVState::Current1A5,
+            _ => CcVState::Current3A0,
+        }
+    }
+}
+
+/// CC line of the Type-C connector.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CcPin {
+    Cc1,
+    Cc2,
+}
+
+/// Receive error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RxError {
+    /// The message has a wrong CRC, or a BMC decoding error.
+    Crc,
+    /// The message didn't fit in the buffer, or the DMA didn't keep up.
+    Overrun,
+    /// A hard reset was received.
+    HardReset,
+}
+
+/// Transmit error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxError {
+    /// The transmission was discarded, because a message was being received.
+    Discarded,
+    /// The transmission was aborted, because the DMA didn't keep up.
+    Aborted,
+    /// A hard reset was received while transmitting.
+    HardReset,
+}
+
+/// UCPD interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // Mask the interrupts that fired, the futures unmask them again while waiting.
+        let r = T::regs();
+        let sr = r.sr().read();
+        r.imr().modify(|w| w.0 &= !sr.0);
+        T::waker().wake();
+    }
+}
+
+/// UCPD driver, as a sink.
+pub struct Ucpd<'d, T: Instance, RxDma, TxDma> {
+    _peri: PeripheralRef<'d, T>,
+    rx_dma: PeripheralRef<'d, RxDma>,
+    tx_dma: PeripheralRef<'d, TxDma>,
+}
+
+impl<'d, T: Instance, RxDma: self::RxDma<T>, TxDma: self::TxDma<T>> Ucpd<'d, T, RxDma, TxDma> {
+    /// Create a UCPD driver, presenting the sink pull-downs on both CC lines.
+    ///
+    /// This also disables the dead battery pull-downs, which kept the source supplying VBUS
+    /// until now.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        cc1: impl Peripheral<P = impl Cc1Pin<T>> + 'd,
+        cc2: impl Peripheral<P = impl Cc2Pin<T>> + 'd,
+        rx_dma: impl Peripheral<P = RxDma> + 'd,
+        tx_dma: impl Peripheral<P = TxDma> + 'd,
+    ) -> Self {
+        into_ref!(peri, cc1, cc2, rx_dma, tx_dma);
+
+        cc1.set_as_analog();
+        cc2.set_as_analog();
+
+        T::enable();
+        T::reset();
+
+        // Find the lowest prescaler keeping the clock under its maximum.
+        let pclk = T::frequency().0;
+        let psc = unwrap!((0..=4).find(|psc| pclk >> psc <= MAX_UCPD_CLK_HZ));
+        let ucpd_clk = pclk >> psc;
+        let hbitclkdiv = (ucpd_clk + HBIT_CLK_HZ / 2) / HBIT_CLK_HZ - 1;
+        let ifrgap = ((ucpd_clk + IFRGAP_CLK_HZ / 2) / IFRGAP_CLK_HZ - 1).clamp(1, 31);
+        trace!(
+            "UCPD clock {} Hz, PSC={}, HBITCLKDIV={}, IFRGAP={}",
+            ucpd_clk,
+            psc,
+            hbitclkdiv,
+            ifrgap
+        );
+
+        let r = T::regs();
+        r.cfgr1().write(|w| {
+            w.set_hbitclkdiv(hbitclkdiv as u8);
+            w.set_ifrgap(ifrgap as u8);
+            w.set_transwin(TRANSWIN);
+            w.set_psc_usbpdclk(psc as u8);
+            w.set_rxordseten(RXORDSETEN_SOP_HARD_RESET);
+            w.set_txdmaen(true);
+            w.set_rxdmaen(true);
+        });
+        r.cfgr1().modify(|w| w.set_ucpden(true));
+
+        r.cr().write(|w| {
+            w.set_anamode(vals::Anamode::SINK);
+            w.set_ccenable(vals::Ccenable::BOTH);
+        });
+        disable_dead_battery::<T>();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _peri: peri,
+            rx_dma,
+            tx_dma,
+        }
+    }
+
+    /// Voltage state of the CC1 and CC2 lines.
+    pub fn cc_vstate(&self) -> (CcVState, CcVState) {
+        let sr = T::regs().sr().read();
+        (
+            CcVState::from_bits(sr.typec_vstate_cc1().to_bits()),
+            CcVState::from_bits(sr.typec_vstate_cc2().to_bits()),
+        )
+    }
+
+    /// Wait for the voltage state of a CC line to change, and return the new states.
+    pub async fn wait_for_cc_vstate_change(&self) -> (CcVState, CcVState) {
+        let clear = |w: &mut regs::Icr| {
+            w.set_typecevt1cf(true);
+            w.set_typecevt2cf(true);
+        };
+        T::regs().icr().write(clear);
+        wait_for::<T>(|w| {
+            w.set_typecevt1ie(true);
+            w.set_typecevt2ie(true);
+        })
+        .await;
+        T::regs().icr().write(clear);
+        self.cc_vstate()
+    }
+
+    /// Enable the PHY on the CC line of the cable, the one which isn't open.
+    pub fn enable_pd_phy(&mut self, cc: CcPin) {
+        let phyccsel = match cc {
+            CcPin::Cc1 => vals::Phyccsel::CC1,
+            CcPin::Cc2 => vals::Phyccsel::CC2,
+        };
+        T::regs().cr().modify(|w| {
+            w.set_phyccsel(phyccsel);
+            w.set_phyrxen(true);
+        });
+    }
+
+    /// Disable the PHY, once the source is detached.
+    pub fn disable_pd_phy(&mut self) {
+        T::regs().cr().modify(|w| w.set_phyrxen(false));
+    }
+
+    /// Receive a SOP message into `buf`, header included, and return its length.
+    pub async fn receive(&mut self, buf: &mut [u8]) -> Result<usize, RxError> {
+        let r = T::regs();
+        clear_rx_events::<T>();
+
+        let request = self.rx_dma.request();
+        let transfer = unsafe {
+            Transfer::new_read(
+                &mut self.rx_dma,
+                request,
+                r.rxdr().as_ptr() as *mut u8,
+                buf,
+                Default::default(),
+            )
+        };
+
+        let sr = wait_for::<T>(|w| {
+            w.set_rxmsgendie(true);
+            w.set_rxhrstdetie(true);
+        })
+        .await;
+        drop(transfer);
+        clear_rx_events::<T>();
+
+        if sr.rxhrstdet() {
+            return Err(RxError::HardReset);
+        }
+        if sr.rxerr() {
+            return Err(RxError::Crc);
+        }
+        let len = r.rx_payszr().read().rxpaysz() as usize;
+        if sr.rxovr() || len > buf.len() {
+            return Err(RxError::Overrun);
+        }
+        // Only SOP and hard resets are enabled.
+        debug_assert!(r.rx_ordsetr().read().rxordset().to_bits() == 0);
+        Ok(len)
+    }
+
+    /// Transmit a SOP message, header included.
+    pub async fn transmit(&mut self, buf: &[u8]) -> Result<(), TxError> {
+        let r = T::regs();
+        r.tx_ordsetr().write(|w| w.set_txordset(ORDSET_SOP));
+        r.tx_payszr().write(|w| w.set_txpaysz(buf.len() as u16));
+        clear_tx_events::<T>();
+
+        let request = self.tx_dma.request();
+        let transfer = unsafe {
+            Transfer::new_write(
+                &mut self.tx_dma,
+                request,
+                buf,
+                r.txdr().as_ptr() as *mut u8,
+                Default::default(),
+            )
+        };
+
+        r.cr().modify(|w| {
+            w.set_txmode(vals::Txmode::PACKET);
+            w.set_txsend(true);
+        });
+
+        let sr = wait_for::<T>(|w| {
+            w.set_txmsgsentie(true);
+            w.set_txmsgdiscie(true);
+            w.set_txmsgabtie(true);
+            w.set_rxhrstdetie(true);
+        })
+        .await;
+        drop(transfer);
+        clear_tx_events::<T>();
+
+        if sr.txmsgsent() {
+            Ok(())
+        } else if sr.rxhrstdet() {
+            Err(TxError::HardReset)
+        } else if sr.txmsgdisc() {
+            Err(TxError::Discarded)
+        } else {
+            Err(TxError::Aborted)
+        }
+    }
+
+    /// Transmit a hard reset.
+    pub async fn transmit_hard_reset(&mut self) -> Result<(), TxError> {
+        let r = T::regs();
+        let clear = |w: &mut regs::Icr| {
+            w.set_hrstdisccf(true);
+            w.set_hrstsentcf(true);
+        };
+        r.icr().write(clear);
+        r.cr().modify(|w| w.set_txhrst(true));
+
+        let sr = wait_for::<T>(|w| {
+            w.set_hrstdiscie(true);
+            w.set_hrstsentie(true);
+        })
+        .await;
+        r.icr().write(clear);
+
+        match sr.hrstsent() {
+            true => Ok(()),
+            false => Err(TxError::Discarded),
+        }
+    }
+}
+
+impl<'d, T: Instance, RxDma, TxDma> Drop for Ucpd<'d, T, RxDma, TxDma> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        T::regs().cfgr1().write(|_| {});
+        T::disable();
+    }
+}
+
+/// Wait for any of the flags of the `events` interrupts, and return the status register.
+///
+/// The interrupt enable bits of IMR are at the positions of their flags in SR.
+async fn wait_for<T: Instance>(events: impl FnOnce(&mut regs::Imr)) -> regs::Sr {
+    let mut mask = regs::Imr(0);
+    events(&mut mask);
+
+    poll_fn(|cx| {
+        T::waker().register(cx.waker());
+        let r = T::regs();
+        let sr = r.sr().read();
+        if sr.0 & mask.0 != 0 {
+            Poll::Ready(sr)
+        } else {
+            r.imr().modify(|w| w.0 |= mask.0);
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+fn clear_rx_events<T: Instance>() {
+    T::regs().icr().write(|w| {
+        w.set_rxorddetcf(true);
+        w.set_rxhrstdetcf(true);
+        w.set_rxovrcf(true);
+        w.set_rxmsgendcf(true);
+    });
+}
+
+fn clear_tx_events<T: Instance>() {
+    T::regs().icr().write(|w| {
+        w.set_txmsgdisccf(true);
+        w.set_txmsgsentcf(true);
+        w.set_txmsgabtcf(true);
+        w.set_txundcf(true);
+    });
+}
+
+/// Let the UCPD take over the CC lines from the dead battery pull-downs.
+fn disable_dead_battery<T: Instance>() {
+    #[cfg(stm32g0)]
+    {
+        crate::peripherals::SYSCFG::enable();
+        crate::pac::SYSCFG.cfgr1().modify(|w| {
+            w.set_ucpd1_strobe(true);
+            w.set_ucpd2_strobe(true);
+        });
+    }
+
+    #[cfg(any(stm32g4, stm32l5))]
+    {
+        crate::peripherals::PWR::enable();
+        crate::pac::PWR.cr3().modify(|w| w.set_ucpd_dbdis(true));
+    }
+
+    #[cfg(stm32u5)]
+    {
+        crate::pac::RCC.ahb3enr().modify(|w| w.set_pwren(true));
+        crate::pac::PWR.ucpdr().modify(|w| w.set_ucpd_dbdis(true));
+    }
+}
+
+pub(crate) mod sealed {
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    pub trait Instance {
+        fn regs() -> crate::pac::ucpd::Ucpd;
+        fn waker() -> &'static AtomicWaker;
+    }
+}
+
+pub trait Instance: sealed::Instance + RccPeripheral + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pin_trait!(Cc1Pin, Instance);
+pin_trait!(Cc2Pin, Instance);
+
+dma_trait!(RxDma, Instance);
+dma_trait!(TxDma, Instance);
+
+foreach_interrupt!(
+    ($inst:ident, ucpd, $block:ident, GLOBAL, $irq:ident) => {
+        impl sealed::Instance for peripherals::$inst {
+            fn regs() -> crate::pac::ucpd::Ucpd {
+                crate::pac::$inst
+            }
+
+            fn waker() -> &'static AtomicWaker {
+                static WAKER: AtomicWaker = AtomicWaker::new();
+                &WAKER
+            }
+        }
+
+        impl Instance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
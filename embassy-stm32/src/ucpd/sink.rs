@@ -0,0 +1,439 @@
+//! Minimal USB Power Delivery sink policy engine.
+//!
+//! [`Sink`] waits for a source to be attached, requests the fixed supply with the highest voltage
+//! allowed by its [`SinkConfig`], and reports the contract. It answers the messages a sink must
+//! handle in the ready state: new source capabilities, soft resets and sink capability requests.
+//! Programmable and variable supplies, swaps and extended messages aren't supported.
+
+use embassy_time::{with_timeout, Duration, Timer};
+
+use super::{CcPin, CcVState, Instance, RxDma, RxError, TxDma, TxError, Ucpd};
+
+/// Longest non-extended message: the header and 7 data objects.
+const MAX_MESSAGE_SIZE: usize = 2 + 7 * 4;
+
+// Control message types
+const MSG_GOOD_CRC: u8 = 0x01;
+const MSG_ACCEPT: u8 = 0x03;
+const MSG_REJECT: u8 = 0x04;
+const MSG_PING: u8 = 0x05;
+const MSG_PS_RDY: u8 = 0x06;
+const MSG_GET_SINK_CAP: u8 = 0x08;
+const MSG_WAIT: u8 = 0x0c;
+const MSG_SOFT_RESET: u8 = 0x0d;
+const MSG_NOT_SUPPORTED: u8 = 0x10;
+
+// Data message types
+const MSG_SOURCE_CAPABILITIES: u8 = 0x01;
+const MSG_REQUEST: u8 = 0x02;
+const MSG_SINK_CAPABILITIES: u8 = 0x04;
+
+const SPEC_REVISION_2_0: u8 = 0b01;
+const SPEC_REVISION_3_0: u8 = 0b10;
+
+/// Transmissions are retried twice without GoodCRC.
+const RETRY_COUNT: usize = 2;
+const HARD_RESET_COUNT: usize = 2;
+
+/// tCCDebounce
+const CC_DEBOUNCE: Duration = Duration::from_millis(150);
+/// tReceive, waiting for the GoodCRC of a transmitted message.
+const RECEIVE_TIMEOUT: Duration = Duration::from_micros(1100);
+/// tTypeCSinkWaitCap
+const SINK_WAIT_CAP_TIMEOUT: Duration = Duration::from_millis(620);
+/// tSenderResponse
+const SENDER_RESPONSE_TIMEOUT: Duration = Duration::from_millis(30);
+/// tPSTransition
+const PS_TRANSITION_TIMEOUT: Duration = Duration::from_millis(550);
+/// Period of the CC line checks, to detect the detach while waiting for messages.
+const DETACH_POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// Sink configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SinkConfig {
+    /// Highest voltage to request, in mV.
+    pub max_voltage_mv: u32,
+    /// Current to request, in mA. The source's maximum is requested if it's lower.
+    pub current_ma: u32,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            max_voltage_mv: 5000,
+            current_ma: 500,
+        }
+    }
+}
+
+/// Power contract negotiated with the source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Contract {
+    pub voltage_mv: u32,
+    pub current_ma: u32,
+}
+
+/// Sink event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SinkEvent {
+    /// A source was attached, offering this Type-C current at 5 V until a contract is negotiated.
+    Attached(CcVState),
+    /// A contract was negotiated, the source supplies the new voltage.
+    Contract(Contract),
+    /// The source doesn't negotiate, the Type-C current is the only one available.
+    NoContract,
+    /// The source was detached.
+    Detached,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Detached,
+    /// Waiting for the source capabilities, after attach or a reset.
+    WaitCapabilities,
+    /// Contract negotiated, or Type-C current only.
+    Ready,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Error {
+    /// The source didn't acknowledge a message, or didn't respond in time.
+    Timeout,
+    HardReset,
+    Detached,
+}
+
+/// A received message.
+struct Message {
+    header: u16,
+    objects: [u32; 7],
+}
+
+impl Message {
+    fn message_type(&self) -> u8 {
+        (self.header & 0x1f) as u8
+    }
+
+    fn object_count(&self) -> usize {
+        ((self.header >> 12) & 0b111) as usize
+    }
+
+    fn message_id(&self) -> u8 {
+        ((self.header >> 9) & 0b111) as u8
+    }
+
+    fn spec_revision(&self) -> u8 {
+        ((self.header >> 6) & 0b11) as u8
+    }
+
+    fn is_control(&self, message_type: u8) -> bool {
+        self.object_count() == 0 && self.message_type() == message_type
+    }
+
+    fn is_data(&self, message_type: u8) -> bool {
+        self.object_count() != 0 && self.message_type() == message_type
+    }
+
+    fn objects(&self) -> &[u32] {
+        &self.objects[..self.object_count()]
+    }
+}
+
+/// USB PD sink.
+pub struct Sink<'d, T: Instance, Rx, Tx> {
+    ucpd: Ucpd<'d, T, Rx, Tx>,
+    config: SinkConfig,
+    state: State,
+    cc: CcPin,
+    spec_revision: u8,
+    tx_message_id: u8,
+    rx_message_id: Option<u8>,
+    hard_resets: usize,
+}
+
+impl<'d, T: Instance, Rx: RxDma<T>, Tx: TxDma<T>> Sink<'d, T, Rx, Tx> {
+    pub fn new(ucpd: Ucpd<'d, T, Rx, Tx>, config: SinkConfig) -> Self {
+        Self {
+            ucpd,
+            config,
+            state: State::Detached,
+            cc: CcPin::Cc1,
+            spec_revision: SPEC_REVISION_3_0,
+            tx_message_id: 0,
+            rx_message_id: None,
+            hard_resets: 0,
+        }
+    }
+
+    /// Run the policy engine until the next event.
+    pub async fn wait_for_event(&mut self) -> SinkEvent {
+        loop {
+            let result = match self.state {
+                State::Detached => return self.wait_for_attach().await,
+                State::WaitCapabilities => self.wait_for_capabilities().await,
+                State::Ready => self.ready().await,
+            };
+
+            match result {
+                Ok(Some(event)) => return event,
+                Ok(None) => {}
+                Err(Error::Detached) => {
+                    self.ucpd.disable_pd_phy();
+                    self.state = State::Detached;
+                    return SinkEvent::Detached;
+                }
+                Err(Error::HardReset) => {
+                    debug!("hard reset");
+                    self.reset_protocol();
+                    self.state = State::WaitCapabilities;
+                }
+                Err(Error::Timeout) => {
+                    if self.hard_resets < HARD_RESET_COUNT {
+                        self.hard_resets += 1;
+                        let _ = self.ucpd.transmit_hard_reset().await;
+                        self.reset_protocol();
+                        self.state = State::WaitCapabilities;
+                    } else {
+                        self.state = State::Ready;
+                        return SinkEvent::NoContract;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn wait_for_attach(&mut self) -> SinkEvent {
+        loop {
+            // Attached when exactly one CC line sees the Rp of the source, the other one is open, or
+            // is VCONN of the cable.
+            let (cc, vstate) = match self.ucpd.cc_vstate() {
+                (CcVState::Open, CcVState::Open) => {
+                    self.ucpd.wait_for_cc_vstate_change().await;
+                    continue;
+                }
+                (vstate, CcVState::Open) => (CcPin::Cc1, vstate),
+                (CcVState::Open, vstate) => (CcPin::Cc2, vstate),
+                _ => (CcPin::Cc1, CcVState::UsbDefault),
+            };
+
+            Timer::after(CC_DEBOUNCE).await;
+            if self.cc_vstate(cc) == CcVState::Open {
+                continue;
+            }
+
+            debug!("attached on {}, {}", cc, vstate);
+            self.cc = cc;
+            self.ucpd.enable_pd_phy(cc);
+            self.reset_protocol();
+            self.hard_resets = 0;
+            self.state = State::WaitCapabilities;
+            return SinkEvent::Attached(vstate);
+        }
+    }
+
+    async fn wait_for_capabilities(&mut self) -> Result<Option<SinkEvent>, Error> {
+        let message = self.receive_until(SINK_WAIT_CAP_TIMEOUT).await?;
+        if message.is_data(MSG_SOURCE_CAPABILITIES) {
+            self.negotiate(&message).await.map(Some)
+        } else {
+            self.handle_unexpected(&message).await.map(|_| None)
+        }
+    }
+
+    async fn ready(&mut self) -> Result<Option<SinkEvent>, Error> {
+        let message = loop {
+            match with_timeout(DETACH_POLL_PERIOD, self.receive()).await {
+                Ok(message) => break message?,
+                Err(_) => self.check_attached()?,
+            }
+        };
+
+        if message.is_data(MSG_SOURCE_CAPABILITIES) {
+            self.negotiate(&message).await.map(Some)
+        } else if message.is_control(MSG_GET_SINK_CAP) {
+            // A single vSafe5V fixed supply.
+            let pdo = (5000 / 50) << 10 | (self.config.current_ma / 10).min(0x3ff);
+            self.transmit(MSG_SINK_CAPABILITIES, &[pdo]).await.map(|_| None)
+        } else if message.is_control(MSG_PING) {
+            Ok(None)
+        } else {
+            self.handle_unexpected(&message).await.map(|_| None)
+        }
+    }
+
+    /// Request a fixed supply from the source capabilities, and wait for it.
+    async fn negotiate(&mut self, capabilities: &Message) -> Result<SinkEvent, Error> {
+        // Answer in the revision of the source, up to 3.0.
+        self.spec_revision = capabilities.spec_revision().min(SPEC_REVISION_3_0);
+
+        // The first PDO is always the vSafe5V fixed supply.
+        let (position, voltage_mv, max_current_ma) = capabilities
+            .objects()
+            .iter()
+            .enumerate()
+            .filter(|(_, pdo)| *pdo >> 30 == 0b00)
+            .map(|(i, pdo)| (i + 1, ((pdo >> 10) & 0x3ff) * 50, (pdo & 0x3ff) * 10))
+            .filter(|(_, voltage_mv, _)| *voltage_mv <= self.config.max_voltage_mv)
+            .max_by_key(|(_, voltage_mv, _)| *voltage_mv)
+            .unwrap_or((1, 5000, 0));
+
+        let current_ma = self.config.current_ma.min(max_current_ma);
+        let mut rdo = (position as u32) << 28 | (current_ma / 10) << 10 | current_ma / 10;
+        // No USB suspend
+        rdo |= 1 << 24;
+        if current_ma < self.config.current_ma {
+            // Capability mismatch
+            rdo |= 1 << 26;
+        }
+
+        debug!("requesting {} mV, {} mA", voltage_mv, current_ma);
+        self.transmit(MSG_REQUEST, &[rdo]).await?;
+
+        let response = self.receive_until(SENDER_RESPONSE_TIMEOUT).await?;
+        if !response.is_control(MSG_ACCEPT) {
+            if response.is_control(MSG_REJECT) || response.is_control(MSG_WAIT) {
+                debug!("request rejected");
+                self.state = State::Ready;
+                return Ok(SinkEvent::NoContract);
+            }
+            return self.handle_unexpected(&response).await.map(|_| SinkEvent::NoContract);
+        }
+
+        let ready = self.receive_until(PS_TRANSITION_TIMEOUT).await?;
+        if !ready.is_control(MSG_PS_RDY) {
+            return Err(Error::Timeout);
+        }
+
+        self.state = State::Ready;
+        self.hard_resets = 0;
+        Ok(SinkEvent::Contract(Contract { voltage_mv, current_ma }))
+    }
+
+    /// Handle a message the policy engine doesn't expect in its state.
+    async fn handle_unexpected(&mut self, message: &Message) -> Result<(), Error> {
+        if message.is_control(MSG_SOFT_RESET) {
+            self.reset_protocol();
+            self.transmit(MSG_ACCEPT, &[]).await?;
+            self.state = State::WaitCapabilities;
+        } else if message.is_control(MSG_ACCEPT) || message.is_control(MSG_PS_RDY) {
+            // Late responses
+        } else {
+            let response = match self.spec_revision {
+                SPEC_REVISION_2_0 => MSG_REJECT,
+                _ => MSG_NOT_SUPPORTED,
+            };
+            self.transmit(response, &[]).await?;
+        }
+        Ok(())
+    }
+
+    /// Receive a message, or time out after `timeout` while checking that the source is attached.
+    async fn receive_until(&mut self, timeout: Duration) -> Result<Message, Error> {
+        match with_timeout(timeout, self.receive()).await {
+            Ok(message) => message,
+            Err(_) => {
+                self.check_attached()?;
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Receive a message other than GoodCRC, and acknowledge it.
+    async fn receive(&mut self) -> Result<Message, Error> {
+        let mut buf = [0; MAX_MESSAGE_SIZE];
+        loop {
+            let len = match self.ucpd.receive(&mut buf).await {
+                Ok(len) if len >= 2 && len % 2 == 0 => len,
+                Ok(_) | Err(RxError::Crc) | Err(RxError::Overrun) => continue,
+                Err(RxError::HardReset) => return Err(Error::HardReset),
+            };
+
+            let mut message = Message {
+                header: u16::from_le_bytes([buf[0], buf[1]]),
+                objects: [0; 7],
+            };
+            if message.header & (1 << 15) != 0 || len != 2 + message.object_count() * 4 {
+                // Extended message, not supported.
+                continue;
+            }
+            for (object, bytes) in message.objects.iter_mut().zip(buf[2..len].chunks(4)) {
+                *object = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+
+            if message.is_control(MSG_GOOD_CRC) {
+                continue;
+            }
+
+            let header = self.header(MSG_GOOD_CRC, 0, message.message_id());
+            let _ = self.ucpd.transmit(&header.to_le_bytes()).await;
+
+            // A retransmission of the last message, whose GoodCRC was lost.
+            if self.rx_message_id == Some(message.message_id()) && !message.is_control(MSG_SOFT_RESET) {
+                continue;
+            }
+            self.rx_message_id = Some(message.message_id());
+            return Ok(message);
+        }
+    }
+
+    /// Transmit a message, and wait for its GoodCRC.
+    async fn transmit(&mut self, message_type: u8, objects: &[u32]) -> Result<(), Error> {
+        let mut buf = [0; MAX_MESSAGE_SIZE];
+        let header = self.header(message_type, objects.len(), self.tx_message_id);
+        buf[..2].copy_from_slice(&header.to_le_bytes());
+        for (bytes, object) in buf[2..].chunks_mut(4).zip(objects) {
+            bytes.copy_from_slice(&object.to_le_bytes());
+        }
+        let len = 2 + objects.len() * 4;
+
+        for _ in 0..=RETRY_COUNT {
+            match self.ucpd.transmit(&buf[..len]).await {
+                Ok(()) => {}
+                Err(TxError::HardReset) => return Err(Error::HardReset),
+                Err(_) => continue,
+            }
+
+            let mut crc = [0; MAX_MESSAGE_SIZE];
+            match with_timeout(RECEIVE_TIMEOUT, self.ucpd.receive(&mut crc)).await {
+                Ok(Ok(2)) => {
+                    let header = u16::from_le_bytes([crc[0], crc[1]]);
+                    if header & 0x1f == MSG_GOOD_CRC as u16 && ((header >> 9) & 0b111) as u8 == self.tx_message_id {
+                        self.tx_message_id = (self.tx_message_id + 1) % 8;
+                        return Ok(());
+                    }
+                }
+                Ok(Err(RxError::HardReset)) => return Err(Error::HardReset),
+                _ => {}
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+
+    fn header(&self, message_type: u8, object_count: usize, message_id: u8) -> u16 {
+        // Sink and UFP roles are 0.
+        message_type as u16 | (self.spec_revision as u16) << 6 | (message_id as u16) << 9 | (object_count as u16) << 12
+    }
+
+    fn reset_protocol(&mut self) {
+        self.tx_message_id = 0;
+        self.rx_message_id = None;
+    }
+
+    fn cc_vstate(&self, cc: CcPin) -> CcVState {
+        match cc {
+            CcPin::Cc1 => self.ucpd.cc_vstate().0,
+            CcPin::Cc2 => self.ucpd.cc_vstate().1,
+        }
+    }
+
+    fn check_attached(&self) -> Result<(), Error> {
+        match self.cc_vstate(self.cc) {
+            CcVState::Open => Err(Error::Detached),
+            _ => Ok(()),
+        }
+    }
+}
@@ -200,7 +200,7 @@ impl<'d, T: BasicInstance> BufferedUart<'d, T> {
         rx.set_as_af(rx.af_num(), AFType::Input);
         tx.set_as_af(tx.af_num(), AFType::OutputPushPull);
 
-        configure(r, &config, T::frequency(), T::KIND, true, true);
+        unwrap!(configure(r, &config, T::frequency(), T::KIND, true, true));
 
         r.cr1().modify(|w| {
             #[cfg(lpuart_v2)]
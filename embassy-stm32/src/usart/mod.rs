@@ -5,6 +5,7 @@ use core::marker::PhantomData;
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::task::Poll;
 
+use embassy_embedded_hal::SetConfig;
 use embassy_hal_common::drop::OnDrop;
 use embassy_hal_common::{into_ref, PeripheralRef};
 use futures::future::{select, Either};
@@ -26,6 +27,26 @@ use crate::pac::usart::{regs, vals};
 use crate::time::Hertz;
 use crate::{interrupt, peripherals, Peripheral};
 
+// CR1 bits
+#[cfg(any(usart_v3, usart_v4))]
+const CR1_UESM: u32 = 1 << 1;
+
+// CR2 bits
+#[cfg(any(usart_v3, usart_v4))]
+const CR2_ADDM7: u32 = 1 << 4;
+#[cfg(any(usart_v3, usart_v4))]
+const CR2_ADD_POS: u32 = 24;
+
+// CR3 bits
+#[cfg(any(usart_v3, usart_v4))]
+const CR3_WUS_POS: u32 = 20;
+#[cfg(any(usart_v3, usart_v4))]
+const CR3_WUFIE: u32 = 1 << 22;
+
+// ISR and ICR bits
+#[cfg(any(usart_v3, usart_v4))]
+const ISR_WUF: u32 = 1 << 20;
+
 /// Interrupt handler.
 pub struct InterruptHandler<T: BasicInstance> {
     _phantom: PhantomData<T>,
@@ -38,6 +59,12 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
 
         let (sr, cr1, cr3) = (sr(r).read(), r.cr1().read(), r.cr3().read());
 
+        #[cfg(any(usart_v3, usart_v4))]
+        if cr3.0 & CR3_WUFIE != 0 && sr.0 & ISR_WUF != 0 {
+            // Woken up from Stop mode, the reception goes on and raises its own events.
+            r.icr().write(|w| w.0 = ISR_WUF);
+        }
+
         let has_errors = (sr.pe() && cr1.peie()) || ((sr.fe() || sr.ne() || sr.ore()) && cr3.eie());
         if has_errors {
             // clear all interrupts and DMA Rx Request
@@ -74,6 +101,19 @@ impl<T: BasicInstance> interrupt::typelevel::Handler<T::Interrupt> for Interrupt
     }
 }
 
+/// Event that wakes the MCU up from Stop mode.
+#[cfg(any(usart_v3, usart_v4))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupEvent {
+    /// A received frame starts with this 7-bit address, in the bits below the MSB.
+    AddressMatch(u8),
+    /// A start bit is detected.
+    StartBit,
+    /// A frame is received.
+    RxNotEmpty,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum DataBits {
     DataBits8,
@@ -150,6 +190,17 @@ pub enum Error {
     BufferTooLong,
 }
 
+/// Config Error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The baudrate is too low for the peripheral clock.
+    BaudrateTooLow,
+    /// The baudrate is too high for the peripheral clock.
+    BaudrateTooHigh,
+}
+
 enum ReadCompletionEvent {
     // DMA Read transfer completed first
     DmaCompleted,
@@ -220,7 +271,7 @@ impl<'d, T: BasicInstance, TxDma> UartTx<'d, T, TxDma> {
 
         tx.set_as_af(tx.af_num(), AFType::OutputPushPull);
 
-        configure(r, &config, T::frequency(), T::KIND, false, true);
+        unwrap!(configure(r, &config, T::frequency(), T::KIND, false, true));
 
         // create state once!
         let _s = T::state();
@@ -311,7 +362,7 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
 
         rx.set_as_af(rx.af_num(), AFType::Input);
 
-        configure(r, &config, T::frequency(), T::KIND, true, false);
+        unwrap!(configure(r, &config, T::frequency(), T::KIND, true, false));
 
         T::Interrupt::unpend();
         unsafe { T::Interrupt::enable() };
@@ -415,6 +466,51 @@ impl<'d, T: BasicInstance, RxDma> UartRx<'d, T, RxDma> {
         self.inner_read(buffer, true).await
     }
 
+    /// Keep receiving in Stop mode, and wake the MCU up on `event`.
+    ///
+    /// The receive futures stay armed while the MCU is in Stop mode: the receiver wakes it up,
+    /// and the reception goes on. This requires a kernel clock that runs in Stop mode, HSI or
+    /// LSE, selected in RCC, and the EXTI line of the peripheral, which most chips enable by
+    /// default. Check the reference manual, not all instances can wake the MCU up.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn enable_wakeup_from_stop(&mut self, event: WakeupEvent) {
+        let r = T::regs();
+        let ue = r.cr1().read().ue();
+
+        // WUS and ADD are only writable while the peripheral is disabled.
+        r.cr1().modify(|w| w.set_ue(false));
+
+        let wus = match event {
+            WakeupEvent::AddressMatch(address) => {
+                r.cr2().modify(|w| {
+                    w.0 &= !(0xFF << CR2_ADD_POS);
+                    w.0 |= CR2_ADDM7 | ((address as u32 & 0x7F) << CR2_ADD_POS);
+                });
+                0b00
+            }
+            WakeupEvent::StartBit => 0b10,
+            WakeupEvent::RxNotEmpty => 0b11,
+        };
+        r.cr3().modify(|w| {
+            w.0 &= !(0b11 << CR3_WUS_POS);
+            w.0 |= (wus << CR3_WUS_POS) | CR3_WUFIE;
+        });
+        r.icr().write(|w| w.0 = ISR_WUF);
+
+        r.cr1().modify(|w| {
+            w.0 |= CR1_UESM;
+            w.set_ue(ue);
+        });
+    }
+
+    /// Stop receiving in Stop mode.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn disable_wakeup_from_stop(&mut self) {
+        let r = T::regs();
+        r.cr1().modify(|w| w.0 &= !CR1_UESM);
+        r.cr3().modify(|w| w.0 &= !CR3_WUFIE);
+    }
+
     async fn inner_read_run(
         &mut self,
         buffer: &mut [u8],
@@ -691,7 +787,7 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
         rx.set_as_af(rx.af_num(), AFType::Input);
         tx.set_as_af(tx.af_num(), AFType::OutputPushPull);
 
-        configure(r, &config, T::frequency(), T::KIND, true, true);
+        unwrap!(configure(r, &config, T::frequency(), T::KIND, true, true));
 
         T::Interrupt::unpend();
         unsafe { T::Interrupt::enable() };
@@ -751,6 +847,20 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
         self.rx.read_until_idle(buffer).await
     }
 
+    /// Keep receiving in Stop mode, and wake the MCU up on `event`.
+    ///
+    /// See [`UartRx::enable_wakeup_from_stop()`].
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn enable_wakeup_from_stop(&mut self, event: WakeupEvent) {
+        self.rx.enable_wakeup_from_stop(event)
+    }
+
+    /// Stop receiving in Stop mode.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn disable_wakeup_from_stop(&mut self) {
+        self.rx.disable_wakeup_from_stop()
+    }
+
     /// Split the Uart into a transmitter and receiver, which is
     /// particularly useful when having two tasks correlating to
     /// transmitting and receiving.
@@ -759,10 +869,21 @@ impl<'d, T: BasicInstance, TxDma, RxDma> Uart<'d, T, TxDma, RxDma> {
     }
 }
 
-fn configure(r: Regs, config: &Config, pclk_freq: Hertz, kind: Kind, enable_rx: bool, enable_tx: bool) {
+fn configure(
+    r: Regs,
+    config: &Config,
+    pclk_freq: Hertz,
+    kind: Kind,
+    enable_rx: bool,
+    enable_tx: bool,
+) -> Result<(), ConfigError> {
     if !enable_rx && !enable_tx {
         panic!("USART: At least one of RX or TX should be enabled");
     }
+    // A zero baudrate would divide by zero below.
+    if config.baudrate == 0 {
+        return Err(ConfigError::BaudrateTooLow);
+    }
 
     #[cfg(not(usart_v4))]
     static DIVS: [(u16, ()); 1] = [(1, ())];
@@ -808,26 +929,29 @@ fn configure(r: Regs, config: &Config, pclk_freq: Hertz, kind: Kind, enable_rx:
             if div * 2 >= brr_min && kind == Kind::Uart && !cfg!(usart_v1) {
                 over8 = true;
                 let div = div as u32;
-                r.brr().write_value(regs::Brr(((div << 1) & !0xF) | (div & 0x07)));
-                #[cfg(usart_v4)]
-                r.presc().write(|w| w.set_prescaler(_presc_val));
-                found = Some(div);
+                found = Some((div, ((div << 1) & !0xF) | (div & 0x07), _presc_val));
                 break;
             }
-            panic!("USART: baudrate too high");
+            return Err(ConfigError::BaudrateTooHigh);
         }
 
         if div < brr_max {
             let div = div as u32;
-            r.brr().write_value(regs::Brr(div));
-            #[cfg(usart_v4)]
-            r.presc().write(|w| w.set_prescaler(_presc_val));
-            found = Some(div);
+            found = Some((div, div, _presc_val));
             break;
         }
     }
 
-    let div = found.expect("USART: baudrate too low");
+    let (div, brr, _presc_val) = match found {
+        Some(found) => found,
+        None => return Err(ConfigError::BaudrateTooLow),
+    };
+
+    // The baudrate only changes while the USART is disabled.
+    r.cr1().modify(|w| w.set_ue(false));
+    r.brr().write_value(regs::Brr(brr));
+    #[cfg(usart_v4)]
+    r.presc().write(|w| w.set_prescaler(_presc_val));
 
     #[cfg(not(usart_v1))]
     let oversampling = if over8 { "8 bit" } else { "16 bit" };
@@ -876,6 +1000,43 @@ fn configure(r: Regs, config: &Config, pclk_freq: Hertz, kind: Kind, enable_rx:
     r.cr3().modify(|w| {
         w.set_onebit(config.assume_noise_free);
     });
+
+    Ok(())
+}
+
+/// Reconfigure a running USART, after the pending transmission, keeping its receiver and
+/// transmitter enabled or disabled.
+fn reconfigure<T: BasicInstance>(config: &Config) -> Result<(), ConfigError> {
+    let r = T::regs();
+    let cr1 = r.cr1().read();
+    if cr1.te() {
+        while !sr(r).read().tc() {}
+    }
+    configure(r, config, T::frequency(), T::KIND, cr1.re(), cr1.te())
+}
+
+impl<'d, T: BasicInstance, TxDma> SetConfig for UartTx<'d, T, TxDma> {
+    type Config = Config;
+    type ConfigError = ConfigError;
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)
+    }
+}
+
+impl<'d, T: BasicInstance, RxDma> SetConfig for UartRx<'d, T, RxDma> {
+    type Config = Config;
+    type ConfigError = ConfigError;
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)
+    }
+}
+
+impl<'d, T: BasicInstance, TxDma, RxDma> SetConfig for Uart<'d, T, TxDma, RxDma> {
+    type Config = Config;
+    type ConfigError = ConfigError;
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)
+    }
 }
 
 mod eh02 {
@@ -84,14 +84,21 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
         if istr.ctr() {
             let index = istr.ep_id() as usize;
             let mut epr = regs.epr(index).read();
+            let iso = epr.ep_type() == EpType::ISO;
             if epr.ctr_rx() {
                 if index == 0 && epr.setup() {
                     EP0_SETUP.store(true, Ordering::Relaxed);
                 }
+                if iso {
+                    ISO_OUT_READY[index].store(true, Ordering::Relaxed);
+                }
                 //trace!("EP {} RX, setup={}", index, epr.setup());
                 EP_OUT_WAKERS[index].wake();
             }
             if epr.ctr_tx() {
+                if iso {
+                    ISO_IN_DONE[index].store(true, Ordering::Relaxed);
+                }
                 //trace!("EP {} TX", index);
                 EP_IN_WAKERS[index].wake();
             }
@@ -125,6 +132,10 @@ static BUS_WAKER: AtomicWaker = NEW_AW;
 static EP0_SETUP: AtomicBool = AtomicBool::new(false);
 static EP_IN_WAKERS: [AtomicWaker; EP_COUNT] = [NEW_AW; EP_COUNT];
 static EP_OUT_WAKERS: [AtomicWaker; EP_COUNT] = [NEW_AW; EP_COUNT];
+const NEW_FLAG: AtomicBool = AtomicBool::new(false);
+// Isochronous endpoints don't NAK, so completed transactions are flagged by the interrupt handler.
+static ISO_OUT_READY: [AtomicBool; EP_COUNT] = [NEW_FLAG; EP_COUNT];
+static ISO_IN_DONE: [AtomicBool; EP_COUNT] = [NEW_FLAG; EP_COUNT];
 static IRQ_RESET: AtomicBool = AtomicBool::new(false);
 static IRQ_SUSPEND: AtomicBool = AtomicBool::new(false);
 static IRQ_RESUME: AtomicBool = AtomicBool::new(false);
@@ -182,6 +193,21 @@ mod btable {
     pub(super) fn read_out_len<T: Instance>(index: usize) -> u16 {
         USBRAM.mem(index * 4 + 3).read()
     }
+
+    // Double-buffered endpoints use the TX descriptor for buffer 0 and the RX descriptor for buffer 1.
+    pub(super) fn write_in_double<T: Instance>(index: usize, buf: usize, addr: u16, len: u16) {
+        USBRAM.mem(index * 4 + buf * 2).write_value(addr);
+        USBRAM.mem(index * 4 + buf * 2 + 1).write_value(len);
+    }
+
+    pub(super) fn write_out_double<T: Instance>(index: usize, buf: usize, addr: u16, max_len_bits: u16) {
+        USBRAM.mem(index * 4 + buf * 2).write_value(addr);
+        USBRAM.mem(index * 4 + buf * 2 + 1).write_value(max_len_bits);
+    }
+
+    pub(super) fn read_out_len_double<T: Instance>(index: usize, buf: usize) -> u16 {
+        USBRAM.mem(index * 4 + buf * 2 + 1).read()
+    }
 }
 #[cfg(usbram_32_2048)]
 mod btable {
@@ -202,6 +228,23 @@ mod btable {
     pub(super) fn read_out_len<T: Instance>(index: usize) -> u16 {
         (USBRAM.mem(index * 2 + 1).read() >> 16) as u16
     }
+
+    // Double-buffered endpoints use the TX descriptor for buffer 0 and the RX descriptor for buffer 1.
+    pub(super) fn write_in_double<T: Instance>(index: usize, buf: usize, addr: u16, len: u16) {
+        USBRAM
+            .mem(index * 2 + buf)
+            .write_value((addr as u32) | ((len as u32) << 16));
+    }
+
+    pub(super) fn write_out_double<T: Instance>(index: usize, buf: usize, addr: u16, max_len_bits: u16) {
+        USBRAM
+            .mem(index * 2 + buf)
+            .write_value((addr as u32) | ((max_len_bits as u32) << 16));
+    }
+
+    pub(super) fn read_out_len_double<T: Instance>(index: usize, buf: usize) -> u16 {
+        (USBRAM.mem(index * 2 + buf).read() >> 16) as u16
+    }
 }
 
 struct EndpointBuffer<T: Instance> {
@@ -338,7 +381,8 @@ impl<'d, T: Instance> Driver<'d, T> {
                 Direction::Out => ep.used_out,
                 Direction::In => ep.used_in,
             };
-            !used || (ep.ep_type == ep_type && !used_dir)
+            // Isochronous endpoints are double-buffered, using both buffer descriptors of the index.
+            !used || (ep.ep_type == ep_type && ep_type != EndpointType::Isochronous && !used_dir)
         });
 
         let (index, ep) = match index {
@@ -348,7 +392,55 @@ impl<'d, T: Instance> Driver<'d, T> {
 
         ep.ep_type = ep_type;
 
-        let buf = match D::dir() {
+        let (buf, buf1) = match D::dir() {
+            Direction::Out if ep_type == EndpointType::Isochronous => {
+                ep.used_out = true;
+
+                let (len, len_bits) = calc_out_len(max_packet_size);
+                let addr0 = self.alloc_ep_mem(len);
+                let addr1 = self.alloc_ep_mem(len);
+
+                trace!("  len_bits = {:04x}", len_bits);
+                btable::write_out_double::<T>(index, 0, addr0, len_bits);
+                btable::write_out_double::<T>(index, 1, addr1, len_bits);
+
+                (
+                    EndpointBuffer {
+                        addr: addr0,
+                        len,
+                        _phantom: PhantomData,
+                    },
+                    Some(EndpointBuffer {
+                        addr: addr1,
+                        len,
+                        _phantom: PhantomData,
+                    }),
+                )
+            }
+            Direction::In if ep_type == EndpointType::Isochronous => {
+                ep.used_in = true;
+
+                let len = align_len_up(max_packet_size);
+                let addr0 = self.alloc_ep_mem(len);
+                let addr1 = self.alloc_ep_mem(len);
+
+                // Until the first packet is written, send zero-length packets.
+                btable::write_in_double::<T>(index, 0, addr0, 0);
+                btable::write_in_double::<T>(index, 1, addr1, 0);
+
+                (
+                    EndpointBuffer {
+                        addr: addr0,
+                        len,
+                        _phantom: PhantomData,
+                    },
+                    Some(EndpointBuffer {
+                        addr: addr1,
+                        len,
+                        _phantom: PhantomData,
+                    }),
+                )
+            }
             Direction::Out => {
                 assert!(!ep.used_out);
                 ep.used_out = true;
@@ -359,11 +451,14 @@ impl<'d, T: Instance> Driver<'d, T> {
                 trace!("  len_bits = {:04x}", len_bits);
                 btable::write_out::<T>(index, addr, len_bits);
 
-                EndpointBuffer {
-                    addr,
-                    len,
-                    _phantom: PhantomData,
-                }
+                (
+                    EndpointBuffer {
+                        addr,
+                        len,
+                        _phantom: PhantomData,
+                    },
+                    None,
+                )
             }
             Direction::In => {
                 assert!(!ep.used_in);
@@ -375,11 +470,14 @@ impl<'d, T: Instance> Driver<'d, T> {
                 // ep_in_len is written when actually TXing packets.
                 btable::write_in::<T>(index, addr);
 
-                EndpointBuffer {
-                    addr,
-                    len,
-                    _phantom: PhantomData,
-                }
+                (
+                    EndpointBuffer {
+                        addr,
+                        len,
+                        _phantom: PhantomData,
+                    },
+                    None,
+                )
             }
         };
 
@@ -394,6 +492,7 @@ impl<'d, T: Instance> Driver<'d, T> {
                 interval_ms,
             },
             buf,
+            buf1,
         })
     }
 }
@@ -475,6 +574,12 @@ pub struct Bus<'d, T: Instance> {
     inited: bool,
 }
 
+impl<'d, T: Instance> Bus<'d, T> {
+    fn is_iso(&self, ep_addr: EndpointAddress) -> bool {
+        ep_addr.index() != 0 && self.ep_types[ep_addr.index() - 1] == EpType::ISO
+    }
+}
+
 impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     async fn poll(&mut self) -> Event {
         poll_fn(move |cx| {
@@ -536,57 +641,15 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     }
 
     fn endpoint_set_stalled(&mut self, ep_addr: EndpointAddress, stalled: bool) {
-        // This can race, so do a retry loop.
-        let reg = T::regs().epr(ep_addr.index() as _);
-        match ep_addr.direction() {
-            Direction::In => {
-                loop {
-                    let r = reg.read();
-                    match r.stat_tx() {
-                        Stat::DISABLED => break, // if disabled, stall does nothing.
-                        Stat::STALL => break,    // done!
-                        _ => {
-                            let want_stat = match stalled {
-                                false => Stat::NAK,
-                                true => Stat::STALL,
-                            };
-                            let mut w = invariant(r);
-                            w.set_stat_tx(Stat::from_bits(r.stat_tx().to_bits() ^ want_stat.to_bits()));
-                            reg.write_value(w);
-                        }
-                    }
-                }
-                EP_IN_WAKERS[ep_addr.index()].wake();
-            }
-            Direction::Out => {
-                loop {
-                    let r = reg.read();
-                    match r.stat_rx() {
-                        Stat::DISABLED => break, // if disabled, stall does nothing.
-                        Stat::STALL => break,    // done!
-                        _ => {
-                            let want_stat = match stalled {
-                                false => Stat::VALID,
-                                true => Stat::STALL,
-                            };
-                            let mut w = invariant(r);
-                            w.set_stat_rx(Stat::from_bits(r.stat_rx().to_bits() ^ want_stat.to_bits()));
-                            reg.write_value(w);
-                        }
-                    }
-                }
-                EP_OUT_WAKERS[ep_addr.index()].wake();
-            }
+        if self.is_iso(ep_addr) {
+            // Isochronous endpoints can't be stalled.
+            return;
         }
+        endpoint_set_stalled::<T>(ep_addr, stalled)
     }
 
     fn endpoint_is_stalled(&mut self, ep_addr: EndpointAddress) -> bool {
-        let regs = T::regs();
-        let epr = regs.epr(ep_addr.index() as _).read();
-        match ep_addr.direction() {
-            Direction::In => epr.stat_tx() == Stat::STALL,
-            Direction::Out => epr.stat_rx() == Stat::STALL,
-        }
+        endpoint_is_stalled::<T>(ep_addr)
     }
 
     fn endpoint_set_enabled(&mut self, ep_addr: EndpointAddress, enabled: bool) {
@@ -594,11 +657,17 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
         // This can race, so do a retry loop.
         let reg = T::regs().epr(ep_addr.index() as _);
         trace!("EPR before: {:04x}", reg.read().0);
+        let iso = self.is_iso(ep_addr);
         match ep_addr.direction() {
             Direction::In => {
+                if iso {
+                    ISO_IN_DONE[ep_addr.index()].store(false, Ordering::Relaxed);
+                }
                 loop {
+                    // Isochronous endpoints have no handshake, so they are always VALID while enabled.
                     let want_stat = match enabled {
                         false => Stat::DISABLED,
+                        true if iso => Stat::VALID,
                         true => Stat::NAK,
                     };
                     let r = reg.read();
@@ -612,6 +681,9 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                 EP_IN_WAKERS[ep_addr.index()].wake();
             }
             Direction::Out => {
+                if iso {
+                    ISO_OUT_READY[ep_addr.index()].store(false, Ordering::Relaxed);
+                }
                 loop {
                     let want_stat = match enabled {
                         false => Stat::DISABLED,
@@ -635,7 +707,74 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     async fn disable(&mut self) {}
 
     async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+        let regs = T::regs();
+        regs.cntr().modify(|w| {
+            w.set_fsusp(false);
+            w.set_lpmode(false);
+        });
+
+        // Resume signaling must be driven for 1 to 15 ms.
+        regs.cntr().modify(|w| w.set_resume(true));
+        #[cfg(feature = "time")]
+        embassy_time::Timer::after(embassy_time::Duration::from_millis(5)).await;
+        #[cfg(not(feature = "time"))]
+        cortex_m::asm::delay(unsafe { crate::rcc::get_freqs() }.sys.0 / 200);
+        regs.cntr().modify(|w| w.set_resume(false));
+
+        Ok(())
+    }
+}
+
+fn endpoint_set_stalled<T: Instance>(ep_addr: EndpointAddress, stalled: bool) {
+    // This can race, so do a retry loop.
+    let reg = T::regs().epr(ep_addr.index() as _);
+    match ep_addr.direction() {
+        Direction::In => {
+            let want_stat = match stalled {
+                false => Stat::NAK,
+                true => Stat::STALL,
+            };
+            loop {
+                let r = reg.read();
+                match r.stat_tx() {
+                    Stat::DISABLED => break,            // if disabled, stall does nothing.
+                    stat if stat == want_stat => break, // done!
+                    _ => {
+                        let mut w = invariant(r);
+                        w.set_stat_tx(Stat::from_bits(r.stat_tx().to_bits() ^ want_stat.to_bits()));
+                        reg.write_value(w);
+                    }
+                }
+            }
+            EP_IN_WAKERS[ep_addr.index()].wake();
+        }
+        Direction::Out => {
+            let want_stat = match stalled {
+                false => Stat::VALID,
+                true => Stat::STALL,
+            };
+            loop {
+                let r = reg.read();
+                match r.stat_rx() {
+                    Stat::DISABLED => break,            // if disabled, stall does nothing.
+                    stat if stat == want_stat => break, // done!
+                    _ => {
+                        let mut w = invariant(r);
+                        w.set_stat_rx(Stat::from_bits(r.stat_rx().to_bits() ^ want_stat.to_bits()));
+                        reg.write_value(w);
+                    }
+                }
+            }
+            EP_OUT_WAKERS[ep_addr.index()].wake();
+        }
+    }
+}
+
+fn endpoint_is_stalled<T: Instance>(ep_addr: EndpointAddress) -> bool {
+    let epr = T::regs().epr(ep_addr.index() as _).read();
+    match ep_addr.direction() {
+        Direction::In => epr.stat_tx() == Stat::STALL,
+        Direction::Out => epr.stat_rx() == Stat::STALL,
     }
 }
 
@@ -672,6 +811,8 @@ pub struct Endpoint<'d, T: Instance, D> {
     _phantom: PhantomData<(&'d mut T, D)>,
     info: EndpointInfo,
     buf: EndpointBuffer<T>,
+    /// Second buffer of double-buffered (isochronous) endpoints.
+    buf1: Option<EndpointBuffer<T>>,
 }
 
 impl<'d, T: Instance, D> Endpoint<'d, T, D> {
@@ -691,6 +832,10 @@ impl<'d, T: Instance, D> Endpoint<'d, T, D> {
         self.buf.read(&mut buf[..rx_len]);
         Ok(rx_len)
     }
+
+    fn is_iso(&self) -> bool {
+        self.info.ep_type == EndpointType::Isochronous
+    }
 }
 
 impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, In> {
@@ -713,6 +858,17 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, In> {
         .await;
         trace!("wait_enabled OUT OK");
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        // Isochronous endpoints can't be stalled.
+        if !self.is_iso() {
+            endpoint_set_stalled::<T>(self.info.addr, stalled)
+        }
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, Out> {
@@ -735,11 +891,26 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, Out> {
         .await;
         trace!("wait_enabled OUT OK");
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        // Isochronous endpoints can't be stalled.
+        if !self.is_iso() {
+            endpoint_set_stalled::<T>(self.info.addr, stalled)
+        }
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
         trace!("READ WAITING, buf.len() = {}", buf.len());
+        if self.is_iso() {
+            return self.read_iso(buf).await;
+        }
+
         let index = self.info.addr.index();
         let stat = poll_fn(|cx| {
             EP_OUT_WAKERS[index].register(cx.waker());
@@ -774,12 +945,102 @@ impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
     }
 }
 
+impl<'d, T: Instance> Endpoint<'d, T, Out> {
+    async fn read_iso(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        let index = self.info.addr.index();
+        let epr = poll_fn(|cx| {
+            EP_OUT_WAKERS[index].register(cx.waker());
+            let epr = T::regs().epr(index).read();
+            if epr.stat_rx() == Stat::DISABLED || ISO_OUT_READY[index].load(Ordering::Relaxed) {
+                Poll::Ready(epr)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        if epr.stat_rx() == Stat::DISABLED {
+            return Err(EndpointError::Disabled);
+        }
+        ISO_OUT_READY[index].store(false, Ordering::Relaxed);
+
+        // The hardware toggles DTOG_RX after each transaction, so the buffer that was just
+        // filled is the one DTOG_RX no longer points to.
+        let filled = if epr.dtog_rx() { 0 } else { 1 };
+        let rx_len = btable::read_out_len_double::<T>(index, filled) as usize & 0x3FF;
+        if rx_len > buf.len() {
+            return Err(EndpointError::BufferOverflow);
+        }
+        match filled {
+            0 => self.buf.read(&mut buf[..rx_len]),
+            _ => unwrap!(self.buf1.as_mut()).read(&mut buf[..rx_len]),
+        }
+        trace!("READ OK, rx_len = {}", rx_len);
+
+        Ok(rx_len)
+    }
+}
+
+impl<'d, T: Instance> Endpoint<'d, T, In> {
+    async fn write_iso(&mut self, buf: &[u8]) -> Result<(), EndpointError> {
+        let index = self.info.addr.index();
+        let regs = T::regs();
+
+        let epr = regs.epr(index).read();
+        if epr.stat_tx() == Stat::DISABLED {
+            return Err(EndpointError::Disabled);
+        }
+
+        // DTOG_TX selects the buffer sent in the next frame. Fill it, then wait until the host
+        // has taken it.
+        let next = if epr.dtog_tx() { 1 } else { 0 };
+        match next {
+            0 => self.buf.write(buf),
+            _ => unwrap!(self.buf1.as_mut()).write(buf),
+        }
+        let addr = match next {
+            0 => self.buf.addr,
+            _ => unwrap!(self.buf1.as_ref()).addr,
+        };
+        ISO_IN_DONE[index].store(false, Ordering::Relaxed);
+        btable::write_in_double::<T>(index, next, addr, buf.len() as _);
+
+        trace!("WRITE WAITING");
+        let stat = poll_fn(|cx| {
+            EP_IN_WAKERS[index].register(cx.waker());
+            let stat = regs.epr(index).read().stat_tx();
+            if stat == Stat::DISABLED || ISO_IN_DONE[index].load(Ordering::Relaxed) {
+                Poll::Ready(stat)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        if stat == Stat::DISABLED {
+            return Err(EndpointError::Disabled);
+        }
+        ISO_IN_DONE[index].store(false, Ordering::Relaxed);
+
+        // Don't send the same data again if nothing is written in time for the next frame.
+        btable::write_in_double::<T>(index, next, addr, 0);
+
+        trace!("WRITE OK");
+
+        Ok(())
+    }
+}
+
 impl<'d, T: Instance> driver::EndpointIn for Endpoint<'d, T, In> {
     async fn write(&mut self, buf: &[u8]) -> Result<(), EndpointError> {
         if buf.len() > self.info.max_packet_size as usize {
             return Err(EndpointError::BufferOverflow);
         }
 
+        if self.is_iso() {
+            return self.write_iso(buf).await;
+        }
+
         let index = self.info.addr.index();
 
         trace!("WRITE WAITING");
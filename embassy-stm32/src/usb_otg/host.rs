@@ -0,0 +1,951 @@
+//! USB OTG host mode.
+//!
+//! The core runs in slave mode: packets are moved through the FIFOs by the interrupt handler, and
+//! each pipe owns one of the host channels of the core. Only the root port is supported, without
+//! hubs and split transactions.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use atomic_polyfill::{AtomicU16, AtomicU8, Ordering};
+use embassy_hal_common::drop::OnDrop;
+use embassy_hal_common::{into_ref, Peripheral};
+use embassy_sync::waitqueue::AtomicWaker;
+use embassy_usb_driver::host::{self, DeviceEvent, PipeAllocError, PipeError, Speed};
+use embassy_usb_driver::{EndpointInfo, EndpointType};
+use futures::future::poll_fn;
+
+use super::usb::{core_reset, power_down, power_up};
+use super::*;
+use crate::gpio::sealed::AFType;
+use crate::interrupt;
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::otg::{regs, vals};
+use crate::rcc::sealed::RccPeripheral;
+
+// GRXSTSP packet status in host mode
+const PKTSTS_IN_DATA: u8 = 2;
+
+// HCFG.FSLSPCS values
+const FSLSPCS_48MHZ: u8 = 1;
+const FSLSPCS_6MHZ: u8 = 2;
+
+// HPRT.PSPD value of a low-speed device
+const PSPD_LOW: u8 = 2;
+
+// HCTSIZ.DPID values
+const DPID_DATA0: u8 = 0;
+const DPID_DATA1: u8 = 2;
+const DPID_SETUP: u8 = 3;
+
+/// Largest HCTSIZ.PKTCNT value.
+const MAX_PKTCNT: usize = 0x3ff;
+
+// Port events, set by the interrupt handler
+const PORT_CONNECTED: u8 = 1 << 0;
+const PORT_DISCONNECTED: u8 = 1 << 1;
+const PORT_ENABLED: u8 = 1 << 2;
+
+// Channel transfer results
+const RESULT_BUSY: u8 = 0;
+const RESULT_DONE: u8 = 1;
+const RESULT_NAK: u8 = 2;
+const RESULT_STALL: u8 = 3;
+const RESULT_ERROR: u8 = 4;
+const RESULT_OVERFLOW: u8 = 5;
+const RESULT_DISCONNECTED: u8 = 6;
+
+/// Transactions failing with a CRC, timeout or toggle error are retried this many times.
+const MAX_RETRIES: usize = 3;
+
+/// USB 2.0 spec, 7.1.7.5: the root port is reset for at least 50 ms.
+const RESET_DURATION_MS: u32 = 50;
+/// USB 2.0 spec, 7.1.7.3: the device gets 10 ms of recovery after reset.
+const RESET_RECOVERY_MS: u32 = 10;
+
+/// Modifies HPRT without clearing its write-1-to-clear bits.
+///
+/// Writing 1 to PENA disables the port, so the change flags and PENA are written as 0.
+fn modify_hprt<T: Instance>(f: impl FnOnce(&mut regs::Hprt)) {
+    let r = T::regs();
+    let mut hprt = r.hprt().read();
+    hprt.set_pcdet(false);
+    hprt.set_pena(false);
+    hprt.set_penchng(false);
+    hprt.set_pocchng(false);
+    f(&mut hprt);
+    r.hprt().write_value(hprt);
+}
+
+/// Clears the interrupts of a channel.
+fn clear_channel_interrupts<T: Instance>(ch: usize) {
+    T::regs().hcint(ch).write_value(regs::Hcint(0x7ff));
+}
+
+async fn delay_ms(ms: u32) {
+    #[cfg(feature = "time")]
+    embassy_time::Timer::after(embassy_time::Duration::from_millis(ms as u64)).await;
+
+    #[cfg(not(feature = "time"))]
+    cortex_m::asm::delay(unsafe { crate::rcc::get_freqs() }.sys.0 / 1000 * ms);
+}
+
+pub struct HostState<const CH_COUNT: usize> {
+    port_waker: AtomicWaker,
+    /// `PORT_*` events not handled yet by [`Driver::wait_for_device_event()`].
+    port_events: AtomicU8,
+    ch_wakers: [AtomicWaker; CH_COUNT],
+    /// `RESULT_*` of the transfer of each channel, set once the channel is halted.
+    ch_result: [AtomicU8; CH_COUNT],
+    /// Result recorded by the interrupt handler while the channel is being halted.
+    ch_pending: [AtomicU8; CH_COUNT],
+    /// Buffer of the current IN transfer of each channel, filled from the shared RX FIFO.
+    ch_buf: [UnsafeCell<*mut u8>; CH_COUNT],
+    ch_buf_len: [AtomicU16; CH_COUNT],
+    ch_received: [AtomicU16; CH_COUNT],
+    /// Mask of the channels owned by pipes.
+    ch_allocated: AtomicU16,
+}
+
+unsafe impl<const CH_COUNT: usize> Send for HostState<CH_COUNT> {}
+unsafe impl<const CH_COUNT: usize> Sync for HostState<CH_COUNT> {}
+
+impl<const CH_COUNT: usize> HostState<CH_COUNT> {
+    pub const fn new() -> Self {
+        const NEW_AW: AtomicWaker = AtomicWaker::new();
+        const NEW_RESULT: AtomicU8 = AtomicU8::new(RESULT_BUSY);
+        const NEW_BUF: UnsafeCell<*mut u8> = UnsafeCell::new(0 as _);
+        const NEW_LEN: AtomicU16 = AtomicU16::new(0);
+
+        Self {
+            port_waker: NEW_AW,
+            port_events: AtomicU8::new(0),
+            ch_wakers: [NEW_AW; CH_COUNT],
+            ch_result: [NEW_RESULT; CH_COUNT],
+            ch_pending: [NEW_RESULT; CH_COUNT],
+            ch_buf: [NEW_BUF; CH_COUNT],
+            ch_buf_len: [NEW_LEN; CH_COUNT],
+            ch_received: [NEW_LEN; CH_COUNT],
+            ch_allocated: AtomicU16::new(0),
+        }
+    }
+}
+
+/// Host mode interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let state = T::host_state();
+
+        let ints = regs::Gintsts(r.gintsts().read().0 & r.gintmsk().read().0);
+
+        if ints.hprtint() {
+            let hprt = r.hprt().read();
+            // Clear the change flags, without writing 1 to PENA.
+            r.hprt().write_value({
+                let mut w = hprt;
+                w.set_pena(false);
+                w
+            });
+
+            let mut events = 0;
+            if hprt.pcdet() {
+                events |= PORT_CONNECTED;
+            }
+            if hprt.penchng() && hprt.pena() {
+                // The full-speed PHY runs at 6 MHz for low-speed devices.
+                if r.hcfg().read().fslss() {
+                    let (fslspcs, frame_interval) = match hprt.pspd() == PSPD_LOW {
+                        true => (FSLSPCS_6MHZ, 6000),
+                        false => (FSLSPCS_48MHZ, 48000),
+                    };
+                    r.hcfg().modify(|w| w.set_fslspcs(fslspcs));
+                    r.hfir().write(|w| w.set_frivl(frame_interval));
+                }
+                events |= PORT_ENABLED;
+            }
+            if hprt.pocchng() {
+                warn!("USB host port overcurrent");
+            }
+
+            state.port_events.fetch_or(events, Ordering::Release);
+            state.port_waker.wake();
+        }
+
+        if ints.discint() {
+            r.gintsts().write(|w| w.set_discint(true));
+            state.port_events.fetch_or(PORT_DISCONNECTED, Ordering::Release);
+            state.port_waker.wake();
+
+            let allocated = state.ch_allocated.load(Ordering::Relaxed);
+            for ch in (0..T::HOST_CHANNEL_COUNT).filter(|ch| allocated & (1 << ch) != 0) {
+                if state.ch_result[ch].load(Ordering::Relaxed) == RESULT_BUSY {
+                    state.ch_result[ch].store(RESULT_DISCONNECTED, Ordering::Release);
+                    state.ch_wakers[ch].wake();
+                }
+            }
+        }
+
+        // Handle RX
+        while r.gintsts().read().rxflvl() {
+            // In host mode, the endpoint number field holds the channel number.
+            let status = r.grxstsp().read();
+            let ch = status.epnum() as usize;
+            let len = status.bcnt() as usize;
+
+            if status.pktstsd().to_bits() != PKTSTS_IN_DATA {
+                continue;
+            }
+            let capacity = state.ch_buf_len[ch].load(Ordering::Relaxed) as usize;
+            let received = state.ch_received[ch].load(Ordering::Relaxed) as usize;
+            if received + len <= capacity {
+                // SAFETY: the buffer is borrowed by the transfer until the channel is halted, and the
+                // capacity is set to 0 before the buffer is released.
+                let buf = unsafe { core::slice::from_raw_parts_mut((*state.ch_buf[ch].get()).add(received), len) };
+                for chunk in buf.chunks_mut(4) {
+                    // RX FIFO is shared so always read from fifo(0)
+                    let data = r.fifo(0).read().0;
+                    chunk.copy_from_slice(&data.to_ne_bytes()[0..chunk.len()]);
+                }
+                state.ch_received[ch].store((received + len) as u16, Ordering::Release);
+            } else {
+                for _ in 0..(len + 3) / 4 {
+                    r.fifo(0).read();
+                }
+                let _ = state.ch_pending[ch].compare_exchange(
+                    RESULT_BUSY,
+                    RESULT_OVERFLOW,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+
+            // The channel is disabled after each packet, re-enable it for the rest of the transfer.
+            if len > 0 && r.hctsiz(ch).read().pktcnt() != 0 {
+                r.hcchar(ch).modify(|w| {
+                    w.set_chdis(false);
+                    w.set_chena(true);
+                });
+            }
+        }
+
+        if ints.hcint() {
+            let mut mask = r.haint().read().haint();
+            while mask != 0 {
+                let ch = mask.trailing_zeros() as usize;
+                mask &= !(1 << ch);
+                on_channel_interrupt::<T>(ch);
+            }
+        }
+    }
+}
+
+fn on_channel_interrupt<T: Instance>(ch: usize) {
+    let r = T::regs();
+    let state = T::host_state();
+
+    let ints = regs::Hcint(r.hcint(ch).read().0 & r.hcintmsk(ch).read().0);
+    r.hcint(ch).write_value(ints);
+
+    if ints.chh() {
+        // A channel halted without a result was halted because its transfer was dropped.
+        let result = match state.ch_pending[ch].swap(RESULT_BUSY, Ordering::Relaxed) {
+            RESULT_BUSY => RESULT_ERROR,
+            result => result,
+        };
+        state.ch_result[ch].store(result, Ordering::Release);
+        state.ch_wakers[ch].wake();
+        return;
+    }
+
+    let hcchar = r.hcchar(ch).read();
+    let result = if ints.xfrc() {
+        RESULT_DONE
+    } else if ints.stall() {
+        RESULT_STALL
+    } else if ints.txerr() || ints.bberr() || ints.frmor() || ints.dterr() {
+        RESULT_ERROR
+    } else if ints.nak() {
+        if hcchar.epdir() && matches!(hcchar.eptyp(), vals::Eptyp::CONTROL | vals::Eptyp::BULK) {
+            // Bulk and control IN transactions are retried until the device has data.
+            r.hcchar(ch).modify(|w| {
+                w.set_chdis(false);
+                w.set_chena(true);
+            });
+            return;
+        }
+        RESULT_NAK
+    } else {
+        return;
+    };
+
+    // Keep the first result, for example a buffer overflow seen before the transfer completed.
+    let _ = state.ch_pending[ch].compare_exchange(RESULT_BUSY, result, Ordering::Relaxed, Ordering::Relaxed);
+
+    if hcchar.chena() {
+        // The result is reported by the channel halted interrupt.
+        r.hcchar(ch).modify(|w| w.set_chdis(true));
+    } else {
+        let result = state.ch_pending[ch].swap(RESULT_BUSY, Ordering::Relaxed);
+        state.ch_result[ch].store(result, Ordering::Release);
+        state.ch_wakers[ch].wake();
+    }
+}
+
+/// Halts a channel, and stops filling the buffer of its IN transfer.
+fn halt<T: Instance>(ch: usize) {
+    critical_section::with(|_| {
+        T::host_state().ch_buf_len[ch].store(0, Ordering::Relaxed);
+
+        let r = T::regs();
+        if r.hcchar(ch).read().chena() {
+            r.hcchar(ch).modify(|w| w.set_chdis(true));
+        }
+    });
+}
+
+fn to_error(result: u8) -> PipeError {
+    match result {
+        RESULT_STALL => PipeError::Stall,
+        RESULT_OVERFLOW => PipeError::BufferOverflow,
+        RESULT_DISCONNECTED => PipeError::Disconnected,
+        _ => PipeError::Transaction,
+    }
+}
+
+/// USB OTG host driver.
+///
+/// Pipes are mapped to host channels, so at most `HOST_CHANNEL_COUNT` pipes (8 to 16 depending on
+/// the chip) can be allocated at the same time, the control pipe included.
+pub struct Driver<'d, T: Instance> {
+    phantom: PhantomData<&'d mut T>,
+    phy_type: PhyType,
+    inited: bool,
+    connected: bool,
+}
+
+impl<'d, T: Instance> Driver<'d, T> {
+    /// Initializes USB OTG peripheral in host mode with internal Full-Speed PHY.
+    ///
+    /// The VBUS supply of the port is not controlled by the peripheral: on most boards, it's
+    /// switched on with a GPIO.
+    pub fn new_fs(
+        _peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        dp: impl Peripheral<P = impl DpPin<T>> + 'd,
+        dm: impl Peripheral<P = impl DmPin<T>> + 'd,
+    ) -> Self {
+        into_ref!(dp, dm);
+
+        dp.set_as_af(dp.af_num(), AFType::OutputPushPull);
+        dm.set_as_af(dm.af_num(), AFType::OutputPushPull);
+
+        Self {
+            phantom: PhantomData,
+            phy_type: PhyType::InternalFullSpeed,
+            inited: false,
+            connected: false,
+        }
+    }
+
+    /// Initializes USB OTG peripheral in host mode with external High-Speed PHY.
+    ///
+    /// The VBUS supply is usually switched by the PHY, which can be configured with the
+    /// [`ulpi`](super::ulpi) register access once [`wait_for_device_event()`](host::Driver::wait_for_device_event)
+    /// is called.
+    pub fn new_hs_ulpi(
+        _peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        ulpi_clk: impl Peripheral<P = impl UlpiClkPin<T>> + 'd,
+        ulpi_dir: impl Peripheral<P = impl UlpiDirPin<T>> + 'd,
+        ulpi_nxt: impl Peripheral<P = impl UlpiNxtPin<T>> + 'd,
+        ulpi_stp: impl Peripheral<P = impl UlpiStpPin<T>> + 'd,
+        ulpi_d0: impl Peripheral<P = impl UlpiD0Pin<T>> + 'd,
+        ulpi_d1: impl Peripheral<P = impl UlpiD1Pin<T>> + 'd,
+        ulpi_d2: impl Peripheral<P = impl UlpiD2Pin<T>> + 'd,
+        ulpi_d3: impl Peripheral<P = impl UlpiD3Pin<T>> + 'd,
+        ulpi_d4: impl Peripheral<P = impl UlpiD4Pin<T>> + 'd,
+        ulpi_d5: impl Peripheral<P = impl UlpiD5Pin<T>> + 'd,
+        ulpi_d6: impl Peripheral<P = impl UlpiD6Pin<T>> + 'd,
+        ulpi_d7: impl Peripheral<P = impl UlpiD7Pin<T>> + 'd,
+    ) -> Self {
+        assert!(T::HIGH_SPEED == true, "Peripheral is not capable of high-speed USB");
+
+        config_ulpi_pins!(
+            ulpi_clk, ulpi_dir, ulpi_nxt, ulpi_stp, ulpi_d0, ulpi_d1, ulpi_d2, ulpi_d3, ulpi_d4, ulpi_d5, ulpi_d6,
+            ulpi_d7
+        );
+
+        Self {
+            phantom: PhantomData,
+            phy_type: PhyType::ExternalHighSpeed,
+            inited: false,
+            connected: false,
+        }
+    }
+
+    async fn init(&mut self) {
+        power_up::<T>(self.phy_type);
+
+        <T as RccPeripheral>::enable();
+        <T as RccPeripheral>::reset();
+
+        let r = T::regs();
+        let core_id = r.cid().read().0;
+        info!("Core id {:08x}", core_id);
+
+        // Wait for AHB ready.
+        while !r.grstctl().read().ahbidl() {}
+
+        // Configure as host.
+        let fs_phy = self.phy_type.internal() && !self.phy_type.high_speed();
+        r.gusbcfg().write(|w| {
+            // Force host mode
+            w.set_fhmod(true);
+            // Enable internal full-speed PHY
+            w.set_physel(fs_phy);
+        });
+
+        if !self.phy_type.internal() {
+            core_reset::<T>();
+        }
+
+        // The host doesn't sense VBUS, it's switched on by the board.
+        match core_id {
+            0x0000_1200 | 0x0000_1100 => {
+                assert!(self.phy_type != PhyType::InternalHighSpeed);
+
+                r.gccfg_v1().modify(|w| {
+                    // Enable internal full-speed PHY, logic is inverted
+                    w.set_pwrdwn(self.phy_type.internal());
+                    w.set_novbussens(true);
+                    w.set_vbusasen(false);
+                    w.set_vbusbsen(false);
+                    w.set_sofouten(false);
+                });
+            }
+            0x0000_2000 | 0x0000_2100 | 0x0000_2300 | 0x0000_3000 | 0x0000_3100 => {
+                r.gccfg_v2().modify(|w| {
+                    // Enable internal full-speed PHY, logic is inverted
+                    w.set_pwrdwn(fs_phy);
+                    w.set_phyhsen(self.phy_type.internal() && self.phy_type.high_speed());
+                    w.set_vbden(false);
+                });
+
+                r.gotgctl().modify(|w| {
+                    w.set_bvaloen(false);
+                    w.set_bvaloval(false);
+                });
+            }
+            _ => unimplemented!("Unknown USB core id {:X}", core_id),
+        }
+
+        // Wait for the core to switch to host mode, which takes up to 25 ms.
+        while !r.gintsts().read().cmod() {}
+
+        r.hcfg().write(|w| {
+            if fs_phy {
+                w.set_fslss(true);
+                w.set_fslspcs(FSLSPCS_48MHZ);
+            }
+        });
+
+        // Split the FIFO RAM between the RX FIFO, and the non-periodic and periodic TX FIFOs.
+        let rx_fifo_size_words = T::FIFO_DEPTH_WORDS * 2 / 5;
+        let nptx_fifo_size_words = T::FIFO_DEPTH_WORDS * 3 / 10;
+        let ptx_fifo_size_words = T::FIFO_DEPTH_WORDS - rx_fifo_size_words - nptx_fifo_size_words;
+        r.grxfsiz().modify(|w| w.set_rxfd(rx_fifo_size_words));
+        r.hnptxfsiz().write(|w| {
+            w.set_sa(rx_fifo_size_words);
+            w.set_fd(nptx_fifo_size_words);
+        });
+        r.hptxfsiz().write(|w| {
+            w.set_sa(rx_fifo_size_words + nptx_fifo_size_words);
+            w.set_fd(ptx_fifo_size_words);
+        });
+
+        // Flush fifos
+        r.grstctl().write(|w| {
+            w.set_rxfflsh(true);
+            w.set_txfflsh(true);
+            w.set_txfnum(0x10);
+        });
+        loop {
+            let x = r.grstctl().read();
+            if !x.rxfflsh() && !x.txfflsh() {
+                break;
+            }
+        }
+
+        // Unmask and clear core interrupts. Channel interrupts are unmasked when they're allocated.
+        r.haintmsk().write(|_| {});
+        r.gintsts().write_value(regs::Gintsts(0xFFFF_FFFF));
+        r.gintmsk().write(|w| {
+            w.set_rxflvlm(true);
+            w.set_prtim(true);
+            w.set_hcim(true);
+            w.set_discint(true);
+        });
+        r.gahbcfg().write(|w| {
+            w.set_gint(true); // unmask global interrupt
+        });
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        // Power the port, connections are detected from now on.
+        modify_hprt::<T>(|w| w.set_ppwr(true));
+    }
+
+    fn alloc_channel(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Channel<'d, T>, PipeAllocError> {
+        if endpoint.ep_type == EndpointType::Isochronous {
+            return Err(PipeAllocError);
+        }
+
+        let state = T::host_state();
+        let index = critical_section::with(|_| {
+            let allocated = state.ch_allocated.load(Ordering::Relaxed);
+            let index = (0..T::HOST_CHANNEL_COUNT).find(|ch| allocated & (1 << ch) == 0)?;
+            state.ch_allocated.store(allocated | (1 << index), Ordering::Relaxed);
+            T::regs().haintmsk().modify(|w| w.set_haintm(w.haintm() | (1 << index)));
+            Some(index)
+        })
+        .ok_or(PipeAllocError)?;
+
+        clear_channel_interrupts::<T>(index);
+        T::regs().hcintmsk(index).write(|w| {
+            w.set_xfrcm(true);
+            w.set_chhm(true);
+            w.set_stallm(true);
+            w.set_nakm(true);
+            w.set_txerrm(true);
+            w.set_bberrm(true);
+            w.set_frmorm(true);
+            w.set_dterrm(true);
+        });
+
+        trace!("allocated channel {} for ep {:02x}", index, endpoint.addr.index());
+
+        Ok(Channel {
+            _phantom: PhantomData,
+            index,
+            device_address,
+            endpoint: endpoint.addr.index() as u8,
+            ep_type: endpoint.ep_type,
+            max_packet_size: endpoint.max_packet_size,
+            interval_ms: endpoint.interval_ms,
+            low_speed: speed == Speed::Low,
+            data_pid: DPID_DATA0,
+        })
+    }
+}
+
+impl<'d, T: Instance> host::Driver<'d> for Driver<'d, T> {
+    type ControlPipe = ControlPipe<'d, T>;
+    type PipeIn = PipeIn<'d, T>;
+    type PipeOut = PipeOut<'d, T>;
+
+    async fn wait_for_device_event(&mut self) -> DeviceEvent {
+        if !self.inited {
+            self.init().await;
+            self.inited = true;
+        }
+
+        let state = T::host_state();
+        poll_fn(|cx| {
+            state.port_waker.register(cx.waker());
+
+            let events = state.port_events.load(Ordering::Acquire);
+            if events & PORT_DISCONNECTED != 0 {
+                state
+                    .port_events
+                    .fetch_and(!(PORT_DISCONNECTED | PORT_ENABLED), Ordering::AcqRel);
+                if self.connected {
+                    self.connected = false;
+                    return Poll::Ready(DeviceEvent::Disconnected);
+                }
+            }
+
+            if events & PORT_CONNECTED != 0 {
+                state.port_events.fetch_and(!PORT_CONNECTED, Ordering::AcqRel);
+                let hprt = T::regs().hprt().read();
+                if hprt.pcsts() && !self.connected {
+                    self.connected = true;
+                    // Low-speed devices pull D- up. High-speed devices attach as full-speed
+                    // devices, the core only tells them apart after the reset.
+                    let speed = match hprt.plsts() & 0b10 != 0 {
+                        true => Speed::Low,
+                        false => Speed::Full,
+                    };
+                    return Poll::Ready(DeviceEvent::Connected(speed));
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn bus_reset(&mut self) {
+        let state = T::host_state();
+        state.port_events.fetch_and(!PORT_ENABLED, Ordering::AcqRel);
+
+        modify_hprt::<T>(|w| w.set_prst(true));
+        delay_ms(RESET_DURATION_MS).await;
+        modify_hprt::<T>(|w| w.set_prst(false));
+
+        // The port is enabled once the reset is complete.
+        poll_fn(|cx| {
+            state.port_waker.register(cx.waker());
+            match state.port_events.load(Ordering::Acquire) & (PORT_ENABLED | PORT_DISCONNECTED) {
+                0 => Poll::Pending,
+                _ => Poll::Ready(()),
+            }
+        })
+        .await;
+
+        delay_ms(RESET_RECOVERY_MS).await;
+    }
+
+    fn alloc_control_pipe(
+        &mut self,
+        device_address: u8,
+        max_packet_size: u16,
+        speed: Speed,
+    ) -> Result<Self::ControlPipe, PipeAllocError> {
+        let endpoint = EndpointInfo {
+            addr: embassy_usb_driver::EndpointAddress::from_parts(0, embassy_usb_driver::Direction::Out),
+            ep_type: EndpointType::Control,
+            max_packet_size,
+            interval_ms: 0,
+        };
+        let channel = self.alloc_channel(device_address, &endpoint, speed)?;
+        Ok(ControlPipe { channel })
+    }
+
+    fn alloc_pipe_in(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeIn, PipeAllocError> {
+        let channel = self.alloc_channel(device_address, endpoint, speed)?;
+        Ok(PipeIn {
+            channel,
+            info: *endpoint,
+        })
+    }
+
+    fn alloc_pipe_out(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeOut, PipeAllocError> {
+        let channel = self.alloc_channel(device_address, endpoint, speed)?;
+        Ok(PipeOut {
+            channel,
+            info: *endpoint,
+        })
+    }
+}
+
+impl<'d, T: Instance> Drop for Driver<'d, T> {
+    fn drop(&mut self) {
+        if self.inited {
+            T::Interrupt::disable();
+            modify_hprt::<T>(|w| w.set_ppwr(false));
+
+            <T as RccPeripheral>::disable();
+
+            power_down::<T>();
+        }
+    }
+}
+
+/// A host channel, programmed with the endpoint characteristics on each transfer.
+struct Channel<'d, T: Instance> {
+    _phantom: PhantomData<&'d mut T>,
+    index: usize,
+    device_address: u8,
+    endpoint: u8,
+    ep_type: EndpointType,
+    max_packet_size: u16,
+    interval_ms: u8,
+    low_speed: bool,
+    /// Data PID of the next transaction.
+    data_pid: u8,
+}
+
+impl<'d, T: Instance> Channel<'d, T> {
+    fn periodic(&self) -> bool {
+        self.ep_type == EndpointType::Interrupt
+    }
+
+    fn hcchar(&self, dir_in: bool) -> regs::Hcchar {
+        let mut w = regs::Hcchar(0);
+        w.set_mpsiz(self.max_packet_size);
+        w.set_epnum(self.endpoint);
+        w.set_epdir(dir_in);
+        w.set_lsdev(self.low_speed);
+        w.set_eptyp(match self.ep_type {
+            EndpointType::Bulk => vals::Eptyp::BULK,
+            EndpointType::Interrupt => vals::Eptyp::INTERRUPT,
+            _ => vals::Eptyp::CONTROL,
+        });
+        w.set_mcnt(1);
+        w.set_dad(self.device_address);
+        // Periodic transactions are scheduled in the next frame.
+        w.set_oddfrm(self.periodic() && T::regs().hfnum().read().frnum() & 1 == 0);
+        w
+    }
+
+    /// Runs one IN transfer of up to `len` bytes, or one OUT packet, and waits for its result.
+    async fn transfer(&mut self, dir_in: bool, buf: *mut u8, len: usize) -> u8 {
+        let r = T::regs();
+        let ch = self.index;
+        let state = T::host_state();
+
+        // Wait for a previous transfer that was dropped to be halted.
+        loop {
+            if !r.hprt().read().pcsts() {
+                return RESULT_DISCONNECTED;
+            }
+            if !r.hcchar(ch).read().chena() {
+                break;
+            }
+        }
+
+        state.ch_pending[ch].store(RESULT_BUSY, Ordering::Relaxed);
+        state.ch_result[ch].store(RESULT_BUSY, Ordering::Relaxed);
+        clear_channel_interrupts::<T>(ch);
+
+        let mps = self.max_packet_size as usize;
+        let (xfrsiz, pktcnt) = if dir_in {
+            let pktcnt = ((len + mps - 1) / mps).max(1);
+            critical_section::with(|_| {
+                unsafe { *state.ch_buf[ch].get() = buf };
+                state.ch_buf_len[ch].store(len as u16, Ordering::Relaxed);
+                state.ch_received[ch].store(0, Ordering::Relaxed);
+            });
+            (pktcnt * mps, pktcnt)
+        } else {
+            (len, 1)
+        };
+        r.hctsiz(ch).write(|w| {
+            w.set_xfrsiz(xfrsiz as u32);
+            w.set_pktcnt(pktcnt as u16);
+            w.set_dpid(self.data_pid);
+        });
+
+        let halt_on_drop = OnDrop::new(|| halt::<T>(ch));
+
+        r.hcchar(ch).write_value({
+            let mut w = self.hcchar(dir_in);
+            w.set_chena(true);
+            w
+        });
+
+        if !dir_in {
+            // SAFETY: OUT transfers are started from a slice of `len` bytes.
+            let data = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+
+            let len_words = (len + 3) / 4;
+            let tx_fifo_space = || match self.periodic() {
+                true => r.hptxsts().read().ptxfsavl() as usize,
+                false => r.gnptxsts().read().nptxfsav() as usize,
+            };
+            while tx_fifo_space() < len_words {}
+
+            for chunk in data.chunks(4) {
+                let mut word = [0; 4];
+                word[0..chunk.len()].copy_from_slice(chunk);
+                r.fifo(ch).write_value(regs::Fifo(u32::from_ne_bytes(word)));
+            }
+        }
+
+        let result = poll_fn(|cx| {
+            state.ch_wakers[ch].register(cx.waker());
+            match state.ch_result[ch].load(Ordering::Acquire) {
+                RESULT_BUSY => Poll::Pending,
+                result => Poll::Ready(result),
+            }
+        })
+        .await;
+
+        halt_on_drop.defuse();
+
+        // The core toggles the data PID of the channel on each acknowledged packet.
+        self.data_pid = r.hctsiz(ch).read().dpid();
+        result
+    }
+
+    /// Reads packets into `buf` until a short packet is received, or `buf` is full.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        let mps = self.max_packet_size as usize;
+        let max_chunk_len = (u16::MAX as usize / mps).min(MAX_PKTCNT) * mps;
+
+        if buf.is_empty() {
+            return self.read_chunk(buf).await;
+        }
+
+        let mut total = 0;
+        for chunk in buf.chunks_mut(max_chunk_len) {
+            let n = self.read_chunk(chunk).await?;
+            total += n;
+            if n < chunk.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    async fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        let mut retries = 0;
+        loop {
+            let result = self.transfer(true, buf.as_mut_ptr(), buf.len()).await;
+            let received = T::host_state().ch_received[self.index].load(Ordering::Acquire) as usize;
+            match result {
+                RESULT_DONE => return Ok(received),
+                // Only interrupt transfers are halted on NAK, they're polled at the endpoint interval.
+                RESULT_NAK => delay_ms(self.interval_ms.max(1) as u32).await,
+                RESULT_ERROR if received == 0 && retries < MAX_RETRIES => retries += 1,
+                result => return Err(to_error(result)),
+            }
+        }
+    }
+
+    /// Writes `data` in packets, with a zero-length packet if `data` is empty.
+    async fn write(&mut self, data: &[u8]) -> Result<(), PipeError> {
+        if data.is_empty() {
+            return self.write_packet(data).await;
+        }
+
+        for packet in data.chunks(self.max_packet_size as usize) {
+            self.write_packet(packet).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_packet(&mut self, packet: &[u8]) -> Result<(), PipeError> {
+        let mut retries = 0;
+        loop {
+            match self.transfer(false, packet.as_ptr() as *mut u8, packet.len()).await {
+                RESULT_DONE => return Ok(()),
+                RESULT_NAK if self.periodic() => delay_ms(self.interval_ms.max(1) as u32).await,
+                RESULT_NAK => {}
+                RESULT_ERROR if retries < MAX_RETRIES => retries += 1,
+                result => return Err(to_error(result)),
+            }
+        }
+    }
+}
+
+impl<'d, T: Instance> Drop for Channel<'d, T> {
+    fn drop(&mut self) {
+        halt::<T>(self.index);
+
+        critical_section::with(|_| {
+            T::regs()
+                .haintmsk()
+                .modify(|w| w.set_haintm(w.haintm() & !(1 << self.index)));
+            T::host_state()
+                .ch_allocated
+                .fetch_and(!(1 << self.index), Ordering::Relaxed);
+        });
+    }
+}
+
+/// Control pipe to endpoint 0 of a device.
+pub struct ControlPipe<'d, T: Instance> {
+    channel: Channel<'d, T>,
+}
+
+impl<'d, T: Instance> ControlPipe<'d, T> {
+    async fn setup(&mut self, setup: &[u8; 8]) -> Result<(), PipeError> {
+        self.channel.data_pid = DPID_SETUP;
+        self.channel.write_packet(setup).await?;
+        // Data and status stages start with DATA1.
+        self.channel.data_pid = DPID_DATA1;
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance> host::ControlPipe for ControlPipe<'d, T> {
+    fn set_device_address(&mut self, device_address: u8) {
+        self.channel.device_address = device_address;
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: u16) {
+        self.channel.max_packet_size = max_packet_size;
+    }
+
+    async fn control_in(&mut self, setup: &[u8; 8], buf: &mut [u8]) -> Result<usize, PipeError> {
+        self.setup(setup).await?;
+
+        let n = match buf.is_empty() {
+            true => 0,
+            false => self.channel.read(buf).await?,
+        };
+
+        // Status stage
+        self.channel.data_pid = DPID_DATA1;
+        self.channel.write(&[]).await?;
+        Ok(n)
+    }
+
+    async fn control_out(&mut self, setup: &[u8; 8], data: &[u8]) -> Result<(), PipeError> {
+        self.setup(setup).await?;
+
+        if !data.is_empty() {
+            self.channel.write(data).await?;
+        }
+
+        // Status stage
+        self.channel.data_pid = DPID_DATA1;
+        self.channel.read(&mut []).await?;
+        Ok(())
+    }
+}
+
+/// Pipe to an IN endpoint.
+pub struct PipeIn<'d, T: Instance> {
+    channel: Channel<'d, T>,
+    info: EndpointInfo,
+}
+
+impl<'d, T: Instance> host::PipeIn for PipeIn<'d, T> {
+    fn info(&self) -> &EndpointInfo {
+        &self.info
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        self.channel.read(buf).await
+    }
+}
+
+/// Pipe to an OUT endpoint.
+pub struct PipeOut<'d, T: Instance> {
+    channel: Channel<'d, T>,
+    info: EndpointInfo,
+}
+
+impl<'d, T: Instance> host::PipeOut for PipeOut<'d, T> {
+    fn info(&self) -> &EndpointInfo {
+        &self.info
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> Result<(), PipeError> {
+        self.channel.write(buf).await
+    }
+}
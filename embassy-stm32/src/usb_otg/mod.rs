@@ -2,23 +2,32 @@ use crate::rcc::RccPeripheral;
 use crate::{interrupt, peripherals};
 
 #[cfg(feature = "nightly")]
+#[macro_use]
 mod usb;
 #[cfg(feature = "nightly")]
 pub use usb::*;
+#[cfg(feature = "nightly")]
+pub mod host;
+pub mod ulpi;
 
 // Using Instance::ENDPOINT_COUNT requires feature(const_generic_expr) so just define maximum eps
 #[cfg(feature = "nightly")]
 const MAX_EP_COUNT: usize = 9;
+#[cfg(feature = "nightly")]
+const MAX_HOST_CHANNEL_COUNT: usize = 16;
 
 pub(crate) mod sealed {
     pub trait Instance {
         const HIGH_SPEED: bool;
         const FIFO_DEPTH_WORDS: u16;
         const ENDPOINT_COUNT: usize;
+        const HOST_CHANNEL_COUNT: usize;
 
         fn regs() -> crate::pac::otg::Otg;
         #[cfg(feature = "nightly")]
         fn state() -> &'static super::State<{ super::MAX_EP_COUNT }>;
+        #[cfg(feature = "nightly")]
+        fn host_state() -> &'static super::host::HostState<{ super::MAX_HOST_CHANNEL_COUNT }>;
     }
 }
 
@@ -53,6 +62,7 @@ foreach_interrupt!(
                 if #[cfg(stm32f1)] {
                     const FIFO_DEPTH_WORDS: u16 = 128;
                     const ENDPOINT_COUNT: usize = 8;
+                    const HOST_CHANNEL_COUNT: usize = 8;
                 } else if #[cfg(any(
                     stm32f2,
                     stm32f401,
@@ -68,6 +78,7 @@ foreach_interrupt!(
                 ))] {
                     const FIFO_DEPTH_WORDS: u16 = 320;
                     const ENDPOINT_COUNT: usize = 4;
+                    const HOST_CHANNEL_COUNT: usize = 8;
                 } else if #[cfg(any(
                     stm32f412,
                     stm32f413,
@@ -81,15 +92,19 @@ foreach_interrupt!(
                 ))] {
                     const FIFO_DEPTH_WORDS: u16 = 320;
                     const ENDPOINT_COUNT: usize = 6;
+                    const HOST_CHANNEL_COUNT: usize = 12;
                 } else if #[cfg(stm32g0x1)] {
                     const FIFO_DEPTH_WORDS: u16 = 512;
                     const ENDPOINT_COUNT: usize = 8;
+                    const HOST_CHANNEL_COUNT: usize = 8;
                 } else if #[cfg(stm32h7)] {
                     const FIFO_DEPTH_WORDS: u16 = 1024;
                     const ENDPOINT_COUNT: usize = 9;
+                    const HOST_CHANNEL_COUNT: usize = 16;
                 } else if #[cfg(stm32u5)] {
                     const FIFO_DEPTH_WORDS: u16 = 320;
                     const ENDPOINT_COUNT: usize = 6;
+                    const HOST_CHANNEL_COUNT: usize = 12;
                 } else {
                     compile_error!("USB_OTG_FS peripheral is not supported by this chip.");
                 }
@@ -104,6 +119,12 @@ foreach_interrupt!(
                 static STATE: State<MAX_EP_COUNT> = State::new();
                 &STATE
             }
+
+            #[cfg(feature = "nightly")]
+            fn host_state() -> &'static host::HostState<MAX_HOST_CHANNEL_COUNT> {
+                static STATE: host::HostState<MAX_HOST_CHANNEL_COUNT> = host::HostState::new();
+                &STATE
+            }
         }
 
         impl Instance for peripherals::USB_OTG_FS {
@@ -129,6 +150,7 @@ foreach_interrupt!(
                 ))] {
                     const FIFO_DEPTH_WORDS: u16 = 1024;
                     const ENDPOINT_COUNT: usize = 6;
+                    const HOST_CHANNEL_COUNT: usize = 12;
                 } else if #[cfg(any(
                     stm32f446,
                     stm32f469,
@@ -138,9 +160,11 @@ foreach_interrupt!(
                 ))] {
                     const FIFO_DEPTH_WORDS: u16 = 1024;
                     const ENDPOINT_COUNT: usize = 9;
+                    const HOST_CHANNEL_COUNT: usize = 16;
                 } else if #[cfg(stm32u5)] {
                     const FIFO_DEPTH_WORDS: u16 = 1024;
                     const ENDPOINT_COUNT: usize = 9;
+                    const HOST_CHANNEL_COUNT: usize = 16;
                 } else {
                     compile_error!("USB_OTG_HS peripheral is not supported by this chip.");
                 }
@@ -156,6 +180,12 @@ foreach_interrupt!(
                 static STATE: State<MAX_EP_COUNT> = State::new();
                 &STATE
             }
+
+            #[cfg(feature = "nightly")]
+            fn host_state() -> &'static host::HostState<MAX_HOST_CHANNEL_COUNT> {
+                static STATE: host::HostState<MAX_HOST_CHANNEL_COUNT> = host::HostState::new();
+                &STATE
+            }
         }
 
         impl Instance for peripherals::USB_OTG_HS {
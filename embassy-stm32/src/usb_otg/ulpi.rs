@@ -0,0 +1,115 @@
+//! Register access to an external ULPI PHY.
+//!
+//! The registers are accessed through the vendor control register (GPVNDCTL) of the core, which
+//! must be configured for the external PHY: this is the case once the device driver is started,
+//! or once the host driver waits for a device. The PHY must be clocked, and out of reset.
+
+use super::Instance;
+use crate::pac::otg::regs::Gpvndctl;
+
+/// Polling iterations before giving up on the PHY.
+const TIMEOUT_ITERATIONS: u32 = 100_000;
+
+/// Immediate register addresses, from the ULPI specification.
+pub mod reg {
+    pub const VENDOR_ID_LOW: u8 = 0x00;
+    pub const VENDOR_ID_HIGH: u8 = 0x01;
+    pub const PRODUCT_ID_LOW: u8 = 0x02;
+    pub const PRODUCT_ID_HIGH: u8 = 0x03;
+    pub const FUNCTION_CONTROL: u8 = 0x04;
+    pub const INTERFACE_CONTROL: u8 = 0x07;
+    pub const OTG_CONTROL: u8 = 0x0a;
+    pub const USB_INTERRUPT_ENABLE_RISING: u8 = 0x0d;
+    pub const USB_INTERRUPT_ENABLE_FALLING: u8 = 0x10;
+    pub const USB_INTERRUPT_STATUS: u8 = 0x13;
+    pub const USB_INTERRUPT_LATCH: u8 = 0x14;
+    pub const DEBUG: u8 = 0x15;
+    pub const SCRATCH: u8 = 0x16;
+
+    /// Offset of the write-only alias setting the written bits of a read/write register.
+    pub const SET: u8 = 1;
+    /// Offset of the write-only alias clearing the written bits of a read/write register.
+    pub const CLEAR: u8 = 2;
+}
+
+/// OTG Control register bits.
+pub mod otg_control {
+    pub const ID_PULLUP: u8 = 1 << 0;
+    pub const DP_PULLDOWN: u8 = 1 << 1;
+    pub const DM_PULLDOWN: u8 = 1 << 2;
+    pub const DISCHRG_VBUS: u8 = 1 << 3;
+    pub const CHRG_VBUS: u8 = 1 << 4;
+    /// Drive 5 V on VBUS.
+    pub const DRV_VBUS: u8 = 1 << 5;
+    /// Drive VBUS with an external supply, enabled by the PHY.
+    pub const DRV_VBUS_EXTERNAL: u8 = 1 << 6;
+    pub const USE_EXTERNAL_VBUS_INDICATOR: u8 = 1 << 7;
+}
+
+/// ULPI register access error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The PHY didn't complete the access, because it's not clocked or not configured.
+    Timeout,
+}
+
+/// Reads an immediate register of the PHY.
+pub fn read_register<T: Instance>(addr: u8) -> Result<u8, Error> {
+    let mut request = Gpvndctl(0);
+    request.set_regaddr(addr);
+    access::<T>(request).map(|value| value.regdata())
+}
+
+/// Writes an immediate register of the PHY.
+pub fn write_register<T: Instance>(addr: u8, value: u8) -> Result<(), Error> {
+    let mut request = Gpvndctl(0);
+    request.set_regaddr(addr);
+    request.set_regwr(true);
+    request.set_regdata(value);
+    access::<T>(request).map(|_| ())
+}
+
+/// Sets bits of a register of the PHY, through its set alias.
+pub fn set_bits<T: Instance>(addr: u8, bits: u8) -> Result<(), Error> {
+    write_register::<T>(addr + reg::SET, bits)
+}
+
+/// Clears bits of a register of the PHY, through its clear alias.
+pub fn clear_bits<T: Instance>(addr: u8, bits: u8) -> Result<(), Error> {
+    write_register::<T>(addr + reg::CLEAR, bits)
+}
+
+/// Reads the vendor and product IDs of the PHY, for example to check the PHY interface.
+pub fn read_ids<T: Instance>() -> Result<(u16, u16), Error> {
+    let id = |low, high| -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([
+            read_register::<T>(low)?,
+            read_register::<T>(high)?,
+        ]))
+    };
+    Ok((
+        id(reg::VENDOR_ID_LOW, reg::VENDOR_ID_HIGH)?,
+        id(reg::PRODUCT_ID_LOW, reg::PRODUCT_ID_HIGH)?,
+    ))
+}
+
+fn access<T: Instance>(mut request: Gpvndctl) -> Result<Gpvndctl, Error> {
+    let gpvndctl = T::regs().gpvndctl();
+
+    wait(|| !gpvndctl.read().vstsbsy())?;
+    request.set_newregreq(true);
+    gpvndctl.write_value(request);
+    wait(|| gpvndctl.read().vstsdone())?;
+
+    Ok(gpvndctl.read())
+}
+
+fn wait(mut done: impl FnMut() -> bool) -> Result<(), Error> {
+    for _ in 0..TIMEOUT_ITERATIONS {
+        if done() {
+            return Ok(());
+        }
+    }
+    Err(Error::Timeout)
+}
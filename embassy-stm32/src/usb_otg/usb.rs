@@ -7,7 +7,7 @@ use embassy_hal_common::{into_ref, Peripheral};
 use embassy_sync::waitqueue::AtomicWaker;
 use embassy_usb_driver::{
     self, Bus as _, Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointIn, EndpointInfo,
-    EndpointOut, EndpointType, Event, Unsupported,
+    EndpointOut, EndpointType, Event, TestMode, Unsupported,
 };
 use futures::future::poll_fn;
 
@@ -539,106 +539,7 @@ impl<'d, T: Instance> Bus<'d, T> {
 
 impl<'d, T: Instance> Bus<'d, T> {
     fn init(&mut self) {
-        #[cfg(stm32l4)]
-        {
-            crate::peripherals::PWR::enable();
-            critical_section::with(|_| crate::pac::PWR.cr2().modify(|w| w.set_usv(true)));
-        }
-
-        #[cfg(stm32f7)]
-        {
-            // Enable ULPI clock if external PHY is used
-            let ulpien = !self.phy_type.internal();
-            critical_section::with(|_| {
-                crate::pac::RCC.ahb1enr().modify(|w| {
-                    if T::HIGH_SPEED {
-                        w.set_usb_otg_hsulpien(ulpien);
-                    } else {
-                        w.set_usb_otg_hsen(ulpien);
-                    }
-                });
-
-                // Low power mode
-                crate::pac::RCC.ahb1lpenr().modify(|w| {
-                    if T::HIGH_SPEED {
-                        w.set_usb_otg_hsulpilpen(ulpien);
-                    } else {
-                        w.set_usb_otg_hslpen(ulpien);
-                    }
-                });
-            });
-        }
-
-        #[cfg(stm32h7)]
-        {
-            // If true, VDD33USB is generated by internal regulator from VDD50USB
-            // If false, VDD33USB and VDD50USB must be suplied directly with 3.3V (default on nucleo)
-            // TODO: unhardcode
-            let internal_regulator = false;
-
-            // Enable USB power
-            critical_section::with(|_| {
-                crate::pac::PWR.cr3().modify(|w| {
-                    w.set_usb33den(true);
-                    w.set_usbregen(internal_regulator);
-                })
-            });
-
-            // Wait for USB power to stabilize
-            while !crate::pac::PWR.cr3().read().usb33rdy() {}
-
-            // Use internal 48MHz HSI clock. Should be enabled in RCC by default.
-            critical_section::with(|_| {
-                crate::pac::RCC
-                    .d2ccip2r()
-                    .modify(|w| w.set_usbsel(crate::pac::rcc::vals::Usbsel::HSI48))
-            });
-
-            // Enable ULPI clock if external PHY is used
-            let ulpien = !self.phy_type.internal();
-            critical_section::with(|_| {
-                crate::pac::RCC.ahb1enr().modify(|w| {
-                    if T::HIGH_SPEED {
-                        w.set_usb_otg_hs_ulpien(ulpien);
-                    } else {
-                        w.set_usb_otg_fs_ulpien(ulpien);
-                    }
-                });
-                crate::pac::RCC.ahb1lpenr().modify(|w| {
-                    if T::HIGH_SPEED {
-                        w.set_usb_otg_hs_ulpilpen(ulpien);
-                    } else {
-                        w.set_usb_otg_fs_ulpilpen(ulpien);
-                    }
-                });
-            });
-        }
-
-        #[cfg(stm32u5)]
-        {
-            // Enable USB power
-            critical_section::with(|_| {
-                crate::pac::RCC.ahb3enr().modify(|w| {
-                    w.set_pwren(true);
-                });
-                cortex_m::asm::delay(2);
-
-                crate::pac::PWR.svmcr().modify(|w| {
-                    w.set_usv(true);
-                    w.set_uvmen(true);
-                });
-            });
-
-            // Wait for USB power to stabilize
-            while !crate::pac::PWR.svmsr().read().vddusbrdy() {}
-
-            // Select HSI48 as USB clock source.
-            critical_section::with(|_| {
-                crate::pac::RCC.ccipr1().modify(|w| {
-                    w.set_iclksel(crate::pac::rcc::vals::Iclksel::HSI48);
-                })
-            });
-        }
+        power_up::<T>(self.phy_type);
 
         <T as RccPeripheral>::enable();
         <T as RccPeripheral>::reset();
@@ -661,6 +562,10 @@ impl<'d, T: Instance> Bus<'d, T> {
             w.set_physel(self.phy_type.internal() && !self.phy_type.high_speed());
         });
 
+        if !self.phy_type.internal() {
+            core_reset::<T>();
+        }
+
         // Configuring Vbus sense and SOF output
         match core_id {
             0x0000_1200 | 0x0000_1100 => {
@@ -849,12 +754,142 @@ impl<'d, T: Instance> Bus<'d, T> {
 
         <T as RccPeripheral>::disable();
 
-        #[cfg(stm32l4)]
-        crate::pac::PWR.cr2().modify(|w| w.set_usv(false));
-        // Cannot disable PWR, because other peripherals might be using it
+        power_down::<T>();
+    }
+}
+
+/// Enables the USB supply and clocks, shared by the device and host drivers.
+pub(super) fn power_up<T: Instance>(phy_type: PhyType) {
+    // Only the families with a ULPI clock enable need the PHY type.
+    #[cfg(not(any(stm32f2, stm32f4, stm32f7, stm32h7)))]
+    let _ = phy_type;
+
+    #[cfg(stm32l4)]
+    {
+        crate::peripherals::PWR::enable();
+        critical_section::with(|_| crate::pac::PWR.cr2().modify(|w| w.set_usv(true)));
+    }
+
+    #[cfg(any(stm32f2, stm32f4))]
+    if T::HIGH_SPEED {
+        // Enable ULPI clock if external PHY is used
+        let ulpien = !phy_type.internal();
+        critical_section::with(|_| {
+            crate::pac::RCC.ahb1enr().modify(|w| w.set_otghsulpien(ulpien));
+            crate::pac::RCC.ahb1lpenr().modify(|w| w.set_otghsulpilpen(ulpien));
+        });
+    }
+
+    #[cfg(stm32f7)]
+    {
+        // Enable ULPI clock if external PHY is used
+        let ulpien = !phy_type.internal();
+        critical_section::with(|_| {
+            crate::pac::RCC.ahb1enr().modify(|w| {
+                if T::HIGH_SPEED {
+                    w.set_usb_otg_hsulpien(ulpien);
+                } else {
+                    w.set_usb_otg_hsen(ulpien);
+                }
+            });
+
+            // Low power mode
+            crate::pac::RCC.ahb1lpenr().modify(|w| {
+                if T::HIGH_SPEED {
+                    w.set_usb_otg_hsulpilpen(ulpien);
+                } else {
+                    w.set_usb_otg_hslpen(ulpien);
+                }
+            });
+        });
+    }
+
+    #[cfg(stm32h7)]
+    {
+        // If true, VDD33USB is generated by internal regulator from VDD50USB
+        // If false, VDD33USB and VDD50USB must be suplied directly with 3.3V (default on nucleo)
+        // TODO: unhardcode
+        let internal_regulator = false;
+
+        // Enable USB power
+        critical_section::with(|_| {
+            crate::pac::PWR.cr3().modify(|w| {
+                w.set_usb33den(true);
+                w.set_usbregen(internal_regulator);
+            })
+        });
+
+        // Wait for USB power to stabilize
+        while !crate::pac::PWR.cr3().read().usb33rdy() {}
+
+        // Use internal 48MHz HSI clock. Should be enabled in RCC by default.
+        critical_section::with(|_| {
+            crate::pac::RCC
+                .d2ccip2r()
+                .modify(|w| w.set_usbsel(crate::pac::rcc::vals::Usbsel::HSI48))
+        });
+
+        // Enable ULPI clock if external PHY is used
+        let ulpien = !phy_type.internal();
+        critical_section::with(|_| {
+            crate::pac::RCC.ahb1enr().modify(|w| {
+                if T::HIGH_SPEED {
+                    w.set_usb_otg_hs_ulpien(ulpien);
+                } else {
+                    w.set_usb_otg_fs_ulpien(ulpien);
+                }
+            });
+            crate::pac::RCC.ahb1lpenr().modify(|w| {
+                if T::HIGH_SPEED {
+                    w.set_usb_otg_hs_ulpilpen(ulpien);
+                } else {
+                    w.set_usb_otg_fs_ulpilpen(ulpien);
+                }
+            });
+        });
+    }
+
+    #[cfg(stm32u5)]
+    {
+        // Enable USB power
+        critical_section::with(|_| {
+            crate::pac::RCC.ahb3enr().modify(|w| {
+                w.set_pwren(true);
+            });
+            cortex_m::asm::delay(2);
+
+            crate::pac::PWR.svmcr().modify(|w| {
+                w.set_usv(true);
+                w.set_uvmen(true);
+            });
+        });
+
+        // Wait for USB power to stabilize
+        while !crate::pac::PWR.svmsr().read().vddusbrdy() {}
+
+        // Select HSI48 as USB clock source.
+        critical_section::with(|_| {
+            crate::pac::RCC.ccipr1().modify(|w| {
+                w.set_iclksel(crate::pac::rcc::vals::Iclksel::HSI48);
+            })
+        });
     }
 }
 
+/// Resets the core, which is needed after switching to the external ULPI PHY.
+pub(super) fn core_reset<T: Instance>() {
+    let r = T::regs();
+    while !r.grstctl().read().ahbidl() {}
+    r.grstctl().write(|w| w.set_csrst(true));
+    while r.grstctl().read().csrst() {}
+}
+
+pub(super) fn power_down<T: Instance>() {
+    #[cfg(stm32l4)]
+    crate::pac::PWR.cr2().modify(|w| w.set_usv(false));
+    // Cannot disable PWR, because other peripherals might be using it
+}
+
 impl<'d, T: Instance> embassy_usb_driver::Bus for Bus<'d, T> {
     async fn poll(&mut self) -> Event {
         poll_fn(move |cx| {
@@ -952,50 +987,11 @@ impl<'d, T: Instance> embassy_usb_driver::Bus for Bus<'d, T> {
     }
 
     fn endpoint_set_stalled(&mut self, ep_addr: EndpointAddress, stalled: bool) {
-        trace!("endpoint_set_stalled ep={:?} en={}", ep_addr, stalled);
-
-        assert!(
-            ep_addr.index() < T::ENDPOINT_COUNT,
-            "endpoint_set_stalled index {} out of range",
-            ep_addr.index()
-        );
-
-        let regs = T::regs();
-        match ep_addr.direction() {
-            Direction::Out => {
-                critical_section::with(|_| {
-                    regs.doepctl(ep_addr.index()).modify(|w| {
-                        w.set_stall(stalled);
-                    });
-                });
-
-                T::state().ep_out_wakers[ep_addr.index()].wake();
-            }
-            Direction::In => {
-                critical_section::with(|_| {
-                    regs.diepctl(ep_addr.index()).modify(|w| {
-                        w.set_stall(stalled);
-                    });
-                });
-
-                T::state().ep_in_wakers[ep_addr.index()].wake();
-            }
-        }
+        endpoint_set_stalled::<T>(ep_addr, stalled)
     }
 
     fn endpoint_is_stalled(&mut self, ep_addr: EndpointAddress) -> bool {
-        assert!(
-            ep_addr.index() < T::ENDPOINT_COUNT,
-            "endpoint_is_stalled index {} out of range",
-            ep_addr.index()
-        );
-
-        let regs = T::regs();
-
-        match ep_addr.direction() {
-            Direction::Out => regs.doepctl(ep_addr.index()).read().stall(),
-            Direction::In => regs.diepctl(ep_addr.index()).read().stall(),
-        }
+        endpoint_is_stalled::<T>(ep_addr)
     }
 
     fn endpoint_set_enabled(&mut self, ep_addr: EndpointAddress, enabled: bool) {
@@ -1074,7 +1070,28 @@ impl<'d, T: Instance> embassy_usb_driver::Bus for Bus<'d, T> {
     }
 
     async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+        let r = T::regs();
+
+        // Resume signaling must be driven for 1 to 15 ms.
+        r.dctl().modify(|w| w.set_rwusig(true));
+        #[cfg(feature = "time")]
+        embassy_time::Timer::after(embassy_time::Duration::from_millis(5)).await;
+        #[cfg(not(feature = "time"))]
+        cortex_m::asm::delay(unsafe { crate::rcc::get_freqs() }.sys.0 / 200);
+        r.dctl().modify(|w| w.set_rwusig(false));
+
+        Ok(())
+    }
+
+    fn set_test_mode(&mut self, mode: TestMode) -> Result<(), Unsupported> {
+        // Test modes are only defined for high speed.
+        if !self.phy_type.high_speed() {
+            return Err(Unsupported);
+        }
+
+        trace!("test mode {}", mode as u8);
+        T::regs().dctl().modify(|w| w.set_tctl(mode as u8));
+        Ok(())
     }
 }
 
@@ -1084,6 +1101,53 @@ impl<'d, T: Instance> Drop for Bus<'d, T> {
     }
 }
 
+fn endpoint_set_stalled<T: Instance>(ep_addr: EndpointAddress, stalled: bool) {
+    trace!("endpoint_set_stalled ep={:?} en={}", ep_addr, stalled);
+
+    assert!(
+        ep_addr.index() < T::ENDPOINT_COUNT,
+        "endpoint_set_stalled index {} out of range",
+        ep_addr.index()
+    );
+
+    let regs = T::regs();
+    match ep_addr.direction() {
+        Direction::Out => {
+            critical_section::with(|_| {
+                regs.doepctl(ep_addr.index()).modify(|w| {
+                    w.set_stall(stalled);
+                });
+            });
+
+            T::state().ep_out_wakers[ep_addr.index()].wake();
+        }
+        Direction::In => {
+            critical_section::with(|_| {
+                regs.diepctl(ep_addr.index()).modify(|w| {
+                    w.set_stall(stalled);
+                });
+            });
+
+            T::state().ep_in_wakers[ep_addr.index()].wake();
+        }
+    }
+}
+
+fn endpoint_is_stalled<T: Instance>(ep_addr: EndpointAddress) -> bool {
+    assert!(
+        ep_addr.index() < T::ENDPOINT_COUNT,
+        "endpoint_is_stalled index {} out of range",
+        ep_addr.index()
+    );
+
+    let regs = T::regs();
+
+    match ep_addr.direction() {
+        Direction::Out => regs.doepctl(ep_addr.index()).read().stall(),
+        Direction::In => regs.diepctl(ep_addr.index()).read().stall(),
+    }
+}
+
 trait Dir {
     fn dir() -> Direction;
 }
@@ -1126,6 +1190,14 @@ impl<'d, T: Instance> embassy_usb_driver::Endpoint for Endpoint<'d, T, In> {
         })
         .await
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        endpoint_set_stalled::<T>(self.info.addr, stalled)
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> embassy_usb_driver::Endpoint for Endpoint<'d, T, Out> {
@@ -1147,6 +1219,14 @@ impl<'d, T: Instance> embassy_usb_driver::Endpoint for Endpoint<'d, T, Out> {
         })
         .await
     }
+
+    fn set_stalled(&mut self, stalled: bool) {
+        endpoint_set_stalled::<T>(self.info.addr, stalled)
+    }
+
+    fn is_stalled(&mut self) -> bool {
+        endpoint_is_stalled::<T>(self.info.addr)
+    }
 }
 
 impl<'d, T: Instance> embassy_usb_driver::EndpointOut for Endpoint<'d, T, Out> {
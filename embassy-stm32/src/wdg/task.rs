@@ -0,0 +1,93 @@
+use atomic_polyfill::{AtomicU32, Ordering};
+
+/// Task watchdog, checking that several tasks are making progress before the hardware
+/// watchdog is petted.
+///
+/// Each supervised task gets a [`TaskHandle`] with [`TaskWatchdog::register()`], and calls
+/// [`TaskHandle::feed()`] in its loop. The task petting the hardware watchdog does it only
+/// when [`TaskWatchdog::check()`] reports that all the registered tasks fed since the last
+/// check, so that a single stalled task resets the MCU:
+///
+/// ```ignore
+/// static TASK_WATCHDOG: TaskWatchdog = TaskWatchdog::new();
+///
+/// let mut wdg = IndependentWatchdog::new(p.IWDG, 2_000_000);
+/// wdg.unleash();
+/// loop {
+///     Timer::after(Duration::from_secs(1)).await;
+///     if TASK_WATCHDOG.check() {
+///         wdg.pet();
+///     }
+/// }
+/// ```
+pub struct TaskWatchdog {
+    registered: AtomicU32,
+    fed: AtomicU32,
+}
+
+/// A task supervised by a [`TaskWatchdog`].
+pub struct TaskHandle {
+    watchdog: &'static TaskWatchdog,
+    mask: u32,
+}
+
+impl TaskWatchdog {
+    /// Maximum number of supervised tasks.
+    pub const MAX_TASKS: usize = 32;
+
+    pub const fn new() -> Self {
+        Self {
+            registered: AtomicU32::new(0),
+            fed: AtomicU32::new(0),
+        }
+    }
+
+    /// Register a task, `None` if [Self::MAX_TASKS] are already registered.
+    ///
+    /// The task counts as fed until the next check.
+    pub fn register(&'static self) -> Option<TaskHandle> {
+        let mut registered = self.registered.load(Ordering::Relaxed);
+        loop {
+            let mask = 1 << (!registered).trailing_zeros().min(31);
+            if registered & mask != 0 {
+                return None;
+            }
+
+            self.fed.fetch_or(mask, Ordering::Relaxed);
+            match self
+                .registered
+                .compare_exchange(registered, registered | mask, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(_) => return Some(TaskHandle { watchdog: self, mask }),
+                Err(r) => registered = r,
+            }
+        }
+    }
+
+    /// Check whether all the registered tasks fed the watchdog since the last check, and start
+    /// a new period.
+    pub fn check(&self) -> bool {
+        let registered = self.registered.load(Ordering::Acquire);
+        let fed = self.fed.swap(0, Ordering::AcqRel);
+        fed & registered == registered
+    }
+
+    /// Mask of the registered tasks that didn't feed the watchdog since the last check, in the
+    /// order of their registration, for example to log the stalled tasks before the reset.
+    pub fn starving(&self) -> u32 {
+        let registered = self.registered.load(Ordering::Acquire);
+        registered & !self.fed.load(Ordering::Acquire)
+    }
+}
+
+impl TaskHandle {
+    /// Report that the task is making progress.
+    pub fn feed(&self) {
+        self.watchdog.fed.fetch_or(self.mask, Ordering::Release);
+    }
+
+    /// Stop supervising the task, for example when it's waiting for an event without timeout.
+    pub fn unregister(self) {
+        self.watchdog.registered.fetch_and(!self.mask, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,176 @@
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_common::{into_ref, Peripheral};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt;
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::wwdg::vals::{Wdga, Wdgtb};
+use crate::rcc::RccPeripheral;
+
+static EWI_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// The MCU is reset when the down-counter goes from 0x40 to 0x3F.
+const MIN_COUNTER: u32 = 0x40;
+const MAX_COUNTER: u32 = 0x7f;
+
+#[cfg(not(wwdg_v2))]
+const MAX_PRESCALER_POWER: u32 = 3;
+#[cfg(wwdg_v2)]
+const MAX_PRESCALER_POWER: u32 = 7;
+
+/// Duration of a count of the down-counter in us, for a prescaler of 2^`power`.
+fn tick_us(pclk: u32, power: u32) -> u64 {
+    4096 * (1 << power) as u64 * 1_000_000 / pclk as u64
+}
+
+/// Early wakeup interrupt handler.
+pub struct EarlyWakeupInterruptHandler<T: WwdgInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: WwdgInstance> interrupt::typelevel::Handler<T::Interrupt> for EarlyWakeupInterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The EWI bit can't be cleared, the interrupt is masked until the flag is awaited again.
+        T::Interrupt::disable();
+        EWI_WAKER.wake();
+    }
+}
+
+pub struct WindowWatchdog<'d, T: WwdgInstance> {
+    wdg: PhantomData<&'d mut T>,
+    counter: u32,
+    window: u32,
+    power: u32,
+    ewi: bool,
+}
+
+impl<'d, T: WwdgInstance> WindowWatchdog<'d, T> {
+    /// Creates a WWDG (Window Watchdog) instance with a given timeout value in microseconds.
+    ///
+    /// [Self] has to be started with [Self::unleash()]. Once started, the MCU is reset when the
+    /// timer expires, or when it's reloaded with [Self::pet()] sooner than `window_us` after
+    /// the last reload. A `window_us` of 0 disables the window.
+    pub fn new(_instance: impl Peripheral<P = T> + 'd, timeout_us: u32, window_us: u32) -> Self {
+        into_ref!(_instance);
+
+        T::enable();
+
+        // Find the lowest prescaler, which makes the longest timeout, 64 counts, longer or equal
+        // to the timeout.
+        let pclk = T::frequency().0;
+        let max_counts = MAX_COUNTER - MIN_COUNTER + 1;
+        let power = unwrap!((0..=MAX_PRESCALER_POWER).find(|power| {
+            let max_timeout_us = tick_us(pclk, *power) * max_counts as u64;
+            timeout_us as u64 <= max_timeout_us
+        }));
+        let tick = tick_us(pclk, power);
+
+        let counts = ((timeout_us as u64 / tick) as u32).clamp(1, max_counts);
+        let counter = MIN_COUNTER - 1 + counts;
+
+        // The counter must be below the window for the reload to be allowed.
+        let window = match window_us {
+            0 => MAX_COUNTER,
+            _ => {
+                let window_counts = ((window_us as u64 + tick - 1) / tick) as u32;
+                counter.saturating_sub(window_counts).max(MIN_COUNTER)
+            }
+        };
+
+        trace!(
+            "Window watchdog configured with {}us timeout, desired was {}us (WDGTB={}, T={})",
+            tick * counts as u64,
+            timeout_us,
+            power,
+            counter
+        );
+
+        Self {
+            wdg: PhantomData,
+            counter,
+            window,
+            power,
+            ewi: false,
+        }
+    }
+
+    /// Start the watchdog. Like the IWDG, it can't be stopped until the MCU is reset.
+    pub fn unleash(&mut self) {
+        self.write_cfr();
+        self.pet();
+    }
+
+    /// Reload the down-counter.
+    pub fn pet(&mut self) {
+        T::regs().cr().write(|w| {
+            w.set_wdga(Wdga::ENABLED);
+            w.set_t(self.counter as u8);
+        });
+    }
+
+    /// Whether [Self::pet()] would be allowed now, outside the window.
+    pub fn can_pet(&self) -> bool {
+        T::regs().cr().read().t() as u32 <= self.window
+    }
+
+    /// Wait for the early wakeup interrupt, one count before the reset.
+    ///
+    /// It's the last chance to save a state, or to pet the watchdog, for example after a task
+    /// watchdog reported a stalled task and the application decided to recover.
+    pub async fn wait_for_early_wakeup(
+        &mut self,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, EarlyWakeupInterruptHandler<T>>,
+    ) {
+        T::regs().sr().write(|w| w.set_ewif(false));
+        self.ewi = true;
+        self.write_cfr();
+
+        poll_fn(|cx| {
+            EWI_WAKER.register(cx.waker());
+            if T::regs().sr().read().ewif() {
+                T::regs().sr().write(|w| w.set_ewif(false));
+                Poll::Ready(())
+            } else {
+                T::Interrupt::unpend();
+                unsafe { T::Interrupt::enable() };
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    fn write_cfr(&self) {
+        T::regs().cfr().write(|w| {
+            w.set_wdgtb(Wdgtb::from_bits(self.power as u8));
+            w.set_w(self.window as u8);
+            w.set_ewi(self.ewi);
+        });
+    }
+}
+
+mod sealed {
+    pub trait WwdgInstance {
+        fn regs() -> crate::pac::wwdg::Wwdg;
+    }
+}
+
+pub trait WwdgInstance: sealed::WwdgInstance + RccPeripheral + 'static {
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, wwdg, $block:ident, GLOBAL, $irq:ident) => {
+        impl sealed::WwdgInstance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::wwdg::Wwdg {
+                crate::pac::$inst
+            }
+        }
+
+        impl WwdgInstance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
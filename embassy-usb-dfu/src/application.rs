@@ -0,0 +1,125 @@
+//! DFU runtime interface, used by the application to switch to DFU mode on request of the host.
+
+use core::marker::PhantomData;
+
+use embassy_boot::BlockingFirmwareUpdater;
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request as ControlRequest, RequestType};
+use embassy_usb::driver::Driver;
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Handler};
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::consts::{
+    functional_descriptor, DfuAttributes, Request, State, Status, APPN_SPEC_SUBCLASS_DFU, DESC_DFU_FUNCTIONAL,
+    DFU_PROTOCOL_RT, USB_CLASS_APPN_SPEC,
+};
+use crate::Reset;
+
+/// Handler for the DFU runtime interface.
+///
+/// On `DFU_DETACH`, the next USB bus reset marks the `embassy-boot` state as
+/// [`DfuDetach`](embassy_boot::State::DfuDetach) and resets the device, so the bootloader can
+/// take over and receive the new firmware.
+pub struct Control<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset> {
+    updater: BlockingFirmwareUpdater<DFU, STATE>,
+    aligned: &'d mut [u8],
+    attrs: DfuAttributes,
+    state: State,
+    if_num: Option<InterfaceNumber>,
+    _rst: PhantomData<RST>,
+}
+
+impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset> Control<'d, DFU, STATE, RST> {
+    /// Create a new DFU runtime handler.
+    ///
+    /// The `aligned` buffer must have a size of `STATE::WRITE_SIZE`, and follow the alignment rules
+    /// for the state partition.
+    pub fn new(updater: BlockingFirmwareUpdater<DFU, STATE>, aligned: &'d mut [u8], attrs: DfuAttributes) -> Self {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+
+        Self {
+            updater,
+            aligned,
+            attrs,
+            state: State::AppIdle,
+            if_num: None,
+            _rst: PhantomData,
+        }
+    }
+}
+
+impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset> Handler for Control<'d, DFU, STATE, RST> {
+    fn reset(&mut self) {
+        if self.state == State::AppDetach {
+            trace!("dfu: bus reset after DETACH, rebooting into DFU mode");
+            match self.updater.mark_dfu(self.aligned) {
+                Ok(()) => RST::sys_reset(),
+                Err(e) => {
+                    error!("dfu: failed to mark DFU mode: {:?}", e);
+                    self.state = State::AppIdle;
+                }
+            }
+        }
+    }
+
+    fn control_out(&mut self, req: ControlRequest, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, Some(req.index as u8))
+            != (RequestType::Class, Recipient::Interface, self.if_num.map(u8::from))
+        {
+            return None;
+        }
+
+        match Request::try_from(req.request) {
+            Ok(Request::Detach) => {
+                trace!("dfu: received DETACH, awaiting bus reset");
+                self.state = State::AppDetach;
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: ControlRequest, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, Some(req.index as u8))
+            != (RequestType::Class, Recipient::Interface, self.if_num.map(u8::from))
+        {
+            return None;
+        }
+
+        match Request::try_from(req.request) {
+            Ok(Request::GetStatus) => {
+                buf[0..6].copy_from_slice(&[Status::Ok as u8, 0x32, 0x00, 0x00, self.state as u8, 0x00]);
+                Some(InResponse::Accepted(&buf[0..6]))
+            }
+            Ok(Request::GetState) => {
+                buf[0] = self.state as u8;
+                Some(InResponse::Accepted(&buf[0..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Adds a DFU runtime interface to the device.
+///
+/// The host will wait up to `detach_timeout_ms` for the bus reset after a `DFU_DETACH` request.
+/// `transfer_size` is the maximum number of bytes per `DFU_DNLOAD` request accepted in DFU mode,
+/// and should match the value used by the bootloader.
+pub fn usb_dfu<'d, D: Driver<'d>, DFU: NorFlash, STATE: NorFlash, RST: Reset>(
+    builder: &mut Builder<'d, D>,
+    handler: &'d mut Control<'d, DFU, STATE, RST>,
+    detach_timeout_ms: u16,
+    transfer_size: u16,
+) {
+    let mut func = builder.function(USB_CLASS_APPN_SPEC, APPN_SPEC_SUBCLASS_DFU, DFU_PROTOCOL_RT);
+    let mut iface = func.interface();
+    handler.if_num = Some(iface.interface_number());
+    let mut alt = iface.alt_setting(USB_CLASS_APPN_SPEC, APPN_SPEC_SUBCLASS_DFU, DFU_PROTOCOL_RT, None);
+    alt.descriptor(
+        DESC_DFU_FUNCTIONAL,
+        &functional_descriptor(handler.attrs, detach_timeout_ms, transfer_size),
+    );
+
+    drop(func);
+    builder.handler(handler);
+}
@@ -0,0 +1,131 @@
+//! DFU 1.1 protocol constants and types.
+
+pub(crate) const USB_CLASS_APPN_SPEC: u8 = 0xFE;
+pub(crate) const APPN_SPEC_SUBCLASS_DFU: u8 = 0x01;
+#[allow(unused)]
+pub(crate) const DFU_PROTOCOL_DFU: u8 = 0x02;
+#[allow(unused)]
+pub(crate) const DFU_PROTOCOL_RT: u8 = 0x01;
+pub(crate) const DESC_DFU_FUNCTIONAL: u8 = 0x21;
+
+/// Attributes advertised in the DFU functional descriptor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DfuAttributes(u8);
+
+impl DfuAttributes {
+    /// The device will detach itself after a `DFU_DETACH` request, without waiting for a bus reset.
+    pub const WILL_DETACH: Self = Self(0b0000_1000);
+    /// The device keeps communicating over USB after the manifestation phase.
+    pub const MANIFESTATION_TOLERANT: Self = Self(0b0000_0100);
+    /// The device can upload firmware to the host.
+    pub const CAN_UPLOAD: Self = Self(0b0000_0010);
+    /// The device can download firmware from the host.
+    pub const CAN_DOWNLOAD: Self = Self(0b0000_0001);
+
+    /// No attributes.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Raw value of the `bmAttributes` field.
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns whether all attributes in `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for DfuAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+#[allow(unused)]
+pub(crate) enum State {
+    AppIdle = 0,
+    AppDetach = 1,
+    DfuIdle = 2,
+    DlSync = 3,
+    DlBusy = 4,
+    Download = 5,
+    ManifestSync = 6,
+    Manifest = 7,
+    ManifestWaitReset = 8,
+    UploadIdle = 9,
+    Error = 10,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+#[allow(unused)]
+pub(crate) enum Status {
+    Ok = 0x00,
+    ErrTarget = 0x01,
+    ErrFile = 0x02,
+    ErrWrite = 0x03,
+    ErrErase = 0x04,
+    ErrCheckErased = 0x05,
+    ErrProg = 0x06,
+    ErrVerify = 0x07,
+    ErrAddress = 0x08,
+    ErrNotDone = 0x09,
+    ErrFirmware = 0x0A,
+    ErrVendor = 0x0B,
+    ErrUsbr = 0x0C,
+    ErrPor = 0x0D,
+    ErrUnknown = 0x0E,
+    ErrStalledPkt = 0x0F,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum Request {
+    Detach = 0,
+    Dnload = 1,
+    Upload = 2,
+    GetStatus = 3,
+    ClrStatus = 4,
+    GetState = 5,
+    Abort = 6,
+}
+
+impl TryFrom<u8> for Request {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Request::Detach),
+            1 => Ok(Request::Dnload),
+            2 => Ok(Request::Upload),
+            3 => Ok(Request::GetStatus),
+            4 => Ok(Request::ClrStatus),
+            5 => Ok(Request::GetState),
+            6 => Ok(Request::Abort),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Builds the DFU functional descriptor, without the length and type header.
+pub(crate) fn functional_descriptor(attrs: DfuAttributes, detach_timeout_ms: u16, transfer_size: u16) -> [u8; 7] {
+    [
+        attrs.bits(),
+        detach_timeout_ms as u8,
+        (detach_timeout_ms >> 8) as u8, // wDetachTimeOut
+        transfer_size as u8,
+        (transfer_size >> 8) as u8, // wTransferSize
+        0x10,
+        0x01, // bcdDFUVersion (1.1)
+    ]
+}
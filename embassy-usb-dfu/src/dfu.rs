@@ -0,0 +1,208 @@
+//! DFU mode interface, used by the bootloader to receive new firmware from the host.
+
+use core::marker::PhantomData;
+
+use embassy_boot::{AlignedBuffer, BlockingFirmwareUpdater};
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request as ControlRequest, RequestType};
+use embassy_usb::driver::Driver;
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Handler};
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::consts::{
+    functional_descriptor, DfuAttributes, Request, State, Status, APPN_SPEC_SUBCLASS_DFU, DESC_DFU_FUNCTIONAL,
+    DFU_PROTOCOL_DFU, USB_CLASS_APPN_SPEC,
+};
+use crate::Reset;
+
+/// Handler for the DFU mode interface.
+///
+/// Firmware downloaded by the host is written into the DFU partition in blocks of `BLOCK_SIZE`
+/// bytes, which must be a multiple of the DFU partition erase size. Only the last block of a
+/// download may be shorter than `BLOCK_SIZE`. Once the download completes, the update is marked
+/// for swapping and the device is reset on the next bus reset.
+pub struct Control<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> {
+    updater: BlockingFirmwareUpdater<DFU, STATE>,
+    aligned: &'d mut [u8],
+    attrs: DfuAttributes,
+    state: State,
+    status: Status,
+    offset: usize,
+    buf: AlignedBuffer<BLOCK_SIZE>,
+    if_num: Option<InterfaceNumber>,
+    _rst: PhantomData<RST>,
+}
+
+impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Control<'d, DFU, STATE, RST, BLOCK_SIZE> {
+    /// Create a new DFU mode handler.
+    ///
+    /// The `aligned` buffer must have a size of `STATE::WRITE_SIZE`, and follow the alignment rules
+    /// for the state partition.
+    pub fn new(updater: BlockingFirmwareUpdater<DFU, STATE>, aligned: &'d mut [u8], attrs: DfuAttributes) -> Self {
+        assert_eq!(aligned.len(), STATE::WRITE_SIZE);
+        assert_eq!(BLOCK_SIZE % DFU::ERASE_SIZE, 0);
+
+        Self {
+            updater,
+            aligned,
+            attrs,
+            state: State::DfuIdle,
+            status: Status::Ok,
+            offset: 0,
+            buf: AlignedBuffer([0; BLOCK_SIZE]),
+            if_num: None,
+            _rst: PhantomData,
+        }
+    }
+
+    fn reset_state(&mut self) {
+        self.offset = 0;
+        self.state = State::DfuIdle;
+        self.status = Status::Ok;
+    }
+
+    fn fail(&mut self, status: Status) -> OutResponse {
+        self.state = State::Error;
+        self.status = status;
+        OutResponse::Rejected
+    }
+
+    fn download(&mut self, data: &[u8]) -> OutResponse {
+        if data.len() > BLOCK_SIZE {
+            return self.fail(Status::ErrStalledPkt);
+        }
+        // A short block ends the firmware, the next one would overlap its padding.
+        if self.offset % BLOCK_SIZE != 0 {
+            error!("dfu: block after a short block, at offset {}", self.offset);
+            return self.fail(Status::ErrAddress);
+        }
+
+        // Pad partial blocks with the erased value so the whole block can be written.
+        self.buf.0[..data.len()].copy_from_slice(data);
+        self.buf.0[data.len()..].fill(0xFF);
+
+        match self.updater.write_firmware(self.aligned, self.offset, &self.buf.0) {
+            Ok(()) => {
+                self.offset += data.len();
+                self.state = State::DlSync;
+                OutResponse::Accepted
+            }
+            Err(e) => {
+                error!("dfu: failed to write firmware at offset {}: {:?}", self.offset, e);
+                self.fail(Status::ErrWrite)
+            }
+        }
+    }
+}
+
+impl<'d, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize> Handler
+    for Control<'d, DFU, STATE, RST, BLOCK_SIZE>
+{
+    fn reset(&mut self) {
+        if matches!(self.state, State::Manifest | State::ManifestWaitReset) {
+            trace!("dfu: bus reset after manifestation, rebooting");
+            RST::sys_reset();
+        }
+    }
+
+    fn control_out(&mut self, req: ControlRequest, data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, Some(req.index as u8))
+            != (RequestType::Class, Recipient::Interface, self.if_num.map(u8::from))
+        {
+            return None;
+        }
+
+        match Request::try_from(req.request) {
+            Ok(Request::Abort) => {
+                self.reset_state();
+                Some(OutResponse::Accepted)
+            }
+            Ok(Request::Dnload) if self.attrs.contains(DfuAttributes::CAN_DOWNLOAD) => match self.state {
+                State::DfuIdle | State::Download if req.length > 0 => {
+                    if self.state == State::DfuIdle {
+                        self.offset = 0;
+                    }
+                    trace!("dfu: download block {} at offset {}", req.value, self.offset);
+                    Some(self.download(data))
+                }
+                State::Download if req.length == 0 => {
+                    trace!("dfu: download complete, {} bytes", self.offset);
+                    self.state = State::ManifestSync;
+                    Some(OutResponse::Accepted)
+                }
+                _ => Some(self.fail(Status::ErrStalledPkt)),
+            },
+            Ok(Request::ClrStatus) if self.state == State::Error => {
+                self.reset_state();
+                Some(OutResponse::Accepted)
+            }
+            Ok(Request::Detach) => Some(OutResponse::Accepted),
+            _ => Some(self.fail(Status::ErrStalledPkt)),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: ControlRequest, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, Some(req.index as u8))
+            != (RequestType::Class, Recipient::Interface, self.if_num.map(u8::from))
+        {
+            return None;
+        }
+
+        match Request::try_from(req.request) {
+            Ok(Request::GetStatus) => {
+                // Writes are done synchronously in `control_out`, so there is never a need to
+                // report busy states to the host.
+                match self.state {
+                    State::DlSync => self.state = State::Download,
+                    State::ManifestSync => match self.updater.mark_updated(self.aligned) {
+                        Ok(()) => self.state = State::Manifest,
+                        Err(e) => {
+                            error!("dfu: failed to mark firmware updated: {:?}", e);
+                            self.state = State::Error;
+                            self.status = Status::ErrVerify;
+                        }
+                    },
+                    _ => {}
+                }
+
+                buf[0..6].copy_from_slice(&[self.status as u8, 0x32, 0x00, 0x00, self.state as u8, 0x00]);
+                if self.state == State::Manifest && !self.attrs.contains(DfuAttributes::MANIFESTATION_TOLERANT) {
+                    self.state = State::ManifestWaitReset;
+                }
+                Some(InResponse::Accepted(&buf[0..6]))
+            }
+            Ok(Request::GetState) => {
+                buf[0] = self.state as u8;
+                Some(InResponse::Accepted(&buf[0..1]))
+            }
+            _ => {
+                self.state = State::Error;
+                self.status = Status::ErrStalledPkt;
+                Some(InResponse::Rejected)
+            }
+        }
+    }
+}
+
+/// Adds a DFU mode interface to the device.
+///
+/// The builder's control buffer must be at least `BLOCK_SIZE` bytes long, as every `DFU_DNLOAD`
+/// request carries up to one block of data.
+pub fn usb_dfu<'d, D: Driver<'d>, DFU: NorFlash, STATE: NorFlash, RST: Reset, const BLOCK_SIZE: usize>(
+    builder: &mut Builder<'d, D>,
+    handler: &'d mut Control<'d, DFU, STATE, RST, BLOCK_SIZE>,
+) {
+    assert!(builder.control_buf_len() >= BLOCK_SIZE);
+
+    let mut func = builder.function(USB_CLASS_APPN_SPEC, APPN_SPEC_SUBCLASS_DFU, DFU_PROTOCOL_DFU);
+    let mut iface = func.interface();
+    handler.if_num = Some(iface.interface_number());
+    let mut alt = iface.alt_setting(USB_CLASS_APPN_SPEC, APPN_SPEC_SUBCLASS_DFU, DFU_PROTOCOL_DFU, None);
+    alt.descriptor(
+        DESC_DFU_FUNCTIONAL,
+        &functional_descriptor(handler.attrs, 0, BLOCK_SIZE as u16),
+    );
+
+    drop(func);
+    builder.handler(handler);
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+// This mod MUST go first, so that the others see its macros.
+pub(crate) mod fmt;
+
+pub mod consts;
+pub use consts::DfuAttributes;
+
+#[cfg(feature = "application")]
+pub mod application;
+#[cfg(feature = "dfu")]
+pub mod dfu;
+
+/// Provides a platform-agnostic way to reset the device after DFU state changes.
+pub trait Reset {
+    /// Reset the device.
+    fn sys_reset() -> !;
+}
+
+/// Resets a Cortex-M core immediately through the system control block.
+#[cfg(feature = "cortex-m")]
+pub struct ResetImmediate;
+
+#[cfg(feature = "cortex-m")]
+impl Reset for ResetImmediate {
+    fn sys_reset() -> ! {
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+}
@@ -0,0 +1,134 @@
+//! USB host driver traits.
+//!
+//! These are the host-mode counterpart of the device traits in the crate root. They are implemented
+//! by HALs for peripherals that can act as a USB host, and used by `embassy-usb-host`.
+
+use crate::EndpointInfo;
+
+/// Speed of a device attached to the host port.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Speed {
+    /// Low speed, 1.5 Mbit/s.
+    Low,
+    /// Full speed, 12 Mbit/s.
+    Full,
+    /// High speed, 480 Mbit/s.
+    High,
+}
+
+/// Event on the host port.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeviceEvent {
+    /// A device was attached. It has not been reset yet.
+    Connected(Speed),
+    /// The device was detached. All pipes to it return [`PipeError::Disconnected`].
+    Disconnected,
+}
+
+/// Allocating a pipe failed, because all hardware channels are in use or the endpoint is not
+/// supported by the host controller.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PipeAllocError;
+
+/// Transfer error.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PipeError {
+    /// The device responded with STALL.
+    Stall,
+    /// The device sent more data than fits in the buffer.
+    BufferOverflow,
+    /// The transfer failed after retries, for example because of CRC or bit stuffing errors, or
+    /// because the device didn't respond.
+    Transaction,
+    /// The device was disconnected.
+    Disconnected,
+}
+
+/// Driver for a USB host controller.
+///
+/// A host driver handles a single root port. Pipes are released when they're dropped.
+pub trait Driver<'a> {
+    /// Type of the control pipes of this driver.
+    type ControlPipe: ControlPipe + 'a;
+    /// Type of the IN pipes of this driver.
+    type PipeIn: PipeIn + 'a;
+    /// Type of the OUT pipes of this driver.
+    type PipeOut: PipeOut + 'a;
+
+    /// Waits for a device to be attached or detached.
+    async fn wait_for_device_event(&mut self) -> DeviceEvent;
+
+    /// Resets the device on the port. Afterwards the device answers on address 0.
+    async fn bus_reset(&mut self);
+
+    /// Allocates a control pipe to endpoint 0 of a device.
+    fn alloc_control_pipe(
+        &mut self,
+        device_address: u8,
+        max_packet_size: u16,
+        speed: Speed,
+    ) -> Result<Self::ControlPipe, PipeAllocError>;
+
+    /// Allocates a pipe to an IN endpoint of a device.
+    ///
+    /// Interrupt pipes are polled with the interval in `endpoint`.
+    fn alloc_pipe_in(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeIn, PipeAllocError>;
+
+    /// Allocates a pipe to an OUT endpoint of a device.
+    fn alloc_pipe_out(
+        &mut self,
+        device_address: u8,
+        endpoint: &EndpointInfo,
+        speed: Speed,
+    ) -> Result<Self::PipeOut, PipeAllocError>;
+}
+
+/// Control pipe to endpoint 0 of a device.
+pub trait ControlPipe {
+    /// Changes the address the pipe talks to, after a SET_ADDRESS request.
+    fn set_device_address(&mut self, device_address: u8);
+
+    /// Changes the maximum packet size, once it's known from the device descriptor.
+    fn set_max_packet_size(&mut self, max_packet_size: u16);
+
+    /// Performs a control transfer with an IN data stage, or no data stage if `buf` is empty.
+    ///
+    /// Returns the number of bytes received.
+    async fn control_in(&mut self, setup: &[u8; 8], buf: &mut [u8]) -> Result<usize, PipeError>;
+
+    /// Performs a control transfer with an OUT data stage, or no data stage if `data` is empty.
+    async fn control_out(&mut self, setup: &[u8; 8], data: &[u8]) -> Result<(), PipeError>;
+}
+
+/// Pipe to an IN endpoint.
+pub trait PipeIn {
+    /// Gets the endpoint this pipe talks to.
+    fn info(&self) -> &EndpointInfo;
+
+    /// Performs an IN transfer.
+    ///
+    /// The transfer completes when a short packet is received or `buf` is full, and returns the
+    /// number of bytes received. NAKs are retried until the device sends data.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PipeError>;
+}
+
+/// Pipe to an OUT endpoint.
+pub trait PipeOut {
+    /// Gets the endpoint this pipe talks to.
+    fn info(&self) -> &EndpointInfo;
+
+    /// Performs an OUT transfer of `buf`, split into packets of the endpoint's max packet size.
+    ///
+    /// Like on the device side, a transfer that is a multiple of the max packet size is not
+    /// terminated with a zero-length packet.
+    async fn write(&mut self, buf: &[u8]) -> Result<(), PipeError>;
+}
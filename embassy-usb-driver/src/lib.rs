@@ -3,6 +3,8 @@
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 
+pub mod host;
+
 /// Direction of USB traffic. Note that in the USB standard the direction is always indicated from
 /// the perspective of the host, which is backward for devices, but the standard directions are used
 /// for consistency.
@@ -27,7 +29,11 @@ pub enum EndpointType {
     /// Control endpoint. Used for device management. Only the host can initiate requests. Usually
     /// used only endpoint 0.
     Control = 0b00,
-    /// Isochronous endpoint. Used for time-critical unreliable data. Not implemented yet.
+    /// Isochronous endpoint. Used for time-critical unreliable data.
+    ///
+    /// Isochronous endpoints transfer at most one packet per (micro)frame. There is no handshake
+    /// and no retry: a packet that is not read or written in time is dropped, and the host keeps
+    /// polling with the schedule implied by the endpoint interval.
     Isochronous = 0b01,
     /// Bulk endpoint. Used for large amounts of best-effort reliable data.
     Bulk = 0b10,
@@ -216,6 +222,55 @@ pub trait Bus {
     /// * [`Unsupported`](crate::Unsupported) - This UsbBus implementation doesn't support
     ///   remote wakeup or it has not been enabled at creation time.
     async fn remote_wakeup(&mut self) -> Result<(), Unsupported>;
+
+    /// Put the port in one of the USB 2.0 compliance test modes.
+    ///
+    /// This is called after the status stage of the SET_FEATURE(TEST_MODE) request completes.
+    /// The device must only leave the test mode when it is power cycled.
+    ///
+    /// The default implementation just returns `Unsupported`.
+    ///
+    /// # Errors
+    ///
+    /// * [`Unsupported`](crate::Unsupported) - This UsbBus implementation doesn't support
+    ///   test modes, for example because it is not running at high speed.
+    fn set_test_mode(&mut self, mode: TestMode) -> Result<(), Unsupported> {
+        let _ = mode;
+        Err(Unsupported)
+    }
+}
+
+/// USB 2.0 compliance test mode, selected by the host with SET_FEATURE(TEST_MODE).
+///
+/// Test modes are only defined for high-speed devices, see section 7.1.20 of the USB 2.0
+/// specification.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TestMode {
+    /// Drive a constant J state.
+    J = 1,
+    /// Drive a constant K state.
+    K = 2,
+    /// Respond to IN tokens with NAK, and stay in receive mode.
+    Se0Nak = 3,
+    /// Repeatedly send the test packet.
+    Packet = 4,
+    /// Enable the downstream facing port of a hub.
+    ForceEnable = 5,
+}
+
+impl TestMode {
+    /// Get the test mode for a test selector, from the high byte of `wIndex`.
+    pub fn from_selector(selector: u8) -> Option<Self> {
+        match selector {
+            1 => Some(Self::J),
+            2 => Some(Self::K),
+            3 => Some(Self::Se0Nak),
+            4 => Some(Self::Packet),
+            5 => Some(Self::ForceEnable),
+            _ => None,
+        }
+    }
 }
 
 /// Endpoint trait, common for OUT and IN.
@@ -225,6 +280,16 @@ pub trait Endpoint {
 
     /// Wait for the endpoint to be enabled.
     async fn wait_enabled(&mut self);
+
+    /// Set or clear the STALL condition of the endpoint.
+    ///
+    /// Classes use this to report an error to the host on a data endpoint. The host clears the
+    /// condition with CLEAR_FEATURE(ENDPOINT_HALT), which the stack forwards to
+    /// [`Bus::endpoint_set_stalled`].
+    fn set_stalled(&mut self, stalled: bool);
+
+    /// Get whether the STALL condition is set for the endpoint.
+    fn is_stalled(&mut self) -> bool;
 }
 
 /// OUT Endpoint trait.
@@ -233,6 +298,9 @@ pub trait EndpointOut: Endpoint {
     /// the packet.
     ///
     /// This should also clear any NAK flags and prepare the endpoint to receive the next packet.
+    ///
+    /// For isochronous endpoints this returns the packet received in the last frame. Packets that
+    /// arrive while nobody is reading are dropped.
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError>;
 }
 
@@ -346,6 +414,10 @@ pub trait ControlPipe {
 /// IN Endpoint trait.
 pub trait EndpointIn: Endpoint {
     /// Write a single packet of data to the endpoint.
+    ///
+    /// For isochronous endpoints the packet is sent in the next frame in which the host polls the
+    /// endpoint. The future completes once the packet has been handed to the host, so calling
+    /// `write` in a loop produces one packet per frame.
     async fn write(&mut self, buf: &[u8]) -> Result<(), EndpointError>;
 }
 
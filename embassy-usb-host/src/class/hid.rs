@@ -0,0 +1,271 @@
+//! HID host class driver, for keyboards, mice and other human interface devices.
+//!
+//! Devices exposing the boot interface (keyboards and mice) can be switched to the boot protocol,
+//! which has a fixed report format that can be parsed with [`BootKeyboardReport`] and
+//! [`BootMouseReport`] without parsing the report descriptor.
+
+use embassy_usb_driver::EndpointType;
+
+use crate::control::{request, Recipient, RequestType, SetupPacket};
+use crate::descriptor::{EndpointDescriptor, InterfaceDescriptor};
+use crate::driver::{Driver, PipeError, PipeIn};
+use crate::{Device, HostError, UsbHost};
+
+/// Interface class code for HID.
+pub const USB_CLASS_HID: u8 = 0x03;
+
+const USB_SUBCLASS_BOOT: u8 = 0x01;
+
+const HID_DESC_DESCTYPE_HID: u8 = 0x21;
+const HID_DESC_DESCTYPE_HID_REPORT: u8 = 0x22;
+
+const HID_REQ_GET_REPORT: u8 = 0x01;
+const HID_REQ_SET_REPORT: u8 = 0x09;
+const HID_REQ_SET_IDLE: u8 = 0x0a;
+const HID_REQ_SET_PROTOCOL: u8 = 0x0b;
+
+/// Report protocol used by the device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// Fixed report format defined by the HID specification.
+    Boot = 0,
+    /// Report format described by the device's report descriptor.
+    Report = 1,
+}
+
+/// Boot interface of a HID device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootInterface {
+    /// The device doesn't support the boot protocol.
+    None,
+    /// Boot keyboard.
+    Keyboard,
+    /// Boot mouse.
+    Mouse,
+}
+
+/// HID report type.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportType {
+    /// Input report.
+    Input = 1,
+    /// Output report.
+    Output = 2,
+    /// Feature report.
+    Feature = 3,
+}
+
+/// HID host class driver for one HID interface.
+pub struct HidHost<'d, D: Driver<'d>> {
+    interface: u8,
+    boot_interface: BootInterface,
+    report_descriptor_len: u16,
+    ep_in: EndpointDescriptor,
+    pipe_in: D::PipeIn,
+}
+
+impl<'d, D: Driver<'d>> HidHost<'d, D> {
+    /// Finds the first HID interface of a device.
+    pub fn find_interface<'b>(device: &Device<'d, 'b, D>) -> Option<InterfaceDescriptor<'b>> {
+        device.config_descriptor().find_interface(USB_CLASS_HID, None, None)
+    }
+
+    /// Sets up a HID interface.
+    ///
+    /// Selects `protocol` if the device has a boot interface, and disables idle reports so that
+    /// reports are only sent when something changes. Fails with [`HostError::NotFound`] if the boot
+    /// protocol is requested but the interface doesn't support it.
+    pub async fn new(
+        host: &mut UsbHost<'d, D>,
+        device: &mut Device<'d, '_, D>,
+        interface: &InterfaceDescriptor<'_>,
+        protocol: Protocol,
+    ) -> Result<Self, HostError> {
+        let ep_in = interface
+            .endpoints()
+            .find(|ep| ep.address.is_in() && ep.ep_type() == EndpointType::Interrupt)
+            .ok_or(HostError::NotFound)?;
+
+        let report_descriptor_len = interface
+            .descriptors()
+            .find(|(ty, _)| *ty == HID_DESC_DESCTYPE_HID)
+            .and_then(|(_, desc)| {
+                // The first class descriptor listed in the HID descriptor is the report descriptor.
+                (desc.len() >= 9 && desc[6] == HID_DESC_DESCTYPE_HID_REPORT)
+                    .then(|| u16::from_le_bytes([desc[7], desc[8]]))
+            })
+            .ok_or(HostError::InvalidDescriptor)?;
+
+        let boot_interface = match (interface.interface_sub_class, interface.interface_protocol) {
+            (USB_SUBCLASS_BOOT, 1) => BootInterface::Keyboard,
+            (USB_SUBCLASS_BOOT, 2) => BootInterface::Mouse,
+            _ => BootInterface::None,
+        };
+        if boot_interface == BootInterface::None && protocol == Protocol::Boot {
+            return Err(HostError::NotFound);
+        }
+
+        let pipe_in = host.alloc_pipe_in(device, &ep_in)?;
+
+        let this = Self {
+            interface: interface.interface_number,
+            boot_interface,
+            report_descriptor_len,
+            ep_in,
+            pipe_in,
+        };
+
+        if boot_interface != BootInterface::None {
+            this.set_protocol(device, protocol).await?;
+        }
+
+        // SET_IDLE is optional for devices other than boot keyboards, and some stall it.
+        match this.set_idle(device, 0, 0).await {
+            Ok(()) | Err(HostError::Pipe(PipeError::Stall)) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(this)
+    }
+
+    /// Gets the interface number.
+    pub fn interface_number(&self) -> u8 {
+        self.interface
+    }
+
+    /// Gets the boot interface of the device.
+    pub fn boot_interface(&self) -> BootInterface {
+        self.boot_interface
+    }
+
+    /// Gets the length of the report descriptor.
+    pub fn report_descriptor_len(&self) -> usize {
+        self.report_descriptor_len as usize
+    }
+
+    /// Gets the interrupt IN endpoint reports are received on.
+    pub fn endpoint(&self) -> &EndpointDescriptor {
+        &self.ep_in
+    }
+
+    /// Waits for the next input report.
+    pub async fn read_report(&mut self, buf: &mut [u8]) -> Result<usize, HostError> {
+        Ok(self.pipe_in.read(buf).await?)
+    }
+
+    /// Reads the report descriptor.
+    pub async fn get_report_descriptor(
+        &self,
+        device: &mut Device<'d, '_, D>,
+        buf: &mut [u8],
+    ) -> Result<usize, HostError> {
+        let len = buf.len().min(self.report_descriptor_len as usize);
+        let setup = SetupPacket {
+            request_type: RequestType::Standard,
+            recipient: Recipient::Interface,
+            request: request::GET_DESCRIPTOR,
+            value: (HID_DESC_DESCTYPE_HID_REPORT as u16) << 8,
+            index: self.interface as u16,
+        };
+        device.control_in(&setup, &mut buf[..len]).await
+    }
+
+    /// Reads a report through the control pipe.
+    pub async fn get_report(
+        &self,
+        device: &mut Device<'d, '_, D>,
+        report_type: ReportType,
+        report_id: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, HostError> {
+        let value = (report_type as u16) << 8 | report_id as u16;
+        let setup = SetupPacket::class_interface(HID_REQ_GET_REPORT, value, self.interface);
+        device.control_in(&setup, buf).await
+    }
+
+    /// Sends a report through the control pipe, for example to set the LEDs of a keyboard.
+    pub async fn set_report(
+        &self,
+        device: &mut Device<'d, '_, D>,
+        report_type: ReportType,
+        report_id: u8,
+        data: &[u8],
+    ) -> Result<(), HostError> {
+        let value = (report_type as u16) << 8 | report_id as u16;
+        let setup = SetupPacket::class_interface(HID_REQ_SET_REPORT, value, self.interface);
+        device.control_out(&setup, data).await
+    }
+
+    /// Sets how often the device repeats an unchanged report, in units of 4 ms. 0 only sends reports
+    /// when the data changes.
+    pub async fn set_idle(&self, device: &mut Device<'d, '_, D>, duration: u8, report_id: u8) -> Result<(), HostError> {
+        let value = (duration as u16) << 8 | report_id as u16;
+        let setup = SetupPacket::class_interface(HID_REQ_SET_IDLE, value, self.interface);
+        device.control_out(&setup, &[]).await
+    }
+
+    /// Selects the boot or report protocol.
+    pub async fn set_protocol(&self, device: &mut Device<'d, '_, D>, protocol: Protocol) -> Result<(), HostError> {
+        let setup = SetupPacket::class_interface(HID_REQ_SET_PROTOCOL, protocol as u16, self.interface);
+        device.control_out(&setup, &[]).await
+    }
+}
+
+/// Boot protocol keyboard input report.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootKeyboardReport {
+    /// Modifier keys, bit 0 is left control and bit 7 is right GUI.
+    pub modifiers: u8,
+    /// Keycodes of up to 6 pressed keys.
+    pub keycodes: [u8; 6],
+}
+
+impl BootKeyboardReport {
+    /// Parses a boot keyboard report.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let mut keycodes = [0; 6];
+        keycodes.copy_from_slice(&data[2..8]);
+        Some(Self {
+            modifiers: data[0],
+            keycodes,
+        })
+    }
+
+    /// Returns `true` if too many keys are pressed to report them (phantom state).
+    pub fn is_rollover(&self) -> bool {
+        self.keycodes.iter().all(|&k| k == 0x01)
+    }
+}
+
+/// Boot protocol mouse input report.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootMouseReport {
+    /// Buttons, bit 0 is the left button.
+    pub buttons: u8,
+    /// Relative X movement.
+    pub x: i8,
+    /// Relative Y movement.
+    pub y: i8,
+}
+
+impl BootMouseReport {
+    /// Parses a boot mouse report.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 3 {
+            return None;
+        }
+        Some(Self {
+            buttons: data[0],
+            x: data[1] as i8,
+            y: data[2] as i8,
+        })
+    }
+}
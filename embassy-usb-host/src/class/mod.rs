@@ -0,0 +1,3 @@
+//! Host-side drivers for well-known USB classes.
+pub mod hid;
+pub mod msc;
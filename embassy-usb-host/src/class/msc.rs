@@ -0,0 +1,300 @@
+//! Mass storage host class driver, for USB flash drives and card readers.
+//!
+//! Implements the Bulk-Only Transport with the SCSI transparent command set, which is what
+//! practically all mass storage devices use. Blocks are addressed with 32-bit LBAs, so devices up
+//! to 2 TiB with 512 byte blocks are supported.
+
+use embassy_time::{Duration, Timer};
+use embassy_usb_driver::EndpointType;
+
+use crate::control::SetupPacket;
+use crate::descriptor::{EndpointDescriptor, InterfaceDescriptor};
+use crate::driver::{Driver, PipeError, PipeIn, PipeOut};
+use crate::{Device, HostError, UsbHost};
+
+/// Interface class code for mass storage.
+pub const USB_CLASS_MSC: u8 = 0x08;
+/// SCSI transparent command set sub-class.
+pub const MSC_SUBCLASS_SCSI: u8 = 0x06;
+/// Bulk-Only Transport protocol.
+pub const MSC_PROTOCOL_BBB: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xfe;
+const REQ_BULK_ONLY_RESET: u8 = 0xff;
+
+const CBW_SIGNATURE: u32 = 0x43425355;
+const CSW_SIGNATURE: u32 = 0x53425355;
+const CBW_SIZE: usize = 31;
+const CSW_SIZE: usize = 13;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2a;
+
+/// How many times TEST UNIT READY is retried while the medium is spinning up.
+const UNIT_READY_RETRIES: usize = 20;
+const UNIT_READY_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Mass storage error.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MscError {
+    /// A USB transfer failed. After a [`PipeError::Stall`], call [`MscHost::reset_recovery`].
+    Host(HostError),
+    /// The device reported that the command failed. [`MscHost::request_sense`] returns the reason.
+    CommandFailed,
+    /// The device reported a phase error. Call [`MscHost::reset_recovery`].
+    PhaseError,
+    /// The device sent an invalid command status wrapper.
+    InvalidStatus,
+    /// The buffer is not a multiple of the block size, or the blocks are past the end of the device.
+    InvalidBlocks,
+    /// The device never became ready.
+    NotReady,
+}
+
+impl From<HostError> for MscError {
+    fn from(e: HostError) -> Self {
+        Self::Host(e)
+    }
+}
+
+impl From<PipeError> for MscError {
+    fn from(e: PipeError) -> Self {
+        Self::Host(HostError::Pipe(e))
+    }
+}
+
+/// Sense data returned by REQUEST SENSE.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sense {
+    /// Sense key.
+    pub key: u8,
+    /// Additional sense code.
+    pub asc: u8,
+    /// Additional sense code qualifier.
+    pub ascq: u8,
+}
+
+enum Data<'a> {
+    None,
+    In(&'a mut [u8]),
+    Out(&'a [u8]),
+}
+
+/// Mass storage host class driver for one logical unit.
+pub struct MscHost<'d, D: Driver<'d>> {
+    interface: u8,
+    ep_in: EndpointDescriptor,
+    ep_out: EndpointDescriptor,
+    pipe_in: D::PipeIn,
+    pipe_out: D::PipeOut,
+    lun: u8,
+    tag: u32,
+    block_size: u32,
+    block_count: u32,
+}
+
+impl<'d, D: Driver<'d>> MscHost<'d, D> {
+    /// Finds the first Bulk-Only SCSI mass storage interface of a device.
+    pub fn find_interface<'b>(device: &Device<'d, 'b, D>) -> Option<InterfaceDescriptor<'b>> {
+        device
+            .config_descriptor()
+            .find_interface(USB_CLASS_MSC, Some(MSC_SUBCLASS_SCSI), Some(MSC_PROTOCOL_BBB))
+    }
+
+    /// Sets up a mass storage interface, talking to logical unit `lun`.
+    ///
+    /// Call [`init`](Self::init) before reading or writing blocks.
+    pub fn new(
+        host: &mut UsbHost<'d, D>,
+        device: &Device<'d, '_, D>,
+        interface: &InterfaceDescriptor<'_>,
+        lun: u8,
+    ) -> Result<Self, HostError> {
+        let ep_in = interface
+            .endpoints()
+            .find(|ep| ep.address.is_in() && ep.ep_type() == EndpointType::Bulk)
+            .ok_or(HostError::NotFound)?;
+        let ep_out = interface
+            .endpoints()
+            .find(|ep| ep.address.is_out() && ep.ep_type() == EndpointType::Bulk)
+            .ok_or(HostError::NotFound)?;
+
+        Ok(Self {
+            interface: interface.interface_number,
+            pipe_in: host.alloc_pipe_in(device, &ep_in)?,
+            pipe_out: host.alloc_pipe_out(device, &ep_out)?,
+            ep_in,
+            ep_out,
+            lun,
+            tag: 0,
+            block_size: 0,
+            block_count: 0,
+        })
+    }
+
+    /// Gets the highest logical unit number of the device.
+    pub async fn max_lun(&self, device: &mut Device<'d, '_, D>) -> Result<u8, HostError> {
+        let mut buf = [0; 1];
+        let setup = SetupPacket::class_interface(REQ_GET_MAX_LUN, 0, self.interface);
+        match device.control_in(&setup, &mut buf).await {
+            Ok(1) => Ok(buf[0]),
+            // Devices with a single LUN may stall the request.
+            Ok(_) | Err(HostError::Pipe(PipeError::Stall)) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Waits for the medium to become ready and reads its capacity.
+    pub async fn init(&mut self) -> Result<(), MscError> {
+        let mut ready = false;
+        for _ in 0..UNIT_READY_RETRIES {
+            match self.test_unit_ready().await {
+                Ok(()) => {
+                    ready = true;
+                    break;
+                }
+                Err(MscError::CommandFailed) => {
+                    // Read the sense data to clear the unit attention condition.
+                    let sense = self.request_sense().await?;
+                    debug!("unit not ready: {:?}", sense);
+                    Timer::after(UNIT_READY_RETRY_DELAY).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if !ready {
+            return Err(MscError::NotReady);
+        }
+
+        let mut buf = [0; 8];
+        let mut cb = [0; 10];
+        cb[0] = SCSI_READ_CAPACITY_10;
+        self.command(&cb, Data::In(&mut buf)).await?;
+        let last_lba = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        self.block_size = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        self.block_count = last_lba.wrapping_add(1);
+        debug!("capacity: {} blocks of {} bytes", self.block_count, self.block_size);
+
+        Ok(())
+    }
+
+    /// Gets the block size in bytes. Only valid after [`init`](Self::init).
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Gets the number of blocks. Only valid after [`init`](Self::init).
+    pub fn block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    /// Checks whether the medium is ready.
+    pub async fn test_unit_ready(&mut self) -> Result<(), MscError> {
+        let cb = [SCSI_TEST_UNIT_READY, 0, 0, 0, 0, 0];
+        self.command(&cb, Data::None).await
+    }
+
+    /// Reads the standard INQUIRY data, with the vendor and product identification.
+    pub async fn inquiry(&mut self, buf: &mut [u8; 36]) -> Result<(), MscError> {
+        let cb = [SCSI_INQUIRY, 0, 0, 0, buf.len() as u8, 0];
+        self.command(&cb, Data::In(buf)).await
+    }
+
+    /// Gets the reason the last command failed.
+    pub async fn request_sense(&mut self) -> Result<Sense, MscError> {
+        let mut buf = [0; 18];
+        let cb = [SCSI_REQUEST_SENSE, 0, 0, 0, buf.len() as u8, 0];
+        self.command(&cb, Data::In(&mut buf)).await?;
+        Ok(Sense {
+            key: buf[2] & 0x0f,
+            asc: buf[12],
+            ascq: buf[13],
+        })
+    }
+
+    /// Reads blocks starting at `lba`. The length of `buf` must be a multiple of the block size.
+    pub async fn read_blocks(&mut self, lba: u32, buf: &mut [u8]) -> Result<(), MscError> {
+        let cb = self.rw_command(SCSI_READ_10, lba, buf.len())?;
+        self.command(&cb, Data::In(buf)).await
+    }
+
+    /// Writes blocks starting at `lba`. The length of `buf` must be a multiple of the block size.
+    pub async fn write_blocks(&mut self, lba: u32, buf: &[u8]) -> Result<(), MscError> {
+        let cb = self.rw_command(SCSI_WRITE_10, lba, buf.len())?;
+        self.command(&cb, Data::Out(buf)).await
+    }
+
+    /// Performs the Bulk-Only reset recovery, after a stall or phase error.
+    pub async fn reset_recovery(&mut self, device: &mut Device<'d, '_, D>) -> Result<(), HostError> {
+        let setup = SetupPacket::class_interface(REQ_BULK_ONLY_RESET, 0, self.interface);
+        device.control_out(&setup, &[]).await?;
+        device.clear_halt(&self.ep_in).await?;
+        device.clear_halt(&self.ep_out).await
+    }
+
+    fn rw_command(&self, opcode: u8, lba: u32, len: usize) -> Result<[u8; 10], MscError> {
+        if self.block_size == 0 || len % self.block_size as usize != 0 {
+            return Err(MscError::InvalidBlocks);
+        }
+        let blocks = len / self.block_size as usize;
+        if blocks > u16::MAX as usize || lba as u64 + blocks as u64 > self.block_count as u64 {
+            return Err(MscError::InvalidBlocks);
+        }
+
+        let mut cb = [0; 10];
+        cb[0] = opcode;
+        cb[2..6].copy_from_slice(&lba.to_be_bytes());
+        cb[7..9].copy_from_slice(&(blocks as u16).to_be_bytes());
+        Ok(cb)
+    }
+
+    async fn command(&mut self, cb: &[u8], data: Data<'_>) -> Result<(), MscError> {
+        self.tag = self.tag.wrapping_add(1);
+
+        let (len, flags) = match &data {
+            Data::None => (0, 0x00),
+            Data::In(buf) => (buf.len(), 0x80),
+            Data::Out(buf) => (buf.len(), 0x00),
+        };
+
+        let mut cbw = [0; CBW_SIZE];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+        cbw[12] = flags;
+        cbw[13] = self.lun;
+        cbw[14] = cb.len() as u8;
+        cbw[15..15 + cb.len()].copy_from_slice(cb);
+        self.pipe_out.write(&cbw).await?;
+
+        match data {
+            Data::None => {}
+            Data::In(buf) => {
+                self.pipe_in.read(buf).await?;
+            }
+            Data::Out(buf) => self.pipe_out.write(buf).await?,
+        }
+
+        let mut csw = [0; CSW_SIZE];
+        let n = self.pipe_in.read(&mut csw).await?;
+        if n != CSW_SIZE
+            || u32::from_le_bytes([csw[0], csw[1], csw[2], csw[3]]) != CSW_SIGNATURE
+            || u32::from_le_bytes([csw[4], csw[5], csw[6], csw[7]]) != self.tag
+        {
+            return Err(MscError::InvalidStatus);
+        }
+
+        match csw[12] {
+            0 => Ok(()),
+            1 => Err(MscError::CommandFailed),
+            2 => Err(MscError::PhaseError),
+            _ => Err(MscError::InvalidStatus),
+        }
+    }
+}
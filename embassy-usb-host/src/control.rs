@@ -0,0 +1,115 @@
+//! USB control requests.
+
+/// Standard request codes.
+#[allow(missing_docs)]
+pub mod request {
+    pub const GET_STATUS: u8 = 0;
+    pub const CLEAR_FEATURE: u8 = 1;
+    pub const SET_FEATURE: u8 = 3;
+    pub const SET_ADDRESS: u8 = 5;
+    pub const GET_DESCRIPTOR: u8 = 6;
+    pub const SET_DESCRIPTOR: u8 = 7;
+    pub const GET_CONFIGURATION: u8 = 8;
+    pub const SET_CONFIGURATION: u8 = 9;
+    pub const GET_INTERFACE: u8 = 10;
+    pub const SET_INTERFACE: u8 = 11;
+}
+
+/// Feature selector for clearing the halt condition of an endpoint.
+pub const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+/// Type of a control request, bits 5..6 of `bmRequestType`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RequestType {
+    /// Request is a USB standard request.
+    Standard = 0,
+    /// Request is intended for a USB class.
+    Class = 1,
+    /// Request is vendor-specific.
+    Vendor = 2,
+}
+
+/// Recipient of a control request, bits 0..4 of `bmRequestType`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Recipient {
+    /// Request is intended for the entire device.
+    Device = 0,
+    /// Request is intended for an interface.
+    Interface = 1,
+    /// Request is intended for an endpoint.
+    Endpoint = 2,
+    /// Recipient is unspecified.
+    Other = 3,
+}
+
+/// A control request SETUP packet.
+///
+/// The direction is implied by the transfer it's used with, and is filled in by
+/// [`Device::control_in`](crate::Device::control_in) and
+/// [`Device::control_out`](crate::Device::control_out).
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SetupPacket {
+    /// Type of the request.
+    pub request_type: RequestType,
+    /// Recipient of the request.
+    pub recipient: Recipient,
+    /// Request code.
+    pub request: u8,
+    /// Request value.
+    pub value: u16,
+    /// Request index.
+    pub index: u16,
+}
+
+impl SetupPacket {
+    /// Creates a standard request to the device.
+    pub const fn standard(request: u8, value: u16, index: u16) -> Self {
+        Self {
+            request_type: RequestType::Standard,
+            recipient: Recipient::Device,
+            request,
+            value,
+            index,
+        }
+    }
+
+    /// Creates a class request to an interface.
+    pub const fn class_interface(request: u8, value: u16, interface: u8) -> Self {
+        Self {
+            request_type: RequestType::Class,
+            recipient: Recipient::Interface,
+            request,
+            value,
+            index: interface as u16,
+        }
+    }
+
+    /// Creates a GET_DESCRIPTOR request.
+    pub const fn get_descriptor(descriptor_type: u8, index: u8, language_id: u16) -> Self {
+        Self::standard(
+            request::GET_DESCRIPTOR,
+            (descriptor_type as u16) << 8 | index as u16,
+            language_id,
+        )
+    }
+
+    /// Serializes the packet, with the given direction bit and data stage length.
+    pub fn to_bytes(&self, device_to_host: bool, length: u16) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = length.to_le_bytes();
+        [
+            (device_to_host as u8) << 7 | (self.request_type as u8) << 5 | self.recipient as u8,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+}
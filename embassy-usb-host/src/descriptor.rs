@@ -0,0 +1,452 @@
+//! Parsing of standard USB descriptors.
+
+use embassy_usb_driver::host::Speed;
+use embassy_usb_driver::{EndpointAddress, EndpointInfo, EndpointType};
+
+/// Standard descriptor types
+#[allow(missing_docs)]
+pub mod descriptor_type {
+    pub const DEVICE: u8 = 1;
+    pub const CONFIGURATION: u8 = 2;
+    pub const STRING: u8 = 3;
+    pub const INTERFACE: u8 = 4;
+    pub const ENDPOINT: u8 = 5;
+    pub const IAD: u8 = 11;
+    pub const BOS: u8 = 15;
+}
+
+/// Iterator over the descriptors in a descriptor buffer, for example a configuration descriptor
+/// with all its interface, endpoint and class-specific descriptors.
+///
+/// Yields `(descriptor_type, descriptor)`, where `descriptor` includes the length and type bytes.
+/// Iteration stops at the first malformed descriptor.
+#[derive(Clone, Debug)]
+pub struct DescriptorIter<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DescriptorIter<'a> {
+    /// Creates an iterator over the descriptors in `buf`.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for DescriptorIter<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < 2 {
+            return None;
+        }
+        let len = self.buf[0] as usize;
+        if len < 2 || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+        let (desc, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some((desc[1], desc))
+    }
+}
+
+/// Device descriptor.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceDescriptor {
+    /// USB specification release number, in BCD.
+    pub usb_release: u16,
+    /// Device class.
+    pub device_class: u8,
+    /// Device sub-class.
+    pub device_sub_class: u8,
+    /// Device protocol.
+    pub device_protocol: u8,
+    /// Maximum packet size of endpoint 0.
+    pub max_packet_size_0: u8,
+    /// Vendor ID.
+    pub vendor_id: u16,
+    /// Product ID.
+    pub product_id: u16,
+    /// Device release number, in BCD.
+    pub device_release: u16,
+    /// Index of the manufacturer string.
+    pub manufacturer: u8,
+    /// Index of the product string.
+    pub product: u8,
+    /// Index of the serial number string.
+    pub serial_number: u8,
+    /// Number of configurations.
+    pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// Length of a device descriptor.
+    pub const SIZE: usize = 18;
+
+    /// Parses a device descriptor.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE || buf[1] != descriptor_type::DEVICE {
+            return None;
+        }
+        Some(Self {
+            usb_release: u16::from_le_bytes([buf[2], buf[3]]),
+            device_class: buf[4],
+            device_sub_class: buf[5],
+            device_protocol: buf[6],
+            max_packet_size_0: buf[7],
+            vendor_id: u16::from_le_bytes([buf[8], buf[9]]),
+            product_id: u16::from_le_bytes([buf[10], buf[11]]),
+            device_release: u16::from_le_bytes([buf[12], buf[13]]),
+            manufacturer: buf[14],
+            product: buf[15],
+            serial_number: buf[16],
+            num_configurations: buf[17],
+        })
+    }
+}
+
+/// Configuration descriptor, with all the descriptors that follow it.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigurationDescriptor<'a> {
+    /// Number of interfaces.
+    pub num_interfaces: u8,
+    /// Value to pass to SET_CONFIGURATION to select this configuration.
+    pub configuration_value: u8,
+    /// Configuration attributes, bit 6 is self-powered and bit 5 is remote wakeup.
+    pub attributes: u8,
+    /// Maximum power consumption, in units of 2 mA.
+    pub max_power: u8,
+    buf: &'a [u8],
+}
+
+impl<'a> ConfigurationDescriptor<'a> {
+    /// Length of the configuration descriptor itself, without the descriptors that follow it.
+    pub const SIZE: usize = 9;
+
+    /// Parses a configuration descriptor.
+    ///
+    /// `buf` must contain the whole configuration, `wTotalLength` bytes.
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE || buf[1] != descriptor_type::CONFIGURATION {
+            return None;
+        }
+        let total_len = u16::from_le_bytes([buf[2], buf[3]]) as usize;
+        if total_len > buf.len() {
+            return None;
+        }
+        Some(Self {
+            num_interfaces: buf[4],
+            configuration_value: buf[5],
+            attributes: buf[7],
+            max_power: buf[8],
+            buf: &buf[..total_len],
+        })
+    }
+
+    /// Returns the total length from a configuration descriptor header.
+    pub fn total_length(header: &[u8]) -> Option<usize> {
+        if header.len() < 4 || header[1] != descriptor_type::CONFIGURATION {
+            return None;
+        }
+        Some(u16::from_le_bytes([header[2], header[3]]) as usize)
+    }
+
+    /// Gets the raw bytes of the configuration.
+    pub fn raw(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Iterates over all descriptors in the configuration.
+    pub fn descriptors(&self) -> DescriptorIter<'a> {
+        DescriptorIter::new(self.buf)
+    }
+
+    /// Iterates over the interfaces, including alternate settings.
+    pub fn interfaces(&self) -> impl Iterator<Item = InterfaceDescriptor<'a>> + 'a {
+        let buf = self.buf;
+        let mut iter = DescriptorIter::new(buf);
+        core::iter::from_fn(move || loop {
+            let (ty, desc) = iter.next()?;
+            if ty == descriptor_type::INTERFACE {
+                if let Some(iface) = InterfaceDescriptor::parse(desc, iter.clone()) {
+                    return Some(iface);
+                }
+            }
+        })
+    }
+
+    /// Finds the first interface with the given class, sub-class and protocol, in alternate setting 0.
+    ///
+    /// `None` matches any value.
+    pub fn find_interface(
+        &self,
+        class: u8,
+        sub_class: Option<u8>,
+        protocol: Option<u8>,
+    ) -> Option<InterfaceDescriptor<'a>> {
+        self.interfaces().find(|i| {
+            i.alternate_setting == 0
+                && i.interface_class == class
+                && sub_class.map_or(true, |s| s == i.interface_sub_class)
+                && protocol.map_or(true, |p| p == i.interface_protocol)
+        })
+    }
+}
+
+/// Interface descriptor, with the descriptors that follow it.
+#[derive(Clone, Debug)]
+pub struct InterfaceDescriptor<'a> {
+    /// Interface number.
+    pub interface_number: u8,
+    /// Alternate setting.
+    pub alternate_setting: u8,
+    /// Number of endpoints.
+    pub num_endpoints: u8,
+    /// Interface class.
+    pub interface_class: u8,
+    /// Interface sub-class.
+    pub interface_sub_class: u8,
+    /// Interface protocol.
+    pub interface_protocol: u8,
+    /// Index of the interface string.
+    pub interface_string: u8,
+    rest: DescriptorIter<'a>,
+}
+
+impl<'a> InterfaceDescriptor<'a> {
+    fn parse(buf: &[u8], rest: DescriptorIter<'a>) -> Option<Self> {
+        if buf.len() < 9 {
+            return None;
+        }
+        Some(Self {
+            interface_number: buf[2],
+            alternate_setting: buf[3],
+            num_endpoints: buf[4],
+            interface_class: buf[5],
+            interface_sub_class: buf[6],
+            interface_protocol: buf[7],
+            interface_string: buf[8],
+            rest,
+        })
+    }
+
+    /// Iterates over the descriptors following this interface descriptor, up to the next interface.
+    ///
+    /// This includes the endpoint descriptors and any class-specific descriptors.
+    pub fn descriptors(&self) -> impl Iterator<Item = (u8, &'a [u8])> + 'a {
+        self.rest
+            .clone()
+            .take_while(|(ty, _)| *ty != descriptor_type::INTERFACE && *ty != descriptor_type::IAD)
+    }
+
+    /// Iterates over the endpoints of this interface.
+    pub fn endpoints(&self) -> impl Iterator<Item = EndpointDescriptor> + 'a {
+        self.descriptors()
+            .filter(|(ty, _)| *ty == descriptor_type::ENDPOINT)
+            .filter_map(|(_, desc)| EndpointDescriptor::parse(desc))
+    }
+}
+
+/// Endpoint descriptor.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EndpointDescriptor {
+    /// Endpoint address.
+    pub address: EndpointAddress,
+    /// Endpoint attributes, bits 0..1 are the transfer type.
+    pub attributes: u8,
+    /// Maximum packet size.
+    pub max_packet_size: u16,
+    /// Polling interval, `bInterval` as in the descriptor.
+    ///
+    /// For high speed interrupt endpoints this is an exponent, see [`interval_ms`](Self::interval_ms).
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Parses an endpoint descriptor.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 7 || buf[1] != descriptor_type::ENDPOINT {
+            return None;
+        }
+        Some(Self {
+            address: EndpointAddress::from(buf[2]),
+            attributes: buf[3],
+            max_packet_size: u16::from_le_bytes([buf[4], buf[5]]) & 0x7ff,
+            interval: buf[6],
+        })
+    }
+
+    /// Gets the transfer type of the endpoint.
+    pub fn ep_type(&self) -> EndpointType {
+        match self.attributes & 0b11 {
+            0b00 => EndpointType::Control,
+            0b01 => EndpointType::Isochronous,
+            0b10 => EndpointType::Bulk,
+            _ => EndpointType::Interrupt,
+        }
+    }
+
+    /// Gets the polling interval in milliseconds, for a device running at `speed`.
+    ///
+    /// High speed devices give the interval as `2^(bInterval-1)` microframes of 125 us. Intervals
+    /// shorter than 1 ms are rounded up to 1 ms, longer ones are capped at 255 ms.
+    pub fn interval_ms(&self, speed: Speed) -> u8 {
+        match speed {
+            Speed::Low | Speed::Full => self.interval,
+            Speed::High => {
+                let microframes = 1u32 << (self.interval.clamp(1, 16) - 1);
+                (microframes / 8).clamp(1, u8::MAX as u32) as u8
+            }
+        }
+    }
+
+    /// Converts the descriptor to the endpoint info used to allocate a pipe on a device running at `speed`.
+    pub fn info(&self, speed: Speed) -> EndpointInfo {
+        EndpointInfo {
+            addr: self.address,
+            ep_type: self.ep_type(),
+            max_packet_size: self.max_packet_size,
+            interval_ms: self.interval_ms(speed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Configuration with one HID interface with an IN interrupt endpoint, followed by a
+    // second interface with a bulk IN and a bulk OUT endpoint.
+    const CONFIG: &[u8] = &[
+        0x09, 0x02, 0x39, 0x00, 0x02, 0x01, 0x00, 0xa0, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface 0, HID boot keyboard
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // HID
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint 0x81, interrupt
+        0x09, 0x04, 0x01, 0x00, 0x02, 0x08, 0x06, 0x50, 0x00, // interface 1, mass storage
+        0x07, 0x05, 0x82, 0x02, 0x40, 0x00, 0x00, // endpoint 0x82, bulk
+        0x07, 0x05, 0x02, 0x02, 0x40, 0x00, 0x00, // endpoint 0x02, bulk
+    ];
+
+    #[test]
+    fn test_descriptor_iter() {
+        let types: [u8; 7] = [2, 4, 0x21, 5, 4, 5, 5];
+        let mut iter = DescriptorIter::new(CONFIG);
+        for ty in types {
+            let (t, desc) = iter.next().unwrap();
+            assert_eq!(t, ty);
+            assert_eq!(desc[1], ty);
+            assert_eq!(desc.len(), desc[0] as usize);
+        }
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_descriptor_iter_truncated() {
+        // The endpoint descriptor claims 7 bytes but only 4 are there.
+        let mut iter = DescriptorIter::new(&CONFIG[..31]);
+        assert_eq!(iter.next().map(|(t, _)| t), Some(2));
+        assert_eq!(iter.next().map(|(t, _)| t), Some(4));
+        assert_eq!(iter.next().map(|(t, _)| t), Some(0x21));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_descriptor_iter_zero_length() {
+        let buf = [
+            0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, 0x00, 0x05, 0x81, 0x03,
+        ];
+        let mut iter = DescriptorIter::new(&buf);
+        assert_eq!(iter.next().map(|(t, _)| t), Some(4));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(DescriptorIter::new(&[0x00, 0x00]).count(), 0);
+        assert_eq!(DescriptorIter::new(&[0x01, 0x05, 0x00]).count(), 0);
+    }
+
+    #[test]
+    fn test_config_parse() {
+        let config = ConfigurationDescriptor::parse(CONFIG).unwrap();
+        assert_eq!(ConfigurationDescriptor::total_length(CONFIG), Some(CONFIG.len()));
+        assert_eq!(config.num_interfaces, 2);
+        assert_eq!(config.configuration_value, 1);
+        assert_eq!(config.raw(), CONFIG);
+        assert_eq!(config.interfaces().count(), 2);
+
+        let hid = config.find_interface(0x03, None, Some(0x01)).unwrap();
+        assert_eq!(hid.interface_number, 0);
+        assert_eq!(hid.descriptors().count(), 2);
+        let mut eps = hid.endpoints();
+        let ep = eps.next().unwrap();
+        assert_eq!(ep.address, EndpointAddress::from(0x81));
+        assert_eq!(ep.ep_type(), EndpointType::Interrupt);
+        assert_eq!(ep.max_packet_size, 8);
+        assert_eq!(ep.interval, 10);
+        assert!(eps.next().is_none());
+
+        let msc = config.find_interface(0x08, Some(0x06), Some(0x50)).unwrap();
+        assert_eq!(msc.interface_number, 1);
+        let mut eps = msc.endpoints();
+        assert_eq!(eps.next().unwrap().address, EndpointAddress::from(0x82));
+        assert_eq!(eps.next().unwrap().address, EndpointAddress::from(0x02));
+        assert!(eps.next().is_none());
+
+        assert!(config.find_interface(0x09, None, None).is_none());
+    }
+
+    #[test]
+    fn test_config_parse_truncated() {
+        // Shorter than wTotalLength.
+        assert!(ConfigurationDescriptor::parse(&CONFIG[..CONFIG.len() - 1]).is_none());
+        // Shorter than the configuration descriptor itself.
+        assert!(ConfigurationDescriptor::parse(&CONFIG[..8]).is_none());
+        assert_eq!(ConfigurationDescriptor::total_length(&CONFIG[..3]), None);
+
+        // wTotalLength cuts the last endpoint descriptor in half.
+        let mut buf = [0; 57];
+        buf.copy_from_slice(CONFIG);
+        buf[2] = 53;
+        let config = ConfigurationDescriptor::parse(&buf).unwrap();
+        let msc = config.interfaces().nth(1).unwrap();
+        let mut eps = msc.endpoints();
+        assert_eq!(eps.next().unwrap().address, EndpointAddress::from(0x82));
+        assert!(eps.next().is_none());
+    }
+
+    #[test]
+    fn test_config_parse_zero_length() {
+        // A zero-length descriptor in the middle ends the iteration instead of looping forever.
+        let mut buf = [0; 57];
+        buf.copy_from_slice(CONFIG);
+        buf[18] = 0;
+        let config = ConfigurationDescriptor::parse(&buf).unwrap();
+        assert_eq!(config.interfaces().count(), 1);
+        let hid = config.interfaces().next().unwrap();
+        assert_eq!(hid.descriptors().count(), 0);
+        assert_eq!(hid.endpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_interval_ms() {
+        let mut ep = EndpointDescriptor::parse(&CONFIG[27..34]).unwrap();
+        assert_eq!(ep.interval_ms(Speed::Low), 10);
+        assert_eq!(ep.interval_ms(Speed::Full), 10);
+        assert_eq!(ep.info(Speed::Full).interval_ms, 10);
+
+        // 2^(bInterval-1) microframes.
+        ep.interval = 1;
+        assert_eq!(ep.interval_ms(Speed::High), 1);
+        ep.interval = 4;
+        assert_eq!(ep.interval_ms(Speed::High), 1);
+        ep.interval = 7;
+        assert_eq!(ep.interval_ms(Speed::High), 8);
+        ep.interval = 16;
+        assert_eq!(ep.interval_ms(Speed::High), 255);
+        ep.interval = 0;
+        assert_eq!(ep.interval_ms(Speed::High), 1);
+    }
+}
@@ -0,0 +1,254 @@
+#![no_std]
+#![feature(async_fn_in_trait)]
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+// This mod MUST go first, so that the others see its macros.
+pub(crate) mod fmt;
+
+pub use embassy_usb_driver::host as driver;
+
+pub mod class;
+pub mod control;
+pub mod descriptor;
+
+use core::marker::PhantomData;
+
+use embassy_time::{Duration, Timer};
+
+use crate::control::{request, SetupPacket};
+use crate::descriptor::{descriptor_type, ConfigurationDescriptor, DeviceDescriptor, EndpointDescriptor};
+use crate::driver::{ControlPipe, DeviceEvent, Driver, PipeAllocError, PipeError, Speed};
+
+/// Address assigned to the device during enumeration. Only one device is supported, as there
+/// is no hub support.
+const DEVICE_ADDRESS: u8 = 1;
+
+/// Time the device is allowed to take to apply a new address.
+const SET_ADDRESS_RECOVERY: Duration = Duration::from_millis(2);
+
+/// Time to wait after the device is attached, before resetting it.
+const ATTACH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Error returned by the host stack.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostError {
+    /// A transfer failed.
+    Pipe(PipeError),
+    /// A pipe could not be allocated.
+    PipeAlloc,
+    /// The device sent an invalid descriptor.
+    InvalidDescriptor,
+    /// The configuration descriptor doesn't fit in the buffer.
+    BufferTooSmall,
+    /// The device doesn't have the requested interface or endpoint.
+    NotFound,
+}
+
+impl From<PipeError> for HostError {
+    fn from(e: PipeError) -> Self {
+        Self::Pipe(e)
+    }
+}
+
+impl From<PipeAllocError> for HostError {
+    fn from(_: PipeAllocError) -> Self {
+        Self::PipeAlloc
+    }
+}
+
+/// USB host.
+pub struct UsbHost<'d, D: Driver<'d>> {
+    driver: D,
+    _phantom: PhantomData<&'d ()>,
+}
+
+impl<'d, D: Driver<'d>> UsbHost<'d, D> {
+    /// Creates a USB host on top of a host driver.
+    pub fn new(driver: D) -> Self {
+        Self {
+            driver,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Waits for a device to be attached, and enumerates it.
+    ///
+    /// The whole configuration descriptor is read into `config_buf`, and the first configuration
+    /// is selected.
+    pub async fn wait_for_device<'b>(&mut self, config_buf: &'b mut [u8]) -> Result<Device<'d, 'b, D>, HostError> {
+        let speed = loop {
+            match self.driver.wait_for_device_event().await {
+                DeviceEvent::Connected(speed) => break speed,
+                DeviceEvent::Disconnected => {}
+            }
+        };
+        debug!("device attached, speed {:?}", speed);
+
+        Timer::after(ATTACH_DEBOUNCE).await;
+        self.enumerate(speed, config_buf).await
+    }
+
+    /// Waits for the device to be detached.
+    pub async fn wait_for_disconnect(&mut self) {
+        while self.driver.wait_for_device_event().await != DeviceEvent::Disconnected {}
+        debug!("device detached");
+    }
+
+    async fn enumerate<'b>(&mut self, speed: Speed, config_buf: &'b mut [u8]) -> Result<Device<'d, 'b, D>, HostError> {
+        self.driver.bus_reset().await;
+
+        // Only the first 8 bytes of the device descriptor are guaranteed to fit in one packet
+        // before the max packet size is known.
+        let mut control = self.driver.alloc_control_pipe(0, 8, speed)?;
+        let mut buf = [0; DeviceDescriptor::SIZE];
+        let setup = SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, 0);
+        let n = control.control_in(&setup.to_bytes(true, 8), &mut buf[..8]).await?;
+        if n < 8 {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let max_packet_size_0 = buf[7];
+        trace!("max_packet_size_0 = {}", max_packet_size_0);
+        control.set_max_packet_size(max_packet_size_0 as u16);
+
+        let setup = SetupPacket::standard(request::SET_ADDRESS, DEVICE_ADDRESS as u16, 0);
+        control.control_out(&setup.to_bytes(false, 0), &[]).await?;
+        Timer::after(SET_ADDRESS_RECOVERY).await;
+        control.set_device_address(DEVICE_ADDRESS);
+
+        let setup = SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, 0);
+        let n = control
+            .control_in(&setup.to_bytes(true, DeviceDescriptor::SIZE as u16), &mut buf)
+            .await?;
+        let device_descriptor = DeviceDescriptor::parse(&buf[..n]).ok_or(HostError::InvalidDescriptor)?;
+        debug!(
+            "device {:04x}:{:04x}",
+            device_descriptor.vendor_id, device_descriptor.product_id
+        );
+
+        // Read the configuration header first to find out the total length.
+        let setup = SetupPacket::get_descriptor(descriptor_type::CONFIGURATION, 0, 0);
+        let mut header = [0; ConfigurationDescriptor::SIZE];
+        let n = control
+            .control_in(&setup.to_bytes(true, header.len() as u16), &mut header)
+            .await?;
+        let total_len = ConfigurationDescriptor::total_length(&header[..n]).ok_or(HostError::InvalidDescriptor)?;
+        if total_len > config_buf.len() {
+            warn!("configuration descriptor is {} bytes, buffer is too small", total_len);
+            return Err(HostError::BufferTooSmall);
+        }
+        let n = control
+            .control_in(&setup.to_bytes(true, total_len as u16), &mut config_buf[..total_len])
+            .await?;
+        let config_descriptor = ConfigurationDescriptor::parse(&config_buf[..n]).ok_or(HostError::InvalidDescriptor)?;
+
+        let setup = SetupPacket::standard(
+            request::SET_CONFIGURATION,
+            config_descriptor.configuration_value as u16,
+            0,
+        );
+        control.control_out(&setup.to_bytes(false, 0), &[]).await?;
+        debug!("configured");
+
+        Ok(Device {
+            control,
+            address: DEVICE_ADDRESS,
+            speed,
+            device_descriptor,
+            config_descriptor,
+        })
+    }
+
+    /// Allocates a pipe to an IN endpoint of a device.
+    pub fn alloc_pipe_in(
+        &mut self,
+        device: &Device<'d, '_, D>,
+        endpoint: &EndpointDescriptor,
+    ) -> Result<D::PipeIn, HostError> {
+        Ok(self
+            .driver
+            .alloc_pipe_in(device.address, &endpoint.info(device.speed), device.speed)?)
+    }
+
+    /// Allocates a pipe to an OUT endpoint of a device.
+    pub fn alloc_pipe_out(
+        &mut self,
+        device: &Device<'d, '_, D>,
+        endpoint: &EndpointDescriptor,
+    ) -> Result<D::PipeOut, HostError> {
+        Ok(self
+            .driver
+            .alloc_pipe_out(device.address, &endpoint.info(device.speed), device.speed)?)
+    }
+}
+
+/// An enumerated and configured device.
+pub struct Device<'d, 'b, D: Driver<'d>> {
+    control: D::ControlPipe,
+    address: u8,
+    speed: Speed,
+    device_descriptor: DeviceDescriptor,
+    config_descriptor: ConfigurationDescriptor<'b>,
+}
+
+impl<'d, 'b, D: Driver<'d>> Device<'d, 'b, D> {
+    /// Gets the address of the device.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Gets the speed of the device.
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Gets the device descriptor.
+    pub fn device_descriptor(&self) -> &DeviceDescriptor {
+        &self.device_descriptor
+    }
+
+    /// Gets the active configuration descriptor.
+    pub fn config_descriptor(&self) -> &ConfigurationDescriptor<'b> {
+        &self.config_descriptor
+    }
+
+    /// Performs a control transfer with an IN data stage.
+    ///
+    /// Returns the number of bytes received.
+    pub async fn control_in(&mut self, setup: &SetupPacket, buf: &mut [u8]) -> Result<usize, HostError> {
+        let setup = setup.to_bytes(true, buf.len() as u16);
+        Ok(self.control.control_in(&setup, buf).await?)
+    }
+
+    /// Performs a control transfer with an OUT data stage, or no data stage if `data` is empty.
+    pub async fn control_out(&mut self, setup: &SetupPacket, data: &[u8]) -> Result<(), HostError> {
+        let setup = setup.to_bytes(false, data.len() as u16);
+        Ok(self.control.control_out(&setup, data).await?)
+    }
+
+    /// Reads a descriptor with GET_DESCRIPTOR.
+    pub async fn get_descriptor(
+        &mut self,
+        descriptor_type: u8,
+        index: u8,
+        language_id: u16,
+        buf: &mut [u8],
+    ) -> Result<usize, HostError> {
+        self.control_in(&SetupPacket::get_descriptor(descriptor_type, index, language_id), buf)
+            .await
+    }
+
+    /// Clears the halt condition of an endpoint, for example after a transfer failed with
+    /// [`PipeError::Stall`].
+    pub async fn clear_halt(&mut self, endpoint: &EndpointDescriptor) -> Result<(), HostError> {
+        let setup = SetupPacket {
+            request_type: control::RequestType::Standard,
+            recipient: control::Recipient::Endpoint,
+            request: request::CLEAR_FEATURE,
+            value: control::FEATURE_ENDPOINT_HALT,
+            index: u8::from(endpoint.address) as u16,
+        };
+        self.control_out(&setup, &[]).await
+    }
+}
@@ -0,0 +1,94 @@
+//! `defmt` global logger sending the encoded frames over USB.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::pipe::Pipe;
+use embassy_usb::driver::Driver;
+
+#[cfg(feature = "embedded-io")]
+use crate::run_pipe_io;
+use crate::{run_pipe, LoggerState, CS};
+
+/// Size of the buffer holding encoded `defmt` frames until they're sent to the host.
+const BUFFER_SIZE: usize = 1024;
+
+static BUFFER: Pipe<CS, BUFFER_SIZE> = Pipe::new();
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+/// Set when part of the current frame didn't fit in the buffer.
+static DROPPING: AtomicBool = AtomicBool::new(false);
+static mut CS_RESTORE: critical_section::RestoreState = critical_section::RestoreState::invalid();
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // safety: must be paired with the corresponding call to release(), see below
+        let restore = unsafe { critical_section::acquire() };
+
+        // safety: accessing the `static mut` is OK because we have acquired a critical section.
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly")
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        DROPPING.store(false, Ordering::Relaxed);
+
+        // safety: accessing the `static mut` is OK because we have acquired a critical section.
+        unsafe {
+            CS_RESTORE = restore;
+            ENCODER.start_frame(write);
+        }
+    }
+
+    unsafe fn flush() {
+        // Frames are sent by the USB task, there's no way to wait for it from inside a critical section.
+    }
+
+    unsafe fn release() {
+        // safety: accessing the `static mut` is OK because we have acquired a critical section.
+        ENCODER.end_frame(write);
+        TAKEN.store(false, Ordering::Relaxed);
+
+        // safety: accessing the `static mut` is OK because we have acquired a critical section.
+        let restore = CS_RESTORE;
+        critical_section::release(restore);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        // safety: accessing the `static mut` is OK because we have acquired a critical section.
+        ENCODER.write(bytes, write);
+    }
+}
+
+fn write(bytes: &[u8]) {
+    // Drop the rest of the frame once it no longer fits, but still try to write its terminator.
+    // The host decoder skips the truncated frame and resynchronizes on the next one.
+    if DROPPING.load(Ordering::Relaxed) && !bytes.contains(&0) {
+        return;
+    }
+    if BUFFER.free_capacity() < bytes.len() {
+        DROPPING.store(true, Ordering::Relaxed);
+        return;
+    }
+    let _ = BUFFER.try_write(bytes);
+}
+
+/// Run the USB logger for `defmt`, using the state and USB driver. Never returns.
+///
+/// The encoded frames are sent on a CDC-ACM serial port. They can be decoded on the host by
+/// piping the port into `defmt-print`.
+pub async fn run_defmt<'d, D>(state: &'d mut LoggerState<'d>, driver: D) -> !
+where
+    D: Driver<'d>,
+{
+    run_pipe(&BUFFER, state, driver).await
+}
+
+/// Run the logger for `defmt` writing to `writer`, such as a UART or a TCP socket, instead of
+/// USB. Never returns.
+#[cfg(feature = "embedded-io")]
+pub async fn run_defmt_io<W: embedded_io::asynch::Write>(writer: W) -> ! {
+    run_pipe_io(&BUFFER, writer).await
+}
@@ -11,8 +11,20 @@ use embassy_usb::driver::Driver;
 use embassy_usb::{Builder, Config};
 use log::{Metadata, Record};
 
+#[cfg(feature = "defmt")]
+mod defmt_logger;
+#[cfg(feature = "defmt")]
+pub use defmt_logger::run_defmt;
+#[cfg(all(feature = "defmt", feature = "embedded-io"))]
+pub use defmt_logger::run_defmt_io;
+
 type CS = embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 
+/// Maximum length of a single `log` message, including the line terminator.
+///
+/// Longer messages are truncated.
+pub const MAX_MESSAGE_LEN: usize = 256;
+
 /// The logger state containing buffers that must live as long as the USB peripheral.
 pub struct LoggerState<'d> {
     state: State<'d>,
@@ -52,49 +64,81 @@ impl<const N: usize> UsbLogger<N> {
         D: Driver<'d>,
         Self: 'd,
     {
-        const MAX_PACKET_SIZE: u8 = 64;
-        let mut config = Config::new(0xc0de, 0xcafe);
-        config.manufacturer = Some("Embassy");
-        config.product = Some("USB-serial logger");
-        config.serial_number = None;
-        config.max_power = 100;
-        config.max_packet_size_0 = MAX_PACKET_SIZE;
-
-        // Required for windows compatiblity.
-        // https://developer.nordicsemi.com/nRF_Connect_SDK/doc/1.9.1/kconfig/CONFIG_CDC_ACM_IAD.html#help
-        config.device_class = 0xEF;
-        config.device_sub_class = 0x02;
-        config.device_protocol = 0x01;
-        config.composite_with_iads = true;
-
-        let mut builder = Builder::new(
-            driver,
-            config,
-            &mut state.device_descriptor,
-            &mut state.config_descriptor,
-            &mut state.bos_descriptor,
-            &mut state.control_buf,
-        );
-
-        // Create classes on the builder.
-        let mut class = CdcAcmClass::new(&mut builder, &mut state.state, MAX_PACKET_SIZE as u16);
-
-        // Build the builder.
-        let mut device = builder.build();
+        run_pipe(&self.buffer, state, driver).await
+    }
+
+    /// Run the logger writing to `writer`, such as a UART or a TCP socket, instead of USB. Never
+    /// returns.
+    #[cfg(feature = "embedded-io")]
+    pub async fn run_io<W: embedded_io::asynch::Write>(&self, writer: W) -> ! {
+        run_pipe_io(&self.buffer, writer).await
+    }
+}
 
+/// Forwards everything written into `pipe` to `writer`.
+#[cfg(feature = "embedded-io")]
+async fn run_pipe_io<W: embedded_io::asynch::Write, const N: usize>(pipe: &Pipe<CS, N>, mut writer: W) -> ! {
+    let mut buf = [0; 64];
+    loop {
+        let len = pipe.read(&mut buf).await;
+        // Data that fails to be written is dropped: the link, such as a socket, may come back.
+        if writer.write_all(&buf[..len]).await.is_ok() {
+            let _ = writer.flush().await;
+        }
+    }
+}
+
+/// Runs a CDC-ACM device forwarding everything written into `pipe` to the host.
+async fn run_pipe<'d, D, const N: usize>(pipe: &'d Pipe<CS, N>, state: &'d mut LoggerState<'d>, driver: D) -> !
+where
+    D: Driver<'d>,
+{
+    const MAX_PACKET_SIZE: u8 = 64;
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB-serial logger");
+    config.serial_number = None;
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE;
+
+    // Required for windows compatiblity.
+    // https://developer.nordicsemi.com/nRF_Connect_SDK/doc/1.9.1/kconfig/CONFIG_CDC_ACM_IAD.html#help
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut state.device_descriptor,
+        &mut state.config_descriptor,
+        &mut state.bos_descriptor,
+        &mut state.control_buf,
+    );
+
+    // Create classes on the builder.
+    let mut class = CdcAcmClass::new(&mut builder, &mut state.state, MAX_PACKET_SIZE as u16);
+
+    // Build the builder.
+    let mut device = builder.build();
+
+    let run_fut = device.run();
+    let log_fut = async {
+        let mut rx: [u8; MAX_PACKET_SIZE as usize] = [0; MAX_PACKET_SIZE as usize];
         loop {
-            let run_fut = device.run();
-            let log_fut = async {
-                let mut rx: [u8; MAX_PACKET_SIZE as usize] = [0; MAX_PACKET_SIZE as usize];
-                class.wait_connection().await;
-                loop {
-                    let len = self.buffer.read(&mut rx[..]).await;
-                    let _ = class.write_packet(&rx[..len]).await;
+            // Logs written while no host is connected stay in the buffer until it is full, after
+            // which new messages are dropped.
+            class.wait_connection().await;
+            loop {
+                let len = pipe.read(&mut rx[..]).await;
+                if class.write_packet(&rx[..len]).await.is_err() {
+                    break;
                 }
-            };
-            join(run_fut, log_fut).await;
+            }
         }
-    }
+    };
+    join(run_fut, log_fut).await.0
 }
 
 impl<const N: usize> log::Log for UsbLogger<N> {
@@ -104,18 +148,43 @@ impl<const N: usize> log::Log for UsbLogger<N> {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let _ = write!(Writer(&self.buffer), "{}\r\n", record.args());
+            let mut line = LineBuffer {
+                buf: [0; MAX_MESSAGE_LEN],
+                len: 0,
+            };
+            let _ = write!(line, "{}", record.args());
+            line.truncate_for_terminator();
+            let _ = line.write_str("\r\n");
+
+            // Drop the whole message if it doesn't fit, rather than sending a partial line.
+            let line = &line.buf[..line.len];
+            if self.buffer.free_capacity() >= line.len() {
+                let _ = self.buffer.try_write(line);
+            }
         }
     }
 
     fn flush(&self) {}
 }
 
-struct Writer<'d, const N: usize>(&'d Pipe<CS, N>);
+/// Formats a message on the stack, truncating it if it's too long.
+struct LineBuffer {
+    buf: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+}
+
+impl LineBuffer {
+    /// Makes room for the `\r\n` line terminator.
+    fn truncate_for_terminator(&mut self) {
+        self.len = self.len.min(MAX_MESSAGE_LEN - 2);
+    }
+}
 
-impl<'d, const N: usize> core::fmt::Write for Writer<'d, N> {
+impl core::fmt::Write for LineBuffer {
     fn write_str(&mut self, s: &str) -> Result<(), core::fmt::Error> {
-        let _ = self.0.try_write(s.as_bytes());
+        let n = s.len().min(MAX_MESSAGE_LEN - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
         Ok(())
     }
 }
@@ -143,3 +212,28 @@ macro_rules! run {
         let _ = LOGGER.run(&mut ::embassy_usb_logger::LoggerState::new(), $p).await;
     };
 }
+
+/// Initialize and run the logger over an `embedded-io` writer, never returns.
+///
+/// Arguments specify the buffer size, log level and the writer, respectively.
+///
+/// # Usage
+///
+/// ```
+/// embassy_usb_logger::run_io!(1024, log::LevelFilter::Info, uart_tx);
+/// ```
+///
+/// # Safety
+///
+/// This macro should only be invoked only once since it is setting the global logging state of the application.
+#[cfg(feature = "embedded-io")]
+#[macro_export]
+macro_rules! run_io {
+    ( $x:expr, $l:expr, $w:expr ) => {
+        static LOGGER: ::embassy_usb_logger::UsbLogger<$x> = ::embassy_usb_logger::UsbLogger::new();
+        unsafe {
+            let _ = ::log::set_logger_racy(&LOGGER).map(|()| log::set_max_level_racy($l));
+        }
+        let _ = LOGGER.run_io($w).await;
+    };
+}
@@ -1,12 +1,12 @@
 use heapless::Vec;
 
 use crate::config::*;
-use crate::descriptor::{BosWriter, DescriptorWriter};
-use crate::driver::{Driver, Endpoint, EndpointType};
+use crate::descriptor::{BosWriter, DescriptorWriter, SynchronizationType, UsageType};
+use crate::driver::{Driver, Endpoint, EndpointInfo, EndpointType};
 #[cfg(feature = "msos-descriptor")]
 use crate::msos::{DeviceLevelDescriptor, FunctionLevelDescriptor, MsOsDescriptorWriter};
 use crate::types::*;
-use crate::{Handler, Interface, UsbDevice, MAX_INTERFACE_COUNT, STRING_INDEX_CUSTOM_START};
+use crate::{Handler, Interface, UsbDevice, CONFIGURATION_VALUE, MAX_INTERFACE_COUNT, STRING_INDEX_CUSTOM_START};
 
 #[derive(Debug, Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -58,6 +58,8 @@ pub struct Config<'a> {
 
     /// Serial number string descriptor.
     ///
+    /// The string can be generated at runtime, see [`Config::set_serial_number_hex`].
+    ///
     /// Default: (none)
     pub serial_number: Option<&'a str>,
 
@@ -117,15 +119,39 @@ impl<'a> Config<'a> {
             max_power: 100,
         }
     }
+
+    /// Set the serial number string to the hexadecimal representation of `id`.
+    ///
+    /// This is meant for serial numbers derived at runtime from a chip's unique ID. The string is
+    /// formatted into `buf`, which must be at least twice as long as `id` and must live as long as
+    /// the descriptor buffers passed to [`Builder::new`].
+    pub fn set_serial_number_hex(&mut self, id: &[u8], buf: &'a mut [u8]) {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+        let buf: &'a mut [u8] = &mut buf[..id.len() * 2];
+        for (chunk, byte) in buf.chunks_exact_mut(2).zip(id) {
+            chunk[0] = HEX[(byte >> 4) as usize];
+            chunk[1] = HEX[(byte & 0x0f) as usize];
+        }
+
+        // The buffer only contains ASCII hex digits.
+        self.serial_number = Some(core::str::from_utf8(buf).unwrap());
+    }
 }
 
 /// [`UsbDevice`] builder.
 pub struct Builder<'d, D: Driver<'d>> {
     config: Config<'d>,
     handlers: Vec<&'d mut dyn Handler, MAX_HANDLER_COUNT>,
+    handler_configurations: Vec<u8, MAX_HANDLER_COUNT>,
     interfaces: Vec<Interface, MAX_INTERFACE_COUNT>,
     control_buf: &'d mut [u8],
 
+    /// bConfigurationValue of the configuration currently being built.
+    configuration: u8,
+    /// Index in `interfaces` of the first interface of the current configuration.
+    first_interface: usize,
+
     driver: D,
     next_string_index: u8,
 
@@ -173,7 +199,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
         let mut bos_descriptor = BosWriter::new(DescriptorWriter::new(bos_descriptor_buf));
 
         device_descriptor.device(&config);
-        config_descriptor.configuration(&config);
+        config_descriptor.configuration(&config, CONFIGURATION_VALUE, None);
         bos_descriptor.bos();
 
         Builder {
@@ -181,7 +207,10 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
             config,
             interfaces: Vec::new(),
             handlers: Vec::new(),
+            handler_configurations: Vec::new(),
             control_buf,
+            configuration: CONFIGURATION_VALUE,
+            first_interface: 0,
             next_string_index: STRING_INDEX_CUSTOM_START,
 
             device_descriptor,
@@ -199,6 +228,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
         let msos_descriptor = self.msos_descriptor.build(&mut self.bos_descriptor);
 
         self.config_descriptor.end_configuration();
+        self.device_descriptor.set_num_configurations(self.configuration);
         self.bos_descriptor.end_bos();
 
         // Log the number of allocator bytes actually used in descriptor buffers
@@ -213,6 +243,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
             self.driver,
             self.config,
             self.handlers,
+            self.handler_configurations,
             self.device_descriptor.into_buf(),
             self.config_descriptor.into_buf(),
             self.bos_descriptor.writer.into_buf(),
@@ -229,6 +260,32 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
         self.control_buf.len()
     }
 
+    /// Start a new configuration.
+    ///
+    /// Functions, interfaces and handlers added after this call belong to the new configuration.
+    /// The host selects one of the configurations with a SET_CONFIGURATION request, and only the
+    /// endpoints and handlers of the selected configuration are enabled.
+    ///
+    /// [`Builder::new`] starts the first configuration, with value [`CONFIGURATION_VALUE`].
+    /// Each call to this function allocates the next value, which is returned. Interface numbers
+    /// start from 0 again in every configuration.
+    pub fn configuration(&mut self, config_string: Option<StringIndex>) -> u8 {
+        self.config_descriptor.end_configuration();
+
+        self.configuration += 1;
+        self.first_interface = self.interfaces.len();
+        self.config_descriptor
+            .configuration(&self.config, self.configuration, config_string);
+
+        // MS OS 2.0 configuration subsets are indexed from 0, not by bConfigurationValue.
+        #[cfg(feature = "msos-descriptor")]
+        if self.msos_descriptor.is_in_config_subset() {
+            self.msos_descriptor.configuration(self.configuration - 1);
+        }
+
+        self.configuration
+    }
+
     /// Add an USB function.
     ///
     /// If [`Config::composite_with_iads`] is set, this will add an IAD descriptor
@@ -236,7 +293,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
     ///
     /// If it's not set, no IAD descriptor is added.
     pub fn function(&mut self, class: u8, subclass: u8, protocol: u8) -> FunctionBuilder<'_, 'd, D> {
-        let first_interface = InterfaceNumber::new(self.interfaces.len() as u8);
+        let first_interface = InterfaceNumber::new((self.interfaces.len() - self.first_interface) as u8);
         let iface_count_index = if self.config.composite_with_iads {
             self.config_descriptor
                 .iad(first_interface, 0, class, subclass, protocol);
@@ -259,6 +316,10 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
     ///
     /// The Handler is called on some USB bus events, and to handle all control requests not already
     /// handled by the USB stack.
+    ///
+    /// The handler belongs to the configuration currently being built: it is only notified of
+    /// [`Handler::configured`] and [`Handler::set_alternate_setting`] events, and only receives
+    /// interface requests, while that configuration is selected.
     pub fn handler(&mut self, handler: &'d mut dyn Handler) {
         if self.handlers.push(handler).is_err() {
             panic!(
@@ -266,6 +327,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
                 MAX_HANDLER_COUNT
             )
         }
+        unwrap!(self.handler_configurations.push(self.configuration));
     }
 
     /// Allocates a new string index.
@@ -275,6 +337,15 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
         StringIndex::new(index)
     }
 
+    /// Add a device capability descriptor to the BOS descriptor.
+    ///
+    /// `data` is the capability-dependent part of the descriptor, following `bDevCapabilityType`.
+    /// The USB 2.0 extension capability is always added, and the MS OS 2.0 platform capability is
+    /// added automatically when an MS OS 2.0 Descriptor Set is configured.
+    pub fn bos_capability(&mut self, capability_type: u8, data: &[u8]) {
+        self.bos_descriptor.capability(capability_type, data);
+    }
+
     #[cfg(feature = "msos-descriptor")]
     /// Add an MS OS 2.0 Descriptor Set.
     ///
@@ -326,8 +397,10 @@ impl<'a, 'd, D: Driver<'d>> FunctionBuilder<'a, 'd, D> {
             self.builder.config_descriptor.buf[i] += 1;
         }
 
-        let number = self.builder.interfaces.len() as _;
+        let index = self.builder.interfaces.len();
+        let number = (index - self.builder.first_interface) as _;
         let iface = Interface {
+            configuration: self.builder.configuration,
             current_alt_setting: 0,
             num_alt_settings: 0,
         };
@@ -341,6 +414,7 @@ impl<'a, 'd, D: Driver<'d>> FunctionBuilder<'a, 'd, D> {
 
         InterfaceBuilder {
             builder: self.builder,
+            index,
             interface_number: InterfaceNumber::new(number),
             next_alt_setting_number: 0,
         }
@@ -350,7 +424,9 @@ impl<'a, 'd, D: Driver<'d>> FunctionBuilder<'a, 'd, D> {
     /// Add an MS OS 2.0 Function Level Feature Descriptor.
     pub fn msos_feature<T: FunctionLevelDescriptor>(&mut self, desc: T) {
         if !self.builder.msos_descriptor.is_in_config_subset() {
-            self.builder.msos_descriptor.configuration(0);
+            self.builder
+                .msos_descriptor
+                .configuration(self.builder.configuration - 1);
         }
 
         if !self.builder.msos_descriptor.is_in_function_subset() {
@@ -365,6 +441,7 @@ impl<'a, 'd, D: Driver<'d>> FunctionBuilder<'a, 'd, D> {
 /// Interface builder.
 pub struct InterfaceBuilder<'a, 'd, D: Driver<'d>> {
     builder: &'a mut Builder<'d, D>,
+    index: usize,
     interface_number: InterfaceNumber,
     next_alt_setting_number: u8,
 }
@@ -394,7 +471,7 @@ impl<'a, 'd, D: Driver<'d>> InterfaceBuilder<'a, 'd, D> {
     ) -> InterfaceAltBuilder<'_, 'd, D> {
         let number = self.next_alt_setting_number;
         self.next_alt_setting_number += 1;
-        self.builder.interfaces[self.interface_number.0 as usize].num_alt_settings += 1;
+        self.builder.interfaces[self.index].num_alt_settings += 1;
 
         self.builder.config_descriptor.interface_alt(
             self.interface_number,
@@ -439,26 +516,90 @@ impl<'a, 'd, D: Driver<'d>> InterfaceAltBuilder<'a, 'd, D> {
         self.builder.config_descriptor.write(descriptor_type, descriptor)
     }
 
-    fn endpoint_in(&mut self, ep_type: EndpointType, max_packet_size: u16, interval_ms: u8) -> D::EndpointIn {
+    /// Allocate an IN endpoint of any type, without writing its descriptor.
+    ///
+    /// This is a low-level escape hatch for protocols not covered by the other endpoint
+    /// functions, e.g. with class-specific descriptors following the endpoint descriptor. The
+    /// endpoint descriptor must be written with [`Self::endpoint_descriptor`]: the USB stack
+    /// finds the endpoints to enable in the configuration descriptor, so endpoints without one
+    /// are never enabled.
+    ///
+    /// Panics if the driver can't allocate the endpoint.
+    pub fn alloc_endpoint_in(&mut self, ep_type: EndpointType, max_packet_size: u16, interval_ms: u8) -> D::EndpointIn {
+        self.builder
+            .driver
+            .alloc_endpoint_in(ep_type, max_packet_size, interval_ms)
+            .expect("alloc_endpoint_in failed")
+    }
+
+    /// Allocate an OUT endpoint of any type, without writing its descriptor.
+    ///
+    /// See [`Self::alloc_endpoint_in`].
+    pub fn alloc_endpoint_out(
+        &mut self,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> D::EndpointOut {
+        self.builder
+            .driver
+            .alloc_endpoint_out(ep_type, max_packet_size, interval_ms)
+            .expect("alloc_endpoint_out failed")
+    }
+
+    /// Write the standard endpoint descriptor for an endpoint allocated with
+    /// [`Self::alloc_endpoint_in`] or [`Self::alloc_endpoint_out`].
+    ///
+    /// The synchronization and usage types are only used for isochronous endpoints.
+    pub fn endpoint_descriptor(
+        &mut self,
+        info: &EndpointInfo,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) {
+        self.builder
+            .config_descriptor
+            .endpoint(info, synchronization_type, usage_type);
+    }
+
+    fn endpoint_in(
+        &mut self,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) -> D::EndpointIn {
         let ep = self
             .builder
             .driver
             .alloc_endpoint_in(ep_type, max_packet_size, interval_ms)
             .expect("alloc_endpoint_in failed");
 
-        self.builder.config_descriptor.endpoint(ep.info());
+        self.builder
+            .config_descriptor
+            .endpoint(ep.info(), synchronization_type, usage_type);
 
         ep
     }
 
-    fn endpoint_out(&mut self, ep_type: EndpointType, max_packet_size: u16, interval_ms: u8) -> D::EndpointOut {
+    fn endpoint_out(
+        &mut self,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) -> D::EndpointOut {
         let ep = self
             .builder
             .driver
             .alloc_endpoint_out(ep_type, max_packet_size, interval_ms)
             .expect("alloc_endpoint_out failed");
 
-        self.builder.config_descriptor.endpoint(ep.info());
+        self.builder
+            .config_descriptor
+            .endpoint(ep.info(), synchronization_type, usage_type);
 
         ep
     }
@@ -468,7 +609,13 @@ impl<'a, 'd, D: Driver<'d>> InterfaceAltBuilder<'a, 'd, D> {
     /// Descriptors are written in the order builder functions are called. Note that some
     /// classes care about the order.
     pub fn endpoint_bulk_in(&mut self, max_packet_size: u16) -> D::EndpointIn {
-        self.endpoint_in(EndpointType::Bulk, max_packet_size, 0)
+        self.endpoint_in(
+            EndpointType::Bulk,
+            max_packet_size,
+            0,
+            SynchronizationType::NoSynchronization,
+            UsageType::DataEndpoint,
+        )
     }
 
     /// Allocate a BULK OUT endpoint and write its descriptor.
@@ -476,7 +623,13 @@ impl<'a, 'd, D: Driver<'d>> InterfaceAltBuilder<'a, 'd, D> {
     /// Descriptors are written in the order builder functions are called. Note that some
     /// classes care about the order.
     pub fn endpoint_bulk_out(&mut self, max_packet_size: u16) -> D::EndpointOut {
-        self.endpoint_out(EndpointType::Bulk, max_packet_size, 0)
+        self.endpoint_out(
+            EndpointType::Bulk,
+            max_packet_size,
+            0,
+            SynchronizationType::NoSynchronization,
+            UsageType::DataEndpoint,
+        )
     }
 
     /// Allocate a INTERRUPT IN endpoint and write its descriptor.
@@ -484,24 +637,63 @@ impl<'a, 'd, D: Driver<'d>> InterfaceAltBuilder<'a, 'd, D> {
     /// Descriptors are written in the order builder functions are called. Note that some
     /// classes care about the order.
     pub fn endpoint_interrupt_in(&mut self, max_packet_size: u16, interval_ms: u8) -> D::EndpointIn {
-        self.endpoint_in(EndpointType::Interrupt, max_packet_size, interval_ms)
+        self.endpoint_in(
+            EndpointType::Interrupt,
+            max_packet_size,
+            interval_ms,
+            SynchronizationType::NoSynchronization,
+            UsageType::DataEndpoint,
+        )
     }
 
     /// Allocate a INTERRUPT OUT endpoint and write its descriptor.
     pub fn endpoint_interrupt_out(&mut self, max_packet_size: u16, interval_ms: u8) -> D::EndpointOut {
-        self.endpoint_out(EndpointType::Interrupt, max_packet_size, interval_ms)
+        self.endpoint_out(
+            EndpointType::Interrupt,
+            max_packet_size,
+            interval_ms,
+            SynchronizationType::NoSynchronization,
+            UsageType::DataEndpoint,
+        )
     }
 
     /// Allocate a ISOCHRONOUS IN endpoint and write its descriptor.
     ///
+    /// For isochronous endpoints, `interval_ms` is written to `bInterval` as is, i.e. the polling
+    /// interval is 2^(`interval_ms` - 1) frames.
+    ///
     /// Descriptors are written in the order builder functions are called. Note that some
     /// classes care about the order.
-    pub fn endpoint_isochronous_in(&mut self, max_packet_size: u16, interval_ms: u8) -> D::EndpointIn {
-        self.endpoint_in(EndpointType::Isochronous, max_packet_size, interval_ms)
+    pub fn endpoint_isochronous_in(
+        &mut self,
+        max_packet_size: u16,
+        interval_ms: u8,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) -> D::EndpointIn {
+        self.endpoint_in(
+            EndpointType::Isochronous,
+            max_packet_size,
+            interval_ms,
+            synchronization_type,
+            usage_type,
+        )
     }
 
     /// Allocate a ISOCHRONOUS OUT endpoint and write its descriptor.
-    pub fn endpoint_isochronous_out(&mut self, max_packet_size: u16, interval_ms: u8) -> D::EndpointOut {
-        self.endpoint_out(EndpointType::Isochronous, max_packet_size, interval_ms)
+    pub fn endpoint_isochronous_out(
+        &mut self,
+        max_packet_size: u16,
+        interval_ms: u8,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) -> D::EndpointOut {
+        self.endpoint_out(
+            EndpointType::Isochronous,
+            max_packet_size,
+            interval_ms,
+            synchronization_type,
+            usage_type,
+        )
     }
 }
@@ -0,0 +1,432 @@
+//! CCID (chip/smart card interface device) class implementation.
+//!
+//! This presents the device as a USB smart card reader with a single slot, holding a card that
+//! is always inserted. The class handles the CCID bulk message framing and the reader-level
+//! commands (power on/off, slot status, protocol parameters) itself, and passes the APDUs sent to
+//! the card to the application, which can implement e.g. a PIV or OpenPGP applet.
+//!
+//! Only the T=1 protocol with APDU-level exchanges is supported: each command APDU (short or
+//! extended) arrives in one message, and each response APDU is sent in one message.
+
+use core::mem::MaybeUninit;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// Interface class code for CCID.
+pub const USB_CLASS_CCID: u8 = 0x0b;
+
+const CCID_SUBCLASS_NONE: u8 = 0x00;
+const CCID_PROTOCOL_NONE: u8 = 0x00;
+
+const CCID_DESC_TYPE_FUNCTIONAL: u8 = 0x21;
+
+// Class requests
+const REQ_ABORT: u8 = 0x01;
+
+// PC_to_RDR messages
+const PC_TO_RDR_SET_PARAMETERS: u8 = 0x61;
+const PC_TO_RDR_ICC_POWER_ON: u8 = 0x62;
+const PC_TO_RDR_ICC_POWER_OFF: u8 = 0x63;
+const PC_TO_RDR_GET_SLOT_STATUS: u8 = 0x65;
+const PC_TO_RDR_SECURE: u8 = 0x69;
+const PC_TO_RDR_ESCAPE: u8 = 0x6b;
+const PC_TO_RDR_GET_PARAMETERS: u8 = 0x6c;
+const PC_TO_RDR_RESET_PARAMETERS: u8 = 0x6d;
+const PC_TO_RDR_XFR_BLOCK: u8 = 0x6f;
+const PC_TO_RDR_ABORT: u8 = 0x72;
+const PC_TO_RDR_SET_DATA_RATE_AND_CLOCK_FREQUENCY: u8 = 0x73;
+
+// RDR_to_PC messages
+const RDR_TO_PC_DATA_BLOCK: u8 = 0x80;
+const RDR_TO_PC_SLOT_STATUS: u8 = 0x81;
+const RDR_TO_PC_PARAMETERS: u8 = 0x82;
+const RDR_TO_PC_ESCAPE: u8 = 0x83;
+const RDR_TO_PC_DATA_RATE_AND_CLOCK_FREQUENCY: u8 = 0x84;
+
+// bStatus
+const ICC_STATUS_ACTIVE: u8 = 0x00;
+const ICC_STATUS_INACTIVE: u8 = 0x01;
+const COMMAND_STATUS_FAILED: u8 = 0x40;
+const COMMAND_STATUS_TIME_EXTENSION: u8 = 0x80;
+
+// bError
+const ERROR_CMD_NOT_SUPPORTED: u8 = 0x00;
+/// The error is the offset of the bad field in the message.
+const ERROR_BAD_LENGTH: u8 = 0x01;
+const ERROR_BAD_SLOT: u8 = 0x05;
+const ERROR_ICC_MUTE: u8 = 0xfe;
+
+const HEADER_LEN: usize = 10;
+const MAX_PACKET_SIZE: usize = 64;
+
+/// T=1 protocol data structure returned for the parameter commands.
+const T1_PARAMETERS: [u8; 7] = [
+    0x11, // bmFindexDindex: Fi=372, Di=1
+    0x10, // bmTCCKST1: LRC checksum, direct convention
+    0x00, // bGuardTimeT1
+    0x4d, // bmWaitingIntegersT1: BWI=4, CWI=13
+    0x00, // bClockStop: not allowed
+    0xfe, // bIFSC
+    0x00, // bNadValue
+];
+
+/// Configuration for the CCID class.
+pub struct Config<'d> {
+    /// Answer-to-reset of the card, sent to the host when it powers the card on.
+    ///
+    /// It must advertise the T=1 protocol.
+    pub atr: &'d [u8],
+
+    /// Maximum length of the command and response APDUs.
+    ///
+    /// 261 bytes fit any short APDU. Extended APDUs need up to 65544 bytes, applets usually
+    /// limit them to a few kilobytes.
+    pub max_apdu_len: usize,
+
+    /// Max packet size for both the bulk IN and OUT endpoints, at most 64 bytes.
+    pub max_packet_size: u16,
+}
+
+/// Internal state for CCID.
+pub struct State {
+    control: MaybeUninit<Control>,
+}
+
+impl State {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+        }
+    }
+}
+
+struct Control {
+    if_num: InterfaceNumber,
+}
+
+impl Handler for Control {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            // The abort is completed by the PC_to_RDR_Abort bulk message, answered in `read_apdu`.
+            REQ_ABORT => Some(OutResponse::Accepted),
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, _buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        // GET_CLOCK_FREQUENCIES and GET_DATA_RATES are not needed, since the functional
+        // descriptor lists no clock frequencies or data rates.
+        Some(InResponse::Rejected)
+    }
+}
+
+/// CCID class with a single slot.
+pub struct CcidClass<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    atr: &'d [u8],
+    powered: bool,
+    /// bSeq of the XfrBlock message being answered.
+    seq: u8,
+}
+
+impl<'d, D: Driver<'d>> CcidClass<'d, D> {
+    /// Creates a new CCID class, adding its interface and endpoints to the builder.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State, config: Config<'d>) -> Self {
+        assert!(config.max_packet_size as usize <= MAX_PACKET_SIZE);
+
+        let max_message_len = (HEADER_LEN + config.max_apdu_len) as u32;
+
+        let mut func = builder.function(USB_CLASS_CCID, CCID_SUBCLASS_NONE, CCID_PROTOCOL_NONE);
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_CCID, CCID_SUBCLASS_NONE, CCID_PROTOCOL_NONE, None);
+
+        let mut desc = [0; 52];
+        desc[0..2].copy_from_slice(&0x0110u16.to_le_bytes()); // bcdCCID 1.10
+        desc[2] = 0; // bMaxSlotIndex
+        desc[3] = 0x07; // bVoltageSupport: 5V, 3V, 1.8V
+        desc[4..8].copy_from_slice(&2u32.to_le_bytes()); // dwProtocols: T=1
+        desc[8..12].copy_from_slice(&3580u32.to_le_bytes()); // dwDefaultClock (kHz)
+        desc[12..16].copy_from_slice(&3580u32.to_le_bytes()); // dwMaximumClock (kHz)
+        desc[16] = 0; // bNumClockSupported
+        desc[17..21].copy_from_slice(&9600u32.to_le_bytes()); // dwDataRate (bps)
+        desc[21..25].copy_from_slice(&9600u32.to_le_bytes()); // dwMaxDataRate (bps)
+        desc[25] = 0; // bNumDataRatesSupported
+        desc[26..30].copy_from_slice(&254u32.to_le_bytes()); // dwMaxIFSD
+        desc[30..34].copy_from_slice(&0u32.to_le_bytes()); // dwSynchProtocols
+        desc[34..38].copy_from_slice(&0u32.to_le_bytes()); // dwMechanical
+
+        // dwFeatures: automatic activation, voltage, clock, baud rate and parameter
+        // negotiation, extended APDU level exchange.
+        desc[38..42].copy_from_slice(&0x0004_00feu32.to_le_bytes());
+        desc[42..46].copy_from_slice(&max_message_len.to_le_bytes()); // dwMaxCCIDMessageLength
+        desc[46] = 0xff; // bClassGetResponse: echo the class of the APDU
+        desc[47] = 0xff; // bClassEnvelope: echo the class of the APDU
+        desc[48..50].copy_from_slice(&0u16.to_le_bytes()); // wLcdLayout: no LCD
+        desc[50] = 0; // bPINSupport: no PIN pad
+        desc[51] = 1; // bMaxCCIDBusySlots
+        alt.descriptor(CCID_DESC_TYPE_FUNCTIONAL, &desc);
+
+        let read_ep = alt.endpoint_bulk_out(config.max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(config.max_packet_size);
+
+        drop(func);
+
+        let control = state.control.write(Control { if_num });
+        builder.handler(control);
+
+        CcidClass {
+            read_ep,
+            write_ep,
+            atr: config.atr,
+            powered: false,
+            seq: 0,
+        }
+    }
+
+    /// Waits for the host to select the configuration of this class.
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+
+    /// Reads the next command APDU sent to the card into `buf`, returning its length.
+    ///
+    /// All other CCID messages are answered while waiting. Every APDU must be answered with
+    /// [`Self::write_response`] before reading the next one.
+    pub async fn read_apdu(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        loop {
+            let mut header = [0; HEADER_LEN];
+            let len = match self.read_message(&mut header, buf).await? {
+                Some(len) => len,
+                None => {
+                    // The data doesn't fit in `buf`.
+                    self.write_slot_status(&header, COMMAND_STATUS_FAILED, ERROR_BAD_LENGTH)
+                        .await?;
+                    continue;
+                }
+            };
+
+            let icc_status = self.icc_status();
+            let slot = header[5];
+            if slot != 0 {
+                self.write_slot_status(&header, COMMAND_STATUS_FAILED, ERROR_BAD_SLOT)
+                    .await?;
+                continue;
+            }
+
+            match header[0] {
+                PC_TO_RDR_ICC_POWER_ON => {
+                    trace!("ccid: power on");
+                    self.powered = true;
+                    let atr = self.atr;
+                    self.write_message(RDR_TO_PC_DATA_BLOCK, &header, ICC_STATUS_ACTIVE, 0, 0, atr)
+                        .await?;
+                }
+                PC_TO_RDR_ICC_POWER_OFF => {
+                    trace!("ccid: power off");
+                    self.powered = false;
+                    self.write_slot_status(&header, 0, 0).await?;
+                }
+                PC_TO_RDR_GET_SLOT_STATUS | PC_TO_RDR_ABORT => {
+                    self.write_slot_status(&header, 0, 0).await?;
+                }
+                PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS => {
+                    // Only T=1 is supported, and the parameters can't be changed.
+                    let status = if header[0] == PC_TO_RDR_SET_PARAMETERS && header[7] != 1 {
+                        icc_status | COMMAND_STATUS_FAILED
+                    } else {
+                        icc_status
+                    };
+                    self.write_message(RDR_TO_PC_PARAMETERS, &header, status, 0, 1, &T1_PARAMETERS)
+                        .await?;
+                }
+                PC_TO_RDR_XFR_BLOCK if self.powered => {
+                    self.seq = header[6];
+                    return Ok(len);
+                }
+                PC_TO_RDR_XFR_BLOCK => {
+                    warn!("ccid: APDU sent to an unpowered card");
+                    self.write_message(
+                        RDR_TO_PC_DATA_BLOCK,
+                        &header,
+                        icc_status | COMMAND_STATUS_FAILED,
+                        ERROR_ICC_MUTE,
+                        0,
+                        &[],
+                    )
+                    .await?;
+                }
+                kind => {
+                    debug!("ccid: unsupported message {:02x}", kind);
+                    let response = match kind {
+                        PC_TO_RDR_ESCAPE => RDR_TO_PC_ESCAPE,
+                        PC_TO_RDR_SECURE => RDR_TO_PC_DATA_BLOCK,
+                        PC_TO_RDR_SET_DATA_RATE_AND_CLOCK_FREQUENCY => RDR_TO_PC_DATA_RATE_AND_CLOCK_FREQUENCY,
+                        _ => RDR_TO_PC_SLOT_STATUS,
+                    };
+                    self.write_message(
+                        response,
+                        &header,
+                        icc_status | COMMAND_STATUS_FAILED,
+                        ERROR_CMD_NOT_SUPPORTED,
+                        0,
+                        &[],
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+
+    /// Sends the response APDU, including the status word, for the last command APDU.
+    pub async fn write_response(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        let header = self.xfr_header();
+        self.write_message(RDR_TO_PC_DATA_BLOCK, &header, ICC_STATUS_ACTIVE, 0, 0, data)
+            .await
+    }
+
+    /// Asks the host for more time to process the last command APDU.
+    ///
+    /// The host waits for the block waiting time again each time this is sent, so long operations
+    /// (e.g. key generation) should call this periodically until the response is ready.
+    pub async fn request_time_extension(&mut self) -> Result<(), EndpointError> {
+        let header = self.xfr_header();
+        // bError is the multiplier of the block waiting time.
+        self.write_message(
+            RDR_TO_PC_DATA_BLOCK,
+            &header,
+            ICC_STATUS_ACTIVE | COMMAND_STATUS_TIME_EXTENSION,
+            1,
+            0,
+            &[],
+        )
+        .await
+    }
+
+    fn icc_status(&self) -> u8 {
+        if self.powered {
+            ICC_STATUS_ACTIVE
+        } else {
+            ICC_STATUS_INACTIVE
+        }
+    }
+
+    fn xfr_header(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[0] = PC_TO_RDR_XFR_BLOCK;
+        header[6] = self.seq;
+        header
+    }
+
+    /// Reads a message, storing its header in `header` and its data in `buf`.
+    ///
+    /// Returns `None` if the data doesn't fit in `buf`, after discarding it.
+    async fn read_message(
+        &mut self,
+        header: &mut [u8; HEADER_LEN],
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, EndpointError> {
+        let mut packet = [0; MAX_PACKET_SIZE];
+        let max_packet_size = self.read_ep.info().max_packet_size as usize;
+
+        // Wait for a packet starting with a complete header.
+        let mut n = loop {
+            let n = self.read_ep.read(&mut packet).await?;
+            if n >= HEADER_LEN {
+                break n;
+            }
+            warn!("ccid: short message");
+        };
+        header.copy_from_slice(&packet[..HEADER_LEN]);
+
+        let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        let fits = len <= buf.len();
+        let mut pos = 0;
+        let mut data = &packet[HEADER_LEN..n];
+        loop {
+            if fits {
+                let count = data.len().min(len - pos);
+                buf[pos..pos + count].copy_from_slice(&data[..count]);
+            }
+            pos += data.len();
+
+            // A short packet ends the transfer.
+            if pos >= len || n < max_packet_size {
+                break;
+            }
+            n = self.read_ep.read(&mut packet).await?;
+            data = &packet[..n];
+        }
+
+        if !fits {
+            warn!("ccid: message too long ({} bytes)", len);
+            return Ok(None);
+        }
+        // Truncated messages only return what was received.
+        Ok(Some(pos.min(len)))
+    }
+
+    async fn write_slot_status(
+        &mut self,
+        header: &[u8; HEADER_LEN],
+        command_status: u8,
+        error: u8,
+    ) -> Result<(), EndpointError> {
+        let status = self.icc_status() | command_status;
+        self.write_message(RDR_TO_PC_SLOT_STATUS, header, status, error, 0, &[])
+            .await
+    }
+
+    /// Writes a RDR_to_PC message answering the message with `header`.
+    async fn write_message(
+        &mut self,
+        kind: u8,
+        header: &[u8; HEADER_LEN],
+        status: u8,
+        error: u8,
+        specific: u8,
+        data: &[u8],
+    ) -> Result<(), EndpointError> {
+        let max_packet_size = self.write_ep.info().max_packet_size as usize;
+
+        let mut packet = [0; MAX_PACKET_SIZE];
+        packet[0] = kind;
+        packet[1..5].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        packet[5] = header[5]; // bSlot
+        packet[6] = header[6]; // bSeq
+        packet[7] = status;
+        packet[8] = error;
+        packet[9] = specific;
+
+        let first = data.len().min(max_packet_size - HEADER_LEN);
+        packet[HEADER_LEN..HEADER_LEN + first].copy_from_slice(&data[..first]);
+        self.write_ep.write(&packet[..HEADER_LEN + first]).await?;
+
+        for chunk in data[first..].chunks(max_packet_size) {
+            self.write_ep.write(chunk).await?;
+        }
+
+        // End the transfer with a zero-length packet if the last packet was full.
+        if (HEADER_LEN + data.len()) % max_packet_size == 0 {
+            self.write_ep.write(&[]).await?;
+        }
+        Ok(())
+    }
+}
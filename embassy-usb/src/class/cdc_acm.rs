@@ -4,7 +4,9 @@ use core::cell::Cell;
 use core::mem::{self, MaybeUninit};
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_sync::channel::Channel;
 
 use crate::control::{self, InResponse, OutResponse, Recipient, Request, RequestType};
 use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
@@ -29,6 +31,12 @@ const REQ_GET_ENCAPSULATED_COMMAND: u8 = 0x01;
 const REQ_SET_LINE_CODING: u8 = 0x20;
 const REQ_GET_LINE_CODING: u8 = 0x21;
 const REQ_SET_CONTROL_LINE_STATE: u8 = 0x22;
+const REQ_SEND_BREAK: u8 = 0x23;
+
+const NOTIFICATION_SERIAL_STATE: u8 = 0x20;
+
+/// Number of control events buffered until the application handles them.
+const CONTROL_EVENT_COUNT: usize = 4;
 
 /// Internal state for CDC-ACM
 pub struct State<'a> {
@@ -59,7 +67,8 @@ impl<'a> State<'a> {
 ///   can be sent if there is no other data to send. This is because USB bulk transactions must be
 ///   terminated with a short packet, even if the bulk endpoint is used for stream-like data.
 pub struct CdcAcmClass<'d, D: Driver<'d>> {
-    _comm_ep: D::EndpointIn,
+    comm_ep: D::EndpointIn,
+    comm_if: InterfaceNumber,
     _data_if: InterfaceNumber,
     read_ep: D::EndpointOut,
     write_ep: D::EndpointIn,
@@ -76,6 +85,7 @@ struct ControlShared {
     line_coding: CriticalSectionMutex<Cell<LineCoding>>,
     dtr: AtomicBool,
     rts: AtomicBool,
+    events: Channel<CriticalSectionRawMutex, ControlEvent, CONTROL_EVENT_COUNT>,
 }
 
 impl Default for ControlShared {
@@ -83,6 +93,7 @@ impl Default for ControlShared {
         ControlShared {
             dtr: AtomicBool::new(false),
             rts: AtomicBool::new(false),
+            events: Channel::new(),
             line_coding: CriticalSectionMutex::new(Cell::new(LineCoding {
                 stop_bits: StopBits::One,
                 data_bits: 8,
@@ -93,6 +104,16 @@ impl Default for ControlShared {
     }
 }
 
+impl ControlShared {
+    fn push_event(&self, event: ControlEvent) {
+        // The current line coding and line state can always be read, so dropping an event only
+        // loses the notification, not the state.
+        if self.events.try_send(event).is_err() {
+            trace!("CDC ACM: control event queue full, dropping {:?}", event);
+        }
+    }
+}
+
 impl<'a> Control<'a> {
     fn shared(&mut self) -> &'a ControlShared {
         self.shared
@@ -127,7 +148,9 @@ impl<'d> Handler for Control<'d> {
                     parity_type: data[5].into(),
                     data_bits: data[6],
                 };
-                self.shared().line_coding.lock(|x| x.set(coding));
+                let shared = self.shared();
+                shared.line_coding.lock(|x| x.set(coding));
+                shared.push_event(ControlEvent::LineCoding(coding));
                 debug!("Set line coding to: {:?}", coding);
 
                 Some(OutResponse::Accepted)
@@ -139,10 +162,22 @@ impl<'d> Handler for Control<'d> {
                 let shared = self.shared();
                 shared.dtr.store(dtr, Ordering::Relaxed);
                 shared.rts.store(rts, Ordering::Relaxed);
+                shared.push_event(ControlEvent::LineState { dtr, rts });
                 debug!("Set dtr {}, rts {}", dtr, rts);
 
                 Some(OutResponse::Accepted)
             }
+            REQ_SEND_BREAK => {
+                let duration = match req.value {
+                    0 => BreakDuration::Stop,
+                    0xFFFF => BreakDuration::UntilStopped,
+                    ms => BreakDuration::Millis(ms),
+                };
+                self.shared().push_event(ControlEvent::Break(duration));
+                debug!("Send break {:?}", duration);
+
+                Some(OutResponse::Accepted)
+            }
             _ => Some(OutResponse::Rejected),
         }
     }
@@ -197,10 +232,11 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
             CS_INTERFACE,
             &[
                 CDC_TYPE_ACM, // bDescriptorSubtype
-                0x02,         // bmCapabilities:
+                0x06,         // bmCapabilities:
                               // D1: Device supports the request combination of
                               // Set_Line_Coding, Set_Control_Line_State, Get_Line_Coding,
                               // and the Notification Serial_State.
+                              // D2: Device supports the request Send_Break.
             ],
         );
         alt.descriptor(
@@ -232,7 +268,8 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
         let control_shared = &state.shared;
 
         CdcAcmClass {
-            _comm_ep: comm_ep,
+            comm_ep,
+            comm_if,
             _data_if: data_if,
             read_ep,
             write_ep,
@@ -262,6 +299,21 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
         self.control.rts.load(Ordering::Relaxed)
     }
 
+    /// Waits for the host to change the line coding or line state, or to send a break.
+    ///
+    /// Events are buffered, but only a few of them. If the application doesn't keep up, events
+    /// are dropped. The current state is still available from [`line_coding`](Self::line_coding),
+    /// [`dtr`](Self::dtr) and [`rts`](Self::rts).
+    pub async fn wait_control_event(&self) -> ControlEvent {
+        self.control.events.receive().await
+    }
+
+    /// Sends a SERIAL_STATE notification to the host, reporting the state of the modem input
+    /// lines and UART errors.
+    pub async fn send_serial_state(&mut self, state: SerialState) -> Result<(), EndpointError> {
+        send_serial_state::<D>(&mut self.comm_ep, self.comm_if, state).await
+    }
+
     /// Writes a single packet into the IN endpoint.
     pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
         self.write_ep.write(data).await
@@ -281,6 +333,14 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
     ///
     /// This allows concurrently sending and receiving packets from separate tasks.
     pub fn split(self) -> (Sender<'d, D>, Receiver<'d, D>) {
+        let (sender, receiver, _) = self.split_with_control();
+        (sender, receiver)
+    }
+
+    /// Split the class into a sender, a receiver and a handle for the control lines.
+    ///
+    /// This allows handling modem line changes in a separate task from the data.
+    pub fn split_with_control(self) -> (Sender<'d, D>, Receiver<'d, D>, ControlLines<'d, D>) {
         (
             Sender {
                 write_ep: self.write_ep,
@@ -290,10 +350,75 @@ impl<'d, D: Driver<'d>> CdcAcmClass<'d, D> {
                 read_ep: self.read_ep,
                 control: self.control,
             },
+            ControlLines {
+                comm_ep: self.comm_ep,
+                comm_if: self.comm_if,
+                control: self.control,
+            },
         )
     }
 }
 
+async fn send_serial_state<'d, D: Driver<'d>>(
+    comm_ep: &mut D::EndpointIn,
+    comm_if: InterfaceNumber,
+    state: SerialState,
+) -> Result<(), EndpointError> {
+    let mut buf = [0; 10];
+    buf[0] = 0xA1; // bmRequestType: device to host, class, interface
+    buf[1] = NOTIFICATION_SERIAL_STATE;
+    // wValue is zero.
+    buf[4..6].copy_from_slice(&(u8::from(comm_if) as u16).to_le_bytes());
+    buf[6..8].copy_from_slice(&2u16.to_le_bytes()); // wLength
+    buf[8..10].copy_from_slice(&state.bits().to_le_bytes());
+
+    // The notification is longer than the endpoint's max packet size.
+    for chunk in buf.chunks(comm_ep.info().max_packet_size as usize) {
+        comm_ep.write(chunk).await?;
+    }
+    Ok(())
+}
+
+/// CDC ACM class control line handle.
+///
+/// You can obtain a `ControlLines` with [`CdcAcmClass::split_with_control`]
+pub struct ControlLines<'d, D: Driver<'d>> {
+    comm_ep: D::EndpointIn,
+    comm_if: InterfaceNumber,
+    control: &'d ControlShared,
+}
+
+impl<'d, D: Driver<'d>> ControlLines<'d, D> {
+    /// Gets the current line coding.
+    pub fn line_coding(&self) -> LineCoding {
+        self.control.line_coding.lock(|x| x.get())
+    }
+
+    /// Gets the DTR (data terminal ready) state
+    pub fn dtr(&self) -> bool {
+        self.control.dtr.load(Ordering::Relaxed)
+    }
+
+    /// Gets the RTS (request to send) state
+    pub fn rts(&self) -> bool {
+        self.control.rts.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the host to change the line coding or line state, or to send a break.
+    ///
+    /// See [`CdcAcmClass::wait_control_event`].
+    pub async fn wait_control_event(&self) -> ControlEvent {
+        self.control.events.receive().await
+    }
+
+    /// Sends a SERIAL_STATE notification to the host.
+    ///
+    /// See [`CdcAcmClass::send_serial_state`].
+    pub async fn send_serial_state(&mut self, state: SerialState) -> Result<(), EndpointError> {
+        send_serial_state::<D>(&mut self.comm_ep, self.comm_if, state).await
+    }
+}
+
 /// CDC ACM class packet sender.
 ///
 /// You can obtain a `Sender` with [`CdcAcmClass::split`]
@@ -378,6 +503,67 @@ impl<'d, D: Driver<'d>> Receiver<'d, D> {
     }
 }
 
+/// Control request received from the host.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ControlEvent {
+    /// The host changed the line coding with SET_LINE_CODING.
+    LineCoding(LineCoding),
+    /// The host changed the DTR and RTS lines with SET_CONTROL_LINE_STATE.
+    LineState {
+        /// Data terminal ready.
+        dtr: bool,
+        /// Request to send.
+        rts: bool,
+    },
+    /// The host requested a break condition on the line with SEND_BREAK.
+    Break(BreakDuration),
+}
+
+/// Duration of a break requested by the host.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakDuration {
+    /// End a break that is in progress.
+    Stop,
+    /// Hold the break condition for the given number of milliseconds.
+    Millis(u16),
+    /// Hold the break condition until the host sends [`BreakDuration::Stop`].
+    UntilStopped,
+}
+
+/// UART state reported to the host with a SERIAL_STATE notification.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SerialState {
+    /// Data carrier detect (bRxCarrier).
+    pub dcd: bool,
+    /// Data set ready (bTxCarrier).
+    pub dsr: bool,
+    /// A break condition was detected.
+    pub break_detected: bool,
+    /// Ring indicator.
+    pub ring: bool,
+    /// A framing error occurred.
+    pub framing_error: bool,
+    /// A parity error occurred.
+    pub parity_error: bool,
+    /// Received data was discarded due to an overrun.
+    pub overrun: bool,
+}
+
+impl SerialState {
+    fn bits(&self) -> u16 {
+        (self.dcd as u16)
+            | (self.dsr as u16) << 1
+            | (self.break_detected as u16) << 2
+            | (self.ring as u16) << 3
+            | (self.framing_error as u16) << 4
+            | (self.parity_error as u16) << 5
+            | (self.overrun as u16) << 6
+    }
+}
+
 /// Number of stop bits for LineCoding
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -0,0 +1,397 @@
+//! HID report descriptor building blocks.
+//!
+//! [`ReportDescriptorBuilder`] encodes short items into a caller-provided buffer, so descriptors
+//! can be generated at runtime without `usbd-hid`. The module also contains the fixed boot
+//! protocol descriptors and reports for keyboards and mice, and a consumer control descriptor.
+
+/// Generic Desktop usage page.
+pub const USAGE_PAGE_GENERIC_DESKTOP: u16 = 0x01;
+/// Simulation Controls usage page.
+pub const USAGE_PAGE_SIMULATION: u16 = 0x02;
+/// Keyboard/Keypad usage page.
+pub const USAGE_PAGE_KEYBOARD: u16 = 0x07;
+/// LED usage page.
+pub const USAGE_PAGE_LED: u16 = 0x08;
+/// Button usage page.
+pub const USAGE_PAGE_BUTTON: u16 = 0x09;
+/// Consumer usage page.
+pub const USAGE_PAGE_CONSUMER: u16 = 0x0C;
+
+/// Generic Desktop: Pointer.
+pub const USAGE_POINTER: u16 = 0x01;
+/// Generic Desktop: Mouse.
+pub const USAGE_MOUSE: u16 = 0x02;
+/// Generic Desktop: Joystick.
+pub const USAGE_JOYSTICK: u16 = 0x04;
+/// Generic Desktop: Gamepad.
+pub const USAGE_GAMEPAD: u16 = 0x05;
+/// Generic Desktop: Keyboard.
+pub const USAGE_KEYBOARD: u16 = 0x06;
+/// Generic Desktop: X axis.
+pub const USAGE_X: u16 = 0x30;
+/// Generic Desktop: Y axis.
+pub const USAGE_Y: u16 = 0x31;
+/// Generic Desktop: Z axis.
+pub const USAGE_Z: u16 = 0x32;
+/// Generic Desktop: X rotation.
+pub const USAGE_RX: u16 = 0x33;
+/// Generic Desktop: Y rotation.
+pub const USAGE_RY: u16 = 0x34;
+/// Generic Desktop: Z rotation.
+pub const USAGE_RZ: u16 = 0x35;
+/// Generic Desktop: Slider.
+pub const USAGE_SLIDER: u16 = 0x36;
+/// Generic Desktop: Dial.
+pub const USAGE_DIAL: u16 = 0x37;
+/// Generic Desktop: Wheel.
+pub const USAGE_WHEEL: u16 = 0x38;
+/// Generic Desktop: Hat switch.
+pub const USAGE_HAT_SWITCH: u16 = 0x39;
+/// Consumer: Consumer Control.
+pub const USAGE_CONSUMER_CONTROL: u16 = 0x01;
+
+/// Main item flag: the field is constant (padding) instead of data.
+pub const ITEM_CONSTANT: u8 = 0x01;
+/// Main item flag: each field is a variable instead of an array of usages.
+pub const ITEM_VARIABLE: u8 = 0x02;
+/// Main item flag: values are relative to the previous report.
+pub const ITEM_RELATIVE: u8 = 0x04;
+/// Main item flag: values wrap around.
+pub const ITEM_WRAP: u8 = 0x08;
+/// Main item flag: values outside the logical range mean "no input".
+pub const ITEM_NULL_STATE: u8 = 0x40;
+
+/// Collection type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Collection {
+    /// Physical collection, e.g. a group of axes.
+    Physical = 0x00,
+    /// Application collection, the top level of a report.
+    Application = 0x01,
+    /// Logical collection.
+    Logical = 0x02,
+}
+
+// Item prefixes, without the size bits.
+const MAIN_INPUT: u8 = 0x80;
+const MAIN_OUTPUT: u8 = 0x90;
+const MAIN_FEATURE: u8 = 0xB0;
+const MAIN_COLLECTION: u8 = 0xA0;
+const MAIN_END_COLLECTION: u8 = 0xC0;
+const GLOBAL_USAGE_PAGE: u8 = 0x04;
+const GLOBAL_LOGICAL_MINIMUM: u8 = 0x14;
+const GLOBAL_LOGICAL_MAXIMUM: u8 = 0x24;
+const GLOBAL_PHYSICAL_MINIMUM: u8 = 0x34;
+const GLOBAL_PHYSICAL_MAXIMUM: u8 = 0x44;
+const GLOBAL_UNIT_EXPONENT: u8 = 0x54;
+const GLOBAL_UNIT: u8 = 0x64;
+const GLOBAL_REPORT_SIZE: u8 = 0x74;
+const GLOBAL_REPORT_ID: u8 = 0x84;
+const GLOBAL_REPORT_COUNT: u8 = 0x94;
+const LOCAL_USAGE: u8 = 0x08;
+const LOCAL_USAGE_MINIMUM: u8 = 0x18;
+const LOCAL_USAGE_MAXIMUM: u8 = 0x28;
+
+/// Writes a HID report descriptor into a buffer.
+///
+/// Every item is encoded with the smallest data size that holds its value.
+///
+/// # Panics
+///
+/// Panics if the buffer is too small.
+pub struct ReportDescriptorBuilder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> ReportDescriptorBuilder<'a> {
+    /// Create a new builder writing into `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the finished report descriptor.
+    pub fn finish(self) -> &'a [u8] {
+        &self.buf[..self.len]
+    }
+
+    fn item(&mut self, prefix: u8, data: &[u8]) -> &mut Self {
+        let size = match data.len() {
+            0 => 0,
+            1 => 1,
+            2 => 2,
+            4 => 3,
+            _ => unreachable!(),
+        };
+        if self.len + 1 + data.len() > self.buf.len() {
+            panic!("HID report descriptor buffer full");
+        }
+        self.buf[self.len] = prefix | size;
+        self.buf[self.len + 1..self.len + 1 + data.len()].copy_from_slice(data);
+        self.len += 1 + data.len();
+        self
+    }
+
+    fn unsigned(&mut self, prefix: u8, value: u32) -> &mut Self {
+        let bytes = value.to_le_bytes();
+        if value == 0 {
+            self.item(prefix, &[])
+        } else if value <= 0xFF {
+            self.item(prefix, &bytes[..1])
+        } else if value <= 0xFFFF {
+            self.item(prefix, &bytes[..2])
+        } else {
+            self.item(prefix, &bytes)
+        }
+    }
+
+    fn signed(&mut self, prefix: u8, value: i32) -> &mut Self {
+        let bytes = value.to_le_bytes();
+        if value == 0 {
+            self.item(prefix, &[])
+        } else if i8::try_from(value).is_ok() {
+            self.item(prefix, &bytes[..1])
+        } else if i16::try_from(value).is_ok() {
+            self.item(prefix, &bytes[..2])
+        } else {
+            self.item(prefix, &bytes)
+        }
+    }
+
+    /// Usage Page item.
+    pub fn usage_page(&mut self, page: u16) -> &mut Self {
+        self.unsigned(GLOBAL_USAGE_PAGE, page.into())
+    }
+
+    /// Usage item.
+    pub fn usage(&mut self, usage: u16) -> &mut Self {
+        self.unsigned(LOCAL_USAGE, usage.into())
+    }
+
+    /// Usage Minimum item.
+    pub fn usage_minimum(&mut self, usage: u16) -> &mut Self {
+        self.unsigned(LOCAL_USAGE_MINIMUM, usage.into())
+    }
+
+    /// Usage Maximum item.
+    pub fn usage_maximum(&mut self, usage: u16) -> &mut Self {
+        self.unsigned(LOCAL_USAGE_MAXIMUM, usage.into())
+    }
+
+    /// Logical Minimum item.
+    pub fn logical_minimum(&mut self, value: i32) -> &mut Self {
+        self.signed(GLOBAL_LOGICAL_MINIMUM, value)
+    }
+
+    /// Logical Maximum item.
+    pub fn logical_maximum(&mut self, value: i32) -> &mut Self {
+        self.signed(GLOBAL_LOGICAL_MAXIMUM, value)
+    }
+
+    /// Physical Minimum item.
+    pub fn physical_minimum(&mut self, value: i32) -> &mut Self {
+        self.signed(GLOBAL_PHYSICAL_MINIMUM, value)
+    }
+
+    /// Physical Maximum item.
+    pub fn physical_maximum(&mut self, value: i32) -> &mut Self {
+        self.signed(GLOBAL_PHYSICAL_MAXIMUM, value)
+    }
+
+    /// Unit Exponent item.
+    pub fn unit_exponent(&mut self, exponent: i8) -> &mut Self {
+        self.unsigned(GLOBAL_UNIT_EXPONENT, u32::from(exponent as u8 & 0x0F))
+    }
+
+    /// Unit item.
+    pub fn unit(&mut self, unit: u32) -> &mut Self {
+        self.unsigned(GLOBAL_UNIT, unit)
+    }
+
+    /// Report Size item, in bits.
+    pub fn report_size(&mut self, bits: u8) -> &mut Self {
+        self.unsigned(GLOBAL_REPORT_SIZE, bits.into())
+    }
+
+    /// Report Count item.
+    pub fn report_count(&mut self, count: u8) -> &mut Self {
+        self.unsigned(GLOBAL_REPORT_COUNT, count.into())
+    }
+
+    /// Report ID item.
+    ///
+    /// When used, every report of the interface is prefixed by its ID byte.
+    pub fn report_id(&mut self, id: u8) -> &mut Self {
+        assert!(id != 0, "report ID 0 is reserved");
+        self.unsigned(GLOBAL_REPORT_ID, id.into())
+    }
+
+    /// Input item. `flags` is a combination of the `ITEM_*` constants.
+    pub fn input(&mut self, flags: u8) -> &mut Self {
+        self.unsigned(MAIN_INPUT, flags.into())
+    }
+
+    /// Output item. `flags` is a combination of the `ITEM_*` constants.
+    pub fn output(&mut self, flags: u8) -> &mut Self {
+        self.unsigned(MAIN_OUTPUT, flags.into())
+    }
+
+    /// Feature item. `flags` is a combination of the `ITEM_*` constants.
+    pub fn feature(&mut self, flags: u8) -> &mut Self {
+        self.unsigned(MAIN_FEATURE, flags.into())
+    }
+
+    /// Collection item. Must be matched with [`end_collection`](Self::end_collection).
+    pub fn collection(&mut self, kind: Collection) -> &mut Self {
+        self.item(MAIN_COLLECTION, &[kind as u8])
+    }
+
+    /// End Collection item.
+    pub fn end_collection(&mut self) -> &mut Self {
+        self.item(MAIN_END_COLLECTION, &[])
+    }
+
+    /// Constant padding of `bits` bits in the input report.
+    pub fn input_padding(&mut self, bits: u8) -> &mut Self {
+        self.report_size(1).report_count(bits).input(ITEM_CONSTANT)
+    }
+}
+
+/// Boot keyboard report descriptor, from appendix B.1 of the HID spec.
+///
+/// The input report is a [`BootKeyboardReport`], the output report is one byte of LED state.
+pub const BOOT_KEYBOARD: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xA1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0xE0, //   Usage Minimum (Left Control)
+    0x29, 0xE7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute): modifier byte
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x08, //   Report Size (8)
+    0x81, 0x01, //   Input (Constant): reserved byte
+    0x95, 0x05, //   Report Count (5)
+    0x75, 0x01, //   Report Size (1)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x91, 0x02, //   Output (Data, Variable, Absolute): LED report
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x03, //   Report Size (3)
+    0x91, 0x01, //   Output (Constant): LED report padding
+    0x95, 0x06, //   Report Count (6)
+    0x75, 0x08, //   Report Size (8)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x65, //   Logical Maximum (101)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0x65, //   Usage Maximum (101)
+    0x81, 0x00, //   Input (Data, Array): key codes
+    0xC0, // End Collection
+];
+
+/// Boot mouse report descriptor, from appendix B.2 of the HID spec.
+///
+/// The input report is a [`BootMouseReport`].
+pub const BOOT_MOUSE: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x02, // Usage (Mouse)
+    0xA1, 0x01, // Collection (Application)
+    0x09, 0x01, //   Usage (Pointer)
+    0xA1, 0x00, //   Collection (Physical)
+    0x05, 0x09, //     Usage Page (Button)
+    0x19, 0x01, //     Usage Minimum (1)
+    0x29, 0x03, //     Usage Maximum (3)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x25, 0x01, //     Logical Maximum (1)
+    0x95, 0x03, //     Report Count (3)
+    0x75, 0x01, //     Report Size (1)
+    0x81, 0x02, //     Input (Data, Variable, Absolute): buttons
+    0x95, 0x01, //     Report Count (1)
+    0x75, 0x05, //     Report Size (5)
+    0x81, 0x01, //     Input (Constant): padding
+    0x05, 0x01, //     Usage Page (Generic Desktop)
+    0x09, 0x30, //     Usage (X)
+    0x09, 0x31, //     Usage (Y)
+    0x15, 0x81, //     Logical Minimum (-127)
+    0x25, 0x7F, //     Logical Maximum (127)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x02, //     Report Count (2)
+    0x81, 0x06, //     Input (Data, Variable, Relative): X, Y
+    0xC0, //   End Collection
+    0xC0, // End Collection
+];
+
+/// Consumer control report descriptor.
+///
+/// The input report is a single little-endian 16-bit consumer usage code, e.g. `0xE9` for
+/// Volume Increment, or 0 when nothing is pressed.
+pub const CONSUMER_CONTROL: &[u8] = &[
+    0x05, 0x0C, // Usage Page (Consumer)
+    0x09, 0x01, // Usage (Consumer Control)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x03, //   Logical Maximum (0x3FF)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x2A, 0xFF, 0x03, //   Usage Maximum (0x3FF)
+    0x75, 0x10, //   Report Size (16)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array)
+    0xC0, // End Collection
+];
+
+/// Boot keyboard input report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootKeyboardReport {
+    /// Modifier bits, left control in bit 0 up to right GUI in bit 7.
+    pub modifier: u8,
+    /// Usage codes of up to 6 pressed keys, 0 for unused slots.
+    pub keycodes: [u8; 6],
+}
+
+impl BootKeyboardReport {
+    /// Serialize into the 8-byte boot format.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut buf = [0; 8];
+        buf[0] = self.modifier;
+        buf[2..].copy_from_slice(&self.keycodes);
+        buf
+    }
+}
+
+/// Boot mouse input report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootMouseReport {
+    /// Button bits, left button in bit 0.
+    pub buttons: u8,
+    /// Relative X movement.
+    pub x: i8,
+    /// Relative Y movement.
+    pub y: i8,
+}
+
+impl BootMouseReport {
+    /// Serialize into the 3-byte boot format.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.buttons, self.x as u8, self.y as u8]
+    }
+}
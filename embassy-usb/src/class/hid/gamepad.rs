@@ -0,0 +1,273 @@
+//! Gamepad and joystick report descriptors.
+//!
+//! A [`GamepadConfig`] describes the controls of the device. It generates the matching report
+//! descriptor with [`GamepadConfig::report_descriptor`] and packs a [`GamepadReport`] into the
+//! wire format with [`GamepadConfig::serialize`].
+//!
+//! The input report is laid out as follows:
+//! - the report ID, if one is configured,
+//! - one bit per button, padded to a whole byte,
+//! - every axis as a little-endian `i16` in the range `-32767..=32767`,
+//! - the hat switch in the low 4 bits of one byte, if enabled.
+
+use super::descriptor::*;
+
+/// Maximum number of buttons.
+pub const MAX_BUTTONS: u8 = 32;
+/// Maximum number of axes.
+pub const MAX_AXES: usize = 9;
+
+/// Size of a report descriptor buffer that fits any [`GamepadConfig`].
+pub const MAX_REPORT_DESCRIPTOR_SIZE: usize = 128;
+
+/// Top-level usage of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GamepadKind {
+    /// A gamepad, thumb sticks and buttons.
+    Gamepad,
+    /// A joystick or flight stick.
+    Joystick,
+}
+
+/// Analog axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Axis {
+    /// X axis.
+    X,
+    /// Y axis.
+    Y,
+    /// Z axis.
+    Z,
+    /// X rotation.
+    Rx,
+    /// Y rotation.
+    Ry,
+    /// Z rotation.
+    Rz,
+    /// Slider.
+    Slider,
+    /// Dial.
+    Dial,
+    /// Wheel.
+    Wheel,
+}
+
+impl Axis {
+    fn usage(self) -> u16 {
+        match self {
+            Axis::X => USAGE_X,
+            Axis::Y => USAGE_Y,
+            Axis::Z => USAGE_Z,
+            Axis::Rx => USAGE_RX,
+            Axis::Ry => USAGE_RY,
+            Axis::Rz => USAGE_RZ,
+            Axis::Slider => USAGE_SLIDER,
+            Axis::Dial => USAGE_DIAL,
+            Axis::Wheel => USAGE_WHEEL,
+        }
+    }
+}
+
+/// Hat switch (D-pad) position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hat {
+    /// Not pressed.
+    #[default]
+    Centered,
+    /// Up.
+    Up,
+    /// Up and right.
+    UpRight,
+    /// Right.
+    Right,
+    /// Down and right.
+    DownRight,
+    /// Down.
+    Down,
+    /// Down and left.
+    DownLeft,
+    /// Left.
+    Left,
+    /// Up and left.
+    UpLeft,
+}
+
+impl Hat {
+    fn value(self) -> u8 {
+        match self {
+            Hat::Up => 0,
+            Hat::UpRight => 1,
+            Hat::Right => 2,
+            Hat::DownRight => 3,
+            Hat::Down => 4,
+            Hat::DownLeft => 5,
+            Hat::Left => 6,
+            Hat::UpLeft => 7,
+            // Outside the logical range, reported as the null state.
+            Hat::Centered => 8,
+        }
+    }
+}
+
+/// Gamepad or joystick layout.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GamepadConfig<'a> {
+    /// Top-level usage.
+    pub kind: GamepadKind,
+    /// Report ID, needed when the interface carries other reports too.
+    pub report_id: Option<u8>,
+    /// Number of buttons, at most [`MAX_BUTTONS`].
+    pub buttons: u8,
+    /// Axes, at most [`MAX_AXES`].
+    pub axes: &'a [Axis],
+    /// Whether the device has a hat switch.
+    pub hat_switch: bool,
+}
+
+impl<'a> Default for GamepadConfig<'a> {
+    fn default() -> Self {
+        Self {
+            kind: GamepadKind::Gamepad,
+            report_id: None,
+            buttons: 16,
+            axes: &[Axis::X, Axis::Y, Axis::Rx, Axis::Ry],
+            hat_switch: true,
+        }
+    }
+}
+
+/// State of a gamepad or joystick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GamepadReport {
+    /// Button bits, button 1 in bit 0.
+    pub buttons: u32,
+    /// Axis values, in the order of [`GamepadConfig::axes`].
+    pub axes: [i16; MAX_AXES],
+    /// Hat switch position. Ignored if the config has no hat switch.
+    pub hat: Hat,
+}
+
+impl<'a> GamepadConfig<'a> {
+    fn check(&self) {
+        assert!(self.buttons <= MAX_BUTTONS);
+        assert!(self.axes.len() <= MAX_AXES);
+    }
+
+    /// Writes the report descriptor into `buf`, returning the written part.
+    ///
+    /// A buffer of [`MAX_REPORT_DESCRIPTOR_SIZE`] bytes is always large enough.
+    pub fn report_descriptor<'b>(&self, buf: &'b mut [u8]) -> &'b [u8] {
+        self.check();
+
+        let mut w = ReportDescriptorBuilder::new(buf);
+        w.usage_page(USAGE_PAGE_GENERIC_DESKTOP)
+            .usage(match self.kind {
+                GamepadKind::Gamepad => USAGE_GAMEPAD,
+                GamepadKind::Joystick => USAGE_JOYSTICK,
+            })
+            .collection(Collection::Application);
+
+        if let Some(id) = self.report_id {
+            w.report_id(id);
+        }
+
+        if self.buttons > 0 {
+            w.usage_page(USAGE_PAGE_BUTTON)
+                .usage_minimum(1)
+                .usage_maximum(self.buttons.into())
+                .logical_minimum(0)
+                .logical_maximum(1)
+                .report_size(1)
+                .report_count(self.buttons)
+                .input(ITEM_VARIABLE);
+            if self.buttons % 8 != 0 {
+                w.input_padding(8 - self.buttons % 8);
+            }
+        }
+
+        if !self.axes.is_empty() {
+            w.usage_page(USAGE_PAGE_GENERIC_DESKTOP)
+                .usage(USAGE_POINTER)
+                .collection(Collection::Physical);
+            for axis in self.axes {
+                w.usage(axis.usage());
+            }
+            w.logical_minimum(-32767)
+                .logical_maximum(32767)
+                .report_size(16)
+                .report_count(self.axes.len() as u8)
+                .input(ITEM_VARIABLE)
+                .end_collection();
+        }
+
+        if self.hat_switch {
+            w.usage_page(USAGE_PAGE_GENERIC_DESKTOP)
+                .usage(USAGE_HAT_SWITCH)
+                .logical_minimum(0)
+                .logical_maximum(7)
+                .physical_minimum(0)
+                .physical_maximum(315)
+                // English rotation, degrees
+                .unit(0x14)
+                .report_size(4)
+                .report_count(1)
+                .input(ITEM_VARIABLE | ITEM_NULL_STATE)
+                .unit(0)
+                .input_padding(4);
+        }
+
+        w.end_collection();
+        w.finish()
+    }
+
+    /// Length of a serialized report in bytes, including the report ID.
+    pub fn report_len(&self) -> usize {
+        self.report_id.is_some() as usize
+            + (self.buttons as usize + 7) / 8
+            + self.axes.len() * 2
+            + self.hat_switch as usize
+    }
+
+    /// Packs `report` into `buf`, returning the report length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is shorter than [`report_len`](Self::report_len).
+    pub fn serialize(&self, report: &GamepadReport, buf: &mut [u8]) -> usize {
+        self.check();
+        let len = self.report_len();
+        let buf = &mut buf[..len];
+        let mut pos = 0;
+
+        if let Some(id) = self.report_id {
+            buf[pos] = id;
+            pos += 1;
+        }
+
+        let button_bytes = (self.buttons as usize + 7) / 8;
+        let buttons = match self.buttons {
+            MAX_BUTTONS => report.buttons,
+            n => report.buttons & ((1 << n) - 1),
+        };
+        buf[pos..pos + button_bytes].copy_from_slice(&buttons.to_le_bytes()[..button_bytes]);
+        pos += button_bytes;
+
+        for value in &report.axes[..self.axes.len()] {
+            // -32768 is outside the logical range.
+            let value = (*value).max(-32767);
+            buf[pos..pos + 2].copy_from_slice(&value.to_le_bytes());
+            pos += 2;
+        }
+
+        if self.hat_switch {
+            buf[pos] = report.hat.value();
+        }
+
+        len
+    }
+}
@@ -1,8 +1,13 @@
 //! USB HID (Human Interface Device) class implementation.
+//!
+//! Every [`HidReaderWriter`] or [`HidWriter`] adds one HID interface to the device. To build a
+//! composite device, e.g. keyboard + mouse + consumer control, create one per function, each with
+//! its own [`State`] and report descriptor. Only keyboard and mouse interfaces should set
+//! [`Config::boot_protocol`], and a device should have at most one of each.
 
 use core::mem::MaybeUninit;
 use core::ops::Range;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 
 #[cfg(feature = "usbd-hid")]
 use ssmarshal::serialize;
@@ -14,9 +19,12 @@ use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
 use crate::types::InterfaceNumber;
 use crate::{Builder, Handler};
 
+pub mod descriptor;
+pub mod gamepad;
+
 const USB_CLASS_HID: u8 = 0x03;
 const USB_SUBCLASS_NONE: u8 = 0x00;
-const USB_PROTOCOL_NONE: u8 = 0x00;
+const USB_SUBCLASS_BOOT: u8 = 0x01;
 
 // HID
 const HID_DESC_DESCTYPE_HID: u8 = 0x21;
@@ -48,6 +56,67 @@ pub struct Config<'d> {
 
     /// Max packet size for both the IN and OUT endpoints.
     pub max_packet_size: u16,
+
+    /// Boot protocol supported by this interface.
+    ///
+    /// Anything other than [`HidBootProtocol::None`] marks the interface as a boot device, so
+    /// BIOSes and other simple hosts can use it without parsing the report descriptor. While the
+    /// host has the interface in [`HidProtocolMode::Boot`], reports must use the fixed boot
+    /// format from appendix B of the HID spec, see [`descriptor::BOOT_KEYBOARD`] and
+    /// [`descriptor::BOOT_MOUSE`].
+    pub boot_protocol: HidBootProtocol,
+}
+
+impl<'d> Default for Config<'d> {
+    fn default() -> Self {
+        Self {
+            report_descriptor: &[],
+            request_handler: None,
+            poll_ms: 10,
+            max_packet_size: 64,
+            boot_protocol: HidBootProtocol::default(),
+        }
+    }
+}
+
+/// Boot protocol advertised by a HID interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HidBootProtocol {
+    /// Not a boot device.
+    None = 0x00,
+    /// Boot keyboard.
+    Keyboard = 0x01,
+    /// Boot mouse.
+    Mouse = 0x02,
+}
+
+impl Default for HidBootProtocol {
+    fn default() -> Self {
+        HidBootProtocol::None
+    }
+}
+
+/// Protocol currently selected by the host with `SET_PROTOCOL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HidProtocolMode {
+    /// Fixed boot report format.
+    Boot = 0,
+    /// Reports as described by the report descriptor. This is the default after reset.
+    Report = 1,
+}
+
+impl From<u8> for HidProtocolMode {
+    fn from(mode: u8) -> Self {
+        if mode == HidProtocolMode::Boot as u8 {
+            HidProtocolMode::Boot
+        } else {
+            HidProtocolMode::Report
+        }
+    }
 }
 
 /// Report ID
@@ -77,6 +146,7 @@ impl ReportId {
 pub struct State<'d> {
     control: MaybeUninit<Control<'d>>,
     out_report_offset: AtomicUsize,
+    protocol: AtomicU8,
 }
 
 impl<'d> State<'d> {
@@ -85,6 +155,7 @@ impl<'d> State<'d> {
         State {
             control: MaybeUninit::uninit(),
             out_report_offset: AtomicUsize::new(0),
+            protocol: AtomicU8::new(HidProtocolMode::Report as u8),
         }
     }
 }
@@ -100,13 +171,19 @@ fn build<'d, D: Driver<'d>>(
     state: &'d mut State<'d>,
     config: Config<'d>,
     with_out_endpoint: bool,
-) -> (Option<D::EndpointOut>, D::EndpointIn, &'d AtomicUsize) {
+) -> (Option<D::EndpointOut>, D::EndpointIn, &'d AtomicUsize, &'d AtomicU8) {
     let len = config.report_descriptor.len();
 
-    let mut func = builder.function(USB_CLASS_HID, USB_SUBCLASS_NONE, USB_PROTOCOL_NONE);
+    let subclass = match config.boot_protocol {
+        HidBootProtocol::None => USB_SUBCLASS_NONE,
+        _ => USB_SUBCLASS_BOOT,
+    };
+    let protocol = config.boot_protocol as u8;
+
+    let mut func = builder.function(USB_CLASS_HID, subclass, protocol);
     let mut iface = func.interface();
     let if_num = iface.interface_number();
-    let mut alt = iface.alt_setting(USB_CLASS_HID, USB_SUBCLASS_NONE, USB_PROTOCOL_NONE, None);
+    let mut alt = iface.alt_setting(USB_CLASS_HID, subclass, protocol, None);
 
     // HID descriptor
     alt.descriptor(
@@ -140,11 +217,13 @@ fn build<'d, D: Driver<'d>>(
         if_num,
         config.report_descriptor,
         config.request_handler,
+        config.boot_protocol,
         &state.out_report_offset,
+        &state.protocol,
     ));
     builder.handler(control);
 
-    (ep_out, ep_in, &state.out_report_offset)
+    (ep_out, ep_in, &state.out_report_offset, &state.protocol)
 }
 
 impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWriter<'d, D, READ_N, WRITE_N> {
@@ -154,14 +233,14 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
     /// HID reports, consider using [`HidWriter::new`] instead, which allocates an IN endpoint only.
     ///
     pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
-        let (ep_out, ep_in, offset) = build(builder, state, config, true);
+        let (ep_out, ep_in, offset, protocol) = build(builder, state, config, true);
 
         Self {
             reader: HidReader {
                 ep_out: ep_out.unwrap(),
                 offset,
             },
-            writer: HidWriter { ep_in },
+            writer: HidWriter { ep_in, protocol },
         }
     }
 
@@ -176,6 +255,11 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
         self.writer.ready().await;
     }
 
+    /// Returns the protocol currently selected by the host.
+    pub fn protocol(&self) -> HidProtocolMode {
+        self.writer.protocol()
+    }
+
     /// Writes an input report by serializing the given report structure.
     #[cfg(feature = "usbd-hid")]
     pub async fn write_serialize<IR: AsInputReport>(&mut self, r: &IR) -> Result<(), EndpointError> {
@@ -200,6 +284,7 @@ impl<'d, D: Driver<'d>, const READ_N: usize, const WRITE_N: usize> HidReaderWrit
 /// You can obtain a `HidWriter` using [`HidReaderWriter::split`].
 pub struct HidWriter<'d, D: Driver<'d>, const N: usize> {
     ep_in: D::EndpointIn,
+    protocol: &'d AtomicU8,
 }
 
 /// USB HID reader.
@@ -243,11 +328,11 @@ impl<'d, D: Driver<'d>, const N: usize> HidWriter<'d, D, N> {
     /// of CPU on the device & bandwidth on the bus. A value of 10 is reasonable for
     /// high performance uses, and a value of 255 is good for best-effort usecases.
     pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
-        let (ep_out, ep_in, _offset) = build(builder, state, config, false);
+        let (ep_out, ep_in, _offset, protocol) = build(builder, state, config, false);
 
         assert!(ep_out.is_none());
 
-        Self { ep_in }
+        Self { ep_in, protocol }
     }
 
     /// Waits for the interrupt in endpoint to be enabled.
@@ -255,6 +340,13 @@ impl<'d, D: Driver<'d>, const N: usize> HidWriter<'d, D, N> {
         self.ep_in.wait_enabled().await
     }
 
+    /// Returns the protocol currently selected by the host.
+    ///
+    /// When this is [`HidProtocolMode::Boot`], input reports must be written in the boot format.
+    pub fn protocol(&self) -> HidProtocolMode {
+        self.protocol.load(Ordering::Relaxed).into()
+    }
+
     /// Writes an input report by serializing the given report structure.
     #[cfg(feature = "usbd-hid")]
     pub async fn write_serialize<IR: AsInputReport>(&mut self, r: &IR) -> Result<(), EndpointError> {
@@ -402,13 +494,20 @@ pub trait RequestHandler {
     fn set_idle_ms(&self, id: Option<ReportId>, duration_ms: u32) {
         let _ = (id, duration_ms);
     }
+
+    /// Called when the host switches a boot interface between boot and report protocol.
+    fn set_protocol(&self, protocol: HidProtocolMode) {
+        let _ = protocol;
+    }
 }
 
 struct Control<'d> {
     if_num: InterfaceNumber,
     report_descriptor: &'d [u8],
     request_handler: Option<&'d dyn RequestHandler>,
+    boot_protocol: HidBootProtocol,
     out_report_offset: &'d AtomicUsize,
+    protocol: &'d AtomicU8,
     hid_descriptor: [u8; 9],
 }
 
@@ -417,13 +516,17 @@ impl<'d> Control<'d> {
         if_num: InterfaceNumber,
         report_descriptor: &'d [u8],
         request_handler: Option<&'d dyn RequestHandler>,
+        boot_protocol: HidBootProtocol,
         out_report_offset: &'d AtomicUsize,
+        protocol: &'d AtomicU8,
     ) -> Self {
         Control {
             if_num,
             report_descriptor,
             request_handler,
+            boot_protocol,
             out_report_offset,
+            protocol,
             hid_descriptor: [
                 // Length of buf inclusive of size prefix
                 9,
@@ -449,6 +552,7 @@ impl<'d> Control<'d> {
 impl<'d> Handler for Control<'d> {
     fn reset(&mut self) {
         self.out_report_offset.store(0, Ordering::Release);
+        self.protocol.store(HidProtocolMode::Report as u8, Ordering::Relaxed);
     }
 
     fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
@@ -478,12 +582,20 @@ impl<'d> Handler for Control<'d> {
                 _ => Some(OutResponse::Rejected),
             },
             HID_REQ_SET_PROTOCOL => {
-                if req.value == 1 {
-                    Some(OutResponse::Accepted)
-                } else {
-                    warn!("HID Boot Protocol is unsupported.");
-                    Some(OutResponse::Rejected) // UNSUPPORTED: Boot Protocol
+                let protocol = match req.value {
+                    0 => HidProtocolMode::Boot,
+                    1 => HidProtocolMode::Report,
+                    _ => return Some(OutResponse::Rejected),
+                };
+                if protocol == HidProtocolMode::Boot && self.boot_protocol == HidBootProtocol::None {
+                    warn!("HID Boot Protocol requested on a non-boot interface.");
+                    return Some(OutResponse::Rejected);
+                }
+                self.protocol.store(protocol as u8, Ordering::Relaxed);
+                if let Some(handler) = self.request_handler {
+                    handler.set_protocol(protocol);
                 }
+                Some(OutResponse::Accepted)
             }
             _ => Some(OutResponse::Rejected),
         }
@@ -535,8 +647,7 @@ impl<'d> Handler for Control<'d> {
                         }
                     }
                     HID_REQ_GET_PROTOCOL => {
-                        // UNSUPPORTED: Boot Protocol
-                        buf[0] = 1;
+                        buf[0] = self.protocol.load(Ordering::Relaxed);
                         Some(InResponse::Accepted(&buf[0..1]))
                     }
                     _ => Some(InResponse::Rejected),
@@ -0,0 +1,352 @@
+//! USB MIDI 1.0 class implementation.
+//!
+//! MIDI data travels in 4-byte [`MidiPacket`]s. The upper nibble of their first byte is the
+//! virtual cable number, so one USB MIDI function can carry up to 16 independent MIDI ports in
+//! each direction, each showing up as a separate port on the host.
+
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::Builder;
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_AUDIO: u8 = 0x01;
+
+const USB_SUBCLASS_AUDIOCONTROL: u8 = 0x01;
+const USB_SUBCLASS_MIDISTREAMING: u8 = 0x03;
+const USB_PROTOCOL_NONE: u8 = 0x00;
+
+const CS_INTERFACE: u8 = 0x24;
+const CS_ENDPOINT: u8 = 0x25;
+const AC_HEADER: u8 = 0x01;
+const MS_HEADER: u8 = 0x01;
+const MS_GENERAL: u8 = 0x01;
+const MIDI_IN_JACK: u8 = 0x02;
+const MIDI_OUT_JACK: u8 = 0x03;
+const JACK_EMBEDDED: u8 = 0x01;
+const JACK_EXTERNAL: u8 = 0x02;
+
+/// Maximum number of virtual cables in each direction.
+pub const MAX_CABLES: u8 = 16;
+
+/// Size of a USB MIDI event packet.
+pub const PACKET_SIZE: usize = 4;
+
+/// Packet level implementation of a USB MIDI device.
+pub struct MidiClass<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+}
+
+impl<'d, D: Driver<'d>> MidiClass<'d, D> {
+    /// Creates a new MidiClass with the provided UsbBus, number of input and output jacks and
+    /// max_packet_size in bytes. For full-speed devices, max_packet_size has to be one of 8, 16,
+    /// 32 or 64.
+    ///
+    /// `n_in_jacks` is the number of cables carrying MIDI from the host to the device, and
+    /// `n_out_jacks` the number of cables carrying MIDI from the device to the host.
+    pub fn new(builder: &mut Builder<'d, D>, n_in_jacks: u8, n_out_jacks: u8, max_packet_size: u16) -> Self {
+        assert!(n_in_jacks <= MAX_CABLES && n_out_jacks <= MAX_CABLES);
+        assert!(max_packet_size as usize % PACKET_SIZE == 0);
+
+        let mut func = builder.function(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOCONTROL, USB_PROTOCOL_NONE);
+
+        // Audio control interface
+        let mut iface = func.interface();
+        let audio_if = iface.interface_number();
+        let midi_if = u8::from(audio_if) + 1;
+        let mut alt = iface.alt_setting(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOCONTROL, USB_PROTOCOL_NONE, None);
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AC_HEADER, // bDescriptorSubtype
+                0x00, 0x01, // bcdADC (1.00)
+                0x09, 0x00,    // wTotalLength
+                0x01,    // bInCollection
+                midi_if, // baInterfaceNr
+            ],
+        );
+
+        // MIDI streaming interface
+        let mut iface = func.interface();
+        let mut alt = iface.alt_setting(USB_CLASS_AUDIO, USB_SUBCLASS_MIDISTREAMING, USB_PROTOCOL_NONE, None);
+
+        // Header, 15 bytes of jacks per cable, and both endpoints with their
+        // class-specific descriptors.
+        let n_jacks = u16::from(n_in_jacks) + u16::from(n_out_jacks);
+        let total_len = 7 + 15 * n_jacks + 2 * (7 + 4) + n_jacks;
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                MS_HEADER, // bDescriptorSubtype
+                0x00,
+                0x01, // bcdMSC (1.00)
+                total_len as u8,
+                (total_len >> 8) as u8, // wTotalLength
+            ],
+        );
+
+        // Host -> device: an embedded IN jack fed by the OUT endpoint, wired to an external OUT jack.
+        for i in 0..n_in_jacks {
+            alt.descriptor(
+                CS_INTERFACE,
+                &[
+                    MIDI_IN_JACK,  // bDescriptorSubtype
+                    JACK_EMBEDDED, // bJackType
+                    in_jack_id(i), // bJackID
+                    0x00,          // iJack
+                ],
+            );
+            alt.descriptor(
+                CS_INTERFACE,
+                &[
+                    MIDI_OUT_JACK,     // bDescriptorSubtype
+                    JACK_EXTERNAL,     // bJackType
+                    in_jack_id(i) + 1, // bJackID
+                    0x01,              // bNrInputPins
+                    in_jack_id(i),     // baSourceID
+                    0x01,              // baSourcePin
+                    0x00,              // iJack
+                ],
+            );
+        }
+
+        // Device -> host: an external IN jack wired to an embedded OUT jack feeding the IN endpoint.
+        for i in 0..n_out_jacks {
+            alt.descriptor(
+                CS_INTERFACE,
+                &[
+                    MIDI_IN_JACK,                   // bDescriptorSubtype
+                    JACK_EXTERNAL,                  // bJackType
+                    out_jack_id(n_in_jacks, i) - 1, // bJackID
+                    0x00,                           // iJack
+                ],
+            );
+            alt.descriptor(
+                CS_INTERFACE,
+                &[
+                    MIDI_OUT_JACK,                  // bDescriptorSubtype
+                    JACK_EMBEDDED,                  // bJackType
+                    out_jack_id(n_in_jacks, i),     // bJackID
+                    0x01,                           // bNrInputPins
+                    out_jack_id(n_in_jacks, i) - 1, // baSourceID
+                    0x01,                           // baSourcePin
+                    0x00,                           // iJack
+                ],
+            );
+        }
+
+        let mut endpoint_data = [MS_GENERAL, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        let read_ep = alt.endpoint_bulk_out(max_packet_size);
+        endpoint_data[1] = n_in_jacks; // bNumEmbMIDIJack
+        for (i, id) in endpoint_data[2..2 + n_in_jacks as usize].iter_mut().enumerate() {
+            *id = in_jack_id(i as u8); // baAssocJackID
+        }
+        alt.descriptor(CS_ENDPOINT, &endpoint_data[..2 + n_in_jacks as usize]);
+
+        let write_ep = alt.endpoint_bulk_in(max_packet_size);
+        endpoint_data[1] = n_out_jacks; // bNumEmbMIDIJack
+        for (i, id) in endpoint_data[2..2 + n_out_jacks as usize].iter_mut().enumerate() {
+            *id = out_jack_id(n_in_jacks, i as u8); // baAssocJackID
+        }
+        alt.descriptor(CS_ENDPOINT, &endpoint_data[..2 + n_out_jacks as usize]);
+
+        MidiClass { read_ep, write_ep }
+    }
+
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        // The size is the same for both endpoints.
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Writes a single USB packet into the IN endpoint.
+    ///
+    /// `data` must be a multiple of [`PACKET_SIZE`] bytes long, i.e. a number of
+    /// [`MidiPacket`]s.
+    pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        self.write_ep.write(data).await
+    }
+
+    /// Reads a single USB packet from the OUT endpoint.
+    ///
+    /// Use [`MidiPacket::parse`] to split the received data into MIDI packets.
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.read_ep.read(data).await
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+
+    /// Split the class into a sender and receiver.
+    ///
+    /// This allows concurrently sending and receiving packets from separate tasks.
+    pub fn split(self) -> (Sender<'d, D>, Receiver<'d, D>) {
+        (
+            Sender {
+                write_ep: self.write_ep,
+            },
+            Receiver { read_ep: self.read_ep },
+        )
+    }
+}
+
+fn in_jack_id(cable: u8) -> u8 {
+    1 + 2 * cable
+}
+
+fn out_jack_id(n_in_jacks: u8, cable: u8) -> u8 {
+    2 + 2 * n_in_jacks + 2 * cable
+}
+
+/// MIDI class packet sender.
+///
+/// You can obtain a `Sender` with [`MidiClass::split`]
+pub struct Sender<'d, D: Driver<'d>> {
+    write_ep: D::EndpointIn,
+}
+
+impl<'d, D: Driver<'d>> Sender<'d, D> {
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.write_ep.info().max_packet_size
+    }
+
+    /// Writes a single USB packet into the IN endpoint.
+    ///
+    /// `data` must be a multiple of [`PACKET_SIZE`] bytes long, i.e. a number of
+    /// [`MidiPacket`]s.
+    pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        self.write_ep.write(data).await
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.write_ep.wait_enabled().await
+    }
+}
+
+/// MIDI class packet receiver.
+///
+/// You can obtain a `Receiver` with [`MidiClass::split`]
+pub struct Receiver<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+}
+
+impl<'d, D: Driver<'d>> Receiver<'d, D> {
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Reads a single USB packet from the OUT endpoint.
+    ///
+    /// Use [`MidiPacket::parse`] to split the received data into MIDI packets.
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.read_ep.read(data).await
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+}
+
+/// USB MIDI event packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MidiPacket([u8; PACKET_SIZE]);
+
+impl MidiPacket {
+    /// Creates a packet from its raw bytes.
+    pub fn from_bytes(bytes: [u8; PACKET_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Creates a packet carrying a complete MIDI message on `cable`.
+    ///
+    /// Returns `None` if `message` is not a complete channel or system message, system exclusive
+    /// messages have to be sent with [`MidiPacket::sysex`].
+    pub fn from_message(cable: u8, message: &[u8]) -> Option<Self> {
+        assert!(cable < MAX_CABLES);
+
+        let status = *message.first()?;
+        let code_index = match status {
+            0x80..=0xEF => status >> 4,
+            // MIDI time code, song select
+            0xF1 | 0xF3 => 0x2,
+            // Song position pointer
+            0xF2 => 0x3,
+            // Tune request
+            0xF6 => 0x5,
+            // Real time
+            0xF8..=0xFF => 0xF,
+            _ => return None,
+        };
+
+        let len = message_len(code_index);
+        if message.len() != len {
+            return None;
+        }
+
+        let mut bytes = [cable << 4 | code_index, 0, 0, 0];
+        bytes[1..1 + len].copy_from_slice(message);
+        Some(Self(bytes))
+    }
+
+    /// Splits a system exclusive message, including its `0xF0` and `0xF7` bytes, into packets.
+    pub fn sysex(cable: u8, message: &[u8]) -> impl Iterator<Item = MidiPacket> + '_ {
+        assert!(cable < MAX_CABLES);
+
+        let n = message.chunks(3).count();
+        message.chunks(3).enumerate().map(move |(i, chunk)| {
+            let code_index = match (i + 1 == n, chunk.len()) {
+                (false, _) => 0x4,
+                (true, 1) => 0x5,
+                (true, 2) => 0x6,
+                (true, _) => 0x7,
+            };
+            let mut bytes = [cable << 4 | code_index, 0, 0, 0];
+            bytes[1..1 + chunk.len()].copy_from_slice(chunk);
+            Self(bytes)
+        })
+    }
+
+    /// Splits data read from the OUT endpoint into packets.
+    ///
+    /// Padding packets with a code index number of 0 are skipped.
+    pub fn parse(data: &[u8]) -> impl Iterator<Item = MidiPacket> + '_ {
+        data.chunks_exact(PACKET_SIZE)
+            .map(|chunk| Self(chunk.try_into().unwrap()))
+            .filter(|packet| packet.code_index() != 0)
+    }
+
+    /// The raw bytes of the packet.
+    pub fn bytes(&self) -> [u8; PACKET_SIZE] {
+        self.0
+    }
+
+    /// The virtual cable number.
+    pub fn cable(&self) -> u8 {
+        self.0[0] >> 4
+    }
+
+    /// The code index number, which classifies the carried MIDI message.
+    pub fn code_index(&self) -> u8 {
+        self.0[0] & 0x0F
+    }
+
+    /// The MIDI bytes carried by the packet.
+    pub fn message(&self) -> &[u8] {
+        &self.0[1..1 + message_len(self.code_index())]
+    }
+}
+
+fn message_len(code_index: u8) -> usize {
+    match code_index {
+        0x5 | 0xF => 1,
+        0x2 | 0x6 | 0xC | 0xD => 2,
+        _ => 3,
+    }
+}
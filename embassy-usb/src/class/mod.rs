@@ -1,4 +1,10 @@
 //! Implementations of well-known USB classes.
+pub mod ccid;
 pub mod cdc_acm;
 pub mod cdc_ncm;
 pub mod hid;
+pub mod midi;
+pub mod msc;
+pub mod uac2;
+pub mod vendor;
+pub mod web_usb;
@@ -0,0 +1,80 @@
+//! [`BlockDevice`] adapter for NOR flash.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use super::BlockDevice;
+
+/// Exposes a region of NOR flash as a [`BlockDevice`].
+///
+/// Flash has to be erased before it can be written, and the erase size is usually larger than the
+/// USB block size. Writes are therefore done read-modify-write on a whole erase sector, using
+/// a caller-provided scratch buffer of `F::ERASE_SIZE` bytes.
+///
+/// The region must be erase-sector aligned, and its size must be a multiple of the block size.
+pub struct FlashBlockDevice<'d, F: NorFlash, const BLOCK_SIZE: usize = 512> {
+    flash: F,
+    offset: u32,
+    size: u32,
+    sector_buf: &'d mut [u8],
+}
+
+impl<'d, F: NorFlash, const BLOCK_SIZE: usize> FlashBlockDevice<'d, F, BLOCK_SIZE> {
+    /// Create a new block device over `size` bytes of `flash` starting at `offset`.
+    pub fn new(flash: F, offset: u32, size: u32, sector_buf: &'d mut [u8]) -> Self {
+        assert!(BLOCK_SIZE <= F::ERASE_SIZE);
+        assert_eq!(F::ERASE_SIZE % BLOCK_SIZE, 0);
+        assert_eq!(BLOCK_SIZE % F::WRITE_SIZE, 0);
+        assert_eq!(offset as usize % F::ERASE_SIZE, 0);
+        assert_eq!(size as usize % F::ERASE_SIZE, 0);
+        assert!(sector_buf.len() >= F::ERASE_SIZE);
+
+        Self {
+            flash,
+            offset,
+            size,
+            sector_buf,
+        }
+    }
+
+    /// Return the underlying flash.
+    pub fn release(self) -> F {
+        self.flash
+    }
+}
+
+impl<'d, F: NorFlash, const BLOCK_SIZE: usize> BlockDevice for FlashBlockDevice<'d, F, BLOCK_SIZE> {
+    type Error = F::Error;
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        self.size / BLOCK_SIZE as u32
+    }
+
+    async fn read(&mut self, lba: u32, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.flash
+            .read(self.offset + lba * BLOCK_SIZE as u32, &mut buf[..BLOCK_SIZE])
+            .await
+    }
+
+    async fn write(&mut self, lba: u32, data: &[u8]) -> Result<(), Self::Error> {
+        let addr = self.offset + lba * BLOCK_SIZE as u32;
+        let sector_start = addr - (addr % F::ERASE_SIZE as u32);
+        let sector_end = sector_start + F::ERASE_SIZE as u32;
+        let in_sector = (addr - sector_start) as usize;
+
+        let sector = &mut self.sector_buf[..F::ERASE_SIZE];
+        self.flash.read(sector_start, sector).await?;
+
+        if sector[in_sector..in_sector + BLOCK_SIZE] == data[..BLOCK_SIZE] {
+            // Nothing changed, save an erase cycle.
+            return Ok(());
+        }
+
+        sector[in_sector..in_sector + BLOCK_SIZE].copy_from_slice(&data[..BLOCK_SIZE]);
+        self.flash.erase(sector_start, sector_end).await?;
+        self.flash.write(sector_start, sector).await
+    }
+}
@@ -0,0 +1,634 @@
+//! USB Mass Storage Class (MSC) implementation.
+//!
+//! This implements the Bulk-Only Transport (BOT) with a subset of the SCSI transparent command
+//! set, which is enough for every mainstream host OS to mount the device as a removable drive.
+//!
+//! The storage itself is provided by the user through the [`BlockDevice`] trait. See
+//! [`flash::FlashBlockDevice`] for an adapter that exposes a region of NOR flash.
+
+use core::mem::MaybeUninit;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+#[cfg(feature = "embedded-storage")]
+pub mod flash;
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_MSC: u8 = 0x08;
+
+const MSC_SUBCLASS_SCSI: u8 = 0x06;
+const MSC_PROTOCOL_BBB: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xFE;
+const REQ_BULK_ONLY_RESET: u8 = 0xFF;
+
+const CBW_SIGNATURE: u32 = 0x43425355;
+const CBW_LEN: usize = 31;
+const CSW_SIGNATURE: u32 = 0x53425355;
+const CSW_LEN: usize = 13;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_MODE_SENSE_6: u8 = 0x1A;
+const SCSI_START_STOP_UNIT: u8 = 0x1B;
+const SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
+const SCSI_READ_FORMAT_CAPACITIES: u8 = 0x23;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2A;
+const SCSI_VERIFY_10: u8 = 0x2F;
+const SCSI_SYNCHRONIZE_CACHE_10: u8 = 0x35;
+const SCSI_MODE_SENSE_10: u8 = 0x5A;
+
+/// A block storage device that can be exposed over USB mass storage.
+///
+/// All blocks have the same size, returned by [`block_size`](Self::block_size). The class calls
+/// [`read`](Self::read) and [`write`](Self::write) with buffers of exactly one block.
+pub trait BlockDevice {
+    /// Error type returned by the device.
+    type Error;
+
+    /// Size of a single block, in bytes. This is usually 512.
+    fn block_size(&self) -> usize;
+
+    /// Total number of blocks in the device.
+    fn block_count(&self) -> u32;
+
+    /// Returns whether the device is read-only.
+    ///
+    /// The host will mount the drive read-only and will never issue writes.
+    fn is_write_protected(&self) -> bool {
+        false
+    }
+
+    /// Read block `lba` into `buf`.
+    async fn read(&mut self, lba: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write `data` into block `lba`.
+    async fn write(&mut self, lba: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Flush any write caches to the underlying storage.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Internal state for the MSC class.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    shared: ControlShared,
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            shared: ControlShared::default(),
+        }
+    }
+}
+
+/// Shared data between Control and MscClass
+#[derive(Default)]
+struct ControlShared {
+    /// Signaled on a bus reset or a Bulk-Only Mass Storage Reset.
+    reset: Signal<CriticalSectionRawMutex, ()>,
+}
+
+struct Control<'d> {
+    if_num: InterfaceNumber,
+    shared: &'d ControlShared,
+}
+
+impl<'d> Handler for Control<'d> {
+    fn reset(&mut self) {
+        self.shared.reset.signal(());
+    }
+
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            REQ_BULK_ONLY_RESET => {
+                debug!("msc: bulk-only mass storage reset");
+                self.shared.reset.signal(());
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            REQ_GET_MAX_LUN => {
+                // Only a single logical unit is supported.
+                buf[0] = 0;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Identification strings returned in the SCSI INQUIRY response.
+///
+/// Strings longer than the field size are truncated, shorter ones are padded with spaces.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config<'d> {
+    /// Vendor identification, up to 8 ASCII characters.
+    pub vendor: &'d str,
+    /// Product identification, up to 16 ASCII characters.
+    pub product: &'d str,
+    /// Product revision level, up to 4 ASCII characters.
+    pub revision: &'d str,
+    /// Max packet size for both the IN and OUT endpoints.
+    pub max_packet_size: u16,
+}
+
+impl<'d> Default for Config<'d> {
+    fn default() -> Self {
+        Self {
+            vendor: "Embassy",
+            product: "Mass Storage",
+            revision: "1.0",
+            max_packet_size: 64,
+        }
+    }
+}
+
+/// Error while handling a single command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransportError {
+    /// The endpoint is disabled.
+    Disabled,
+    /// The host sent something that doesn't follow the Bulk-Only Transport protocol.
+    Protocol,
+}
+
+impl From<EndpointError> for TransportError {
+    fn from(val: EndpointError) -> Self {
+        match val {
+            EndpointError::BufferOverflow => TransportError::Protocol,
+            EndpointError::Disabled => TransportError::Disabled,
+        }
+    }
+}
+
+/// SCSI sense data, reported to the host with REQUEST SENSE after a failed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sense {
+    key: u8,
+    asc: u8,
+    ascq: u8,
+}
+
+impl Sense {
+    const NO_SENSE: Sense = Sense::new(0x00, 0x00, 0x00);
+    const MEDIUM_ERROR_UNRECOVERED_READ: Sense = Sense::new(0x03, 0x11, 0x00);
+    const MEDIUM_ERROR_WRITE_FAULT: Sense = Sense::new(0x03, 0x03, 0x00);
+    const ILLEGAL_REQUEST_INVALID_COMMAND: Sense = Sense::new(0x05, 0x20, 0x00);
+    const ILLEGAL_REQUEST_LBA_OUT_OF_RANGE: Sense = Sense::new(0x05, 0x21, 0x00);
+    const ILLEGAL_REQUEST_INVALID_FIELD: Sense = Sense::new(0x05, 0x24, 0x00);
+    const DATA_PROTECT_WRITE_PROTECTED: Sense = Sense::new(0x07, 0x27, 0x00);
+
+    const fn new(key: u8, asc: u8, ascq: u8) -> Self {
+        Self { key, asc, ascq }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommandStatus {
+    Passed = 0x00,
+    Failed = 0x01,
+    PhaseError = 0x02,
+}
+
+/// A parsed Command Block Wrapper.
+#[derive(Debug, Clone, Copy)]
+struct Cbw {
+    tag: u32,
+    data_len: u32,
+    data_in: bool,
+    cb: [u8; 16],
+}
+
+impl Cbw {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() != CBW_LEN || u32::from_le_bytes(buf[0..4].try_into().unwrap()) != CBW_SIGNATURE {
+            return None;
+        }
+
+        let cb_len = buf[14] & 0x1F;
+        if cb_len == 0 || cb_len > 16 {
+            return None;
+        }
+
+        let mut cb = [0; 16];
+        cb[..cb_len as usize].copy_from_slice(&buf[15..][..cb_len as usize]);
+
+        Some(Self {
+            tag: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+            data_len: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            data_in: buf[12] & 0x80 != 0,
+            cb,
+        })
+    }
+}
+
+/// USB Mass Storage class using the Bulk-Only Transport.
+pub struct MscClass<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    control: &'d ControlShared,
+    config: Config<'d>,
+    sense: Sense,
+}
+
+impl<'d, D: Driver<'d>> MscClass<'d, D> {
+    /// Creates a new MscClass with the provided builder and config.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
+        let mut func = builder.function(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BBB);
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BBB, None);
+        let read_ep = alt.endpoint_bulk_out(config.max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(config.max_packet_size);
+
+        drop(func);
+
+        let control = state.control.write(Control {
+            if_num,
+            shared: &state.shared,
+        });
+        builder.handler(control);
+
+        MscClass {
+            read_ep,
+            write_ep,
+            control: &state.shared,
+            config,
+            sense: Sense::NO_SENSE,
+        }
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+
+    /// Serves SCSI commands from the host, backed by `device`.
+    ///
+    /// `block_buf` is used as scratch space for block transfers, it must be at least
+    /// [`BlockDevice::block_size`] bytes long.
+    pub async fn run<B: BlockDevice>(&mut self, device: &mut B, block_buf: &mut [u8]) -> ! {
+        assert!(block_buf.len() >= device.block_size());
+        assert!(block_buf.len() >= 64);
+
+        loop {
+            self.wait_connection().await;
+            self.control.reset.reset();
+            self.sense = Sense::NO_SENSE;
+
+            loop {
+                match self.handle_command(device, block_buf).await {
+                    Ok(()) => {}
+                    Err(TransportError::Disabled) => break,
+                    Err(TransportError::Protocol) => {
+                        warn!("msc: invalid CBW, stalling until reset recovery");
+                        self.stall_until_reset().await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stall both bulk endpoints until the host does a Reset Recovery (BOT 6.6.1).
+    ///
+    /// The Reset Recovery is a Bulk-Only Mass Storage Reset, after which the host clears the
+    /// stalls with CLEAR_FEATURE(ENDPOINT_HALT). The endpoints are stalled again if the host
+    /// clears them and sends data before the reset.
+    async fn stall_until_reset(&mut self) {
+        // Forget the resets that happened before the stall, the host only recovers after seeing it.
+        self.control.reset.reset();
+        loop {
+            self.read_ep.set_stalled(true);
+            self.write_ep.set_stalled(true);
+
+            let mut discard = [0; 64];
+            match select(self.control.reset.wait(), self.read_ep.read(&mut discard)).await {
+                Either::First(()) | Either::Second(Err(EndpointError::Disabled)) => return,
+                Either::Second(_) => {}
+            }
+        }
+    }
+
+    async fn handle_command<B: BlockDevice>(&mut self, device: &mut B, buf: &mut [u8]) -> Result<(), TransportError> {
+        let mps = self.read_ep.info().max_packet_size as usize;
+        let n = self.read_ep.read(&mut buf[..mps]).await?;
+        let cbw = Cbw::parse(&buf[..n]).ok_or(TransportError::Protocol)?;
+
+        trace!("msc: command {:02x}, data len {}", cbw.cb[0], cbw.data_len);
+
+        let (status, residue) = match cbw.cb[0] {
+            SCSI_READ_10 => self.read_10(&cbw, device, buf).await?,
+            SCSI_WRITE_10 => self.write_10(&cbw, device, buf).await?,
+            _ => {
+                let (status, len) = self.handle_simple_command(&cbw, device, buf).await;
+                match status {
+                    CommandStatus::Passed if cbw.data_in => {
+                        let len = len.min(cbw.data_len as usize);
+                        self.write_data(&buf[..len], cbw.data_len as usize).await?;
+                        (status, cbw.data_len - len as u32)
+                    }
+                    CommandStatus::Passed if cbw.data_len == 0 => (status, 0),
+                    _ => (self.skip_data(&cbw, buf).await?, cbw.data_len),
+                }
+            }
+        };
+
+        self.write_csw(cbw.tag, residue, status).await
+    }
+
+    /// Handles commands with no or very little data, building the response in `buf`.
+    ///
+    /// Returns the status and the length of the response.
+    async fn handle_simple_command<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        buf: &mut [u8],
+    ) -> (CommandStatus, usize) {
+        let cb = &cbw.cb;
+        let res = match cb[0] {
+            SCSI_TEST_UNIT_READY | SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL | SCSI_START_STOP_UNIT | SCSI_VERIFY_10 => Ok(0),
+            SCSI_SYNCHRONIZE_CACHE_10 => match device.flush().await {
+                Ok(()) => Ok(0),
+                Err(_) => Err(Sense::MEDIUM_ERROR_WRITE_FAULT),
+            },
+            SCSI_REQUEST_SENSE => {
+                buf[..18].fill(0);
+                buf[0] = 0x70; // response code: current errors, fixed format
+                buf[2] = self.sense.key;
+                buf[7] = 10; // additional sense length
+                buf[12] = self.sense.asc;
+                buf[13] = self.sense.ascq;
+                self.sense = Sense::NO_SENSE;
+                // Return directly, the sense data must not be overwritten below.
+                return (CommandStatus::Passed, 18.min(cb[4] as usize));
+            }
+            SCSI_INQUIRY => {
+                if cb[1] & 0x01 != 0 {
+                    // Vital product data pages are not supported.
+                    Err(Sense::ILLEGAL_REQUEST_INVALID_FIELD)
+                } else {
+                    buf[..36].fill(b' ');
+                    buf[0] = 0x00; // direct access block device
+                    buf[1] = 0x80; // removable medium
+                    buf[2] = 0x04; // SPC-2
+                    buf[3] = 0x02; // response data format
+                    buf[4] = 36 - 5; // additional length
+                    buf[5] = 0;
+                    buf[6] = 0;
+                    buf[7] = 0;
+                    copy_padded(&mut buf[8..16], self.config.vendor);
+                    copy_padded(&mut buf[16..32], self.config.product);
+                    copy_padded(&mut buf[32..36], self.config.revision);
+                    Ok(36.min(u16::from_be_bytes([cb[3], cb[4]]) as usize))
+                }
+            }
+            SCSI_MODE_SENSE_6 => {
+                buf[0] = 3; // mode data length
+                buf[1] = 0; // medium type
+                buf[2] = if device.is_write_protected() { 0x80 } else { 0x00 };
+                buf[3] = 0; // block descriptor length
+                Ok(4.min(cb[4] as usize))
+            }
+            SCSI_MODE_SENSE_10 => {
+                buf[..8].fill(0);
+                buf[1] = 6; // mode data length
+                buf[3] = if device.is_write_protected() { 0x80 } else { 0x00 };
+                Ok(8.min(u16::from_be_bytes([cb[7], cb[8]]) as usize))
+            }
+            SCSI_READ_CAPACITY_10 => {
+                let last_lba = device.block_count().saturating_sub(1);
+                buf[0..4].copy_from_slice(&last_lba.to_be_bytes());
+                buf[4..8].copy_from_slice(&(device.block_size() as u32).to_be_bytes());
+                Ok(8)
+            }
+            SCSI_READ_FORMAT_CAPACITIES => {
+                buf[0..4].copy_from_slice(&[0, 0, 0, 8]); // capacity list length
+                buf[4..8].copy_from_slice(&device.block_count().to_be_bytes());
+                buf[8] = 0x02; // formatted media
+                buf[9..12].copy_from_slice(&(device.block_size() as u32).to_be_bytes()[1..]);
+                Ok(12.min(u16::from_be_bytes([cb[7], cb[8]]) as usize))
+            }
+            _ => {
+                debug!("msc: unsupported SCSI command {:02x}", cb[0]);
+                Err(Sense::ILLEGAL_REQUEST_INVALID_COMMAND)
+            }
+        };
+
+        match res {
+            Ok(len) => {
+                self.sense = Sense::NO_SENSE;
+                (CommandStatus::Passed, len)
+            }
+            Err(sense) => {
+                self.sense = sense;
+                (CommandStatus::Failed, 0)
+            }
+        }
+    }
+
+    async fn read_10<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        buf: &mut [u8],
+    ) -> Result<(CommandStatus, u32), TransportError> {
+        let (lba, count) = parse_rw_10(&cbw.cb);
+        let block_size = device.block_size();
+        let total = count as usize * block_size;
+
+        if !cbw.data_in || total > cbw.data_len as usize {
+            return Ok((self.phase_error(cbw, buf).await?, cbw.data_len));
+        }
+        if lba.checked_add(count).map_or(true, |end| end > device.block_count()) {
+            self.sense = Sense::ILLEGAL_REQUEST_LBA_OUT_OF_RANGE;
+            return Ok((self.skip_data(cbw, buf).await?, cbw.data_len));
+        }
+
+        for i in 0..count {
+            let block = &mut buf[..block_size];
+            if device.read(lba + i, block).await.is_err() {
+                warn!("msc: read of block {} failed", lba + i);
+                self.sense = Sense::MEDIUM_ERROR_UNRECOVERED_READ;
+                let sent = i as usize * block_size;
+                self.pad_in(cbw.data_len as usize - sent, buf).await?;
+                return Ok((CommandStatus::Failed, cbw.data_len - sent as u32));
+            }
+            self.write_packets(block).await?;
+        }
+
+        // Terminate the data stage if the host asked for more than we sent.
+        let residue = cbw.data_len - total as u32;
+        if residue != 0 && total % self.write_ep.info().max_packet_size as usize == 0 {
+            self.write_ep.write(&[]).await?;
+        }
+
+        self.sense = Sense::NO_SENSE;
+        Ok((CommandStatus::Passed, residue))
+    }
+
+    async fn write_10<B: BlockDevice>(
+        &mut self,
+        cbw: &Cbw,
+        device: &mut B,
+        buf: &mut [u8],
+    ) -> Result<(CommandStatus, u32), TransportError> {
+        let (lba, count) = parse_rw_10(&cbw.cb);
+        let block_size = device.block_size();
+        let total = count as usize * block_size;
+
+        if cbw.data_in || total > cbw.data_len as usize {
+            return Ok((self.phase_error(cbw, buf).await?, cbw.data_len));
+        }
+        if device.is_write_protected() {
+            self.sense = Sense::DATA_PROTECT_WRITE_PROTECTED;
+            return Ok((self.skip_data(cbw, buf).await?, cbw.data_len));
+        }
+        if lba.checked_add(count).map_or(true, |end| end > device.block_count()) {
+            self.sense = Sense::ILLEGAL_REQUEST_LBA_OUT_OF_RANGE;
+            return Ok((self.skip_data(cbw, buf).await?, cbw.data_len));
+        }
+
+        let mut status = CommandStatus::Passed;
+        for i in 0..count {
+            let block = &mut buf[..block_size];
+            self.read_packets(block).await?;
+
+            // Keep consuming the data stage after a failure so the transport stays in sync.
+            if status == CommandStatus::Passed && device.write(lba + i, block).await.is_err() {
+                warn!("msc: write of block {} failed", lba + i);
+                self.sense = Sense::MEDIUM_ERROR_WRITE_FAULT;
+                status = CommandStatus::Failed;
+            }
+        }
+
+        if status == CommandStatus::Passed {
+            self.sense = Sense::NO_SENSE;
+        }
+        Ok((status, cbw.data_len - total as u32))
+    }
+
+    /// Handles a mismatch between the direction or length the host expects and what the command needs.
+    async fn phase_error(&mut self, cbw: &Cbw, buf: &mut [u8]) -> Result<CommandStatus, TransportError> {
+        self.sense = Sense::ILLEGAL_REQUEST_INVALID_FIELD;
+        self.skip_data(cbw, buf).await?;
+        Ok(CommandStatus::PhaseError)
+    }
+
+    /// Completes the data stage of a failed command without transferring meaningful data.
+    async fn skip_data(&mut self, cbw: &Cbw, buf: &mut [u8]) -> Result<CommandStatus, TransportError> {
+        let len = cbw.data_len as usize;
+        if cbw.data_in {
+            self.pad_in(len, buf).await?;
+        } else {
+            let mps = self.read_ep.info().max_packet_size as usize;
+            let mut remaining = len;
+            while remaining > 0 {
+                let n = self.read_ep.read(&mut buf[..mps]).await?;
+                remaining = remaining.saturating_sub(n);
+                if n < mps {
+                    break;
+                }
+            }
+        }
+        Ok(CommandStatus::Failed)
+    }
+
+    /// Sends `len` bytes of zeroes on the IN endpoint.
+    async fn pad_in(&mut self, len: usize, buf: &mut [u8]) -> Result<(), TransportError> {
+        let mps = self.write_ep.info().max_packet_size as usize;
+        buf[..mps].fill(0);
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(mps);
+            self.write_ep.write(&buf[..n]).await?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+
+    /// Sends the data stage of a command, terminating it with a short packet if it's shorter
+    /// than what the host expects.
+    async fn write_data(&mut self, data: &[u8], expected: usize) -> Result<(), TransportError> {
+        self.write_packets(data).await?;
+        if data.len() < expected && data.len() % self.write_ep.info().max_packet_size as usize == 0 {
+            self.write_ep.write(&[]).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_packets(&mut self, data: &[u8]) -> Result<(), TransportError> {
+        let mps = self.write_ep.info().max_packet_size as usize;
+        for chunk in data.chunks(mps) {
+            self.write_ep.write(chunk).await?;
+        }
+        Ok(())
+    }
+
+    async fn read_packets(&mut self, buf: &mut [u8]) -> Result<(), TransportError> {
+        let mps = self.read_ep.info().max_packet_size as usize;
+        for chunk in buf.chunks_mut(mps) {
+            let n = self.read_ep.read(chunk).await?;
+            if n != chunk.len() {
+                return Err(TransportError::Protocol);
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_csw(&mut self, tag: u32, residue: u32, status: CommandStatus) -> Result<(), TransportError> {
+        let mut csw = [0; CSW_LEN];
+        csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&residue.to_le_bytes());
+        csw[12] = status as u8;
+        self.write_ep.write(&csw).await?;
+        Ok(())
+    }
+}
+
+fn parse_rw_10(cb: &[u8; 16]) -> (u32, u32) {
+    let lba = u32::from_be_bytes(cb[2..6].try_into().unwrap());
+    let count = u16::from_be_bytes(cb[7..9].try_into().unwrap()) as u32;
+    (lba, count)
+}
+
+fn copy_padded(dst: &mut [u8], src: &str) {
+    dst.fill(b' ');
+    let n = src.len().min(dst.len());
+    dst[..n].copy_from_slice(&src.as_bytes()[..n]);
+}
@@ -0,0 +1,734 @@
+//! USB Audio Class 2.0 implementation.
+//!
+//! Provides a [`Speaker`] (host to device) and a [`Microphone`] (device to host) function, each
+//! with a fixed topology:
+//!
+//! - Speaker: USB streaming input terminal -> feature unit -> speaker output terminal.
+//! - Microphone: microphone input terminal -> feature unit -> USB streaming output terminal.
+//!
+//! The feature unit exposes master mute and volume controls, and an internal clock source exposes
+//! the sample rate. The host selects these with control requests, which are available through
+//! [`AudioControls`].
+//!
+//! Streaming uses isochronous endpoints, carrying one packet of interleaved PCM samples per
+//! frame. The speaker runs from its own clock, so it has an explicit feedback endpoint telling
+//! the host how many samples per frame it actually consumes, see [`Feedback`].
+//!
+//! UAC2 requires an interface association descriptor, so [`crate::Config::composite_with_iads`]
+//! must be enabled. Only full-speed operation is supported.
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicI16, AtomicU32, Ordering};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::descriptor::{SynchronizationType, UsageType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+const USB_CLASS_AUDIO: u8 = 0x01;
+const FUNCTION_SUBCLASS_UNDEFINED: u8 = 0x00;
+const SUBCLASS_AUDIOCONTROL: u8 = 0x01;
+const SUBCLASS_AUDIOSTREAMING: u8 = 0x02;
+const IP_VERSION_02_00: u8 = 0x20;
+
+const CS_INTERFACE: u8 = 0x24;
+const CS_ENDPOINT: u8 = 0x25;
+
+// Audio control interface descriptor subtypes
+const AC_HEADER: u8 = 0x01;
+const AC_INPUT_TERMINAL: u8 = 0x02;
+const AC_OUTPUT_TERMINAL: u8 = 0x03;
+const AC_FEATURE_UNIT: u8 = 0x06;
+const AC_CLOCK_SOURCE: u8 = 0x0A;
+
+// Audio streaming interface descriptor subtypes
+const AS_GENERAL: u8 = 0x01;
+const AS_FORMAT_TYPE: u8 = 0x02;
+const EP_GENERAL: u8 = 0x01;
+
+const FORMAT_TYPE_I: u8 = 0x01;
+const FORMAT_PCM: u32 = 0x0000_0001;
+
+const FUNCTION_CATEGORY_DESKTOP_SPEAKER: u8 = 0x01;
+const FUNCTION_CATEGORY_MICROPHONE: u8 = 0x03;
+
+const TERMINAL_USB_STREAMING: u16 = 0x0101;
+const TERMINAL_MICROPHONE: u16 = 0x0201;
+const TERMINAL_SPEAKER: u16 = 0x0301;
+
+const REQ_CUR: u8 = 0x01;
+const REQ_RANGE: u8 = 0x02;
+
+const CS_SAM_FREQ_CONTROL: u8 = 0x01;
+const CS_CLOCK_VALID_CONTROL: u8 = 0x02;
+const FU_MUTE_CONTROL: u8 = 0x01;
+const FU_VOLUME_CONTROL: u8 = 0x02;
+
+// Entity IDs
+const CLOCK_SOURCE_ID: u8 = 1;
+const INPUT_TERMINAL_ID: u8 = 2;
+const FEATURE_UNIT_ID: u8 = 3;
+const OUTPUT_TERMINAL_ID: u8 = 4;
+
+/// Maximum number of supported sample rates.
+pub const MAX_SAMPLE_RATES: usize = 8;
+
+/// Maximum number of channels.
+pub const MAX_CHANNELS: u8 = 8;
+
+/// Volume range of the feature unit, in 1/256 dB steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VolumeRange {
+    /// Minimum volume.
+    pub min: i16,
+    /// Maximum volume.
+    pub max: i16,
+    /// Volume resolution.
+    pub res: i16,
+}
+
+impl Default for VolumeRange {
+    fn default() -> Self {
+        // -60 dB to 0 dB in 1 dB steps.
+        Self {
+            min: -60 * 256,
+            max: 0,
+            res: 256,
+        }
+    }
+}
+
+/// Configuration for an audio function.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config<'d> {
+    /// Supported sample rates in Hz. The first one is selected after reset.
+    pub sample_rates: &'d [u32],
+
+    /// Number of interleaved channels, at most [`MAX_CHANNELS`].
+    pub channels: u8,
+
+    /// Bytes per sample, one of 1, 2, 3 or 4.
+    pub subslot_size: u8,
+
+    /// Number of significant bits per sample, at most `8 * subslot_size`.
+    pub bit_resolution: u8,
+
+    /// Range of the master volume control.
+    pub volume_range: VolumeRange,
+}
+
+impl<'d> Default for Config<'d> {
+    fn default() -> Self {
+        Self {
+            sample_rates: &[48_000],
+            channels: 2,
+            subslot_size: 2,
+            bit_resolution: 16,
+            volume_range: VolumeRange::default(),
+        }
+    }
+}
+
+impl<'d> Config<'d> {
+    /// Size of one audio frame, i.e. one sample of every channel, in bytes.
+    pub fn frame_size(&self) -> usize {
+        self.channels as usize * self.subslot_size as usize
+    }
+
+    /// Largest packet size needed, one frame more than the highest sample rate requires, to
+    /// leave room for clock drift.
+    pub fn max_packet_size(&self) -> u16 {
+        let max_rate = self.sample_rates.iter().copied().max().unwrap_or(0) as usize;
+        (((max_rate + 999) / 1000 + 1) * self.frame_size()) as u16
+    }
+
+    fn channel_config(&self) -> u32 {
+        match self.channels {
+            // Front left, front right.
+            2 => 0x0000_0003,
+            // Non-predefined spatial positions.
+            _ => 0,
+        }
+    }
+}
+
+/// Internal state for an audio function.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    shared: ControlShared,
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            shared: ControlShared::default(),
+        }
+    }
+}
+
+/// Shared data between Control and the audio classes.
+struct ControlShared {
+    sample_rate: AtomicU32,
+    mute: AtomicBool,
+    volume: AtomicI16,
+    streaming: AtomicBool,
+    changed: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl Default for ControlShared {
+    fn default() -> Self {
+        ControlShared {
+            sample_rate: AtomicU32::new(0),
+            mute: AtomicBool::new(false),
+            volume: AtomicI16::new(0),
+            streaming: AtomicBool::new(false),
+            changed: Signal::new(),
+        }
+    }
+}
+
+struct Control<'d> {
+    ac_if: InterfaceNumber,
+    as_if: InterfaceNumber,
+    sample_rates: &'d [u32],
+    volume_range: VolumeRange,
+    shared: &'d ControlShared,
+}
+
+impl<'d> Control<'d> {
+    fn reset_controls(&mut self) {
+        let shared = self.shared;
+        shared.sample_rate.store(self.sample_rates[0], Ordering::Relaxed);
+        shared.mute.store(false, Ordering::Relaxed);
+        shared.volume.store(self.volume_range.max, Ordering::Relaxed);
+        shared.streaming.store(false, Ordering::Relaxed);
+        shared.changed.signal(());
+    }
+
+    /// Returns `(entity, control selector, channel)` for requests to this function.
+    fn target(&self, req: &Request) -> Option<(u8, u8, u8)> {
+        if (req.request_type, req.recipient, req.index as u8)
+            != (RequestType::Class, Recipient::Interface, self.ac_if.0)
+        {
+            return None;
+        }
+        Some(((req.index >> 8) as u8, (req.value >> 8) as u8, req.value as u8))
+    }
+}
+
+impl<'d> Handler for Control<'d> {
+    fn reset(&mut self) {
+        self.reset_controls();
+    }
+
+    fn set_alternate_setting(&mut self, iface: InterfaceNumber, alternate_setting: u8) {
+        if iface == self.as_if {
+            debug!("audio streaming alt setting {}", alternate_setting);
+            self.shared.streaming.store(alternate_setting != 0, Ordering::Relaxed);
+            self.shared.changed.signal(());
+        }
+    }
+
+    fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
+        let (entity, selector, channel) = self.target(&req)?;
+        if req.request != REQ_CUR {
+            return Some(OutResponse::Rejected);
+        }
+
+        let shared = self.shared;
+        match (entity, selector, channel) {
+            (CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL, 0) if data.len() >= 4 => {
+                let rate = u32::from_le_bytes(data[..4].try_into().unwrap());
+                if !self.sample_rates.contains(&rate) {
+                    warn!("unsupported sample rate {}", rate);
+                    return Some(OutResponse::Rejected);
+                }
+                debug!("set sample rate {}", rate);
+                shared.sample_rate.store(rate, Ordering::Relaxed);
+            }
+            (FEATURE_UNIT_ID, FU_MUTE_CONTROL, 0) if !data.is_empty() => {
+                debug!("set mute {}", data[0] != 0);
+                shared.mute.store(data[0] != 0, Ordering::Relaxed);
+            }
+            (FEATURE_UNIT_ID, FU_VOLUME_CONTROL, 0) if data.len() >= 2 => {
+                let volume = i16::from_le_bytes([data[0], data[1]]);
+                let volume = volume.clamp(self.volume_range.min, self.volume_range.max);
+                debug!("set volume {}", volume);
+                shared.volume.store(volume, Ordering::Relaxed);
+            }
+            _ => return Some(OutResponse::Rejected),
+        }
+
+        shared.changed.signal(());
+        Some(OutResponse::Accepted)
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        let (entity, selector, channel) = self.target(&req)?;
+
+        let shared = self.shared;
+        let len = match (req.request, entity, selector, channel) {
+            (REQ_CUR, CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL, 0) => {
+                buf[..4].copy_from_slice(&shared.sample_rate.load(Ordering::Relaxed).to_le_bytes());
+                4
+            }
+            (REQ_RANGE, CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL, 0) => {
+                // One discrete subrange per rate
+                let n = self.sample_rates.len();
+                buf[..2].copy_from_slice(&(n as u16).to_le_bytes());
+                for (i, rate) in self.sample_rates.iter().enumerate() {
+                    let range = &mut buf[2 + 12 * i..][..12];
+                    range[0..4].copy_from_slice(&rate.to_le_bytes()); // dMIN
+                    range[4..8].copy_from_slice(&rate.to_le_bytes()); // dMAX
+                    range[8..12].copy_from_slice(&0u32.to_le_bytes()); // dRES
+                }
+                2 + 12 * n
+            }
+            (REQ_CUR, CLOCK_SOURCE_ID, CS_CLOCK_VALID_CONTROL, 0) => {
+                buf[0] = 1;
+                1
+            }
+            (REQ_CUR, FEATURE_UNIT_ID, FU_MUTE_CONTROL, 0) => {
+                buf[0] = shared.mute.load(Ordering::Relaxed) as u8;
+                1
+            }
+            (REQ_CUR, FEATURE_UNIT_ID, FU_VOLUME_CONTROL, 0) => {
+                buf[..2].copy_from_slice(&shared.volume.load(Ordering::Relaxed).to_le_bytes());
+                2
+            }
+            (REQ_RANGE, FEATURE_UNIT_ID, FU_VOLUME_CONTROL, 0) => {
+                let range = self.volume_range;
+                buf[0..2].copy_from_slice(&1u16.to_le_bytes()); // wNumSubRanges
+                buf[2..4].copy_from_slice(&range.min.to_le_bytes()); // wMIN
+                buf[4..6].copy_from_slice(&range.max.to_le_bytes()); // wMAX
+                buf[6..8].copy_from_slice(&range.res.to_le_bytes()); // wRES
+                8
+            }
+            _ => return Some(InResponse::Rejected),
+        };
+
+        Some(InResponse::Accepted(&buf[..len]))
+    }
+}
+
+/// Handle for reading the controls set by the host.
+#[derive(Clone, Copy)]
+pub struct AudioControls<'d> {
+    shared: &'d ControlShared,
+}
+
+impl<'d> AudioControls<'d> {
+    /// Gets the sample rate selected by the host, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.shared.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// Gets the master mute state.
+    pub fn mute(&self) -> bool {
+        self.shared.mute.load(Ordering::Relaxed)
+    }
+
+    /// Gets the master volume, in 1/256 dB steps.
+    pub fn volume(&self) -> i16 {
+        self.shared.volume.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the host has selected the operational streaming alternate setting.
+    pub fn streaming(&self) -> bool {
+        self.shared.streaming.load(Ordering::Relaxed)
+    }
+
+    /// Waits until the host changes a control or starts or stops streaming.
+    ///
+    /// Only one task should wait for changes at a time.
+    pub async fn wait_changed(&self) {
+        self.shared.changed.wait().await
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Topology {
+    Speaker,
+    Microphone,
+}
+
+/// Endpoints allocated by [`build`].
+struct Endpoints<'d, D: Driver<'d>> {
+    stream_out: Option<D::EndpointOut>,
+    stream_in: Option<D::EndpointIn>,
+    feedback: Option<D::EndpointIn>,
+    controls: AudioControls<'d>,
+}
+
+fn build<'d, D: Driver<'d>>(
+    builder: &mut Builder<'d, D>,
+    state: &'d mut State<'d>,
+    config: Config<'d>,
+    topology: Topology,
+) -> Endpoints<'d, D> {
+    assert!(!config.sample_rates.is_empty() && config.sample_rates.len() <= MAX_SAMPLE_RATES);
+    assert!(config.channels > 0 && config.channels <= MAX_CHANNELS);
+    assert!(matches!(config.subslot_size, 1..=4));
+    assert!(config.bit_resolution <= 8 * config.subslot_size);
+    assert!(builder.control_buf_len() >= 2 + 12 * config.sample_rates.len());
+
+    let (category, input_terminal, output_terminal) = match topology {
+        Topology::Speaker => (
+            FUNCTION_CATEGORY_DESKTOP_SPEAKER,
+            TERMINAL_USB_STREAMING,
+            TERMINAL_SPEAKER,
+        ),
+        Topology::Microphone => (
+            FUNCTION_CATEGORY_MICROPHONE,
+            TERMINAL_MICROPHONE,
+            TERMINAL_USB_STREAMING,
+        ),
+    };
+    let channels = config.channels;
+    let channel_config = config.channel_config().to_le_bytes();
+
+    let mut func = builder.function(USB_CLASS_AUDIO, FUNCTION_SUBCLASS_UNDEFINED, IP_VERSION_02_00);
+
+    // Audio control interface
+    let mut iface = func.interface();
+    let ac_if = iface.interface_number();
+    let mut alt = iface.alt_setting(USB_CLASS_AUDIO, SUBCLASS_AUDIOCONTROL, IP_VERSION_02_00, None);
+
+    let feature_unit_len = 6 + (channels as u16 + 1) * 4;
+    let total_len = 9 + 8 + 17 + feature_unit_len + 12;
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AC_HEADER, // bDescriptorSubtype
+            0x00,
+            0x02,     // bcdADC (2.00)
+            category, // bCategory
+            total_len as u8,
+            (total_len >> 8) as u8, // wTotalLength
+            0x00,                   // bmControls
+        ],
+    );
+    let (clock_attributes, clock_controls) = if config.sample_rates.len() > 1 {
+        // Internal programmable clock, sample rate read/write, validity read-only
+        (0x03, 0x07)
+    } else {
+        // Internal fixed clock, sample rate and validity read-only
+        (0x01, 0x05)
+    };
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AC_CLOCK_SOURCE,  // bDescriptorSubtype
+            CLOCK_SOURCE_ID,  // bClockID
+            clock_attributes, // bmAttributes
+            clock_controls,   // bmControls
+            0x00,             // bAssocTerminal
+            0x00,             // iClockSource
+        ],
+    );
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AC_INPUT_TERMINAL, // bDescriptorSubtype
+            INPUT_TERMINAL_ID, // bTerminalID
+            input_terminal as u8,
+            (input_terminal >> 8) as u8, // wTerminalType
+            0x00,                        // bAssocTerminal
+            CLOCK_SOURCE_ID,             // bCSourceID
+            channels,                    // bNrChannels
+            channel_config[0],
+            channel_config[1],
+            channel_config[2],
+            channel_config[3], // bmChannelConfig
+            0x00,              // iChannelNames
+            0x00,
+            0x00, // bmControls
+            0x00, // iTerminal
+        ],
+    );
+
+    let mut feature_unit = [0; 4 + 4 * (MAX_CHANNELS as usize + 1)];
+    feature_unit[0] = AC_FEATURE_UNIT; // bDescriptorSubtype
+    feature_unit[1] = FEATURE_UNIT_ID; // bUnitID
+    feature_unit[2] = INPUT_TERMINAL_ID; // bSourceID
+    feature_unit[3] = 0x0F; // bmaControls(0): master mute and volume read/write
+                            // bmaControls(1..) are all zero, the last byte is iFeature.
+    alt.descriptor(CS_INTERFACE, &feature_unit[..feature_unit_len as usize - 2]);
+
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AC_OUTPUT_TERMINAL, // bDescriptorSubtype
+            OUTPUT_TERMINAL_ID, // bTerminalID
+            output_terminal as u8,
+            (output_terminal >> 8) as u8, // wTerminalType
+            0x00,                         // bAssocTerminal
+            FEATURE_UNIT_ID,              // bSourceID
+            CLOCK_SOURCE_ID,              // bCSourceID
+            0x00,
+            0x00, // bmControls
+            0x00, // iTerminal
+        ],
+    );
+
+    // Audio streaming interface, zero-bandwidth alt setting 0 and operational alt setting 1.
+    let mut iface = func.interface();
+    let as_if = iface.interface_number();
+    let _alt = iface.alt_setting(USB_CLASS_AUDIO, SUBCLASS_AUDIOSTREAMING, IP_VERSION_02_00, None);
+    let mut alt = iface.alt_setting(USB_CLASS_AUDIO, SUBCLASS_AUDIOSTREAMING, IP_VERSION_02_00, None);
+
+    let terminal_link = match topology {
+        Topology::Speaker => INPUT_TERMINAL_ID,
+        Topology::Microphone => OUTPUT_TERMINAL_ID,
+    };
+    let formats = FORMAT_PCM.to_le_bytes();
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AS_GENERAL,    // bDescriptorSubtype
+            terminal_link, // bTerminalLink
+            0x00,          // bmControls
+            FORMAT_TYPE_I, // bFormatType
+            formats[0],
+            formats[1],
+            formats[2],
+            formats[3], // bmFormats
+            channels,   // bNrChannels
+            channel_config[0],
+            channel_config[1],
+            channel_config[2],
+            channel_config[3], // bmChannelConfig
+            0x00,              // iChannelNames
+        ],
+    );
+    alt.descriptor(
+        CS_INTERFACE,
+        &[
+            AS_FORMAT_TYPE,        // bDescriptorSubtype
+            FORMAT_TYPE_I,         // bFormatType
+            config.subslot_size,   // bSubslotSize
+            config.bit_resolution, // bBitResolution
+        ],
+    );
+
+    let max_packet_size = config.max_packet_size();
+    let cs_endpoint = [
+        EP_GENERAL, // bDescriptorSubtype
+        0x00,       // bmAttributes
+        0x00,       // bmControls
+        0x00,       // bLockDelayUnits
+        0x00, 0x00, // wLockDelay
+    ];
+    let endpoints = match topology {
+        Topology::Speaker => {
+            let stream_out = alt.endpoint_isochronous_out(
+                max_packet_size,
+                1,
+                SynchronizationType::Asynchronous,
+                UsageType::DataEndpoint,
+            );
+            alt.descriptor(CS_ENDPOINT, &cs_endpoint);
+            let feedback = alt.endpoint_isochronous_in(
+                FEEDBACK_PACKET_SIZE as u16,
+                1,
+                SynchronizationType::NoSynchronization,
+                UsageType::FeedbackEndpoint,
+            );
+            Endpoints {
+                stream_out: Some(stream_out),
+                stream_in: None,
+                feedback: Some(feedback),
+                controls: AudioControls { shared: &state.shared },
+            }
+        }
+        Topology::Microphone => {
+            let stream_in = alt.endpoint_isochronous_in(
+                max_packet_size,
+                1,
+                SynchronizationType::Asynchronous,
+                UsageType::DataEndpoint,
+            );
+            alt.descriptor(CS_ENDPOINT, &cs_endpoint);
+            Endpoints {
+                stream_out: None,
+                stream_in: Some(stream_in),
+                feedback: None,
+                controls: AudioControls { shared: &state.shared },
+            }
+        }
+    };
+
+    drop(func);
+
+    let control = state.control.write(Control {
+        ac_if,
+        as_if,
+        sample_rates: config.sample_rates,
+        volume_range: config.volume_range,
+        shared: &state.shared,
+    });
+    control.reset_controls();
+    builder.handler(control);
+
+    endpoints
+}
+
+/// Size of a full-speed feedback value, in 10.14 fixed point format.
+const FEEDBACK_PACKET_SIZE: usize = 3;
+
+/// USB audio speaker, receiving samples from the host.
+pub struct Speaker<'d, D: Driver<'d>> {
+    stream: Stream<'d, D>,
+    feedback: Feedback<'d, D>,
+}
+
+impl<'d, D: Driver<'d>> Speaker<'d, D> {
+    /// Creates a new speaker function.
+    ///
+    /// This allocates an isochronous OUT endpoint of [`Config::max_packet_size`] bytes for the
+    /// samples and an isochronous IN endpoint for the rate feedback.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
+        let endpoints = build(builder, state, config, Topology::Speaker);
+        Self {
+            stream: Stream {
+                ep: endpoints.stream_out.unwrap(),
+                controls: endpoints.controls,
+            },
+            feedback: Feedback {
+                ep: endpoints.feedback.unwrap(),
+            },
+        }
+    }
+
+    /// Gets the controls set by the host.
+    pub fn controls(&self) -> AudioControls<'d> {
+        self.stream.controls
+    }
+
+    /// Waits for the host to start streaming.
+    pub async fn wait_connection(&mut self) {
+        self.stream.wait_connection().await
+    }
+
+    /// Reads one frame worth of samples.
+    ///
+    /// See [`Stream::read_packet`].
+    pub async fn read_packet(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        self.stream.read_packet(buf).await
+    }
+
+    /// Writes a feedback value.
+    ///
+    /// See [`Feedback::write`].
+    pub async fn write_feedback(&mut self, samples_per_frame: u32) -> Result<(), EndpointError> {
+        self.feedback.write(samples_per_frame).await
+    }
+
+    /// Splits the speaker into the sample stream and the feedback writer.
+    ///
+    /// This allows handling them from separate tasks.
+    pub fn split(self) -> (Stream<'d, D>, Feedback<'d, D>) {
+        (self.stream, self.feedback)
+    }
+}
+
+/// Isochronous sample stream of a [`Speaker`].
+pub struct Stream<'d, D: Driver<'d>> {
+    ep: D::EndpointOut,
+    controls: AudioControls<'d>,
+}
+
+impl<'d, D: Driver<'d>> Stream<'d, D> {
+    /// Gets the controls set by the host.
+    pub fn controls(&self) -> AudioControls<'d> {
+        self.controls
+    }
+
+    /// Waits for the host to start streaming.
+    pub async fn wait_connection(&mut self) {
+        self.ep.wait_enabled().await
+    }
+
+    /// Reads one frame worth of interleaved samples.
+    ///
+    /// `buf` must be at least [`Config::max_packet_size`] bytes long. The number of samples
+    /// varies from frame to frame, depending on the sample rate and the feedback values.
+    pub async fn read_packet(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        self.ep.read(buf).await
+    }
+}
+
+/// Computes the nominal feedback value for a sample rate, for use with [`Feedback::write`].
+pub fn nominal_feedback(sample_rate: u32) -> u32 {
+    (((sample_rate as u64) << 16) / 1000) as u32
+}
+
+/// Explicit feedback endpoint of a [`Speaker`].
+pub struct Feedback<'d, D: Driver<'d>> {
+    ep: D::EndpointIn,
+}
+
+impl<'d, D: Driver<'d>> Feedback<'d, D> {
+    /// Waits for the host to start streaming.
+    pub async fn wait_connection(&mut self) {
+        self.ep.wait_enabled().await
+    }
+
+    /// Reports the rate at which the device consumes samples.
+    ///
+    /// `samples_per_frame` is the average number of samples consumed per 1 ms frame, in 16.16
+    /// fixed point format. The host adjusts the number of samples it sends to this rate, which
+    /// keeps the device buffer from over- or underflowing. It should be measured against the
+    /// start-of-frame clock, or derived from the fill level of the sample buffer.
+    pub async fn write(&mut self, samples_per_frame: u32) -> Result<(), EndpointError> {
+        // Full speed uses the 10.14 format.
+        let value = (samples_per_frame >> 2).to_le_bytes();
+        self.ep.write(&value[..FEEDBACK_PACKET_SIZE]).await
+    }
+}
+
+/// USB audio microphone, sending samples to the host.
+pub struct Microphone<'d, D: Driver<'d>> {
+    ep: D::EndpointIn,
+    controls: AudioControls<'d>,
+}
+
+impl<'d, D: Driver<'d>> Microphone<'d, D> {
+    /// Creates a new microphone function.
+    ///
+    /// This allocates an isochronous IN endpoint of [`Config::max_packet_size`] bytes.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
+        let endpoints = build(builder, state, config, Topology::Microphone);
+        Self {
+            ep: endpoints.stream_in.unwrap(),
+            controls: endpoints.controls,
+        }
+    }
+
+    /// Gets the controls set by the host.
+    pub fn controls(&self) -> AudioControls<'d> {
+        self.controls
+    }
+
+    /// Waits for the host to start streaming.
+    pub async fn wait_connection(&mut self) {
+        self.ep.wait_enabled().await
+    }
+
+    /// Writes one frame worth of interleaved samples.
+    ///
+    /// Send `sample_rate / 1000` samples per frame on average. As the device clock drifts
+    /// relative to the host, send one sample more or less from time to time to keep the local
+    /// buffer from over- or underflowing.
+    pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        self.ep.write(data).await
+    }
+}
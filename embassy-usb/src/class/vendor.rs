@@ -0,0 +1,370 @@
+//! Helper for vendor-specific USB classes.
+//!
+//! [`VendorClass`] adds one vendor-specific interface with a bulk IN and a bulk OUT endpoint, and
+//! routes the class and vendor control requests addressed to it. This takes care of the descriptor
+//! writing and [`Handler`] plumbing needed by one-off device protocols, which only have to
+//! implement the requests they care about.
+//!
+//! Control requests with data from the host can be handled in two ways:
+//!
+//! - synchronously, by a [`RequestHandler`] set in [`Config::request_handler`]. The handler must
+//!   answer immediately, from inside [`UsbDevice::run()`](crate::UsbDevice::run).
+//! - asynchronously, without a request handler. Requests of up to [`MAX_CONTROL_DATA`] bytes are
+//!   accepted and queued, and the application receives them with [`VendorClass::wait_control_out`].
+//!
+//! Requests with data to the host are always answered by the [`RequestHandler`], since the reply
+//! must be sent during the control transfer.
+
+use core::mem::MaybeUninit;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use heapless::Vec;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// Vendor-specific interface class code.
+pub const USB_CLASS_VENDOR: u8 = 0xff;
+
+/// Maximum data length of the control requests queued for [`VendorClass::wait_control_out`].
+pub const MAX_CONTROL_DATA: usize = 64;
+
+const CONTROL_QUEUE_LEN: usize = 4;
+
+/// Configuration for the vendor class.
+pub struct Config<'d> {
+    /// Handler for control requests.
+    ///
+    /// If `None`, control OUT requests are queued for [`VendorClass::wait_control_out`] and control
+    /// IN requests are rejected.
+    pub request_handler: Option<&'d dyn RequestHandler>,
+
+    /// Interface subclass code.
+    pub subclass: u8,
+
+    /// Interface protocol code.
+    pub protocol: u8,
+
+    /// Max packet size for both the IN and OUT endpoints.
+    pub max_packet_size: u16,
+
+    /// Also route vendor requests with the device as recipient to this class.
+    ///
+    /// Only one class of the device should set this. Requests addressed to the interface are
+    /// always routed.
+    pub device_requests: bool,
+
+    /// Bind the interface to the WinUSB driver on Windows, with the given device interface GUIDs.
+    ///
+    /// This adds an MS OS 2.0 compatible ID and `DeviceInterfaceGUIDs` registry property to the
+    /// function, and requires an MS OS 2.0 descriptor set to be added with
+    /// [`Builder::msos_descriptor`](crate::Builder::msos_descriptor).
+    #[cfg(feature = "msos-descriptor")]
+    pub winusb_guids: Option<&'d [&'d str]>,
+}
+
+impl<'d> Default for Config<'d> {
+    fn default() -> Self {
+        Self {
+            request_handler: None,
+            subclass: 0,
+            protocol: 0,
+            max_packet_size: 64,
+            device_requests: false,
+            #[cfg(feature = "msos-descriptor")]
+            winusb_guids: None,
+        }
+    }
+}
+
+/// Handler for vendor class control requests.
+///
+/// Only requests of type class or vendor, addressed to the interface of the class (or to the
+/// device, if [`Config::device_requests`] is set), are passed to the handler.
+pub trait RequestHandler {
+    /// Handles a control request with data from the host.
+    fn control_out(&self, req: Request, data: &[u8]) -> OutResponse {
+        let _ = (req, data);
+        OutResponse::Rejected
+    }
+
+    /// Handles a control request with data to the host, writing the reply into `buf`.
+    ///
+    /// Returns the length of the reply, or `None` to reject the request.
+    fn control_in(&self, req: Request, buf: &mut [u8]) -> Option<usize> {
+        let _ = (req, buf);
+        None
+    }
+}
+
+/// A control OUT request queued for the application.
+#[derive(Clone, Debug)]
+pub struct ControlOut {
+    /// The request from the SETUP packet.
+    pub request: Request,
+    /// The data of the request.
+    pub data: Vec<u8, MAX_CONTROL_DATA>,
+}
+
+/// Internal state for the vendor class.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    requests: Channel<CriticalSectionRawMutex, ControlOut, CONTROL_QUEUE_LEN>,
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            requests: Channel::new(),
+        }
+    }
+}
+
+struct Control<'d> {
+    if_num: InterfaceNumber,
+    device_requests: bool,
+    request_handler: Option<&'d dyn RequestHandler>,
+    requests: &'d Channel<CriticalSectionRawMutex, ControlOut, CONTROL_QUEUE_LEN>,
+}
+
+impl<'d> Control<'d> {
+    fn accepts(&self, req: &Request) -> bool {
+        match (req.request_type, req.recipient) {
+            (RequestType::Class | RequestType::Vendor, Recipient::Interface) => req.index == self.if_num.0 as u16,
+            (RequestType::Vendor, Recipient::Device) => self.device_requests,
+            _ => false,
+        }
+    }
+}
+
+impl<'d> Handler for Control<'d> {
+    fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
+        if !self.accepts(&req) {
+            return None;
+        }
+
+        if let Some(handler) = self.request_handler {
+            return Some(handler.control_out(req, data));
+        }
+
+        let data = match Vec::from_slice(data) {
+            Ok(data) => data,
+            Err(()) => return Some(OutResponse::Rejected),
+        };
+        match self.requests.try_send(ControlOut { request: req, data }) {
+            Ok(()) => Some(OutResponse::Accepted),
+            Err(_) => {
+                warn!("vendor: control request queue full");
+                Some(OutResponse::Rejected)
+            }
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if !self.accepts(&req) {
+            return None;
+        }
+
+        match self.request_handler.and_then(|h| h.control_in(req, buf)) {
+            Some(len) => Some(InResponse::Accepted(&buf[..len])),
+            None => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Vendor-specific class with a bulk IN and a bulk OUT endpoint.
+pub struct VendorClass<'d, D: Driver<'d>> {
+    if_num: InterfaceNumber,
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    requests: &'d Channel<CriticalSectionRawMutex, ControlOut, CONTROL_QUEUE_LEN>,
+}
+
+impl<'d, D: Driver<'d>> VendorClass<'d, D> {
+    /// Creates a new vendor class, adding its interface and endpoints to the builder.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) -> Self {
+        let mut func = builder.function(USB_CLASS_VENDOR, config.subclass, config.protocol);
+
+        #[cfg(feature = "msos-descriptor")]
+        if let Some(guids) = config.winusb_guids {
+            use crate::msos::{CompatibleIdFeatureDescriptor, PropertyData, RegistryPropertyFeatureDescriptor};
+
+            func.msos_feature(CompatibleIdFeatureDescriptor::new("WINUSB", ""));
+            func.msos_feature(RegistryPropertyFeatureDescriptor::new(
+                "DeviceInterfaceGUIDs",
+                PropertyData::RegMultiSz(guids),
+            ));
+        }
+
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_VENDOR, config.subclass, config.protocol, None);
+        let read_ep = alt.endpoint_bulk_out(config.max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(config.max_packet_size);
+
+        drop(func);
+
+        let control = state.control.write(Control {
+            if_num,
+            device_requests: config.device_requests,
+            request_handler: config.request_handler,
+            requests: &state.requests,
+        });
+        builder.handler(control);
+
+        VendorClass {
+            if_num,
+            read_ep,
+            write_ep,
+            requests: &state.requests,
+        }
+    }
+
+    /// Gets the interface number of the class.
+    pub fn interface_number(&self) -> InterfaceNumber {
+        self.if_num
+    }
+
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        // The size is the same for both endpoints.
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Waits for the host to select the configuration of this class.
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+
+    /// Waits for a control OUT request queued for the application.
+    ///
+    /// Requests are only queued if [`Config::request_handler`] is `None`. The queue is short, so
+    /// requests arriving while the application doesn't keep up are rejected.
+    pub async fn wait_control_out(&self) -> ControlOut {
+        self.requests.receive().await
+    }
+
+    /// Gets a handle to wait for queued control OUT requests, which can be used after [`Self::split`].
+    pub fn control_requests(&self) -> ControlRequests<'d> {
+        ControlRequests {
+            requests: self.requests,
+        }
+    }
+
+    /// Reads a single packet from the OUT endpoint.
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.read_ep.read(data).await
+    }
+
+    /// Writes a single packet into the IN endpoint.
+    pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        self.write_ep.write(data).await
+    }
+
+    /// Writes `data` as one bulk transfer, split into packets.
+    ///
+    /// A zero-length packet is added if needed, so the host sees the end of the transfer.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        write::<D>(&mut self.write_ep, data).await
+    }
+
+    /// Split the class into a sender and receiver.
+    ///
+    /// This allows concurrently sending and receiving packets from separate tasks.
+    pub fn split(self) -> (Sender<'d, D>, Receiver<'d, D>) {
+        (
+            Sender {
+                write_ep: self.write_ep,
+            },
+            Receiver { read_ep: self.read_ep },
+        )
+    }
+}
+
+/// Handle to wait for queued control OUT requests.
+///
+/// You can obtain a `ControlRequests` with [`VendorClass::control_requests`]
+#[derive(Clone, Copy)]
+pub struct ControlRequests<'d> {
+    requests: &'d Channel<CriticalSectionRawMutex, ControlOut, CONTROL_QUEUE_LEN>,
+}
+
+impl<'d> ControlRequests<'d> {
+    /// Waits for a control OUT request queued for the application.
+    ///
+    /// See [`VendorClass::wait_control_out`].
+    pub async fn wait_control_out(&self) -> ControlOut {
+        self.requests.receive().await
+    }
+}
+
+async fn write<'d, D: Driver<'d>>(ep: &mut D::EndpointIn, data: &[u8]) -> Result<(), EndpointError> {
+    let max_packet_size = ep.info().max_packet_size as usize;
+    for chunk in data.chunks(max_packet_size) {
+        ep.write(chunk).await?;
+    }
+    if data.len() % max_packet_size == 0 {
+        ep.write(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Vendor class packet sender.
+///
+/// You can obtain a `Sender` with [`VendorClass::split`]
+pub struct Sender<'d, D: Driver<'d>> {
+    write_ep: D::EndpointIn,
+}
+
+impl<'d, D: Driver<'d>> Sender<'d, D> {
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.write_ep.info().max_packet_size
+    }
+
+    /// Waits for the host to select the configuration of this class.
+    pub async fn wait_connection(&mut self) {
+        self.write_ep.wait_enabled().await
+    }
+
+    /// Writes a single packet into the IN endpoint.
+    pub async fn write_packet(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        self.write_ep.write(data).await
+    }
+
+    /// Writes `data` as one bulk transfer, split into packets.
+    ///
+    /// A zero-length packet is added if needed, so the host sees the end of the transfer.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        write::<D>(&mut self.write_ep, data).await
+    }
+}
+
+/// Vendor class packet receiver.
+///
+/// You can obtain a `Receiver` with [`VendorClass::split`]
+pub struct Receiver<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+}
+
+impl<'d, D: Driver<'d>> Receiver<'d, D> {
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Waits for the host to select the configuration of this class.
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await
+    }
+
+    /// Reads a single packet from the OUT endpoint.
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.read_ep.read(data).await
+    }
+}
@@ -0,0 +1,144 @@
+//! WebUSB support.
+//!
+//! Adds the WebUSB platform capability to the BOS descriptor and answers the `GET_URL` request,
+//! so browsers implementing the WebUSB API can discover the device and show a landing page.
+//!
+//! <https://wicg.github.io/webusb/>
+//!
+//! WebUSB only describes the device to the browser, the actual data is exchanged over a vendor
+//! interface. On Windows, that interface also needs a WinUSB compatible ID, for example with an MS OS
+//! 2.0 descriptor (see [`FunctionBuilder::msos_feature`](crate::FunctionBuilder::msos_feature)).
+
+use core::mem::MaybeUninit;
+
+use crate::control::{InResponse, Recipient, Request, RequestType};
+use crate::descriptor::capability_type;
+use crate::driver::Driver;
+use crate::{Builder, Handler};
+
+const USB_BCD_WEBUSB: u16 = 0x0100;
+
+/// WebUSB platform capability UUID {3408b638-09a9-47a0-8bfd-a0768815b665}, in little-endian layout.
+const WEBUSB_PLATFORM_UUID: [u8; 16] = [
+    0x38, 0xb6, 0x08, 0x34, 0xa9, 0x09, 0xa0, 0x47, 0x8b, 0xfd, 0xa0, 0x76, 0x88, 0x15, 0xb6, 0x65,
+];
+
+const WEBUSB_REQUEST_GET_URL: u16 = 0x02;
+const WEBUSB_DESCRIPTOR_TYPE_URL: u8 = 0x03;
+
+const LANDING_PAGE_INDEX: u8 = 1;
+
+/// A URL for use in WebUSB descriptors.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Url<'d> {
+    scheme: u8,
+    url: &'d str,
+}
+
+impl<'d> Url<'d> {
+    /// Create a new URL.
+    ///
+    /// The `http://` or `https://` prefix is encoded as a scheme byte, any other URL is sent as is.
+    pub fn new(url: &'d str) -> Self {
+        let (scheme, url) = if let Some(url) = url.strip_prefix("https://") {
+            (1, url)
+        } else if let Some(url) = url.strip_prefix("http://") {
+            (0, url)
+        } else {
+            (255, url)
+        };
+        Self { scheme, url }
+    }
+
+    fn write<'a>(&self, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let len = 3 + self.url.len();
+        if len > buf.len() || len > 255 {
+            return None;
+        }
+        buf[0] = len as u8;
+        buf[1] = WEBUSB_DESCRIPTOR_TYPE_URL;
+        buf[2] = self.scheme;
+        buf[3..len].copy_from_slice(self.url.as_bytes());
+        Some(&buf[..len])
+    }
+}
+
+/// Configuration for WebUSB.
+pub struct Config<'d> {
+    /// Vendor code the host uses to request WebUSB descriptors.
+    ///
+    /// This can be the same as the MS OS 2.0 vendor code.
+    pub vendor_code: u8,
+
+    /// URL the browser suggests opening when the device is plugged in.
+    pub landing_url: Option<Url<'d>>,
+}
+
+/// Internal state for WebUSB.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+        }
+    }
+}
+
+/// WebUSB capability of a device.
+pub struct WebUsb;
+
+impl WebUsb {
+    /// Adds the WebUSB platform capability to the device and registers the `GET_URL` handler.
+    pub fn configure<'d, D: Driver<'d>>(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config<'d>) {
+        let version = USB_BCD_WEBUSB.to_le_bytes();
+        let landing_page = match config.landing_url {
+            Some(_) => LANDING_PAGE_INDEX,
+            None => 0,
+        };
+
+        let mut data = [0; 21];
+        data[0] = 0; // bReserved
+        data[1..17].copy_from_slice(&WEBUSB_PLATFORM_UUID);
+        data[17..19].copy_from_slice(&version);
+        data[19] = config.vendor_code;
+        data[20] = landing_page;
+        builder.bos_capability(capability_type::PLATFORM, &data);
+
+        builder.handler(state.control.write(Control {
+            vendor_code: config.vendor_code,
+            landing_url: config.landing_url,
+        }));
+    }
+}
+
+struct Control<'d> {
+    vendor_code: u8,
+    landing_url: Option<Url<'d>>,
+}
+
+impl<'d> Handler for Control<'d> {
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient) != (RequestType::Vendor, Recipient::Device)
+            || req.request != self.vendor_code
+            || req.index != WEBUSB_REQUEST_GET_URL
+        {
+            return None;
+        }
+
+        match self.landing_url {
+            Some(url) if req.value == LANDING_PAGE_INDEX as u16 => match url.write(buf) {
+                Some(data) => Some(InResponse::Accepted(data)),
+                None => {
+                    warn!("WebUSB: control buffer too small for the landing page URL");
+                    Some(InResponse::Rejected)
+                }
+            },
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
@@ -99,6 +99,9 @@ impl Request {
     /// Standard USB feature Device Remote Wakeup for Set/Clear Feature
     pub const FEATURE_DEVICE_REMOTE_WAKEUP: u16 = 1;
 
+    /// Standard USB feature Test Mode for Set Feature
+    pub const FEATURE_TEST_MODE: u16 = 2;
+
     /// Parses a USB control request from a byte array.
     pub fn parse(buf: &[u8; 8]) -> Request {
         let rt = buf[0];
@@ -3,7 +3,6 @@
 use crate::builder::Config;
 use crate::driver::EndpointInfo;
 use crate::types::*;
-use crate::CONFIGURATION_VALUE;
 
 /// Standard descriptor types
 #[allow(missing_docs)]
@@ -36,10 +35,39 @@ pub mod capability_type {
     pub const PLATFORM: u8 = 5;
 }
 
+/// Isochronous endpoint synchronization type, bits 2..3 of `bmAttributes`.
+#[repr(u8)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SynchronizationType {
+    /// No synchronization. Used for all non-isochronous endpoints.
+    NoSynchronization = 0b00,
+    /// The endpoint runs on its own clock, rate matching is done with a feedback endpoint.
+    Asynchronous = 0b01,
+    /// The endpoint adapts to the data rate of the host.
+    Adaptive = 0b10,
+    /// The endpoint is locked to the USB start-of-frame clock.
+    Synchronous = 0b11,
+}
+
+/// Isochronous endpoint usage type, bits 4..5 of `bmAttributes`.
+#[repr(u8)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UsageType {
+    /// Data endpoint. Used for all non-isochronous endpoints.
+    DataEndpoint = 0b00,
+    /// Explicit feedback endpoint.
+    FeedbackEndpoint = 0b01,
+    /// Data endpoint that also provides implicit feedback.
+    ImplicitFeedbackDataEndpoint = 0b10,
+}
+
 /// A writer for USB descriptors.
 pub(crate) struct DescriptorWriter<'a> {
     pub buf: &'a mut [u8],
     position: usize,
+    configuration_mark: usize,
     num_interfaces_mark: Option<usize>,
     num_endpoints_mark: Option<usize>,
 }
@@ -49,6 +77,7 @@ impl<'a> DescriptorWriter<'a> {
         DescriptorWriter {
             buf,
             position: 0,
+            configuration_mark: 0,
             num_interfaces_mark: None,
             num_endpoints_mark: None,
         }
@@ -105,20 +134,21 @@ impl<'a> DescriptorWriter<'a> {
         )
     }
 
-    pub(crate) fn configuration(&mut self, config: &Config) {
+    pub(crate) fn configuration(&mut self, config: &Config, value: u8, config_string: Option<StringIndex>) {
+        self.configuration_mark = self.position;
         self.num_interfaces_mark = Some(self.position + 4);
 
         self.write(
             descriptor_type::CONFIGURATION,
             &[
                 0,
-                0,                   // wTotalLength
-                0,                   // bNumInterfaces
-                CONFIGURATION_VALUE, // bConfigurationValue
-                0,                   // iConfiguration
+                0,                                   // wTotalLength
+                0,                                   // bNumInterfaces
+                value,                               // bConfigurationValue
+                config_string.map_or(0, Into::into), // iConfiguration
                 0x80 | if config.self_powered { 0x40 } else { 0x00 }
                     | if config.supports_remote_wakeup { 0x20 } else { 0x00 }, // bmAttributes
-                (config.max_power / 2) as u8, // bMaxPower
+                (config.max_power / 2) as u8,        // bMaxPower
             ],
         )
     }
@@ -129,8 +159,16 @@ impl<'a> DescriptorWriter<'a> {
     }
 
     pub(crate) fn end_configuration(&mut self) {
-        let position = self.position as u16;
-        self.buf[2..4].copy_from_slice(&position.to_le_bytes());
+        let mark = self.configuration_mark;
+        let total_length = (self.position - mark) as u16;
+        self.buf[mark + 2..mark + 4].copy_from_slice(&total_length.to_le_bytes());
+        self.num_interfaces_mark = None;
+        self.num_endpoints_mark = None;
+    }
+
+    pub(crate) fn set_num_configurations(&mut self, count: u8) {
+        // bNumConfigurations is the last byte of the device descriptor.
+        self.buf[17] = count;
     }
 
     /// Writes a interface association descriptor. Call from `UsbClass::get_configuration_descriptors`
@@ -225,17 +263,26 @@ impl<'a> DescriptorWriter<'a> {
     ///
     /// * `endpoint` - Endpoint previously allocated with
     ///   [`UsbDeviceBuilder`](crate::bus::UsbDeviceBuilder).
-    pub fn endpoint(&mut self, endpoint: &EndpointInfo) {
+    /// * `synchronization_type` - Synchronization type, only meaningful for isochronous endpoints.
+    /// * `usage_type` - Usage type, only meaningful for isochronous endpoints.
+    pub fn endpoint(
+        &mut self,
+        endpoint: &EndpointInfo,
+        synchronization_type: SynchronizationType,
+        usage_type: UsageType,
+    ) {
         match self.num_endpoints_mark {
             Some(mark) => self.buf[mark] += 1,
             None => panic!("you can only call `endpoint` after `interface/interface_alt`."),
         };
 
+        let attributes = endpoint.ep_type as u8 | (synchronization_type as u8) << 2 | (usage_type as u8) << 4;
+
         self.write(
             descriptor_type::ENDPOINT,
             &[
-                endpoint.addr.into(),   // bEndpointAddress
-                endpoint.ep_type as u8, // bmAttributes
+                endpoint.addr.into(), // bEndpointAddress
+                attributes,           // bmAttributes
                 endpoint.max_packet_size as u8,
                 (endpoint.max_packet_size >> 8) as u8, // wMaxPacketSize
                 endpoint.interval_ms,                  // bInterval
@@ -1,4 +1,5 @@
 #![no_std]
+#![feature(async_fn_in_trait)]
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 
@@ -28,7 +29,7 @@ use crate::config::*;
 use crate::control::*;
 use crate::descriptor::*;
 use crate::descriptor_reader::foreach_endpoint;
-use crate::driver::{Bus, ControlPipe, Direction, Driver, EndpointAddress, Event};
+use crate::driver::{Bus, ControlPipe, Direction, Driver, EndpointAddress, Event, TestMode};
 use crate::types::*;
 
 /// The global state of the USB device.
@@ -54,6 +55,23 @@ pub enum UsbDeviceState {
     Configured,
 }
 
+/// Bus power management event returned by [`UsbDevice::run_until_power_event`].
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerEvent {
+    /// The host suspended the bus.
+    ///
+    /// A bus-powered device must reduce its current draw from VBUS to at most 2.5 mA within
+    /// 10 ms, until the bus is resumed.
+    Suspended,
+    /// The bus was resumed by the host, by a bus reset, or by a remote wakeup.
+    Resumed,
+    /// VBUS was detected and the USB peripheral has been enabled.
+    PowerDetected,
+    /// VBUS was removed and the USB peripheral has been disabled.
+    PowerRemoved,
+}
+
 /// Error returned by [`UsbDevice::remote_wakeup`].
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -73,7 +91,9 @@ impl From<driver::Unsupported> for RemoteWakeupError {
 /// The bConfiguration value for the not configured state.
 pub const CONFIGURATION_NONE: u8 = 0;
 
-/// The bConfiguration value for the single configuration supported by this device.
+/// The bConfiguration value for the first configuration of this device.
+///
+/// Additional configurations added with [`Builder::configuration`] use consecutive values.
 pub const CONFIGURATION_VALUE: u8 = 1;
 
 const STRING_INDEX_MANUFACTURER: u8 = 1;
@@ -96,9 +116,14 @@ pub trait Handler {
     fn addressed(&mut self, _addr: u8) {}
 
     /// Called when the host has enabled or disabled the configuration of the device.
+    ///
+    /// Only called on handlers belonging to the configuration that is enabled or disabled.
     fn configured(&mut self, _configured: bool) {}
 
     /// Called when the bus has entered or exited the suspend state.
+    ///
+    /// While suspended, a bus-powered device must draw at most 2.5 mA from VBUS. This is the
+    /// place to stop peripherals that are only needed while the host is active.
     fn suspended(&mut self, _suspended: bool) {}
 
     /// Called when remote wakeup feature is enabled or disabled.
@@ -160,6 +185,7 @@ pub trait Handler {
 }
 
 struct Interface {
+    configuration: u8,
     current_alt_setting: u8,
     num_alt_settings: u8,
 }
@@ -199,6 +225,8 @@ struct Inner<'d, D: Driver<'d>> {
     bos_descriptor: &'d [u8],
 
     device_state: UsbDeviceState,
+    /// bConfigurationValue of the selected configuration, or `CONFIGURATION_NONE`.
+    configuration: u8,
     suspended: bool,
     remote_wakeup_enabled: bool,
     self_powered: bool,
@@ -209,9 +237,13 @@ struct Inner<'d, D: Driver<'d>> {
     /// This flag indicates that requests must be handled by `ControlPipe::accept_set_address()`
     /// instead of regular `accept()`.
     set_address_pending: bool,
+    /// Test mode to enter after the status stage of the SET_FEATURE(TEST_MODE) request.
+    test_mode_pending: Option<TestMode>,
 
     interfaces: Vec<Interface, MAX_INTERFACE_COUNT>,
     handlers: Vec<&'d mut dyn Handler, MAX_HANDLER_COUNT>,
+    /// The configuration each handler belongs to.
+    handler_configurations: Vec<u8, MAX_HANDLER_COUNT>,
 
     #[cfg(feature = "msos-descriptor")]
     msos_descriptor: crate::msos::MsOsDescriptorSet<'d>,
@@ -222,6 +254,7 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
         driver: D,
         config: Config<'d>,
         handlers: Vec<&'d mut dyn Handler, MAX_HANDLER_COUNT>,
+        handler_configurations: Vec<u8, MAX_HANDLER_COUNT>,
         device_descriptor: &'d [u8],
         config_descriptor: &'d [u8],
         bos_descriptor: &'d [u8],
@@ -244,13 +277,16 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
                 bos_descriptor,
 
                 device_state: UsbDeviceState::Unpowered,
+                configuration: CONFIGURATION_NONE,
                 suspended: false,
                 remote_wakeup_enabled: false,
                 self_powered: false,
                 address: 0,
                 set_address_pending: false,
+                test_mode_pending: None,
                 interfaces,
                 handlers,
+                handler_configurations,
                 #[cfg(feature = "msos-descriptor")]
                 msos_descriptor,
             },
@@ -299,12 +335,57 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
             let control_fut = self.control.setup();
             let bus_fut = self.inner.bus.poll();
             match select(bus_fut, control_fut).await {
-                Either::First(evt) => self.inner.handle_bus_event(evt).await,
+                Either::First(evt) => {
+                    self.inner.handle_bus_event(evt).await;
+                }
                 Either::Second(req) => self.handle_control(req).await,
             }
         }
     }
 
+    /// Runs the `UsbDevice` until the next bus power management event.
+    ///
+    /// Control requests are handled while the bus is active. While it is suspended, only bus
+    /// events are processed. The event has already been delivered to all handlers when this
+    /// returns, so the application can for example enter a low-power state on
+    /// [`PowerEvent::Suspended`] and leave it on [`PowerEvent::Resumed`].
+    ///
+    /// This future may leave the bus in an invalid state if it is dropped.
+    /// After dropping the future, [`UsbDevice::disable()`] should be called
+    /// before calling any other `UsbDevice` methods to fully reset the
+    /// peripheral.
+    pub async fn run_until_power_event(&mut self) -> PowerEvent {
+        loop {
+            let evt = if self.inner.suspended {
+                self.inner.bus.poll().await
+            } else {
+                let control_fut = self.control.setup();
+                let bus_fut = self.inner.bus.poll();
+                match select(bus_fut, control_fut).await {
+                    Either::First(evt) => evt,
+                    Either::Second(req) => {
+                        self.handle_control(req).await;
+                        continue;
+                    }
+                }
+            };
+
+            if let Some(evt) = self.inner.handle_bus_event(evt).await {
+                return evt;
+            }
+        }
+    }
+
+    /// Returns the current state of the device.
+    pub fn state(&self) -> UsbDeviceState {
+        self.inner.device_state
+    }
+
+    /// Returns `true` if the bus is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.inner.suspended
+    }
+
     /// Disables the USB peripheral.
     pub async fn disable(&mut self) {
         if self.inner.device_state != UsbDeviceState::Disabled {
@@ -437,6 +518,13 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
                 } else {
                     self.control.accept().await
                 }
+
+                if let Some(mode) = self.inner.test_mode_pending.take() {
+                    match self.inner.bus.set_test_mode(mode) {
+                        Ok(()) => info!("usb: entered test mode {:?}", mode),
+                        Err(_) => warn!("usb: test mode {:?} not supported by the driver", mode),
+                    }
+                }
             }
             OutResponse::Rejected => self.control.reject().await,
         }
@@ -444,11 +532,14 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
 }
 
 impl<'d, D: Driver<'d>> Inner<'d, D> {
-    async fn handle_bus_event(&mut self, evt: Event) {
+    /// Handles a bus event, returning the corresponding power management event if any.
+    async fn handle_bus_event(&mut self, evt: Event) -> Option<PowerEvent> {
         match evt {
             Event::Reset => {
                 trace!("usb: reset");
+                let was_suspended = self.suspended;
                 self.device_state = UsbDeviceState::Default;
+                self.configuration = CONFIGURATION_NONE;
                 self.suspended = false;
                 self.remote_wakeup_enabled = false;
                 self.address = 0;
@@ -457,12 +548,31 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                     h.reset();
                 }
 
-                for (i, iface) in self.interfaces.iter_mut().enumerate() {
+                let mut configuration = CONFIGURATION_NONE;
+                let mut number = 0;
+                for iface in self.interfaces.iter_mut() {
+                    if iface.configuration != configuration {
+                        configuration = iface.configuration;
+                        number = 0;
+                    }
                     iface.current_alt_setting = 0;
 
+                    for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+                        if c == configuration {
+                            h.set_alternate_setting(InterfaceNumber::new(number), 0);
+                        }
+                    }
+                    number += 1;
+                }
+
+                // A reset also ends the suspend state.
+                if was_suspended {
                     for h in &mut self.handlers {
-                        h.set_alternate_setting(InterfaceNumber::new(i as _), 0);
+                        h.suspended(false);
                     }
+                    Some(PowerEvent::Resumed)
+                } else {
+                    None
                 }
             }
             Event::Resume => {
@@ -471,6 +581,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 for h in &mut self.handlers {
                     h.suspended(false);
                 }
+                Some(PowerEvent::Resumed)
             }
             Event::Suspend => {
                 trace!("usb: suspend");
@@ -478,6 +589,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 for h in &mut self.handlers {
                     h.suspended(true);
                 }
+                Some(PowerEvent::Suspended)
             }
             Event::PowerDetected => {
                 trace!("usb: power detected");
@@ -487,6 +599,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 for h in &mut self.handlers {
                     h.enabled(true);
                 }
+                Some(PowerEvent::PowerDetected)
             }
             Event::PowerRemoved => {
                 trace!("usb: power removed");
@@ -496,13 +609,53 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 for h in &mut self.handlers {
                     h.enabled(false);
                 }
+                Some(PowerEvent::PowerRemoved)
             }
         }
     }
 
+    /// Disables the endpoints of the selected configuration and notifies its handlers.
+    fn deconfigure(&mut self) {
+        let configuration = self.configuration;
+        if configuration == CONFIGURATION_NONE {
+            return;
+        }
+        self.configuration = CONFIGURATION_NONE;
+
+        // Disable all endpoints.
+        foreach_endpoint(self.config_descriptor, |ep| {
+            if ep.configuration == configuration {
+                self.bus.endpoint_set_enabled(ep.ep_address, false);
+            }
+        })
+        .unwrap();
+
+        // Notify handlers.
+        for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+            if c == configuration {
+                h.configured(false);
+            }
+        }
+    }
+
+    /// Checks `configuration` is one of the configurations written by the builder.
+    fn has_configuration(&self, configuration: u8) -> bool {
+        configuration_descriptor(self.config_descriptor, configuration - 1).is_some()
+    }
+
+    fn find_interface(
+        interfaces: &mut [Interface],
+        configuration: u8,
+        number: InterfaceNumber,
+    ) -> Option<&mut Interface> {
+        interfaces
+            .iter_mut()
+            .filter(|iface| iface.configuration == configuration)
+            .nth(number.0 as usize)
+    }
+
     fn handle_control_out(&mut self, req: Request, data: &[u8]) -> OutResponse {
         const CONFIGURATION_NONE_U16: u16 = CONFIGURATION_NONE as u16;
-        const CONFIGURATION_VALUE_U16: u16 = CONFIGURATION_VALUE as u16;
 
         match (req.request_type, req.recipient) {
             (RequestType::Standard, Recipient::Device) => match (req.request, req.value) {
@@ -514,12 +667,26 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                     OutResponse::Accepted
                 }
                 (Request::SET_FEATURE, Request::FEATURE_DEVICE_REMOTE_WAKEUP) => {
+                    // Only allowed if advertised in the configuration descriptor.
+                    if !self.config.supports_remote_wakeup {
+                        return OutResponse::Rejected;
+                    }
                     self.remote_wakeup_enabled = true;
                     for h in &mut self.handlers {
                         h.remote_wakeup_enabled(true);
                     }
                     OutResponse::Accepted
                 }
+                (Request::SET_FEATURE, Request::FEATURE_TEST_MODE) => {
+                    // The low byte of wIndex must be zero, the high byte is the test selector.
+                    let mode = match (req.index as u8, TestMode::from_selector((req.index >> 8) as u8)) {
+                        (0, Some(mode)) => mode,
+                        _ => return OutResponse::Rejected,
+                    };
+                    debug!("SET_FEATURE: test mode {:?}", mode);
+                    self.test_mode_pending = Some(mode);
+                    OutResponse::Accepted
+                }
                 (Request::SET_ADDRESS, addr @ 1..=127) => {
                     self.address = addr as u8;
                     self.set_address_pending = true;
@@ -529,50 +696,53 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                     }
                     OutResponse::Accepted
                 }
-                (Request::SET_CONFIGURATION, CONFIGURATION_VALUE_U16) => {
-                    debug!("SET_CONFIGURATION: configured");
+                (Request::SET_CONFIGURATION, CONFIGURATION_NONE_U16) => match self.device_state {
+                    UsbDeviceState::Default => OutResponse::Accepted,
+                    _ => {
+                        debug!("SET_CONFIGURATION: unconfigured");
+                        self.device_state = UsbDeviceState::Addressed;
+                        self.deconfigure();
+                        OutResponse::Accepted
+                    }
+                },
+                (Request::SET_CONFIGURATION, value) => {
+                    let value = match u8::try_from(value) {
+                        Ok(value) if self.has_configuration(value) => value,
+                        _ => {
+                            warn!("SET_CONFIGURATION: unknown configuration {}", value);
+                            return OutResponse::Rejected;
+                        }
+                    };
+
+                    debug!("SET_CONFIGURATION: configured {}", value);
+                    self.deconfigure();
                     self.device_state = UsbDeviceState::Configured;
+                    self.configuration = value;
 
                     // Enable all endpoints of selected alt settings.
                     foreach_endpoint(self.config_descriptor, |ep| {
-                        let iface = &self.interfaces[ep.interface.0 as usize];
-                        self.bus
-                            .endpoint_set_enabled(ep.ep_address, iface.current_alt_setting == ep.interface_alt);
+                        if ep.configuration == value {
+                            let iface = Self::find_interface(&mut self.interfaces, value, ep.interface).unwrap();
+                            self.bus
+                                .endpoint_set_enabled(ep.ep_address, iface.current_alt_setting == ep.interface_alt);
+                        }
                     })
                     .unwrap();
 
                     // Notify handlers.
-                    for h in &mut self.handlers {
-                        h.configured(true);
+                    for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+                        if c == value {
+                            h.configured(true);
+                        }
                     }
 
                     OutResponse::Accepted
                 }
-                (Request::SET_CONFIGURATION, CONFIGURATION_NONE_U16) => match self.device_state {
-                    UsbDeviceState::Default => OutResponse::Accepted,
-                    _ => {
-                        debug!("SET_CONFIGURATION: unconfigured");
-                        self.device_state = UsbDeviceState::Addressed;
-
-                        // Disable all endpoints.
-                        foreach_endpoint(self.config_descriptor, |ep| {
-                            self.bus.endpoint_set_enabled(ep.ep_address, false);
-                        })
-                        .unwrap();
-
-                        // Notify handlers.
-                        for h in &mut self.handlers {
-                            h.configured(false);
-                        }
-
-                        OutResponse::Accepted
-                    }
-                },
                 _ => OutResponse::Rejected,
             },
             (RequestType::Standard, Recipient::Interface) => {
                 let iface_num = InterfaceNumber::new(req.index as _);
-                let iface = match self.interfaces.get_mut(iface_num.0 as usize) {
+                let iface = match Self::find_interface(&mut self.interfaces, self.configuration, iface_num) {
                     Some(iface) => iface,
                     None => return OutResponse::Rejected,
                 };
@@ -589,18 +759,19 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                         iface.current_alt_setting = new_altsetting;
 
                         // Enable/disable EPs of this interface as needed.
+                        let configuration = self.configuration;
                         foreach_endpoint(self.config_descriptor, |ep| {
-                            if ep.interface == iface_num {
+                            if ep.configuration == configuration && ep.interface == iface_num {
                                 self.bus
                                     .endpoint_set_enabled(ep.ep_address, iface.current_alt_setting == ep.interface_alt);
                             }
                         })
                         .unwrap();
 
-                        // TODO check it is valid (not out of range)
-
-                        for h in &mut self.handlers {
-                            h.set_alternate_setting(iface_num, new_altsetting);
+                        for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+                            if c == configuration {
+                                h.set_alternate_setting(iface_num, new_altsetting);
+                            }
                         }
                         OutResponse::Accepted
                     }
@@ -640,17 +811,14 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 }
                 Request::GET_DESCRIPTOR => self.handle_get_descriptor(req, buf),
                 Request::GET_CONFIGURATION => {
-                    let status = match self.device_state {
-                        UsbDeviceState::Configured => CONFIGURATION_VALUE,
-                        _ => CONFIGURATION_NONE,
-                    };
-                    buf[0] = status;
+                    buf[0] = self.configuration;
                     InResponse::Accepted(&buf[..1])
                 }
                 _ => InResponse::Rejected,
             },
             (RequestType::Standard, Recipient::Interface) => {
-                let iface = match self.interfaces.get_mut(req.index as usize) {
+                let iface_num = InterfaceNumber::new(req.index as _);
+                let iface = match Self::find_interface(&mut self.interfaces, self.configuration, iface_num) {
                     Some(iface) => iface,
                     None => return InResponse::Rejected,
                 };
@@ -697,7 +865,12 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
     }
 
     fn handle_control_out_delegated(&mut self, req: Request, data: &[u8]) -> OutResponse {
-        for h in &mut self.handlers {
+        let configuration = self.configuration;
+        for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+            // Interface numbers are only unique within a configuration.
+            if req.recipient == Recipient::Interface && c != configuration {
+                continue;
+            }
             if let Some(res) = h.control_out(req, data) {
                 return res;
             }
@@ -710,7 +883,12 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
             core::mem::transmute(r)
         }
 
-        for h in &mut self.handlers {
+        let configuration = self.configuration;
+        for (h, &c) in self.handlers.iter_mut().zip(&self.handler_configurations) {
+            // Interface numbers are only unique within a configuration.
+            if req.recipient == Recipient::Interface && c != configuration {
+                continue;
+            }
             if let Some(res) = h.control_in(req, buf) {
                 // safety: the borrow checker isn't smart enough to know this pattern (returning a
                 // borrowed value from inside the loop) is sound. Workaround by unsafely extending lifetime.
@@ -728,7 +906,10 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
         match dtype {
             descriptor_type::BOS => InResponse::Accepted(self.bos_descriptor),
             descriptor_type::DEVICE => InResponse::Accepted(self.device_descriptor),
-            descriptor_type::CONFIGURATION => InResponse::Accepted(self.config_descriptor),
+            descriptor_type::CONFIGURATION => match configuration_descriptor(self.config_descriptor, index) {
+                Some(desc) => InResponse::Accepted(desc),
+                None => InResponse::Rejected,
+            },
             descriptor_type::STRING => {
                 if index == 0 {
                     buf[0] = 4; // len
@@ -783,6 +964,17 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
     }
 }
 
+/// Returns the configuration descriptor with the given index, including all its interface and
+/// endpoint descriptors, from the concatenated descriptors written by the builder.
+fn configuration_descriptor(mut data: &[u8], index: u8) -> Option<&[u8]> {
+    for _ in 0..index {
+        let total_length = u16::from_le_bytes([*data.get(2)?, *data.get(3)?]) as usize;
+        data = data.get(total_length..)?;
+    }
+    let total_length = u16::from_le_bytes([*data.get(2)?, *data.get(3)?]) as usize;
+    data.get(..total_length)
+}
+
 fn first_last<T: Iterator>(iter: T) -> impl Iterator<Item = (bool, bool, T::Item)> {
     let mut iter = iter.peekable();
     let mut first = true;
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, unwrap};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::config::HfclkSource;
+use embassy_nrf::radio::ble::{self, BleRadio};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RADIO => ble::InterruptHandler;
+});
+
+/// Advertising interval, in microseconds.
+const INTERVAL_US: u32 = 100_000;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.hfclk_source = HfclkSource::ExternalXtal;
+    let p = embassy_nrf::init(config);
+
+    let mut radio = BleRadio::new(p.RADIO, Irqs, p.TIMER0, p.PPI_CH0, p.PPI_CH1, p.PPI_CH2, p.PPI_CH3);
+
+    // ADV_NONCONN_IND with a random address, and the "embassy" complete local name.
+    let pdu = [
+        0x42, 15, 0x01, 0x02, 0x03, 0x04, 0x05, 0xC6, 8, 0x09, b'e', b'm', b'b', b'a', b's', b's', b'y',
+    ];
+
+    info!("Advertising");
+    let mut at = radio.now().wrapping_add(1000);
+    loop {
+        // Advertising events on the three primary advertising channels, at exact intervals.
+        for (i, channel) in [37, 38, 39].into_iter().enumerate() {
+            radio.set_channel(channel);
+            unwrap!(radio.transmit_at(at.wrapping_add(i as u32 * 1000), &pdu).await);
+        }
+        at = at.wrapping_add(INTERVAL_US);
+    }
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::clock::{self, Clocks};
+use embassy_nrf::config::LfclkSource;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    POWER_CLOCK => clock::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.lfclk_source = LfclkSource::InternalRC;
+    let _p = embassy_nrf::init(config);
+
+    let clocks = Clocks::new(Irqs);
+
+    loop {
+        // Keep the internal RC oscillator accurate.
+        clocks.calibrate().await;
+        info!("LFRC calibrated, HFXO running: {}", clocks.is_hfxo_running());
+
+        {
+            let _hfxo = clocks.request_hfxo().await;
+            info!("HFXO running: {}", clocks.is_hfxo_running());
+        }
+
+        Timer::after(Duration::from_secs(4)).await;
+    }
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::egu::{self, Egu};
+use embassy_nrf::gpio::{Input, Pull};
+use embassy_nrf::gpiote::{InputChannel, InputChannelPolarity};
+use embassy_nrf::ppi::Ppi;
+use embassy_nrf::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    SWI0_EGU0 => egu::InterruptHandler<peripherals::EGU0>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    let mut egu = Egu::new(p.EGU0, Irqs);
+
+    let button = InputChannel::new(
+        p.GPIOTE_CH0,
+        Input::new(p.P0_11, Pull::Up),
+        InputChannelPolarity::HiToLo,
+    );
+
+    // Button presses trigger EGU channel 0 in hardware.
+    let mut ppi = Ppi::new_one_to_one(p.PPI_CH0, button.event_in(), egu.task_trigger(0));
+    ppi.enable();
+
+    // Software can trigger the same channel.
+    egu.trigger(0);
+
+    loop {
+        egu.wait(0).await;
+        info!("EGU channel 0 triggered");
+    }
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::config::HfclkSource;
+use embassy_nrf::radio::esb::{self, Esb, Packet};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RADIO => esb::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.hfclk_source = HfclkSource::ExternalXtal;
+    let p = embassy_nrf::init(config);
+
+    let mut esb = Esb::new(p.RADIO, Irqs, p.TIMER0, p.PPI_CH0, p.PPI_CH1, esb::Config::default());
+
+    let mut packet = Packet::new();
+    let mut ack = Packet::new();
+    let mut received = 0u32;
+    loop {
+        // The acknowledgement payload carries the number of packets received so far.
+        ack.copy_from_slice(&received.to_le_bytes());
+        match esb.receive(&mut packet, &ack).await {
+            Ok(()) => {
+                received += 1;
+                info!(
+                    "Received {:02x} on pipe {}, RSSI {}",
+                    &packet[..],
+                    packet.pipe(),
+                    packet.rssi()
+                );
+            }
+            Err(e) => warn!("Receive failed: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::config::HfclkSource;
+use embassy_nrf::radio::esb::{self, Esb, Packet};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RADIO => esb::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.hfclk_source = HfclkSource::ExternalXtal;
+    let p = embassy_nrf::init(config);
+
+    let mut esb = Esb::new(p.RADIO, Irqs, p.TIMER0, p.PPI_CH0, p.PPI_CH1, esb::Config::default());
+
+    let mut packet = Packet::new();
+    let mut ack = Packet::new();
+    let mut counter = 0u8;
+    loop {
+        packet.copy_from_slice(&[counter]);
+        match esb.send(&packet, &mut ack).await {
+            Ok(()) => info!("Acknowledged with {:02x}", &ack[..]),
+            Err(e) => warn!("Send failed: {:?}", e),
+        }
+        counter = counter.wrapping_add(1);
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::config::HfclkSource;
+use embassy_nrf::radio::ieee802154::{self, Packet, Radio};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RADIO => ieee802154::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.hfclk_source = HfclkSource::ExternalXtal;
+    let p = embassy_nrf::init(config);
+
+    let mut config = ieee802154::Config::default();
+    config.channel = 15;
+    config.pan_id = 0xABCD;
+    config.short_address = 0x0001;
+    let mut radio = Radio::new(p.RADIO, Irqs, config);
+
+    // Data frame from 0x0001 to broadcast, with PAN ID compression.
+    let mut packet = Packet::new();
+    packet.copy_from_slice(&[0x41, 0x88, 0x00, 0xCD, 0xAB, 0xFF, 0xFF, 0x01, 0x00, b'h', b'i']);
+    match radio.send(&packet).await {
+        Ok(()) => info!("Sent"),
+        Err(e) => warn!("Send failed: {:?}", e),
+    }
+
+    loop {
+        match radio.receive(&mut packet).await {
+            Ok(()) => info!("Received {:02x}, LQI {}", &packet[..], packet.lqi()),
+            Err(e) => warn!("Receive failed: {:?}", e),
+        }
+    }
+}
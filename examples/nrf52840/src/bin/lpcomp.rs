@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::lpcomp::{self, Lpcomp};
+use embassy_nrf::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    COMP_LPCOMP => lpcomp::InterruptHandler<peripherals::COMP_LPCOMP>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    // Monitor a voltage on P0_02 (AIN0), e.g. a battery through a divider, against half of VDD.
+    let mut config = lpcomp::Config::default();
+    config.reference = lpcomp::Reference::Vdd4_8;
+    let mut comp = Lpcomp::new(p.COMP_LPCOMP, Irqs, p.P0_02, config);
+
+    loop {
+        if comp.sample() {
+            info!("Above threshold");
+            comp.wait_for_cross_down().await;
+        } else {
+            info!("Below threshold");
+            comp.wait_for_cross_up().await;
+        }
+    }
+}
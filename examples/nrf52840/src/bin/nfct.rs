@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf::nfct::{self, Config, NfcId, NfcT};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    NFCT => nfct::InterruptHandler;
+});
+
+const READ: u8 = 0x30;
+const HALT: u8 = 0x50;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    const UID: [u8; 7] = [0x5F, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+    let mut config = Config::default();
+    config.nfcid1 = NfcId::DoubleSize(UID);
+    let mut nfc = NfcT::new(p.NFCT, Irqs, config);
+
+    // Type 2 tag memory, holding a NDEF message with the URI "https://embassy.dev".
+    let mut memory = [0u8; 64];
+    memory[..3].copy_from_slice(&UID[..3]);
+    memory[4..8].copy_from_slice(&UID[3..]);
+    // Capability container: NDEF, version 1.0, 48 bytes of data, read/write.
+    memory[12..16].copy_from_slice(&[0xE1, 0x10, 0x06, 0x00]);
+    // NDEF message TLV with a well known URI record.
+    memory[16..18].copy_from_slice(&[0x03, 0x10]);
+    memory[18..22].copy_from_slice(&[0xD1, 0x01, 0x0C, b'U']);
+    memory[22] = 0x04; // https://
+    memory[23..34].copy_from_slice(b"embassy.dev");
+    memory[34] = 0xFE;
+
+    let mut buf = [0; 16];
+    let mut response = [0; 16];
+    loop {
+        nfc.wait_for_selected().await;
+        info!("Selected");
+
+        loop {
+            let n = match nfc.receive(&mut buf).await {
+                Ok(n) => n,
+                Err(nfct::Error::FieldLost) => break,
+                Err(e) => {
+                    warn!("Receive error: {:?}", e);
+                    continue;
+                }
+            };
+
+            match &buf[..n] {
+                // Read 4 blocks of 4 bytes, wrapping around the memory.
+                [READ, block] => {
+                    for (i, b) in response.iter_mut().enumerate() {
+                        *b = memory[(*block as usize * 4 + i) % memory.len()];
+                    }
+                    if let Err(e) = nfc.transmit(&response).await {
+                        warn!("Transmit error: {:?}", e);
+                    }
+                }
+                [HALT, 0x00] => {
+                    nfc.sleep();
+                    break;
+                }
+                // Other commands are not answered, the reader times out.
+                cmd => warn!("Unsupported command: {:02x}", cmd),
+            }
+        }
+        info!("Deselected");
+    }
+}
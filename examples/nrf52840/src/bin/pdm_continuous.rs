@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::pdm::{self, Config, Pdm};
+use embassy_nrf::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PDM => pdm::InterruptHandler<peripherals::PDM>;
+});
+
+#[embassy_executor::main]
+async fn main(_p: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let config = Config::default();
+    let mut pdm = Pdm::new(p.PDM, Irqs, p.P0_01, p.P0_00, config);
+
+    const SAMPLES: usize = 1024;
+    let mut bufs = [[0i16; SAMPLES]; 2];
+    let mut sampler = pdm.continuous(&mut bufs).unwrap();
+
+    loop {
+        let buf = sampler.read().await;
+
+        // Print the peak level of each buffer. Processing must take less time than filling
+        // a buffer, or samples get lost.
+        let peak = buf.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        info!("peak: {}", peak);
+    }
+}
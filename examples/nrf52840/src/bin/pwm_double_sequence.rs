@@ -7,9 +7,14 @@ use embassy_executor::Spawner;
 use embassy_nrf::pwm::{
     Config, Prescaler, Sequence, SequenceConfig, SequenceMode, SequencePwm, Sequencer, StartSequence,
 };
+use embassy_nrf::{bind_interrupts, peripherals, pwm};
 use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    PWM0 => pwm::InterruptHandler<peripherals::PWM0>;
+});
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
@@ -26,7 +31,7 @@ async fn main(_spawner: Spawner) {
     seq_config.refresh = 624;
     // thus our sequence takes 5 * 5000ms or 25 seconds
 
-    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, p.P0_13, config));
+    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, Irqs, p.P0_13, config));
 
     let sequence_0 = Sequence::new(&seq_words_0, seq_config.clone());
     let sequence_1 = Sequence::new(&seq_words_1, seq_config);
@@ -5,9 +5,14 @@
 use defmt::*;
 use embassy_executor::Spawner;
 use embassy_nrf::pwm::{Config, Prescaler, SequenceConfig, SequencePwm, SingleSequenceMode, SingleSequencer};
+use embassy_nrf::{bind_interrupts, peripherals, pwm};
 use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    PWM0 => pwm::InterruptHandler<peripherals::PWM0>;
+});
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
@@ -23,7 +28,7 @@ async fn main(_spawner: Spawner) {
     seq_config.refresh = 624;
     // thus our sequence takes 5 * 5000ms or 25 seconds
 
-    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, p.P0_13, config,));
+    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, Irqs, p.P0_13, config));
 
     let sequencer = SingleSequencer::new(&mut pwm, &seq_words, seq_config);
     unwrap!(sequencer.start(SingleSequenceMode::Times(1)));
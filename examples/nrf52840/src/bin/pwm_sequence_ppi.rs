@@ -10,8 +10,13 @@ use embassy_nrf::gpio::{Input, Pull};
 use embassy_nrf::gpiote::{InputChannel, InputChannelPolarity};
 use embassy_nrf::ppi::Ppi;
 use embassy_nrf::pwm::{Config, Prescaler, SequenceConfig, SequencePwm, SingleSequenceMode, SingleSequencer};
+use embassy_nrf::{bind_interrupts, peripherals, pwm};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    PWM0 => pwm::InterruptHandler<peripherals::PWM0>;
+});
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
@@ -26,7 +31,7 @@ async fn main(_spawner: Spawner) {
     let mut seq_config = SequenceConfig::default();
     seq_config.refresh = 30;
 
-    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, p.P0_13, config));
+    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, Irqs, p.P0_13, config));
 
     // pwm.stop() deconfigures pins, and then the task_start_seq0 task cant work
     // so its going to have to start running in order load the configuration
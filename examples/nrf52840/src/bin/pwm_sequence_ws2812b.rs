@@ -7,9 +7,14 @@ use embassy_executor::Spawner;
 use embassy_nrf::pwm::{
     Config, Prescaler, SequenceConfig, SequenceLoad, SequencePwm, SingleSequenceMode, SingleSequencer,
 };
+use embassy_nrf::{bind_interrupts, peripherals, pwm};
 use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    PWM0 => pwm::InterruptHandler<peripherals::PWM0>;
+});
+
 // WS2812B LED light demonstration. Drives just one light.
 // The following reference on WS2812B may be of use:
 // https://cdn-shop.adafruit.com/datasheets/WS2812B.pdf.
@@ -32,7 +37,7 @@ async fn main(_spawner: Spawner) {
     config.sequence_load = SequenceLoad::Common;
     config.prescaler = Prescaler::Div1;
     config.max_duty = 20; // 1.25us (1s / 16Mhz * 20)
-    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, p.P1_05, config));
+    let mut pwm = unwrap!(SequencePwm::new_1ch(p.PWM0, Irqs, p.P1_05, config));
 
     // Declare the bits of 24 bits in a buffer we'll be
     // mutating later.
@@ -21,7 +21,8 @@ async fn main(_spawner: Spawner) {
     info!("Turn rotary encoder!");
     let mut value = 0;
     loop {
-        value += rotary_enc.read().await;
+        // Only wake up once the encoder moved by at least 4 steps.
+        value += rotary_enc.wait_for_steps(4).await;
         info!("Value: {}", value);
     }
 }
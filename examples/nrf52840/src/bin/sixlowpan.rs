@@ -0,0 +1,140 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use core::mem;
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{Ipv6Address, Ipv6Cidr, Stack, StackResources, StaticConfigV6};
+use embassy_net_driver_channel as ch;
+use embassy_nrf::config::HfclkSource;
+use embassy_nrf::radio::ieee802154::{self, Packet, Radio};
+use embassy_nrf::rng::Rng;
+use embassy_nrf::{bind_interrupts, pac, peripherals, rng};
+use heapless::Vec;
+use static_cell::make_static;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RADIO => ieee802154::InterruptHandler;
+    RNG => rng::InterruptHandler<peripherals::RNG>;
+});
+
+/// Largest MAC frame, without the FCS appended by the radio.
+const MTU: usize = ieee802154::MAX_PACKET_SIZE - 2;
+const PAN_ID: u16 = 0xABCD;
+
+type Device = ch::Device<'static, MTU>;
+
+#[embassy_executor::task]
+async fn radio_task(mut radio: Radio<'static>, runner: ch::Runner<'static, MTU>) -> ! {
+    let (state_chan, mut rx_chan, mut tx_chan) = runner.split();
+    state_chan.set_link_state(ch::driver::LinkState::Up);
+
+    let mut packet = Packet::new();
+    loop {
+        match select(radio.receive(&mut packet), tx_chan.tx_buf()).await {
+            Either::First(Ok(())) => match rx_chan.try_rx_buf() {
+                Some(buf) => {
+                    buf[..packet.len()].copy_from_slice(&packet);
+                    rx_chan.rx_done(packet.len());
+                }
+                None => warn!("rx buffer full, dropping frame"),
+            },
+            Either::First(Err(e)) => warn!("receive failed: {:?}", e),
+            Either::Second(frame) => {
+                packet.copy_from_slice(frame);
+                tx_chan.tx_done();
+                if let Err(e) = radio.send(&packet).await {
+                    warn!("send failed: {:?}", e);
+                }
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn net_task(stack: &'static Stack<Device>) -> ! {
+    stack.run().await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let mut config = embassy_nrf::config::Config::default();
+    config.hfclk_source = HfclkSource::ExternalXtal;
+    let p = embassy_nrf::init(config);
+
+    // Use the 64-bit device ID from FICR as extended address, so every board gets its own.
+    let ficr: pac::FICR = unsafe { mem::transmute(()) };
+    let mut address = [0; 8];
+    address[..4].copy_from_slice(&ficr.deviceid[1].read().bits().to_be_bytes());
+    address[4..].copy_from_slice(&ficr.deviceid[0].read().bits().to_be_bytes());
+    // Locally administered, unicast.
+    address[0] = address[0] & !0x01 | 0x02;
+    info!("extended address: {:02x}", address);
+
+    let mut config = ieee802154::Config::default();
+    config.channel = 15;
+    config.pan_id = PAN_ID;
+    config.extended_address = u64::from_be_bytes(address);
+    let radio = Radio::new(p.RADIO, Irqs, config);
+
+    let state = make_static!(ch::State::<MTU, 4, 4>::new());
+    let (runner, device) = ch::new_ieee802154(state, address);
+    unwrap!(spawner.spawn(radio_task(radio, runner)));
+
+    // Link-local address, with the interface identifier derived from the extended address.
+    let mut ip = [0; 16];
+    ip[..2].copy_from_slice(&[0xfe, 0x80]);
+    ip[8..].copy_from_slice(&address);
+    ip[8] ^= 0x02;
+    let mut config = embassy_net::Config::ipv6_static(StaticConfigV6 {
+        address: Ipv6Cidr::new(Ipv6Address::from_bytes(&ip), 64),
+        gateway: None,
+        dns_servers: Vec::new(),
+    });
+    config.ieee802154_pan_id = Some(PAN_ID);
+
+    // Generate random seed
+    let mut rng = Rng::new(p.RNG, Irqs);
+    let mut seed = [0; 8];
+    rng.blocking_fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    let stack = &*make_static!(Stack::new(
+        device,
+        config,
+        make_static!(StackResources::<2>::new()),
+        seed
+    ));
+
+    unwrap!(spawner.spawn(net_task(stack)));
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 1024];
+    let mut buf = [0; 1024];
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+    unwrap!(socket.bind(1234));
+
+    info!("Echoing on UDP [{}]:1234...", Ipv6Address::from_bytes(&ip));
+    loop {
+        let (n, ep) = match socket.recv_from(&mut buf).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("recv error: {:?}", e);
+                continue;
+            }
+        };
+        info!("rx {} bytes from {}", n, ep);
+        if let Err(e) = socket.send_to(&buf[..n], ep).await {
+            warn!("send error: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::gpio::{Level, Output, OutputDrive, Pull};
+use embassy_nrf::power;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    info!("Running, going to System OFF in 5 seconds...");
+
+    let mut led = Output::new(p.P0_13, Level::Low, OutputDrive::Standard);
+    Timer::after(Duration::from_secs(5)).await;
+    led.set_high();
+
+    // Wake up when button 1 is pressed.
+    power::enable_wakeup_pin(p.P0_11, Pull::Up, Level::Low);
+
+    info!("System OFF, press button 1 to wake up.");
+    power::system_off();
+}
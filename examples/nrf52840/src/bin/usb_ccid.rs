@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use core::mem;
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_usb::class::ccid::{CcidClass, Config as CcidConfig, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+// Minimal ATR advertising the T=1 protocol, without historical bytes.
+const ATR: &[u8] = &[0x3b, 0x80, 0x80, 0x01, 0x01];
+
+const SW_OK: [u8; 2] = [0x90, 0x00];
+const SW_INS_NOT_SUPPORTED: [u8; 2] = [0x6d, 0x00];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("CCID example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    let ccid_config = CcidConfig {
+        atr: ATR,
+        max_apdu_len: 261,
+        max_packet_size: 64,
+    };
+    let mut ccid = CcidClass::new(&mut builder, &mut state, ccid_config);
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Answer SELECT with success and reject every other instruction. A real applet would
+    // dispatch on the selected application here.
+    let ccid_fut = async {
+        let mut apdu = [0; 261];
+        loop {
+            ccid.wait_connection().await;
+            info!("Connected");
+            loop {
+                let n = match ccid.read_apdu(&mut apdu).await {
+                    Ok(n) => n,
+                    Err(_) => break,
+                };
+                info!("APDU: {:x}", &apdu[..n]);
+
+                let sw = match apdu.get(1) {
+                    Some(0xa4) => SW_OK,
+                    _ => SW_INS_NOT_SUPPORTED,
+                };
+                if let Err(e) = ccid.write_response(&sw).await {
+                    warn!("Failed to send response: {:?}", e);
+                    break;
+                }
+            }
+            info!("Disconnected");
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join(usb_fut, ccid_fut).await;
+}
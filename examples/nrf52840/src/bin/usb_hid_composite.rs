@@ -0,0 +1,206 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use core::mem;
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_futures::join::{join, join4};
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_time::{Duration, Timer};
+use embassy_usb::class::hid::descriptor::{self, BootKeyboardReport, BootMouseReport};
+use embassy_usb::class::hid::gamepad::{self, GamepadConfig, GamepadReport, Hat};
+use embassy_usb::class::hid::{HidBootProtocol, HidProtocolMode, HidWriter, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("HID composite example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    // Every HID interface needs its own state.
+    let mut keyboard_state = State::new();
+    let mut mouse_state = State::new();
+    let mut consumer_state = State::new();
+    let mut gamepad_state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    // The boot descriptors work both in report and boot protocol mode, so the keyboard and
+    // mouse also work in BIOS setup screens.
+    let config = embassy_usb::class::hid::Config {
+        report_descriptor: descriptor::BOOT_KEYBOARD,
+        request_handler: None,
+        poll_ms: 10,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::Keyboard,
+    };
+    let mut keyboard = HidWriter::<_, 8>::new(&mut builder, &mut keyboard_state, config);
+
+    let config = embassy_usb::class::hid::Config {
+        report_descriptor: descriptor::BOOT_MOUSE,
+        request_handler: None,
+        poll_ms: 10,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::Mouse,
+    };
+    let mut mouse = HidWriter::<_, 3>::new(&mut builder, &mut mouse_state, config);
+
+    let config = embassy_usb::class::hid::Config {
+        report_descriptor: descriptor::CONSUMER_CONTROL,
+        request_handler: None,
+        poll_ms: 10,
+        max_packet_size: 8,
+        boot_protocol: HidBootProtocol::None,
+    };
+    let mut consumer = HidWriter::<_, 2>::new(&mut builder, &mut consumer_state, config);
+
+    let gamepad_config = GamepadConfig::default();
+    let mut gamepad_descriptor = [0; gamepad::MAX_REPORT_DESCRIPTOR_SIZE];
+    let config = embassy_usb::class::hid::Config {
+        report_descriptor: gamepad_config.report_descriptor(&mut gamepad_descriptor),
+        request_handler: None,
+        poll_ms: 10,
+        max_packet_size: 16,
+        boot_protocol: HidBootProtocol::None,
+    };
+    let mut gamepad = HidWriter::<_, 16>::new(&mut builder, &mut gamepad_state, config);
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Type "a" every second.
+    let keyboard_fut = async {
+        loop {
+            Timer::after(Duration::from_secs(1)).await;
+            if keyboard.protocol() == HidProtocolMode::Boot {
+                info!("Keyboard is in boot protocol mode");
+            }
+
+            let mut report = BootKeyboardReport::default();
+            report.keycodes[0] = 0x04; // a
+            if let Err(e) = keyboard.write(&report.to_bytes()).await {
+                warn!("Failed to send report: {:?}", e);
+            }
+            let report = BootKeyboardReport::default();
+            if let Err(e) = keyboard.write(&report.to_bytes()).await {
+                warn!("Failed to send report: {:?}", e);
+            }
+        }
+    };
+
+    // Wiggle the mouse.
+    let mouse_fut = async {
+        let mut y: i8 = 5;
+        loop {
+            Timer::after(Duration::from_millis(500)).await;
+            y = -y;
+            let report = BootMouseReport { buttons: 0, x: 0, y };
+            if let Err(e) = mouse.write(&report.to_bytes()).await {
+                warn!("Failed to send report: {:?}", e);
+            }
+        }
+    };
+
+    // Tap volume up and then volume down.
+    let consumer_fut = async {
+        loop {
+            for usage in [0x00E9u16, 0x00EA] {
+                Timer::after(Duration::from_secs(5)).await;
+                for usage in [usage, 0] {
+                    if let Err(e) = consumer.write(&usage.to_le_bytes()).await {
+                        warn!("Failed to send report: {:?}", e);
+                    }
+                }
+            }
+        }
+    };
+
+    // Turn the left stick and the hat in circles.
+    let gamepad_fut = async {
+        const HATS: [Hat; 8] = [
+            Hat::Up,
+            Hat::UpRight,
+            Hat::Right,
+            Hat::DownRight,
+            Hat::Down,
+            Hat::DownLeft,
+            Hat::Left,
+            Hat::UpLeft,
+        ];
+        const STICK: [(i16, i16); 8] = [
+            (0, -32767),
+            (23170, -23170),
+            (32767, 0),
+            (23170, 23170),
+            (0, 32767),
+            (-23170, 23170),
+            (-32767, 0),
+            (-23170, -23170),
+        ];
+        let mut buf = [0; 16];
+        loop {
+            for (i, hat) in HATS.iter().enumerate() {
+                Timer::after(Duration::from_millis(250)).await;
+                let mut report = GamepadReport {
+                    buttons: 1 << i,
+                    hat: *hat,
+                    ..Default::default()
+                };
+                (report.axes[0], report.axes[1]) = STICK[i];
+                let len = gamepad_config.serialize(&report, &mut buf);
+                if let Err(e) = gamepad.write(&buf[..len]).await {
+                    warn!("Failed to send report: {:?}", e);
+                }
+            }
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join(usb_fut, join4(keyboard_fut, mouse_fut, consumer_fut, gamepad_fut)).await;
+}
@@ -79,6 +79,7 @@ async fn main(_spawner: Spawner) {
         request_handler: Some(&request_handler),
         poll_ms: 60,
         max_packet_size: 64,
+        ..Default::default()
     };
     let hid = HidReaderWriter::<_, 1, 8>::new(&mut builder, &mut state, config);
 
@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+#![feature(async_fn_in_trait)]
+
+use core::mem;
+
+use defmt::info;
+use defmt_rtt as _;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_usb::class::msc::{BlockDevice, Config as MscConfig, MscClass, State};
+use embassy_usb::{Builder, Config};
+use panic_probe as _;
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+const BLOCK_SIZE: usize = 512;
+const BLOCK_COUNT: usize = 128;
+
+/// A volatile RAM disk. Format it from the host after plugging the device in.
+struct RamDisk {
+    data: [u8; BLOCK_SIZE * BLOCK_COUNT],
+}
+
+impl BlockDevice for RamDisk {
+    type Error = ();
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        BLOCK_COUNT as u32
+    }
+
+    async fn read(&mut self, lba: u32, buf: &mut [u8]) -> Result<(), ()> {
+        let start = lba as usize * BLOCK_SIZE;
+        buf[..BLOCK_SIZE].copy_from_slice(&self.data[start..][..BLOCK_SIZE]);
+        Ok(())
+    }
+
+    async fn write(&mut self, lba: u32, data: &[u8]) -> Result<(), ()> {
+        let start = lba as usize * BLOCK_SIZE;
+        self.data[start..][..BLOCK_SIZE].copy_from_slice(&data[..BLOCK_SIZE]);
+        Ok(())
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB mass storage example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    let mut class = MscClass::new(&mut builder, &mut state, MscConfig::default());
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Do stuff with the class!
+    let mut disk = RamDisk {
+        data: [0; BLOCK_SIZE * BLOCK_COUNT],
+    };
+    let mut block_buf = [0; BLOCK_SIZE];
+    let msc_fut = class.run(&mut disk, &mut block_buf);
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join(usb_fut, msc_fut).await;
+}
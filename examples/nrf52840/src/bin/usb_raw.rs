@@ -0,0 +1,156 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+//! Prototype a custom protocol without writing a class: allocate the interface and its endpoints
+//! directly on the builder, and handle its control requests with a small `Handler`.
+
+use core::mem;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_time::{Duration, Timer};
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use embassy_usb::descriptor::{SynchronizationType, UsageType};
+use embassy_usb::driver::{Endpoint, EndpointIn, EndpointType};
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Config, Handler};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+// Vendor requests of the prototype protocol.
+const REQ_SET_RATE: u8 = 0x01;
+const REQ_GET_RATE: u8 = 0x02;
+
+/// Interval between two reports, in 10 ms units.
+static RATE: AtomicU8 = AtomicU8::new(100);
+
+struct ControlHandler {
+    if_num: InterfaceNumber,
+}
+
+impl Handler for ControlHandler {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Vendor, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+        match (req.request, req.value) {
+            (REQ_SET_RATE, rate @ 1..=255) => {
+                info!("rate set to {}", rate);
+                RATE.store(rate as u8, Ordering::Relaxed);
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Vendor, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+        match req.request {
+            REQ_GET_RATE => {
+                buf[0] = RATE.load(Ordering::Relaxed);
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB raw interface example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Add the interface and allocate its endpoint.
+    let mut func = builder.function(0xff, 0x00, 0x00);
+    let mut iface = func.interface();
+    let if_num = iface.interface_number();
+    let mut alt = iface.alt_setting(0xff, 0x00, 0x00, None);
+    let mut ep_in = alt.alloc_endpoint_in(EndpointType::Interrupt, 8, 10);
+    alt.endpoint_descriptor(
+        ep_in.info(),
+        SynchronizationType::NoSynchronization,
+        UsageType::DataEndpoint,
+    );
+    drop(func);
+
+    // Route the control requests of the interface to our handler.
+    let mut handler = ControlHandler { if_num };
+    builder.handler(&mut handler);
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Send a counter at the rate configured by the host.
+    let report_fut = async {
+        let mut counter = 0u32;
+        loop {
+            ep_in.wait_enabled().await;
+            info!("Connected");
+            loop {
+                Timer::after(Duration::from_millis(RATE.load(Ordering::Relaxed) as u64 * 10)).await;
+                counter = counter.wrapping_add(1);
+                if let Err(e) = ep_in.write(&counter.to_le_bytes()).await {
+                    warn!("Failed to send report: {:?}", e);
+                    break;
+                }
+            }
+            info!("Disconnected");
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join(usb_fut, report_fut).await;
+}
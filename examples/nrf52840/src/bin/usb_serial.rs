@@ -32,11 +32,19 @@ async fn main(_spawner: Spawner) {
     // Create the driver, from the HAL.
     let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
 
+    // Use the 64-bit device ID from FICR as serial number, so every board shows up as a
+    // different device on the host.
+    let ficr: pac::FICR = unsafe { mem::transmute(()) };
+    let mut device_id = [0; 8];
+    device_id[..4].copy_from_slice(&ficr.deviceid[1].read().bits().to_be_bytes());
+    device_id[4..].copy_from_slice(&ficr.deviceid[0].read().bits().to_be_bytes());
+    let mut serial_number = [0; 16];
+
     // Create embassy-usb Config
     let mut config = Config::new(0xc0de, 0xcafe);
     config.manufacturer = Some("Embassy");
     config.product = Some("USB-serial example");
-    config.serial_number = Some("12345678");
+    config.set_serial_number_hex(&device_id, &mut serial_number);
     config.max_power = 100;
     config.max_packet_size_0 = 64;
 
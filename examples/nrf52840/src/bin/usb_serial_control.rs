@@ -0,0 +1,123 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use core::mem;
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::join::join3;
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, ControlEvent, SerialState, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB-serial control lines example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Required for windows compatibility.
+    // https://developer.nordicsemi.com/nRF_Connect_SDK/doc/1.9.1/kconfig/CONFIG_CDC_ACM_IAD.html#help
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    let class = CdcAcmClass::new(&mut builder, &mut state, 64);
+    let (mut sender, mut receiver, mut control) = class.split_with_control();
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Echo the data back, like a loopback plug between TX and RX.
+    let echo_fut = async {
+        let mut buf = [0; 64];
+        loop {
+            receiver.wait_connection().await;
+            info!("Connected");
+            loop {
+                let n = match receiver.read_packet(&mut buf).await {
+                    Ok(n) => n,
+                    Err(_) => break,
+                };
+                if sender.write_packet(&buf[..n]).await.is_err() {
+                    break;
+                }
+            }
+            info!("Disconnected");
+        }
+    };
+
+    // Loop the modem lines back too: DTR drives DSR and DCD, RTS drives RI.
+    let control_fut = async {
+        loop {
+            match control.wait_control_event().await {
+                ControlEvent::LineCoding(coding) => info!("Line coding: {:?}", coding),
+                ControlEvent::LineState { dtr, rts } => {
+                    info!("DTR {} RTS {}", dtr, rts);
+                    let state = SerialState {
+                        dcd: dtr,
+                        dsr: dtr,
+                        ring: rts,
+                        ..Default::default()
+                    };
+                    if let Err(e) = control.send_serial_state(state).await {
+                        warn!("Failed to send serial state: {:?}", e);
+                    }
+                }
+                ControlEvent::Break(duration) => info!("Break: {:?}", duration),
+            }
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join3(usb_fut, echo_fut, control_fut).await;
+}
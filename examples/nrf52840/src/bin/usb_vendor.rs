@@ -0,0 +1,130 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use core::mem;
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::join::join3;
+use embassy_nrf::gpio::{Level, Output, OutputDrive};
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_nrf::{bind_interrupts, pac, peripherals, usb};
+use embassy_usb::class::vendor::{Config as VendorConfig, State, VendorClass};
+use embassy_usb::control::RequestType;
+use embassy_usb::msos::windows_version;
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+});
+
+// This is a randomly generated GUID to allow clients on Windows to find our device
+const DEVICE_INTERFACE_GUIDS: &[&str] = &["{8B1A6C0E-43F6-4C1A-9E0B-2B5D1C8F7A34}"];
+
+// Vendor request turning the LED on (wValue = 1) or off (wValue = 0).
+const REQ_SET_LED: u8 = 0x01;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+
+    info!("Enabling ext hfosc...");
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+
+    let mut led = Output::new(p.P0_13, Level::High, OutputDrive::Standard);
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USBD, Irqs, HardwareVbusDetect::new(Irqs));
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB vendor class example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut msos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut msos_descriptor,
+        &mut control_buf,
+    );
+
+    // Bind WinUSB on Windows, so the device can be used with libusb without installing a driver.
+    builder.msos_descriptor(windows_version::WIN8_1, 2);
+
+    // Create classes on the builder.
+    // Without a request handler, control requests are queued for the application.
+    let vendor_config = VendorConfig {
+        winusb_guids: Some(DEVICE_INTERFACE_GUIDS),
+        ..Default::default()
+    };
+    let class = VendorClass::new(&mut builder, &mut state, vendor_config);
+    let requests = class.control_requests();
+    let (mut sender, mut receiver) = class.split();
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Handle the vendor control requests.
+    let control_fut = async {
+        loop {
+            let req = requests.wait_control_out().await;
+            match (req.request.request_type, req.request.request) {
+                (RequestType::Vendor, REQ_SET_LED) => {
+                    info!("LED {}", req.request.value != 0);
+                    // The LED is active low.
+                    led.set_level(Level::from(req.request.value == 0));
+                }
+                _ => warn!("Unknown request: {:?}", req.request),
+            }
+        }
+    };
+
+    // Echo everything sent on the bulk OUT endpoint.
+    let echo_fut = async {
+        let mut buf = [0; 64];
+        loop {
+            receiver.wait_connection().await;
+            info!("Connected");
+            loop {
+                let n = match receiver.read_packet(&mut buf).await {
+                    Ok(n) => n,
+                    Err(_) => break,
+                };
+                if let Err(e) = sender.write(&buf[..n]).await {
+                    warn!("Failed to echo: {:?}", e);
+                    break;
+                }
+            }
+            info!("Disconnected");
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join3(usb_fut, control_fut, echo_fut).await;
+}
@@ -6,8 +6,13 @@ use defmt::*;
 use embassy_executor::Spawner;
 use embassy_nrf::gpio::{Input, Pull};
 use embassy_nrf::wdt::{Config, Watchdog};
+use embassy_nrf::{bind_interrupts, wdt};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    WDT => wdt::InterruptHandler;
+});
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
@@ -16,11 +21,11 @@ async fn main(_spawner: Spawner) {
     let mut config = Config::default();
     config.timeout_ticks = 32768 * 3; // 3 seconds
 
-    // This is needed for `probe-rs run` to be able to catch the panic message
+    // This is needed for `probe-rs run` to be able to catch the message logged
     // in the WDT interrupt. The core resets 2 ticks after firing the interrupt.
     config.run_during_debug_halt = false;
 
-    let (_wdt, [mut handle]) = match Watchdog::try_new(p.WDT, config) {
+    let (mut wdt, [mut handle]) = match Watchdog::try_new(p.WDT, config) {
         Ok(x) => x,
         Err(_) => {
             info!("Watchdog already active with wrong config, waiting for it to timeout...");
@@ -28,6 +33,8 @@ async fn main(_spawner: Spawner) {
         }
     };
 
+    wdt.set_timeout_callback(Irqs, |unfed| warn!("Watchdog timeout, unfed handles: {:08b}", unfed));
+
     let mut button = Input::new(p.P0_11, Pull::Up);
 
     info!("Watchdog started, press button 1 to pet it or I'll reset in 3 seconds!");
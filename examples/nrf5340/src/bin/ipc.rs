@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_nrf::ipc::{self, Ipc};
+use embassy_nrf::{bind_interrupts, gpio, pac};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    IPC => ipc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    // Let the network core drive an LED.
+    gpio::assign_to_network_core(p.P0_29);
+
+    // Send on channel 0, receive on channel 1: the network core does the opposite.
+    let mut ipc = Ipc::new(p.IPC, Irqs);
+    ipc.configure_send(0, 1 << 0);
+    ipc.configure_receive(1, 1 << 1);
+
+    // Release the network core from reset.
+    let reset = unsafe { &*pac::RESET::ptr() };
+    reset.network.forceoff.write(|w| w.forceoff().release());
+
+    let mut counter = 0;
+    loop {
+        ipc.write_gpmem(0, counter);
+        ipc.send(0);
+        ipc.wait(1).await;
+        info!("Network core replied {}", ipc.read_gpmem(1));
+
+        counter += 1;
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::adc::{convert_to_celsius, Adc, Channels, Config, InterruptHandler};
+use embassy_rp::bind_interrupts;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_IRQ_FIFO => InterruptHandler;
+});
+
+/// Ring buffer for the DMA, aligned to its size.
+#[repr(C, align(512))]
+struct RingBuffer([u16; 256]);
+
+static mut RING: RingBuffer = RingBuffer([0; 256]);
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    let mut adc = Adc::new(p.ADC, Irqs, Config::default());
+
+    let mut p26 = p.PIN_26;
+    let mut p27 = p.PIN_27;
+    let channels = Channels::new().pin(&mut p26).pin(&mut p27).temperature();
+
+    // 3 channels, at 1000 samples per second each.
+    let ring = unsafe { &mut RING.0 };
+    let mut sampling = adc.start_free_running(channels, 3000, p.DMA_CH0, ring);
+
+    let mut samples = [0u16; 3 * 100];
+    loop {
+        match sampling.read(&mut samples).await {
+            Ok(n) => {
+                // The samples of pin 26, pin 27 and the temperature sensor alternate.
+                let frames = &samples[..n - n % 3];
+                let count = (frames.len() / 3).max(1) as u32;
+                let sum = frames.chunks(3).fold([0u32; 3], |mut sum, frame| {
+                    for (s, &v) in sum.iter_mut().zip(frame) {
+                        *s += v as u32;
+                    }
+                    sum
+                });
+                info!(
+                    "Pin 26: {}, pin 27: {}, temp: {} degrees",
+                    sum[0] / count,
+                    sum[1] / count,
+                    convert_to_celsius((sum[2] / count) as u16)
+                );
+            }
+            Err(e) => warn!("ADC error: {:?}", e),
+        }
+    }
+}
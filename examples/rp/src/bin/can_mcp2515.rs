@@ -0,0 +1,76 @@
+//! This example sends a frame every second, and prints the received frames, with an MCP2515 CAN
+//! module on SPI1.
+//!
+//! Common modules have an 8 MHz crystal, and a TJA1050 transceiver which needs 5V.
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_can::{Filter, Filters, Frame, Receive, StandardId, Transmit};
+use embassy_can_mcp25xx::mcp2515::{self, Runner};
+use embassy_can_mcp25xx::State;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+use embassy_rp::peripherals::{PIN_13, PIN_15, SPI1};
+use embassy_rp::spi::{Async, Config as SpiConfig, Spi};
+use embassy_time::{Delay, Duration, Timer};
+use embedded_hal_async::spi::ExclusiveDevice;
+use static_cell::make_static;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::task]
+async fn can_task(
+    runner: Runner<
+        'static,
+        ExclusiveDevice<Spi<'static, SPI1, Async>, Output<'static, PIN_13>, Delay>,
+        Input<'static, PIN_15>,
+    >,
+) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let mut spi_cfg = SpiConfig::default();
+    spi_cfg.frequency = 10_000_000;
+    let (miso, mosi, clk) = (p.PIN_12, p.PIN_11, p.PIN_10);
+    let spi = Spi::new(p.SPI1, clk, mosi, miso, p.DMA_CH0, p.DMA_CH1, spi_cfg);
+    let cs = Output::new(p.PIN_13, Level::High);
+    let int = Input::new(p.PIN_15, Pull::Up);
+
+    let mut config = mcp2515::Config::default();
+    config.bitrate = 250_000;
+    let state = make_static!(State::<8>::new());
+    let Ok((can, runner)) = mcp2515::new(config, state, ExclusiveDevice::new(spi, cs, Delay), int).await else {
+        panic!("MCP2515 init failed");
+    };
+    unwrap!(spawner.spawn(can_task(runner)));
+
+    let (mut tx, mut rx) = can.split();
+    // Receive the frames of the nodes 0x100 to 0x10F.
+    unwrap!(rx.set_filters(&[Filter::standard(unwrap!(StandardId::new(0x100)), 0x7F0)]));
+
+    let heartbeat = async {
+        let mut counter = 0u8;
+        loop {
+            let frame = unwrap!(Frame::new_data(unwrap!(StandardId::new(0x123)), &[counter]));
+            unwrap!(tx.transmit(&frame).await);
+            counter = counter.wrapping_add(1);
+            Timer::after(Duration::from_secs(1)).await;
+        }
+    };
+    let print = async {
+        loop {
+            match rx.receive().await {
+                Ok(frame) => info!("rx {}: {:x}", Debug2Format(&frame.id()), frame.data()),
+                Err(e) => warn!("receive error: {}", e),
+            }
+        }
+    };
+    join(heartbeat, print).await;
+}
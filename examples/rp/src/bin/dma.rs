@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::dma::{self, ControlBlock, SniffConfig};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = embassy_rp::init(Default::default());
+
+    // Copy a buffer.
+    let from = [0x5au32; 64];
+    let mut to = [0u32; 64];
+    unsafe { dma::copy(&mut p.DMA_CH0, &from, &mut to) }.await;
+    info!("copied: {}", to[..4]);
+
+    // Gather three buffers in one.
+    let a = [1u8; 4];
+    let b = [2u8; 8];
+    let c = [3u8; 4];
+    let mut gathered = [0u8; 16];
+    let (ga, rest) = gathered.split_at_mut(4);
+    let (gb, gc) = rest.split_at_mut(8);
+    let mut blocks = unsafe {
+        [
+            ControlBlock::copy(&a, ga),
+            ControlBlock::copy(&b, gb),
+            ControlBlock::copy(&c, gc),
+            ControlBlock::END,
+        ]
+    };
+    unsafe { dma::scatter_gather(&mut p.DMA_CH0, &mut p.DMA_CH1, &mut blocks) }.await;
+    info!("gathered: {}", gathered);
+
+    // Check the CRC-32 of "123456789", 0xcbf43926.
+    let crc = dma::checksum(&mut p.DMA_CH0, b"123456789", &SniffConfig::crc32()).await;
+    info!("crc32: {:08x}", crc);
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::gpio::{InterruptTrigger, Level, Output, Pull};
+use embassy_rp::power;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = embassy_rp::init(Default::default());
+    let mut led = Output::new(p.PIN_25, Level::Low);
+
+    loop {
+        for _ in 0..5 {
+            led.set_high();
+            Timer::after(Duration::from_millis(100)).await;
+            led.set_low();
+            Timer::after(Duration::from_millis(100)).await;
+        }
+
+        // Stop everything until a button between PIN_28 and ground is pressed.
+        info!("going dormant");
+        power::dormant_until(&mut p.PIN_28, Pull::Up, InterruptTrigger::EdgeLow);
+        info!("woken up");
+    }
+}
@@ -7,6 +7,7 @@ use embassy_executor::Spawner;
 use embassy_rp::flash::{ERASE_SIZE, FLASH_BASE};
 use embassy_rp::peripherals::FLASH;
 use embassy_time::{Duration, Timer};
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
 use {defmt_rtt as _, panic_probe as _};
 
 const ADDR_OFFSET: u32 = 0x100000;
@@ -38,6 +39,8 @@ async fn main(_spawner: Spawner) {
 
     multiwrite_bytes(&mut flash, ERASE_SIZE as u32);
 
+    erase_write_sector_async(&mut flash, 2 * ERASE_SIZE as u32).await;
+
     loop {}
 }
 
@@ -97,3 +100,29 @@ fn erase_write_sector(flash: &mut embassy_rp::flash::Flash<'_, FLASH, FLASH_SIZE
         defmt::panic!("unexpected");
     }
 }
+
+async fn erase_write_sector_async(flash: &mut embassy_rp::flash::Flash<'_, FLASH, FLASH_SIZE>, offset: u32) {
+    info!(">>>> [erase_write_sector_async]");
+    let mut buf = [0u8; ERASE_SIZE];
+
+    defmt::unwrap!(NorFlash::erase(flash, ADDR_OFFSET + offset, ADDR_OFFSET + offset + ERASE_SIZE as u32).await);
+
+    defmt::unwrap!(ReadNorFlash::read(flash, ADDR_OFFSET + offset, &mut buf).await);
+    info!("Contents after erase starts with {=[u8]}", buf[0..4]);
+    if buf.iter().any(|x| *x != 0xFF) {
+        defmt::panic!("unexpected");
+    }
+
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    // Written a page at a time, letting other tasks run in between.
+    defmt::unwrap!(NorFlash::write(flash, ADDR_OFFSET + offset + 3, &buf[3..]).await);
+
+    defmt::unwrap!(ReadNorFlash::read(flash, ADDR_OFFSET + offset, &mut buf).await);
+    info!("Contents after write starts with {=[u8]}", buf[0..4]);
+    if buf[3..].iter().enumerate().any(|(i, x)| *x != (i + 3) as u8) {
+        defmt::panic!("unexpected");
+    }
+}
@@ -0,0 +1,82 @@
+//! This example shows how to use the RP2040 as an I2C slave, a register file of 16 bytes written
+//! and read by an I2C master, here the other I2C controller of the same chip.
+//!
+//! Connect pin 2 to pin 4, and pin 3 to pin 5.
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::bind_interrupts;
+use embassy_rp::i2c::{self, InterruptHandler};
+use embassy_rp::i2c_slave::{self, Command, I2cSlave};
+use embassy_rp::peripherals::{I2C0, I2C1};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    I2C0_IRQ => InterruptHandler<I2C0>;
+    I2C1_IRQ => InterruptHandler<I2C1>;
+});
+
+const ADDR: u16 = 0x42;
+
+#[embassy_executor::task]
+async fn device(mut dev: I2cSlave<'static, I2C1>) {
+    let mut registers = [0u8; 16];
+    let mut reg = 0usize;
+
+    loop {
+        // The master writes the register number, then its new value, if any.
+        let mut buf = [0u8; 2];
+        match dev.listen(&mut buf).await {
+            Ok(Command::Write(n)) if n > 0 => {
+                reg = buf[0] as usize % registers.len();
+                if n > 1 {
+                    registers[reg] = buf[1];
+                    info!("register {} = {}", reg, buf[1]);
+                }
+            }
+            Ok(Command::WriteRead(n)) if n > 0 => {
+                reg = buf[0] as usize % registers.len();
+                if let Err(e) = dev.respond_and_fill(&registers[reg..], 0xff).await {
+                    warn!("read failed: {}", e);
+                }
+            }
+            Ok(Command::Read) => {
+                if let Err(e) = dev.respond_and_fill(&registers[reg..], 0xff).await {
+                    warn!("read failed: {}", e);
+                }
+            }
+            Ok(cmd) => info!("ignored {}", cmd),
+            Err(e) => warn!("listen failed: {}", e),
+        }
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let mut config = i2c_slave::Config::default();
+    config.addr = ADDR;
+    let dev = I2cSlave::new(p.I2C1, p.PIN_3, p.PIN_2, Irqs, config);
+    unwrap!(spawner.spawn(device(dev)));
+
+    let mut bus = i2c::I2c::new_async(p.I2C0, p.PIN_5, p.PIN_4, Irqs, i2c::Config::default());
+
+    let mut value = 0u8;
+    loop {
+        unwrap!(bus.write_async(ADDR, [3, value]).await);
+
+        let mut regs = [0u8; 4];
+        unwrap!(bus.write_async(ADDR, [2]).await);
+        unwrap!(bus.read_async(ADDR, &mut regs).await);
+        info!("registers 2 to 5: {}", regs);
+
+        value = value.wrapping_add(1);
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::interp::{self, Interp, Lane};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    // Blend between 500 and 1500, by a fraction of 256ths in accumulator 1.
+    let mut config = interp::Config::default();
+    config.blend = true;
+    let mut interp0 = Interp::new(p.INTERP0, config);
+    interp0.set_base(0, 500);
+    interp0.set_base(1, 1500);
+    for fraction in [0, 64, 128, 192, 255] {
+        interp0.set_accum(Lane::Lane1, fraction);
+        info!("blend {}/256: {}", fraction, interp0.peek(Lane::Lane1));
+    }
+
+    // Clamp values between -100 and 100.
+    let mut config = interp::Config::default();
+    config.clamp = true;
+    config.lane0.signed = true;
+    let mut clamp = Interp::new(p.INTERP1, config);
+    clamp.set_base(0, -100i32 as u32);
+    clamp.set_base(1, 100);
+    for value in [-1000i32, -50, 0, 50, 1000] {
+        clamp.set_accum(Lane::Lane0, value as u32);
+        info!("clamp {}: {}", value, clamp.peek(Lane::Lane0) as i32);
+    }
+
+    // Walk a table of words: lane 0 masks the byte offset in accumulator 0 to the size of the
+    // table and adds it to the table address, and `add_accum` steps the offset.
+    static TABLE: [u32; 8] = [1, 1, 2, 3, 5, 8, 13, 21];
+    let mut config = interp::Config::default();
+    config.lane0.mask_lsb = 2;
+    config.lane0.mask_msb = 4;
+    interp0.set_config(&config);
+    interp0.set_accum(Lane::Lane0, 0);
+    interp0.set_base(0, TABLE.as_ptr() as u32);
+    for _ in 0..2 * TABLE.len() {
+        let addr = interp0.peek(Lane::Lane0) as *const u32;
+        info!("table: {}", unsafe { *addr });
+        interp0.add_accum(Lane::Lane0, 4);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -5,10 +5,8 @@
 use defmt::*;
 use embassy_executor::Executor;
 use embassy_rp::gpio::{Level, Output};
-use embassy_rp::multicore::{spawn_core1, Stack};
+use embassy_rp::multicore::{fifo_receive, fifo_send, spawn_core1, Stack};
 use embassy_rp::peripherals::PIN_25;
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::channel::Channel;
 use embassy_time::{Duration, Timer};
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
@@ -16,12 +14,9 @@ use {defmt_rtt as _, panic_probe as _};
 static mut CORE1_STACK: Stack<4096> = Stack::new();
 static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
 static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
-static CHANNEL: Channel<CriticalSectionRawMutex, LedState, 1> = Channel::new();
-
-enum LedState {
-    On,
-    Off,
-}
+// Sent to core 1 through the inter-core FIFO.
+const LED_OFF: u32 = 0;
+const LED_ON: u32 = 1;
 
 #[cortex_m_rt::entry]
 fn main() -> ! {
@@ -41,9 +36,9 @@ fn main() -> ! {
 async fn core0_task() {
     info!("Hello from core 0");
     loop {
-        CHANNEL.send(LedState::On).await;
+        fifo_send(LED_ON);
         Timer::after(Duration::from_millis(100)).await;
-        CHANNEL.send(LedState::Off).await;
+        fifo_send(LED_OFF);
         Timer::after(Duration::from_millis(400)).await;
     }
 }
@@ -52,9 +47,9 @@ async fn core0_task() {
 async fn core1_task(mut led: Output<'static, PIN_25>) {
     info!("Hello from core 1");
     loop {
-        match CHANNEL.recv().await {
-            LedState::On => led.set_high(),
-            LedState::Off => led.set_low(),
+        match fifo_receive().await {
+            LED_ON => led.set_high(),
+            _ => led.set_low(),
         }
     }
 }
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::clocks::{self, ClockConfig};
+use embassy_rp::config::Config;
+use embassy_time::{Duration, Instant, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) -> ! {
+    // Run the system at 200 MHz from the 12 MHz crystal of the Pico.
+    let config = Config::new(ClockConfig::crystal_freq(12_000_000, 200_000_000));
+    let _p = embassy_rp::init(config);
+
+    info!("system clock: {} Hz", clocks::clk_sys_freq());
+    info!("peripheral clock: {} Hz", clocks::clk_peri_freq());
+    info!("usb clock: {} Hz", clocks::clk_usb_freq());
+
+    loop {
+        // Count to a million, to compare with the nominal clock.
+        let start = Instant::now();
+        let mut x = 0u32;
+        for i in 0..1_000_000 {
+            x = core::hint::black_box(x.wrapping_add(i));
+        }
+        info!("counted to a million in {} us", start.elapsed().as_micros());
+
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
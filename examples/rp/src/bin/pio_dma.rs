@@ -7,8 +7,6 @@ use embassy_futures::join::join;
 use embassy_rp::pio::{Config, Pio, ShiftConfig, ShiftDirection};
 use embassy_rp::relocate::RelocatedProgram;
 use embassy_rp::Peripheral;
-use fixed::traits::ToFixed;
-use fixed_macro::types::U56F8;
 use {defmt_rtt as _, panic_probe as _};
 
 fn swap_nibbles(v: u32) -> u32 {
@@ -42,7 +40,7 @@ async fn main(_spawner: Spawner) {
     let relocated = RelocatedProgram::new(&prg.program);
     let mut cfg = Config::default();
     cfg.use_program(&common.load_program(&relocated), &[]);
-    cfg.clock_divider = (U56F8!(125_000_000) / U56F8!(10_000)).to_fixed();
+    cfg.set_frequency(10_000);
     cfg.shift_in = ShiftConfig {
         auto_fill: true,
         threshold: 32,
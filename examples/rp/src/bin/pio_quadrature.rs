@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::pio::Pio;
+use embassy_rp::pio_quadrature::QuadratureEncoder;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let Pio { mut common, sm0, .. } = Pio::new(p.PIO0);
+
+    // The A and B phases of the encoder, on consecutive pins.
+    let mut encoder = QuadratureEncoder::new(&mut common, sm0, p.PIN_4, p.PIN_5);
+
+    loop {
+        let velocity = encoder.read_velocity().await;
+        info!("Position: {}, velocity: {} steps/s", encoder.read().await, velocity);
+        Timer::after(Duration::from_millis(100)).await;
+    }
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::pio::Pio;
+use embassy_rp::pio_vga::{Timing, Vga};
+use static_cell::make_static;
+use {defmt_rtt as _, panic_probe as _};
+
+const TIMING: Timing = Timing::VGA_640X480_60;
+const W: usize = TIMING.h_total();
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let Pio { mut common, sm0, .. } = Pio::new(p.PIO0);
+
+    // Red, green and blue on PIN_0 to PIN_2, through 270 ohm resistors, HSYNC on PIN_3 and VSYNC
+    // on PIN_4.
+    let mut pins = [
+        common.make_pio_pin(p.PIN_0),
+        common.make_pio_pin(p.PIN_1),
+        common.make_pio_pin(p.PIN_2),
+        common.make_pio_pin(p.PIN_3),
+        common.make_pio_pin(p.PIN_4),
+    ];
+    let buffers = make_static!([[0u16; W]; 2]);
+    let mut vga = Vga::new(&mut common, sm0, p.DMA_CH0, p.DMA_CH1, &mut pins, buffers, TIMING);
+
+    // Scroll 8 vertical color bars, one pixel per frame. Each line is copied from a template, as
+    // the time to draw a line is short.
+    let mut bars = [0u16; TIMING.h_active as usize];
+    let mut offset = 0;
+    draw_bars(&mut bars, offset);
+    loop {
+        match vga.next_line().await {
+            Ok((line, pixels)) => {
+                pixels.copy_from_slice(&bars);
+                if line == TIMING.v_active as usize - 1 {
+                    offset = (offset + 1) % TIMING.h_active as usize;
+                    draw_bars(&mut bars, offset);
+                }
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+fn draw_bars(bars: &mut [u16], offset: usize) {
+    let mut color = (offset / 80 % 8) as u16;
+    let mut left = 80 - offset % 80;
+    for pixel in bars.iter_mut() {
+        *pixel = color;
+        left -= 1;
+        if left == 0 {
+            left = 80;
+            color = (color + 1) % 8;
+        }
+    }
+}
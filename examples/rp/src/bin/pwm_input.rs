@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::pwm::{Config, InputMode, Pwm};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    // Generate a 1 kHz signal at 25% duty cycle on pin 4: connect it to pins 3 and 7.
+    let mut c = Config::default();
+    c.divider = 125.into();
+    c.top = 999;
+    c.compare_a = 250;
+    let _out = Pwm::new_output_a(p.PWM_CH2, p.PIN_4, c);
+
+    let mut frequency = Pwm::new_input(p.PWM_CH1, p.PIN_3, InputMode::RisingEdge, Config::default());
+
+    // Count the cycles the input is high in units of 16 system clock cycles, for gates up to
+    // about 8 ms.
+    let mut c = Config::default();
+    c.divider = 16.into();
+    let mut duty = Pwm::new_input(p.PWM_CH3, p.PIN_7, InputMode::Level, c);
+
+    loop {
+        let gate = Duration::from_millis(5);
+        match frequency.measure_frequency(gate).await {
+            Some(hz) => info!("frequency: {} Hz", hz),
+            None => warn!("frequency too high for the gate"),
+        }
+        match duty.measure_duty_cycle(gate).await {
+            Some(d) => info!("duty cycle: {}%", d * 100.0),
+            None => warn!("gate too long for the divider"),
+        }
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
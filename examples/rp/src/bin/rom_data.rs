@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::gpio::{Input, Pull};
+use embassy_rp::rom_data;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    info!(
+        "bootrom v{}, revision {:08x}: {}",
+        rom_data::rom_version_number(),
+        rom_data::git_revision(),
+        rom_data::copyright_string()
+    );
+
+    let div = rom_data::divide_unsigned(1000, 7);
+    info!("1000 / 7 = {}, remainder {}", div.quotient, div.remainder);
+    info!("popcount(0xf0f0) = {}", rom_data::popcount32(0xf0f0));
+    info!("sqrt(2) = {}", rom_data::float_funcs::fsqrt(2.0));
+
+    // Reboot to BOOTSEL mode, with the LED as activity light, when the button between PIN_28 and
+    // ground is pressed.
+    let mut button = Input::new(p.PIN_28, Pull::Up);
+    button.wait_for_falling_edge().await;
+    info!("rebooting to BOOTSEL");
+    rom_data::reset_to_usb_boot(1 << 25, 0);
+}
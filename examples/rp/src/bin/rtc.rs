@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::bind_interrupts;
+use embassy_rp::rtc::{DateTime, DateTimeFilter, DayOfWeek, InterruptHandler, RealTimeClock};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RTC_IRQ => InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    info!("Hello World!");
+
+    let now = DateTime {
+        year: 2023,
+        month: 6,
+        day: 12,
+        day_of_week: DayOfWeek::Monday,
+        hour: 10,
+        minute: 30,
+        second: 50,
+    };
+    let mut rtc = unwrap!(RealTimeClock::new(p.RTC, Irqs, now).ok());
+
+    loop {
+        // Wake up at the start of every minute.
+        let now = unwrap!(rtc.now().ok());
+        let next_minute = (now.minute + 1) % 60;
+        rtc.wait_for_alarm(DateTimeFilter::default().minute(next_minute).second(0))
+            .await;
+
+        let now = unwrap!(rtc.now().ok());
+        info!("Alarm at {}:{}:{}", now.hour, now.minute, now.second);
+    }
+}
@@ -0,0 +1,65 @@
+//! This example shows how to use the RP2040 as an SPI slave, here for the other SPI controller of
+//! the same chip.
+//!
+//! Connect pin 10 to pin 2 (clock), 11 to 3 (MOSI), 12 to 4 (MISO) and 13 to 5 (chip select).
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::peripherals::SPI0;
+use embassy_rp::spi::{self, Phase, Polarity, Spi};
+use embassy_rp::spi_slave::{self, SpiSlave};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::task]
+async fn slave(mut slave: SpiSlave<'static, SPI0>) {
+    let mut counter = 0u8;
+    loop {
+        let mut read = [0u8; 8];
+        let write = [counter; 8];
+        match slave.transfer(&mut read, &write).await {
+            Ok(n) => info!("slave received {}", read[..n]),
+            Err(e) => warn!("slave error: {}", e),
+        }
+        counter = counter.wrapping_add(1);
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let dev = SpiSlave::new(
+        p.SPI0,
+        p.PIN_2,
+        p.PIN_3,
+        p.PIN_4,
+        p.PIN_5,
+        p.DMA_CH0,
+        p.DMA_CH1,
+        spi_slave::Config::default(),
+    );
+    unwrap!(spawner.spawn(slave(dev)));
+
+    // The master uses the same mode as the slave.
+    let mut config = spi::Config::default();
+    config.phase = Phase::CaptureOnSecondTransition;
+    config.polarity = Polarity::IdleHigh;
+    let mut master = Spi::new(p.SPI1, p.PIN_10, p.PIN_11, p.PIN_12, p.DMA_CH2, p.DMA_CH3, config);
+    let mut cs = Output::new(p.PIN_13, Level::High);
+
+    loop {
+        let mut read = [0u8; 8];
+        cs.set_low();
+        unwrap!(master.transfer(&mut read, &[1, 2, 3, 4, 5, 6, 7, 8]).await);
+        cs.set_high();
+        info!("master received {}", read);
+
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
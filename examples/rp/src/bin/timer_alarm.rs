@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::bind_interrupts;
+use embassy_rp::timer::{Alarm, InterruptHandler};
+use embassy_time::{Duration, Instant};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    TIMER_IRQ_3 => InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    let mut alarm = Alarm::new(p.TIMER_ALARM3, Irqs);
+
+    // Tick every 250 ms, without drifting.
+    let mut next = Instant::now();
+    loop {
+        next += Duration::from_millis(250);
+        alarm.wait_until(next).await;
+        info!("tick, {} us late", Instant::now().as_micros() - next.as_micros());
+    }
+}
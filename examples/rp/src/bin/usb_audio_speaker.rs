@@ -0,0 +1,121 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::join::join3;
+use embassy_rp::bind_interrupts;
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::{Driver, InterruptHandler};
+use embassy_usb::class::uac2::{nominal_feedback, Config as AudioConfig, Speaker, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USB, Irqs);
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB audio speaker example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // USB audio class 2.0 requires interface association descriptors.
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut device_descriptor = [0; 256];
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut device_descriptor,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    // 48 kHz or 44.1 kHz, stereo, 16 bit.
+    let audio_config = AudioConfig {
+        sample_rates: &[48_000, 44_100],
+        ..Default::default()
+    };
+    let speaker = Speaker::new(&mut builder, &mut state, audio_config);
+    let controls = speaker.controls();
+    let (mut stream, mut feedback) = speaker.split();
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Receive samples and print the peak level of every 1000 packets.
+    // A real device would push them to an I2S DAC here.
+    let stream_fut = async {
+        let mut buf = [0; 256];
+        loop {
+            stream.wait_connection().await;
+            info!("Streaming at {} Hz", controls.sample_rate());
+
+            let mut peak = 0u16;
+            let mut packets = 0;
+            loop {
+                let n = match stream.read_packet(&mut buf).await {
+                    Ok(n) => n,
+                    Err(_) => break,
+                };
+                for sample in buf[..n].chunks_exact(2) {
+                    peak = peak.max(i16::from_le_bytes([sample[0], sample[1]]).unsigned_abs());
+                }
+                packets += 1;
+                if packets == 1000 {
+                    info!("peak {} mute {} volume {}", peak, controls.mute(), controls.volume());
+                    peak = 0;
+                    packets = 0;
+                }
+            }
+            info!("Stopped streaming");
+        }
+    };
+
+    // Without a DAC the nominal rate is consumed exactly. A real device would measure how fast
+    // its DAC consumes samples and report that instead.
+    let feedback_fut = async {
+        loop {
+            feedback.wait_connection().await;
+            loop {
+                let value = nominal_feedback(controls.sample_rate());
+                if let Err(e) = feedback.write(value).await {
+                    warn!("Feedback failed: {:?}", e);
+                    break;
+                }
+            }
+        }
+    };
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join3(usb_fut, stream_fut, feedback_fut).await;
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::bind_interrupts;
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::host::{Driver, InterruptHandler};
+use embassy_usb_host::class::hid::{HidHost, Protocol};
+use embassy_usb_host::driver::PipeError;
+use embassy_usb_host::{HostError, UsbHost};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello World!");
+
+    let p = embassy_rp::init(Default::default());
+
+    // The controller doesn't switch VBUS: the device must be supplied with 5 V by the board.
+    let driver = Driver::new(p.USB, Irqs);
+    let mut host = UsbHost::new(driver);
+
+    let mut config_buf = [0; 256];
+    loop {
+        info!("Waiting for a keyboard or mouse...");
+        let mut device = match host.wait_for_device(&mut config_buf).await {
+            Ok(device) => device,
+            Err(e) => {
+                warn!("enumeration failed: {:?}", e);
+                host.wait_for_disconnect().await;
+                continue;
+            }
+        };
+        let descriptor = device.device_descriptor();
+        info!("Device {:04x}:{:04x}", descriptor.vendor_id, descriptor.product_id);
+
+        let Some(interface) = HidHost::find_interface(&device) else {
+            warn!("not a HID device");
+            drop(device);
+            host.wait_for_disconnect().await;
+            continue;
+        };
+
+        let mut hid = match HidHost::new(&mut host, &mut device, &interface, Protocol::Boot).await {
+            Ok(hid) => hid,
+            Err(e) => {
+                warn!("HID setup failed: {:?}", e);
+                drop(device);
+                host.wait_for_disconnect().await;
+                continue;
+            }
+        };
+        info!("{:?} attached", hid.boot_interface());
+
+        let mut report = [0; 8];
+        loop {
+            match hid.read_report(&mut report).await {
+                Ok(n) => info!("report: {:02x}", report[..n]),
+                Err(HostError::Pipe(PipeError::Disconnected)) => break,
+                Err(e) => warn!("read failed: {:?}", e),
+            }
+        }
+        info!("Device detached");
+    }
+}
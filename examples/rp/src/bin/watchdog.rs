@@ -18,6 +18,14 @@ async fn main(_spawner: Spawner) {
     let mut watchdog = Watchdog::new(p.WATCHDOG);
     let mut led = Output::new(p.PIN_25, Level::Low);
 
+    // Count the resets caused by the watchdog in a scratch register, kept across them
+    let resets = match watchdog.reset_reason() {
+        Some(ResetReason::TimedOut) => watchdog.get_scratch(0) + 1,
+        _ => 0,
+    };
+    watchdog.set_scratch(0, resets);
+    info!("Reset by the watchdog {} times", resets);
+
     // Set the LED high for 2 seconds so we know when we're about to start the watchdog
     led.set_high();
     Timer::after(Duration::from_secs(2)).await;
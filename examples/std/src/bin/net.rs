@@ -6,6 +6,7 @@ use clap::Parser;
 use embassy_executor::{Executor, Spawner};
 use embassy_net::tcp::TcpSocket;
 use embassy_net::{Config, Ipv4Address, Ipv4Cidr, Stack, StackResources};
+use embassy_net_tuntap::TunTapDevice;
 use embassy_time::Duration;
 use embedded_io::asynch::Write;
 use heapless::Vec;
@@ -13,10 +14,6 @@ use log::*;
 use rand_core::{OsRng, RngCore};
 use static_cell::{make_static, StaticCell};
 
-#[path = "../tuntap.rs"]
-mod tuntap;
-
-use crate::tuntap::TunTapDevice;
 #[derive(Parser)]
 #[clap(version = "1.0")]
 struct Opts {
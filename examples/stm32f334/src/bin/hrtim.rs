@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::hrtim::{
+    self, BurstConfig, Fault, FaultConfig, FaultPin, FaultPolarity, FaultState, Hrtim, PwmConfig, PwmPin,
+};
+use embassy_stm32::rcc::HrtimClockSrc;
+use embassy_stm32::time::{khz, mhz};
+use embassy_stm32::Config;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = Config::default();
+    config.rcc.hse = Some(mhz(8));
+    config.rcc.bypass_hse = true;
+    config.rcc.sysclk = Some(mhz(72));
+    config.rcc.pclk1 = Some(mhz(36));
+    config.rcc.hrtim = HrtimClockSrc::PllClk;
+    let p = embassy_stm32::init(config);
+    info!("Hello World!");
+
+    // A half-bridge on PA8 (high side) and PA9 (low side), with an overcurrent comparator
+    // pulling PA12 low.
+    let mut hrtim = Hrtim::new(p.HRTIM1, [PwmPin::cha1(p.PA8), PwmPin::cha2(p.PA9)]);
+    hrtim.configure_fault(
+        FaultPin::flt1(p.PA12),
+        FaultConfig {
+            polarity: FaultPolarity::ActiveLow,
+            filter: 3,
+        },
+    );
+    hrtim.set_fault_protection(hrtim::Timer::A, &[Fault::Fault1], FaultState::Inactive);
+
+    hrtim.configure_pwm(
+        hrtim::Timer::A,
+        &PwmConfig {
+            frequency: khz(200),
+            dead_time_ns: 100,
+        },
+    );
+    let max = hrtim.get_max_duty(hrtim::Timer::A);
+    hrtim.set_duty(hrtim::Timer::A, max / 4);
+    hrtim.start(hrtim::Timer::A);
+    hrtim.enable_outputs(hrtim::Timer::A);
+
+    // Skip 3 of every 10 periods of 5 us, as at light load.
+    let burst = BurstConfig {
+        prescaler_power: 0,
+        period: 10 * 720,
+        idle: 3 * 720,
+        continuous: true,
+    };
+
+    loop {
+        info!("duty 25%");
+        Timer::after(Duration::from_secs(2)).await;
+
+        info!("duty 25%, burst mode");
+        hrtim.start_burst(&[hrtim::Timer::A], &burst);
+        Timer::after(Duration::from_secs(2)).await;
+        hrtim.stop_burst();
+
+        if hrtim.is_faulted(Fault::Fault1) {
+            warn!("overcurrent, restarting");
+            hrtim.clear_fault(Fault::Fault1);
+            hrtim.enable_outputs(hrtim::Timer::A);
+        }
+    }
+}
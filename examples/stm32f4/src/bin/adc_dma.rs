@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::adc::{Adc, AdcPin, SampleTime, Trigger, TriggerEdge};
+use embassy_stm32::peripherals::ADC1;
+use embassy_stm32::pwm::simple_pwm::{PwmPin, SimplePwm};
+use embassy_stm32::pwm::Channel;
+use embassy_stm32::time::khz;
+use embassy_time::Delay;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // TIM1 CC1 events trigger a conversion of the sequence, 1000 times per second.
+    let ch1 = PwmPin::new_ch1(p.PE9);
+    let mut pwm = SimplePwm::new(p.TIM1, Some(ch1), None, None, None, khz(1));
+    pwm.set_duty(Channel::Ch1, pwm.get_max_duty() / 2);
+    pwm.enable(Channel::Ch1);
+
+    let mut pc1 = p.PC1;
+    let mut pc2 = p.PC2;
+    let mut sequence: [(&mut dyn AdcPin<ADC1>, SampleTime); 2] =
+        [(&mut pc1, SampleTime::Cycles112), (&mut pc2, SampleTime::Cycles112)];
+
+    let trigger = Trigger {
+        // TIM1_CC1
+        source: 0b0000,
+        edge: TriggerEdge::Rising,
+    };
+
+    let mut dma_buf = [0u16; 200];
+    let adc = Adc::new(p.ADC1, &mut Delay);
+    let mut adc = adc.into_ring_buffered(p.DMA2_CH0, &mut dma_buf, &mut sequence, Some(trigger));
+
+    adc.start();
+
+    let mut samples = [0u16; 100];
+    loop {
+        match adc.read(&mut samples).await {
+            // Samples alternate between PC1 and PC2.
+            Ok(n) => info!("{} samples: {}", n, samples[..n]),
+            Err(e) => warn!("ADC error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::can::bxcan::filter::{ListEntry32, Mask32};
+use embassy_stm32::can::bxcan::{Fifo, Frame, StandardId};
+use embassy_stm32::can::{Can, Rx0InterruptHandler, Rx1InterruptHandler, SceInterruptHandler, TxInterruptHandler};
+use embassy_stm32::gpio::{Input, Pull};
+use embassy_stm32::peripherals::CAN1;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    CAN1_RX0 => Rx0InterruptHandler<CAN1>;
+    CAN1_RX1 => Rx1InterruptHandler<CAN1>;
+    CAN1_SCE => SceInterruptHandler<CAN1>;
+    CAN1_TX => TxInterruptHandler<CAN1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello World!");
+
+    let mut p = embassy_stm32::init(Default::default());
+
+    // The next two lines are a workaround for testing without transceiver.
+    // To synchronise to the bus the RX input needs to see a high level.
+    // Use `mem::forget()` to release the borrow on the pin but keep the
+    // pull-up resistor enabled.
+    let rx_pin = Input::new(&mut p.PA11, Pull::Up);
+    core::mem::forget(rx_pin);
+
+    let mut can = Can::new(p.CAN1, p.PA11, p.PA12, Irqs);
+
+    {
+        let mut filters = can.modify_filters();
+        // Exactly 0x100 and 0x101 in FIFO 0.
+        filters.enable_bank(
+            0,
+            Fifo::Fifo0,
+            [
+                ListEntry32::data_frames_with_id(unwrap!(StandardId::new(0x100))),
+                ListEntry32::data_frames_with_id(unwrap!(StandardId::new(0x101))),
+            ],
+        );
+        // 0x200 to 0x2FF in FIFO 1.
+        filters.enable_bank(
+            1,
+            Fifo::Fifo1,
+            Mask32::frames_with_std_id(unwrap!(StandardId::new(0x200)), unwrap!(StandardId::new(0x700))),
+        );
+    }
+
+    can.modify_config()
+        .set_bit_timing(0x001c0003) // http://www.bittiming.can-wiki.info/
+        .set_loopback(true) // Receive own frames
+        .set_silent(true)
+        .enable();
+
+    let mut i: u16 = 0;
+    loop {
+        // Frames with IDs not matching the filters are sent but not received.
+        let id = unwrap!(StandardId::new(0x100 + (i % 0x300)));
+        let tx_frame = Frame::new_data(id, [i as u8]);
+        can.write(&tx_frame).await;
+
+        while let Ok(Some((time, rx_frame))) = can.try_read() {
+            info!("received {} at {}", rx_frame.id(), time);
+        }
+        i = i.wrapping_add(1);
+    }
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::dac::{Ch1Trigger, DacCh1, DacChannel, ValueArray};
+use embassy_stm32::pac::timer::vals::{Mms, Opm};
+use embassy_stm32::peripherals::TIM6;
+use embassy_stm32::rcc::low_level::RccPeripheral;
+use embassy_stm32::time::Hertz;
+use embassy_stm32::timer::low_level::Basic16bitInstance;
+use micromath::F32Ext;
+use {defmt_rtt as _, panic_probe as _};
+
+const SAMPLES: usize = 128;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut dac = DacCh1::new(p.DAC, p.DMA1_CH5, p.PA4);
+
+    // A 12-bit sine wave, output at 1 kHz.
+    let mut data = [0u16; SAMPLES];
+    for (i, v) in data.iter_mut().enumerate() {
+        let angle = 2.0 * core::f32::consts::PI * i as f32 / SAMPLES as f32;
+        *v = (angle.sin() * 2047.0 + 2048.0) as u16;
+    }
+
+    const FREQUENCY: Hertz = Hertz::hz(1000);
+    let reload = TIM6::frequency().0 / FREQUENCY.0 / SAMPLES as u32;
+    info!("TIM6 frequency {}, reload {}", TIM6::frequency(), reload);
+
+    // TIM6 update events trigger the conversions, and the DMA feeds the DAC a new sample
+    // after each of them.
+    TIM6::enable();
+    TIM6::regs().arr().modify(|w| w.set_arr(reload as u16 - 1));
+    TIM6::regs().cr2().modify(|w| w.set_mms(Mms::UPDATE));
+    TIM6::regs().cr1().modify(|w| {
+        w.set_opm(Opm::DISABLED);
+        w.set_cen(true);
+    });
+
+    unwrap!(dac.select_trigger(Ch1Trigger::Tim6));
+    unwrap!(dac.enable_channel());
+
+    // In circular mode, the DMA outputs the table again and again, and this never returns.
+    unwrap!(dac.write(ValueArray::Bit12Right(&data), true).await);
+}
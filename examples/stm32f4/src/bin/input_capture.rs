@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::Pull;
+use embassy_stm32::pwm::input_capture::{CapturePin, InputCapture};
+use embassy_stm32::pwm::{input_capture, Channel};
+use embassy_stm32::time::mhz;
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    TIM3 => input_capture::InterruptHandler<peripherals::TIM3>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Fan tachometer output on PA6, two pulses per revolution.
+    let ch1 = CapturePin::new_ch1(p.PA6, Pull::Up);
+    let mut ic = InputCapture::new(p.TIM3, Some(ch1), None, None, None, Irqs, mhz(1));
+
+    // Ignore glitches shorter than 8 timer clocks.
+    ic.set_input_capture_filter(Channel::Ch1, 0b0011);
+
+    let mut last = ic.wait_for_falling_edge(Channel::Ch1).await;
+    loop {
+        let capture = ic.wait_for_falling_edge(Channel::Ch1).await;
+        let period_us = capture.wrapping_sub(last) as u32;
+        last = capture;
+
+        if period_us > 0 {
+            info!("{} RPM", 60_000_000 / (2 * period_us));
+        }
+    }
+}
@@ -4,9 +4,9 @@
 
 use defmt::*;
 use embassy_executor::Spawner;
-use embassy_stm32::pwm::complementary_pwm::{ComplementaryPwm, ComplementaryPwmPin};
+use embassy_stm32::pwm::complementary_pwm::{BreakPolarity, ComplementaryPwm, ComplementaryPwmPin};
 use embassy_stm32::pwm::simple_pwm::PwmPin;
-use embassy_stm32::pwm::Channel;
+use embassy_stm32::pwm::{Channel, CountingMode};
 use embassy_stm32::time::khz;
 use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
@@ -31,15 +31,27 @@ async fn main(_spawner: Spawner) {
         khz(10),
     );
 
+    // Center the pulses in the period, which is what motor drives usually sample the phase
+    // currents against.
+    pwm.set_counting_mode(CountingMode::CenterAlignedBothInterrupts);
+
     let max = pwm.get_max_duty();
     pwm.set_dead_time(max / 1024);
 
+    // Overcurrent signal of the gate driver on PE15, active low.
+    pwm.enable_break_input(p.PE15, BreakPolarity::ActiveLow);
+
     pwm.enable(Channel::Ch1);
 
     info!("PWM initialized");
     info!("PWM max duty {}", max);
 
     loop {
+        if pwm.is_break_active() {
+            warn!("overcurrent, resuming");
+            pwm.resume_after_break();
+        }
+
         pwm.set_duty(Channel::Ch1, 0);
         Timer::after(Duration::from_millis(300)).await;
         pwm.set_duty(Channel::Ch1, max / 4);
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::Pull;
+use embassy_stm32::pwm::input_capture;
+use embassy_stm32::pwm::pwm_input::PwmInput;
+use embassy_stm32::time::mhz;
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    TIM4 => input_capture::InterruptHandler<peripherals::TIM4>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // RC receiver channel on PB6, with 1 µs resolution.
+    let mut pwm_input = PwmInput::new(p.TIM4, p.PB6, Pull::None, Irqs, mhz(1));
+    pwm_input.enable();
+
+    loop {
+        let (period, width) = pwm_input.wait_for_cycle().await;
+        info!(
+            "period: {} us, pulse: {} us, duty cycle: {}",
+            period,
+            width,
+            pwm_input.get_duty_cycle()
+        );
+    }
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::Pull;
+use embassy_stm32::pwm::input_capture;
+use embassy_stm32::pwm::qei::{Qei, QeiPin};
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    TIM3 => input_capture::InterruptHandler<peripherals::TIM3>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Encoder A and B signals on PA6 and PA7.
+    let ch1 = QeiPin::new_ch1(p.PA6, Pull::Up);
+    let ch2 = QeiPin::new_ch2(p.PA7, Pull::Up);
+    let mut qei = Qei::new(p.TIM3, ch1, ch2, Irqs);
+    qei.set_input_filter(0b0100);
+
+    loop {
+        // Report every 1/10 revolution of a 1000 lines encoder, which counts 4000 edges per
+        // revolution.
+        let position = qei.wait_for_delta(400).await;
+        info!("position: {}, direction: {}", position, qei.direction());
+    }
+}
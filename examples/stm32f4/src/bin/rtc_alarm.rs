@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use chrono::NaiveDate;
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::rtc::{
+    Alarm, AlarmInterruptHandler, AlarmTime, Rtc, RtcConfig, TamperInput, TamperInterruptHandler, TamperLevel,
+    WakeupInterruptHandler,
+};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RTC_ALARM => AlarmInterruptHandler;
+    RTC_WKUP => WakeupInterruptHandler;
+    TAMP_STAMP => TamperInterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let now = NaiveDate::from_ymd_opt(2020, 5, 15)
+        .unwrap()
+        .and_hms_opt(10, 30, 15)
+        .unwrap();
+
+    let mut rtc = Rtc::new(p.RTC, RtcConfig::default());
+    rtc.set_datetime(now.into()).expect("datetime not set");
+
+    // The backup registers keep their value across resets, but are erased by a tampering.
+    let boots = rtc.read_backup_register(0).unwrap_or(0) + 1;
+    rtc.write_backup_register(0, boots);
+    info!("boot #{}", boots);
+
+    // Fire at the next 30 second mark of every minute.
+    rtc.set_alarm(
+        Alarm::A,
+        AlarmTime {
+            second: Some(30),
+            ..Default::default()
+        },
+        Irqs,
+    );
+    rtc.wait_for_alarm(Alarm::A).await;
+    info!("alarm");
+    rtc.disable_alarm(Alarm::A);
+
+    rtc.start_wakeup_timer(5, Irqs);
+    for _ in 0..3 {
+        rtc.wait_for_wakeup().await;
+        info!("wakeup");
+    }
+    rtc.stop_wakeup_timer();
+
+    // PC13 is held low by a switch, against an external pull-up resistor, and the switch opens
+    // with the enclosure.
+    rtc.enable_tamper(TamperInput::Tamper1, TamperLevel::High, Irqs);
+    let input = rtc.wait_for_tamper().await;
+    info!("enclosure opened on {}", input);
+    info!("backup register: {}", rtc.read_backup_register(0));
+}
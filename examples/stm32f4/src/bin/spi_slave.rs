@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::{Level, Output, Speed};
+use embassy_stm32::spi::{Config, SpiSlave};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // SPI1 slave on PA5 (SCK), PA7 (MOSI), PA6 (MISO) and PA4 (NSS).
+    let mut spi = SpiSlave::new(
+        p.SPI1,
+        p.PA5,
+        p.PA7,
+        p.PA6,
+        p.PA4,
+        p.DMA2_CH3,
+        p.DMA2_CH2,
+        Config::default(),
+    );
+
+    // Tells the master that a transfer is prepared.
+    let mut ready = Output::new(p.PB0, Level::Low, Speed::Low);
+
+    let mut status = [0u8; 8];
+    let mut command = [0u8; 8];
+    loop {
+        status[0] = status[0].wrapping_add(1);
+
+        let transfer = spi.prepare(&mut command, &status);
+        ready.set_high();
+        let result = transfer.wait().await;
+        ready.set_low();
+
+        match result {
+            Ok(()) => info!("received command {:02x}", command),
+            Err(e) => warn!("transfer error: {:?}", e),
+        }
+    }
+}
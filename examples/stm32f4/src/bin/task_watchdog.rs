@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::wdg::{IndependentWatchdog, TaskHandle, TaskWatchdog};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+static TASK_WATCHDOG: TaskWatchdog = TaskWatchdog::new();
+
+#[embassy_executor::task(pool_size = 2)]
+async fn worker(handle: TaskHandle, period: Duration, iterations: u32) {
+    // The task stalls after `iterations`, which resets the MCU.
+    for _ in 0..iterations {
+        handle.feed();
+        Timer::after(period).await;
+    }
+    loop {
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let fast = unwrap!(TASK_WATCHDOG.register());
+    let slow = unwrap!(TASK_WATCHDOG.register());
+    unwrap!(spawner.spawn(worker(fast, Duration::from_millis(100), u32::MAX)));
+    unwrap!(spawner.spawn(worker(slow, Duration::from_millis(500), 10)));
+
+    let mut wdg = IndependentWatchdog::new(p.IWDG, 2_000_000);
+    wdg.unleash();
+
+    loop {
+        Timer::after(Duration::from_secs(1)).await;
+        if TASK_WATCHDOG.check() {
+            wdg.pet();
+        } else {
+            warn!("stalled tasks: {:b}", TASK_WATCHDOG.starving());
+        }
+    }
+}
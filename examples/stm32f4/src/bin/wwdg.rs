@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::wdg::{EarlyWakeupInterruptHandler, WindowWatchdog};
+use embassy_stm32::{bind_interrupts, peripherals};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    WWDG => EarlyWakeupInterruptHandler<peripherals::WWDG>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Reset when not petted within 100 ms, or when petted sooner than 20 ms after the last pet.
+    let mut wdg = WindowWatchdog::new(p.WWDG, 100_000, 20_000);
+    wdg.unleash();
+
+    for _ in 0..20 {
+        Timer::after(Duration::from_millis(50)).await;
+        wdg.pet();
+    }
+
+    info!("stopped petting");
+    wdg.wait_for_early_wakeup(Irqs).await;
+    info!("early wakeup, resetting");
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::can::{Fdcan, Frame, IT0InterruptHandler, OperatingMode, StandardId};
+use embassy_stm32::{bind_interrupts, peripherals};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    FDCAN1_IT0 => IT0InterruptHandler<peripherals::FDCAN1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut can = Fdcan::new(p.FDCAN1, p.PA11, p.PA12, Irqs);
+    can.set_bitrate(250_000);
+    can.set_fd_data_bitrate(1_000_000);
+    // Receive our own frames, without a transceiver.
+    can.enable(OperatingMode::InternalLoopback);
+
+    let mut i: u8 = 0;
+    loop {
+        let data = [i; 16];
+        let frame = unwrap!(Frame::new_fd(unwrap!(StandardId::new(0x123)), &data, true));
+        can.write(&frame).await;
+
+        match can.read().await {
+            Ok((time, frame)) => info!("rx at {}: {:x}", time, frame.data()),
+            Err(err) => error!("bus error: {}", err),
+        }
+
+        i = i.wrapping_add(1);
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
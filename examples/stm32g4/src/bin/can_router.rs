@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_can::{Error, Filter, Filters, Frame, Receive, Route, Router, StandardId, Transmit};
+use embassy_executor::Spawner;
+use embassy_futures::join::join3;
+use embassy_futures::select::{select, Either};
+use embassy_stm32::can::{Fdcan, IT0InterruptHandler, OperatingMode};
+use embassy_stm32::{bind_interrupts, peripherals};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    FDCAN1_IT0 => IT0InterruptHandler<peripherals::FDCAN1>;
+});
+
+const NODE_ID: u8 = 0x12;
+
+/// Sends CANopen heartbeats, with any controller.
+async fn heartbeat<TX: Transmit>(tx: &mut TX) -> ! {
+    let id = unwrap!(StandardId::new(0x700 + NODE_ID as u16));
+    loop {
+        // Operational.
+        let frame = unwrap!(Frame::new_data(id, &[0x05]));
+        if let Err(e) = tx.transmit(&frame).await {
+            warn!("transmit error: {}", e.kind());
+        }
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+/// Dispatches the received frames, with any controller.
+async fn dispatch<RX: Receive>(router: &Router<'_, NoopRawMutex, 4>, rx: &mut RX) -> ! {
+    loop {
+        let e = router.run(rx).await;
+        warn!("receive error: {}", e.kind());
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut can = Fdcan::new(p.FDCAN1, p.PA11, p.PA12, Irqs);
+    can.set_bitrate(250_000);
+    // Receive our own frames, without a transceiver.
+    can.enable(OperatingMode::InternalLoopback);
+
+    // Heartbeats of all the nodes, and the SDO requests to this one.
+    let heartbeats = Channel::<NoopRawMutex, Frame, 4>::new();
+    let sdo_requests = Channel::<NoopRawMutex, Frame, 4>::new();
+    let routes = [
+        Route::new(Filter::standard(unwrap!(StandardId::new(0x700)), 0x780), &heartbeats),
+        Route::new(
+            Filter::exact(unwrap!(StandardId::new(0x600 + NODE_ID as u16))),
+            &sdo_requests,
+        ),
+    ];
+    let router = Router::new(&routes);
+
+    let mut filters = heapless::Vec::<Filter, 2>::new();
+    filters.extend(router.filters());
+    unwrap!(can.set_filters(&filters));
+
+    let (mut tx, mut rx) = can.split();
+    join3(heartbeat(&mut tx), dispatch(&router, &mut rx), async {
+        loop {
+            let frame = match select(heartbeats.recv(), sdo_requests.recv()).await {
+                Either::First(frame) => frame,
+                Either::Second(frame) => frame,
+            };
+            info!("rx {}: {:x}", Debug2Format(&frame.id()), frame.data());
+        }
+    })
+    .await;
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::comp::{self, Comp, Hysteresis, InvertingInput};
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    COMP1_2_3 => comp::InterruptHandler<peripherals::COMP1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Compare the voltage on PA1 with 1/2 of VREFINT, about 0.6 V.
+    let mut config = comp::Config::default();
+    config.inverting_input = InvertingInput::HalfVrefint;
+    config.hysteresis = Hysteresis::Mv20;
+    let mut comp = Comp::new(p.COMP1, p.PA1, Irqs, config);
+
+    loop {
+        comp.wait_for_output_high().await;
+        info!("PA1 is above the threshold");
+        comp.wait_for_output_low().await;
+        info!("PA1 is below the threshold");
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::opamp::{OpAmp, OpAmpGain, OpAmpSpeed};
+use embassy_time::{Delay, Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut opamp = OpAmp::new(p.OPAMP1);
+    opamp.set_speed(OpAmpSpeed::HighSpeed);
+    opamp.calibrate(&mut Delay);
+
+    // Amplify the voltage on PA1 by 2, to PA2.
+    let mut in_pin = p.PA1;
+    let mut out_pin = p.PA2;
+    let _output = opamp.buffer_ext(&mut in_pin, &mut out_pin, OpAmpGain::Mul2);
+
+    loop {
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::ucpd::sink::{Sink, SinkConfig, SinkEvent};
+use embassy_stm32::ucpd::{self, Ucpd};
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    UCPD1 => ucpd::InterruptHandler<peripherals::UCPD1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // CC1 and CC2 of the USB-C connector.
+    let ucpd = Ucpd::new(p.UCPD1, Irqs, p.PB6, p.PB4, p.DMA1_CH1, p.DMA1_CH2);
+
+    let config = SinkConfig {
+        max_voltage_mv: 9000,
+        current_ma: 1000,
+    };
+    let mut sink = Sink::new(ucpd, config);
+
+    loop {
+        match sink.wait_for_event().await {
+            SinkEvent::Attached(vstate) => info!("Source attached, Type-C current {}", vstate),
+            SinkEvent::Contract(contract) => info!("Contract: {} mV, {} mA", contract.voltage_mv, contract.current_ma),
+            SinkEvent::NoContract => info!("No contract, Type-C current only"),
+            SinkEvent::Detached => info!("Source detached"),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::dma::mdma::{self, Mdma};
+use embassy_stm32::dma::{Transfer, TransferOptions};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    MDMA => mdma::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut src = [0u32; 256];
+    for (i, w) in src.iter_mut().enumerate() {
+        *w = i as u32;
+    }
+
+    // With the DMA.
+    let mut dst = [0u32; 256];
+    unsafe { Transfer::new_copy(p.DMA1_CH0, &src, &mut dst, TransferOptions::default()) }.await;
+    assert_eq!(src, dst);
+    info!("DMA copy done");
+
+    // With the MDMA.
+    let [mut ch0, ..] = Mdma::new(p.MDMA, Irqs).split();
+    let mut dst = [0u32; 256];
+    unwrap!(ch0.copy(&src, &mut dst).await);
+    assert_eq!(src, dst);
+    info!("MDMA copy done");
+}
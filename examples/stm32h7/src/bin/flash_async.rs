@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::{info, unwrap};
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::flash::{Flash, InterruptHandler, FLASH_SIZE};
+use embassy_stm32::gpio::{AnyPin, Level, Output, Pin, Speed};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    FLASH => InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello Flash!");
+
+    let mut f = Flash::new(p.FLASH, Irqs);
+    info!("Banks swapped: {}", f.is_bank_swapped());
+
+    // Led should blink uninterrupted during the erase operation
+    spawner.spawn(blinky(p.PB14.degrade())).unwrap();
+
+    // Test on the second bank in the address space, in order not to stall the CPU running from
+    // the first one.
+    let offset = FLASH_SIZE as u32 / 2;
+
+    info!("Reading...");
+    let mut buf = [0u8; 32];
+    unwrap!(f.read(offset, &mut buf));
+    info!("Read: {=[u8]:x}", buf);
+
+    info!("Erasing...");
+    unwrap!(f.erase(offset, offset + 128 * 1024).await);
+
+    info!("Reading...");
+    let mut buf = [0u8; 32];
+    unwrap!(f.read(offset, &mut buf));
+    info!("Read after erase: {=[u8]:x}", buf);
+
+    info!("Writing...");
+    let data: [u8; 32] = core::array::from_fn(|i| i as u8 + 1);
+    unwrap!(f.write(offset, &data).await);
+
+    info!("Reading...");
+    let mut buf = [0u8; 32];
+    unwrap!(f.read(offset, &mut buf));
+    info!("Read: {=[u8]:x}", buf);
+    assert_eq!(buf, data);
+}
+
+#[embassy_executor::task]
+async fn blinky(p: AnyPin) {
+    let mut led = Output::new(p, Level::High, Speed::Low);
+
+    loop {
+        led.set_high();
+        Timer::after(Duration::from_millis(300)).await;
+
+        led.set_low();
+        Timer::after(Duration::from_millis(300)).await;
+    }
+}
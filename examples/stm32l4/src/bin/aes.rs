@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::aes::{Aes, Direction, Mode};
+use {defmt_rtt as _, panic_probe as _};
+
+#[repr(align(4))]
+struct Aligned<const N: usize>([u8; N]);
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut aes = Aes::new(p.AES, p.DMA1_CH1, p.DMA1_CH2);
+
+    // NIST SP 800-38A, F.2.1 CBC-AES128.Encrypt
+    let key = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c,
+    ];
+    let iv = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    ];
+    let mut block = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+    ];
+    unwrap!(aes.blocking_process(&key, Mode::Cbc { iv: &iv }, Direction::Encrypt, &mut block));
+    info!("CBC: {:02x}", block);
+    assert_eq!(
+        block,
+        [0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9, 0x19, 0x7d]
+    );
+
+    // Encrypt and decrypt a record with AES-GCM, the payload being moved with DMA.
+    let nonce = [0x42; 12];
+    let aad = b"record header";
+    let mut record = Aligned([0; 64]);
+    record.0[..13].copy_from_slice(b"Hello, World!");
+
+    let tag = unwrap!(aes.gcm_encrypt(&key, &nonce, aad, &mut record.0).await);
+    info!("GCM tag: {:02x}", tag);
+    unwrap!(aes.gcm_decrypt(&key, &nonce, aad, &mut record.0, &tag).await);
+    assert_eq!(&record.0[..13], b"Hello, World!");
+
+    info!("Test OK");
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::crc::{Config, Crc};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let data = b"123456789";
+
+    let mut crc = Crc::new(p.CRC, Config::crc32());
+    let crc32 = !crc.feed_bytes(data);
+    info!("CRC-32: {:08x}", crc32);
+    assert_eq!(crc32, 0xCBF4_3926);
+
+    crc.set_config(Config::crc16_modbus());
+    let crc16 = crc.feed_bytes(data) as u16;
+    info!("CRC-16/Modbus: {:04x}", crc16);
+    assert_eq!(crc16, 0x4B37);
+
+    info!("Test OK");
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::hash::{Algorithm, Hash, MAX_DIGEST_SIZE};
+use {defmt_rtt as _, panic_probe as _};
+
+#[repr(align(4))]
+struct Aligned<const N: usize>([u8; N]);
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut hash = Hash::new(p.HASH, p.DMA2_CH7);
+    let mut digest = [0; MAX_DIGEST_SIZE];
+
+    // FIPS 180-2, SHA-256 of "abc", fed in two updates.
+    let mut ctx = hash.start(Algorithm::Sha256, None);
+    hash.blocking_update(&mut ctx, b"a");
+    hash.blocking_update(&mut ctx, b"bc");
+    let n = hash.blocking_finish(ctx, &mut digest);
+    info!("SHA-256: {:02x}", digest[..n]);
+    assert_eq!(
+        digest[..n],
+        [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23, 0xb0, 0x03,
+            0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+        ]
+    );
+
+    // RFC 4231, test case 2.
+    let mut ctx = hash.start(Algorithm::Sha256, Some(b"Jefe"));
+    hash.blocking_update(&mut ctx, b"what do ya want for nothing?");
+    let n = hash.blocking_finish(ctx, &mut digest);
+    info!("HMAC-SHA-256: {:02x}", digest[..n]);
+    assert_eq!(
+        digest[..n],
+        [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75, 0xc7, 0x5a, 0x00,
+            0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+        ]
+    );
+
+    // Hash an image with DMA, interleaved with another digest computation.
+    let image = Aligned([0x5a; 1024]);
+    let mut sha256 = hash.start(Algorithm::Sha256, None);
+    let mut sha1 = hash.start(Algorithm::Sha1, None);
+    for chunk in image.0.chunks(256) {
+        hash.update(&mut sha256, chunk).await;
+        hash.blocking_update(&mut sha1, chunk);
+    }
+    let n = hash.blocking_finish(sha256, &mut digest);
+    info!("image SHA-256: {:02x}", digest[..n]);
+    let n = hash.blocking_finish(sha1, &mut digest);
+    info!("image SHA-1: {:02x}", digest[..n]);
+
+    info!("Test OK");
+}
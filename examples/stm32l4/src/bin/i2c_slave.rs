@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+// Exposes a map of 16 registers at address 0x42. The master writes a register index, then
+// writes the registers from it, or restarts and reads them.
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::i2c::{Address, I2cSlave, SlaveCommandKind, SlaveConfig};
+use embassy_stm32::time::Hertz;
+use embassy_stm32::{bind_interrupts, i2c, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    I2C2_EV => i2c::InterruptHandler<peripherals::I2C2>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = SlaveConfig::default();
+    config.address = Address::SevenBit(0x42);
+    let mut i2c = I2cSlave::new(p.I2C2, p.PB10, p.PB11, Irqs, Hertz(400_000), config);
+
+    let mut registers = [0u8; 16];
+    let mut index = 0;
+
+    loop {
+        let command = match i2c.listen().await {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("I2C error: {}", e);
+                continue;
+            }
+        };
+
+        match command.kind {
+            SlaveCommandKind::Write => {
+                let mut buf = [0u8; 17];
+                match i2c.respond_to_write(&mut buf).await {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        index = buf[0] as usize % registers.len();
+                        for (i, byte) in buf[1..n].iter().enumerate() {
+                            registers[(index + i) % registers.len()] = *byte;
+                        }
+                        info!("write: register {}, {} bytes", index, n - 1);
+                    }
+                    Err(e) => warn!("I2C error: {}", e),
+                }
+            }
+            SlaveCommandKind::Read => match i2c.respond_to_read(&registers[index..]).await {
+                Ok(n) => info!("read: register {}, {} bytes", index, n),
+                Err(e) => warn!("I2C error: {}", e),
+            },
+        }
+    }
+}
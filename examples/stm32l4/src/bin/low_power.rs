@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use cortex_m_rt::entry;
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::gpio::{Level, Output, Speed};
+use embassy_stm32::low_power::{self, Executor, StopMode};
+use embassy_stm32::peripherals::PB14;
+use embassy_stm32::rtc::{self, Rtc, RtcConfig};
+use embassy_time::{Duration, Timer};
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RTC_WKUP => rtc::WakeupInterruptHandler;
+});
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[embassy_executor::task]
+async fn blinky(mut led: Output<'static, PB14>) {
+    loop {
+        // The executor spends the time between the blinks in Stop 2 mode.
+        led.set_high();
+        Timer::after(Duration::from_millis(20)).await;
+        led.set_low();
+        Timer::after(Duration::from_secs(2)).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn measure() {
+    loop {
+        // Peripherals that need the high-speed clocks keep the executor out of Stop mode.
+        let blocker = low_power::block_stop();
+        info!("measuring");
+        Timer::after(Duration::from_millis(5)).await;
+        drop(blocker);
+
+        Timer::after(Duration::from_secs(10)).await;
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let rtc = Rtc::new(p.RTC, RtcConfig::default());
+    let led = Output::new(p.PB14, Level::Low, Speed::Low);
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.set_rtc(rtc, Irqs);
+    executor.set_stop_mode(StopMode::Stop2);
+    executor.run(|spawner: Spawner| {
+        unwrap!(spawner.spawn(blinky(led)));
+        unwrap!(spawner.spawn(measure()));
+    });
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::rcc::ClockSrc;
+use embassy_stm32::usart::{Config, Uart, WakeupEvent};
+use embassy_stm32::{bind_interrupts, pac, peripherals, usart};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    LPUART1 => usart::InterruptHandler<peripherals::LPUART1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    // Run from HSI16, which is also the LPUART kernel clock, so that the baudrate computed from
+    // PCLK is right.
+    let mut config = embassy_stm32::Config::default();
+    config.rcc.mux = ClockSrc::HSI16;
+    let p = embassy_stm32::init(config);
+    info!("Hello World!");
+
+    // LPUART1SEL = HSI16, kept running in Stop mode by HSIKERON, and wake up from Stop mode on
+    // HSI16 (STOPWUCK).
+    pac::RCC.ccipr().modify(|w| w.0 = (w.0 & !(0b11 << 10)) | (0b10 << 10));
+    pac::RCC.cr().modify(|w| w.0 |= 1 << 9);
+    pac::RCC.cfgr().modify(|w| w.0 |= 1 << 15);
+
+    let mut config = Config::default();
+    config.baudrate = 9600;
+    let mut uart = Uart::new(p.LPUART1, p.PA3, p.PA2, Irqs, p.DMA1_CH2, p.DMA1_CH1, config);
+    uart.enable_wakeup_from_stop(WakeupEvent::StartBit);
+
+    // Enter Stop 2 mode when the executor waits for events.
+    pac::PWR.cr1().modify(|w| w.0 = (w.0 & !0b111) | 0b010);
+    let mut cp = unwrap!(cortex_m::Peripherals::take());
+    cp.SCB.set_sleepdeep();
+
+    let mut buf = [0u8; 64];
+    loop {
+        // The MCU sleeps in Stop 2 mode until a command starts.
+        match uart.read_until_idle(&mut buf).await {
+            Ok(n) => {
+                info!("command: {:a}", &buf[..n]);
+                unwrap!(uart.write(b"OK\r\n").await);
+            }
+            Err(e) => warn!("read error: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::tsc::{self, IoPin, IoRole, KeyEvent, TouchKey, Tsc};
+use embassy_stm32::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    TSC => tsc::InterruptHandler<peripherals::TSC>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    // Two buttons, in groups 1 and 2, with the sampling capacitors on IO1.
+    let button1 = IoPin::g1_io2(p.PB13, IoRole::Channel);
+    let button2 = IoPin::g2_io2(p.PB5, IoRole::Channel);
+    let mut keys = [
+        TouchKey::new(button1.channel(), 40, 20, 3),
+        TouchKey::new(button2.channel(), 40, 20, 3),
+    ];
+    let pins = [
+        IoPin::g1_io1(p.PB12, IoRole::SamplingCapacitor),
+        IoPin::g2_io1(p.PB4, IoRole::SamplingCapacitor),
+        button1,
+        button2,
+    ];
+    let mut tsc = Tsc::new(p.TSC, Irqs, pins, Default::default());
+
+    loop {
+        match tsc.wait_for_event(&mut keys).await {
+            Ok((key, KeyEvent::Pressed)) => info!("button {} pressed", key + 1),
+            Ok((key, KeyEvent::Released)) => info!("button {} released", key + 1),
+            Err(e) => error!("error: {}", e),
+        }
+    }
+}
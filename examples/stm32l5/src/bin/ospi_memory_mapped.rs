@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+// Reads the MX25LM51245G octal flash of the STM32L562E-DK, in SPI mode, then maps it in memory.
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::dma::NoDma;
+use embassy_stm32::ospi::enums::{AddressSize, ChipSelectHighTime, MemorySize, MemoryType, OspiWidth};
+use embassy_stm32::ospi::{Config, Ospi, TransferConfig};
+use {defmt_rtt as _, panic_probe as _};
+
+const CMD_READ_ID: u32 = 0x9F;
+const CMD_READ_4B: u32 = 0x13;
+const CMD_PAGE_PROGRAM_4B: u32 = 0x12;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = Config::default();
+    config.memory_type = MemoryType::Macronix;
+    config.device_size = MemorySize::_64MiB;
+    config.chip_select_high_time = ChipSelectHighTime::_2Cycle;
+    config.clock_prescaler = 3;
+
+    let mut ospi = Ospi::new_octospi(
+        p.OCTOSPI1, p.PA3, p.PB1, p.PB0, p.PA7, p.PA6, p.PC1, p.PC2, p.PC3, p.PC0, p.PA2, p.PB2, NoDma, config,
+    );
+
+    let mut id = [0u8; 3];
+    unwrap!(ospi.blocking_read(
+        &mut id,
+        TransferConfig {
+            iwidth: OspiWidth::SING,
+            dwidth: OspiWidth::SING,
+            instruction: Some(CMD_READ_ID),
+            ..Default::default()
+        },
+    ));
+    info!("flash id: {:02x}", id);
+
+    let read = TransferConfig {
+        iwidth: OspiWidth::SING,
+        adwidth: OspiWidth::SING,
+        adsize: AddressSize::_32Bit,
+        dwidth: OspiWidth::SING,
+        instruction: Some(CMD_READ_4B),
+        ..Default::default()
+    };
+
+    let mut buf = [0u8; 16];
+    unwrap!(ospi.blocking_read(
+        &mut buf,
+        TransferConfig {
+            address: Some(0),
+            ..read
+        },
+    ));
+    info!("indirect read: {:02x}", buf);
+
+    // Writes aren't used with a flash, which must be erased and programmed with commands, but
+    // the mode needs a write configuration.
+    let write = TransferConfig {
+        iwidth: OspiWidth::SING,
+        adwidth: OspiWidth::SING,
+        adsize: AddressSize::_32Bit,
+        dwidth: OspiWidth::SING,
+        instruction: Some(CMD_PAGE_PROGRAM_4B),
+        ..Default::default()
+    };
+    unwrap!(ospi.enable_memory_mapped_mode(read, write));
+
+    let flash = unwrap!(ospi.memory_mapped_slice());
+    info!("memory-mapped read: {:02x}", flash[..16]);
+
+    ospi.disable_memory_mapped_mode();
+}
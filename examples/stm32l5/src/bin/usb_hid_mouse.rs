@@ -62,6 +62,7 @@ async fn main(_spawner: Spawner) {
         request_handler: Some(&request_handler),
         poll_ms: 60,
         max_packet_size: 8,
+        ..Default::default()
     };
 
     let mut writer = HidWriter::<_, 5>::new(&mut builder, &mut state, config);
@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::pka::{Pka, P256};
+use embassy_stm32::{bind_interrupts, peripherals, pka};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PKA => pka::InterruptHandler<peripherals::PKA>;
+});
+
+const PUBLIC_X: [u8; 32] = [
+    0xd8, 0x83, 0x2d, 0x82, 0x13, 0x60, 0x9a, 0x71, 0x2c, 0xc1, 0xda, 0xa1, 0xe6, 0x0f, 0x4b, 0x8c, 0xaf, 0xf6, 0xcf,
+    0xf6, 0x7e, 0x6d, 0xdb, 0x31, 0x8b, 0x2f, 0x83, 0x20, 0xbe, 0xa4, 0xc9, 0x9e,
+];
+const PUBLIC_Y: [u8; 32] = [
+    0xe6, 0x3b, 0xbf, 0x45, 0x15, 0xe0, 0xfa, 0x16, 0x57, 0x13, 0x55, 0xfb, 0xdd, 0x54, 0x5b, 0xe3, 0x55, 0x12, 0x58,
+    0x3b, 0x9b, 0x7c, 0xca, 0x04, 0xdc, 0x1d, 0x99, 0x0d, 0x9c, 0xf0, 0xae, 0x57,
+];
+
+/// SHA-256 of the signed firmware image.
+const HASH: [u8; 32] = [
+    0x1d, 0xf2, 0xf3, 0x85, 0x3d, 0x10, 0xa3, 0x05, 0xaa, 0x52, 0xd3, 0x6f, 0xd4, 0xa0, 0x3f, 0x57, 0x21, 0xd7, 0xce,
+    0x7d, 0xae, 0xf6, 0xf7, 0xe5, 0xe8, 0xd5, 0x10, 0x74, 0xd3, 0x13, 0x61, 0xf1,
+];
+const SIGNATURE_R: [u8; 32] = [
+    0x03, 0xb1, 0x63, 0xf7, 0x0c, 0x35, 0x54, 0x63, 0xa1, 0xe7, 0xbe, 0xfb, 0xe3, 0xcc, 0xe8, 0xbf, 0xc4, 0x9d, 0x4b,
+    0x8e, 0x45, 0xda, 0x20, 0x95, 0x15, 0xeb, 0xe3, 0x00, 0x47, 0x2c, 0x59, 0xf9,
+];
+const SIGNATURE_S: [u8; 32] = [
+    0x3c, 0x02, 0x21, 0xe1, 0xf8, 0x5c, 0xd5, 0xbb, 0xee, 0xc4, 0xbc, 0x8f, 0x91, 0x43, 0x8c, 0x55, 0x24, 0xb0, 0x49,
+    0x89, 0x57, 0xbd, 0x04, 0xf2, 0xff, 0x74, 0x80, 0xc5, 0xb1, 0xc8, 0x15, 0xe5,
+];
+
+/// Private keys of both sides of a key exchange.
+const PRIVATE_KEY: [u8; 32] = [
+    0x2f, 0xa5, 0x83, 0xb0, 0x96, 0x0e, 0xb1, 0x27, 0x2c, 0xc8, 0xdb, 0x72, 0x55, 0xe3, 0x53, 0xb2, 0xe4, 0x95, 0x0f,
+    0x06, 0x5b, 0xb1, 0x74, 0xc1, 0x08, 0x85, 0x15, 0x03, 0xe3, 0x03, 0x28, 0x77,
+];
+const PEER_PRIVATE_KEY: [u8; 32] = [
+    0x2f, 0xfc, 0x1d, 0x06, 0x38, 0x7e, 0xf8, 0xbb, 0x7a, 0x34, 0x31, 0x2b, 0x6c, 0x6c, 0x3f, 0x69, 0x15, 0x50, 0x68,
+    0x45, 0x08, 0xc3, 0xce, 0x7e, 0xe3, 0x88, 0x93, 0x75, 0xa1, 0x8d, 0x6f, 0xa0,
+];
+const SHARED_SECRET: [u8; 32] = [
+    0x9e, 0x13, 0x5b, 0xb0, 0xb3, 0x2a, 0x4a, 0x41, 0xc9, 0xab, 0x47, 0x2c, 0x41, 0xbd, 0x01, 0xb0, 0x03, 0x4c, 0xdd,
+    0x63, 0xa5, 0xcb, 0x67, 0x92, 0xfb, 0x1c, 0x5b, 0x9c, 0x3a, 0x29, 0x5f, 0x4b,
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+    info!("Hello World!");
+
+    let mut pka = Pka::new(p.PKA, Irqs);
+
+    let result = pka
+        .ecdsa_verify(&P256, &PUBLIC_X, &PUBLIC_Y, &HASH, &SIGNATURE_R, &SIGNATURE_S)
+        .await;
+    info!("signature: {}", result);
+    unwrap!(result);
+
+    let mut tampered = HASH;
+    tampered[0] ^= 1;
+    let result = pka
+        .ecdsa_verify(&P256, &PUBLIC_X, &PUBLIC_Y, &tampered, &SIGNATURE_R, &SIGNATURE_S)
+        .await;
+    info!("tampered signature: {}", result);
+    assert_eq!(result, Err(pka::Error::InvalidSignature));
+
+    // ECDH, with the public key of the peer computed here.
+    let mut peer_x = [0; 32];
+    let mut peer_y = [0; 32];
+    unwrap!(
+        pka.ecc_public_key(&P256, &PEER_PRIVATE_KEY, &mut peer_x, &mut peer_y)
+            .await
+    );
+    let mut shared_secret = [0; 32];
+    unwrap!(
+        pka.ecdh(&P256, &PRIVATE_KEY, &peer_x, &peer_y, &mut shared_secret)
+            .await
+    );
+    info!("shared secret: {:02x}", shared_secret);
+    assert_eq!(shared_secret, SHARED_SECRET);
+
+    info!("Test OK");
+}
@@ -0,0 +1,73 @@
+//! This example runs on the STM32WL board, which has a builtin Semtech Sx1262 radio.
+//! It listens for LoRa transmissions with channel activity detection, then with duty-cycled
+//! reception, driving the radio with raw commands.
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_stm32::bind_interrupts;
+use embassy_stm32::subghz::{self, opcode, CadConfig, CadSymbols, SubGhz};
+use embassy_time::{Duration, Timer};
+use {defmt_rtt as _, panic_probe as _};
+
+const LORA_FREQUENCY_IN_HZ: u32 = 903_900_000; // warning: set this appropriately for the region
+
+bind_interrupts!(struct Irqs{
+    SUBGHZ_RADIO => subghz::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut config = embassy_stm32::Config::default();
+    config.rcc.mux = embassy_stm32::rcc::ClockSrc::HSE32;
+    let p = embassy_stm32::init(config);
+
+    let mut radio = SubGhz::new(p.SUBGHZSPI, Irqs, p.DMA1_CH1, p.DMA1_CH2);
+
+    // The TCXO of the board is supplied at 1.7 V by the radio, with a 5 ms startup time.
+    unwrap!(radio.command(opcode::SET_STANDBY, &[0x00]).await);
+    unwrap!(radio.command(opcode::SET_TCXO_MODE, &[0x01, 0x00, 0x01, 0x40]).await);
+    unwrap!(radio.command(opcode::CALIBRATE, &[0x7f]).await);
+
+    // LoRa, SF7, 125 kHz, CR 4/5, 8 symbols of preamble, explicit header, CRC.
+    let frequency = ((LORA_FREQUENCY_IN_HZ as u64) << 25) / 32_000_000;
+    unwrap!(radio.command(opcode::SET_PACKET_TYPE, &[0x01]).await);
+    unwrap!(radio.command(opcode::SET_RF_FREQUENCY, &(frequency as u32).to_be_bytes()).await);
+    unwrap!(radio.command(opcode::SET_MODULATION_PARAMS, &[7, 0x04, 0x01, 0x00]).await);
+    unwrap!(radio.command(opcode::SET_PACKET_PARAMS, &[0, 8, 0x00, 0xff, 0x01, 0x00]).await);
+    unwrap!(radio.command(opcode::SET_BUFFER_BASE_ADDRESS, &[0, 0]).await);
+
+    let cad = CadConfig {
+        symbols: CadSymbols::_2,
+        detection_peak: 22,
+        detection_min: 10,
+    };
+
+    loop {
+        match radio.cad(&cad).await {
+            Ok(true) => info!("Channel activity detected"),
+            Ok(false) => {
+                Timer::after(Duration::from_millis(500)).await;
+                continue;
+            }
+            Err(e) => {
+                info!("CAD error: {}", e);
+                continue;
+            }
+        }
+
+        // Listen for 2 ms every 6 ms, shorter than the preamble, until a packet is received.
+        match radio.rx_duty_cycle(2_000, 4_000).await {
+            Ok((len, offset)) => {
+                let mut packet = [0; 255];
+                let packet = &mut packet[..len as usize];
+                unwrap!(radio.read_buffer(offset, packet).await);
+                info!("Received {:02x}", packet);
+            }
+            Err(e) => info!("Receive error: {}", e),
+        }
+        unwrap!(radio.command(opcode::SET_STANDBY, &[0x00]).await);
+    }
+}
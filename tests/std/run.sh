@@ -0,0 +1,56 @@
+#!/bin/bash
+# Runs the tests in a throwaway network namespace, with tap0 on one side and the peer on the other:
+# dnsmasq for DHCP and DNS, and echo_server. Needs sudo for the namespace, and dnsmasq.
+
+set -euo pipefail
+
+cd "$(dirname "$0")"
+
+NS=embassy-test
+TARGET_DIR=${CARGO_TARGET_DIR:-target}/debug
+
+cargo build --bins
+
+sudo ip netns add $NS
+PIDS=()
+cleanup() {
+    for pid in "${PIDS[@]}"; do
+        sudo kill "$pid" 2>/dev/null || true
+    done
+    sudo ip netns del $NS
+}
+trap cleanup EXIT
+
+sudo ip netns exec $NS ip link set lo up
+sudo ip netns exec $NS ip tuntap add name tap0 mode tap user "$USER"
+sudo ip netns exec $NS ip link set tap0 up
+sudo ip netns exec $NS ip addr add 192.168.69.1/24 dev tap0
+
+sudo ip netns exec $NS dnsmasq --keep-in-foreground --no-resolv --no-hosts \
+    --interface=tap0 --bind-interfaces --pid-file= --dhcp-leasefile=/dev/null \
+    --dhcp-range=192.168.69.50,192.168.69.150,1h \
+    --address=/peer.embassy.test/192.168.69.1 &
+PIDS+=($!)
+sudo ip netns exec $NS "$TARGET_DIR/echo_server" &
+PIDS+=($!)
+
+FAILED=()
+for test in src/bin/*.rs; do
+    test=$(basename "$test" .rs)
+    if [ "$test" = echo_server ]; then
+        continue
+    fi
+
+    echo "=== $test"
+    if sudo ip netns exec $NS sudo -u "$USER" timeout 60 "$TARGET_DIR/$test"; then
+        echo "=== $test: ok"
+    else
+        echo "=== $test: FAILED"
+        FAILED+=("$test")
+    fi
+done
+
+if [ ${#FAILED[@]} -ne 0 ]; then
+    echo "failed: ${FAILED[*]}"
+    exit 1
+fi
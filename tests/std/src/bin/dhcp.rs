@@ -0,0 +1,28 @@
+#![feature(type_alias_impl_trait)]
+#[path = "../board.rs"]
+mod board;
+
+use embassy_executor::Spawner;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::{Config, IpAddress};
+use log::*;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let stack = board::init(spawner, Config::dhcpv4(Default::default()));
+    let config = board::wait_config_up(stack).await;
+    info!("DHCP config: {:?}", config);
+
+    // Leased from the range of the peer, see `run.sh`.
+    let octets = config.address.address().0;
+    assert_eq!(&octets[..3], &[192, 168, 69]);
+    assert!((50..=150).contains(&octets[3]));
+    assert_eq!(config.gateway, Some(board::PEER_ADDRESS));
+    assert_eq!(&config.dns_servers[..], &[board::PEER_ADDRESS]);
+
+    // The peer also answers DNS queries, for its own name.
+    let addresses = stack.dns_query("peer.embassy.test", DnsQueryType::A).await.unwrap();
+    assert_eq!(&addresses[..], &[IpAddress::Ipv4(board::PEER_ADDRESS)]);
+
+    board::test_ok();
+}
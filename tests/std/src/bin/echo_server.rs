@@ -0,0 +1,43 @@
+//! The peer of the tests, outside of the board: echoes TCP and UDP.
+//!
+//! This one is plain `std`, not a test; `run.sh` starts it in the network namespace.
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::thread::spawn;
+
+fn main() {
+    spawn(udp_echo);
+    tcp_echo();
+}
+
+fn tcp_echo() {
+    let listener = TcpListener::bind("0.0.0.0:4321").unwrap();
+    loop {
+        let (socket, addr) = listener.accept().unwrap();
+        println!("tcp: connection from {}", addr);
+        spawn(|| tcp_conn(socket));
+    }
+}
+
+fn tcp_conn(mut socket: TcpStream) {
+    let mut buf = [0; 1024];
+    loop {
+        match socket.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => {
+                if socket.write_all(&buf[..n]).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn udp_echo() {
+    let socket = UdpSocket::bind("0.0.0.0:4322").unwrap();
+    let mut buf = [0; 2048];
+    loop {
+        let (n, addr) = socket.recv_from(&mut buf).unwrap();
+        socket.send_to(&buf[..n], addr).unwrap();
+    }
+}
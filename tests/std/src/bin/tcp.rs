@@ -0,0 +1,54 @@
+#![feature(type_alias_impl_trait)]
+#[path = "../board.rs"]
+mod board;
+
+use embassy_executor::Spawner;
+use embassy_net::tcp::TcpSocket;
+use embassy_time::{with_timeout, Duration};
+use embedded_io::asynch::Write;
+use log::*;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let stack = board::init(spawner, board::static_config());
+    board::wait_config_up(stack).await;
+
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(10)));
+
+    let remote_endpoint = (board::PEER_ADDRESS, board::TCP_ECHO_PORT);
+    info!("connecting to {:?}...", remote_endpoint);
+    socket.connect(remote_endpoint).await.unwrap();
+    info!("connected!");
+
+    // More than the socket buffers, to go through several windows.
+    let mut sent = [0; 16 * 1024];
+    for (i, b) in sent.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+    let mut received = [0; 16 * 1024];
+
+    let (mut reader, mut writer) = socket.split();
+    let write = async {
+        writer.write_all(&sent).await.unwrap();
+        writer.flush().await.unwrap();
+    };
+    let read = async {
+        let mut len = 0;
+        while len < received.len() {
+            match reader.read(&mut received[len..]).await.unwrap() {
+                0 => panic!("read EOF"),
+                n => len += n,
+            }
+        }
+    };
+    with_timeout(Duration::from_secs(10), embassy_futures::join::join(write, read))
+        .await
+        .unwrap();
+    assert!(sent == received);
+
+    socket.close();
+    board::test_ok();
+}
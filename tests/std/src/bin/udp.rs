@@ -0,0 +1,42 @@
+#![feature(type_alias_impl_trait)]
+#[path = "../board.rs"]
+mod board;
+
+use embassy_executor::Spawner;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_time::{with_timeout, Duration};
+use log::*;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let stack = board::init(spawner, board::static_config());
+    board::wait_config_up(stack).await;
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; 4096];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 4096];
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+    socket.bind(9400).unwrap();
+
+    let remote_endpoint = (board::PEER_ADDRESS, board::UDP_ECHO_PORT);
+    let mut buf = [0; 1024];
+    for i in 0..10u8 {
+        // The first datagram can be lost while the peer address resolves, so retry.
+        let sent = [i; 1000];
+        let n = loop {
+            socket.send_to(&sent, remote_endpoint).await.unwrap();
+            match with_timeout(Duration::from_secs(1), socket.recv_from(&mut buf)).await {
+                Ok(Ok((n, endpoint))) => {
+                    info!("echo of {} bytes from {:?}", n, endpoint);
+                    break n;
+                }
+                Ok(Err(e)) => panic!("recv error: {:?}", e),
+                Err(_) => warn!("no echo, retrying"),
+            }
+        };
+        assert_eq!(&buf[..n], &sent[..]);
+    }
+
+    board::test_ok();
+}
@@ -0,0 +1,85 @@
+//! The host "board": the network of a firmware, on a TAP interface of Linux.
+//!
+//! `run.sh` creates the interface in a network namespace, with the peer at [`PEER_ADDRESS`]:
+//! a DHCP server, and the echo servers of `echo_server`.
+#![allow(dead_code)]
+
+use embassy_executor::Spawner;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4};
+pub use embassy_net_tuntap::TunTapDevice as Device;
+use embassy_time::{with_timeout, Duration, Timer};
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::make_static;
+
+/// Address of the peer, on the other side of the interface.
+pub const PEER_ADDRESS: Ipv4Address = Ipv4Address::new(192, 168, 69, 1);
+/// Address of the board, with a static configuration.
+pub const STATIC_ADDRESS: Ipv4Address = Ipv4Address::new(192, 168, 69, 2);
+/// TCP echo port of the peer.
+pub const TCP_ECHO_PORT: u16 = 4321;
+/// UDP echo port of the peer.
+pub const UDP_ECHO_PORT: u16 = 4322;
+
+/// Static configuration of the board, on the network of the peer.
+pub fn static_config() -> Config {
+    let mut dns_servers = Vec::new();
+    dns_servers.push(PEER_ADDRESS).unwrap();
+    Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(STATIC_ADDRESS, 24),
+        dns_servers,
+        gateway: Some(PEER_ADDRESS),
+    })
+}
+
+#[embassy_executor::task]
+async fn net_task(stack: &'static Stack<Device>) -> ! {
+    stack.run().await
+}
+
+/// Bring up the board: the logger, the TAP interface `EMBASSY_TAP` (default `tap0`), and the
+/// network stack with `config`, running in its own task.
+pub fn init(spawner: Spawner, config: Config) -> &'static Stack<Device> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let tap = std::env::var("EMBASSY_TAP").unwrap_or_else(|_| "tap0".into());
+    let device = Device::new(&tap).unwrap_or_else(|e| panic!("failed to open {}: {}", tap, e));
+
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    let stack = &*make_static!(Stack::new(
+        device,
+        config,
+        make_static!(StackResources::<3>::new()),
+        seed
+    ));
+    spawner.spawn(net_task(stack)).unwrap();
+    stack
+}
+
+/// Wait for the link and the IP configuration to be up.
+pub async fn wait_config_up(stack: &Stack<Device>) -> StaticConfigV4 {
+    with_timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(config) = stack.config_v4() {
+                return config;
+            }
+            Timer::after(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the IP configuration")
+}
+
+/// Report success, and end the process.
+pub fn test_ok() -> ! {
+    info!("Test OK");
+    std::process::exit(0)
+}